@@ -0,0 +1,178 @@
+use std::fmt::Write;
+use std::ops::Range;
+use std::sync::Arc;
+
+use datafusion::arrow::array::{ArrayRef, Float64Array, StringArray, TimestampNanosecondArray};
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use datafusion::arrow::error::Result as ArrowResult;
+use datafusion::arrow::ipc::writer::StreamWriter;
+use datafusion::arrow::record_batch::RecordBatch;
+use rand::Rng;
+
+/// Distinct values of the tags other than the first one
+const TAG_VALUES: usize = 16;
+/// Nanoseconds between the points of a series
+const POINT_INTERVAL: i64 = 1_000_000;
+
+/// Generates the points written by the benchmark.
+///
+/// The points are numbered from 0, point `i` belongs to the series `i % cardinality`
+/// and is the `i / cardinality`th point of the series, so a range of the numbers
+/// is written round by round, one point per series each round. The first tag tells
+/// the series apart, the others have at most `TAG_VALUES` values.
+pub struct Workload {
+    table: String,
+    tags: usize,
+    fields: usize,
+    cardinality: usize,
+    start: i64,
+}
+
+impl Workload {
+    pub fn new(table: String, tags: usize, fields: usize, cardinality: usize, start: i64) -> Self {
+        Self {
+            table,
+            tags: tags.max(1),
+            fields: fields.max(1),
+            cardinality: cardinality.max(1),
+            start,
+        }
+    }
+
+    pub fn create_table_sql(&self) -> String {
+        let fields = (0..self.fields)
+            .map(|i| format!("f{} DOUBLE", i))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let tags = (0..self.tags)
+            .map(|i| format!("t{}", i))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        format!(
+            "CREATE TABLE IF NOT EXISTS {} ({}, TAGS({}))",
+            self.table, fields, tags
+        )
+    }
+
+    fn tag_value(&self, series: usize, tag: usize) -> String {
+        if tag == 0 {
+            format!("s{}", series)
+        } else {
+            format!("v{}", series % TAG_VALUES)
+        }
+    }
+
+    fn timestamp(&self, point: u64) -> i64 {
+        self.start + (point / self.cardinality as u64) as i64 * POINT_INTERVAL
+    }
+
+    /// The points as lines of the line protocol with timestamps in nanoseconds
+    pub fn lines(&self, points: Range<u64>, rng: &mut impl Rng) -> String {
+        let mut lines = String::new();
+        for point in points {
+            let series = (point % self.cardinality as u64) as usize;
+            lines.push_str(&self.table);
+            for tag in 0..self.tags {
+                let _ = write!(lines, ",t{}={}", tag, self.tag_value(series, tag));
+            }
+            for field in 0..self.fields {
+                let sep = if field == 0 { ' ' } else { ',' };
+                let _ = write!(lines, "{}f{}={}", sep, field, rng.gen::<f64>());
+            }
+            let _ = writeln!(lines, " {}", self.timestamp(point));
+        }
+
+        lines
+    }
+
+    pub fn schema(&self) -> SchemaRef {
+        let mut fields = vec![Field::new(
+            "time",
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            false,
+        )];
+        fields.extend((0..self.tags).map(|i| Field::new(&format!("t{}", i), DataType::Utf8, true)));
+        fields.extend(
+            (0..self.fields).map(|i| Field::new(&format!("f{}", i), DataType::Float64, true)),
+        );
+
+        Arc::new(Schema::new(fields))
+    }
+
+    pub fn record_batch(&self, points: Range<u64>, rng: &mut impl Rng) -> ArrowResult<RecordBatch> {
+        let mut columns: Vec<ArrayRef> = vec![Arc::new(TimestampNanosecondArray::from(
+            points
+                .clone()
+                .map(|e| self.timestamp(e))
+                .collect::<Vec<_>>(),
+        ))];
+        for tag in 0..self.tags {
+            let values = points
+                .clone()
+                .map(|e| Some(self.tag_value((e % self.cardinality as u64) as usize, tag)))
+                .collect::<StringArray>();
+            columns.push(Arc::new(values));
+        }
+        for _ in 0..self.fields {
+            let values = points.clone().map(|_| rng.gen::<f64>()).collect::<Vec<_>>();
+            columns.push(Arc::new(Float64Array::from(values)));
+        }
+
+        RecordBatch::try_new(self.schema(), columns)
+    }
+
+    /// The points as an arrow ipc stream, the payload of the grpc writes
+    pub fn ipc(&self, points: Range<u64>, rng: &mut impl Rng) -> ArrowResult<Vec<u8>> {
+        let batch = self.record_batch(points, rng)?;
+        let mut bytes = vec![];
+        {
+            let mut writer = StreamWriter::try_new(&mut bytes, &batch.schema())?;
+            writer.write(&batch)?;
+            writer.finish()?;
+        }
+
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn test_lines() {
+        let workload = Workload::new("m".to_string(), 2, 1, 2, 100);
+        let mut rng = StdRng::seed_from_u64(0);
+        let lines = workload.lines(1..4, &mut rng);
+        let lines = lines.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("m,t0=s1,t1=v1 f0="));
+        assert!(lines[0].ends_with(" 100"));
+        assert!(lines[1].starts_with("m,t0=s0,t1=v0 f0="));
+        assert!(lines[1].ends_with(&format!(" {}", 100 + POINT_INTERVAL)));
+        assert!(lines[2].starts_with("m,t0=s1,t1=v1 f0="));
+    }
+
+    #[test]
+    fn test_record_batch() {
+        let workload = Workload::new("m".to_string(), 1, 2, 3, 0);
+        let mut rng = StdRng::seed_from_u64(0);
+        let batch = workload.record_batch(0..7, &mut rng).unwrap();
+        assert_eq!(batch.num_rows(), 7);
+        assert_eq!(batch.num_columns(), 4);
+        let series = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(series.value(4), "s1");
+        assert_eq!(
+            workload.create_table_sql(),
+            "CREATE TABLE IF NOT EXISTS m (f0 DOUBLE, f1 DOUBLE, TAGS(t0))"
+        );
+    }
+}
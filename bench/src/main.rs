@@ -0,0 +1,297 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use clap::{ArgEnum, Parser};
+use http_protocol::http_client::HttpClient;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::generator::Workload;
+use crate::stats::Latencies;
+use crate::writer::{execute_sql, Credentials, Writer};
+
+mod generator;
+mod stats;
+mod writer;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+enum Protocol {
+    LineProtocol,
+    Grpc,
+}
+
+#[derive(Debug, Parser)]
+#[clap(author, version, about = "Generates write load for CnosDB and reports the latencies", long_about = None)]
+struct Args {
+    #[clap(short, long, help = "CnosDB server host", default_value = "127.0.0.1")]
+    host: String,
+
+    #[clap(
+        short = 'P',
+        long,
+        help = "CnosDB server http api port",
+        default_value = "31007"
+    )]
+    port: usize,
+
+    #[clap(long, help = "CnosDB server grpc port", default_value = "31006")]
+    grpc_port: usize,
+
+    #[clap(
+        short,
+        long,
+        help = "The user name used to connect to the CnosDB",
+        default_value = "cnosdb"
+    )]
+    user: String,
+
+    #[clap(short, long, help = "Password used to connect to the CnosDB")]
+    password: Option<String>,
+
+    #[clap(
+        short,
+        long,
+        help = "Database written to, created if absent",
+        default_value = "bench"
+    )]
+    database: String,
+
+    #[clap(
+        long,
+        help = "Table written to, created if absent",
+        default_value = "bench"
+    )]
+    table: String,
+
+    #[clap(
+        long,
+        arg_enum,
+        help = "Protocol of the writes",
+        default_value = "line-protocol"
+    )]
+    protocol: Protocol,
+
+    #[clap(long, help = "Number of tags of the table", default_value = "3")]
+    tags: usize,
+
+    #[clap(
+        long,
+        help = "Number of double fields of the table",
+        default_value = "5"
+    )]
+    fields: usize,
+
+    #[clap(long, help = "Number of series written", default_value = "1000")]
+    cardinality: usize,
+
+    #[clap(long, help = "Number of points of each write", default_value = "1000")]
+    batch_size: usize,
+
+    #[clap(
+        long,
+        help = "Number of points written, unlimited if --duration is set"
+    )]
+    points: Option<u64>,
+
+    #[clap(long, help = "Seconds to keep writing")]
+    duration: Option<u64>,
+
+    #[clap(
+        long,
+        help = "Points written per second, 0 for unlimited",
+        default_value = "0"
+    )]
+    rate: u64,
+
+    #[clap(
+        short,
+        long,
+        help = "Number of concurrent writers",
+        default_value = "4"
+    )]
+    concurrency: usize,
+
+    #[clap(short, long, help = "Query run after the writes, repeatable")]
+    query: Vec<String>,
+
+    #[clap(long, help = "Times each query is run", default_value = "10")]
+    query_iterations: usize,
+}
+
+impl Args {
+    fn credentials(&self) -> Credentials {
+        Credentials {
+            user: self.user.clone(),
+            password: self.password.clone(),
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), String> {
+    let args = Args::parse();
+    let http_client = Arc::new(HttpClient::from_addr(args.host.clone(), args.port));
+    let credentials = Arc::new(args.credentials());
+
+    let start = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_nanos() as i64;
+    let workload = Arc::new(Workload::new(
+        args.table.clone(),
+        args.tags,
+        args.fields,
+        args.cardinality,
+        start,
+    ));
+
+    // The table is created up front as the grpc writes are mapped to its columns
+    let create_database = format!("CREATE DATABASE IF NOT EXISTS {}", args.database);
+    execute_sql(&http_client, &credentials, "public", &create_database).await?;
+    execute_sql(
+        &http_client,
+        &credentials,
+        &args.database,
+        &workload.create_table_sql(),
+    )
+    .await?;
+
+    let points = match (args.points, args.duration) {
+        (Some(points), _) => points,
+        (None, Some(_)) => u64::MAX,
+        (None, None) => 1_000_000,
+    };
+    let deadline = args
+        .duration
+        .map(|e| Instant::now() + Duration::from_secs(e));
+
+    let written = run_writes(
+        &args,
+        http_client.clone(),
+        credentials.clone(),
+        workload,
+        points,
+        deadline,
+    )
+    .await?;
+    if written.points > 0 {
+        println!(
+            "write ({:?}): {} points in {:.3?}, {:.0} points/s",
+            args.protocol,
+            written.points,
+            written.elapsed,
+            written.points as f64 / written.elapsed.as_secs_f64()
+        );
+        println!("  {}", written.latencies.summary());
+    }
+
+    for query in args.query.iter() {
+        let mut latencies = Latencies::default();
+        for _ in 0..args.query_iterations {
+            let now = Instant::now();
+            match execute_sql(&http_client, &credentials, &args.database, query).await {
+                Ok(_) => latencies.record(now.elapsed()),
+                Err(e) => {
+                    eprintln!("query failed: {}", e);
+                    latencies.record_error();
+                }
+            }
+        }
+        println!("query: {}", query);
+        println!("  {}", latencies.summary());
+    }
+
+    Ok(())
+}
+
+struct Written {
+    points: u64,
+    elapsed: Duration,
+    latencies: Latencies,
+}
+
+/// Writes the points by the concurrent writers, each takes the next batch of the
+/// points until all the points are written or the deadline is reached
+async fn run_writes(
+    args: &Args,
+    http_client: Arc<HttpClient>,
+    credentials: Arc<Credentials>,
+    workload: Arc<Workload>,
+    points: u64,
+    deadline: Option<Instant>,
+) -> Result<Written, String> {
+    let batch_size = args.batch_size.max(1) as u64;
+    let concurrency = args.concurrency.max(1);
+    // Points per second of each writer
+    let rate = args.rate as f64 / concurrency as f64;
+    let next = Arc::new(AtomicU64::new(0));
+    let started = Instant::now();
+
+    let mut handles = Vec::with_capacity(concurrency);
+    for _ in 0..concurrency {
+        let mut writer = match args.protocol {
+            Protocol::LineProtocol => Writer::line_protocol(http_client.clone()),
+            Protocol::Grpc => Writer::grpc(&args.host, args.grpc_port, &credentials).await?,
+        };
+        let (credentials, workload, next) = (credentials.clone(), workload.clone(), next.clone());
+        let (database, table) = (args.database.clone(), args.table.clone());
+
+        handles.push(tokio::spawn(async move {
+            let mut rng = StdRng::from_entropy();
+            let mut latencies = Latencies::default();
+            let (mut attempted, mut sent) = (0_u64, 0_u64);
+            loop {
+                if deadline.map(|e| Instant::now() >= e).unwrap_or(false) {
+                    break;
+                }
+                let from = next.fetch_add(batch_size, Ordering::Relaxed);
+                if from >= points {
+                    break;
+                }
+                let to = points.min(from + batch_size);
+
+                if rate > 0.0 {
+                    let due = started + Duration::from_secs_f64(attempted as f64 / rate);
+                    tokio::time::sleep_until(due.into()).await;
+                }
+
+                let payload = match writer.payload(&workload, from..to, &mut rng) {
+                    Ok(payload) => payload,
+                    Err(e) => {
+                        eprintln!("generate points failed: {}", e);
+                        break;
+                    }
+                };
+                let now = Instant::now();
+                match writer.write(&credentials, &database, &table, payload).await {
+                    Ok(_) => {
+                        latencies.record(now.elapsed());
+                        sent += to - from;
+                    }
+                    Err(e) => {
+                        eprintln!("write failed: {}", e);
+                        latencies.record_error();
+                    }
+                }
+                attempted += to - from;
+            }
+
+            (sent, latencies)
+        }));
+    }
+
+    let mut written = Written {
+        points: 0,
+        elapsed: Duration::ZERO,
+        latencies: Latencies::default(),
+    };
+    for handle in handles {
+        let (sent, latencies) = handle.await.map_err(|e| e.to_string())?;
+        written.points += sent;
+        written.latencies.merge(latencies);
+    }
+    written.elapsed = started.elapsed();
+
+    Ok(written)
+}
@@ -0,0 +1,90 @@
+use std::fmt;
+use std::time::Duration;
+
+/// Latencies of the requests of a phase of the benchmark
+#[derive(Default)]
+pub struct Latencies {
+    samples: Vec<Duration>,
+    errors: usize,
+}
+
+impl Latencies {
+    pub fn record(&mut self, latency: Duration) {
+        self.samples.push(latency);
+    }
+
+    pub fn record_error(&mut self) {
+        self.errors += 1;
+    }
+
+    pub fn merge(&mut self, other: Latencies) {
+        self.samples.extend(other.samples);
+        self.errors += other.errors;
+    }
+
+    pub fn summary(mut self) -> Summary {
+        self.samples.sort_unstable();
+        let percentile = |p: usize| -> Duration {
+            if self.samples.is_empty() {
+                return Duration::ZERO;
+            }
+            // Nearest rank
+            let rank = (self.samples.len() * p + 99) / 100;
+            self.samples[rank.max(1) - 1]
+        };
+
+        Summary {
+            requests: self.samples.len(),
+            errors: self.errors,
+            p50: percentile(50),
+            p90: percentile(90),
+            p99: percentile(99),
+            max: self.samples.last().copied().unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Summary {
+    pub requests: usize,
+    pub errors: usize,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "requests: {}, errors: {}, p50: {:.3?}, p90: {:.3?}, p99: {:.3?}, max: {:.3?}",
+            self.requests, self.errors, self.p50, self.p90, self.p99, self.max
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary() {
+        let mut latencies = Latencies::default();
+        for ms in (1..=100).rev() {
+            latencies.record(Duration::from_millis(ms));
+        }
+        latencies.record_error();
+
+        let summary = latencies.summary();
+        assert_eq!(summary.requests, 100);
+        assert_eq!(summary.errors, 1);
+        assert_eq!(summary.p50, Duration::from_millis(50));
+        assert_eq!(summary.p90, Duration::from_millis(90));
+        assert_eq!(summary.p99, Duration::from_millis(99));
+        assert_eq!(summary.max, Duration::from_millis(100));
+
+        let summary = Latencies::default().summary();
+        assert_eq!(summary.p99, Duration::ZERO);
+    }
+}
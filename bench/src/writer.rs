@@ -0,0 +1,153 @@
+use std::ops::Range;
+use std::sync::Arc;
+
+use http_protocol::header::BASIC_PREFIX;
+use http_protocol::http_client::HttpClient;
+use http_protocol::parameter::WriteParam;
+use http_protocol::status_code::OK;
+use protos::kv_service::tskv_service_client::TskvServiceClient;
+use protos::kv_service::WriteRecordBatchRpcRequest;
+use rand::rngs::StdRng;
+use tonic::metadata::MetadataValue;
+use tonic::transport::Channel;
+use tonic::Request;
+
+use crate::generator::Workload;
+
+pub const API_V1_SQL_PATH: &str = "/api/v1/sql";
+pub const API_V1_WRITE_PATH: &str = "/api/v1/write";
+
+pub struct Credentials {
+    pub user: String,
+    pub password: Option<String>,
+}
+
+impl Credentials {
+    fn basic_auth(&self) -> String {
+        let content = format!("{}:{}", self.user, self.password.as_deref().unwrap_or(""));
+        format!("{}{}", BASIC_PREFIX, base64::encode(content))
+    }
+}
+
+/// Runs a statement over the http api, the body of the response is dropped
+pub async fn execute_sql(
+    client: &HttpClient,
+    credentials: &Credentials,
+    database: &str,
+    sql: &str,
+) -> Result<(), String> {
+    let resp = client
+        .post(API_V1_SQL_PATH)
+        .basic_auth(&credentials.user, credentials.password.as_deref())
+        .query(&[("db", database)])
+        .body(sql.to_string())
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    match resp.status() {
+        OK => resp.bytes().await.map(|_| ()).map_err(|e| e.to_string()),
+        _ => Err(resp.text().await.map_err(|e| e.to_string())?),
+    }
+}
+
+/// Writes the points generated by the workload over one of the protocols
+pub enum Writer {
+    LineProtocol {
+        client: Arc<HttpClient>,
+    },
+    Grpc {
+        client: TskvServiceClient<Channel>,
+        authorization: MetadataValue<tonic::metadata::Ascii>,
+    },
+}
+
+impl Writer {
+    pub fn line_protocol(client: Arc<HttpClient>) -> Self {
+        Self::LineProtocol { client }
+    }
+
+    pub async fn grpc(host: &str, port: usize, credentials: &Credentials) -> Result<Self, String> {
+        let client = TskvServiceClient::connect(format!("http://{}:{}", host, port))
+            .await
+            .map_err(|e| e.to_string())?;
+        let authorization = credentials
+            .basic_auth()
+            .parse()
+            .map_err(|_| "invalid user or password".to_string())?;
+
+        Ok(Self::Grpc {
+            client,
+            authorization,
+        })
+    }
+
+    /// The payload of the points, generated before the write so that it is not
+    /// counted in the latency
+    pub fn payload(
+        &self,
+        workload: &Workload,
+        points: Range<u64>,
+        rng: &mut StdRng,
+    ) -> Result<Vec<u8>, String> {
+        match self {
+            Self::LineProtocol { .. } => Ok(workload.lines(points, rng).into_bytes()),
+            Self::Grpc { .. } => workload.ipc(points, rng).map_err(|e| e.to_string()),
+        }
+    }
+
+    pub async fn write(
+        &mut self,
+        credentials: &Credentials,
+        database: &str,
+        table: &str,
+        payload: Vec<u8>,
+    ) -> Result<(), String> {
+        match self {
+            Self::LineProtocol { client } => {
+                let param = WriteParam {
+                    db: database.to_string(),
+                    consistency: None,
+                    precision: None,
+                };
+                let resp = client
+                    .post(API_V1_WRITE_PATH)
+                    .basic_auth(&credentials.user, credentials.password.as_deref())
+                    .query(&param)
+                    .body(payload)
+                    .send()
+                    .await
+                    .map_err(|e| e.to_string())?;
+
+                match resp.status() {
+                    OK => Ok(()),
+                    _ => Err(resp.text().await.map_err(|e| e.to_string())?),
+                }
+            }
+            Self::Grpc {
+                client,
+                authorization,
+            } => {
+                let req = WriteRecordBatchRpcRequest {
+                    database: database.to_string(),
+                    table: table.to_string(),
+                    ipc: payload,
+                };
+                let mut request = Request::new(futures::stream::iter(vec![req]));
+                request
+                    .metadata_mut()
+                    .insert("authorization", authorization.clone());
+
+                let mut resp = client
+                    .write_record_batches(request)
+                    .await
+                    .map_err(|e| e.to_string())?
+                    .into_inner();
+                match resp.message().await.map_err(|e| e.to_string())? {
+                    Some(_) => Ok(()),
+                    None => Err("no response to the write".to_string()),
+                }
+            }
+        }
+    }
+}
@@ -2,7 +2,7 @@
 
 use crate::ctx::{ResultSet, SessionContext};
 use crate::functions::{display_all_functions, Function};
-use crate::print_format::PrintFormat;
+use crate::print_format::{PrintFormat, TimeFormat};
 use crate::print_options::PrintOptions;
 use clap::ArgEnum;
 use datafusion::arrow::array::{ArrayRef, StringArray};
@@ -26,11 +26,14 @@ pub enum Command {
     SearchFunctions(String),
     QuietMode(Option<bool>),
     OutputFormat(Option<String>),
+    ToggleExpanded,
     Write(String),
 }
 
 pub enum OutputFormat {
     ChangeFormat(String),
+    ChangeNull(String),
+    ChangeTimeFormat(String),
 }
 
 impl Command {
@@ -96,6 +99,14 @@ impl Command {
             Self::OutputFormat(_) => {
                 Err("Unexpected change output format, this should be handled outside".to_string())
             }
+            Self::ToggleExpanded => {
+                print_options.expanded = !print_options.expanded;
+                println!(
+                    "Expanded display is {}.",
+                    if print_options.expanded { "on" } else { "off" }
+                );
+                Ok(())
+            }
             Self::Write(path) => {
                 let results = ctx.write(path).await?;
                 print_options.print_batches(&results, now)
@@ -114,13 +125,17 @@ impl Command {
             Self::ListFunctions => ("\\h", "function list"),
             Self::SearchFunctions(_) => ("\\h function", "search function"),
             Self::QuietMode(_) => ("\\quiet (true|false)?", "print or set quiet mode"),
-            Self::OutputFormat(_) => ("\\pset [NAME [VALUE]]", "set table output option\n(format)"),
+            Self::OutputFormat(_) => (
+                "\\pset [NAME [VALUE]]",
+                "set table output option\n(format, null, time_format)",
+            ),
+            Self::ToggleExpanded => ("\\x", "toggle expanded display"),
             Self::Write(_) => ("\\w path", "line protocol"),
         }
     }
 }
 
-const ALL_COMMANDS: [Command; 11] = [
+const ALL_COMMANDS: [Command; 12] = [
     Command::ConnectDatabase(String::new()),
     Command::ListTables,
     Command::DescribeTable(String::new()),
@@ -131,6 +146,7 @@ const ALL_COMMANDS: [Command; 11] = [
     Command::SearchFunctions(String::new()),
     Command::QuietMode(None),
     Command::OutputFormat(None),
+    Command::ToggleExpanded,
     Command::Write(String::new()),
 ];
 
@@ -177,6 +193,7 @@ impl FromStr for Command {
             ("quiet", None) => Self::QuietMode(None),
             ("pset", Some(subcommand)) => Self::OutputFormat(Some(subcommand.to_string())),
             ("pset", None) => Self::OutputFormat(None),
+            ("x", None) => Self::ToggleExpanded,
             ("w", Some(path)) => Self::Write(path.into()),
             _ => return Err(()),
         })
@@ -194,6 +211,9 @@ impl FromStr for OutputFormat {
         };
         Ok(match (c, arg) {
             ("format", Some(format)) => Self::ChangeFormat(format.to_string()),
+            ("null", Some(null)) => Self::ChangeNull(null.to_string()),
+            ("null", None) => Self::ChangeNull(String::new()),
+            ("time_format", Some(format)) => Self::ChangeTimeFormat(format.to_string()),
             _ => return Err(()),
         })
     }
@@ -215,6 +235,18 @@ impl OutputFormat {
                     )))
                 }
             }
+            Self::ChangeNull(null) => {
+                print_options.null = null.clone();
+                println!("Null display is \"{}\".", print_options.null);
+                Ok(())
+            }
+            Self::ChangeTimeFormat(format) => {
+                print_options.time_format = format
+                    .parse::<TimeFormat>()
+                    .map_err(DataFusionError::Execution)?;
+                println!("Time format is {:?}.", print_options.time_format);
+                Ok(())
+            }
         }
     }
 }
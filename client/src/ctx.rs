@@ -1,24 +1,34 @@
+use datafusion::arrow::ipc::reader::StreamReader;
 use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::arrow::util::display::array_value_to_string;
+use std::io::Cursor;
 
-use http_protocol::header::ACCEPT;
-use http_protocol::parameter::{SqlParam, WriteParam};
-use http_protocol::{http_client::HttpClient, status_code::OK};
+use http_protocol::header::{ACCEPT, APPLICATION_ARROW};
+use http_protocol::http_client::HttpClient;
+use http_protocol::parameter::{
+    AsyncResultParam, PartitionParam, SqlParam, VerifyParam, WriteParam,
+};
+use http_protocol::status_code::{ACCEPTED, OK};
 
-use crate::{config::ConfigOptions, print_format::PrintFormat};
+use crate::config::ConfigOptions;
+use crate::print_options::PrintOptions;
 
 pub const DEFAULT_USER: &str = "cnosdb";
 pub const DEFAULT_PASSWORD: &str = "";
 pub const DEFAULT_DATABASE: &str = "public";
 
 pub const API_V1_SQL_PATH: &str = "/api/v1/sql";
+pub const API_V1_SQL_ASYNC_PATH: &str = "/api/v1/sql/async";
 pub const API_V1_WRITE_PATH: &str = "/api/v1/write";
+pub const API_V1_VERIFY_PATH: &str = "/api/v1/verify";
+pub const API_V1_EXPORT_PARTITION_PATH: &str = "/api/v1/partition/export";
+pub const API_V1_IMPORT_PARTITION_PATH: &str = "/api/v1/partition/import";
 
 pub struct SessionConfig {
     pub user_info: UserInfo,
     pub connection_info: ConnectionInfo,
     pub database: String,
     pub target_partitions: Option<usize>,
-    pub fmt: PrintFormat,
     pub config_options: ConfigOptions,
 }
 
@@ -33,7 +43,6 @@ impl SessionConfig {
             database: DEFAULT_DATABASE.to_string(),
             target_partitions: None,
             config_options,
-            fmt: PrintFormat::Csv,
         }
     }
 
@@ -78,12 +87,6 @@ impl SessionConfig {
 
         self
     }
-
-    pub fn with_result_format(mut self, fmt: PrintFormat) -> Self {
-        self.fmt = fmt;
-
-        self
-    }
 }
 
 pub struct UserInfo {
@@ -138,6 +141,8 @@ impl SessionContext {
         self.session_config.database.as_str()
     }
 
+    /// The results are fetched as arrow record batches, printed by the options of
+    /// the client
     pub async fn sql(&self, sql: String) -> Result<ResultSet, String> {
         let user_info = &self.session_config.user_info;
 
@@ -147,6 +152,11 @@ impl SessionContext {
             db: Some(db),
             chunked: None,
             target_partitions,
+            format: None,
+            consistency: None,
+            tz: None,
+            page_size: None,
+            cursor: None,
         };
 
         // let param = &[("db", &self.session_config.database)];
@@ -155,7 +165,7 @@ impl SessionContext {
             .http_client
             .post(API_V1_SQL_PATH)
             .basic_auth::<&str, &str>(&user_info.user, user_info.password.as_deref())
-            .header(ACCEPT, self.session_config.fmt.get_http_content_type())
+            .header(ACCEPT, APPLICATION_ARROW)
             .query(&param)
             .body(sql)
             .send()
@@ -165,8 +175,7 @@ impl SessionContext {
         match resp.status() {
             OK => {
                 let body = resp.bytes().await.map_err(|e| format!("{}", e))?;
-
-                Ok(ResultSet::Bytes((body.to_vec(), 0)))
+                Ok(ResultSet::RecordBatches(read_batches(&body)?))
             }
             _ => {
                 let body = resp.text().await.map_err(|e| format!("{}", e))?;
@@ -176,6 +185,149 @@ impl SessionContext {
         }
     }
 
+    /// The values of the first column of the result of the statement, e.g. the names
+    /// of the tables of `SHOW TABLES`
+    pub async fn first_column(&self, sql: &str) -> Result<Vec<String>, String> {
+        let batches = match self.sql(sql.to_string()).await? {
+            ResultSet::RecordBatches(batches) => batches,
+            ResultSet::Bytes(_) => return Ok(vec![]),
+        };
+
+        let mut values = vec![];
+        for batch in batches.iter().filter(|e| e.num_columns() > 0) {
+            for row in 0..batch.num_rows() {
+                values
+                    .push(array_value_to_string(batch.column(0), row).map_err(|e| e.to_string())?);
+            }
+        }
+        Ok(values)
+    }
+
+    /// Submits the statement as an asynchronous query, the id returned by the server
+    /// as the query begins identifies it to fetch the results or to cancel it, e.g.
+    /// once interrupted by Ctrl-C
+    pub async fn submit_query(&self, sql: String) -> Result<String, String> {
+        let user_info = &self.session_config.user_info;
+        let param = SqlParam {
+            db: Some(self.session_config.database.clone()),
+            chunked: None,
+            target_partitions: self.session_config.target_partitions,
+            format: None,
+            consistency: None,
+            tz: None,
+            page_size: None,
+            cursor: None,
+        };
+
+        let resp = self
+            .http_client
+            .post(API_V1_SQL_ASYNC_PATH)
+            .basic_auth::<&str, &str>(&user_info.user, user_info.password.as_deref())
+            .query(&param)
+            .body(sql)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if resp.status() != ACCEPTED {
+            return Err(resp.text().await.map_err(|e| e.to_string())?);
+        }
+        let body = resp.bytes().await.map_err(|e| e.to_string())?;
+        let status: serde_json::Value = serde_json::from_slice(&body).map_err(|e| e.to_string())?;
+        status["id"]
+            .as_str()
+            .map(|e| e.to_string())
+            .ok_or_else(|| format!("No id in the status of the query: {}", status))
+    }
+
+    /// The results of the query submitted, fetched a page at a time until the query
+    /// is finished, the query is removed then
+    pub async fn fetch_query(&self, id: &str) -> Result<ResultSet, String> {
+        let result = self.fetch_pages(id).await;
+        // The results are kept on the server until removed or expired
+        let removed = self.cancel_query(id).await;
+        let batches = result?;
+        removed?;
+        Ok(ResultSet::RecordBatches(batches))
+    }
+
+    /// Cancels the query submitted if running and removes its results
+    pub async fn cancel_query(&self, id: &str) -> Result<(), String> {
+        let user_info = &self.session_config.user_info;
+        let resp = self
+            .http_client
+            .delete(format!("{}/{}", API_V1_SQL_ASYNC_PATH, id))
+            .basic_auth::<&str, &str>(&user_info.user, user_info.password.as_deref())
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        match resp.status() {
+            OK => Ok(()),
+            _ => Err(resp.text().await.map_err(|e| e.to_string())?),
+        }
+    }
+
+    async fn fetch_pages(&self, id: &str) -> Result<Vec<RecordBatch>, String> {
+        let mut batches = vec![];
+        for page in 0_usize.. {
+            match self.fetch_page(id, page).await {
+                // The query returns no results
+                Ok(page_batches) if page_batches.is_empty() => break,
+                Ok(page_batches) => batches.extend(page_batches),
+                Err(e) => {
+                    // The page after the last one once the query succeeded
+                    let status = self.query_status(id).await?;
+                    let pages = status["pages"].as_u64().unwrap_or_default();
+                    if status["state"] == "succeeded" && pages <= page as u64 {
+                        break;
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        Ok(batches)
+    }
+
+    /// The page of the results, waited for by the server if the query is running
+    async fn fetch_page(&self, id: &str, page: usize) -> Result<Vec<RecordBatch>, String> {
+        let user_info = &self.session_config.user_info;
+        let param = AsyncResultParam {
+            page: Some(page),
+            format: None,
+        };
+        let resp = self
+            .http_client
+            .get(format!("{}/{}/result", API_V1_SQL_ASYNC_PATH, id))
+            .basic_auth::<&str, &str>(&user_info.user, user_info.password.as_deref())
+            .header(ACCEPT, APPLICATION_ARROW)
+            .query(&param)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        match resp.status() {
+            OK => {
+                let body = resp.bytes().await.map_err(|e| e.to_string())?;
+                read_batches(&body)
+            }
+            _ => Err(resp.text().await.map_err(|e| e.to_string())?),
+        }
+    }
+
+    async fn query_status(&self, id: &str) -> Result<serde_json::Value, String> {
+        let user_info = &self.session_config.user_info;
+        let resp = self
+            .http_client
+            .get(format!("{}/{}", API_V1_SQL_ASYNC_PATH, id))
+            .basic_auth::<&str, &str>(&user_info.user, user_info.password.as_deref())
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if resp.status() != OK {
+            return Err(resp.text().await.map_err(|e| e.to_string())?);
+        }
+        let body = resp.bytes().await.map_err(|e| e.to_string())?;
+        serde_json::from_slice(&body).map_err(|e| e.to_string())
+    }
+
     pub async fn write(&self, path: &str) -> Result<ResultSet, String> {
         let body = tokio::fs::read(path).await.map_err(|e| e.to_string())?;
 
@@ -183,6 +335,8 @@ impl SessionContext {
 
         let param = WriteParam {
             db: self.session_config.database.clone(),
+            consistency: None,
+            precision: None,
         };
 
         // let param = &[("db", &self.session_config.database)];
@@ -210,6 +364,64 @@ impl SessionContext {
             }
         }
     }
+
+    /// Verifies the integrity of the files of the database on the server, the
+    /// report is returned as json
+    pub async fn verify(&self, db: &str) -> Result<ResultSet, String> {
+        let user_info = &self.session_config.user_info;
+
+        let param = VerifyParam { db: db.to_string() };
+
+        let resp = self
+            .http_client
+            .get(API_V1_VERIFY_PATH)
+            .basic_auth::<&str, &str>(&user_info.user, user_info.password.as_deref())
+            .query(&param)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        match resp.status() {
+            OK => {
+                let body = resp.bytes().await.map_err(|e| format!("{}", e))?;
+
+                Ok(ResultSet::Bytes((body.to_vec(), 0)))
+            }
+            _ => {
+                let body = resp.text().await.map_err(|e| format!("{}", e))?;
+
+                Err(body)
+            }
+        }
+    }
+
+    /// Exports the data of the database in `[start, end)` to a directory of the
+    /// server, or imports the data exported to the directory into the database
+    pub async fn partition(&self, path: &str, param: &PartitionParam) -> Result<ResultSet, String> {
+        let user_info = &self.session_config.user_info;
+
+        let resp = self
+            .http_client
+            .post(path)
+            .basic_auth::<&str, &str>(&user_info.user, user_info.password.as_deref())
+            .query(param)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        match resp.status() {
+            OK => {
+                let body = resp.bytes().await.map_err(|e| format!("{}", e))?;
+
+                Ok(ResultSet::Bytes((body.to_vec(), 0)))
+            }
+            _ => {
+                let body = resp.text().await.map_err(|e| format!("{}", e))?;
+
+                Err(body)
+            }
+        }
+    }
 }
 
 pub enum ResultSet {
@@ -219,10 +431,13 @@ pub enum ResultSet {
 }
 
 impl ResultSet {
-    pub fn print_fmt(&self, print: &PrintFormat) -> Result<(), String> {
+    pub fn print_fmt(&self, options: &PrintOptions) -> Result<(), String> {
         match self {
             Self::RecordBatches(batches) => {
-                print.print_batches(batches).map_err(|e| e.to_string())?;
+                options
+                    .print_format()
+                    .print_batches(batches, &options.null, &options.time_format)
+                    .map_err(|e| e.to_string())?;
             }
             Self::Bytes((r, _)) => {
                 let str = String::from_utf8(r.to_owned()).map_err(|e| e.to_string())?;
@@ -242,3 +457,13 @@ impl ResultSet {
         }
     }
 }
+
+/// The record batches of an arrow ipc stream, empty if the statement returns no results
+fn read_batches(body: &[u8]) -> Result<Vec<RecordBatch>, String> {
+    if body.is_empty() {
+        return Ok(vec![]);
+    }
+    StreamReader::try_new(Cursor::new(body), None)
+        .and_then(|reader| reader.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| e.to_string())
+}
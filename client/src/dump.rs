@@ -0,0 +1,212 @@
+//! Subcommands backing up the databases by `EXPORT DATABASE` and restoring them by
+//! `IMPORT DATABASE`, the locations of the dumps are resolved by the server, e.g.
+//! a directory of the server or `s3://bucket/path`
+
+use clap::{Args, Subcommand};
+
+#[derive(Debug, Subcommand, PartialEq, Eq)]
+pub enum DumpCommand {
+    /// Exports the points of a database to a dump
+    Dump(DumpArgs),
+    /// Imports the points of a dump into a database
+    Restore(RestoreArgs),
+}
+
+#[derive(Debug, Args, PartialEq, Eq)]
+pub struct DumpArgs {
+    #[clap(long, help = "The database to dump")]
+    pub db: String,
+
+    #[clap(
+        long,
+        help = "The location the dump is written to, a local one is relative to the dump_dir of the server"
+    )]
+    pub out: String,
+
+    #[clap(
+        long,
+        help = "Inclusive lower bound of the time, nanoseconds or a RFC3339 timestamp"
+    )]
+    pub start: Option<String>,
+
+    #[clap(
+        long,
+        help = "Exclusive upper bound of the time, nanoseconds or a RFC3339 timestamp"
+    )]
+    pub end: Option<String>,
+
+    #[clap(long, help = "The tables dumped, separated by commas, all if not set")]
+    pub tables: Option<String>,
+
+    #[clap(long, help = "gzip or none, gzip if not set")]
+    pub compression: Option<String>,
+}
+
+#[derive(Debug, Args, PartialEq, Eq)]
+pub struct RestoreArgs {
+    #[clap(long, help = "The database the dump is restored into")]
+    pub db: String,
+
+    #[clap(
+        long,
+        help = "The location of the dump, a local one is relative to the dump_dir of the server"
+    )]
+    pub from: String,
+
+    #[clap(
+        long,
+        help = "Inclusive lower bound of the time, nanoseconds or a RFC3339 timestamp"
+    )]
+    pub start: Option<String>,
+
+    #[clap(
+        long,
+        help = "Exclusive upper bound of the time, nanoseconds or a RFC3339 timestamp"
+    )]
+    pub end: Option<String>,
+
+    #[clap(
+        long,
+        help = "The tables restored, separated by commas, all if not set"
+    )]
+    pub tables: Option<String>,
+
+    #[clap(
+        long = "map",
+        multiple_occurrences = true,
+        help = "Restores a table of the dump as another table, e.g. --map cpu=cpu_2022"
+    )]
+    pub table_mapping: Vec<String>,
+}
+
+impl DumpCommand {
+    /// The statement executed by the server
+    pub fn to_sql(&self) -> Result<String, String> {
+        match self {
+            Self::Dump(args) => {
+                let options = [
+                    ("start_time", args.start.clone()),
+                    ("end_time", args.end.clone()),
+                    ("tables", args.tables.clone()),
+                    ("compression", args.compression.clone()),
+                ];
+                Ok(format!(
+                    "EXPORT DATABASE {} TO {}{}",
+                    quote_ident(&args.db),
+                    quote_literal(&args.out),
+                    with_options(&options)
+                ))
+            }
+            Self::Restore(args) => {
+                let table_mapping = args
+                    .table_mapping
+                    .iter()
+                    .map(|e| match e.split_once('=') {
+                        Some((from, to)) => Ok(format!("{}:{}", from.trim(), to.trim())),
+                        None => Err(format!(
+                            "Invalid table mapping '{}', expected source_table=target_table",
+                            e
+                        )),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                let options = [
+                    ("start_time", args.start.clone()),
+                    ("end_time", args.end.clone()),
+                    ("tables", args.tables.clone()),
+                    (
+                        "table_mapping",
+                        Some(table_mapping.join(",")).filter(|e| !e.is_empty()),
+                    ),
+                ];
+                Ok(format!(
+                    "IMPORT DATABASE {} FROM {}{}",
+                    quote_ident(&args.db),
+                    quote_literal(&args.from),
+                    with_options(&options)
+                ))
+            }
+        }
+    }
+}
+
+fn quote_ident(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+fn quote_literal(literal: &str) -> String {
+    format!("'{}'", literal.replace('\'', "''"))
+}
+
+/// `WITH (key = 'value', ...)` of the options set
+fn with_options(options: &[(&str, Option<String>)]) -> String {
+    let options = options
+        .iter()
+        .filter_map(|(key, value)| {
+            value
+                .as_ref()
+                .map(|e| format!("{} = {}", key, quote_literal(e)))
+        })
+        .collect::<Vec<_>>();
+    if options.is_empty() {
+        String::new()
+    } else {
+        format!(" WITH ({})", options.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_sql() {
+        let dump = DumpCommand::Dump(DumpArgs {
+            db: "db1".to_string(),
+            out: "db1/it's".to_string(),
+            start: Some("2022-01-01T00:00:00Z".to_string()),
+            end: None,
+            tables: Some("cpu,mem".to_string()),
+            compression: None,
+        });
+        assert_eq!(
+            dump.to_sql().unwrap(),
+            "EXPORT DATABASE \"db1\" TO 'db1/it''s' WITH (start_time = '2022-01-01T00:00:00Z', tables = 'cpu,mem')"
+        );
+
+        let mut args = RestoreArgs {
+            db: "db2".to_string(),
+            from: "s3://bucket/dump".to_string(),
+            start: None,
+            end: None,
+            tables: None,
+            table_mapping: vec![],
+        };
+        assert_eq!(
+            DumpCommand::Restore(args).to_sql().unwrap(),
+            "IMPORT DATABASE \"db2\" FROM 's3://bucket/dump'"
+        );
+
+        args = RestoreArgs {
+            db: "db2".to_string(),
+            from: "dump".to_string(),
+            start: None,
+            end: Some("100".to_string()),
+            tables: None,
+            table_mapping: vec!["cpu=cpu_2022".to_string(), "mem = mem_2022".to_string()],
+        };
+        assert_eq!(
+            DumpCommand::Restore(args).to_sql().unwrap(),
+            "IMPORT DATABASE \"db2\" FROM 'dump' WITH (end_time = '100', table_mapping = 'cpu:cpu_2022,mem:mem_2022')"
+        );
+
+        let restore = DumpCommand::Restore(RestoreArgs {
+            db: "db2".to_string(),
+            from: "dump".to_string(),
+            start: None,
+            end: None,
+            tables: None,
+            table_mapping: vec!["cpu".to_string()],
+        });
+        assert!(restore.to_sql().is_err());
+    }
+}
@@ -7,12 +7,18 @@ use crate::{
     print_options::PrintOptions,
 };
 use rustyline::error::ReadlineError;
-use rustyline::Editor;
+use rustyline::{Cmd, CompletionType, Config, Editor, KeyCode, KeyEvent, Modifiers};
+use std::collections::BTreeSet;
 use std::fs::File;
 use std::io::prelude::*;
 use std::io::BufReader;
+use std::path::PathBuf;
 use std::time::Instant;
 
+/// The history kept in the home directory across the sessions
+const HISTORY_FILE: &str = ".cnosdb_history";
+const MAX_HISTORY_SIZE: usize = 1000;
+
 /// run and execute SQL statements and commands from a file, against a context with the given print options
 pub async fn exec_from_lines(
     ctx: &mut SessionContext,
@@ -71,9 +77,19 @@ pub async fn exec_from_files(
 
 /// run and execute SQL statements and commands against a context with the given print options
 pub async fn exec_from_repl(ctx: &mut SessionContext, print_options: &mut PrintOptions) {
-    let mut rl = Editor::<CliHelper>::new();
+    let config = Config::builder()
+        .history_ignore_dups(true)
+        .history_ignore_space(true)
+        .max_history_size(MAX_HISTORY_SIZE)
+        .completion_type(CompletionType::List)
+        .build();
+    let mut rl = Editor::<CliHelper>::with_config(config);
     rl.set_helper(Some(CliHelper::default()));
-    rl.load_history(".history").ok();
+    // Alt-Enter breaks the line of the statement edited without submitting it
+    rl.bind_sequence(KeyEvent(KeyCode::Enter, Modifiers::ALT), Cmd::Newline);
+    let history = history_path();
+    rl.load_history(&history).ok();
+    refresh_names(ctx, &mut rl).await;
 
     let mut print_options = print_options.clone();
 
@@ -81,6 +97,7 @@ pub async fn exec_from_repl(ctx: &mut SessionContext, print_options: &mut PrintO
         match rl.readline(format!("{} ❯ ", ctx.get_database()).as_str()) {
             Ok(line) if line.starts_with('\\') => {
                 rl.add_history_entry(line.trim_end());
+                rl.save_history(&history).ok();
                 let command = line.split_whitespace().collect::<Vec<_>>().join(" ");
                 if let Ok(cmd) = &command[1..].parse::<Command>() {
                     match cmd {
@@ -98,6 +115,12 @@ pub async fn exec_from_repl(ctx: &mut SessionContext, print_options: &mut PrintO
                                 println!("Output format is {:?}.", print_options.format);
                             }
                         }
+                        Command::ConnectDatabase(_) => {
+                            match cmd.execute(ctx, &mut print_options).await {
+                                Ok(_) => refresh_names(ctx, &mut rl).await,
+                                Err(e) => eprintln!("{}", e),
+                            }
+                        }
                         _ => {
                             if let Err(e) = cmd.execute(ctx, &mut print_options).await {
                                 eprintln!("{}", e)
@@ -110,10 +133,35 @@ pub async fn exec_from_repl(ctx: &mut SessionContext, print_options: &mut PrintO
             }
             Ok(line) => {
                 rl.add_history_entry(line.trim_end());
-                match exec_and_print(ctx, &print_options, line).await {
-                    Ok(_) => {}
+                rl.save_history(&history).ok();
+                let changes_schema = is_ddl(&line);
+                let now = Instant::now();
+                // Ctrl-C cancels the query submitted by its id instead of quitting, the
+                // queries of the other clients of the user are not affected
+                match ctx.submit_query(line).await {
+                    Ok(id) => {
+                        let cancelled = tokio::select! {
+                            result = fetch_and_print(ctx, &print_options, &id, now) => {
+                                if let Err(err) = result {
+                                    eprintln!("{:?}", err);
+                                }
+                                false
+                            }
+                            _ = tokio::signal::ctrl_c() => true,
+                        };
+                        if cancelled {
+                            println!("^C");
+                            match ctx.cancel_query(&id).await {
+                                Ok(_) => eprintln!("Query cancelled"),
+                                Err(e) => eprintln!("Failed to cancel the query: {}", e),
+                            }
+                        }
+                    }
                     Err(err) => eprintln!("{:?}", err),
                 }
+                if changes_schema {
+                    refresh_names(ctx, &mut rl).await;
+                }
             }
             Err(ReadlineError::Interrupted) => {
                 println!("^C");
@@ -130,7 +178,42 @@ pub async fn exec_from_repl(ctx: &mut SessionContext, print_options: &mut PrintO
         }
     }
 
-    rl.save_history(".history").ok();
+    rl.save_history(&history).ok();
+}
+
+fn history_path() -> PathBuf {
+    match dirs::home_dir() {
+        Some(home) => home.join(HISTORY_FILE),
+        None => PathBuf::from(HISTORY_FILE),
+    }
+}
+
+/// Whether the statement may change the tables or the columns
+fn is_ddl(sql: &str) -> bool {
+    let keyword = sql.split_whitespace().next().unwrap_or_default();
+    ["CREATE", "DROP", "ALTER"]
+        .iter()
+        .any(|e| keyword.eq_ignore_ascii_case(e))
+}
+
+/// Loads the names of the tables and the columns of the database connected to
+/// complete them, kept if the database is not available
+async fn refresh_names(ctx: &SessionContext, rl: &mut Editor<CliHelper>) {
+    let tables = match ctx.first_column("SHOW TABLES").await {
+        Ok(tables) => tables,
+        Err(_) => return,
+    };
+    let mut names = BTreeSet::new();
+    for table in tables {
+        let sql = format!("DESCRIBE TABLE \"{}\"", table.replace('"', "\"\""));
+        if let Ok(columns) = ctx.first_column(&sql).await {
+            names.extend(columns);
+        }
+        names.insert(table);
+    }
+    if let Some(helper) = rl.helper_mut() {
+        helper.set_names(names.into_iter().collect());
+    }
 }
 
 async fn exec_and_print(
@@ -144,3 +227,15 @@ async fn exec_and_print(
 
     Ok(())
 }
+
+async fn fetch_and_print(
+    ctx: &SessionContext,
+    print_options: &PrintOptions,
+    id: &str,
+    now: Instant,
+) -> Result<(), String> {
+    let results = ctx.fetch_query(id).await?;
+    print_options.print_batches(&results, now)?;
+
+    Ok(())
+}
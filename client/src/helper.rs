@@ -1,5 +1,6 @@
 //! Helper that helps with interactive editing, including multi-line parsing and validation,
-//! and auto-completion for file name during creating external table.
+//! and auto-completion for file name during creating external table and for the names of
+//! the tables and the columns of the database connected.
 
 use datafusion::sql::parser::{DFParser, Statement};
 use rustyline::completion::Completer;
@@ -18,6 +19,38 @@ use rustyline::Result;
 #[derive(Default)]
 pub struct CliHelper {
     completer: FilenameCompleter,
+    /// Names of the tables and the columns of the database connected
+    names: Vec<String>,
+}
+
+impl CliHelper {
+    pub fn set_names(&mut self, names: Vec<String>) {
+        self.names = names;
+    }
+
+    /// The names starting with the word before the position, case insensitive
+    fn complete_name(&self, line: &str, pos: usize) -> (usize, Vec<Pair>) {
+        let start = line[..pos].trim_end_matches(is_name_char).len();
+        let prefix = line[start..pos].to_lowercase();
+        if prefix.is_empty() {
+            return (pos, Vec::with_capacity(0));
+        }
+
+        let candidates = self
+            .names
+            .iter()
+            .filter(|name| name.to_lowercase().starts_with(&prefix))
+            .map(|name| Pair {
+                display: name.clone(),
+                replacement: name.clone(),
+            })
+            .collect();
+        (start, candidates)
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
 }
 
 impl Highlighter for CliHelper {}
@@ -51,7 +84,7 @@ impl Completer for CliHelper {
         if is_open_quote_for_location(line, pos) {
             self.completer.complete(line, pos, ctx)
         } else {
-            Ok((0, Vec::with_capacity(0)))
+            Ok(self.complete_name(line, pos))
         }
     }
 }
@@ -77,3 +110,30 @@ impl Validator for CliHelper {
 }
 
 impl Helper for CliHelper {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_complete_name() {
+        let mut helper = CliHelper::default();
+        helper.set_names(vec![
+            "air".to_string(),
+            "Air_Quality".to_string(),
+            "station".to_string(),
+        ]);
+
+        let (start, candidates) = helper.complete_name("SELECT * FROM ai", 16);
+        assert_eq!(start, 14);
+        let candidates: Vec<_> = candidates.into_iter().map(|e| e.replacement).collect();
+        assert_eq!(candidates, vec!["air", "Air_Quality"]);
+
+        let (start, candidates) = helper.complete_name("SELECT st FROM air", 9);
+        assert_eq!(start, 7);
+        assert_eq!(candidates.len(), 1);
+
+        let (_, candidates) = helper.complete_name("SELECT ", 7);
+        assert!(candidates.is_empty());
+    }
+}
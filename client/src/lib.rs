@@ -4,6 +4,7 @@ pub const CNOSDB_CLI_VERSION: &str = env!("CARGO_PKG_VERSION");
 pub mod command;
 pub mod config;
 pub mod ctx;
+pub mod dump;
 pub mod exec;
 pub mod functions;
 pub mod helper;
@@ -1,11 +1,17 @@
-use clap::Parser;
-use client::ctx::{SessionConfig, SessionContext};
-use client::{exec, print_format::PrintFormat, print_options::PrintOptions, CNOSDB_CLI_VERSION};
-use datafusion::error::Result;
+use clap::{Parser, Subcommand};
+use client::ctx::{
+    SessionConfig, SessionContext, API_V1_EXPORT_PARTITION_PATH, API_V1_IMPORT_PARTITION_PATH,
+};
+use client::dump::DumpCommand;
+use client::print_format::{PrintFormat, TimeFormat};
+use client::{exec, print_options::PrintOptions, CNOSDB_CLI_VERSION};
+use datafusion::error::{DataFusionError, Result};
+use http_protocol::parameter::PartitionParam;
 use mimalloc::MiMalloc;
 use std::env;
 use std::net::IpAddr;
 use std::path::Path;
+use std::time::Instant;
 
 #[global_allocator]
 static GLOBAL: MiMalloc = MiMalloc;
@@ -92,12 +98,60 @@ struct Args {
     #[clap(long, arg_enum, default_value_t = PrintFormat::Table)]
     format: PrintFormat,
 
+    #[clap(
+        long,
+        help = "The text the NULLs are printed as, except in json",
+        default_value = ""
+    )]
+    null: String,
+
+    #[clap(
+        long,
+        help = "How the timestamps are printed: rfc3339, epoch or a strftime pattern",
+        default_value = "rfc3339"
+    )]
+    time_format: TimeFormat,
+
     #[clap(
         short,
         long,
         help = "Reduce printing other than the results and work quietly"
     )]
     quiet: bool,
+
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand, PartialEq, Eq)]
+enum Command {
+    #[clap(flatten)]
+    Dump(DumpCommand),
+    /// Verifies the checksums, the index and the tombstones of the files of a
+    /// database on the server, the problems found are reported
+    Verify {
+        #[clap(long, help = "The database to verify")]
+        db: String,
+    },
+    /// Exports the data of a time partition of a database to a directory of the
+    /// server, as the files of the server with a manifest
+    ExportPartition {
+        #[clap(long, help = "The database to export")]
+        db: String,
+        #[clap(long, help = "The directory of the server exported to")]
+        dir: String,
+        #[clap(long, help = "Inclusive lower bound of the time in nanoseconds")]
+        start: Option<i64>,
+        #[clap(long, help = "Exclusive upper bound of the time in nanoseconds")]
+        end: Option<i64>,
+    },
+    /// Imports the data exported to a directory of the server into a database
+    ImportPartition {
+        #[clap(long, help = "The database imported into")]
+        db: String,
+        #[clap(long, help = "The directory of the server exported to")]
+        dir: String,
+    },
 }
 
 #[tokio::main]
@@ -121,16 +175,72 @@ pub async fn main() -> Result<()> {
         .with_user(args.user)
         .with_password(args.password)
         .with_database(args.database)
-        .with_target_partitions(args.target_partitions)
-        .with_result_format(args.format);
+        .with_target_partitions(args.target_partitions);
 
     let mut ctx = SessionContext::new(session_config);
 
     let mut print_options = PrintOptions {
         format: args.format,
         quiet: args.quiet,
+        expanded: false,
+        null: args.null,
+        time_format: args.time_format,
     };
 
+    // Backs up, restores or verifies a database, then exits
+    match args.command {
+        Some(Command::Dump(command)) => {
+            let sql = command.to_sql().map_err(DataFusionError::Execution)?;
+            let now = Instant::now();
+            let results = ctx.sql(sql).await.map_err(DataFusionError::Execution)?;
+            return print_options
+                .print_batches(&results, now)
+                .map_err(DataFusionError::Execution);
+        }
+        Some(Command::Verify { db }) => {
+            let report = ctx.verify(&db).await.map_err(DataFusionError::Execution)?;
+            return report
+                .print_fmt(&print_options)
+                .map_err(DataFusionError::Execution);
+        }
+        Some(Command::ExportPartition {
+            db,
+            dir,
+            start,
+            end,
+        }) => {
+            let param = PartitionParam {
+                db,
+                dir,
+                start,
+                end,
+            };
+            let manifest = ctx
+                .partition(API_V1_EXPORT_PARTITION_PATH, &param)
+                .await
+                .map_err(DataFusionError::Execution)?;
+            return manifest
+                .print_fmt(&print_options)
+                .map_err(DataFusionError::Execution);
+        }
+        Some(Command::ImportPartition { db, dir }) => {
+            let param = PartitionParam {
+                db,
+                dir,
+                start: None,
+                end: None,
+            };
+            let manifest = ctx
+                .partition(API_V1_IMPORT_PARTITION_PATH, &param)
+                .await
+                .map_err(DataFusionError::Execution)?;
+            return manifest
+                .print_fmt(&print_options)
+                .map_err(DataFusionError::Execution);
+        }
+        None => {}
+    }
+
     let files = args.file;
     let rc = match args.rc {
         Some(file) => file,
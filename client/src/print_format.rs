@@ -1,13 +1,18 @@
 //! Print format variants
+use datafusion::arrow::array::{
+    as_primitive_array, Array, ArrayRef, StringArray, TimestampNanosecondArray,
+};
+use datafusion::arrow::compute::cast;
 use datafusion::arrow::csv::writer::WriterBuilder;
+use datafusion::arrow::datatypes::{DataType, Field, Schema, TimeUnit, TimestampNanosecondType};
 use datafusion::arrow::json::{ArrayWriter, LineDelimitedWriter};
 use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::arrow::util::display::array_value_to_string;
 use datafusion::arrow::util::pretty;
 use datafusion::error::{DataFusionError, Result};
-use http_protocol::header::{
-    APPLICATION_CSV, APPLICATION_JSON, APPLICATION_NDJSON, APPLICATION_TABLE, APPLICATION_TSV,
-};
+use std::fmt::Write;
 use std::str::FromStr;
+use std::sync::Arc;
 
 /// Allow records to be printed in different formats
 #[derive(Debug, PartialEq, Eq, clap::ArgEnum, Clone, Copy)]
@@ -17,6 +22,7 @@ pub enum PrintFormat {
     Table,
     Json,
     NdJson,
+    Vertical,
 }
 
 impl FromStr for PrintFormat {
@@ -27,6 +33,42 @@ impl FromStr for PrintFormat {
     }
 }
 
+/// How the timestamps are printed
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum TimeFormat {
+    /// e.g. `2022-10-01T08:00:00.000000001`
+    #[default]
+    Rfc3339,
+    /// The integer of the unit of the column
+    Epoch,
+    /// strftime pattern, e.g. `%Y-%m-%d %H:%M:%S`
+    Pattern(String),
+}
+
+impl FromStr for TimeFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "rfc3339" => Ok(Self::Rfc3339),
+            "epoch" => Ok(Self::Epoch),
+            _ if s.contains('%') => {
+                let invalid = chrono::format::StrftimeItems::new(s)
+                    .any(|e| matches!(e, chrono::format::Item::Error));
+                if invalid {
+                    Err(format!("Invalid time format pattern '{}'", s))
+                } else {
+                    Ok(Self::Pattern(s.to_string()))
+                }
+            }
+            _ => Err(format!(
+                "Invalid time format '{}' [possible values: rfc3339, epoch, strftime pattern]",
+                s
+            )),
+        }
+    }
+}
+
 macro_rules! batches_to_json {
     ($WRITER: ident, $batches: expr) => {{
         let mut bytes = vec![];
@@ -55,29 +97,143 @@ fn print_batches_with_sep(batches: &[RecordBatch], delimiter: u8) -> Result<Stri
     Ok(formatted)
 }
 
-impl PrintFormat {
-    /// print the batches to stdout using the specified format
-    pub fn print_batches(&self, batches: &[RecordBatch]) -> Result<()> {
-        match self {
-            Self::Csv => println!("{}", print_batches_with_sep(batches, b',')?),
-            Self::Tsv => println!("{}", print_batches_with_sep(batches, b'\t')?),
-            Self::Table => pretty::print_batches(batches)?,
-            Self::Json => println!("{}", batches_to_json!(ArrayWriter, batches)),
-            Self::NdJson => {
-                println!("{}", batches_to_json!(LineDelimitedWriter, batches))
+/// One line per column of the rows, for the rows too wide to be read in a table
+fn print_batches_vertical(batches: &[RecordBatch]) -> Result<String> {
+    let mut formatted = String::new();
+    let mut row_number = 0;
+    for batch in batches {
+        let schema = batch.schema();
+        let width = schema
+            .fields()
+            .iter()
+            .map(|e| e.name().chars().count())
+            .max()
+            .unwrap_or_default();
+        for row in 0..batch.num_rows() {
+            row_number += 1;
+            writeln!(
+                formatted,
+                "*************************** {}. row ***************************",
+                row_number
+            )
+            .map_err(|e| DataFusionError::Execution(e.to_string()))?;
+            for (field, column) in schema.fields().iter().zip(batch.columns()) {
+                writeln!(
+                    formatted,
+                    "{:>width$}: {}",
+                    field.name(),
+                    array_value_to_string(column, row)?,
+                    width = width
+                )
+                .map_err(|e| DataFusionError::Execution(e.to_string()))?;
             }
         }
-        Ok(())
     }
+    Ok(formatted)
+}
+
+/// The timestamps of the array formatted, others kept
+fn format_times(array: &ArrayRef, time_format: &TimeFormat) -> Result<ArrayRef> {
+    if !matches!(array.data_type(), DataType::Timestamp(..)) {
+        return Ok(array.clone());
+    }
+    match time_format {
+        TimeFormat::Rfc3339 => Ok(array.clone()),
+        TimeFormat::Epoch => Ok(cast(array, &DataType::Int64)?),
+        TimeFormat::Pattern(pattern) => {
+            let nanos = cast(array, &DataType::Timestamp(TimeUnit::Nanosecond, None))?;
+            let nanos: &TimestampNanosecondArray =
+                as_primitive_array::<TimestampNanosecondType>(&nanos);
+            let formatted: StringArray = (0..nanos.len())
+                .map(|row| {
+                    nanos
+                        .value_as_datetime(row)
+                        .filter(|_| nanos.is_valid(row))
+                        .map(|e| e.format(pattern).to_string())
+                })
+                .collect();
+            Ok(Arc::new(formatted))
+        }
+    }
+}
+
+/// The values of the batch as the texts printed, the NULLs as `null`
+fn batch_to_text(batch: &RecordBatch, null: &str, time_format: &TimeFormat) -> Result<RecordBatch> {
+    let schema = batch.schema();
+    let mut fields = Vec::with_capacity(batch.num_columns());
+    let mut columns = Vec::with_capacity(batch.num_columns());
+    for (field, column) in schema.fields().iter().zip(batch.columns()) {
+        let column = format_times(column, time_format)?;
+        let values = (0..column.len())
+            .map(|row| match column.is_null(row) {
+                true => Ok(null.to_string()),
+                false => array_value_to_string(&column, row),
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        fields.push(Field::new(field.name(), DataType::Utf8, false));
+        columns.push(Arc::new(StringArray::from(values)) as ArrayRef);
+    }
+    Ok(RecordBatch::try_new(
+        Arc::new(Schema::new(fields)),
+        columns,
+    )?)
+}
+
+/// The timestamps of the batch formatted, the NULLs are kept as those of json
+fn batch_to_json(batch: &RecordBatch, time_format: &TimeFormat) -> Result<RecordBatch> {
+    let schema = batch.schema();
+    let mut fields = Vec::with_capacity(batch.num_columns());
+    let mut columns = Vec::with_capacity(batch.num_columns());
+    for (field, column) in schema.fields().iter().zip(batch.columns()) {
+        let column = format_times(column, time_format)?;
+        fields.push(Field::new(
+            field.name(),
+            column.data_type().clone(),
+            field.is_nullable(),
+        ));
+        columns.push(column);
+    }
+    Ok(RecordBatch::try_new(
+        Arc::new(Schema::new(fields)),
+        columns,
+    )?)
+}
+
+impl PrintFormat {
+    /// print the batches to stdout using the specified format, the NULLs of the text
+    /// formats as `null`
+    pub fn print_batches(
+        &self,
+        batches: &[RecordBatch],
+        null: &str,
+        time_format: &TimeFormat,
+    ) -> Result<()> {
+        if batches.is_empty() {
+            return Ok(());
+        }
+        if matches!(self, Self::Json | Self::NdJson) {
+            let batches = batches
+                .iter()
+                .map(|e| batch_to_json(e, time_format))
+                .collect::<Result<Vec<_>>>()?;
+            match self {
+                Self::Json => println!("{}", batches_to_json!(ArrayWriter, &batches)),
+                _ => println!("{}", batches_to_json!(LineDelimitedWriter, &batches)),
+            }
+            return Ok(());
+        }
 
-    pub fn get_http_content_type(&self) -> &'static str {
+        let batches = batches
+            .iter()
+            .map(|e| batch_to_text(e, null, time_format))
+            .collect::<Result<Vec<_>>>()?;
         match self {
-            Self::Csv => APPLICATION_CSV,
-            Self::Tsv => APPLICATION_TSV,
-            Self::Json => APPLICATION_JSON,
-            Self::NdJson => APPLICATION_NDJSON,
-            Self::Table => APPLICATION_TABLE,
+            Self::Csv => println!("{}", print_batches_with_sep(&batches, b',')?),
+            Self::Tsv => println!("{}", print_batches_with_sep(&batches, b'\t')?),
+            Self::Table => pretty::print_batches(&batches)?,
+            _ => print!("{}", print_batches_vertical(&batches)?),
         }
+        Ok(())
     }
 }
 
@@ -154,4 +310,59 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn test_print_batches_vertical() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int32, false),
+            Field::new("bcd", DataType::Int32, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int32Array::from_slice([1, 2])),
+                Arc::new(Int32Array::from_slice([3, 4])),
+            ],
+        )
+        .unwrap();
+
+        let r = print_batches_vertical(&[batch]).unwrap();
+        assert_eq!(
+            "*************************** 1. row ***************************\n  a: 1\nbcd: 3\n\
+             *************************** 2. row ***************************\n  a: 2\nbcd: 4\n",
+            r
+        );
+    }
+
+    #[test]
+    fn test_batch_to_text() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(
+                "time",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+            Field::new("v", DataType::Int32, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(TimestampNanosecondArray::from(vec![1_000_000_000, 0])),
+                Arc::new(Int32Array::from(vec![Some(1), None])),
+            ],
+        )
+        .unwrap();
+
+        let text = batch_to_text(&batch, "NULL", &TimeFormat::Epoch).unwrap();
+        let r = print_batches_with_sep(&[text], b',').unwrap();
+        assert_eq!("time,v\n1000000000,1\n0,NULL\n", r);
+
+        let time_format = "%Y-%m-%d %H:%M:%S".parse::<TimeFormat>().unwrap();
+        let text = batch_to_text(&batch, "", &time_format).unwrap();
+        let r = print_batches_with_sep(&[text], b',').unwrap();
+        assert_eq!("time,v\n1970-01-01 00:00:01,1\n1970-01-01 00:00:00,\n", r);
+
+        assert!("%Q".parse::<TimeFormat>().is_err());
+        assert!("iso".parse::<TimeFormat>().is_err());
+    }
 }
@@ -1,11 +1,16 @@
 use crate::ctx::ResultSet;
-use crate::print_format::PrintFormat;
+use crate::print_format::{PrintFormat, TimeFormat};
 use std::time::Instant;
 
 #[derive(Debug, Clone)]
 pub struct PrintOptions {
     pub format: PrintFormat,
     pub quiet: bool,
+    /// Prints the rows vertically whatever the format is, toggled by `\x`
+    pub expanded: bool,
+    /// The text the NULLs are printed as, except in json
+    pub null: String,
+    pub time_format: TimeFormat,
 }
 
 fn print_timing_info(_row_count: usize, now: Instant) {
@@ -19,13 +24,22 @@ fn print_timing_info(_row_count: usize, now: Instant) {
 }
 
 impl PrintOptions {
+    /// The format the results are printed in
+    pub fn print_format(&self) -> PrintFormat {
+        if self.expanded {
+            PrintFormat::Vertical
+        } else {
+            self.format
+        }
+    }
+
     /// print the batches to stdout using the specified format
     pub fn print_batches(
         &self,
         result_set: &ResultSet,
         now: Instant,
     ) -> std::result::Result<(), String> {
-        result_set.print_fmt(&self.format)?;
+        result_set.print_fmt(self)?;
 
         if !self.quiet {
             print_timing_info(result_set.row_count(), now);
@@ -1,5 +1,8 @@
 // re-export const header names
-pub use reqwest::header::{HeaderValue, ACCEPT, AUTHORIZATION, CONTENT_TYPE};
+pub use reqwest::header::{
+    HeaderValue, ACCEPT, ACCEPT_ENCODING, AUTHORIZATION, CONTENT_ENCODING, CONTENT_LENGTH,
+    CONTENT_TYPE, RETRY_AFTER, VARY,
+};
 
 /// value
 pub const APPLICATION_PREFIX: &str = "application/";
@@ -8,8 +11,20 @@ pub const APPLICATION_TSV: &str = "application/tsv";
 pub const APPLICATION_JSON: &str = "application/json";
 pub const APPLICATION_NDJSON: &str = "application/nd-json";
 pub const APPLICATION_TABLE: &str = "application/table";
+pub const APPLICATION_MSGPACK: &str = "application/msgpack";
+pub const APPLICATION_ARROW: &str = "application/vnd.apache.arrow.stream";
 pub const APPLICATION_STAR: &str = "application/*";
 pub const STAR_STAR: &str = "*/*";
+pub const PROMETHEUS_TEXT: &str = "text/plain; version=0.0.4";
+pub const APPLICATION_OCTET_STREAM: &str = "application/octet-stream";
+
+/// name
+/// Cursor of the next page of the results of a paged query, absent after the last one
+pub const NEXT_CURSOR: &str = "x-cnosdb-next-cursor";
 
 /// basic auth
 pub const BASIC_PREFIX: &str = "Basic ";
+/// api token or jwt
+pub const BEARER_PREFIX: &str = "Bearer ";
+/// api token of the influxdb 2.x clients
+pub const TOKEN_PREFIX: &str = "Token ";
@@ -7,10 +7,91 @@ pub struct SqlParam {
     pub chunked: Option<String>,
     // Number of partitions for query execution. Increasing partitions can increase concurrency.
     pub target_partitions: Option<usize>,
+    // Result format, takes precedence over the Accept header, e.g. csv, json, nd-json, arrow
+    pub format: Option<String>,
+    // Replicas of a shard read by the query in a cluster, one of one, quorum, all
+    pub consistency: Option<String>,
+    // Time zone of the buckets of GROUP BY time(...), e.g. Asia/Shanghai; UTC if absent
+    pub tz: Option<String>,
+    // Rows per page of the results of a paged or an asynchronous query, the results of
+    // a paged query are returned a page at a time with the cursor of the next page
+    pub page_size: Option<usize>,
+    // Cursor of the page of a paged query to fetch, the query in the body is ignored
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct AsyncResultParam {
+    // Page of the results fetched, from 0
+    pub page: Option<usize>,
+    // Result format, takes precedence over the Accept header, e.g. csv, json, nd-json, arrow
+    pub format: Option<String>,
 }
 
 #[derive(Deserialize, Serialize)]
 #[serde(rename_all = "snake_case")]
 pub struct WriteParam {
     pub db: String,
+    // Replicas of a shard acknowledged the write in a cluster, one of any, one, quorum, all
+    pub consistency: Option<String>,
+    // Unit of the timestamps of the lines, one of ns, us, ms, s; ns if absent
+    pub precision: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct ChangesParam {
+    pub db: String,
+    // Offset to read from, `earliest` or the `next_offset` of the last response
+    pub offset: Option<String>,
+    // Max number of writes returned
+    pub limit: Option<usize>,
+    // Seconds to wait for new writes if there is none
+    pub timeout: Option<u64>,
+    // Name of the consumer, the wal from the offset on is kept for it
+    pub consumer: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct VerifyParam {
+    pub db: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct PartitionParam {
+    pub db: String,
+    // Directory the partition is exported to or imported from, relative to the
+    // dump directory of the server
+    pub dir: String,
+    // Inclusive lower bound of the time of the partition exported in nanoseconds
+    pub start: Option<i64>,
+    // Exclusive upper bound of the time of the partition exported in nanoseconds
+    pub end: Option<i64>,
+}
+
+/// Parameters of the influxdb 1.x compatible `/query` endpoint,
+/// passed in the query string or the form body
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct InfluxQueryParam {
+    pub db: Option<String>,
+    // Statements separated by semicolons
+    pub q: Option<String>,
+    // Precision of the returned timestamps, one of ns, u, µ, ms, s, m, h; RFC3339 if absent
+    pub epoch: Option<String>,
+    // Username and password if the Authorization header is absent
+    pub u: Option<String>,
+    pub p: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct ProfileParam {
+    // Seconds the cpu is profiled for
+    pub seconds: Option<u64>,
+    // Samples per second
+    pub frequency: Option<i32>,
 }
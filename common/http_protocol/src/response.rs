@@ -11,6 +11,9 @@ pub struct EmptyResponse {}
 pub struct ErrorResponse {
     error_code: String,
     error_message: String,
+    /// The cause of the error, e.g. the message of the underlying io error
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error_detail: Option<String>,
 }
 
 impl ErrorResponse {
@@ -18,6 +21,24 @@ impl ErrorResponse {
         Self {
             error_code: error_code.as_str().to_string(),
             error_message,
+            error_detail: None,
         }
     }
+
+    pub fn with_detail(mut self, error_detail: impl Into<String>) -> Self {
+        self.error_detail = Some(error_detail.into());
+        self
+    }
+
+    pub fn error_code(&self) -> &str {
+        &self.error_code
+    }
+
+    pub fn error_message(&self) -> &str {
+        &self.error_message
+    }
+
+    pub fn error_detail(&self) -> Option<&str> {
+        self.error_detail.as_deref()
+    }
 }
@@ -1,10 +1,16 @@
 use reqwest::StatusCode;
 
 pub const OK: StatusCode = StatusCode::OK;
+/// 请求已接受，异步执行
+pub const ACCEPTED: StatusCode = StatusCode::ACCEPTED;
+/// 请求成功，无响应体
+pub const NO_CONTENT: StatusCode = StatusCode::NO_CONTENT;
 /// 请求参数非法
 pub const BAD_REQUEST: StatusCode = StatusCode::BAD_REQUEST;
 /// 用户密码错误 或 用户不存在
-pub const _UNAUTHORIZED: StatusCode = StatusCode::UNAUTHORIZED;
+pub const UNAUTHORIZED: StatusCode = StatusCode::UNAUTHORIZED;
+/// 跨域请求的来源、请求头或请求方式不被允许
+pub const FORBIDDEN: StatusCode = StatusCode::FORBIDDEN;
 /// 路径不存在
 pub const NOT_FOUND: StatusCode = StatusCode::NOT_FOUND;
 /// 路径不支持对应的请求方式
@@ -13,8 +19,10 @@ pub const METHOD_NOT_ALLOWED: StatusCode = StatusCode::METHOD_NOT_ALLOWED;
 pub const PAYLOAD_TOO_LARGE: StatusCode = StatusCode::PAYLOAD_TOO_LARGE;
 /// 操作执行失败
 pub const UNPROCESSABLE_ENTITY: StatusCode = StatusCode::UNPROCESSABLE_ENTITY;
+/// 请求过于频繁，超过限流
+pub const TOO_MANY_REQUESTS: StatusCode = StatusCode::TOO_MANY_REQUESTS;
 
 /// 查询超时或外部环境引起的异常
 pub const INTERNAL_SERVER_ERROR: StatusCode = StatusCode::INTERNAL_SERVER_ERROR;
 /// 服务不可用
-pub const _SERVICE_UNAVAILABLE: StatusCode = StatusCode::SERVICE_UNAVAILABLE;
+pub const SERVICE_UNAVAILABLE: StatusCode = StatusCode::SERVICE_UNAVAILABLE;
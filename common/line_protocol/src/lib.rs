@@ -1,7 +1,13 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use protos::models as fb_models;
 use snafu::Snafu;
 
 mod parser;
+mod points;
 pub use parser::{FieldValue, Line, Parser};
+pub use points::lines_to_points;
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
@@ -10,9 +16,330 @@ pub type Result<T, E = Error> = std::result::Result<T, E>;
 pub enum Error {
     #[snafu(display("Error: pos: {}, in: '{}'", pos, content))]
     Parse { pos: usize, content: String },
+
+    #[snafu(display("Error: timestamp {} overflows in nanoseconds", timestamp))]
+    TimestampOverflow { timestamp: i64 },
+
+    #[snafu(display("Error: {} tags exceed the limit of {} tags per point", count, max))]
+    TooManyTags { count: usize, max: usize },
+
+    #[snafu(display(
+        "Error: tag key '{}' of {} bytes exceeds the limit of {} bytes",
+        key,
+        len,
+        max
+    ))]
+    TagKeyTooLong { key: String, len: usize, max: usize },
+
+    #[snafu(display(
+        "Error: value of tag '{}' of {} bytes exceeds the limit of {} bytes",
+        key,
+        len,
+        max
+    ))]
+    TagValueTooLong { key: String, len: usize, max: usize },
+
+    #[snafu(display(
+        "Error: tag '{}={}' has characters not allowed by the charset {}",
+        key,
+        value,
+        charset
+    ))]
+    TagCharsetNotAllowed {
+        key: String,
+        value: String,
+        charset: TagCharset,
+    },
+}
+
+/// Unit of the timestamps of the lines written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precision {
+    Nanosecond,
+    Microsecond,
+    Millisecond,
+    Second,
+}
+
+impl Precision {
+    pub fn nanos(&self) -> i64 {
+        match self {
+            Self::Nanosecond => 1,
+            Self::Microsecond => 1_000,
+            Self::Millisecond => 1_000_000,
+            Self::Second => 1_000_000_000,
+        }
+    }
+}
+
+impl FromStr for Precision {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "ns" | "n" => Ok(Self::Nanosecond),
+            "us" | "u" => Ok(Self::Microsecond),
+            "ms" => Ok(Self::Millisecond),
+            "s" => Ok(Self::Second),
+            _ => Err(format!(
+                "precision {} is not supported, expected one of ns, us, ms, s",
+                s
+            )),
+        }
+    }
+}
+
+/// Characters allowed in the tag keys and values
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TagCharset {
+    /// Any UTF-8 characters
+    #[default]
+    Any,
+    /// UTF-8 characters except the control characters
+    Printable,
+    /// Printable ASCII characters
+    Ascii,
+}
+
+impl TagCharset {
+    pub fn allows(&self, s: &str) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Printable => !s.chars().any(char::is_control),
+            Self::Ascii => s.chars().all(|c| c.is_ascii_graphic() || c == ' '),
+        }
+    }
+}
+
+impl Display for TagCharset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Any => write!(f, "any"),
+            Self::Printable => write!(f, "printable"),
+            Self::Ascii => write!(f, "ascii"),
+        }
+    }
+}
+
+/// Limits of the tags of the lines written, 0 for unlimited, the lengths are the
+/// bytes of the keys and the values as written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TagLimits {
+    pub max_tags_per_point: usize,
+    pub max_tag_key_len: usize,
+    pub max_tag_value_len: usize,
+    pub charset: TagCharset,
+}
+
+impl TagLimits {
+    pub fn check(&self, line: &Line) -> Result<()> {
+        self.check_tags(&line.tags)
+    }
+
+    /// Checks the tags of an encoded point, those not in UTF-8 are checked as decoded
+    /// lossily
+    pub fn check_point(&self, point: &fb_models::Point) -> Result<()> {
+        let tags = point
+            .tags()
+            .into_iter()
+            .flatten()
+            .map(|tag| {
+                (
+                    String::from_utf8_lossy(tag.key().unwrap_or_default()),
+                    String::from_utf8_lossy(tag.value().unwrap_or_default()),
+                )
+            })
+            .collect::<Vec<_>>();
+        self.check_tags(&tags)
+    }
+
+    /// Checks the keys and the values of the tags of a point
+    pub fn check_tags<K: AsRef<str>, V: AsRef<str>>(&self, tags: &[(K, V)]) -> Result<()> {
+        let exceeds = |len: usize, max: usize| max > 0 && len > max;
+        if exceeds(tags.len(), self.max_tags_per_point) {
+            return Err(Error::TooManyTags {
+                count: tags.len(),
+                max: self.max_tags_per_point,
+            });
+        }
+        for (key, value) in tags.iter() {
+            let (key, value) = (key.as_ref(), value.as_ref());
+            if exceeds(key.len(), self.max_tag_key_len) {
+                return Err(Error::TagKeyTooLong {
+                    key: key.to_string(),
+                    len: key.len(),
+                    max: self.max_tag_key_len,
+                });
+            }
+            if exceeds(value.len(), self.max_tag_value_len) {
+                return Err(Error::TagValueTooLong {
+                    key: key.to_string(),
+                    len: value.len(),
+                    max: self.max_tag_value_len,
+                });
+            }
+            if !self.charset.allows(key) || !self.charset.allows(value) {
+                return Err(Error::TagCharsetNotAllowed {
+                    key: key.escape_debug().to_string(),
+                    value: value.escape_debug().to_string(),
+                    charset: self.charset,
+                });
+            }
+        }
+        Ok(())
+    }
 }
 
 pub fn line_protocol_to_lines(lines: &str, default_time: i64) -> Result<Vec<Line>> {
     let parser = Parser::new(default_time);
     parser.parse(lines)
 }
+
+/// A line failed to be parsed
+#[derive(Debug)]
+pub struct LineError {
+    /// Number of the line, from 1
+    pub line: usize,
+    pub error: Error,
+}
+
+/// The lines parsed of a write, and the lines failed
+#[derive(Debug, Default)]
+pub struct ParsedLines<'a> {
+    pub lines: Vec<Line<'a>>,
+    /// Numbers of the lines parsed, from 1
+    pub numbers: Vec<usize>,
+    pub errors: Vec<LineError>,
+}
+
+/// Parses the lines one by one, the timestamps of which are in the precision, into
+/// the lines in nanoseconds. The lines failed, or with tags over the limits, are
+/// skipped, and the lines without timestamps are written at `now` truncated to the
+/// precision.
+pub fn line_protocol_to_lines_partially(
+    lines: &str,
+    now: i64,
+    precision: Precision,
+    tag_limits: &TagLimits,
+) -> ParsedLines {
+    let nanos = precision.nanos();
+    let parser = Parser::new(now / nanos);
+    let mut parsed = ParsedLines::default();
+    for (i, text) in lines.split('\n').enumerate() {
+        if text.trim().is_empty() {
+            continue;
+        }
+        let result = parser.parse(text).and_then(|lines| {
+            lines
+                .into_iter()
+                .map(|mut line| {
+                    tag_limits.check(&line)?;
+                    line.timestamp =
+                        line.timestamp
+                            .checked_mul(nanos)
+                            .ok_or(Error::TimestampOverflow {
+                                timestamp: line.timestamp,
+                            })?;
+                    Ok(line)
+                })
+                .collect::<Result<Vec<_>>>()
+        });
+        match result {
+            Ok(lines) => {
+                parsed
+                    .numbers
+                    .extend(std::iter::repeat(i + 1).take(lines.len()));
+                parsed.lines.extend(lines);
+            }
+            Err(error) => parsed.errors.push(LineError { line: i + 1, error }),
+        }
+    }
+    parsed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lines_partially() {
+        let parsed = line_protocol_to_lines_partially(
+            "cpu,host=a usage=1 3\n\ncpu,host=a usage=2\ncpu,host=a\ncpu,host=a usage=3 4",
+            5_500_000_000,
+            Precision::Second,
+            &TagLimits::default(),
+        );
+        let timestamps = parsed.lines.iter().map(|e| e.timestamp).collect::<Vec<_>>();
+        assert_eq!(
+            timestamps,
+            vec![3_000_000_000, 5_000_000_000, 4_000_000_000]
+        );
+        assert_eq!(parsed.numbers, vec![1, 3, 5]);
+        assert_eq!(parsed.errors.len(), 1);
+        assert_eq!(parsed.errors[0].line, 4);
+
+        let overflow = format!("cpu,host=a usage=1 {}", i64::MAX);
+        let parsed = line_protocol_to_lines_partially(
+            &overflow,
+            0,
+            Precision::Millisecond,
+            &TagLimits::default(),
+        );
+        assert!(parsed.lines.is_empty());
+        assert!(matches!(
+            parsed.errors[0].error,
+            Error::TimestampOverflow { .. }
+        ));
+
+        assert_eq!(Precision::from_str("ms").unwrap(), Precision::Millisecond);
+        assert!(Precision::from_str("m").is_err());
+    }
+
+    #[test]
+    fn test_tag_limits() {
+        let limits = TagLimits {
+            max_tags_per_point: 2,
+            max_tag_key_len: 4,
+            max_tag_value_len: 8,
+            charset: TagCharset::Ascii,
+        };
+        let parsed = line_protocol_to_lines_partially(
+            "cpu,host=a,dc=b usage=1 1\n\
+             cpu,host=a,dc=b,rack=c usage=1 1\n\
+             cpu,hostname=a usage=1 1\n\
+             cpu,host=abcdefghi usage=1 1\n\
+             cpu,host=ä usage=1 1",
+            0,
+            Precision::Nanosecond,
+            &limits,
+        );
+        assert_eq!(parsed.numbers, vec![1]);
+        let errors = parsed.errors.iter().map(|e| &e.error).collect::<Vec<_>>();
+        assert!(matches!(errors[0], Error::TooManyTags { count: 3, max: 2 }));
+        assert!(matches!(errors[1], Error::TagKeyTooLong { len: 8, .. }));
+        assert!(matches!(errors[2], Error::TagValueTooLong { len: 9, .. }));
+        assert!(matches!(errors[3], Error::TagCharsetNotAllowed { .. }));
+
+        assert!(TagCharset::Printable.allows("ä b"));
+        assert!(!TagCharset::Printable.allows("a\tb"));
+        assert!(TagLimits::default()
+            .check(&Line {
+                measurement: "cpu",
+                tags: vec![("host", "a\tb")],
+                fields: vec![],
+                timestamp: 0,
+            })
+            .is_ok());
+
+        // The encoded points are checked as the lines
+        let lines = line_protocol_to_lines("cpu,host=a,dc=b,rack=c usage=1 1", 0).unwrap();
+        let points = lines_to_points("db", &lines);
+        let points = flatbuffers::root::<fb_models::Points>(&points).unwrap();
+        let point = points.points().unwrap().get(0);
+        assert!(matches!(
+            limits.check_point(&point),
+            Err(Error::TooManyTags { count: 3, max: 2 })
+        ));
+        assert!(TagLimits::default().check_point(&point).is_ok());
+    }
+}
@@ -1,3 +1,5 @@
+use std::fmt::{self, Display, Write};
+
 use crate::{Error, Result};
 
 pub struct Parser {
@@ -100,6 +102,49 @@ pub struct Line<'a> {
     pub timestamp: i64,
 }
 
+/// Writes the line in line protocol, escaping special characters
+impl Display for Line<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_escaped(f, self.measurement, &[',', ' '])?;
+        for (k, v) in self.tags.iter() {
+            f.write_char(',')?;
+            write_escaped(f, k, &[',', '=', ' '])?;
+            f.write_char('=')?;
+            write_escaped(f, v, &[',', '=', ' '])?;
+        }
+        for (i, (k, v)) in self.fields.iter().enumerate() {
+            f.write_char(if i == 0 { ' ' } else { ',' })?;
+            write_escaped(f, k, &[',', '=', ' '])?;
+            f.write_char('=')?;
+            match v {
+                FieldValue::U64(v) => write!(f, "{}u", v)?,
+                FieldValue::I64(v) => write!(f, "{}i", v)?,
+                // Signed, otherwise they are not parsed as numbers
+                FieldValue::F64(v) if v.is_nan() => f.write_str("+NaN")?,
+                FieldValue::F64(v) if v.is_infinite() => write!(f, "{:+}", v)?,
+                FieldValue::F64(v) => write!(f, "{}", v)?,
+                FieldValue::Bool(v) => write!(f, "{}", v)?,
+                FieldValue::Str(v) => {
+                    f.write_char('"')?;
+                    write_escaped(f, &String::from_utf8_lossy(v), &['"', '\\'])?;
+                    f.write_char('"')?;
+                }
+            }
+        }
+        write!(f, " {}", self.timestamp)
+    }
+}
+
+fn write_escaped(f: &mut fmt::Formatter<'_>, s: &str, special: &[char]) -> fmt::Result {
+    for c in s.chars() {
+        if special.contains(&c) {
+            f.write_char('\\')?;
+        }
+        f.write_char(c)?;
+    }
+    Ok(())
+}
+
 fn check_pos_valid(buf: &str, pos: usize) -> Result<()> {
     if pos < buf.len() {
         return Ok(());
@@ -320,13 +365,18 @@ fn parse_numeric_field(buf: &str, positive: bool) -> Result<FieldValue> {
     }
     let field_val = match &buf[buf.len() - 1..] {
         "i" | "I" => {
-            let v = buf[..buf.len() - 1]
-                .parse::<i64>()
-                .map_err(|_e| Error::Parse {
-                    pos: 0,
-                    content: buf.to_string(),
-                })?;
-            FieldValue::I64(if positive { v } else { -v })
+            let digits = &buf[..buf.len() - 1];
+            // Parsed with the sign, -9223372036854775808 has no positive counterpart
+            let v = if positive {
+                digits.parse::<i64>()
+            } else {
+                format!("-{}", digits).parse::<i64>()
+            }
+            .map_err(|_e| Error::Parse {
+                pos: 0,
+                content: buf.to_string(),
+            })?;
+            FieldValue::I64(v)
         }
         "u" | "U" => {
             if !positive {
@@ -577,6 +627,69 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_display_line() {
+        let line = Line {
+            measurement: "m a",
+            tags: vec![("t,a", "1=2")],
+            fields: vec![
+                ("fa", FieldValue::F64(-1.5)),
+                ("fb", FieldValue::I64(2)),
+                ("fc", FieldValue::U64(3)),
+                ("fd", FieldValue::Bool(true)),
+                ("fe", FieldValue::Str(b"say \"hi\"".to_vec())),
+            ],
+            timestamp: 10,
+        };
+        assert_eq!(
+            line.to_string(),
+            r#"m\ a,t\,a=1\=2 fa=-1.5,fb=2i,fc=3u,fd=true,fe="say \"hi\"" 10"#
+        );
+
+        let line = Line {
+            measurement: "cpu",
+            tags: vec![("host", "a")],
+            fields: vec![("usage", FieldValue::F64(1.0)), ("n", FieldValue::I64(-3))],
+            timestamp: -1,
+        };
+        let text = line.to_string();
+        let parsed = Parser::new(0).parse(&text).unwrap();
+        assert_eq!(parsed, vec![line]);
+
+        let line = Line {
+            measurement: "cpu",
+            tags: vec![],
+            fields: vec![
+                ("a", FieldValue::F64(f64::INFINITY)),
+                ("b", FieldValue::F64(f64::NEG_INFINITY)),
+                ("c", FieldValue::F64(f64::NAN)),
+            ],
+            timestamp: 1,
+        };
+        let text = line.to_string();
+        assert_eq!(text, "cpu a=+inf,b=-inf,c=+NaN 1");
+        let parsed = Parser::new(0).parse(&text).unwrap();
+        assert_eq!(parsed[0].fields[..2], line.fields[..2]);
+        assert!(matches!(parsed[0].fields[2].1, FieldValue::F64(v) if v.is_nan()));
+    }
+
+    #[test]
+    fn test_parse_integer_bounds() {
+        let lines = format!("m u={}u,i={}i,j={}i 1", u64::MAX, i64::MIN, i64::MAX);
+        let parsed = Parser::new(0).parse(&lines).unwrap();
+        assert_eq!(
+            parsed[0].fields,
+            vec![
+                ("u", FieldValue::U64(u64::MAX)),
+                ("i", FieldValue::I64(i64::MIN)),
+                ("j", FieldValue::I64(i64::MAX)),
+            ]
+        );
+
+        assert!(Parser::new(0).parse("m u=-1u 1").is_err());
+        assert!(Parser::new(0).parse("m u=18446744073709551616u 1").is_err());
+    }
+
     #[test]
     #[ignore]
     fn test_generated_data() {
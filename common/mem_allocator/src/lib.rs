@@ -97,6 +97,32 @@ unsafe impl GlobalAlloc for Jemalloc {
     }
 }
 
+/// Heap profiling of jemalloc, the profiles are readable by `jeprof`
+#[cfg(feature = "profiling")]
+pub mod profiling {
+    use std::ffi::CString;
+
+    use libc::c_char;
+    use tikv_jemalloc_ctl::raw;
+
+    /// Jemalloc is started with `prof:true`, e.g. `_RJEM_MALLOC_CONF=prof:true`
+    pub fn is_heap_profiling_active() -> bool {
+        unsafe { raw::read::<bool>(b"opt.prof\0") }.unwrap_or(false)
+    }
+
+    /// Dumps the profile of the heap allocated to the file
+    pub fn dump_heap_profile(path: &str) -> Result<(), String> {
+        if !is_heap_profiling_active() {
+            return Err(
+                "heap profiling is not active, start with _RJEM_MALLOC_CONF=prof:true".to_string(),
+            );
+        }
+        let path = CString::new(path).map_err(|e| e.to_string())?;
+        unsafe { raw::write(b"prof.dump\0", path.as_ptr() as *const c_char) }
+            .map_err(|e| e.to_string())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::Jemalloc;
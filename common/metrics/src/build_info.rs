@@ -0,0 +1,54 @@
+//! Version of the server and when it is started, for `SHOW DIAGNOSTICS` and the
+//! `server_build_info` metric
+use std::time::SystemTime;
+
+use once_cell::sync::{Lazy, OnceCell};
+use prometheus::{IntGaugeVec, Opts};
+use trace::error;
+
+use crate::{REGISTRY, SERVER_NAMESPACE};
+
+#[derive(Debug, Clone)]
+pub struct BuildInfo {
+    pub version: String,
+    /// Git commit the server is built of
+    pub revision: String,
+    pub started_at: SystemTime,
+}
+
+static BUILD_INFO: OnceCell<BuildInfo> = OnceCell::new();
+
+static BUILD_INFO_GAUGE: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(
+        Opts::new(
+            "build_info",
+            "1 labeled by the version and the revision of the server",
+        )
+        .namespace(SERVER_NAMESPACE),
+        &["version", "revision"],
+    )
+    .expect("build info metric cannot be created")
+});
+
+/// Records the build of the server once it is started, later calls are ignored
+pub fn set_build_info(version: &str, revision: &str) {
+    let info = BuildInfo {
+        version: version.to_string(),
+        revision: revision.trim().to_string(),
+        started_at: SystemTime::now(),
+    };
+    if BUILD_INFO.set(info).is_err() {
+        return;
+    }
+    if let Err(e) = REGISTRY.register(Box::new(BUILD_INFO_GAUGE.clone())) {
+        error!("build info metric cannot be registered: {}", e);
+        return;
+    }
+    BUILD_INFO_GAUGE
+        .with_label_values(&[version, revision.trim()])
+        .set(1);
+}
+
+pub fn build_info() -> Option<&'static BuildInfo> {
+    BUILD_INFO.get()
+}
@@ -0,0 +1,140 @@
+//! Counters of the writes and queries of each database on this node since started,
+//! served by `SELECT * FROM system.database_stats` for chargeback and troubleshooting.
+//!
+//! The databases are keyed by the tenant, as the databases of the tenants share the names.
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+use once_cell::sync::Lazy;
+
+/// The most databases counted, the others are not counted once reached
+pub const MAX_DATABASES: usize = 10_000;
+
+static DATABASE_STATS: Lazy<RwLock<HashMap<(String, String), Arc<Counters>>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+#[derive(Debug, Default)]
+struct Counters {
+    points_written: AtomicU64,
+    bytes_written: AtomicU64,
+    writes_failed: AtomicU64,
+    queries: AtomicU64,
+    queries_failed: AtomicU64,
+    bytes_scanned: AtomicU64,
+}
+
+/// Counters of a database
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DatabaseStats {
+    pub points_written: u64,
+    pub bytes_written: u64,
+    /// Writes failed, the points and bytes of which are not counted
+    pub writes_failed: u64,
+    /// Queries scanning the database, including the failed ones
+    pub queries: u64,
+    pub queries_failed: u64,
+    /// Bytes scanned by the queries, split evenly among the databases scanned
+    pub bytes_scanned: u64,
+}
+
+/// The counters of the database, created if `create` and not too many databases are counted
+fn counters(tenant: &str, db: &str, create: bool) -> Option<Arc<Counters>> {
+    let key = (tenant.to_string(), db.to_string());
+    if let Some(counters) = DATABASE_STATS.read().unwrap().get(&key) {
+        return Some(counters.clone());
+    }
+    if !create {
+        return None;
+    }
+    let mut stats = DATABASE_STATS.write().unwrap();
+    if stats.len() >= MAX_DATABASES && !stats.contains_key(&key) {
+        return None;
+    }
+    Some(stats.entry(key).or_default().clone())
+}
+
+/// The failed writes are counted only to the databases counted already,
+/// as the database written might not exist
+pub fn record_write(tenant: &str, db: &str, points: u64, bytes: u64, success: bool) {
+    let counters = match counters(tenant, db, success) {
+        Some(counters) => counters,
+        None => return,
+    };
+    if success {
+        counters.points_written.fetch_add(points, Ordering::Relaxed);
+        counters.bytes_written.fetch_add(bytes, Ordering::Relaxed);
+    } else {
+        counters.writes_failed.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Count a query to a database scanned by it
+pub fn record_query(tenant: &str, db: &str, bytes_scanned: u64, success: bool) {
+    let counters = match counters(tenant, db, true) {
+        Some(counters) => counters,
+        None => return,
+    };
+    counters.queries.fetch_add(1, Ordering::Relaxed);
+    if !success {
+        counters.queries_failed.fetch_add(1, Ordering::Relaxed);
+    }
+    counters
+        .bytes_scanned
+        .fetch_add(bytes_scanned, Ordering::Relaxed);
+}
+
+/// Counters of the databases written or queried, by the tenant and the name
+pub fn database_stats() -> BTreeMap<(String, String), DatabaseStats> {
+    DATABASE_STATS
+        .read()
+        .unwrap()
+        .iter()
+        .map(|(key, c)| {
+            let stats = DatabaseStats {
+                points_written: c.points_written.load(Ordering::Relaxed),
+                bytes_written: c.bytes_written.load(Ordering::Relaxed),
+                writes_failed: c.writes_failed.load(Ordering::Relaxed),
+                queries: c.queries.load(Ordering::Relaxed),
+                queries_failed: c.queries_failed.load(Ordering::Relaxed),
+                bytes_scanned: c.bytes_scanned.load(Ordering::Relaxed),
+            };
+            (key.clone(), stats)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn stats(tenant: &str, db: &str) -> Option<DatabaseStats> {
+        database_stats().remove(&(tenant.to_string(), db.to_string()))
+    }
+
+    #[test]
+    fn test_database_stats() {
+        record_write("stats_tenant", "stats_db", 10, 1024, true);
+        record_write("stats_tenant", "stats_db", 5, 512, false);
+        record_query("stats_tenant", "stats_db", 2048, true);
+        record_query("stats_tenant", "stats_db", 0, false);
+
+        assert_eq!(
+            stats("stats_tenant", "stats_db").unwrap(),
+            DatabaseStats {
+                points_written: 10,
+                bytes_written: 1024,
+                writes_failed: 1,
+                queries: 2,
+                queries_failed: 1,
+                bytes_scanned: 2048,
+            }
+        );
+        // The databases of the other tenants are counted apart
+        assert!(stats("stats_other", "stats_db").is_none());
+
+        // The failed writes do not count the unknown databases
+        record_write("stats_tenant", "stats_unknown", 1, 1, false);
+        assert!(stats("stats_tenant", "stats_unknown").is_none());
+    }
+}
@@ -1,14 +1,24 @@
 use once_cell::sync::Lazy;
 use prometheus::Registry;
-use prometheus::{linear_buckets, HistogramOpts, HistogramVec, IntCounter, Opts};
+use prometheus::{
+    linear_buckets, Gauge, GaugeVec, HistogramOpts, HistogramVec, IntCounter, IntCounterVec,
+    IntGauge, IntGaugeVec, Opts,
+};
 use trace::error;
 
+pub mod build_info;
+pub mod database_stats;
+pub mod stats;
+pub mod tenant_usage;
+
 pub const SERVER_NAMESPACE: &str = "server";
 
 pub const TSKV_SUBSYSTEM: &str = "tskv";
 
 pub const QUERY_SUBSYSTEM: &str = "query";
 
+pub const REPLICATION_SUBSYSTEM: &str = "replication";
+
 pub static REGISTRY: Lazy<Registry> = Lazy::new(Registry::new);
 
 pub static QUERY_READ_SUCCESS: Lazy<IntCounter> = Lazy::new(|| {
@@ -84,6 +94,65 @@ pub static POINT_WRITE_LATENCY: Lazy<HistogramVec> = Lazy::new(|| {
     .expect("query metric cannot be created")
 });
 
+pub static TENANT_WRITES_REJECTED: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "tenant_write_rejected_total",
+            "total num of writes of the tenant rejected by the limits or the quota",
+        )
+        .namespace(SERVER_NAMESPACE)
+        .subsystem(QUERY_SUBSYSTEM),
+        &["tenant", "reason"],
+    )
+    .expect("query metric cannot be created")
+});
+
+pub static TENANT_STORED_BYTES: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(
+        Opts::new(
+            "tenant_stored_bytes",
+            "bytes of the data of the tenant stored on this node",
+        )
+        .namespace(SERVER_NAMESPACE)
+        .subsystem(QUERY_SUBSYSTEM),
+        &["tenant"],
+    )
+    .expect("query metric cannot be created")
+});
+
+pub static PLAN_CACHE_HITS: Lazy<IntCounter> = Lazy::new(|| {
+    IntCounter::with_opts(
+        Opts::new("plan_cache_hits_total", "total num of plan cache hits")
+            .namespace(SERVER_NAMESPACE)
+            .subsystem(QUERY_SUBSYSTEM),
+    )
+    .expect("query metric cannot be created")
+});
+
+pub static PLAN_CACHE_MISSES: Lazy<IntCounter> = Lazy::new(|| {
+    IntCounter::with_opts(
+        Opts::new(
+            "plan_cache_misses_total",
+            "total num of cacheable queries planned",
+        )
+        .namespace(SERVER_NAMESPACE)
+        .subsystem(QUERY_SUBSYSTEM),
+    )
+    .expect("query metric cannot be created")
+});
+
+pub static PLAN_CACHE_HIT_RATIO: Lazy<Gauge> = Lazy::new(|| {
+    Gauge::with_opts(
+        Opts::new(
+            "plan_cache_hit_ratio",
+            "ratio of plan cache hits in all cacheable queries",
+        )
+        .namespace(SERVER_NAMESPACE)
+        .subsystem(QUERY_SUBSYSTEM),
+    )
+    .expect("query metric cannot be created")
+});
+
 pub fn init_query_metrics_recorder() {
     REGISTRY
         .register(Box::new(QUERY_READ_LATENCY.clone()))
@@ -108,6 +177,23 @@ pub fn init_query_metrics_recorder() {
     REGISTRY
         .register(Box::new(POINT_WRITE_SUCCESS.clone()))
         .expect("query metrics collector cannot be registered");
+
+    REGISTRY
+        .register(Box::new(TENANT_WRITES_REJECTED.clone()))
+        .expect("query metrics collector cannot be registered");
+
+    REGISTRY
+        .register(Box::new(TENANT_STORED_BYTES.clone()))
+        .expect("query metrics collector cannot be registered");
+    REGISTRY
+        .register(Box::new(PLAN_CACHE_HITS.clone()))
+        .expect("query metrics collector cannot be registered");
+    REGISTRY
+        .register(Box::new(PLAN_CACHE_MISSES.clone()))
+        .expect("query metrics collector cannot be registered");
+    REGISTRY
+        .register(Box::new(PLAN_CACHE_HIT_RATIO.clone()))
+        .expect("query metrics collector cannot be registered");
 }
 
 pub fn sample_query_read_latency(tenant: &str, db: &str, delta: f64) {
@@ -130,6 +216,24 @@ pub fn incr_query_read_failed() {
     QUERY_READ_FAILED.inc()
 }
 
+pub fn incr_plan_cache_hit() {
+    PLAN_CACHE_HITS.inc();
+    set_plan_cache_hit_ratio();
+}
+
+pub fn incr_plan_cache_miss() {
+    PLAN_CACHE_MISSES.inc();
+    set_plan_cache_hit_ratio();
+}
+
+fn set_plan_cache_hit_ratio() {
+    let hits = PLAN_CACHE_HITS.get();
+    let total = hits + PLAN_CACHE_MISSES.get();
+    if total > 0 {
+        PLAN_CACHE_HIT_RATIO.set(hits as f64 / total as f64);
+    }
+}
+
 pub fn incr_point_write_failed() {
     POINT_WRITE_FAILED.inc()
 }
@@ -173,6 +277,148 @@ pub static COMPACTION_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
     .expect("tskv metric cannot be created")
 });
 
+pub static COMPACTION_BACKLOG: Lazy<IntGauge> = Lazy::new(|| {
+    IntGauge::with_opts(
+        Opts::new(
+            "compaction_backlog",
+            "num of ts families waiting for compaction",
+        )
+        .namespace(SERVER_NAMESPACE)
+        .subsystem(TSKV_SUBSYSTEM),
+    )
+    .expect("tskv metric cannot be created")
+});
+
+pub static WRITE_POINTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new("write_points_total", "total num of points written")
+            .namespace(SERVER_NAMESPACE)
+            .subsystem(TSKV_SUBSYSTEM),
+        &["db"],
+    )
+    .expect("tskv metric cannot be created")
+});
+
+pub static WRITE_BYTES: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new("write_bytes_total", "total bytes of points written")
+            .namespace(SERVER_NAMESPACE)
+            .subsystem(TSKV_SUBSYSTEM),
+        &["db"],
+    )
+    .expect("tskv metric cannot be created")
+});
+
+pub static MEMCACHE_SIZE: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(
+        Opts::new(
+            "memcache_size_bytes",
+            "bytes of the mutable and immutable memcaches",
+        )
+        .namespace(SERVER_NAMESPACE)
+        .subsystem(TSKV_SUBSYSTEM),
+        &["db"],
+    )
+    .expect("tskv metric cannot be created")
+});
+
+pub static FLUSH_PENDING_SIZE: Lazy<IntGauge> = Lazy::new(|| {
+    IntGauge::with_opts(
+        Opts::new(
+            "flush_pending_bytes",
+            "bytes of the immutable memcaches not flushed yet",
+        )
+        .namespace(SERVER_NAMESPACE)
+        .subsystem(TSKV_SUBSYSTEM),
+    )
+    .expect("tskv metric cannot be created")
+});
+
+pub static WRITE_STALLS: Lazy<IntCounter> = Lazy::new(|| {
+    IntCounter::with_opts(
+        Opts::new(
+            "write_stalls_total",
+            "total num of writes waiting for the flushes falling behind",
+        )
+        .namespace(SERVER_NAMESPACE)
+        .subsystem(TSKV_SUBSYSTEM),
+    )
+    .expect("tskv metric cannot be created")
+});
+
+pub static WAL_SIZE: Lazy<IntGauge> = Lazy::new(|| {
+    IntGauge::with_opts(
+        Opts::new("wal_size_bytes", "bytes of the wal files on disk")
+            .namespace(SERVER_NAMESPACE)
+            .subsystem(TSKV_SUBSYSTEM),
+    )
+    .expect("tskv metric cannot be created")
+});
+
+pub static DISK_FREE_SPACE: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(
+        Opts::new("disk_free_bytes", "free bytes of the volumes written to")
+            .namespace(SERVER_NAMESPACE)
+            .subsystem(TSKV_SUBSYSTEM),
+        &["dir"],
+    )
+    .expect("tskv metric cannot be created")
+});
+
+pub static READ_ONLY: Lazy<IntGauge> = Lazy::new(|| {
+    IntGauge::with_opts(
+        Opts::new(
+            "read_only",
+            "1 if the writes are rejected for the low free space of disk",
+        )
+        .namespace(SERVER_NAMESPACE)
+        .subsystem(TSKV_SUBSYSTEM),
+    )
+    .expect("tskv metric cannot be created")
+});
+
+pub static PAGE_CACHE_HITS: Lazy<IntCounter> = Lazy::new(|| {
+    IntCounter::with_opts(
+        Opts::new("page_cache_hits_total", "total num of page cache hits")
+            .namespace(SERVER_NAMESPACE)
+            .subsystem(TSKV_SUBSYSTEM),
+    )
+    .expect("tskv metric cannot be created")
+});
+
+pub static PAGE_CACHE_MISSES: Lazy<IntCounter> = Lazy::new(|| {
+    IntCounter::with_opts(
+        Opts::new("page_cache_misses_total", "total num of page cache misses")
+            .namespace(SERVER_NAMESPACE)
+            .subsystem(TSKV_SUBSYSTEM),
+    )
+    .expect("tskv metric cannot be created")
+});
+
+pub static PAGE_CACHE_HIT_RATIO: Lazy<Gauge> = Lazy::new(|| {
+    Gauge::with_opts(
+        Opts::new(
+            "page_cache_hit_ratio",
+            "ratio of page cache hits in all reads",
+        )
+        .namespace(SERVER_NAMESPACE)
+        .subsystem(TSKV_SUBSYSTEM),
+    )
+    .expect("tskv metric cannot be created")
+});
+
+pub static SERIES_GC_REMOVED: Lazy<IntCounter> = Lazy::new(|| {
+    IntCounter::with_opts(
+        Opts::new(
+            "series_gc_removed_total",
+            "total num of series removed from the index by the series gc",
+        )
+        .namespace(SERVER_NAMESPACE)
+        .subsystem(TSKV_SUBSYSTEM),
+    )
+    .expect("tskv metric cannot be created")
+});
+
 pub fn init_tskv_metrics_recorder() {
     REGISTRY
         .register(Box::new(COMPACTION_SUCCESS.clone()))
@@ -183,6 +429,45 @@ pub fn init_tskv_metrics_recorder() {
     REGISTRY
         .register(Box::new(COMPACTION_DURATION.clone()))
         .expect("tskv metrics collector cannot be registered");
+    REGISTRY
+        .register(Box::new(COMPACTION_BACKLOG.clone()))
+        .expect("tskv metrics collector cannot be registered");
+    REGISTRY
+        .register(Box::new(WRITE_POINTS.clone()))
+        .expect("tskv metrics collector cannot be registered");
+    REGISTRY
+        .register(Box::new(WRITE_BYTES.clone()))
+        .expect("tskv metrics collector cannot be registered");
+    REGISTRY
+        .register(Box::new(MEMCACHE_SIZE.clone()))
+        .expect("tskv metrics collector cannot be registered");
+    REGISTRY
+        .register(Box::new(FLUSH_PENDING_SIZE.clone()))
+        .expect("tskv metrics collector cannot be registered");
+    REGISTRY
+        .register(Box::new(WRITE_STALLS.clone()))
+        .expect("tskv metrics collector cannot be registered");
+    REGISTRY
+        .register(Box::new(WAL_SIZE.clone()))
+        .expect("tskv metrics collector cannot be registered");
+    REGISTRY
+        .register(Box::new(DISK_FREE_SPACE.clone()))
+        .expect("tskv metrics collector cannot be registered");
+    REGISTRY
+        .register(Box::new(READ_ONLY.clone()))
+        .expect("tskv metrics collector cannot be registered");
+    REGISTRY
+        .register(Box::new(PAGE_CACHE_HITS.clone()))
+        .expect("tskv metrics collector cannot be registered");
+    REGISTRY
+        .register(Box::new(PAGE_CACHE_MISSES.clone()))
+        .expect("tskv metrics collector cannot be registered");
+    REGISTRY
+        .register(Box::new(PAGE_CACHE_HIT_RATIO.clone()))
+        .expect("tskv metrics collector cannot be registered");
+    REGISTRY
+        .register(Box::new(SERIES_GC_REMOVED.clone()))
+        .expect("tskv metrics collector cannot be registered");
 }
 
 pub fn incr_compaction_success() {
@@ -199,6 +484,103 @@ pub fn sample_tskv_compaction_duration(db: &str, ts_family: &str, level: &str, d
         .observe(delta)
 }
 
+pub fn incr_compaction_backlog() {
+    COMPACTION_BACKLOG.inc();
+}
+
+pub fn decr_compaction_backlog() {
+    COMPACTION_BACKLOG.dec();
+}
+
+pub fn incr_write_points(db: &str, points: u64, bytes: u64) {
+    WRITE_POINTS.with_label_values(&[db]).inc_by(points);
+    WRITE_BYTES.with_label_values(&[db]).inc_by(bytes);
+}
+
+pub fn set_memcache_size(db: &str, bytes: u64) {
+    MEMCACHE_SIZE.with_label_values(&[db]).set(bytes as i64)
+}
+
+pub fn set_flush_pending_size(bytes: u64) {
+    FLUSH_PENDING_SIZE.set(bytes as i64)
+}
+
+pub fn incr_write_stalls() {
+    WRITE_STALLS.inc();
+}
+
+pub fn set_wal_size(bytes: u64) {
+    WAL_SIZE.set(bytes as i64)
+}
+
+pub fn set_disk_free_space(dir: &str, bytes: u64) {
+    DISK_FREE_SPACE.with_label_values(&[dir]).set(bytes as i64)
+}
+
+pub fn set_read_only(read_only: bool) {
+    READ_ONLY.set(read_only as i64)
+}
+
+pub fn incr_series_gc_removed(num: u64) {
+    SERIES_GC_REMOVED.inc_by(num)
+}
+
+/// Catches the page cache counters up with the counts of the cache
+pub fn set_page_cache_counts(hits: u64, misses: u64) {
+    PAGE_CACHE_HITS.inc_by(hits.saturating_sub(PAGE_CACHE_HITS.get()));
+    PAGE_CACHE_MISSES.inc_by(misses.saturating_sub(PAGE_CACHE_MISSES.get()));
+    if hits + misses > 0 {
+        PAGE_CACHE_HIT_RATIO.set(hits as f64 / (hits + misses) as f64);
+    }
+}
+
+pub static DC_REPLICATION_LAG: Lazy<GaugeVec> = Lazy::new(|| {
+    GaugeVec::new(
+        Opts::new(
+            "dc_replication_lag_seconds",
+            "seconds since the writes of the primary were last fully replicated",
+        )
+        .namespace(SERVER_NAMESPACE)
+        .subsystem(REPLICATION_SUBSYSTEM),
+        &["primary", "db"],
+    )
+    .expect("replication metric cannot be created")
+});
+
+pub static DC_REPLICATION_APPLIED: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "dc_replication_applied_total",
+            "total num of writes replicated from the primary",
+        )
+        .namespace(SERVER_NAMESPACE)
+        .subsystem(REPLICATION_SUBSYSTEM),
+        &["primary", "db"],
+    )
+    .expect("replication metric cannot be created")
+});
+
+pub fn init_replication_metrics_recorder() {
+    REGISTRY
+        .register(Box::new(DC_REPLICATION_LAG.clone()))
+        .expect("replication metrics collector cannot be registered");
+    REGISTRY
+        .register(Box::new(DC_REPLICATION_APPLIED.clone()))
+        .expect("replication metrics collector cannot be registered");
+}
+
+pub fn set_dc_replication_lag(primary: &str, db: &str, seconds: f64) {
+    DC_REPLICATION_LAG
+        .with_label_values(&[primary, db])
+        .set(seconds)
+}
+
+pub fn incr_dc_replication_applied(primary: &str, db: &str, num: u64) {
+    DC_REPLICATION_APPLIED
+        .with_label_values(&[primary, db])
+        .inc_by(num)
+}
+
 pub fn gather_metrics_as_prometheus_string() -> String {
     use prometheus::Encoder;
     let encoder = prometheus::TextEncoder::new();
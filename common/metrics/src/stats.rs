@@ -0,0 +1,127 @@
+//! Samples of the metrics registered, for `SHOW STATS`
+use prometheus::proto::{MetricFamily, MetricType};
+
+use crate::{QUERY_SUBSYSTEM, REGISTRY, REPLICATION_SUBSYSTEM, SERVER_NAMESPACE, TSKV_SUBSYSTEM};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatSample {
+    /// Subsystem of the metric, `server` if it is of none
+    pub module: String,
+    pub name: String,
+    /// `<label>=<value>` of the labels joined by commas
+    pub tags: String,
+    pub value: f64,
+}
+
+/// The samples of the metrics registered, a histogram or a summary gives the
+/// samples of its count and sum
+pub fn gather_stats() -> Vec<StatSample> {
+    samples(&REGISTRY.gather())
+}
+
+fn samples(families: &[MetricFamily]) -> Vec<StatSample> {
+    let mut samples = vec![];
+    for family in families {
+        let (module, name) = split_name(family.get_name());
+        for metric in family.get_metric() {
+            let tags = metric
+                .get_label()
+                .iter()
+                .map(|e| format!("{}={}", e.get_name(), e.get_value()))
+                .collect::<Vec<_>>()
+                .join(",");
+            let mut push = |name: String, value: f64| {
+                samples.push(StatSample {
+                    module: module.to_string(),
+                    name,
+                    tags: tags.clone(),
+                    value,
+                })
+            };
+            match family.get_field_type() {
+                MetricType::COUNTER => push(name.to_string(), metric.get_counter().get_value()),
+                MetricType::GAUGE => push(name.to_string(), metric.get_gauge().get_value()),
+                MetricType::UNTYPED => push(name.to_string(), metric.get_untyped().get_value()),
+                MetricType::HISTOGRAM => {
+                    let histogram = metric.get_histogram();
+                    push(
+                        format!("{}_count", name),
+                        histogram.get_sample_count() as f64,
+                    );
+                    push(format!("{}_sum", name), histogram.get_sample_sum());
+                }
+                MetricType::SUMMARY => {
+                    let summary = metric.get_summary();
+                    push(format!("{}_count", name), summary.get_sample_count() as f64);
+                    push(format!("{}_sum", name), summary.get_sample_sum());
+                }
+            }
+        }
+    }
+    samples
+}
+
+/// Splits `server_<subsystem>_<name>` into the subsystem and the name
+fn split_name(name: &str) -> (&str, &str) {
+    let name = name
+        .strip_prefix(SERVER_NAMESPACE)
+        .and_then(|e| e.strip_prefix('_'))
+        .unwrap_or(name);
+    for subsystem in [TSKV_SUBSYSTEM, QUERY_SUBSYSTEM, REPLICATION_SUBSYSTEM] {
+        if let Some(rest) = name
+            .strip_prefix(subsystem)
+            .and_then(|e| e.strip_prefix('_'))
+        {
+            return (subsystem, rest);
+        }
+    }
+    (SERVER_NAMESPACE, name)
+}
+
+#[cfg(test)]
+mod test {
+    use prometheus::{HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry};
+
+    use super::*;
+
+    #[test]
+    fn test_samples() {
+        let registry = Registry::new();
+        let writes = IntCounterVec::new(
+            Opts::new("write_points_total", "points")
+                .namespace(SERVER_NAMESPACE)
+                .subsystem(TSKV_SUBSYSTEM),
+            &["database"],
+        )
+        .unwrap();
+        let latency = HistogramVec::new(
+            HistogramOpts::new("query_read_milliseconds", "latency")
+                .namespace(SERVER_NAMESPACE)
+                .subsystem(QUERY_SUBSYSTEM),
+            &["database"],
+        )
+        .unwrap();
+        registry.register(Box::new(writes.clone())).unwrap();
+        registry.register(Box::new(latency.clone())).unwrap();
+        writes.with_label_values(&["db1"]).inc_by(3);
+        latency.with_label_values(&["db1"]).observe(2.0);
+        latency.with_label_values(&["db1"]).observe(4.0);
+
+        let samples = samples(&registry.gather());
+        let sample = |module: &str, name: &str, value: f64| StatSample {
+            module: module.to_string(),
+            name: name.to_string(),
+            tags: "database=db1".to_string(),
+            value,
+        };
+        assert_eq!(
+            samples,
+            vec![
+                sample("query", "query_read_milliseconds_count", 2.0),
+                sample("query", "query_read_milliseconds_sum", 6.0),
+                sample("tskv", "write_points_total", 3.0),
+            ]
+        );
+        assert_eq!(split_name("server_build_info"), ("server", "build_info"));
+    }
+}
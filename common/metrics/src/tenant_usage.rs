@@ -0,0 +1,134 @@
+//! Usage of the writes of each tenant on this node since started, served by
+//! `SELECT * FROM system.tenant_usage` to find the tenants starving the others.
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+use once_cell::sync::Lazy;
+
+use crate::{TENANT_STORED_BYTES, TENANT_WRITES_REJECTED};
+
+static TENANT_USAGE: Lazy<RwLock<HashMap<String, Arc<Counters>>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+#[derive(Debug, Default)]
+struct Counters {
+    points_written: AtomicU64,
+    bytes_written: AtomicU64,
+    writes_rate_limited: AtomicU64,
+    writes_over_quota: AtomicU64,
+    stored_bytes: AtomicU64,
+}
+
+/// Usage of a tenant
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TenantUsage {
+    pub points_written: u64,
+    pub bytes_written: u64,
+    /// Writes rejected by the rate limits of the tenant or its users
+    pub writes_rate_limited: u64,
+    /// Writes rejected as the data stored is over the quota
+    pub writes_over_quota: u64,
+    /// Bytes of the data stored by the tenant when measured last
+    pub stored_bytes: u64,
+}
+
+/// Why a write of a tenant is rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteRejection {
+    RateLimited,
+    OverQuota,
+}
+
+impl WriteRejection {
+    fn as_str(&self) -> &'static str {
+        match self {
+            WriteRejection::RateLimited => "rate_limited",
+            WriteRejection::OverQuota => "over_quota",
+        }
+    }
+}
+
+fn counters(tenant: &str) -> Arc<Counters> {
+    if let Some(counters) = TENANT_USAGE.read().unwrap().get(tenant) {
+        return counters.clone();
+    }
+    TENANT_USAGE
+        .write()
+        .unwrap()
+        .entry(tenant.to_string())
+        .or_default()
+        .clone()
+}
+
+pub fn record_tenant_write(tenant: &str, points: u64, bytes: u64) {
+    let counters = counters(tenant);
+    counters.points_written.fetch_add(points, Ordering::Relaxed);
+    counters.bytes_written.fetch_add(bytes, Ordering::Relaxed);
+}
+
+pub fn record_write_rejected(tenant: &str, rejection: WriteRejection) {
+    let counters = counters(tenant);
+    match rejection {
+        WriteRejection::RateLimited => &counters.writes_rate_limited,
+        WriteRejection::OverQuota => &counters.writes_over_quota,
+    }
+    .fetch_add(1, Ordering::Relaxed);
+    TENANT_WRITES_REJECTED
+        .with_label_values(&[tenant, rejection.as_str()])
+        .inc();
+}
+
+pub fn record_stored_bytes(tenant: &str, bytes: u64) {
+    counters(tenant)
+        .stored_bytes
+        .store(bytes, Ordering::Relaxed);
+    TENANT_STORED_BYTES
+        .with_label_values(&[tenant])
+        .set(bytes as i64);
+}
+
+/// Usage of the tenants written, by the name
+pub fn tenant_usage() -> BTreeMap<String, TenantUsage> {
+    TENANT_USAGE
+        .read()
+        .unwrap()
+        .iter()
+        .map(|(tenant, c)| {
+            let usage = TenantUsage {
+                points_written: c.points_written.load(Ordering::Relaxed),
+                bytes_written: c.bytes_written.load(Ordering::Relaxed),
+                writes_rate_limited: c.writes_rate_limited.load(Ordering::Relaxed),
+                writes_over_quota: c.writes_over_quota.load(Ordering::Relaxed),
+                stored_bytes: c.stored_bytes.load(Ordering::Relaxed),
+            };
+            (tenant.clone(), usage)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_tenant_usage() {
+        record_tenant_write("usage_tenant", 10, 1024);
+        record_tenant_write("usage_tenant", 5, 512);
+        record_write_rejected("usage_tenant", WriteRejection::RateLimited);
+        record_write_rejected("usage_tenant", WriteRejection::OverQuota);
+        record_stored_bytes("usage_tenant", 4096);
+
+        let usage = tenant_usage().remove("usage_tenant").unwrap();
+        assert_eq!(
+            usage,
+            TenantUsage {
+                points_written: 15,
+                bytes_written: 1536,
+                writes_rate_limited: 1,
+                writes_over_quota: 1,
+                stored_bytes: 4096,
+            }
+        );
+    }
+}
@@ -1,13 +1,17 @@
 use datafusion::arrow::{
     array::{
-        ArrayBuilder, BooleanBuilder, Float64Builder, Int64Builder, StringBuilder,
+        Array, ArrayBuilder, ArrayRef, BooleanBuilder, DictionaryArray, Float64Builder,
+        Int64Builder, StringArray, StringBuilder, StringDictionaryBuilder,
         TimestampMicrosecondBuilder, TimestampMillisecondBuilder, TimestampNanosecondBuilder,
         TimestampSecondBuilder, UInt64Builder,
     },
-    datatypes::{DataType, SchemaRef, TimeUnit},
+    compute::cast,
+    datatypes::{DataType, Field, Int32Type, Schema, SchemaRef, TimeUnit},
     error::ArrowError,
+    record_batch::RecordBatch,
 };
 use std::str;
+use std::sync::Arc;
 
 pub trait WriteArrow {
     fn write(self, builder: &mut Box<dyn ArrayBuilder>) -> Result<(), ArrowError>;
@@ -15,15 +19,27 @@ pub trait WriteArrow {
 
 impl WriteArrow for Vec<Option<Vec<u8>>> {
     fn write(self, builder: &mut Box<dyn ArrayBuilder>) -> Result<(), ArrowError> {
-        let builder = builder
-            .as_any_mut()
-            .downcast_mut::<StringBuilder>()
-            .ok_or_else(|| {
-                ArrowError::SchemaError(
-                    "Cast failed for ListBuilder<StringBuilder> during nested data parsing"
-                        .to_string(),
-                )
-            })?;
+        let builder = builder.as_any_mut();
+        if let Some(builder) = builder.downcast_mut::<StringDictionaryBuilder<Int32Type>>() {
+            for e in self {
+                match e {
+                    Some(ref e) => {
+                        builder.append(
+                            str::from_utf8(e)
+                                .map_err(|e| ArrowError::ExternalError(Box::new(e)))?,
+                        )?;
+                    }
+                    None => builder.append_null(),
+                }
+            }
+            return Ok(());
+        }
+
+        let builder = builder.downcast_mut::<StringBuilder>().ok_or_else(|| {
+            ArrowError::SchemaError(
+                "Cast failed for ListBuilder<StringBuilder> during nested data parsing".to_string(),
+            )
+        })?;
 
         for e in self {
             let val = if let Some(ref e) = e {
@@ -39,6 +55,59 @@ impl WriteArrow for Vec<Option<Vec<u8>>> {
     }
 }
 
+/// The string at the row of a string array or a dictionary array of the tags, None
+/// if it's null or the array is not of strings
+pub fn string_value(array: &ArrayRef, row: usize) -> Option<&str> {
+    if array.is_null(row) {
+        return None;
+    }
+    if let Some(array) = array.as_any().downcast_ref::<StringArray>() {
+        return Some(array.value(row));
+    }
+    let array = array
+        .as_any()
+        .downcast_ref::<DictionaryArray<Int32Type>>()?;
+    let values = array.values().as_any().downcast_ref::<StringArray>()?;
+    Some(values.value(array.keys().value(row) as usize))
+}
+
+/// Casts the dictionary columns to the types of the values of them, for the writers
+/// not supporting dictionaries, e.g. csv
+pub fn unpack_dictionaries(batch: &RecordBatch) -> Result<RecordBatch, ArrowError> {
+    let schema = batch.schema();
+    if !schema
+        .fields()
+        .iter()
+        .any(|e| matches!(e.data_type(), DataType::Dictionary(..)))
+    {
+        return Ok(batch.clone());
+    }
+
+    let mut fields = Vec::with_capacity(schema.fields().len());
+    let mut columns = Vec::with_capacity(schema.fields().len());
+    for (field, column) in schema.fields().iter().zip(batch.columns()) {
+        match field.data_type() {
+            DataType::Dictionary(_, value_type) => {
+                fields.push(
+                    Field::new(
+                        field.name(),
+                        value_type.as_ref().clone(),
+                        field.is_nullable(),
+                    )
+                    .with_metadata(field.metadata().cloned()),
+                );
+                columns.push(cast(column, value_type)?);
+            }
+            _ => {
+                fields.push(field.clone());
+                columns.push(column.clone());
+            }
+        }
+    }
+    let schema = Schema::new_with_metadata(fields, schema.metadata().clone());
+    RecordBatch::try_new(Arc::new(schema), columns)
+}
+
 /// Create array builder from schema
 pub fn build_arrow_array_builders(
     schema: SchemaRef,
@@ -61,6 +130,12 @@ pub fn build_arrow_array_builder(
             let values_builder = StringBuilder::with_capacity(batch_size, 128);
             Ok(Box::new(values_builder))
         }
+        DataType::Dictionary(key_type, value_type)
+            if **key_type == DataType::Int32 && **value_type == DataType::Utf8 =>
+        {
+            let values_builder = StringDictionaryBuilder::<Int32Type>::new();
+            Ok(Box::new(values_builder))
+        }
         DataType::UInt64 => {
             let values_builder = UInt64Builder::with_capacity(batch_size);
             Ok(Box::new(values_builder))
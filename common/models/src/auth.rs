@@ -0,0 +1,115 @@
+//! Passwords of the users are stored as salted hashes of PBKDF2-HMAC-SHA256, in the
+//! format `pbkdf2_sha256$<rounds>$<salt>$<hash>`, the salt and the hash are encoded
+//! in base64.
+//!
+//! API tokens are random, so they are stored as the unsalted SHA256 hashes, which
+//! are looked up as the token is presented.
+use hmac::Hmac;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+const ALGORITHM: &str = "pbkdf2_sha256";
+const ROUNDS: u32 = 10_000;
+const SALT_LEN: usize = 16;
+const HASH_LEN: usize = 32;
+
+/// Prefix of the API tokens, telling them apart from the JWTs
+pub const API_TOKEN_PREFIX: &str = "cnosdb_";
+const API_TOKEN_LEN: usize = 32;
+
+/// Hashes the password with a random salt
+pub fn hash_password(password: &str) -> String {
+    let mut salt = [0_u8; SALT_LEN];
+    rand::thread_rng().fill_bytes(&mut salt);
+    let hash = pbkdf2_sha256(password, &salt, ROUNDS);
+    format!(
+        "{}${}${}${}",
+        ALGORITHM,
+        ROUNDS,
+        base64::encode(salt),
+        base64::encode(hash)
+    )
+}
+
+/// Whether the password matches the hash, false if the hash is malformed
+pub fn verify_password(password: &str, password_hash: &str) -> bool {
+    let parts: Vec<&str> = password_hash.split('$').collect();
+    let (rounds, salt, hash) = match parts.as_slice() {
+        [ALGORITHM, rounds, salt, hash] => (rounds, salt, hash),
+        _ => return false,
+    };
+    let (rounds, salt, hash) = match (
+        rounds.parse::<u32>(),
+        base64::decode(salt),
+        base64::decode(hash),
+    ) {
+        (Ok(rounds), Ok(salt), Ok(hash)) => (rounds, salt, hash),
+        _ => return false,
+    };
+
+    let actual = pbkdf2_sha256(password, &salt, rounds);
+    // Compared in constant time, so the hash is not guessed by the time taken
+    actual.len() == hash.len()
+        && actual
+            .iter()
+            .zip(hash.iter())
+            .fold(0_u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+/// Value of the `authorization` header authenticating the user by the password
+pub fn basic_auth(user: &str, password: &str) -> String {
+    format!("Basic {}", base64::encode(format!("{}:{}", user, password)))
+}
+
+/// New API token, `cnosdb_` followed by random bytes encoded in url safe base64
+pub fn generate_api_token() -> String {
+    let mut token = [0_u8; API_TOKEN_LEN];
+    rand::thread_rng().fill_bytes(&mut token);
+    format!(
+        "{}{}",
+        API_TOKEN_PREFIX,
+        base64::encode_config(token, base64::URL_SAFE_NO_PAD)
+    )
+}
+
+pub fn hash_api_token(token: &str) -> String {
+    base64::encode(Sha256::digest(token.as_bytes()))
+}
+
+fn pbkdf2_sha256(password: &str, salt: &[u8], rounds: u32) -> [u8; HASH_LEN] {
+    let mut hash = [0_u8; HASH_LEN];
+    pbkdf2::pbkdf2::<Hmac<Sha256>>(password.as_bytes(), salt, rounds, &mut hash);
+    hash
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_password_hash() {
+        let hash = hash_password("secret");
+        assert!(hash.starts_with("pbkdf2_sha256$10000$"));
+        assert!(verify_password("secret", &hash));
+        assert!(!verify_password("Secret", &hash));
+        assert!(!verify_password("", &hash));
+
+        // Salted, the hashes of the same password differ
+        assert_ne!(hash, hash_password("secret"));
+        assert!(!verify_password("secret", "secret"));
+        assert!(!verify_password("secret", ""));
+    }
+
+    #[test]
+    fn test_api_token() {
+        let token = generate_api_token();
+        assert!(token.starts_with(API_TOKEN_PREFIX));
+        assert_ne!(token, generate_api_token());
+        assert_eq!(hash_api_token(&token), hash_api_token(&token));
+        assert_ne!(
+            hash_api_token(&token),
+            hash_api_token(&generate_api_token())
+        );
+    }
+}
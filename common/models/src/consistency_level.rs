@@ -1,4 +1,10 @@
-#[allow(dead_code)]
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// Number of the replicas of a shard required to acknowledge a write or read
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ConsistencyLevel {
     /// allows for hinted handoff, potentially no write happened yet.
     Any,
@@ -9,3 +15,80 @@ pub enum ConsistencyLevel {
     /// requires all data nodes to acknowledge a write or read.
     All,
 }
+
+impl ConsistencyLevel {
+    /// Acknowledgements required of the shard of `replicas` replicas
+    pub fn required(&self, replicas: usize) -> usize {
+        match self {
+            Self::Any => 0,
+            Self::One => 1.min(replicas),
+            Self::Quorum => replicas / 2 + 1,
+            Self::All => replicas,
+        }
+    }
+}
+
+impl Default for ConsistencyLevel {
+    fn default() -> Self {
+        Self::One
+    }
+}
+
+impl FromStr for ConsistencyLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "ANY" => Ok(Self::Any),
+            "ONE" => Ok(Self::One),
+            "QUORUM" => Ok(Self::Quorum),
+            "ALL" => Ok(Self::All),
+            _ => Err(format!(
+                "consistency level {} is not supported, expected one of ANY, ONE, QUORUM, ALL",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for ConsistencyLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Any => "ANY",
+            Self::One => "ONE",
+            Self::Quorum => "QUORUM",
+            Self::All => "ALL",
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_required() {
+        assert_eq!(ConsistencyLevel::Any.required(3), 0);
+        assert_eq!(ConsistencyLevel::One.required(3), 1);
+        assert_eq!(ConsistencyLevel::One.required(0), 0);
+        assert_eq!(ConsistencyLevel::Quorum.required(1), 1);
+        assert_eq!(ConsistencyLevel::Quorum.required(3), 2);
+        assert_eq!(ConsistencyLevel::Quorum.required(4), 3);
+        assert_eq!(ConsistencyLevel::All.required(3), 3);
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            "quorum".parse::<ConsistencyLevel>(),
+            Ok(ConsistencyLevel::Quorum)
+        );
+        assert_eq!(
+            ConsistencyLevel::All
+                .to_string()
+                .parse::<ConsistencyLevel>(),
+            Ok(ConsistencyLevel::All)
+        );
+        assert!("two".parse::<ConsistencyLevel>().is_err());
+    }
+}
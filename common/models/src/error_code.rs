@@ -46,9 +46,12 @@ macro_rules! define_error_codes {
 //
 // MM：Indicates the module number. 2 bit integer. Number values are as follows:
 //
-//     00: Indicates an unknown module, meaningless, and serves as a placeholder
+//     00: Indicates an unknown module, meaningless, and serves as a placeholder,
+//         or the errors common to the modules, such as the authentication
 //     01: query engine
 //     02: tskv engine
+//     03: meta data
+//     04: coordinator
 //
 // CCCC：Indicates the error code, a 4-digit integer.
 //
@@ -61,10 +64,107 @@ define_error_codes! {
     /// The sql state code needs to be developed later
     /// and is currently used as a placeholder
     (Unknown, b"0000000");
+    /// Invalid parameters or headers of a request
+    (InvalidParameter, b"0000011");
+    /// Missing or wrong credentials
+    (AuthFailed, b"0000021");
+    /// The user is not allowed to do the operation
+    (PermissionDenied, b"0000031");
+    /// Too many requests, retry after a while
+    (RateLimited, b"0000041");
+    /// The tenant stores more data than its quota
+    (QuotaExceeded, b"0000051");
+    /// The connections of the server or of the user are at the limit
+    (TooManyConnections, b"0000061");
+
     /// The sql state code needs to be developed later
     /// and is currently used as a placeholder
     (QueryUnknown, b"0100000");
+    /// The sql is not valid
+    (SqlParse, b"0100011");
+    /// The sql is valid but not meaningful, such as a column not of the table
+    (Semantic, b"0100021");
+    /// The sql is valid but not supported yet
+    (NotImplemented, b"0100031");
+    /// Multiple statements in a request which only accepts one
+    (MultiStatement, b"0100041");
+    /// Too many concurrent queries
+    (QueryLimitExceeded, b"0100051");
+    /// The query is canceled
+    (QueryCanceled, b"0100061");
+    /// The query fails during the execution
+    (QueryExecution, b"0100073");
+    /// A bug of the query engine
+    (QueryInternal, b"0100089");
+
     /// The sql state code needs to be developed later
     /// and is currently used as a placeholder
     (TskvUnknown, b"0200000");
+    /// The points written are not valid
+    (InvalidPoint, b"0200011");
+    /// The database is not found by the storage
+    (TskvDatabaseNotFound, b"0200021");
+    /// The table or the field is not found by the storage
+    (TskvTableNotFound, b"0200031");
+    /// The writes are stalled for the flushes falling behind, retry after a while
+    (WriteStalled, b"0200043");
+    /// Failed to read or write the files
+    (TskvIo, b"0200057");
+    /// The disk is full
+    (DiskFull, b"0200068");
+    /// The data files are corrupted
+    (TskvCorrupted, b"0200079");
+
+    /// Unknown error of the meta data
+    (MetaUnknown, b"0300000");
+    /// The database is not found
+    (DatabaseNotFound, b"0300011");
+    /// The database already exists
+    (DatabaseAlreadyExists, b"0300021");
+    /// The table is not found
+    (TableNotFound, b"0300031");
+    /// The table already exists
+    (TableAlreadyExists, b"0300041");
+    /// The table is an external table, not stored by tskv
+    (TableNotTskv, b"0300051");
+    /// A user, role, token, policy, stream source or asynchronous query is not found
+    (ObjectNotFound, b"0300061");
+    /// A user, role, token, policy or stream source already exists
+    (ObjectAlreadyExists, b"0300071");
+    /// The schema of a table or stream source is not valid
+    (InvalidSchema, b"0300081");
+    /// The meta data is changed concurrently by another node, retry the change
+    (MetaConflict, b"0300093");
+    /// Failed to store or replicate the meta data
+    (MetaInternal, b"0300097");
+
+    /// Unknown error of the coordinator
+    (CoordinatorUnknown, b"0400000");
+    /// A data node is unreachable, retry after a while
+    (NodeUnavailable, b"0400015");
+    /// Fewer replicas than the consistency level required are written
+    (NotEnoughReplicas, b"0400025");
+    /// A rebalance of the cluster is already running
+    (RebalanceInProgress, b"0400031");
+    /// The cluster is a standby of another cluster, the writes of the clients are
+    /// rejected until it is promoted
+    (Standby, b"0400041");
+}
+
+/// Key of the metadata of the grpc responses carrying the [`ErrorCode`] of an error
+pub const GRPC_ERROR_CODE_KEY: &str = "x-cnosdb-error-code";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_code() {
+        assert_eq!(ErrorCode::Semantic.as_str(), "0100021");
+        assert_eq!(
+            ErrorCode::from_bytes(b"0300031"),
+            Some(ErrorCode::TableNotFound)
+        );
+        assert_eq!(ErrorCode::from_bytes(b"9999999"), None);
+    }
 }
@@ -1,12 +1,16 @@
+pub mod auth;
 pub mod codec;
-mod consistency_level;
+pub mod consistency_level;
 mod errors;
 mod field_info;
+pub mod meta_data;
 mod node_info;
 mod points;
 pub mod schema;
 mod series_info;
+pub mod stream_source;
 pub mod tag;
+pub mod task;
 pub mod utils;
 #[macro_use]
 pub mod error_code;
@@ -0,0 +1,262 @@
+//! Metadata of a cluster which is replicated by the meta service.
+//!
+//! - tenants
+//!     - databases
+//!         - tables
+//!         - buckets: time range of data
+//!             - replication sets: a shard of the bucket
+//!                 - vnodes: a replica of the shard located on a data node
+//! - users
+//! - data nodes
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::schema::{DatabaseSchema, TableSchema};
+use crate::Timestamp;
+
+pub type NodeId = u64;
+pub type VnodeId = u32;
+pub type ReplicationSetId = u32;
+pub type BucketId = u32;
+
+pub const DEFAULT_TENANT: &str = "cnosdb";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct NodeInfo {
+    pub id: NodeId,
+    /// Address of the rpc service, e.g. `127.0.0.1:31006`
+    pub grpc_addr: String,
+    /// Address of the http service, e.g. `127.0.0.1:31007`
+    pub http_addr: String,
+    pub status: NodeStatus,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeStatus {
+    Healthy,
+    Unreachable,
+    /// Being removed from the cluster, no new vnode is placed on it and its vnodes
+    /// are moved to the other nodes
+    Decommissioning,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct UserInfo {
+    pub name: String,
+    pub is_admin: bool,
+    /// Salted hash of the password, see [`crate::auth::hash_password`]
+    #[serde(default)]
+    pub password_hash: String,
+    /// API tokens the user is also authenticated by
+    #[serde(default)]
+    pub tokens: Vec<ApiToken>,
+    /// Names of the roles granted to the user
+    #[serde(default)]
+    pub roles: Vec<String>,
+    /// External identity provider the user is authenticated by, such as `ldap`
+    /// or `oidc`, the roles are those mapped from the groups of the user then
+    #[serde(default)]
+    pub provider: Option<String>,
+}
+
+impl UserInfo {
+    pub fn token(&self, name: &str) -> Option<&ApiToken> {
+        self.tokens.iter().find(|e| e.name == name)
+    }
+}
+
+/// The users granted the role are restricted by the policies of it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RoleInfo {
+    pub name: String,
+    #[serde(default)]
+    pub policies: Vec<RowPolicy>,
+    #[serde(default)]
+    pub column_privileges: Vec<ColumnPrivilege>,
+    /// Groups of the external identity providers mapped to the role
+    #[serde(default)]
+    pub groups: Vec<String>,
+}
+
+/// Rows of the table readable by the role, those the predicate is true for
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RowPolicy {
+    pub name: String,
+    pub tenant: String,
+    pub database: String,
+    pub table: String,
+    /// SQL expression on the columns of the table
+    pub predicate: String,
+}
+
+impl RowPolicy {
+    pub fn is_on(&self, tenant: &str, database: &str, table: &str) -> bool {
+        self.tenant == tenant && self.database == database && self.table == table
+    }
+}
+
+/// Columns of the table readable by the role, the others of the table are not
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ColumnPrivilege {
+    pub tenant: String,
+    pub database: String,
+    pub table: String,
+    pub columns: Vec<String>,
+}
+
+impl ColumnPrivilege {
+    pub fn is_on(&self, tenant: &str, database: &str, table: &str) -> bool {
+        self.tenant == tenant && self.database == database && self.table == table
+    }
+}
+
+/// Only the hash of the token is stored, the token is shown once as it is created
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ApiToken {
+    pub name: String,
+    /// See [`crate::auth::hash_api_token`]
+    pub token_hash: String,
+    /// Unix timestamp in milliseconds
+    pub created_at: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VnodeInfo {
+    pub id: VnodeId,
+    pub node_id: NodeId,
+}
+
+/// Replicas of a shard
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReplicationSet {
+    pub id: ReplicationSetId,
+    pub vnodes: Vec<VnodeInfo>,
+}
+
+/// Data of a database in the time range `[start_time, end_time)`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BucketInfo {
+    pub id: BucketId,
+    pub start_time: Timestamp,
+    pub end_time: Timestamp,
+    pub shard_group: Vec<ReplicationSet>,
+}
+
+impl BucketInfo {
+    pub fn contains(&self, ts: Timestamp) -> bool {
+        self.start_time <= ts && ts < self.end_time
+    }
+
+    /// The shard owning the series of the hash, `None` if the bucket has no shards
+    pub fn vnode_for(&self, hash: u64) -> Option<&ReplicationSet> {
+        if self.shard_group.is_empty() {
+            return None;
+        }
+        Some(&self.shard_group[(hash % self.shard_group.len() as u64) as usize])
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DatabaseInfo {
+    pub schema: DatabaseSchema,
+    pub tables: HashMap<String, TableSchema>,
+    /// Sorted by start time
+    pub buckets: Vec<BucketInfo>,
+}
+
+impl DatabaseInfo {
+    pub fn new(schema: DatabaseSchema) -> Self {
+        Self {
+            schema,
+            tables: HashMap::new(),
+            buckets: vec![],
+        }
+    }
+
+    pub fn bucket_by_timestamp(&self, ts: Timestamp) -> Option<&BucketInfo> {
+        self.buckets.iter().find(|e| e.contains(ts))
+    }
+
+    /// Buckets overlapping the time range `[min_ts, max_ts]`
+    pub fn buckets_by_time_range(&self, min_ts: Timestamp, max_ts: Timestamp) -> Vec<&BucketInfo> {
+        self.buckets
+            .iter()
+            .filter(|e| e.start_time <= max_ts && min_ts < e.end_time)
+            .collect()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct TenantMetaData {
+    /// Increased by each change of the tenant
+    pub version: u64,
+    pub dbs: HashMap<String, DatabaseInfo>,
+}
+
+impl TenantMetaData {
+    pub fn database(&self, db: &str) -> Option<&DatabaseInfo> {
+        self.dbs.get(db)
+    }
+
+    pub fn table(&self, db: &str, table: &str) -> Option<&TableSchema> {
+        self.dbs.get(db).and_then(|e| e.tables.get(table))
+    }
+
+    pub fn database_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.dbs.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    pub fn table_names(&self, db: &str) -> Option<Vec<String>> {
+        self.dbs.get(db).map(|e| {
+            let mut names: Vec<String> = e.tables.keys().cloned().collect();
+            names.sort();
+            names
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bucket(id: BucketId, start_time: Timestamp, end_time: Timestamp) -> BucketInfo {
+        BucketInfo {
+            id,
+            start_time,
+            end_time,
+            shard_group: vec![
+                ReplicationSet {
+                    id: 1,
+                    vnodes: vec![VnodeInfo { id: 2, node_id: 1 }],
+                },
+                ReplicationSet {
+                    id: 3,
+                    vnodes: vec![VnodeInfo { id: 4, node_id: 2 }],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_locate_bucket() {
+        let mut db = DatabaseInfo::new(DatabaseSchema::new("db"));
+        db.buckets.push(bucket(1, 0, 100));
+        db.buckets.push(bucket(2, 100, 200));
+
+        assert_eq!(db.bucket_by_timestamp(100).unwrap().id, 2);
+        assert!(db.bucket_by_timestamp(200).is_none());
+        assert_eq!(db.buckets_by_time_range(50, 100).len(), 2);
+        assert_eq!(db.buckets_by_time_range(100, 300).len(), 1);
+
+        let bucket = db.bucket_by_timestamp(0).unwrap();
+        assert_eq!(bucket.vnode_for(3).unwrap().id, 3);
+        assert_eq!(bucket.vnode_for(4).unwrap().id, 1);
+
+        let mut empty = bucket.clone();
+        empty.shard_group.clear();
+        assert!(empty.vnode_for(3).is_none());
+    }
+}
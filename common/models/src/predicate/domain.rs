@@ -410,6 +410,7 @@ impl ValueEntry {
 pub fn utf8_from(val: &ScalarValue) -> Option<&str> {
     match &val {
         ScalarValue::Utf8(v) => v.as_deref(),
+        ScalarValue::Dictionary(_, v) => utf8_from(v),
         _ => None,
     }
 }
@@ -44,15 +44,27 @@ impl NormalizedSimpleComparison {
         }
 
         match (left, op, right) {
-            (Expr::Column(column), _, Expr::Literal(value)) => Some(Self { column, op, value }),
+            (Expr::Column(column), _, Expr::Literal(value)) => Some(Self {
+                column,
+                op,
+                value: Self::unpack_dictionary(value),
+            }),
             (Expr::Literal(value), _, Expr::Column(column)) => Some(Self {
                 column,
                 op: Self::reverse_op(op),
-                value,
+                value: Self::unpack_dictionary(value),
             }),
             (_, _, _) => None,
         }
     }
+    /// The literals compared with the tags are dictionaries like the tags, the domains
+    /// hold the values of them
+    fn unpack_dictionary(value: ScalarValue) -> ScalarValue {
+        match value {
+            ScalarValue::Dictionary(_, value) => *value,
+            value => value,
+        }
+    }
     /// Determine if a data type is sortable
     fn is_orderable(&self) -> bool {
         match self.value {
@@ -1311,6 +1323,13 @@ mod tests {
             assert_eq!(nsc.column, Column::from_name(c1));
             assert_eq!(nsc.op, Operator::Lt);
             assert_eq!(nsc.value, ScalarValue::Int32(Some(val)));
+
+            let tag = ScalarValue::Dictionary(
+                Box::new(DataType::Int32),
+                Box::new(ScalarValue::Utf8(Some("host1".to_string()))),
+            );
+            let nsc = NormalizedSimpleComparison::of(col(c1), Operator::Eq, lit(tag)).unwrap();
+            assert_eq!(nsc.value, ScalarValue::Utf8(Some("host1".to_string())));
         }
 
         #[test]
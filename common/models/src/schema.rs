@@ -26,7 +26,9 @@ use datafusion::datasource::file_format::file_type::{FileCompressionType, FileTy
 use datafusion::datasource::file_format::json::JsonFormat;
 use datafusion::datasource::file_format::parquet::ParquetFormat;
 use datafusion::datasource::file_format::FileFormat;
-use datafusion::datasource::listing::ListingOptions;
+use datafusion::datasource::listing::{
+    ListingOptions, ListingTable, ListingTableConfig, ListingTableUrl,
+};
 use datafusion::error::{DataFusionError, Result as DataFusionResult};
 
 use crate::codec::Encoding;
@@ -36,6 +38,14 @@ pub type TableSchemaRef = Arc<TskvTableSchema>;
 
 pub const TIME_FIELD_NAME: &str = "time";
 
+/// Compression of external files which is not supported by datafusion
+pub const ZSTD: &str = "ZSTD";
+pub const ZSTD_EXTENSION: &str = ".zst";
+
+/// Number of records sampled from each CSV or JSON file to infer the schema
+/// of an external table created without columns
+pub const SCHEMA_INFER_MAX_RECORDS: usize = 1000;
+
 pub const FIELD_ID: &str = "_field_id";
 pub const TAG: &str = "_tag";
 pub const TIME_FIELD: &str = "time";
@@ -81,35 +91,107 @@ impl ExternalTableSchema {
         let file_format: Arc<dyn FileFormat> = match FileType::from_str(&self.file_type)? {
             FileType::CSV => Arc::new(
                 CsvFormat::default()
+                    .with_schema_infer_max_rec(Some(SCHEMA_INFER_MAX_RECORDS))
                     .with_has_header(self.has_header)
                     .with_delimiter(self.delimiter)
-                    .with_file_compression_type(
-                        FileCompressionType::from_str(&self.file_compression_type).map_err(
-                            |_| {
-                                DataFusionError::Execution(
-                                    "Only known FileCompressionTypes can be ListingTables!"
-                                        .to_string(),
-                                )
-                            },
-                        )?,
-                    ),
+                    .with_file_compression_type(self.compression_type()?),
             ),
             FileType::PARQUET => Arc::new(ParquetFormat::default()),
             FileType::AVRO => Arc::new(AvroFormat::default()),
-            FileType::JSON => Arc::new(JsonFormat::default().with_file_compression_type(
-                FileCompressionType::from_str(&self.file_compression_type)?,
-            )),
+            FileType::JSON => Arc::new(
+                JsonFormat::default()
+                    .with_schema_infer_max_rec(Some(SCHEMA_INFER_MAX_RECORDS))
+                    .with_file_compression_type(self.compression_type()?),
+            ),
         };
 
         Ok(ListingOptions {
             format: file_format,
             collect_stat: false,
-            file_extension: FileType::from_str(&self.file_type)?
-                .get_ext_with_compression(self.file_compression_type.to_owned().parse()?)?,
+            file_extension: self.file_extension()?,
             target_partitions: self.target_partitions,
             table_partition_cols: self.table_partition_cols.clone(),
         })
     }
+
+    /// The table reading the files under the location, the partition columns
+    /// are appended to the schema of the files.
+    pub fn table_provider(&self) -> DataFusionResult<ListingTable> {
+        let table_path = ListingTableUrl::parse(&self.location)?;
+        let config = ListingTableConfig::new(table_path)
+            .with_listing_options(self.table_options()?)
+            .with_schema(Arc::new(self.schema.clone()));
+        ListingTable::try_new(config)
+    }
+
+    /// Extension of the files listed under the location.
+    ///
+    /// A location naming a single file is read whatever its extension is,
+    /// e.g. `data.ndjson` or `data.jsonl` stored as NDJSON.
+    pub fn file_extension(&self) -> DataFusionResult<String> {
+        let file_type = FileType::from_str(&self.file_type).map_err(|_| {
+            DataFusionError::Execution("Only known FileTypes can be ListingTables!".to_string())
+        })?;
+        let mut extension = file_type.get_ext_with_compression(self.compression_type()?)?;
+        if self.is_zstd() {
+            if matches!(file_type, FileType::PARQUET | FileType::AVRO) {
+                return Err(DataFusionError::Execution(format!(
+                    "FileCompressionType can not be {} for {}",
+                    ZSTD, self.file_type
+                )));
+            }
+            extension.push_str(ZSTD_EXTENSION);
+        }
+
+        if is_single_file(&self.location) {
+            return Ok(String::new());
+        }
+        Ok(extension)
+    }
+
+    /// Compression of the files which is decompressed by the file format.
+    ///
+    /// Zstd files are decompressed by the object store, so they are read as uncompressed.
+    fn compression_type(&self) -> DataFusionResult<FileCompressionType> {
+        if self.is_zstd() {
+            return Ok(FileCompressionType::UNCOMPRESSED);
+        }
+        FileCompressionType::from_str(&self.file_compression_type).map_err(|_| {
+            DataFusionError::Execution(
+                "Only known FileCompressionTypes can be ListingTables!".to_string(),
+            )
+        })
+    }
+
+    fn is_zstd(&self) -> bool {
+        self.file_compression_type.eq_ignore_ascii_case(ZSTD)
+    }
+
+    /// Detects the compression of a single file location by its extension
+    pub fn compression_from_extension(location: &str) -> Option<&'static str> {
+        if !is_single_file(location) {
+            return None;
+        }
+
+        let location = location.to_ascii_lowercase();
+        if location.ends_with(".gz") {
+            Some("GZIP")
+        } else if location.ends_with(".bz2") {
+            Some("BZIP2")
+        } else if location.ends_with(ZSTD_EXTENSION) {
+            Some(ZSTD)
+        } else {
+            None
+        }
+    }
+}
+
+fn is_single_file(location: &str) -> bool {
+    !location.ends_with('/')
+        && location
+            .rsplit('/')
+            .next()
+            .map_or(false, |e| e.contains('.'))
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -122,6 +204,22 @@ pub struct TskvTableSchema {
     columns: Vec<TableColumn>,
     //ColumnName -> ColumnsIndex
     columns_index: HashMap<String, usize>,
+    /// Collected by `ANALYZE TABLE`, absent if never analyzed
+    #[serde(default)]
+    statistics: Option<TableStatistics>,
+}
+
+/// Statistics of a tskv table for the optimizer, which are estimates as the table
+/// keeps changing after analyzed
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct TableStatistics {
+    /// Nanoseconds since the epoch when analyzed
+    pub analyzed_at: i64,
+    pub row_count: u64,
+    pub min_time: Option<i64>,
+    pub max_time: Option<i64>,
+    /// Number of distinct values of each tag
+    pub tag_ndvs: BTreeMap<String, u64>,
 }
 
 impl Default for TskvTableSchema {
@@ -133,6 +231,7 @@ impl Default for TskvTableSchema {
             next_column_id: 0,
             columns: Default::default(),
             columns_index: Default::default(),
+            statistics: None,
         }
     }
 }
@@ -158,6 +257,7 @@ impl TskvTableSchema {
             next_column_id: columns.len() as ColumnId,
             columns,
             columns_index,
+            statistics: None,
         }
     }
 
@@ -283,6 +383,14 @@ impl TskvTableSchema {
     pub fn contains_column(&self, column_name: &str) -> bool {
         self.columns_index.contains_key(column_name)
     }
+
+    pub fn statistics(&self) -> Option<&TableStatistics> {
+        self.statistics.as_ref()
+    }
+
+    pub fn set_statistics(&mut self, statistics: TableStatistics) {
+        self.statistics = Some(statistics);
+    }
 }
 
 pub fn is_time_column(field: &ArrowField) -> bool {
@@ -356,10 +464,12 @@ impl TableColumn {
     }
 }
 
+/// The tags are dictionary encoded from the scans to the results of the queries, the
+/// values of a tag repeat in the rows of a series
 impl From<ColumnType> for ArrowDataType {
     fn from(t: ColumnType) -> Self {
         match t {
-            ColumnType::Tag => Self::Utf8,
+            ColumnType::Tag => Self::Dictionary(Box::new(Self::Int32), Box::new(Self::Utf8)),
             ColumnType::Time => Self::Timestamp(TimeUnit::Nanosecond, None),
             ColumnType::Field(ValueType::Float) => Self::Float64,
             ColumnType::Field(ValueType::Integer) => Self::Int64,
@@ -376,10 +486,19 @@ impl TryFrom<ArrowDataType> for ColumnType {
 
     fn try_from(value: ArrowDataType) -> Result<Self, Self::Error> {
         match value {
-            ArrowDataType::Float64 => Ok(Self::Field(ValueType::Float)),
-            ArrowDataType::Int64 => Ok(Self::Field(ValueType::Integer)),
-            ArrowDataType::UInt64 => Ok(Self::Field(ValueType::Unsigned)),
-            ArrowDataType::Utf8 => Ok(Self::Field(ValueType::String)),
+            // The narrower numbers are widened to the 64 bits types
+            ArrowDataType::Float16 | ArrowDataType::Float32 | ArrowDataType::Float64 => {
+                Ok(Self::Field(ValueType::Float))
+            }
+            ArrowDataType::Int8
+            | ArrowDataType::Int16
+            | ArrowDataType::Int32
+            | ArrowDataType::Int64 => Ok(Self::Field(ValueType::Integer)),
+            ArrowDataType::UInt8
+            | ArrowDataType::UInt16
+            | ArrowDataType::UInt32
+            | ArrowDataType::UInt64 => Ok(Self::Field(ValueType::Unsigned)),
+            ArrowDataType::Utf8 | ArrowDataType::LargeUtf8 => Ok(Self::Field(ValueType::String)),
             ArrowDataType::Boolean => Ok(Self::Field(ValueType::Boolean)),
             _ => Err("Error field type not supported"),
         }
@@ -473,6 +592,23 @@ impl ColumnType {
     pub fn is_field(&self) -> bool {
         matches!(self, ColumnType::Field(_))
     }
+
+    /// Whether the values of the column can be compressed by the encoding, the
+    /// tags are not compressed
+    pub fn supports_encoding(&self, encoding: &Encoding) -> bool {
+        match self {
+            ColumnType::Tag => *encoding == Encoding::Default,
+            ColumnType::Time => encoding.is_timestamp_encoding(),
+            ColumnType::Field(value_type) => match value_type {
+                ValueType::Float => encoding.is_double_encoding(),
+                ValueType::Integer => encoding.is_bigint_encoding(),
+                ValueType::Unsigned => encoding.is_unsigned_encoding(),
+                ValueType::Boolean => encoding.is_bool_encoding(),
+                ValueType::String => encoding.is_string_encoding(),
+                ValueType::Unknown => false,
+            },
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
@@ -502,6 +638,21 @@ pub struct DatabaseOptions {
     replica: Option<u64>,
     // timestamp percision
     precision: Option<Precision>,
+    // create the tables and the columns written not exist
+    #[serde(default)]
+    auto_create_schema: Option<bool>,
+    // write of the fields the types of which differ from the columns
+    #[serde(default)]
+    field_type_conflict: Option<FieldTypeConflict>,
+    // timestamps written later than now by more are out of the window
+    #[serde(default)]
+    future_tolerance: Option<Duration>,
+    // timestamps written earlier than now by more are out of the window
+    #[serde(default)]
+    past_tolerance: Option<Duration>,
+    // write of the points the timestamps of which are out of the window
+    #[serde(default)]
+    out_of_window: Option<OutOfWindow>,
 }
 
 impl DatabaseOptions {
@@ -516,6 +667,11 @@ impl DatabaseOptions {
         unit: DurationUnit::Day,
     };
     pub const DEFAULT_PRECISION: Precision = Precision::NS;
+    pub const DEFAULT_AUTO_CREATE_SCHEMA: bool = true;
+    pub const DEFAULT_FIELD_TYPE_CONFLICT: FieldTypeConflict = FieldTypeConflict::Reject;
+    pub const DEFAULT_OUT_OF_WINDOW: OutOfWindow = OutOfWindow::Reject;
+    pub const MAX_SHARD_NUM: u64 = 1024;
+    pub const MAX_REPLICA: u64 = 16;
 
     pub fn ttl(&self) -> &Option<Duration> {
         &self.ttl
@@ -561,6 +717,75 @@ impl DatabaseOptions {
             .unwrap_or(&DatabaseOptions::DEFAULT_PRECISION)
     }
 
+    pub fn auto_create_schema(&self) -> &Option<bool> {
+        &self.auto_create_schema
+    }
+
+    pub fn auto_create_schema_or_default(&self) -> bool {
+        self.auto_create_schema
+            .unwrap_or(DatabaseOptions::DEFAULT_AUTO_CREATE_SCHEMA)
+    }
+
+    pub fn field_type_conflict(&self) -> &Option<FieldTypeConflict> {
+        &self.field_type_conflict
+    }
+
+    pub fn field_type_conflict_or_default(&self) -> FieldTypeConflict {
+        self.field_type_conflict
+            .unwrap_or(DatabaseOptions::DEFAULT_FIELD_TYPE_CONFLICT)
+    }
+
+    pub fn future_tolerance(&self) -> &Option<Duration> {
+        &self.future_tolerance
+    }
+
+    pub fn past_tolerance(&self) -> &Option<Duration> {
+        &self.past_tolerance
+    }
+
+    pub fn out_of_window(&self) -> &Option<OutOfWindow> {
+        &self.out_of_window
+    }
+
+    pub fn out_of_window_or_default(&self) -> OutOfWindow {
+        self.out_of_window
+            .unwrap_or(DatabaseOptions::DEFAULT_OUT_OF_WINDOW)
+    }
+
+    /// Checks the options are in range with the defaults of those not set, the ttl
+    /// must not be shorter than the duration of a shard set
+    pub fn validate(&self) -> Result<(), String> {
+        let shard_num = self.shard_num_or_default();
+        if !(1..=Self::MAX_SHARD_NUM).contains(&shard_num) {
+            return Err(format!(
+                "SHARD {} is out of range, use 1 to {}",
+                shard_num,
+                Self::MAX_SHARD_NUM
+            ));
+        }
+        let replica = self.replica_or_default();
+        if !(1..=Self::MAX_REPLICA).contains(&replica) {
+            return Err(format!(
+                "REPLICA {} is out of range, use 1 to {}",
+                replica,
+                Self::MAX_REPLICA
+            ));
+        }
+        let ttl = self.ttl_or_default();
+        let vnode_duration = self.vnode_duration_or_default();
+        if ttl.time_num == 0 || vnode_duration.time_num == 0 {
+            return Err("TTL and SHARD DURATION must be greater than 0".to_string());
+        }
+        // A ttl set alone keeps the default duration of the shards
+        if self.vnode_duration.is_some() && ttl.to_nanoseconds() < vnode_duration.to_nanoseconds() {
+            return Err(format!(
+                "TTL {} is shorter than SHARD DURATION {}",
+                ttl, vnode_duration
+            ));
+        }
+        Ok(())
+    }
+
     pub fn with_ttl(&mut self, ttl: Duration) {
         self.ttl = Some(ttl);
     }
@@ -580,6 +805,26 @@ impl DatabaseOptions {
     pub fn with_precision(&mut self, precision: Precision) {
         self.precision = Some(precision)
     }
+
+    pub fn with_auto_create_schema(&mut self, auto_create_schema: bool) {
+        self.auto_create_schema = Some(auto_create_schema)
+    }
+
+    pub fn with_field_type_conflict(&mut self, field_type_conflict: FieldTypeConflict) {
+        self.field_type_conflict = Some(field_type_conflict)
+    }
+
+    pub fn with_future_tolerance(&mut self, future_tolerance: Duration) {
+        self.future_tolerance = Some(future_tolerance)
+    }
+
+    pub fn with_past_tolerance(&mut self, past_tolerance: Duration) {
+        self.past_tolerance = Some(past_tolerance)
+    }
+
+    pub fn with_out_of_window(&mut self, out_of_window: OutOfWindow) {
+        self.out_of_window = Some(out_of_window)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
@@ -610,6 +855,67 @@ impl fmt::Display for Precision {
     }
 }
 
+/// What is written of a point, a field of which has a type different from the column
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FieldTypeConflict {
+    /// The point is not written
+    Reject,
+    /// The value is converted to the type of the column, the point is not written
+    /// if the value changes by the conversion
+    Coerce,
+    /// The value is written to the column named by the field and its type
+    Suffix,
+}
+
+impl FieldTypeConflict {
+    pub fn new(text: &str) -> Option<Self> {
+        match text.to_uppercase().as_str() {
+            "REJECT" => Some(FieldTypeConflict::Reject),
+            "COERCE" => Some(FieldTypeConflict::Coerce),
+            "SUFFIX" => Some(FieldTypeConflict::Suffix),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for FieldTypeConflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FieldTypeConflict::Reject => f.write_str("REJECT"),
+            FieldTypeConflict::Coerce => f.write_str("COERCE"),
+            FieldTypeConflict::Suffix => f.write_str("SUFFIX"),
+        }
+    }
+}
+
+/// What is written of a point, the timestamp of which is out of the window allowed
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OutOfWindow {
+    /// The point is not written
+    Reject,
+    /// The timestamp is changed to the nearest bound of the window
+    Clamp,
+}
+
+impl OutOfWindow {
+    pub fn new(text: &str) -> Option<Self> {
+        match text.to_uppercase().as_str() {
+            "REJECT" => Some(OutOfWindow::Reject),
+            "CLAMP" => Some(OutOfWindow::Clamp),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for OutOfWindow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OutOfWindow::Reject => f.write_str("REJECT"),
+            OutOfWindow::Clamp => f.write_str("CLAMP"),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub enum DurationUnit {
     Minutes,
@@ -634,6 +940,17 @@ impl fmt::Display for Duration {
 }
 
 impl Duration {
+    pub fn to_nanoseconds(&self) -> i64 {
+        let secs = match self.unit {
+            DurationUnit::Minutes => 60,
+            DurationUnit::Hour => 60 * 60,
+            DurationUnit::Day => 24 * 60 * 60,
+        };
+        (self.time_num as i64)
+            .saturating_mul(secs)
+            .saturating_mul(1_000_000_000)
+    }
+
     // with default DurationUnit day
     pub fn new(text: &str) -> Option<Self> {
         if text.is_empty() {
@@ -667,3 +984,137 @@ impl Duration {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn external_schema(file_type: &str, location: &str) -> ExternalTableSchema {
+        ExternalTableSchema {
+            db: "public".to_string(),
+            name: "t".to_string(),
+            file_compression_type: "".to_string(),
+            file_type: file_type.to_string(),
+            location: location.to_string(),
+            target_partitions: 1,
+            table_partition_cols: vec![],
+            has_header: false,
+            delimiter: b',',
+            schema: Schema::empty(),
+        }
+    }
+
+    #[test]
+    fn test_column_type_from_arrow() {
+        let cases = [
+            (ArrowDataType::Int32, ValueType::Integer),
+            (ArrowDataType::UInt8, ValueType::Unsigned),
+            (ArrowDataType::UInt64, ValueType::Unsigned),
+            (ArrowDataType::Float32, ValueType::Float),
+            (ArrowDataType::LargeUtf8, ValueType::String),
+        ];
+        for (data_type, value_type) in cases {
+            assert_eq!(
+                ColumnType::try_from(data_type),
+                Ok(ColumnType::Field(value_type))
+            );
+        }
+        assert!(ColumnType::try_from(ArrowDataType::Date32).is_err());
+        assert_eq!(
+            ArrowDataType::from(ColumnType::Field(ValueType::Unsigned)),
+            ArrowDataType::UInt64
+        );
+    }
+
+    #[test]
+    fn test_validate_database_options() {
+        let day = |time_num| Duration {
+            time_num,
+            unit: DurationUnit::Day,
+        };
+        let mut options = DatabaseOptions::default();
+        assert!(options.validate().is_ok());
+        options.with_ttl(day(1));
+        assert!(options.validate().is_ok());
+
+        options.with_ttl(day(30));
+        options.with_vnode_duration(day(7));
+        options.with_shard_num(DatabaseOptions::MAX_SHARD_NUM);
+        options.with_replica(3);
+        assert!(options.validate().is_ok());
+
+        let mut shorter_ttl = options.clone();
+        shorter_ttl.with_ttl(day(1));
+        assert!(shorter_ttl.validate().is_err());
+
+        let mut no_shard = options.clone();
+        no_shard.with_shard_num(0);
+        assert!(no_shard.validate().is_err());
+
+        let mut too_many_replicas = options;
+        too_many_replicas.with_replica(DatabaseOptions::MAX_REPLICA + 1);
+        assert!(too_many_replicas.validate().is_err());
+    }
+
+    #[test]
+    fn test_external_table_options() {
+        let schema = external_schema("NDJSON", "/data/ndjson/");
+        let options = schema.table_options().unwrap();
+        assert_eq!(options.file_extension, ".json");
+        assert!(options.format.as_any().is::<JsonFormat>());
+
+        let schema = external_schema("AVRO", "/data/avro");
+        let options = schema.table_options().unwrap();
+        assert_eq!(options.file_extension, ".avro");
+        assert!(options.format.as_any().is::<AvroFormat>());
+
+        let schema = external_schema("NDJSON", "/data/ndjson/cpu.ndjson");
+        assert_eq!(schema.file_extension().unwrap(), "");
+
+        let schema = external_schema("ORC", "/data/orc/");
+        assert!(schema.table_options().is_err());
+    }
+
+    #[test]
+    fn test_external_table_compression() {
+        let mut schema = external_schema("CSV", "/data/csv/");
+        schema.file_compression_type = "GZIP".to_string();
+        assert_eq!(schema.table_options().unwrap().file_extension, ".csv.gz");
+
+        schema.file_compression_type = "ZSTD".to_string();
+        assert_eq!(schema.table_options().unwrap().file_extension, ".csv.zst");
+
+        let mut schema = external_schema("PARQUET", "/data/parquet/");
+        schema.file_compression_type = "ZSTD".to_string();
+        assert!(schema.table_options().is_err());
+
+        assert_eq!(
+            ExternalTableSchema::compression_from_extension("/data/cpu.ndjson.zst"),
+            Some(ZSTD)
+        );
+        assert_eq!(
+            ExternalTableSchema::compression_from_extension("/data/cpu.csv.GZ"),
+            Some("GZIP")
+        );
+        assert_eq!(
+            ExternalTableSchema::compression_from_extension("/data/cpu.csv"),
+            None
+        );
+        assert_eq!(
+            ExternalTableSchema::compression_from_extension("/data/gz/"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_column_type_supports_encoding() {
+        let float = ColumnType::Field(ValueType::Float);
+        assert!(float.supports_encoding(&Encoding::Gorilla));
+        assert!(!float.supports_encoding(&Encoding::Delta));
+        assert!(ColumnType::Field(ValueType::String).supports_encoding(&Encoding::Zstd));
+        assert!(ColumnType::Field(ValueType::Boolean).supports_encoding(&Encoding::BitPack));
+        assert!(ColumnType::Time.supports_encoding(&Encoding::Delta));
+        assert!(ColumnType::Tag.supports_encoding(&Encoding::Default));
+        assert!(!ColumnType::Tag.supports_encoding(&Encoding::Zstd));
+    }
+}
@@ -23,6 +23,11 @@ impl SeriesKey {
     pub fn set_id(&mut self, id: SeriesId) {
         self.id = id;
     }
+
+    pub fn set_db(&mut self, db: String) {
+        self.db = db;
+    }
+
     pub fn tags(&self) -> &Vec<Tag> {
         &self.tags
     }
@@ -46,9 +51,14 @@ impl SeriesKey {
     }
 
     pub fn hash(&self) -> u64 {
+        Self::hash_of(&self.table, &self.tags)
+    }
+
+    /// Hash of the series of the table, the tags must be sorted by key
+    pub fn hash_of(table: &str, tags: &[Tag]) -> u64 {
         let mut hasher = BkdrHasher::new();
-        hasher.hash_with(self.table.as_bytes());
-        for tag in &self.tags {
+        hasher.hash_with(table.as_bytes());
+        for tag in tags {
             hasher.hash_with(&tag.key);
             hasher.hash_with(&tag.value);
         }
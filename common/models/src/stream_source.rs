@@ -0,0 +1,118 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// External systems that records can be continuously ingested from
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConnectorType {
+    Kafka,
+    Mqtt,
+}
+
+impl FromStr for ConnectorType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "KAFKA" => Ok(Self::Kafka),
+            "MQTT" => Ok(Self::Mqtt),
+            _ => Err(format!(
+                "connector {} is not supported, expected one of KAFKA, MQTT",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for ConnectorType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Kafka => "KAFKA",
+            Self::Mqtt => "MQTT",
+        })
+    }
+}
+
+/// Encoding of the records read from a stream source
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PayloadFormat {
+    /// One or more lines of line protocol per record
+    LineProtocol,
+    /// A json object `{"measurement", "tags", "fields", "timestamp"}` or an array of them
+    Json,
+}
+
+impl FromStr for PayloadFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "LINE_PROTOCOL" | "LINEPROTOCOL" | "LP" => Ok(Self::LineProtocol),
+            "JSON" => Ok(Self::Json),
+            _ => Err(format!(
+                "format {} is not supported, expected one of LINE_PROTOCOL, JSON",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for PayloadFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::LineProtocol => "LINE_PROTOCOL",
+            Self::Json => "JSON",
+        })
+    }
+}
+
+/// Definition of a stream source created by `CREATE STREAM SOURCE`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StreamSourceDefinition {
+    pub name: String,
+    pub connector: ConnectorType,
+    pub format: PayloadFormat,
+    /// Database that the records are written into
+    pub database: String,
+    /// Table used for json records without `measurement`, may be a template of the topic
+    pub table: Option<String>,
+    /// Connector specific options, such as brokers and topic for kafka
+    pub options: BTreeMap<String, String>,
+}
+
+impl StreamSourceDefinition {
+    pub fn option(&self, key: &str) -> Option<&str> {
+        self.options.get(key).map(|v| v.as_str())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamSourceState {
+    Running,
+    Stopped,
+    Failed,
+}
+
+impl Display for StreamSourceState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Running => "RUNNING",
+            Self::Stopped => "STOPPED",
+            Self::Failed => "FAILED",
+        })
+    }
+}
+
+/// Runtime status of a stream source, returned by `SHOW STREAM SOURCES`
+#[derive(Debug, Clone)]
+pub struct StreamSourceStatus {
+    pub definition: StreamSourceDefinition,
+    pub state: StreamSourceState,
+    /// Number of records written into tskv
+    pub records_written: u64,
+    /// Number of records that could not be decoded or written, sent to the dead letter topic if any
+    pub records_failed: u64,
+    pub last_error: Option<String>,
+}
@@ -0,0 +1,163 @@
+use std::fmt::{self, Display};
+
+use serde::{Deserialize, Serialize};
+
+use crate::Timestamp;
+
+/// Placeholder of the query of a task replaced by the time the last successful run
+/// of it started, the unix epoch before the first one
+pub const LAST_RUN_PLACEHOLDER: &str = "$last_run";
+/// Placeholder of the query of a task replaced by the time the run started
+pub const NOW_PLACEHOLDER: &str = "$now";
+/// Latest runs of a task kept in its progress
+pub const MAX_TASK_RUNS: usize = 100;
+
+/// When a task runs
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum TaskTrigger {
+    /// Runs every interval, in seconds
+    Schedule { interval: u64 },
+    /// Runs once rows newer than those seen by the last run are written to the
+    /// table, checked every interval, in seconds
+    OnData { table: String, interval: u64 },
+}
+
+impl TaskTrigger {
+    pub fn interval(&self) -> u64 {
+        match self {
+            Self::Schedule { interval } | Self::OnData { interval, .. } => *interval,
+        }
+    }
+}
+
+impl Display for TaskTrigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Schedule { interval } => write!(f, "EVERY {}s", interval),
+            Self::OnData { table, interval } => {
+                write!(f, "ON DATA IN {} EVERY {}s", table, interval)
+            }
+        }
+    }
+}
+
+/// Where the rows of the query of a task go
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum TaskSink {
+    /// Inserted into the table of the database of the task
+    Table(String),
+    /// Posted to the url as a json `{"task", "rows"}` if there is any, to alert
+    Webhook(String),
+}
+
+impl Display for TaskSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Table(table) => write!(f, "INTO {}", table),
+            Self::Webhook(url) => write!(f, "WEBHOOK '{}'", url),
+        }
+    }
+}
+
+/// Definition of a task created by `CREATE TASK`, a pipeline of a query run by the
+/// owner of it on a trigger, the rows of which are written to a table or alerted
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TaskDefinition {
+    pub name: String,
+    pub tenant: String,
+    pub database: String,
+    /// The user the query is run by, with the privileges of the user
+    pub owner: String,
+    /// The query may refer to `$last_run` and `$now` to process the rows written
+    /// since the last run only
+    pub query: String,
+    pub trigger: TaskTrigger,
+    pub sink: TaskSink,
+    pub enabled: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskRunState {
+    Succeeded,
+    Failed,
+    /// No row is written to the table of the trigger since the last run
+    Skipped,
+}
+
+impl Display for TaskRunState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Succeeded => "SUCCEEDED",
+            Self::Failed => "FAILED",
+            Self::Skipped => "SKIPPED",
+        })
+    }
+}
+
+/// A run of a task
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TaskRun {
+    pub tenant: String,
+    pub task: String,
+    pub started_at: Timestamp,
+    pub duration_ms: u64,
+    pub state: TaskRunState,
+    /// Rows written to the table or posted to the webhook
+    pub rows: u64,
+    pub error: Option<String>,
+}
+
+/// Progress of a task saved after every run, the runs resume from it once the node
+/// restarts or another node takes the task over
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct TaskProgress {
+    pub tenant: String,
+    pub task: String,
+    /// Start of the last successful run, the value of `$last_run`
+    pub last_run: Timestamp,
+    /// The latest time of the rows of the table of the trigger seen by the last
+    /// successful run
+    pub watermark: Option<Timestamp>,
+    /// The latest runs, the oldest first
+    pub runs: Vec<TaskRun>,
+}
+
+impl TaskProgress {
+    pub fn new(tenant: &str, task: &str) -> Self {
+        Self {
+            tenant: tenant.to_string(),
+            task: task.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// The oldest runs beyond `MAX_TASK_RUNS` are dropped
+    pub fn record(&mut self, run: TaskRun) {
+        let excess = (self.runs.len() + 1).saturating_sub(MAX_TASK_RUNS);
+        self.runs.drain(..excess);
+        self.runs.push(run);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_record_run() {
+        let mut progress = TaskProgress::new("cnosdb", "t0");
+        for i in 0..MAX_TASK_RUNS as i64 + 2 {
+            progress.record(TaskRun {
+                tenant: "cnosdb".to_string(),
+                task: "t0".to_string(),
+                started_at: i,
+                duration_ms: 1,
+                state: TaskRunState::Succeeded,
+                rows: 0,
+                error: None,
+            });
+        }
+        assert_eq!(progress.runs.len(), MAX_TASK_RUNS);
+        assert_eq!(progress.runs[0].started_at, 2);
+    }
+}
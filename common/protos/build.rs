@@ -24,8 +24,15 @@ mod flatbuffers_generated;
         let proto_file_paths = &[
             proto_files_dir.join("kv_service.proto"),
             proto_files_dir.join("schema_service.proto"),
+            proto_files_dir.join("flight.proto"),
+        ];
+        // (<package of the .proto file>, <rust mod name>), the file generated is
+        // named by the package
+        let rust_mod_names = &[
+            ("kv_service", "kv_service"),
+            ("schema_service", "schema_service"),
+            ("arrow.flight.protocol", "arrow_flight"),
         ];
-        let rust_mod_names = &["kv_service".to_string(), "schema_service".to_string()];
 
         // src/generated/protobuf_generated/
         let output_dir_final = env::current_dir()
@@ -47,7 +54,11 @@ mod flatbuffers_generated;
 
         // src/generated/protobuf_generated/mod.rs
         let mut protobuf_generated_mod_rs_file = fs::File::create(output_dir_final.join("mod.rs"))?;
-        for mod_name in rust_mod_names.iter() {
+        for (package, mod_name) in rust_mod_names.iter() {
+            if package != mod_name {
+                protobuf_generated_mod_rs_file
+                    .write_all(format!("#[path = \"{}.rs\"]\n", package).as_bytes())?;
+            }
             protobuf_generated_mod_rs_file.write_all(b"pub mod ")?;
             protobuf_generated_mod_rs_file.write_all(mod_name.as_bytes())?;
             protobuf_generated_mod_rs_file.write_all(b";\n")?;
@@ -0,0 +1,212 @@
+//! Logs formatted one json per line, ingestible by the log collectors as is, e.g.
+//! `{"timestamp":"..","level":"WARN","target":"main::http","message":"..","trace_id":"..","db":"public"}`
+//!
+//! The context fields of the spans an event is in are flattened into the event, so
+//! the logs of a request are found by the trace id, tenant, database or query id.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use serde_json::Value;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::fmt::time::{ChronoUtc, FormatTime};
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Fields of the spans flattened into the events in them
+pub const CONTEXT_FIELDS: &[&str] = &["trace_id", "tenant", "db", "query_id"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human readable
+    Text,
+    /// One json per line
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("Unknown log format {}, expected text or json", s)),
+        }
+    }
+}
+
+/// Context fields recorded to a span
+#[derive(Debug, Default)]
+struct ContextFields(BTreeMap<&'static str, String>);
+
+impl Visit for ContextFields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if let Some(name) = CONTEXT_FIELDS.iter().find(|e| **e == field.name()) {
+            self.0.insert(name, value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if let Some(name) = CONTEXT_FIELDS.iter().find(|e| **e == field.name()) {
+            self.0.insert(name, format!("{:?}", value));
+        }
+    }
+}
+
+/// Keeps the context fields of the spans for `JsonFormat`
+pub struct ContextLayer;
+
+impl<S> Layer<S> for ContextLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut fields = ContextFields::default();
+        attrs.record(&mut fields);
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(fields);
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            let mut extensions = span.extensions_mut();
+            if let Some(fields) = extensions.get_mut::<ContextFields>() {
+                values.record(fields);
+            }
+        }
+    }
+}
+
+/// Fields of an event
+#[derive(Default)]
+struct EventFields(serde_json::Map<String, Value>);
+
+impl Visit for EventFields {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{:?}", value).into());
+    }
+}
+
+pub struct JsonFormat;
+
+impl<S, N> FormatEvent<S, N> for JsonFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        writer: &mut dyn fmt::Write,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let mut timestamp = String::new();
+        ChronoUtc::rfc3339().format_time(&mut timestamp)?;
+        let metadata = event.metadata();
+
+        let mut log = serde_json::Map::new();
+        log.insert("timestamp".to_string(), timestamp.trim().into());
+        log.insert("level".to_string(), metadata.level().to_string().into());
+        log.insert("target".to_string(), metadata.target().into());
+
+        // The fields of the inner spans take precedence
+        let mut context = BTreeMap::new();
+        let mut span_name = None;
+        ctx.visit_spans::<fmt::Error, _>(|span| {
+            span_name = Some(span.name());
+            if let Some(fields) = span.extensions().get::<ContextFields>() {
+                context.extend(fields.0.iter().map(|(k, v)| (*k, v.clone())));
+            }
+            Ok(())
+        })?;
+        if let Some(name) = span_name {
+            log.insert("span".to_string(), name.into());
+        }
+        for (k, v) in context {
+            log.insert(k.to_string(), v.into());
+        }
+
+        let mut fields = EventFields::default();
+        event.record(&mut fields);
+        log.extend(fields.0);
+
+        let line = serde_json::to_string(&log).map_err(|_| fmt::Error)?;
+        writeln!(writer, "{}", line)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::{fmt, Registry};
+
+    use super::*;
+    use crate::{info, info_span};
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_json_format() {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = Registry::default().with(ContextLayer).with(
+            fmt::layer()
+                .event_format(JsonFormat)
+                .with_writer(move || writer.clone()),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = info_span!("http", tenant = "cnosdb", db = tracing::field::Empty);
+            span.record("db", &"public");
+            let _enter = span.enter();
+            info_span!("query", query_id = 7).in_scope(|| info!(rows = 3, "done"));
+        });
+
+        let content = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let log: Value = serde_json::from_str(content.trim()).unwrap();
+        assert_eq!(log["level"], "INFO");
+        assert_eq!(log["message"], "done");
+        assert_eq!(log["span"], "query");
+        assert_eq!(log["tenant"], "cnosdb");
+        assert_eq!(log["db"], "public");
+        assert_eq!(log["query_id"], "7");
+        assert_eq!(log["rows"], 3);
+        assert_eq!("json".parse::<LogFormat>(), Ok(LogFormat::Json));
+    }
+}
@@ -1,13 +1,27 @@
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex, Once};
 
-use once_cell::sync::Lazy;
-pub use tracing::{debug, error, info, instrument, trace, warn};
+use once_cell::sync::{Lazy, OnceCell};
+use opentelemetry::sdk::propagation::TraceContextPropagator;
+use opentelemetry::sdk::{trace as sdktrace, Resource};
+use opentelemetry::trace::TraceContextExt;
+use opentelemetry::{global, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+pub use tracing::{
+    debug, error, field, info, info_span, instrument, trace, warn, Instrument, Span,
+};
 use tracing_appender::{non_blocking, non_blocking::WorkerGuard, rolling};
 use tracing_error::ErrorLayer;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 use tracing_subscriber::{
-    filter::EnvFilter, fmt, layer::SubscriberExt, util::SubscriberInitExt, Registry,
+    filter::EnvFilter, fmt, layer::SubscriberExt, reload, util::SubscriberInitExt, Registry,
 };
 
+use crate::json::{ContextLayer, JsonFormat};
+pub use crate::json::{LogFormat, CONTEXT_FIELDS};
+
+mod json;
+
 /// only use for unit test
 /// parameter only use for first call
 pub fn init_default_global_tracing(dir: &str, file_name: &str, level: &str) {
@@ -22,21 +36,83 @@ pub fn init_default_global_tracing(dir: &str, file_name: &str, level: &str) {
 static GLOBAL_UT_LOG_GUARD: Lazy<Arc<Mutex<Option<Vec<WorkerGuard>>>>> =
     Lazy::new(|| Arc::new(Mutex::new(None)));
 
+/// Replaces the level filter of the global tracing
+static LOG_FILTER: OnceCell<reload::Handle<EnvFilter, Registry>> = OnceCell::new();
+
 pub fn init_global_tracing(dir: &str, file_name: &str, level: &str) -> Vec<WorkerGuard> {
+    init_global_tracing_with_otlp(dir, file_name, level, LogFormat::Text, None)
+}
+
+/// The collector the spans are exported to by otlp
+#[derive(Debug, Clone)]
+pub struct OtlpExporter {
+    pub service_name: String,
+    pub endpoint: String,
+}
+
+/// Same as `init_global_tracing`, the logs are in the format, the spans are also
+/// exported to the collector if given. Must be called in a tokio runtime, the spans
+/// are exported in batch by it.
+pub fn init_global_tracing_with_otlp(
+    dir: &str,
+    file_name: &str,
+    level: &str,
+    format: LogFormat,
+    otlp: Option<&OtlpExporter>,
+) -> Vec<WorkerGuard> {
     let env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(level));
-    let formatting_layer = fmt::layer().pretty().with_writer(std::io::stderr);
+    let (env_filter, filter_handle) = reload::Layer::new(env_filter);
+    let _ = LOG_FILTER.set(filter_handle);
 
     let file_appender = rolling::daily(dir, file_name);
     let (non_blocking_appender, guard) = non_blocking(file_appender);
-    let file_layer = fmt::layer().with_writer(non_blocking_appender);
+
+    let json = format == LogFormat::Json;
+    let context_layer = json.then(|| ContextLayer);
+    let (formatting_layer, file_layer) = if json {
+        (None, None)
+    } else {
+        (
+            Some(fmt::layer().pretty().with_writer(std::io::stderr)),
+            Some(fmt::layer().with_writer(non_blocking_appender.clone())),
+        )
+    };
+    let (json_formatting_layer, json_file_layer) = if json {
+        (
+            Some(
+                fmt::layer()
+                    .event_format(JsonFormat)
+                    .with_writer(std::io::stderr),
+            ),
+            Some(
+                fmt::layer()
+                    .event_format(JsonFormat)
+                    .with_writer(non_blocking_appender),
+            ),
+        )
+    } else {
+        (None, None)
+    };
 
     let guards = vec![guard];
 
+    let otlp_layer = otlp.and_then(|otlp| match otlp_tracer(otlp) {
+        Ok(tracer) => Some(tracing_opentelemetry::layer().with_tracer(tracer)),
+        Err(e) => {
+            eprintln!("Failed to export spans to {}: {}", otlp.endpoint, e);
+            None
+        }
+    });
+
     Registry::default()
         .with(env_filter)
         .with(ErrorLayer::default())
+        .with(context_layer)
         .with(formatting_layer)
         .with(file_layer)
+        .with(json_formatting_layer)
+        .with(json_file_layer)
+        .with(otlp_layer)
         .init();
 
     match color_eyre::install() {
@@ -50,6 +126,71 @@ pub fn init_global_tracing(dir: &str, file_name: &str, level: &str) -> Vec<Worke
 
     guards
 }
+fn otlp_tracer(otlp: &OtlpExporter) -> Result<sdktrace::Tracer, opentelemetry::trace::TraceError> {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(&otlp.endpoint),
+        )
+        .with_trace_config(
+            sdktrace::config().with_resource(Resource::new(vec![KeyValue::new(
+                "service.name",
+                otlp.service_name.clone(),
+            )])),
+        )
+        .install_batch(opentelemetry::runtime::Tokio)
+}
+
+/// Changes the level of the global tracing on the fly, e.g. `debug` or `info,tskv=debug`
+pub fn set_log_level(level: &str) -> Result<(), String> {
+    let env_filter = log_filter(level)?;
+    match LOG_FILTER.get() {
+        Some(handle) => handle.reload(env_filter).map_err(|e| e.to_string()),
+        None => Err("global tracing is not initialized".to_string()),
+    }
+}
+
+/// Checks the level is valid for `set_log_level`
+pub fn validate_log_level(level: &str) -> Result<(), String> {
+    log_filter(level).map(|_| ())
+}
+
+fn log_filter(level: &str) -> Result<EnvFilter, String> {
+    EnvFilter::try_new(level).map_err(|e| e.to_string())
+}
+
+/// Exports the spans not exported yet
+pub fn shutdown_global_tracing() {
+    global::shutdown_tracer_provider();
+}
+
+/// Makes the span a child of the span of the caller, the trace context of which is
+/// carried in the w3c `traceparent` header, so a request is traced end to end.
+///
+/// The id of the trace is recorded to the `trace_id` field of the span if declared.
+pub fn set_remote_parent<'a>(span: &Span, headers: impl IntoIterator<Item = (&'a str, &'a str)>) {
+    let carrier: HashMap<String, String> = headers
+        .into_iter()
+        .map(|(k, v)| (k.to_lowercase(), v.to_string()))
+        .collect();
+    let cx = global::get_text_map_propagator(|propagator| propagator.extract(&carrier));
+    let remote = cx.span().span_context().clone();
+    span.set_parent(cx);
+
+    // A trace is started by the span if exported and not continued
+    let trace_id = if remote.is_valid() {
+        remote.trace_id()
+    } else {
+        span.context().span().span_context().trace_id()
+    };
+    if trace_id != opentelemetry::trace::TraceId::invalid() {
+        span.record("trace_id", &trace_id.to_hex().as_str());
+    }
+}
+
 ///Use this macro to wrap the expression, you can output the error log
 #[macro_export]
 macro_rules! log_error {
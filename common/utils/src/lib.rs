@@ -1,7 +1,9 @@
 mod bkdr_hash;
 mod bloom_filter;
 mod dedup;
+mod memory_budget;
 
 pub use bkdr_hash::BkdrHasher;
 pub use bloom_filter::BloomFilter;
 pub use dedup::{dedup_front_by, dedup_front_by_key};
+pub use memory_budget::{MemoryBudget, MemoryPool, MemoryPoolKind, PressureHandler};
@@ -0,0 +1,219 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Mutex,
+};
+
+/// Subsystems sharing the memory budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MemoryPoolKind {
+    Memcache,
+    Cache,
+    Query,
+}
+
+impl MemoryPoolKind {
+    const ALL: [MemoryPoolKind; 3] = [Self::Memcache, Self::Cache, Self::Query];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Memcache => "memcache",
+            Self::Cache => "cache",
+            Self::Query => "query",
+        }
+    }
+}
+
+/// Called with the bytes the pool is asked to free
+pub type PressureHandler = Box<dyn Fn(u64) + Send + Sync>;
+
+/// Memory of a subsystem accounted against its share of the budget. The share is
+/// not a hard limit, a pool may use the memory the others do not, until the budget
+/// is exceeded and the pools over their shares are asked to free memory.
+pub struct MemoryPool {
+    kind: MemoryPoolKind,
+    quota: u64,
+    used: AtomicU64,
+    handlers: Mutex<Vec<PressureHandler>>,
+}
+
+impl MemoryPool {
+    fn new(kind: MemoryPoolKind, quota: u64) -> Self {
+        Self {
+            kind,
+            quota,
+            used: AtomicU64::new(0),
+            handlers: Mutex::new(vec![]),
+        }
+    }
+
+    pub fn kind(&self) -> MemoryPoolKind {
+        self.kind
+    }
+
+    /// Bytes of the share of the budget
+    pub fn quota(&self) -> u64 {
+        self.quota
+    }
+
+    pub fn used(&self) -> u64 {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    pub fn set_used(&self, bytes: u64) {
+        self.used.store(bytes, Ordering::Relaxed);
+    }
+
+    pub fn grow(&self, bytes: u64) {
+        self.used.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn shrink(&self, bytes: u64) {
+        let _ = self
+            .used
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |v| {
+                Some(v.saturating_sub(bytes))
+            });
+    }
+
+    /// Registers the handler freeing the memory of the pool under pressure, like
+    /// flushing the memcaches early or evicting the caches
+    pub fn on_pressure(&self, handler: impl Fn(u64) + Send + Sync + 'static) {
+        self.handlers.lock().unwrap().push(Box::new(handler));
+    }
+
+    fn excess(&self) -> u64 {
+        self.used().saturating_sub(self.quota)
+    }
+
+    fn relieve(&self, bytes: u64) {
+        for handler in self.handlers.lock().unwrap().iter() {
+            handler(bytes);
+        }
+    }
+}
+
+/// Accounts the memory of the memcaches, the caches and the queries against a
+/// budget of the node, split by the shares of the subsystems, so that they do not
+/// grow uncoordinated until the node runs out of memory.
+pub struct MemoryBudget {
+    limit: u64,
+    pools: Vec<MemoryPool>,
+}
+
+impl MemoryBudget {
+    /// The shares of the pools are the fractions of the limit, 0 for unlimited
+    pub fn new(limit: u64, shares: &[(MemoryPoolKind, f64)]) -> Self {
+        let pools = MemoryPoolKind::ALL
+            .iter()
+            .map(|kind| {
+                let share = shares
+                    .iter()
+                    .find(|(k, _)| k == kind)
+                    .map_or(0.0, |(_, share)| *share);
+                MemoryPool::new(*kind, (limit as f64 * share) as u64)
+            })
+            .collect();
+        Self { limit, pools }
+    }
+
+    pub fn unlimited() -> Self {
+        Self::new(0, &[])
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    pub fn pool(&self, kind: MemoryPoolKind) -> &MemoryPool {
+        self.pools
+            .iter()
+            .find(|e| e.kind == kind)
+            .expect("pool of each kind")
+    }
+
+    pub fn used(&self) -> u64 {
+        self.pools.iter().map(|e| e.used()).sum()
+    }
+
+    /// Asks the pools over their shares to free the bytes the budget is exceeded
+    /// by, the pools the most over first, returns the bytes asked of each pool
+    pub fn relieve_pressure(&self) -> Vec<(MemoryPoolKind, u64)> {
+        let mut excess = self.used().saturating_sub(self.limit);
+        if self.limit == 0 || excess == 0 {
+            return vec![];
+        }
+
+        let mut pools = self
+            .pools
+            .iter()
+            .filter(|e| e.excess() > 0)
+            .collect::<Vec<_>>();
+        pools.sort_by_key(|e| std::cmp::Reverse(e.excess()));
+        let mut relieved = vec![];
+        for pool in pools {
+            if excess == 0 {
+                break;
+            }
+            let bytes = pool.excess().min(excess);
+            pool.relieve(bytes);
+            relieved.push((pool.kind, bytes));
+            excess -= bytes;
+        }
+        relieved
+    }
+}
+
+impl std::fmt::Debug for MemoryBudget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut s = f.debug_struct("MemoryBudget");
+        s.field("limit", &self.limit);
+        for pool in self.pools.iter() {
+            s.field(pool.kind.as_str(), &(pool.used(), pool.quota));
+        }
+        s.finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn test_relieve_pressure() {
+        let budget = MemoryBudget::new(
+            100,
+            &[
+                (MemoryPoolKind::Memcache, 0.5),
+                (MemoryPoolKind::Cache, 0.2),
+                (MemoryPoolKind::Query, 0.3),
+            ],
+        );
+        let flushed = Arc::new(AtomicU64::new(0));
+        {
+            let flushed = flushed.clone();
+            budget
+                .pool(MemoryPoolKind::Memcache)
+                .on_pressure(move |bytes| {
+                    flushed.fetch_add(bytes, Ordering::Relaxed);
+                });
+        }
+
+        // The memcaches use the share of the caches not used
+        budget.pool(MemoryPoolKind::Memcache).set_used(70);
+        budget.pool(MemoryPoolKind::Query).set_used(30);
+        assert!(budget.relieve_pressure().is_empty());
+
+        budget.pool(MemoryPoolKind::Cache).set_used(25);
+        assert_eq!(
+            budget.relieve_pressure(),
+            vec![(MemoryPoolKind::Memcache, 20), (MemoryPoolKind::Cache, 5)]
+        );
+        assert_eq!(flushed.load(Ordering::Relaxed), 20);
+
+        let budget = MemoryBudget::unlimited();
+        budget.pool(MemoryPoolKind::Cache).grow(1 << 40);
+        assert!(budget.relieve_pressure().is_empty());
+    }
+}
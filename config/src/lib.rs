@@ -1,8 +1,45 @@
+use std::collections::{BTreeMap, BTreeSet};
 use std::{fs::File, io::prelude::Read};
 
 use serde::{Deserialize, Serialize};
 use trace::info;
 
+pub use settings::{Setting, Settings, SettingsRef};
+
+mod settings;
+
+/// Settings applied on the fly once the configuration is reloaded, the changes of
+/// the others take effect after a restart
+pub const RELOADABLE_SETTINGS: &[&str] = &[
+    "log.level",
+    "cache.max_buffer_size",
+    "cache.max_immutable_number",
+    "cache.max_flush_pending_size",
+    "cache.write_stall_timeout_ms",
+    "storage.max_compact_mb_per_sec",
+    "storage.max_concurrent_compactions",
+    "query.query_sql_limit",
+    "query.write_sql_limit",
+    "query.slow_query_threshold_ms",
+    "query.slow_write_threshold_ms",
+    "query.max_connections_per_user",
+    "query.idle_session_timeout_secs",
+    "rate_limit",
+    "quota",
+    "tag_limits",
+];
+
+/// Whether the setting is applied on the fly, the settings of a section in
+/// [`RELOADABLE_SETTINGS`] are all reloadable, e.g. `rate_limit.users.<user>.queries_per_sec`
+pub fn is_reloadable(name: &str) -> bool {
+    RELOADABLE_SETTINGS.iter().any(|e| {
+        name == *e
+            || name
+                .strip_prefix(e)
+                .map_or(false, |rest| rest.starts_with('.'))
+    })
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub query: QueryConfig,
@@ -11,23 +48,154 @@ pub struct Config {
     pub cache: CacheConfig,
     pub log: LogConfig,
     pub security: SecurityConfig,
+    #[serde(default)]
+    pub object_store: ObjectStoreConfig,
+    #[serde(default)]
+    pub cluster: ClusterConfig,
+    #[serde(default)]
+    pub dc_replication: DcReplicationConfig,
+    #[serde(default)]
+    pub audit: AuditConfig,
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
+    #[serde(default)]
+    pub quota: QuotaConfig,
+    #[serde(default)]
+    pub tag_limits: TagLimitsConfig,
+    #[serde(default)]
+    pub memory: MemoryConfig,
     pub reporting_disabled: Option<bool>,
 }
 
 impl Config {
+    /// Checks the settings which can not be checked when parsed
+    pub fn check(&self) -> Result<(), String> {
+        self.storage.compression.check()?;
+        if let Some(cors) = &self.security.cors {
+            cors.check()
+                .map_err(|err| format!("security.cors: {}", err))?;
+        }
+        Ok(())
+    }
+
     pub fn override_by_env(&mut self) {
         self.storage.override_by_env();
         self.wal.override_by_env();
         self.cache.override_by_env();
         self.query.override_by_env();
+        self.object_store.override_by_env();
+        self.cluster.override_by_env();
+    }
+
+    /// Applies the reloadable settings of the configuration reloaded, the changes of
+    /// the other settings are only reported, so they are reported again by the next
+    /// reload until a restart
+    pub fn reload(&mut self, new: &Config) -> ConfigChanges {
+        let old = flatten(self);
+        let new_values = flatten(new);
+        let mut changes = ConfigChanges::default();
+        let keys: BTreeSet<&String> = old.keys().chain(new_values.keys()).collect();
+        for key in keys {
+            if old.get(key) == new_values.get(key) {
+                continue;
+            }
+            if is_reloadable(key) {
+                changes.reloaded.push(key.clone());
+            } else {
+                changes.restart_required.push(key.clone());
+            }
+        }
+
+        self.log.level = new.log.level.clone();
+        self.cache = new.cache.clone();
+        self.query.query_sql_limit = new.query.query_sql_limit;
+        self.query.write_sql_limit = new.query.write_sql_limit;
+        self.query.slow_query_threshold_ms = new.query.slow_query_threshold_ms;
+        self.query.slow_write_threshold_ms = new.query.slow_write_threshold_ms;
+        self.query.max_connections_per_user = new.query.max_connections_per_user;
+        self.query.idle_session_timeout_secs = new.query.idle_session_timeout_secs;
+        self.rate_limit = new.rate_limit.clone();
+        self.quota = new.quota.clone();
+        self.tag_limits = new.tag_limits;
+
+        changes
+    }
+}
+
+/// Settings changed by a reload, named `<section>.<key>`
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ConfigChanges {
+    pub reloaded: Vec<String>,
+    pub restart_required: Vec<String>,
+}
+
+/// Values of the settings by `<section>.<key>`, an array is a single value
+fn flatten(config: &Config) -> BTreeMap<String, toml::Value> {
+    fn visit(prefix: &str, value: toml::Value, values: &mut BTreeMap<String, toml::Value>) {
+        match value {
+            toml::Value::Table(table) => {
+                for (k, v) in table {
+                    let key = if prefix.is_empty() {
+                        k
+                    } else {
+                        format!("{}.{}", prefix, k)
+                    };
+                    visit(&key, v, values);
+                }
+            }
+            v => {
+                values.insert(prefix.to_string(), v);
+            }
+        }
     }
+
+    let mut values = BTreeMap::new();
+    let value = toml::Value::try_from(config).expect("serialize configuration");
+    visit("", value, &mut values);
+    values
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QueryConfig {
+    /// Connections of the clients by http and grpc at the same time, the new ones
+    /// are closed once reached. The queries running at the same time are limited
+    /// to it as well, the setting requires a restart
     pub max_server_connections: u32,
     pub query_sql_limit: u64,
     pub write_sql_limit: u64,
+    /// Queries taking longer are logged, 0 to disable
+    #[serde(default = "QueryConfig::default_slow_query_threshold_ms")]
+    pub slow_query_threshold_ms: u64,
+    /// Write batches taking longer are logged with the time of each step, 0 to disable
+    #[serde(default = "QueryConfig::default_slow_write_threshold_ms")]
+    pub slow_write_threshold_ms: u64,
+    /// Requests in flight are waited for up to the timeout on shutdown
+    #[serde(default = "QueryConfig::default_shutdown_timeout_ms")]
+    pub shutdown_timeout_ms: u64,
+    /// Plans of the queries cached for the repeated queries, 0 to disable
+    #[serde(default = "QueryConfig::default_plan_cache_capacity")]
+    pub plan_cache_capacity: usize,
+    /// Results of the asynchronous queries are removed once finished for longer
+    #[serde(default = "QueryConfig::default_async_result_ttl_secs")]
+    pub async_result_ttl_secs: u64,
+    /// Asynchronous queries of each user running or with the results kept at the
+    /// same time, 0 for no limit
+    #[serde(default = "QueryConfig::default_max_async_queries_per_user")]
+    pub max_async_queries_per_user: usize,
+    /// Bytes of the results of the asynchronous queries of each user spooled to
+    /// the disk, the queries spooling more fail, 0 for no limit
+    #[serde(default = "QueryConfig::default_max_async_result_bytes_per_user")]
+    pub max_async_result_bytes_per_user: u64,
+    /// Connections of each user by http and grpc at the same time, 0 for no limit
+    #[serde(default)]
+    pub max_connections_per_user: usize,
+    /// Connections without a request for longer are closed, 0 to disable
+    #[serde(default = "QueryConfig::default_idle_session_timeout_secs")]
+    pub idle_session_timeout_secs: u64,
+    /// Directory the local locations of `EXPORT DATABASE` and `IMPORT DATABASE` are
+    /// relative to, they can not be outside of it
+    #[serde(default = "QueryConfig::default_dump_dir")]
+    pub dump_dir: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -42,9 +210,52 @@ pub struct StorageConfig {
     pub dio_max_non_resident: usize,
     pub dio_page_len_scale: usize,
     pub strict_write: bool,
+    /// Writes are rejected once the free space of the data or wal volume is less
+    #[serde(default = "StorageConfig::default_min_free_space")]
+    pub min_free_space: u64,
+    /// Writes are accepted again once the free space of both volumes is more
+    #[serde(default = "StorageConfig::default_resume_free_space")]
+    pub resume_free_space: u64,
+    /// MiB written by all the compactions per second, 0 to disable
+    #[serde(default)]
+    pub max_compact_mb_per_sec: u64,
+    /// Compactions of the ts families running at the same time
+    #[serde(default = "StorageConfig::default_max_concurrent_compactions")]
+    pub max_concurrent_compactions: usize,
+    /// Codecs of the strings of the columns of the DEFAULT codec by the levels
+    #[serde(default)]
+    pub compression: CompressionConfig,
+    /// Seconds between the passes removing the series all the data of which is
+    /// deleted from the index, 0 to disable
+    #[serde(default = "StorageConfig::default_series_gc_interval")]
+    pub series_gc_interval: u64,
+    /// Seconds between the passes removing the files expired by the ttl of the
+    /// databases set explicitly, 0 to disable. The databases without a ttl are kept
+    #[serde(default = "StorageConfig::default_retention_interval")]
+    pub retention_interval: u64,
 }
 
 impl StorageConfig {
+    fn default_min_free_space() -> u64 {
+        1024 * 1024 * 1024
+    }
+
+    fn default_resume_free_space() -> u64 {
+        2 * 1024 * 1024 * 1024
+    }
+
+    fn default_max_concurrent_compactions() -> usize {
+        1
+    }
+
+    fn default_series_gc_interval() -> u64 {
+        3600
+    }
+
+    fn default_retention_interval() -> u64 {
+        600
+    }
+
     pub fn override_by_env(&mut self) {
         if let Ok(path) = std::env::var("CNOSDB_APPLICATION_PATH") {
             self.path = path;
@@ -79,11 +290,99 @@ impl StorageConfig {
     }
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CompressionConfig {
+    #[serde(flatten)]
+    pub levels: LevelCompressionConfig,
+    /// Databases compressed otherwise than the default
+    #[serde(default)]
+    pub databases: BTreeMap<String, LevelCompressionConfig>,
+}
+
+impl CompressionConfig {
+    fn check(&self) -> Result<(), String> {
+        self.levels
+            .check()
+            .map_err(|err| format!("storage.compression: {}", err))?;
+        for (db, levels) in self.databases.iter() {
+            levels
+                .check()
+                .map_err(|err| format!("storage.compression.databases.{}: {}", db, err))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LevelCompressionConfig {
+    /// Codec of the files flushed and the files of the levels below `strong_level`
+    #[serde(default = "LevelCompressionConfig::default_fast_codec")]
+    pub fast_codec: String,
+    /// Codec of the files compacted into `strong_level` or the deeper levels
+    #[serde(default = "LevelCompressionConfig::default_strong_codec")]
+    pub strong_codec: String,
+    #[serde(default = "LevelCompressionConfig::default_strong_level")]
+    pub strong_level: u32,
+}
+
+impl LevelCompressionConfig {
+    /// Codecs of the strings but DEFAULT, the same as `models::codec::STRING_CODEC`
+    const CODECS: [&'static str; 6] = ["null", "gzip", "bzip", "zstd", "snappy", "zlib"];
+
+    fn check(&self) -> Result<(), String> {
+        for codec in [&self.fast_codec, &self.strong_codec] {
+            if !Self::CODECS.iter().any(|e| e.eq_ignore_ascii_case(codec)) {
+                return Err(format!(
+                    "'{}' is not a codec of the strings, expected one of {}",
+                    codec,
+                    Self::CODECS.join(", ")
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn default_fast_codec() -> String {
+        "snappy".to_string()
+    }
+
+    fn default_strong_codec() -> String {
+        "zstd".to_string()
+    }
+
+    fn default_strong_level() -> u32 {
+        3
+    }
+}
+
+impl Default for LevelCompressionConfig {
+    fn default() -> Self {
+        Self {
+            fast_codec: Self::default_fast_codec(),
+            strong_codec: Self::default_strong_codec(),
+            strong_level: Self::default_strong_level(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WalConfig {
     pub enabled: bool,
     pub path: String,
     pub sync: bool,
+    /// Syncs the wal in a dedicated thread, so the fsyncs do not block the writes
+    /// of the next batch
+    #[serde(default)]
+    pub sync_thread: bool,
+    /// Databases the wal of which is in another directory than `path`, e.g. on a
+    /// device different from the one of the data files
+    #[serde(default)]
+    pub databases: BTreeMap<String, DatabaseWalConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatabaseWalConfig {
+    pub path: String,
 }
 
 impl WalConfig {
@@ -97,6 +396,9 @@ impl WalConfig {
         if let Ok(sync) = std::env::var("CNOSDB_WAL_SYNC") {
             self.sync = sync.as_str() == sync;
         }
+        if let Ok(sync_thread) = std::env::var("CNOSDB_WAL_SYNC_THREAD") {
+            self.sync_thread = sync_thread.as_str() == "true";
+        }
     }
 }
 
@@ -104,9 +406,24 @@ impl WalConfig {
 pub struct CacheConfig {
     pub max_buffer_size: u64,
     pub max_immutable_number: u16,
+    /// Writes wait for the flushes once the bytes of the memcaches pending flush
+    /// exceed it, 0 to disable
+    #[serde(default = "CacheConfig::default_max_flush_pending_size")]
+    pub max_flush_pending_size: u64,
+    /// Writes waiting for the flushes longer are rejected as the server is busy
+    #[serde(default = "CacheConfig::default_write_stall_timeout_ms")]
+    pub write_stall_timeout_ms: u64,
 }
 
 impl CacheConfig {
+    fn default_max_flush_pending_size() -> u64 {
+        2 * 1024 * 1024 * 1024
+    }
+
+    fn default_write_stall_timeout_ms() -> u64 {
+        10000
+    }
+
     pub fn override_by_env(&mut self) {
         if let Ok(size) = std::env::var("CNOSDB_CACHE_MAX_BUFFER_SIZE") {
             self.max_buffer_size = size.parse::<u64>().unwrap();
@@ -118,6 +435,42 @@ impl CacheConfig {
 }
 
 impl QueryConfig {
+    fn default_slow_query_threshold_ms() -> u64 {
+        5000
+    }
+
+    fn default_slow_write_threshold_ms() -> u64 {
+        1000
+    }
+
+    fn default_shutdown_timeout_ms() -> u64 {
+        30000
+    }
+
+    fn default_plan_cache_capacity() -> usize {
+        1024
+    }
+
+    fn default_async_result_ttl_secs() -> u64 {
+        3600
+    }
+
+    fn default_max_async_queries_per_user() -> usize {
+        16
+    }
+
+    fn default_max_async_result_bytes_per_user() -> u64 {
+        10 * 1024 * 1024 * 1024
+    }
+
+    fn default_idle_session_timeout_secs() -> u64 {
+        900
+    }
+
+    fn default_dump_dir() -> String {
+        "data/dump".to_string()
+    }
+
     pub fn override_by_env(&mut self) {
         if let Ok(size) = std::env::var("MAX_SERVER_CONNECTIONS") {
             self.max_server_connections = size.parse::<u32>().unwrap();
@@ -135,9 +488,18 @@ impl QueryConfig {
 pub struct LogConfig {
     pub level: String,
     pub path: String,
+    /// Address of the opentelemetry collector the spans are exported to by otlp
+    pub otlp_endpoint: Option<String>,
+    /// `text`, or `json` for the log collectors
+    #[serde(default = "LogConfig::default_format")]
+    pub format: String,
 }
 
 impl LogConfig {
+    fn default_format() -> String {
+        "text".to_string()
+    }
+
     pub fn override_by_env(&mut self) {
         if let Ok(level) = std::env::var("CNOSDB_LOG_LEVEL") {
             self.level = level;
@@ -145,37 +507,675 @@ impl LogConfig {
         if let Ok(path) = std::env::var("CNOSDB_LOG_PATH") {
             self.path = path;
         }
+        if let Ok(endpoint) = std::env::var("CNOSDB_LOG_OTLP_ENDPOINT") {
+            self.otlp_endpoint = Some(endpoint);
+        }
+        if let Ok(format) = std::env::var("CNOSDB_LOG_FORMAT") {
+            self.format = format;
+        }
     }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SecurityConfig {
     pub tls_config: Option<TLSConfig>,
+    /// Serves the cpu and heap profiles at `/debug/pprof/*`
+    #[serde(default)]
+    pub pprof_enabled: bool,
+    /// Requires the clients to authenticate by the password of a user
+    #[serde(default = "SecurityConfig::default_auth_enabled")]
+    pub auth_enabled: bool,
+    /// Admin user created on the first start if there is no user, the data nodes
+    /// of a cluster also connect to each other as the user
+    #[serde(default = "SecurityConfig::default_admin_user")]
+    pub admin_user: String,
+    #[serde(default)]
+    pub admin_password: String,
+    /// Authenticates the clients presenting a JWT as the bearer token
+    #[serde(default)]
+    pub jwt: Option<JwtConfig>,
+    /// Authenticates the users not in the database by the passwords of them in LDAP
+    #[serde(default)]
+    pub ldap: Option<LdapConfig>,
+    /// Authenticates the users not in the database by the ID tokens of an OIDC issuer
+    #[serde(default)]
+    pub oidc: Option<OidcConfig>,
+    /// Allows the browsers to call the http api from the pages of other origins
+    #[serde(default)]
+    pub cors: Option<CorsConfig>,
+}
+
+impl SecurityConfig {
+    fn default_auth_enabled() -> bool {
+        true
+    }
+
+    fn default_admin_user() -> String {
+        "cnosdb".to_string()
+    }
 }
 
+/// TLS of the http and grpc listeners, the files are loaded again once SIGHUP is received
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TLSConfig {
     pub certificate: String,
     pub private_key: String,
+    /// CA verifying the certificates of the clients, and of the other data nodes
+    #[serde(default)]
+    pub ca_certificate: Option<String>,
+    /// Rejects the clients without a certificate signed by the CA, the certificate
+    /// is optional otherwise
+    #[serde(default)]
+    pub require_client_cert: bool,
+    /// Name in the certificate the data nodes are verified by, as they connect
+    /// to each other by ip address
+    #[serde(default)]
+    pub server_name: Option<String>,
 }
 
-pub fn get_config(path: &str) -> Config {
-    let mut file = match File::open(path) {
-        Ok(file) => file,
-        Err(err) => panic!("Failed to open configurtion file '{}': {}", path, err),
+/// JWTs signed by the keys of the JWKS, the claim names the user the client is
+/// authenticated as, which must exist
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JwtConfig {
+    /// Required `iss` of the tokens
+    #[serde(default)]
+    pub issuer: Option<String>,
+    /// Required `aud` of the tokens
+    #[serde(default)]
+    pub audience: Option<String>,
+    /// JWKS fetched periodically, such as `https://<idp>/.well-known/jwks.json`
+    #[serde(default)]
+    pub jwks_url: Option<String>,
+    /// JWKS read from the file once on start, if there is no `jwks_url`
+    #[serde(default)]
+    pub jwks_path: Option<String>,
+    #[serde(default = "JwtConfig::default_jwks_refresh_interval_secs")]
+    pub jwks_refresh_interval_secs: u64,
+    #[serde(default = "JwtConfig::default_user_claim")]
+    pub user_claim: String,
+}
+
+impl JwtConfig {
+    fn default_jwks_refresh_interval_secs() -> u64 {
+        300
+    }
+
+    fn default_user_claim() -> String {
+        "sub".to_string()
+    }
+}
+
+/// The users are searched by the bind user, then authenticated by binding as
+/// them. The groups of the users are mapped to the roles granted to them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LdapConfig {
+    /// Such as `ldap://ldap.example.com:389` or `ldaps://ldap.example.com:636`
+    pub url: String,
+    #[serde(default)]
+    pub bind_dn: String,
+    #[serde(default)]
+    pub bind_password: String,
+    pub user_base_dn: String,
+    /// `{username}` is replaced by the escaped name of the user
+    #[serde(default = "LdapConfig::default_user_filter")]
+    pub user_filter: String,
+    /// Attribute of the user entries listing the groups of them
+    #[serde(default = "LdapConfig::default_group_attribute")]
+    pub group_attribute: String,
+    #[serde(default)]
+    pub starttls: bool,
+    /// The users not authenticated by the server within it are refused
+    #[serde(default = "LdapConfig::default_timeout_ms")]
+    pub timeout_ms: u64,
+    /// How long a user authenticated is trusted before it is authenticated by the
+    /// server again, 0 authenticates each of the requests
+    #[serde(default = "LdapConfig::default_cache_ttl_secs")]
+    pub cache_ttl_secs: u64,
+}
+
+impl LdapConfig {
+    fn default_timeout_ms() -> u64 {
+        5000
+    }
+
+    fn default_cache_ttl_secs() -> u64 {
+        60
+    }
+
+    fn default_user_filter() -> String {
+        "(uid={username})".to_string()
+    }
+
+    fn default_group_attribute() -> String {
+        "memberOf".to_string()
+    }
+}
+
+/// ID tokens of the issuer, validated by the keys of the JWKS of its discovery document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OidcConfig {
+    /// `<issuer>/.well-known/openid-configuration` is the discovery document
+    pub issuer: String,
+    /// Client id the ID tokens are issued to
+    #[serde(default)]
+    pub audience: Option<String>,
+    #[serde(default = "OidcConfig::default_user_claim")]
+    pub user_claim: String,
+    #[serde(default = "OidcConfig::default_groups_claim")]
+    pub groups_claim: String,
+    #[serde(default = "OidcConfig::default_jwks_refresh_interval_secs")]
+    pub jwks_refresh_interval_secs: u64,
+}
+
+impl OidcConfig {
+    fn default_user_claim() -> String {
+        "preferred_username".to_string()
+    }
+
+    fn default_groups_claim() -> String {
+        "groups".to_string()
+    }
+
+    fn default_jwks_refresh_interval_secs() -> u64 {
+        300
+    }
+}
+
+/// Cross-origin requests of the browsers, the preflight requests are answered by
+/// the allowed origins, headers and methods
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorsConfig {
+    /// Such as `https://dashboard.example.com`, `*` allows any origin
+    pub allowed_origins: Vec<String>,
+    #[serde(default = "CorsConfig::default_allowed_headers")]
+    pub allowed_headers: Vec<String>,
+    #[serde(default = "CorsConfig::default_allowed_methods")]
+    pub allowed_methods: Vec<String>,
+    /// Response headers readable by the pages
+    #[serde(default = "CorsConfig::default_exposed_headers")]
+    pub exposed_headers: Vec<String>,
+    /// Sends the cookies and the `Authorization` header, `*` may not be allowed then
+    #[serde(default)]
+    pub allow_credentials: bool,
+    /// Seconds the browsers cache the responses of the preflight requests for
+    #[serde(default = "CorsConfig::default_max_age_secs")]
+    pub max_age_secs: u64,
+}
+
+impl CorsConfig {
+    fn default_allowed_headers() -> Vec<String> {
+        [
+            "accept",
+            "accept-encoding",
+            "authorization",
+            "content-encoding",
+            "content-type",
+        ]
+        .iter()
+        .map(|e| e.to_string())
+        .collect()
+    }
+
+    fn default_allowed_methods() -> Vec<String> {
+        ["GET", "POST", "DELETE"]
+            .iter()
+            .map(|e| e.to_string())
+            .collect()
+    }
+
+    fn default_exposed_headers() -> Vec<String> {
+        vec!["x-cnosdb-next-cursor".to_string()]
+    }
+
+    fn default_max_age_secs() -> u64 {
+        600
+    }
+
+    /// Any origin is allowed
+    pub fn allows_any_origin(&self) -> bool {
+        self.allowed_origins.iter().any(|e| e == "*")
+    }
+
+    /// The origins, the headers and the methods are valid, and any origin is not
+    /// allowed with the credentials
+    fn check(&self) -> Result<(), String> {
+        if self.allow_credentials && self.allows_any_origin() {
+            return Err("'*' may not be allowed with allow_credentials".to_string());
+        }
+        if let Some(origin) = self
+            .allowed_origins
+            .iter()
+            .find(|e| *e != "*" && !is_origin(e))
+        {
+            return Err(format!(
+                "'{}' is not an origin, expected <scheme>://<host>[:<port>]",
+                origin
+            ));
+        }
+        if let Some(header) = self
+            .allowed_headers
+            .iter()
+            .chain(self.exposed_headers.iter())
+            .find(|e| !is_token(e))
+        {
+            return Err(format!("'{}' is not a header name", header));
+        }
+        if let Some(method) = self.allowed_methods.iter().find(|e| !is_token(e)) {
+            return Err(format!("'{}' is not a method", method));
+        }
+        Ok(())
+    }
+}
+
+/// A token of rfc 7230, the names of the headers and the methods
+fn is_token(s: &str) -> bool {
+    !s.is_empty()
+        && s.bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+/// `<scheme>://<host>[:<port>]` without a path
+fn is_origin(s: &str) -> bool {
+    let (scheme, authority) = match s.split_once("://") {
+        Some(parts) => parts,
+        None => return false,
     };
-    let mut content = String::new();
-    if let Err(err) = file.read_to_string(&mut content) {
-        panic!("Failed to read configurtion file '{}': {}", path, err);
+    let scheme_valid = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"+-.".contains(&b));
+    let host = match authority.rsplit_once(':') {
+        // Not the port but a part of an ipv6 address
+        Some((host, port)) if !port.ends_with(']') => {
+            if port.parse::<u16>().is_err() {
+                return false;
+            }
+            host
+        }
+        _ => authority,
+    };
+    scheme_valid
+        && !host.is_empty()
+        && !host
+            .bytes()
+            .any(|b| b.is_ascii_whitespace() || b.is_ascii_control() || b"/?#@".contains(&b))
+}
+
+/// Credentials of the object stores that external tables may be located on
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ObjectStoreConfig {
+    /// Locations `s3://<bucket>/<path>`
+    pub s3: Option<S3Config>,
+    /// Locations `gs://<bucket>/<path>`
+    pub gcs: Option<GcsConfig>,
+    /// Locations `az://<container>/<path>`
+    pub azure: Option<AzureConfig>,
+}
+
+impl ObjectStoreConfig {
+    pub fn override_by_env(&mut self) {
+        if let (Ok(access_key_id), Ok(secret_access_key)) = (
+            std::env::var("AWS_ACCESS_KEY_ID"),
+            std::env::var("AWS_SECRET_ACCESS_KEY"),
+        ) {
+            let s3 = self.s3.get_or_insert_with(Default::default);
+            s3.access_key_id = Some(access_key_id);
+            s3.secret_access_key = Some(secret_access_key);
+        }
+        if let Ok(region) = std::env::var("AWS_REGION") {
+            self.s3.get_or_insert_with(Default::default).region = Some(region);
+        }
+        if let Ok(path) = std::env::var("GOOGLE_SERVICE_ACCOUNT") {
+            self.gcs = Some(GcsConfig {
+                service_account_path: path,
+            });
+        }
     }
-    let config: Config = match toml::from_str(&content) {
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct S3Config {
+    pub region: Option<String>,
+    /// Custom endpoint for s3 compatible stores, e.g. minio
+    pub endpoint: Option<String>,
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<String>,
+    pub session_token: Option<String>,
+    /// Allow http endpoints
+    #[serde(default)]
+    pub allow_http: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GcsConfig {
+    pub service_account_path: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AzureConfig {
+    pub account: String,
+    pub access_key: String,
+}
+
+/// The node runs standalone if no meta service is configured
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClusterConfig {
+    /// Id of the data node, unique in the cluster
+    #[serde(default)]
+    pub node_id: u64,
+    /// Http addresses of the meta nodes, e.g. `127.0.0.1:21001`
+    #[serde(default)]
+    pub meta_service_addr: Vec<String>,
+    /// Credential shared by the meta nodes and the data nodes, required by the meta service
+    #[serde(default)]
+    pub meta_service_token: String,
+    /// CA verifying the meta nodes, which are connected by https once set
+    #[serde(default)]
+    pub meta_service_ca_certificate: Option<String>,
+    /// Max bytes of the writes buffered for each unavailable replica
+    #[serde(default = "ClusterConfig::default_hinted_handoff_max_size")]
+    pub hinted_handoff_max_size: u64,
+    /// Max bytes per second of the points copied to move vnodes between data nodes
+    #[serde(default = "ClusterConfig::default_rebalance_rate_limit")]
+    pub rebalance_rate_limit: u64,
+}
+
+impl Default for ClusterConfig {
+    fn default() -> Self {
+        Self {
+            node_id: 0,
+            meta_service_addr: vec![],
+            meta_service_token: String::new(),
+            meta_service_ca_certificate: None,
+            hinted_handoff_max_size: Self::default_hinted_handoff_max_size(),
+            rebalance_rate_limit: Self::default_rebalance_rate_limit(),
+        }
+    }
+}
+
+impl ClusterConfig {
+    fn default_hinted_handoff_max_size() -> u64 {
+        1024 * 1024 * 1024
+    }
+
+    fn default_rebalance_rate_limit() -> u64 {
+        16 * 1024 * 1024
+    }
+
+    pub fn is_standalone(&self) -> bool {
+        self.meta_service_addr.is_empty()
+    }
+
+    pub fn override_by_env(&mut self) {
+        if let Ok(id) = std::env::var("CNOSDB_NODE_ID") {
+            self.node_id = id.parse::<u64>().unwrap();
+        }
+        if let Ok(addrs) = std::env::var("CNOSDB_META_SERVICE_ADDR") {
+            self.meta_service_addr = addrs.split(',').map(|e| e.trim().to_string()).collect();
+        }
+        if let Ok(token) = std::env::var("CNOSDB_CLUSTER_TOKEN") {
+            self.meta_service_token = token;
+        }
+    }
+}
+
+/// Replicates the writes of a primary cluster in another datacenter into this one,
+/// disabled if no primary is configured
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DcReplicationConfig {
+    /// Grpc addresses of every data node of the primary cluster, e.g. `10.0.1.1:31005`,
+    /// the writes of the nodes not listed are not replicated
+    #[serde(default)]
+    pub primary_grpc_addr: Vec<String>,
+    /// Databases replicated
+    #[serde(default)]
+    pub databases: Vec<String>,
+    /// Milliseconds between the polls of the writes of the primary
+    #[serde(default = "DcReplicationConfig::default_poll_interval_ms")]
+    pub poll_interval_ms: u64,
+    /// User of the primary cluster the writes are polled as
+    #[serde(default = "DcReplicationConfig::default_user")]
+    pub user: String,
+    #[serde(default)]
+    pub password: String,
+}
+
+impl Default for DcReplicationConfig {
+    fn default() -> Self {
+        Self {
+            primary_grpc_addr: vec![],
+            databases: vec![],
+            poll_interval_ms: Self::default_poll_interval_ms(),
+            user: Self::default_user(),
+            password: String::new(),
+        }
+    }
+}
+
+impl DcReplicationConfig {
+    fn default_poll_interval_ms() -> u64 {
+        1000
+    }
+
+    fn default_user() -> String {
+        "cnosdb".to_string()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.primary_grpc_addr.is_empty() && !self.databases.is_empty()
+    }
+}
+
+/// Records the ddl statements and the authentications to an append-only file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "AuditConfig::default_path")]
+    pub path: String,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: Self::default_path(),
+        }
+    }
+}
+
+impl AuditConfig {
+    fn default_path() -> String {
+        "data/audit".to_string()
+    }
+}
+
+/// Token bucket limits of the http requests, 0 for unlimited, a request exceeding
+/// a limit is rejected by `429 Too Many Requests` with `Retry-After`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateLimitConfig {
+    /// Limits of each user without limits of its own
+    #[serde(default)]
+    pub default_user: RateLimits,
+    /// Limits of each user by the name of it
+    #[serde(default)]
+    pub users: BTreeMap<String, RateLimits>,
+    /// Limits shared by all of the users of a tenant, by the name of it
+    #[serde(default)]
+    pub tenants: BTreeMap<String, RateLimits>,
+}
+
+impl RateLimitConfig {
+    pub fn user(&self, user: &str) -> RateLimits {
+        self.users.get(user).copied().unwrap_or(self.default_user)
+    }
+
+    pub fn tenant(&self, tenant: &str) -> RateLimits {
+        self.tenants.get(tenant).copied().unwrap_or_default()
+    }
+}
+
+/// Rates per second, up to a second of them may be used in a burst
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateLimits {
+    #[serde(default)]
+    pub write_points_per_sec: u64,
+    #[serde(default)]
+    pub write_bytes_per_sec: u64,
+    #[serde(default)]
+    pub queries_per_sec: u64,
+}
+
+/// Quotas of the data of the tenants stored on this node, a write of a tenant over
+/// its quota is rejected by `429 Too Many Requests`. The points written per second
+/// of a tenant are limited by `rate_limit.tenants`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaConfig {
+    /// Quota of each tenant by the name of it, unlimited if absent
+    #[serde(default)]
+    pub tenants: BTreeMap<String, TenantQuota>,
+}
+
+impl QuotaConfig {
+    pub fn tenant(&self, tenant: &str) -> TenantQuota {
+        self.tenants.get(tenant).copied().unwrap_or_default()
+    }
+}
+
+/// 0 for unlimited
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TenantQuota {
+    /// Bytes of the files and the caches of the databases of the tenant
+    #[serde(default)]
+    pub max_stored_bytes: u64,
+}
+
+/// Limits of the tags of the points written, 0 for unlimited, a line with tags over
+/// the limits is rejected as a failed line of the write, the grpc and arrow writes
+/// with points over the limits are rejected as a whole
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TagLimitsConfig {
+    #[serde(default = "TagLimitsConfig::default_max_tags_per_point")]
+    pub max_tags_per_point: usize,
+    /// Bytes of a tag key
+    #[serde(default = "TagLimitsConfig::default_max_tag_key_len")]
+    pub max_tag_key_len: usize,
+    /// Bytes of a tag value
+    #[serde(default = "TagLimitsConfig::default_max_tag_value_len")]
+    pub max_tag_value_len: usize,
+    #[serde(default)]
+    pub charset: TagCharset,
+}
+
+impl Default for TagLimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_tags_per_point: Self::default_max_tags_per_point(),
+            max_tag_key_len: Self::default_max_tag_key_len(),
+            max_tag_value_len: Self::default_max_tag_value_len(),
+            charset: TagCharset::default(),
+        }
+    }
+}
+
+impl TagLimitsConfig {
+    fn default_max_tags_per_point() -> usize {
+        128
+    }
+
+    fn default_max_tag_key_len() -> usize {
+        256
+    }
+
+    fn default_max_tag_value_len() -> usize {
+        4096
+    }
+}
+
+/// Characters allowed in the tag keys and values
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TagCharset {
+    /// Any UTF-8 characters
+    #[default]
+    Any,
+    /// UTF-8 characters except the control characters
+    Printable,
+    /// Printable ASCII characters
+    Ascii,
+}
+
+/// Budget of the memory shared by the memcaches, the caches and the queries, the
+/// subsystems over their shares are asked to free memory once it is exceeded, by
+/// flushing the memcaches early and evicting the caches. The queries are limited
+/// to their share, the operators spill or fail beyond it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MemoryConfig {
+    /// Bytes of the budget, 0 to disable
+    #[serde(default)]
+    pub limit: u64,
+    #[serde(default = "MemoryConfig::default_memcache_share")]
+    pub memcache_share: f64,
+    #[serde(default = "MemoryConfig::default_cache_share")]
+    pub cache_share: f64,
+    #[serde(default = "MemoryConfig::default_query_share")]
+    pub query_share: f64,
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            limit: 0,
+            memcache_share: Self::default_memcache_share(),
+            cache_share: Self::default_cache_share(),
+            query_share: Self::default_query_share(),
+        }
+    }
+}
+
+impl MemoryConfig {
+    fn default_memcache_share() -> f64 {
+        0.4
+    }
+
+    fn default_cache_share() -> f64 {
+        0.2
+    }
+
+    fn default_query_share() -> f64 {
+        0.4
+    }
+
+    /// Bytes of the share of the queries, 0 for unlimited
+    pub fn query_limit(&self) -> u64 {
+        (self.limit as f64 * self.query_share) as u64
+    }
+}
+
+pub fn get_config(path: &str) -> Config {
+    let config = match read_config(path) {
         Ok(config) => config,
-        Err(err) => panic!("Failed to parse configurtion file '{}': {}", path, err),
+        Err(err) => panic!("{}", err),
     };
     info!("Start with configuration: {:#?}", config);
     config
 }
 
+/// Same as `get_config`, the errors are returned, e.g. to reload the configuration
+pub fn read_config(path: &str) -> Result<Config, String> {
+    let mut file = File::open(path)
+        .map_err(|err| format!("Failed to open configurtion file '{}': {}", path, err))?;
+    let mut content = String::new();
+    file.read_to_string(&mut content)
+        .map_err(|err| format!("Failed to read configurtion file '{}': {}", path, err))?;
+    let config: Config = toml::from_str(&content)
+        .map_err(|err| format!("Failed to parse configurtion file '{}': {}", path, err))?;
+    config
+        .check()
+        .map_err(|err| format!("Invalid configurtion file '{}': {}", path, err))?;
+    Ok(config)
+}
+
 #[test]
 fn test() {
     let config_str = r#"
@@ -209,9 +1209,248 @@ level = 'info'
 path = 'data/log'
 
 [security]
+[security.jwt]
+issuer = 'https://idp.example.com/'
+jwks_url = 'https://idp.example.com/.well-known/jwks.json'
+[security.ldap]
+url = 'ldap://ldap.example.com:389'
+user_base_dn = 'ou=people,dc=example,dc=com'
+[security.oidc]
+issuer = 'https://idp.example.com'
+[security.cors]
+allowed_origins = ['https://dashboard.example.com']
+
+[object_store.s3]
+region = 'us-east-1'
+endpoint = 'http://localhost:9000'
+access_key_id = 'minioadmin'
+secret_access_key = 'minioadmin'
+allow_http = true
+
+[cluster]
+node_id = 1
+meta_service_addr = ['127.0.0.1:21001', '127.0.0.1:21002']
+
+[dc_replication]
+primary_grpc_addr = ['10.0.1.1:31005']
+databases = ['public']
+
+[rate_limit.default_user]
+queries_per_sec = 100
+[rate_limit.users.ingest]
+write_points_per_sec = 1000000
+write_bytes_per_sec = 104857600
+[rate_limit.tenants.cnosdb]
+queries_per_sec = 1000
+
+[quota.tenants.cnosdb]
+max_stored_bytes = 1073741824
+
+[tag_limits]
+max_tag_value_len = 1024
+charset = 'printable'
 
 "#;
 
     let config: Config = toml::from_str(config_str).unwrap();
-    dbg!(config);
+    assert!(config.object_store.s3.unwrap().allow_http);
+    assert!(config.object_store.gcs.is_none());
+    assert_eq!(config.cluster.node_id, 1);
+    assert_eq!(config.cluster.meta_service_addr.len(), 2);
+    assert!(!config.cluster.is_standalone());
+    assert_eq!(config.cluster.hinted_handoff_max_size, 1024 * 1024 * 1024);
+    assert_eq!(config.cluster.rebalance_rate_limit, 16 * 1024 * 1024);
+    assert!(config.dc_replication.is_enabled());
+    assert_eq!(config.dc_replication.poll_interval_ms, 1000);
+    assert!(config.log.otlp_endpoint.is_none());
+    assert_eq!(config.audit, AuditConfig::default());
+    assert_eq!(config.query.slow_query_threshold_ms, 5000);
+    assert_eq!(config.query.slow_write_threshold_ms, 1000);
+    assert_eq!(config.query.plan_cache_capacity, 1024);
+    assert_eq!(config.query.async_result_ttl_secs, 3600);
+    assert_eq!(config.query.max_async_queries_per_user, 16);
+    assert_eq!(
+        config.query.max_async_result_bytes_per_user,
+        10 * 1024 * 1024 * 1024
+    );
+    assert_eq!(config.query.max_connections_per_user, 0);
+    assert_eq!(config.query.idle_session_timeout_secs, 900);
+    assert_eq!(config.cache.max_flush_pending_size, 2 * 1024 * 1024 * 1024);
+    assert_eq!(config.cache.write_stall_timeout_ms, 10000);
+    assert!(config.security.auth_enabled);
+    assert_eq!(config.security.admin_user, "cnosdb");
+    let jwt = config.security.jwt.as_ref().unwrap();
+    assert_eq!(jwt.issuer.as_deref(), Some("https://idp.example.com/"));
+    assert!(jwt.audience.is_none());
+    assert_eq!(jwt.jwks_refresh_interval_secs, 300);
+    assert_eq!(jwt.user_claim, "sub");
+    let ldap = config.security.ldap.as_ref().unwrap();
+    assert_eq!(ldap.user_filter, "(uid={username})");
+    assert_eq!(ldap.group_attribute, "memberOf");
+    assert!(!ldap.starttls);
+    let oidc = config.security.oidc.as_ref().unwrap();
+    assert_eq!(oidc.user_claim, "preferred_username");
+    assert_eq!(oidc.groups_claim, "groups");
+    let cors = config.security.cors.as_ref().unwrap();
+    assert!(!cors.allows_any_origin());
+    assert_eq!(cors.allowed_methods, vec!["GET", "POST", "DELETE"]);
+    assert_eq!(cors.max_age_secs, 600);
+    assert_eq!(config.dc_replication.user, "cnosdb");
+    let rate_limit = &config.rate_limit;
+    assert_eq!(rate_limit.user("root").queries_per_sec, 100);
+    assert_eq!(rate_limit.user("ingest").queries_per_sec, 0);
+    assert_eq!(rate_limit.user("ingest").write_points_per_sec, 1000000);
+    assert_eq!(rate_limit.tenant("cnosdb").queries_per_sec, 1000);
+    assert_eq!(rate_limit.tenant("other"), RateLimits::default());
+    assert_eq!(config.quota.tenant("cnosdb").max_stored_bytes, 1073741824);
+    assert_eq!(config.quota.tenant("other"), TenantQuota::default());
+    assert_eq!(config.tag_limits.max_tags_per_point, 128);
+    assert_eq!(config.tag_limits.max_tag_value_len, 1024);
+    assert_eq!(config.tag_limits.charset, TagCharset::Printable);
+}
+
+#[test]
+fn test_check_compression() {
+    let config_str = r#"
+fast_codec = 'LZ4'
+[databases.db0]
+strong_codec = 'Zstd'
+"#;
+
+    let mut config: CompressionConfig = toml::from_str(config_str).unwrap();
+    assert_eq!(
+        config.check().unwrap_err(),
+        "storage.compression: 'LZ4' is not a codec of the strings, \
+        expected one of null, gzip, bzip, zstd, snappy, zlib"
+    );
+    config.levels.fast_codec = "snappy".to_string();
+    assert!(config.check().is_ok());
+    config.databases.get_mut("db0").unwrap().strong_codec = "default".to_string();
+    assert!(config
+        .check()
+        .unwrap_err()
+        .starts_with("storage.compression.databases.db0: 'default'"));
+}
+
+#[test]
+fn test_check_cors() {
+    let config_str = r#"
+allowed_origins = ['https://dashboard.example.com', 'http://[::1]:8080', 'http://localhost:3000']
+"#;
+
+    let mut config: CorsConfig = toml::from_str(config_str).unwrap();
+    assert!(config.check().is_ok());
+
+    config.allowed_origins = vec!["*".to_string()];
+    assert!(config.check().is_ok());
+    config.allow_credentials = true;
+    assert_eq!(
+        config.check().unwrap_err(),
+        "'*' may not be allowed with allow_credentials"
+    );
+    config.allow_credentials = false;
+
+    for origin in [
+        "dashboard.example.com",
+        "https://dashboard.example.com/",
+        "https://dashboard.example.com:abc",
+        "https://",
+        "null",
+    ] {
+        config.allowed_origins = vec![origin.to_string()];
+        assert!(config.check().is_err(), "{}", origin);
+    }
+    config.allowed_origins = vec!["*".to_string()];
+
+    config.allowed_headers = vec!["x cnosdb".to_string()];
+    assert_eq!(
+        config.check().unwrap_err(),
+        "'x cnosdb' is not a header name"
+    );
+    config.allowed_headers = vec![];
+    config.allowed_methods = vec!["GET".to_string(), "".to_string()];
+    assert_eq!(config.check().unwrap_err(), "'' is not a method");
+}
+
+#[test]
+fn test_tls_config() {
+    let config_str = r#"
+[tls_config]
+certificate = './config/tls/server.crt'
+private_key = './config/tls/server.key'
+ca_certificate = './config/tls/ca.crt'
+"#;
+
+    let config: SecurityConfig = toml::from_str(config_str).unwrap();
+    let tls_config = config.tls_config.unwrap();
+    assert_eq!(tls_config.certificate, "./config/tls/server.crt");
+    assert_eq!(
+        tls_config.ca_certificate.as_deref(),
+        Some("./config/tls/ca.crt")
+    );
+    assert!(!tls_config.require_client_cert);
+    assert!(tls_config.server_name.is_none());
+}
+
+#[test]
+fn test_reload() {
+    let config_str = r#"
+[query]
+max_server_connections = 10240
+query_sql_limit = 16777216
+write_sql_limit = 167772160
+[storage]
+path = 'data/db'
+max_summary_size = 134217728
+max_level = 4
+base_file_size = 16777216
+compact_trigger = 4
+max_compact_size = 2147483648
+dio_max_resident = 1024
+dio_max_non_resident = 1024
+dio_page_len_scale = 1
+strict_write = true
+[wal]
+enabled = true
+path = 'data/wal'
+sync = true
+[cache]
+max_buffer_size = 1048576
+max_immutable_number = 4
+[log]
+level = 'info'
+path = 'data/log'
+[security]
+"#;
+
+    let mut config: Config = toml::from_str(config_str).unwrap();
+    assert_eq!(config.reload(&config.clone()), ConfigChanges::default());
+
+    let mut new = config.clone();
+    new.log.level = "debug".to_string();
+    new.cache.max_buffer_size = 2097152;
+    new.storage.path = "data/db2".to_string();
+    new.cluster.meta_service_addr = vec!["127.0.0.1:21001".to_string()];
+    new.rate_limit.default_user.queries_per_sec = 10;
+    let changes = config.reload(&new);
+    assert_eq!(
+        changes.reloaded,
+        vec![
+            "cache.max_buffer_size",
+            "log.level",
+            "rate_limit.default_user.queries_per_sec"
+        ]
+    );
+    assert_eq!(
+        changes.restart_required,
+        vec!["cluster.meta_service_addr", "storage.path"]
+    );
+    assert_eq!(config.log.level, "debug");
+    assert_eq!(config.storage.path, "data/db");
+    assert_eq!(config.rate_limit.default_user.queries_per_sec, 10);
+
+    // Not applied until a restart
+    let changes = config.reload(&new);
+    assert!(changes.reloaded.is_empty());
+    assert_eq!(changes.restart_required.len(), 2);
 }
@@ -0,0 +1,326 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
+
+use crate::{flatten, is_reloadable, read_config, Config, ConfigChanges};
+
+pub type SettingsRef = Arc<Settings>;
+
+type Listener = Box<dyn Fn(&Config) + Send + Sync>;
+
+/// A setting of `SHOW SETTINGS`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Setting {
+    /// `<section>.<key>`
+    pub name: String,
+    pub value: String,
+    /// Changeable on the fly by `ALTER SYSTEM SET`
+    pub reloadable: bool,
+}
+
+/// Configuration of the running server.
+///
+/// The reloadable settings are changed on the fly by reloading the configuration
+/// file or by `ALTER SYSTEM SET`, the latter are persisted to an overrides file
+/// applied over the configuration file, so they survive the reloads and restarts.
+pub struct Settings {
+    /// Configuration file
+    path: Option<String>,
+    overrides_path: Option<PathBuf>,
+    overrides: Mutex<BTreeMap<String, toml::Value>>,
+    /// Settings in effect
+    config: RwLock<Config>,
+    listeners: RwLock<Vec<Listener>>,
+}
+
+impl Settings {
+    /// Neither reloaded nor persisted
+    pub fn memory(config: Config) -> Self {
+        Self {
+            path: None,
+            overrides_path: None,
+            overrides: Mutex::new(BTreeMap::new()),
+            config: RwLock::new(config),
+            listeners: RwLock::new(vec![]),
+        }
+    }
+
+    /// The settings overridden before are applied over the configuration read from
+    /// the file of the path
+    pub fn open(
+        path: &str,
+        config: Config,
+        overrides_path: impl Into<PathBuf>,
+    ) -> Result<Self, String> {
+        let overrides_path = overrides_path.into();
+        let overrides = match std::fs::read_to_string(&overrides_path) {
+            Ok(content) => toml::from_str(&content).map_err(|e| {
+                format!(
+                    "Failed to parse settings overrides '{}': {}",
+                    overrides_path.display(),
+                    e
+                )
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => {
+                return Err(format!(
+                    "Failed to read settings overrides '{}': {}",
+                    overrides_path.display(),
+                    e
+                ))
+            }
+        };
+        let config = apply_overrides(config, &overrides)?;
+
+        Ok(Self {
+            path: Some(path.to_string()),
+            overrides_path: Some(overrides_path),
+            overrides: Mutex::new(overrides),
+            config: RwLock::new(config),
+            listeners: RwLock::new(vec![]),
+        })
+    }
+
+    pub fn config(&self) -> Config {
+        self.config.read().unwrap().clone()
+    }
+
+    /// Called with the settings in effect once the reloadable ones are changed
+    pub fn on_change(&self, listener: impl Fn(&Config) + Send + Sync + 'static) {
+        self.listeners.write().unwrap().push(Box::new(listener));
+    }
+
+    pub fn show(&self) -> Vec<Setting> {
+        flatten(&self.config.read().unwrap())
+            .into_iter()
+            .map(|(name, value)| {
+                let value = if is_secret(&name) {
+                    "******".to_string()
+                } else {
+                    match value {
+                        toml::Value::String(s) => s,
+                        v => v.to_string(),
+                    }
+                };
+                Setting {
+                    reloadable: is_reloadable(&name),
+                    name,
+                    value,
+                }
+            })
+            .collect()
+    }
+
+    /// Changes a reloadable setting, e.g. `set("cache.max_buffer_size", "134217728")`
+    pub fn set(&self, name: &str, value: &str) -> Result<(), String> {
+        if !is_reloadable(name) {
+            return if flatten(&self.config.read().unwrap()).contains_key(name) {
+                Err(format!(
+                    "Setting {} takes effect after a restart, change it in the configuration file",
+                    name
+                ))
+            } else {
+                Err(format!("Unknown setting {}", name))
+            };
+        }
+
+        let mut overrides = self.overrides.lock().unwrap();
+        let value = parse_value(value);
+        let new = with_value(&self.config.read().unwrap(), name, value.clone())?;
+        validate(&new)?;
+
+        // Nothing is changed unless persisted
+        let mut new_overrides = overrides.clone();
+        new_overrides.insert(name.to_string(), value);
+        if let Some(path) = &self.overrides_path {
+            let content = toml::to_string(&new_overrides).map_err(|e| e.to_string())?;
+            let tmp = path.with_extension("tmp");
+            std::fs::write(&tmp, content)
+                .and_then(|_| std::fs::rename(&tmp, path))
+                .map_err(|e| {
+                    format!(
+                        "Failed to persist settings overrides '{}': {}",
+                        path.display(),
+                        e
+                    )
+                })?;
+        }
+        *overrides = new_overrides;
+
+        *self.config.write().unwrap() = new.clone();
+        self.notify(&new);
+        Ok(())
+    }
+
+    /// Reads the configuration file again, the overrides are applied over it
+    pub fn reload(&self) -> Result<ConfigChanges, String> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(ConfigChanges::default()),
+        };
+        let overrides = self.overrides.lock().unwrap();
+        let new = apply_overrides(read_config(path)?, &overrides)?;
+        validate(&new)?;
+
+        let (changes, config) = {
+            let mut config = self.config.write().unwrap();
+            (config.reload(&new), config.clone())
+        };
+        self.notify(&config);
+        Ok(changes)
+    }
+
+    fn notify(&self, config: &Config) {
+        for listener in self.listeners.read().unwrap().iter() {
+            listener(config);
+        }
+    }
+}
+
+fn apply_overrides(
+    mut config: Config,
+    overrides: &BTreeMap<String, toml::Value>,
+) -> Result<Config, String> {
+    for (name, value) in overrides {
+        config = with_value(&config, name, value.clone())?;
+    }
+    Ok(config)
+}
+
+/// Copy of the configuration with the setting `<section>.<key>` changed
+fn with_value(config: &Config, name: &str, value: toml::Value) -> Result<Config, String> {
+    let mut root = toml::Value::try_from(config).map_err(|e| e.to_string())?;
+    let (section, key) = name
+        .split_once('.')
+        .ok_or_else(|| format!("Unknown setting {}", name))?;
+    let mut table = match root.get_mut(section).and_then(|v| v.as_table_mut()) {
+        Some(table) => table,
+        None => return Err(format!("Unknown setting {}", name)),
+    };
+    // The tables of a nested setting, e.g. `rate_limit.users.<user>.queries_per_sec`
+    let (tables, key) = match key.rsplit_once('.') {
+        Some((tables, key)) => (tables.split('.').collect(), key),
+        None => (vec![], key),
+    };
+    for t in tables {
+        table = table
+            .entry(t.to_string())
+            .or_insert_with(|| toml::Value::Table(Default::default()))
+            .as_table_mut()
+            .ok_or_else(|| format!("Unknown setting {}", name))?;
+    }
+    table.insert(key.to_string(), value);
+    let config: Config = root
+        .try_into()
+        .map_err(|e| format!("Invalid value of setting {}: {}", name, e))?;
+    if !flatten(&config).contains_key(name) {
+        return Err(format!("Unknown setting {}", name));
+    }
+    Ok(config)
+}
+
+/// A toml value, e.g. `1024` or `true`, the others are strings, e.g. `debug`
+fn parse_value(value: &str) -> toml::Value {
+    format!("v = {}", value)
+        .parse::<toml::Value>()
+        .ok()
+        .and_then(|v| v.get("v").cloned())
+        .unwrap_or_else(|| toml::Value::String(value.to_string()))
+}
+
+fn validate(config: &Config) -> Result<(), String> {
+    trace::validate_log_level(&config.log.level)
+        .map_err(|e| format!("Invalid value of setting log.level: {}", e))?;
+    let memory = &config.memory;
+    let shares = [
+        memory.memcache_share,
+        memory.cache_share,
+        memory.query_share,
+    ];
+    if shares.iter().any(|e| !(0.0..=1.0).contains(e)) || shares.iter().sum::<f64>() > 1.0 + 1e-6 {
+        return Err(
+            "Invalid value of settings memory.*_share: between 0 and 1, and at most 1 in total"
+                .to_string(),
+        );
+    }
+    if config.storage.max_concurrent_compactions == 0 {
+        return Err(
+            "Invalid value of setting storage.max_concurrent_compactions: at least 1".to_string(),
+        );
+    }
+    config
+        .check()
+        .map_err(|e| format!("Invalid value of setting {}", e))
+}
+
+fn is_secret(name: &str) -> bool {
+    name.contains("secret")
+        || name.contains("token")
+        || name.contains("password")
+        || name.ends_with("access_key")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_settings() {
+        let dir = tempfile::tempdir().unwrap();
+        let overrides_path = dir.path().join("settings.toml");
+        let config = crate::get_config("config.toml");
+
+        let settings = Settings::open("config.toml", config.clone(), &overrides_path).unwrap();
+        let changed = Arc::new(Mutex::new(0_u64));
+        let c = changed.clone();
+        settings.on_change(move |config| *c.lock().unwrap() = config.cache.max_buffer_size);
+
+        settings.set("cache.max_buffer_size", "2097152").unwrap();
+        assert_eq!(*changed.lock().unwrap(), 2097152);
+        settings.set("log.level", "debug").unwrap();
+        assert!(settings.set("log.level", "debug=what").is_err());
+        assert!(settings.set("cache.max_buffer_size", "'big'").is_err());
+        assert!(settings.set("storage.path", "/tmp").is_err());
+        settings
+            .set("storage.max_compact_mb_per_sec", "64")
+            .unwrap();
+        assert!(settings
+            .set("storage.max_concurrent_compactions", "0")
+            .is_err());
+        assert!(settings.set("cache.unknown", "1").is_err());
+        settings
+            .set("rate_limit.users.ingest.write_points_per_sec", "1000")
+            .unwrap();
+        assert_eq!(
+            settings
+                .config()
+                .rate_limit
+                .user("ingest")
+                .write_points_per_sec,
+            1000
+        );
+        assert!(settings
+            .set("rate_limit.users.ingest.unknown", "1")
+            .is_err());
+
+        let shown = settings.show();
+        let level = shown.iter().find(|e| e.name == "log.level").unwrap();
+        assert_eq!(level.value, "debug");
+        assert!(level.reloadable);
+        assert!(is_secret("security.ldap.bind_password"));
+        assert!(is_secret("query.admin_password"));
+
+        // Applied over the configuration file
+        let settings = Settings::open("config.toml", config, &overrides_path).unwrap();
+        assert_eq!(settings.config().cache.max_buffer_size, 2097152);
+        assert!(settings.reload().unwrap().reloaded.is_empty());
+        assert_eq!(settings.config().log.level, "debug");
+
+        // Not changed unless persisted
+        let missing = dir.path().join("missing").join("settings.toml");
+        let settings = Settings::open("config.toml", settings.config(), missing).unwrap();
+        assert!(settings.set("log.level", "warn").is_err());
+        assert_eq!(settings.config().log.level, "debug");
+        assert!(settings.overrides.lock().unwrap().is_empty());
+    }
+}
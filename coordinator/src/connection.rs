@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use config::TLSConfig;
+use meta::meta_client::MetaClientRef;
+use models::auth::basic_auth;
+use models::meta_data::NodeId;
+use parking_lot::RwLock;
+use protos::kv_service::tskv_service_client::TskvServiceClient;
+use tonic::metadata::{Ascii, MetadataValue};
+use tonic::transport::{Certificate, Channel, ClientTlsConfig, Endpoint, Identity};
+use tonic::Request;
+
+use crate::errors::{CoordinatorError, Result};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Grpc channels to the data nodes, a node is connected on the first use
+#[derive(Debug)]
+pub struct NodeConnections {
+    meta: MetaClientRef,
+    channels: RwLock<HashMap<NodeId, Channel>>,
+    /// Credentials the requests to the data nodes are authenticated by
+    authorization: Option<MetadataValue<Ascii>>,
+    tls: Option<ClientTlsConfig>,
+}
+
+impl NodeConnections {
+    pub fn new(meta: MetaClientRef) -> Self {
+        Self {
+            meta,
+            channels: RwLock::new(HashMap::new()),
+            authorization: None,
+            tls: None,
+        }
+    }
+
+    /// Connects to the data nodes by TLS
+    pub fn with_tls(mut self, tls: Option<ClientTlsConfig>) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    pub fn with_credentials(mut self, user: &str, password: &str) -> Self {
+        self.authorization = Some(auth_metadata(user, password));
+        self
+    }
+
+    pub fn meta(&self) -> MetaClientRef {
+        self.meta.clone()
+    }
+
+    pub async fn client(&self, id: NodeId) -> Result<TskvServiceClient<Channel>> {
+        if let Some(channel) = self.channels.read().get(&id).cloned() {
+            return Ok(TskvServiceClient::new(channel));
+        }
+
+        let node = self
+            .meta
+            .data_nodes()
+            .await?
+            .into_iter()
+            .find(|e| e.id == id)
+            .ok_or(CoordinatorError::NodeNotFound { id })?;
+        let connect_err = |e: tonic::transport::Error| CoordinatorError::Connect {
+            addr: node.grpc_addr.clone(),
+            msg: e.to_string(),
+        };
+        let channel = endpoint(&node.grpc_addr, self.tls.as_ref())
+            .map_err(connect_err)?
+            .connect_timeout(CONNECT_TIMEOUT)
+            .connect()
+            .await
+            .map_err(connect_err)?;
+
+        self.channels.write().insert(id, channel.clone());
+        Ok(TskvServiceClient::new(channel))
+    }
+
+    /// Request to a data node, authenticated by the credentials if set
+    pub fn request<T>(&self, message: T) -> Request<T> {
+        let mut request = Request::new(message);
+        if let Some(authorization) = self.authorization.clone() {
+            request
+                .metadata_mut()
+                .insert("authorization", authorization);
+        }
+        request
+    }
+
+    /// Drops the channel of an unreachable node, it is connected again on the next use
+    pub fn invalidate(&self, id: NodeId) {
+        self.channels.write().remove(&id);
+    }
+}
+
+/// Metadata of the basic authentication
+pub fn auth_metadata(user: &str, password: &str) -> MetadataValue<Ascii> {
+    MetadataValue::from_str(&basic_auth(user, password)).expect("base64 is ascii")
+}
+
+/// Endpoint of the grpc address, https if the TLS is configured
+pub fn endpoint(
+    addr: &str,
+    tls: Option<&ClientTlsConfig>,
+) -> std::result::Result<Endpoint, tonic::transport::Error> {
+    match tls {
+        Some(tls) => Endpoint::from_shared(format!("https://{}", addr))?.tls_config(tls.clone()),
+        None => Endpoint::from_shared(format!("http://{}", addr)),
+    }
+}
+
+/// TLS of the connections to the data nodes, the servers are verified by the CA,
+/// the certificate of this node is presented in case the client is verified
+pub fn client_tls_config(config: &TLSConfig) -> std::io::Result<ClientTlsConfig> {
+    let identity = Identity::from_pem(
+        std::fs::read(&config.certificate)?,
+        std::fs::read(&config.private_key)?,
+    );
+    let mut tls = ClientTlsConfig::new().identity(identity);
+    if let Some(ca_certificate) = &config.ca_certificate {
+        tls = tls.ca_certificate(Certificate::from_pem(std::fs::read(ca_certificate)?));
+    }
+    if let Some(server_name) = &config.server_name {
+        tls = tls.domain_name(server_name);
+    }
+    Ok(tls)
+}
@@ -0,0 +1,503 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use config::DcReplicationConfig;
+use metrics::{incr_dc_replication_applied, set_dc_replication_lag};
+use models::consistency_level::ConsistencyLevel;
+use models::meta_data::{NodeId, DEFAULT_TENANT};
+use parking_lot::Mutex;
+use protos::kv_service::tskv_service_client::TskvServiceClient;
+use protos::kv_service::{FetchChangesRequest, WritePointsRpcRequest};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use snafu::ResultExt;
+use tonic::metadata::{Ascii, MetadataValue};
+use tonic::transport::{Channel, ClientTlsConfig};
+use tonic::Request;
+use trace::{error, info, warn};
+use tskv::cdc::ChangeOffset;
+use tskv::engine::EngineRef;
+
+use crate::connection::{auth_metadata, endpoint, NodeConnections};
+use crate::errors::{CoordinatorError, IoSnafu, Result};
+use crate::replica_sync::SyncCursors;
+use crate::service::{Coordinator, CoordinatorRef};
+use crate::writer::{WriteAck, DEFAULT_WRITE_CONSISTENCY};
+
+const FETCH_LIMIT: u32 = 1024;
+/// Writes of a database remembered to apply once those fetched from several nodes
+const RECENT_WRITES: usize = 1 << 16;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+const PROMOTED_FILE: &str = "PROMOTED";
+
+/// Replicates the writes of a primary cluster in another datacenter into this one
+/// for disaster recovery.
+///
+/// The standby polls the committed writes of each replicated database from the wal
+/// of every data node of the primary, including those replayed from other replicas,
+/// and writes them by its own coordinator, so they are routed to the shards of this
+/// cluster. The position read in each wal is persisted. A write fetched from several
+/// replicas of a shard of the primary is applied once if fetched within the last
+/// `RECENT_WRITES` writes of the database, the writes are idempotent anyway.
+///
+/// The writes of the clients are rejected by a standby, see [`StandbyCoordinator`].
+/// Once the primary is lost, the standby is promoted: the replication stops for good
+/// and the standby takes writes.
+#[derive(Debug)]
+pub struct DcReplication {
+    coord: CoordinatorRef,
+    primary: Vec<String>,
+    databases: Vec<String>,
+    interval: Duration,
+    dir: PathBuf,
+    cursors: SyncCursors,
+    channels: Mutex<HashMap<String, Channel>>,
+    /// Credentials of the user of the primary
+    authorization: MetadataValue<Ascii>,
+    tls: Option<ClientTlsConfig>,
+    links: Mutex<HashMap<(String, String), LinkState>>,
+    recent: Mutex<HashMap<String, RecentWrites>>,
+    promoted: AtomicBool,
+}
+
+#[derive(Debug)]
+struct LinkState {
+    applied: u64,
+    /// Last time all the writes of the primary were replicated, the replication
+    /// is started if never
+    caught_up_at: Instant,
+    gaps: u64,
+    error: Option<String>,
+}
+
+/// Digests of the writes of a database applied lately, in the order applied
+#[derive(Debug, Default)]
+struct RecentWrites {
+    digests: HashSet<[u8; 32]>,
+    order: VecDeque<[u8; 32]>,
+}
+
+/// Result of a replication from a data node of the primary
+struct Replicated {
+    applied: u64,
+    /// Whether all the writes committed are replicated
+    caught_up: bool,
+    /// Whether the wal of the primary is removed before replicated
+    truncated: bool,
+}
+
+/// Replication from a data node of the primary of a database
+#[derive(Debug, Clone, Serialize)]
+pub struct LinkStatus {
+    pub primary: String,
+    pub database: String,
+    pub offset: String,
+    pub applied: u64,
+    pub lag_seconds: f64,
+    /// Times the wal of the primary was removed before it was replicated, the writes
+    /// in between are lost
+    pub gaps: u64,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplicationStatus {
+    pub promoted: bool,
+    pub links: Vec<LinkStatus>,
+}
+
+impl DcReplication {
+    pub fn new(
+        coord: CoordinatorRef,
+        config: &DcReplicationConfig,
+        dir: impl AsRef<Path>,
+    ) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        let cursors = SyncCursors::open(&dir)?;
+        let promoted = dir.join(PROMOTED_FILE).exists();
+        Ok(Self {
+            coord,
+            primary: config.primary_grpc_addr.clone(),
+            databases: config.databases.clone(),
+            interval: Duration::from_millis(config.poll_interval_ms),
+            dir,
+            cursors,
+            channels: Mutex::new(HashMap::new()),
+            authorization: auth_metadata(&config.user, &config.password),
+            tls: None,
+            links: Mutex::new(HashMap::new()),
+            recent: Mutex::new(HashMap::new()),
+            promoted: AtomicBool::new(promoted),
+        })
+    }
+
+    /// Fetches the changes of the primary by TLS
+    pub fn with_tls(mut self, tls: Option<ClientTlsConfig>) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    /// Whether the writes are replicated from the primary, the node takes no writes
+    /// of the clients meanwhile
+    pub fn is_standby(&self) -> bool {
+        !self.promoted.load(Ordering::Acquire)
+    }
+
+    /// Replicates periodically in the background until promoted
+    pub fn start(self: Arc<Self>) {
+        if !self.is_standby() {
+            info!("The node is promoted, writes of the primary are not replicated");
+            return;
+        }
+        tokio::spawn(async move {
+            while self.is_standby() {
+                self.replicate().await;
+                tokio::time::sleep(self.interval).await;
+            }
+        });
+    }
+
+    /// Replicates the writes committed by the primary since the last time
+    pub async fn replicate(&self) {
+        for primary in self.primary.iter() {
+            for db in self.databases.iter() {
+                let res = self.replicate_from(primary, db).await;
+                let mut links = self.links.lock();
+                let link = links
+                    .entry((primary.clone(), db.clone()))
+                    .or_insert_with(LinkState::new);
+                match res {
+                    Ok(replicated) => {
+                        link.applied += replicated.applied;
+                        if replicated.caught_up {
+                            link.caught_up_at = Instant::now();
+                        }
+                        if replicated.truncated {
+                            link.gaps += 1;
+                        }
+                        link.error = None;
+                    }
+                    Err(e) => {
+                        warn!("Failed to replicate {} from {}: {}", db, primary, e);
+                        link.error = Some(e.to_string());
+                    }
+                }
+                set_dc_replication_lag(primary, db, link.lag().as_secs_f64());
+            }
+        }
+    }
+
+    /// Stops the replication and takes the writes of the clients from now on, the
+    /// writes of the primary not replicated yet are fetched once more if reachable.
+    pub async fn promote(&self) -> Result<ReplicationStatus> {
+        if self.is_standby() {
+            self.replicate().await;
+            std::fs::write(self.dir.join(PROMOTED_FILE), b"").context(IoSnafu)?;
+            self.promoted.store(true, Ordering::Release);
+            info!("Promoted from a standby of {:?}", self.primary);
+        }
+        Ok(self.status())
+    }
+
+    pub fn status(&self) -> ReplicationStatus {
+        let links = self.links.lock();
+        let mut status = vec![];
+        for primary in self.primary.iter() {
+            for db in self.databases.iter() {
+                let key = (primary.clone(), db.clone());
+                let link = links.get(&key);
+                status.push(LinkStatus {
+                    primary: primary.clone(),
+                    database: db.clone(),
+                    offset: self.cursors.get(&cursor_key(primary, db)).to_string(),
+                    applied: link.map_or(0, |e| e.applied),
+                    lag_seconds: link.map_or(0.0, |e| e.lag().as_secs_f64()),
+                    gaps: link.map_or(0, |e| e.gaps),
+                    error: link.and_then(|e| e.error.clone()),
+                });
+            }
+        }
+        ReplicationStatus {
+            promoted: !self.is_standby(),
+            links: status,
+        }
+    }
+
+    /// Replicates the writes until the offset read in the wal of the primary does not
+    /// advance, the round ends earlier once the interval is elapsed
+    async fn replicate_from(&self, primary: &str, db: &str) -> Result<Replicated> {
+        let key = cursor_key(primary, db);
+        let mut offset = self.cursors.get(&key);
+        let mut client = self.client(primary).await?;
+        let started = Instant::now();
+        let mut replicated = Replicated {
+            applied: 0,
+            caught_up: false,
+            truncated: false,
+        };
+
+        loop {
+            let mut request = Request::new(FetchChangesRequest {
+                database: db.to_string(),
+                file_id: offset.file_id,
+                pos: offset.pos,
+                limit: FETCH_LIMIT,
+                include_replayed: true,
+                consumer: format!("standby.{}", self.coord.node_id()),
+            });
+            request
+                .metadata_mut()
+                .insert("authorization", self.authorization.clone());
+            let resp = client
+                .fetch_changes(request)
+                .await
+                .map_err(|e| {
+                    self.channels.lock().remove(primary);
+                    CoordinatorError::from(e)
+                })?
+                .into_inner();
+
+            if resp.truncated {
+                error!(
+                    "The wal of {} on {} is removed after offset {}, the writes in \
+                     between are not replicated",
+                    db, primary, offset
+                );
+                replicated.truncated = true;
+            }
+
+            let mut applied = 0;
+            for points in resp.points {
+                if self.is_applied(db, &points) {
+                    continue;
+                }
+                let digest = digest(&points);
+                let req = WritePointsRpcRequest { version: 1, points };
+                self.coord
+                    .write_points(DEFAULT_TENANT, DEFAULT_WRITE_CONSISTENCY, req)
+                    .await?;
+                self.recent
+                    .lock()
+                    .entry(db.to_string())
+                    .or_default()
+                    .insert(digest);
+                applied += 1;
+            }
+            incr_dc_replication_applied(primary, db, applied);
+            replicated.applied += applied;
+
+            let next = ChangeOffset::new(resp.next_file_id, resp.next_pos);
+            if next == offset {
+                replicated.caught_up = true;
+                break;
+            }
+            offset = next;
+            self.cursors.set(&key, offset)?;
+            if started.elapsed() >= self.interval.max(Duration::from_secs(1)) {
+                break;
+            }
+        }
+
+        Ok(replicated)
+    }
+
+    /// Whether the write is applied lately, fetched from another data node
+    fn is_applied(&self, db: &str, points: &[u8]) -> bool {
+        self.recent
+            .lock()
+            .get(db)
+            .map_or(false, |e| e.digests.contains(&digest(points)))
+    }
+
+    async fn client(&self, addr: &str) -> Result<TskvServiceClient<Channel>> {
+        if let Some(channel) = self.channels.lock().get(addr).cloned() {
+            return Ok(TskvServiceClient::new(channel));
+        }
+
+        let connect_err = |e: tonic::transport::Error| CoordinatorError::Connect {
+            addr: addr.to_string(),
+            msg: e.to_string(),
+        };
+        let channel = endpoint(addr, self.tls.as_ref())
+            .map_err(connect_err)?
+            .connect_timeout(CONNECT_TIMEOUT)
+            .connect()
+            .await
+            .map_err(connect_err)?;
+
+        self.channels
+            .lock()
+            .insert(addr.to_string(), channel.clone());
+        Ok(TskvServiceClient::new(channel))
+    }
+}
+
+impl LinkState {
+    fn new() -> Self {
+        Self {
+            applied: 0,
+            caught_up_at: Instant::now(),
+            gaps: 0,
+            error: None,
+        }
+    }
+
+    fn lag(&self) -> Duration {
+        self.caught_up_at.elapsed()
+    }
+}
+
+impl RecentWrites {
+    fn insert(&mut self, digest: [u8; 32]) {
+        if !self.digests.insert(digest) {
+            return;
+        }
+        self.order.push_back(digest);
+        if self.order.len() > RECENT_WRITES {
+            if let Some(oldest) = self.order.pop_front() {
+                self.digests.remove(&oldest);
+            }
+        }
+    }
+}
+
+fn digest(points: &[u8]) -> [u8; 32] {
+    Sha256::digest(points).into()
+}
+
+fn cursor_key(primary: &str, db: &str) -> String {
+    format!("{}.{}", primary, db)
+}
+
+/// The coordinator of the clients of a node, the writes of which are rejected while
+/// the cluster is a standby. The replication writes by the inner coordinator.
+#[derive(Debug)]
+pub struct StandbyCoordinator {
+    inner: CoordinatorRef,
+    replication: Arc<DcReplication>,
+}
+
+impl StandbyCoordinator {
+    pub fn new(inner: CoordinatorRef, replication: Arc<DcReplication>) -> Self {
+        Self { inner, replication }
+    }
+}
+
+#[async_trait]
+impl Coordinator for StandbyCoordinator {
+    fn node_id(&self) -> NodeId {
+        self.inner.node_id()
+    }
+
+    fn engine(&self) -> EngineRef {
+        self.inner.engine()
+    }
+
+    fn connections(&self) -> Option<Arc<NodeConnections>> {
+        self.inner.connections()
+    }
+
+    async fn write_points(
+        &self,
+        tenant: &str,
+        consistency: ConsistencyLevel,
+        req: WritePointsRpcRequest,
+    ) -> Result<WriteAck> {
+        if self.replication.is_standby() {
+            return Err(CoordinatorError::Standby);
+        }
+        self.inner.write_points(tenant, consistency, req).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tskv::engine::MockEngine;
+
+    use super::*;
+    use crate::service::LocalCoordinator;
+
+    fn coordinator() -> CoordinatorRef {
+        Arc::new(LocalCoordinator::new(Arc::new(MockEngine::default())))
+    }
+
+    fn config() -> DcReplicationConfig {
+        DcReplicationConfig {
+            // Nothing listens on the port
+            primary_grpc_addr: vec!["127.0.0.1:1".to_string()],
+            databases: vec!["db0".to_string()],
+            poll_interval_ms: 10,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_primary() {
+        let dir = tempfile::tempdir().unwrap();
+        let replication = DcReplication::new(coordinator(), &config(), dir.path()).unwrap();
+        replication.replicate().await;
+
+        let status = replication.status();
+        assert!(!status.promoted);
+        assert_eq!(status.links.len(), 1);
+        assert_eq!(status.links[0].offset, ChangeOffset::EARLIEST.to_string());
+        assert_eq!(status.links[0].applied, 0);
+        assert!(status.links[0].error.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_promote() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let replication = DcReplication::new(coordinator(), &config(), dir.path()).unwrap();
+            assert!(replication.is_standby());
+            // The primary is lost
+            assert!(replication.promote().await.unwrap().promoted);
+            assert!(!replication.is_standby());
+        }
+
+        let replication = DcReplication::new(coordinator(), &config(), dir.path()).unwrap();
+        assert!(!replication.is_standby());
+    }
+
+    #[tokio::test]
+    async fn test_standby_coordinator() {
+        let dir = tempfile::tempdir().unwrap();
+        let replication = DcReplication::new(coordinator(), &config(), dir.path()).unwrap();
+        let replication = Arc::new(replication);
+        let coord = StandbyCoordinator::new(coordinator(), replication.clone());
+        let req = || WritePointsRpcRequest {
+            version: 1,
+            points: vec![],
+        };
+
+        let res = coord
+            .write_points("cnosdb", ConsistencyLevel::One, req())
+            .await;
+        assert!(matches!(res, Err(CoordinatorError::Standby)));
+
+        replication.promote().await.unwrap();
+        let res = coord
+            .write_points("cnosdb", ConsistencyLevel::One, req())
+            .await;
+        assert!(!matches!(res, Err(CoordinatorError::Standby)));
+    }
+
+    #[test]
+    fn test_recent_writes() {
+        let mut recent = RecentWrites::default();
+        for i in 0..=RECENT_WRITES {
+            recent.insert(digest(&i.to_le_bytes()));
+        }
+        recent.insert(digest(&1_usize.to_le_bytes()));
+        assert_eq!(recent.order.len(), RECENT_WRITES);
+        assert!(!recent.digests.contains(&digest(&0_usize.to_le_bytes())));
+        assert!(recent
+            .digests
+            .contains(&digest(&RECENT_WRITES.to_le_bytes())));
+    }
+}
@@ -0,0 +1,138 @@
+use meta::error::MetaError;
+use models::define_result;
+use models::error_code::ErrorCode;
+use models::meta_data::{BucketId, NodeId, VnodeId};
+use snafu::Snafu;
+
+define_result!(CoordinatorError);
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum CoordinatorError {
+    #[snafu(display("Meta error: {}", source))]
+    Meta { source: MetaError },
+
+    #[snafu(display("Tskv error: {}", source))]
+    Tskv { source: tskv::Error },
+
+    #[snafu(display("Data node {} not found", id))]
+    NodeNotFound { id: NodeId },
+
+    #[snafu(display("Failed to connect data node {}: {}", addr, msg))]
+    Connect { addr: String, msg: String },
+
+    #[snafu(display("Grpc error: {}", msg))]
+    Grpc { code: tonic::Code, msg: String },
+
+    #[snafu(display("Invalid points: {}", msg))]
+    InvalidPoints { msg: String },
+
+    #[snafu(display(
+        "Only {} of {} replicas are written, {} required: {}",
+        success,
+        replicas,
+        required,
+        msg
+    ))]
+    NotEnoughReplicas {
+        success: usize,
+        replicas: usize,
+        required: usize,
+        msg: String,
+    },
+
+    #[snafu(display("Bucket {} has no shards", id))]
+    EmptyBucket { id: BucketId },
+
+    #[snafu(display("A rebalance of the cluster is already running"))]
+    RebalanceInProgress,
+
+    #[snafu(display("No replica of vnode {} is available to copy: {}", vnode, msg))]
+    NoSourceReplica { vnode: VnodeId, msg: String },
+
+    #[snafu(display("The cluster is a standby of the primary cluster until promoted"))]
+    Standby,
+
+    #[snafu(display("Failed to persist replication state: {}", source))]
+    Io { source: std::io::Error },
+}
+
+impl CoordinatorError {
+    /// Whether the request may succeed if sent again, e.g. the connection is broken
+    /// or the node is unavailable for a while
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Self::Connect { .. }
+                | Self::Grpc {
+                    code: tonic::Code::Unavailable | tonic::Code::DeadlineExceeded,
+                    ..
+                }
+        )
+    }
+
+    /// Whether the writes are stalled for the flushes falling behind, the clients
+    /// should back off before sending them again
+    pub fn is_write_stalled(&self) -> bool {
+        matches!(
+            self,
+            Self::Tskv {
+                source: tskv::Error::WriteStalled { .. }
+            }
+        )
+    }
+
+    pub fn error_code(&self) -> ErrorCode {
+        match self {
+            Self::Meta { source } => source.error_code(),
+            Self::Tskv { source } => source.error_code(),
+            Self::NodeNotFound { .. } | Self::Connect { .. } | Self::Grpc { .. } => {
+                ErrorCode::NodeUnavailable
+            }
+            Self::InvalidPoints { .. } => ErrorCode::InvalidPoint,
+            Self::NotEnoughReplicas { .. } | Self::NoSourceReplica { .. } => {
+                ErrorCode::NotEnoughReplicas
+            }
+            Self::RebalanceInProgress => ErrorCode::RebalanceInProgress,
+            Self::Standby => ErrorCode::Standby,
+            Self::EmptyBucket { .. } | Self::Io { .. } => ErrorCode::CoordinatorUnknown,
+        }
+    }
+}
+
+impl From<MetaError> for CoordinatorError {
+    fn from(source: MetaError) -> Self {
+        CoordinatorError::Meta { source }
+    }
+}
+
+impl From<tonic::Status> for CoordinatorError {
+    fn from(status: tonic::Status) -> Self {
+        CoordinatorError::Grpc {
+            code: status.code(),
+            msg: status.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tonic::{Code, Status};
+
+    use super::*;
+
+    #[test]
+    fn test_is_retryable() {
+        let grpc = |code| CoordinatorError::from(Status::new(code, ""));
+        assert!(grpc(Code::Unavailable).is_retryable());
+        assert!(grpc(Code::DeadlineExceeded).is_retryable());
+        assert!(!grpc(Code::Internal).is_retryable());
+        assert!(!grpc(Code::InvalidArgument).is_retryable());
+        assert!(CoordinatorError::Connect {
+            addr: "127.0.0.1:1".to_string(),
+            msg: String::new(),
+        }
+        .is_retryable());
+        assert!(!CoordinatorError::EmptyBucket { id: 1 }.is_retryable());
+    }
+}
@@ -0,0 +1,340 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use models::meta_data::NodeId;
+use parking_lot::Mutex;
+use protos::kv_service::WritePointsRpcRequest;
+use snafu::ResultExt;
+use trace::{info, warn};
+
+use crate::errors::{IoSnafu, Result};
+use crate::writer::PointWriter;
+
+pub const DEFAULT_REPLAY_INTERVAL: Duration = Duration::from_secs(10);
+
+const HINTS_FILE_SUFFIX: &str = "hints";
+
+/// Buffers the writes of the replicas failed to write, e.g. the data node is down,
+/// and replays them in order once the node is reachable again.
+///
+/// The hints of each node are appended to a file in `dir` so they survive a restart
+/// of this node. At most `max_size` bytes of points are buffered for a node, the
+/// writes beyond are dropped and left to [`crate::replica_sync::ReplicaSync`].
+///
+/// Each node has a queue of its own, the files of which are written on the blocking
+/// threads, so the hints of a node are neither delayed by the others nor block the
+/// writes.
+#[derive(Debug)]
+pub struct HintedHandoff {
+    dir: PathBuf,
+    max_size: u64,
+    queues: Mutex<HashMap<NodeId, Arc<Mutex<HintQueue>>>>,
+}
+
+impl HintedHandoff {
+    pub fn open(dir: impl AsRef<Path>, max_size: u64) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir).context(IoSnafu)?;
+
+        let mut queues = HashMap::new();
+        for entry in fs::read_dir(&dir).context(IoSnafu)? {
+            let path = entry.context(IoSnafu)?.path();
+            if path.extension().map_or(true, |e| e != HINTS_FILE_SUFFIX) {
+                continue;
+            }
+            let node_id = match path.file_stem().and_then(|e| e.to_str()) {
+                Some(stem) => match stem.parse::<NodeId>() {
+                    Ok(id) => id,
+                    Err(_) => continue,
+                },
+                None => continue,
+            };
+            let queue = HintQueue::load(path)?;
+            info!("Loaded {} hints of node {}", queue.hints.len(), node_id);
+            queues.insert(node_id, Arc::new(Mutex::new(queue)));
+        }
+
+        Ok(Self {
+            dir,
+            max_size,
+            queues: Mutex::new(queues),
+        })
+    }
+
+    /// Buffers a write of the node, returns false if the buffer of the node is full
+    pub async fn add(&self, node_id: NodeId, req: WritePointsRpcRequest) -> Result<bool> {
+        let queue = self.queue(node_id);
+        let max_size = self.max_size;
+        blocking(move || {
+            let mut queue = queue.lock();
+            if queue.size + req.points.len() as u64 > max_size {
+                warn!(
+                    "Hints of node {} exceed {} bytes, dropped a write",
+                    node_id, max_size
+                );
+                return Ok(false);
+            }
+            queue.push(req)?;
+            Ok(true)
+        })
+        .await
+    }
+
+    /// Number of the writes buffered for the node
+    pub fn pending(&self, node_id: NodeId) -> usize {
+        self.queues
+            .lock()
+            .get(&node_id)
+            .map_or(0, |e| e.lock().hints.len())
+    }
+
+    fn queue(&self, node_id: NodeId) -> Arc<Mutex<HintQueue>> {
+        self.queues
+            .lock()
+            .entry(node_id)
+            .or_insert_with(|| Arc::new(Mutex::new(HintQueue::new(self.hints_path(node_id)))))
+            .clone()
+    }
+
+    /// Replays the hints periodically in the background
+    pub fn start(self: Arc<Self>, writer: Arc<PointWriter>, interval: Duration) {
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                if let Err(e) = self.replay(&writer).await {
+                    warn!("Failed to replay hints: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Sends the hints of the nodes concurrently, those of each node in order, stops
+    /// at the first failed write of a node and retries it next time.
+    pub async fn replay(&self, writer: &PointWriter) -> Result<()> {
+        let queues: Vec<(NodeId, Arc<Mutex<HintQueue>>)> = self
+            .queues
+            .lock()
+            .iter()
+            .map(|(id, queue)| (*id, queue.clone()))
+            .collect();
+
+        let replays = queues
+            .into_iter()
+            .filter(|(_, queue)| !queue.lock().hints.is_empty())
+            .map(|(node_id, queue)| Self::replay_node(writer, node_id, queue));
+        futures::future::join_all(replays)
+            .await
+            .into_iter()
+            .collect()
+    }
+
+    async fn replay_node(
+        writer: &PointWriter,
+        node_id: NodeId,
+        queue: Arc<Mutex<HintQueue>>,
+    ) -> Result<()> {
+        let mut replayed = 0;
+        loop {
+            // Hints are only added to the back, the front is taken by this task only
+            let req = match queue.lock().hints.front().cloned() {
+                Some(req) => req,
+                None => break,
+            };
+            if let Err(e) = writer.write_to_node(node_id, req).await {
+                warn!("Failed to replay hints of node {}: {}", node_id, e);
+                break;
+            }
+            queue.lock().pop();
+            replayed += 1;
+        }
+
+        if replayed > 0 {
+            info!("Replayed {} hints of node {}", replayed, node_id);
+            blocking(move || queue.lock().persist()).await?;
+        }
+        Ok(())
+    }
+
+    fn hints_path(&self, node_id: NodeId) -> PathBuf {
+        self.dir.join(format!("{}.{}", node_id, HINTS_FILE_SUFFIX))
+    }
+}
+
+/// The hints of a node, each is stored as `version: u32, len: u32, points`
+#[derive(Debug)]
+struct HintQueue {
+    path: PathBuf,
+    hints: VecDeque<WritePointsRpcRequest>,
+    size: u64,
+}
+
+impl HintQueue {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            hints: VecDeque::new(),
+            size: 0,
+        }
+    }
+
+    fn load(path: PathBuf) -> Result<Self> {
+        let mut queue = Self::new(path);
+        let mut reader = BufReader::new(File::open(&queue.path).context(IoSnafu)?);
+        let mut header = [0_u8; 8];
+        let mut truncated = false;
+        loop {
+            match reader.read_exact(&mut header) {
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e).context(IoSnafu),
+            }
+            let version = u32::from_le_bytes(header[..4].try_into().unwrap());
+            let len = u32::from_le_bytes(header[4..].try_into().unwrap());
+            let mut points = vec![0_u8; len as usize];
+            // The last hint may be partially written before a crash
+            if let Err(e) = reader.read_exact(&mut points) {
+                warn!("Truncated hint in {}: {}", queue.path.display(), e);
+                truncated = true;
+                break;
+            }
+            queue.size += points.len() as u64;
+            queue
+                .hints
+                .push_back(WritePointsRpcRequest { version, points });
+        }
+
+        // Drops the partial hint so that new hints are appended after the complete ones
+        if truncated {
+            queue.persist()?;
+        }
+        Ok(queue)
+    }
+
+    fn push(&mut self, req: WritePointsRpcRequest) -> Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .context(IoSnafu)?;
+        file.write_all(&encode_hint(&req)).context(IoSnafu)?;
+        file.sync_data().context(IoSnafu)?;
+
+        self.size += req.points.len() as u64;
+        self.hints.push_back(req);
+        Ok(())
+    }
+
+    fn pop(&mut self) {
+        if let Some(req) = self.hints.pop_front() {
+            self.size -= req.points.len() as u64;
+        }
+    }
+
+    /// Rewrites the file with the hints not replayed yet
+    fn persist(&self) -> Result<()> {
+        if self.hints.is_empty() {
+            return match fs::remove_file(&self.path) {
+                Err(e) if e.kind() != ErrorKind::NotFound => Err(e).context(IoSnafu),
+                _ => Ok(()),
+            };
+        }
+
+        let tmp = self.path.with_extension("tmp");
+        {
+            let mut writer = BufWriter::new(File::create(&tmp).context(IoSnafu)?);
+            for req in self.hints.iter() {
+                writer.write_all(&encode_hint(req)).context(IoSnafu)?;
+            }
+            writer
+                .into_inner()
+                .map_err(|e| e.into_error())
+                .context(IoSnafu)?
+                .sync_data()
+                .context(IoSnafu)?;
+        }
+        fs::rename(&tmp, &self.path).context(IoSnafu)
+    }
+}
+
+/// Runs the file operations on the blocking threads
+async fn blocking<T: Send + 'static>(f: impl FnOnce() -> Result<T> + Send + 'static) -> Result<T> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| std::io::Error::new(ErrorKind::Other, e))
+        .context(IoSnafu)?
+}
+
+fn encode_hint(req: &WritePointsRpcRequest) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(8 + req.points.len());
+    buf.extend_from_slice(&req.version.to_le_bytes());
+    buf.extend_from_slice(&(req.points.len() as u32).to_le_bytes());
+    buf.extend_from_slice(&req.points);
+    buf
+}
+
+#[cfg(test)]
+mod test {
+    use meta::meta_client::MockMetaClient;
+    use protos::models_helper;
+    use tskv::engine::MockEngine;
+
+    use super::*;
+    use crate::connection::NodeConnections;
+
+    fn request(points: &[u8]) -> WritePointsRpcRequest {
+        WritePointsRpcRequest {
+            version: 1,
+            points: points.to_vec(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hints_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let handoff = HintedHandoff::open(dir.path(), 8).unwrap();
+            assert!(handoff.add(2, request(b"abc")).await.unwrap());
+            assert!(handoff.add(2, request(b"defg")).await.unwrap());
+            // Exceeds the max size
+            assert!(!handoff.add(2, request(b"hi")).await.unwrap());
+            assert!(handoff.add(3, request(b"hi")).await.unwrap());
+        }
+
+        let handoff = HintedHandoff::open(dir.path(), 8).unwrap();
+        assert_eq!(handoff.pending(2), 2);
+        assert_eq!(handoff.pending(3), 1);
+        let queue = handoff.queue(2);
+        let queue = queue.lock();
+        assert_eq!(queue.size, 7);
+        assert_eq!(queue.hints[1].points, b"defg");
+    }
+
+    fn points_request() -> WritePointsRpcRequest {
+        let mut fbb = flatbuffers::FlatBufferBuilder::new();
+        let points = models_helper::create_const_points(&mut fbb, 1);
+        fbb.finish(points, None);
+        request(fbb.finished_data())
+    }
+
+    #[tokio::test]
+    async fn test_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let connections = Arc::new(NodeConnections::new(Arc::new(MockMetaClient::default())));
+        let writer = PointWriter::new(1, Arc::new(MockEngine::default()), connections);
+
+        let handoff = HintedHandoff::open(dir.path(), 1024).unwrap();
+        handoff.add(1, points_request()).await.unwrap();
+        // Node 2 is not registered
+        handoff.add(2, points_request()).await.unwrap();
+        handoff.replay(&writer).await.unwrap();
+
+        assert_eq!(handoff.pending(1), 0);
+        assert_eq!(handoff.pending(2), 1);
+        assert!(!handoff.hints_path(1).exists());
+        assert!(handoff.hints_path(2).exists());
+    }
+}
@@ -1 +1,23 @@
-mod meta_client;
+//! Coordinates the data nodes of a cluster.
+//!
+//! The protocol handlers and the query server reach the data through a
+//! [`service::Coordinator`], which places the points written on the shards of the
+//! tenant, or writes them locally if the node runs standalone.
+//!
+//! The writes of a shard are replicated to the data nodes of its
+//! [`models::meta_data::ReplicationSet`], the writes of the unavailable replicas are
+//! buffered by [`hinted_handoff::HintedHandoff`], and the replicas missing writes catch
+//! up from the others by [`replica_sync::ReplicaSync`]. Points are routed to the
+//! shards by [`sharding::PointRouter`]. Vnodes are moved between the data nodes by
+//! [`rebalance::Rebalancer`] once nodes are added or decommissioned. The writes of a
+//! primary cluster in another datacenter are replicated by
+//! [`dc_replication::DcReplication`].
+pub mod connection;
+pub mod dc_replication;
+pub mod errors;
+pub mod hinted_handoff;
+pub mod rebalance;
+pub mod replica_sync;
+pub mod service;
+pub mod sharding;
+pub mod writer;
@@ -0,0 +1,556 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use meta::meta_client::MetaClientRef;
+use models::meta_data::{
+    BucketId, NodeId, NodeInfo, NodeStatus, TenantMetaData, VnodeId, VnodeInfo,
+};
+use models::schema::TableSchema;
+use models::ColumnId;
+use parking_lot::RwLock;
+use protos::kv_service::{
+    DeleteShardPointsRequest, FetchShardPointsRequest, ShardFilter, WritePointsRpcRequest,
+};
+use serde::Serialize;
+use snafu::ResultExt;
+use trace::{info, warn};
+use tskv::engine::EngineRef;
+use tskv::TimeRange;
+
+use crate::connection::NodeConnections;
+use crate::errors::{CoordinatorError, Result, TskvSnafu};
+use crate::writer::PointWriter;
+
+/// Time for the data nodes to route the writes by the vnode moved, before the writes
+/// to the old replica are copied again and removed from it
+const ROUTE_REFRESH_WAIT: Duration = Duration::from_secs(5);
+
+/// A replica of a shard moved to another data node
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VnodeMove {
+    pub tenant: String,
+    pub db: String,
+    pub bucket: BucketId,
+    pub vnode: VnodeId,
+    pub from: NodeId,
+    pub to: NodeId,
+    pub shard: ShardFilter,
+    /// The other replicas of the shard, copied from if `from` is unavailable
+    pub peers: Vec<NodeId>,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct RebalanceProgress {
+    pub running: bool,
+    pub total: usize,
+    pub finished: usize,
+    pub failed: usize,
+    pub copied_bytes: u64,
+    /// The vnode being moved
+    pub current: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Moves the vnodes between data nodes, to balance the vnodes once a node is added,
+/// or to drain a node being decommissioned.
+///
+/// The points of a vnode are streamed from one of its replicas to the new node at
+/// most `rate_limit` bytes per second, then the vnode is moved in the meta service.
+/// The points written to the old replica during the copy are copied again after the
+/// move, writes are idempotent, then the points of the shard are removed from the
+/// old node.
+#[derive(Debug)]
+pub struct Rebalancer {
+    meta: MetaClientRef,
+    connections: Arc<NodeConnections>,
+    writer: Arc<PointWriter>,
+    rate_limit: u64,
+    progress: RwLock<RebalanceProgress>,
+}
+
+impl Rebalancer {
+    pub fn new(
+        connections: Arc<NodeConnections>,
+        writer: Arc<PointWriter>,
+        rate_limit: u64,
+    ) -> Self {
+        Self {
+            meta: connections.meta(),
+            connections,
+            writer,
+            rate_limit,
+            progress: RwLock::new(RebalanceProgress::default()),
+        }
+    }
+
+    pub fn progress(&self) -> RebalanceProgress {
+        self.progress.read().clone()
+    }
+
+    /// Runs a rebalance in the background, the node to decommission is removed from
+    /// the cluster once all of its vnodes are moved.
+    pub fn start(self: &Arc<Self>, decommission: Option<NodeId>) -> Result<()> {
+        self.begin()?;
+        let rebalancer = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = rebalancer.execute(decommission).await {
+                warn!("Failed to rebalance the cluster: {}", e);
+                rebalancer.progress.write().error = Some(e.to_string());
+            }
+            rebalancer.progress.write().running = false;
+        });
+        Ok(())
+    }
+
+    pub async fn run(&self, decommission: Option<NodeId>) -> Result<RebalanceProgress> {
+        self.begin()?;
+        let res = self.execute(decommission).await;
+        let mut progress = self.progress.write();
+        progress.running = false;
+        if let Err(e) = &res {
+            progress.error = Some(e.to_string());
+        }
+        res.map(|_| progress.clone())
+    }
+
+    fn begin(&self) -> Result<()> {
+        let mut progress = self.progress.write();
+        if progress.running {
+            return Err(CoordinatorError::RebalanceInProgress);
+        }
+        *progress = RebalanceProgress {
+            running: true,
+            ..Default::default()
+        };
+        Ok(())
+    }
+
+    async fn execute(&self, decommission: Option<NodeId>) -> Result<()> {
+        if let Some(id) = decommission {
+            self.meta
+                .update_data_node_status(id, NodeStatus::Decommissioning)
+                .await?;
+        }
+
+        let nodes = self.meta.data_nodes().await?;
+        let mut tenants = vec![];
+        for tenant in self.meta.tenants().await? {
+            tenants.push((tenant.clone(), self.meta.refresh(&tenant).await?));
+        }
+
+        let moves = plan_moves(&nodes, &tenants);
+        info!("Rebalance the cluster by {} vnode moves", moves.len());
+        self.progress.write().total = moves.len();
+
+        for m in moves.iter() {
+            self.progress.write().current = Some(format!(
+                "vnode {} of {}.{}: node {} -> {}",
+                m.vnode, m.tenant, m.db, m.from, m.to
+            ));
+            match self.move_vnode(m).await {
+                Ok(_) => self.progress.write().finished += 1,
+                Err(e) => {
+                    warn!("Failed to move vnode {}: {}", m.vnode, e);
+                    self.progress.write().failed += 1;
+                }
+            }
+        }
+        self.progress.write().current = None;
+
+        if let Some(id) = decommission {
+            self.meta.remove_data_node(id).await?;
+            info!("Data node {} is decommissioned", id);
+        }
+        Ok(())
+    }
+
+    async fn move_vnode(&self, m: &VnodeMove) -> Result<()> {
+        self.copy_shard(m).await?;
+        self.meta
+            .move_vnode(&m.tenant, &m.db, m.bucket, m.vnode, m.to)
+            .await?;
+        // Catches up the writes routed to the old replica during the copy, and those
+        // routed to it by the data nodes not aware of the move yet
+        tokio::time::sleep(ROUTE_REFRESH_WAIT).await;
+        self.copy_shard(m).await?;
+
+        // The vnode is moved even if the old node is unavailable, the points left on
+        // it are not read anymore
+        if let Err(e) = self.delete_shard(m).await {
+            warn!(
+                "Failed to remove vnode {} from node {}: {}",
+                m.vnode, m.from, e
+            );
+        }
+        Ok(())
+    }
+
+    async fn delete_shard(&self, m: &VnodeMove) -> Result<()> {
+        let mut client = self.connections.client(m.from).await?;
+        let request = DeleteShardPointsRequest {
+            database: m.db.clone(),
+            shard: Some(m.shard.clone()),
+        };
+        let resp = client
+            .delete_shard_points(self.connections.request(request))
+            .await
+            .map_err(|status| {
+                self.connections.invalidate(m.from);
+                CoordinatorError::from(status)
+            })?
+            .into_inner();
+        info!(
+            "Removed {} series of vnode {} from node {}",
+            resp.series, m.vnode, m.from
+        );
+        Ok(())
+    }
+
+    async fn copy_shard(&self, m: &VnodeMove) -> Result<()> {
+        let mut errors = vec![];
+        for source in std::iter::once(m.from).chain(m.peers.iter().cloned()) {
+            match self.copy_shard_from(source, m).await {
+                Ok(_) => return Ok(()),
+                Err(e) => {
+                    warn!(
+                        "Failed to copy vnode {} from node {}: {}",
+                        m.vnode, source, e
+                    );
+                    errors.push(format!("node {}: {}", source, e));
+                }
+            }
+        }
+        Err(CoordinatorError::NoSourceReplica {
+            vnode: m.vnode,
+            msg: errors.join(", "),
+        })
+    }
+
+    async fn copy_shard_from(&self, source: NodeId, m: &VnodeMove) -> Result<()> {
+        let mut client = self.connections.client(source).await?;
+        let request = FetchShardPointsRequest {
+            database: m.db.clone(),
+            shard: Some(m.shard.clone()),
+        };
+        let mut stream = match client
+            .fetch_shard_points(self.connections.request(request))
+            .await
+        {
+            Ok(resp) => resp.into_inner(),
+            Err(status) => {
+                self.connections.invalidate(source);
+                return Err(status.into());
+            }
+        };
+
+        let start = Instant::now();
+        let mut copied = 0_u64;
+        while let Some(resp) = stream.message().await? {
+            let len = resp.points.len() as u64;
+            let req = WritePointsRpcRequest {
+                version: 1,
+                points: resp.points,
+            };
+            self.writer.write_to_node(m.to, req).await?;
+
+            copied += len;
+            self.progress.write().copied_bytes += len;
+            if let Some(wait) = throttle(copied, self.rate_limit, start.elapsed()) {
+                tokio::time::sleep(wait).await;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Removes the points of the shard from the local engine, after the vnode of it is
+/// moved to another data node. Returns the number of the series of the shard.
+pub fn delete_shard_points(
+    engine: &EngineRef,
+    database: &str,
+    shard: &ShardFilter,
+) -> Result<usize> {
+    let index_err = |source| CoordinatorError::Tskv {
+        source: tskv::Error::IndexErr { source },
+    };
+    let time_range = TimeRange::new(shard.start_time, shard.end_time.saturating_sub(1));
+
+    let mut deleted = 0;
+    for table in engine.list_tables(database).context(TskvSnafu)? {
+        let schema = match engine
+            .get_table_schema(database, &table)
+            .context(TskvSnafu)?
+        {
+            Some(TableSchema::TsKvTableSchema(schema)) => schema,
+            _ => continue,
+        };
+
+        let mut series_ids = vec![];
+        for sid in engine
+            .get_series_id_list(database, &table, &[])
+            .map_err(index_err)?
+        {
+            let key = match engine.get_series_key(database, sid).map_err(index_err)? {
+                Some(key) => key,
+                None => continue,
+            };
+            if shard.shard_num > 0 && key.hash() % shard.shard_num as u64 == shard.index as u64 {
+                series_ids.push(sid);
+            }
+        }
+        if series_ids.is_empty() {
+            continue;
+        }
+
+        let field_ids: Vec<ColumnId> = schema.fields().iter().map(|e| e.id).collect();
+        engine
+            .delete_series(database, &series_ids, &field_ids, &time_range)
+            .context(TskvSnafu)?;
+        deleted += series_ids.len();
+    }
+    Ok(deleted)
+}
+
+/// Time to wait so that the bytes copied do not exceed the rate limit
+fn throttle(copied: u64, rate_limit: u64, elapsed: Duration) -> Option<Duration> {
+    if rate_limit == 0 {
+        return None;
+    }
+    let expected = Duration::from_secs_f64(copied as f64 / rate_limit as f64);
+    expected.checked_sub(elapsed).filter(|e| !e.is_zero())
+}
+
+/// A shard with the vnodes as planned
+struct PlannedShard {
+    tenant: String,
+    db: String,
+    bucket: BucketId,
+    shard: ShardFilter,
+    vnodes: Vec<VnodeInfo>,
+}
+
+/// Moves the vnodes out of the decommissioning nodes, then moves the vnodes from the
+/// most loaded node to the least loaded one until their vnodes differ by at most one.
+/// A node never owns two replicas of the same shard.
+pub fn plan_moves(nodes: &[NodeInfo], tenants: &[(String, Arc<TenantMetaData>)]) -> Vec<VnodeMove> {
+    let mut shards = vec![];
+    for (tenant, meta) in tenants.iter() {
+        for (db, info) in meta.dbs.iter() {
+            for bucket in info.buckets.iter() {
+                for (index, set) in bucket.shard_group.iter().enumerate() {
+                    shards.push(PlannedShard {
+                        tenant: tenant.clone(),
+                        db: db.clone(),
+                        bucket: bucket.id,
+                        shard: ShardFilter {
+                            start_time: bucket.start_time,
+                            end_time: bucket.end_time,
+                            shard_num: bucket.shard_group.len() as u32,
+                            index: index as u32,
+                        },
+                        vnodes: set.vnodes.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    // Vnodes of each node which may own vnodes
+    let mut load: BTreeMap<NodeId, usize> = nodes
+        .iter()
+        .filter(|e| e.status == NodeStatus::Healthy)
+        .map(|e| (e.id, 0))
+        .collect();
+    for vnode in shards.iter().flat_map(|e| e.vnodes.iter()) {
+        if let Some(n) = load.get_mut(&vnode.node_id) {
+            *n += 1;
+        }
+    }
+    let draining: Vec<NodeId> = nodes
+        .iter()
+        .filter(|e| e.status == NodeStatus::Decommissioning)
+        .map(|e| e.id)
+        .collect();
+
+    let mut moves = vec![];
+    let mut move_vnode = |shard: &mut PlannedShard, i: usize, to: NodeId| {
+        let from = shard.vnodes[i].node_id;
+        shard.vnodes[i].node_id = to;
+        moves.push(VnodeMove {
+            tenant: shard.tenant.clone(),
+            db: shard.db.clone(),
+            bucket: shard.bucket,
+            vnode: shard.vnodes[i].id,
+            from,
+            to,
+            shard: shard.shard.clone(),
+            peers: shard
+                .vnodes
+                .iter()
+                .filter(|e| e.node_id != to)
+                .map(|e| e.node_id)
+                .collect(),
+        });
+    };
+    let owns = |shard: &PlannedShard, node: NodeId| shard.vnodes.iter().any(|e| e.node_id == node);
+
+    for shard in shards.iter_mut() {
+        for i in 0..shard.vnodes.len() {
+            if !draining.contains(&shard.vnodes[i].node_id) {
+                continue;
+            }
+            let target = load
+                .iter()
+                .filter(|(id, _)| !owns(shard, **id))
+                .min_by_key(|(id, n)| (**n, **id))
+                .map(|(id, _)| *id);
+            match target {
+                Some(to) => {
+                    *load.entry(to).or_default() += 1;
+                    move_vnode(shard, i, to);
+                }
+                None => warn!(
+                    "No data node to move vnode {} of {}.{}",
+                    shard.vnodes[i].id, shard.tenant, shard.db
+                ),
+            }
+        }
+    }
+
+    loop {
+        let (max, min) = match (
+            load.iter()
+                .max_by_key(|(id, n)| (**n, std::cmp::Reverse(**id))),
+            load.iter().min_by_key(|(id, n)| (**n, **id)),
+        ) {
+            (Some((max, max_n)), Some((min, min_n))) if max_n - min_n > 1 => (*max, *min),
+            _ => break,
+        };
+        let found = shards.iter_mut().find_map(|shard| {
+            if owns(shard, min) {
+                return None;
+            }
+            shard
+                .vnodes
+                .iter()
+                .position(|e| e.node_id == max)
+                .map(|i| (shard, i))
+        });
+        match found {
+            Some((shard, i)) => {
+                move_vnode(shard, i, min);
+                *load.entry(max).or_default() -= 1;
+                *load.entry(min).or_default() += 1;
+            }
+            None => break,
+        }
+    }
+
+    moves
+}
+
+#[cfg(test)]
+mod test {
+    use models::meta_data::{BucketInfo, DatabaseInfo, ReplicationSet};
+    use models::schema::DatabaseSchema;
+
+    use super::*;
+
+    fn node(id: NodeId, status: NodeStatus) -> NodeInfo {
+        NodeInfo {
+            id,
+            grpc_addr: format!("127.0.0.1:{}", 31006 + id),
+            http_addr: format!("127.0.0.1:{}", 31007 + id),
+            status,
+        }
+    }
+
+    /// A bucket with the shards of two replicas on the nodes
+    fn tenant(shards: &[[NodeId; 2]]) -> Vec<(String, Arc<TenantMetaData>)> {
+        let mut id = 0;
+        let mut next_id = || {
+            id += 1;
+            id
+        };
+        let bucket = BucketInfo {
+            id: next_id(),
+            start_time: 0,
+            end_time: 100,
+            shard_group: shards
+                .iter()
+                .map(|nodes| ReplicationSet {
+                    id: next_id(),
+                    vnodes: nodes
+                        .iter()
+                        .map(|node_id| VnodeInfo {
+                            id: next_id(),
+                            node_id: *node_id,
+                        })
+                        .collect(),
+                })
+                .collect(),
+        };
+        let mut db = DatabaseInfo::new(DatabaseSchema::new("db"));
+        db.buckets.push(bucket);
+        let mut meta = TenantMetaData::default();
+        meta.dbs.insert("db".to_string(), db);
+        vec![("cnosdb".to_string(), Arc::new(meta))]
+    }
+
+    fn load_after(moves: &[VnodeMove], shards: &[[NodeId; 2]], node_id: NodeId) -> usize {
+        let before = shards.iter().flatten().filter(|e| **e == node_id).count();
+        let added = moves.iter().filter(|e| e.to == node_id).count();
+        let removed = moves.iter().filter(|e| e.from == node_id).count();
+        before + added - removed
+    }
+
+    #[test]
+    fn test_plan_new_node() {
+        let shards = [[1, 2], [2, 1], [1, 2], [2, 1]];
+        let nodes = vec![
+            node(1, NodeStatus::Healthy),
+            node(2, NodeStatus::Healthy),
+            node(3, NodeStatus::Healthy),
+        ];
+        let moves = plan_moves(&nodes, &tenant(&shards));
+
+        assert!(!moves.is_empty());
+        assert!(moves.iter().all(|e| e.to == 3));
+        for id in 1..=3 {
+            let load = load_after(&moves, &shards, id);
+            assert!((2..=3).contains(&load), "node {} owns {}", id, load);
+        }
+        for m in moves.iter() {
+            assert!(!m.peers.contains(&m.to));
+        }
+    }
+
+    #[test]
+    fn test_plan_decommission() {
+        let shards = [[1, 2], [2, 3], [3, 1]];
+        let nodes = vec![
+            node(1, NodeStatus::Healthy),
+            node(2, NodeStatus::Decommissioning),
+            node(3, NodeStatus::Healthy),
+        ];
+        let moves = plan_moves(&nodes, &tenant(&shards));
+
+        // Each vnode of node 2 is moved to the node without a replica of the shard
+        assert_eq!(moves.len(), 2);
+        assert!(moves.iter().all(|e| e.from == 2));
+        assert_eq!(moves[0].to, 3);
+        assert_eq!(moves[1].to, 1);
+        assert_eq!(load_after(&moves, &shards, 2), 0);
+    }
+
+    #[test]
+    fn test_throttle() {
+        assert_eq!(throttle(100, 0, Duration::ZERO), None);
+        assert_eq!(
+            throttle(100, 100, Duration::from_millis(400)),
+            Some(Duration::from_millis(600))
+        );
+        assert_eq!(throttle(100, 100, Duration::from_secs(2)), None);
+    }
+}
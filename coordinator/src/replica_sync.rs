@@ -0,0 +1,311 @@
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use meta::meta_client::MetaClientRef;
+use models::meta_data::{DatabaseInfo, NodeId};
+use models::SeriesKey;
+use parking_lot::Mutex;
+use protos::kv_service::{FetchChangesRequest, WritePointsRpcRequest};
+use protos::models as fb_models;
+use snafu::ResultExt;
+use trace::{error, info, warn};
+use tskv::cdc::ChangeOffset;
+use tskv::engine::EngineRef;
+
+use crate::connection::NodeConnections;
+use crate::errors::{CoordinatorError, IoSnafu, Result, TskvSnafu};
+
+pub const DEFAULT_SYNC_INTERVAL: Duration = Duration::from_secs(60);
+
+const FETCH_LIMIT: u32 = 1024;
+const CURSORS_FILE: &str = "cursors.json";
+
+/// Replays the writes missed by the replicas on this node, e.g. during a restart,
+/// from the wal of the other replicas of the same shards.
+///
+/// The position read in the wal of each peer is persisted, so only the writes after
+/// the last sync are fetched. Writes are idempotent, a write received both directly
+/// and from a peer is stored once. The writes replayed are marked in the wal, so the
+/// peers do not fetch them back.
+///
+/// The databases of the storage are not namespaced by the tenants, so are those
+/// synced, the replication sets of the databases of the same name are merged.
+#[derive(Debug)]
+pub struct ReplicaSync {
+    node_id: NodeId,
+    engine: EngineRef,
+    meta: MetaClientRef,
+    connections: Arc<NodeConnections>,
+    cursors: SyncCursors,
+}
+
+impl ReplicaSync {
+    pub fn new(
+        node_id: NodeId,
+        engine: EngineRef,
+        connections: Arc<NodeConnections>,
+        dir: impl AsRef<Path>,
+    ) -> Result<Self> {
+        Ok(Self {
+            node_id,
+            engine,
+            meta: connections.meta(),
+            connections,
+            cursors: SyncCursors::open(dir)?,
+        })
+    }
+
+    /// Catches up at once, then syncs periodically in the background
+    pub fn start(self: Arc<Self>, interval: Duration) {
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = self.sync().await {
+                    warn!("Failed to sync replicas: {}", e);
+                }
+                tokio::time::sleep(interval).await;
+            }
+        });
+    }
+
+    pub async fn sync(&self) -> Result<()> {
+        let mut databases: HashMap<String, Vec<DatabaseInfo>> = HashMap::new();
+        for tenant in self.meta.tenants().await? {
+            let meta = self.meta.refresh(&tenant).await?;
+            for (db, info) in meta.dbs.into_iter() {
+                databases.entry(db).or_default().push(info);
+            }
+        }
+
+        for (db, infos) in databases.iter() {
+            for peer in peers(self.node_id, infos) {
+                if let Err(e) = self.sync_from(db, infos, peer).await {
+                    warn!("Failed to sync {} from node {}: {}", db, peer, e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn sync_from(&self, db: &str, infos: &[DatabaseInfo], peer: NodeId) -> Result<()> {
+        let key = format!("{}.{}", db, peer);
+        let mut offset = self.cursors.get(&key);
+        let mut client = self.connections.client(peer).await?;
+        let mut replayed = 0;
+
+        loop {
+            let resp = client
+                .fetch_changes(self.connections.request(FetchChangesRequest {
+                    database: db.to_string(),
+                    file_id: offset.file_id,
+                    pos: offset.pos,
+                    limit: FETCH_LIMIT,
+                    include_replayed: false,
+                    consumer: format!("replica.{}", self.node_id),
+                }))
+                .await
+                .map_err(|e| {
+                    self.connections.invalidate(peer);
+                    CoordinatorError::from(e)
+                })?
+                .into_inner();
+
+            if resp.truncated {
+                error!(
+                    "The wal of {} on node {} is removed after offset {}, the writes in \
+                     between are not replayed, the replicas of node {} have to be rebuilt",
+                    db, peer, offset, self.node_id
+                );
+            }
+            for points in resp.points {
+                if owns(self.node_id, infos, &points)? {
+                    self.engine
+                        .write_replayed(WritePointsRpcRequest { version: 1, points })
+                        .await
+                        .context(TskvSnafu)?;
+                    replayed += 1;
+                }
+            }
+
+            // The writes replayed by the peer are skipped, so fewer writes than the
+            // limit are fetched even if there are more
+            let next = ChangeOffset::new(resp.next_file_id, resp.next_pos);
+            if next == offset {
+                break;
+            }
+            offset = next;
+            self.cursors.set(&key, offset)?;
+        }
+
+        if replayed > 0 {
+            info!("Replayed {} writes of {} from node {}", replayed, db, peer);
+        }
+        Ok(())
+    }
+}
+
+/// Nodes sharing a replication set of the databases with the node
+fn peers(node_id: NodeId, infos: &[DatabaseInfo]) -> BTreeSet<NodeId> {
+    let mut peers = BTreeSet::new();
+    let buckets = infos.iter().flat_map(|e| e.buckets.iter());
+    for set in buckets.flat_map(|e| e.shard_group.iter()) {
+        if set.vnodes.iter().any(|e| e.node_id == node_id) {
+            peers.extend(set.vnodes.iter().map(|e| e.node_id));
+        }
+    }
+    peers.remove(&node_id);
+    peers
+}
+
+/// Whether the write belongs to a replication set of the databases located on the
+/// node.
+///
+/// Writes are routed by replication set, so all points of a write belong to the
+/// same one.
+fn owns(node_id: NodeId, infos: &[DatabaseInfo], points: &[u8]) -> Result<bool> {
+    let invalid = |msg: String| CoordinatorError::InvalidPoints { msg };
+    let points =
+        flatbuffers::root::<fb_models::Points>(points).map_err(|e| invalid(e.to_string()))?;
+    let point = match points.points().and_then(|e| e.iter().next()) {
+        Some(point) => point,
+        None => return Ok(false),
+    };
+    let hash = SeriesKey::from_flatbuffer(&point)
+        .map_err(|e| invalid(e.to_string()))?
+        .hash();
+
+    Ok(infos.iter().any(|info| {
+        info.bucket_by_timestamp(point.timestamp())
+            .and_then(|e| e.vnode_for(hash))
+            .map(|e| e.vnodes.iter().any(|e| e.node_id == node_id))
+            .unwrap_or(false)
+    }))
+}
+
+/// Offsets of the wal of the peers synced, `<db>.<peer>` -> offset
+#[derive(Debug)]
+pub(crate) struct SyncCursors {
+    path: PathBuf,
+    cursors: Mutex<HashMap<String, ChangeOffset>>,
+}
+
+impl SyncCursors {
+    pub(crate) fn open(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir).context(IoSnafu)?;
+        let path = dir.join(CURSORS_FILE);
+
+        let mut cursors = HashMap::new();
+        if path.exists() {
+            let content = std::fs::read_to_string(&path).context(IoSnafu)?;
+            let saved: HashMap<String, String> = serde_json::from_str(&content)
+                .map_err(|e| CoordinatorError::Io { source: e.into() })?;
+            for (key, offset) in saved {
+                match offset.parse::<ChangeOffset>() {
+                    Ok(offset) => {
+                        cursors.insert(key, offset);
+                    }
+                    Err(e) => warn!("Ignore the sync cursor of {}: {}", key, e),
+                }
+            }
+        }
+
+        Ok(Self {
+            path,
+            cursors: Mutex::new(cursors),
+        })
+    }
+
+    pub(crate) fn get(&self, key: &str) -> ChangeOffset {
+        self.cursors
+            .lock()
+            .get(key)
+            .copied()
+            .unwrap_or(ChangeOffset::EARLIEST)
+    }
+
+    pub(crate) fn set(&self, key: &str, offset: ChangeOffset) -> Result<()> {
+        let mut cursors = self.cursors.lock();
+        cursors.insert(key.to_string(), offset);
+        let saved: HashMap<&String, String> =
+            cursors.iter().map(|(k, v)| (k, v.to_string())).collect();
+        let content =
+            serde_json::to_string(&saved).map_err(|e| CoordinatorError::Io { source: e.into() })?;
+
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, content).context(IoSnafu)?;
+        std::fs::rename(&tmp, &self.path).context(IoSnafu)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use models::meta_data::{BucketInfo, ReplicationSet, VnodeInfo};
+    use models::schema::DatabaseSchema;
+    use protos::models_helper;
+
+    use super::*;
+
+    fn database(sets: Vec<Vec<NodeId>>) -> DatabaseInfo {
+        let mut info = DatabaseInfo::new(DatabaseSchema::new("db0"));
+        info.buckets.push(BucketInfo {
+            id: 1,
+            start_time: 0,
+            end_time: 100,
+            shard_group: sets
+                .into_iter()
+                .enumerate()
+                .map(|(i, nodes)| ReplicationSet {
+                    id: i as u32,
+                    vnodes: nodes
+                        .into_iter()
+                        .map(|node_id| VnodeInfo {
+                            id: i as u32,
+                            node_id,
+                        })
+                        .collect(),
+                })
+                .collect(),
+        });
+        info
+    }
+
+    #[test]
+    fn test_peers() {
+        let infos = [database(vec![vec![1, 2], vec![2, 3], vec![3, 4]])];
+        assert_eq!(peers(1, &infos).into_iter().collect::<Vec<_>>(), vec![2]);
+        assert_eq!(peers(3, &infos).into_iter().collect::<Vec<_>>(), vec![2, 4]);
+        assert!(peers(5, &infos).is_empty());
+
+        // Databases of the same name of different tenants
+        let infos = [database(vec![vec![1, 2]]), database(vec![vec![1, 3]])];
+        assert_eq!(peers(1, &infos).into_iter().collect::<Vec<_>>(), vec![2, 3]);
+    }
+
+    #[test]
+    fn test_owns() {
+        let mut fbb = flatbuffers::FlatBufferBuilder::new();
+        let points = models_helper::create_const_points(&mut fbb, 1);
+        fbb.finish(points, None);
+        let points = fbb.finished_data();
+
+        let infos = [database(vec![vec![1, 2]])];
+        assert!(owns(1, &infos, points).unwrap());
+        assert!(!owns(3, &infos, points).unwrap());
+
+        let infos = [database(vec![vec![1, 2]]), database(vec![vec![3, 4]])];
+        assert!(owns(3, &infos, points).unwrap());
+    }
+
+    #[test]
+    fn test_cursors_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let cursors = SyncCursors::open(dir.path()).unwrap();
+        assert_eq!(cursors.get("db.2"), ChangeOffset::EARLIEST);
+        cursors.set("db.2", ChangeOffset::new(3, 128)).unwrap();
+
+        let cursors = SyncCursors::open(dir.path()).unwrap();
+        assert_eq!(cursors.get("db.2"), ChangeOffset::new(3, 128));
+    }
+}
@@ -0,0 +1,203 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use metrics::database_stats::record_write;
+use models::consistency_level::ConsistencyLevel;
+use models::meta_data::NodeId;
+use protos::kv_service::WritePointsRpcRequest;
+use protos::models as fb_models;
+use snafu::ResultExt;
+use trace::{info_span, Instrument};
+use tskv::engine::EngineRef;
+
+use crate::connection::NodeConnections;
+use crate::errors::{Result, TskvSnafu};
+use crate::sharding::PointRouter;
+use crate::writer::WriteAck;
+
+pub type CoordinatorRef = Arc<dyn Coordinator>;
+
+/// Entry of the protocol handlers and the query server to the data of the tenants.
+///
+/// The points written are placed by the coordinator, the storage of this node is
+/// only read or written directly for the data located on it.
+#[async_trait]
+pub trait Coordinator: Send + Sync + Debug {
+    /// Id of this data node in the cluster
+    fn node_id(&self) -> NodeId;
+
+    /// Storage of the data located on this node
+    fn engine(&self) -> EngineRef;
+
+    /// Connections to the data nodes of the cluster, none if standalone
+    fn connections(&self) -> Option<Arc<NodeConnections>>;
+
+    /// Writes the points to the shards of the tenant owning them
+    async fn write_points(
+        &self,
+        tenant: &str,
+        consistency: ConsistencyLevel,
+        req: WritePointsRpcRequest,
+    ) -> Result<WriteAck>;
+}
+
+/// The node is the only replica of all the data
+#[derive(Debug)]
+pub struct LocalCoordinator {
+    engine: EngineRef,
+}
+
+impl LocalCoordinator {
+    pub fn new(engine: EngineRef) -> Self {
+        Self { engine }
+    }
+}
+
+#[async_trait]
+impl Coordinator for LocalCoordinator {
+    fn node_id(&self) -> NodeId {
+        0
+    }
+
+    fn engine(&self) -> EngineRef {
+        self.engine.clone()
+    }
+
+    fn connections(&self) -> Option<Arc<NodeConnections>> {
+        None
+    }
+
+    async fn write_points(
+        &self,
+        tenant: &str,
+        consistency: ConsistencyLevel,
+        req: WritePointsRpcRequest,
+    ) -> Result<WriteAck> {
+        let stats = WriteStats::new(tenant, &req);
+        let res = self.engine.write(req).await.context(TskvSnafu);
+        stats.record(res.is_ok());
+        res?;
+        Ok(WriteAck::standalone(consistency))
+    }
+}
+
+/// The points are routed to the replicas of the shards owning them
+#[derive(Debug)]
+pub struct ClusterCoordinator {
+    node_id: NodeId,
+    engine: EngineRef,
+    connections: Arc<NodeConnections>,
+    router: Arc<PointRouter>,
+}
+
+impl ClusterCoordinator {
+    pub fn new(
+        node_id: NodeId,
+        engine: EngineRef,
+        connections: Arc<NodeConnections>,
+        router: Arc<PointRouter>,
+    ) -> Self {
+        Self {
+            node_id,
+            engine,
+            connections,
+            router,
+        }
+    }
+}
+
+#[async_trait]
+impl Coordinator for ClusterCoordinator {
+    fn node_id(&self) -> NodeId {
+        self.node_id
+    }
+
+    fn engine(&self) -> EngineRef {
+        self.engine.clone()
+    }
+
+    fn connections(&self) -> Option<Arc<NodeConnections>> {
+        Some(self.connections.clone())
+    }
+
+    async fn write_points(
+        &self,
+        tenant: &str,
+        consistency: ConsistencyLevel,
+        req: WritePointsRpcRequest,
+    ) -> Result<WriteAck> {
+        let stats = WriteStats::new(tenant, &req);
+        let res = self
+            .router
+            .write_points(tenant, req, consistency)
+            .instrument(info_span!("route_points", tenant))
+            .await;
+        stats.record(res.is_ok());
+        res
+    }
+}
+
+/// Points and bytes of a write counted to the database, by the node the client
+/// writes to, so the writes to the replicas are counted once
+struct WriteStats {
+    tenant: String,
+    /// None if the points are malformed
+    db: Option<String>,
+    points: u64,
+    bytes: u64,
+}
+
+impl WriteStats {
+    fn new(tenant: &str, req: &WritePointsRpcRequest) -> Self {
+        let fb_points = flatbuffers::root::<fb_models::Points>(&req.points).ok();
+        Self {
+            tenant: tenant.to_string(),
+            db: fb_points
+                .and_then(|e| e.db())
+                .map(|e| String::from_utf8_lossy(e).to_string()),
+            points: fb_points
+                .and_then(|e| e.points())
+                .map_or(0, |e| e.len() as u64),
+            bytes: req.points.len() as u64,
+        }
+    }
+
+    fn record(&self, success: bool) {
+        if let Some(db) = &self.db {
+            record_write(&self.tenant, db, self.points, self.bytes, success);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use protos::models_helper;
+    use tskv::engine::MockEngine;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_local_write() {
+        let mut fbb = flatbuffers::FlatBufferBuilder::new();
+        let points = models_helper::create_const_points(&mut fbb, 1);
+        fbb.finish(points, None);
+        let req = WritePointsRpcRequest {
+            version: 1,
+            points: fbb.finished_data().to_vec(),
+        };
+
+        let coord = LocalCoordinator::new(Arc::new(MockEngine::default()));
+        assert!(coord.connections().is_none());
+        let ack = coord
+            .write_points("cnosdb", ConsistencyLevel::Quorum, req)
+            .await
+            .unwrap();
+        assert_eq!(ack, WriteAck::standalone(ConsistencyLevel::Quorum));
+
+        let stats = metrics::database_stats::database_stats()
+            .remove(&("cnosdb".to_string(), "db0".to_string()))
+            .unwrap();
+        assert!(stats.points_written >= 1);
+    }
+}
@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use flatbuffers::FlatBufferBuilder;
+use meta::meta_client::MetaClientRef;
+use models::consistency_level::ConsistencyLevel;
+use models::meta_data::{BucketId, BucketInfo, ReplicationSet, ReplicationSetId};
+use models::SeriesKey;
+use protos::kv_service::WritePointsRpcRequest;
+use protos::models as fb_models;
+
+use crate::errors::{CoordinatorError, Result};
+use crate::writer::{PointWriter, WriteAck};
+
+/// Routes each point to the shard owning it.
+///
+/// A database is partitioned by time into buckets, and the series of a bucket are
+/// partitioned by hash into shards, see [`BucketInfo::vnode_for`]. The buckets are
+/// allocated by the meta service when the first point of the time range is written.
+#[derive(Debug)]
+pub struct PointRouter {
+    meta: MetaClientRef,
+    writer: Arc<PointWriter>,
+}
+
+impl PointRouter {
+    pub fn new(meta: MetaClientRef, writer: Arc<PointWriter>) -> Self {
+        Self { meta, writer }
+    }
+
+    /// Writes the points to the shards owning them, returns the acknowledgements
+    /// of the replicas of all the shards
+    pub async fn write_points(
+        &self,
+        tenant: &str,
+        req: WritePointsRpcRequest,
+        consistency: ConsistencyLevel,
+    ) -> Result<WriteAck> {
+        let shards = self.split(tenant, &req.points).await?;
+        self.writer.write_shards(shards, consistency).await
+    }
+
+    /// Groups the points by the shard owning them
+    pub async fn split(
+        &self,
+        tenant: &str,
+        points: &[u8],
+    ) -> Result<Vec<(ReplicationSet, Vec<u8>)>> {
+        let invalid = |msg: String| CoordinatorError::InvalidPoints { msg };
+        let fb_points =
+            flatbuffers::root::<fb_models::Points>(points).map_err(|e| invalid(e.to_string()))?;
+        let db_bytes = fb_points.db().unwrap_or_default();
+        let db = String::from_utf8(db_bytes.to_vec()).map_err(|e| invalid(e.to_string()))?;
+
+        // Points of a write are mostly in the same bucket
+        let mut cached: Option<BucketInfo> = None;
+        let mut shards: HashMap<(BucketId, ReplicationSetId), (ReplicationSet, Vec<_>)> =
+            HashMap::new();
+        for point in fb_points.points().into_iter().flatten() {
+            let ts = point.timestamp();
+            if !cached.as_ref().map_or(false, |e| e.contains(ts)) {
+                cached = Some(self.meta.locate_bucket_for_write(tenant, &db, ts).await?);
+            }
+            let bucket = cached.as_ref().unwrap();
+            let hash = SeriesKey::from_flatbuffer(&point)
+                .map_err(|e| invalid(e.to_string()))?
+                .hash();
+            let set = bucket
+                .vnode_for(hash)
+                .ok_or(CoordinatorError::EmptyBucket { id: bucket.id })?;
+
+            shards
+                .entry((bucket.id, set.id))
+                .or_insert_with(|| (set.clone(), vec![]))
+                .1
+                .push(point);
+        }
+
+        Ok(shards
+            .into_values()
+            .map(|(set, points)| (set, encode_points(db_bytes, &points)))
+            .collect())
+    }
+}
+
+/// Encodes the points into the flatbuffers `Points` of the database
+pub fn encode_points(db: &[u8], points: &[fb_models::Point]) -> Vec<u8> {
+    let mut fbb = FlatBufferBuilder::new();
+    let mut point_offsets = Vec::with_capacity(points.len());
+    for point in points {
+        let mut tags = vec![];
+        for tag in point.tags().into_iter().flatten() {
+            let key = fbb.create_vector(tag.key().unwrap_or_default());
+            let value = fbb.create_vector(tag.value().unwrap_or_default());
+            let mut tag_builder = fb_models::TagBuilder::new(&mut fbb);
+            tag_builder.add_key(key);
+            tag_builder.add_value(value);
+            tags.push(tag_builder.finish());
+        }
+        let mut fields = vec![];
+        for field in point.fields().into_iter().flatten() {
+            let name = fbb.create_vector(field.name().unwrap_or_default());
+            let value = fbb.create_vector(field.value().unwrap_or_default());
+            let mut field_builder = fb_models::FieldBuilder::new(&mut fbb);
+            field_builder.add_name(name);
+            field_builder.add_type_(field.type_());
+            field_builder.add_value(value);
+            fields.push(field_builder.finish());
+        }
+        let point_args = fb_models::PointArgs {
+            db: Some(fbb.create_vector(point.db().unwrap_or(db))),
+            tab: Some(fbb.create_vector(point.tab().unwrap_or_default())),
+            tags: Some(fbb.create_vector(&tags)),
+            fields: Some(fbb.create_vector(&fields)),
+            timestamp: point.timestamp(),
+        };
+        point_offsets.push(fb_models::Point::create(&mut fbb, &point_args));
+    }
+
+    let fbb_db = fbb.create_vector(db);
+    let points_raw = fbb.create_vector(&point_offsets);
+    let points = fb_models::Points::create(
+        &mut fbb,
+        &fb_models::PointsArgs {
+            db: Some(fbb_db),
+            points: Some(points_raw),
+        },
+    );
+    fbb.finish(points, None);
+    fbb.finished_data().to_vec()
+}
+
+#[cfg(test)]
+mod test {
+    use meta::meta_client::MockMetaClient;
+    use models::meta_data::{DatabaseInfo, TenantMetaData, VnodeInfo};
+    use models::schema::DatabaseSchema;
+    use protos::models_helper;
+    use tskv::engine::MockEngine;
+
+    use super::*;
+    use crate::connection::NodeConnections;
+
+    fn bucket(id: BucketId, start_time: i64, end_time: i64) -> BucketInfo {
+        BucketInfo {
+            id,
+            start_time,
+            end_time,
+            shard_group: (0..2)
+                .map(|i| ReplicationSet {
+                    id: id * 10 + i,
+                    vnodes: vec![VnodeInfo {
+                        id: id * 10 + i,
+                        node_id: 1,
+                    }],
+                })
+                .collect(),
+        }
+    }
+
+    fn router() -> PointRouter {
+        let mut db = DatabaseInfo::new(DatabaseSchema::new("db"));
+        db.buckets.push(bucket(1, 0, 100));
+        db.buckets.push(bucket(2, 100, 200));
+        let mut tenant = TenantMetaData::default();
+        tenant.dbs.insert("db".to_string(), db);
+
+        let meta = Arc::new(MockMetaClient::default());
+        meta.set_tenant_meta("cnosdb", tenant);
+        let connections = Arc::new(NodeConnections::new(meta.clone()));
+        let writer = PointWriter::new(1, Arc::new(MockEngine::default()), connections);
+        PointRouter::new(meta, Arc::new(writer))
+    }
+
+    #[tokio::test]
+    async fn test_split_points() {
+        let mut fbb = flatbuffers::FlatBufferBuilder::new();
+        let db = fbb.create_vector("db".as_bytes());
+        let mut point_offsets = vec![];
+        for i in 0..20 {
+            let host = format!("h{}", i % 7);
+            let tags = models_helper::create_tags(&mut fbb, vec![("host", &host)]);
+            let value = (i as i64).to_be_bytes();
+            let fields = models_helper::create_fields(
+                &mut fbb,
+                vec![("usage", fb_models::FieldType::Integer, value.as_slice())],
+            );
+            let table = fbb.create_vector("cpu".as_bytes());
+            point_offsets.push(models_helper::create_point(
+                &mut fbb,
+                i * 10,
+                db,
+                table,
+                tags,
+                fields,
+            ));
+        }
+        let point_offsets = fbb.create_vector(&point_offsets);
+        let points = fb_models::Points::create(
+            &mut fbb,
+            &fb_models::PointsArgs {
+                db: Some(db),
+                points: Some(point_offsets),
+            },
+        );
+        fbb.finish(points, None);
+        let points = fbb.finished_data();
+        let fb_points = flatbuffers::root::<fb_models::Points>(points).unwrap();
+        let num = fb_points.points().unwrap().len();
+
+        let router = router();
+        let shards = router.split("cnosdb", points).await.unwrap();
+        assert!(!shards.is_empty());
+
+        let mut split_num = 0;
+        for (set, points) in shards {
+            let fb_points = flatbuffers::root::<fb_models::Points>(&points).unwrap();
+            assert_eq!(fb_points.db().unwrap(), b"db");
+            for point in fb_points.points().unwrap() {
+                let hash = SeriesKey::from_flatbuffer(&point).unwrap().hash();
+                let bucket = if point.timestamp() < 100 {
+                    bucket(1, 0, 100)
+                } else {
+                    bucket(2, 100, 200)
+                };
+                assert_eq!(bucket.vnode_for(hash), Some(&set));
+                split_num += 1;
+            }
+        }
+        assert_eq!(split_num, num);
+
+        router
+            .write_points(
+                "cnosdb",
+                WritePointsRpcRequest {
+                    version: 1,
+                    points: points.to_vec(),
+                },
+                ConsistencyLevel::All,
+            )
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,431 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::stream::FuturesUnordered;
+use futures::StreamExt;
+use models::consistency_level::ConsistencyLevel;
+use models::meta_data::{NodeId, ReplicationSet};
+use protos::kv_service::WritePointsRpcRequest;
+use protos::models as fb_models;
+use serde::Serialize;
+use snafu::ResultExt;
+use trace::{debug, warn};
+use tskv::engine::EngineRef;
+
+use crate::connection::NodeConnections;
+use crate::errors::{CoordinatorError, Result, TskvSnafu};
+use crate::hinted_handoff::HintedHandoff;
+use crate::sharding::encode_points;
+
+const WRITE_RETRIES: usize = 3;
+const RETRY_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Consistency level of the writes not specifying one
+pub const DEFAULT_WRITE_CONSISTENCY: ConsistencyLevel = ConsistencyLevel::Quorum;
+
+/// Acknowledgements of the replicas of a write
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct WriteAck {
+    pub consistency: ConsistencyLevel,
+    /// Replicas of the shards written
+    pub replicas: usize,
+    /// Replicas acknowledged the write before it returns
+    pub acked: usize,
+    /// Replicas failed to write and buffered by the hinted handoff
+    pub hinted: usize,
+}
+
+impl WriteAck {
+    pub fn new(consistency: ConsistencyLevel) -> Self {
+        Self {
+            consistency,
+            replicas: 0,
+            acked: 0,
+            hinted: 0,
+        }
+    }
+
+    /// Acknowledgements of the write of all the replicas of a standalone node
+    pub fn standalone(consistency: ConsistencyLevel) -> Self {
+        Self {
+            consistency,
+            replicas: 1,
+            acked: 1,
+            hinted: 0,
+        }
+    }
+
+    /// Adds up the acknowledgements of the writes of different shards
+    pub fn merge(&mut self, other: &WriteAck) {
+        self.replicas += other.replicas;
+        self.acked += other.acked;
+        self.hinted += other.hinted;
+    }
+
+    fn satisfied(&self) -> bool {
+        match self.consistency {
+            // A hint is enough, the write may not happen until the node is back
+            ConsistencyLevel::Any => self.acked + self.hinted >= 1,
+            _ => self.acked >= self.consistency.required(self.replicas),
+        }
+    }
+}
+
+/// Writes the points of the shards to all of their replicas
+#[derive(Debug)]
+pub struct PointWriter {
+    node_id: NodeId,
+    engine: EngineRef,
+    connections: Arc<NodeConnections>,
+    handoff: Option<Arc<HintedHandoff>>,
+}
+
+impl PointWriter {
+    pub fn new(node_id: NodeId, engine: EngineRef, connections: Arc<NodeConnections>) -> Self {
+        Self {
+            node_id,
+            engine,
+            connections,
+            handoff: None,
+        }
+    }
+
+    /// Buffers the writes of the unavailable replicas to replay later
+    pub fn with_hinted_handoff(mut self, handoff: Arc<HintedHandoff>) -> Self {
+        self.handoff = Some(handoff);
+        self
+    }
+
+    /// The write is sent to all the replicas, and returns once the replicas required
+    /// by the consistency level acknowledge it, the others complete in the background.
+    /// The replicas failed to write catch up by the hinted handoff or from the others
+    /// later.
+    pub async fn write_to_replication_set(
+        self: &Arc<Self>,
+        set: &ReplicationSet,
+        req: WritePointsRpcRequest,
+        consistency: ConsistencyLevel,
+    ) -> Result<WriteAck> {
+        self.write_shards(vec![(set.clone(), req.points)], consistency)
+            .await
+    }
+
+    /// Writes the points of several shards like [`Self::write_to_replication_set`],
+    /// the points of all the shards replicated on a data node are sent to it in one
+    /// request. Returns once every shard is acknowledged as the consistency level
+    /// requires.
+    pub async fn write_shards(
+        self: &Arc<Self>,
+        shards: Vec<(ReplicationSet, Vec<u8>)>,
+        consistency: ConsistencyLevel,
+    ) -> Result<WriteAck> {
+        // Data node -> the shards replicated on it
+        let mut nodes: BTreeMap<NodeId, Vec<usize>> = BTreeMap::new();
+        for (i, (set, _)) in shards.iter().enumerate() {
+            for vnode in set.vnodes.iter() {
+                nodes.entry(vnode.node_id).or_default().push(i);
+            }
+        }
+
+        let pending: FuturesUnordered<_> = FuturesUnordered::new();
+        for (node_id, indexes) in nodes {
+            let points = merge_points(indexes.iter().map(|i| shards[*i].1.as_slice()))?;
+            let req = WritePointsRpcRequest { version: 1, points };
+            let writer = self.clone();
+            pending.push(async move {
+                let res = writer.write_to_node_with_retry(node_id, req.clone()).await;
+                let hinted = match &res {
+                    Ok(_) => false,
+                    Err(e) => {
+                        warn!(
+                            "Failed to write {} shards on node {}: {}",
+                            indexes.len(),
+                            node_id,
+                            e
+                        );
+                        writer.add_hint(node_id, &req).await
+                    }
+                };
+                (node_id, indexes, res, hinted)
+            });
+        }
+
+        let mut acks: Vec<WriteAck> = shards
+            .iter()
+            .map(|(set, _)| WriteAck {
+                replicas: set.vnodes.len(),
+                ..WriteAck::new(consistency)
+            })
+            .collect();
+        let mut pending = pending;
+        let mut errors = vec![];
+        while !acks.iter().all(|e| e.satisfied()) {
+            let (node_id, indexes, res, hinted) = match pending.next().await {
+                Some(result) => result,
+                None => break,
+            };
+            for i in indexes {
+                if res.is_ok() {
+                    acks[i].acked += 1;
+                }
+                if hinted {
+                    acks[i].hinted += 1;
+                }
+            }
+            if let Err(e) = res {
+                errors.push(format!("node {}: {}", node_id, e));
+            }
+        }
+
+        if let Some(ack) = acks.iter().find(|e| !e.satisfied()) {
+            return Err(CoordinatorError::NotEnoughReplicas {
+                success: ack.acked,
+                replicas: ack.replicas,
+                required: consistency.required(ack.replicas),
+                msg: errors.join(", "),
+            });
+        }
+        if !pending.is_empty() {
+            tokio::spawn(async move { while pending.next().await.is_some() {} });
+        }
+
+        let mut total = WriteAck::new(consistency);
+        for ack in acks.iter() {
+            total.merge(ack);
+        }
+        Ok(total)
+    }
+
+    /// Retries the write on the errors of the connection, a write applied twice is
+    /// stored once.
+    async fn write_to_node_with_retry(
+        &self,
+        node_id: NodeId,
+        req: WritePointsRpcRequest,
+    ) -> Result<()> {
+        let mut backoff = RETRY_BACKOFF;
+        let mut retries = 0;
+        loop {
+            match self.write_to_node(node_id, req.clone()).await {
+                Err(e) if e.is_retryable() && retries < WRITE_RETRIES => {
+                    debug!(
+                        "Retry the write on node {} in {:?}: {}",
+                        node_id, backoff, e
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                    retries += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+
+    /// Buffers the write of the node, returns whether it is buffered
+    async fn add_hint(&self, node_id: NodeId, req: &WritePointsRpcRequest) -> bool {
+        if node_id == self.node_id {
+            return false;
+        }
+        match &self.handoff {
+            Some(handoff) => match handoff.add(node_id, req.clone()).await {
+                Ok(added) => added,
+                Err(e) => {
+                    warn!("Failed to add hint of node {}: {}", node_id, e);
+                    false
+                }
+            },
+            None => false,
+        }
+    }
+
+    pub async fn write_to_node(&self, node_id: NodeId, req: WritePointsRpcRequest) -> Result<()> {
+        if node_id == self.node_id {
+            self.engine.write(req).await.context(TskvSnafu)?;
+            return Ok(());
+        }
+
+        let mut client = self.connections.client(node_id).await?;
+        let res = match client
+            .write_points(self.connections.request(futures::stream::iter(vec![req])))
+            .await
+        {
+            Ok(resp) => resp.into_inner().message().await,
+            Err(status) => Err(status),
+        };
+        match res {
+            Ok(Some(_)) => Ok(()),
+            Ok(None) => Err(CoordinatorError::Grpc {
+                code: tonic::Code::Unknown,
+                msg: format!("no response of node {}", node_id),
+            }),
+            Err(status) => {
+                self.connections.invalidate(node_id);
+                Err(status.into())
+            }
+        }
+    }
+}
+
+/// Merges the flatbuffers `Points` of the same database into one
+fn merge_points<'a>(payloads: impl Iterator<Item = &'a [u8]>) -> Result<Vec<u8>> {
+    let payloads: Vec<&[u8]> = payloads.collect();
+    if payloads.len() == 1 {
+        return Ok(payloads[0].to_vec());
+    }
+
+    let mut db: &[u8] = &[];
+    let mut points = vec![];
+    for payload in payloads {
+        let fb_points = flatbuffers::root::<fb_models::Points>(payload)
+            .map_err(|e| CoordinatorError::InvalidPoints { msg: e.to_string() })?;
+        db = fb_points.db().unwrap_or_default();
+        points.extend(fb_points.points().into_iter().flatten());
+    }
+    Ok(encode_points(db, &points))
+}
+
+#[cfg(test)]
+mod test {
+    use meta::meta_client::MockMetaClient;
+    use models::meta_data::VnodeInfo;
+    use protos::models_helper;
+    use tskv::engine::MockEngine;
+
+    use super::*;
+
+    fn request() -> WritePointsRpcRequest {
+        let mut fbb = flatbuffers::FlatBufferBuilder::new();
+        let points = models_helper::create_const_points(&mut fbb, 1);
+        fbb.finish(points, None);
+        WritePointsRpcRequest {
+            version: 1,
+            points: fbb.finished_data().to_vec(),
+        }
+    }
+
+    fn replication_set(nodes: &[NodeId]) -> ReplicationSet {
+        ReplicationSet {
+            id: 1,
+            vnodes: nodes
+                .iter()
+                .enumerate()
+                .map(|(i, node_id)| VnodeInfo {
+                    id: i as u32 + 2,
+                    node_id: *node_id,
+                })
+                .collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_write_replicas() {
+        let connections = Arc::new(NodeConnections::new(Arc::new(MockMetaClient::default())));
+        let writer = Arc::new(PointWriter::new(
+            1,
+            Arc::new(MockEngine::default()),
+            connections,
+        ));
+
+        let ack = writer
+            .write_to_replication_set(&replication_set(&[1]), request(), ConsistencyLevel::All)
+            .await
+            .unwrap();
+        assert_eq!(ack, WriteAck::standalone(ConsistencyLevel::All));
+
+        // Node 2 and 3 are not registered
+        match writer
+            .write_to_replication_set(
+                &replication_set(&[1, 2, 3]),
+                request(),
+                ConsistencyLevel::Quorum,
+            )
+            .await
+        {
+            Err(CoordinatorError::NotEnoughReplicas {
+                success, required, ..
+            }) => {
+                assert_eq!(success, 1);
+                assert_eq!(required, 2);
+            }
+            res => panic!("unexpected result {:?}", res),
+        }
+
+        let ack = writer
+            .write_to_replication_set(
+                &replication_set(&[1, 2, 3]),
+                request(),
+                ConsistencyLevel::One,
+            )
+            .await
+            .unwrap();
+        assert_eq!(ack.replicas, 3);
+        assert_eq!(ack.acked, 1);
+    }
+
+    #[tokio::test]
+    async fn test_hint_failed_replicas() {
+        let dir = tempfile::tempdir().unwrap();
+        let handoff = Arc::new(HintedHandoff::open(dir.path(), 1024 * 1024).unwrap());
+        let connections = Arc::new(NodeConnections::new(Arc::new(MockMetaClient::default())));
+        let writer = Arc::new(
+            PointWriter::new(1, Arc::new(MockEngine::default()), connections)
+                .with_hinted_handoff(handoff.clone()),
+        );
+
+        // Node 2 is not registered
+        writer
+            .write_to_replication_set(&replication_set(&[1, 2]), request(), ConsistencyLevel::All)
+            .await
+            .unwrap_err();
+        assert_eq!(handoff.pending(1), 0);
+        assert_eq!(handoff.pending(2), 1);
+
+        // The hint is enough for the write of any level
+        let ack = writer
+            .write_to_replication_set(&replication_set(&[2]), request(), ConsistencyLevel::Any)
+            .await
+            .unwrap();
+        assert_eq!(ack.acked, 0);
+        assert_eq!(ack.hinted, 1);
+        assert_eq!(handoff.pending(2), 2);
+    }
+
+    #[tokio::test]
+    async fn test_write_shards() {
+        let dir = tempfile::tempdir().unwrap();
+        let handoff = Arc::new(HintedHandoff::open(dir.path(), 1024 * 1024).unwrap());
+        let connections = Arc::new(NodeConnections::new(Arc::new(MockMetaClient::default())));
+        let writer = Arc::new(
+            PointWriter::new(1, Arc::new(MockEngine::default()), connections)
+                .with_hinted_handoff(handoff.clone()),
+        );
+
+        let shards = vec![
+            (replication_set(&[1, 2]), request().points),
+            (replication_set(&[1, 2]), request().points),
+        ];
+        // Node 2 is not registered, the points of both shards are sent to it in one request
+        writer
+            .write_shards(shards.clone(), ConsistencyLevel::All)
+            .await
+            .unwrap_err();
+        assert_eq!(handoff.pending(2), 1);
+
+        let ack = writer
+            .write_shards(shards, ConsistencyLevel::One)
+            .await
+            .unwrap();
+        assert_eq!(ack.replicas, 4);
+        assert_eq!(ack.acked, 2);
+    }
+
+    #[test]
+    fn test_merge_points() {
+        let points = request().points;
+        let merged = merge_points([points.as_slice(), points.as_slice()].into_iter()).unwrap();
+        let fb_points = flatbuffers::root::<fb_models::Points>(&merged).unwrap();
+        assert_eq!(fb_points.db().unwrap(), b"db0");
+        assert_eq!(fb_points.points().unwrap().len(), 2);
+    }
+}
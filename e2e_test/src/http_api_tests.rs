@@ -1,7 +1,7 @@
 #[cfg(test)]
 mod test {
     use http_protocol::{
-        header::{HeaderValue, ACCEPT, CONTENT_TYPE},
+        header::{HeaderValue, ACCEPT, CONTENT_TYPE, PROMETHEUS_TEXT},
         http_client::HttpClient,
         response::Response,
         status_code,
@@ -273,6 +273,27 @@ mod test {
             .unwrap();
         assert_eq!(resp.status(), status_code::OK);
 
+        let resp: Response = client
+            .post(path)
+            .query(&[("db", "public"), ("consistency", "all")])
+            .basic_auth::<&str, &str>(username, None)
+            .body(body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), status_code::OK);
+
+        // unknown consistency level
+        let resp: Response = client
+            .post(path)
+            .query(&[("db", "public"), ("consistency", "two")])
+            .basic_auth::<&str, &str>(username, None)
+            .body(body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), status_code::BAD_REQUEST);
+
         // lost username
         let resp: Response = client
             .post(path)
@@ -358,4 +379,38 @@ mod test {
         let resp: Response = client.delete(path).send().await.unwrap();
         assert_eq!(resp.status(), status_code::METHOD_NOT_ALLOWED);
     }
+
+    #[tokio::test]
+    async fn test_metrics_path() {
+        let path = "/metrics";
+
+        let client = client();
+
+        let resp: Response = client.get(path).send().await.unwrap();
+        assert_eq!(resp.status(), status_code::OK);
+        assert_eq!(
+            resp.headers().get(CONTENT_TYPE).unwrap(),
+            HeaderValue::from_static(PROMETHEUS_TEXT)
+        );
+        let body = resp.text().await.unwrap();
+        assert!(body.contains("server_tskv_compaction_backlog"));
+        assert!(body.contains("server_tskv_wal_size_bytes"));
+
+        let resp: Response = client.post(path).send().await.unwrap();
+        assert_eq!(resp.status(), status_code::METHOD_NOT_ALLOWED);
+    }
+
+    #[tokio::test]
+    async fn test_health_path() {
+        let client = client();
+
+        let resp: Response = client.get("/health/live").send().await.unwrap();
+        assert_eq!(resp.status(), status_code::OK);
+
+        let resp: Response = client.get("/health/ready").send().await.unwrap();
+        assert_eq!(resp.status(), status_code::OK);
+        let body = resp.text().await.unwrap();
+        assert!(body.contains("\"ready\":true"));
+        assert!(body.contains("disk_wal"));
+    }
 }
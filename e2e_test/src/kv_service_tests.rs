@@ -14,6 +14,15 @@ mod test {
             .ca_certificate(ca)
             .domain_name("cnosdb.com")
     }
+    /// Authenticated as the default admin `cnosdb` without password
+    fn authorized<T>(message: T) -> Request<T> {
+        let mut request = Request::new(message);
+        request
+            .metadata_mut()
+            .insert("authorization", "Basic Y25vc2RiOg==".parse().unwrap());
+        request
+    }
+
     async fn get_client(tls: bool) -> TskvServiceClient<Channel> {
         if tls {
             let channel = Channel::from_static("http://127.0.0.1:31006")
@@ -49,7 +58,7 @@ mod test {
         let mut client = get_client(true).await;
 
         let resp = client
-            .ping(Request::new(PingRequest {
+            .ping(authorized(PingRequest {
                 version: 10,
                 body: finished_data.to_vec(),
             }))
@@ -85,7 +94,11 @@ mod test {
 
         let mut client = get_client(true).await;
 
-        let mut resp_stream = client.write_points(req_stream).await.unwrap().into_inner();
+        let mut resp_stream = client
+            .write_points(authorized(req_stream))
+            .await
+            .unwrap()
+            .into_inner();
 
         loop {
             match resp_stream.message().await {
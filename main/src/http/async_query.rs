@@ -0,0 +1,869 @@
+//! Asynchronous queries over http.
+//!
+//! A long running query is submitted and executed in the background, its results
+//! are spooled to arrow ipc files of pages of rows under the storage directory as
+//! they are produced, so the client polls the status and fetches the pages one by
+//! one, even before the query finishes, instead of holding the connection open.
+//! The results are removed once expired.
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use datafusion::arrow::compute::concat_batches;
+use datafusion::arrow::error::{ArrowError, Result as ArrowResult};
+use datafusion::arrow::ipc::reader::FileReader;
+use datafusion::arrow::ipc::writer::FileWriter;
+use datafusion::arrow::record_batch::RecordBatch;
+use parking_lot::{Mutex, RwLock};
+use serde::Serialize;
+use snafu::ResultExt;
+use spi::query::execution::BatchSink;
+use spi::server::dbms::DBMSRef;
+use spi::service::protocol::Query;
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+
+use super::result_format::fetch_record_batches;
+use super::{Error as HttpError, QuerySnafu};
+
+/// Directory of the results under the storage path
+pub const ASYNC_RESULTS_DIR: &str = "async_results";
+const DEFAULT_PAGE_SIZE: usize = 10000;
+const MAX_PAGE_SIZE: usize = 1000000;
+const EXPIRE_INTERVAL: Duration = Duration::from_secs(60);
+/// Batches produced but not spooled yet, the query waits once reached
+const SPOOL_BUFFER: usize = 4;
+
+pub type AsyncQueriesRef = Arc<AsyncQueries>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AsyncQueryState {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/// Status of an asynchronous query returned by the submit and the status endpoints
+#[derive(Debug, Clone, Serialize)]
+pub struct AsyncQueryStatus {
+    pub id: String,
+    pub state: AsyncQueryState,
+    pub sql: String,
+    /// Unix timestamp in milliseconds
+    pub submitted_at: u64,
+    pub elapsed_ms: u64,
+    pub rows: u64,
+    /// Pages of the results, fetched by `page` from 0
+    pub pages: usize,
+    pub error: Option<String>,
+    /// Unix timestamp in milliseconds the results are removed at, once finished
+    pub expires_at: Option<u64>,
+}
+
+/// Rows and pages of the results spooled so far
+#[derive(Debug, Clone, Copy, Default)]
+struct Spooled {
+    rows: u64,
+    pages: usize,
+    /// The query is finished or removed, no more pages are spooled
+    done: bool,
+}
+
+struct AsyncQuery {
+    user: String,
+    /// Directory of the files of the pages
+    dir: PathBuf,
+    started: Instant,
+    status: Mutex<AsyncQueryStatus>,
+    finished: Mutex<Option<Instant>>,
+    task: Mutex<Option<JoinHandle<()>>>,
+    usage: SpoolUsage,
+    spooled: watch::Sender<Spooled>,
+    /// Taken by the paged query waiting for its first page to return it as is
+    error: Mutex<Option<HttpError>>,
+}
+
+impl AsyncQuery {
+    fn status(&self) -> AsyncQueryStatus {
+        let mut status = self.status.lock().clone();
+        if status.state == AsyncQueryState::Running {
+            let spooled = *self.spooled.borrow();
+            status.elapsed_ms = self.started.elapsed().as_millis() as u64;
+            status.rows = spooled.rows;
+            status.pages = spooled.pages;
+        }
+        status
+    }
+
+    fn finish(&self, result: Result<(), HttpError>, ttl: Duration) {
+        let spooled = *self.spooled.borrow();
+        {
+            let mut status = self.status.lock();
+            status.elapsed_ms = self.started.elapsed().as_millis() as u64;
+            status.expires_at = Some(unix_millis() + ttl.as_millis() as u64);
+            status.rows = spooled.rows;
+            status.pages = spooled.pages;
+            match result {
+                Ok(()) => status.state = AsyncQueryState::Succeeded,
+                Err(error) => {
+                    status.state = AsyncQueryState::Failed;
+                    status.error = Some(error.to_string());
+                    *self.error.lock() = Some(error);
+                }
+            }
+        }
+        *self.finished.lock() = Some(Instant::now());
+        self.spooled.send_modify(|e| e.done = true);
+    }
+
+    /// Waits until the page is spooled or no more pages are, returns the pages spooled
+    async fn wait_for_page(&self, page: usize) -> Spooled {
+        let mut spooled = self.spooled.subscribe();
+        loop {
+            let current = *spooled.borrow();
+            if current.pages > page || current.done || spooled.changed().await.is_err() {
+                return current;
+            }
+        }
+    }
+
+    /// The error of the query once failed, the pages spooled before are not returned
+    fn check_failed(&self) -> Result<(), HttpError> {
+        let status = self.status.lock();
+        match status.state {
+            AsyncQueryState::Failed => Err(HttpError::FetchResult {
+                reason: status.error.clone().unwrap_or_default(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    fn page_path(&self, page: usize) -> PathBuf {
+        page_path(&self.dir, page)
+    }
+
+    /// Cancels the query if running and removes its results
+    fn remove(&self) {
+        if let Some(task) = self.task.lock().take() {
+            task.abort();
+        }
+        self.usage.release();
+        self.spooled.send_modify(|e| e.done = true);
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Bytes of the results of a query spooled, counted into those of its user
+#[derive(Clone)]
+struct SpoolUsage {
+    user: String,
+    /// `None` once the results are removed
+    bytes: Arc<Mutex<Option<u64>>>,
+    user_bytes: Arc<AtomicU64>,
+    max_user_bytes: u64,
+}
+
+impl SpoolUsage {
+    fn add(&self, bytes: u64) {
+        if let Some(total) = self.bytes.lock().as_mut() {
+            *total += bytes;
+            self.user_bytes.fetch_add(bytes, Ordering::Relaxed);
+        }
+    }
+
+    fn check(&self) -> Result<(), HttpError> {
+        let user_bytes = self.user_bytes.load(Ordering::Relaxed);
+        if self.max_user_bytes > 0 && user_bytes > self.max_user_bytes {
+            return Err(HttpError::AsyncQueryLimit {
+                user: self.user.clone(),
+                reason: format!(
+                    "{} bytes of the results spooled of {}",
+                    user_bytes, self.max_user_bytes
+                ),
+            });
+        }
+        Ok(())
+    }
+
+    /// The bytes of the query are given back to the user once its results are removed
+    fn release(&self) {
+        if let Some(bytes) = self.bytes.lock().take() {
+            self.user_bytes.fetch_sub(bytes, Ordering::Relaxed);
+        }
+    }
+
+    fn is_released(&self) -> bool {
+        self.bytes.lock().is_none()
+    }
+}
+
+/// Counts the bytes written to the file of the results, including those buffered
+struct CountingWriter<W> {
+    inner: W,
+    usage: SpoolUsage,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        self.usage.add(len as u64);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Sends the batches of the results of the query to the spool, closed once the
+/// query is done
+struct SpoolSink {
+    sender: Mutex<Option<mpsc::Sender<RecordBatch>>>,
+}
+
+impl SpoolSink {
+    fn close(&self) {
+        self.sender.lock().take();
+    }
+}
+
+#[async_trait]
+impl BatchSink for SpoolSink {
+    async fn append(&self, batch: RecordBatch) -> ArrowResult<()> {
+        let closed = || ArrowError::IoError("the spool of the results is closed".to_string());
+        let sender = self.sender.lock().clone().ok_or_else(closed)?;
+        sender.send(batch).await.map_err(|_| closed())
+    }
+}
+
+/// The asynchronous queries of all the users, kept in memory, so they are lost on
+/// restart
+pub struct AsyncQueries {
+    dir: PathBuf,
+    ttl: Duration,
+    max_queries_per_user: usize,
+    max_bytes_per_user: u64,
+    next_id: AtomicU64,
+    queries: RwLock<HashMap<String, Arc<AsyncQuery>>>,
+    /// Bytes of the results spooled by the users
+    user_bytes: Mutex<HashMap<String, Arc<AtomicU64>>>,
+}
+
+impl AsyncQueries {
+    /// The results left in the directory by the last run are removed
+    pub fn new(dir: impl AsRef<Path>, ttl: Duration) -> std::io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir)?;
+        }
+        std::fs::create_dir_all(&dir)?;
+
+        Ok(Self {
+            dir,
+            ttl,
+            max_queries_per_user: 0,
+            max_bytes_per_user: 0,
+            next_id: AtomicU64::new(0),
+            queries: RwLock::new(HashMap::new()),
+            user_bytes: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Queries of each user running or with the results kept and bytes of the
+    /// results spooled of each user, 0 for no limit
+    pub fn with_limits(mut self, max_queries_per_user: usize, max_bytes_per_user: u64) -> Self {
+        self.max_queries_per_user = max_queries_per_user;
+        self.max_bytes_per_user = max_bytes_per_user;
+        self
+    }
+
+    /// Removes the expired results every minute until dropped
+    pub fn start(self: &Arc<Self>) {
+        let queries = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(EXPIRE_INTERVAL);
+            loop {
+                ticker.tick().await;
+                match Weak::upgrade(&queries) {
+                    Some(queries) => queries.expire(),
+                    None => break,
+                }
+            }
+        });
+    }
+
+    /// Executes the query in the background, the results are split into pages of
+    /// `page_size` rows
+    pub fn submit(
+        self: &Arc<Self>,
+        dbms: DBMSRef,
+        query: Query,
+        page_size: Option<usize>,
+    ) -> Result<AsyncQueryStatus, HttpError> {
+        let page_size = page_size.unwrap_or(DEFAULT_PAGE_SIZE);
+        Ok(self.start_query(dbms, query, page_size)?.status())
+    }
+
+    /// Executes the query in the background and returns the first page once spooled
+    /// with the cursor of the next, the query failed before is removed and its error
+    /// is returned as is
+    pub async fn execute(
+        self: &Arc<Self>,
+        dbms: DBMSRef,
+        query: Query,
+        page_size: usize,
+    ) -> Result<(Vec<RecordBatch>, Option<String>), HttpError> {
+        let async_query = self.start_query(dbms, query, page_size)?;
+        async_query.wait_for_page(0).await;
+        let error = async_query.error.lock().take();
+        if let Some(e) = error {
+            let id = async_query.status.lock().id.clone();
+            self.queries.write().remove(&id);
+            async_query.remove();
+            return Err(e);
+        }
+
+        let cursor = cursor_of(&async_query.status.lock().id, 0);
+        self.next_page(&async_query.user, &cursor).await
+    }
+
+    /// The rows of the page the cursor points to and the cursor of the next page,
+    /// `None` after the last one, the next page is waited for to be spooled
+    pub async fn next_page(
+        &self,
+        user: &str,
+        cursor: &str,
+    ) -> Result<(Vec<RecordBatch>, Option<String>), HttpError> {
+        let (id, page) = parse_cursor(cursor)?;
+        let batches = self.page(user, id, page).await?;
+        let async_query = self.get(user, id)?;
+        let spooled = async_query.wait_for_page(page + 1).await;
+        let next = if page + 1 < spooled.pages {
+            Some(cursor_of(id, page + 1))
+        } else {
+            // The results are incomplete
+            async_query.check_failed()?;
+            None
+        };
+        Ok((batches, next))
+    }
+
+    fn start_query(
+        self: &Arc<Self>,
+        dbms: DBMSRef,
+        query: Query,
+        page_size: usize,
+    ) -> Result<Arc<AsyncQuery>, HttpError> {
+        let page_size = page_size.clamp(1, MAX_PAGE_SIZE);
+        let async_query = Arc::new(self.new_query(&query));
+        {
+            let mut queries = self.queries.write();
+            self.check_queries(&queries, &async_query.user)?;
+            queries.insert(async_query.status().id, async_query.clone());
+        }
+
+        let ttl = self.ttl;
+        let task = tokio::spawn({
+            let async_query = async_query.clone();
+            async move {
+                let result = execute(dbms, &query, &async_query, page_size).await;
+                if let Err(e) = &result {
+                    trace::info!("Asynchronous query {} failed: {}", query.content(), e);
+                }
+                async_query.finish(result, ttl);
+            }
+        });
+        *async_query.task.lock() = Some(task);
+
+        Ok(async_query)
+    }
+
+    fn check_queries(
+        &self,
+        queries: &HashMap<String, Arc<AsyncQuery>>,
+        user: &str,
+    ) -> Result<(), HttpError> {
+        let count = queries.values().filter(|e| e.user == user).count();
+        if self.max_queries_per_user > 0 && count >= self.max_queries_per_user {
+            return Err(HttpError::AsyncQueryLimit {
+                user: user.to_string(),
+                reason: format!(
+                    "{} queries running or with the results kept of {}",
+                    count, self.max_queries_per_user
+                ),
+            });
+        }
+        Ok(())
+    }
+
+    fn new_query(&self, query: &Query) -> AsyncQuery {
+        let id = format!(
+            "{:x}{:04x}",
+            unix_millis(),
+            self.next_id.fetch_add(1, Ordering::Relaxed) & 0xffff
+        );
+        let user = query.context().user_info().user.clone();
+        let user_bytes = self
+            .user_bytes
+            .lock()
+            .entry(user.clone())
+            .or_default()
+            .clone();
+        AsyncQuery {
+            usage: SpoolUsage {
+                user: user.clone(),
+                bytes: Arc::new(Mutex::new(Some(0))),
+                user_bytes,
+                max_user_bytes: self.max_bytes_per_user,
+            },
+            user,
+            dir: self.dir.join(&id),
+            started: Instant::now(),
+            status: Mutex::new(AsyncQueryStatus {
+                id,
+                state: AsyncQueryState::Running,
+                sql: query.content().to_string(),
+                submitted_at: unix_millis(),
+                elapsed_ms: 0,
+                rows: 0,
+                pages: 0,
+                error: None,
+                expires_at: None,
+            }),
+            finished: Mutex::new(None),
+            task: Mutex::new(None),
+            spooled: watch::channel(Spooled::default()).0,
+            error: Mutex::new(None),
+        }
+    }
+
+    pub fn status(&self, user: &str, id: &str) -> Result<AsyncQueryStatus, HttpError> {
+        Ok(self.get(user, id)?.status())
+    }
+
+    /// The rows of the page of the results, waited for to be spooled if the query
+    /// is running
+    pub async fn page(
+        &self,
+        user: &str,
+        id: &str,
+        page: usize,
+    ) -> Result<Vec<RecordBatch>, HttpError> {
+        let async_query = self.get(user, id)?;
+        let spooled = async_query.wait_for_page(page).await;
+        async_query.check_failed()?;
+        if spooled.pages == 0 {
+            return Ok(vec![]);
+        }
+        if page >= spooled.pages {
+            return Err(HttpError::InvalidParameter {
+                reason: format!("page {} out of the {} pages", page, spooled.pages),
+            });
+        }
+
+        let path = async_query.page_path(page);
+        tokio::task::spawn_blocking(move || read_page(&path))
+            .await
+            .map_err(fetch_error)?
+            .map(|batch| vec![batch])
+            .map_err(fetch_error)
+    }
+
+    /// Cancels the query if running and removes its results
+    pub fn remove(&self, user: &str, id: &str) -> Result<(), HttpError> {
+        self.get(user, id)?;
+        if let Some(async_query) = self.queries.write().remove(id) {
+            async_query.remove();
+        }
+        Ok(())
+    }
+
+    /// Other users may not see the queries of a user
+    fn get(&self, user: &str, id: &str) -> Result<Arc<AsyncQuery>, HttpError> {
+        self.queries
+            .read()
+            .get(id)
+            .filter(|e| e.user == user)
+            .cloned()
+            .ok_or_else(|| HttpError::AsyncQueryNotFound { id: id.to_string() })
+    }
+
+    fn expire(&self) {
+        let expired: Vec<_> = self
+            .queries
+            .read()
+            .iter()
+            .filter(|(_, e)| {
+                e.finished
+                    .lock()
+                    .map_or(false, |finished| finished.elapsed() >= self.ttl)
+            })
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in expired {
+            if let Some(async_query) = self.queries.write().remove(&id) {
+                async_query.remove();
+            }
+        }
+    }
+}
+
+async fn execute(
+    dbms: DBMSRef,
+    query: &Query,
+    async_query: &Arc<AsyncQuery>,
+    page_size: usize,
+) -> Result<(), HttpError> {
+    let (sender, receiver) = mpsc::channel(SPOOL_BUFFER);
+    let sink = Arc::new(SpoolSink {
+        sender: Mutex::new(Some(sender)),
+    });
+    let writer = tokio::task::spawn_blocking({
+        let async_query = async_query.clone();
+        move || {
+            let result = spool(
+                &async_query.dir,
+                receiver,
+                page_size,
+                &async_query.usage,
+                &async_query.spooled,
+            );
+            // Removed while being spooled
+            if async_query.usage.is_released() {
+                let _ = std::fs::remove_dir_all(&async_query.dir);
+            }
+            result
+        }
+    });
+
+    let query = query.clone().with_batch_sink(sink.clone());
+    let executed = async {
+        let mut result = dbms.execute(&query).await.context(QuerySnafu)?;
+        // Only the results of the queries are streamed, not those of the other statements
+        for batch in fetch_record_batches(&mut result)
+            .await
+            .map_err(fetch_error)?
+        {
+            sink.append(batch).await.map_err(fetch_error)?;
+        }
+        Ok::<_, HttpError>(())
+    }
+    .await;
+    sink.close();
+
+    // The spool fails first once over the limit, the query fails with it
+    writer.await.map_err(fetch_error)??;
+    executed
+}
+
+fn fetch_error(e: impl ToString) -> HttpError {
+    HttpError::FetchResult {
+        reason: e.to_string(),
+    }
+}
+
+/// Writes the batches received to the files of the pages of `page_size` rows in
+/// the directory until the sender is closed, the last one may be smaller, the rows
+/// and the pages are published once each page is written
+fn spool(
+    dir: &Path,
+    mut batches: mpsc::Receiver<RecordBatch>,
+    page_size: usize,
+    usage: &SpoolUsage,
+    spooled: &watch::Sender<Spooled>,
+) -> Result<(), HttpError> {
+    let mut next = batches.blocking_recv();
+    let schema = match &next {
+        Some(batch) => batch.schema(),
+        None => return Ok(()),
+    };
+    std::fs::create_dir_all(dir).map_err(fetch_error)?;
+    let mut pages = 0;
+    let mut write_page = |pending: &[RecordBatch]| {
+        let batch = concat_batches(&schema, pending).map_err(fetch_error)?;
+        let file = CountingWriter {
+            inner: BufWriter::new(File::create(page_path(dir, pages)).map_err(fetch_error)?),
+            usage: usage.clone(),
+        };
+        let mut writer = FileWriter::try_new(file, &schema).map_err(fetch_error)?;
+        writer.write(&batch).map_err(fetch_error)?;
+        writer.finish().map_err(fetch_error)?;
+        usage.check()?;
+
+        pages += 1;
+        spooled.send_modify(|e| {
+            e.rows += batch.num_rows() as u64;
+            e.pages = pages;
+        });
+        Ok::<_, HttpError>(())
+    };
+
+    let mut pending = vec![];
+    let mut pending_rows = 0;
+    while let Some(batch) = next {
+        let mut offset = 0;
+        while offset < batch.num_rows() {
+            let len = (page_size - pending_rows).min(batch.num_rows() - offset);
+            pending.push(batch.slice(offset, len));
+            pending_rows += len;
+            offset += len;
+            if pending_rows == page_size {
+                write_page(&pending)?;
+                pending.clear();
+                pending_rows = 0;
+            }
+        }
+        next = batches.blocking_recv();
+    }
+    if pending_rows > 0 {
+        write_page(&pending)?;
+    }
+
+    Ok(())
+}
+
+fn page_path(dir: &Path, page: usize) -> PathBuf {
+    dir.join(format!("{}.arrow", page))
+}
+
+fn read_page(path: &Path) -> ArrowResult<RecordBatch> {
+    let mut reader = FileReader::try_new(File::open(path)?, None)?;
+    reader.next().unwrap_or_else(|| {
+        Err(ArrowError::IoError(format!(
+            "no rows in {}",
+            path.display()
+        )))
+    })
+}
+
+/// The cursor of a page of the results of a query, valid until the results expire
+pub fn cursor_of(id: &str, page: usize) -> String {
+    format!("{}-{}", id, page)
+}
+
+fn parse_cursor(cursor: &str) -> Result<(&str, usize), HttpError> {
+    cursor
+        .rsplit_once('-')
+        .and_then(|(id, page)| page.parse().ok().map(|page| (id, page)))
+        .ok_or_else(|| HttpError::InvalidParameter {
+            reason: format!("invalid cursor: {}", cursor),
+        })
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|e| e.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use datafusion::arrow::array::{Int64Array, StringArray};
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use spi::service::protocol::{ContextBuilder, UserInfo};
+
+    use super::*;
+
+    fn batch(start: i64, len: i64) -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, false),
+        ]));
+        let ids: Vec<i64> = (start..start + len).collect();
+        let names: Vec<String> = ids.iter().map(|e| format!("n{}", e)).collect();
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(ids)),
+                Arc::new(StringArray::from(names)),
+            ],
+        )
+        .unwrap()
+    }
+
+    fn usage(max_user_bytes: u64) -> SpoolUsage {
+        SpoolUsage {
+            user: "root".to_string(),
+            bytes: Arc::new(Mutex::new(Some(0))),
+            user_bytes: Arc::new(AtomicU64::new(0)),
+            max_user_bytes,
+        }
+    }
+
+    fn spool_batches(
+        dir: &Path,
+        batches: Vec<RecordBatch>,
+        page_size: usize,
+        usage: &SpoolUsage,
+        spooled: &watch::Sender<Spooled>,
+    ) -> Result<(), HttpError> {
+        let (sender, receiver) = mpsc::channel(batches.len().max(1));
+        for batch in batches {
+            sender.try_send(batch).unwrap();
+        }
+        drop(sender);
+        spool(dir, receiver, page_size, usage, spooled)
+    }
+
+    /// A running query of root with a page of a row spooled
+    async fn running_query(queries: &AsyncQueries) -> Arc<AsyncQuery> {
+        let user_info = UserInfo {
+            user: "root".to_string(),
+            password: String::new(),
+        };
+        let query = Query::new(
+            ContextBuilder::new(user_info).build(),
+            "SELECT 1".to_string(),
+        );
+        let async_query = Arc::new(queries.new_query(&query));
+        queries
+            .queries
+            .write()
+            .insert(async_query.status().id, async_query.clone());
+
+        tokio::task::spawn_blocking({
+            let q = async_query.clone();
+            move || spool_batches(&q.dir, vec![batch(0, 1)], 1, &q.usage, &q.spooled)
+        })
+        .await
+        .unwrap()
+        .unwrap();
+        async_query
+    }
+
+    #[test]
+    fn test_spool() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool_dir = dir.path().join("q");
+
+        let usage = usage(0);
+        let (spooled, _) = watch::channel(Spooled::default());
+        let batches = vec![batch(0, 3), batch(3, 5), batch(8, 2)];
+        spool_batches(&spool_dir, batches, 4, &usage, &spooled).unwrap();
+        let current = *spooled.borrow();
+        assert_eq!((current.rows, current.pages), (10, 3));
+        let bytes = (0..3)
+            .map(|i| std::fs::metadata(page_path(&spool_dir, i)).unwrap().len())
+            .sum();
+        assert_eq!(*usage.bytes.lock(), Some(bytes));
+        assert_eq!(usage.user_bytes.load(Ordering::Relaxed), bytes);
+
+        let pages: Vec<_> = (0..3)
+            .map(|i| read_page(&page_path(&spool_dir, i)).unwrap())
+            .collect();
+        assert_eq!(
+            pages.iter().map(|e| e.num_rows()).collect::<Vec<_>>(),
+            vec![4, 4, 2]
+        );
+        let ids = pages[1]
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(ids.values(), &[4, 5, 6, 7]);
+        assert!(read_page(&page_path(&spool_dir, 3)).is_err());
+
+        let empty_dir = dir.path().join("empty");
+        let (spooled, _) = watch::channel(Spooled::default());
+        spool_batches(&empty_dir, vec![], 4, &usage, &spooled).unwrap();
+        assert_eq!(spooled.borrow().pages, 0);
+        assert!(!empty_dir.exists());
+
+        usage.release();
+        assert!(usage.is_released());
+        assert_eq!(usage.user_bytes.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_spool_limit() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let usage = usage(1);
+        let (spooled, _) = watch::channel(Spooled::default());
+        assert!(matches!(
+            spool_batches(dir.path(), vec![batch(0, 10)], 4, &usage, &spooled),
+            Err(HttpError::AsyncQueryLimit { .. })
+        ));
+        assert_eq!(spooled.borrow().pages, 0);
+    }
+
+    #[tokio::test]
+    async fn test_expire() {
+        let dir = tempfile::tempdir().unwrap();
+        let queries = AsyncQueries::new(dir.path(), Duration::ZERO)
+            .unwrap()
+            .with_limits(1, 0);
+        let async_query = running_query(&queries).await;
+        let id = async_query.status().id;
+
+        // Other users may not see the query
+        assert!(queries.status("other", &id).is_err());
+        assert!(queries.page("other", &id, 0).await.is_err());
+
+        // The pages spooled are fetched while running, the next is waited for
+        assert_eq!(queries.status("root", &id).unwrap().pages, 1);
+        assert_eq!(queries.page("root", &id, 0).await.unwrap()[0].num_rows(), 1);
+        let next_page = queries.next_page("root", &cursor_of(&id, 0));
+        assert!(tokio::time::timeout(Duration::from_millis(10), next_page)
+            .await
+            .is_err());
+
+        // Running queries are not expired
+        queries.expire();
+        assert!(queries.status("root", &id).is_ok());
+        assert!(matches!(
+            queries.check_queries(&queries.queries.read(), "root"),
+            Err(HttpError::AsyncQueryLimit { .. })
+        ));
+        assert!(queries
+            .check_queries(&queries.queries.read(), "other")
+            .is_ok());
+
+        async_query.finish(Ok(()), queries.ttl);
+        let status = queries.status("root", &id).unwrap();
+        assert_eq!(status.state, AsyncQueryState::Succeeded);
+        assert_eq!((status.rows, status.pages), (1, 1));
+        let (batches, next) = queries.next_page("root", &cursor_of(&id, 0)).await.unwrap();
+        assert_eq!(batches[0].num_rows(), 1);
+        assert!(next.is_none());
+        assert!(queries.next_page("root", &id).await.is_err());
+        assert!(queries.next_page("root", &cursor_of(&id, 1)).await.is_err());
+
+        queries.expire();
+        assert!(matches!(
+            queries.status("root", &id),
+            Err(HttpError::AsyncQueryNotFound { .. })
+        ));
+        assert!(!async_query.dir.exists());
+        assert!(async_query.usage.is_released());
+    }
+
+    #[tokio::test]
+    async fn test_failed() {
+        let dir = tempfile::tempdir().unwrap();
+        let queries = AsyncQueries::new(dir.path(), Duration::from_secs(60)).unwrap();
+        let async_query = running_query(&queries).await;
+        let id = async_query.status().id;
+
+        // The pages spooled before are not returned once failed
+        let error = HttpError::FetchResult {
+            reason: "failed".to_string(),
+        };
+        async_query.finish(Err(error), queries.ttl);
+        let status = queries.status("root", &id).unwrap();
+        assert_eq!(status.state, AsyncQueryState::Failed);
+        assert_eq!(status.error.as_deref(), Some("Fetch result: failed"));
+        assert!(queries.page("root", &id, 0).await.is_err());
+        assert!(async_query.error.lock().is_some());
+
+        queries.remove("root", &id).unwrap();
+        assert!(!async_query.dir.exists());
+    }
+}
@@ -0,0 +1,224 @@
+//! Change data capture over http, see [`tskv::cdc`].
+//!
+//! Each record is a committed write of the database, its points are encoded
+//! the same way as the json payload of stream sources, so the response can be
+//! replicated into another database as is.
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+use protos::models as fb_models;
+use serde::Serialize;
+use serde_json::Value;
+use snafu::ResultExt;
+use tskv::cdc::{ChangeBatch, ChangeOffset};
+use tskv::engine::EngineRef;
+
+use super::{Error as HttpError, TskvSnafu};
+
+const DEFAULT_LIMIT: usize = 100;
+const MAX_LIMIT: usize = 10000;
+const MAX_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Serialize)]
+pub struct ChangesResponse {
+    next_offset: String,
+    records: Vec<ChangeRecord>,
+}
+
+#[derive(Debug, Serialize)]
+struct ChangeRecord {
+    offset: String,
+    points: Vec<ChangePoint>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+struct ChangePoint {
+    measurement: String,
+    tags: BTreeMap<String, String>,
+    fields: BTreeMap<String, Value>,
+    timestamp: i64,
+}
+
+/// Reads the writes of `db` from `offset`, if there is none waits
+/// at most `timeout` seconds for new writes. The wal from `offset` on is kept for
+/// the `consumer` if named.
+pub async fn read_changes(
+    kv_inst: EngineRef,
+    db: &str,
+    offset: Option<&str>,
+    limit: Option<usize>,
+    timeout: Option<u64>,
+    consumer: Option<&str>,
+) -> Result<ChangesResponse, HttpError> {
+    let from = match offset {
+        Some(offset) => ChangeOffset::from_str(offset)
+            .map_err(|reason| HttpError::InvalidParameter { reason })?,
+        None => ChangeOffset::EARLIEST,
+    };
+    if let Some(consumer) = consumer {
+        let (kv_inst, db, consumer) = (kv_inst.clone(), db.to_string(), consumer.to_string());
+        tokio::task::spawn_blocking(move || kv_inst.retain_changes(&consumer, &db, from))
+            .await
+            .map_err(|e| HttpError::FetchResult {
+                reason: e.to_string(),
+            })?
+            .context(TskvSnafu)?;
+    }
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let timeout = Duration::from_secs(timeout.unwrap_or(0)).min(MAX_TIMEOUT);
+
+    // Subscribe before reading, so writes committed in between are not missed
+    let mut events = kv_inst.subscribe_writes();
+    let mut batch = read_wal(&kv_inst, db, from, limit).await?;
+
+    if batch.records.is_empty() && !timeout.is_zero() {
+        let wait = async {
+            loop {
+                match events.recv().await {
+                    Ok(event) if event.database != db => continue,
+                    _ => break,
+                }
+            }
+        };
+        if tokio::time::timeout(timeout, wait).await.is_ok() {
+            batch = read_wal(&kv_inst, db, batch.next_offset, limit).await?;
+        }
+    }
+
+    batch_to_response(batch)
+}
+
+/// The wal files are read on the blocking threads
+async fn read_wal(
+    kv_inst: &EngineRef,
+    db: &str,
+    from: ChangeOffset,
+    limit: usize,
+) -> Result<ChangeBatch, HttpError> {
+    let (kv_inst, db) = (kv_inst.clone(), db.to_string());
+    tokio::task::spawn_blocking(move || kv_inst.read_changes(&db, from, limit))
+        .await
+        .map_err(|e| HttpError::FetchResult {
+            reason: e.to_string(),
+        })?
+        .context(TskvSnafu)
+}
+
+fn batch_to_response(batch: ChangeBatch) -> Result<ChangesResponse, HttpError> {
+    let records = batch
+        .records
+        .iter()
+        .map(|record| {
+            Ok(ChangeRecord {
+                offset: record.offset.to_string(),
+                points: points_to_json(&record.points)?,
+            })
+        })
+        .collect::<Result<Vec<_>, HttpError>>()?;
+
+    Ok(ChangesResponse {
+        next_offset: batch.next_offset.to_string(),
+        records,
+    })
+}
+
+fn points_to_json(points: &[u8]) -> Result<Vec<ChangePoint>, HttpError> {
+    let invalid = |reason: String| HttpError::FetchResult { reason };
+
+    let points =
+        flatbuffers::root::<fb_models::Points>(points).map_err(|e| invalid(e.to_string()))?;
+    let points = match points.points() {
+        Some(points) => points,
+        None => return Ok(vec![]),
+    };
+
+    let to_string =
+        |bytes: Option<&[u8]>| String::from_utf8_lossy(bytes.unwrap_or_default()).to_string();
+
+    let mut result = Vec::with_capacity(points.len());
+    for point in points.iter() {
+        let mut tags = BTreeMap::new();
+        for tag in point.tags().iter().flat_map(|e| e.iter()) {
+            tags.insert(to_string(tag.key()), to_string(tag.value()));
+        }
+
+        let mut fields = BTreeMap::new();
+        for field in point.fields().iter().flat_map(|e| e.iter()) {
+            let name = to_string(field.name());
+            let value = field.value().unwrap_or_default();
+            let value = match field.type_() {
+                fb_models::FieldType::Float => {
+                    let v =
+                        f64::from_be_bytes(value.try_into().map_err(|_| {
+                            invalid(format!("invalid float value of field {}", name))
+                        })?);
+                    serde_json::Number::from_f64(v)
+                        .map(Value::Number)
+                        .unwrap_or(Value::Null)
+                }
+                fb_models::FieldType::Integer => {
+                    Value::from(i64::from_be_bytes(value.try_into().map_err(|_| {
+                        invalid(format!("invalid integer value of field {}", name))
+                    })?))
+                }
+                fb_models::FieldType::Unsigned => {
+                    Value::from(u64::from_be_bytes(value.try_into().map_err(|_| {
+                        invalid(format!("invalid unsigned value of field {}", name))
+                    })?))
+                }
+                fb_models::FieldType::Boolean => Value::Bool(value.first() == Some(&1)),
+                fb_models::FieldType::String => {
+                    Value::String(String::from_utf8_lossy(value).to_string())
+                }
+                _ => return Err(invalid(format!("unknown type of field {}", name))),
+            };
+            fields.insert(name, value);
+        }
+
+        result.push(ChangePoint {
+            measurement: to_string(point.tab()),
+            tags,
+            fields,
+            timestamp: point.timestamp(),
+        });
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use line_protocol::{lines_to_points, FieldValue, Line};
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_points_to_json() {
+        let lines = vec![Line {
+            measurement: "cpu",
+            tags: vec![("host", "a")],
+            fields: vec![
+                ("f", FieldValue::F64(1.5)),
+                ("i", FieldValue::I64(-2)),
+                ("u", FieldValue::U64(3)),
+                ("b", FieldValue::Bool(true)),
+                ("s", FieldValue::Str(b"x".to_vec())),
+            ],
+            timestamp: 10,
+        }];
+        let points = lines_to_points("db", &lines);
+
+        let points = points_to_json(&points).unwrap();
+        assert_eq!(
+            serde_json::to_value(&points).unwrap(),
+            json!([{
+                "measurement": "cpu",
+                "tags": {"host": "a"},
+                "fields": {"f": 1.5, "i": -2, "u": 3, "b": true, "s": "x"},
+                "timestamp": 10
+            }])
+        );
+    }
+}
@@ -0,0 +1,261 @@
+//! Compression of the bodies of the requests and the responses.
+//!
+//! The bodies of the writes and the queries may be compressed by gzip or zstd as
+//! the `Content-Encoding` header says, the responses of the queries are compressed
+//! by the encoding the client prefers in the `Accept-Encoding` header.
+use std::cmp::Ordering;
+use std::io::{Read, Write};
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use http_protocol::header::{HeaderValue, CONTENT_ENCODING, CONTENT_LENGTH, VARY};
+use warp::hyper::body::{to_bytes, Bytes};
+use warp::reply::Response;
+
+use super::response::ResponseBuilder;
+use super::Error as HttpError;
+
+/// Smaller responses are not worth compressing
+const MIN_COMPRESSED_SIZE: usize = 1024;
+const ZSTD_LEVEL: i32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Gzip,
+    Zstd,
+}
+
+impl Encoding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Zstd => "zstd",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "gzip" | "x-gzip" => Some(Self::Gzip),
+            "zstd" => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Picks the supported encoding with the highest quality value of the
+    /// `Accept-Encoding` header, zstd is preferred to gzip if equal
+    pub fn negotiate(accept_encoding: &str) -> Option<Self> {
+        let mut encodings: Vec<(Self, f32)> = accept_encoding
+            .split(',')
+            .filter_map(|coding| {
+                let mut parts = coding.split(';');
+                let name = parts.next().unwrap_or_default().trim();
+                let quality = parts
+                    .filter_map(|p| p.trim().strip_prefix("q="))
+                    .find_map(|q| q.parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                Self::from_name(name).map(|e| (e, quality))
+            })
+            .filter(|(_, quality)| *quality > 0.0)
+            .collect();
+
+        encodings.sort_by(|a, b| {
+            b.1.partial_cmp(&a.1)
+                .unwrap_or(Ordering::Equal)
+                .then_with(|| (b.0 == Self::Zstd).cmp(&(a.0 == Self::Zstd)))
+        });
+        encodings.first().map(|(encoding, _)| *encoding)
+    }
+
+    fn compress(&self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Self::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            Self::Zstd => zstd::stream::encode_all(data, ZSTD_LEVEL),
+        }
+    }
+
+    fn decompress(&self, data: &[u8], limit: u64) -> Result<Vec<u8>, HttpError> {
+        let reader: Box<dyn Read + '_> = match self {
+            Self::Gzip => Box::new(GzDecoder::new(data)),
+            Self::Zstd => Box::new(zstd::stream::read::Decoder::new(data).map_err(|e| {
+                HttpError::InvalidBody {
+                    reason: format!("invalid zstd body: {}", e),
+                }
+            })?),
+        };
+        let mut decoded = Vec::new();
+        reader
+            .take(limit.saturating_add(1))
+            .read_to_end(&mut decoded)
+            .map_err(|e| HttpError::InvalidBody {
+                reason: format!("invalid {} body: {}", self.as_str(), e),
+            })?;
+        if decoded.len() as u64 > limit {
+            return Err(HttpError::BodyOversize {
+                size: decoded.len(),
+            });
+        }
+
+        Ok(decoded)
+    }
+}
+
+/// Decompresses the body by the `Content-Encoding` of the request off the async
+/// runtime, the body decompressed may not exceed the limit either
+pub async fn decode_body(
+    content_encoding: Option<&str>,
+    body: Bytes,
+    limit: u64,
+) -> Result<Bytes, HttpError> {
+    let encoding = match content_encoding.map(str::trim) {
+        None | Some("") => return Ok(body),
+        Some(name) if name.eq_ignore_ascii_case("identity") => return Ok(body),
+        Some(name) => Encoding::from_name(name).ok_or_else(|| HttpError::InvalidHeader {
+            reason: format!("content encoding not support: {}", name),
+        })?,
+    };
+
+    tokio::task::spawn_blocking(move || encoding.decompress(&body, limit))
+        .await
+        .map_err(|e| HttpError::InvalidBody {
+            reason: format!("failed to decompress the {} body: {}", encoding.as_str(), e),
+        })?
+        .map(Bytes::from)
+}
+
+/// Compresses the body of a successful response by the encoding the client
+/// prefers, the response is returned as is if not worth it or if the compression
+/// fails. A body failed to read is answered by 500.
+pub async fn compress_response(resp: Response, accept_encoding: Option<&str>) -> Response {
+    let encoding = match accept_encoding.and_then(Encoding::negotiate) {
+        Some(encoding) => encoding,
+        None => return resp,
+    };
+    if !resp.status().is_success() || resp.headers().contains_key(CONTENT_ENCODING) {
+        return resp;
+    }
+
+    let (mut parts, body) = resp.into_parts();
+    let body = match to_bytes(body).await {
+        Ok(body) => body,
+        Err(e) => {
+            trace::error!("Failed to read the body of the response: {}", e);
+            return ResponseBuilder::internal_server_error();
+        }
+    };
+    parts
+        .headers
+        .insert(VARY, HeaderValue::from_static("accept-encoding"));
+    if body.len() < MIN_COMPRESSED_SIZE {
+        return Response::from_parts(parts, body.into());
+    }
+
+    // The body is kept to be sent uncompressed if the compression fails
+    let data = body.clone();
+    let compressed = tokio::task::spawn_blocking(move || encoding.compress(&data)).await;
+    match compressed {
+        Ok(Ok(compressed)) => {
+            parts.headers.insert(
+                CONTENT_ENCODING,
+                HeaderValue::from_static(encoding.as_str()),
+            );
+            parts
+                .headers
+                .insert(CONTENT_LENGTH, HeaderValue::from(compressed.len()));
+            Response::from_parts(parts, compressed.into())
+        }
+        Ok(Err(e)) => {
+            trace::warn!("Failed to compress the response: {}", e);
+            Response::from_parts(parts, body.into())
+        }
+        Err(e) => {
+            trace::error!("Failed to compress the response: {}", e);
+            Response::from_parts(parts, body.into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(Encoding::negotiate("gzip"), Some(Encoding::Gzip));
+        assert_eq!(
+            Encoding::negotiate("gzip, deflate, zstd"),
+            Some(Encoding::Zstd)
+        );
+        assert_eq!(
+            Encoding::negotiate("zstd;q=0.5, gzip;q=0.8"),
+            Some(Encoding::Gzip)
+        );
+        assert_eq!(Encoding::negotiate("gzip;q=0, br"), None);
+        assert_eq!(Encoding::negotiate("identity"), None);
+    }
+
+    #[tokio::test]
+    async fn test_decode_body() {
+        let lines = "cpu,host=a usage=1 1\n".repeat(100);
+        for encoding in [Encoding::Gzip, Encoding::Zstd] {
+            let compressed = encoding.compress(lines.as_bytes()).unwrap();
+            assert!(compressed.len() < lines.len());
+            let decoded = decode_body(Some(encoding.as_str()), compressed.clone().into(), 1 << 20)
+                .await
+                .unwrap();
+            assert_eq!(decoded.as_ref(), lines.as_bytes());
+
+            // The limit applies to the body decompressed
+            assert!(matches!(
+                decode_body(Some(encoding.as_str()), compressed.into(), 100).await,
+                Err(HttpError::BodyOversize { .. })
+            ));
+        }
+
+        let body = Bytes::from_static(b"cpu usage=1");
+        assert_eq!(decode_body(None, body.clone(), 100).await.unwrap(), body);
+        assert_eq!(
+            decode_body(Some("identity"), body.clone(), 100)
+                .await
+                .unwrap(),
+            body
+        );
+        assert!(decode_body(Some("br"), body.clone(), 100).await.is_err());
+        assert!(matches!(
+            decode_body(Some("gzip"), body, 100).await,
+            Err(HttpError::InvalidBody { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_compress_response() {
+        let body = "a,b\n1,2\n".repeat(1000);
+        let resp = Response::new(body.clone().into());
+        let resp = compress_response(resp, Some("gzip")).await;
+        assert_eq!(resp.headers()[CONTENT_ENCODING], "gzip");
+
+        let compressed = to_bytes(resp.into_body()).await.unwrap();
+        let decoded = decode_body(Some("gzip"), compressed, 1 << 20)
+            .await
+            .unwrap();
+        assert_eq!(decoded.as_ref(), body.as_bytes());
+
+        // Small responses are not compressed
+        let resp = compress_response(Response::new("a".into()), Some("zstd")).await;
+        assert!(!resp.headers().contains_key(CONTENT_ENCODING));
+        let resp = compress_response(Response::new(body.into()), None).await;
+        assert!(!resp.headers().contains_key(CONTENT_ENCODING));
+
+        // A body failed to read is not sent as an empty success
+        let broken = futures::stream::iter(vec![Err::<Bytes, _>(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            "broken",
+        ))]);
+        let resp = Response::new(warp::hyper::Body::wrap_stream(broken));
+        let resp = compress_response(resp, Some("gzip")).await;
+        assert_eq!(resp.status(), 500);
+    }
+}
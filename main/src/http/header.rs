@@ -2,12 +2,21 @@ use super::Error as HttpError;
 use spi::service::protocol::UserInfo;
 use warp::http::header::{HeaderName, HeaderValue};
 
-use http_protocol::header::{APPLICATION_CSV, BASIC_PREFIX};
+use http_protocol::header::{APPLICATION_CSV, BASIC_PREFIX, BEARER_PREFIX, TOKEN_PREFIX};
 
-#[derive(Debug, Clone)]
+/// What the client is authenticated by
+pub enum Credentials {
+    Password(UserInfo),
+    /// API token or JWT
+    Token(String),
+}
+
+#[derive(Clone)]
 pub struct Header {
     accept: Option<String>,
     authorization: String,
+    /// The user authenticated by the credentials
+    user: Option<UserInfo>,
 }
 
 impl Header {
@@ -15,6 +24,31 @@ impl Header {
         Self {
             accept,
             authorization,
+            user: None,
+        }
+    }
+
+    pub fn with_user(mut self, user: UserInfo) -> Self {
+        self.user = Some(user);
+        self
+    }
+
+    /// The authenticated user, or the one of the basic auth if not authenticated
+    pub fn user_info(&self) -> Result<UserInfo, HttpError> {
+        match &self.user {
+            Some(user) => Ok(user.clone()),
+            None => self.try_get_basic_auth(),
+        }
+    }
+
+    pub fn credentials(&self) -> Result<Credentials, HttpError> {
+        let token = self
+            .authorization
+            .strip_prefix(BEARER_PREFIX)
+            .or_else(|| self.authorization.strip_prefix(TOKEN_PREFIX));
+        match token {
+            Some(token) => Ok(Credentials::Token(token.trim().to_string())),
+            None => self.try_get_basic_auth().map(Credentials::Password),
         }
     }
 
@@ -146,4 +180,23 @@ mod tests {
         let header = Header::with(None, auth);
         assert!(header.try_get_basic_auth().is_err());
     }
+
+    #[test]
+    fn test_header_credentials() {
+        let header = Header::with(None, format!("{}{}", BEARER_PREFIX, "t1"));
+        assert!(matches!(header.credentials(), Ok(Credentials::Token(t)) if t == "t1"));
+        let header = Header::with(None, format!("{}{}", TOKEN_PREFIX, "t2"));
+        assert!(matches!(header.credentials(), Ok(Credentials::Token(t)) if t == "t2"));
+        assert!(header.try_get_basic_auth().is_err());
+
+        let header = Header::with(None, format!("{}{}", BASIC_PREFIX, base64::encode("xx:")));
+        assert!(matches!(header.credentials(), Ok(Credentials::Password(u)) if u.user == "xx"));
+
+        // The user authenticated by the token
+        let header = Header::with(None, format!("{}{}", BEARER_PREFIX, "t1")).with_user(UserInfo {
+            user: "u1".to_string(),
+            password: String::new(),
+        });
+        assert_eq!(header.user_info().unwrap().user, "u1");
+    }
 }
@@ -0,0 +1,127 @@
+//! Liveness and readiness probes, e.g. of kubernetes.
+//!
+//! The http server is started once the storage has replayed its wal, so a node
+//! still recovering refuses the probes. A node serving them is alive, it is ready
+//! if the meta service is reachable and the data can be written to the disks.
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use coordinator::service::CoordinatorRef;
+use serde::Serialize;
+
+const META_TIMEOUT: Duration = Duration::from_secs(3);
+const DISK_TIMEOUT: Duration = Duration::from_secs(3);
+const PROBE_FILE: &str = ".health_probe";
+
+/// Numbers the probe files, so that the concurrent probes do not remove each other's
+static PROBE_ID: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Serialize)]
+pub struct Readiness {
+    pub ready: bool,
+    pub checks: Vec<Check>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: String,
+    pub healthy: bool,
+    pub error: Option<String>,
+}
+
+impl Check {
+    fn new(name: impl Into<String>, result: Result<(), String>) -> Self {
+        Self {
+            name: name.into(),
+            healthy: result.is_ok(),
+            error: result.err(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct HealthChecker {
+    coord: CoordinatorRef,
+    /// Directories the data is written to, named
+    dirs: Vec<(String, PathBuf)>,
+}
+
+impl HealthChecker {
+    pub fn new(coord: CoordinatorRef, dirs: Vec<(String, PathBuf)>) -> Self {
+        Self { coord, dirs }
+    }
+
+    pub async fn readiness(&self) -> Readiness {
+        let mut checks = vec![];
+        // A standalone node has no meta service
+        if let Some(connections) = self.coord.connections() {
+            let meta = connections.meta();
+            let result = match tokio::time::timeout(META_TIMEOUT, meta.data_nodes()).await {
+                Ok(Ok(_)) => Ok(()),
+                Ok(Err(e)) => Err(e.to_string()),
+                Err(_) => Err(format!("no response in {:?}", META_TIMEOUT)),
+            };
+            checks.push(Check::new("meta", result));
+        }
+        for (name, dir) in self.dirs.iter() {
+            let dir = dir.clone();
+            let probe = tokio::task::spawn_blocking(move || probe_dir(&dir));
+            let result = match tokio::time::timeout(DISK_TIMEOUT, probe).await {
+                Ok(Ok(result)) => result,
+                Ok(Err(e)) => Err(e.to_string()),
+                Err(_) => Err(format!("no response in {:?}", DISK_TIMEOUT)),
+            };
+            checks.push(Check::new(format!("disk_{}", name), result));
+        }
+
+        Readiness {
+            ready: checks.iter().all(|e| e.healthy),
+            checks,
+        }
+    }
+}
+
+/// Writes a file to the directory and removes it
+fn probe_dir(dir: &Path) -> Result<(), String> {
+    let path = dir.join(format!(
+        "{}.{}.{}",
+        PROBE_FILE,
+        std::process::id(),
+        PROBE_ID.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::write(&path, b"ok")
+        .and_then(|_| std::fs::remove_file(&path))
+        .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use coordinator::service::LocalCoordinator;
+    use tskv::engine::MockEngine;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_readiness() {
+        let dir = tempfile::tempdir().unwrap();
+        let coord = Arc::new(LocalCoordinator::new(Arc::new(MockEngine::default())));
+        let checker = HealthChecker::new(
+            coord.clone(),
+            vec![("data".to_string(), dir.path().to_path_buf())],
+        );
+        let readiness = checker.readiness().await;
+        assert!(readiness.ready);
+        assert_eq!(readiness.checks.len(), 1);
+
+        let checker = HealthChecker::new(
+            coord,
+            vec![("data".to_string(), dir.path().join("not_exists"))],
+        );
+        let readiness = checker.readiness().await;
+        assert!(!readiness.ready);
+        assert!(readiness.checks[0].error.is_some());
+    }
+}
@@ -1,111 +1,709 @@
+use std::path::{Component, Path, PathBuf};
 use std::{collections::HashMap, convert::Infallible, net::SocketAddr};
 
-use http_protocol::header::{ACCEPT, AUTHORIZATION};
-use http_protocol::parameter::{SqlParam, WriteParam};
+use http_protocol::header::{
+    HeaderValue, ACCEPT, ACCEPT_ENCODING, APPLICATION_OCTET_STREAM, AUTHORIZATION,
+    CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, NEXT_CURSOR, PROMETHEUS_TEXT,
+};
+use http_protocol::parameter::{
+    AsyncResultParam, ChangesParam, InfluxQueryParam, PartitionParam, ProfileParam, SqlParam,
+    VerifyParam, WriteParam,
+};
 use http_protocol::response::ErrorResponse;
+use http_protocol::status_code::{ACCEPTED, FORBIDDEN, OK, SERVICE_UNAVAILABLE};
 
-use super::header::Header;
+use super::async_query::AsyncQueriesRef;
+use super::header::{Credentials, Header};
 use super::Error as HttpError;
-use super::QuerySnafu;
-use crate::http::response::ResponseBuilder;
+use super::{QuerySnafu, SessionSnafu, TskvSnafu};
+use crate::http::changes::read_changes;
+use crate::http::compression::{compress_response, decode_body};
+use crate::http::health::HealthChecker;
+use crate::http::influx;
+use crate::http::profile::{
+    cpu_profile, heap_profile, DEFAULT_PROFILE_FREQUENCY, DEFAULT_PROFILE_SECONDS,
+    MAX_PROFILE_SECONDS,
+};
+use crate::http::quota::{stored_bytes, TenantQuotas};
+use crate::http::rate_limit::RateLimiter;
+use crate::http::response::{FailedLine, ResponseBuilder, WriteResponse};
 use crate::http::result_format::fetch_record_batches;
 use crate::http::result_format::ResultFormat;
-use crate::http::Error;
-use crate::http::ParseLineProtocolSnafu;
-use crate::http::TskvSnafu;
+use crate::http::subscription::handle_subscription;
+use crate::http::CoordinatorSnafu;
 use crate::server;
 use crate::server::{Service, ServiceHandle};
+use crate::tls::{self, TlsCertsRef};
 use chrono::Local;
-use config::TLSConfig;
+use config::{Config, CorsConfig, TagLimitsConfig};
+use coordinator::dc_replication::DcReplication;
+use coordinator::rebalance::Rebalancer;
+use coordinator::service::CoordinatorRef;
+use coordinator::writer::DEFAULT_WRITE_CONSISTENCY;
+use datafusion::arrow::record_batch::RecordBatch;
 use datafusion::parquet::data_type::AsBytes;
-use flatbuffers::FlatBufferBuilder;
-use line_protocol::{line_protocol_to_lines, Line};
+use line_protocol::{
+    line_protocol_to_lines_partially, lines_to_points, Precision, TagCharset, TagLimits,
+};
+use meta::error::MetaError;
+use metrics::tenant_usage::{record_tenant_write, record_write_rejected, WriteRejection};
 use metrics::{
     gather_metrics_as_prometheus_string, incr_point_write_failed, incr_point_write_success,
     incr_query_read_failed, incr_query_read_success, sample_point_write_latency,
     sample_query_read_latency,
 };
+use models::consistency_level::ConsistencyLevel;
 use models::error_code::ErrorCode;
+use parking_lot::RwLock;
 use protos::kv_service::WritePointsRpcRequest;
-use protos::models as fb_models;
-use protos::models::{FieldBuilder, Point, PointArgs, Points, PointsArgs, TagBuilder};
+use query::audit::{AuditEvent, AuditKind, AuditLog, AuditLogRef};
+use query::session::{SessionGuard, SessionRef, SessionRegistry, SessionRegistryRef};
 use snafu::ResultExt;
 use spi::server::dbms::DBMSRef;
 use spi::service::protocol::ContextBuilder;
 use spi::service::protocol::Query;
-use std::time::Instant;
-use tokio::sync::oneshot;
+use spi::service::protocol::UserInfo;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::{mpsc, oneshot, watch};
 use trace::debug;
-use trace::info;
+use trace::{error, info, warn};
+use trace::{field, info_span, set_remote_parent, Instrument, Span};
 use tskv::engine::EngineRef;
+use tskv::partition::{self, PartitionManifest};
+use tskv::TimeRange;
+use warp::cors::{Cors, CorsForbidden};
+use warp::filters::BoxedFilter;
 use warp::hyper::body::Bytes;
+use warp::hyper::server::conn::Http;
+use warp::hyper::service::{service_fn, Service as _};
+use warp::hyper::{Body, Request};
 use warp::reject::MethodNotAllowed;
 use warp::reject::MissingHeader;
 use warp::reject::PayloadTooLarge;
 use warp::reply::Response;
+use warp::ws::Ws;
 use warp::Rejection;
 use warp::Reply;
 use warp::{header, reject, Filter};
 
+/// The idle timeout reloaded is applied to the connections by then at most
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+const HTTP_ALPN: &[&[u8]] = &[b"h2", b"http/1.1"];
+
+/// Limits of the requests, reloaded on the fly
+#[derive(Debug)]
+pub struct HttpLimits {
+    query_body_limit: AtomicU64,
+    write_body_limit: AtomicU64,
+    slow_query_threshold_ms: AtomicU64,
+    slow_write_threshold_ms: AtomicU64,
+    rate_limiter: RateLimiter,
+    quotas: TenantQuotas,
+    tag_limits: RwLock<TagLimits>,
+}
+
+impl HttpLimits {
+    pub fn new(config: &Config) -> Self {
+        let query = &config.query;
+        Self {
+            query_body_limit: AtomicU64::new(query.query_sql_limit),
+            write_body_limit: AtomicU64::new(query.write_sql_limit),
+            slow_query_threshold_ms: AtomicU64::new(query.slow_query_threshold_ms),
+            slow_write_threshold_ms: AtomicU64::new(query.slow_write_threshold_ms),
+            rate_limiter: RateLimiter::new(&config.rate_limit),
+            quotas: TenantQuotas::new(&config.quota),
+            tag_limits: RwLock::new(tag_limits(&config.tag_limits)),
+        }
+    }
+
+    pub fn update(&self, config: &Config) {
+        self.rate_limiter.update(&config.rate_limit);
+        self.quotas.update(&config.quota);
+        *self.tag_limits.write() = tag_limits(&config.tag_limits);
+        let config = &config.query;
+        self.query_body_limit
+            .store(config.query_sql_limit, Ordering::Relaxed);
+        self.write_body_limit
+            .store(config.write_sql_limit, Ordering::Relaxed);
+        self.slow_query_threshold_ms
+            .store(config.slow_query_threshold_ms, Ordering::Relaxed);
+        self.slow_write_threshold_ms
+            .store(config.slow_write_threshold_ms, Ordering::Relaxed);
+    }
+
+    fn query_body_limit(&self) -> u64 {
+        self.query_body_limit.load(Ordering::Relaxed)
+    }
+
+    fn write_body_limit(&self) -> u64 {
+        self.write_body_limit.load(Ordering::Relaxed)
+    }
+
+    fn slow_query_threshold_ms(&self) -> u64 {
+        self.slow_query_threshold_ms.load(Ordering::Relaxed)
+    }
+
+    fn slow_write_threshold_ms(&self) -> u64 {
+        self.slow_write_threshold_ms.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn tag_limits(&self) -> TagLimits {
+        *self.tag_limits.read()
+    }
+
+    pub(crate) fn check_query(&self, user: &str, tenant: &str) -> Result<(), HttpError> {
+        self.rate_limiter
+            .check_query(user, tenant)
+            .map_err(|retry_after| HttpError::RateLimited { retry_after })
+    }
+
+    /// Checks the rate limits of the user and the tenant, and the quota of the data
+    /// stored by the tenant, measured by `stored_bytes` on the blocking threads if needed
+    pub(crate) async fn check_write(
+        &self,
+        user: &str,
+        tenant: &str,
+        points: usize,
+        bytes: usize,
+        stored_bytes: impl FnOnce() -> u64 + Send + 'static,
+    ) -> Result<(), HttpError> {
+        if let Err(retry_after) =
+            self.rate_limiter
+                .check_write(user, tenant, points as u64, bytes as u64)
+        {
+            record_write_rejected(tenant, WriteRejection::RateLimited);
+            return Err(HttpError::RateLimited { retry_after });
+        }
+        if let Err((stored_bytes, max_stored_bytes)) =
+            self.quotas.check_write(tenant, stored_bytes).await
+        {
+            record_write_rejected(tenant, WriteRejection::OverQuota);
+            return Err(HttpError::QuotaExceeded {
+                tenant: tenant.to_string(),
+                stored_bytes,
+                max_stored_bytes,
+            });
+        }
+        Ok(())
+    }
+}
+
+fn tag_limits(config: &TagLimitsConfig) -> TagLimits {
+    TagLimits {
+        max_tags_per_point: config.max_tags_per_point,
+        max_tag_key_len: config.max_tag_key_len,
+        max_tag_value_len: config.max_tag_value_len,
+        charset: match config.charset {
+            config::TagCharset::Any => TagCharset::Any,
+            config::TagCharset::Printable => TagCharset::Printable,
+            config::TagCharset::Ascii => TagCharset::Ascii,
+        },
+    }
+}
+
+/// Directory of the partition in the dump directory, `dir` must be relative and
+/// can not lead out of the dump directory. The directory is created if `create`.
+fn partition_dir(dump_dir: &Path, dir: &str, create: bool) -> Result<PathBuf, HttpError> {
+    let outside = || HttpError::Partition {
+        reason: format!(
+            "directory {} must be a relative directory in the dump directory",
+            dir
+        ),
+    };
+    let io_error = |e: std::io::Error| HttpError::Partition {
+        reason: format!("directory {}: {}", dir, e),
+    };
+
+    let relative = Path::new(dir);
+    if relative.is_absolute()
+        || relative
+            .components()
+            .any(|e| !matches!(e, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(outside());
+    }
+
+    let path = dump_dir.join(relative);
+    if create {
+        std::fs::create_dir_all(&path).map_err(io_error)?;
+    }
+    // Symbolic links can not lead out of the dump directory either
+    let root = dump_dir.canonicalize().map_err(io_error)?;
+    let path = path.canonicalize().map_err(io_error)?;
+    if !path.starts_with(&root) {
+        return Err(outside());
+    }
+    Ok(path)
+}
+
+/// Checks that the vnodes of all the series of the partition in the buckets of its
+/// time range are located on this node, otherwise the files imported are not read
+/// by the queries routed by the buckets. All the data is on the node if standalone.
+async fn check_placement(
+    coord: &CoordinatorRef,
+    db: &str,
+    manifest: &PartitionManifest,
+) -> Result<(), HttpError> {
+    let meta = match coord.connections() {
+        Some(connections) => connections.meta(),
+        None => return Ok(()),
+    };
+    let meta_error = |e: MetaError| HttpError::Partition {
+        reason: e.to_string(),
+    };
+
+    let mut buckets = vec![];
+    for tenant in meta.tenants().await.map_err(meta_error)? {
+        let tenant_meta = meta.refresh(&tenant).await.map_err(meta_error)?;
+        if let Some(info) = tenant_meta.database(db) {
+            buckets.extend(
+                info.buckets_by_time_range(manifest.min_ts, manifest.max_ts)
+                    .into_iter()
+                    .cloned(),
+            );
+        }
+    }
+    if buckets.is_empty() && !manifest.series.is_empty() {
+        return Err(HttpError::Partition {
+            reason: format!(
+                "no bucket of database {} covers the partition [{}, {}]",
+                db, manifest.min_ts, manifest.max_ts
+            ),
+        });
+    }
+
+    let node_id = coord.node_id();
+    for bucket in buckets.iter() {
+        for key in manifest.series.iter() {
+            let located = bucket
+                .vnode_for(key.hash())
+                .map_or(false, |e| e.vnodes.iter().any(|e| e.node_id == node_id));
+            if !located {
+                return Err(HttpError::Partition {
+                    reason: format!(
+                        "series of the partition in bucket {} are not located on node {}",
+                        bucket.id, node_id
+                    ),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The manifest of a partition without the series and the tables, which may be many
+fn partition_summary(manifest: &PartitionManifest) -> serde_json::Value {
+    serde_json::json!({
+        "database": manifest.database,
+        "min_ts": manifest.min_ts,
+        "max_ts": manifest.max_ts,
+        "files": manifest.files.len(),
+        "series": manifest.series.len(),
+        "tables": manifest.tables.len(),
+    })
+}
+
 pub struct HttpService {
-    tls_config: Option<TLSConfig>,
+    tls: Option<TlsCertsRef>,
     addr: SocketAddr,
     dbms: DBMSRef,
-    kv_inst: EngineRef,
+    coord: CoordinatorRef,
+    rebalancer: Option<Arc<Rebalancer>>,
+    dc_replication: Option<Arc<DcReplication>>,
+    async_queries: Option<AsyncQueriesRef>,
+    audit_log: AuditLogRef,
+    sessions: SessionRegistryRef,
+    health: Arc<HealthChecker>,
+    pprof_enabled: bool,
+    cors: Option<Cors>,
+    dump_dir: PathBuf,
     handle: Option<ServiceHandle<()>>,
-    query_body_limit: u64,
-    write_body_limit: u64,
+    limits: Arc<HttpLimits>,
 }
 
 impl HttpService {
     pub fn new(
         dbms: DBMSRef,
-        kv_inst: EngineRef,
+        coord: CoordinatorRef,
         addr: SocketAddr,
-        tls_config: Option<TLSConfig>,
-        query_body_limit: u64,
-        write_body_limit: u64,
+        tls: Option<TlsCertsRef>,
+        limits: Arc<HttpLimits>,
     ) -> Self {
         Self {
-            tls_config,
+            tls,
             addr,
             dbms,
-            kv_inst,
+            coord: coord.clone(),
+            rebalancer: None,
+            dc_replication: None,
+            async_queries: None,
+            audit_log: Arc::new(AuditLog::memory()),
+            sessions: Arc::new(SessionRegistry::default()),
+            health: Arc::new(HealthChecker::new(coord.clone(), vec![])),
+            pprof_enabled: false,
+            cors: None,
+            dump_dir: PathBuf::from("data/dump"),
             handle: None,
-            query_body_limit,
-            write_body_limit,
+            limits,
         }
     }
 
+    /// Serves the membership operations of the cluster
+    pub fn with_rebalancer(mut self, rebalancer: Arc<Rebalancer>) -> Self {
+        self.rebalancer = Some(rebalancer);
+        self
+    }
+
+    /// Serves the replication from the primary cluster, the writes of the clients are
+    /// rejected until promoted
+    pub fn with_dc_replication(mut self, replication: Arc<DcReplication>) -> Self {
+        self.dc_replication = Some(replication);
+        self
+    }
+
+    /// Serves the queries submitted to run in the background, the results of which
+    /// are fetched in pages
+    pub fn with_async_queries(mut self, async_queries: AsyncQueriesRef) -> Self {
+        self.async_queries = Some(async_queries);
+        self
+    }
+
+    /// Checks the readiness of the node, e.g. the data directories are writable
+    pub fn with_health_checker(mut self, health: HealthChecker) -> Self {
+        self.health = Arc::new(health);
+        self
+    }
+
+    /// Serves the cpu and heap profiles for the diagnosis
+    pub fn with_pprof(mut self, enabled: bool) -> Self {
+        self.pprof_enabled = enabled;
+        self
+    }
+
+    /// Directory of the node the partitions are exported to and imported from
+    pub fn with_dump_dir(mut self, dump_dir: impl Into<PathBuf>) -> Self {
+        self.dump_dir = dump_dir.into();
+        self
+    }
+
+    /// Answers the preflight requests of the browsers and allows the cross-origin
+    /// requests, the origins, the headers and the methods are checked on load
+    pub fn with_cors(mut self, config: &CorsConfig) -> Self {
+        let cors = warp::cors()
+            .allow_headers(config.allowed_headers.iter().map(String::as_str))
+            .allow_methods(config.allowed_methods.iter().map(String::as_str))
+            .expose_headers(config.exposed_headers.iter().map(String::as_str))
+            .allow_credentials(config.allow_credentials)
+            .max_age(config.max_age_secs);
+        let cors = if config.allows_any_origin() {
+            cors.allow_any_origin()
+        } else {
+            cors.allow_origins(config.allowed_origins.iter().map(String::as_str))
+        };
+        self.cors = Some(cors.build());
+        self
+    }
+
+    /// Records the authentications of the clients
+    pub fn with_audit_log(mut self, audit_log: AuditLogRef) -> Self {
+        self.audit_log = audit_log;
+        self
+    }
+
+    /// Registers the connections of the clients as the sessions of `SHOW SESSIONS`
+    pub fn with_sessions(mut self, sessions: SessionRegistryRef) -> Self {
+        self.sessions = sessions;
+        self
+    }
+
     /// user_id
     /// database
     /// =》
     /// Authorization
     /// Accept
+    /// The credentials of the client are authenticated
     fn handle_header(&self) -> impl Filter<Extract = (Header,), Error = warp::Rejection> + Clone {
+        let audit_log = self.audit_log.clone();
+        let dbms = self.dbms.clone();
+        let sessions = self.sessions.clone();
         header::optional::<String>(ACCEPT.as_str())
             .and(header::<String>(AUTHORIZATION.as_str()))
-            .and_then(|accept, authorization| async move {
-                let res: Result<Header, warp::Rejection> = Ok(Header::with(accept, authorization));
-                res
+            .and(warp::ext::optional::<SessionRef>())
+            .and_then(move |accept, authorization, session: Option<SessionRef>| {
+                let audit_log = audit_log.clone();
+                let dbms = dbms.clone();
+                let sessions = sessions.clone();
+                async move {
+                    let header = Header::with(accept, authorization);
+                    let res = match header.credentials() {
+                        Ok(credentials) => authenticate(&dbms, credentials).await,
+                        Err(e) => Err(e),
+                    };
+                    audit_auth(&audit_log, "http", &res);
+                    // The connection is bound to the user, the connections of whom are limited
+                    let res = match (&session, res) {
+                        (Some(session), Ok(user)) => sessions
+                            .authenticate(session, &user.user)
+                            .map(|_| user)
+                            .context(SessionSnafu),
+                        (_, res) => res,
+                    };
+                    res.map(|user| header.with_user(user))
+                        .map_err(reject::custom)
+                }
             })
     }
+
+    /// Same as `handle_header`, the user authenticated must be an admin,
+    /// used by the routes managing the node or reading the data of any tenant
+    fn handle_admin_header(
+        &self,
+    ) -> impl Filter<Extract = (Header,), Error = warp::Rejection> + Clone {
+        let dbms = self.dbms.clone();
+        self.handle_header().and_then(move |header: Header| {
+            let dbms = dbms.clone();
+            async move {
+                let user_info = header.user_info().map_err(reject::custom)?;
+                check_admin(&dbms, &user_info).map_err(reject::custom)?;
+                Ok::<_, Rejection>(header)
+            }
+        })
+    }
+
+    fn audit_log(&self) -> impl Filter<Extract = (AuditLogRef,), Error = Infallible> + Clone {
+        let audit_log = self.audit_log.clone();
+        warp::any().map(move || audit_log.clone())
+    }
+
+    fn sessions(&self) -> impl Filter<Extract = (SessionRegistryRef,), Error = Infallible> + Clone {
+        let sessions = self.sessions.clone();
+        warp::any().map(move || sessions.clone())
+    }
+
     fn with_dbms(&self) -> impl Filter<Extract = (DBMSRef,), Error = Infallible> + Clone {
         let dbms = self.dbms.clone();
         warp::any().map(move || dbms.clone())
     }
+    fn with_limits(&self) -> impl Filter<Extract = (Arc<HttpLimits>,), Error = Infallible> + Clone {
+        let limits = self.limits.clone();
+        warp::any().map(move || limits.clone())
+    }
+
+    /// Same as `warp::body::content_length_limit`, the limit is read by each request
+    fn body_limit(
+        &self,
+        limit: fn(&HttpLimits) -> u64,
+    ) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+        let limits = self.limits.clone();
+        warp::body::content_length_limit(u64::MAX)
+            .and(header::<u64>(CONTENT_LENGTH.as_str()))
+            .and_then(move |len: u64| {
+                let max = limit(&limits);
+                async move {
+                    if len > max {
+                        Err(reject::custom(HttpError::BodyOversize {
+                            size: len as usize,
+                        }))
+                    } else {
+                        Ok(())
+                    }
+                }
+            })
+            .untuple_one()
+    }
+
+    /// The body of the request decompressed by the `Content-Encoding`, the limit
+    /// applies to the body decompressed too. The routes take it after `handle_header`,
+    /// the bodies of the clients not authenticated are not decompressed.
+    fn decoded_body(
+        &self,
+        limit: fn(&HttpLimits) -> u64,
+    ) -> impl Filter<Extract = (Bytes,), Error = Rejection> + Clone {
+        let limits = self.limits.clone();
+        header::optional::<String>(CONTENT_ENCODING.as_str())
+            .and(warp::body::bytes())
+            .and_then(move |encoding: Option<String>, body: Bytes| {
+                let max = limit(&limits);
+                async move {
+                    decode_body(encoding.as_deref(), body, max)
+                        .await
+                        .map_err(reject::custom)
+                }
+            })
+    }
+
+    /// The response of the filter compressed by the `Accept-Encoding` of the request
+    fn compressed<F, R>(
+        &self,
+        filter: F,
+    ) -> impl Filter<Extract = (Response,), Error = Rejection> + Clone
+    where
+        F: Filter<Extract = (R,), Error = Rejection> + Clone + Send + Sync + 'static,
+        R: Reply + Send + 'static,
+    {
+        header::optional::<String>(ACCEPT_ENCODING.as_str())
+            .and(filter)
+            .and_then(|accept_encoding: Option<String>, reply: R| async move {
+                let resp = compress_response(reply.into_response(), accept_encoding.as_deref());
+                Ok::<_, Rejection>(resp.await)
+            })
+    }
+
     fn with_kv_inst(&self) -> impl Filter<Extract = (EngineRef,), Error = Infallible> + Clone {
-        let kv_inst = self.kv_inst.clone();
+        let kv_inst = self.coord.engine();
         warp::any().map(move || kv_inst.clone())
     }
 
+    fn dump_dir(&self) -> impl Filter<Extract = (PathBuf,), Error = Infallible> + Clone {
+        let dump_dir = self.dump_dir.clone();
+        warp::any().map(move || dump_dir.clone())
+    }
+
+    fn with_coordinator(
+        &self,
+    ) -> impl Filter<Extract = (CoordinatorRef,), Error = Infallible> + Clone {
+        let coord = self.coord.clone();
+        warp::any().map(move || coord.clone())
+    }
+
+    /// Span of the request, continuing the trace of the client if carried in the headers
+    fn traced(
+        &self,
+        name: &'static str,
+    ) -> impl Filter<Extract = (Span,), Error = Infallible> + Clone {
+        warp::header::headers_cloned().map(move |headers: warp::http::HeaderMap| {
+            let span = info_span!(
+                "http",
+                name,
+                trace_id = field::Empty,
+                tenant = field::Empty,
+                db = field::Empty
+            );
+            set_remote_parent(
+                &span,
+                headers
+                    .iter()
+                    .filter_map(|(k, v)| v.to_str().ok().map(|v| (k.as_str(), v))),
+            );
+            span
+        })
+    }
+
+    fn rebalancer(
+        &self,
+    ) -> impl Filter<Extract = (Arc<Rebalancer>,), Error = warp::Rejection> + Clone {
+        let rebalancer = self.rebalancer.clone();
+        warp::any().and_then(move || {
+            let rebalancer = rebalancer.clone();
+            async move {
+                rebalancer.ok_or_else(|| {
+                    reject::custom(HttpError::Cluster {
+                        reason: "the node is not in a cluster".to_string(),
+                    })
+                })
+            }
+        })
+    }
+
+    fn dc_replication(
+        &self,
+    ) -> impl Filter<Extract = (Arc<DcReplication>,), Error = warp::Rejection> + Clone {
+        let replication = self.dc_replication.clone();
+        warp::any().and_then(move || {
+            let replication = replication.clone();
+            async move {
+                replication.ok_or_else(|| {
+                    reject::custom(HttpError::Cluster {
+                        reason: "the node is not a standby of another cluster".to_string(),
+                    })
+                })
+            }
+        })
+    }
+
+    /// The asynchronous query endpoints are not found unless enabled
+    fn async_queries(
+        &self,
+    ) -> impl Filter<Extract = (AsyncQueriesRef,), Error = warp::Rejection> + Clone {
+        let async_queries = self.async_queries.clone();
+        warp::any().and_then(move || {
+            let async_queries = async_queries.clone();
+            async move { async_queries.ok_or_else(reject::not_found) }
+        })
+    }
+
+    /// Results of the paged queries are spooled with those of the asynchronous ones
+    fn with_paging(
+        &self,
+    ) -> impl Filter<Extract = (Option<AsyncQueriesRef>,), Error = Infallible> + Clone {
+        let async_queries = self.async_queries.clone();
+        warp::any().map(move || async_queries.clone())
+    }
+
+    /// Rejects the writes of the clients to a standby of another cluster
+    fn writable(&self) -> impl Filter<Extract = (), Error = warp::Rejection> + Clone {
+        let replication = self.dc_replication.clone();
+        warp::any()
+            .and_then(move || {
+                let standby = replication.as_ref().map_or(false, |e| e.is_standby());
+                async move {
+                    if standby {
+                        return Err(reject::custom(HttpError::Cluster {
+                            reason: "the node is a standby of the primary cluster until promoted"
+                                .to_string(),
+                        }));
+                    }
+                    Ok(())
+                }
+            })
+            .untuple_one()
+    }
+
+    /// The profiling endpoints are not found unless enabled
+    fn pprof_enabled(&self) -> impl Filter<Extract = (), Error = warp::Rejection> + Clone {
+        let enabled = self.pprof_enabled;
+        warp::any()
+            .and_then(move || async move {
+                if enabled {
+                    Ok(())
+                } else {
+                    Err(reject::not_found())
+                }
+            })
+            .untuple_one()
+    }
+
     fn routes(
         &self,
     ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
         self.ping()
-            .or(self.query())
+            .or(self.health_live())
+            .or(self.health_ready())
+            .or(self.compressed(self.query()))
+            .or(self.submit_async_query())
+            .or(self.async_query_status())
+            .or(self.compressed(self.async_query_result()))
+            .or(self.remove_async_query())
             .or(self.write_line_protocol())
             .or(self.metrics())
+            .or(self.prom_metrics())
+            .or(self.subscribe())
+            .or(self.changes())
+            .or(self.verify())
+            .or(self.export_partition())
+            .or(self.import_partition())
+            .or(self.rebalance())
+            .or(self.decommission())
+            .or(self.replication())
+            .or(self.promote())
+            .or(self.influx_ping())
+            .or(self.compressed(self.influx_query()))
+            .or(self.pprof_profile())
+            .or(self.pprof_heap())
     }
 
     fn ping(&self) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
@@ -119,37 +717,104 @@ impl HttpService {
             })
     }
 
+    fn health_live(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("health" / "live").and(warp::get()).map(|| {
+            let mut resp = HashMap::new();
+            resp.insert("status", "alive");
+            ResponseBuilder::new(OK).json(&resp)
+        })
+    }
+
+    fn health_ready(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        let health = self.health.clone();
+        warp::path!("health" / "ready")
+            .and(warp::get())
+            .and_then(move || {
+                let health = health.clone();
+                async move {
+                    let readiness = health.readiness().await;
+                    // Not ready nodes are taken out of the load balancing
+                    let status = if readiness.ready {
+                        OK
+                    } else {
+                        SERVICE_UNAVAILABLE
+                    };
+                    Ok::<_, Rejection>(ResponseBuilder::new(status).json(&readiness))
+                }
+            })
+    }
+
     fn query(&self) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
         // let dbms = self.dbms.clone();
         warp::path!("api" / "v1" / "sql")
             .and(warp::post())
-            .and(warp::body::content_length_limit(self.query_body_limit))
-            .and(warp::body::bytes())
+            .and(self.body_limit(HttpLimits::query_body_limit))
             .and(self.handle_header())
+            .and(self.decoded_body(HttpLimits::query_body_limit))
             .and(warp::query::<SqlParam>())
             .and(self.with_dbms())
+            .and(self.with_limits())
+            .and(self.with_paging())
+            .and(warp::ext::optional::<SessionRef>())
+            .and(self.traced("sql"))
             .and_then(
-                |req: Bytes, header: Header, param: SqlParam, dbms: DBMSRef| async move {
+                |header: Header,
+                 req: Bytes,
+                 param: SqlParam,
+                 dbms: DBMSRef,
+                 limits: Arc<HttpLimits>,
+                 async_queries: Option<AsyncQueriesRef>,
+                 session: Option<SessionRef>,
+                 span: Span| async move {
                     let req_log = format!(
                         "Receive http sql request, header: {:?}, param: {:?}",
                         header, param
                     );
                     debug!(req_log);
 
-                    // Parse req、header and param to construct query request
-                    let query_req = construct_query(req, &header, param);
+                    let format = param.format.clone();
+                    let page_size = param.page_size;
 
+                    // The next page of a paged query is read from the results spooled,
+                    // the query is not run again
+                    if let Some(cursor) = &param.cursor {
+                        return next_page_handle(&header, format, cursor, async_queries)
+                            .await
+                            .map_err(reject::custom);
+                    }
+
+                    // Parse req、header and param to construct query request
+                    let query_req = construct_query(req, &header, param).and_then(|q| {
+                        limits.check_query(&q.context().user_info().user, q.context().catalog())?;
+                        Ok(q)
+                    });
                     let result = match query_req {
                         Ok(ref q) => {
                             let start = Instant::now();
+                            span.record("tenant", &q.context().catalog());
+                            span.record("db", &q.context().database());
+                            let _running = session.map(|e| e.begin_query(q.content()));
 
-                            let result = sql_handle(q, header, dbms).await;
+                            let result =
+                                sql_handle(q, header, format, dbms, page_size, async_queries)
+                                    .instrument(span.clone())
+                                    .await;
 
+                            let elapsed_ms = start.elapsed().as_millis() as u64;
                             sample_query_read_latency(
                                 q.context().catalog(),
                                 q.context().database(),
-                                start.elapsed().as_millis() as f64,
+                                elapsed_ms as f64,
                             );
+                            let threshold = limits.slow_query_threshold_ms();
+                            let _enter = span.enter();
+                            if threshold > 0 && elapsed_ms > threshold {
+                                trace::warn!("Slow query of {} ms: {}", elapsed_ms, q.content());
+                            }
 
                             result.map_err(|e| {
                                 trace::error!("Failed to handle http sql request, err: {}", e);
@@ -170,31 +835,214 @@ impl HttpService {
             )
     }
 
+    /// Runs the query in the background, the status returned is polled until the
+    /// results are ready
+    fn submit_async_query(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "sql" / "async")
+            .and(warp::post())
+            .and(self.body_limit(HttpLimits::query_body_limit))
+            .and(self.handle_header())
+            .and(self.decoded_body(HttpLimits::query_body_limit))
+            .and(warp::query::<SqlParam>())
+            .and(self.with_dbms())
+            .and(self.with_limits())
+            .and(self.async_queries())
+            .and_then(
+                |header: Header,
+                 req: Bytes,
+                 param: SqlParam,
+                 dbms: DBMSRef,
+                 limits: Arc<HttpLimits>,
+                 async_queries: AsyncQueriesRef| async move {
+                    let page_size = param.page_size;
+                    let query = construct_query(req, &header, param).and_then(|q| {
+                        limits.check_query(&q.context().user_info().user, q.context().catalog())?;
+                        Ok(q)
+                    });
+                    query
+                        .and_then(|query| async_queries.submit(dbms, query, page_size))
+                        .map(|status| ResponseBuilder::new(ACCEPTED).json(&status))
+                        .map_err(reject::custom)
+                },
+            )
+    }
+
+    fn async_query_status(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "sql" / "async" / String)
+            .and(warp::get())
+            .and(self.handle_header())
+            .and(self.async_queries())
+            .and_then(
+                |id: String, header: Header, async_queries: AsyncQueriesRef| async move {
+                    header
+                        .user_info()
+                        .and_then(|user_info| async_queries.status(&user_info.user, &id))
+                        .map(|status| ResponseBuilder::new(OK).json(&status))
+                        .map_err(reject::custom)
+                },
+            )
+    }
+
+    /// A page of the results of a query in the format negotiated, waited for to be
+    /// spooled if the query is running
+    fn async_query_result(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "sql" / "async" / String / "result")
+            .and(warp::get())
+            .and(self.handle_header())
+            .and(warp::query::<AsyncResultParam>())
+            .and(self.async_queries())
+            .and_then(
+                |id: String,
+                 header: Header,
+                 param: AsyncResultParam,
+                 async_queries: AsyncQueriesRef| async move {
+                    let fmt = ResultFormat::negotiate(param.format.as_deref(), header.get_accept())
+                        .map_err(reject::custom)?;
+                    let user_info = header.user_info().map_err(reject::custom)?;
+                    async_queries
+                        .page(&user_info.user, &id, param.page.unwrap_or(0))
+                        .await
+                        .and_then(|batches| fmt.wrap_batches_to_response(&batches))
+                        .map_err(reject::custom)
+                },
+            )
+    }
+
+    /// Cancels the query if running and removes the results
+    fn remove_async_query(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "sql" / "async" / String)
+            .and(warp::delete())
+            .and(self.handle_header())
+            .and(self.async_queries())
+            .and_then(
+                |id: String, header: Header, async_queries: AsyncQueriesRef| async move {
+                    header
+                        .user_info()
+                        .and_then(|user_info| async_queries.remove(&user_info.user, &id))
+                        .map(|_| ResponseBuilder::ok())
+                        .map_err(reject::custom)
+                },
+            )
+    }
+
     fn write_line_protocol(
         &self,
     ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
         warp::path!("api" / "v1" / "write")
             .and(warp::post())
-            .and(warp::body::content_length_limit(self.write_body_limit))
-            .and(warp::body::bytes())
+            .and(self.writable())
+            .and(self.body_limit(HttpLimits::write_body_limit))
             .and(self.handle_header())
+            .and(self.decoded_body(HttpLimits::write_body_limit))
             .and(warp::query::<WriteParam>())
-            .and(self.with_kv_inst())
+            .and(self.with_coordinator())
+            .and(self.with_dbms())
+            .and(self.with_limits())
+            .and(self.traced("write"))
             .and_then(
-                |req: Bytes, header: Header, param: WriteParam, kv_inst: EngineRef| async move {
+                |header: Header,
+                 req: Bytes,
+                 param: WriteParam,
+                 coord: CoordinatorRef,
+                 dbms: DBMSRef,
+                 limits: Arc<HttpLimits>,
+                 span: Span| async move {
                     let start = Instant::now();
-                    let lines = String::from_utf8_lossy(req.as_ref());
-                    let line_protocol_lines =
-                        line_protocol_to_lines(&lines, Local::now().timestamp_nanos())
-                            .context(ParseLineProtocolSnafu)?;
-                    let points = parse_lines_to_points(&param.db, &line_protocol_lines)?;
-                    let req = WritePointsRpcRequest { version: 1, points };
-                    let resp = kv_inst.write(req).await.context(TskvSnafu);
-
-                    let user_info = match header.try_get_basic_auth() {
-                        Ok(u) => u,
-                        Err(e) => return Err(reject::custom(e)),
+                    let req_len = req.len();
+                    let user_info = header.user_info().map_err(reject::custom)?;
+                    // The tenant of the user, the same as that of the queries
+                    let tenant = user_info.user.as_str();
+                    span.record("tenant", &tenant);
+                    span.record("db", &param.db.as_str());
+                    let consistency = param
+                        .consistency
+                        .as_deref()
+                        .map(ConsistencyLevel::from_str)
+                        .transpose()
+                        .map_err(|reason| reject::custom(HttpError::InvalidParameter { reason }))?
+                        .unwrap_or(DEFAULT_WRITE_CONSISTENCY);
+                    let precision = param
+                        .precision
+                        .as_deref()
+                        .map(Precision::from_str)
+                        .transpose()
+                        .map_err(|reason| reject::custom(HttpError::InvalidParameter { reason }))?
+                        .unwrap_or(Precision::Nanosecond);
+                    let now = Local::now().timestamp_nanos();
+                    let (points, numbers, parse_failures) = span
+                        .in_scope(|| {
+                            let _parse = info_span!("parse_line_protocol").entered();
+                            let lines = String::from_utf8_lossy(req.as_ref());
+                            let mut parsed = line_protocol_to_lines_partially(
+                                &lines,
+                                now,
+                                precision,
+                                &limits.tag_limits(),
+                            );
+                            // The write fails as a whole if none of the lines is parsed
+                            if parsed.lines.is_empty() && !parsed.errors.is_empty() {
+                                let source = parsed.errors.swap_remove(0).error;
+                                return Err(HttpError::ParseLineProtocol { source });
+                            }
+                            let points = lines_to_points(&param.db, &parsed.lines);
+                            let failures: Vec<FailedLine> =
+                                parsed.errors.into_iter().map(FailedLine::from).collect();
+                            Ok((points, parsed.numbers, failures))
+                        })
+                        .map_err(reject::custom)?;
+                    let parse_elapsed = start.elapsed();
+                    let measure = {
+                        let (dbms, engine) = (dbms.clone(), coord.engine());
+                        let tenant = tenant.to_string();
+                        move || {
+                            let databases = dbms.database_names(&tenant).unwrap_or_default();
+                            stored_bytes(&engine, &databases)
+                        }
+                    };
+                    limits
+                        .check_write(&user_info.user, tenant, numbers.len(), req_len, measure)
+                        .await
+                        .map_err(reject::custom)?;
+                    let schema_span = span.in_scope(|| info_span!("resolve_schema_on_write"));
+                    let written = dbms
+                        .resolve_schema_on_write(tenant, points, now)
+                        .instrument(schema_span)
+                        .await
+                        .context(QuerySnafu)
+                        .map_err(reject::custom)?;
+                    let req = WritePointsRpcRequest {
+                        version: 1,
+                        points: written.points,
                     };
+                    let resp = coord
+                        .write_points(tenant, consistency, req)
+                        .instrument(span.clone())
+                        .await
+                        .context(CoordinatorSnafu);
+
+                    // The steps of the write to the storage are logged by it if slow
+                    let elapsed = start.elapsed();
+                    let threshold = limits.slow_write_threshold_ms();
+                    if threshold > 0 && elapsed.as_millis() as u64 > threshold {
+                        span.in_scope(|| {
+                            trace::warn!(
+                                "Slow write of {} ms to {}, {} bytes: parse {} ms, write {} ms",
+                                elapsed.as_millis(),
+                                param.db,
+                                req_len,
+                                parse_elapsed.as_millis(),
+                                (elapsed - parse_elapsed).as_millis()
+                            )
+                        });
+                    }
 
                     sample_point_write_latency(
                         &user_info.user,
@@ -202,9 +1050,11 @@ impl HttpService {
                         start.elapsed().as_millis() as f64,
                     );
                     match resp {
-                        Ok(_) => {
+                        Ok(ack) => {
                             incr_point_write_success();
-                            Ok(ResponseBuilder::ok())
+                            record_tenant_write(tenant, numbers.len() as u64, req_len as u64);
+                            let resp = WriteResponse::new(ack, written, &numbers, parse_failures);
+                            Ok(ResponseBuilder::new(OK).json(&resp))
                         }
                         Err(e) => {
                             incr_point_write_failed();
@@ -215,40 +1065,462 @@ impl HttpService {
             )
     }
 
+    /// Upgrades to websocket, see [`handle_subscription`]
+    fn subscribe(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "subscribe")
+            .and(warp::ws())
+            .and(self.handle_header())
+            .and(warp::query::<SqlParam>())
+            .and(self.with_dbms())
+            .and(self.with_kv_inst())
+            .and_then(
+                |ws: Ws, header: Header, param: SqlParam, dbms: DBMSRef, kv_inst: EngineRef| async move {
+                    let user_info = header.user_info().map_err(reject::custom)?;
+                    let context = ContextBuilder::new(user_info)
+                        .with_database(param.db)
+                        .with_target_partitions(param.target_partitions)
+                        .build();
+
+                    let reply = ws.on_upgrade(move |socket| {
+                        handle_subscription(socket, context, dbms, kv_inst)
+                    });
+                    Ok::<_, Rejection>(reply)
+                },
+            )
+    }
+
+    /// The changes of all of the rows, regardless of the row policies
+    fn changes(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "changes")
+            .and(warp::get())
+            .and(self.handle_admin_header())
+            .and(warp::query::<ChangesParam>())
+            .and(self.with_dbms())
+            .and(self.with_kv_inst())
+            .and_then(
+                |header: Header, param: ChangesParam, dbms: DBMSRef, kv_inst: EngineRef| async move {
+                    let user_info = header.user_info().map_err(reject::custom)?;
+                    check_database_access(&dbms, &user_info, &param.db)
+                        .map_err(reject::custom)?;
+
+                    let resp = read_changes(
+                        kv_inst,
+                        &param.db,
+                        param.offset.as_deref(),
+                        param.limit,
+                        param.timeout,
+                        param.consumer.as_deref(),
+                    )
+                    .await
+                    .map_err(reject::custom)?;
+
+                    Ok::<_, Rejection>(ResponseBuilder::new(OK).json(&resp))
+                },
+            )
+    }
+
+    /// Verifies the integrity of the files of a database on the node, the problems
+    /// found are reported while the node keeps serving
+    fn verify(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "verify")
+            .and(warp::get())
+            .and(self.handle_admin_header())
+            .and(warp::query::<VerifyParam>())
+            .and(self.with_kv_inst())
+            .and_then(
+                |_header: Header, param: VerifyParam, kv_inst: EngineRef| async move {
+                    let report = tokio::task::spawn_blocking(move || kv_inst.verify(&param.db))
+                        .await
+                        .map_err(|e| {
+                            reject::custom(HttpError::Verify {
+                                reason: e.to_string(),
+                            })
+                        })?
+                        .context(TskvSnafu)
+                        .map_err(reject::custom)?;
+                    Ok::<_, Rejection>(ResponseBuilder::new(OK).json(&report))
+                },
+            )
+    }
+
+    /// Exports the data of a database in `[start, end)` to a directory in the dump
+    /// directory of the node, as the files of the node with a manifest, regardless
+    /// of the row policies
+    fn export_partition(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "partition" / "export")
+            .and(warp::post())
+            .and(self.handle_admin_header())
+            .and(warp::query::<PartitionParam>())
+            .and(self.with_kv_inst())
+            .and(self.dump_dir())
+            .and_then(
+                |_header: Header, param: PartitionParam, kv_inst: EngineRef, dump_dir: PathBuf| async move {
+                    let time_range = TimeRange::new(
+                        param.start.unwrap_or(i64::MIN),
+                        param.end.map_or(i64::MAX, |e| e.saturating_sub(1)),
+                    );
+                    // The data in the memcaches is exported too
+                    kv_inst
+                        .flush_database(&param.db)
+                        .await
+                        .context(TskvSnafu)
+                        .map_err(reject::custom)?;
+                    let manifest = tokio::task::spawn_blocking(move || {
+                        let dir = partition_dir(&dump_dir, &param.dir, true)?;
+                        kv_inst
+                            .export_partition(&param.db, &time_range, &dir)
+                            .context(TskvSnafu)
+                    })
+                    .await
+                    .map_err(|e| {
+                        reject::custom(HttpError::Partition {
+                            reason: e.to_string(),
+                        })
+                    })?
+                    .map_err(reject::custom)?;
+                    Ok::<_, Rejection>(ResponseBuilder::new(OK).json(&partition_summary(&manifest)))
+                },
+            )
+    }
+
+    /// Imports the data exported to a directory in the dump directory of the node
+    /// into a database, the series and the columns are mapped to the ones of the
+    /// database. The vnodes of the series in the partition must be located on the node.
+    fn import_partition(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "partition" / "import")
+            .and(warp::post())
+            .and(self.handle_admin_header())
+            .and(warp::query::<PartitionParam>())
+            .and(self.with_coordinator())
+            .and(self.dump_dir())
+            .and_then(
+                |_header: Header,
+                 param: PartitionParam,
+                 coord: CoordinatorRef,
+                 dump_dir: PathBuf| async move {
+                    let (dir, manifest) = tokio::task::spawn_blocking(move || {
+                        let dir = partition_dir(&dump_dir, &param.dir, false)?;
+                        let manifest = partition::read_manifest(&dir).context(TskvSnafu)?;
+                        Ok::<_, HttpError>((dir, manifest))
+                    })
+                    .await
+                    .map_err(|e| {
+                        reject::custom(HttpError::Partition {
+                            reason: e.to_string(),
+                        })
+                    })?
+                    .map_err(reject::custom)?;
+                    check_placement(&coord, &param.db, &manifest)
+                        .await
+                        .map_err(reject::custom)?;
+
+                    let manifest = coord
+                        .engine()
+                        .import_partition(&param.db, &dir)
+                        .await
+                        .context(TskvSnafu)
+                        .map_err(reject::custom)?;
+                    Ok::<_, Rejection>(ResponseBuilder::new(OK).json(&partition_summary(&manifest)))
+                },
+            )
+    }
+
+    /// Starts to balance the vnodes over the data nodes by a post, reports the
+    /// progress of the last rebalance by a get
+    fn rebalance(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        let start = warp::post().map(|| true);
+        let progress = warp::get().map(|| false);
+        warp::path!("api" / "v1" / "cluster" / "rebalance")
+            .and(start.or(progress).unify())
+            .and(self.handle_admin_header())
+            .and(self.rebalancer())
+            .and_then(
+                |start: bool, _header: Header, rebalancer: Arc<Rebalancer>| async move {
+                    if start {
+                        rebalancer.start(None).map_err(|e| {
+                            reject::custom(HttpError::Cluster {
+                                reason: e.to_string(),
+                            })
+                        })?;
+                    }
+                    Ok::<_, Rejection>(ResponseBuilder::new(OK).json(&rebalancer.progress()))
+                },
+            )
+    }
+
+    /// Moves the vnodes out of the data node, then removes it from the cluster
+    fn decommission(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "cluster" / "decommission" / u64)
+            .and(warp::post())
+            .and(self.handle_admin_header())
+            .and(self.rebalancer())
+            .and_then(
+                |node_id: u64, _header: Header, rebalancer: Arc<Rebalancer>| async move {
+                    rebalancer.start(Some(node_id)).map_err(|e| {
+                        reject::custom(HttpError::Cluster {
+                            reason: e.to_string(),
+                        })
+                    })?;
+                    Ok::<_, Rejection>(ResponseBuilder::new(OK).json(&rebalancer.progress()))
+                },
+            )
+    }
+
+    /// Status of the replication from the primary cluster
+    fn replication(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "replication")
+            .and(warp::get())
+            .and(self.handle_admin_header())
+            .and(self.dc_replication())
+            .and_then(
+                |_header: Header, replication: Arc<DcReplication>| async move {
+                    Ok::<_, Rejection>(ResponseBuilder::new(OK).json(&replication.status()))
+                },
+            )
+    }
+
+    /// Fails over to the standby once the primary cluster is lost
+    fn promote(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "replication" / "promote")
+            .and(warp::post())
+            .and(self.handle_admin_header())
+            .and(self.dc_replication())
+            .and_then(
+                |_header: Header, replication: Arc<DcReplication>| async move {
+                    let status = replication.promote().await.map_err(|e| {
+                        reject::custom(HttpError::Cluster {
+                            reason: e.to_string(),
+                        })
+                    })?;
+                    Ok::<_, Rejection>(ResponseBuilder::new(OK).json(&status))
+                },
+            )
+    }
+
+    /// Influxdb 1.x compatible ping, probed by grafana
+    fn influx_ping(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("ping")
+            .and(warp::get().or(warp::head()))
+            .map(|_| influx::ping())
+    }
+
+    /// Influxdb 1.x compatible query, the parameters of a post may be sent in the form body
+    fn influx_query(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        let get = warp::get().and(warp::query::<InfluxQueryParam>());
+        let post = warp::post()
+            .and(warp::query::<InfluxQueryParam>())
+            .and(self.body_limit(HttpLimits::query_body_limit))
+            .and(warp::body::form::<InfluxQueryParam>())
+            .map(
+                |query: InfluxQueryParam, form: InfluxQueryParam| InfluxQueryParam {
+                    db: query.db.or(form.db),
+                    q: query.q.or(form.q),
+                    epoch: query.epoch.or(form.epoch),
+                    u: query.u.or(form.u),
+                    p: query.p.or(form.p),
+                },
+            );
+
+        warp::path!("query")
+            .and(get.or(post).unify())
+            .and(header::optional::<String>(AUTHORIZATION.as_str()))
+            .and(warp::ext::optional::<SessionRef>())
+            .and(self.sessions())
+            .and(self.with_dbms())
+            .and(self.with_limits())
+            .and(self.audit_log())
+            .and_then(
+                |param: InfluxQueryParam,
+                 authorization: Option<String>,
+                 session: Option<SessionRef>,
+                 sessions: SessionRegistryRef,
+                 dbms: DBMSRef,
+                 limits: Arc<HttpLimits>,
+                 audit_log: AuditLogRef| async move {
+                    let resp = influx::query(
+                        param,
+                        authorization,
+                        session,
+                        &sessions,
+                        dbms,
+                        &limits,
+                        audit_log,
+                    );
+                    Ok::<_, Rejection>(resp.await)
+                },
+            )
+    }
+
+    /// Profile of the cpu in the protobuf format of pprof, e.g.
+    /// `go tool pprof http://root:@127.0.0.1:31007/debug/pprof/profile?seconds=30`
+    fn pprof_profile(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("debug" / "pprof" / "profile")
+            .and(warp::get())
+            .and(self.pprof_enabled())
+            .and(warp::query::<ProfileParam>())
+            .and(self.handle_admin_header())
+            .and_then(|param: ProfileParam, _header: Header| async move {
+                let seconds = param
+                    .seconds
+                    .unwrap_or(DEFAULT_PROFILE_SECONDS)
+                    .clamp(1, MAX_PROFILE_SECONDS);
+                let frequency = param.frequency.unwrap_or(DEFAULT_PROFILE_FREQUENCY);
+                let profile = tokio::task::spawn_blocking(move || cpu_profile(seconds, frequency))
+                    .await
+                    .map_err(|e| e.to_string())
+                    .and_then(|e| e)
+                    .map_err(|reason| reject::custom(HttpError::Profile { reason }))?;
+                Ok::<_, Rejection>(
+                    ResponseBuilder::new(OK)
+                        .insert_header((CONTENT_TYPE, APPLICATION_OCTET_STREAM))
+                        .build(profile),
+                )
+            })
+    }
+
+    /// Snapshot of the heap in the profile format of jemalloc, e.g.
+    /// `jeprof --svg <cnosdb binary> heap.prof`
+    fn pprof_heap(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("debug" / "pprof" / "heap")
+            .and(warp::get())
+            .and(self.pprof_enabled())
+            .and(self.handle_admin_header())
+            .and_then(|_header: Header| async move {
+                let profile = tokio::task::spawn_blocking(heap_profile)
+                    .await
+                    .map_err(|e| e.to_string())
+                    .and_then(|e| e)
+                    .map_err(|reason| reject::custom(HttpError::Profile { reason }))?;
+                Ok::<_, Rejection>(
+                    ResponseBuilder::new(OK)
+                        .insert_header((CONTENT_TYPE, APPLICATION_OCTET_STREAM))
+                        .build(profile),
+                )
+            })
+    }
+
     fn metrics(
         &self,
     ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
         warp::path!("api" / "v1" / "metrics")
             .map(|| warp::reply::json(&gather_metrics_as_prometheus_string()))
     }
+
+    /// Metrics of the server in the prometheus text format, to be scraped
+    fn prom_metrics(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("metrics").and(warp::get()).map(|| {
+            ResponseBuilder::new(OK)
+                .insert_header((CONTENT_TYPE, PROMETHEUS_TEXT))
+                .build(gather_metrics_as_prometheus_string().into_bytes())
+        })
+    }
 }
 
 #[async_trait::async_trait]
 impl Service for HttpService {
     fn start(&mut self) -> Result<(), server::Error> {
+        // The errors are answered with the CORS headers too, so the pages may read them,
+        // the requests forbidden by the CORS are answered outside
         let routes = self.routes().recover(handle_rejection);
-        let (shutdown, rx) = oneshot::channel();
-        let signal = async {
-            rx.await.ok();
-            info!("http server graceful shutdown!");
+        let routes = match &self.cors {
+            Some(cors) => routes
+                .map(Reply::into_response)
+                .with(cors.clone())
+                .map(Reply::into_response)
+                .boxed(),
+            None => routes.map(Reply::into_response).boxed(),
         };
-        let join_handle = if let Some(TLSConfig {
-            certificate,
-            private_key,
-        }) = &self.tls_config
-        {
-            let (addr, server) = warp::serve(routes)
-                .tls()
-                .cert_path(certificate)
-                .key_path(private_key)
-                .bind_with_graceful_shutdown(self.addr, signal);
-            info!("http server start addr: {}", addr);
-            tokio::spawn(server)
+        let routes = routes
+            .recover(handle_rejection)
+            .map(Reply::into_response)
+            .boxed();
+        // Fails early if the acceptor can not be built, it is built again for each
+        // connection to verify the clients by the CA loaded last
+        if let Some(tls) = &self.tls {
+            tls.acceptor(HTTP_ALPN)?;
+        }
+        let tls = self.tls.clone();
+        let listener = tls::bind(self.addr)?;
+        let sessions = self.sessions.clone();
+        let (shutdown, mut rx) = oneshot::channel();
+        if tls.is_some() {
+            info!("https server start addr: {}", self.addr);
         } else {
-            let (addr, server) = warp::serve(routes).bind_with_graceful_shutdown(self.addr, signal);
-            info!("http server start addr: {}", addr);
-            tokio::spawn(server)
-        };
+            info!("http server start addr: {}", self.addr);
+        }
+
+        let join_handle = tokio::spawn(async move {
+            // The connections hold the senders, all of them are closed once received None
+            let (closing, closed) = watch::channel(false);
+            let (conn_tx, mut conn_rx) = mpsc::channel::<()>(1);
+            loop {
+                let (stream, addr) = tokio::select! {
+                    _ = &mut rx => break,
+                    res = tls::accept(&listener) => res,
+                };
+                let routes = routes.clone();
+                let acceptor = match tls.as_ref().map(|e| e.acceptor(HTTP_ALPN)).transpose() {
+                    Ok(acceptor) => acceptor,
+                    Err(e) => {
+                        error!("Connection of {} is closed, no TLS acceptor: {}", addr, e);
+                        continue;
+                    }
+                };
+                let session = match sessions.open(addr.to_string(), "http") {
+                    Ok(session) => session,
+                    Err(e) => {
+                        warn!("Connection of {} is closed: {}", addr, e);
+                        continue;
+                    }
+                };
+                let closed = closed.clone();
+                let conn_tx = conn_tx.clone();
+                tokio::spawn(async move {
+                    let _conn_tx = conn_tx;
+                    match acceptor {
+                        Some(acceptor) => {
+                            if let Some(stream) = tls::handshake(acceptor, stream, addr).await {
+                                serve_connection(stream, routes, session, closed).await
+                            }
+                        }
+                        None => serve_connection(stream, routes, session, closed).await,
+                    }
+                });
+            }
+
+            info!("http server graceful shutdown!");
+            let _ = closing.send(true);
+            drop(conn_tx);
+            let _ = conn_rx.recv().await;
+        });
         self.handle = Some(ServiceHandle::new(
             "http service".to_string(),
             join_handle,
@@ -264,82 +1536,145 @@ impl Service for HttpService {
     }
 }
 
-fn parse_lines_to_points(db: &str, lines: &[Line]) -> Result<Vec<u8>, Error> {
-    let mut fbb = FlatBufferBuilder::new();
-    let mut point_offsets = Vec::with_capacity(lines.len());
-    for line in lines.iter() {
-        let mut tags = Vec::with_capacity(line.tags.len());
-        for (k, v) in line.tags.iter() {
-            let fbk = fbb.create_vector(k.as_bytes());
-            let fbv = fbb.create_vector(v.as_bytes());
-            let mut tag_builder = TagBuilder::new(&mut fbb);
-            tag_builder.add_key(fbk);
-            tag_builder.add_value(fbv);
-            tags.push(tag_builder.finish());
+/// Serves the requests of the connection, each of them carries the session of the
+/// connection, which is closed once the session is killed and drops the requests
+/// in flight. The connection is closed gracefully once idle for the timeout, and
+/// finishes its requests in flight once the server stops, unless the server is
+/// aborted
+async fn serve_connection<I>(
+    io: I,
+    routes: BoxedFilter<(Response,)>,
+    guard: SessionGuard,
+    mut closed: watch::Receiver<bool>,
+) where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let session = guard.session().clone();
+    let service = warp::service(routes);
+    let request_session = session.clone();
+    let service = service_fn(move |mut req: Request<Body>| {
+        req.extensions_mut().insert(request_session.clone());
+        let request = request_session.begin_request();
+        let mut service = service.clone();
+        let response = service.call(req);
+        async move {
+            let response = response.await;
+            drop(request);
+            response
         }
-        let mut fields = Vec::with_capacity(line.fields.len());
-        for (k, v) in line.fields.iter() {
-            let fbk = fbb.create_vector(k.as_bytes());
-            let (fbv_type, fbv) = match v {
-                line_protocol::FieldValue::U64(field_val) => (
-                    fb_models::FieldType::Unsigned,
-                    fbb.create_vector(&field_val.to_be_bytes()),
-                ),
-                line_protocol::FieldValue::I64(field_val) => (
-                    fb_models::FieldType::Integer,
-                    fbb.create_vector(&field_val.to_be_bytes()),
-                ),
-                line_protocol::FieldValue::Str(field_val) => {
-                    (fb_models::FieldType::String, fbb.create_vector(field_val))
+    });
+    let conn = Http::new().serve_connection(io, service).with_upgrades();
+    tokio::pin!(conn);
+
+    let mut closing = false;
+    loop {
+        let idle_deadline = guard.idle_deadline();
+        let idle = tokio::time::sleep_until(
+            idle_deadline
+                .unwrap_or_else(|| Instant::now() + IDLE_CHECK_INTERVAL)
+                .into(),
+        );
+        tokio::select! {
+            res = conn.as_mut() => {
+                if let Err(e) = res {
+                    debug!("Connection of session {} failed: {}", session.id(), e);
                 }
-                line_protocol::FieldValue::F64(field_val) => (
-                    fb_models::FieldType::Float,
-                    fbb.create_vector(&field_val.to_be_bytes()),
-                ),
-                line_protocol::FieldValue::Bool(field_val) => (
-                    fb_models::FieldType::Boolean,
-                    if *field_val {
-                        fbb.create_vector(&[1_u8][..])
-                    } else {
-                        fbb.create_vector(&[0_u8][..])
-                    },
-                ),
-            };
-            let mut field_builder = FieldBuilder::new(&mut fbb);
-            field_builder.add_name(fbk);
-            field_builder.add_type_(fbv_type);
-            field_builder.add_value(fbv);
-            fields.push(field_builder.finish());
+                return;
+            }
+            _ = session.killed() => {
+                info!("Session {} is killed, the connection is closed", session.id());
+                return;
+            }
+            res = closed.changed() => {
+                // The sender is dropped once the server is aborted
+                if res.is_err() {
+                    debug!("Server is aborted, the connection of session {} is closed", session.id());
+                    return;
+                }
+                if !closing {
+                    closing = true;
+                    conn.as_mut().graceful_shutdown();
+                }
+            }
+            _ = idle, if !closing => {
+                if guard.idle_deadline().map_or(false, |e| e <= Instant::now()) {
+                    debug!("Session {} is idle, the connection is closed", session.id());
+                    closing = true;
+                    conn.as_mut().graceful_shutdown();
+                }
+            }
         }
-        let point_args = PointArgs {
-            db: Some(fbb.create_vector(db.as_bytes())),
-            tab: Some(fbb.create_vector(line.measurement.as_bytes())),
-            tags: Some(fbb.create_vector(&tags)),
-            fields: Some(fbb.create_vector(&fields)),
-            timestamp: line.timestamp,
-        };
-        point_offsets.push(Point::create(&mut fbb, &point_args));
     }
+}
 
-    let fbb_db = fbb.create_vector(db.as_bytes());
-    let points_raw = fbb.create_vector(&point_offsets);
-    let points = Points::create(
-        &mut fbb,
-        &PointsArgs {
-            db: Some(fbb_db),
-            points: Some(points_raw),
-        },
-    );
-    fbb.finish(points, None);
-    Ok(fbb.finished_data().to_vec())
+/// Checks the password of the user or the token, returns the user authenticated
+pub(crate) async fn authenticate(
+    dbms: &DBMSRef,
+    credentials: Credentials,
+) -> Result<UserInfo, HttpError> {
+    let res = match credentials {
+        Credentials::Password(user_info) => dbms.authenticate(&user_info).await.map(|_| user_info),
+        Credentials::Token(token) => dbms.authenticate_token(&token),
+    };
+    res.map_err(|e| HttpError::Auth {
+        reason: e.to_string(),
+    })
+}
+
+/// Checks that the database belongs to the tenant of the user
+pub(crate) fn check_database_access(
+    dbms: &DBMSRef,
+    user_info: &UserInfo,
+    database: &str,
+) -> Result<(), HttpError> {
+    let databases = dbms
+        .database_names(&user_info.user)
+        .map_err(|source| HttpError::Query { source })?;
+    if !databases.iter().any(|e| e == database) {
+        return Err(HttpError::PermissionDenied {
+            reason: format!("user {} can not read database {}", user_info.user, database),
+        });
+    }
+    Ok(())
+}
+
+/// Checks that the user is an admin
+pub(crate) fn check_admin(dbms: &DBMSRef, user_info: &UserInfo) -> Result<(), HttpError> {
+    let is_admin = dbms
+        .is_admin(&user_info.user)
+        .map_err(|source| HttpError::Query { source })?;
+    if !is_admin {
+        return Err(HttpError::PermissionDenied {
+            reason: format!("user {} is not an admin", user_info.user),
+        });
+    }
+    Ok(())
+}
+
+/// Records the authentication of a client connected by the protocol
+pub(crate) fn audit_auth(audit_log: &AuditLog, protocol: &str, res: &Result<UserInfo, HttpError>) {
+    let event = match res {
+        Ok(user_info) => AuditEvent::new(AuditKind::Auth, user_info.user.as_str(), protocol),
+        Err(e) => AuditEvent::new(AuditKind::Auth, "", protocol).with_error(e),
+    };
+    audit_log.record(event);
 }
 
 fn construct_query(req: Bytes, header: &Header, param: SqlParam) -> Result<Query, HttpError> {
-    let user_info = header.try_get_basic_auth()?;
+    let user_info = header.user_info()?;
+
+    let consistency = param
+        .consistency
+        .as_deref()
+        .map(ConsistencyLevel::from_str)
+        .transpose()
+        .map_err(|reason| HttpError::InvalidParameter { reason })?;
 
     let context = ContextBuilder::new(user_info)
         .with_database(param.db)
         .with_target_partitions(param.target_partitions)
+        .with_read_consistency(consistency)
+        .with_time_zone(param.tz)
         .build();
 
     Ok(Query::new(
@@ -348,10 +1683,26 @@ fn construct_query(req: Bytes, header: &Header, param: SqlParam) -> Result<Query
     ))
 }
 
-async fn sql_handle(query: &Query, header: Header, dbms: DBMSRef) -> Result<Response, HttpError> {
+async fn sql_handle(
+    query: &Query,
+    header: Header,
+    format: Option<String>,
+    dbms: DBMSRef,
+    page_size: Option<usize>,
+    async_queries: Option<AsyncQueriesRef>,
+) -> Result<Response, HttpError> {
     debug!("prepare to execute: {:?}", query.content());
 
-    let fmt = ResultFormat::try_from(header.get_accept())?;
+    let fmt = ResultFormat::negotiate(format.as_deref(), header.get_accept())?;
+
+    // The results are spooled, the first page is returned with the cursor of the next
+    // once spooled, the rest are spooled as the query goes on
+    if let Some(page_size) = page_size {
+        let page = paging_enabled(async_queries)?
+            .execute(dbms, query.clone(), page_size)
+            .await?;
+        return page_response(&fmt, page);
+    }
 
     let mut result = dbms.execute(query).await.context(QuerySnafu)?;
 
@@ -364,6 +1715,38 @@ async fn sql_handle(query: &Query, header: Header, dbms: DBMSRef) -> Result<Resp
     fmt.wrap_batches_to_response(&batches)
 }
 
+async fn next_page_handle(
+    header: &Header,
+    format: Option<String>,
+    cursor: &str,
+    async_queries: Option<AsyncQueriesRef>,
+) -> Result<Response, HttpError> {
+    let fmt = ResultFormat::negotiate(format.as_deref(), header.get_accept())?;
+    let user_info = header.user_info()?;
+    let page = paging_enabled(async_queries)?
+        .next_page(&user_info.user, cursor)
+        .await?;
+    page_response(&fmt, page)
+}
+
+fn paging_enabled(async_queries: Option<AsyncQueriesRef>) -> Result<AsyncQueriesRef, HttpError> {
+    async_queries.ok_or_else(|| HttpError::InvalidParameter {
+        reason: "the pagination of the results is not enabled".to_string(),
+    })
+}
+
+/// The rows of the page in the format, the cursor of the next page is in the header
+fn page_response(
+    fmt: &ResultFormat,
+    (batches, next): (Vec<RecordBatch>, Option<String>),
+) -> Result<Response, HttpError> {
+    let mut resp = fmt.wrap_batches_to_response(&batches)?;
+    if let Some(value) = next.and_then(|e| HeaderValue::from_str(&e).ok()) {
+        resp.headers_mut().insert(NEXT_CURSOR, value);
+    }
+    Ok(resp)
+}
+
 /*************** top ****************/
 // Custom rejection handler that maps rejections into responses.
 async fn handle_rejection(err: Rejection) -> Result<impl Reply, std::convert::Infallible> {
@@ -373,8 +1756,11 @@ async fn handle_rejection(err: Rejection) -> Result<impl Reply, std::convert::In
         Ok(ResponseBuilder::method_not_allowed())
     } else if err.find::<PayloadTooLarge>().is_some() {
         Ok(ResponseBuilder::payload_too_large())
+    } else if let Some(e) = err.find::<CorsForbidden>() {
+        let error_resp = ErrorResponse::new(ErrorCode::PermissionDenied, e.to_string());
+        Ok(ResponseBuilder::new(FORBIDDEN).json(&error_resp))
     } else if let Some(e) = err.find::<MissingHeader>() {
-        let error_resp = ErrorResponse::new(ErrorCode::Unknown, e.to_string());
+        let error_resp = ErrorResponse::new(ErrorCode::InvalidParameter, e.to_string());
         Ok(ResponseBuilder::bad_request(&error_resp))
     } else if let Some(e) = err.find::<HttpError>() {
         let resp: Response = e.into();
@@ -389,6 +1775,28 @@ async fn handle_rejection(err: Rejection) -> Result<impl Reply, std::convert::In
 mod test {
     use tokio::time;
 
+    use super::partition_dir;
+
+    #[test]
+    fn test_partition_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let dump_dir = dir.path().join("dump");
+        std::fs::create_dir_all(&dump_dir).unwrap();
+
+        let path = partition_dir(&dump_dir, "./db/p1", true).unwrap();
+        assert!(path.is_dir());
+        assert_eq!(partition_dir(&dump_dir, "db/p1", false).unwrap(), path);
+        assert!(partition_dir(&dump_dir, "db/p2", false).is_err());
+        assert!(partition_dir(&dump_dir, "../p1", true).is_err());
+        assert!(partition_dir(&dump_dir, dir.path().to_str().unwrap(), true).is_err());
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(dir.path(), dump_dir.join("link")).unwrap();
+            assert!(partition_dir(&dump_dir, "link", false).is_err());
+        }
+    }
+
     #[tokio::test]
     async fn test1() {
         use warp::Filter;
@@ -0,0 +1,632 @@
+//! Influxdb 1.x compatible endpoints, so that cnosdb can be added to grafana
+//! as an influxdb data source without any plugin.
+//!
+//! `/ping` returns the version headers the data source probes, `/query` executes
+//! the statements in `q` and returns the results in the influxdb json format.
+//! `SHOW DATABASES` and `SHOW MEASUREMENTS` are answered as influxdb does, other
+//! statements are sql, where the time literals generated by grafana such as
+//! `time >= 1667000000000ms` and `now() - 1h` are rewritten into timestamps and intervals.
+use std::str::FromStr;
+
+use chrono::{SecondsFormat, TimeZone, Utc};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::scalar::ScalarValue;
+use datafusion::sql::sqlparser::ast::{SetExpr, Statement, TableFactor};
+use datafusion::sql::sqlparser::dialect::GenericDialect;
+use datafusion::sql::sqlparser::parser::Parser;
+use http_protocol::parameter::InfluxQueryParam;
+use http_protocol::status_code::{
+    BAD_REQUEST, FORBIDDEN, NO_CONTENT, OK, TOO_MANY_REQUESTS, UNAUTHORIZED,
+};
+use query::audit::AuditLogRef;
+use query::session::{SessionError, SessionRef, SessionRegistry};
+use serde_json::{json, Map, Number, Value};
+use snafu::ResultExt;
+use spi::server::dbms::DBMSRef;
+use spi::service::protocol::{Context, ContextBuilder, Query, UserInfo};
+use trace::debug;
+use warp::http::header::HeaderName;
+use warp::reply::Response;
+
+use super::header::{Credentials, Header};
+use super::http_service::{audit_auth, authenticate, HttpLimits};
+use super::response::ResponseBuilder;
+use super::result_format::fetch_record_batches;
+use super::{Error as HttpError, QuerySnafu, SessionSnafu};
+
+const INFLUXDB_VERSION: &str = "1.8.10";
+const INFLUXDB_BUILD: &str = "OSS";
+const TIME_COLUMN: &str = "time";
+/// Series name of a `SELECT` whose table can not be determined
+const DEFAULT_SERIES: &str = "result";
+
+fn version_headers(builder: ResponseBuilder) -> ResponseBuilder {
+    builder
+        .insert_header((
+            HeaderName::from_static("x-influxdb-version"),
+            INFLUXDB_VERSION,
+        ))
+        .insert_header((HeaderName::from_static("x-influxdb-build"), INFLUXDB_BUILD))
+}
+
+pub fn ping() -> Response {
+    version_headers(ResponseBuilder::new(NO_CONTENT)).build(vec![])
+}
+
+pub async fn query(
+    param: InfluxQueryParam,
+    authorization: Option<String>,
+    session: Option<SessionRef>,
+    sessions: &SessionRegistry,
+    dbms: DBMSRef,
+    limits: &HttpLimits,
+    audit_log: AuditLogRef,
+) -> Response {
+    let results = execute(
+        param,
+        authorization,
+        session,
+        sessions,
+        dbms,
+        limits,
+        audit_log,
+    );
+    match results.await {
+        Ok(results) => {
+            version_headers(ResponseBuilder::new(OK)).json(&json!({ "results": results }))
+        }
+        Err(e) => {
+            let status = match e {
+                HttpError::Auth { .. } => UNAUTHORIZED,
+                HttpError::PermissionDenied { .. }
+                | HttpError::Session {
+                    source: SessionError::UserMismatch { .. },
+                } => FORBIDDEN,
+                // Over the rate limit, or the connections of the user are at the limit
+                HttpError::RateLimited { .. } | HttpError::Session { .. } => TOO_MANY_REQUESTS,
+                _ => BAD_REQUEST,
+            };
+            version_headers(ResponseBuilder::new(status)).json(&json!({ "error": e.to_string() }))
+        }
+    }
+}
+
+/// Errors of a statement are returned in its result, as influxdb does
+async fn execute(
+    param: InfluxQueryParam,
+    authorization: Option<String>,
+    session: Option<SessionRef>,
+    sessions: &SessionRegistry,
+    dbms: DBMSRef,
+    limits: &HttpLimits,
+    audit_log: AuditLogRef,
+) -> Result<Vec<Value>, HttpError> {
+    let epoch = param
+        .epoch
+        .as_deref()
+        .map(Epoch::from_str)
+        .transpose()
+        .map_err(|reason| HttpError::InvalidParameter { reason })?;
+    let q = param
+        .q
+        .as_deref()
+        .filter(|e| !e.trim().is_empty())
+        .ok_or_else(|| HttpError::InvalidParameter {
+            reason: "missing required parameter \"q\"".to_string(),
+        })?;
+    let auth = match credentials(&param, authorization) {
+        Ok(credentials) => authenticate(&dbms, credentials).await,
+        Err(e) => Err(e),
+    };
+    audit_auth(&audit_log, "influx", &auth);
+    let user = auth?;
+    // The connection is bound to the user, as by the other routes
+    if let Some(session) = &session {
+        sessions
+            .authenticate(session, &user.user)
+            .context(SessionSnafu)?;
+    }
+    let context = ContextBuilder::new(user)
+        .with_database(param.db.clone())
+        .build();
+    // Limited as the queries by http
+    limits.check_query(&context.user_info().user, context.catalog())?;
+
+    let mut results = vec![];
+    for (statement_id, stmt) in split_statements(q).into_iter().enumerate() {
+        let mut result = Map::new();
+        result.insert("statement_id".to_string(), statement_id.into());
+        let _running = session.as_ref().map(|e| e.begin_query(stmt));
+        match execute_statement(&dbms, &context, stmt, epoch).await {
+            Ok(Some(series)) => {
+                result.insert("series".to_string(), Value::Array(vec![series]));
+            }
+            Ok(None) => {}
+            Err(e) => {
+                result.insert("error".to_string(), e.to_string().into());
+            }
+        }
+        results.push(Value::Object(result));
+    }
+
+    Ok(results)
+}
+
+fn credentials(
+    param: &InfluxQueryParam,
+    authorization: Option<String>,
+) -> Result<Credentials, HttpError> {
+    if let Some(authorization) = authorization {
+        return Header::with(None, authorization).credentials();
+    }
+
+    match &param.u {
+        Some(user) => Ok(Credentials::Password(UserInfo {
+            user: user.clone(),
+            password: param.p.clone().unwrap_or_default(),
+        })),
+        None => Err(HttpError::ParseAuth {
+            reason: "missing credentials".to_string(),
+        }),
+    }
+}
+
+async fn execute_statement(
+    dbms: &DBMSRef,
+    context: &Context,
+    stmt: &str,
+    epoch: Option<Epoch>,
+) -> Result<Option<Value>, HttpError> {
+    let stmt = InfluxStatement::new(stmt);
+    debug!("Execute influxdb statement: {}", stmt.sql);
+
+    let query = Query::new(context.clone(), stmt.sql.clone());
+    let mut result = dbms.execute(&query).await.context(QuerySnafu)?;
+    let batches = fetch_record_batches(&mut result)
+        .await
+        .map_err(|e| HttpError::FetchResult {
+            reason: e.to_string(),
+        })?;
+
+    to_series(&stmt, &batches, epoch)
+}
+
+/// Precision of the timestamps in the response
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Epoch {
+    Nanosecond,
+    Microsecond,
+    Millisecond,
+    Second,
+    Minute,
+    Hour,
+}
+
+impl Epoch {
+    fn nanos(&self) -> i64 {
+        match self {
+            Self::Nanosecond => 1,
+            Self::Microsecond => 1_000,
+            Self::Millisecond => 1_000_000,
+            Self::Second => 1_000_000_000,
+            Self::Minute => 60_000_000_000,
+            Self::Hour => 3_600_000_000_000,
+        }
+    }
+}
+
+impl FromStr for Epoch {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ns" => Ok(Self::Nanosecond),
+            "u" | "µ" | "us" => Ok(Self::Microsecond),
+            "ms" => Ok(Self::Millisecond),
+            "s" => Ok(Self::Second),
+            "m" => Ok(Self::Minute),
+            "h" => Ok(Self::Hour),
+            _ => Err(format!(
+                "epoch {} is not supported, expected one of ns, u, ms, s, m, h",
+                s
+            )),
+        }
+    }
+}
+
+fn format_time(nanos: i64, epoch: Option<Epoch>) -> Value {
+    match epoch {
+        Some(epoch) => (nanos / epoch.nanos()).into(),
+        None => Utc
+            .timestamp_nanos(nanos)
+            .to_rfc3339_opts(SecondsFormat::AutoSi, true)
+            .into(),
+    }
+}
+
+/// Splits on the semicolons outside of quotes, grafana joins the queries of a panel with them
+fn split_statements(q: &str) -> Vec<&str> {
+    let mut statements = vec![];
+    let mut quote = None;
+    let mut begin = 0;
+    for (idx, c) in q.char_indices() {
+        match (quote, c) {
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(open), _) if open == c => quote = None,
+            (None, ';') => {
+                statements.push(&q[begin..idx]);
+                begin = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    statements.push(&q[begin..]);
+
+    statements
+        .into_iter()
+        .map(|e| e.trim())
+        .filter(|e| !e.is_empty())
+        .collect()
+}
+
+/// Nanoseconds and sql interval unit of an influxql duration unit
+fn duration_unit(unit: &str) -> Option<(i64, &'static str)> {
+    let unit = match unit {
+        "ns" => (1, "nanosecond"),
+        "u" | "µ" => (1_000, "microsecond"),
+        "ms" => (1_000_000, "millisecond"),
+        "s" => (1_000_000_000, "second"),
+        "m" => (60_000_000_000, "minute"),
+        "h" => (3_600_000_000_000, "hour"),
+        "d" => (86_400_000_000_000, "day"),
+        "w" => (604_800_000_000_000, "week"),
+        _ => return None,
+    };
+    Some(unit)
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.'
+}
+
+/// Rewrites influxql duration literals outside of quotes, a literal compared with
+/// a column is an absolute time (`time >= 1667000000000ms`), otherwise it is an
+/// interval (`now() - 1h`, `date_bin(10s, time)`).
+fn rewrite_time_literals(stmt: &str) -> String {
+    let mut result = String::with_capacity(stmt.len());
+    let mut quote = None;
+    let mut chars = stmt.char_indices().peekable();
+    let mut prev = None;
+
+    while let Some((idx, c)) = chars.next() {
+        let is_literal_start =
+            quote.is_none() && c.is_ascii_digit() && !prev.map(is_ident_char).unwrap_or(false);
+        if !is_literal_start {
+            match (quote, c) {
+                (None, '\'' | '"') => quote = Some(c),
+                (Some(open), _) if open == c => quote = None,
+                _ => {}
+            }
+            result.push(c);
+            prev = Some(c);
+            continue;
+        }
+
+        let mut end = idx + c.len_utf8();
+        while let Some((i, c)) = chars.peek().copied() {
+            if !c.is_ascii_digit() {
+                break;
+            }
+            end = i + c.len_utf8();
+            chars.next();
+        }
+        let digits_end = end;
+        while let Some((i, c)) = chars.peek().copied() {
+            if !is_ident_char(c) {
+                break;
+            }
+            end = i + c.len_utf8();
+            chars.next();
+        }
+
+        let literal = &stmt[idx..end];
+        prev = literal.chars().last();
+        let value = stmt[idx..digits_end].parse::<i64>().ok();
+        match (value, duration_unit(&stmt[digits_end..end])) {
+            (Some(value), Some((nanos, unit))) => {
+                let compared = result
+                    .trim_end()
+                    .ends_with(|c| matches!(c, '=' | '<' | '>'));
+                if compared {
+                    result.push_str(&format!(
+                        "CAST({} AS TIMESTAMP)",
+                        value.saturating_mul(nanos)
+                    ));
+                } else {
+                    result.push_str(&format!("INTERVAL '{} {}'", value, unit));
+                }
+            }
+            _ => result.push_str(literal),
+        }
+    }
+
+    result
+}
+
+struct InfluxStatement {
+    sql: String,
+    /// Series name of the influxql meta statements, whose single column is named `name`
+    meta: Option<&'static str>,
+    limit: Option<usize>,
+}
+
+impl InfluxStatement {
+    fn new(stmt: &str) -> Self {
+        let tokens = stmt.split_whitespace().collect::<Vec<_>>();
+        let upper = tokens
+            .iter()
+            .map(|e| e.to_ascii_uppercase())
+            .collect::<Vec<_>>();
+        let upper = upper.iter().map(|e| e.as_str()).collect::<Vec<_>>();
+
+        match upper.as_slice() {
+            ["SHOW", "DATABASES"] => Self {
+                sql: "SHOW DATABASES".to_string(),
+                meta: Some("databases"),
+                limit: None,
+            },
+            ["SHOW", "MEASUREMENTS", rest @ ..] => {
+                let mut sql = "SHOW TABLES".to_string();
+                let mut limit = None;
+                let mut i = 0;
+                while i + 1 < rest.len() {
+                    match rest[i] {
+                        "ON" => sql = format!("SHOW TABLES ON {}", tokens[2 + i + 1]),
+                        "LIMIT" => limit = rest[i + 1].parse().ok(),
+                        _ => {}
+                    }
+                    i += 1;
+                }
+                Self {
+                    sql,
+                    meta: Some("measurements"),
+                    limit,
+                }
+            }
+            _ => Self {
+                sql: rewrite_time_literals(stmt),
+                meta: None,
+                limit: None,
+            },
+        }
+    }
+
+    fn series_name(&self) -> String {
+        if let Some(name) = self.meta {
+            return name.to_string();
+        }
+
+        let table = Parser::parse_sql(&GenericDialect {}, &self.sql)
+            .ok()
+            .and_then(|statements| match statements.into_iter().next() {
+                Some(Statement::Query(query)) => match *query.body {
+                    SetExpr::Select(select) => select.from.into_iter().next(),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .and_then(|from| match from.relation {
+                TableFactor::Table { name, .. } => name.0.last().map(|e| e.value.clone()),
+                _ => None,
+            });
+
+        table.unwrap_or_else(|| DEFAULT_SERIES.to_string())
+    }
+}
+
+/// Converts the result into a series, the `time` column is placed first as grafana expects
+fn to_series(
+    stmt: &InfluxStatement,
+    batches: &[RecordBatch],
+    epoch: Option<Epoch>,
+) -> Result<Option<Value>, HttpError> {
+    let schema = match batches.first() {
+        Some(batch) => batch.schema(),
+        None => return Ok(None),
+    };
+
+    let mut indices = (0..schema.fields().len()).collect::<Vec<_>>();
+    if let Some(pos) = indices
+        .iter()
+        .position(|i| schema.field(*i).name() == TIME_COLUMN)
+    {
+        let time = indices.remove(pos);
+        indices.insert(0, time);
+    }
+    let columns = indices
+        .iter()
+        .map(|i| match stmt.meta {
+            Some(_) => "name".to_string(),
+            None => schema.field(*i).name().clone(),
+        })
+        .collect::<Vec<_>>();
+
+    let mut values = vec![];
+    'batches: for batch in batches {
+        for row in 0..batch.num_rows() {
+            if stmt.limit.map(|e| values.len() >= e).unwrap_or(false) {
+                break 'batches;
+            }
+            let mut value = Vec::with_capacity(indices.len());
+            for i in indices.iter() {
+                let scalar = ScalarValue::try_from_array(batch.column(*i), row).map_err(|e| {
+                    HttpError::FetchResult {
+                        reason: e.to_string(),
+                    }
+                })?;
+                value.push(scalar_to_json(scalar, epoch));
+            }
+            values.push(Value::Array(value));
+        }
+    }
+
+    if values.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(json!({
+        "name": stmt.series_name(),
+        "columns": columns,
+        "values": values,
+    })))
+}
+
+fn scalar_to_json(scalar: ScalarValue, epoch: Option<Epoch>) -> Value {
+    if scalar.is_null() {
+        return Value::Null;
+    }
+
+    match scalar {
+        ScalarValue::Boolean(Some(v)) => v.into(),
+        ScalarValue::Float32(Some(v)) => float_to_json(v as f64),
+        ScalarValue::Float64(Some(v)) => float_to_json(v),
+        ScalarValue::Int8(Some(v)) => v.into(),
+        ScalarValue::Int16(Some(v)) => v.into(),
+        ScalarValue::Int32(Some(v)) => v.into(),
+        ScalarValue::Int64(Some(v)) => v.into(),
+        ScalarValue::UInt8(Some(v)) => v.into(),
+        ScalarValue::UInt16(Some(v)) => v.into(),
+        ScalarValue::UInt32(Some(v)) => v.into(),
+        ScalarValue::UInt64(Some(v)) => v.into(),
+        ScalarValue::Utf8(Some(v)) | ScalarValue::LargeUtf8(Some(v)) => v.into(),
+        ScalarValue::TimestampSecond(Some(v), _) => {
+            format_time(v.saturating_mul(1_000_000_000), epoch)
+        }
+        ScalarValue::TimestampMillisecond(Some(v), _) => {
+            format_time(v.saturating_mul(1_000_000), epoch)
+        }
+        ScalarValue::TimestampMicrosecond(Some(v), _) => {
+            format_time(v.saturating_mul(1_000), epoch)
+        }
+        ScalarValue::TimestampNanosecond(Some(v), _) => format_time(v, epoch),
+        other => other.to_string().into(),
+    }
+}
+
+fn float_to_json(v: f64) -> Value {
+    Number::from_f64(v)
+        .map(Value::Number)
+        .unwrap_or(Value::Null)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use datafusion::arrow::array::{Float64Array, StringArray, TimestampNanosecondArray};
+    use datafusion::arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+
+    use super::*;
+
+    #[test]
+    fn test_epoch() {
+        assert_eq!(Epoch::from_str("ms").unwrap(), Epoch::Millisecond);
+        assert_eq!(Epoch::from_str("µ").unwrap(), Epoch::Microsecond);
+        assert!(Epoch::from_str("d").is_err());
+
+        let nanos = 1_667_000_000_123_456_789;
+        assert_eq!(
+            format_time(nanos, Some(Epoch::Millisecond)),
+            json!(1_667_000_000_123_i64)
+        );
+        assert_eq!(
+            format_time(nanos, None),
+            json!("2022-10-28T23:33:20.123456789Z")
+        );
+    }
+
+    #[test]
+    fn test_split_statements() {
+        assert_eq!(
+            split_statements("SELECT 1; SELECT ';' FROM t;"),
+            vec!["SELECT 1", "SELECT ';' FROM t"]
+        );
+        assert!(split_statements(" ; ").is_empty());
+    }
+
+    #[test]
+    fn test_rewrite_time_literals() {
+        assert_eq!(
+            rewrite_time_literals("SELECT * FROM cpu WHERE time >= 1667000000000ms and time <= now()"),
+            "SELECT * FROM cpu WHERE time >= CAST(1667000000000000000 AS TIMESTAMP) and time <= now()"
+        );
+        assert_eq!(
+            rewrite_time_literals("SELECT date_bin(10s, time) FROM cpu WHERE time > now() - 1h"),
+            "SELECT date_bin(INTERVAL '10 second', time) FROM cpu WHERE time > now() - INTERVAL '1 hour'"
+        );
+        assert_eq!(
+            rewrite_time_literals("SELECT * FROM cpu10s WHERE host = '5m' LIMIT 10"),
+            "SELECT * FROM cpu10s WHERE host = '5m' LIMIT 10"
+        );
+    }
+
+    #[test]
+    fn test_influx_statement() {
+        let stmt = InfluxStatement::new("show databases");
+        assert_eq!(stmt.sql, "SHOW DATABASES");
+        assert_eq!(stmt.series_name(), "databases");
+
+        let stmt = InfluxStatement::new("SHOW MEASUREMENTS ON db LIMIT 1");
+        assert_eq!(stmt.sql, "SHOW TABLES ON db");
+        assert_eq!(stmt.limit, Some(1));
+        assert_eq!(stmt.series_name(), "measurements");
+
+        let stmt = InfluxStatement::new("SELECT usage FROM public.cpu WHERE time > 0s");
+        assert_eq!(
+            stmt.sql,
+            "SELECT usage FROM public.cpu WHERE time > CAST(0 AS TIMESTAMP)"
+        );
+        assert_eq!(stmt.series_name(), "cpu");
+        assert_eq!(
+            InfluxStatement::new("SELECT 1").series_name(),
+            DEFAULT_SERIES
+        );
+    }
+
+    #[test]
+    fn test_to_series() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("host", DataType::Utf8, true),
+            Field::new(
+                TIME_COLUMN,
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+            Field::new("usage", DataType::Float64, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec![Some("a"), None])),
+                Arc::new(TimestampNanosecondArray::from(vec![1_000_000, 2_000_000])),
+                Arc::new(Float64Array::from(vec![Some(0.5), None])),
+            ],
+        )
+        .unwrap();
+
+        let stmt = InfluxStatement::new("SELECT * FROM cpu");
+        let series = to_series(&stmt, &[batch], Some(Epoch::Millisecond))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            series,
+            json!({
+                "name": "cpu",
+                "columns": ["time", "host", "usage"],
+                "values": [[1, "a", 0.5], [2, null, null]],
+            })
+        );
+
+        assert!(to_series(&stmt, &[], None).unwrap().is_none());
+    }
+}
@@ -1,6 +1,8 @@
 use std::net::AddrParseError;
+use std::time::Duration;
 
 use models::error_code::ErrorCode;
+use query::session::SessionError;
 use snafu::Snafu;
 use spi::server::ServerError;
 use tokio::sync::mpsc::error::SendError;
@@ -10,15 +12,28 @@ use tokio::sync::oneshot::error::RecvError;
 use warp::reject;
 use warp::reply::Response;
 
+use http_protocol::header::{HeaderValue, RETRY_AFTER};
 use http_protocol::response::ErrorResponse;
-use http_protocol::status_code::UNPROCESSABLE_ENTITY;
+use http_protocol::status_code::{
+    FORBIDDEN, NOT_FOUND, SERVICE_UNAVAILABLE, TOO_MANY_REQUESTS, UNAUTHORIZED,
+    UNPROCESSABLE_ENTITY,
+};
 
 use self::response::ResponseBuilder;
 
-mod header;
+pub mod async_query;
+mod changes;
+mod compression;
+pub(crate) mod header;
+pub mod health;
 pub mod http_service;
+mod influx;
+mod profile;
+pub(crate) mod quota;
+mod rate_limit;
 mod response;
-mod result_format;
+pub(crate) mod result_format;
+mod subscription;
 
 #[derive(Debug, Snafu)]
 #[snafu(visibility(pub))]
@@ -51,43 +66,197 @@ pub enum Error {
     #[snafu(display("Error from tskv: {}", source))]
     Tskv { source: tskv::Error },
 
+    #[snafu(display("Error from coordinator: {}", source))]
+    Coordinator {
+        source: coordinator::errors::CoordinatorError,
+    },
+
     #[snafu(display("Invalid header: {}", reason))]
     InvalidHeader { reason: String },
 
+    #[snafu(display("Invalid parameter: {}", reason))]
+    InvalidParameter { reason: String },
+
+    #[snafu(display("Invalid body: {}", reason))]
+    InvalidBody { reason: String },
+
     #[snafu(display("Parse auth, malformed basic auth encoding: {}", reason))]
     ParseAuth { reason: String },
 
+    #[snafu(display("Authentication failed: {}", reason))]
+    Auth { reason: String },
+
+    #[snafu(display("Permission denied: {}", reason))]
+    PermissionDenied { reason: String },
+
     #[snafu(display("Fetch result: {}", reason))]
     FetchResult { reason: String },
+
+    #[snafu(display("Cluster error: {}", reason))]
+    Cluster { reason: String },
+
+    #[snafu(display("Failed to profile: {}", reason))]
+    Profile { reason: String },
+
+    #[snafu(display("Failed to verify: {}", reason))]
+    Verify { reason: String },
+
+    #[snafu(display("Failed to move the partition: {}", reason))]
+    Partition { reason: String },
+
+    #[snafu(display("Asynchronous query {} is not found or expired", id))]
+    AsyncQueryNotFound { id: String },
+
+    #[snafu(display("Asynchronous queries of user {} over the limit: {}", user, reason))]
+    AsyncQueryLimit { user: String, reason: String },
+
+    #[snafu(display("Rate limit exceeded, retry after {} ms", retry_after.as_millis()))]
+    RateLimited { retry_after: Duration },
+
+    #[snafu(display(
+        "Quota exceeded, tenant {} stores {} bytes of {}",
+        tenant,
+        stored_bytes,
+        max_stored_bytes
+    ))]
+    QuotaExceeded {
+        tenant: String,
+        stored_bytes: u64,
+        max_stored_bytes: u64,
+    },
+
+    #[snafu(display("{}", source))]
+    Session { source: SessionError },
 }
 
 impl reject::Reject for Error {}
 
+impl Error {
+    pub fn error_code(&self) -> ErrorCode {
+        match self {
+            Error::Query { source } => source.error_code(),
+            Error::Tskv { source } => source.error_code(),
+            Error::Coordinator { source } => source.error_code(),
+            Error::FetchResult { .. } => ErrorCode::QueryExecution,
+            Error::Cluster { .. } => ErrorCode::CoordinatorUnknown,
+            Error::NotUtf8 | Error::ParseLineProtocol { .. } => ErrorCode::InvalidPoint,
+            Error::BodyOversize { .. }
+            | Error::InvalidHeader { .. }
+            | Error::InvalidParameter { .. }
+            | Error::InvalidBody { .. } => ErrorCode::InvalidParameter,
+            Error::ParseAuth { .. } | Error::Auth { .. } => ErrorCode::AuthFailed,
+            Error::PermissionDenied { .. } => ErrorCode::PermissionDenied,
+            Error::RateLimited { .. } => ErrorCode::RateLimited,
+            Error::QuotaExceeded { .. } | Error::AsyncQueryLimit { .. } => ErrorCode::QuotaExceeded,
+            Error::Session { source } => source.error_code(),
+            Error::AsyncQueryNotFound { .. } => ErrorCode::ObjectNotFound,
+            _ => ErrorCode::Unknown,
+        }
+    }
+
+    /// The root cause of the errors of the storage, e.g. the io error, the messages
+    /// of the query errors already point at the statement
+    fn error_detail(&self) -> Option<String> {
+        let mut cause: &dyn std::error::Error = match self {
+            Error::Tskv { source } => source,
+            Error::Coordinator { source } => source,
+            _ => return None,
+        };
+        while let Some(source) = cause.source() {
+            cause = source;
+        }
+        Some(cause.to_string())
+    }
+
+    fn error_response(&self) -> ErrorResponse {
+        let error_resp = ErrorResponse::new(self.error_code(), self.to_string());
+        match self.error_detail() {
+            Some(detail) => error_resp.with_detail(detail),
+            None => error_resp,
+        }
+    }
+}
+
 impl From<&Error> for Response {
     fn from(e: &Error) -> Self {
-        let error_message = format!("{}", e);
-
         match e {
+            Error::Query { source: _ } if e.error_code() == ErrorCode::PermissionDenied => {
+                let error_resp = e.error_response();
+
+                ResponseBuilder::new(FORBIDDEN).json(&error_resp)
+            }
             Error::Query { source: _ } => {
-                let error_resp = ErrorResponse::new(ErrorCode::QueryUnknown, error_message);
+                let error_resp = e.error_response();
 
                 ResponseBuilder::new(UNPROCESSABLE_ENTITY).json(&error_resp)
             }
             Error::FetchResult { reason: _ } => {
-                let error_resp = ErrorResponse::new(ErrorCode::QueryUnknown, error_message);
+                let error_resp = e.error_response();
+
+                ResponseBuilder::new(UNPROCESSABLE_ENTITY).json(&error_resp)
+            }
+            Error::Coordinator { source } if source.is_write_stalled() => {
+                let error_resp = e.error_response();
+
+                ResponseBuilder::new(SERVICE_UNAVAILABLE)
+                    .insert_header((RETRY_AFTER, HeaderValue::from(1_u64)))
+                    .json(&error_resp)
+            }
+            Error::Cluster { reason: _ }
+            | Error::Coordinator { source: _ }
+            | Error::Profile { reason: _ }
+            | Error::Verify { reason: _ }
+            | Error::Partition { reason: _ } => {
+                let error_resp = e.error_response();
 
                 ResponseBuilder::new(UNPROCESSABLE_ENTITY).json(&error_resp)
             }
             Error::Tskv { source: _ } => {
-                let error_resp = ErrorResponse::new(ErrorCode::TskvUnknown, error_message);
+                let error_resp = e.error_response();
 
                 ResponseBuilder::new(UNPROCESSABLE_ENTITY).json(&error_resp)
             }
-            Error::InvalidHeader { reason: _ } | Error::ParseAuth { reason: _ } => {
-                let error_resp = ErrorResponse::new(ErrorCode::Unknown, error_message);
+            Error::BodyOversize { size: _ } => ResponseBuilder::payload_too_large(),
+            Error::Auth { reason: _ } => {
+                let error_resp = e.error_response();
+
+                ResponseBuilder::new(UNAUTHORIZED).json(&error_resp)
+            }
+            Error::PermissionDenied { reason: _ }
+            | Error::Session {
+                source: SessionError::UserMismatch { .. },
+            } => {
+                let error_resp = e.error_response();
+
+                ResponseBuilder::new(FORBIDDEN).json(&error_resp)
+            }
+            Error::InvalidHeader { reason: _ }
+            | Error::InvalidParameter { reason: _ }
+            | Error::InvalidBody { reason: _ }
+            | Error::ParseAuth { reason: _ } => {
+                let error_resp = e.error_response();
 
                 ResponseBuilder::bad_request(&error_resp)
             }
+            Error::RateLimited { retry_after } => {
+                let error_resp = e.error_response();
+                // In whole seconds, rounded up
+                let secs = retry_after.as_secs() + (retry_after.subsec_nanos() > 0) as u64;
+
+                ResponseBuilder::new(TOO_MANY_REQUESTS)
+                    .insert_header((RETRY_AFTER, HeaderValue::from(secs.max(1))))
+                    .json(&error_resp)
+            }
+            Error::QuotaExceeded { .. } | Error::AsyncQueryLimit { .. } | Error::Session { .. } => {
+                let error_resp = e.error_response();
+
+                ResponseBuilder::new(TOO_MANY_REQUESTS).json(&error_resp)
+            }
+            Error::AsyncQueryNotFound { .. } => {
+                let error_resp = e.error_response();
+
+                ResponseBuilder::new(NOT_FOUND).json(&error_resp)
+            }
             _ => ResponseBuilder::internal_server_error(),
         }
     }
@@ -102,9 +271,12 @@ impl From<Error> for Response {
 #[cfg(test)]
 mod tests {
     use spi::query::QueryError;
-    use warp::http::header::{HeaderValue, CONTENT_TYPE};
+    use warp::http::header::CONTENT_TYPE;
 
-    use http_protocol::{header::APPLICATION_JSON, status_code::BAD_REQUEST};
+    use http_protocol::{
+        header::APPLICATION_JSON,
+        status_code::{BAD_REQUEST, PAYLOAD_TOO_LARGE},
+    };
 
     use super::*;
 
@@ -152,6 +324,13 @@ mod tests {
         assert_eq!(content_type, HeaderValue::from_static(APPLICATION_JSON));
     }
 
+    #[test]
+    fn test_body_oversize_error() {
+        let resp: Response = Error::BodyOversize { size: 1024 }.into();
+
+        assert_eq!(resp.status(), PAYLOAD_TOO_LARGE);
+    }
+
     #[test]
     fn test_invalid_header_error() {
         let resp: Response = Error::InvalidHeader {
@@ -179,4 +358,112 @@ mod tests {
 
         assert_eq!(content_type, HeaderValue::from_static(APPLICATION_JSON));
     }
+
+    #[test]
+    fn test_rate_limited_error() {
+        let resp: Response = Error::RateLimited {
+            retry_after: Duration::from_millis(1200),
+        }
+        .into();
+
+        assert_eq!(resp.status(), TOO_MANY_REQUESTS);
+        assert_eq!(
+            resp.headers().get(RETRY_AFTER).unwrap(),
+            HeaderValue::from_static("2")
+        );
+    }
+
+    #[test]
+    fn test_quota_exceeded_error() {
+        let resp: Response = Error::QuotaExceeded {
+            tenant: "cnosdb".to_string(),
+            stored_bytes: 200,
+            max_stored_bytes: 100,
+        }
+        .into();
+
+        assert_eq!(resp.status(), TOO_MANY_REQUESTS);
+        assert!(resp.headers().get(RETRY_AFTER).is_none());
+    }
+
+    #[test]
+    fn test_write_stalled_error() {
+        let resp: Response = Error::Coordinator {
+            source: coordinator::errors::CoordinatorError::Tskv {
+                source: tskv::Error::WriteStalled {
+                    pending: 1024,
+                    timeout_ms: 10000,
+                },
+            },
+        }
+        .into();
+
+        assert_eq!(resp.status(), SERVICE_UNAVAILABLE);
+        assert!(resp.headers().get(RETRY_AFTER).is_some());
+    }
+
+    #[test]
+    fn test_permission_denied_error() {
+        let resp: Response = Error::PermissionDenied {
+            reason: "test".to_string(),
+        }
+        .into();
+        assert_eq!(resp.status(), FORBIDDEN);
+
+        let resp: Response = Error::Query {
+            source: ServerError::Query {
+                source: QueryError::Execution {
+                    source: spi::query::execution::ExecutionError::PermissionDenied {
+                        reason: "test".to_string(),
+                    },
+                },
+            },
+        }
+        .into();
+        assert_eq!(resp.status(), FORBIDDEN);
+    }
+
+    #[test]
+    fn test_auth_error() {
+        let resp: Response = Error::Auth {
+            reason: "test".to_string(),
+        }
+        .into();
+
+        assert_eq!(resp.status(), UNAUTHORIZED);
+
+        let content_type = resp.headers().get(CONTENT_TYPE).unwrap();
+
+        assert_eq!(content_type, HeaderValue::from_static(APPLICATION_JSON));
+    }
+
+    #[test]
+    fn test_error_code() {
+        let err = Error::Query {
+            source: ServerError::Query {
+                source: QueryError::Parser {
+                    source: datafusion::sql::sqlparser::parser::ParserError::ParserError(
+                        "test".to_string(),
+                    ),
+                },
+            },
+        };
+        assert_eq!(err.error_code(), ErrorCode::SqlParse);
+        assert!(err.error_detail().is_none());
+
+        let err = Error::Coordinator {
+            source: coordinator::errors::CoordinatorError::Tskv {
+                source: tskv::Error::WriteFile {
+                    source: std::io::Error::new(std::io::ErrorKind::Other, "no space"),
+                },
+            },
+        };
+        assert_eq!(err.error_code(), ErrorCode::TskvIo);
+        assert_eq!(err.error_detail().as_deref(), Some("no space"));
+
+        let err = Error::RateLimited {
+            retry_after: Duration::from_millis(100),
+        };
+        assert_eq!(err.error_code(), ErrorCode::RateLimited);
+    }
 }
@@ -0,0 +1,59 @@
+//! Profiles of a running server, readable by `go tool pprof` and `jeprof`.
+//!
+//! Served at `/debug/pprof/*` only if `security.pprof_enabled` is set.
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use mem_allocator::profiling::dump_heap_profile;
+use pprof::protos::Message;
+use pprof::ProfilerGuard;
+
+pub const DEFAULT_PROFILE_SECONDS: u64 = 30;
+pub const MAX_PROFILE_SECONDS: u64 = 300;
+/// Not a multiple of the timers of the server, so they are sampled fairly
+pub const DEFAULT_PROFILE_FREQUENCY: i32 = 99;
+
+/// Heap profiles dumped, so the files of the concurrent dumps differ
+static HEAP_DUMPS: AtomicU64 = AtomicU64::new(0);
+
+/// Samples the stacks of the threads for the seconds, blocks the calling thread,
+/// returns the profile in the protobuf format of pprof
+pub fn cpu_profile(seconds: u64, frequency: i32) -> Result<Vec<u8>, String> {
+    // Fails if a profile is already in progress
+    let guard = ProfilerGuard::new(frequency).map_err(|e| e.to_string())?;
+    std::thread::sleep(Duration::from_secs(seconds));
+    let profile = guard
+        .report()
+        .build()
+        .and_then(|report| report.pprof())
+        .map_err(|e| e.to_string())?;
+
+    let mut content = Vec::new();
+    profile.encode(&mut content).map_err(|e| e.to_string())?;
+    Ok(content)
+}
+
+/// Snapshot of the heap allocated in the profile format of jemalloc
+pub fn heap_profile() -> Result<Vec<u8>, String> {
+    let path = std::env::temp_dir().join(format!(
+        "cnosdb_heap_{}_{}.prof",
+        std::process::id(),
+        HEAP_DUMPS.fetch_add(1, Ordering::Relaxed)
+    ));
+    let path = path.to_string_lossy().to_string();
+    dump_heap_profile(&path)?;
+    let content = std::fs::read(&path).map_err(|e| format!("{}: {}", path, e));
+    let _ = std::fs::remove_file(&path);
+    content
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_cpu_profile() {
+        let profile = cpu_profile(1, DEFAULT_PROFILE_FREQUENCY).unwrap();
+        assert!(pprof::protos::Profile::decode(profile.as_slice()).is_ok());
+    }
+}
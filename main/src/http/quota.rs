@@ -0,0 +1,136 @@
+//! Quotas of the data stored by each tenant on this node, the bytes stored by a
+//! tenant are measured again once older than [`USAGE_TTL`] as a write of it checked.
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use config::QuotaConfig;
+use metrics::tenant_usage::record_stored_bytes;
+use parking_lot::{Mutex, RwLock};
+use trace::warn;
+use tskv::engine::EngineRef;
+
+/// Time the bytes stored by a tenant are used for before measured again
+const USAGE_TTL: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Default)]
+pub struct TenantQuotas {
+    config: RwLock<QuotaConfig>,
+    /// Bytes stored by the tenants and the time measured
+    stored: Mutex<HashMap<String, (u64, Instant)>>,
+}
+
+impl TenantQuotas {
+    pub fn new(config: &QuotaConfig) -> Self {
+        Self {
+            config: RwLock::new(config.clone()),
+            stored: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn update(&self, config: &QuotaConfig) {
+        *self.config.write() = config.clone();
+    }
+
+    /// The bytes stored and the quota if the tenant is over it, the bytes stored are
+    /// only measured for the tenants with quotas, on the blocking threads
+    pub async fn check_write(
+        &self,
+        tenant: &str,
+        measure: impl FnOnce() -> u64 + Send + 'static,
+    ) -> Result<(), (u64, u64)> {
+        let max_stored_bytes = self.config.read().tenant(tenant).max_stored_bytes;
+        if max_stored_bytes == 0 {
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        let cached = self
+            .stored
+            .lock()
+            .get(tenant)
+            .filter(|(_, measured)| now.saturating_duration_since(*measured) < USAGE_TTL)
+            .map(|(bytes, _)| *bytes);
+        let stored_bytes = match cached {
+            Some(bytes) => bytes,
+            None => {
+                let bytes = match tokio::task::spawn_blocking(measure).await {
+                    Ok(bytes) => bytes,
+                    Err(e) => {
+                        warn!("Failed to measure the bytes stored by {}: {}", tenant, e);
+                        return Ok(());
+                    }
+                };
+                record_stored_bytes(tenant, bytes);
+                self.stored.lock().insert(tenant.to_string(), (bytes, now));
+                bytes
+            }
+        };
+
+        if stored_bytes >= max_stored_bytes {
+            return Err((stored_bytes, max_stored_bytes));
+        }
+        Ok(())
+    }
+}
+
+/// Bytes used by the databases on this node, including the points in the caches
+/// and the wals of their own, the directories of which are walked
+pub fn stored_bytes(engine: &EngineRef, databases: &[String]) -> u64 {
+    databases
+        .iter()
+        .map(|db| match engine.database_usage(db) {
+            Ok(usage) => usage.total(),
+            Err(e) => {
+                warn!("Failed to measure the bytes stored by {}: {}", db, e);
+                0
+            }
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use config::TenantQuota;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_check_write() {
+        let config = QuotaConfig {
+            tenants: BTreeMap::from([(
+                "t1".to_string(),
+                TenantQuota {
+                    max_stored_bytes: 100,
+                },
+            )]),
+        };
+        let quotas = TenantQuotas::new(&config);
+        let measured = Arc::new(AtomicUsize::new(0));
+        let measure = |bytes: u64| {
+            let measured = measured.clone();
+            move || {
+                measured.fetch_add(1, Ordering::Relaxed);
+                bytes
+            }
+        };
+
+        // Unlimited tenants are not measured
+        assert!(quotas.check_write("t2", measure(1000)).await.is_ok());
+        assert_eq!(measured.load(Ordering::Relaxed), 0);
+
+        assert!(quotas.check_write("t1", measure(50)).await.is_ok());
+        // Measured again only once expired
+        assert!(quotas.check_write("t1", measure(150)).await.is_ok());
+        assert_eq!(measured.load(Ordering::Relaxed), 1);
+
+        quotas.stored.lock().clear();
+        assert_eq!(
+            quotas.check_write("t1", measure(150)).await,
+            Err((150, 100))
+        );
+    }
+}
@@ -0,0 +1,212 @@
+//! Token buckets of the requests of each user and of each tenant, a request is
+//! admitted only if all of the buckets it is limited by have the tokens of it.
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use config::{RateLimitConfig, RateLimits};
+use parking_lot::{Mutex, RwLock};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Owner {
+    User(String),
+    Tenant(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Resource {
+    Queries,
+    WritePoints,
+    WriteBytes,
+}
+
+impl Resource {
+    fn rate(&self, limits: &RateLimits) -> u64 {
+        match self {
+            Resource::Queries => limits.queries_per_sec,
+            Resource::WritePoints => limits.write_points_per_sec,
+            Resource::WriteBytes => limits.write_bytes_per_sec,
+        }
+    }
+}
+
+/// Tokens refilled at the rate per second, up to a second of them are kept for a burst
+#[derive(Debug)]
+struct TokenBucket {
+    rate: u64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: u64, now: Instant) -> Self {
+        Self {
+            rate,
+            tokens: rate as f64,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate as f64).min(self.rate as f64);
+        self.updated = now;
+    }
+
+    /// Time until the tokens are available, a request larger than the burst waits for
+    /// a full bucket and leaves it in debt
+    fn wait(&self, tokens: u64) -> Duration {
+        let needed = tokens.min(self.rate) as f64;
+        if self.tokens >= needed {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((needed - self.tokens) / self.rate as f64)
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    config: RwLock<RateLimitConfig>,
+    buckets: Mutex<HashMap<(Owner, Resource), TokenBucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        Self {
+            config: RwLock::new(config.clone()),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The buckets of the changed rates are filled again
+    pub fn update(&self, config: &RateLimitConfig) {
+        *self.config.write() = config.clone();
+    }
+
+    /// Time to wait before retrying if the query is rejected
+    pub fn check_query(&self, user: &str, tenant: &str) -> Result<(), Duration> {
+        self.acquire(user, tenant, &[(Resource::Queries, 1)])
+    }
+
+    /// Time to wait before retrying if the write is rejected
+    pub fn check_write(
+        &self,
+        user: &str,
+        tenant: &str,
+        points: u64,
+        bytes: u64,
+    ) -> Result<(), Duration> {
+        self.acquire(
+            user,
+            tenant,
+            &[
+                (Resource::WritePoints, points),
+                (Resource::WriteBytes, bytes),
+            ],
+        )
+    }
+
+    fn acquire(
+        &self,
+        user: &str,
+        tenant: &str,
+        requests: &[(Resource, u64)],
+    ) -> Result<(), Duration> {
+        let (user_limits, tenant_limits) = {
+            let config = self.config.read();
+            (config.user(user), config.tenant(tenant))
+        };
+        let owners = [
+            (Owner::User(user.to_string()), user_limits),
+            (Owner::Tenant(tenant.to_string()), tenant_limits),
+        ];
+
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock();
+        let mut acquired = vec![];
+        let mut wait = Duration::ZERO;
+        for (owner, limits) in &owners {
+            for &(resource, tokens) in requests {
+                let rate = resource.rate(limits);
+                if rate == 0 {
+                    continue;
+                }
+                let key = (owner.clone(), resource);
+                let bucket = buckets
+                    .entry(key.clone())
+                    .or_insert_with(|| TokenBucket::new(rate, now));
+                if bucket.rate != rate {
+                    *bucket = TokenBucket::new(rate, now);
+                }
+                bucket.refill(now);
+                wait = wait.max(bucket.wait(tokens));
+                acquired.push((key, tokens));
+            }
+        }
+
+        // Nothing is taken from any of the buckets if the request is rejected
+        if !wait.is_zero() {
+            return Err(wait);
+        }
+        for (key, tokens) in acquired {
+            if let Some(bucket) = buckets.get_mut(&key) {
+                bucket.tokens -= tokens as f64;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new(10, now);
+        assert!(bucket.wait(10).is_zero());
+        bucket.tokens -= 10.0;
+        assert_eq!(bucket.wait(5), Duration::from_millis(500));
+
+        bucket.refill(now + Duration::from_millis(500));
+        assert!(bucket.wait(5).is_zero());
+        // Up to a second of the tokens are kept
+        bucket.refill(now + Duration::from_secs(10));
+        assert_eq!(bucket.tokens, 10.0);
+        // Larger than the burst, admitted by a full bucket
+        assert!(bucket.wait(100).is_zero());
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        let mut config = RateLimitConfig::default();
+        config.default_user.queries_per_sec = 2;
+        config.tenants.insert(
+            "cnosdb".to_string(),
+            RateLimits {
+                write_points_per_sec: 100,
+                ..Default::default()
+            },
+        );
+        let limiter = RateLimiter::new(&config);
+
+        assert!(limiter.check_query("a", "cnosdb").is_ok());
+        assert!(limiter.check_query("a", "cnosdb").is_ok());
+        let wait = limiter.check_query("a", "cnosdb").unwrap_err();
+        assert!(wait > Duration::ZERO && wait <= Duration::from_millis(500));
+        // Each user has its own bucket
+        assert!(limiter.check_query("b", "cnosdb").is_ok());
+
+        // The tenant is shared by the users
+        assert!(limiter.check_write("a", "cnosdb", 60, 1024).is_ok());
+        assert!(limiter.check_write("b", "cnosdb", 60, 1024).is_err());
+        assert!(limiter.check_write("b", "other", 60, 1024).is_ok());
+
+        config.default_user.queries_per_sec = 0;
+        limiter.update(&config);
+        for _ in 0..10 {
+            assert!(limiter.check_query("a", "cnosdb").is_ok());
+        }
+    }
+}
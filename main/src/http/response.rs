@@ -1,4 +1,7 @@
+use coordinator::writer::WriteAck;
+use line_protocol::LineError;
 use serde::Serialize;
+use spi::server::dbms::{ConflictResolution, FieldConflict, TimestampOutOfWindow, WrittenPoints};
 use warp::http::header::HeaderMap;
 use warp::http::HeaderValue;
 use warp::http::StatusCode;
@@ -92,6 +95,82 @@ impl ResponseBuilder {
     }
 }
 
+/// Body of the response of a write
+#[derive(Debug, Serialize)]
+pub struct WriteResponse {
+    #[serde(flatten)]
+    pub ack: WriteAck,
+    /// The fields of the points the types of which differ from the columns
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub conflicts: Vec<FieldConflict>,
+    /// The points the timestamps of which are out of the window allowed
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub out_of_window: Vec<TimestampOutOfWindow>,
+    /// The lines not written, in the order of the numbers
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub failed_lines: Vec<FailedLine>,
+}
+
+impl WriteResponse {
+    /// The response of the points written of the lines of the numbers, and the
+    /// lines failed to be parsed
+    pub fn new(
+        ack: WriteAck,
+        written: WrittenPoints,
+        numbers: &[usize],
+        mut failed_lines: Vec<FailedLine>,
+    ) -> Self {
+        let line = |point: usize| numbers.get(point).copied().unwrap_or_default();
+        for e in written.conflicts.iter() {
+            if e.resolution == ConflictResolution::Rejected {
+                failed_lines.push(FailedLine {
+                    line: line(e.point),
+                    reason: format!(
+                        "field {} of type {} conflicts with the column of type {} in table {}",
+                        e.field, e.value_type, e.column_type, e.table
+                    ),
+                });
+            }
+        }
+        for e in written.out_of_window.iter() {
+            if e.clamped.is_none() {
+                failed_lines.push(FailedLine {
+                    line: line(e.point),
+                    reason: format!(
+                        "timestamp {} is out of the window allowed by the database",
+                        e.timestamp
+                    ),
+                });
+            }
+        }
+        failed_lines.sort_by_key(|e| e.line);
+
+        Self {
+            ack,
+            conflicts: written.conflicts,
+            out_of_window: written.out_of_window,
+            failed_lines,
+        }
+    }
+}
+
+/// A line of a write not written
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FailedLine {
+    /// Number of the line, from 1
+    pub line: usize,
+    pub reason: String,
+}
+
+impl From<LineError> for FailedLine {
+    fn from(e: LineError) -> Self {
+        Self {
+            line: e.line,
+            reason: e.error.to_string(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -128,4 +207,42 @@ mod tests {
 
         assert_eq!(content_type, HeaderValue::from_static(APPLICATION_JSON));
     }
+
+    #[test]
+    fn test_write_response() {
+        use models::consistency_level::ConsistencyLevel;
+        use models::schema::ColumnType;
+        use models::ValueType;
+
+        let written = WrittenPoints {
+            points: vec![],
+            conflicts: vec![FieldConflict {
+                point: 1,
+                table: "cpu".to_string(),
+                field: "usage".to_string(),
+                column_type: ColumnType::Field(ValueType::Float),
+                value_type: ValueType::String,
+                resolution: ConflictResolution::Rejected,
+            }],
+            out_of_window: vec![TimestampOutOfWindow {
+                point: 0,
+                table: "cpu".to_string(),
+                timestamp: 1,
+                clamped: Some(2),
+            }],
+        };
+        let parse_failure = FailedLine {
+            line: 2,
+            reason: "invalid".to_string(),
+        };
+        let resp = WriteResponse::new(
+            WriteAck::standalone(ConsistencyLevel::Quorum),
+            written,
+            &[1, 3],
+            vec![parse_failure],
+        );
+        let lines = resp.failed_lines.iter().map(|e| e.line).collect::<Vec<_>>();
+        assert_eq!(lines, vec![2, 3]);
+        assert_eq!(resp.out_of_window.len(), 1);
+    }
 }
@@ -1,48 +1,25 @@
 use super::Error as HttpError;
-use datafusion::arrow::csv::writer::WriterBuilder;
 use datafusion::arrow::error::Result as ArrowResult;
-use datafusion::arrow::json::{ArrayWriter, LineDelimitedWriter};
 use datafusion::arrow::record_batch::RecordBatch;
-use datafusion::arrow::util::pretty::pretty_format_batches;
 use spi::query::execution::Output;
 use spi::service::protocol::QueryHandle;
+use spi::service::serializer::{
+    ArrowIpcSerializer, CsvSerializer, JsonSerializer, MessagePackSerializer, NdJsonSerializer,
+    RecordBatchSerializer, TableSerializer,
+};
+use std::cmp::Ordering;
 use std::str::FromStr;
 use warp::reply::Response;
 
 use crate::http::response::ResponseBuilder;
 
 use http_protocol::header::{
-    APPLICATION_CSV, APPLICATION_JSON, APPLICATION_NDJSON, APPLICATION_PREFIX, APPLICATION_STAR,
-    APPLICATION_TABLE, APPLICATION_TSV, CONTENT_TYPE, STAR_STAR,
+    APPLICATION_ARROW, APPLICATION_CSV, APPLICATION_JSON, APPLICATION_MSGPACK, APPLICATION_NDJSON,
+    APPLICATION_PREFIX, APPLICATION_STAR, APPLICATION_TABLE, APPLICATION_TSV, CONTENT_TYPE,
+    STAR_STAR,
 };
 use http_protocol::status_code::OK;
 
-macro_rules! batches_to_json {
-    ($WRITER: ident, $batches: expr) => {{
-        let mut bytes = vec![];
-        {
-            let mut writer = $WRITER::new(&mut bytes);
-            writer.write_batches($batches)?;
-            writer.finish()?;
-        }
-        Ok(bytes)
-    }};
-}
-
-fn batches_with_sep(batches: &[RecordBatch], delimiter: u8) -> ArrowResult<Vec<u8>> {
-    let mut bytes = vec![];
-    {
-        let builder = WriterBuilder::new()
-            .has_headers(true)
-            .with_delimiter(delimiter);
-        let mut writer = builder.build(&mut bytes);
-        for batch in batches {
-            writer.write(batch)?;
-        }
-    }
-    Ok(bytes)
-}
-
 /// Allow records to be printed in different formats
 #[derive(Debug, PartialEq, Eq, clap::ArgEnum, Clone)]
 pub enum ResultFormat {
@@ -51,6 +28,8 @@ pub enum ResultFormat {
     Json,
     NdJson,
     Table,
+    Arrow,
+    Msgpack,
 }
 
 impl ResultFormat {
@@ -61,24 +40,41 @@ impl ResultFormat {
             Self::Json => APPLICATION_JSON,
             Self::NdJson => APPLICATION_NDJSON,
             Self::Table => APPLICATION_TABLE,
+            Self::Arrow => APPLICATION_ARROW,
+            Self::Msgpack => APPLICATION_MSGPACK,
         }
     }
 
-    pub fn format_batches(&self, batches: &[RecordBatch]) -> ArrowResult<Vec<u8>> {
-        if batches.is_empty() {
-            return Ok(Vec::new());
-        }
+    fn serializer(&self) -> Box<dyn RecordBatchSerializer> {
         match self {
-            Self::Csv => batches_with_sep(batches, b','),
-            Self::Tsv => batches_with_sep(batches, b'\t'),
-            Self::Json => batches_to_json!(ArrayWriter, batches),
-            Self::NdJson => {
-                batches_to_json!(LineDelimitedWriter, batches)
+            Self::Csv => Box::new(CsvSerializer::new(b',')),
+            Self::Tsv => Box::new(CsvSerializer::new(b'\t')),
+            Self::Json => Box::new(JsonSerializer),
+            Self::NdJson => Box::new(NdJsonSerializer),
+            Self::Table => Box::new(TableSerializer),
+            Self::Arrow => Box::new(ArrowIpcSerializer),
+            Self::Msgpack => Box::new(MessagePackSerializer),
+        }
+    }
+
+    /// The `format` parameter takes precedence over the `Accept` header
+    pub fn negotiate(format: Option<&str>, accept: &str) -> Result<Self, HttpError> {
+        match format {
+            Some(fmt) => {
+                ResultFormat::from_str(fmt).map_err(|reason| HttpError::InvalidParameter { reason })
             }
-            Self::Table => Ok(pretty_format_batches(batches)?.to_string().into_bytes()),
+            None => ResultFormat::try_from(accept),
         }
     }
 
+    /// The empty result is an empty body, except for msgpack, which is an empty array
+    pub fn format_batches(&self, batches: &[RecordBatch]) -> ArrowResult<Vec<u8>> {
+        if batches.is_empty() && *self != Self::Msgpack {
+            return Ok(Vec::new());
+        }
+        self.serializer().serialize(batches)
+    }
+
     pub fn wrap_batches_to_response(&self, batches: &[RecordBatch]) -> Result<Response, HttpError> {
         let result = self
             .format_batches(batches)
@@ -92,24 +88,51 @@ impl ResultFormat {
 
         Ok(resp)
     }
+
+    fn from_media_type(media_type: &str) -> Option<Self> {
+        if media_type.is_empty() || media_type == APPLICATION_STAR || media_type == STAR_STAR {
+            return Some(ResultFormat::Csv);
+        }
+
+        if media_type == APPLICATION_ARROW {
+            return Some(ResultFormat::Arrow);
+        }
+
+        media_type
+            .strip_prefix(APPLICATION_PREFIX)
+            .and_then(|fmt| ResultFormat::from_str(fmt).ok())
+    }
 }
 
 impl TryFrom<&str> for ResultFormat {
     type Error = HttpError;
 
+    /// Pick the supported media type with the highest quality value,
+    /// e.g. `application/vnd.apache.arrow.stream, application/json;q=0.9`
     fn try_from(s: &str) -> Result<Self, Self::Error> {
-        if s.is_empty() || s == APPLICATION_STAR || s == STAR_STAR {
-            return Ok(ResultFormat::Csv);
-        }
-
-        if let Some(fmt) = s.strip_prefix(APPLICATION_PREFIX) {
-            return ResultFormat::from_str(fmt)
-                .map_err(|reason| HttpError::InvalidHeader { reason });
-        }
-
-        Err(HttpError::InvalidHeader {
-            reason: format!("accept type not support: {}", s),
-        })
+        let mut media_ranges: Vec<(&str, f32)> = s
+            .split(',')
+            .map(|media_range| {
+                let mut parts = media_range.split(';');
+                let media_type = parts.next().unwrap_or_default().trim();
+                let quality = parts
+                    .filter_map(|p| p.trim().strip_prefix("q="))
+                    .find_map(|q| q.parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                (media_type, quality)
+            })
+            .filter(|(_, quality)| *quality > 0.0)
+            .collect();
+
+        // stable sort, keep the order of the header for equal quality values
+        media_ranges.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+
+        media_ranges
+            .iter()
+            .find_map(|(media_type, _)| ResultFormat::from_media_type(media_type))
+            .ok_or_else(|| HttpError::InvalidHeader {
+                reason: format!("accept type not support: {}", s),
+            })
     }
 }
 
@@ -143,76 +166,55 @@ pub async fn fetch_record_batches(res: &mut QueryHandle) -> ArrowResult<Vec<Reco
 #[cfg(test)]
 mod tests {
     use super::*;
-    use datafusion::arrow::array::Int32Array;
-    use datafusion::arrow::datatypes::{DataType, Field, Schema};
-    use datafusion::from_slice::FromSlice;
-    use std::sync::Arc;
 
     #[test]
-    fn test_format_batches_with_sep() {
-        let batches = vec![];
-        assert_eq!("".as_bytes(), batches_with_sep(&batches, b',').unwrap());
-
-        let schema = Arc::new(Schema::new(vec![
-            Field::new("a", DataType::Int32, false),
-            Field::new("b", DataType::Int32, false),
-            Field::new("c", DataType::Int32, false),
-        ]));
-
-        let batch = RecordBatch::try_new(
-            schema,
-            vec![
-                Arc::new(Int32Array::from_slice([1, 2, 3])),
-                Arc::new(Int32Array::from_slice([4, 5, 6])),
-                Arc::new(Int32Array::from_slice([7, 8, 9])),
-            ],
-        )
-        .unwrap();
-
-        let batches = vec![batch];
-        let r = batches_with_sep(&batches, b',').unwrap();
-        assert_eq!("a,b,c\n1,4,7\n2,5,8\n3,6,9\n".as_bytes(), r);
+    fn test_accept_to_result_format() {
+        assert_eq!(ResultFormat::try_from("").unwrap(), ResultFormat::Csv);
+        assert_eq!(ResultFormat::try_from("*/*").unwrap(), ResultFormat::Csv);
+        assert_eq!(
+            ResultFormat::try_from(APPLICATION_NDJSON).unwrap(),
+            ResultFormat::NdJson
+        );
+        assert_eq!(
+            ResultFormat::try_from(APPLICATION_ARROW).unwrap(),
+            ResultFormat::Arrow
+        );
+        assert_eq!(
+            ResultFormat::try_from("text/html, application/json;q=0.5, application/tsv;q=0.8")
+                .unwrap(),
+            ResultFormat::Tsv
+        );
+        assert_eq!(
+            ResultFormat::try_from("application/csv;q=0, application/json").unwrap(),
+            ResultFormat::Json
+        );
+        assert_eq!(
+            ResultFormat::try_from(APPLICATION_MSGPACK).unwrap(),
+            ResultFormat::Msgpack
+        );
+        assert!(ResultFormat::try_from("text/html").is_err());
     }
 
     #[test]
-    fn test_format_batches_to_json_empty() -> ArrowResult<()> {
-        let batches = vec![];
-        let r: ArrowResult<Vec<u8>> = batches_to_json!(ArrayWriter, &batches);
-        assert_eq!("".as_bytes(), r?);
-
-        let r: ArrowResult<Vec<u8>> = batches_to_json!(LineDelimitedWriter, &batches);
-        assert_eq!("".as_bytes(), r?);
-
-        let schema = Arc::new(Schema::new(vec![
-            Field::new("a", DataType::Int32, false),
-            Field::new("b", DataType::Int32, false),
-            Field::new("c", DataType::Int32, false),
-        ]));
-
-        let batch = RecordBatch::try_new(
-            schema,
-            vec![
-                Arc::new(Int32Array::from_slice([1, 2, 3])),
-                Arc::new(Int32Array::from_slice([4, 5, 6])),
-                Arc::new(Int32Array::from_slice([7, 8, 9])),
-            ],
-        )
-        .unwrap();
-
-        let batches = vec![batch];
-        let r: ArrowResult<Vec<u8>> = batches_to_json!(ArrayWriter, &batches);
+    fn test_format_param_overrides_accept() {
+        assert_eq!(
+            ResultFormat::negotiate(Some("arrow"), APPLICATION_JSON).unwrap(),
+            ResultFormat::Arrow
+        );
         assert_eq!(
-            "[{\"a\":1,\"b\":4,\"c\":7},{\"a\":2,\"b\":5,\"c\":8},{\"a\":3,\"b\":6,\"c\":9}]"
-                .as_bytes(),
-            r?
+            ResultFormat::negotiate(None, APPLICATION_JSON).unwrap(),
+            ResultFormat::Json
         );
+        assert!(ResultFormat::negotiate(Some("xml"), APPLICATION_JSON).is_err());
+    }
 
-        let r: ArrowResult<Vec<u8>> = batches_to_json!(LineDelimitedWriter, &batches);
+    #[test]
+    fn test_format_empty_batches() {
+        assert!(ResultFormat::Csv.format_batches(&[]).unwrap().is_empty());
+        assert!(ResultFormat::Json.format_batches(&[]).unwrap().is_empty());
         assert_eq!(
-            "{\"a\":1,\"b\":4,\"c\":7}\n{\"a\":2,\"b\":5,\"c\":8}\n{\"a\":3,\"b\":6,\"c\":9}\n"
-                .as_bytes(),
-            r?
+            ResultFormat::Msgpack.format_batches(&[]).unwrap(),
+            vec![0x90]
         );
-        Ok(())
     }
 }
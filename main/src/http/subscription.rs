@@ -0,0 +1,465 @@
+//! Streaming query subscriptions over websocket.
+//!
+//! The client sends `SUBSCRIBE SELECT ...` as the first text message, then the
+//! query is evaluated again each time points are written into the database of
+//! the session, and rows which have not been pushed are sent back as json text
+//! messages. Rows written late are pushed if their time is within [`LATENESS`]
+//! of the latest row pushed.
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::time::Duration;
+
+use chrono::Local;
+use datafusion::arrow::array::{BooleanArray, TimestampNanosecondArray};
+use datafusion::arrow::compute::{filter_record_batch, max};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::arrow::util::display::array_value_to_string;
+use datafusion::sql::sqlparser::ast::{
+    Expr, Function, FunctionArg, FunctionArgExpr, Ident, ObjectName, Value,
+};
+use futures::{SinkExt, StreamExt};
+use query::sql::logical::visitor::{visit_statement, VisitorMut};
+use query::sql::parser::ExtParser;
+use snafu::ResultExt;
+use spi::query::ast::ExtStatement;
+use spi::server::dbms::DBMSRef;
+use spi::service::protocol::{Context, Query};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::Receiver;
+use trace::{debug, info};
+use tskv::engine::{EngineRef, WriteEvent};
+use warp::ws::{Message, WebSocket};
+
+use super::result_format::{fetch_record_batches, ResultFormat};
+use super::{Error as HttpError, QuerySnafu};
+
+const SUBSCRIBE: &str = "SUBSCRIBE";
+const TIME_COLUMN: &str = "time";
+/// Evaluate the query periodically even if no write event arrives,
+/// e.g. the points are written by another node
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// Points written at most this long (ns) after the latest row pushed are still pushed
+const LATENESS: i64 = 60_000_000_000;
+
+pub async fn handle_subscription(
+    mut socket: WebSocket,
+    context: Context,
+    dbms: DBMSRef,
+    kv_inst: EngineRef,
+) {
+    let sql = match recv_subscribe_statement(&mut socket).await {
+        Ok(Some(sql)) => sql,
+        Ok(None) => return,
+        Err(e) => {
+            send_error(&mut socket, &e).await;
+            return;
+        }
+    };
+
+    info!(
+        "Subscription started on database {}: {}",
+        context.database(),
+        sql
+    );
+
+    let mut subscription =
+        match Subscription::try_new(context, &sql, Local::now().timestamp_nanos()) {
+            Ok(subscription) => subscription,
+            Err(e) => {
+                send_error(&mut socket, &e).await;
+                return;
+            }
+        };
+    let mut events = Some(kv_inst.subscribe_writes());
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+    loop {
+        tokio::select! {
+            msg = socket.next() => match msg {
+                Some(Ok(msg)) if msg.is_close() => break,
+                Some(Ok(_)) => continue,
+                Some(Err(_)) | None => break,
+            },
+            changed = wait_for_write(&mut events, subscription.context.database()) => {
+                if !changed {
+                    continue;
+                }
+            },
+            _ = interval.tick() => {},
+        }
+
+        let batches = match subscription.poll(&dbms).await {
+            Ok(batches) => batches,
+            Err(e) => {
+                send_error(&mut socket, &e).await;
+                break;
+            }
+        };
+        if batches.is_empty() {
+            continue;
+        }
+
+        let payload = match ResultFormat::Json.format_batches(&batches) {
+            Ok(payload) => payload,
+            Err(e) => {
+                let e = HttpError::FetchResult {
+                    reason: e.to_string(),
+                };
+                send_error(&mut socket, &e).await;
+                break;
+            }
+        };
+        let text = String::from_utf8_lossy(&payload).to_string();
+        if socket.send(Message::text(text)).await.is_err() {
+            break;
+        }
+    }
+
+    let _ = socket.close().await;
+    info!("Subscription stopped: {}", sql);
+}
+
+/// Returns the query of the first text message, or None if the socket is closed before it
+async fn recv_subscribe_statement(socket: &mut WebSocket) -> Result<Option<String>, HttpError> {
+    while let Some(msg) = socket.next().await {
+        let msg = match msg {
+            Ok(msg) => msg,
+            Err(_) => return Ok(None),
+        };
+        if msg.is_close() {
+            return Ok(None);
+        }
+        if let Ok(text) = msg.to_str() {
+            return parse_subscribe_statement(text).map(Some);
+        }
+    }
+
+    Ok(None)
+}
+
+/// Strips the leading `SUBSCRIBE` keyword, the rest must be a `SELECT` statement
+fn parse_subscribe_statement(text: &str) -> Result<String, HttpError> {
+    let invalid = || HttpError::InvalidParameter {
+        reason: format!("expected 'SUBSCRIBE SELECT ...', but found '{}'", text),
+    };
+
+    let text = text.trim().trim_end_matches(';').trim_end();
+    let keyword = text.get(..SUBSCRIBE.len()).ok_or_else(invalid)?;
+    if !keyword.eq_ignore_ascii_case(SUBSCRIBE) {
+        return Err(invalid());
+    }
+
+    let query = &text[SUBSCRIBE.len()..];
+    if !query.starts_with(char::is_whitespace) {
+        return Err(invalid());
+    }
+    let query = query.trim_start();
+    let is_select = query
+        .get(.."SELECT".len())
+        .map(|e| e.eq_ignore_ascii_case("SELECT"))
+        .unwrap_or(false);
+    if !is_select {
+        return Err(invalid());
+    }
+
+    Ok(query.to_string())
+}
+
+/// Waits until points are written into `database`, returns false if only
+/// events of other databases arrived.
+///
+/// If the engine does not publish write events the subscription falls back to polling.
+async fn wait_for_write(events: &mut Option<Receiver<WriteEvent>>, database: &str) -> bool {
+    let receiver = match events {
+        Some(receiver) => receiver,
+        None => return futures::future::pending().await,
+    };
+
+    let mut changed = match receiver.recv().await {
+        Ok(event) => event.database == database,
+        // Some events are missed, assume the database is changed
+        Err(RecvError::Lagged(_)) => true,
+        Err(RecvError::Closed) => {
+            *events = None;
+            return false;
+        }
+    };
+
+    // Evaluate the query once for a burst of writes
+    loop {
+        match receiver.try_recv() {
+            Ok(event) => changed |= event.database == database,
+            Err(tokio::sync::broadcast::error::TryRecvError::Lagged(_)) => changed = true,
+            Err(_) => break,
+        }
+    }
+
+    changed
+}
+
+struct Subscription {
+    context: Context,
+    query: String,
+    /// Time of subscribing (ns), rows at or before it are not pushed
+    start: i64,
+    /// The latest time (ns) of the rows pushed
+    watermark: i64,
+    /// Hashes of the rows pushed within [`LATENESS`] before the watermark, by time
+    pushed: BTreeMap<i64, HashSet<u64>>,
+}
+
+impl Subscription {
+    /// `now()` in the query is pinned to `start`, otherwise
+    /// points written shortly before an evaluation would be filtered out
+    fn try_new(context: Context, query: &str, start: i64) -> Result<Self, HttpError> {
+        Ok(Self {
+            context,
+            query: pin_now(query, start)?,
+            start,
+            watermark: start,
+            pushed: BTreeMap::new(),
+        })
+    }
+
+    /// Rows written late, or at the same time as the watermark, are evaluated
+    /// again until they are older than [`LATENESS`]
+    fn incremental_sql(&self) -> String {
+        let lower = (self.start + 1).max(self.watermark - LATENESS);
+        format!(
+            "SELECT * FROM ({}) AS subscription WHERE {} >= CAST({} AS TIMESTAMP) ORDER BY {}",
+            self.query, TIME_COLUMN, lower, TIME_COLUMN
+        )
+    }
+
+    async fn poll(&mut self, dbms: &DBMSRef) -> Result<Vec<RecordBatch>, HttpError> {
+        let query = Query::new(self.context.clone(), self.incremental_sql());
+        debug!("Evaluate subscription: {}", query.content());
+
+        let mut result = dbms.execute(&query).await.context(QuerySnafu)?;
+        let batches =
+            fetch_record_batches(&mut result)
+                .await
+                .map_err(|e| HttpError::FetchResult {
+                    reason: e.to_string(),
+                })?;
+
+        let mut result = vec![];
+        for batch in batches.iter().filter(|e| e.num_rows() > 0) {
+            let batch = self.filter_pushed(batch)?;
+            if batch.num_rows() > 0 {
+                result.push(batch);
+            }
+        }
+        if let Some(watermark) = max_time(&result)? {
+            self.watermark = self.watermark.max(watermark);
+        }
+        self.pushed = self.pushed.split_off(&(self.watermark - LATENESS));
+
+        Ok(result)
+    }
+
+    /// Keeps the rows which have not been pushed, and remembers them as pushed
+    fn filter_pushed(&mut self, batch: &RecordBatch) -> Result<RecordBatch, HttpError> {
+        let times = time_column(batch)?;
+        let mut keep = Vec::with_capacity(batch.num_rows());
+        for row in 0..batch.num_rows() {
+            let mut hasher = DefaultHasher::new();
+            for column in batch.columns() {
+                array_value_to_string(column, row)
+                    .map_err(|e| HttpError::FetchResult {
+                        reason: e.to_string(),
+                    })?
+                    .hash(&mut hasher);
+            }
+            keep.push(
+                self.pushed
+                    .entry(times.value(row))
+                    .or_default()
+                    .insert(hasher.finish()),
+            );
+        }
+
+        filter_record_batch(batch, &BooleanArray::from(keep)).map_err(|e| HttpError::FetchResult {
+            reason: e.to_string(),
+        })
+    }
+}
+
+/// Replaces `now()` in the query by the time of subscribing
+fn pin_now(query: &str, now: i64) -> Result<String, HttpError> {
+    let invalid = |reason: String| HttpError::InvalidParameter { reason };
+
+    let mut statements = ExtParser::parse_sql(query).map_err(|e| invalid(e.to_string()))?;
+    let mut statement = match (statements.pop_front(), statements.is_empty()) {
+        (Some(ExtStatement::SqlStatement(statement)), true) => *statement,
+        _ => return Err(invalid(format!("expected one select statement: {}", query))),
+    };
+    visit_statement(&mut statement, &mut PinNow(now)).map_err(|e| invalid(e.to_string()))?;
+
+    Ok(statement.to_string())
+}
+
+/// Rewrites `now()` to `to_timestamp(<ns>)`
+struct PinNow(i64);
+
+impl VisitorMut for PinNow {
+    fn post_visit_expr(&mut self, expr: &mut Expr) -> spi::query::logical_planner::Result<()> {
+        let is_now = match expr {
+            Expr::Function(function) => match function.name.0.as_slice() {
+                [name] => function.args.is_empty() && name.value.eq_ignore_ascii_case("now"),
+                _ => false,
+            },
+            _ => false,
+        };
+        if is_now {
+            *expr = Expr::Function(Function {
+                name: ObjectName(vec![Ident::new("to_timestamp")]),
+                args: vec![FunctionArg::Unnamed(FunctionArgExpr::Expr(Expr::Value(
+                    Value::Number(self.0.to_string(), false),
+                )))],
+                over: None,
+                distinct: false,
+                special: false,
+            });
+        }
+        Ok(())
+    }
+}
+
+fn time_column(batch: &RecordBatch) -> Result<&TimestampNanosecondArray, HttpError> {
+    batch
+        .column_by_name(TIME_COLUMN)
+        .and_then(|e| e.as_any().downcast_ref::<TimestampNanosecondArray>())
+        .ok_or_else(|| HttpError::InvalidParameter {
+            reason: format!(
+                "subscription query must select the '{}' column",
+                TIME_COLUMN
+            ),
+        })
+}
+
+fn max_time(batches: &[RecordBatch]) -> Result<Option<i64>, HttpError> {
+    let mut result = None;
+    for batch in batches {
+        result = result.max(max(time_column(batch)?));
+    }
+    Ok(result)
+}
+
+async fn send_error(socket: &mut WebSocket, err: &HttpError) {
+    let text = serde_json::json!({ "error": err.to_string() }).to_string();
+    let _ = socket.send(Message::text(text)).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use datafusion::arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+    use spi::service::protocol::{ContextBuilder, UserInfo};
+
+    use super::*;
+
+    #[test]
+    fn test_parse_subscribe_statement() {
+        assert_eq!(
+            parse_subscribe_statement("subscribe select * from cpu where time > now();").unwrap(),
+            "select * from cpu where time > now()"
+        );
+        assert_eq!(
+            parse_subscribe_statement("  SUBSCRIBE\n  SELECT 1").unwrap(),
+            "SELECT 1"
+        );
+        assert!(parse_subscribe_statement("SELECT * FROM cpu").is_err());
+        assert!(parse_subscribe_statement("SUBSCRIBESELECT 1").is_err());
+        assert!(parse_subscribe_statement("SUBSCRIBE DROP TABLE cpu").is_err());
+        assert!(parse_subscribe_statement("SUB").is_err());
+    }
+
+    #[test]
+    fn test_pin_now() {
+        assert_eq!(
+            pin_now("SELECT * FROM cpu WHERE time > NOW() - interval '1m'", 10).unwrap(),
+            "SELECT * FROM cpu WHERE time > to_timestamp(10) - INTERVAL '1m'"
+        );
+        // Not a call of now()
+        assert_eq!(
+            pin_now("SELECT 'now()', \"now()\" FROM cpu", 10).unwrap(),
+            "SELECT 'now()', \"now()\" FROM cpu"
+        );
+        assert_eq!(
+            pin_now(
+                "SELECT * FROM (SELECT * FROM cpu WHERE time > now()) AS t",
+                10
+            )
+            .unwrap(),
+            "SELECT * FROM (SELECT * FROM cpu WHERE time > to_timestamp(10)) AS t"
+        );
+        assert!(pin_now("SELECT 1; SELECT 2", 10).is_err());
+    }
+
+    #[test]
+    fn test_filter_pushed() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(
+                TIME_COLUMN,
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+            Field::new("a", DataType::Int64, false),
+        ]));
+        let batch = |times: Vec<i64>, values: Vec<i64>| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(TimestampNanosecondArray::from(times)),
+                    Arc::new(datafusion::arrow::array::Int64Array::from(values)),
+                ],
+            )
+            .unwrap()
+        };
+        let context = ContextBuilder::new(UserInfo {
+            user: "root".to_string(),
+            password: String::new(),
+        })
+        .build();
+        let mut subscription = Subscription::try_new(context, "SELECT 1", 0).unwrap();
+
+        let pushed = subscription
+            .filter_pushed(&batch(vec![5, 5], vec![1, 2]))
+            .unwrap();
+        assert_eq!(pushed.num_rows(), 2);
+        // The same rows are not pushed again, a row at the same time is
+        let pushed = subscription
+            .filter_pushed(&batch(vec![5, 5, 5], vec![1, 2, 3]))
+            .unwrap();
+        assert_eq!(pushed.num_rows(), 1);
+    }
+
+    #[test]
+    fn test_max_time() {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            TIME_COLUMN,
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            false,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![Arc::new(TimestampNanosecondArray::from(vec![3, 7, 5]))],
+        )
+        .unwrap();
+        assert_eq!(max_time(&[batch]).unwrap(), Some(7));
+        assert_eq!(max_time(&[]).unwrap(), None);
+
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int64, false)]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![Arc::new(datafusion::arrow::array::Int64Array::from(vec![
+                1,
+            ]))],
+        )
+        .unwrap();
+        assert!(max_time(&[batch]).is_err());
+    }
+}
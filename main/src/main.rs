@@ -1,15 +1,35 @@
 use clap::{Parser, Subcommand};
+use config::{ClusterConfig, Config, Settings};
+use coordinator::connection::{client_tls_config, NodeConnections};
+use coordinator::dc_replication::{DcReplication, StandbyCoordinator};
+use coordinator::hinted_handoff::{HintedHandoff, DEFAULT_REPLAY_INTERVAL};
+use coordinator::rebalance::Rebalancer;
+use coordinator::replica_sync::{ReplicaSync, DEFAULT_SYNC_INTERVAL};
+use coordinator::service::{ClusterCoordinator, CoordinatorRef, LocalCoordinator};
+use coordinator::sharding::PointRouter;
+use coordinator::writer::PointWriter;
+use meta::meta_client::{MetaClient, MetaClientRef, RemoteMetaClient};
+use models::meta_data::{NodeInfo, NodeStatus};
 use once_cell::sync::Lazy;
+use query::audit::{AuditLog, AuditLogRef};
 use query::instance::make_cnosdbms;
-use std::{net::SocketAddr, sync::Arc};
+use std::time::Duration;
+use std::{net::SocketAddr, path::Path, sync::Arc};
 use tokio::runtime::Runtime;
-use trace::{info, init_global_tracing};
+use tonic::transport::ClientTlsConfig;
+use trace::{
+    info, init_global_tracing_with_otlp, shutdown_global_tracing, LogFormat, OtlpExporter,
+};
+use tskv::engine::EngineRef;
 use tskv::TsKv;
+use utils::{MemoryBudget, MemoryPoolKind};
 mod http;
+mod reload;
 mod report;
 mod rpc;
 pub mod server;
 mod signal;
+mod tls;
 
 static VERSION: Lazy<String> = Lazy::new(|| {
     format!(
@@ -76,6 +96,20 @@ enum SubCommand {
     /// run cnosdb server
     #[clap(arg_required_else_help = false)]
     Run {},
+    /// upgrade the data files to the current format, the server must be stopped
+    #[clap(arg_required_else_help = false)]
+    UpgradeFiles {},
+    /// salvage the readable blocks of a damaged tsm file into a new file of the same
+    /// name, and report the time ranges lost, the server must be stopped
+    #[clap(arg_required_else_help = true)]
+    RepairFile {
+        /// path of the damaged tsm or delta file
+        #[clap(long)]
+        path: String,
+        /// directory of the repaired file, `repaired` beside the damaged file by default
+        #[clap(long)]
+        output_dir: Option<String>,
+    },
     // /// run tskv
     // #[clap(arg_required_else_help = true)]
     // Tskv { debug: String },
@@ -84,15 +118,44 @@ enum SubCommand {
     // Query {},
 }
 
-use crate::http::http_service::HttpService;
+use crate::http::async_query::{AsyncQueries, ASYNC_RESULTS_DIR};
+use crate::http::health::HealthChecker;
+use crate::http::http_service::{HttpLimits, HttpService};
+use crate::reload::ConfigReloader;
 use crate::report::ReportService;
 use crate::rpc::grpc_service::GrpcService;
+use crate::tls::TlsCerts;
 use mem_allocator::Jemalloc;
-use metrics::{init_query_metrics_recorder, init_tskv_metrics_recorder};
+use metrics::{
+    init_query_metrics_recorder, init_replication_metrics_recorder, init_tskv_metrics_recorder,
+};
 
 #[global_allocator]
 static A: Jemalloc = Jemalloc;
 
+/// Interval of checking the changes of the meta service
+const META_WATCH_INTERVAL: Duration = Duration::from_secs(1);
+/// File of the settings changed by `ALTER SYSTEM SET`, in the storage directory
+const SETTINGS_OVERRIDES_FILE: &str = "settings_overrides.toml";
+
+/// The share of the queries is reserved, it is limited by the memory manager of the
+/// queries instead
+fn memory_budget(config: &Config) -> MemoryBudget {
+    let memory = &config.memory;
+    let budget = MemoryBudget::new(
+        memory.limit,
+        &[
+            (MemoryPoolKind::Memcache, memory.memcache_share),
+            (MemoryPoolKind::Cache, memory.cache_share),
+            (MemoryPoolKind::Query, memory.query_share),
+        ],
+    );
+    budget
+        .pool(MemoryPoolKind::Query)
+        .set_used(memory.query_limit());
+    budget
+}
+
 /// To run cnosdb-cli:
 ///
 /// ```bash
@@ -108,11 +171,39 @@ fn main() -> Result<(), std::io::Error> {
         cli.grpc_host, cli.http_host, cli.cpu, cli.memory, cli.config, cli.subcmd
     );
     let global_config = config::get_config(cli.config.as_str());
-    let mut _trace_guard = init_global_tracing(
-        &global_config.log.path,
-        "tsdb.log",
-        &global_config.log.level,
-    );
+    // Settings changed by `ALTER SYSTEM SET` are applied over the configuration file
+    let settings = Settings::open(
+        &cli.config,
+        global_config.clone(),
+        Path::new(&global_config.storage.path).join(SETTINGS_OVERRIDES_FILE),
+    )
+    .expect("open settings");
+    let settings = Arc::new(settings);
+    let global_config = settings.config();
+    let otlp = global_config
+        .log
+        .otlp_endpoint
+        .as_ref()
+        .map(|endpoint| OtlpExporter {
+            service_name: "cnosdb".to_string(),
+            endpoint: endpoint.clone(),
+        });
+    let log_format = global_config
+        .log
+        .format
+        .parse::<LogFormat>()
+        .expect("Invalid log format");
+    let mut _trace_guard = {
+        // The spans are exported by the runtime
+        let _runtime = runtime.enter();
+        init_global_tracing_with_otlp(
+            &global_config.log.path,
+            "tsdb.log",
+            &global_config.log.level,
+            log_format,
+            otlp.as_ref(),
+        )
+    };
 
     let grpc_host = cli
         .grpc_host
@@ -125,32 +216,193 @@ fn main() -> Result<(), std::io::Error> {
 
     init_tskv_metrics_recorder();
     init_query_metrics_recorder();
+    init_replication_metrics_recorder();
+    metrics::build_info::set_build_info(
+        option_env!("CARGO_PKG_VERSION").unwrap_or("UNKNOWN"),
+        option_env!("GIT_HASH").unwrap_or("UNKNOWN"),
+    );
 
     runtime.clone().block_on(async move {
         match &cli.subcmd {
             SubCommand::Debug { debug: _ } => {
                 todo!()
             }
+            SubCommand::UpgradeFiles {} => {
+                let tskv_options = tskv::Options::from(&global_config);
+                match tskv::upgrade_files(&tskv_options) {
+                    Ok(report) => println!(
+                        "Upgraded {} of {} tsm files and {} of {} indexes",
+                        report.tsm_files_upgraded,
+                        report.tsm_files,
+                        report.indexes_upgraded,
+                        report.indexes
+                    ),
+                    Err(e) => {
+                        eprintln!("Failed to upgrade the files: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            SubCommand::RepairFile { path, output_dir } => {
+                let path = Path::new(path);
+                let output_dir = match output_dir {
+                    Some(dir) => Path::new(dir).to_path_buf(),
+                    None => path
+                        .parent()
+                        .unwrap_or_else(|| Path::new("."))
+                        .join("repaired"),
+                };
+                match tskv::repair_tsm_file(path, &output_dir) {
+                    Ok(report) => {
+                        println!(
+                            "{}",
+                            serde_json::to_string_pretty(&report).unwrap_or_default()
+                        );
+                        if !report.is_lossless() {
+                            eprintln!(
+                                "Salvaged {} of {} blocks to {}, replace the damaged file with it \
+                                 to drop the time ranges lost",
+                                report.blocks_salvaged,
+                                report.blocks,
+                                report.path.display()
+                            );
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to repair the file: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
             SubCommand::Run {} => {
+                // The admin is created by the password, and the data nodes connect to
+                // each other by it
+                if global_config.security.auth_enabled
+                    && global_config.security.admin_password.is_empty()
+                {
+                    eprintln!(
+                        "The password of the admin is not set while the authentication is \
+                         enabled: security.admin_password"
+                    );
+                    std::process::exit(1);
+                }
                 let tskv_options = tskv::Options::from(&global_config);
                 let query_options = tskv::Options::from(&global_config);
                 let kv_inst = Arc::new(TsKv::open(tskv_options, runtime).await.unwrap());
-                let dbms =
-                    Arc::new(make_cnosdbms(kv_inst.clone(), query_options).expect("make dbms"));
-                let http_service = Box::new(HttpService::new(
+                kv_inst.register_memory_budget(Arc::new(memory_budget(&global_config)));
+                // Points written are routed to the shards in a cluster
+                let (coord, rebalancer) = if global_config.cluster.is_standalone() {
+                    let coord: CoordinatorRef = Arc::new(LocalCoordinator::new(kv_inst.clone()));
+                    (coord, None)
+                } else {
+                    if global_config.cluster.meta_service_token.is_empty() {
+                        eprintln!(
+                            "The token of the meta service is not set: cluster.meta_service_token"
+                        );
+                        std::process::exit(1);
+                    }
+                    register_data_node(&global_config.cluster, grpc_host, http_host).await;
+                    let (coord, rebalancer) = start_cluster(&global_config, kv_inst.clone()).await;
+                    (coord, Some(rebalancer))
+                };
+                // Replicates the writes of the primary cluster in another datacenter, the
+                // writes of the clients are rejected until promoted
+                let replication = if global_config.dc_replication.is_enabled() {
+                    let replication = DcReplication::new(
+                        coord.clone(),
+                        &global_config.dc_replication,
+                        Path::new(&global_config.storage.path).join("dc_replication"),
+                    )
+                    .expect("open dc replication")
+                    .with_tls(client_tls(&global_config));
+                    Some(Arc::new(replication))
+                } else {
+                    None
+                };
+                let coord: CoordinatorRef = match &replication {
+                    Some(replication) => {
+                        Arc::new(StandbyCoordinator::new(coord, replication.clone()))
+                    }
+                    None => coord,
+                };
+                // Ddl statements and authentications are always queryable in memory
+                let audit_log: AuditLogRef = if global_config.audit.enabled {
+                    Arc::new(AuditLog::open(&global_config.audit.path).expect("open audit log"))
+                } else {
+                    Arc::new(AuditLog::memory())
+                };
+                let dbms = Arc::new(
+                    make_cnosdbms(
+                        coord.clone(),
+                        query_options,
+                        audit_log.clone(),
+                        settings.clone(),
+                    )
+                    .await
+                    .expect("make dbms"),
+                );
+                // Shared by the listeners, so that both serve the reloaded certificate
+                let tls =
+                    global_config.security.tls_config.as_ref().map(|config| {
+                        Arc::new(TlsCerts::open(config).expect("load tls certificate"))
+                    });
+                let http_limits = Arc::new(HttpLimits::new(&global_config));
+                let mut http_service = HttpService::new(
                     dbms.clone(),
-                    kv_inst.clone(),
+                    coord.clone(),
                     http_host,
-                    global_config.security.tls_config.clone(),
-                    global_config.query.query_sql_limit,
-                    global_config.query.write_sql_limit,
-                ));
-                let grpc_service = Box::new(GrpcService::new(
-                    dbms.clone(),
-                    kv_inst.clone(),
-                    grpc_host,
-                    global_config.security.tls_config.clone(),
+                    tls.clone(),
+                    http_limits.clone(),
+                )
+                .with_audit_log(audit_log)
+                .with_sessions(dbms.sessions())
+                .with_pprof(global_config.security.pprof_enabled)
+                .with_dump_dir(&global_config.query.dump_dir)
+                .with_health_checker(HealthChecker::new(
+                    coord.clone(),
+                    vec![
+                        (
+                            "storage".to_string(),
+                            global_config.storage.path.clone().into(),
+                        ),
+                        ("wal".to_string(), global_config.wal.path.clone().into()),
+                    ],
                 ));
+                if let Some(cors) = &global_config.security.cors {
+                    http_service = http_service.with_cors(cors);
+                }
+                if let Some(rebalancer) = rebalancer {
+                    http_service = http_service.with_rebalancer(rebalancer);
+                }
+                if let Some(replication) = replication {
+                    replication.clone().start();
+                    http_service = http_service.with_dc_replication(replication);
+                }
+                // Results of the asynchronous queries are spooled under the storage path
+                let async_queries = AsyncQueries::new(
+                    Path::new(&global_config.storage.path).join(ASYNC_RESULTS_DIR),
+                    Duration::from_secs(global_config.query.async_result_ttl_secs),
+                )
+                .expect("open async query results")
+                .with_limits(
+                    global_config.query.max_async_queries_per_user,
+                    global_config.query.max_async_result_bytes_per_user,
+                );
+                let async_queries = Arc::new(async_queries);
+                async_queries.start();
+                http_service = http_service.with_async_queries(async_queries);
+                let http_service = Box::new(http_service);
+                let grpc_service = Box::new(
+                    GrpcService::new(
+                        dbms.clone(),
+                        coord.clone(),
+                        kv_inst.clone(),
+                        grpc_host,
+                        tls.clone(),
+                        http_limits.clone(),
+                    )
+                    .with_sessions(dbms.sessions()),
+                );
 
                 let report_service = Box::new(ReportService::new());
 
@@ -165,16 +417,118 @@ fn main() -> Result<(), std::io::Error> {
                 let mut server = server_builder.build().expect("build server.");
 
                 server.start().expect("server start.");
-                signal::block_waiting_ctrl_c();
-                server.stop(true).await;
+                ConfigReloader::new(
+                    settings.clone(),
+                    kv_inst.clone(),
+                    http_limits,
+                    dbms.sessions(),
+                )
+                .with_tls(tls)
+                .start();
+                signal::wait_for_shutdown().await;
+                server
+                    .shutdown(Duration::from_millis(
+                        global_config.query.shutdown_timeout_ms,
+                    ))
+                    .await;
+                // The caches are flushed and the wal is synced
                 kv_inst.close().await;
                 println!("CnosDB is stopped.");
             }
         }
     });
+    shutdown_global_tracing();
     Ok(())
 }
 
+/// Registers the node to the meta service, so that vnodes can be placed on it
+async fn register_data_node(cluster: &ClusterConfig, grpc_host: SocketAddr, http_host: SocketAddr) {
+    let client = meta_client(cluster);
+    let node = NodeInfo {
+        id: cluster.node_id,
+        grpc_addr: grpc_host.to_string(),
+        http_addr: http_host.to_string(),
+        status: NodeStatus::Healthy,
+    };
+    client
+        .add_data_node(&node)
+        .await
+        .expect("register data node");
+    info!("Data node {} is registered to the meta service", node.id);
+}
+
+/// Client of the meta service, by https if the CA of the meta nodes is configured
+fn meta_client(cluster: &ClusterConfig) -> RemoteMetaClient {
+    let client = RemoteMetaClient::new(
+        cluster.meta_service_addr.clone(),
+        cluster.meta_service_token.clone(),
+    );
+    match &cluster.meta_service_ca_certificate {
+        Some(ca_certificate) => client
+            .with_tls(ca_certificate)
+            .expect("load meta service ca certificate"),
+        None => client,
+    }
+}
+
+/// TLS of the connections to the other nodes, the listeners of all nodes share the config
+fn client_tls(config: &Config) -> Option<ClientTlsConfig> {
+    config
+        .security
+        .tls_config
+        .as_ref()
+        .map(|config| client_tls_config(config).expect("load tls certificate"))
+}
+
+/// Starts to replicate the shards located on this node, returns the coordinator
+/// routing the points written to the shards
+async fn start_cluster(config: &Config, local: EngineRef) -> (CoordinatorRef, Arc<Rebalancer>) {
+    let client = Arc::new(meta_client(&config.cluster));
+    client.start_watch(META_WATCH_INTERVAL).await;
+    let meta: MetaClientRef = client;
+    // The data nodes connect to each other as the admin
+    let connections = Arc::new(
+        NodeConnections::new(meta.clone())
+            .with_credentials(&config.security.admin_user, &config.security.admin_password)
+            .with_tls(client_tls(config)),
+    );
+
+    // Catches up the writes missed by the replicas on this node from the other replicas
+    let sync = ReplicaSync::new(
+        config.cluster.node_id,
+        local.clone(),
+        connections.clone(),
+        Path::new(&config.storage.path).join("replication"),
+    )
+    .expect("open replica sync");
+    Arc::new(sync).start(DEFAULT_SYNC_INTERVAL);
+
+    // Buffers the writes of the unavailable replicas and replays them once they are back
+    let handoff = HintedHandoff::open(
+        Path::new(&config.storage.path).join("hints"),
+        config.cluster.hinted_handoff_max_size,
+    )
+    .expect("open hinted handoff");
+    let handoff = Arc::new(handoff);
+
+    let writer = Arc::new(
+        PointWriter::new(config.cluster.node_id, local.clone(), connections.clone())
+            .with_hinted_handoff(handoff.clone()),
+    );
+    handoff.start(writer.clone(), DEFAULT_REPLAY_INTERVAL);
+
+    // Moves the vnodes between the data nodes once nodes are added or decommissioned
+    let rebalancer = Arc::new(Rebalancer::new(
+        connections.clone(),
+        writer.clone(),
+        config.cluster.rebalance_rate_limit,
+    ));
+
+    let router = Arc::new(PointRouter::new(meta, writer));
+    let coord = ClusterCoordinator::new(config.cluster.node_id, local, connections, router);
+    (Arc::new(coord), rebalancer)
+}
+
 fn init_runtime(cores: Option<usize>) -> Result<Runtime, std::io::Error> {
     use tokio::runtime::Builder;
     let kind = std::io::ErrorKind::Other;
@@ -0,0 +1,95 @@
+//! Applies the reloadable settings changed on the fly, by `ALTER SYSTEM SET` or by
+//! reloading the configuration file once SIGHUP is received, e.g. `kill -HUP <pid>`.
+//!
+//! The changes of the other settings are reported to take effect after a restart.
+//! The TLS certificates are also loaded again once SIGHUP is received.
+use std::sync::Arc;
+
+use config::{ConfigChanges, SettingsRef};
+use query::session::SessionRegistryRef;
+use tokio::signal::unix::{signal, SignalKind};
+use trace::{error, info, set_log_level, warn};
+use tskv::TsKv;
+
+use crate::http::http_service::HttpLimits;
+use crate::tls::TlsCertsRef;
+
+pub struct ConfigReloader {
+    settings: SettingsRef,
+    tls: Option<TlsCertsRef>,
+}
+
+impl ConfigReloader {
+    pub fn new(
+        settings: SettingsRef,
+        kv_inst: Arc<TsKv>,
+        http_limits: Arc<HttpLimits>,
+        sessions: SessionRegistryRef,
+    ) -> Self {
+        settings.on_change(move |config| {
+            if let Err(e) = set_log_level(&config.log.level) {
+                error!("Failed to set log level {}: {}", config.log.level, e);
+            }
+            kv_inst.reload_cache_options(&config.cache);
+            kv_inst.reload_query_options(&config.query);
+            kv_inst.reload_storage_options(&config.storage);
+            http_limits.update(config);
+            sessions.update(&config.query);
+        });
+        Self {
+            settings,
+            tls: None,
+        }
+    }
+
+    pub fn with_tls(mut self, tls: Option<TlsCertsRef>) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    pub fn start(self) {
+        tokio::spawn(async move {
+            let mut hangup = match signal(SignalKind::hangup()) {
+                Ok(hangup) => hangup,
+                Err(e) => {
+                    error!(
+                        "Failed to listen to SIGHUP, configuration is not reloadable: {}",
+                        e
+                    );
+                    return;
+                }
+            };
+            while hangup.recv().await.is_some() {
+                match self.settings.reload() {
+                    Ok(changes) => report(&changes),
+                    Err(e) => error!("Failed to reload configuration, nothing changed: {}", e),
+                }
+                if let Some(tls) = &self.tls {
+                    if let Err(e) = tls.reload() {
+                        error!(
+                            "Failed to reload TLS certificate, the current one is kept: {}",
+                            e
+                        );
+                    }
+                }
+            }
+        });
+    }
+}
+
+fn report(changes: &ConfigChanges) {
+    if changes.reloaded.is_empty() && changes.restart_required.is_empty() {
+        info!("Configuration reloaded, nothing changed");
+        return;
+    }
+    info!(
+        "Configuration reloaded, applied: [{}]",
+        changes.reloaded.join(", ")
+    );
+    if !changes.restart_required.is_empty() {
+        warn!(
+            "Configuration reloaded, changes taking effect after a restart: [{}]",
+            changes.restart_required.join(", ")
+        );
+    }
+}
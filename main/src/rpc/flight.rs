@@ -0,0 +1,464 @@
+//! Arrow Flight service, the connectors of Spark, Trino and the other engines read
+//! the tables of cnosdb by it in parallel.
+//!
+//! A table is described by the path `[<database>, <table>]`, or by the command of a
+//! json [`FlightCommand`] restricting the time range and the rows read. The
+//! endpoints of the flight info are the time partitions of the database, the
+//! ticket of each is a json [`FlightTicket`] redeemed by `DoGet` on any node, the
+//! rows of which are read by a query of the user, so the privileges of the user
+//! apply to them.
+use std::pin::Pin;
+use std::sync::Arc;
+
+use datafusion::arrow::datatypes::Schema;
+use datafusion::arrow::error::Result as ArrowResult;
+use datafusion::arrow::ipc::writer::{
+    write_message, DictionaryTracker, IpcDataGenerator, IpcWriteOptions,
+};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::sql::sqlparser::dialect::GenericDialect;
+use datafusion::sql::sqlparser::parser::Parser;
+use datafusion::sql::sqlparser::tokenizer::Token;
+use futures::{stream, Stream};
+use models::error_code::ErrorCode;
+use models::Timestamp;
+use protos::arrow_flight::{
+    flight_descriptor::DescriptorType, flight_service_server::FlightService, Action, ActionType,
+    Criteria, Empty, FlightData, FlightDescriptor, FlightEndpoint, FlightInfo, HandshakeRequest,
+    HandshakeResponse, PutResult, SchemaResult, Ticket,
+};
+use query::session::SessionRef;
+use serde::{Deserialize, Serialize};
+use spi::server::dbms::DBMSRef;
+use spi::server::ServerError;
+use spi::service::protocol::{ContextBuilder, Query, UserInfo};
+use tonic::{Code, Request, Response, Status, Streaming};
+use trace::debug;
+
+use crate::http::http_service::HttpLimits;
+use crate::http::result_format::fetch_record_batches;
+use crate::rpc::error_status;
+
+type FlightStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send + 'static>>;
+
+/// The command of a flight descriptor, the time range is `[start, end)` in
+/// nanoseconds, the predicate is a sql expression on the columns of the table
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct FlightCommand {
+    pub database: String,
+    pub table: String,
+    #[serde(default)]
+    pub start: Option<Timestamp>,
+    #[serde(default)]
+    pub end: Option<Timestamp>,
+    #[serde(default)]
+    pub predicate: Option<String>,
+}
+
+/// The ticket of a time partition of a table
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlightTicket {
+    pub database: String,
+    pub table: String,
+    pub start: Timestamp,
+    pub end: Timestamp,
+    #[serde(default)]
+    pub predicate: Option<String>,
+}
+
+impl FlightTicket {
+    fn to_sql(&self) -> String {
+        let mut sql = format!(
+            "SELECT * FROM {}.{} WHERE time >= {} AND time < {}",
+            quote_ident(&self.database),
+            quote_ident(&self.table),
+            self.start,
+            self.end
+        );
+        if let Some(predicate) = &self.predicate {
+            sql.push_str(&format!(" AND ({})", predicate));
+        }
+        sql
+    }
+}
+
+fn quote_ident(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+/// The predicate must be a single sql expression, not ending the where clause of
+/// the query of the ticket
+fn check_predicate(predicate: &str) -> Result<(), String> {
+    let mut parser = Parser::new(&GenericDialect {})
+        .try_with_sql(predicate)
+        .map_err(|e| e.to_string())?;
+    parser.parse_expr().map_err(|e| e.to_string())?;
+    match parser.peek_token() {
+        Token::EOF => Ok(()),
+        token => Err(format!("unexpected {} in the predicate", token)),
+    }
+}
+
+/// The partitions overlapping the time range of the command, cut to it
+fn tickets(cmd: &FlightCommand, partitions: &[(Timestamp, Timestamp)]) -> Vec<FlightTicket> {
+    let (lower, upper) = (
+        cmd.start.unwrap_or(Timestamp::MIN),
+        cmd.end.unwrap_or(Timestamp::MAX),
+    );
+    partitions
+        .iter()
+        .map(|(start, end)| (*start.max(&lower), *end.min(&upper)))
+        .filter(|(start, end)| start < end)
+        .map(|(start, end)| FlightTicket {
+            database: cmd.database.clone(),
+            table: cmd.table.clone(),
+            start,
+            end,
+            predicate: cmd.predicate.clone(),
+        })
+        .collect()
+}
+
+/// The schema in the ipc form of the flight info and the schema result
+fn schema_to_ipc(schema: &Schema) -> ArrowResult<Vec<u8>> {
+    let options = IpcWriteOptions::default();
+    let encoded = IpcDataGenerator::default().schema_to_bytes(schema, &options);
+    let mut bytes = vec![];
+    write_message(&mut bytes, encoded, &options)?;
+    Ok(bytes)
+}
+
+/// The flight data of the schema followed by those of the batches
+fn batches_to_flight_data(
+    schema: &Schema,
+    batches: &[RecordBatch],
+) -> ArrowResult<Vec<FlightData>> {
+    let options = IpcWriteOptions::default();
+    let generator = IpcDataGenerator::default();
+    let mut tracker = DictionaryTracker::new(false);
+
+    let schema = generator.schema_to_bytes(schema, &options);
+    let mut data = vec![FlightData {
+        data_header: schema.ipc_message,
+        ..Default::default()
+    }];
+    for batch in batches {
+        let (dictionaries, batch) = generator.encoded_batch(batch, &mut tracker, &options)?;
+        for encoded in dictionaries.into_iter().chain(std::iter::once(batch)) {
+            data.push(FlightData {
+                data_header: encoded.ipc_message,
+                data_body: encoded.arrow_data,
+                ..Default::default()
+            });
+        }
+    }
+    Ok(data)
+}
+
+fn server_status(e: ServerError) -> Status {
+    let error_code = e.error_code();
+    let code = match error_code {
+        ErrorCode::AuthFailed => Code::Unauthenticated,
+        ErrorCode::PermissionDenied => Code::PermissionDenied,
+        ErrorCode::InvalidParameter | ErrorCode::SqlParse | ErrorCode::Semantic => {
+            Code::InvalidArgument
+        }
+        ErrorCode::DatabaseNotFound | ErrorCode::TableNotFound => Code::NotFound,
+        _ => Code::Internal,
+    };
+    error_status(code, error_code, e.to_string())
+}
+
+fn invalid_argument(message: impl Into<String>) -> Status {
+    error_status(Code::InvalidArgument, ErrorCode::InvalidParameter, message)
+}
+
+fn arrow_status(e: impl std::fmt::Display) -> Status {
+    error_status(Code::Internal, ErrorCode::QueryInternal, e.to_string())
+}
+
+pub struct FlightServiceImpl {
+    pub dbms: DBMSRef,
+    pub limits: Arc<HttpLimits>,
+}
+
+impl FlightServiceImpl {
+    fn user_info<T>(request: &Request<T>) -> Result<UserInfo, Status> {
+        request
+            .extensions()
+            .get::<UserInfo>()
+            .cloned()
+            .ok_or_else(|| {
+                error_status(
+                    Code::Unauthenticated,
+                    ErrorCode::AuthFailed,
+                    "missing authorization",
+                )
+            })
+    }
+
+    fn command(descriptor: &FlightDescriptor) -> Result<FlightCommand, Status> {
+        let cmd = match descriptor.r#type() {
+            DescriptorType::Path => match descriptor.path.as_slice() {
+                [database, table] => FlightCommand {
+                    database: database.clone(),
+                    table: table.clone(),
+                    ..Default::default()
+                },
+                _ => return Err(invalid_argument("the path must be [<database>, <table>]")),
+            },
+            DescriptorType::Cmd => serde_json::from_slice(&descriptor.cmd)
+                .map_err(|e| invalid_argument(format!("invalid command: {}", e)))?,
+            DescriptorType::Unknown => return Err(invalid_argument("unknown descriptor type")),
+        };
+        if let Some(predicate) = &cmd.predicate {
+            check_predicate(predicate).map_err(invalid_argument)?;
+        }
+        Ok(cmd)
+    }
+
+    fn flight_info(
+        &self,
+        tenant: &str,
+        cmd: &FlightCommand,
+        partitions: &[(Timestamp, Timestamp)],
+    ) -> Result<FlightInfo, Status> {
+        let schema = self
+            .dbms
+            .table_schema(tenant, &cmd.database, &cmd.table)
+            .map_err(server_status)?;
+        let endpoint = tickets(cmd, partitions)
+            .iter()
+            .map(|ticket| {
+                let ticket = serde_json::to_vec(ticket).map_err(arrow_status)?;
+                Ok(FlightEndpoint {
+                    ticket: Some(Ticket { ticket }),
+                    location: vec![],
+                })
+            })
+            .collect::<Result<Vec<_>, Status>>()?;
+
+        Ok(FlightInfo {
+            schema: schema_to_ipc(&schema).map_err(arrow_status)?,
+            flight_descriptor: Some(FlightDescriptor {
+                r#type: DescriptorType::Cmd as i32,
+                cmd: serde_json::to_vec(cmd).map_err(arrow_status)?,
+                path: vec![],
+            }),
+            endpoint,
+            total_records: -1,
+            total_bytes: -1,
+        })
+    }
+}
+
+#[tonic::async_trait]
+impl FlightService for FlightServiceImpl {
+    type HandshakeStream = FlightStream<HandshakeResponse>;
+
+    /// The requests are authenticated by the `authorization` metadata of each of
+    /// them, the handshake only acknowledges the credentials
+    async fn handshake(
+        &self,
+        request: Request<Streaming<HandshakeRequest>>,
+    ) -> Result<Response<Self::HandshakeStream>, Status> {
+        Self::user_info(&request)?;
+        let resp = HandshakeResponse::default();
+        Ok(Response::new(Box::pin(stream::iter(vec![Ok(resp)]))))
+    }
+
+    type ListFlightsStream = FlightStream<FlightInfo>;
+
+    /// The tables of the database of the expression, or of all the databases
+    async fn list_flights(
+        &self,
+        request: Request<Criteria>,
+    ) -> Result<Response<Self::ListFlightsStream>, Status> {
+        let tenant = Self::user_info(&request)?.user;
+        let expression = String::from_utf8(request.into_inner().expression)
+            .map_err(|_| invalid_argument("the expression must be the name of a database"))?;
+        let databases = if expression.is_empty() {
+            self.dbms.database_names(&tenant).map_err(server_status)?
+        } else {
+            vec![expression]
+        };
+
+        let mut infos = vec![];
+        for database in databases {
+            let partitions = self
+                .dbms
+                .time_partitions(&tenant, &database)
+                .map_err(server_status)?;
+            for table in self
+                .dbms
+                .table_names(&tenant, &database)
+                .map_err(server_status)?
+            {
+                let cmd = FlightCommand {
+                    database: database.clone(),
+                    table,
+                    ..Default::default()
+                };
+                infos.push(self.flight_info(&tenant, &cmd, &partitions));
+            }
+        }
+        Ok(Response::new(Box::pin(stream::iter(infos))))
+    }
+
+    async fn get_flight_info(
+        &self,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        let tenant = Self::user_info(&request)?.user;
+        let cmd = Self::command(request.get_ref())?;
+        let partitions = self
+            .dbms
+            .time_partitions(&tenant, &cmd.database)
+            .map_err(server_status)?;
+        Ok(Response::new(self.flight_info(
+            &tenant,
+            &cmd,
+            &partitions,
+        )?))
+    }
+
+    async fn get_schema(
+        &self,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<SchemaResult>, Status> {
+        let tenant = Self::user_info(&request)?.user;
+        let cmd = Self::command(request.get_ref())?;
+        let schema = self
+            .dbms
+            .table_schema(&tenant, &cmd.database, &cmd.table)
+            .map_err(server_status)?;
+        Ok(Response::new(SchemaResult {
+            schema: schema_to_ipc(&schema).map_err(arrow_status)?,
+        }))
+    }
+
+    type DoGetStream = FlightStream<FlightData>;
+
+    async fn do_get(
+        &self,
+        request: Request<Ticket>,
+    ) -> Result<Response<Self::DoGetStream>, Status> {
+        let user_info = Self::user_info(&request)?;
+        let ticket: FlightTicket = serde_json::from_slice(&request.get_ref().ticket)
+            .map_err(|e| invalid_argument(format!("invalid ticket: {}", e)))?;
+        if let Some(predicate) = &ticket.predicate {
+            check_predicate(predicate).map_err(invalid_argument)?;
+        }
+
+        let sql = ticket.to_sql();
+        debug!("flight ticket of {}: {}", user_info.user, sql);
+        let context = ContextBuilder::new(user_info.clone())
+            .with_database(Some(ticket.database.clone()))
+            .build();
+        // Limited as the queries by http
+        self.limits
+            .check_query(&context.user_info().user, context.catalog())
+            .map_err(|e| error_status(Code::ResourceExhausted, e.error_code(), e.to_string()))?;
+        let session = request.extensions().get::<SessionRef>().cloned();
+        let _running = session.map(|e| e.begin_query(&sql));
+        let mut result = self
+            .dbms
+            .execute(&Query::new(context, sql))
+            .await
+            .map_err(server_status)?;
+        let batches = fetch_record_batches(&mut result)
+            .await
+            .map_err(arrow_status)?;
+        let schema = match batches.first() {
+            Some(batch) => batch.schema(),
+            None => self
+                .dbms
+                .table_schema(&user_info.user, &ticket.database, &ticket.table)
+                .map_err(server_status)?,
+        };
+
+        let data = batches_to_flight_data(&schema, &batches).map_err(arrow_status)?;
+        Ok(Response::new(Box::pin(stream::iter(
+            data.into_iter().map(Ok),
+        ))))
+    }
+
+    type DoPutStream = FlightStream<PutResult>;
+
+    async fn do_put(
+        &self,
+        _request: Request<Streaming<FlightData>>,
+    ) -> Result<Response<Self::DoPutStream>, Status> {
+        Err(Status::unimplemented("the flight service is read only"))
+    }
+
+    type DoExchangeStream = FlightStream<FlightData>;
+
+    async fn do_exchange(
+        &self,
+        _request: Request<Streaming<FlightData>>,
+    ) -> Result<Response<Self::DoExchangeStream>, Status> {
+        Err(Status::unimplemented("the flight service is read only"))
+    }
+
+    type DoActionStream = FlightStream<protos::arrow_flight::Result>;
+
+    async fn do_action(
+        &self,
+        _request: Request<Action>,
+    ) -> Result<Response<Self::DoActionStream>, Status> {
+        Err(Status::unimplemented("no action is supported"))
+    }
+
+    type ListActionsStream = FlightStream<ActionType>;
+
+    async fn list_actions(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<Self::ListActionsStream>, Status> {
+        Ok(Response::new(Box::pin(stream::empty())))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_tickets() {
+        let cmd = FlightCommand {
+            database: "public".to_string(),
+            table: "air".to_string(),
+            start: Some(15),
+            end: None,
+            predicate: Some("station = 'XiaoMaiDao'".to_string()),
+        };
+        let tickets = tickets(&cmd, &[(0, 10), (10, 20), (20, 30)]);
+        let ranges = tickets.iter().map(|e| (e.start, e.end)).collect::<Vec<_>>();
+        assert_eq!(ranges, vec![(15, 20), (20, 30)]);
+        assert_eq!(
+            tickets[0].to_sql(),
+            "SELECT * FROM \"public\".\"air\" WHERE time >= 15 AND time < 20 \
+             AND (station = 'XiaoMaiDao')"
+        );
+    }
+
+    #[test]
+    fn test_check_predicate() {
+        assert!(check_predicate("station = 'XiaoMaiDao' AND pressure > 10").is_ok());
+        assert!(check_predicate("true) UNION SELECT * FROM t WHERE (true").is_err());
+        assert!(check_predicate("").is_err());
+    }
+
+    #[test]
+    fn test_schema_to_ipc() {
+        use datafusion::arrow::datatypes::{DataType, Field};
+
+        let schema = Schema::new(vec![Field::new("pressure", DataType::Float64, true)]);
+        let bytes = schema_to_ipc(&schema).unwrap();
+        // The continuation marker followed by the length of the message
+        assert_eq!(bytes[..4], [0xff; 4]);
+        let len = i32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+        assert_eq!(bytes.len(), 8 + len);
+    }
+}
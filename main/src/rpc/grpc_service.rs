@@ -1,73 +1,382 @@
+use crate::http::header::{Credentials, Header};
+use crate::http::http_service::{authenticate as authenticate_credentials, HttpLimits};
+use crate::rpc::error_status;
+use crate::rpc::flight::FlightServiceImpl;
 use crate::rpc::tskv::TskvServiceImpl;
 use crate::server::{Service, ServiceHandle};
+use crate::tls::{self, TlsCertsRef};
 use crate::{info, server};
-use config::TLSConfig;
+use coordinator::service::CoordinatorRef;
+use futures::future::BoxFuture;
+use futures::{Future, Stream, StreamExt};
+use models::error_code::ErrorCode;
+use parking_lot::Mutex;
+use protos::arrow_flight::flight_service_server::FlightServiceServer;
 use protos::kv_service::tskv_service_server::TskvServiceServer;
+use query::session::{SessionError, SessionGuard, SessionRef, SessionRegistry, SessionRegistryRef};
+use sha2::{Digest, Sha256};
 use spi::server::dbms::DBMSRef;
+use spi::service::protocol::UserInfo;
+use std::collections::HashMap;
+use std::io;
 use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 use tokio::sync::oneshot;
-use tonic::transport::{Identity, Server, ServerTlsConfig};
+use tonic::body::BoxBody;
+use tonic::codegen::http;
+use tonic::transport::server::Connected;
+use tonic::transport::{Body, Server};
+use tonic::{Code, Status};
+use tower::Layer;
+use trace::warn;
+
 use tskv::engine::EngineRef;
 
 pub struct GrpcService {
-    tls_config: Option<TLSConfig>,
+    tls: Option<TlsCertsRef>,
     addr: SocketAddr,
-    //todo grpc support sql query
-    _dbms: DBMSRef,
+    dbms: DBMSRef,
+    coord: CoordinatorRef,
     kv_inst: EngineRef,
+    limits: Arc<HttpLimits>,
+    sessions: SessionRegistryRef,
     handle: Option<ServiceHandle<Result<(), tonic::transport::Error>>>,
 }
 
 impl GrpcService {
     pub fn new(
         dbms: DBMSRef,
+        coord: CoordinatorRef,
         kv_inst: EngineRef,
         addr: SocketAddr,
-        tls_config: Option<TLSConfig>,
+        tls: Option<TlsCertsRef>,
+        limits: Arc<HttpLimits>,
     ) -> Self {
         Self {
-            tls_config,
+            tls,
             addr,
-            _dbms: dbms,
+            dbms,
+            coord,
             kv_inst,
+            limits,
+            sessions: Arc::new(SessionRegistry::default()),
             handle: None,
         }
     }
+
+    /// Registers the connections of the clients as the sessions of `SHOW SESSIONS`
+    pub fn with_sessions(mut self, sessions: SessionRegistryRef) -> Self {
+        self.sessions = sessions;
+        self
+    }
 }
 
-fn build_grpc_server(tls_config: &Option<TLSConfig>) -> server::Result<Server> {
-    let server = Server::builder();
-    if tls_config.is_none() {
-        return Ok(server);
+const GRPC_ALPN: &[&[u8]] = &[b"h2"];
+
+/// How long a password verified is trusted before it is verified again, so the
+/// users dropped or the passwords changed are refused once expired
+const VERIFIED_TTL: Duration = Duration::from_secs(60);
+/// The most passwords kept verified, the expired ones are evicted once reached
+const MAX_VERIFIED: usize = 4096;
+
+/// The passwords verified recently by the digest of the `authorization`, as a client
+/// sends the password with each of its requests and hashing the password is slow
+#[derive(Default)]
+struct VerifiedPasswords {
+    entries: Mutex<HashMap<[u8; 32], (UserInfo, Instant)>>,
+}
+
+impl VerifiedPasswords {
+    fn get(&self, key: &[u8; 32]) -> Option<UserInfo> {
+        match self.entries.lock().get(key) {
+            Some((user_info, verified)) if verified.elapsed() < VERIFIED_TTL => {
+                Some(user_info.clone())
+            }
+            _ => None,
+        }
     }
 
-    let TLSConfig {
-        certificate,
-        private_key,
-    } = tls_config.as_ref().unwrap();
-    let cert = std::fs::read(certificate)?;
-    let key = std::fs::read(private_key)?;
-    let identity = Identity::from_pem(cert, key);
-    let server = server.tls_config(ServerTlsConfig::new().identity(identity))?;
+    fn insert(&self, key: [u8; 32], user_info: UserInfo) {
+        let mut entries = self.entries.lock();
+        if entries.len() >= MAX_VERIFIED {
+            entries.retain(|_, (_, verified)| verified.elapsed() < VERIFIED_TTL);
+            if entries.len() >= MAX_VERIFIED {
+                entries.clear();
+            }
+        }
+        entries.insert(key, (user_info, Instant::now()));
+    }
+}
+
+/// Checks the basic auth or the token in the `authorization` header of the request,
+/// returns the user authenticated
+async fn authenticate(
+    dbms: &DBMSRef,
+    verified: &VerifiedPasswords,
+    headers: &http::HeaderMap,
+) -> Result<UserInfo, Status> {
+    let authorization = headers
+        .get("authorization")
+        .and_then(|e| e.to_str().ok())
+        .ok_or_else(|| {
+            error_status(
+                Code::Unauthenticated,
+                ErrorCode::AuthFailed,
+                "missing authorization",
+            )
+        })?;
+    let key: [u8; 32] = Sha256::digest(authorization.as_bytes()).into();
+    if let Some(user_info) = verified.get(&key) {
+        return Ok(user_info);
+    }
 
-    Ok(server)
+    let credentials = Header::with(None, authorization.to_string())
+        .credentials()
+        .map_err(|e| error_status(Code::Unauthenticated, e.error_code(), e.to_string()))?;
+    let is_password = matches!(credentials, Credentials::Password(_));
+    let user_info = authenticate_credentials(dbms, credentials)
+        .await
+        .map_err(|e| error_status(Code::Unauthenticated, e.error_code(), e.to_string()))?;
+    // The tokens are cheap to check, and expire
+    if is_password {
+        verified.insert(key, user_info.clone());
+    }
+    Ok(user_info)
+}
+
+/// A connection of a client registered as a session, closed at once when the
+/// session is killed or idle for the timeout
+struct SessionStream<IO> {
+    inner: IO,
+    guard: SessionGuard,
+    closed: Pin<Box<dyn Future<Output = ()> + Send>>,
+    is_closed: bool,
+}
+
+impl<IO> SessionStream<IO> {
+    fn new(inner: IO, guard: SessionGuard) -> Self {
+        let closed = Box::pin(guard.closed());
+        Self {
+            inner,
+            guard,
+            closed,
+            is_closed: false,
+        }
+    }
+
+    fn poll_closed(&mut self, cx: &mut Context<'_>) -> io::Result<()> {
+        if !self.is_closed && self.closed.as_mut().poll(cx).is_ready() {
+            self.is_closed = true;
+        }
+        if self.is_closed {
+            return Err(io::Error::new(
+                io::ErrorKind::ConnectionAborted,
+                format!("session {} is closed", self.guard.session().id()),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl<IO: AsyncRead + Unpin> AsyncRead for SessionStream<IO> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        this.poll_closed(cx)?;
+        Pin::new(&mut this.inner).poll_read(cx, buf)
+    }
+}
+
+impl<IO: AsyncWrite + Unpin> AsyncWrite for SessionStream<IO> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        this.poll_closed(cx)?;
+        Pin::new(&mut this.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+/// The session is in the extensions of the requests of the connection
+impl<IO> Connected for SessionStream<IO> {
+    type ConnectInfo = SessionRef;
+
+    fn connect_info(&self) -> Self::ConnectInfo {
+        self.guard.session().clone()
+    }
+}
+
+/// The connections accepted as the sessions, those over the limit are closed
+fn sessions_of<IO, S>(
+    incoming: S,
+    sessions: SessionRegistryRef,
+    peer_addr: fn(&IO) -> io::Result<SocketAddr>,
+) -> impl Stream<Item = io::Result<SessionStream<IO>>>
+where
+    S: Stream<Item = io::Result<IO>>,
+{
+    incoming.filter_map(move |res| {
+        let res = match res {
+            Ok(io) => {
+                let addr = peer_addr(&io).map_or_else(|_| "unknown".to_string(), |e| e.to_string());
+                match sessions.open(addr.clone(), "grpc") {
+                    Ok(guard) => Some(Ok(SessionStream::new(io, guard))),
+                    Err(e) => {
+                        warn!("Connection of {} is closed: {}", addr, e);
+                        None
+                    }
+                }
+            }
+            Err(e) => Some(Err(e)),
+        };
+        futures::future::ready(res)
+    })
+}
+
+fn session_status(e: SessionError) -> Status {
+    let code = match e {
+        SessionError::UserMismatch { .. } => Code::PermissionDenied,
+        _ => Code::ResourceExhausted,
+    };
+    error_status(code, e.error_code(), e.to_string())
+}
+
+/// Authenticates the requests of the grpc services before they are handled, the user
+/// authenticated is kept in the extensions of the request. A layer rather than an
+/// interceptor, as the passwords of the identity providers are checked asynchronously
+#[derive(Clone)]
+struct AuthLayer {
+    dbms: DBMSRef,
+    verified: Arc<VerifiedPasswords>,
+    sessions: SessionRegistryRef,
+}
+
+impl<S> Layer<S> for AuthLayer {
+    type Service = AuthService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AuthService {
+            inner,
+            dbms: self.dbms.clone(),
+            verified: self.verified.clone(),
+            sessions: self.sessions.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct AuthService<S> {
+    inner: S,
+    dbms: DBMSRef,
+    verified: Arc<VerifiedPasswords>,
+    sessions: SessionRegistryRef,
+}
+
+impl<S> tower::Service<http::Request<Body>> for AuthService<S>
+where
+    S: tower::Service<http::Request<Body>, Response = http::Response<BoxBody>>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: http::Request<Body>) -> Self::Future {
+        // The service polled ready handles the request, a clone of it is left for the next
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let dbms = self.dbms.clone();
+        let verified = self.verified.clone();
+        let sessions = self.sessions.clone();
+        Box::pin(async move {
+            let user_info = match authenticate(&dbms, &verified, request.headers()).await {
+                Ok(user_info) => user_info,
+                Err(status) => return Ok(status.to_http()),
+            };
+            // The connection is bound to the user, the connections of whom are limited
+            let session = request.extensions().get::<SessionRef>().cloned();
+            if let Some(session) = &session {
+                if let Err(e) = sessions.authenticate(session, &user_info.user) {
+                    return Ok(session_status(e).to_http());
+                }
+            }
+            let _request = session.as_ref().map(|e| e.begin_request());
+            request.extensions_mut().insert(user_info);
+            inner.call(request).await
+        })
+    }
 }
 
 #[async_trait::async_trait]
 impl Service for GrpcService {
     fn start(&mut self) -> server::Result<()> {
         let (shutdown, rx) = oneshot::channel();
+        let auth = AuthLayer {
+            dbms: self.dbms.clone(),
+            verified: Arc::new(VerifiedPasswords::default()),
+            sessions: self.sessions.clone(),
+        };
         let tskv_grpc_service = TskvServiceServer::new(TskvServiceImpl {
             kv_engine: self.kv_inst.clone(),
+            dbms: self.dbms.clone(),
+            coord: self.coord.clone(),
+            limits: self.limits.clone(),
         });
-        let mut grpc_builder = build_grpc_server(&self.tls_config)?;
-        let grpc_router = grpc_builder.add_service(tskv_grpc_service);
-        let server = grpc_router.serve_with_shutdown(self.addr, async {
+        let flight_service = FlightServiceServer::new(FlightServiceImpl {
+            dbms: self.dbms.clone(),
+            limits: self.limits.clone(),
+        });
+        let signal = async {
             rx.await.ok();
             info!("grpc server graceful shutdown!");
-        });
-        info!("grpc server start addr: {}", self.addr);
-        let grpc_handle = tokio::spawn(server);
+        };
+        let router = Server::builder()
+            .layer(auth)
+            .add_service(tskv_grpc_service)
+            .add_service(flight_service);
+        let sessions = self.sessions.clone();
+        let grpc_handle = if let Some(tls) = &self.tls {
+            // Fails early if the acceptor can not be built
+            tls.acceptor(GRPC_ALPN)?;
+            let listener = tls::bind(self.addr)?;
+            info!("grpc server start addr: {}, tls enabled", self.addr);
+            let incoming = tls::incoming(listener, tls.clone(), GRPC_ALPN);
+            let incoming = sessions_of(incoming, sessions, |e| e.get_ref().0.peer_addr());
+            tokio::spawn(router.serve_with_incoming_shutdown(incoming.boxed(), signal))
+        } else {
+            let listener = tls::bind(self.addr)?;
+            info!("grpc server start addr: {}", self.addr);
+            let incoming = futures::stream::unfold(listener, |listener| async move {
+                let (stream, _) = tls::accept(&listener).await;
+                Some((Ok(stream), listener))
+            });
+            let incoming = sessions_of(incoming, sessions, |e| e.peer_addr());
+            tokio::spawn(router.serve_with_incoming_shutdown(incoming.boxed(), signal))
+        };
         self.handle = Some(ServiceHandle::new(
             "grpc service".to_string(),
             grpc_handle,
@@ -1,3 +1,39 @@
+use models::error_code::{ErrorCode, GRPC_ERROR_CODE_KEY};
+use tonic::metadata::MetadataValue;
+use tonic::{Code, Status};
+
+pub mod flight;
 pub mod grpc_service;
 pub mod schema;
 pub mod tskv;
+
+/// The status carries the error code of cnosdb in the metadata, so that the clients
+/// can tell the errors apart from those with the same grpc code
+pub(crate) fn error_status(
+    code: Code,
+    error_code: ErrorCode,
+    message: impl Into<String>,
+) -> Status {
+    let mut status = Status::new(code, message);
+    status.metadata_mut().insert(
+        GRPC_ERROR_CODE_KEY,
+        MetadataValue::from_static(error_code.as_str()),
+    );
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_status() {
+        let status = error_status(Code::Unavailable, ErrorCode::WriteStalled, "stalled");
+        assert_eq!(status.code(), Code::Unavailable);
+        assert_eq!(status.message(), "stalled");
+        assert_eq!(
+            status.metadata().get(GRPC_ERROR_CODE_KEY).unwrap(),
+            ErrorCode::WriteStalled.as_str()
+        );
+    }
+}
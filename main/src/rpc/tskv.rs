@@ -1,24 +1,143 @@
 use std::pin::Pin;
+use std::sync::Arc;
 
+use chrono::Local;
+use coordinator::rebalance::delete_shard_points;
+use coordinator::service::CoordinatorRef;
+use coordinator::writer::DEFAULT_WRITE_CONSISTENCY;
+use datafusion::arrow::compute::concat_batches;
 use futures::Stream;
+use line_protocol::TagLimits;
+use metrics::tenant_usage::record_tenant_write;
+use metrics::{incr_point_write_failed, incr_point_write_success};
+use models::error_code::ErrorCode;
 use protos::{
     kv_service::{
         tskv_service_server::TskvService, AddSeriesRpcRequest, AddSeriesRpcResponse,
+        DeleteShardPointsRequest, DeleteShardPointsResponse, FetchChangesRequest,
+        FetchChangesResponse, FetchShardPointsRequest, FetchShardPointsResponse,
         GetSeriesInfoRpcRequest, GetSeriesInfoRpcResponse, PingRequest, PingResponse,
-        WritePointsRpcRequest, WritePointsRpcResponse, WriteRowsRpcRequest, WriteRowsRpcResponse,
+        RecordBatchResponse, ScanTableRequest, WritePointsRpcRequest, WritePointsRpcResponse,
+        WriteRecordBatchRpcRequest, WriteRecordBatchRpcResponse, WriteRowsRpcRequest,
+        WriteRowsRpcResponse,
     },
-    models::{PingBody, PingBodyBuilder},
+    models::{self as fb_models, PingBody, PingBodyBuilder},
 };
+use query::data_source::shard_scan::{
+    batch_to_ipc, ipc_to_batches, scan_shard_points, scan_shards,
+};
+use spi::query::datafusion_error_code;
+use spi::server::dbms::DBMSRef;
+use spi::service::protocol::UserInfo;
 use tokio::sync::mpsc::{self};
 use tokio_stream::{wrappers::ReceiverStream, StreamExt};
-use tonic::{Request, Response, Status, Streaming};
-use trace::debug;
+use tonic::{Code, Request, Response, Status, Streaming};
+use trace::{debug, warn};
 
+use tskv::cdc::ChangeOffset;
 use tskv::engine::EngineRef;
 
+use crate::http::http_service::HttpLimits;
+use crate::http::quota::stored_bytes;
+use crate::rpc::error_status;
+
+const SCAN_BATCH_SIZE: usize = 4096;
+
 pub struct TskvServiceImpl {
     // pub sender: channel::Sender<tskv::Task>,
     pub kv_engine: EngineRef,
+    pub dbms: DBMSRef,
+    pub coord: CoordinatorRef,
+    pub limits: Arc<HttpLimits>,
+}
+
+/// Checks the tags of the points written against the limits, as those of the lines
+/// written by http
+fn check_tag_limits(tag_limits: &TagLimits, points: &[u8]) -> Result<(), Status> {
+    let invalid = |e: String| error_status(Code::InvalidArgument, ErrorCode::InvalidParameter, e);
+    let points =
+        flatbuffers::root::<fb_models::Points>(points).map_err(|e| invalid(e.to_string()))?;
+    for point in points.points().into_iter().flatten() {
+        tag_limits
+            .check_point(&point)
+            .map_err(|e| invalid(e.to_string()))?;
+    }
+    Ok(())
+}
+
+/// Writes the rows of the record batches of the request to the table of the tenant
+/// of the user, the writes are limited as those by http. The rows of a request are
+/// written or rejected as a whole, except those rejected by the schema on write
+async fn write_record_batches(
+    dbms: &DBMSRef,
+    coord: &CoordinatorRef,
+    limits: &HttpLimits,
+    user: &str,
+    req: WriteRecordBatchRpcRequest,
+) -> Result<WriteRecordBatchRpcResponse, Status> {
+    // The tenant of the user, the same as that of the http writes
+    let tenant = user;
+    let batches = ipc_to_batches(&req.ipc).map_err(|e| {
+        error_status(
+            Code::InvalidArgument,
+            ErrorCode::InvalidParameter,
+            e.to_string(),
+        )
+    })?;
+    let num_rows = batches.iter().map(|e| e.num_rows()).sum::<usize>();
+    let measure = {
+        let (dbms, engine) = (dbms.clone(), coord.engine());
+        let tenant = tenant.to_string();
+        move || {
+            let databases = dbms.database_names(&tenant).unwrap_or_default();
+            stored_bytes(&engine, &databases)
+        }
+    };
+    limits
+        .check_write(user, tenant, num_rows, req.ipc.len(), measure)
+        .await
+        .map_err(|e| error_status(Code::ResourceExhausted, e.error_code(), e.to_string()))?;
+
+    // The batches are written at once, so a request failed is retried without
+    // writing the batches of it twice
+    let batch = match batches.first() {
+        Some(first) => concat_batches(&first.schema(), &batches).map_err(|e| {
+            error_status(
+                Code::InvalidArgument,
+                ErrorCode::InvalidParameter,
+                e.to_string(),
+            )
+        })?,
+        None => return Ok(WriteRecordBatchRpcResponse::default()),
+    };
+    if batch.num_rows() == 0 {
+        return Ok(WriteRecordBatchRpcResponse::default());
+    }
+    let now = Local::now().timestamp_nanos();
+    let points = dbms
+        .record_batch_to_points(tenant, &req.database, &req.table, &batch)
+        .map_err(|e| error_status(Code::InvalidArgument, e.error_code(), e.to_string()))?;
+    check_tag_limits(&limits.tag_limits(), &points)?;
+    let written = dbms
+        .resolve_schema_on_write(tenant, points, now)
+        .await
+        .map_err(|e| error_status(Code::InvalidArgument, e.error_code(), e.to_string()))?;
+    let rejected = written.rejected_points();
+    let write = WritePointsRpcRequest {
+        version: 1,
+        points: written.points,
+    };
+    coord
+        .write_points(tenant, DEFAULT_WRITE_CONSISTENCY, write)
+        .await
+        .map_err(|e| error_status(Code::Internal, e.error_code(), e.to_string()))?;
+    let resp = WriteRecordBatchRpcResponse {
+        rows: (batch.num_rows() - rejected) as u64,
+        rejected_rows: rejected as u64,
+    };
+    record_tenant_write(tenant, resp.rows, req.ipc.len() as u64);
+
+    Ok(resp)
 }
 
 #[tonic::async_trait]
@@ -121,11 +240,17 @@ impl TskvService for TskvServiceImpl {
                     //     .send(tskv::Task::WritePoints { req, tx })
                     //     .await
                     //     .map_err(|err| Status::internal(err.to_string()));
-                    let ret = self
-                        .kv_engine
-                        .write(req)
-                        .await
-                        .map_err(|err| Status::internal(err.to_string()));
+                    // The clients back off if the writes are stalled
+                    let ret = match check_tag_limits(&self.limits.tag_limits(), &req.points) {
+                        Ok(()) => self.kv_engine.write(req).await.map_err(|err| {
+                            let code = match err {
+                                tskv::Error::WriteStalled { .. } => Code::Unavailable,
+                                _ => Code::Internal,
+                            };
+                            error_status(code, err.error_code(), err.to_string())
+                        }),
+                        Err(status) => Err(status),
+                    };
                     // 2. if something wrong when sending Request
                     // if let Err(err) = ret {
                     //     resp_sender.send(Err(err)).await.expect("successful");
@@ -157,4 +282,182 @@ impl TskvService for TskvServiceImpl {
 
         Ok(Response::new(Box::pin(out_stream)))
     }
+
+    type WriteRecordBatchesStream = Pin<
+        Box<dyn Stream<Item = Result<WriteRecordBatchRpcResponse, Status>> + Send + Sync + 'static>,
+    >;
+
+    async fn write_record_batches(
+        &self,
+        request: Request<Streaming<WriteRecordBatchRpcRequest>>,
+    ) -> Result<Response<Self::WriteRecordBatchesStream>, Status> {
+        let user = request
+            .extensions()
+            .get::<UserInfo>()
+            .map(|e| e.user.clone())
+            .unwrap_or_default();
+        let mut stream = request.into_inner();
+        let (dbms, coord, limits) = (self.dbms.clone(), self.coord.clone(), self.limits.clone());
+
+        let (resp_sender, resp_receiver) = mpsc::channel(16);
+        tokio::spawn(async move {
+            while let Some(result) = stream.next().await {
+                let resp = match result {
+                    Ok(req) => {
+                        let resp = write_record_batches(&dbms, &coord, &limits, &user, req).await;
+                        match resp {
+                            Ok(_) => incr_point_write_success(),
+                            Err(_) => incr_point_write_failed(),
+                        }
+                        resp
+                    }
+                    Err(status) => Err(status),
+                };
+                if resp_sender.send(resp).await.is_err() {
+                    warn!("write of record batches is cancelled");
+                    break;
+                }
+            }
+        });
+
+        let out_stream = ReceiverStream::new(resp_receiver);
+        Ok(Response::new(Box::pin(out_stream)))
+    }
+
+    async fn fetch_changes(
+        &self,
+        request: Request<FetchChangesRequest>,
+    ) -> Result<Response<FetchChangesResponse>, Status> {
+        let req = request.into_inner();
+        let from = ChangeOffset::new(req.file_id, req.pos);
+        if !req.consumer.is_empty() {
+            self.kv_engine
+                .retain_changes(&req.consumer, &req.database, from)
+                .map_err(|err| error_status(Code::Internal, err.error_code(), err.to_string()))?;
+        }
+        let batch = self
+            .kv_engine
+            .read_changes(&req.database, from, req.limit as usize)
+            .map_err(|err| error_status(Code::Internal, err.error_code(), err.to_string()))?;
+
+        // The replayed writes are those of the peers, not served to them again
+        Ok(Response::new(FetchChangesResponse {
+            points: batch
+                .records
+                .into_iter()
+                .filter(|e| req.include_replayed || !e.replayed)
+                .map(|e| e.points)
+                .collect(),
+            next_file_id: batch.next_offset.file_id,
+            next_pos: batch.next_offset.pos,
+            truncated: batch.truncated,
+        }))
+    }
+
+    type ScanTableStream =
+        Pin<Box<dyn Stream<Item = Result<RecordBatchResponse, Status>> + Send + Sync + 'static>>;
+
+    async fn scan_table(
+        &self,
+        request: Request<ScanTableRequest>,
+    ) -> Result<Response<Self::ScanTableStream>, Status> {
+        let req = request.into_inner();
+        let mut stream =
+            match scan_shards(self.kv_engine.clone(), &req, SCAN_BATCH_SIZE).map_err(|err| {
+                error_status(Code::Internal, datafusion_error_code(&err), err.to_string())
+            })? {
+                Some(stream) => stream,
+                None => return Ok(Response::new(Box::pin(tokio_stream::empty()))),
+            };
+
+        let (resp_sender, resp_receiver) = mpsc::channel(16);
+        tokio::spawn(async move {
+            while let Some(result) = stream.next().await {
+                let resp = result
+                    .and_then(|batch| batch_to_ipc(&batch))
+                    .map(|ipc| RecordBatchResponse { ipc })
+                    .map_err(|err| {
+                        error_status(Code::Internal, ErrorCode::QueryExecution, err.to_string())
+                    });
+                let failed = resp.is_err();
+                if resp_sender.send(resp).await.is_err() {
+                    warn!("scan of {}.{} is cancelled", req.database, req.table);
+                    break;
+                }
+                if failed {
+                    break;
+                }
+            }
+        });
+
+        let out_stream = ReceiverStream::new(resp_receiver);
+        Ok(Response::new(Box::pin(out_stream)))
+    }
+
+    type FetchShardPointsStream = Pin<
+        Box<dyn Stream<Item = Result<FetchShardPointsResponse, Status>> + Send + Sync + 'static>,
+    >;
+
+    async fn fetch_shard_points(
+        &self,
+        request: Request<FetchShardPointsRequest>,
+    ) -> Result<Response<Self::FetchShardPointsStream>, Status> {
+        let req = request.into_inner();
+        let shard = req
+            .shard
+            .ok_or_else(|| Status::invalid_argument("shard is required"))?;
+        let stream = scan_shard_points(
+            self.kv_engine.clone(),
+            &req.database,
+            &shard,
+            SCAN_BATCH_SIZE,
+        )
+        .map_err(|err| {
+            error_status(Code::Internal, datafusion_error_code(&err), err.to_string())
+        })?;
+
+        let (resp_sender, resp_receiver) = mpsc::channel(16);
+        tokio::spawn(async move {
+            let mut stream = Box::pin(stream);
+            while let Some(result) = stream.next().await {
+                let resp = result
+                    .map(|points| FetchShardPointsResponse { points })
+                    .map_err(|err| {
+                        error_status(Code::Internal, datafusion_error_code(&err), err.to_string())
+                    });
+                let failed = resp.is_err();
+                if resp_sender.send(resp).await.is_err() {
+                    warn!("fetch of shard points of {} is cancelled", req.database);
+                    break;
+                }
+                if failed {
+                    break;
+                }
+            }
+        });
+
+        let out_stream = ReceiverStream::new(resp_receiver);
+        Ok(Response::new(Box::pin(out_stream)))
+    }
+
+    async fn delete_shard_points(
+        &self,
+        request: Request<DeleteShardPointsRequest>,
+    ) -> Result<Response<DeleteShardPointsResponse>, Status> {
+        let req = request.into_inner();
+        let shard = req
+            .shard
+            .ok_or_else(|| Status::invalid_argument("shard is required"))?;
+        let engine = self.kv_engine.clone();
+        let series = tokio::task::spawn_blocking(move || {
+            delete_shard_points(&engine, &req.database, &shard)
+        })
+        .await
+        .map_err(|err| Status::internal(err.to_string()))?
+        .map_err(|err| error_status(Code::Internal, err.error_code(), err.to_string()))?;
+
+        Ok(Response::new(DeleteShardPointsResponse {
+            series: series as u64,
+        }))
+    }
 }
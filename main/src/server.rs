@@ -1,8 +1,12 @@
+use std::time::Duration;
+
+use futures::future::join_all;
 use snafu::Backtrace;
 use tokio::sync::oneshot::Sender;
 use tokio::task::JoinHandle;
 
 use snafu::Snafu;
+use trace::warn;
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 #[derive(Debug, Snafu)]
@@ -51,6 +55,7 @@ impl<R> ServiceHandle<R> {
             shutdown,
         }
     }
+    /// The service is aborted if the shutdown is cancelled, e.g. timed out
     pub async fn shutdown(self, force: bool) {
         if force {
             self.join_handle.abort();
@@ -58,7 +63,17 @@ impl<R> ServiceHandle<R> {
         }
         let _ = self.shutdown.send(());
         let msg = format!("shutting down service {}", self.name);
-        self.join_handle.await.expect(&msg);
+        let mut task = AbortOnDrop(self.join_handle);
+        (&mut task.0).await.expect(&msg);
+    }
+}
+
+/// Aborts the task once dropped
+struct AbortOnDrop<R>(JoinHandle<R>);
+
+impl<R> Drop for AbortOnDrop<R> {
+    fn drop(&mut self) {
+        self.0.abort();
     }
 }
 
@@ -79,6 +94,20 @@ impl Server {
             x.stop(force).await;
         }
     }
+
+    /// Stops accepting new connections and waits for the requests in flight up to
+    /// the timeout, the services not stopped by then are aborted and their requests
+    /// are dropped
+    pub async fn shutdown(&mut self, timeout: Duration) {
+        let drain = join_all(self.services.iter_mut().map(|x| x.stop(false)));
+        if tokio::time::timeout(timeout, drain).await.is_err() {
+            warn!(
+                "Requests in flight are not finished in {:?}, they are dropped",
+                timeout
+            );
+            self.stop(true).await;
+        }
+    }
 }
 
 #[derive(Default)]
@@ -1,10 +1,13 @@
-pub fn block_waiting_ctrl_c() {
-    let (tx, rx) = std::sync::mpsc::channel();
-    ctrlc::set_handler(move || tx.send(()).expect("cannot send signal on channel."))
-        .expect("error setting Ctrl-C handler");
-    println!("blocking waiting for Ctrl-C...");
-    rx.recv().expect("could not receive from channel.");
-    println!("\nreceived Ctrl-C, CnosDB is stoping...");
+use tokio::signal::unix::{signal, SignalKind};
+
+/// Waits for SIGINT, e.g. Ctrl-C, or SIGTERM, e.g. sent on a rolling restart
+pub async fn wait_for_shutdown() {
+    let mut terminate = signal(SignalKind::terminate()).expect("listen to SIGTERM");
+    println!("waiting for Ctrl-C or SIGTERM...");
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => println!("\nreceived Ctrl-C, CnosDB is stopping..."),
+        _ = terminate.recv() => println!("\nreceived SIGTERM, CnosDB is stopping..."),
+    }
 }
 
 pub fn install_crash_handler() {
@@ -0,0 +1,256 @@
+//! TLS of the http and grpc listeners.
+//!
+//! The certificate is resolved on every handshake and the acceptor is built for
+//! every connection, so the certificate and the CA loaded again once SIGHUP is
+//! received serve the new connections without a restart, the connections
+//! established before are kept.
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use config::TLSConfig;
+use parking_lot::RwLock;
+use rustls::server::{
+    AllowAnyAnonymousOrAuthenticatedClient, AllowAnyAuthenticatedClient, ClientHello, NoClientAuth,
+    ResolvesServerCert,
+};
+use rustls::sign::{self, CertifiedKey};
+use rustls::{Certificate, PrivateKey, RootCertStore, ServerConfig};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+use tokio_stream::wrappers::ReceiverStream;
+use trace::{debug, error, info};
+
+/// A client not finishing the handshake in time is disconnected
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Handshaked connections waiting to be served
+const ACCEPT_BACKLOG: usize = 128;
+/// Delays of accepting again after a failure, e.g. out of the file descriptors,
+/// doubled from the min to the max while the failures last
+const MIN_ACCEPT_DELAY: Duration = Duration::from_millis(5);
+const MAX_ACCEPT_DELAY: Duration = Duration::from_secs(1);
+
+pub type TlsCertsRef = Arc<TlsCerts>;
+
+pub struct TlsCerts {
+    config: TLSConfig,
+    key: RwLock<Arc<CertifiedKey>>,
+    /// The CA verifying the client certificates, if configured
+    roots: RwLock<Option<RootCertStore>>,
+}
+
+impl TlsCerts {
+    pub fn open(config: &TLSConfig) -> io::Result<Self> {
+        Ok(Self {
+            key: RwLock::new(Arc::new(certified_key(config)?)),
+            roots: RwLock::new(root_cert_store(config)?),
+            config: config.clone(),
+        })
+    }
+
+    /// Loads the certificate, the private key and the CA again, the current ones
+    /// are kept if any of them fails to load
+    pub fn reload(&self) -> io::Result<()> {
+        let key = certified_key(&self.config)?;
+        let roots = root_cert_store(&self.config)?;
+        *self.key.write() = Arc::new(key);
+        *self.roots.write() = roots;
+        info!("TLS certificate {} reloaded", self.config.certificate);
+        Ok(())
+    }
+
+    /// Acceptor of the connections negotiating one of the protocols, the clients
+    /// are verified by the CA if configured. It is cheap to build, and is built for
+    /// each connection to verify the clients by the CA loaded last
+    pub fn acceptor(self: &Arc<Self>, alpn_protocols: &[&[u8]]) -> io::Result<TlsAcceptor> {
+        let builder = ServerConfig::builder().with_safe_defaults();
+        let builder = match self.roots.read().clone() {
+            Some(roots) => {
+                if self.config.require_client_cert {
+                    builder.with_client_cert_verifier(AllowAnyAuthenticatedClient::new(roots))
+                } else {
+                    builder.with_client_cert_verifier(AllowAnyAnonymousOrAuthenticatedClient::new(
+                        roots,
+                    ))
+                }
+            }
+            None if self.config.require_client_cert => {
+                return Err(invalid_data(
+                    "ca_certificate is required to verify the client certificates",
+                ))
+            }
+            None => builder.with_client_cert_verifier(NoClientAuth::new()),
+        };
+
+        let mut config = builder.with_cert_resolver(self.clone());
+        config.alpn_protocols = alpn_protocols.iter().map(|e| e.to_vec()).collect();
+        Ok(TlsAcceptor::from(Arc::new(config)))
+    }
+}
+
+impl ResolvesServerCert for TlsCerts {
+    fn resolve(&self, _client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        Some(self.key.read().clone())
+    }
+}
+
+/// Listener of the address, bound before the runtime serves it
+pub fn bind(addr: SocketAddr) -> io::Result<TcpListener> {
+    let listener = std::net::TcpListener::bind(addr)?;
+    listener.set_nonblocking(true)?;
+    TcpListener::from_std(listener)
+}
+
+/// Accepts a connection of the listener, the failures are retried after a delay
+/// so that the loop does not spin while they last
+pub async fn accept(listener: &TcpListener) -> (TcpStream, SocketAddr) {
+    let mut delay = MIN_ACCEPT_DELAY;
+    loop {
+        match listener.accept().await {
+            Ok(e) => return e,
+            Err(e) => {
+                error!("Failed to accept connection, retry in {:?}: {}", delay, e);
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(MAX_ACCEPT_DELAY);
+            }
+        }
+    }
+}
+
+/// Connections of the listener accepting TLS, the handshakes are done concurrently
+/// so that a slow client does not hold the others back. The listener is closed
+/// once the stream is dropped
+pub fn incoming(
+    listener: TcpListener,
+    certs: TlsCertsRef,
+    alpn_protocols: &'static [&'static [u8]],
+) -> ReceiverStream<io::Result<TlsStream<TcpStream>>> {
+    let (sender, receiver) = mpsc::channel(ACCEPT_BACKLOG);
+    tokio::spawn(async move {
+        loop {
+            let (stream, addr) = tokio::select! {
+                _ = sender.closed() => break,
+                res = accept(&listener) => res,
+            };
+            let acceptor = match certs.acceptor(alpn_protocols) {
+                Ok(acceptor) => acceptor,
+                Err(e) => {
+                    error!("Connection of {} is closed, no TLS acceptor: {}", addr, e);
+                    continue;
+                }
+            };
+            let sender = sender.clone();
+            tokio::spawn(async move {
+                if let Some(stream) = handshake(acceptor, stream, addr).await {
+                    let _ = sender.send(Ok(stream)).await;
+                }
+            });
+        }
+    });
+    ReceiverStream::new(receiver)
+}
+
+/// The TLS connection of the client, None if the handshake fails or times out
+pub async fn handshake(
+    acceptor: TlsAcceptor,
+    stream: TcpStream,
+    addr: SocketAddr,
+) -> Option<TlsStream<TcpStream>> {
+    match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+        Ok(Ok(stream)) => Some(stream),
+        Ok(Err(e)) => {
+            debug!("TLS handshake with {} failed: {}", addr, e);
+            None
+        }
+        Err(_) => {
+            debug!("TLS handshake with {} timed out", addr);
+            None
+        }
+    }
+}
+
+fn certified_key(config: &TLSConfig) -> io::Result<CertifiedKey> {
+    let certs = load_certs(&config.certificate)?;
+    if certs.is_empty() {
+        return Err(invalid_data(format!(
+            "no certificate in {}",
+            config.certificate
+        )));
+    }
+    let key = load_private_key(&config.private_key)?;
+    let key = sign::any_supported_type(&key).map_err(invalid_data)?;
+    Ok(CertifiedKey::new(certs, key))
+}
+
+/// The CA of the client certificates, None if not configured
+fn root_cert_store(config: &TLSConfig) -> io::Result<Option<RootCertStore>> {
+    let path = match &config.ca_certificate {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(path)? {
+        roots.add(&cert).map_err(invalid_data)?;
+    }
+    Ok(Some(roots))
+}
+
+fn load_certs(path: &str) -> io::Result<Vec<Certificate>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    Ok(rustls_pemfile::certs(&mut reader)?
+        .into_iter()
+        .map(Certificate)
+        .collect())
+}
+
+/// The first private key in the file, in the pkcs8, rsa or ec format
+fn load_private_key(path: &str) -> io::Result<PrivateKey> {
+    let mut reader = BufReader::new(File::open(path)?);
+    while let Some(item) = rustls_pemfile::read_one(&mut reader)? {
+        match item {
+            rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::ECKey(key) => return Ok(PrivateKey(key)),
+            _ => {}
+        }
+    }
+    Err(invalid_data(format!("no private key in {}", path)))
+}
+
+fn invalid_data(e: impl ToString) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn config() -> TLSConfig {
+        TLSConfig {
+            certificate: "../config/tls/server.crt".to_string(),
+            private_key: "../config/tls/server.key".to_string(),
+            ca_certificate: Some("../config/tls/ca.crt".to_string()),
+            require_client_cert: true,
+            server_name: None,
+        }
+    }
+
+    #[test]
+    fn test_tls_certs() {
+        let certs = Arc::new(TlsCerts::open(&config()).unwrap());
+        certs.acceptor(&[b"h2"]).unwrap();
+        certs.reload().unwrap();
+
+        let mut config = config();
+        config.ca_certificate = None;
+        let certs = Arc::new(TlsCerts::open(&config).unwrap());
+        assert!(certs.acceptor(&[b"h2"]).is_err());
+
+        config.private_key = config.certificate.clone();
+        assert!(TlsCerts::open(&config).is_err());
+    }
+}
@@ -0,0 +1,106 @@
+use clap::Parser;
+use meta::service::{start_meta_node, MetaTls};
+use meta::NodeId;
+use trace::init_global_tracing;
+
+/// Environment variable of the credential shared by the nodes of the cluster
+const CLUSTER_TOKEN_ENV: &str = "CNOSDB_CLUSTER_TOKEN";
+
+/// To start a meta cluster of three nodes:
+///
+/// ```bash
+/// export CNOSDB_CLUSTER_TOKEN=<secret>
+/// cnosdb-meta --id 1 --http-addr 127.0.0.1:21001 --data-path /tmp/meta/1
+/// cnosdb-meta --id 2 --http-addr 127.0.0.1:21002 --data-path /tmp/meta/2
+/// cnosdb-meta --id 3 --http-addr 127.0.0.1:21003 --data-path /tmp/meta/3
+///
+/// H="x-cnosdb-cluster-token: $CNOSDB_CLUSTER_TOKEN"
+/// curl -H "$H" -X POST http://127.0.0.1:21001/init
+/// curl -H "$H" -X POST http://127.0.0.1:21001/add-learner -d '[2, "127.0.0.1:21002"]'
+/// curl -H "$H" -X POST http://127.0.0.1:21001/add-learner -d '[3, "127.0.0.1:21003"]'
+/// curl -H "$H" -X POST http://127.0.0.1:21001/change-membership -d '[1, 2, 3]'
+/// ```
+///
+/// The data nodes use the same token, set by `cluster.meta_service_token`.
+///
+/// The api is served by https once `--tls-certificate`, `--tls-private-key` and
+/// `--tls-ca-certificate` are given, the data nodes then connect to it by
+/// `cluster.meta_service_ca_certificate`.
+#[derive(Debug, Parser)]
+#[clap(name = "cnosdb-meta")]
+struct Cli {
+    /// Id of the meta node, unique in the cluster
+    #[clap(long)]
+    id: NodeId,
+
+    /// Address of the http api, which is also used by raft
+    #[clap(long, default_value = "127.0.0.1:21001")]
+    http_addr: String,
+
+    /// Address the http api is bound to, e.g. one of the network only reachable by
+    /// the nodes of the cluster, defaults to the http address
+    #[clap(long)]
+    listen_addr: Option<String>,
+
+    /// Directory of the raft logs and the state machine
+    #[clap(long, default_value = "data/meta")]
+    data_path: String,
+
+    #[clap(long, default_value = "data/log")]
+    log_path: String,
+
+    #[clap(long, default_value = "info")]
+    log_level: String,
+
+    /// Certificate of the https api
+    #[clap(long)]
+    tls_certificate: Option<String>,
+
+    #[clap(long)]
+    tls_private_key: Option<String>,
+
+    /// CA verifying the certificates of the other meta nodes
+    #[clap(long)]
+    tls_ca_certificate: Option<String>,
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    let _trace_guard = init_global_tracing(&cli.log_path, "meta.log", &cli.log_level);
+
+    // Read from the environment, so that it is not seen in the arguments of the process
+    let token = std::env::var(CLUSTER_TOKEN_ENV).unwrap_or_default();
+    let listen_addr = cli.listen_addr.unwrap_or_else(|| cli.http_addr.clone());
+    let tls = match (
+        cli.tls_certificate,
+        cli.tls_private_key,
+        cli.tls_ca_certificate,
+    ) {
+        (Some(certificate), Some(private_key), Some(ca_certificate)) => Some(MetaTls {
+            certificate,
+            private_key,
+            ca_certificate,
+        }),
+        (None, None, None) => None,
+        _ => {
+            eprintln!(
+                "--tls-certificate, --tls-private-key and --tls-ca-certificate are required together"
+            );
+            std::process::exit(1);
+        }
+    };
+    let res = start_meta_node(
+        cli.id,
+        cli.http_addr,
+        listen_addr,
+        &cli.data_path,
+        token,
+        tls,
+    )
+    .await;
+    if let Err(e) = res {
+        eprintln!("Failed to start meta node {}: {}", cli.id, e);
+        std::process::exit(1);
+    }
+}
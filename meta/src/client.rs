@@ -0,0 +1,158 @@
+use std::time::Duration;
+
+use openraft::error::{CheckIsLeaderError, ClientWriteError, ForwardToLeader};
+use openraft::raft::ClientWriteResponse;
+use parking_lot::RwLock;
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use trace::warn;
+
+use crate::error::{MetaError, MetaResult};
+use crate::service::api::CLUSTER_TOKEN_HEADER;
+use crate::store::command::{CommandResp, ReadCommand, WriteCommand};
+use crate::{NodeId, TypeConfig};
+
+const MAX_RETRIES: usize = 8;
+const RETRY_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Sends commands to the leader of the meta nodes.
+///
+/// Requests are forwarded to the leader told by followers, and retried on
+/// the other nodes if a node is unreachable.
+#[derive(Debug)]
+pub struct MetaHttpClient {
+    addrs: Vec<String>,
+    leader: RwLock<String>,
+    /// Credential of the cluster, see [`CLUSTER_TOKEN_HEADER`]
+    token: String,
+    /// `https` once the meta nodes are verified by a CA
+    scheme: &'static str,
+    inner: reqwest::Client,
+}
+
+impl MetaHttpClient {
+    pub fn new(addrs: Vec<String>, token: String) -> Self {
+        let leader = addrs.first().cloned().unwrap_or_default();
+        Self {
+            addrs,
+            leader: RwLock::new(leader),
+            token,
+            scheme: "http",
+            inner: reqwest::Client::new(),
+        }
+    }
+
+    /// Connects to the meta nodes by https, verifying them by the CA in the file
+    pub fn with_tls(mut self, ca_certificate: &str) -> MetaResult<Self> {
+        self.scheme = "https";
+        self.inner = https_client(ca_certificate)?;
+        Ok(self)
+    }
+
+    pub async fn write(&self, cmd: &WriteCommand) -> MetaResult<CommandResp> {
+        let resp: ClientWriteResponse<TypeConfig> = self
+            .send("write", cmd, |e: &ClientWriteError<NodeId>| match e {
+                ClientWriteError::ForwardToLeader(forward) => Some(forward.clone()),
+                _ => None,
+            })
+            .await?;
+        Ok(resp.data)
+    }
+
+    pub async fn read(&self, cmd: &ReadCommand) -> MetaResult<CommandResp> {
+        self.send("read", cmd, |e: &CheckIsLeaderError<NodeId>| match e {
+            CheckIsLeaderError::ForwardToLeader(forward) => Some(forward.clone()),
+            _ => None,
+        })
+        .await
+    }
+
+    async fn send<Req, Resp, Err>(
+        &self,
+        uri: &str,
+        req: &Req,
+        forward_to: impl Fn(&Err) -> Option<ForwardToLeader<NodeId>>,
+    ) -> MetaResult<Resp>
+    where
+        Req: Serialize,
+        Resp: DeserializeOwned,
+        Err: DeserializeOwned + std::error::Error,
+    {
+        let mut last_error = MetaError::Http {
+            msg: "no meta node is configured".to_string(),
+        };
+
+        for retry in 0..MAX_RETRIES {
+            let addr = self.leader.read().clone();
+            if addr.is_empty() {
+                break;
+            }
+
+            let res = self
+                .inner
+                .post(format!("{}://{}/{}", self.scheme, addr, uri))
+                .header(CLUSTER_TOKEN_HEADER, &self.token)
+                .json(req)
+                .send()
+                .await;
+            let res: Result<Resp, Err> = match res {
+                Ok(resp) if resp.status() == StatusCode::UNAUTHORIZED => {
+                    return Err(MetaError::Http {
+                        msg: format!("the cluster token is rejected by meta node {}", addr),
+                    });
+                }
+                Ok(resp) => resp.json().await?,
+                Err(e) => {
+                    warn!("Failed to request meta node {}: {}", addr, e);
+                    last_error = e.into();
+                    self.switch_node(&addr, retry);
+                    tokio::time::sleep(RETRY_INTERVAL).await;
+                    continue;
+                }
+            };
+
+            match res {
+                Ok(resp) => return Ok(resp),
+                Err(e) => match forward_to(&e) {
+                    Some(ForwardToLeader {
+                        leader_node: Some(node),
+                        ..
+                    }) => {
+                        *self.leader.write() = node.addr;
+                    }
+                    // No leader is elected yet
+                    Some(_) => {
+                        last_error = MetaError::Raft { msg: e.to_string() };
+                        tokio::time::sleep(RETRY_INTERVAL).await;
+                    }
+                    None => return Err(MetaError::Raft { msg: e.to_string() }),
+                },
+            }
+        }
+
+        Err(last_error)
+    }
+
+    /// Tries the next configured node after the unreachable one
+    fn switch_node(&self, unreachable: &str, retry: usize) {
+        if self.addrs.is_empty() {
+            return;
+        }
+        let mut leader = self.leader.write();
+        if *leader == unreachable {
+            *leader = self.addrs[(retry + 1) % self.addrs.len()].clone();
+        }
+    }
+}
+
+/// Client of the meta nodes serving https, verified by the CA in the file
+pub(crate) fn https_client(ca_certificate: &str) -> MetaResult<reqwest::Client> {
+    let pem = std::fs::read(ca_certificate).map_err(|e| MetaError::Http {
+        msg: format!("failed to read {}: {}", ca_certificate, e),
+    })?;
+    let client = reqwest::Client::builder()
+        .add_root_certificate(reqwest::Certificate::from_pem(&pem)?)
+        .build()?;
+    Ok(client)
+}
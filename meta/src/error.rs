@@ -0,0 +1,133 @@
+use models::error_code::ErrorCode;
+use models::meta_data::{NodeId, ReplicationSetId, VnodeId};
+use models::SchemaId;
+use serde::{Deserialize, Serialize};
+use snafu::Snafu;
+
+pub type MetaResult<T> = Result<T, MetaError>;
+
+/// Errors are serializable, they are returned by the state machine to the clients
+#[derive(Snafu, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[snafu(visibility(pub))]
+pub enum MetaError {
+    #[snafu(display("Tenant {} already exists", tenant))]
+    TenantAlreadyExists { tenant: String },
+
+    #[snafu(display("Tenant {} not found", tenant))]
+    TenantNotFound { tenant: String },
+
+    #[snafu(display("Database {} already exists", database))]
+    DatabaseAlreadyExists { database: String },
+
+    #[snafu(display("Database {} not found", database))]
+    DatabaseNotFound { database: String },
+
+    #[snafu(display("Table {} already exists", table))]
+    TableAlreadyExists { table: String },
+
+    #[snafu(display("Table {} not found", table))]
+    TableNotFound { table: String },
+
+    #[snafu(display(
+        "Table {} is altered concurrently, schema version {} expected but {} found",
+        table,
+        expected,
+        actual
+    ))]
+    TableSchemaChanged {
+        table: String,
+        expected: SchemaId,
+        actual: SchemaId,
+    },
+
+    #[snafu(display("Table {} is not a tskv table", table))]
+    TableIsNotTsKv { table: String },
+
+    #[snafu(display("User {} already exists", user))]
+    UserAlreadyExists { user: String },
+
+    #[snafu(display("User {} not found", user))]
+    UserNotFound { user: String },
+
+    #[snafu(display("Role {} already exists", role))]
+    RoleAlreadyExists { role: String },
+
+    #[snafu(display("Role {} not found", role))]
+    RoleNotFound { role: String },
+
+    #[snafu(display("Task {} already exists", task))]
+    TaskAlreadyExists { task: String },
+
+    #[snafu(display("Task {} not found", task))]
+    TaskNotFound { task: String },
+
+    #[snafu(display("Data node {} not found", id))]
+    DataNodeNotFound { id: NodeId },
+
+    #[snafu(display("Data node {} still owns {} vnodes", id, vnodes))]
+    DataNodeInUse { id: NodeId, vnodes: usize },
+
+    #[snafu(display("Vnode {} not found", id))]
+    VnodeNotFound { id: VnodeId },
+
+    #[snafu(display("Replication set {} already has a replica on node {}", set, node))]
+    ReplicaAlreadyOnNode { set: ReplicationSetId, node: NodeId },
+
+    #[snafu(display(
+        "Not enough data nodes for {} replicas, only {} nodes available",
+        replica,
+        nodes
+    ))]
+    NotEnoughDataNodes { replica: u64, nodes: usize },
+
+    #[snafu(display("Storage error: {}", msg))]
+    Storage { msg: String },
+
+    #[snafu(display("Raft error: {}", msg))]
+    Raft { msg: String },
+
+    #[snafu(display("Request meta service error: {}", msg))]
+    Http { msg: String },
+
+    #[snafu(display("Unexpected response of meta service: {}", msg))]
+    UnexpectedResponse { msg: String },
+}
+
+impl MetaError {
+    pub fn error_code(&self) -> ErrorCode {
+        match self {
+            Self::DatabaseAlreadyExists { .. } => ErrorCode::DatabaseAlreadyExists,
+            Self::DatabaseNotFound { .. } => ErrorCode::DatabaseNotFound,
+            Self::TableAlreadyExists { .. } => ErrorCode::TableAlreadyExists,
+            Self::TableNotFound { .. } => ErrorCode::TableNotFound,
+            Self::TenantAlreadyExists { .. }
+            | Self::UserAlreadyExists { .. }
+            | Self::RoleAlreadyExists { .. }
+            | Self::TaskAlreadyExists { .. }
+            | Self::ReplicaAlreadyOnNode { .. } => ErrorCode::ObjectAlreadyExists,
+            Self::TenantNotFound { .. }
+            | Self::UserNotFound { .. }
+            | Self::RoleNotFound { .. }
+            | Self::TaskNotFound { .. }
+            | Self::DataNodeNotFound { .. }
+            | Self::VnodeNotFound { .. } => ErrorCode::ObjectNotFound,
+            Self::DataNodeInUse { .. } | Self::TableIsNotTsKv { .. } => ErrorCode::InvalidParameter,
+            Self::TableSchemaChanged { .. } => ErrorCode::MetaConflict,
+            Self::NotEnoughDataNodes { .. } => ErrorCode::NotEnoughReplicas,
+            Self::Storage { .. } | Self::Raft { .. } => ErrorCode::MetaInternal,
+            Self::Http { .. } | Self::UnexpectedResponse { .. } => ErrorCode::MetaUnknown,
+        }
+    }
+}
+
+impl From<sled::Error> for MetaError {
+    fn from(e: sled::Error) -> Self {
+        MetaError::Storage { msg: e.to_string() }
+    }
+}
+
+impl From<reqwest::Error> for MetaError {
+    fn from(e: reqwest::Error) -> Self {
+        MetaError::Http { msg: e.to_string() }
+    }
+}
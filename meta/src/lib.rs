@@ -0,0 +1,25 @@
+//! Meta service of a cluster, which replicates the metadata (tenants, databases, tables,
+//! users, data nodes and the placement of shards) by raft across meta nodes.
+//!
+//! The data nodes consume the metadata through [`meta_client::MetaClient`].
+pub mod client;
+pub mod error;
+pub mod meta_client;
+pub mod service;
+pub mod store;
+
+use std::sync::Arc;
+
+pub use models::meta_data::NodeId;
+use openraft::Raft;
+
+use crate::service::network::MetaNetworkFactory;
+use crate::store::command::{CommandResp, WriteCommand};
+use crate::store::Store;
+
+openraft::declare_raft_types!(
+    /// Types of the raft replicating the metadata
+    pub TypeConfig: D = WriteCommand, R = CommandResp, NodeId = NodeId
+);
+
+pub type MetaRaft = Raft<TypeConfig, MetaNetworkFactory, Arc<Store>>;
@@ -0,0 +1,636 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use models::meta_data::{
+    BucketId, BucketInfo, NodeId, NodeInfo, NodeStatus, RoleInfo, TenantMetaData, UserInfo, VnodeId,
+};
+use models::schema::{DatabaseSchema, TableSchema};
+use models::task::{TaskDefinition, TaskProgress};
+use models::{SchemaId, Timestamp};
+use parking_lot::RwLock;
+use trace::{debug, warn};
+
+use crate::client::MetaHttpClient;
+use crate::error::{MetaError, MetaResult};
+use crate::store::command::{CommandResp, ReadCommand, WriteCommand};
+
+pub type MetaClientRef = Arc<dyn MetaClient>;
+
+/// Metadata of the cluster used by data nodes.
+///
+/// Changes are written to the meta service, reads of tenant metadata are served
+/// from a local cache which is refreshed once the meta service changes.
+#[async_trait]
+pub trait MetaClient: Send + Sync + Debug {
+    async fn add_data_node(&self, node: &NodeInfo) -> MetaResult<()>;
+    async fn data_nodes(&self) -> MetaResult<Vec<NodeInfo>>;
+    async fn update_data_node_status(&self, id: NodeId, status: NodeStatus) -> MetaResult<()>;
+    /// Removes the data node, fails if it still owns vnodes
+    async fn remove_data_node(&self, id: NodeId) -> MetaResult<()>;
+
+    async fn create_user(&self, user: &UserInfo) -> MetaResult<()>;
+    async fn alter_user(&self, user: &UserInfo) -> MetaResult<()>;
+    async fn drop_user(&self, name: &str) -> MetaResult<()>;
+    async fn users(&self) -> MetaResult<Vec<UserInfo>>;
+
+    async fn create_role(&self, role: &RoleInfo) -> MetaResult<()>;
+    async fn alter_role(&self, role: &RoleInfo) -> MetaResult<()>;
+    async fn drop_role(&self, name: &str) -> MetaResult<()>;
+    async fn roles(&self) -> MetaResult<Vec<RoleInfo>>;
+
+    async fn create_task(&self, task: &TaskDefinition) -> MetaResult<()>;
+    async fn alter_task(&self, task: &TaskDefinition) -> MetaResult<()>;
+    async fn drop_task(&self, tenant: &str, name: &str) -> MetaResult<()>;
+    /// The tasks of all the tenants
+    async fn tasks(&self) -> MetaResult<Vec<TaskDefinition>>;
+    async fn set_task_progress(&self, progress: &TaskProgress) -> MetaResult<()>;
+    /// The progresses of the tasks of all the tenants
+    async fn task_progresses(&self) -> MetaResult<Vec<TaskProgress>>;
+
+    async fn create_tenant(&self, tenant: &str) -> MetaResult<()>;
+    async fn tenants(&self) -> MetaResult<Vec<String>>;
+    async fn drop_tenant(&self, tenant: &str) -> MetaResult<()>;
+
+    async fn create_db(&self, tenant: &str, schema: &DatabaseSchema) -> MetaResult<()>;
+    async fn alter_db(&self, tenant: &str, schema: &DatabaseSchema) -> MetaResult<()>;
+    async fn drop_db(&self, tenant: &str, db: &str) -> MetaResult<()>;
+
+    async fn create_table(&self, tenant: &str, schema: &TableSchema) -> MetaResult<()>;
+    /// Replaces the table if its schema version is still `version`, see [`WriteCommand::UpdateTable`]
+    async fn update_table(
+        &self,
+        tenant: &str,
+        schema: &TableSchema,
+        version: SchemaId,
+    ) -> MetaResult<()>;
+    async fn drop_table(&self, tenant: &str, db: &str, table: &str) -> MetaResult<()>;
+
+    /// Returns the bucket containing the timestamp, the bucket is created if not exists
+    async fn locate_bucket_for_write(
+        &self,
+        tenant: &str,
+        db: &str,
+        ts: Timestamp,
+    ) -> MetaResult<BucketInfo>;
+
+    /// Moves the vnode of the bucket to another data node
+    async fn move_vnode(
+        &self,
+        tenant: &str,
+        db: &str,
+        bucket: BucketId,
+        vnode: VnodeId,
+        to: NodeId,
+    ) -> MetaResult<()>;
+
+    /// Cached metadata of the tenant, `None` if the tenant is not loaded by [`Self::refresh`]
+    fn tenant_meta(&self, tenant: &str) -> Option<Arc<TenantMetaData>>;
+
+    /// Loads the metadata of the tenant from the meta service
+    async fn refresh(&self, tenant: &str) -> MetaResult<Arc<TenantMetaData>>;
+}
+
+#[derive(Debug)]
+pub struct RemoteMetaClient {
+    client: MetaHttpClient,
+    /// tenant -> metadata
+    tenants: RwLock<HashMap<String, Arc<TenantMetaData>>>,
+    /// Version of the meta service when the cache is refreshed
+    version: RwLock<u64>,
+}
+
+impl RemoteMetaClient {
+    /// `token` is the credential of the cluster checked by the meta nodes
+    pub fn new(addrs: Vec<String>, token: String) -> Self {
+        Self {
+            client: MetaHttpClient::new(addrs, token),
+            tenants: RwLock::new(HashMap::new()),
+            version: RwLock::new(0),
+        }
+    }
+
+    /// Connects to the meta nodes by https, verifying them by the CA in the file
+    pub fn with_tls(mut self, ca_certificate: &str) -> MetaResult<Self> {
+        self.client = self.client.with_tls(ca_certificate)?;
+        Ok(self)
+    }
+
+    /// Refreshes the cached tenants in the background once the meta service changes,
+    /// the tenants are loaded before it returns
+    pub async fn start_watch(self: &Arc<Self>, interval: Duration) {
+        if let Err(e) = self.sync().await {
+            warn!("Failed to load metadata: {}", e);
+        }
+        let client = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let client = match client.upgrade() {
+                    Some(client) => client,
+                    None => return,
+                };
+                if let Err(e) = client.sync().await {
+                    warn!("Failed to sync metadata: {}", e);
+                }
+            }
+        });
+    }
+
+    async fn sync(&self) -> MetaResult<()> {
+        let version = match self.client.read(&ReadCommand::Version).await? {
+            CommandResp::Version(version) => version,
+            resp => return Err(unexpected(resp)),
+        };
+        if version == *self.version.read() {
+            return Ok(());
+        }
+
+        debug!("Metadata changed to version {}", version);
+        // All the tenants are cached, so that the metadata is read without waiting
+        // for the meta service
+        let tenants = self.tenants().await?;
+        self.tenants.write().retain(|e, _| tenants.contains(e));
+        for tenant in tenants {
+            match self.refresh(&tenant).await {
+                // Dropped after listed
+                Ok(_) | Err(MetaError::TenantNotFound { .. }) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        *self.version.write() = version;
+        Ok(())
+    }
+
+    async fn write(&self, cmd: &WriteCommand) -> MetaResult<CommandResp> {
+        match self.client.write(cmd).await? {
+            CommandResp::Err(e) => Err(e),
+            resp => Ok(resp),
+        }
+    }
+
+    /// Writes the change of the tenant and refreshes its cache
+    async fn write_tenant(&self, tenant: &str, cmd: &WriteCommand) -> MetaResult<CommandResp> {
+        let resp = self.write(cmd).await?;
+        self.refresh(tenant).await?;
+        Ok(resp)
+    }
+}
+
+#[async_trait]
+impl MetaClient for RemoteMetaClient {
+    async fn add_data_node(&self, node: &NodeInfo) -> MetaResult<()> {
+        self.write(&WriteCommand::AddDataNode(node.clone()))
+            .await
+            .map(|_| ())
+    }
+
+    async fn data_nodes(&self) -> MetaResult<Vec<NodeInfo>> {
+        match self.client.read(&ReadCommand::DataNodes).await? {
+            CommandResp::DataNodes(nodes) => Ok(nodes),
+            resp => Err(unexpected(resp)),
+        }
+    }
+
+    async fn update_data_node_status(&self, id: NodeId, status: NodeStatus) -> MetaResult<()> {
+        self.write(&WriteCommand::UpdateDataNodeStatus(id, status))
+            .await
+            .map(|_| ())
+    }
+
+    async fn remove_data_node(&self, id: NodeId) -> MetaResult<()> {
+        self.write(&WriteCommand::RemoveDataNode(id))
+            .await
+            .map(|_| ())
+    }
+
+    async fn create_user(&self, user: &UserInfo) -> MetaResult<()> {
+        self.write(&WriteCommand::CreateUser(user.clone()))
+            .await
+            .map(|_| ())
+    }
+
+    async fn alter_user(&self, user: &UserInfo) -> MetaResult<()> {
+        self.write(&WriteCommand::AlterUser(user.clone()))
+            .await
+            .map(|_| ())
+    }
+
+    async fn drop_user(&self, name: &str) -> MetaResult<()> {
+        self.write(&WriteCommand::DropUser(name.to_string()))
+            .await
+            .map(|_| ())
+    }
+
+    async fn users(&self) -> MetaResult<Vec<UserInfo>> {
+        match self.client.read(&ReadCommand::Users).await? {
+            CommandResp::Users(users) => Ok(users),
+            resp => Err(unexpected(resp)),
+        }
+    }
+
+    async fn create_role(&self, role: &RoleInfo) -> MetaResult<()> {
+        self.write(&WriteCommand::CreateRole(role.clone()))
+            .await
+            .map(|_| ())
+    }
+
+    async fn alter_role(&self, role: &RoleInfo) -> MetaResult<()> {
+        self.write(&WriteCommand::AlterRole(role.clone()))
+            .await
+            .map(|_| ())
+    }
+
+    async fn drop_role(&self, name: &str) -> MetaResult<()> {
+        self.write(&WriteCommand::DropRole(name.to_string()))
+            .await
+            .map(|_| ())
+    }
+
+    async fn roles(&self) -> MetaResult<Vec<RoleInfo>> {
+        match self.client.read(&ReadCommand::Roles).await? {
+            CommandResp::Roles(roles) => Ok(roles),
+            resp => Err(unexpected(resp)),
+        }
+    }
+
+    async fn create_task(&self, task: &TaskDefinition) -> MetaResult<()> {
+        self.write(&WriteCommand::CreateTask(task.clone()))
+            .await
+            .map(|_| ())
+    }
+
+    async fn alter_task(&self, task: &TaskDefinition) -> MetaResult<()> {
+        self.write(&WriteCommand::AlterTask(task.clone()))
+            .await
+            .map(|_| ())
+    }
+
+    async fn drop_task(&self, tenant: &str, name: &str) -> MetaResult<()> {
+        self.write(&WriteCommand::DropTask(
+            tenant.to_string(),
+            name.to_string(),
+        ))
+        .await
+        .map(|_| ())
+    }
+
+    async fn tasks(&self) -> MetaResult<Vec<TaskDefinition>> {
+        match self.client.read(&ReadCommand::Tasks).await? {
+            CommandResp::Tasks(tasks) => Ok(tasks),
+            resp => Err(unexpected(resp)),
+        }
+    }
+
+    async fn set_task_progress(&self, progress: &TaskProgress) -> MetaResult<()> {
+        self.write(&WriteCommand::SetTaskProgress(progress.clone()))
+            .await
+            .map(|_| ())
+    }
+
+    async fn task_progresses(&self) -> MetaResult<Vec<TaskProgress>> {
+        match self.client.read(&ReadCommand::TaskProgresses).await? {
+            CommandResp::TaskProgresses(progresses) => Ok(progresses),
+            resp => Err(unexpected(resp)),
+        }
+    }
+
+    async fn create_tenant(&self, tenant: &str) -> MetaResult<()> {
+        let cmd = WriteCommand::CreateTenant(tenant.to_string());
+        self.write_tenant(tenant, &cmd).await.map(|_| ())
+    }
+
+    async fn tenants(&self) -> MetaResult<Vec<String>> {
+        match self.client.read(&ReadCommand::Tenants).await? {
+            CommandResp::Tenants(tenants) => Ok(tenants),
+            resp => Err(unexpected(resp)),
+        }
+    }
+
+    async fn drop_tenant(&self, tenant: &str) -> MetaResult<()> {
+        self.write(&WriteCommand::DropTenant(tenant.to_string()))
+            .await?;
+        self.tenants.write().remove(tenant);
+        Ok(())
+    }
+
+    async fn create_db(&self, tenant: &str, schema: &DatabaseSchema) -> MetaResult<()> {
+        let cmd = WriteCommand::CreateDB(tenant.to_string(), schema.clone());
+        self.write_tenant(tenant, &cmd).await.map(|_| ())
+    }
+
+    async fn alter_db(&self, tenant: &str, schema: &DatabaseSchema) -> MetaResult<()> {
+        let cmd = WriteCommand::AlterDB(tenant.to_string(), schema.clone());
+        self.write_tenant(tenant, &cmd).await.map(|_| ())
+    }
+
+    async fn drop_db(&self, tenant: &str, db: &str) -> MetaResult<()> {
+        let cmd = WriteCommand::DropDB(tenant.to_string(), db.to_string());
+        self.write_tenant(tenant, &cmd).await.map(|_| ())
+    }
+
+    async fn create_table(&self, tenant: &str, schema: &TableSchema) -> MetaResult<()> {
+        let cmd = WriteCommand::CreateTable(tenant.to_string(), schema.clone());
+        self.write_tenant(tenant, &cmd).await.map(|_| ())
+    }
+
+    async fn update_table(
+        &self,
+        tenant: &str,
+        schema: &TableSchema,
+        version: SchemaId,
+    ) -> MetaResult<()> {
+        let cmd = WriteCommand::UpdateTable(tenant.to_string(), schema.clone(), version);
+        self.write_tenant(tenant, &cmd).await.map(|_| ())
+    }
+
+    async fn drop_table(&self, tenant: &str, db: &str, table: &str) -> MetaResult<()> {
+        let cmd = WriteCommand::DropTable(tenant.to_string(), db.to_string(), table.to_string());
+        self.write_tenant(tenant, &cmd).await.map(|_| ())
+    }
+
+    async fn locate_bucket_for_write(
+        &self,
+        tenant: &str,
+        db: &str,
+        ts: Timestamp,
+    ) -> MetaResult<BucketInfo> {
+        if let Some(bucket) = self.tenant_meta(tenant).and_then(|e| {
+            e.database(db)
+                .and_then(|e| e.bucket_by_timestamp(ts).cloned())
+        }) {
+            return Ok(bucket);
+        }
+
+        let cmd = WriteCommand::CreateBucket {
+            tenant: tenant.to_string(),
+            db: db.to_string(),
+            ts,
+        };
+        match self.write_tenant(tenant, &cmd).await? {
+            CommandResp::Bucket(bucket) => Ok(bucket),
+            resp => Err(unexpected(resp)),
+        }
+    }
+
+    async fn move_vnode(
+        &self,
+        tenant: &str,
+        db: &str,
+        bucket: BucketId,
+        vnode: VnodeId,
+        to: NodeId,
+    ) -> MetaResult<()> {
+        let cmd = WriteCommand::MoveVnode {
+            tenant: tenant.to_string(),
+            db: db.to_string(),
+            bucket,
+            vnode,
+            to,
+        };
+        self.write_tenant(tenant, &cmd).await.map(|_| ())
+    }
+
+    fn tenant_meta(&self, tenant: &str) -> Option<Arc<TenantMetaData>> {
+        self.tenants.read().get(tenant).cloned()
+    }
+
+    async fn refresh(&self, tenant: &str) -> MetaResult<Arc<TenantMetaData>> {
+        let meta = match self
+            .client
+            .read(&ReadCommand::TenantMeta(tenant.to_string()))
+            .await?
+        {
+            CommandResp::TenantMeta(meta) => Arc::new(meta),
+            CommandResp::Err(e) => return Err(e),
+            resp => return Err(unexpected(resp)),
+        };
+
+        let mut tenants = self.tenants.write();
+        // A concurrent refresh may have loaded a newer version
+        match tenants.get(tenant) {
+            Some(cached) if cached.version > meta.version => Ok(cached.clone()),
+            _ => {
+                tenants.insert(tenant.to_string(), meta.clone());
+                Ok(meta)
+            }
+        }
+    }
+}
+
+/// Serves the metadata set by the tests, changes other than data nodes and vnodes are ignored
+#[derive(Debug, Default)]
+pub struct MockMetaClient {
+    pub data_nodes: RwLock<Vec<NodeInfo>>,
+    pub tenants: RwLock<HashMap<String, Arc<TenantMetaData>>>,
+}
+
+impl MockMetaClient {
+    pub fn new(data_nodes: Vec<NodeInfo>) -> Self {
+        Self {
+            data_nodes: RwLock::new(data_nodes),
+            tenants: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn set_tenant_meta(&self, tenant: &str, meta: TenantMetaData) {
+        self.tenants
+            .write()
+            .insert(tenant.to_string(), Arc::new(meta));
+    }
+}
+
+#[async_trait]
+impl MetaClient for MockMetaClient {
+    async fn add_data_node(&self, node: &NodeInfo) -> MetaResult<()> {
+        let mut nodes = self.data_nodes.write();
+        nodes.retain(|e| e.id != node.id);
+        nodes.push(node.clone());
+        Ok(())
+    }
+
+    async fn data_nodes(&self) -> MetaResult<Vec<NodeInfo>> {
+        Ok(self.data_nodes.read().clone())
+    }
+
+    async fn update_data_node_status(&self, id: NodeId, status: NodeStatus) -> MetaResult<()> {
+        let mut nodes = self.data_nodes.write();
+        let node = nodes
+            .iter_mut()
+            .find(|e| e.id == id)
+            .ok_or(MetaError::DataNodeNotFound { id })?;
+        node.status = status;
+        Ok(())
+    }
+
+    async fn remove_data_node(&self, id: NodeId) -> MetaResult<()> {
+        self.data_nodes.write().retain(|e| e.id != id);
+        Ok(())
+    }
+
+    async fn create_user(&self, _user: &UserInfo) -> MetaResult<()> {
+        Ok(())
+    }
+
+    async fn alter_user(&self, _user: &UserInfo) -> MetaResult<()> {
+        Ok(())
+    }
+
+    async fn drop_user(&self, _name: &str) -> MetaResult<()> {
+        Ok(())
+    }
+
+    async fn users(&self) -> MetaResult<Vec<UserInfo>> {
+        Ok(vec![])
+    }
+
+    async fn create_role(&self, _role: &RoleInfo) -> MetaResult<()> {
+        Ok(())
+    }
+
+    async fn alter_role(&self, _role: &RoleInfo) -> MetaResult<()> {
+        Ok(())
+    }
+
+    async fn drop_role(&self, _name: &str) -> MetaResult<()> {
+        Ok(())
+    }
+
+    async fn roles(&self) -> MetaResult<Vec<RoleInfo>> {
+        Ok(vec![])
+    }
+
+    async fn create_task(&self, _task: &TaskDefinition) -> MetaResult<()> {
+        Ok(())
+    }
+
+    async fn alter_task(&self, _task: &TaskDefinition) -> MetaResult<()> {
+        Ok(())
+    }
+
+    async fn drop_task(&self, _tenant: &str, _name: &str) -> MetaResult<()> {
+        Ok(())
+    }
+
+    async fn tasks(&self) -> MetaResult<Vec<TaskDefinition>> {
+        Ok(vec![])
+    }
+
+    async fn set_task_progress(&self, _progress: &TaskProgress) -> MetaResult<()> {
+        Ok(())
+    }
+
+    async fn task_progresses(&self) -> MetaResult<Vec<TaskProgress>> {
+        Ok(vec![])
+    }
+
+    async fn create_tenant(&self, _tenant: &str) -> MetaResult<()> {
+        Ok(())
+    }
+
+    async fn tenants(&self) -> MetaResult<Vec<String>> {
+        Ok(self.tenants.read().keys().cloned().collect())
+    }
+
+    async fn drop_tenant(&self, _tenant: &str) -> MetaResult<()> {
+        Ok(())
+    }
+
+    async fn create_db(&self, _tenant: &str, _schema: &DatabaseSchema) -> MetaResult<()> {
+        Ok(())
+    }
+
+    async fn alter_db(&self, _tenant: &str, _schema: &DatabaseSchema) -> MetaResult<()> {
+        Ok(())
+    }
+
+    async fn drop_db(&self, _tenant: &str, _db: &str) -> MetaResult<()> {
+        Ok(())
+    }
+
+    async fn create_table(&self, _tenant: &str, _schema: &TableSchema) -> MetaResult<()> {
+        Ok(())
+    }
+
+    async fn update_table(
+        &self,
+        _tenant: &str,
+        _schema: &TableSchema,
+        _version: SchemaId,
+    ) -> MetaResult<()> {
+        Ok(())
+    }
+
+    async fn drop_table(&self, _tenant: &str, _db: &str, _table: &str) -> MetaResult<()> {
+        Ok(())
+    }
+
+    async fn locate_bucket_for_write(
+        &self,
+        tenant: &str,
+        db: &str,
+        ts: Timestamp,
+    ) -> MetaResult<BucketInfo> {
+        self.tenant_meta(tenant)
+            .and_then(|e| {
+                e.database(db)
+                    .and_then(|e| e.bucket_by_timestamp(ts).cloned())
+            })
+            .ok_or_else(|| MetaError::DatabaseNotFound {
+                database: db.to_string(),
+            })
+    }
+
+    async fn move_vnode(
+        &self,
+        tenant: &str,
+        db: &str,
+        bucket: BucketId,
+        vnode: VnodeId,
+        to: NodeId,
+    ) -> MetaResult<()> {
+        let mut tenants = self.tenants.write();
+        let meta = tenants
+            .get_mut(tenant)
+            .ok_or_else(|| MetaError::TenantNotFound {
+                tenant: tenant.to_string(),
+            })?;
+        let mut changed = meta.as_ref().clone();
+        let found = changed
+            .dbs
+            .get_mut(db)
+            .into_iter()
+            .flat_map(|e| e.buckets.iter_mut())
+            .filter(|e| e.id == bucket)
+            .flat_map(|e| e.shard_group.iter_mut())
+            .flat_map(|e| e.vnodes.iter_mut())
+            .find(|e| e.id == vnode)
+            .map(|e| e.node_id = to);
+        if found.is_none() {
+            return Err(MetaError::VnodeNotFound { id: vnode });
+        }
+        changed.version += 1;
+        *meta = Arc::new(changed);
+        Ok(())
+    }
+
+    fn tenant_meta(&self, tenant: &str) -> Option<Arc<TenantMetaData>> {
+        self.tenants.read().get(tenant).cloned()
+    }
+
+    async fn refresh(&self, tenant: &str) -> MetaResult<Arc<TenantMetaData>> {
+        self.tenant_meta(tenant)
+            .ok_or_else(|| MetaError::TenantNotFound {
+                tenant: tenant.to_string(),
+            })
+    }
+}
+
+fn unexpected(resp: CommandResp) -> MetaError {
+    MetaError::UnexpectedResponse {
+        msg: format!("{:?}", resp),
+    }
+}
@@ -0,0 +1,193 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use openraft::raft::{AppendEntriesRequest, InstallSnapshotRequest, VoteRequest};
+use openraft::Node;
+use serde::de::DeserializeOwned;
+use warp::http::StatusCode;
+use warp::{Filter, Rejection, Reply};
+
+use crate::service::MetaApp;
+use crate::store::command::{ReadCommand, WriteCommand};
+use crate::{NodeId, TypeConfig};
+
+/// Header of the credential shared by the meta nodes and the data nodes of a cluster
+pub const CLUSTER_TOKEN_HEADER: &str = "x-cnosdb-cluster-token";
+
+/// Http api of a meta node, every request must carry the token of the cluster
+/// in [`CLUSTER_TOKEN_HEADER`]
+///
+/// - management: `/init`, `/add-learner`, `/change-membership`, `/metrics`
+/// - clients: `/write`, `/read`
+/// - raft rpcs: `/raft-vote`, `/raft-append`, `/raft-snapshot`
+pub fn routes(app: Arc<MetaApp>) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    authorized(app.token.clone())
+        .and(
+            init(app.clone())
+                .or(add_learner(app.clone()))
+                .or(change_membership(app.clone()))
+                .or(metrics(app.clone()))
+                .or(write(app.clone()))
+                .or(read(app.clone()))
+                .or(raft_vote(app.clone()))
+                .or(raft_append(app.clone()))
+                .or(raft_snapshot(app)),
+        )
+        .recover(handle_rejection)
+}
+
+#[derive(Debug)]
+struct Unauthorized;
+
+impl warp::reject::Reject for Unauthorized {}
+
+/// Rejects the requests without the token of the cluster
+fn authorized(token: Arc<str>) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::header::optional::<String>(CLUSTER_TOKEN_HEADER)
+        .and_then(move |given: Option<String>| {
+            let token = token.clone();
+            async move {
+                match given {
+                    Some(given) if token_eq(given.as_bytes(), token.as_bytes()) => Ok(()),
+                    _ => Err(warp::reject::custom(Unauthorized)),
+                }
+            }
+        })
+        .untuple_one()
+}
+
+/// Compares in constant time, the token is not guessed by the time of the responses
+fn token_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+async fn handle_rejection(err: Rejection) -> Result<impl Reply, Rejection> {
+    if err.find::<Unauthorized>().is_some() {
+        Ok(warp::reply::with_status(
+            "invalid cluster token",
+            StatusCode::UNAUTHORIZED,
+        ))
+    } else {
+        Err(err)
+    }
+}
+
+fn with_app(
+    app: Arc<MetaApp>,
+) -> impl Filter<Extract = (Arc<MetaApp>,), Error = Infallible> + Clone {
+    warp::any().map(move || app.clone())
+}
+
+fn post<T: DeserializeOwned + Send>(
+    path: &'static str,
+    app: Arc<MetaApp>,
+) -> impl Filter<Extract = (Arc<MetaApp>, T), Error = Rejection> + Clone {
+    warp::post()
+        .and(warp::path(path))
+        .and(warp::path::end())
+        .and(with_app(app))
+        .and(warp::body::json())
+}
+
+/// Initializes a cluster with this node as the only member
+fn init(app: Arc<MetaApp>) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    warp::post()
+        .and(warp::path("init"))
+        .and(warp::path::end())
+        .and(with_app(app))
+        .and_then(|app: Arc<MetaApp>| async move {
+            let nodes = BTreeMap::from([(app.id, Some(Node::new(app.addr.clone())))]);
+            let res = app.raft.initialize(nodes).await;
+            Ok::<_, Rejection>(warp::reply::json(&res))
+        })
+}
+
+fn add_learner(app: Arc<MetaApp>) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    post("add-learner", app).and_then(
+        |app: Arc<MetaApp>, (id, addr): (NodeId, String)| async move {
+            let res = app.raft.add_learner(id, Some(Node::new(addr)), true).await;
+            Ok::<_, Rejection>(warp::reply::json(&res))
+        },
+    )
+}
+
+fn change_membership(
+    app: Arc<MetaApp>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    post("change-membership", app).and_then(
+        |app: Arc<MetaApp>, members: BTreeSet<NodeId>| async move {
+            let res = app.raft.change_membership(members, true, false).await;
+            Ok::<_, Rejection>(warp::reply::json(&res))
+        },
+    )
+}
+
+fn metrics(app: Arc<MetaApp>) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    warp::get()
+        .and(warp::path("metrics"))
+        .and(warp::path::end())
+        .and(with_app(app))
+        .map(|app: Arc<MetaApp>| {
+            let metrics = app.raft.metrics().borrow().clone();
+            warp::reply::json(&metrics)
+        })
+}
+
+fn write(app: Arc<MetaApp>) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    post("write", app).and_then(|app: Arc<MetaApp>, cmd: WriteCommand| async move {
+        let res = app.raft.client_write(cmd).await;
+        Ok::<_, Rejection>(warp::reply::json(&res))
+    })
+}
+
+/// Reads are served by the leader to be linearizable
+fn read(app: Arc<MetaApp>) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    post("read", app).and_then(|app: Arc<MetaApp>, cmd: ReadCommand| async move {
+        let res = match app.raft.is_leader().await {
+            Ok(_) => Ok(app.store.read(&cmd).await),
+            Err(e) => Err(e),
+        };
+        Ok::<_, Rejection>(warp::reply::json(&res))
+    })
+}
+
+fn raft_vote(app: Arc<MetaApp>) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    post("raft-vote", app).and_then(|app: Arc<MetaApp>, req: VoteRequest<NodeId>| async move {
+        let res = app.raft.vote(req).await;
+        Ok::<_, Rejection>(warp::reply::json(&res))
+    })
+}
+
+fn raft_append(app: Arc<MetaApp>) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    post("raft-append", app).and_then(
+        |app: Arc<MetaApp>, req: AppendEntriesRequest<TypeConfig>| async move {
+            let res = app.raft.append_entries(req).await;
+            Ok::<_, Rejection>(warp::reply::json(&res))
+        },
+    )
+}
+
+fn raft_snapshot(
+    app: Arc<MetaApp>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    post("raft-snapshot", app).and_then(
+        |app: Arc<MetaApp>, req: InstallSnapshotRequest<TypeConfig>| async move {
+            let res = app.raft.install_snapshot(req).await;
+            Ok::<_, Rejection>(warp::reply::json(&res))
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_eq() {
+        assert!(token_eq(b"secret", b"secret"));
+        assert!(!token_eq(b"secret", b"secreT"));
+        assert!(!token_eq(b"secret", b"secret1"));
+        assert!(!token_eq(b"", b"secret"));
+    }
+}
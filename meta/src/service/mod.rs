@@ -0,0 +1,101 @@
+pub mod api;
+pub mod network;
+
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+
+use openraft::{Config, Raft};
+use trace::info;
+
+use crate::error::{MetaError, MetaResult};
+use crate::service::network::MetaNetworkFactory;
+use crate::store::Store;
+use crate::{MetaRaft, NodeId};
+
+pub struct MetaApp {
+    pub id: NodeId,
+    /// Http address of this meta node, which is also used by raft
+    pub addr: String,
+    pub raft: MetaRaft,
+    pub store: Arc<Store>,
+    /// Credential of the cluster required by the http api
+    pub token: Arc<str>,
+}
+
+/// TLS of the http api, the meta nodes and the data nodes verify the certificate
+/// of a meta node by the CA
+#[derive(Debug, Clone)]
+pub struct MetaTls {
+    pub certificate: String,
+    pub private_key: String,
+    pub ca_certificate: String,
+}
+
+/// Starts a meta node and serves the http api until the process exits.
+///
+/// A new cluster is initialized by `POST /init` on one node, other nodes are added
+/// by `POST /add-learner` and `POST /change-membership` on the leader.
+///
+/// The api is bound to `listen_addr`, which should be reachable by the nodes of the
+/// cluster only, and `addr` is the address told to the other nodes. The api is
+/// served by https if `tls` is given.
+pub async fn start_meta_node(
+    id: NodeId,
+    addr: String,
+    listen_addr: String,
+    path: impl AsRef<Path>,
+    token: String,
+    tls: Option<MetaTls>,
+) -> MetaResult<()> {
+    if token.is_empty() {
+        return Err(MetaError::Http {
+            msg: "the token of the cluster is not set".to_string(),
+        });
+    }
+    let config = Config {
+        heartbeat_interval: 500,
+        election_timeout_min: 1500,
+        election_timeout_max: 3000,
+        ..Default::default()
+    }
+    .validate()
+    .map_err(|e| MetaError::Raft { msg: e.to_string() })?;
+
+    let mut network = MetaNetworkFactory::new(token.clone());
+    if let Some(tls) = &tls {
+        network = network.with_tls(&tls.ca_certificate)?;
+    }
+
+    let store = Arc::new(Store::open(path)?);
+    let raft = Raft::new(id, Arc::new(config), network, store.clone());
+    let app = Arc::new(MetaApp {
+        id,
+        addr: addr.clone(),
+        raft,
+        store,
+        token: token.into(),
+    });
+
+    let socket: SocketAddr = listen_addr
+        .parse()
+        .map_err(|e: std::net::AddrParseError| MetaError::Http { msg: e.to_string() })?;
+    let server = warp::serve(api::routes(app));
+    match tls {
+        Some(tls) => {
+            info!("Meta node {} is listening on {}, tls enabled", id, socket);
+            server
+                .tls()
+                .cert_path(&tls.certificate)
+                .key_path(&tls.private_key)
+                .run(socket)
+                .await
+        }
+        None => {
+            info!("Meta node {} is listening on {}", id, socket);
+            server.run(socket).await
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,134 @@
+use async_trait::async_trait;
+use openraft::error::{
+    AppendEntriesError, InstallSnapshotError, NetworkError, RPCError, RemoteError, VoteError,
+};
+use openraft::raft::{
+    AppendEntriesRequest, AppendEntriesResponse, InstallSnapshotRequest, InstallSnapshotResponse,
+    VoteRequest, VoteResponse,
+};
+use openraft::{Node, RaftNetwork, RaftNetworkFactory};
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::client::https_client;
+use crate::error::MetaResult;
+use crate::service::api::CLUSTER_TOKEN_HEADER;
+use crate::{NodeId, TypeConfig};
+
+/// Sends the raft rpcs to the http api of other meta nodes
+#[derive(Clone)]
+pub struct MetaNetworkFactory {
+    client: reqwest::Client,
+    /// Credential of the cluster, see [`CLUSTER_TOKEN_HEADER`]
+    token: String,
+    /// `https` once the meta nodes are verified by a CA
+    scheme: &'static str,
+}
+
+impl MetaNetworkFactory {
+    pub fn new(token: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            token,
+            scheme: "http",
+        }
+    }
+
+    /// Connects to the other meta nodes by https, verifying them by the CA in the file
+    pub fn with_tls(mut self, ca_certificate: &str) -> MetaResult<Self> {
+        self.scheme = "https";
+        self.client = https_client(ca_certificate)?;
+        Ok(self)
+    }
+}
+
+#[async_trait]
+impl RaftNetworkFactory<TypeConfig> for MetaNetworkFactory {
+    type Network = MetaNetwork;
+
+    async fn connect(&mut self, target: NodeId, node: Option<&Node>) -> Self::Network {
+        MetaNetwork {
+            client: self.client.clone(),
+            token: self.token.clone(),
+            scheme: self.scheme,
+            target,
+            addr: node.map(|e| e.addr.clone()),
+        }
+    }
+}
+
+pub struct MetaNetwork {
+    client: reqwest::Client,
+    token: String,
+    scheme: &'static str,
+    target: NodeId,
+    addr: Option<String>,
+}
+
+impl MetaNetwork {
+    async fn send_rpc<Req, Resp, Err>(
+        &self,
+        uri: &str,
+        req: Req,
+    ) -> Result<Resp, RPCError<NodeId, Err>>
+    where
+        Req: Serialize,
+        Err: std::error::Error + DeserializeOwned,
+        Resp: DeserializeOwned,
+    {
+        let addr = self.addr.as_ref().ok_or_else(|| {
+            RPCError::Network(NetworkError::new(&std::io::Error::new(
+                std::io::ErrorKind::AddrNotAvailable,
+                format!("address of meta node {} is unknown", self.target),
+            )))
+        })?;
+        let url = format!("{}://{}/{}", self.scheme, addr, uri);
+
+        let resp = self
+            .client
+            .post(url)
+            .header(CLUSTER_TOKEN_HEADER, &self.token)
+            .json(&req)
+            .send()
+            .await
+            .map_err(|e| RPCError::Network(NetworkError::new(&e)))?;
+        if resp.status() == StatusCode::UNAUTHORIZED {
+            return Err(RPCError::Network(NetworkError::new(&std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                format!("the cluster token is rejected by meta node {}", self.target),
+            ))));
+        }
+        let res: Result<Resp, Err> = resp
+            .json()
+            .await
+            .map_err(|e| RPCError::Network(NetworkError::new(&e)))?;
+
+        res.map_err(|e| RPCError::RemoteError(RemoteError::new(self.target, e)))
+    }
+}
+
+#[async_trait]
+impl RaftNetwork<TypeConfig> for MetaNetwork {
+    async fn send_append_entries(
+        &mut self,
+        req: AppendEntriesRequest<TypeConfig>,
+    ) -> Result<AppendEntriesResponse<NodeId>, RPCError<NodeId, AppendEntriesError<NodeId>>> {
+        self.send_rpc("raft-append", req).await
+    }
+
+    async fn send_install_snapshot(
+        &mut self,
+        req: InstallSnapshotRequest<TypeConfig>,
+    ) -> Result<InstallSnapshotResponse<NodeId>, RPCError<NodeId, InstallSnapshotError<NodeId>>>
+    {
+        self.send_rpc("raft-snapshot", req).await
+    }
+
+    async fn send_vote(
+        &mut self,
+        req: VoteRequest<NodeId>,
+    ) -> Result<VoteResponse<NodeId>, RPCError<NodeId, VoteError<NodeId>>> {
+        self.send_rpc("raft-vote", req).await
+    }
+}
@@ -0,0 +1,130 @@
+use models::meta_data::{
+    BucketId, BucketInfo, NodeId, NodeInfo, NodeStatus, RoleInfo, TenantMetaData, UserInfo, VnodeId,
+};
+use models::schema::{DatabaseSchema, TableSchema};
+use models::task::{TaskDefinition, TaskProgress};
+use models::{SchemaId, Timestamp};
+use serde::{Deserialize, Serialize};
+
+use crate::error::MetaError;
+
+/// Changes of the metadata, replicated by raft and applied to the state machine
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum WriteCommand {
+    AddDataNode(NodeInfo),
+    UpdateDataNodeStatus(NodeId, NodeStatus),
+    /// Removes the data node which owns no vnode
+    RemoveDataNode(NodeId),
+    CreateUser(UserInfo),
+    /// Replaces the user of the same name
+    AlterUser(UserInfo),
+    DropUser(String),
+    CreateRole(RoleInfo),
+    /// Replaces the role of the same name
+    AlterRole(RoleInfo),
+    DropRole(String),
+    CreateTask(TaskDefinition),
+    /// Replaces the task of the same tenant and name
+    AlterTask(TaskDefinition),
+    // tenant, task
+    DropTask(String, String),
+    /// Replaces the progress of the task, which must exist
+    SetTaskProgress(TaskProgress),
+    CreateTenant(String),
+    DropTenant(String),
+    // tenant, database
+    CreateDB(String, DatabaseSchema),
+    AlterDB(String, DatabaseSchema),
+    // tenant, database
+    DropDB(String, String),
+    // tenant, table
+    CreateTable(String, TableSchema),
+    /// Replaces the table only if its schema version is still the given one,
+    /// the version of the new schema is the next one
+    UpdateTable(String, TableSchema, SchemaId),
+    // tenant, database, table
+    DropTable(String, String, String),
+    /// Creates the bucket containing the timestamp if it does not exist
+    CreateBucket {
+        tenant: String,
+        db: String,
+        ts: Timestamp,
+    },
+    /// Moves the replica of a shard to another data node
+    MoveVnode {
+        tenant: String,
+        db: String,
+        bucket: BucketId,
+        vnode: VnodeId,
+        to: NodeId,
+    },
+}
+
+impl WriteCommand {
+    /// The tenant of which the metadata is changed by the command
+    pub fn tenant(&self) -> Option<&str> {
+        match self {
+            WriteCommand::CreateDB(tenant, _)
+            | WriteCommand::AlterDB(tenant, _)
+            | WriteCommand::DropDB(tenant, _)
+            | WriteCommand::CreateTable(tenant, _)
+            | WriteCommand::UpdateTable(tenant, _, _)
+            | WriteCommand::DropTable(tenant, _, _)
+            | WriteCommand::CreateBucket { tenant, .. }
+            | WriteCommand::MoveVnode { tenant, .. } => Some(tenant),
+            _ => None,
+        }
+    }
+}
+
+/// Reads of the metadata, served by the leader
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum ReadCommand {
+    /// Version of the whole metadata, increased by each change
+    Version,
+    DataNodes,
+    DataNode(NodeId),
+    Users,
+    Roles,
+    Tasks,
+    TaskProgresses,
+    Tenants,
+    TenantMeta(String),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum CommandResp {
+    Ok,
+    Version(u64),
+    DataNodes(Vec<NodeInfo>),
+    DataNode(NodeInfo),
+    Users(Vec<UserInfo>),
+    Roles(Vec<RoleInfo>),
+    Tasks(Vec<TaskDefinition>),
+    TaskProgresses(Vec<TaskProgress>),
+    Tenants(Vec<String>),
+    TenantMeta(TenantMetaData),
+    Bucket(BucketInfo),
+    Err(MetaError),
+}
+
+impl<T: Into<CommandResp>> From<Result<T, MetaError>> for CommandResp {
+    fn from(res: Result<T, MetaError>) -> Self {
+        match res {
+            Ok(resp) => resp.into(),
+            Err(e) => CommandResp::Err(e),
+        }
+    }
+}
+
+impl From<()> for CommandResp {
+    fn from(_: ()) -> Self {
+        CommandResp::Ok
+    }
+}
+
+impl From<BucketInfo> for CommandResp {
+    fn from(bucket: BucketInfo) -> Self {
+        CommandResp::Bucket(bucket)
+    }
+}
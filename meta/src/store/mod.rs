@@ -0,0 +1,439 @@
+//! Raft storage of the meta service, logs and the state machine are persisted by sled.
+//!
+//! - tree `logs`: index -> log entry
+//! - tree `store`: vote, last purged log id, state machine and the current snapshot
+pub mod command;
+pub mod state_machine;
+
+use std::fmt::Debug;
+use std::io::Cursor;
+use std::ops::RangeBounds;
+use std::path::Path;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use models::meta_data::NodeId;
+use openraft::storage::{LogState, Snapshot};
+use openraft::{
+    AnyError, EffectiveMembership, Entry, EntryPayload, ErrorSubject, ErrorVerb, LogId,
+    RaftLogReader, RaftSnapshotBuilder, RaftStorage, SnapshotMeta, StateMachineChanges,
+    StorageError, StorageIOError, Vote,
+};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::error::{MetaError, MetaResult};
+use crate::store::command::{CommandResp, ReadCommand};
+use crate::store::state_machine::StateMachine;
+use crate::TypeConfig;
+
+const VOTE_KEY: &str = "vote";
+const LAST_PURGED_KEY: &str = "last_purged_log_id";
+const STATE_MACHINE_KEY: &str = "state_machine";
+const SNAPSHOT_KEY: &str = "snapshot";
+
+type StorageResult<T> = Result<T, StorageError<NodeId>>;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StoredSnapshot {
+    pub meta: SnapshotMeta<NodeId>,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub struct Store {
+    db: sled::Db,
+    logs: sled::Tree,
+    store: sled::Tree,
+    state_machine: RwLock<StateMachine>,
+    snapshot_idx: parking_lot::Mutex<u64>,
+}
+
+impl Store {
+    pub fn open(path: impl AsRef<Path>) -> MetaResult<Self> {
+        let db = sled::open(path)?;
+        let logs = db.open_tree("logs")?;
+        let store = db.open_tree("store")?;
+        let state_machine = read_value(&store, STATE_MACHINE_KEY, ErrorSubject::StateMachine)
+            .map_err(|e| MetaError::Storage { msg: e.to_string() })?
+            .unwrap_or_default();
+
+        Ok(Self {
+            db,
+            logs,
+            store,
+            state_machine: RwLock::new(state_machine),
+            snapshot_idx: parking_lot::Mutex::new(0),
+        })
+    }
+
+    /// Reads the state machine of this node, the caller ensures the node is the leader
+    /// if the read should be linearizable.
+    pub async fn read(&self, cmd: &ReadCommand) -> CommandResp {
+        self.state_machine.read().await.data.read(cmd)
+    }
+
+    async fn flush(&self) -> StorageResult<()> {
+        self.db.flush_async().await.map_err(|e| {
+            StorageIOError::new(ErrorSubject::Store, ErrorVerb::Write, AnyError::new(&e))
+        })?;
+        Ok(())
+    }
+
+    fn save_state_machine(&self, sm: &StateMachine) -> StorageResult<()> {
+        write_value(
+            &self.store,
+            STATE_MACHINE_KEY,
+            sm,
+            ErrorSubject::StateMachine,
+        )
+    }
+}
+
+fn read_value<T: DeserializeOwned>(
+    tree: &sled::Tree,
+    key: &str,
+    subject: ErrorSubject<NodeId>,
+) -> StorageResult<Option<T>> {
+    let value = tree
+        .get(key)
+        .map_err(|e| StorageIOError::new(subject.clone(), ErrorVerb::Read, AnyError::new(&e)))?;
+    match value {
+        Some(value) => serde_json::from_slice(&value)
+            .map(Some)
+            .map_err(|e| StorageIOError::new(subject, ErrorVerb::Read, AnyError::new(&e)).into()),
+        None => Ok(None),
+    }
+}
+
+fn write_value<T: Serialize>(
+    tree: &sled::Tree,
+    key: &str,
+    value: &T,
+    subject: ErrorSubject<NodeId>,
+) -> StorageResult<()> {
+    let value = serde_json::to_vec(value)
+        .map_err(|e| StorageIOError::new(subject.clone(), ErrorVerb::Write, AnyError::new(&e)))?;
+    tree.insert(key, value)
+        .map_err(|e| StorageIOError::new(subject, ErrorVerb::Write, AnyError::new(&e)))?;
+    Ok(())
+}
+
+fn log_key(index: u64) -> [u8; 8] {
+    index.to_be_bytes()
+}
+
+fn logs_error(verb: ErrorVerb, e: &(impl std::error::Error + 'static)) -> StorageError<NodeId> {
+    StorageIOError::new(ErrorSubject::Logs, verb, AnyError::new(e)).into()
+}
+
+#[async_trait]
+impl RaftLogReader<TypeConfig> for Arc<Store> {
+    async fn get_log_state(&mut self) -> StorageResult<LogState<TypeConfig>> {
+        let last_purged_log_id: Option<LogId<NodeId>> =
+            read_value(&self.store, LAST_PURGED_KEY, ErrorSubject::Logs)?;
+        let last = match self
+            .logs
+            .last()
+            .map_err(|e| logs_error(ErrorVerb::Read, &e))?
+        {
+            Some((_, value)) => {
+                let entry: Entry<TypeConfig> =
+                    serde_json::from_slice(&value).map_err(|e| logs_error(ErrorVerb::Read, &e))?;
+                Some(entry.log_id)
+            }
+            None => last_purged_log_id,
+        };
+
+        Ok(LogState {
+            last_purged_log_id,
+            last_log_id: last,
+        })
+    }
+
+    async fn try_get_log_entries<RB: RangeBounds<u64> + Clone + Debug + Send + Sync>(
+        &mut self,
+        range: RB,
+    ) -> StorageResult<Vec<Entry<TypeConfig>>> {
+        let start = match range.start_bound() {
+            std::ops::Bound::Included(i) => *i,
+            std::ops::Bound::Excluded(i) => *i + 1,
+            std::ops::Bound::Unbounded => 0,
+        };
+
+        let mut entries = vec![];
+        for item in self.logs.range(log_key(start)..) {
+            let (key, value) = item.map_err(|e| logs_error(ErrorVerb::Read, &e))?;
+            let mut index = [0; 8];
+            index.copy_from_slice(&key);
+            if !range.contains(&u64::from_be_bytes(index)) {
+                break;
+            }
+            let entry: Entry<TypeConfig> =
+                serde_json::from_slice(&value).map_err(|e| logs_error(ErrorVerb::Read, &e))?;
+            entries.push(entry);
+        }
+
+        Ok(entries)
+    }
+}
+
+#[async_trait]
+impl RaftSnapshotBuilder<TypeConfig, Cursor<Vec<u8>>> for Arc<Store> {
+    async fn build_snapshot(&mut self) -> StorageResult<Snapshot<NodeId, Cursor<Vec<u8>>>> {
+        let (data, last_applied_log) = {
+            let sm = self.state_machine.read().await;
+            let data = serde_json::to_vec(&*sm).map_err(|e| {
+                StorageIOError::new(
+                    ErrorSubject::StateMachine,
+                    ErrorVerb::Read,
+                    AnyError::new(&e),
+                )
+            })?;
+            (data, sm.last_applied_log)
+        };
+
+        let last_applied_log = match last_applied_log {
+            Some(log_id) => log_id,
+            None => {
+                return Err(StorageIOError::new(
+                    ErrorSubject::StateMachine,
+                    ErrorVerb::Read,
+                    AnyError::error("can not build snapshot of an empty state machine"),
+                )
+                .into())
+            }
+        };
+
+        let snapshot_idx = {
+            let mut idx = self.snapshot_idx.lock();
+            *idx += 1;
+            *idx
+        };
+        let meta = SnapshotMeta {
+            last_log_id: last_applied_log,
+            snapshot_id: format!(
+                "{}-{}-{}",
+                last_applied_log.leader_id, last_applied_log.index, snapshot_idx
+            ),
+        };
+
+        let snapshot = StoredSnapshot {
+            meta: meta.clone(),
+            data: data.clone(),
+        };
+        write_value(
+            &self.store,
+            SNAPSHOT_KEY,
+            &snapshot,
+            ErrorSubject::Snapshot(meta.clone()),
+        )?;
+        self.flush().await?;
+
+        Ok(Snapshot {
+            meta,
+            snapshot: Box::new(Cursor::new(data)),
+        })
+    }
+}
+
+#[async_trait]
+impl RaftStorage<TypeConfig> for Arc<Store> {
+    type SnapshotData = Cursor<Vec<u8>>;
+    type LogReader = Self;
+    type SnapshotBuilder = Self;
+
+    async fn save_vote(&mut self, vote: &Vote<NodeId>) -> StorageResult<()> {
+        write_value(&self.store, VOTE_KEY, vote, ErrorSubject::Vote)?;
+        self.flush().await
+    }
+
+    async fn read_vote(&mut self) -> StorageResult<Option<Vote<NodeId>>> {
+        read_value(&self.store, VOTE_KEY, ErrorSubject::Vote)
+    }
+
+    async fn get_log_reader(&mut self) -> Self::LogReader {
+        self.clone()
+    }
+
+    async fn append_to_log(&mut self, entries: &[&Entry<TypeConfig>]) -> StorageResult<()> {
+        let mut batch = sled::Batch::default();
+        for entry in entries {
+            let value = serde_json::to_vec(entry).map_err(|e| logs_error(ErrorVerb::Write, &e))?;
+            batch.insert(&log_key(entry.log_id.index), value);
+        }
+        self.logs
+            .apply_batch(batch)
+            .map_err(|e| logs_error(ErrorVerb::Write, &e))?;
+        self.flush().await
+    }
+
+    async fn delete_conflict_logs_since(&mut self, log_id: LogId<NodeId>) -> StorageResult<()> {
+        let mut batch = sled::Batch::default();
+        for item in self.logs.range(log_key(log_id.index)..) {
+            let (key, _) = item.map_err(|e| logs_error(ErrorVerb::Delete, &e))?;
+            batch.remove(key);
+        }
+        self.logs
+            .apply_batch(batch)
+            .map_err(|e| logs_error(ErrorVerb::Delete, &e))?;
+        self.flush().await
+    }
+
+    async fn purge_logs_upto(&mut self, log_id: LogId<NodeId>) -> StorageResult<()> {
+        write_value(&self.store, LAST_PURGED_KEY, &log_id, ErrorSubject::Logs)?;
+
+        let mut batch = sled::Batch::default();
+        for item in self.logs.range(..=log_key(log_id.index)) {
+            let (key, _) = item.map_err(|e| logs_error(ErrorVerb::Delete, &e))?;
+            batch.remove(key);
+        }
+        self.logs
+            .apply_batch(batch)
+            .map_err(|e| logs_error(ErrorVerb::Delete, &e))?;
+        self.flush().await
+    }
+
+    async fn last_applied_state(
+        &mut self,
+    ) -> StorageResult<(Option<LogId<NodeId>>, EffectiveMembership<NodeId>)> {
+        let sm = self.state_machine.read().await;
+        Ok((sm.last_applied_log, sm.last_membership.clone()))
+    }
+
+    async fn apply_to_state_machine(
+        &mut self,
+        entries: &[&Entry<TypeConfig>],
+    ) -> StorageResult<Vec<CommandResp>> {
+        let mut res = Vec::with_capacity(entries.len());
+        let mut sm = self.state_machine.write().await;
+
+        for entry in entries {
+            sm.last_applied_log = Some(entry.log_id);
+            match entry.payload {
+                EntryPayload::Blank => res.push(CommandResp::Ok),
+                EntryPayload::Normal(ref cmd) => res.push(sm.data.apply(cmd)),
+                EntryPayload::Membership(ref membership) => {
+                    sm.last_membership =
+                        EffectiveMembership::new(Some(entry.log_id), membership.clone());
+                    res.push(CommandResp::Ok)
+                }
+            }
+        }
+
+        self.save_state_machine(&sm)?;
+        self.flush().await?;
+        Ok(res)
+    }
+
+    async fn get_snapshot_builder(&mut self) -> Self::SnapshotBuilder {
+        self.clone()
+    }
+
+    async fn begin_receiving_snapshot(&mut self) -> StorageResult<Box<Self::SnapshotData>> {
+        Ok(Box::new(Cursor::new(Vec::new())))
+    }
+
+    async fn install_snapshot(
+        &mut self,
+        meta: &SnapshotMeta<NodeId>,
+        snapshot: Box<Self::SnapshotData>,
+    ) -> StorageResult<StateMachineChanges<TypeConfig>> {
+        let snapshot = StoredSnapshot {
+            meta: meta.clone(),
+            data: snapshot.into_inner(),
+        };
+
+        let new_sm: StateMachine = serde_json::from_slice(&snapshot.data).map_err(|e| {
+            StorageIOError::new(
+                ErrorSubject::Snapshot(meta.clone()),
+                ErrorVerb::Read,
+                AnyError::new(&e),
+            )
+        })?;
+        {
+            let mut sm = self.state_machine.write().await;
+            *sm = new_sm;
+            self.save_state_machine(&sm)?;
+        }
+        write_value(
+            &self.store,
+            SNAPSHOT_KEY,
+            &snapshot,
+            ErrorSubject::Snapshot(meta.clone()),
+        )?;
+        self.flush().await?;
+
+        Ok(StateMachineChanges {
+            last_applied: meta.last_log_id,
+            is_snapshot: true,
+        })
+    }
+
+    async fn get_current_snapshot(
+        &mut self,
+    ) -> StorageResult<Option<Snapshot<NodeId, Self::SnapshotData>>> {
+        let snapshot: Option<StoredSnapshot> =
+            read_value(&self.store, SNAPSHOT_KEY, ErrorSubject::Store)?;
+        Ok(snapshot.map(|e| Snapshot {
+            meta: e.meta,
+            snapshot: Box::new(Cursor::new(e.data)),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use models::meta_data::DEFAULT_TENANT;
+    use openraft::LeaderId;
+
+    use super::*;
+    use crate::store::command::WriteCommand;
+
+    fn entry(index: u64, cmd: WriteCommand) -> Entry<TypeConfig> {
+        Entry {
+            log_id: LogId::new(LeaderId::new(1, 1), index),
+            payload: EntryPayload::Normal(cmd),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_store_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let entries = vec![
+            entry(1, WriteCommand::CreateTenant(DEFAULT_TENANT.to_string())),
+            entry(2, WriteCommand::DropUser("root".to_string())),
+        ];
+        {
+            let mut store = Arc::new(Store::open(dir.path()).unwrap());
+            store
+                .append_to_log(&entries.iter().collect::<Vec<_>>())
+                .await
+                .unwrap();
+            let resp = store
+                .apply_to_state_machine(&entries.iter().collect::<Vec<_>>())
+                .await
+                .unwrap();
+            assert_eq!(resp[0], CommandResp::Ok);
+            assert!(matches!(resp[1], CommandResp::Err(_)));
+        }
+
+        let mut store = Arc::new(Store::open(dir.path()).unwrap());
+        assert_eq!(
+            store.try_get_log_entries(1..3).await.unwrap().len(),
+            entries.len()
+        );
+        let (last_applied, _) = store.last_applied_state().await.unwrap();
+        assert_eq!(last_applied.unwrap().index, 2);
+        assert_eq!(
+            store.read(&ReadCommand::Tenants).await,
+            CommandResp::Tenants(vec![DEFAULT_TENANT.to_string()])
+        );
+
+        store.purge_logs_upto(entries[0].log_id).await.unwrap();
+        let state = store.get_log_state().await.unwrap();
+        assert_eq!(state.last_purged_log_id.unwrap().index, 1);
+        assert_eq!(state.last_log_id.unwrap().index, 2);
+    }
+}
@@ -0,0 +1,904 @@
+use std::collections::BTreeMap;
+
+use models::meta_data::{
+    BucketId, BucketInfo, DatabaseInfo, NodeId, NodeInfo, NodeStatus, ReplicationSet, RoleInfo,
+    TenantMetaData, UserInfo, VnodeId, VnodeInfo,
+};
+use models::schema::{DatabaseSchema, TableSchema};
+use models::task::{TaskDefinition, TaskProgress};
+use models::{SchemaId, Timestamp};
+use openraft::{EffectiveMembership, LogId};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{MetaError, MetaResult};
+use crate::store::command::{CommandResp, ReadCommand, WriteCommand};
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct StateMachine {
+    pub last_applied_log: Option<LogId<NodeId>>,
+    pub last_membership: EffectiveMembership<NodeId>,
+    pub data: ClusterMeta,
+}
+
+/// Metadata of the cluster, changed only by the commands applied in the order of raft logs
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct ClusterMeta {
+    pub version: u64,
+    /// Generator of the ids of buckets, replication sets and vnodes
+    pub incr_id: u32,
+    pub data_nodes: BTreeMap<NodeId, NodeInfo>,
+    pub users: BTreeMap<String, UserInfo>,
+    #[serde(default)]
+    pub roles: BTreeMap<String, RoleInfo>,
+    /// Tasks of the tenants by their names
+    #[serde(default)]
+    pub tasks: BTreeMap<String, BTreeMap<String, TaskDefinition>>,
+    /// Progresses of the tasks of the tenants by the names of the tasks
+    #[serde(default)]
+    pub task_progresses: BTreeMap<String, BTreeMap<String, TaskProgress>>,
+    pub tenants: BTreeMap<String, TenantMetaData>,
+}
+
+impl ClusterMeta {
+    pub fn apply(&mut self, cmd: &WriteCommand) -> CommandResp {
+        let resp: CommandResp = match cmd {
+            WriteCommand::AddDataNode(node) => {
+                self.data_nodes.insert(node.id, node.clone());
+                CommandResp::Ok
+            }
+            WriteCommand::UpdateDataNodeStatus(id, status) => {
+                self.update_data_node_status(*id, *status).into()
+            }
+            WriteCommand::RemoveDataNode(id) => self.remove_data_node(*id).into(),
+            WriteCommand::CreateUser(user) => self.create_user(user).into(),
+            WriteCommand::AlterUser(user) => self.alter_user(user).into(),
+            WriteCommand::DropUser(name) => self.drop_user(name).into(),
+            WriteCommand::CreateRole(role) => self.create_role(role).into(),
+            WriteCommand::AlterRole(role) => self.alter_role(role).into(),
+            WriteCommand::DropRole(name) => self.drop_role(name).into(),
+            WriteCommand::CreateTask(task) => self.create_task(task).into(),
+            WriteCommand::AlterTask(task) => self.alter_task(task).into(),
+            WriteCommand::DropTask(tenant, name) => self.drop_task(tenant, name).into(),
+            WriteCommand::SetTaskProgress(progress) => self.set_task_progress(progress).into(),
+            WriteCommand::CreateTenant(tenant) => self.create_tenant(tenant).into(),
+            WriteCommand::DropTenant(tenant) => self.drop_tenant(tenant).into(),
+            WriteCommand::CreateDB(tenant, schema) => self.create_db(tenant, schema).into(),
+            WriteCommand::AlterDB(tenant, schema) => self.alter_db(tenant, schema).into(),
+            WriteCommand::DropDB(tenant, db) => self.drop_db(tenant, db).into(),
+            WriteCommand::CreateTable(tenant, schema) => self.create_table(tenant, schema).into(),
+            WriteCommand::UpdateTable(tenant, schema, version) => {
+                self.update_table(tenant, schema, *version).into()
+            }
+            WriteCommand::DropTable(tenant, db, table) => self.drop_table(tenant, db, table).into(),
+            WriteCommand::CreateBucket { tenant, db, ts } => {
+                self.create_bucket(tenant, db, *ts).into()
+            }
+            WriteCommand::MoveVnode {
+                tenant,
+                db,
+                bucket,
+                vnode,
+                to,
+            } => self.move_vnode(tenant, db, *bucket, *vnode, *to).into(),
+        };
+
+        if !matches!(resp, CommandResp::Err(_)) {
+            self.version += 1;
+            // The version of the tenant is changed only by the applied commands,
+            // the cached metadata of it would be never refreshed otherwise
+            if let Some(meta) = cmd.tenant().and_then(|e| self.tenants.get_mut(e)) {
+                meta.version += 1;
+            }
+        }
+        resp
+    }
+
+    pub fn read(&self, cmd: &ReadCommand) -> CommandResp {
+        match cmd {
+            ReadCommand::Version => CommandResp::Version(self.version),
+            ReadCommand::DataNodes => {
+                CommandResp::DataNodes(self.data_nodes.values().cloned().collect())
+            }
+            ReadCommand::DataNode(id) => match self.data_nodes.get(id) {
+                Some(node) => CommandResp::DataNode(node.clone()),
+                None => CommandResp::Err(MetaError::DataNodeNotFound { id: *id }),
+            },
+            ReadCommand::Users => CommandResp::Users(self.users.values().cloned().collect()),
+            ReadCommand::Roles => CommandResp::Roles(self.roles.values().cloned().collect()),
+            ReadCommand::Tasks => CommandResp::Tasks(
+                self.tasks
+                    .values()
+                    .flat_map(|e| e.values().cloned())
+                    .collect(),
+            ),
+            ReadCommand::TaskProgresses => CommandResp::TaskProgresses(
+                self.task_progresses
+                    .values()
+                    .flat_map(|e| e.values().cloned())
+                    .collect(),
+            ),
+            ReadCommand::Tenants => CommandResp::Tenants(self.tenants.keys().cloned().collect()),
+            ReadCommand::TenantMeta(tenant) => match self.tenants.get(tenant) {
+                Some(meta) => CommandResp::TenantMeta(meta.clone()),
+                None => CommandResp::Err(MetaError::TenantNotFound {
+                    tenant: tenant.clone(),
+                }),
+            },
+        }
+    }
+
+    fn update_data_node_status(&mut self, id: NodeId, status: NodeStatus) -> MetaResult<()> {
+        let node = self
+            .data_nodes
+            .get_mut(&id)
+            .ok_or(MetaError::DataNodeNotFound { id })?;
+        node.status = status;
+        Ok(())
+    }
+
+    fn remove_data_node(&mut self, id: NodeId) -> MetaResult<()> {
+        if !self.data_nodes.contains_key(&id) {
+            return Err(MetaError::DataNodeNotFound { id });
+        }
+        let vnodes = self
+            .tenants
+            .values()
+            .flat_map(|e| e.dbs.values())
+            .flat_map(|e| e.buckets.iter())
+            .flat_map(|e| e.shard_group.iter())
+            .flat_map(|e| e.vnodes.iter())
+            .filter(|e| e.node_id == id)
+            .count();
+        if vnodes > 0 {
+            return Err(MetaError::DataNodeInUse { id, vnodes });
+        }
+        self.data_nodes.remove(&id);
+        Ok(())
+    }
+
+    fn create_user(&mut self, user: &UserInfo) -> MetaResult<()> {
+        if self.users.contains_key(&user.name) {
+            return Err(MetaError::UserAlreadyExists {
+                user: user.name.clone(),
+            });
+        }
+        self.users.insert(user.name.clone(), user.clone());
+        Ok(())
+    }
+
+    fn alter_user(&mut self, user: &UserInfo) -> MetaResult<()> {
+        let old = self
+            .users
+            .get_mut(&user.name)
+            .ok_or_else(|| MetaError::UserNotFound {
+                user: user.name.clone(),
+            })?;
+        *old = user.clone();
+        Ok(())
+    }
+
+    fn drop_user(&mut self, name: &str) -> MetaResult<()> {
+        self.users
+            .remove(name)
+            .map(|_| ())
+            .ok_or_else(|| MetaError::UserNotFound {
+                user: name.to_string(),
+            })
+    }
+
+    fn create_role(&mut self, role: &RoleInfo) -> MetaResult<()> {
+        if self.roles.contains_key(&role.name) {
+            return Err(MetaError::RoleAlreadyExists {
+                role: role.name.clone(),
+            });
+        }
+        self.roles.insert(role.name.clone(), role.clone());
+        Ok(())
+    }
+
+    fn alter_role(&mut self, role: &RoleInfo) -> MetaResult<()> {
+        let old = self
+            .roles
+            .get_mut(&role.name)
+            .ok_or_else(|| MetaError::RoleNotFound {
+                role: role.name.clone(),
+            })?;
+        *old = role.clone();
+        Ok(())
+    }
+
+    /// The role is revoked from the users as well
+    fn drop_role(&mut self, name: &str) -> MetaResult<()> {
+        self.roles
+            .remove(name)
+            .ok_or_else(|| MetaError::RoleNotFound {
+                role: name.to_string(),
+            })?;
+        for user in self.users.values_mut() {
+            user.roles.retain(|e| e != name);
+        }
+        Ok(())
+    }
+
+    fn create_task(&mut self, task: &TaskDefinition) -> MetaResult<()> {
+        let tasks = self.tasks.entry(task.tenant.clone()).or_default();
+        if tasks.contains_key(&task.name) {
+            return Err(MetaError::TaskAlreadyExists {
+                task: task.name.clone(),
+            });
+        }
+        tasks.insert(task.name.clone(), task.clone());
+        Ok(())
+    }
+
+    fn alter_task(&mut self, task: &TaskDefinition) -> MetaResult<()> {
+        let old = self
+            .tasks
+            .get_mut(&task.tenant)
+            .and_then(|e| e.get_mut(&task.name))
+            .ok_or_else(|| MetaError::TaskNotFound {
+                task: task.name.clone(),
+            })?;
+        *old = task.clone();
+        Ok(())
+    }
+
+    fn drop_task(&mut self, tenant: &str, name: &str) -> MetaResult<()> {
+        if let Some(progresses) = self.task_progresses.get_mut(tenant) {
+            progresses.remove(name);
+        }
+        self.tasks
+            .get_mut(tenant)
+            .and_then(|e| e.remove(name))
+            .map(|_| ())
+            .ok_or_else(|| MetaError::TaskNotFound {
+                task: name.to_string(),
+            })
+    }
+
+    /// The progress of a run finished after the task is dropped is discarded
+    fn set_task_progress(&mut self, progress: &TaskProgress) -> MetaResult<()> {
+        let exists = self
+            .tasks
+            .get(&progress.tenant)
+            .map_or(false, |e| e.contains_key(&progress.task));
+        if !exists {
+            return Err(MetaError::TaskNotFound {
+                task: progress.task.clone(),
+            });
+        }
+        self.task_progresses
+            .entry(progress.tenant.clone())
+            .or_default()
+            .insert(progress.task.clone(), progress.clone());
+        Ok(())
+    }
+
+    fn create_tenant(&mut self, tenant: &str) -> MetaResult<()> {
+        if self.tenants.contains_key(tenant) {
+            return Err(MetaError::TenantAlreadyExists {
+                tenant: tenant.to_string(),
+            });
+        }
+        self.tenants
+            .insert(tenant.to_string(), TenantMetaData::default());
+        Ok(())
+    }
+
+    /// The tasks of the tenant are dropped as well
+    fn drop_tenant(&mut self, tenant: &str) -> MetaResult<()> {
+        self.tasks.remove(tenant);
+        self.task_progresses.remove(tenant);
+        self.tenants
+            .remove(tenant)
+            .map(|_| ())
+            .ok_or_else(|| MetaError::TenantNotFound {
+                tenant: tenant.to_string(),
+            })
+    }
+
+    fn tenant_mut(&mut self, tenant: &str) -> MetaResult<&mut TenantMetaData> {
+        self.tenants
+            .get_mut(tenant)
+            .ok_or_else(|| MetaError::TenantNotFound {
+                tenant: tenant.to_string(),
+            })
+    }
+
+    fn database_mut(&mut self, tenant: &str, db: &str) -> MetaResult<&mut DatabaseInfo> {
+        self.tenant_mut(tenant)?
+            .dbs
+            .get_mut(db)
+            .ok_or_else(|| MetaError::DatabaseNotFound {
+                database: db.to_string(),
+            })
+    }
+
+    fn create_db(&mut self, tenant: &str, schema: &DatabaseSchema) -> MetaResult<()> {
+        let meta = self.tenant_mut(tenant)?;
+        if meta.dbs.contains_key(&schema.name) {
+            return Err(MetaError::DatabaseAlreadyExists {
+                database: schema.name.clone(),
+            });
+        }
+        meta.dbs
+            .insert(schema.name.clone(), DatabaseInfo::new(schema.clone()));
+        Ok(())
+    }
+
+    fn alter_db(&mut self, tenant: &str, schema: &DatabaseSchema) -> MetaResult<()> {
+        self.database_mut(tenant, &schema.name)?.schema = schema.clone();
+        Ok(())
+    }
+
+    fn drop_db(&mut self, tenant: &str, db: &str) -> MetaResult<()> {
+        self.tenant_mut(tenant)?
+            .dbs
+            .remove(db)
+            .map(|_| ())
+            .ok_or_else(|| MetaError::DatabaseNotFound {
+                database: db.to_string(),
+            })
+    }
+
+    fn create_table(&mut self, tenant: &str, schema: &TableSchema) -> MetaResult<()> {
+        let name = schema.name();
+        let db = self.database_mut(tenant, &schema.db())?;
+        if db.tables.contains_key(&name) {
+            return Err(MetaError::TableAlreadyExists { table: name });
+        }
+        db.tables.insert(name, schema.clone());
+        Ok(())
+    }
+
+    fn update_table(
+        &mut self,
+        tenant: &str,
+        schema: &TableSchema,
+        version: SchemaId,
+    ) -> MetaResult<()> {
+        let name = schema.name();
+        let db = self.database_mut(tenant, &schema.db())?;
+        let table = db
+            .tables
+            .get_mut(&name)
+            .ok_or_else(|| MetaError::TableNotFound {
+                table: name.clone(),
+            })?;
+        match (table, schema) {
+            (TableSchema::TsKvTableSchema(table), TableSchema::TsKvTableSchema(schema)) => {
+                // Altered concurrently by another node, the change is based on a stale schema
+                if table.schema_id != version {
+                    return Err(MetaError::TableSchemaChanged {
+                        table: name,
+                        expected: version,
+                        actual: table.schema_id,
+                    });
+                }
+                *table = schema.clone();
+                table.schema_id = version + 1;
+                Ok(())
+            }
+            _ => Err(MetaError::TableIsNotTsKv { table: name }),
+        }
+    }
+
+    fn drop_table(&mut self, tenant: &str, db: &str, table: &str) -> MetaResult<()> {
+        self.database_mut(tenant, db)?
+            .tables
+            .remove(table)
+            .map(|_| ())
+            .ok_or_else(|| MetaError::TableNotFound {
+                table: table.to_string(),
+            })
+    }
+
+    fn move_vnode(
+        &mut self,
+        tenant: &str,
+        db: &str,
+        bucket: BucketId,
+        vnode: VnodeId,
+        to: NodeId,
+    ) -> MetaResult<()> {
+        if !self.data_nodes.contains_key(&to) {
+            return Err(MetaError::DataNodeNotFound { id: to });
+        }
+        let set = self
+            .database_mut(tenant, db)?
+            .buckets
+            .iter_mut()
+            .filter(|e| e.id == bucket)
+            .flat_map(|e| e.shard_group.iter_mut())
+            .find(|e| e.vnodes.iter().any(|v| v.id == vnode))
+            .ok_or(MetaError::VnodeNotFound { id: vnode })?;
+        if set.vnodes.iter().any(|v| v.id != vnode && v.node_id == to) {
+            return Err(MetaError::ReplicaAlreadyOnNode {
+                set: set.id,
+                node: to,
+            });
+        }
+        if let Some(v) = set.vnodes.iter_mut().find(|v| v.id == vnode) {
+            v.node_id = to;
+        }
+        Ok(())
+    }
+
+    fn next_id(&mut self) -> u32 {
+        self.incr_id += 1;
+        self.incr_id
+    }
+
+    /// Places `shard_num` shards of the bucket, each one has `replica` vnodes on
+    /// different data nodes. The first node rotates by buckets to balance the load.
+    fn create_bucket(&mut self, tenant: &str, db: &str, ts: Timestamp) -> MetaResult<BucketInfo> {
+        let nodes: Vec<NodeId> = self
+            .data_nodes
+            .values()
+            .filter(|e| e.status != NodeStatus::Decommissioning)
+            .map(|e| e.id)
+            .collect();
+        let offset = self.incr_id as usize;
+
+        let info = self.database_mut(tenant, db)?;
+        if let Some(bucket) = info.bucket_by_timestamp(ts) {
+            return Ok(bucket.clone());
+        }
+
+        let config = &info.schema.config;
+        let replica = config.replica_or_default();
+        let shard_num = config.shard_num_or_default();
+        if nodes.is_empty() || (nodes.len() as u64) < replica {
+            return Err(MetaError::NotEnoughDataNodes {
+                replica,
+                nodes: nodes.len(),
+            });
+        }
+
+        // The buckets created before the duration is altered are kept, the new
+        // bucket is cut not to overlap them
+        let duration = config.vnode_duration_or_default().to_nanoseconds().max(1);
+        let mut start_time = ts - ts.rem_euclid(duration);
+        let mut end_time = start_time.saturating_add(duration);
+        for bucket in info.buckets.iter() {
+            if bucket.end_time <= ts {
+                start_time = start_time.max(bucket.end_time);
+            } else if bucket.start_time > ts {
+                end_time = end_time.min(bucket.start_time);
+            }
+        }
+
+        let mut ids = (0..shard_num * (replica + 1) + 1).map(|_| self.next_id());
+        let mut bucket = BucketInfo {
+            id: ids.next().unwrap_or_default(),
+            start_time,
+            end_time,
+            shard_group: vec![],
+        };
+        for shard in 0..shard_num as usize {
+            let mut set = ReplicationSet {
+                id: ids.next().unwrap_or_default(),
+                vnodes: vec![],
+            };
+            for i in 0..replica as usize {
+                set.vnodes.push(VnodeInfo {
+                    id: ids.next().unwrap_or_default(),
+                    node_id: nodes[(offset + shard + i) % nodes.len()],
+                });
+            }
+            bucket.shard_group.push(set);
+        }
+        drop(ids);
+
+        let info = self.database_mut(tenant, db)?;
+        info.buckets.push(bucket.clone());
+        info.buckets.sort_by_key(|e| e.start_time);
+
+        Ok(bucket)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use models::meta_data::DEFAULT_TENANT;
+    use models::schema::{DatabaseSchema, Duration, TableSchema, TskvTableSchema};
+
+    use super::*;
+
+    fn node(id: NodeId) -> NodeInfo {
+        NodeInfo {
+            id,
+            grpc_addr: format!("127.0.0.1:{}", 31006 + id),
+            http_addr: format!("127.0.0.1:{}", 31007 + id),
+            status: NodeStatus::Healthy,
+        }
+    }
+
+    fn table(db: &str, name: &str) -> TableSchema {
+        TableSchema::TsKvTableSchema(TskvTableSchema::new(
+            db.to_string(),
+            name.to_string(),
+            vec![],
+        ))
+    }
+
+    #[test]
+    fn test_apply_ddl() {
+        let mut meta = ClusterMeta::default();
+        let tenant = DEFAULT_TENANT.to_string();
+
+        assert_eq!(
+            meta.apply(&WriteCommand::CreateDB(
+                tenant.clone(),
+                DatabaseSchema::new("db")
+            )),
+            CommandResp::Err(MetaError::TenantNotFound {
+                tenant: tenant.clone()
+            })
+        );
+        assert_eq!(meta.version, 0);
+
+        meta.apply(&WriteCommand::CreateTenant(tenant.clone()));
+        meta.apply(&WriteCommand::CreateDB(
+            tenant.clone(),
+            DatabaseSchema::new("db"),
+        ));
+        assert_eq!(
+            meta.apply(&WriteCommand::CreateTable(tenant.clone(), table("db", "t"))),
+            CommandResp::Ok
+        );
+        assert_eq!(
+            meta.apply(&WriteCommand::CreateTable(tenant.clone(), table("db", "t"))),
+            CommandResp::Err(MetaError::TableAlreadyExists {
+                table: "t".to_string()
+            })
+        );
+        assert_eq!(meta.version, 3);
+
+        let tenant_meta = match meta.read(&ReadCommand::TenantMeta(tenant.clone())) {
+            CommandResp::TenantMeta(meta) => meta,
+            resp => panic!("unexpected response {:?}", resp),
+        };
+        assert_eq!(tenant_meta.table_names("db").unwrap(), vec!["t"]);
+
+        meta.apply(&WriteCommand::DropDB(tenant.clone(), "db".to_string()));
+        assert!(meta.tenants[&tenant].database("db").is_none());
+    }
+
+    #[test]
+    fn test_update_table() {
+        let mut meta = ClusterMeta::default();
+        let tenant = DEFAULT_TENANT.to_string();
+        meta.apply(&WriteCommand::CreateTenant(tenant.clone()));
+        meta.apply(&WriteCommand::CreateDB(
+            tenant.clone(),
+            DatabaseSchema::new("db"),
+        ));
+        meta.apply(&WriteCommand::CreateTable(tenant.clone(), table("db", "t")));
+        let version = meta.tenants[&tenant].version;
+
+        assert_eq!(
+            meta.apply(&WriteCommand::UpdateTable(
+                tenant.clone(),
+                table("db", "t"),
+                0
+            )),
+            CommandResp::Ok
+        );
+        // The change based on the replaced schema is rejected
+        assert_eq!(
+            meta.apply(&WriteCommand::UpdateTable(
+                tenant.clone(),
+                table("db", "t"),
+                0
+            )),
+            CommandResp::Err(MetaError::TableSchemaChanged {
+                table: "t".to_string(),
+                expected: 0,
+                actual: 1,
+            })
+        );
+        assert_eq!(
+            meta.apply(&WriteCommand::DropTable(
+                tenant.clone(),
+                "db".to_string(),
+                "other".to_string()
+            )),
+            CommandResp::Err(MetaError::TableNotFound {
+                table: "other".to_string()
+            })
+        );
+        // Only the applied change is seen by the cached metadata
+        assert_eq!(meta.tenants[&tenant].version, version + 1);
+    }
+
+    #[test]
+    fn test_apply_users() {
+        let mut meta = ClusterMeta::default();
+        let mut user = UserInfo {
+            name: "u".to_string(),
+            is_admin: false,
+            password_hash: "h".to_string(),
+            tokens: vec![],
+            roles: vec![],
+            provider: None,
+        };
+        assert_eq!(
+            meta.apply(&WriteCommand::AlterUser(user.clone())),
+            CommandResp::Err(MetaError::UserNotFound {
+                user: "u".to_string()
+            })
+        );
+        meta.apply(&WriteCommand::CreateUser(user.clone()));
+        user.is_admin = true;
+        assert_eq!(
+            meta.apply(&WriteCommand::AlterUser(user.clone())),
+            CommandResp::Ok
+        );
+        assert_eq!(
+            meta.read(&ReadCommand::Users),
+            CommandResp::Users(vec![user])
+        );
+
+        meta.apply(&WriteCommand::DropUser("u".to_string()));
+        assert_eq!(meta.read(&ReadCommand::Users), CommandResp::Users(vec![]));
+        assert_eq!(meta.version, 3);
+    }
+
+    #[test]
+    fn test_apply_roles() {
+        let mut meta = ClusterMeta::default();
+        let role = RoleInfo {
+            name: "r".to_string(),
+            policies: vec![],
+            column_privileges: vec![],
+            groups: vec![],
+        };
+        meta.apply(&WriteCommand::CreateRole(role.clone()));
+        assert_eq!(
+            meta.apply(&WriteCommand::CreateRole(role.clone())),
+            CommandResp::Err(MetaError::RoleAlreadyExists {
+                role: "r".to_string()
+            })
+        );
+        meta.apply(&WriteCommand::CreateUser(UserInfo {
+            name: "u".to_string(),
+            is_admin: false,
+            password_hash: "h".to_string(),
+            tokens: vec![],
+            roles: vec!["r".to_string()],
+            provider: None,
+        }));
+        assert_eq!(
+            meta.read(&ReadCommand::Roles),
+            CommandResp::Roles(vec![role])
+        );
+
+        // The role is revoked from the users
+        meta.apply(&WriteCommand::DropRole("r".to_string()));
+        assert_eq!(meta.read(&ReadCommand::Roles), CommandResp::Roles(vec![]));
+        assert!(meta.users["u"].roles.is_empty());
+    }
+
+    #[test]
+    fn test_apply_tasks() {
+        use models::task::{TaskSink, TaskTrigger};
+
+        let mut meta = ClusterMeta::default();
+        meta.apply(&WriteCommand::CreateTenant("t".to_string()));
+        let mut task = TaskDefinition {
+            name: "k".to_string(),
+            tenant: "t".to_string(),
+            database: "public".to_string(),
+            owner: "u".to_string(),
+            query: "SELECT 1".to_string(),
+            trigger: TaskTrigger::Schedule { interval: 60 },
+            sink: TaskSink::Table("m".to_string()),
+            enabled: true,
+        };
+        meta.apply(&WriteCommand::CreateTask(task.clone()));
+        assert_eq!(
+            meta.apply(&WriteCommand::CreateTask(task.clone())),
+            CommandResp::Err(MetaError::TaskAlreadyExists {
+                task: "k".to_string()
+            })
+        );
+        task.enabled = false;
+        meta.apply(&WriteCommand::AlterTask(task.clone()));
+        let progress = TaskProgress::new("t", "k");
+        meta.apply(&WriteCommand::SetTaskProgress(progress.clone()));
+        assert_eq!(
+            meta.read(&ReadCommand::TaskProgresses),
+            CommandResp::TaskProgresses(vec![progress])
+        );
+        assert_eq!(
+            meta.apply(&WriteCommand::SetTaskProgress(TaskProgress::new("t", "x"))),
+            CommandResp::Err(MetaError::TaskNotFound {
+                task: "x".to_string()
+            })
+        );
+        assert_eq!(
+            meta.read(&ReadCommand::Tasks),
+            CommandResp::Tasks(vec![task])
+        );
+
+        meta.apply(&WriteCommand::DropTenant("t".to_string()));
+        assert_eq!(meta.read(&ReadCommand::Tasks), CommandResp::Tasks(vec![]));
+        assert_eq!(
+            meta.read(&ReadCommand::TaskProgresses),
+            CommandResp::TaskProgresses(vec![])
+        );
+        assert_eq!(
+            meta.apply(&WriteCommand::DropTask("t".to_string(), "k".to_string())),
+            CommandResp::Err(MetaError::TaskNotFound {
+                task: "k".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_create_bucket() {
+        let mut meta = ClusterMeta::default();
+        let tenant = DEFAULT_TENANT.to_string();
+        meta.apply(&WriteCommand::CreateTenant(tenant.clone()));
+        let mut schema = DatabaseSchema::new("db");
+        schema.config.with_shard_num(2);
+        schema.config.with_replica(2);
+        meta.apply(&WriteCommand::CreateDB(tenant.clone(), schema));
+
+        let create_bucket = |meta: &mut ClusterMeta, ts| {
+            meta.apply(&WriteCommand::CreateBucket {
+                tenant: tenant.clone(),
+                db: "db".to_string(),
+                ts,
+            })
+        };
+        assert_eq!(
+            create_bucket(&mut meta, 0),
+            CommandResp::Err(MetaError::NotEnoughDataNodes {
+                replica: 2,
+                nodes: 0
+            })
+        );
+
+        for id in 1..=3 {
+            meta.apply(&WriteCommand::AddDataNode(node(id)));
+        }
+        let bucket = match create_bucket(&mut meta, 10) {
+            CommandResp::Bucket(bucket) => bucket,
+            resp => panic!("unexpected response {:?}", resp),
+        };
+        assert_eq!(bucket.start_time, 0);
+        assert_eq!(bucket.shard_group.len(), 2);
+        for set in bucket.shard_group.iter() {
+            assert_eq!(set.vnodes.len(), 2);
+            assert_ne!(set.vnodes[0].node_id, set.vnodes[1].node_id);
+        }
+
+        // The bucket of the time range is created only once
+        assert_eq!(create_bucket(&mut meta, 20), CommandResp::Bucket(bucket));
+    }
+
+    #[test]
+    fn test_create_bucket_duration_altered() {
+        let mut meta = ClusterMeta::default();
+        let tenant = DEFAULT_TENANT.to_string();
+        meta.apply(&WriteCommand::CreateTenant(tenant.clone()));
+        meta.apply(&WriteCommand::AddDataNode(node(1)));
+        let mut schema = DatabaseSchema::new("db");
+        schema
+            .config
+            .with_vnode_duration(Duration::new("2d").unwrap());
+        meta.apply(&WriteCommand::CreateDB(tenant.clone(), schema.clone()));
+
+        let day = 24 * 3600 * 1_000_000_000_i64;
+        let time_range =
+            |meta: &mut ClusterMeta, ts| match meta.apply(&WriteCommand::CreateBucket {
+                tenant: tenant.clone(),
+                db: "db".to_string(),
+                ts,
+            }) {
+                CommandResp::Bucket(bucket) => (bucket.start_time, bucket.end_time),
+                resp => panic!("unexpected response {:?}", resp),
+            };
+        assert_eq!(time_range(&mut meta, 3 * day), (2 * day, 4 * day));
+
+        // The new buckets do not overlap the bucket created before
+        schema
+            .config
+            .with_vnode_duration(Duration::new("3d").unwrap());
+        meta.apply(&WriteCommand::AlterDB(tenant.clone(), schema));
+        assert_eq!(time_range(&mut meta, 4 * day + 1), (4 * day, 6 * day));
+        assert_eq!(time_range(&mut meta, day), (0, 2 * day));
+    }
+
+    #[test]
+    fn test_move_vnode() {
+        let mut meta = ClusterMeta::default();
+        let tenant = DEFAULT_TENANT.to_string();
+        meta.apply(&WriteCommand::CreateTenant(tenant.clone()));
+        let mut schema = DatabaseSchema::new("db");
+        schema.config.with_shard_num(1);
+        schema.config.with_replica(2);
+        meta.apply(&WriteCommand::CreateDB(tenant.clone(), schema));
+        for id in 1..=3 {
+            meta.apply(&WriteCommand::AddDataNode(node(id)));
+        }
+        let bucket = match meta.apply(&WriteCommand::CreateBucket {
+            tenant: tenant.clone(),
+            db: "db".to_string(),
+            ts: 0,
+        }) {
+            CommandResp::Bucket(bucket) => bucket,
+            resp => panic!("unexpected response {:?}", resp),
+        };
+        let set = &bucket.shard_group[0];
+        let (vnode, other) = (set.vnodes[0], set.vnodes[1]);
+        let free = (1..=3)
+            .find(|id| set.vnodes.iter().all(|v| v.node_id != *id))
+            .unwrap();
+
+        let move_vnode = |meta: &mut ClusterMeta, to| {
+            meta.apply(&WriteCommand::MoveVnode {
+                tenant: tenant.clone(),
+                db: "db".to_string(),
+                bucket: bucket.id,
+                vnode: vnode.id,
+                to,
+            })
+        };
+        assert_eq!(
+            move_vnode(&mut meta, other.node_id),
+            CommandResp::Err(MetaError::ReplicaAlreadyOnNode {
+                set: set.id,
+                node: other.node_id
+            })
+        );
+        assert_eq!(
+            meta.apply(&WriteCommand::RemoveDataNode(vnode.node_id)),
+            CommandResp::Err(MetaError::DataNodeInUse {
+                id: vnode.node_id,
+                vnodes: 1
+            })
+        );
+
+        assert_eq!(move_vnode(&mut meta, free), CommandResp::Ok);
+        let moved = &meta.tenants[&tenant].dbs["db"].buckets[0].shard_group[0].vnodes[0];
+        assert_eq!(moved.node_id, free);
+        assert_eq!(
+            meta.apply(&WriteCommand::RemoveDataNode(vnode.node_id)),
+            CommandResp::Ok
+        );
+    }
+
+    #[test]
+    fn test_skip_decommissioning_nodes() {
+        let mut meta = ClusterMeta::default();
+        let tenant = DEFAULT_TENANT.to_string();
+        meta.apply(&WriteCommand::CreateTenant(tenant.clone()));
+        let mut schema = DatabaseSchema::new("db");
+        schema.config.with_shard_num(4);
+        schema.config.with_replica(1);
+        meta.apply(&WriteCommand::CreateDB(tenant.clone(), schema));
+        for id in 1..=2 {
+            meta.apply(&WriteCommand::AddDataNode(node(id)));
+        }
+        meta.apply(&WriteCommand::UpdateDataNodeStatus(
+            2,
+            NodeStatus::Decommissioning,
+        ));
+
+        let bucket = match meta.apply(&WriteCommand::CreateBucket {
+            tenant,
+            db: "db".to_string(),
+            ts: 0,
+        }) {
+            CommandResp::Bucket(bucket) => bucket,
+            resp => panic!("unexpected response {:?}", resp),
+        };
+        assert!(bucket
+            .shard_group
+            .iter()
+            .all(|e| e.vnodes.iter().all(|v| v.node_id == 1)));
+    }
+}
@@ -0,0 +1,267 @@
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use chrono::Utc;
+use datafusion::arrow::array::{BooleanBuilder, StringBuilder, TimestampMillisecondBuilder};
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use datafusion::arrow::error::ArrowError;
+use datafusion::arrow::record_batch::RecordBatch;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use trace::error;
+
+pub type AuditLogRef = Arc<AuditLog>;
+
+/// Database of the system tables
+pub const SYSTEM_DATABASE: &str = "system";
+/// Table of the audit events, `SELECT * FROM system.audit_log`
+pub const AUDIT_TABLE: &str = "audit_log";
+
+const AUDIT_FILE: &str = "audit.log";
+/// Latest events kept in memory for the audit table
+const MAX_RECENT_EVENTS: usize = 10000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditKind {
+    /// Statements defining or changing the databases, tables, users and privileges
+    Ddl,
+    /// Authentication of a client
+    Auth,
+}
+
+impl AuditKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            AuditKind::Ddl => "ddl",
+            AuditKind::Auth => "auth",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEvent {
+    /// Milliseconds since the unix epoch
+    pub time: i64,
+    pub kind: AuditKind,
+    pub user: String,
+    /// Statement executed, or the source of the authentication
+    pub detail: String,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+impl AuditEvent {
+    pub fn new(kind: AuditKind, user: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self {
+            time: Utc::now().timestamp_millis(),
+            kind,
+            user: user.into(),
+            detail: detail.into(),
+            success: true,
+            error: None,
+        }
+    }
+
+    pub fn with_error(mut self, error: impl ToString) -> Self {
+        self.success = false;
+        self.error = Some(error.to_string());
+        self
+    }
+}
+
+/// Records who did what for compliance.
+///
+/// The events are appended to a file one json per line by a background thread, the
+/// events recorded meanwhile are written at once, the file is never rewritten. The
+/// latest ones are also kept in memory to be queried as a system table.
+#[derive(Debug)]
+pub struct AuditLog {
+    writer: Option<AuditWriter>,
+    recent: Mutex<VecDeque<AuditEvent>>,
+}
+
+/// The events not written yet are written before the writer is dropped
+#[derive(Debug)]
+struct AuditWriter {
+    sender: Mutex<Option<Sender<Vec<u8>>>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl AuditLog {
+    /// Keeps the events in memory only
+    pub fn memory() -> Self {
+        Self {
+            writer: None,
+            recent: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Appends the events to the audit file in the directory, the latest events
+    /// recorded before are loaded
+    pub fn open(dir: impl AsRef<Path>) -> std::io::Result<Self> {
+        std::fs::create_dir_all(dir.as_ref())?;
+        let path = dir.as_ref().join(AUDIT_FILE);
+
+        let mut recent = VecDeque::new();
+        if path.exists() {
+            for line in BufReader::new(File::open(&path)?).lines() {
+                // A line may be torn by a crash
+                if let Ok(event) = serde_json::from_str::<AuditEvent>(&line?) {
+                    if recent.len() == MAX_RECENT_EVENTS {
+                        recent.pop_front();
+                    }
+                    recent.push_back(event);
+                }
+            }
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let (sender, receiver) = mpsc::channel();
+        let handle = std::thread::Builder::new()
+            .name("audit-log".to_string())
+            .spawn(move || write_events(file, receiver))?;
+        Ok(Self {
+            writer: Some(AuditWriter {
+                sender: Mutex::new(Some(sender)),
+                handle: Some(handle),
+            }),
+            recent: Mutex::new(recent),
+        })
+    }
+
+    /// Does not wait for the event to be written to the file
+    pub fn record(&self, event: AuditEvent) {
+        if let Some(writer) = &self.writer {
+            let mut line = serde_json::to_vec(&event).expect("serialize audit event");
+            line.push(b'\n');
+            let sent = match writer.sender.lock().as_ref() {
+                Some(sender) => sender.send(line).is_ok(),
+                None => false,
+            };
+            if !sent {
+                error!(
+                    "Failed to write audit event {:?}: the writer is stopped",
+                    event
+                );
+            }
+        }
+
+        let mut recent = self.recent.lock();
+        if recent.len() == MAX_RECENT_EVENTS {
+            recent.pop_front();
+        }
+        recent.push_back(event);
+    }
+
+    pub fn events(&self) -> Vec<AuditEvent> {
+        self.recent.lock().iter().cloned().collect()
+    }
+
+    pub fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new(
+                "time",
+                DataType::Timestamp(TimeUnit::Millisecond, None),
+                false,
+            ),
+            Field::new("kind", DataType::Utf8, false),
+            Field::new("user", DataType::Utf8, false),
+            Field::new("detail", DataType::Utf8, false),
+            Field::new("success", DataType::Boolean, false),
+            Field::new("error", DataType::Utf8, true),
+        ]))
+    }
+
+    /// The latest events as the rows of the audit table
+    pub fn record_batch(&self) -> Result<RecordBatch, ArrowError> {
+        let events = self.events();
+        let mut times = TimestampMillisecondBuilder::with_capacity(events.len());
+        let mut kinds = StringBuilder::new();
+        let mut users = StringBuilder::new();
+        let mut details = StringBuilder::new();
+        let mut successes = BooleanBuilder::with_capacity(events.len());
+        let mut errors = StringBuilder::new();
+        for event in events.iter() {
+            times.append_value(event.time);
+            kinds.append_value(event.kind.as_str());
+            users.append_value(&event.user);
+            details.append_value(&event.detail);
+            successes.append_value(event.success);
+            errors.append_option(event.error.as_deref());
+        }
+
+        RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(times.finish()),
+                Arc::new(kinds.finish()),
+                Arc::new(users.finish()),
+                Arc::new(details.finish()),
+                Arc::new(successes.finish()),
+                Arc::new(errors.finish()),
+            ],
+        )
+    }
+}
+
+impl Drop for AuditWriter {
+    fn drop(&mut self) {
+        self.sender.lock().take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn write_events(file: File, receiver: Receiver<Vec<u8>>) {
+    let mut writer = BufWriter::new(file);
+    while let Ok(line) = receiver.recv() {
+        let mut res = writer.write_all(&line);
+        for line in receiver.try_iter() {
+            res = res.and_then(|_| writer.write_all(&line));
+        }
+        if let Err(e) = res.and_then(|_| writer.flush()) {
+            error!("Failed to write audit events: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_audit_log() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let audit = AuditLog::open(dir.path()).unwrap();
+            audit.record(AuditEvent::new(AuditKind::Auth, "root", "http"));
+            audit.record(
+                AuditEvent::new(AuditKind::Ddl, "root", "drop database db0")
+                    .with_error("database not found"),
+            );
+        }
+
+        let audit = AuditLog::open(dir.path()).unwrap();
+        audit.record(AuditEvent::new(
+            AuditKind::Ddl,
+            "bob",
+            "create database db1",
+        ));
+        let events = audit.events();
+        assert_eq!(events.len(), 3);
+        assert!(events[0].success);
+        assert_eq!(events[1].error.as_deref(), Some("database not found"));
+        assert_eq!(events[2].user, "bob");
+
+        let batch = audit.record_batch().unwrap();
+        assert_eq!(batch.num_rows(), 3);
+        assert_eq!(batch.schema(), AuditLog::schema());
+    }
+}
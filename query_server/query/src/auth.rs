@@ -0,0 +1,598 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use config::{JwtConfig, OidcConfig};
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{DecodingKey, Validation};
+use meta::error::MetaResult;
+use meta::meta_client::MetaClientRef;
+use models::meta_data::{RoleInfo, UserInfo};
+use parking_lot::RwLock;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use spi::catalog::{MetadataError, Result};
+use trace::{info, warn};
+
+use crate::metadata::meta_error;
+
+pub type UserStoreRef = Arc<dyn UserStore>;
+
+/// File of the users in the standalone mode
+pub const USERS_FILE: &str = "users.json";
+/// File of the roles in the standalone mode
+pub const ROLES_FILE: &str = "roles.json";
+/// Users read from the meta service are cached, the authentication of every
+/// request would otherwise be a round trip. Those changed by the other nodes are
+/// seen once they are reloaded
+const USERS_RELOAD_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Users and the hashes of their passwords, and the roles granted to them
+#[async_trait]
+pub trait UserStore: Send + Sync {
+    async fn create_user(&self, user: UserInfo) -> Result<()>;
+    async fn alter_user(&self, user: UserInfo) -> Result<()>;
+    async fn drop_user(&self, name: &str) -> Result<()>;
+    fn users(&self) -> Result<Vec<UserInfo>>;
+
+    fn user(&self, name: &str) -> Result<Option<UserInfo>> {
+        Ok(self.users()?.into_iter().find(|e| e.name == name))
+    }
+
+    async fn create_role(&self, role: RoleInfo) -> Result<()>;
+    async fn alter_role(&self, role: RoleInfo) -> Result<()>;
+    /// The role is revoked from the users as well
+    async fn drop_role(&self, name: &str) -> Result<()>;
+    fn roles(&self) -> Result<Vec<RoleInfo>>;
+
+    fn role(&self, name: &str) -> Result<Option<RoleInfo>> {
+        Ok(self.roles()?.into_iter().find(|e| e.name == name))
+    }
+}
+
+/// Users and roles of the standalone mode, saved to json files
+pub struct LocalUserStore {
+    dir: Option<PathBuf>,
+    users: RwLock<BTreeMap<String, UserInfo>>,
+    roles: RwLock<BTreeMap<String, RoleInfo>>,
+}
+
+impl LocalUserStore {
+    /// Keeps the users in memory only
+    pub fn memory() -> Self {
+        Self {
+            dir: None,
+            users: RwLock::new(BTreeMap::new()),
+            roles: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    /// Saves the users to the files in the directory, the users saved before are loaded
+    pub fn open(dir: impl AsRef<Path>) -> std::io::Result<Self> {
+        std::fs::create_dir_all(dir.as_ref())?;
+        let users: Vec<UserInfo> = load(&dir.as_ref().join(USERS_FILE))?;
+        let roles: Vec<RoleInfo> = load(&dir.as_ref().join(ROLES_FILE))?;
+
+        Ok(Self {
+            dir: Some(dir.as_ref().to_path_buf()),
+            users: RwLock::new(users.into_iter().map(|e| (e.name.clone(), e)).collect()),
+            roles: RwLock::new(roles.into_iter().map(|e| (e.name.clone(), e)).collect()),
+        })
+    }
+
+    /// Replaces the file by a new one, so that a crash never leaves it half written
+    fn save<T: Serialize>(&self, file: &str, values: &BTreeMap<String, T>) -> Result<()> {
+        let path = match &self.dir {
+            Some(dir) => dir.join(file),
+            None => return Ok(()),
+        };
+        save(&path, values.values().collect())
+    }
+}
+
+/// Replaces the file by the json of the values
+pub(crate) fn save<T: Serialize>(path: &Path, values: Vec<&T>) -> Result<()> {
+    let tmp = path.with_extension(format!("{}.tmp", rand::random::<u32>()));
+    serde_json::to_vec_pretty(&values)
+        .map_err(std::io::Error::from)
+        .and_then(|bytes| std::fs::write(&tmp, bytes))
+        .and_then(|_| std::fs::rename(&tmp, path))
+        .map_err(|e| MetadataError::InternalError {
+            error_msg: format!("failed to save {}: {}", path.display(), e),
+        })
+}
+
+pub(crate) fn load<T: DeserializeOwned>(path: &Path) -> std::io::Result<Vec<T>> {
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+}
+
+#[async_trait]
+impl UserStore for LocalUserStore {
+    async fn create_user(&self, user: UserInfo) -> Result<()> {
+        let mut users = self.users.write();
+        if users.contains_key(&user.name) {
+            return Err(MetadataError::UserAlreadyExists {
+                user_name: user.name,
+            });
+        }
+        let mut new_users = users.clone();
+        new_users.insert(user.name.clone(), user);
+        self.save(USERS_FILE, &new_users)?;
+        *users = new_users;
+        Ok(())
+    }
+
+    async fn alter_user(&self, user: UserInfo) -> Result<()> {
+        let mut users = self.users.write();
+        if !users.contains_key(&user.name) {
+            return Err(MetadataError::UserNotExists {
+                user_name: user.name,
+            });
+        }
+        let mut new_users = users.clone();
+        new_users.insert(user.name.clone(), user);
+        self.save(USERS_FILE, &new_users)?;
+        *users = new_users;
+        Ok(())
+    }
+
+    async fn drop_user(&self, name: &str) -> Result<()> {
+        let mut users = self.users.write();
+        if !users.contains_key(name) {
+            return Err(MetadataError::UserNotExists {
+                user_name: name.to_string(),
+            });
+        }
+        let mut new_users = users.clone();
+        new_users.remove(name);
+        self.save(USERS_FILE, &new_users)?;
+        *users = new_users;
+        Ok(())
+    }
+
+    fn users(&self) -> Result<Vec<UserInfo>> {
+        Ok(self.users.read().values().cloned().collect())
+    }
+
+    fn user(&self, name: &str) -> Result<Option<UserInfo>> {
+        Ok(self.users.read().get(name).cloned())
+    }
+
+    async fn create_role(&self, role: RoleInfo) -> Result<()> {
+        let mut roles = self.roles.write();
+        if roles.contains_key(&role.name) {
+            return Err(MetadataError::RoleAlreadyExists {
+                role_name: role.name,
+            });
+        }
+        let mut new_roles = roles.clone();
+        new_roles.insert(role.name.clone(), role);
+        self.save(ROLES_FILE, &new_roles)?;
+        *roles = new_roles;
+        Ok(())
+    }
+
+    async fn alter_role(&self, role: RoleInfo) -> Result<()> {
+        let mut roles = self.roles.write();
+        if !roles.contains_key(&role.name) {
+            return Err(MetadataError::RoleNotExists {
+                role_name: role.name,
+            });
+        }
+        let mut new_roles = roles.clone();
+        new_roles.insert(role.name.clone(), role);
+        self.save(ROLES_FILE, &new_roles)?;
+        *roles = new_roles;
+        Ok(())
+    }
+
+    async fn drop_role(&self, name: &str) -> Result<()> {
+        let mut users = self.users.write();
+        let mut roles = self.roles.write();
+        if !roles.contains_key(name) {
+            return Err(MetadataError::RoleNotExists {
+                role_name: name.to_string(),
+            });
+        }
+        let mut new_users = users.clone();
+        for user in new_users.values_mut() {
+            user.roles.retain(|e| e != name);
+        }
+        self.save(USERS_FILE, &new_users)?;
+        *users = new_users;
+
+        let mut new_roles = roles.clone();
+        new_roles.remove(name);
+        self.save(ROLES_FILE, &new_roles)?;
+        *roles = new_roles;
+        Ok(())
+    }
+
+    fn roles(&self) -> Result<Vec<RoleInfo>> {
+        Ok(self.roles.read().values().cloned().collect())
+    }
+
+    fn role(&self, name: &str) -> Result<Option<RoleInfo>> {
+        Ok(self.roles.read().get(name).cloned())
+    }
+}
+
+/// Users of a cluster, owned by the meta service
+pub struct RemoteUserStore {
+    client: MetaClientRef,
+    users: RwLock<Vec<UserInfo>>,
+    roles: RwLock<Vec<RoleInfo>>,
+}
+
+impl RemoteUserStore {
+    /// The users are loaded before it returns and reloaded in the background,
+    /// so that they are read without waiting for the meta service
+    pub async fn open(client: MetaClientRef) -> Result<Arc<Self>> {
+        let store = Arc::new(Self {
+            client,
+            users: RwLock::new(vec![]),
+            roles: RwLock::new(vec![]),
+        });
+        store.reload().await?;
+
+        let weak = Arc::downgrade(&store);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(USERS_RELOAD_INTERVAL);
+            loop {
+                ticker.tick().await;
+                let store = match weak.upgrade() {
+                    Some(store) => store,
+                    None => return,
+                };
+                if let Err(e) = store.reload().await {
+                    warn!("Failed to reload users: {}", e);
+                }
+            }
+        });
+        Ok(store)
+    }
+
+    async fn reload(&self) -> Result<()> {
+        let users = self.client.users().await.map_err(meta_error)?;
+        let roles = self.client.roles().await.map_err(meta_error)?;
+        *self.users.write() = users;
+        *self.roles.write() = roles;
+        Ok(())
+    }
+
+    /// The change is seen by the reads of this node once it returns
+    async fn changed(&self, res: MetaResult<()>) -> Result<()> {
+        res.map_err(meta_error)?;
+        self.reload().await
+    }
+}
+
+#[async_trait]
+impl UserStore for RemoteUserStore {
+    async fn create_user(&self, user: UserInfo) -> Result<()> {
+        self.changed(self.client.create_user(&user).await).await
+    }
+
+    async fn alter_user(&self, user: UserInfo) -> Result<()> {
+        self.changed(self.client.alter_user(&user).await).await
+    }
+
+    async fn drop_user(&self, name: &str) -> Result<()> {
+        self.changed(self.client.drop_user(name).await).await
+    }
+
+    fn users(&self) -> Result<Vec<UserInfo>> {
+        Ok(self.users.read().clone())
+    }
+
+    async fn create_role(&self, role: RoleInfo) -> Result<()> {
+        self.changed(self.client.create_role(&role).await).await
+    }
+
+    async fn alter_role(&self, role: RoleInfo) -> Result<()> {
+        self.changed(self.client.alter_role(&role).await).await
+    }
+
+    async fn drop_role(&self, name: &str) -> Result<()> {
+        self.changed(self.client.drop_role(name).await).await
+    }
+
+    fn roles(&self) -> Result<Vec<RoleInfo>> {
+        Ok(self.roles.read().clone())
+    }
+}
+
+pub type JwtValidatorRef = Arc<JwtValidator>;
+
+/// Validates the JWTs by the keys of the JWKS
+pub struct JwtValidator {
+    config: JwtConfig,
+    keys: RwLock<JwkSet>,
+    /// OpenID discovery document naming the JWKS, if there is no `jwks_url`
+    discovery_url: Option<String>,
+}
+
+impl JwtValidator {
+    /// The keys of `jwks_url` are fetched once started
+    pub fn new(config: &JwtConfig) -> std::io::Result<Self> {
+        let keys = match (&config.jwks_url, &config.jwks_path) {
+            (Some(_), _) => JwkSet { keys: vec![] },
+            (None, Some(path)) => serde_json::from_slice(&std::fs::read(path)?)?,
+            (None, None) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "jwks_url or jwks_path is required",
+                ))
+            }
+        };
+        Ok(Self {
+            config: config.clone(),
+            keys: RwLock::new(keys),
+            discovery_url: None,
+        })
+    }
+
+    /// Validates the ID tokens of the issuer, the keys of the JWKS of its
+    /// discovery document are fetched once started
+    pub fn discovered(config: &OidcConfig) -> Self {
+        let issuer = config.issuer.trim_end_matches('/');
+        Self {
+            config: JwtConfig {
+                issuer: Some(config.issuer.clone()),
+                audience: config.audience.clone(),
+                jwks_url: None,
+                jwks_path: None,
+                jwks_refresh_interval_secs: config.jwks_refresh_interval_secs,
+                user_claim: config.user_claim.clone(),
+            },
+            keys: RwLock::new(JwkSet { keys: vec![] }),
+            discovery_url: Some(format!("{}/.well-known/openid-configuration", issuer)),
+        }
+    }
+
+    /// Fetches the keys of `jwks_url` periodically, the keys rotated by the issuer
+    /// are picked up in the interval
+    pub fn start(self: &Arc<Self>) {
+        if self.config.jwks_url.is_none() && self.discovery_url.is_none() {
+            return;
+        }
+        let interval = Duration::from_secs(self.config.jwks_refresh_interval_secs.max(1));
+        let validator = self.clone();
+        tokio::spawn(async move {
+            loop {
+                match validator.fetch_keys().await {
+                    Ok((url, keys)) => {
+                        info!("Fetched {} keys of JWKS {}", keys.keys.len(), url);
+                        *validator.keys.write() = keys;
+                    }
+                    // The keys fetched before are kept
+                    Err(e) => warn!("Failed to fetch JWKS: {}", e),
+                }
+                tokio::time::sleep(interval).await;
+            }
+        });
+    }
+
+    async fn fetch_keys(&self) -> std::result::Result<(String, JwkSet), String> {
+        let url = match (&self.config.jwks_url, &self.discovery_url) {
+            (Some(url), _) => url.clone(),
+            (None, Some(discovery_url)) => {
+                let document = fetch_json::<HashMap<String, serde_json::Value>>(discovery_url)
+                    .await
+                    .map_err(|e| format!("{}: {}", discovery_url, e))?;
+                match document.get("jwks_uri") {
+                    Some(serde_json::Value::String(url)) => url.clone(),
+                    _ => return Err(format!("{}: missing jwks_uri", discovery_url)),
+                }
+            }
+            (None, None) => return Err("no JWKS".to_string()),
+        };
+        let keys = fetch_json::<JwkSet>(&url)
+            .await
+            .map_err(|e| format!("{}: {}", url, e))?;
+        Ok((url, keys))
+    }
+
+    /// Name of the user the token is issued to
+    pub fn validate(&self, token: &str) -> std::result::Result<String, String> {
+        let claims = self.validate_claims(token)?;
+        match claims.get(&self.config.user_claim) {
+            Some(serde_json::Value::String(user)) => Ok(user.clone()),
+            _ => Err(format!("missing claim {}", self.config.user_claim)),
+        }
+    }
+
+    /// Claims of the token, if it is signed by a key of the JWKS and not expired
+    pub fn validate_claims(
+        &self,
+        token: &str,
+    ) -> std::result::Result<HashMap<String, serde_json::Value>, String> {
+        let header = jsonwebtoken::decode_header(token).map_err(|e| e.to_string())?;
+        let key = {
+            let keys = self.keys.read();
+            let jwk = match &header.kid {
+                Some(kid) => keys.find(kid),
+                None if keys.keys.len() == 1 => keys.keys.first(),
+                None => None,
+            }
+            .ok_or_else(|| {
+                format!(
+                    "no key {} in the JWKS",
+                    header.kid.as_deref().unwrap_or_default()
+                )
+            })?;
+            // The algorithm of the key is trusted rather than the one of the token
+            if matches!(jwk.common.algorithm, Some(alg) if alg != header.alg) {
+                return Err(format!("algorithm {:?} is not of the key", header.alg));
+            }
+            DecodingKey::from_jwk(jwk).map_err(|e| e.to_string())?
+        };
+
+        let mut validation = Validation::new(header.alg);
+        if let Some(issuer) = &self.config.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+        if let Some(audience) = &self.config.audience {
+            validation.set_audience(&[audience]);
+        }
+        jsonwebtoken::decode::<HashMap<String, serde_json::Value>>(token, &key, &validation)
+            .map(|e| e.claims)
+            .map_err(|e| e.to_string())
+    }
+}
+
+async fn fetch_json<T: DeserializeOwned>(url: &str) -> std::result::Result<T, String> {
+    let bytes = reqwest::get(url)
+        .await
+        .and_then(|e| e.error_for_status())
+        .map_err(|e| e.to_string())?
+        .bytes()
+        .await
+        .map_err(|e| e.to_string())?;
+    serde_json::from_slice(&bytes).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn user(name: &str, is_admin: bool) -> UserInfo {
+        UserInfo {
+            name: name.to_string(),
+            is_admin,
+            password_hash: models::auth::hash_password(name),
+            tokens: vec![],
+            roles: vec![],
+            provider: None,
+        }
+    }
+
+    const SECRET: &[u8] = b"0123456789abcdef0123456789abcdef";
+
+    fn jwt(claims: serde_json::Value, kid: &str) -> String {
+        let mut header = jsonwebtoken::Header::new(jsonwebtoken::Algorithm::HS256);
+        header.kid = Some(kid.to_string());
+        jsonwebtoken::encode(
+            &header,
+            &claims,
+            &jsonwebtoken::EncodingKey::from_secret(SECRET),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_jwt_validator() {
+        let dir = tempfile::tempdir().unwrap();
+        let jwks = dir.path().join("jwks.json");
+        let k = base64::encode_config(SECRET, base64::URL_SAFE_NO_PAD);
+        std::fs::write(
+            &jwks,
+            serde_json::json!({
+                "keys": [{ "kty": "oct", "kid": "k1", "alg": "HS256", "k": k }]
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let config = JwtConfig {
+            issuer: Some("idp".to_string()),
+            audience: Some("cnosdb".to_string()),
+            jwks_url: None,
+            jwks_path: Some(jwks.to_string_lossy().to_string()),
+            jwks_refresh_interval_secs: 300,
+            user_claim: "sub".to_string(),
+        };
+        let validator = JwtValidator::new(&config).unwrap();
+
+        let exp = chrono::Utc::now().timestamp() + 60;
+        let token = jwt(
+            serde_json::json!({ "sub": "u1", "iss": "idp", "aud": "cnosdb", "exp": exp }),
+            "k1",
+        );
+        assert_eq!(validator.validate(&token).unwrap(), "u1");
+
+        // Unknown key, wrong issuer, expired
+        let token = jwt(
+            serde_json::json!({ "sub": "u1", "iss": "idp", "aud": "cnosdb", "exp": exp }),
+            "k2",
+        );
+        assert!(validator.validate(&token).is_err());
+        let token = jwt(
+            serde_json::json!({ "sub": "u1", "iss": "other", "aud": "cnosdb", "exp": exp }),
+            "k1",
+        );
+        assert!(validator.validate(&token).is_err());
+        let token = jwt(
+            serde_json::json!({ "sub": "u1", "iss": "idp", "aud": "cnosdb", "exp": exp - 3600 }),
+            "k1",
+        );
+        assert!(validator.validate(&token).is_err());
+        assert!(validator.validate("not a jwt").is_err());
+
+        let mut config = config;
+        config.jwks_path = None;
+        assert!(JwtValidator::new(&config).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_local_user_store() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let store = LocalUserStore::open(dir.path()).unwrap();
+            store.create_user(user("root", true)).await.unwrap();
+            store.create_user(user("u1", false)).await.unwrap();
+            assert!(matches!(
+                store.create_user(user("u1", true)).await,
+                Err(MetadataError::UserAlreadyExists { .. })
+            ));
+            store.alter_user(user("u1", true)).await.unwrap();
+            store.drop_user("root").await.unwrap();
+            assert!(matches!(
+                store.drop_user("root").await,
+                Err(MetadataError::UserNotExists { .. })
+            ));
+        }
+
+        // The users are loaded from the file
+        let store = LocalUserStore::open(dir.path()).unwrap();
+        let users = store.users().unwrap();
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0].name, "u1");
+        assert!(users[0].is_admin);
+        assert!(store.user("root").unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_local_roles() {
+        let dir = tempfile::tempdir().unwrap();
+        let role = RoleInfo {
+            name: "eu".to_string(),
+            policies: vec![],
+            column_privileges: vec![],
+            groups: vec![],
+        };
+        {
+            let store = LocalUserStore::open(dir.path()).unwrap();
+            store.create_role(role.clone()).await.unwrap();
+            assert!(matches!(
+                store.create_role(role.clone()).await,
+                Err(MetadataError::RoleAlreadyExists { .. })
+            ));
+            let mut u1 = user("u1", false);
+            u1.roles.push("eu".to_string());
+            store.create_user(u1).await.unwrap();
+        }
+
+        let store = LocalUserStore::open(dir.path()).unwrap();
+        assert_eq!(store.roles().unwrap(), vec![role]);
+        assert_eq!(store.user("u1").unwrap().unwrap().roles, vec!["eu"]);
+        // The role is revoked from the users
+        store.drop_role("eu").await.unwrap();
+        assert!(store.role("eu").unwrap().is_none());
+        assert!(store.user("u1").unwrap().unwrap().roles.is_empty());
+    }
+}
@@ -1,6 +1,6 @@
 use std::{collections::HashMap, sync::Arc};
 
-use models::schema::{DatabaseSchema, TableColumn, TableSchema};
+use models::schema::{DatabaseSchema, TableColumn, TableSchema, TableStatistics};
 use parking_lot::RwLock;
 use spi::catalog::MetadataError;
 use spi::catalog::Result;
@@ -183,6 +183,15 @@ impl Database {
             })
     }
 
+    pub fn table_set_statistics(&self, table: &str, statistics: TableStatistics) -> Result<()> {
+        let _lock = self.tables.write();
+        self.engine
+            .set_table_statistics(&self.db_name, table, statistics)
+            .map_err(|e| MetadataError::External {
+                message: format!("{}", e),
+            })
+    }
+
     pub fn table_alter_column(
         &self,
         table: &str,
@@ -0,0 +1,678 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use coordinator::service::CoordinatorRef;
+use coordinator::writer::DEFAULT_WRITE_CONSISTENCY;
+use datafusion::arrow::array::{
+    BooleanArray, StringBuilder, TimestampNanosecondArray, UInt64Builder,
+};
+use datafusion::arrow::compute::{cast, filter_record_batch, is_not_null, or};
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use datafusion::arrow::error::ArrowError;
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::error::DataFusionError;
+use datafusion::physical_plan::metrics::ExecutionPlanMetricsSet;
+use models::arrow_array::unpack_dictionaries;
+use models::predicate::domain::ColumnDomains;
+use models::schema::{TableSchema, TskvTableSchema, TIME_FIELD};
+use models::{ColumnId, SeriesId};
+use parking_lot::Mutex;
+use protos::kv_service::WritePointsRpcRequest;
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+use spi::query::execution::{ArrowSnafu, ExecutionError, MetadataSnafu};
+use trace::{error, info, warn};
+use tskv::TimeRange;
+
+use crate::auth::{load, save};
+use crate::iterator::{QueryOption, RowIterator};
+use crate::stream::TskvSourceMetrics;
+use crate::utils::point_util::record_batch_to_points_flat_buffer;
+
+/// Table of the conversions of the columns of the tenant between a tag and a field
+/// run by this node, `SELECT * FROM system.column_conversions`
+pub const COLUMN_CONVERSIONS_TABLE: &str = "column_conversions";
+
+/// File of the conversions not finished of this node, resumed once restarted
+pub const COLUMN_CONVERSIONS_FILE: &str = "column_conversions.json";
+
+/// Number of the series converted between the progress saved, the series converted
+/// since are converted again once resumed, which moves no points twice
+const CHECKPOINT_SERIES: usize = 64;
+
+/// Number of rows of a series read at a time when converting a column
+const CONVERT_BATCH_SIZE: usize = 4096;
+
+pub type ColumnConversionsRef = Arc<ColumnConversions>;
+
+/// A conversion saved to the file of the conversions, the series before
+/// `series_done` are converted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversionJob {
+    pub tenant: String,
+    pub old_schema: TskvTableSchema,
+    pub new_schema: TskvTableSchema,
+    pub column_name: String,
+    /// Series of the table before the conversion
+    pub series: Vec<SeriesId>,
+    #[serde(default)]
+    pub series_done: usize,
+    /// Number of the points rewritten
+    #[serde(default)]
+    pub points: u64,
+}
+
+impl ConversionJob {
+    fn key(&self) -> String {
+        format!("{}.{}", self.old_schema.db, self.old_schema.name)
+    }
+
+    fn to_tag(&self) -> bool {
+        self.new_schema
+            .column(&self.column_name)
+            .map(|e| e.column_type.is_tag())
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConversionState {
+    Running,
+    Finished,
+    /// Resumed once the node is restarted
+    Failed,
+}
+
+impl Display for ConversionState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Running => "running",
+            Self::Finished => "finished",
+            Self::Failed => "failed",
+        })
+    }
+}
+
+struct Conversion {
+    job: ConversionJob,
+    state: ConversionState,
+    error: Option<String>,
+}
+
+/// Runs the conversions of the columns of this node.
+///
+/// A conversion is saved before the schema of the table is altered, and its progress
+/// once every [`CHECKPOINT_SERIES`] series, so the conversions interrupted are
+/// resumed from the series last saved once the node is restarted.
+pub struct ColumnConversions {
+    coord: CoordinatorRef,
+    path: PathBuf,
+    /// `<database>.<table>` -> conversion
+    conversions: Mutex<BTreeMap<String, Conversion>>,
+}
+
+impl ColumnConversions {
+    /// Saves the conversions to the file in the directory, the conversions saved
+    /// before are resumed, those the schema of which was not altered are dropped
+    pub fn open(coord: CoordinatorRef, dir: impl AsRef<Path>) -> std::io::Result<Arc<Self>> {
+        std::fs::create_dir_all(dir.as_ref())?;
+        let path = dir.as_ref().join(COLUMN_CONVERSIONS_FILE);
+        let saved: Vec<ConversionJob> = load(&path)?;
+
+        let conversions = Arc::new(Self {
+            coord,
+            path,
+            conversions: Mutex::new(BTreeMap::new()),
+        });
+        for job in saved {
+            if !conversions.is_altered(&job) {
+                warn!(
+                    "Drop the conversion of column {} of table {}, the schema is not altered",
+                    job.column_name, job.old_schema.name
+                );
+                continue;
+            }
+            info!(
+                "Resume the conversion of column {} of table {} from series {}/{}",
+                job.column_name,
+                job.old_schema.name,
+                job.series_done,
+                job.series.len()
+            );
+            conversions.start(job);
+        }
+        if let Err(e) = conversions.save() {
+            warn!("Failed to save the column conversions: {}", e);
+        }
+
+        Ok(conversions)
+    }
+
+    /// Saves the conversion, alters the schema by `alter_schema`, then starts to
+    /// move the points of the series of the table
+    pub async fn submit(
+        self: &Arc<Self>,
+        job: ConversionJob,
+        alter_schema: impl Future<Output = Result<(), ExecutionError>>,
+    ) -> Result<(), ExecutionError> {
+        let key = job.key();
+        {
+            let mut conversions = self.conversions.lock();
+            if let Some(conversion) = conversions.get(&key) {
+                if conversion.state != ConversionState::Finished {
+                    return Err(ExecutionError::External {
+                        source: DataFusionError::Plan(format!(
+                            "column {} of table {} is being converted",
+                            conversion.job.column_name, conversion.job.old_schema.name
+                        )),
+                    });
+                }
+            }
+            conversions.insert(
+                key.clone(),
+                Conversion {
+                    job: job.clone(),
+                    state: ConversionState::Running,
+                    error: None,
+                },
+            );
+        }
+
+        let altered = match self.save() {
+            Ok(_) => alter_schema.await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = altered {
+            self.conversions.lock().remove(&key);
+            if let Err(e) = self.save() {
+                warn!("Failed to save the column conversions: {}", e);
+            }
+            return Err(e);
+        }
+
+        self.start(job);
+        Ok(())
+    }
+
+    /// The conversions of the tenant, the finished ones included until restarted
+    pub fn record_batch(&self, tenant: &str) -> Result<RecordBatch, ArrowError> {
+        let mut databases = StringBuilder::new();
+        let mut tables = StringBuilder::new();
+        let mut columns = StringBuilder::new();
+        let mut states = StringBuilder::new();
+        let mut series_total = UInt64Builder::new();
+        let mut series_done = UInt64Builder::new();
+        let mut points = UInt64Builder::new();
+        let mut errors = StringBuilder::new();
+        for conversion in self.conversions.lock().values() {
+            let job = &conversion.job;
+            if job.tenant != tenant {
+                continue;
+            }
+            databases.append_value(&job.old_schema.db);
+            tables.append_value(&job.old_schema.name);
+            columns.append_value(&job.column_name);
+            states.append_value(conversion.state.to_string());
+            series_total.append_value(job.series.len() as u64);
+            series_done.append_value(job.series_done as u64);
+            points.append_value(job.points);
+            errors.append_option(conversion.error.as_deref());
+        }
+
+        RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(databases.finish()),
+                Arc::new(tables.finish()),
+                Arc::new(columns.finish()),
+                Arc::new(states.finish()),
+                Arc::new(series_total.finish()),
+                Arc::new(series_done.finish()),
+                Arc::new(points.finish()),
+                Arc::new(errors.finish()),
+            ],
+        )
+    }
+
+    pub fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("database", DataType::Utf8, false),
+            Field::new("table", DataType::Utf8, false),
+            Field::new("column", DataType::Utf8, false),
+            Field::new("state", DataType::Utf8, false),
+            Field::new("series_total", DataType::UInt64, false),
+            Field::new("series_done", DataType::UInt64, false),
+            Field::new("points_rewritten", DataType::UInt64, false),
+            Field::new("error", DataType::Utf8, true),
+        ]))
+    }
+
+    /// Whether the column of the table is of the type of the new schema
+    fn is_altered(&self, job: &ConversionJob) -> bool {
+        let schema = self
+            .coord
+            .engine()
+            .get_table_schema(&job.old_schema.db, &job.old_schema.name);
+        let column_type = match schema {
+            Ok(Some(TableSchema::TsKvTableSchema(schema))) => schema
+                .column(&job.column_name)
+                .map(|e| e.column_type.clone()),
+            _ => None,
+        };
+        column_type.is_some()
+            && column_type.as_ref()
+                == job
+                    .new_schema
+                    .column(&job.column_name)
+                    .map(|e| &e.column_type)
+    }
+
+    fn start(self: &Arc<Self>, job: ConversionJob) {
+        let key = job.key();
+        self.conversions.lock().insert(
+            key.clone(),
+            Conversion {
+                job,
+                state: ConversionState::Running,
+                error: None,
+            },
+        );
+
+        let conversions = self.clone();
+        tokio::spawn(async move {
+            let result = conversions.convert(&key).await;
+            if let Some(conversion) = conversions.conversions.lock().get_mut(&key) {
+                let job = &conversion.job;
+                match result {
+                    Ok(_) => {
+                        info!(
+                            "Converted column {} of table {}, {} points rewritten",
+                            job.column_name, job.old_schema.name, job.points
+                        );
+                        conversion.state = ConversionState::Finished;
+                    }
+                    Err(e) => {
+                        error!(
+                            "Failed to convert column {} of table {}: {}",
+                            job.column_name, job.old_schema.name, e
+                        );
+                        conversion.state = ConversionState::Failed;
+                        conversion.error = Some(e.to_string());
+                    }
+                }
+            }
+            if let Err(e) = conversions.save() {
+                warn!("Failed to save the column conversions: {}", e);
+            }
+        });
+    }
+
+    async fn convert(&self, key: &str) -> Result<(), ExecutionError> {
+        let job = match self.conversions.lock().get(key) {
+            Some(conversion) => conversion.job.clone(),
+            None => return Ok(()),
+        };
+        let converter = SeriesConverter {
+            coord: &self.coord,
+            job: &job,
+        };
+
+        for (i, sid) in job.series.iter().enumerate().skip(job.series_done) {
+            let points = converter.convert(*sid).await?;
+            if let Some(conversion) = self.conversions.lock().get_mut(key) {
+                conversion.job.series_done = i + 1;
+                conversion.job.points += points;
+            }
+            if (i + 1) % CHECKPOINT_SERIES == 0 {
+                self.save()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Saves the conversions not finished
+    fn save(&self) -> Result<(), ExecutionError> {
+        let conversions = self.conversions.lock();
+        save(
+            &self.path,
+            conversions
+                .values()
+                .filter(|e| e.state != ConversionState::Finished)
+                .map(|e| &e.job)
+                .collect(),
+        )
+        .context(MetadataSnafu)
+    }
+}
+
+/// Moves the points of the series of a table after a column is converted between
+/// a tag and a field.
+///
+/// The points of a series are read with the old schema, written with the new schema
+/// into the series of the new tags, then deleted from the old series. A field
+/// becoming a tag only moves the points having a value of it, the others stay in
+/// the series without the tag.
+struct SeriesConverter<'a> {
+    coord: &'a CoordinatorRef,
+    job: &'a ConversionJob,
+}
+
+impl SeriesConverter<'_> {
+    /// Returns the number of points rewritten
+    async fn convert(&self, sid: SeriesId) -> Result<u64, ExecutionError> {
+        let engine = self.coord.engine();
+        let job = self.job;
+        let db = &job.old_schema.db;
+        let to_tag = job.to_tag();
+        let key = match engine.get_series_key(db, sid).map_err(external)? {
+            Some(key) => key,
+            None => return Ok(0),
+        };
+        // Only the series having the tag have points to move when it becomes a field
+        let has_tag = key
+            .tags()
+            .iter()
+            .any(|e| e.key == job.column_name.as_bytes());
+        if !to_tag && !has_tag {
+            return Ok(0);
+        }
+
+        let option = QueryOption {
+            table_schema: job.old_schema.clone(),
+            datafusion_schema: job.old_schema.to_arrow_schema(),
+            time_filter: ColumnDomains::all(),
+            tags_filter: ColumnDomains::all(),
+            fields_filter: ColumnDomains::all(),
+        };
+        let metrics = TskvSourceMetrics::new(&ExecutionPlanMetricsSet::new(), 0);
+        let iterator = RowIterator::with_series(
+            metrics,
+            engine.clone(),
+            option,
+            vec![sid],
+            CONVERT_BATCH_SIZE,
+        )
+        .map_err(external)?;
+
+        let mut converted = 0;
+        let mut moved_ranges = vec![];
+        let mut all_moved = true;
+        for batch in iterator {
+            let batch = batch.map_err(external)?;
+            let moved = moved_rows(&batch, &job.column_name, to_tag)?;
+            all_moved &= moved.true_count() == batch.num_rows();
+            moved_ranges.extend(moved_time_ranges(&batch, &moved)?);
+
+            let batch = filter_record_batch(&batch, &moved).context(ArrowSnafu)?;
+            let batch = convert_batch(&batch, &job.new_schema, &job.column_name)?;
+            if batch.num_rows() == 0 {
+                continue;
+            }
+            let points = record_batch_to_points_flat_buffer(&batch, job.new_schema.clone())
+                .map_err(external)?;
+            let req = WritePointsRpcRequest { version: 1, points };
+            self.coord
+                .write_points(&job.tenant, DEFAULT_WRITE_CONSISTENCY, req)
+                .await
+                .map_err(external)?;
+            converted += batch.num_rows() as u64;
+        }
+
+        let field_ids: Vec<ColumnId> = job
+            .old_schema
+            .columns()
+            .iter()
+            .filter(|e| e.column_type.is_field())
+            .map(|e| e.id)
+            .collect();
+        if !all_moved {
+            for range in moved_ranges.iter() {
+                engine
+                    .delete_series(db, &[sid], &field_ids, range)
+                    .map_err(external)?;
+            }
+            return Ok(converted);
+        }
+
+        engine
+            .delete_series(db, &[sid], &field_ids, &TimeRange::all())
+            .map_err(external)?;
+        // New points never have the tag which became a field, while the key of
+        // a series left without points may still be written to
+        if !to_tag {
+            engine.delete_series_index(db, &[sid]).map_err(external)?;
+        }
+
+        Ok(converted)
+    }
+}
+
+/// The rows moved to other series: all the rows when a tag becomes a field, the
+/// rows having a value of the field when a field becomes a tag
+fn moved_rows(
+    batch: &RecordBatch,
+    column_name: &str,
+    to_tag: bool,
+) -> Result<BooleanArray, ExecutionError> {
+    if !to_tag {
+        return Ok(BooleanArray::from(vec![true; batch.num_rows()]));
+    }
+    let index = batch.schema().index_of(column_name).context(ArrowSnafu)?;
+    is_not_null(batch.column(index).as_ref()).context(ArrowSnafu)
+}
+
+/// The time ranges of the consecutive moved rows, the rows are sorted by time
+fn moved_time_ranges(
+    batch: &RecordBatch,
+    moved: &BooleanArray,
+) -> Result<Vec<TimeRange>, ExecutionError> {
+    let index = batch.schema().index_of(TIME_FIELD).context(ArrowSnafu)?;
+    let times = batch
+        .column(index)
+        .as_any()
+        .downcast_ref::<TimestampNanosecondArray>()
+        .ok_or_else(|| {
+            external(ArrowError::CastError(format!(
+                "invalid column {}",
+                TIME_FIELD
+            )))
+        })?;
+
+    let mut ranges: Vec<TimeRange> = vec![];
+    let mut in_range = false;
+    for row in 0..batch.num_rows() {
+        if !moved.value(row) {
+            in_range = false;
+            continue;
+        }
+        let time = times.value(row);
+        match ranges.last_mut() {
+            Some(range) if in_range => range.max_ts = time,
+            _ => ranges.push(TimeRange::new(time, time)),
+        }
+        in_range = true;
+    }
+
+    Ok(ranges)
+}
+
+/// Casts the converted column of the rows read with the old schema to the type of
+/// the new schema. The rows left without any field, e.g. a tag which is not a
+/// number becoming a numeric field, are removed.
+fn convert_batch(
+    batch: &RecordBatch,
+    new_schema: &TskvTableSchema,
+    column_name: &str,
+) -> Result<RecordBatch, ExecutionError> {
+    let batch = unpack_dictionaries(batch).context(ArrowSnafu)?;
+    let schema = batch.schema();
+    let new_column = new_schema.column(column_name).ok_or_else(|| {
+        external(ArrowError::SchemaError(format!(
+            "column {} not found",
+            column_name
+        )))
+    })?;
+    let data_type = if new_column.column_type.is_tag() {
+        DataType::Utf8
+    } else {
+        DataType::from(new_column.column_type)
+    };
+
+    let mut fields = Vec::with_capacity(schema.fields().len());
+    let mut columns = Vec::with_capacity(schema.fields().len());
+    let mut has_field: Option<BooleanArray> = None;
+    for (field, array) in schema.fields().iter().zip(batch.columns()) {
+        let (field, array) = if field.name() == column_name {
+            let array = cast(array, &data_type).context(ArrowSnafu)?;
+            (Field::new(field.name(), data_type.clone(), true), array)
+        } else {
+            (field.clone(), array.clone())
+        };
+
+        let is_field = new_schema
+            .column(field.name())
+            .map(|e| e.column_type.is_field())
+            .unwrap_or_default();
+        if is_field {
+            let valid = is_not_null(array.as_ref()).context(ArrowSnafu)?;
+            has_field = Some(match has_field {
+                Some(has_field) => or(&has_field, &valid).context(ArrowSnafu)?,
+                None => valid,
+            });
+        }
+
+        fields.push(field);
+        columns.push(array);
+    }
+
+    let batch = RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).context(ArrowSnafu)?;
+    match has_field {
+        Some(has_field) => filter_record_batch(&batch, &has_field).context(ArrowSnafu),
+        None => Ok(batch),
+    }
+}
+
+fn external(e: impl std::error::Error + Send + Sync + 'static) -> ExecutionError {
+    ExecutionError::External {
+        source: DataFusionError::External(Box::new(e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::arrow::array::{Array, ArrayRef, Float64Array, StringArray};
+    use models::codec::Encoding;
+    use models::schema::{ColumnType, TableColumn};
+    use models::ValueType;
+
+    use super::*;
+
+    fn schema(value_type: ColumnType) -> TskvTableSchema {
+        TskvTableSchema::new(
+            "db".to_string(),
+            "m".to_string(),
+            vec![
+                TableColumn::new_time_column(0),
+                TableColumn::new_tag_column(1, "host".to_string()),
+                TableColumn::new(2, "region".to_string(), value_type, Encoding::Default),
+                TableColumn::new(
+                    3,
+                    "usage".to_string(),
+                    ColumnType::Field(ValueType::Float),
+                    Encoding::Default,
+                ),
+            ],
+        )
+    }
+
+    fn batch(schema: &TskvTableSchema, region: ArrayRef, usage: Vec<Option<f64>>) -> RecordBatch {
+        let rows = usage.len();
+        let time: TimestampNanosecondArray = (1..=rows as i64).map(Some).collect();
+        let host: StringArray = vec![Some("a"); rows].into_iter().collect();
+        RecordBatch::try_new(
+            schema.to_arrow_schema(),
+            vec![
+                Arc::new(time),
+                cast(
+                    &(Arc::new(host) as ArrayRef),
+                    &DataType::from(ColumnType::Tag),
+                )
+                .unwrap(),
+                region,
+                Arc::new(Float64Array::from(usage)),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_convert_field_to_tag() {
+        let old_schema = schema(ColumnType::Field(ValueType::Float));
+        let new_schema = schema(ColumnType::Tag);
+        let region = Arc::new(Float64Array::from(vec![
+            Some(1.5),
+            None,
+            Some(2.0),
+            Some(3.25),
+        ]));
+        let batch = batch(
+            &old_schema,
+            region,
+            vec![Some(0.1), Some(0.2), None, Some(0.4)],
+        );
+
+        let moved = moved_rows(&batch, "region", true).unwrap();
+        assert_eq!(
+            moved_time_ranges(&batch, &moved).unwrap(),
+            vec![TimeRange::new(1, 1), TimeRange::new(3, 4)]
+        );
+
+        let batch = filter_record_batch(&batch, &moved).unwrap();
+        let converted = convert_batch(&batch, &new_schema, "region").unwrap();
+        // The row at 3 has no field left
+        assert_eq!(converted.num_rows(), 2);
+        let region = converted
+            .column(2)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(region.value(0), "1.5");
+        assert_eq!(region.value(1), "3.25");
+    }
+
+    #[test]
+    fn test_convert_tag_to_field() {
+        let old_schema = schema(ColumnType::Tag);
+        let new_schema = schema(ColumnType::Field(ValueType::Float));
+        let region: StringArray = vec![Some("1.5"), Some("x"), Some("x")]
+            .into_iter()
+            .collect();
+        let region = cast(
+            &(Arc::new(region) as ArrayRef),
+            &DataType::from(ColumnType::Tag),
+        )
+        .unwrap();
+        let batch = batch(&old_schema, region, vec![Some(0.1), Some(0.2), None]);
+
+        let moved = moved_rows(&batch, "region", false).unwrap();
+        assert_eq!(
+            moved_time_ranges(&batch, &moved).unwrap(),
+            vec![TimeRange::new(1, 3)]
+        );
+
+        let converted = convert_batch(&batch, &new_schema, "region").unwrap();
+        // The last row has neither a usage nor a numeric region
+        assert_eq!(converted.num_rows(), 2);
+        let region = converted
+            .column(2)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(region.value(0), 1.5);
+        assert!(region.is_null(1));
+    }
+}
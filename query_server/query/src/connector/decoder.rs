@@ -0,0 +1,283 @@
+use std::collections::BTreeMap;
+
+use chrono::Local;
+use line_protocol::{line_protocol_to_lines, lines_to_points, FieldValue, Line};
+use models::stream_source::{PayloadFormat, StreamSourceDefinition};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::{ConnectorError, Result};
+
+/// Comma separated keys of flat json records that are written as tags, json records
+/// are decoded as flat objects only if the option is set
+pub const TAG_KEYS: &str = "tag_keys";
+
+/// Decodes record payloads into flatbuffers points
+pub struct PayloadDecoder {
+    format: PayloadFormat,
+    /// Set if the json records are flat objects
+    tag_keys: Option<Vec<String>>,
+}
+
+impl PayloadDecoder {
+    pub fn new(definition: &StreamSourceDefinition) -> Self {
+        let tag_keys = definition.option(TAG_KEYS).map(|keys| {
+            keys.split(',')
+                .map(|e| e.trim().to_string())
+                .filter(|e| !e.is_empty())
+                .collect()
+        });
+
+        Self {
+            format: definition.format,
+            tag_keys,
+        }
+    }
+
+    /// Decodes the payload of one record into points of `database`,
+    /// `default_table` is used by json records without `measurement`
+    pub fn decode(
+        &self,
+        database: &str,
+        default_table: Option<&str>,
+        payload: &[u8],
+    ) -> Result<Vec<u8>> {
+        let default_time = Local::now().timestamp_nanos();
+        match self.format {
+            PayloadFormat::LineProtocol => {
+                let lines = std::str::from_utf8(payload).map_err(decode_err)?;
+                let lines = line_protocol_to_lines(lines, default_time).map_err(decode_err)?;
+                if lines.is_empty() {
+                    return Err(decode_err("empty record"));
+                }
+                Ok(lines_to_points(database, &lines))
+            }
+            PayloadFormat::Json => match &self.tag_keys {
+                None => {
+                    let points = decode_json::<JsonPoint>(payload)?;
+                    let lines = points
+                        .iter()
+                        .map(|p| p.to_line(default_table, default_time))
+                        .collect::<Result<Vec<_>>>()?;
+                    Ok(lines_to_points(database, &lines))
+                }
+                Some(tag_keys) => {
+                    let points = decode_json::<BTreeMap<String, Value>>(payload)?;
+                    let lines = points
+                        .iter()
+                        .map(|p| flat_to_line(p, default_table, tag_keys, default_time))
+                        .collect::<Result<Vec<_>>>()?;
+                    Ok(lines_to_points(database, &lines))
+                }
+            },
+        }
+    }
+}
+
+fn decode_err(e: impl ToString) -> ConnectorError {
+    ConnectorError::Decode {
+        reason: e.to_string(),
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonPoints<T> {
+    One(T),
+    Many(Vec<T>),
+}
+
+/// Decodes a json object or an array of them
+fn decode_json<T: DeserializeOwned>(payload: &[u8]) -> Result<Vec<T>> {
+    let points: JsonPoints<T> = serde_json::from_slice(payload).map_err(decode_err)?;
+    let points = match points {
+        JsonPoints::One(p) => vec![p],
+        JsonPoints::Many(p) => p,
+    };
+    if points.is_empty() {
+        return Err(decode_err("empty record"));
+    }
+    Ok(points)
+}
+
+#[derive(Deserialize)]
+struct JsonPoint {
+    measurement: Option<String>,
+    #[serde(default)]
+    tags: BTreeMap<String, String>,
+    fields: BTreeMap<String, Value>,
+    /// Nanoseconds since epoch, defaults to the time of decoding
+    timestamp: Option<i64>,
+}
+
+impl JsonPoint {
+    fn to_line<'a>(
+        &'a self,
+        default_table: Option<&'a str>,
+        default_time: i64,
+    ) -> Result<Line<'a>> {
+        let measurement = self
+            .measurement
+            .as_deref()
+            .or(default_table)
+            .ok_or_else(|| decode_err("missing measurement"))?;
+
+        if self.fields.is_empty() {
+            return Err(decode_err("at least one field is required"));
+        }
+
+        let fields = self
+            .fields
+            .iter()
+            .map(|(k, v)| Ok((k.as_str(), json_to_field_value(k, v)?)))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Line {
+            measurement,
+            tags: self
+                .tags
+                .iter()
+                .map(|(k, v)| (k.as_str(), v.as_str()))
+                .collect(),
+            fields,
+            timestamp: self.timestamp.unwrap_or(default_time),
+        })
+    }
+}
+
+/// A flat object whose keys listed in `tag_keys` are tags, `measurement` and
+/// `timestamp` are the same as those of [`JsonPoint`], the others are fields
+fn flat_to_line<'a>(
+    object: &'a BTreeMap<String, Value>,
+    default_table: Option<&'a str>,
+    tag_keys: &[String],
+    default_time: i64,
+) -> Result<Line<'a>> {
+    let mut measurement = default_table;
+    let mut timestamp = default_time;
+    let mut tags = vec![];
+    let mut fields = vec![];
+    for (k, v) in object.iter() {
+        match (k.as_str(), v) {
+            ("measurement", Value::String(s)) => measurement = Some(s.as_str()),
+            ("measurement", _) => return Err(decode_err("measurement must be a string")),
+            ("timestamp", v) => {
+                timestamp = v
+                    .as_i64()
+                    .ok_or_else(|| decode_err("timestamp must be an integer"))?
+            }
+            (_, Value::Null) => {}
+            (_, Value::String(s)) if tag_keys.contains(k) => tags.push((k.as_str(), s.as_str())),
+            _ if tag_keys.contains(k) => {
+                return Err(decode_err(format!("tag {} must be a string", k)))
+            }
+            _ => fields.push((k.as_str(), json_to_field_value(k, v)?)),
+        }
+    }
+
+    let measurement = measurement.ok_or_else(|| decode_err("missing measurement"))?;
+    if fields.is_empty() {
+        return Err(decode_err("at least one field is required"));
+    }
+
+    Ok(Line {
+        measurement,
+        tags,
+        fields,
+        timestamp,
+    })
+}
+
+fn json_to_field_value(key: &str, value: &Value) -> Result<FieldValue> {
+    let value = match value {
+        Value::Bool(b) => FieldValue::Bool(*b),
+        Value::String(s) => FieldValue::Str(s.as_bytes().to_vec()),
+        Value::Number(n) => match (n.as_i64(), n.as_u64(), n.as_f64()) {
+            (Some(i), _, _) => FieldValue::I64(i),
+            (None, Some(u), _) => FieldValue::U64(u),
+            (None, None, Some(f)) => FieldValue::F64(f),
+            _ => return Err(decode_err(format!("invalid number of field {}", key))),
+        },
+        _ => return Err(decode_err(format!("unsupported value of field {}", key))),
+    };
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use protos::models::Points;
+
+    use super::*;
+
+    fn decoder(format: PayloadFormat, tag_keys: Option<&str>) -> PayloadDecoder {
+        PayloadDecoder {
+            format,
+            tag_keys: tag_keys.map(|keys| keys.split(',').map(|e| e.to_string()).collect()),
+        }
+    }
+
+    fn decode(format: PayloadFormat, payload: &str) -> Result<Vec<u8>> {
+        decoder(format, None).decode("db", Some("default"), payload.as_bytes())
+    }
+
+    #[test]
+    fn test_decode_line_protocol() {
+        let points = decode(
+            PayloadFormat::LineProtocol,
+            "cpu,host=a usage=1.5 1\ncpu,host=b usage=2.5 2",
+        )
+        .unwrap();
+        let points = flatbuffers::root::<Points>(&points).unwrap();
+        assert_eq!(points.points().unwrap().len(), 2);
+
+        assert!(decode(PayloadFormat::LineProtocol, "").is_err());
+        assert!(decode(PayloadFormat::LineProtocol, "cpu,host=a").is_err());
+    }
+
+    #[test]
+    fn test_decode_json() {
+        let points = decode(
+            PayloadFormat::Json,
+            r#"[{"measurement": "cpu", "tags": {"host": "a"}, "fields": {"usage": 1.5}, "timestamp": 1},
+                {"fields": {"usage": 2, "ok": true, "msg": "x"}}]"#,
+        )
+        .unwrap();
+        let points = flatbuffers::root::<Points>(&points).unwrap();
+        let points = points.points().unwrap();
+        assert_eq!(points.len(), 2);
+        assert_eq!(points.get(0).timestamp(), 1);
+        assert_eq!(points.get(1).tab().unwrap().to_vec(), b"default".to_vec());
+
+        assert!(decode(PayloadFormat::Json, "{}").is_err());
+        assert!(decode(PayloadFormat::Json, r#"{"fields": {"a": [1]}}"#).is_err());
+        // Keys other than those of the point are ignored
+        assert!(decode(PayloadFormat::Json, r#"{"fields": {"a": 1}, "b": [1]}"#).is_ok());
+        assert!(decode(PayloadFormat::Json, r#"{"a": 1}"#).is_err());
+        assert!(decoder(PayloadFormat::Json, None)
+            .decode("db", None, r#"{"fields": {"a": 1}}"#.as_bytes())
+            .is_err());
+    }
+
+    #[test]
+    fn test_decode_flat_json() {
+        let decoder = decoder(PayloadFormat::Json, Some("device"));
+        let points = decoder
+            .decode(
+                "db",
+                Some("sensor"),
+                r#"{"device": "d1", "temperature": 21.5, "timestamp": 5}"#.as_bytes(),
+            )
+            .unwrap();
+        let points = flatbuffers::root::<Points>(&points).unwrap();
+        let point = points.points().unwrap().get(0);
+        assert_eq!(point.tab().unwrap().to_vec(), b"sensor".to_vec());
+        assert_eq!(point.tags().unwrap().len(), 1);
+        assert_eq!(point.fields().unwrap().len(), 1);
+        assert_eq!(point.timestamp(), 5);
+
+        assert!(decoder
+            .decode("db", Some("sensor"), r#"{"device": 1, "t": 1}"#.as_bytes())
+            .is_err());
+    }
+}
@@ -0,0 +1,208 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use models::stream_source::{StreamSourceDefinition, StreamSourceState};
+use rdkafka::consumer::{CommitMode, Consumer, StreamConsumer};
+use rdkafka::message::{BorrowedMessage, Header, Message, OwnedHeaders};
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::ClientConfig;
+use snafu::ResultExt;
+use tokio_util::sync::CancellationToken;
+use trace::{debug, info};
+
+use super::decoder::PayloadDecoder;
+use super::{retry_transient, ConnectorError, KafkaSnafu, PointWriter, Result, SourceStats};
+
+pub const BROKERS: &str = "brokers";
+pub const TOPIC: &str = "topic";
+pub const GROUP_ID: &str = "group_id";
+pub const DEAD_LETTER_TOPIC: &str = "dead_letter_topic";
+pub const AUTO_OFFSET_RESET: &str = "auto_offset_reset";
+/// Options with this prefix are passed to librdkafka as is, e.g. `kafka.security.protocol`
+pub const CLIENT_OPTION_PREFIX: &str = "kafka.";
+
+/// Header of dead letter records which holds the reason of the failure
+const ERROR_HEADER: &str = "cnosdb_error";
+const DEAD_LETTER_TIMEOUT: Duration = Duration::from_secs(5);
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Consumes a kafka topic as a member of a consumer group.
+///
+/// Offsets are committed after the record is written into tskv, failed writes are
+/// retried while the cluster is unavailable. Records which can never be written are
+/// sent to the dead letter topic, or skipped if there is none. Records are delivered
+/// at least once across restarts.
+pub struct KafkaSource {
+    name: String,
+    decoder: PayloadDecoder,
+    table: Option<String>,
+    consumer: StreamConsumer,
+    dead_letter: Option<(FutureProducer, String)>,
+}
+
+impl KafkaSource {
+    pub fn try_new(definition: &StreamSourceDefinition) -> Result<Self> {
+        let required = |key: &str| {
+            definition
+                .option(key)
+                .ok_or_else(|| ConnectorError::InvalidOption {
+                    reason: format!("'{}' is required by kafka stream source", key),
+                })
+        };
+        let brokers = required(BROKERS)?;
+        let topics = required(TOPIC)?
+            .split(',')
+            .map(|e| e.trim())
+            .filter(|e| !e.is_empty())
+            .collect::<Vec<_>>();
+        if topics.is_empty() {
+            return Err(ConnectorError::InvalidOption {
+                reason: format!("'{}' can not be empty", TOPIC),
+            });
+        }
+        let group_id = definition
+            .option(GROUP_ID)
+            .map(|e| e.to_string())
+            .unwrap_or_else(|| format!("cnosdb-{}", definition.name));
+
+        let mut client_config = ClientConfig::new();
+        client_config.set("bootstrap.servers", brokers);
+        for (k, v) in definition.options.iter() {
+            if let Some(key) = k.strip_prefix(CLIENT_OPTION_PREFIX) {
+                client_config.set(key, v);
+            }
+        }
+
+        let consumer: StreamConsumer = client_config
+            .clone()
+            .set("group.id", group_id)
+            .set("enable.auto.commit", "false")
+            .set(
+                "auto.offset.reset",
+                definition.option(AUTO_OFFSET_RESET).unwrap_or("earliest"),
+            )
+            .create()
+            .context(KafkaSnafu)?;
+        consumer.subscribe(&topics).context(KafkaSnafu)?;
+
+        let dead_letter = match definition.option(DEAD_LETTER_TOPIC) {
+            Some(topic) => {
+                let producer: FutureProducer = client_config.create().context(KafkaSnafu)?;
+                Some((producer, topic.to_string()))
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            name: definition.name.clone(),
+            decoder: PayloadDecoder::new(definition),
+            table: definition.table.clone(),
+            consumer,
+            dead_letter,
+        })
+    }
+
+    pub async fn run(
+        self,
+        writer: PointWriter,
+        stats: Arc<SourceStats>,
+        cancel: CancellationToken,
+    ) {
+        info!("Kafka stream source {} is running", self.name);
+
+        loop {
+            let message = tokio::select! {
+                _ = cancel.cancelled() => break,
+                message = self.consumer.recv() => message,
+            };
+
+            let message = match message {
+                Ok(message) => message,
+                Err(e) => {
+                    stats.record_error(&ConnectorError::Kafka { source: e });
+                    tokio::time::sleep(RETRY_INTERVAL).await;
+                    continue;
+                }
+            };
+
+            // The offset is not committed until the record is written or given up
+            let result =
+                match retry_transient(&stats, &cancel, || self.process(&writer, &message)).await {
+                    Some(result) => result,
+                    None => break,
+                };
+
+            match result {
+                Ok(_) => stats.record_written(),
+                Err(e) => {
+                    // The record can never be written, e.g. it can not be decoded
+                    stats.record_failed(&e);
+                    if let Err(e) = self.send_to_dead_letter(&message, &e).await {
+                        // Leave the offset uncommitted, the record will be consumed again
+                        stats.record_error(&e);
+                        stats.set_state(StreamSourceState::Failed);
+                        break;
+                    }
+                }
+            }
+
+            if let Err(e) = self
+                .consumer
+                .commit_message(&message, CommitMode::Async)
+                .context(KafkaSnafu)
+            {
+                stats.record_error(&e);
+            }
+        }
+
+        if cancel.is_cancelled() {
+            stats.set_state(StreamSourceState::Stopped);
+            info!("Kafka stream source {} is stopped", self.name);
+        }
+    }
+
+    async fn process(&self, writer: &PointWriter, message: &BorrowedMessage<'_>) -> Result<()> {
+        let payload = message.payload().unwrap_or_default();
+        let points = self
+            .decoder
+            .decode(writer.database(), self.table.as_deref(), payload)?;
+        writer.write(points).await
+    }
+
+    async fn send_to_dead_letter(
+        &self,
+        message: &BorrowedMessage<'_>,
+        err: &ConnectorError,
+    ) -> Result<()> {
+        let (producer, topic) = match &self.dead_letter {
+            Some(e) => e,
+            None => return Ok(()),
+        };
+
+        let reason = err.to_string();
+        let headers = OwnedHeaders::new().insert(Header {
+            key: ERROR_HEADER,
+            value: Some(&reason),
+        });
+        let mut record = FutureRecord::<[u8], [u8]>::to(topic)
+            .payload(message.payload().unwrap_or_default())
+            .headers(headers);
+        if let Some(key) = message.key() {
+            record = record.key(key);
+        }
+
+        producer
+            .send(record, DEAD_LETTER_TIMEOUT)
+            .await
+            .map_err(|(source, _)| ConnectorError::Kafka { source })?;
+
+        debug!(
+            "Kafka stream source {} sent record at offset {} to {}",
+            self.name,
+            message.offset(),
+            topic
+        );
+
+        Ok(())
+    }
+}
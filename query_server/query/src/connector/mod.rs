@@ -0,0 +1,347 @@
+//! Connectors which continuously ingest records from external systems into tskv
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use coordinator::errors::CoordinatorError;
+use coordinator::service::CoordinatorRef;
+use coordinator::writer::DEFAULT_WRITE_CONSISTENCY;
+use models::stream_source::{
+    ConnectorType, StreamSourceDefinition, StreamSourceState, StreamSourceStatus,
+};
+use parking_lot::RwLock;
+use protos::kv_service::WritePointsRpcRequest;
+use serde::{Deserialize, Serialize};
+use snafu::Snafu;
+use spi::catalog::MetadataError;
+use tokio_util::sync::CancellationToken;
+use trace::{info, warn};
+
+use self::kafka::KafkaSource;
+use self::mqtt::MqttSource;
+use crate::auth::{load, save};
+
+mod decoder;
+mod kafka;
+mod mqtt;
+
+pub type Result<T, E = ConnectorError> = std::result::Result<T, E>;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum ConnectorError {
+    #[snafu(display("Invalid option: {}", reason))]
+    InvalidOption { reason: String },
+
+    #[snafu(display("Failed to decode record: {}", reason))]
+    Decode { reason: String },
+
+    #[snafu(display("Failed to write points: {}", source))]
+    Write { source: CoordinatorError },
+
+    #[snafu(display("Kafka error: {}", source))]
+    Kafka { source: rdkafka::error::KafkaError },
+
+    #[snafu(display("Mqtt error: {}", reason))]
+    Mqtt { reason: String },
+}
+
+impl ConnectorError {
+    /// Whether writing the record again may succeed, the record is retried instead of
+    /// being skipped
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Write { source } => source.is_retryable() || source.is_write_stalled(),
+            _ => false,
+        }
+    }
+}
+
+pub type StreamSourceManagerRef = Arc<StreamSourceManager>;
+
+const MIN_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+const MAX_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// File of the stream sources of this node
+pub const STREAM_SOURCES_FILE: &str = "stream_sources.json";
+
+/// Owns the running stream sources of this node
+pub struct StreamSourceManager {
+    coord: CoordinatorRef,
+    path: PathBuf,
+    sources: RwLock<HashMap<String, RunningSource>>,
+}
+
+struct RunningSource {
+    tenant: String,
+    definition: StreamSourceDefinition,
+    stats: Arc<SourceStats>,
+    cancel: CancellationToken,
+}
+
+/// A stream source saved to the file of the stream sources
+#[derive(Serialize, Deserialize)]
+struct SavedSource {
+    tenant: String,
+    definition: StreamSourceDefinition,
+}
+
+impl StreamSourceManager {
+    /// Saves the stream sources to the file in the directory, the stream sources
+    /// saved before are started again
+    pub fn open(coord: CoordinatorRef, dir: impl AsRef<Path>) -> std::io::Result<Self> {
+        std::fs::create_dir_all(dir.as_ref())?;
+        let path = dir.as_ref().join(STREAM_SOURCES_FILE);
+        let saved: Vec<SavedSource> = load(&path)?;
+
+        let manager = Self {
+            coord,
+            path,
+            sources: RwLock::new(HashMap::new()),
+        };
+        let mut sources = manager.sources.write();
+        for SavedSource { tenant, definition } in saved {
+            let source = manager
+                .start(&tenant, definition)
+                .unwrap_or_else(|(definition, e)| {
+                    // Kept so that it is listed with the error and can be dropped
+                    warn!("Failed to restart stream source {}: {}", definition.name, e);
+                    let stats = Arc::new(SourceStats::new());
+                    stats.record_error(&e);
+                    stats.set_state(StreamSourceState::Failed);
+                    RunningSource {
+                        tenant,
+                        definition,
+                        stats,
+                        cancel: CancellationToken::new(),
+                    }
+                });
+            sources.insert(source.definition.name.clone(), source);
+        }
+        drop(sources);
+
+        Ok(manager)
+    }
+
+    /// Starts the source writing into the database of the tenant
+    pub fn create(
+        &self,
+        tenant: &str,
+        definition: StreamSourceDefinition,
+    ) -> Result<(), MetadataError> {
+        let mut sources = self.sources.write();
+        if sources.contains_key(&definition.name) {
+            return Err(MetadataError::StreamSourceAlreadyExists {
+                source_name: definition.name,
+            });
+        }
+
+        let source = self.start(tenant, definition).map_err(|(definition, e)| {
+            MetadataError::InvalidStreamSource {
+                source_name: definition.name,
+                error_msg: e.to_string(),
+            }
+        })?;
+        let mut new_sources = sources
+            .values()
+            .map(|e| (e.tenant.as_str(), &e.definition))
+            .collect::<Vec<_>>();
+        new_sources.push((source.tenant.as_str(), &source.definition));
+        if let Err(e) = self.save(new_sources) {
+            source.cancel.cancel();
+            return Err(e);
+        }
+
+        sources.insert(source.definition.name.clone(), source);
+
+        Ok(())
+    }
+
+    pub fn remove(&self, name: &str) -> Result<(), MetadataError> {
+        let mut sources = self.sources.write();
+        if !sources.contains_key(name) {
+            return Err(MetadataError::StreamSourceNotExists {
+                source_name: name.to_string(),
+            });
+        }
+        self.save(
+            sources
+                .values()
+                .filter(|e| e.definition.name != name)
+                .map(|e| (e.tenant.as_str(), &e.definition))
+                .collect(),
+        )?;
+
+        if let Some(source) = sources.remove(name) {
+            source.cancel.cancel();
+            info!("Stream source {} stopped", name);
+        }
+
+        Ok(())
+    }
+
+    pub fn list(&self) -> Vec<StreamSourceStatus> {
+        let mut result = self
+            .sources
+            .read()
+            .values()
+            .map(|e| e.stats.status(e.definition.clone()))
+            .collect::<Vec<_>>();
+        result.sort_by(|a, b| a.definition.name.cmp(&b.definition.name));
+        result
+    }
+
+    fn start(
+        &self,
+        tenant: &str,
+        definition: StreamSourceDefinition,
+    ) -> std::result::Result<RunningSource, (StreamSourceDefinition, ConnectorError)> {
+        let stats = Arc::new(SourceStats::new());
+        let cancel = CancellationToken::new();
+        let writer = PointWriter {
+            coord: self.coord.clone(),
+            tenant: tenant.to_string(),
+            database: definition.database.clone(),
+        };
+
+        let started = match definition.connector {
+            ConnectorType::Kafka => KafkaSource::try_new(&definition)
+                .map(|source| tokio::spawn(source.run(writer, stats.clone(), cancel.clone()))),
+            ConnectorType::Mqtt => MqttSource::try_new(&definition)
+                .map(|source| tokio::spawn(source.run(writer, stats.clone(), cancel.clone()))),
+        };
+        if let Err(e) = started {
+            return Err((definition, e));
+        }
+
+        info!(
+            "Stream source {} started, connector: {}, format: {}",
+            definition.name, definition.connector, definition.format
+        );
+
+        Ok(RunningSource {
+            tenant: tenant.to_string(),
+            definition,
+            stats,
+            cancel,
+        })
+    }
+
+    fn save(&self, sources: Vec<(&str, &StreamSourceDefinition)>) -> Result<(), MetadataError> {
+        let sources = sources
+            .into_iter()
+            .map(|(tenant, definition)| SavedSource {
+                tenant: tenant.to_string(),
+                definition: definition.clone(),
+            })
+            .collect::<Vec<_>>();
+        save(&self.path, sources.iter().collect())
+    }
+}
+
+impl Drop for StreamSourceManager {
+    fn drop(&mut self) {
+        for source in self.sources.read().values() {
+            source.cancel.cancel();
+        }
+    }
+}
+
+/// Runs `process` until it succeeds or fails permanently, transient failures are
+/// retried with backoff. `None` if the source is cancelled in the meantime.
+pub async fn retry_transient<F, Fut>(
+    stats: &SourceStats,
+    cancel: &CancellationToken,
+    mut process: F,
+) -> Option<Result<()>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let mut backoff = MIN_RETRY_INTERVAL;
+    loop {
+        match process().await {
+            Err(e) if e.is_transient() => {
+                stats.record_error(&e);
+                tokio::select! {
+                    _ = cancel.cancelled() => return None,
+                    _ = tokio::time::sleep(backoff) => {}
+                }
+                backoff = (backoff * 2).min(MAX_RETRY_INTERVAL);
+            }
+            result => return Some(result),
+        }
+    }
+}
+
+/// Writes decoded points of a stream source into its database
+#[derive(Clone)]
+pub struct PointWriter {
+    coord: CoordinatorRef,
+    tenant: String,
+    database: String,
+}
+
+impl PointWriter {
+    pub fn database(&self) -> &str {
+        &self.database
+    }
+
+    pub async fn write(&self, points: Vec<u8>) -> Result<()> {
+        let req = WritePointsRpcRequest { version: 1, points };
+        self.coord
+            .write_points(&self.tenant, DEFAULT_WRITE_CONSISTENCY, req)
+            .await
+            .map(|_| ())
+            .map_err(|source| ConnectorError::Write { source })
+    }
+}
+
+pub struct SourceStats {
+    records_written: AtomicU64,
+    records_failed: AtomicU64,
+    state: RwLock<StreamSourceState>,
+    last_error: RwLock<Option<String>>,
+}
+
+impl SourceStats {
+    fn new() -> Self {
+        Self {
+            records_written: AtomicU64::new(0),
+            records_failed: AtomicU64::new(0),
+            state: RwLock::new(StreamSourceState::Running),
+            last_error: RwLock::new(None),
+        }
+    }
+
+    pub fn record_written(&self) {
+        self.records_written.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_failed(&self, err: &ConnectorError) {
+        self.records_failed.fetch_add(1, Ordering::Relaxed);
+        self.record_error(err);
+    }
+
+    pub fn record_error(&self, err: &ConnectorError) {
+        warn!("Stream source error: {}", err);
+        *self.last_error.write() = Some(err.to_string());
+    }
+
+    pub fn set_state(&self, state: StreamSourceState) {
+        *self.state.write() = state;
+    }
+
+    fn status(&self, definition: StreamSourceDefinition) -> StreamSourceStatus {
+        StreamSourceStatus {
+            definition,
+            state: *self.state.read(),
+            records_written: self.records_written.load(Ordering::Relaxed),
+            records_failed: self.records_failed.load(Ordering::Relaxed),
+            last_error: self.last_error.read().clone(),
+        }
+    }
+}
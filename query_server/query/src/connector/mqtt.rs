@@ -0,0 +1,289 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use models::stream_source::{StreamSourceDefinition, StreamSourceState};
+use rumqttc::{AsyncClient, Event, EventLoop, MqttOptions, Packet, Publish, QoS};
+use tokio_util::sync::CancellationToken;
+use trace::{debug, info};
+
+use super::decoder::PayloadDecoder;
+use super::{retry_transient, ConnectorError, PointWriter, Result, SourceStats};
+
+pub const BROKER: &str = "broker";
+pub const TOPIC: &str = "topic";
+pub const CLIENT_ID: &str = "client_id";
+pub const QOS: &str = "qos";
+pub const USERNAME: &str = "username";
+pub const PASSWORD: &str = "password";
+pub const DEAD_LETTER_TOPIC: &str = "dead_letter_topic";
+
+const DEFAULT_PORT: u16 = 1883;
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+const REQUEST_CAPACITY: usize = 100;
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Subscribes mqtt topic filters, the table of a message is rendered from
+/// the `table` template with the levels of its topic, see [`render_table`].
+///
+/// The session is persistent and messages are acknowledged after they are written
+/// into tskv, failed writes are retried while the cluster is unavailable. Messages
+/// which can never be written are published to the dead letter topic, or skipped if
+/// there is none. With qos 1 or 2 messages received while the subscriber is
+/// disconnected are not lost.
+pub struct MqttSource {
+    name: String,
+    decoder: PayloadDecoder,
+    table: Option<String>,
+    qos: QoS,
+    client: AsyncClient,
+    event_loop: EventLoop,
+    dead_letter_topic: Option<String>,
+}
+
+impl MqttSource {
+    pub fn try_new(definition: &StreamSourceDefinition) -> Result<Self> {
+        let required = |key: &str| {
+            definition
+                .option(key)
+                .ok_or_else(|| ConnectorError::InvalidOption {
+                    reason: format!("'{}' is required by mqtt stream source", key),
+                })
+        };
+        let (host, port) = parse_broker(required(BROKER)?)?;
+        let topics = required(TOPIC)?
+            .split(',')
+            .map(|e| e.trim())
+            .filter(|e| !e.is_empty())
+            .collect::<Vec<_>>();
+        if topics.is_empty() {
+            return Err(ConnectorError::InvalidOption {
+                reason: format!("'{}' can not be empty", TOPIC),
+            });
+        }
+        let qos = match definition.option(QOS).unwrap_or("1") {
+            "0" => QoS::AtMostOnce,
+            "1" => QoS::AtLeastOnce,
+            "2" => QoS::ExactlyOnce,
+            other => {
+                return Err(ConnectorError::InvalidOption {
+                    reason: format!("'{}' must be one of 0, 1, 2, but found {}", QOS, other),
+                })
+            }
+        };
+        let client_id = definition
+            .option(CLIENT_ID)
+            .map(|e| e.to_string())
+            .unwrap_or_else(|| format!("cnosdb-{}", definition.name));
+
+        let mut options = MqttOptions::new(client_id, host, port);
+        options
+            .set_keep_alive(KEEP_ALIVE)
+            .set_clean_session(false)
+            .set_manual_acks(true);
+        if let Some(username) = definition.option(USERNAME) {
+            options.set_credentials(username, definition.option(PASSWORD).unwrap_or_default());
+        }
+
+        let (client, event_loop) = AsyncClient::new(options, REQUEST_CAPACITY);
+        for topic in topics {
+            client
+                .try_subscribe(topic, qos)
+                .map_err(|e| ConnectorError::Mqtt {
+                    reason: e.to_string(),
+                })?;
+        }
+
+        Ok(Self {
+            name: definition.name.clone(),
+            decoder: PayloadDecoder::new(definition),
+            table: definition.table.clone(),
+            qos,
+            client,
+            event_loop,
+            dead_letter_topic: definition.option(DEAD_LETTER_TOPIC).map(|e| e.to_string()),
+        })
+    }
+
+    pub async fn run(
+        mut self,
+        writer: PointWriter,
+        stats: Arc<SourceStats>,
+        cancel: CancellationToken,
+    ) {
+        info!("Mqtt stream source {} is running", self.name);
+
+        loop {
+            let event = tokio::select! {
+                _ = cancel.cancelled() => break,
+                event = self.event_loop.poll() => event,
+            };
+
+            let publish = match event {
+                Ok(Event::Incoming(Packet::Publish(publish))) => publish,
+                Ok(_) => continue,
+                Err(e) => {
+                    // The event loop reconnects on the next poll
+                    stats.record_error(&ConnectorError::Mqtt {
+                        reason: e.to_string(),
+                    });
+                    tokio::time::sleep(RETRY_INTERVAL).await;
+                    continue;
+                }
+            };
+
+            // The message is not acknowledged until it is written or given up
+            let result =
+                match retry_transient(&stats, &cancel, || self.process(&writer, &publish)).await {
+                    Some(result) => result,
+                    None => break,
+                };
+
+            match result {
+                Ok(_) => stats.record_written(),
+                Err(e) => {
+                    // The message can never be written, e.g. it can not be decoded
+                    stats.record_failed(&e);
+                    if let Err(e) = self.send_to_dead_letter(&publish).await {
+                        // Leave the message unacknowledged, the broker will redeliver it
+                        stats.record_error(&e);
+                        stats.set_state(StreamSourceState::Failed);
+                        break;
+                    }
+                }
+            }
+
+            if let Err(e) = self.client.ack(&publish).await {
+                stats.record_error(&ConnectorError::Mqtt {
+                    reason: e.to_string(),
+                });
+            }
+        }
+
+        if cancel.is_cancelled() {
+            let _ = self.client.try_disconnect();
+            stats.set_state(StreamSourceState::Stopped);
+            info!("Mqtt stream source {} is stopped", self.name);
+        }
+    }
+
+    async fn process(&self, writer: &PointWriter, publish: &Publish) -> Result<()> {
+        let table = self
+            .table
+            .as_deref()
+            .map(|template| render_table(template, &publish.topic))
+            .transpose()?;
+        let points = self
+            .decoder
+            .decode(writer.database(), table.as_deref(), &publish.payload)?;
+        writer.write(points).await
+    }
+
+    async fn send_to_dead_letter(&self, publish: &Publish) -> Result<()> {
+        let topic = match &self.dead_letter_topic {
+            Some(topic) => topic,
+            None => return Ok(()),
+        };
+
+        self.client
+            .publish(topic, self.qos, false, publish.payload.to_vec())
+            .await
+            .map_err(|e| ConnectorError::Mqtt {
+                reason: e.to_string(),
+            })?;
+
+        debug!(
+            "Mqtt stream source {} sent message of {} to {}",
+            self.name, publish.topic, topic
+        );
+
+        Ok(())
+    }
+}
+
+/// Accepts `host`, `host:port` and `tcp://host:port`
+fn parse_broker(broker: &str) -> Result<(String, u16)> {
+    let address = broker
+        .strip_prefix("tcp://")
+        .or_else(|| broker.strip_prefix("mqtt://"))
+        .unwrap_or(broker);
+
+    match address.rsplit_once(':') {
+        Some((host, port)) => {
+            let port = port.parse().map_err(|_| ConnectorError::InvalidOption {
+                reason: format!("invalid port of broker {}", broker),
+            })?;
+            Ok((host.to_string(), port))
+        }
+        None => Ok((address.to_string(), DEFAULT_PORT)),
+    }
+}
+
+/// Replaces `{N}` in the template with the N-th level (from 0) of the topic,
+/// e.g. `{1}` of topic `factory/cnc/temperature` is `cnc`. It is an error if the
+/// topic has no such level, or the level is empty.
+fn render_table(template: &str, topic: &str) -> Result<String> {
+    let levels = topic.split('/').collect::<Vec<_>>();
+    let mut table = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        table.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let placeholder = after
+            .find('}')
+            .and_then(|end| after[..end].parse::<usize>().ok().map(|i| (i, end)));
+        match placeholder {
+            Some((i, end)) => {
+                let level = levels.get(i).filter(|e| !e.is_empty()).ok_or_else(|| {
+                    ConnectorError::Decode {
+                        reason: format!(
+                            "topic {} has no level {{{}}} of table {}",
+                            topic, i, template
+                        ),
+                    }
+                })?;
+                table.push_str(level);
+                rest = &after[end + 1..];
+            }
+            None => {
+                table.push('{');
+                rest = after;
+            }
+        }
+    }
+    table.push_str(rest);
+    Ok(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_broker() {
+        assert_eq!(
+            parse_broker("tcp://localhost:1884").unwrap(),
+            ("localhost".to_string(), 1884)
+        );
+        assert_eq!(
+            parse_broker("localhost").unwrap(),
+            ("localhost".to_string(), DEFAULT_PORT)
+        );
+        assert!(parse_broker("localhost:abc").is_err());
+    }
+
+    #[test]
+    fn test_render_table() {
+        assert_eq!(
+            render_table("{1}", "factory/cnc/temperature").unwrap(),
+            "cnc"
+        );
+        assert_eq!(
+            render_table("{0}_{2}", "factory/cnc/temperature").unwrap(),
+            "factory_temperature"
+        );
+        assert_eq!(render_table("sensor", "factory/cnc").unwrap(), "sensor");
+        assert_eq!(render_table("{x}_{1}", "factory/cnc").unwrap(), "{x}_cnc");
+        assert!(render_table("{2}", "factory/cnc").is_err());
+        assert!(render_table("{1}", "factory//temperature").is_err());
+    }
+}
@@ -0,0 +1,145 @@
+use std::sync::Arc;
+
+use config::ObjectStoreConfig;
+use datafusion::datasource::object_store::ObjectStoreProvider;
+use datafusion::error::{DataFusionError, Result};
+use object_store::aws::AmazonS3Builder;
+use object_store::azure::MicrosoftAzureBuilder;
+use object_store::gcp::GoogleCloudStorageBuilder;
+use object_store::ObjectStore;
+use trace::info;
+use url::Url;
+
+use super::decompress_store::DecompressObjectStore;
+
+/// Creates the object store of a bucket the first time a location in it is accessed,
+/// e.g. by `CREATE EXTERNAL TABLE ... LOCATION 's3://bucket/path'`.
+///
+/// The stores are cached by the registry of the runtime env, keyed by scheme and bucket.
+pub struct CloudObjectStoreProvider {
+    config: ObjectStoreConfig,
+}
+
+impl CloudObjectStoreProvider {
+    pub fn new(config: ObjectStoreConfig) -> Self {
+        Self { config }
+    }
+
+    fn build_s3(&self, bucket: &str) -> Result<Arc<dyn ObjectStore>> {
+        let mut builder = AmazonS3Builder::new().with_bucket_name(bucket);
+        if let Some(config) = &self.config.s3 {
+            if let Some(region) = &config.region {
+                builder = builder.with_region(region);
+            }
+            if let Some(endpoint) = &config.endpoint {
+                builder = builder.with_endpoint(endpoint);
+            }
+            if let Some(access_key_id) = &config.access_key_id {
+                builder = builder.with_access_key_id(access_key_id);
+            }
+            if let Some(secret_access_key) = &config.secret_access_key {
+                builder = builder.with_secret_access_key(secret_access_key);
+            }
+            if let Some(session_token) = &config.session_token {
+                builder = builder.with_token(session_token);
+            }
+            builder = builder.with_allow_http(config.allow_http);
+        }
+
+        Ok(Arc::new(builder.build()?))
+    }
+
+    fn build_gcs(&self, bucket: &str) -> Result<Arc<dyn ObjectStore>> {
+        let config = self
+            .config
+            .gcs
+            .as_ref()
+            .ok_or_else(|| missing_config("gcs"))?;
+        let store = GoogleCloudStorageBuilder::new()
+            .with_bucket_name(bucket)
+            .with_service_account_path(&config.service_account_path)
+            .build()?;
+
+        Ok(Arc::new(store))
+    }
+
+    fn build_azure(&self, container: &str) -> Result<Arc<dyn ObjectStore>> {
+        let config = self
+            .config
+            .azure
+            .as_ref()
+            .ok_or_else(|| missing_config("azure"))?;
+        let store = MicrosoftAzureBuilder::new()
+            .with_account(&config.account)
+            .with_access_key(&config.access_key)
+            .with_container_name(container)
+            .build()?;
+
+        Ok(Arc::new(store))
+    }
+}
+
+impl ObjectStoreProvider for CloudObjectStoreProvider {
+    fn get_by_url(&self, url: &Url) -> Result<Arc<dyn ObjectStore>> {
+        let bucket = url
+            .host_str()
+            .filter(|e| !e.is_empty())
+            .ok_or_else(|| DataFusionError::Execution(format!("missing bucket in {}", url)))?;
+
+        let store = match url.scheme() {
+            "s3" => self.build_s3(bucket)?,
+            "gs" => self.build_gcs(bucket)?,
+            "az" | "azure" => self.build_azure(bucket)?,
+            scheme => {
+                return Err(DataFusionError::Execution(format!(
+                    "object store of scheme {} is not supported, expected one of s3, gs, az",
+                    scheme
+                )))
+            }
+        };
+
+        info!("Object store created for {}://{}", url.scheme(), bucket);
+
+        Ok(Arc::new(DecompressObjectStore::new(store)))
+    }
+}
+
+fn missing_config(name: &str) -> DataFusionError {
+    DataFusionError::Execution(format!(
+        "[object_store.{}] is not configured, credentials are required",
+        name
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use config::S3Config;
+
+    use super::*;
+
+    #[test]
+    fn test_get_by_url() {
+        let provider = CloudObjectStoreProvider::new(ObjectStoreConfig {
+            s3: Some(S3Config {
+                region: Some("us-east-1".to_string()),
+                endpoint: Some("http://localhost:9000".to_string()),
+                access_key_id: Some("minioadmin".to_string()),
+                secret_access_key: Some("minioadmin".to_string()),
+                session_token: None,
+                allow_http: true,
+            }),
+            gcs: None,
+            azure: None,
+        });
+
+        assert!(provider
+            .get_by_url(&Url::parse("s3://bucket/path").unwrap())
+            .is_ok());
+        assert!(provider
+            .get_by_url(&Url::parse("gs://bucket/path").unwrap())
+            .is_err());
+        assert!(provider
+            .get_by_url(&Url::parse("hdfs://bucket/path").unwrap())
+            .is_err());
+    }
+}
@@ -0,0 +1,154 @@
+use std::fmt::{Debug, Display, Formatter};
+use std::ops::Range;
+use std::sync::Arc;
+
+use async_compression::tokio::bufread::ZstdDecoder;
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::stream::BoxStream;
+use futures::{StreamExt, TryStreamExt};
+use models::schema::ZSTD_EXTENSION;
+use object_store::path::Path;
+use object_store::{GetResult, ListResult, MultipartId, ObjectMeta, ObjectStore, Result};
+use tokio::io::AsyncWrite;
+use tokio_util::io::{ReaderStream, StreamReader};
+
+const STORE: &str = "DecompressObjectStore";
+
+/// Decompresses zstd objects (`*.zst`) while they are read, other objects are passed through.
+///
+/// Gzip and bzip2 files are decompressed by the file formats of datafusion,
+/// zstd ones are read as uncompressed files through this store.
+pub struct DecompressObjectStore {
+    inner: Arc<dyn ObjectStore>,
+}
+
+impl DecompressObjectStore {
+    pub fn new(inner: Arc<dyn ObjectStore>) -> Self {
+        Self { inner }
+    }
+
+    fn is_zstd(location: &Path) -> bool {
+        location.as_ref().ends_with(ZSTD_EXTENSION)
+    }
+}
+
+impl Debug for DecompressObjectStore {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "DecompressObjectStore({:?})", self.inner)
+    }
+}
+
+impl Display for DecompressObjectStore {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "DecompressObjectStore({})", self.inner)
+    }
+}
+
+#[async_trait]
+impl ObjectStore for DecompressObjectStore {
+    async fn put(&self, location: &Path, bytes: Bytes) -> Result<()> {
+        self.inner.put(location, bytes).await
+    }
+
+    async fn put_multipart(
+        &self,
+        location: &Path,
+    ) -> Result<(MultipartId, Box<dyn AsyncWrite + Unpin + Send>)> {
+        self.inner.put_multipart(location).await
+    }
+
+    async fn abort_multipart(&self, location: &Path, multipart_id: &MultipartId) -> Result<()> {
+        self.inner.abort_multipart(location, multipart_id).await
+    }
+
+    async fn get(&self, location: &Path) -> Result<GetResult> {
+        let result = self.inner.get(location).await?;
+        if !Self::is_zstd(location) {
+            return Ok(result);
+        }
+
+        let compressed = result
+            .into_stream()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e));
+        let decoder = ZstdDecoder::new(StreamReader::new(compressed));
+        let stream = ReaderStream::new(decoder)
+            .map_err(|e| object_store::Error::Generic {
+                store: STORE,
+                source: Box::new(e),
+            })
+            .boxed();
+
+        Ok(GetResult::Stream(stream))
+    }
+
+    async fn get_range(&self, location: &Path, range: Range<usize>) -> Result<Bytes> {
+        if Self::is_zstd(location) {
+            return Err(object_store::Error::NotImplemented);
+        }
+        self.inner.get_range(location, range).await
+    }
+
+    async fn head(&self, location: &Path) -> Result<ObjectMeta> {
+        self.inner.head(location).await
+    }
+
+    async fn delete(&self, location: &Path) -> Result<()> {
+        self.inner.delete(location).await
+    }
+
+    async fn list(&self, prefix: Option<&Path>) -> Result<BoxStream<'_, Result<ObjectMeta>>> {
+        self.inner.list(prefix).await
+    }
+
+    async fn list_with_delimiter(&self, prefix: Option<&Path>) -> Result<ListResult> {
+        self.inner.list_with_delimiter(prefix).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<()> {
+        self.inner.copy(from, to).await
+    }
+
+    async fn copy_if_not_exists(&self, from: &Path, to: &Path) -> Result<()> {
+        self.inner.copy_if_not_exists(from, to).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use object_store::memory::InMemory;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_get_zstd() {
+        let inner = Arc::new(InMemory::new());
+        let content = b"a,b\n1,2\n3,4\n".to_vec();
+        let compressed = zstd::encode_all(content.as_slice(), 0).unwrap();
+        inner
+            .put(&Path::from("data.csv.zst"), compressed.into())
+            .await
+            .unwrap();
+        inner
+            .put(&Path::from("data.csv"), content.clone().into())
+            .await
+            .unwrap();
+
+        let store = DecompressObjectStore::new(inner);
+        for file in ["data.csv.zst", "data.csv"] {
+            let bytes = store
+                .get(&Path::from(file))
+                .await
+                .unwrap()
+                .bytes()
+                .await
+                .unwrap();
+            assert_eq!(bytes.to_vec(), content);
+        }
+
+        assert!(store
+            .get_range(&Path::from("data.csv.zst"), 0..1)
+            .await
+            .is_err());
+    }
+}
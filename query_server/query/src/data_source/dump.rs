@@ -0,0 +1,359 @@
+//! Line protocol dumps written by `EXPORT DATABASE` and read by `IMPORT DATABASE`.
+//!
+//! A dump is a directory holding one line protocol file per table and a
+//! `manifest.json` which lists the files and the filters used to export them.
+use std::io::Write;
+
+use datafusion::arrow::array::{
+    Array, ArrayRef, BooleanArray, Float64Array, Int64Array, StringArray, TimestampNanosecondArray,
+    UInt64Array,
+};
+use datafusion::arrow::record_batch::RecordBatch;
+use flate2::write::{GzDecoder, GzEncoder};
+use flate2::Compression;
+use line_protocol::{FieldValue, Line};
+use models::arrow_array::unpack_dictionaries;
+use models::schema::{ColumnType, TskvTableSchema};
+use models::ValueType;
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+use spi::query::logical_planner::{DumpCompression, DumpFilter};
+
+use super::{ArrowSnafu, DataSourceError, IoSnafu, Result};
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+pub const DUMP_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DumpManifest {
+    pub version: u32,
+    /// Database the dump is exported from
+    pub database: String,
+    /// `GZIP` or `NONE`
+    pub compression: String,
+    pub start_time: Option<i64>,
+    pub end_time: Option<i64>,
+    /// Timestamp (ns) when the dump is created
+    pub created_at: i64,
+    pub tables: Vec<DumpTable>,
+}
+
+impl DumpManifest {
+    pub fn compression(&self) -> Result<DumpCompression> {
+        self.compression
+            .parse()
+            .map_err(|reason| DataSourceError::InvalidDump { reason })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DumpTable {
+    pub name: String,
+    /// File name relative to the dump directory
+    pub file: String,
+    /// Number of lines in the file
+    pub points: u64,
+}
+
+/// Accumulates the lines of a table, the encoded bytes can be drained while writing
+pub enum DumpWriter {
+    Gzip(GzEncoder<Vec<u8>>),
+    Plain(Vec<u8>),
+}
+
+impl DumpWriter {
+    pub fn new(compression: DumpCompression) -> Self {
+        match compression {
+            DumpCompression::Gzip => Self::Gzip(GzEncoder::new(Vec::new(), Compression::default())),
+            DumpCompression::Uncompressed => Self::Plain(Vec::new()),
+        }
+    }
+
+    pub fn write_line(&mut self, line: &Line) -> Result<()> {
+        match self {
+            Self::Gzip(w) => writeln!(w, "{}", line),
+            Self::Plain(w) => writeln!(w, "{}", line),
+        }
+        .context(IoSnafu)
+    }
+
+    /// Takes the bytes encoded so far
+    pub fn take(&mut self) -> Vec<u8> {
+        match self {
+            Self::Gzip(w) => std::mem::take(w.get_mut()),
+            Self::Plain(w) => std::mem::take(w),
+        }
+    }
+
+    /// Returns the remaining bytes
+    pub fn finish(self) -> Result<Vec<u8>> {
+        match self {
+            Self::Gzip(w) => w.finish().context(IoSnafu),
+            Self::Plain(w) => Ok(w),
+        }
+    }
+}
+
+/// Decodes a dump file chunk by chunk, so that the file is never held in memory as a whole
+pub struct DumpReader {
+    decoder: DumpDecoder,
+    /// Bytes decoded after the last complete line
+    pending: Vec<u8>,
+}
+
+enum DumpDecoder {
+    Gzip(GzDecoder<Vec<u8>>),
+    Plain,
+}
+
+impl DumpReader {
+    pub fn new(compression: DumpCompression) -> Self {
+        let decoder = match compression {
+            DumpCompression::Gzip => DumpDecoder::Gzip(GzDecoder::new(Vec::new())),
+            DumpCompression::Uncompressed => DumpDecoder::Plain,
+        };
+        Self {
+            decoder,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Feeds the next chunk of the file, returns the complete lines decoded so far
+    pub fn push(&mut self, data: &[u8]) -> Result<String> {
+        match &mut self.decoder {
+            DumpDecoder::Gzip(decoder) => {
+                decoder.write_all(data).context(IoSnafu)?;
+                decoder.flush().context(IoSnafu)?;
+                self.pending.append(decoder.get_mut());
+            }
+            DumpDecoder::Plain => self.pending.extend_from_slice(data),
+        }
+
+        let end = match self.pending.iter().rposition(|b| *b == b'\n') {
+            Some(idx) => idx + 1,
+            None => return Ok(String::new()),
+        };
+        let rest = self.pending.split_off(end);
+        let lines = std::mem::replace(&mut self.pending, rest);
+        to_string(lines)
+    }
+
+    /// Returns the lines left after the end of the file
+    pub fn finish(mut self) -> Result<String> {
+        if let DumpDecoder::Gzip(decoder) = self.decoder {
+            self.pending.extend(decoder.finish().context(IoSnafu)?);
+        }
+        to_string(self.pending)
+    }
+}
+
+fn to_string(bytes: Vec<u8>) -> Result<String> {
+    String::from_utf8(bytes).map_err(|e| DataSourceError::InvalidDump {
+        reason: e.to_string(),
+    })
+}
+
+/// Splits the content into chunks of at most `max_lines` lines
+pub fn split_lines(content: &str, max_lines: usize) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut begin = 0;
+    let mut lines = 0;
+    for (idx, _) in content.match_indices('\n') {
+        lines += 1;
+        if lines == max_lines {
+            chunks.push(&content[begin..=idx]);
+            begin = idx + 1;
+            lines = 0;
+        }
+    }
+    if !content[begin..].trim().is_empty() {
+        chunks.push(&content[begin..]);
+    }
+    chunks
+}
+
+/// Writes the rows of the batch scanned from `schema` as lines, returns the number of lines.
+///
+/// Null fields are omitted, rows without any field are skipped.
+pub fn write_batch(
+    schema: &TskvTableSchema,
+    batch: &RecordBatch,
+    filter: &DumpFilter,
+    writer: &mut DumpWriter,
+) -> Result<u64> {
+    let batch = &unpack_dictionaries(batch).context(ArrowSnafu)?;
+    let mut time = None;
+    let mut tags = Vec::new();
+    let mut fields = Vec::new();
+    for (column, array) in schema.columns().iter().zip(batch.columns()) {
+        match column.column_type {
+            ColumnType::Time => time = Some(downcast::<TimestampNanosecondArray>(array)?),
+            ColumnType::Tag => tags.push((column.name.as_str(), downcast::<StringArray>(array)?)),
+            ColumnType::Field(value_type) => fields.push((column.name.as_str(), value_type, array)),
+        }
+    }
+    let time = time.ok_or_else(|| DataSourceError::InvalidDump {
+        reason: format!("table {} has no time column", schema.name),
+    })?;
+
+    let mut count = 0;
+    for row in 0..batch.num_rows() {
+        let timestamp = time.value(row);
+        if !filter.contains_time(timestamp) {
+            continue;
+        }
+
+        let mut line_fields = Vec::with_capacity(fields.len());
+        for (name, value_type, array) in fields.iter() {
+            if let Some(value) = field_value(array, *value_type, row)? {
+                line_fields.push((*name, value));
+            }
+        }
+        if line_fields.is_empty() {
+            continue;
+        }
+
+        let line = Line {
+            measurement: &schema.name,
+            tags: tags
+                .iter()
+                .filter(|(_, array)| array.is_valid(row))
+                .map(|(name, array)| (*name, array.value(row)))
+                .collect(),
+            fields: line_fields,
+            timestamp,
+        };
+        writer.write_line(&line)?;
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+fn field_value(array: &ArrayRef, value_type: ValueType, row: usize) -> Result<Option<FieldValue>> {
+    if array.is_null(row) {
+        return Ok(None);
+    }
+
+    let value = match value_type {
+        ValueType::Float => FieldValue::F64(downcast::<Float64Array>(array)?.value(row)),
+        ValueType::Integer => FieldValue::I64(downcast::<Int64Array>(array)?.value(row)),
+        ValueType::Unsigned => FieldValue::U64(downcast::<UInt64Array>(array)?.value(row)),
+        ValueType::Boolean => FieldValue::Bool(downcast::<BooleanArray>(array)?.value(row)),
+        ValueType::String => FieldValue::Str(
+            downcast::<StringArray>(array)?
+                .value(row)
+                .as_bytes()
+                .to_vec(),
+        ),
+        ValueType::Unknown => return Ok(None),
+    };
+
+    Ok(Some(value))
+}
+
+fn downcast<T: 'static>(array: &ArrayRef) -> Result<&T> {
+    array
+        .as_any()
+        .downcast_ref::<T>()
+        .ok_or_else(|| DataSourceError::InvalidDump {
+            reason: format!("unexpected column type {}", array.data_type()),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use line_protocol::line_protocol_to_lines;
+    use models::codec::Encoding;
+    use models::schema::TableColumn;
+
+    use super::*;
+
+    fn schema() -> TskvTableSchema {
+        TskvTableSchema::new(
+            "db".to_string(),
+            "cpu".to_string(),
+            vec![
+                TableColumn::new_time_column(0),
+                TableColumn::new_tag_column(1, "host".to_string()),
+                TableColumn::new(
+                    2,
+                    "usage".to_string(),
+                    ColumnType::Field(ValueType::Float),
+                    Encoding::Default,
+                ),
+                TableColumn::new(
+                    3,
+                    "status".to_string(),
+                    ColumnType::Field(ValueType::String),
+                    Encoding::Default,
+                ),
+            ],
+        )
+    }
+
+    fn batch(schema: &TskvTableSchema) -> RecordBatch {
+        RecordBatch::try_new(
+            schema.to_arrow_schema(),
+            vec![
+                Arc::new(TimestampNanosecondArray::from(vec![1, 2, 3])),
+                Arc::new(StringArray::from(vec![Some("a b"), None, Some("c")])),
+                Arc::new(Float64Array::from(vec![
+                    Some(0.5),
+                    Some(f64::INFINITY),
+                    None,
+                ])),
+                Arc::new(StringArray::from(vec![Some("ok"), None, None])),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_write_batch() {
+        let schema = schema();
+        let filter = DumpFilter {
+            start_time: Some(1),
+            end_time: Some(3),
+            tables: vec![],
+        };
+
+        for compression in [DumpCompression::Gzip, DumpCompression::Uncompressed] {
+            let mut writer = DumpWriter::new(compression);
+            let count = write_batch(&schema, &batch(&schema), &filter, &mut writer).unwrap();
+            assert_eq!(count, 2);
+
+            let mut data = writer.take();
+            data.extend(writer.finish().unwrap());
+            // Decoded in chunks which split the lines
+            let mut reader = DumpReader::new(compression);
+            let mut content = String::new();
+            for chunk in data.chunks(7) {
+                content.push_str(&reader.push(chunk).unwrap());
+            }
+            content.push_str(&reader.finish().unwrap());
+            assert_eq!(
+                content,
+                "cpu,host=a\\ b usage=0.5,status=\"ok\" 1\ncpu usage=+inf 2\n"
+            );
+
+            let lines = line_protocol_to_lines(&content, 0).unwrap();
+            assert_eq!(lines.len(), 2);
+            assert_eq!(lines[1].timestamp, 2);
+            assert_eq!(
+                lines[1].fields,
+                vec![("usage", FieldValue::F64(f64::INFINITY))]
+            );
+        }
+    }
+
+    #[test]
+    fn test_split_lines() {
+        assert_eq!(split_lines("a\nb\nc\n", 2), vec!["a\nb\n", "c\n"]);
+        assert_eq!(split_lines("a\nb\nc", 2), vec!["a\nb\n", "c"]);
+        assert_eq!(split_lines("a\nb\n", 2), vec!["a\nb\n"]);
+        assert!(split_lines("", 2).is_empty());
+    }
+}
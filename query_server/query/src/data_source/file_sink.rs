@@ -0,0 +1,344 @@
+use std::fmt::{self, Display};
+use std::io::Write;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use datafusion::arrow::csv;
+use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::arrow::json::LineDelimitedWriter;
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::parquet::arrow::ArrowWriter;
+use datafusion::physical_plan::metrics::{self, ExecutionPlanMetricsSet, MetricBuilder};
+use models::arrow_array::unpack_dictionaries;
+use object_store::path::Path;
+use object_store::ObjectStore;
+use parking_lot::Mutex;
+use snafu::ResultExt;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use trace::debug;
+
+use super::sink::{RecordBatchSink, RecordBatchSinkProvider};
+use super::Result;
+use super::{ArrowSnafu, IoSnafu, ObjectStoreSnafu, ParquetSnafu};
+
+/// File formats supported by `COPY ... TO`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileFormat {
+    Csv,
+    Parquet,
+    Json,
+}
+
+impl FileFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Parquet => "parquet",
+            Self::Json => "json",
+        }
+    }
+}
+
+impl FromStr for FileFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "CSV" => Ok(Self::Csv),
+            "PARQUET" => Ok(Self::Parquet),
+            "JSON" | "NDJSON" => Ok(Self::Json),
+            _ => Err(format!(
+                "file format {} is not supported, expected one of CSV, PARQUET, JSON",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for FileFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Csv => "CSV",
+            Self::Parquet => "PARQUET",
+            Self::Json => "JSON",
+        })
+    }
+}
+
+/// A `Write` implementation whose content can be drained while the writer is still alive
+#[derive(Clone, Default)]
+struct SharedBuffer {
+    buffer: Arc<Mutex<Vec<u8>>>,
+}
+
+impl SharedBuffer {
+    fn take(&self) -> Vec<u8> {
+        std::mem::take(&mut *self.buffer.lock())
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffer.lock().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+enum BatchWriter {
+    Csv(csv::Writer<SharedBuffer>),
+    Json(LineDelimitedWriter<SharedBuffer>),
+    Parquet(ArrowWriter<SharedBuffer>),
+}
+
+impl BatchWriter {
+    fn try_new(file_format: FileFormat, schema: SchemaRef, buffer: SharedBuffer) -> Result<Self> {
+        let writer = match file_format {
+            FileFormat::Csv => Self::Csv(csv::WriterBuilder::new().has_headers(true).build(buffer)),
+            FileFormat::Json => Self::Json(LineDelimitedWriter::new(buffer)),
+            FileFormat::Parquet => {
+                Self::Parquet(ArrowWriter::try_new(buffer, schema, None).context(ParquetSnafu)?)
+            }
+        };
+        Ok(writer)
+    }
+
+    fn write(&mut self, batch: &RecordBatch) -> Result<()> {
+        match self {
+            Self::Csv(w) => w
+                .write(&unpack_dictionaries(batch).context(ArrowSnafu)?)
+                .context(ArrowSnafu),
+            Self::Json(w) => w
+                .write(unpack_dictionaries(batch).context(ArrowSnafu)?)
+                .context(ArrowSnafu),
+            Self::Parquet(w) => w.write(batch).context(ParquetSnafu),
+        }
+    }
+
+    fn finish(self) -> Result<()> {
+        match self {
+            Self::Csv(_) => Ok(()),
+            Self::Json(mut w) => w.finish().context(ArrowSnafu),
+            Self::Parquet(w) => w.close().map(|_| ()).context(ParquetSnafu),
+        }
+    }
+}
+
+struct SinkState {
+    buffer: SharedBuffer,
+    writer: BatchWriter,
+    upload: Box<dyn AsyncWrite + Send + Unpin>,
+}
+
+/// Serializes record batches of one partition into a single file and uploads it to the object store
+pub struct FileRecordBatchSink {
+    store: Arc<dyn ObjectStore>,
+    path: Path,
+    file_format: FileFormat,
+    schema: SchemaRef,
+    state: tokio::sync::Mutex<Option<SinkState>>,
+
+    metrics: FileSinkMetrics,
+}
+
+impl FileRecordBatchSink {
+    async fn init_state(&self) -> Result<SinkState> {
+        let buffer = SharedBuffer::default();
+        let writer = BatchWriter::try_new(self.file_format, self.schema.clone(), buffer.clone())?;
+        let (_, upload) = self
+            .store
+            .put_multipart(&self.path)
+            .await
+            .context(ObjectStoreSnafu)?;
+
+        Ok(SinkState {
+            buffer,
+            writer,
+            upload,
+        })
+    }
+}
+
+#[async_trait]
+impl RecordBatchSink for FileRecordBatchSink {
+    async fn append(&self, record_batch: RecordBatch) -> Result<()> {
+        let mut state = self.state.lock().await;
+        if state.is_none() {
+            *state = Some(self.init_state().await?);
+        }
+        // Safety: initialized above
+        let state = state.as_mut().unwrap();
+
+        let timer = self.metrics.elapsed_serialize().timer();
+        state.writer.write(&record_batch)?;
+        let bytes = state.buffer.take();
+        timer.done();
+
+        let timer = self.metrics.elapsed_upload().timer();
+        state.upload.write_all(&bytes).await.context(IoSnafu)?;
+        timer.done();
+
+        Ok(())
+    }
+
+    async fn finish(&self) -> Result<()> {
+        let state = match self.state.lock().await.take() {
+            Some(state) => state,
+            None => self.init_state().await?,
+        };
+
+        let SinkState {
+            buffer,
+            writer,
+            mut upload,
+        } = state;
+
+        writer.finish()?;
+        upload.write_all(&buffer.take()).await.context(IoSnafu)?;
+        upload.shutdown().await.context(IoSnafu)?;
+
+        debug!("Finish writing file {}", self.path);
+
+        Ok(())
+    }
+}
+
+pub struct FileRecordBatchSinkProvider {
+    store: Arc<dyn ObjectStore>,
+    /// Directory which the files are written to
+    prefix: Path,
+    file_format: FileFormat,
+    schema: SchemaRef,
+}
+
+impl FileRecordBatchSinkProvider {
+    pub fn new(
+        store: Arc<dyn ObjectStore>,
+        prefix: Path,
+        file_format: FileFormat,
+        schema: SchemaRef,
+    ) -> Self {
+        Self {
+            store,
+            prefix,
+            file_format,
+            schema,
+        }
+    }
+}
+
+impl RecordBatchSinkProvider for FileRecordBatchSinkProvider {
+    fn create_batch_sink(
+        &self,
+        metrics: &ExecutionPlanMetricsSet,
+        partition: usize,
+    ) -> Box<dyn RecordBatchSink> {
+        let file_name = format!("part-{}.{}", partition, self.file_format.extension());
+
+        Box::new(FileRecordBatchSink {
+            store: self.store.clone(),
+            path: self.prefix.child(file_name),
+            file_format: self.file_format,
+            schema: self.schema.clone(),
+            state: tokio::sync::Mutex::new(None),
+            metrics: FileSinkMetrics::new(metrics, partition),
+        })
+    }
+}
+
+/// Stores metrics about the file sink execution.
+#[derive(Debug)]
+pub struct FileSinkMetrics {
+    elapsed_serialize: metrics::Time,
+    elapsed_upload: metrics::Time,
+}
+
+impl FileSinkMetrics {
+    /// Create new metrics
+    pub fn new(metrics: &ExecutionPlanMetricsSet, partition: usize) -> Self {
+        let elapsed_serialize =
+            MetricBuilder::new(metrics).subset_time("elapsed_serialize", partition);
+
+        let elapsed_upload = MetricBuilder::new(metrics).subset_time("elapsed_upload", partition);
+
+        Self {
+            elapsed_serialize,
+            elapsed_upload,
+        }
+    }
+
+    pub fn elapsed_serialize(&self) -> &metrics::Time {
+        &self.elapsed_serialize
+    }
+
+    pub fn elapsed_upload(&self) -> &metrics::Time {
+        &self.elapsed_upload
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::arrow::array::Int32Array;
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::from_slice::FromSlice;
+    use object_store::memory::InMemory;
+
+    use super::*;
+
+    fn test_batch() -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int32, false),
+            Field::new("b", DataType::Int32, false),
+        ]));
+
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int32Array::from_slice([1, 2])),
+                Arc::new(Int32Array::from_slice([3, 4])),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse_file_format() {
+        assert_eq!(FileFormat::from_str("csv").unwrap(), FileFormat::Csv);
+        assert_eq!(
+            FileFormat::from_str("PARQUET").unwrap(),
+            FileFormat::Parquet
+        );
+        assert_eq!(FileFormat::from_str("ndjson").unwrap(), FileFormat::Json);
+        assert!(FileFormat::from_str("avro").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_file_sink_csv() {
+        let store = Arc::new(InMemory::new());
+        let batch = test_batch();
+        let provider = FileRecordBatchSinkProvider::new(
+            store.clone(),
+            Path::from("export"),
+            FileFormat::Csv,
+            batch.schema(),
+        );
+
+        let sink = provider.create_batch_sink(&ExecutionPlanMetricsSet::new(), 0);
+        sink.append(batch.clone()).await.unwrap();
+        sink.append(batch).await.unwrap();
+        sink.finish().await.unwrap();
+
+        let bytes = store
+            .get(&Path::from("export/part-0.csv"))
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap();
+        assert_eq!("a,b\n1,3\n2,4\n1,3\n2,4\n".as_bytes(), bytes.as_ref());
+    }
+}
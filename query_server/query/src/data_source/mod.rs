@@ -1,8 +1,16 @@
+use coordinator::errors::CoordinatorError;
+use datafusion::arrow::error::ArrowError;
+use datafusion::parquet::errors::ParquetError;
 use models::define_result;
 use snafu::Snafu;
 
 use crate::utils::point_util::PointUtilError;
 
+pub mod cloud_store;
+pub mod decompress_store;
+pub mod dump;
+pub mod file_sink;
+pub mod shard_scan;
 pub mod sink;
 pub mod tskv_sink;
 
@@ -15,4 +23,28 @@ pub enum DataSourceError {
 
     #[snafu(display("Tskv operator, err: {}", source))]
     Tskv { source: tskv::Error },
+
+    #[snafu(display("Coordinator error, err: {}", source))]
+    Coordinator { source: CoordinatorError },
+
+    #[snafu(display("Arrow error, err: {}", source))]
+    Arrow { source: ArrowError },
+
+    #[snafu(display("Parquet error, err: {}", source))]
+    Parquet { source: ParquetError },
+
+    #[snafu(display("Object store error, err: {}", source))]
+    ObjectStore { source: object_store::Error },
+
+    #[snafu(display("IO error, err: {}", source))]
+    Io { source: std::io::Error },
+
+    #[snafu(display("Line protocol error, err: {}", source))]
+    LineProtocol { source: line_protocol::Error },
+
+    #[snafu(display("Invalid dump: {}", reason))]
+    InvalidDump { reason: String },
+
+    #[snafu(display("Write rejected: {}", reason))]
+    WriteRejected { reason: String },
 }
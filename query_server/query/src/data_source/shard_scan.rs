@@ -0,0 +1,940 @@
+//! Scans of the tables whose shards are located on the data nodes of a cluster.
+//!
+//! The coordinator plans a partition of [`ShardScanExec`] per group of the shards
+//! read from the same replicas, each data node scans its local engine by
+//! [`scan_shards`] and streams the record batches back, the filters and aggregations
+//! are applied on the coordinator. The schema of a table on a data node may lag
+//! behind that of the meta service, the columns it has not are read as nulls.
+use std::any::Any;
+use std::collections::{BTreeMap, HashSet};
+use std::io::Cursor;
+use std::sync::Arc;
+
+use coordinator::connection::NodeConnections;
+use datafusion::arrow::array::{
+    new_null_array, Array, ArrayRef, BooleanArray, TimestampNanosecondArray,
+};
+use datafusion::arrow::compute::{cast, filter_record_batch};
+use datafusion::arrow::datatypes::{DataType, Schema, SchemaRef};
+use datafusion::arrow::error::{ArrowError, Result as ArrowResult};
+use datafusion::arrow::ipc::reader::StreamReader;
+use datafusion::arrow::ipc::writer::StreamWriter;
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::error::{DataFusionError, Result};
+use datafusion::execution::context::TaskContext;
+use datafusion::logical_expr::{col, lit, Expr};
+use datafusion::physical_expr::PhysicalSortExpr;
+use datafusion::physical_plan::metrics::{ExecutionPlanMetricsSet, MetricsSet};
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::{
+    DisplayFormatType, ExecutionPlan, Partitioning, SendableRecordBatchStream, Statistics,
+};
+use datafusion::scalar::ScalarValue;
+use futures::{SinkExt, Stream, StreamExt, TryStreamExt};
+use models::arrow_array::string_value;
+use models::consistency_level::ConsistencyLevel;
+use models::meta_data::NodeId;
+use models::predicate::domain::Predicate;
+use models::schema::{TableSchema, TskvTableSchema, TIME_FIELD};
+use models::{SeriesKey, Tag};
+use protos::kv_service::{ScanTableRequest, ShardFilter, TimeRange};
+use trace::warn;
+use tskv::engine::EngineRef;
+
+use crate::stream::{TableScanMetrics, TableScanStream};
+use crate::utils::point_util::record_batch_to_points_flat_buffer;
+
+/// Locates the shards of the tables in a cluster
+#[derive(Debug, Clone)]
+pub struct ShardScanContext {
+    pub node_id: NodeId,
+    pub tenant: String,
+    pub connections: Arc<NodeConnections>,
+}
+
+/// Shards scanned on the same replicas
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanTarget {
+    pub shards: Vec<ShardFilter>,
+    /// Data nodes to read the shards from in order, the replica on this node first
+    pub replicas: Vec<NodeId>,
+    /// Replicas required to be read
+    pub required: usize,
+}
+
+impl ShardScanContext {
+    /// The shards of the table overlapping the time ranges grouped by the replicas to
+    /// read them from. A shard is read from one replica, the one on this node is
+    /// preferred, unless the consistency level requires more.
+    pub async fn plan(
+        &self,
+        schema: &TskvTableSchema,
+        time_ranges: &[TimeRange],
+        consistency: ConsistencyLevel,
+    ) -> Result<Vec<ScanTarget>> {
+        let meta = self.connections.meta();
+        let tenant = match meta.tenant_meta(&self.tenant) {
+            Some(tenant) => tenant,
+            None => meta.refresh(&self.tenant).await.map_err(external)?,
+        };
+        let db = match tenant.database(&schema.db) {
+            Some(db) => db,
+            None => return Ok(vec![]),
+        };
+
+        let mut targets: BTreeMap<(Vec<NodeId>, usize), Vec<ShardFilter>> = BTreeMap::new();
+        for bucket in db.buckets.iter().filter(|e| {
+            time_ranges
+                .iter()
+                .any(|r| e.start_time <= r.max_ts && r.min_ts < e.end_time)
+        }) {
+            for (index, set) in bucket.shard_group.iter().enumerate() {
+                let mut replicas: Vec<NodeId> = set.vnodes.iter().map(|e| e.node_id).collect();
+                if replicas.is_empty() {
+                    continue;
+                }
+                if let Some(i) = replicas.iter().position(|e| *e == self.node_id) {
+                    replicas[..=i].rotate_right(1);
+                }
+                let required = consistency.required(replicas.len()).max(1);
+                if required == 1 {
+                    replicas.truncate(1);
+                }
+
+                targets
+                    .entry((replicas, required))
+                    .or_default()
+                    .push(ShardFilter {
+                        start_time: bucket.start_time,
+                        end_time: bucket.end_time,
+                        shard_num: bucket.shard_group.len() as u32,
+                        index: index as u32,
+                    });
+            }
+        }
+
+        Ok(targets
+            .into_iter()
+            .map(|((replicas, required), shards)| ScanTarget {
+                shards,
+                replicas,
+                required,
+            })
+            .collect())
+    }
+}
+
+/// Scans the shards located on the data nodes, a partition per [`ScanTarget`].
+///
+/// The shards read from more than one replica are reconciled: the rows of the
+/// replicas are merged, a row of the same series and time is returned once. If a
+/// replica fails, the next one is read instead.
+#[derive(Debug)]
+pub struct ShardScanExec {
+    context: ShardScanContext,
+    engine: EngineRef,
+    /// Scan of the table without the shards
+    request: ScanTableRequest,
+    targets: Vec<ScanTarget>,
+    schema: SchemaRef,
+    /// Schema of the whole table, the batches of the replicas are aligned to it
+    table_schema: SchemaRef,
+    /// Columns identifying a row, the tags and the time
+    key_columns: Vec<String>,
+    statistics: Statistics,
+
+    metrics: ExecutionPlanMetricsSet,
+}
+
+impl ShardScanExec {
+    pub fn new(
+        context: ShardScanContext,
+        engine: EngineRef,
+        request: ScanTableRequest,
+        targets: Vec<ScanTarget>,
+        schema: SchemaRef,
+        table_schema: SchemaRef,
+        key_columns: Vec<String>,
+    ) -> Self {
+        Self {
+            context,
+            engine,
+            request,
+            targets,
+            schema,
+            table_schema,
+            key_columns,
+            statistics: Statistics::default(),
+            metrics: ExecutionPlanMetricsSet::new(),
+        }
+    }
+
+    pub fn with_statistics(mut self, statistics: Statistics) -> Self {
+        self.statistics = statistics;
+        self
+    }
+}
+
+impl ExecutionPlan for ShardScanExec {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn output_partitioning(&self) -> Partitioning {
+        Partitioning::UnknownPartitioning(self.targets.len())
+    }
+
+    fn output_ordering(&self) -> Option<&[PhysicalSortExpr]> {
+        None
+    }
+
+    fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+        vec![]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        _: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(self)
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let target = self.targets[partition].clone();
+        let mut request = self.request.clone();
+        request.shards = target.shards.clone();
+        let mut scan = ReplicaScan {
+            context: self.context.clone(),
+            engine: self.engine.clone(),
+            schema: self.schema.clone(),
+            batch_size: context.session_config().batch_size(),
+        };
+
+        if target.required <= 1 {
+            return scan.scan(target.replicas[0], request);
+        }
+
+        // The keys are scanned to reconcile the rows of the replicas
+        let output = request.columns.clone();
+        for column in self.key_columns.iter() {
+            if !request.columns.contains(column) {
+                request.columns.push(column.clone());
+            }
+        }
+        let fields = request
+            .columns
+            .iter()
+            .map(|e| self.table_schema.field_with_name(e).cloned())
+            .collect::<ArrowResult<Vec<_>>>()?;
+        scan.schema = Arc::new(Schema::new(fields));
+        let stream = reconcile_replicas(scan, target, request, self.key_columns.clone(), output);
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            self.schema.clone(),
+            stream,
+        )))
+    }
+
+    fn fmt_as(&self, t: DisplayFormatType, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match t {
+            DisplayFormatType::Default => {
+                let targets: Vec<String> = self
+                    .targets
+                    .iter()
+                    .map(|e| {
+                        let replicas: Vec<String> =
+                            e.replicas.iter().map(|id| id.to_string()).collect();
+                        format!("{}/{}:{}", replicas.join("|"), e.required, e.shards.len())
+                    })
+                    .collect();
+                write!(
+                    f,
+                    "ShardScanExec: table={}.{}, projection=[{}], replicas/required:shards=[{}]",
+                    self.request.database,
+                    self.request.table,
+                    self.request.columns.join(","),
+                    targets.join(","),
+                )
+            }
+        }
+    }
+
+    fn statistics(&self) -> Statistics {
+        self.statistics.clone()
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+}
+
+/// Scans the shards on a replica
+#[derive(Clone)]
+struct ReplicaScan {
+    context: ShardScanContext,
+    engine: EngineRef,
+    /// Schema of the columns of the request, the batches of the replica are aligned to
+    schema: SchemaRef,
+    batch_size: usize,
+}
+
+impl ReplicaScan {
+    fn scan(
+        &self,
+        node_id: NodeId,
+        request: ScanTableRequest,
+    ) -> Result<SendableRecordBatchStream> {
+        let schema = self.schema.clone();
+        if node_id == self.context.node_id {
+            let stream = match scan_shards(self.engine.clone(), &request, self.batch_size)? {
+                Some(stream) => stream,
+                None => Box::pin(RecordBatchStreamAdapter::new(
+                    schema.clone(),
+                    futures::stream::empty(),
+                )),
+            };
+            let aligned = stream.map(move |batch| align_batch(batch?, &schema));
+            return Ok(Box::pin(RecordBatchStreamAdapter::new(
+                self.schema.clone(),
+                aligned,
+            )));
+        }
+
+        let connections = self.context.connections.clone();
+        let stream = futures::stream::once(scan_remote_shards(connections, node_id, request))
+            .try_flatten()
+            .map(move |batch| align_batch(batch?, &schema));
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            self.schema.clone(),
+            stream,
+        )))
+    }
+}
+
+/// Aligns the batch of a replica to the schema by the names of the columns, the
+/// columns the replica has not are null, and those of other types are cast
+fn align_batch(batch: RecordBatch, schema: &SchemaRef) -> ArrowResult<RecordBatch> {
+    if batch.schema() == *schema {
+        return Ok(batch);
+    }
+
+    let batch_schema = batch.schema();
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|field| match batch_schema.index_of(field.name()) {
+            Ok(i) if batch.column(i).data_type() == field.data_type() => {
+                Ok(batch.column(i).clone())
+            }
+            Ok(i) => cast(batch.column(i), field.data_type()),
+            Err(_) => Ok(new_null_array(field.data_type(), batch.num_rows())),
+        })
+        .collect::<ArrowResult<Vec<_>>>()?;
+    RecordBatch::try_new(schema.clone(), columns)
+}
+
+/// Most keys of the rows kept to reconcile the replicas of a shard, the scan fails
+/// beyond it rather than exhausting the memory
+const MAX_RECONCILED_KEYS: usize = 1 << 22;
+
+/// Reads the replicas in order until `required` ones are read, the rows already
+/// returned by a replica are skipped.
+fn reconcile_replicas(
+    scan: ReplicaScan,
+    target: ScanTarget,
+    request: ScanTableRequest,
+    key_columns: Vec<String>,
+    output: Vec<String>,
+) -> impl Stream<Item = ArrowResult<RecordBatch>> {
+    let (mut sender, receiver) = futures::channel::mpsc::channel(2);
+    tokio::spawn(async move {
+        let mut seen = HashSet::new();
+        let mut success = 0;
+        let mut errors = vec![];
+        for node_id in target.replicas.iter() {
+            if success >= target.required {
+                break;
+            }
+            let res = async {
+                let mut stream = scan
+                    .scan(*node_id, request.clone())
+                    .map_err(external_arrow)?;
+                while let Some(batch) = stream.next().await {
+                    let batch = dedup_rows(
+                        &batch?,
+                        &key_columns,
+                        &output,
+                        &mut seen,
+                        MAX_RECONCILED_KEYS,
+                    )?;
+                    if batch.num_rows() > 0 && sender.send(Ok(batch)).await.is_err() {
+                        // The scan is cancelled
+                        return Ok(false);
+                    }
+                }
+                Ok::<_, ArrowError>(true)
+            }
+            .await;
+
+            match res {
+                Ok(true) => success += 1,
+                Ok(false) => return,
+                Err(e) => {
+                    warn!("Failed to read replica on node {}: {}", node_id, e);
+                    errors.push(format!("node {}: {}", node_id, e));
+                }
+            }
+        }
+
+        if success < target.required {
+            let msg = format!(
+                "only {} of {} replicas required are read: {}",
+                success,
+                target.required,
+                errors.join(", ")
+            );
+            let _ = sender
+                .send(Err(ArrowError::ExternalError(msg.into())))
+                .await;
+        }
+    });
+    receiver
+}
+
+/// Removes the rows of the keys already seen and projects to the output columns,
+/// fails once more than `max_keys` keys are seen
+fn dedup_rows(
+    batch: &RecordBatch,
+    key_columns: &[String],
+    output: &[String],
+    seen: &mut HashSet<Vec<u8>>,
+    max_keys: usize,
+) -> ArrowResult<RecordBatch> {
+    let schema = batch.schema();
+    let keys = key_columns
+        .iter()
+        .map(|e| Ok(batch.column(schema.index_of(e)?)))
+        .collect::<ArrowResult<Vec<_>>>()?;
+    let mask = (0..batch.num_rows())
+        .map(|row| row_key(&keys, row).map(|key| Some(seen.insert(key))))
+        .collect::<ArrowResult<BooleanArray>>()?;
+    if seen.len() > max_keys {
+        return Err(ArrowError::ExternalError(
+            format!(
+                "more than {} rows to reconcile the replicas, narrow the time range \
+                or read with the consistency level ONE",
+                max_keys
+            )
+            .into(),
+        ));
+    }
+    let indices = output
+        .iter()
+        .map(|e| schema.index_of(e))
+        .collect::<ArrowResult<Vec<_>>>()?;
+
+    filter_record_batch(batch, &mask)?.project(&indices)
+}
+
+fn row_key(columns: &[&ArrayRef], row: usize) -> ArrowResult<Vec<u8>> {
+    let mut key = vec![];
+    for column in columns {
+        if column.is_null(row) {
+            key.push(0);
+            continue;
+        }
+        key.push(1);
+        if let Some(value) = string_value(column, row) {
+            let value = value.as_bytes();
+            key.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            key.extend_from_slice(value);
+        } else if let Some(array) = column.as_any().downcast_ref::<TimestampNanosecondArray>() {
+            key.extend_from_slice(&array.value(row).to_le_bytes());
+        } else {
+            return Err(ArrowError::CastError(format!(
+                "invalid key column of type {}",
+                column.data_type()
+            )));
+        }
+    }
+    Ok(key)
+}
+
+async fn scan_remote_shards(
+    connections: Arc<NodeConnections>,
+    node_id: NodeId,
+    request: ScanTableRequest,
+) -> ArrowResult<impl Stream<Item = ArrowResult<RecordBatch>>> {
+    let mut client = connections.client(node_id).await.map_err(external_arrow)?;
+    let stream = client
+        .scan_table(connections.request(request))
+        .await
+        .map_err(external_arrow)?
+        .into_inner();
+
+    Ok(stream
+        .map_err(external_arrow)
+        .map(|resp| resp.and_then(|e| ipc_to_batches(&e.ipc)))
+        .map_ok(|batches| futures::stream::iter(batches.into_iter().map(Ok)))
+        .try_flatten())
+}
+
+/// Scans the series of the shards in the local engine, `None` if the table is not
+/// written on this node.
+pub fn scan_shards(
+    engine: EngineRef,
+    request: &ScanTableRequest,
+    batch_size: usize,
+) -> Result<Option<SendableRecordBatchStream>> {
+    let table_schema = match engine
+        .get_table_schema(&request.database, &request.table)
+        .map_err(external)?
+    {
+        Some(TableSchema::TsKvTableSchema(schema)) => schema,
+        _ => return Ok(None),
+    };
+
+    // The columns not written on this node yet are left to the coordinator
+    let columns: Vec<String> = request
+        .columns
+        .iter()
+        .filter(|e| table_schema.contains_column(e))
+        .cloned()
+        .collect();
+
+    // The tags and the time are scanned to locate the shard of each row
+    let mut scan_columns = columns.clone();
+    for column in table_schema.columns() {
+        if (column.column_type.is_tag() || column.column_type.is_time())
+            && !scan_columns.contains(&column.name)
+        {
+            scan_columns.push(column.name.clone());
+        }
+    }
+    // A field is required to scan the rows
+    if !table_schema
+        .columns()
+        .iter()
+        .any(|e| e.column_type.is_field() && scan_columns.contains(&e.name))
+    {
+        if let Some(field) = table_schema.fields().first() {
+            scan_columns.push(field.name.clone());
+        }
+    }
+
+    let arrow_schema = table_schema.to_arrow_schema();
+    let index_of = |columns: &[String]| -> Result<Vec<usize>> {
+        columns
+            .iter()
+            .map(|e| {
+                arrow_schema
+                    .index_of(e)
+                    .map_err(DataFusionError::ArrowError)
+            })
+            .collect()
+    };
+    let scan_schema = Arc::new(arrow_schema.project(&index_of(&scan_columns)?)?);
+    let output_schema = Arc::new(arrow_schema.project(&index_of(&columns)?)?);
+
+    let mut tags: Vec<(String, usize)> = table_schema
+        .columns()
+        .iter()
+        .filter(|e| e.column_type.is_tag())
+        .map(|e| Ok((e.name.clone(), scan_schema.index_of(&e.name)?)))
+        .collect::<ArrowResult<_>>()?;
+    tags.sort_by(|a, b| a.0.cmp(&b.0));
+    let time_index = scan_schema.index_of(TIME_FIELD)?;
+    let output: Vec<usize> = columns
+        .iter()
+        .map(|e| scan_schema.index_of(e))
+        .collect::<ArrowResult<_>>()?;
+
+    let predicate = Arc::new(time_predicate(&request.time_ranges, &table_schema));
+    let metrics = TableScanMetrics::new(&ExecutionPlanMetricsSet::new(), 0);
+    let table = table_schema.name.clone();
+    let stream = TableScanStream::new(
+        table_schema,
+        scan_schema,
+        predicate,
+        batch_size,
+        engine,
+        metrics,
+        None,
+    )
+    .map_err(external)?;
+
+    let shards = request.shards.clone();
+    let stream = stream.map(move |batch| {
+        let batch = batch?;
+        let mask = shard_mask(&batch, &table, &tags, time_index, &shards)?;
+        filter_record_batch(&batch, &mask)?.project(&output)
+    });
+
+    Ok(Some(Box::pin(RecordBatchStreamAdapter::new(
+        output_schema,
+        stream,
+    ))))
+}
+
+/// Scans the points of a shard of all the tables in the local engine, each item is
+/// the flatbuffers [`protos::models::Points`] of a record batch.
+pub fn scan_shard_points(
+    engine: EngineRef,
+    database: &str,
+    shard: &ShardFilter,
+    batch_size: usize,
+) -> Result<impl Stream<Item = Result<Vec<u8>>>> {
+    let mut streams = vec![];
+    for table in engine.list_tables(database).map_err(external)? {
+        let schema = match engine
+            .get_table_schema(database, &table)
+            .map_err(external)?
+        {
+            Some(TableSchema::TsKvTableSchema(schema)) => schema,
+            _ => continue,
+        };
+        let request = ScanTableRequest {
+            database: database.to_string(),
+            table,
+            columns: schema.columns().iter().map(|e| e.name.clone()).collect(),
+            time_ranges: vec![TimeRange {
+                min_ts: shard.start_time,
+                max_ts: shard.end_time.saturating_sub(1),
+            }],
+            shards: vec![shard.clone()],
+        };
+        if let Some(stream) = scan_shards(engine.clone(), &request, batch_size)? {
+            let stream = stream
+                .try_filter(|batch| futures::future::ready(batch.num_rows() > 0))
+                .map(move |batch| {
+                    record_batch_to_points_flat_buffer(&batch?, schema.clone()).map_err(external)
+                });
+            streams.push(stream);
+        }
+    }
+
+    Ok(futures::stream::iter(streams).flatten())
+}
+
+/// Whether the rows belong to the shards
+fn shard_mask(
+    batch: &RecordBatch,
+    table: &str,
+    tags: &[(String, usize)],
+    time_index: usize,
+    shards: &[ShardFilter],
+) -> ArrowResult<BooleanArray> {
+    let downcast_err = |name: &str| ArrowError::CastError(format!("invalid column {}", name));
+    let times = batch
+        .column(time_index)
+        .as_any()
+        .downcast_ref::<TimestampNanosecondArray>()
+        .ok_or_else(|| downcast_err(TIME_FIELD))?;
+    let tag_arrays = tags
+        .iter()
+        .map(|(name, index)| {
+            let array = batch.column(*index);
+            match array.data_type() {
+                DataType::Utf8 | DataType::Dictionary(..) => Ok((name.as_bytes(), array)),
+                _ => Err(downcast_err(name)),
+            }
+        })
+        .collect::<ArrowResult<Vec<_>>>()?;
+
+    Ok((0..batch.num_rows())
+        .map(|row| {
+            let tags: Vec<Tag> = tag_arrays
+                .iter()
+                .filter_map(|(key, array)| {
+                    string_value(array, row)
+                        .map(|value| Tag::new(key.to_vec(), value.as_bytes().to_vec()))
+                })
+                .collect();
+            let hash = SeriesKey::hash_of(table, &tags);
+            let ts = times.value(row);
+            Some(shards.iter().any(|e| shard_contains(e, ts, hash)))
+        })
+        .collect())
+}
+
+pub fn shard_contains(shard: &ShardFilter, ts: i64, hash: u64) -> bool {
+    shard.start_time <= ts
+        && ts < shard.end_time
+        && shard.shard_num > 0
+        && hash % shard.shard_num as u64 == shard.index as u64
+}
+
+fn time_predicate(time_ranges: &[TimeRange], schema: &TskvTableSchema) -> Predicate {
+    let ts = |v: i64| lit(ScalarValue::TimestampNanosecond(Some(v), None));
+    let expr = time_ranges
+        .iter()
+        .map(|e| {
+            col(TIME_FIELD)
+                .gt_eq(ts(e.min_ts))
+                .and(col(TIME_FIELD).lt_eq(ts(e.max_ts)))
+        })
+        .reduce(Expr::or);
+
+    match expr {
+        Some(expr) => Predicate::default().push_down_filter(&[expr], schema),
+        None => Predicate::default(),
+    }
+}
+
+/// Encodes the record batch as an arrow ipc stream
+pub fn batch_to_ipc(batch: &RecordBatch) -> ArrowResult<Vec<u8>> {
+    let mut bytes = vec![];
+    {
+        let mut writer = StreamWriter::try_new(&mut bytes, &batch.schema())?;
+        writer.write(batch)?;
+        writer.finish()?;
+    }
+    Ok(bytes)
+}
+
+pub fn ipc_to_batches(bytes: &[u8]) -> ArrowResult<Vec<RecordBatch>> {
+    StreamReader::try_new(Cursor::new(bytes), None)?.collect()
+}
+
+fn external(e: impl std::error::Error + Send + Sync + 'static) -> DataFusionError {
+    DataFusionError::External(Box::new(e))
+}
+
+fn external_arrow(e: impl std::error::Error + Send + Sync + 'static) -> ArrowError {
+    ArrowError::ExternalError(Box::new(e))
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::arrow::array::{Int64Array, StringArray};
+    use datafusion::arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+    use meta::meta_client::MockMetaClient;
+    use models::meta_data::{BucketInfo, DatabaseInfo, ReplicationSet, TenantMetaData, VnodeInfo};
+    use models::schema::DatabaseSchema;
+
+    use super::*;
+
+    #[test]
+    fn test_shard_mask() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(
+                TIME_FIELD,
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+            Field::new("host", DataType::Utf8, true),
+            Field::new("usage", DataType::Int64, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(TimestampNanosecondArray::from(vec![10, 20, 150])),
+                Arc::new(StringArray::from(vec![Some("a"), None, Some("a")])),
+                Arc::new(Int64Array::from(vec![1, 2, 3])),
+            ],
+        )
+        .unwrap();
+
+        let hash = SeriesKey::hash_of("cpu", &[Tag::new(b"host".to_vec(), b"a".to_vec())]);
+        let shard = ShardFilter {
+            start_time: 0,
+            end_time: 100,
+            shard_num: 2,
+            index: (hash % 2) as u32,
+        };
+        let tags = vec![("host".to_string(), 1)];
+        let mask = shard_mask(&batch, "cpu", &tags, 0, &[shard]).unwrap();
+        assert!(mask.value(0));
+        // Out of the time range of the bucket
+        assert!(!mask.value(2));
+        let other = SeriesKey::hash_of("cpu", &[]);
+        assert_eq!(mask.value(1), other % 2 == hash % 2);
+    }
+
+    #[test]
+    fn test_ipc_round_trip() {
+        let schema = Arc::new(Schema::new(vec![Field::new("v", DataType::Int64, true)]));
+        let batch =
+            RecordBatch::try_new(schema, vec![Arc::new(Int64Array::from(vec![1, 2, 3]))]).unwrap();
+        let batches = ipc_to_batches(&batch_to_ipc(&batch).unwrap()).unwrap();
+        assert_eq!(batches, vec![batch]);
+    }
+
+    #[test]
+    fn test_align_batch() {
+        let batch = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new("usage", DataType::Int64, true),
+                Field::new("host", DataType::Utf8, true),
+            ])),
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2])),
+                Arc::new(StringArray::from(vec!["a", "b"])),
+            ],
+        )
+        .unwrap();
+        // The column of the other type and the one not written on the replica
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("host", DataType::Utf8, true),
+            Field::new("usage", DataType::Float64, true),
+            Field::new("region", DataType::Utf8, true),
+        ]));
+
+        let aligned = align_batch(batch, &schema).unwrap();
+        assert_eq!(aligned.schema(), schema);
+        assert_eq!(aligned.num_rows(), 2);
+        assert_eq!(aligned.column(1).data_type(), &DataType::Float64);
+        assert_eq!(aligned.column(2).null_count(), 2);
+    }
+
+    fn context(shards: &[&[NodeId]]) -> ShardScanContext {
+        let mut db = DatabaseInfo::new(DatabaseSchema::new("db"));
+        db.buckets.push(BucketInfo {
+            id: 1,
+            start_time: 0,
+            end_time: 100,
+            shard_group: shards
+                .iter()
+                .enumerate()
+                .map(|(i, nodes)| ReplicationSet {
+                    id: i as u32 + 2,
+                    vnodes: nodes
+                        .iter()
+                        .map(|node_id| VnodeInfo {
+                            id: 0,
+                            node_id: *node_id,
+                        })
+                        .collect(),
+                })
+                .collect(),
+        });
+        let mut tenant = TenantMetaData::default();
+        tenant.dbs.insert("db".to_string(), db);
+        let meta = MockMetaClient::default();
+        meta.set_tenant_meta("cnosdb", tenant);
+
+        ShardScanContext {
+            node_id: 2,
+            tenant: "cnosdb".to_string(),
+            connections: Arc::new(NodeConnections::new(Arc::new(meta))),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_plan_consistency() {
+        let context = context(&[&[1, 2, 3], &[3, 1, 2], &[3, 1, 4]]);
+        let schema = TskvTableSchema::new("db".to_string(), "cpu".to_string(), vec![]);
+        let ranges = [TimeRange {
+            min_ts: 0,
+            max_ts: 10,
+        }];
+
+        // The replica on this node is preferred
+        let targets = context
+            .plan(&schema, &ranges, ConsistencyLevel::One)
+            .await
+            .unwrap();
+        assert_eq!(targets.len(), 2);
+        assert_eq!(targets[0].replicas, vec![2]);
+        assert_eq!(targets[0].shards.len(), 2);
+        assert_eq!(targets[1].replicas, vec![3]);
+
+        let targets = context
+            .plan(&schema, &ranges, ConsistencyLevel::Quorum)
+            .await
+            .unwrap();
+        assert_eq!(targets.len(), 3);
+        assert!(targets.iter().all(|e| e.required == 2));
+        assert!(targets.iter().any(|e| e.replicas == vec![2, 1, 3]));
+        assert!(targets.iter().any(|e| e.replicas == vec![2, 3, 1]));
+
+        // Out of the buckets
+        let ranges = [TimeRange {
+            min_ts: 100,
+            max_ts: 200,
+        }];
+        let targets = context
+            .plan(&schema, &ranges, ConsistencyLevel::All)
+            .await
+            .unwrap();
+        assert!(targets.is_empty());
+    }
+
+    #[test]
+    fn test_dedup_rows() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(
+                TIME_FIELD,
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+            Field::new("host", DataType::Utf8, true),
+            Field::new("usage", DataType::Int64, true),
+        ]));
+        let batch = |times: Vec<i64>, hosts: Vec<Option<&str>>, usages: Vec<i64>| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(TimestampNanosecondArray::from(times)),
+                    Arc::new(StringArray::from(hosts)),
+                    Arc::new(Int64Array::from(usages)),
+                ],
+            )
+            .unwrap()
+        };
+        let keys = vec!["host".to_string(), TIME_FIELD.to_string()];
+        let output = vec!["usage".to_string()];
+        let mut seen = HashSet::new();
+
+        let first = batch(vec![1, 2], vec![Some("a"), None], vec![1, 2]);
+        let rows = dedup_rows(&first, &keys, &output, &mut seen, 4).unwrap();
+        assert_eq!(rows.num_rows(), 2);
+        assert_eq!(rows.num_columns(), 1);
+
+        // The rows of the same series and time read from another replica are skipped
+        let second = batch(
+            vec![1, 2, 2],
+            vec![Some("a"), None, Some("b")],
+            vec![1, 2, 3],
+        );
+        let rows = dedup_rows(&second, &keys, &output, &mut seen, 4).unwrap();
+        assert_eq!(rows.num_rows(), 1);
+        let usages = rows
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(usages.value(0), 3);
+
+        // The tag a replica has not written is null, as the series without it
+        let replica = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new(
+                    TIME_FIELD,
+                    DataType::Timestamp(TimeUnit::Nanosecond, None),
+                    false,
+                ),
+                Field::new("usage", DataType::Int64, true),
+            ])),
+            vec![
+                Arc::new(TimestampNanosecondArray::from(vec![2])),
+                Arc::new(Int64Array::from(vec![2])),
+            ],
+        )
+        .unwrap();
+        let aligned = align_batch(replica, &schema).unwrap();
+        let rows = dedup_rows(&aligned, &keys, &output, &mut seen, 4).unwrap();
+        assert_eq!(rows.num_rows(), 0);
+
+        // Beyond the keys kept
+        let third = batch(vec![3, 4], vec![Some("a"), Some("a")], vec![4, 5]);
+        assert!(dedup_rows(&third, &keys, &output, &mut seen, 4).is_err());
+    }
+}
@@ -8,6 +8,11 @@ use super::Result;
 #[async_trait]
 pub trait RecordBatchSink: Send + Sync {
     async fn append(&self, record_batch: RecordBatch) -> Result<()>;
+
+    /// Called once after the last record batch has been appended
+    async fn finish(&self) -> Result<()> {
+        Ok(())
+    }
 }
 
 pub trait RecordBatchSinkProvider: Send + Sync {
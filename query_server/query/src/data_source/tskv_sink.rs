@@ -1,25 +1,31 @@
 use async_trait::async_trait;
+use chrono::Local;
+use coordinator::service::CoordinatorRef;
+use coordinator::writer::DEFAULT_WRITE_CONSISTENCY;
 use datafusion::arrow::record_batch::RecordBatch;
 
 use datafusion::physical_plan::metrics::{self, ExecutionPlanMetricsSet, MetricBuilder};
-use models::schema::TskvTableSchema;
+use models::schema::{DatabaseOptions, TskvTableSchema};
 use protos::kv_service::WritePointsRpcRequest;
 use snafu::ResultExt;
 use trace::debug;
-use tskv::engine::EngineRef;
 
 use crate::utils::point_util::record_batch_to_points_flat_buffer;
+use crate::write_schema::resolve_table_points;
 
 use super::sink::{RecordBatchSink, RecordBatchSinkProvider};
 
+use super::CoordinatorSnafu;
+use super::DataSourceError;
 use super::PointUtilSnafu;
 use super::Result;
-use super::TskvSnafu;
 
 pub struct TskvRecordBatchSink {
-    engine: EngineRef,
+    coord: CoordinatorRef,
+    tenant: String,
     partition: usize,
     schema: TskvTableSchema,
+    database_options: Option<DatabaseOptions>,
 
     metrics: TskvSinkMetrics,
 }
@@ -37,25 +43,73 @@ impl RecordBatchSink for TskvRecordBatchSink {
         let points = record_batch_to_points_flat_buffer(&record_batch, self.schema.clone())
             .context(PointUtilSnafu)?;
         timer.done();
+        let points = self.resolve_points(points)?;
 
         // points write request
         let timer = self.metrics.elapsed_point_write().timer();
         let req = WritePointsRpcRequest { version: 0, points };
-        let _ = self.engine.write(req).await.context(TskvSnafu)?;
+        self.coord
+            .write_points(&self.tenant, DEFAULT_WRITE_CONSISTENCY, req)
+            .await
+            .context(CoordinatorSnafu)?;
         timer.done();
 
         Ok(())
     }
 }
 
+impl TskvRecordBatchSink {
+    /// The timestamps out of the window allowed by the database are resolved as those
+    /// written by the line protocol, the insert fails if any of the rows is rejected
+    fn resolve_points(&self, points: Vec<u8>) -> Result<Vec<u8>> {
+        let options = match &self.database_options {
+            Some(options) => options,
+            None => return Ok(points),
+        };
+        let now = Local::now().timestamp_nanos();
+        let written = resolve_table_points(options, &self.schema, points, now).map_err(|e| {
+            DataSourceError::WriteRejected {
+                reason: e.to_string(),
+            }
+        })?;
+        let rejected = written
+            .out_of_window
+            .iter()
+            .filter(|e| e.clamped.is_none())
+            .count();
+        if rejected > 0 {
+            return Err(DataSourceError::WriteRejected {
+                reason: format!(
+                    "{} rows out of the time window of database {}",
+                    rejected, self.schema.db
+                ),
+            });
+        }
+        Ok(written.points)
+    }
+}
+
 pub struct TskvRecordBatchSinkProvider {
-    engine: EngineRef,
+    coord: CoordinatorRef,
+    tenant: String,
     schema: TskvTableSchema,
+    database_options: Option<DatabaseOptions>,
 }
 
 impl TskvRecordBatchSinkProvider {
-    pub fn new(engine: EngineRef, schema: TskvTableSchema) -> Self {
-        Self { engine, schema }
+    pub fn new(coord: CoordinatorRef, tenant: &str, schema: TskvTableSchema) -> Self {
+        Self {
+            coord,
+            tenant: tenant.to_string(),
+            schema,
+            database_options: None,
+        }
+    }
+
+    /// The rows written are resolved by the options of the database
+    pub fn with_database_options(mut self, options: Option<DatabaseOptions>) -> Self {
+        self.database_options = options;
+        self
     }
 }
 
@@ -66,9 +120,11 @@ impl RecordBatchSinkProvider for TskvRecordBatchSinkProvider {
         partition: usize,
     ) -> Box<dyn RecordBatchSink> {
         Box::new(TskvRecordBatchSink {
-            engine: self.engine.clone(),
+            coord: self.coord.clone(),
+            tenant: self.tenant.clone(),
             partition,
             schema: self.schema.clone(),
+            database_options: self.database_options.clone(),
             metrics: TskvSinkMetrics::new(metrics, partition),
         })
     }
@@ -0,0 +1,103 @@
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use datafusion::arrow::array::{StringBuilder, UInt64Builder};
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use datafusion::arrow::error::ArrowError;
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::datasource::source_as_provider;
+use datafusion::logical_expr::LogicalPlan;
+use metrics::database_stats::{database_stats, record_query};
+use spi::query::logical_planner::Plan;
+
+use crate::sql::logical::column_privilege::visit_plans;
+use crate::table::ClusterTable;
+
+/// Table of the writes and queries of the databases on this node since started,
+/// `SELECT * FROM system.database_stats`
+pub const DATABASE_STATS_TABLE: &str = "database_stats";
+
+pub fn schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("tenant", DataType::Utf8, false),
+        Field::new("database", DataType::Utf8, false),
+        Field::new("points_written", DataType::UInt64, false),
+        Field::new("bytes_written", DataType::UInt64, false),
+        Field::new("writes_failed", DataType::UInt64, false),
+        Field::new("queries", DataType::UInt64, false),
+        Field::new("queries_failed", DataType::UInt64, false),
+        Field::new("bytes_scanned", DataType::UInt64, false),
+    ]))
+}
+
+/// The databases of the tenant only if `tenant` is given
+pub fn record_batch(tenant: Option<&str>) -> Result<RecordBatch, ArrowError> {
+    let stats = database_stats()
+        .into_iter()
+        .filter(|((t, _), _)| tenant.map_or(true, |tenant| t == tenant))
+        .collect::<Vec<_>>();
+    let mut tenants = StringBuilder::new();
+    let mut databases = StringBuilder::new();
+    let mut points_written = UInt64Builder::with_capacity(stats.len());
+    let mut bytes_written = UInt64Builder::with_capacity(stats.len());
+    let mut writes_failed = UInt64Builder::with_capacity(stats.len());
+    let mut queries = UInt64Builder::with_capacity(stats.len());
+    let mut queries_failed = UInt64Builder::with_capacity(stats.len());
+    let mut bytes_scanned = UInt64Builder::with_capacity(stats.len());
+    for ((tenant, db), e) in stats.iter() {
+        tenants.append_value(tenant);
+        databases.append_value(db);
+        points_written.append_value(e.points_written);
+        bytes_written.append_value(e.bytes_written);
+        writes_failed.append_value(e.writes_failed);
+        queries.append_value(e.queries);
+        queries_failed.append_value(e.queries_failed);
+        bytes_scanned.append_value(e.bytes_scanned);
+    }
+
+    RecordBatch::try_new(
+        schema(),
+        vec![
+            Arc::new(tenants.finish()),
+            Arc::new(databases.finish()),
+            Arc::new(points_written.finish()),
+            Arc::new(bytes_written.finish()),
+            Arc::new(writes_failed.finish()),
+            Arc::new(queries.finish()),
+            Arc::new(queries_failed.finish()),
+            Arc::new(bytes_scanned.finish()),
+        ],
+    )
+}
+
+/// The tenants and the databases of the tables scanned by the plan
+pub fn scanned_databases(plan: &Plan) -> BTreeSet<(String, String)> {
+    let mut databases = BTreeSet::new();
+    if let Plan::Query(query) = plan {
+        let _ = visit_plans(&query.df_plan, &mut |plan| {
+            if let LogicalPlan::TableScan(scan) = plan {
+                let provider = source_as_provider(&scan.source)?;
+                if let Some(table) = provider.as_any().downcast_ref::<ClusterTable>() {
+                    let db = table.table_schema().db.clone();
+                    databases.insert((table.tenant().to_string(), db));
+                }
+            }
+            Ok(())
+        });
+    }
+    databases
+}
+
+/// Count the query to each of the databases scanned, the bytes scanned split evenly
+pub fn record_query_stats(
+    databases: &BTreeSet<(String, String)>,
+    bytes_scanned: u64,
+    success: bool,
+) {
+    let n = databases.len() as u64;
+    for (i, (tenant, db)) in databases.iter().enumerate() {
+        // The remainder is counted to the first one
+        let remainder = if i == 0 { bytes_scanned % n } else { 0 };
+        record_query(tenant, db, bytes_scanned / n + remainder, success);
+    }
+}
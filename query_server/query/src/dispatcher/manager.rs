@@ -1,11 +1,18 @@
+use std::collections::BTreeSet;
 use std::sync::Arc;
 
 use async_trait::async_trait;
-use datafusion::{scheduler::Scheduler, sql::planner::ContextProvider};
+use config::SettingsRef;
+use datafusion::{
+    scheduler::Scheduler,
+    sql::{sqlparser::ast::Statement, TableReference},
+};
 use snafu::ResultExt;
 use spi::catalog::MetaDataRef;
 use spi::query::dispatcher::{QueryInfo, QueryStatus};
 use spi::query::execution::Output;
+use spi::query::logical_planner::Plan;
+use spi::query::session::time_zone;
 use spi::{
     query::{
         ast::ExtStatement,
@@ -21,12 +28,20 @@ use spi::{
 
 use spi::query::QueryError::{self, BuildQueryDispatcher};
 use spi::query::{LogicalPlannerSnafu, Result};
+use trace::{info_span, Instrument};
 
+use crate::audit::{AuditLog, AuditLogRef};
+use crate::database_stats::{record_query_stats, scanned_databases};
 use crate::metadata::MetadataProvider;
+use crate::session::SessionRegistryRef;
+use crate::sql::logical::column_privilege::check_column_privileges;
+use crate::sql::logical::row_policy::apply_row_policies;
+use crate::task::{TaskRunLog, TaskRunLogRef};
 use crate::{
     execution::factory::SqlQueryExecutionFactory, sql::logical::planner::DefaultLogicalPlanner,
 };
 
+use super::plan_cache::{PlanCache, PlanCacheKey};
 use super::query_tracker::QueryTracker;
 
 pub struct SimpleQueryDispatcher {
@@ -39,6 +54,15 @@ pub struct SimpleQueryDispatcher {
     parser: Arc<dyn Parser + Send + Sync>,
     // get query execution factory
     query_execution_factory: Arc<dyn QueryExecutionFactory + Send + Sync>,
+    audit_log: AuditLogRef,
+    task_runs: TaskRunLogRef,
+    plan_cache: PlanCache,
+}
+
+/// A statement to run, the plans of the repeated queries are cached
+enum PreparedStatement {
+    Parsed(ExtStatement),
+    Planned(Plan),
 }
 
 #[async_trait]
@@ -70,21 +94,52 @@ impl QueryDispatcher for SimpleQueryDispatcher {
             .metadata
             .with_catalog(session.catalog())
             .with_database(session.database());
-        let scheme_provider = MetadataProvider::new(metadata.clone());
+        let scheme_provider = MetadataProvider::new(
+            metadata.clone(),
+            self.audit_log.clone(),
+            self.query_tracker.clone(),
+            self.task_runs.clone(),
+            query.context().user_info().user.clone(),
+        );
 
         let logical_planner = DefaultLogicalPlanner::new(scheme_provider);
 
-        let statements = self.parser.parse(query.content())?;
-
-        // not allow multi statement
-        if statements.len() > 1 {
-            return Err(QueryError::MultiStatement {
-                num: statements.len(),
-                sql: query.content().to_string(),
-            });
-        }
-
-        for stmt in statements.iter() {
+        let cache_key = PlanCacheKey {
+            tenant: session.catalog().to_string(),
+            database: session.database().to_string(),
+            time_zone: time_zone(&session.inner().state()),
+            sql: query.content().to_string(),
+        };
+        let cached = self.plan_cache.get(&cache_key, |database, table| {
+            metadata
+                .table(TableReference::Partial {
+                    schema: database,
+                    table,
+                })
+                .ok()
+        });
+        let statements = match cached {
+            Some(plan) => vec![PreparedStatement::Planned(plan)],
+            None => {
+                let statements =
+                    info_span!("parse").in_scope(|| self.parser.parse(query.content()))?;
+
+                // not allow multi statement
+                if statements.len() > 1 {
+                    return Err(QueryError::MultiStatement {
+                        num: statements.len(),
+                        sql: query.content().to_string(),
+                    });
+                }
+
+                statements
+                    .into_iter()
+                    .map(PreparedStatement::Parsed)
+                    .collect()
+            }
+        };
+
+        for stmt in statements {
             let query_state_machine = Arc::new(QueryStateMachine::begin(
                 query_id,
                 query.clone(),
@@ -92,8 +147,15 @@ impl QueryDispatcher for SimpleQueryDispatcher {
                 metadata.clone(),
             ));
 
+            let span = info_span!(
+                "query",
+                query_id = query_id.to_string().as_str(),
+                tenant = query.context().catalog(),
+                db = query.context().database()
+            );
             let result = self
-                .execute_statement(stmt.clone(), &logical_planner, query_state_machine)
+                .execute_statement(stmt, &logical_planner, &cache_key, query_state_machine)
+                .instrument(span)
                 .await?;
 
             results.push(result);
@@ -124,28 +186,92 @@ impl QueryDispatcher for SimpleQueryDispatcher {
 }
 
 impl SimpleQueryDispatcher {
-    async fn execute_statement<S: ContextProvider>(
+    async fn execute_statement(
         &self,
-        stmt: ExtStatement,
-        logical_planner: &DefaultLogicalPlanner<S>,
+        stmt: PreparedStatement,
+        logical_planner: &DefaultLogicalPlanner<MetadataProvider>,
+        cache_key: &PlanCacheKey,
         query_state_machine: Arc<QueryStateMachine>,
     ) -> Result<Output> {
+        let mut databases = BTreeSet::new();
+        let (result, bytes_scanned) = self
+            .run_statement(
+                stmt,
+                logical_planner,
+                cache_key,
+                query_state_machine,
+                &mut databases,
+            )
+            .await;
+        record_query_stats(&databases, bytes_scanned, result.is_ok());
+        result
+    }
+
+    /// The bytes scanned are returned even if failed, the databases scanned
+    /// are collected once planned
+    async fn run_statement(
+        &self,
+        stmt: PreparedStatement,
+        logical_planner: &DefaultLogicalPlanner<MetadataProvider>,
+        cache_key: &PlanCacheKey,
+        query_state_machine: Arc<QueryStateMachine>,
+        databases: &mut BTreeSet<(String, String)>,
+    ) -> (Result<Output>, u64) {
         // begin analyze
         query_state_machine.begin_analyze();
-        let logical_plan = logical_planner
-            .create_logical_plan(stmt.clone(), &query_state_machine.session)
-            .context(LogicalPlannerSnafu)?;
+        let logical_plan = match info_span!("plan")
+            .in_scope(|| {
+                let plan = match stmt {
+                    PreparedStatement::Planned(plan) => plan,
+                    PreparedStatement::Parsed(stmt) => {
+                        let is_query = matches!(
+                            &stmt,
+                            ExtStatement::SqlStatement(stmt) if matches!(stmt.as_ref(), Statement::Query(_))
+                        );
+                        let plan = logical_planner
+                            .create_logical_plan(stmt, &query_state_machine.session)?;
+                        // The privileges are checked below, as the plan is shared by the users
+                        if let (true, Some(tables)) =
+                            (is_query, logical_planner.schema_provider().resolved_tables())
+                        {
+                            self.plan_cache
+                                .insert(cache_key.clone(), plan.clone(), tables);
+                        }
+                        plan
+                    }
+                };
+                let catalog = &query_state_machine.catalog;
+                let user = query_state_machine
+                    .query
+                    .context()
+                    .user_info()
+                    .user
+                    .as_str();
+                check_column_privileges(&plan, catalog, user)?;
+                apply_row_policies(plan, catalog, user)
+            })
+            .context(LogicalPlannerSnafu)
+        {
+            Ok(plan) => plan,
+            Err(e) => return (Err(e), 0),
+        };
         query_state_machine.end_analyze();
+        *databases = scanned_databases(&logical_plan);
 
         let execution = self
             .query_execution_factory
             .create_query_execution(logical_plan, query_state_machine.clone());
 
         // TrackedQuery.drop() is called implicitly when the value goes out of scope,
-        self.query_tracker
-            .try_track_query(query_state_machine.query_id, execution)?
-            .start()
-            .await
+        let query = match self
+            .query_tracker
+            .try_track_query(query_state_machine.query_id, execution)
+        {
+            Ok(query) => query,
+            Err(e) => return (Err(e), 0),
+        };
+        let result = query.start().await;
+        (result, query.status().bytes_scanned())
     }
 }
 
@@ -160,6 +286,11 @@ pub struct SimpleQueryDispatcherBuilder {
     scheduler: Option<Arc<Scheduler>>,
 
     queries_limit: usize,
+    audit_log: Option<AuditLogRef>,
+    task_runs: Option<TaskRunLogRef>,
+    sessions: Option<SessionRegistryRef>,
+    settings: Option<SettingsRef>,
+    plan_cache_capacity: usize,
 }
 
 impl SimpleQueryDispatcherBuilder {
@@ -193,6 +324,31 @@ impl SimpleQueryDispatcherBuilder {
         self
     }
 
+    pub fn with_audit_log(mut self, audit_log: AuditLogRef) -> Self {
+        self.audit_log = Some(audit_log);
+        self
+    }
+
+    pub fn with_task_runs(mut self, task_runs: TaskRunLogRef) -> Self {
+        self.task_runs = Some(task_runs);
+        self
+    }
+
+    pub fn with_sessions(mut self, sessions: SessionRegistryRef) -> Self {
+        self.sessions = Some(sessions);
+        self
+    }
+
+    pub fn with_settings(mut self, settings: SettingsRef) -> Self {
+        self.settings = Some(settings);
+        self
+    }
+
+    pub fn with_plan_cache_capacity(mut self, capacity: usize) -> Self {
+        self.plan_cache_capacity = capacity;
+        self
+    }
+
     pub fn build(self) -> Result<SimpleQueryDispatcher> {
         let metadata = self.metadata.ok_or_else(|| BuildQueryDispatcher {
             err: "lost of metadata".to_string(),
@@ -213,12 +369,24 @@ impl SimpleQueryDispatcherBuilder {
             err: "lost of scheduler".to_string(),
         })?;
 
+        let settings = self.settings.ok_or_else(|| BuildQueryDispatcher {
+            err: "lost of settings".to_string(),
+        })?;
+
         let query_tracker = Arc::new(QueryTracker::new(self.queries_limit));
+        let audit_log = self
+            .audit_log
+            .unwrap_or_else(|| Arc::new(AuditLog::memory()));
+        let task_runs = self.task_runs.unwrap_or_default();
+        let sessions = self.sessions.unwrap_or_default();
 
         let query_execution_factory = Arc::new(SqlQueryExecutionFactory::new(
             optimizer,
             scheduler,
             query_tracker.clone(),
+            audit_log.clone(),
+            sessions,
+            settings,
         ));
 
         Ok(SimpleQueryDispatcher {
@@ -227,6 +395,9 @@ impl SimpleQueryDispatcherBuilder {
             parser,
             query_execution_factory,
             query_tracker,
+            audit_log,
+            task_runs,
+            plan_cache: PlanCache::new(self.plan_cache_capacity),
         })
     }
 }
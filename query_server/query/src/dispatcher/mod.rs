@@ -1,2 +1,3 @@
 pub mod manager;
+pub mod plan_cache;
 pub mod query_tracker;
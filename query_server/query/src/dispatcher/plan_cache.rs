@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+
+use metrics::{incr_plan_cache_hit, incr_plan_cache_miss};
+use models::schema::TableSchema;
+use parking_lot::Mutex;
+use spi::query::logical_planner::Plan;
+use trace::debug;
+
+/// The queries planned in the same way share a plan, the tenant and the database
+/// resolve the tables and the time zone buckets the times
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PlanCacheKey {
+    pub tenant: String,
+    pub database: String,
+    pub time_zone: Option<String>,
+    pub sql: String,
+}
+
+/// A table resolved by the planner with its schema when planned
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedTable {
+    pub database: String,
+    pub table: String,
+    pub schema: TableSchema,
+}
+
+struct Entry {
+    plan: Plan,
+    /// The schema version of the plan, which is stale once any of the tables changes
+    tables: Vec<ResolvedTable>,
+    last_used: u64,
+}
+
+struct Inner {
+    entries: HashMap<PlanCacheKey, Entry>,
+    /// Increased by each access, the least recently used entry is evicted once full
+    clock: u64,
+}
+
+/// The logical plans of the queries, so that the repeated queries are neither
+/// parsed nor planned again, the privileges of the users are still checked
+/// on each query.
+pub struct PlanCache {
+    capacity: usize,
+    inner: Mutex<Inner>,
+}
+
+impl PlanCache {
+    /// Nothing is cached if the capacity is 0
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Mutex::new(Inner {
+                entries: HashMap::new(),
+                clock: 0,
+            }),
+        }
+    }
+
+    /// The plan of the query, `None` if not cached or any table scanned has changed
+    /// since planned, `table` gets the current schema of a table of a database
+    pub fn get(
+        &self,
+        key: &PlanCacheKey,
+        table: impl Fn(&str, &str) -> Option<TableSchema>,
+    ) -> Option<Plan> {
+        if self.capacity == 0 {
+            return None;
+        }
+
+        let mut inner = self.inner.lock();
+        inner.clock += 1;
+        let clock = inner.clock;
+        let entry = inner.entries.get_mut(key)?;
+        let is_stale = entry
+            .tables
+            .iter()
+            .any(|e| table(&e.database, &e.table).as_ref() != Some(&e.schema));
+        if is_stale {
+            debug!("Plan of '{}' is stale", key.sql);
+            inner.entries.remove(key);
+            return None;
+        }
+
+        entry.last_used = clock;
+        incr_plan_cache_hit();
+        Some(entry.plan.clone())
+    }
+
+    /// Caches the plan of a query missed, the tables are all the tables resolved
+    /// when planned
+    pub fn insert(&self, key: PlanCacheKey, plan: Plan, tables: Vec<ResolvedTable>) {
+        if self.capacity == 0 {
+            return;
+        }
+        incr_plan_cache_miss();
+
+        let mut inner = self.inner.lock();
+        inner.clock += 1;
+        let clock = inner.clock;
+        if inner.entries.len() >= self.capacity && !inner.entries.contains_key(&key) {
+            let evicted = inner
+                .entries
+                .iter()
+                .min_by_key(|(_, e)| e.last_used)
+                .map(|(k, _)| k.clone());
+            if let Some(evicted) = evicted {
+                inner.entries.remove(&evicted);
+            }
+        }
+        inner.entries.insert(
+            key,
+            Entry {
+                plan,
+                tables,
+                last_used: clock,
+            },
+        );
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::logical_expr::LogicalPlanBuilder;
+    use models::schema::{TableColumn, TskvTableSchema};
+    use spi::query::logical_planner::QueryPlan;
+
+    use super::*;
+
+    fn key(sql: &str) -> PlanCacheKey {
+        PlanCacheKey {
+            tenant: "cnosdb".to_string(),
+            database: "public".to_string(),
+            time_zone: None,
+            sql: sql.to_string(),
+        }
+    }
+
+    fn plan() -> Plan {
+        Plan::Query(QueryPlan {
+            df_plan: LogicalPlanBuilder::empty(false).build().unwrap(),
+        })
+    }
+
+    fn schema(columns: Vec<TableColumn>) -> TableSchema {
+        TableSchema::TsKvTableSchema(TskvTableSchema::new(
+            "public".to_string(),
+            "m".to_string(),
+            columns,
+        ))
+    }
+
+    #[test]
+    fn test_plan_cache_stale() {
+        let cache = PlanCache::new(8);
+        let old = schema(vec![TableColumn::new_time_column(0)]);
+        let new = schema(vec![
+            TableColumn::new_time_column(0),
+            TableColumn::new_tag_column(1, "t0".to_string()),
+        ]);
+        let tables = vec![ResolvedTable {
+            database: "public".to_string(),
+            table: "m".to_string(),
+            schema: old.clone(),
+        }];
+
+        assert!(cache.get(&key("SELECT * FROM m"), |_, _| None).is_none());
+        cache.insert(key("SELECT * FROM m"), plan(), tables);
+        assert!(cache
+            .get(&key("SELECT * FROM m"), |_, _| Some(old.clone()))
+            .is_some());
+        assert!(cache
+            .get(&key("SELECT * FROM m "), |_, _| Some(old.clone()))
+            .is_none());
+
+        // The table is altered
+        assert!(cache
+            .get(&key("SELECT * FROM m"), |_, _| Some(new.clone()))
+            .is_none());
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_plan_cache_evict() {
+        let cache = PlanCache::new(2);
+        cache.insert(key("SELECT 1"), plan(), vec![]);
+        cache.insert(key("SELECT 2"), plan(), vec![]);
+        assert!(cache.get(&key("SELECT 1"), |_, _| None).is_some());
+        cache.insert(key("SELECT 3"), plan(), vec![]);
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(&key("SELECT 1"), |_, _| None).is_some());
+        assert!(cache.get(&key("SELECT 2"), |_, _| None).is_none());
+        assert!(cache.get(&key("SELECT 3"), |_, _| None).is_some());
+
+        let disabled = PlanCache::new(0);
+        disabled.insert(key("SELECT 1"), plan(), vec![]);
+        assert!(disabled.get(&key("SELECT 1"), |_, _| None).is_none());
+    }
+}
@@ -1,5 +1,11 @@
 use std::{collections::HashMap, ops::Deref, sync::Arc};
 
+use datafusion::arrow::{
+    array::{StringBuilder, UInt64Builder},
+    datatypes::{DataType, Field, Schema, SchemaRef},
+    error::ArrowError,
+    record_batch::RecordBatch,
+};
 use parking_lot::RwLock;
 use spi::{
     query::{execution::QueryExecution, QueryError},
@@ -8,6 +14,9 @@ use spi::{
 use tokio::sync::{Semaphore, SemaphorePermit, TryAcquireError};
 use trace::{debug, warn};
 
+/// Table of the running queries, `SELECT * FROM system.queries`
+pub const QUERIES_TABLE: &str = "queries";
+
 pub struct QueryTracker {
     queries: RwLock<HashMap<QueryId, Arc<dyn QueryExecution>>>,
     query_limit_semaphore: Semaphore,
@@ -86,6 +95,53 @@ impl QueryTracker {
     fn expire_query(&self, id: &QueryId) {
         self.queries.write().remove(id);
     }
+
+    pub fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("query_id", DataType::Utf8, false),
+            Field::new("user", DataType::Utf8, false),
+            Field::new("query", DataType::Utf8, false),
+            Field::new("state", DataType::Utf8, false),
+            Field::new("duration", DataType::UInt64, false),
+            Field::new("bytes_scanned", DataType::UInt64, false),
+        ]))
+    }
+
+    /// The running queries as the rows of `SHOW QUERIES`, those of the user only if
+    /// given, the duration is in milliseconds
+    pub fn record_batch(&self, user: Option<&str>) -> Result<RecordBatch, ArrowError> {
+        let mut query_ids = StringBuilder::new();
+        let mut users = StringBuilder::new();
+        let mut queries = StringBuilder::new();
+        let mut states = StringBuilder::new();
+        let mut durations = UInt64Builder::new();
+        let mut bytes_scanned = UInt64Builder::new();
+        for query in self.running_queries() {
+            let info = query.info();
+            if user.map_or(false, |e| e != info.user()) {
+                continue;
+            }
+            let status = query.status();
+            query_ids.append_value(info.query_id().to_string());
+            users.append_value(info.user());
+            queries.append_value(info.query());
+            states.append_value(status.query_state().to_string());
+            durations.append_value(status.duration().as_millis() as u64);
+            bytes_scanned.append_value(status.bytes_scanned());
+        }
+
+        RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(query_ids.finish()),
+                Arc::new(users.finish()),
+                Arc::new(queries.finish()),
+                Arc::new(states.finish()),
+                Arc::new(durations.finish()),
+                Arc::new(bytes_scanned.finish()),
+            ],
+        )
+    }
 }
 
 pub struct TrackedQuery<'a> {
@@ -115,6 +171,7 @@ mod tests {
     use std::sync::Arc;
 
     use async_trait::async_trait;
+    use datafusion::arrow::array::UInt64Array;
     use spi::{
         query::{
             dispatcher::{QueryInfo, QueryStatus},
@@ -145,6 +202,7 @@ mod tests {
                 QueryState::RUNNING(RUNNING::SCHEDULING),
                 Duration::new(0, 1),
             )
+            .with_bytes_scanned(1024)
         }
     }
 
@@ -211,4 +269,24 @@ mod tests {
 
         assert_eq!(info_actual, info_found);
     }
+
+    #[test]
+    fn test_record_batch() {
+        let query = Arc::new(QueryExecutionMock {});
+        let tracker = new_query_tracker(2);
+        assert_eq!(tracker.record_batch(None).unwrap().num_rows(), 0);
+
+        let _tq = tracker.try_track_query(QueryId::next_id(), query).unwrap();
+        assert_eq!(tracker.record_batch(Some("bob")).unwrap().num_rows(), 0);
+        let batch = tracker.record_batch(Some("UNKNOWN")).unwrap();
+        assert_eq!(batch.num_rows(), 1);
+        let batch = tracker.record_batch(None).unwrap();
+        assert_eq!(batch.num_rows(), 1);
+        let bytes_scanned = batch
+            .column(5)
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .unwrap();
+        assert_eq!(bytes_scanned.value(0), 1024);
+    }
 }
@@ -2,6 +2,7 @@ use crate::execution::ddl::DDLDefinitionTask;
 use async_trait::async_trait;
 use models::schema::DatabaseOptions;
 use snafu::ResultExt;
+use spi::catalog::MetadataError;
 use spi::query::execution;
 use spi::query::execution::{ExecutionError, Output, QueryStateMachineRef};
 use spi::query::logical_planner::AlterDatabase;
@@ -27,9 +28,16 @@ impl DDLDefinitionTask for AlterDatabaseTask {
             .database(&self.stmt.database_name)
             .context(execution::MetadataSnafu)?;
         build_database_schema(&self.stmt.database_options, &mut schema.config);
+        // The options altered are checked together with those kept
+        schema
+            .config
+            .validate()
+            .map_err(|error_msg| MetadataError::InvalidSchema { error_msg })
+            .context(execution::MetadataSnafu)?;
         query_state_machine
             .catalog
             .alter_database(schema)
+            .await
             .context(execution::MetadataSnafu)?;
         return Ok(Output::Nil(()));
     }
@@ -51,4 +59,19 @@ fn build_database_schema(database_options: &DatabaseOptions, config: &mut Databa
     if let Some(precision) = database_options.precision() {
         config.with_precision(precision.clone());
     }
+    if let Some(auto_create_schema) = database_options.auto_create_schema() {
+        config.with_auto_create_schema(*auto_create_schema);
+    }
+    if let Some(field_type_conflict) = database_options.field_type_conflict() {
+        config.with_field_type_conflict(*field_type_conflict);
+    }
+    if let Some(future_tolerance) = database_options.future_tolerance() {
+        config.with_future_tolerance(future_tolerance.clone());
+    }
+    if let Some(past_tolerance) = database_options.past_tolerance() {
+        config.with_past_tolerance(past_tolerance.clone());
+    }
+    if let Some(out_of_window) = database_options.out_of_window() {
+        config.with_out_of_window(*out_of_window);
+    }
 }
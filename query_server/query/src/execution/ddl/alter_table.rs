@@ -1,9 +1,18 @@
+use crate::column_conversion::ConversionJob;
+use crate::execution::ddl::export_database::{external, local_coordinator};
 use crate::execution::ddl::query::execution::MetadataSnafu;
 use crate::execution::ddl::DDLDefinitionTask;
+use crate::metadata::LocalCatalogMeta;
 use async_trait::async_trait;
+use datafusion::arrow::error::ArrowError;
+use datafusion::error::DataFusionError;
+use datafusion::sql::TableReference;
+use models::predicate::domain::ColumnDomains;
+use models::schema::{TableSchema, TskvTableSchema};
 use snafu::ResultExt;
 
-use spi::query::execution::{ExecutionError, Output, QueryStateMachineRef};
+use spi::catalog::MetaDataRef;
+use spi::query::execution::{ExecutionError, ExternalSnafu, Output, QueryStateMachineRef};
 use spi::query::logical_planner::{AlterTable, AlterTableAction};
 
 pub struct AlterTableTask {
@@ -26,17 +35,80 @@ impl DDLDefinitionTask for AlterTableTask {
         match &self._stmt.alter_action {
             AlterTableAction::AddColumn { table_column } => catalog
                 .alter_table_add_column(table_name, table_column.clone())
+                .await
                 .context(MetadataSnafu)?,
             AlterTableAction::DropColumn { column_name } => catalog
                 .alter_table_drop_column(table_name, column_name)
+                .await
                 .context(MetadataSnafu)?,
             AlterTableAction::AlterColumn {
                 column_name,
                 new_column,
             } => catalog
                 .alter_table_alter_column(table_name, column_name, new_column.clone())
+                .await
                 .context(MetadataSnafu)?,
+            AlterTableAction::ConvertColumn {
+                column_name,
+                new_column,
+            } => {
+                let coord = local_coordinator(&catalog)?;
+                let conversions = catalog
+                    .as_any()
+                    .downcast_ref::<LocalCatalogMeta>()
+                    .map(|e| e.column_conversions())
+                    .ok_or_else(|| DataFusionError::Plan("failed to get meta data".to_string()))
+                    .context(ExternalSnafu)?;
+                let old_schema = tskv_table_schema(&catalog, table_name)?;
+                let mut new_schema = old_schema.clone();
+                new_schema.change_column(column_name, new_column.clone());
+                // The series are listed before the new points are written with the new schema
+                let series = coord
+                    .engine()
+                    .get_series_id_by_filter(
+                        &old_schema.db,
+                        &old_schema.name,
+                        &ColumnDomains::all(),
+                    )
+                    .map_err(external)?;
+
+                let job = ConversionJob {
+                    tenant: catalog.catalog_name().to_string(),
+                    old_schema,
+                    new_schema,
+                    column_name: column_name.clone(),
+                    series,
+                    series_done: 0,
+                    points: 0,
+                };
+                // The points are moved in the background, the progress of which is
+                // in `system.column_conversions`
+                conversions
+                    .submit(job, async {
+                        catalog
+                            .alter_table_alter_column(table_name, column_name, new_column.clone())
+                            .await
+                            .context(MetadataSnafu)
+                    })
+                    .await?;
+            }
         }
         return Ok(Output::Nil(()));
     }
 }
+
+fn tskv_table_schema(
+    catalog: &MetaDataRef,
+    table_name: &str,
+) -> Result<TskvTableSchema, ExecutionError> {
+    match catalog
+        .table(TableReference::from(table_name))
+        .context(MetadataSnafu)?
+    {
+        TableSchema::TsKvTableSchema(schema) => Ok(schema),
+        TableSchema::ExternalTableSchema(_) => Err(external(ArrowError::SchemaError(format!(
+            "table {} is not a tskv table",
+            table_name
+        )))),
+    }
+}
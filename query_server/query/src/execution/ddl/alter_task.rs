@@ -0,0 +1,38 @@
+use crate::execution::ddl::{task_of_user, DDLDefinitionTask};
+use async_trait::async_trait;
+use snafu::ResultExt;
+use spi::query::execution;
+use spi::query::execution::{ExecutionError, Output, QueryStateMachineRef};
+use spi::query::logical_planner::AlterTask;
+
+pub struct AlterTaskTask {
+    stmt: AlterTask,
+}
+
+impl AlterTaskTask {
+    pub fn new(stmt: AlterTask) -> Self {
+        Self { stmt }
+    }
+}
+
+#[async_trait]
+impl DDLDefinitionTask for AlterTaskTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> Result<Output, ExecutionError> {
+        let AlterTask {
+            ref name,
+            ref enabled,
+        } = self.stmt;
+
+        let mut task = task_of_user(&query_state_machine, name)?;
+        task.enabled = *enabled;
+        query_state_machine
+            .catalog
+            .alter_task(task)
+            .await
+            .map(|_| Output::Nil(()))
+            .context(execution::MetadataSnafu)
+    }
+}
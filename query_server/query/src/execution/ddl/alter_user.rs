@@ -0,0 +1,54 @@
+use crate::execution::ddl::{check_admin, DDLDefinitionTask};
+use async_trait::async_trait;
+use models::auth::hash_password;
+use snafu::ResultExt;
+use spi::catalog::MetadataError;
+use spi::query::execution;
+use spi::query::execution::{ExecutionError, Output, QueryStateMachineRef};
+use spi::query::logical_planner::AlterUser;
+
+pub struct AlterUserTask {
+    stmt: AlterUser,
+}
+
+impl AlterUserTask {
+    pub fn new(stmt: AlterUser) -> Self {
+        Self { stmt }
+    }
+}
+
+#[async_trait]
+impl DDLDefinitionTask for AlterUserTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> Result<Output, ExecutionError> {
+        let AlterUser {
+            ref name,
+            ref password,
+            ref is_admin,
+        } = self.stmt;
+
+        check_admin(&query_state_machine)?;
+        let catalog = query_state_machine.catalog.clone();
+        let mut user = catalog
+            .user(name)
+            .context(execution::MetadataSnafu)?
+            .ok_or_else(|| MetadataError::UserNotExists {
+                user_name: name.clone(),
+            })
+            .context(execution::MetadataSnafu)?;
+        if let Some(password) = password {
+            user.password_hash = hash_password(password);
+        }
+        if let Some(is_admin) = is_admin {
+            user.is_admin = *is_admin;
+        }
+
+        catalog
+            .alter_user(user)
+            .await
+            .map(|_| Output::Nil(()))
+            .context(execution::MetadataSnafu)
+    }
+}
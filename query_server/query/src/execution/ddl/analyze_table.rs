@@ -0,0 +1,181 @@
+use crate::execution::ddl::export_database::external;
+use crate::execution::ddl::DDLDefinitionTask;
+use crate::metadata::cluster_table;
+use async_trait::async_trait;
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::catalog::TableReference;
+use datafusion::common::Column;
+use datafusion::error::DataFusionError;
+use datafusion::prelude::{approx_distinct, count, lit, max, min, Expr};
+use datafusion::scalar::ScalarValue;
+use models::schema::{TableSchema, TableStatistics, TIME_FIELD_NAME};
+use snafu::ResultExt;
+use spi::catalog::MetadataError;
+use spi::query::execution::{ExecutionError, Output, QueryStateMachineRef};
+use spi::query::execution::{ExternalSnafu, MetadataSnafu};
+use spi::query::logical_planner::AnalyzeTable;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use trace::info;
+
+pub struct AnalyzeTableTask {
+    stmt: AnalyzeTable,
+}
+
+impl AnalyzeTableTask {
+    pub fn new(stmt: AnalyzeTable) -> Self {
+        Self { stmt }
+    }
+}
+
+#[async_trait]
+impl DDLDefinitionTask for AnalyzeTableTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> Result<Output, ExecutionError> {
+        let table_name = self.stmt.table_name.as_str();
+        let catalog = &query_state_machine.catalog;
+        let schema = match catalog
+            .table(TableReference::from(table_name))
+            .context(MetadataSnafu)?
+        {
+            TableSchema::TsKvTableSchema(schema) => schema,
+            TableSchema::ExternalTableSchema(_) => {
+                return Err(MetadataError::TableIsNotTsKv {
+                    table_name: table_name.to_string(),
+                })
+                .context(MetadataSnafu)
+            }
+        };
+
+        // The statistics read all of the rows and the tags of the table
+        super::check_table_unrestricted(&query_state_machine, &schema.db, &schema.name)?;
+
+        let tags: Vec<String> = schema
+            .columns()
+            .iter()
+            .filter(|e| e.column_type.is_tag())
+            .map(|e| e.name.clone())
+            .collect();
+        let table = cluster_table(catalog, schema.clone())
+            .ok_or_else(|| DataFusionError::Plan("failed to get meta data".to_string()))
+            .context(ExternalSnafu)?;
+
+        // A single scan of the table aggregates all the statistics
+        let column = |name: &str| Expr::Column(Column::from_name(name));
+        let mut aggr_expr = vec![
+            count(lit(1)),
+            min(column(TIME_FIELD_NAME)),
+            max(column(TIME_FIELD_NAME)),
+        ];
+        aggr_expr.extend(tags.iter().map(|e| approx_distinct(column(e))));
+        let batches = query_state_machine
+            .session
+            .inner()
+            .read_table(Arc::new(table))
+            .context(ExternalSnafu)?
+            .aggregate(vec![], aggr_expr)
+            .context(ExternalSnafu)?
+            .collect()
+            .await
+            .context(ExternalSnafu)?;
+
+        let statistics = table_statistics(&batches, &tags)?;
+        info!(
+            "Analyzed table {}.{}: {:?}",
+            schema.db, schema.name, statistics
+        );
+        catalog
+            .alter_table_statistics(table_name, statistics)
+            .await
+            .context(MetadataSnafu)?;
+
+        Ok(Output::Nil(()))
+    }
+}
+
+/// The statistics from the row aggregated by the count of the rows, the min and
+/// the max time and the distinct values of each tag
+fn table_statistics(
+    batches: &[RecordBatch],
+    tags: &[String],
+) -> Result<TableStatistics, ExecutionError> {
+    let batch = match batches.iter().find(|e| e.num_rows() > 0) {
+        Some(batch) => batch,
+        None => {
+            return Ok(TableStatistics {
+                analyzed_at: now(),
+                ..Default::default()
+            })
+        }
+    };
+    let value = |i: usize| ScalarValue::try_from_array(batch.column(i), 0).map_err(external);
+
+    let row_count = match value(0)? {
+        ScalarValue::Int64(Some(count)) => count as u64,
+        _ => 0,
+    };
+    let time = |i: usize| -> Result<Option<i64>, ExecutionError> {
+        Ok(match value(i)? {
+            ScalarValue::TimestampNanosecond(time, _) => time,
+            _ => None,
+        })
+    };
+    let mut tag_ndvs = BTreeMap::new();
+    for (i, tag) in tags.iter().enumerate() {
+        if let ScalarValue::UInt64(Some(ndv)) = value(3 + i)? {
+            tag_ndvs.insert(tag.clone(), ndv);
+        }
+    }
+
+    Ok(TableStatistics {
+        analyzed_at: now(),
+        row_count,
+        min_time: time(1)?,
+        max_time: time(2)?,
+        tag_ndvs,
+    })
+}
+
+fn now() -> i64 {
+    chrono::Utc::now().timestamp_nanos()
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::arrow::array::{Int64Array, TimestampNanosecondArray, UInt64Array};
+    use datafusion::arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+
+    use super::*;
+
+    #[test]
+    fn test_table_statistics() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("count", DataType::Int64, true),
+            Field::new("min", DataType::Timestamp(TimeUnit::Nanosecond, None), true),
+            Field::new("max", DataType::Timestamp(TimeUnit::Nanosecond, None), true),
+            Field::new("t0", DataType::UInt64, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(vec![3])),
+                Arc::new(TimestampNanosecondArray::from(vec![1])),
+                Arc::new(TimestampNanosecondArray::from(vec![5])),
+                Arc::new(UInt64Array::from(vec![2])),
+            ],
+        )
+        .unwrap();
+
+        let statistics = table_statistics(&[batch], &["t0".to_string()]).unwrap();
+        assert_eq!(statistics.row_count, 3);
+        assert_eq!(statistics.min_time, Some(1));
+        assert_eq!(statistics.max_time, Some(5));
+        assert_eq!(statistics.tag_ndvs, BTreeMap::from([("t0".to_string(), 2)]));
+
+        let empty = table_statistics(&[], &["t0".to_string()]).unwrap();
+        assert_eq!(empty.row_count, 0);
+        assert!(empty.tag_ndvs.is_empty());
+    }
+}
@@ -45,14 +45,17 @@ impl DDLDefinitionTask for CreateDatabaseTask {
             .context(execution::MetadataSnafu),
             // does not exist, create
             (_, false) => {
-                create_database(&self.stmt, query_state_machine.catalog.clone())?;
+                create_database(&self.stmt, query_state_machine.catalog.clone()).await?;
                 Ok(Output::Nil(()))
             }
         }
     }
 }
 
-fn create_database(stmt: &CreateDatabase, catalog: MetaDataRef) -> Result<(), ExecutionError> {
+async fn create_database(
+    stmt: &CreateDatabase,
+    catalog: MetaDataRef,
+) -> Result<(), ExecutionError> {
     let CreateDatabase {
         ref name,
         ref options,
@@ -65,6 +68,7 @@ fn create_database(stmt: &CreateDatabase, catalog: MetaDataRef) -> Result<(), Ex
     };
     catalog
         .create_database(name, database_schema)
+        .await
         .context(execution::MetadataSnafu)?;
     Ok(())
 }
@@ -1,20 +1,14 @@
 use std::ops::Deref;
-use std::str::FromStr;
 use std::sync::Arc;
 
 use async_trait::async_trait;
-use datafusion::arrow::datatypes::SchemaRef;
-use datafusion::datasource::file_format::avro::AvroFormat;
-use datafusion::datasource::file_format::csv::CsvFormat;
-use datafusion::datasource::file_format::file_type::{FileCompressionType, FileType};
-use datafusion::datasource::file_format::json::JsonFormat;
-use datafusion::datasource::file_format::parquet::ParquetFormat;
-use datafusion::datasource::file_format::FileFormat;
+use datafusion::arrow::datatypes::{Schema, SchemaRef};
 use datafusion::datasource::listing::{ListingOptions, ListingTableUrl};
 use datafusion::error::DataFusionError;
 use datafusion::execution::context::SessionState;
 use datafusion::logical_expr::CreateExternalTable;
 use datafusion::sql::TableReference;
+use futures::TryStreamExt;
 use models::schema::{ExternalTableSchema, TableSchema};
 use snafu::ResultExt;
 use spi::catalog::MetadataError;
@@ -85,6 +79,7 @@ async fn create_exernal_table(
     query_state_machine
         .catalog
         .create_table(name, TableSchema::ExternalTableSchema(schema))
+        .await
         .context(execution::MetadataSnafu)?;
 
     Ok(())
@@ -95,29 +90,82 @@ async fn build_table_schema(
     db: String,
     state: &SessionState,
 ) -> Result<ExternalTableSchema, ExecutionError> {
-    let options =
-        build_external_table_config(stmt, state.config.target_partitions).context(ExternalSnafu)?;
-
-    let schema = construct_listing_table_schema(stmt, state, &options)
-        .await?
-        .deref()
-        .clone();
+    // Compression is detected by the extension of a single file if not declared
+    let file_compression_type = if stmt.file_compression_type.is_empty() {
+        ExternalTableSchema::compression_from_extension(&stmt.location)
+            .unwrap_or_default()
+            .to_string()
+    } else {
+        stmt.file_compression_type.clone()
+    };
 
-    let schema = ExternalTableSchema {
+    let mut schema = ExternalTableSchema {
         db,
         name: stmt.name.clone(),
         location: stmt.location.clone(),
         file_type: stmt.file_type.clone(),
-        file_compression_type: stmt.file_compression_type.clone(),
+        file_compression_type,
         target_partitions: state.config.target_partitions,
         table_partition_cols: stmt.table_partition_cols.clone(),
         has_header: stmt.has_header,
         delimiter: stmt.delimiter as u8,
-        schema,
+        schema: Schema::empty(),
     };
+
+    if schema.table_partition_cols.is_empty() {
+        let options = schema.table_options().context(ExternalSnafu)?;
+        schema.table_partition_cols = discover_partition_cols(state, &stmt.location, &options)
+            .await
+            .context(ExternalSnafu)?;
+    }
+
+    let options = schema.table_options().context(ExternalSnafu)?;
+    schema.schema = construct_listing_table_schema(stmt, state, &options)
+        .await?
+        .deref()
+        .clone();
+
     Ok(schema)
 }
 
+/// Discovers the hive-style partition columns from the `key=value` directories
+/// between the location and its first file, e.g. `year` and `month` of
+/// `location/year=2022/month=10/data.csv`.
+async fn discover_partition_cols(
+    state: &SessionState,
+    location: &str,
+    options: &ListingOptions,
+) -> Result<Vec<String>, DataFusionError> {
+    // A single file is not partitioned
+    if options.file_extension.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let table_path = ListingTableUrl::parse(location)?;
+    let store = state.runtime_env.object_store(table_path.object_store())?;
+    let prefix = table_path.prefix();
+    let mut files = store.list(Some(prefix)).await?;
+
+    while let Some(file) = files.try_next().await? {
+        if !file.location.as_ref().ends_with(&options.file_extension) {
+            continue;
+        }
+
+        let parts = match file.location.prefix_match(prefix) {
+            Some(parts) => parts.collect::<Vec<_>>(),
+            None => continue,
+        };
+        // The last part is the file name
+        let dirs = &parts[..parts.len().saturating_sub(1)];
+        return Ok(dirs
+            .iter()
+            .map_while(|e| e.as_ref().split_once('=').map(|(key, _)| key.to_string()))
+            .collect());
+    }
+
+    Ok(vec![])
+}
+
 async fn construct_listing_table_schema(
     stmt: &CreateExternalTable,
     state: &SessionState,
@@ -133,55 +181,33 @@ async fn construct_listing_table_schema(
     let provided_schema = if schema.fields().is_empty() {
         None
     } else {
-        Some(Arc::new(schema.as_ref().to_owned().into()))
+        // Partition columns are read from the path instead of the files
+        let fields = schema
+            .fields()
+            .iter()
+            .filter(|e| !options.table_partition_cols.contains(e.name()))
+            .map(|e| e.field().clone())
+            .collect();
+        Some(Arc::new(Schema::new(fields)))
     };
 
     let table_path = ListingTableUrl::parse(location).context(ExternalSnafu)?;
     Ok(match provided_schema {
-        None => options
-            .infer_schema(state, &table_path)
-            .await
-            .context(execution::ExternalSnafu)?,
+        // Parquet and avro schema is read from the file metadata, csv and json ones are sampled
+        None => {
+            let schema = options
+                .infer_schema(state, &table_path)
+                .await
+                .context(execution::ExternalSnafu)?;
+            if schema.fields().is_empty() {
+                return Err(DataFusionError::Plan(format!(
+                    "Failed to infer the schema of {}, no columns are specified and no file with extension '{}' is found",
+                    location, options.file_extension
+                )))
+                .context(ExternalSnafu);
+            }
+            schema
+        }
         Some(s) => s,
     })
 }
-
-fn build_external_table_config(
-    stmt: &CreateExternalTable,
-    target_partitions: usize,
-) -> Result<ListingOptions, DataFusionError> {
-    let file_format: Arc<dyn FileFormat> = match FileType::from_str(&stmt.file_type)? {
-        FileType::CSV => Arc::new(
-            CsvFormat::default()
-                .with_has_header(stmt.has_header)
-                .with_delimiter(stmt.delimiter as u8)
-                .with_file_compression_type(
-                    FileCompressionType::from_str(&stmt.file_compression_type).map_err(|_| {
-                        DataFusionError::Execution(
-                            "Only known FileCompressionTypes can be ListingTables!".to_string(),
-                        )
-                    })?,
-                ),
-        ),
-        FileType::PARQUET => Arc::new(ParquetFormat::default()),
-        FileType::AVRO => Arc::new(AvroFormat::default()),
-        FileType::JSON => Arc::new(JsonFormat::default().with_file_compression_type(
-            FileCompressionType::from_str(&stmt.file_compression_type)?,
-        )),
-    };
-
-    let file_type = match FileType::from_str(stmt.file_type.as_str()) {
-        Ok(t) => t,
-        Err(_) => Err(DataFusionError::Execution(
-            "Only known FileTypes can be ListingTables!".to_string(),
-        ))?,
-    };
-    Ok(ListingOptions {
-        format: file_format,
-        collect_stat: false,
-        file_extension: file_type
-            .get_ext_with_compression(stmt.file_compression_type.to_owned().parse()?)?,
-        target_partitions,
-        table_partition_cols: stmt.table_partition_cols.clone(),
-    })
-}
@@ -0,0 +1,72 @@
+use crate::execution::ddl::{check_admin, DDLDefinitionTask};
+use async_trait::async_trait;
+use models::meta_data::RowPolicy;
+use snafu::ResultExt;
+use spi::catalog::MetadataError;
+use spi::query::execution;
+use spi::query::execution::{ExecutionError, Output, QueryStateMachineRef};
+use spi::query::logical_planner::CreatePolicy;
+
+/// The names of the policies are unique on a table, whatever the role
+pub struct CreatePolicyTask {
+    stmt: CreatePolicy,
+}
+
+impl CreatePolicyTask {
+    pub fn new(stmt: CreatePolicy) -> Self {
+        Self { stmt }
+    }
+}
+
+#[async_trait]
+impl DDLDefinitionTask for CreatePolicyTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> Result<Output, ExecutionError> {
+        let CreatePolicy {
+            ref name,
+            ref database,
+            ref table,
+            ref role,
+            ref predicate,
+        } = self.stmt;
+
+        check_admin(&query_state_machine)?;
+
+        let catalog = &query_state_machine.catalog;
+        let tenant = catalog.catalog_name();
+        let roles = catalog.roles().context(execution::MetadataSnafu)?;
+        let exists = roles
+            .iter()
+            .flat_map(|e| e.policies.iter())
+            .any(|e| e.name == *name && e.is_on(tenant, database, table));
+        if exists {
+            return Err(MetadataError::PolicyAlreadyExists {
+                policy_name: name.clone(),
+                table_name: format!("{}.{}", database, table),
+            })
+            .context(execution::MetadataSnafu);
+        }
+
+        let mut role = roles
+            .into_iter()
+            .find(|e| e.name == *role)
+            .ok_or_else(|| MetadataError::RoleNotExists {
+                role_name: role.clone(),
+            })
+            .context(execution::MetadataSnafu)?;
+        role.policies.push(RowPolicy {
+            name: name.clone(),
+            tenant: tenant.to_string(),
+            database: database.clone(),
+            table: table.clone(),
+            predicate: predicate.clone(),
+        });
+        catalog
+            .alter_role(role)
+            .await
+            .map(|_| Output::Nil(()))
+            .context(execution::MetadataSnafu)
+    }
+}
@@ -0,0 +1,46 @@
+use crate::execution::ddl::{check_admin, DDLDefinitionTask};
+use async_trait::async_trait;
+use models::meta_data::RoleInfo;
+use snafu::ResultExt;
+use spi::catalog::MetadataError;
+use spi::query::execution;
+use spi::query::execution::{ExecutionError, Output, QueryStateMachineRef};
+use spi::query::logical_planner::CreateRole;
+
+pub struct CreateRoleTask {
+    stmt: CreateRole,
+}
+
+impl CreateRoleTask {
+    pub fn new(stmt: CreateRole) -> Self {
+        Self { stmt }
+    }
+}
+
+#[async_trait]
+impl DDLDefinitionTask for CreateRoleTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> Result<Output, ExecutionError> {
+        let CreateRole {
+            ref name,
+            ref if_not_exists,
+        } = self.stmt;
+
+        check_admin(&query_state_machine)?;
+
+        let role = RoleInfo {
+            name: name.clone(),
+            policies: vec![],
+            column_privileges: vec![],
+            groups: vec![],
+        };
+        match query_state_machine.catalog.create_role(role).await {
+            Err(MetadataError::RoleAlreadyExists { .. }) if *if_not_exists => Ok(Output::Nil(())),
+            res => res
+                .map(|_| Output::Nil(()))
+                .context(execution::MetadataSnafu),
+        }
+    }
+}
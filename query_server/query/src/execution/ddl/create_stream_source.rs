@@ -0,0 +1,58 @@
+use crate::execution::ddl::DDLDefinitionTask;
+use async_trait::async_trait;
+use models::stream_source::StreamSourceDefinition;
+use snafu::ResultExt;
+use spi::catalog::MetadataError;
+use spi::query::execution;
+use spi::query::execution::{ExecutionError, Output, QueryStateMachineRef};
+use spi::query::logical_planner::CreateStreamSource;
+
+pub struct CreateStreamSourceTask {
+    stmt: CreateStreamSource,
+}
+
+impl CreateStreamSourceTask {
+    pub fn new(stmt: CreateStreamSource) -> Self {
+        Self { stmt }
+    }
+}
+
+#[async_trait]
+impl DDLDefinitionTask for CreateStreamSourceTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> Result<Output, ExecutionError> {
+        let CreateStreamSource {
+            ref name,
+            ref if_not_exists,
+            ref connector,
+            ref format,
+            ref database,
+            ref table,
+            ref options,
+        } = self.stmt;
+
+        let catalog = query_state_machine.catalog.clone();
+        let definition = StreamSourceDefinition {
+            name: name.clone(),
+            connector: *connector,
+            format: *format,
+            database: database
+                .clone()
+                .unwrap_or_else(|| catalog.schema_name().to_string()),
+            table: table.clone(),
+            options: options.clone(),
+        };
+
+        match catalog.create_stream_source(definition) {
+            // do not create if exists
+            Err(MetadataError::StreamSourceAlreadyExists { .. }) if *if_not_exists => {
+                Ok(Output::Nil(()))
+            }
+            res => res
+                .map(|_| Output::Nil(()))
+                .context(execution::MetadataSnafu),
+        }
+    }
+}
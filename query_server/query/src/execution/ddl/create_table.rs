@@ -43,18 +43,19 @@ impl DDLDefinitionTask for CreateTableTask {
             .context(execution::MetadataSnafu),
             // does not exist, create
             (_, Err(_)) => {
-                create_table(&self.stmt, query_state_machine.catalog.clone())?;
+                create_table(&self.stmt, query_state_machine.catalog.clone()).await?;
                 Ok(Output::Nil(()))
             }
         }
     }
 }
 
-fn create_table(stmt: &CreateTable, catalog: MetaDataRef) -> Result<(), ExecutionError> {
+async fn create_table(stmt: &CreateTable, catalog: MetaDataRef) -> Result<(), ExecutionError> {
     let CreateTable { name, .. } = stmt;
     let table_schema = build_schema(stmt, catalog.clone());
     catalog
         .create_table(name, TableSchema::TsKvTableSchema(table_schema))
+        .await
         .context(execution::MetadataSnafu)
 }
 
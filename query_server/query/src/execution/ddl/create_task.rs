@@ -0,0 +1,58 @@
+use crate::execution::ddl::{check_admin, DDLDefinitionTask};
+use async_trait::async_trait;
+use models::task::{TaskDefinition, TaskSink};
+use snafu::ResultExt;
+use spi::catalog::MetadataError;
+use spi::query::execution;
+use spi::query::execution::{ExecutionError, Output, QueryStateMachineRef};
+use spi::query::logical_planner::CreateTask;
+
+pub struct CreateTaskTask {
+    stmt: CreateTask,
+}
+
+impl CreateTaskTask {
+    pub fn new(stmt: CreateTask) -> Self {
+        Self { stmt }
+    }
+}
+
+#[async_trait]
+impl DDLDefinitionTask for CreateTaskTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> Result<Output, ExecutionError> {
+        let CreateTask {
+            ref name,
+            ref if_not_exists,
+            ref query,
+            ref trigger,
+            ref sink,
+        } = self.stmt;
+        // The node posts to the url regardless of where it is, only the admins do
+        if matches!(sink, TaskSink::Webhook(_)) {
+            check_admin(&query_state_machine)?;
+        }
+
+        let catalog = query_state_machine.catalog.clone();
+        let definition = TaskDefinition {
+            name: name.clone(),
+            tenant: catalog.catalog_name().to_string(),
+            database: catalog.schema_name().to_string(),
+            owner: query_state_machine.query.context().user_info().user.clone(),
+            query: query.clone(),
+            trigger: trigger.clone(),
+            sink: sink.clone(),
+            enabled: true,
+        };
+
+        match catalog.create_task(definition).await {
+            // do not create if exists
+            Err(MetadataError::TaskAlreadyExists { .. }) if *if_not_exists => Ok(Output::Nil(())),
+            res => res
+                .map(|_| Output::Nil(()))
+                .context(execution::MetadataSnafu),
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use crate::execution::ddl::{token_owner, DDLDefinitionTask};
+use async_trait::async_trait;
+use datafusion::arrow::array::StringArray;
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::arrow::record_batch::RecordBatch;
+use models::auth::{generate_api_token, hash_api_token};
+use models::meta_data::ApiToken;
+use snafu::ResultExt;
+use spi::catalog::MetadataError;
+use spi::query::execution;
+use spi::query::execution::{ArrowSnafu, ExecutionError, Output, QueryStateMachineRef};
+use spi::query::logical_planner::CreateToken;
+use std::sync::Arc;
+
+/// The token is returned only once, the hash of it is stored
+pub struct CreateTokenTask {
+    stmt: CreateToken,
+}
+
+impl CreateTokenTask {
+    pub fn new(stmt: CreateToken) -> Self {
+        Self { stmt }
+    }
+}
+
+#[async_trait]
+impl DDLDefinitionTask for CreateTokenTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> Result<Output, ExecutionError> {
+        let CreateToken { ref name, ref user } = self.stmt;
+
+        let mut user = token_owner(&query_state_machine, user)?;
+        if user.token(name).is_some() {
+            return Err(MetadataError::TokenAlreadyExists {
+                user_name: user.name,
+                token_name: name.clone(),
+            })
+            .context(execution::MetadataSnafu);
+        }
+        let token = generate_api_token();
+        user.tokens.push(ApiToken {
+            name: name.clone(),
+            token_hash: hash_api_token(&token),
+            created_at: chrono::Utc::now().timestamp_millis(),
+        });
+        query_state_machine
+            .catalog
+            .alter_user(user)
+            .await
+            .context(execution::MetadataSnafu)?;
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("Name", DataType::Utf8, false),
+            Field::new("Token", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec![name.as_str()])),
+                Arc::new(StringArray::from(vec![token.as_str()])),
+            ],
+        )
+        .context(ArrowSnafu)?;
+
+        Ok(Output::StreamData(vec![batch]))
+    }
+}
@@ -0,0 +1,52 @@
+use crate::execution::ddl::{check_admin, DDLDefinitionTask};
+use async_trait::async_trait;
+use models::auth::hash_password;
+use models::meta_data::UserInfo;
+use snafu::ResultExt;
+use spi::catalog::MetadataError;
+use spi::query::execution;
+use spi::query::execution::{ExecutionError, Output, QueryStateMachineRef};
+use spi::query::logical_planner::CreateUser;
+
+pub struct CreateUserTask {
+    stmt: CreateUser,
+}
+
+impl CreateUserTask {
+    pub fn new(stmt: CreateUser) -> Self {
+        Self { stmt }
+    }
+}
+
+#[async_trait]
+impl DDLDefinitionTask for CreateUserTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> Result<Output, ExecutionError> {
+        let CreateUser {
+            ref name,
+            ref if_not_exists,
+            ref password,
+            ref is_admin,
+        } = self.stmt;
+
+        check_admin(&query_state_machine)?;
+        let user = UserInfo {
+            name: name.clone(),
+            is_admin: *is_admin,
+            password_hash: hash_password(password),
+            tokens: vec![],
+            roles: vec![],
+            provider: None,
+        };
+
+        match query_state_machine.catalog.create_user(user).await {
+            // do not create if exists
+            Err(MetadataError::UserAlreadyExists { .. }) if *if_not_exists => Ok(Output::Nil(())),
+            res => res
+                .map(|_| Output::Nil(()))
+                .context(execution::MetadataSnafu),
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use crate::execution::ddl::export_database::{external, local_coordinator};
+use crate::execution::ddl::{check_admin, DDLDefinitionTask};
+use async_trait::async_trait;
+use datafusion::arrow::array::UInt64Array;
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::arrow::record_batch::RecordBatch;
+use snafu::ResultExt;
+use spi::query::execution::{ArrowSnafu, MetadataSnafu};
+use spi::query::execution::{ExecutionError, Output, QueryStateMachineRef};
+use spi::query::logical_planner::DeleteBefore;
+use std::sync::Arc;
+use trace::info;
+
+pub struct DeleteBeforeTask {
+    stmt: DeleteBefore,
+}
+
+impl DeleteBeforeTask {
+    pub fn new(stmt: DeleteBefore) -> Self {
+        Self { stmt }
+    }
+}
+
+#[async_trait]
+impl DDLDefinitionTask for DeleteBeforeTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> Result<Output, ExecutionError> {
+        check_admin(&query_state_machine)?;
+
+        let DeleteBefore {
+            ref database_name,
+            before,
+        } = self.stmt;
+        let catalog = &query_state_machine.catalog;
+        catalog.database(database_name).context(MetadataSnafu)?;
+
+        // The files all the data of which is before the timestamp are removed, the
+        // points before it in the other files and in the caches are tombstoned
+        let deleted = local_coordinator(catalog)?
+            .engine()
+            .delete_before(database_name, before)
+            .await
+            .map_err(external)?;
+        info!(
+            "Deleted the data of database {} before {}, {} files removed, {} series tombstoned",
+            database_name, before, deleted.files, deleted.series
+        );
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("Files", DataType::UInt64, false),
+            Field::new("Series", DataType::UInt64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(UInt64Array::from(vec![deleted.files as u64])),
+                Arc::new(UInt64Array::from(vec![deleted.series as u64])),
+            ],
+        )
+        .context(ArrowSnafu)?;
+
+        Ok(Output::StreamData(vec![batch]))
+    }
+}
@@ -4,6 +4,7 @@ use datafusion::arrow::array::StringArray;
 use datafusion::arrow::datatypes::{DataType, Field, Schema};
 use datafusion::arrow::record_batch::RecordBatch;
 use datafusion::error::DataFusionError;
+use models::schema::Duration;
 use snafu::ResultExt;
 use spi::catalog::MetaDataRef;
 use spi::query::execution;
@@ -44,6 +45,11 @@ fn describe_database(database_name: &str, catalog: MetaDataRef) -> Result<Output
         Field::new("VNODE_DURATION", DataType::Utf8, false),
         Field::new("REPLICA", DataType::Utf8, false),
         Field::new("PRECISION", DataType::Utf8, false),
+        Field::new("AUTO_CREATE_SCHEMA", DataType::Utf8, false),
+        Field::new("FIELD_TYPE_CONFLICT", DataType::Utf8, false),
+        Field::new("FUTURE_TOLERANCE", DataType::Utf8, false),
+        Field::new("PAST_TOLERANCE", DataType::Utf8, false),
+        Field::new("OUT_OF_WINDOW", DataType::Utf8, false),
     ]));
 
     let ttl = db_cfg.config.ttl_or_default().to_string();
@@ -51,6 +57,12 @@ fn describe_database(database_name: &str, catalog: MetaDataRef) -> Result<Output
     let vnode_duration = db_cfg.config.vnode_duration_or_default().to_string();
     let replica = db_cfg.config.replica_or_default().to_string();
     let precision = db_cfg.config.precision_or_default().to_string();
+    let auto_create_schema = db_cfg.config.auto_create_schema_or_default().to_string();
+    let field_type_conflict = db_cfg.config.field_type_conflict_or_default().to_string();
+    let tolerance = |e: &Option<Duration>| e.as_ref().map_or("NONE".to_string(), |e| e.to_string());
+    let future_tolerance = tolerance(db_cfg.config.future_tolerance());
+    let past_tolerance = tolerance(db_cfg.config.past_tolerance());
+    let out_of_window = db_cfg.config.out_of_window_or_default().to_string();
 
     let batch = RecordBatch::try_new(
         schema,
@@ -60,6 +72,11 @@ fn describe_database(database_name: &str, catalog: MetaDataRef) -> Result<Output
             Arc::new(StringArray::from(vec![vnode_duration.as_str()])),
             Arc::new(StringArray::from(vec![replica.as_str()])),
             Arc::new(StringArray::from(vec![precision.as_str()])),
+            Arc::new(StringArray::from(vec![auto_create_schema.as_str()])),
+            Arc::new(StringArray::from(vec![field_type_conflict.as_str()])),
+            Arc::new(StringArray::from(vec![future_tolerance.as_str()])),
+            Arc::new(StringArray::from(vec![past_tolerance.as_str()])),
+            Arc::new(StringArray::from(vec![out_of_window.as_str()])),
         ],
     )
     .map_err(|e| ExecutionError::External {
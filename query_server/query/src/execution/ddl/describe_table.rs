@@ -4,6 +4,7 @@ use datafusion::arrow::array::StringBuilder;
 use datafusion::arrow::datatypes::{DataType, Field, Schema};
 use datafusion::arrow::record_batch::RecordBatch;
 use datafusion::catalog::TableReference;
+use datafusion::datasource::TableProvider;
 use models::schema::TableSchema;
 use snafu::ResultExt;
 use spi::catalog::MetaDataRef;
@@ -75,9 +76,14 @@ fn describe_table(table_name: &str, catalog: MetaDataRef) -> Result<Output, Exec
             Ok(Output::StreamData(batches))
         }
         TableSchema::ExternalTableSchema(external_schema) => {
+            // Includes the partition columns
+            let table_schema = external_schema
+                .table_provider()
+                .context(ExternalSnafu)?
+                .schema();
             let mut name = StringBuilder::new();
             let mut data_type = StringBuilder::new();
-            external_schema.schema.fields.iter().for_each(|field| {
+            table_schema.fields().iter().for_each(|field| {
                 name.append_value(field.name());
                 data_type.append_value(field.data_type().to_string());
             });
@@ -36,8 +36,9 @@ impl DDLDefinitionTask for DropObjectTask {
         } = self.stmt;
 
         let res = match obj_type {
-            ObjectType::Table => query_state_machine.catalog.drop_table(object_name),
-            ObjectType::Database => query_state_machine.catalog.drop_database(object_name),
+            ObjectType::Table => query_state_machine.catalog.drop_table(object_name).await,
+            ObjectType::Database => query_state_machine.catalog.drop_database(object_name).await,
+            ObjectType::StreamSource => query_state_machine.catalog.drop_stream_source(object_name),
         };
 
         if *if_exist {
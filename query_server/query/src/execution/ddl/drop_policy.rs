@@ -0,0 +1,64 @@
+use crate::execution::ddl::{check_admin, DDLDefinitionTask};
+use async_trait::async_trait;
+use datafusion::sql::TableReference;
+use snafu::ResultExt;
+use spi::catalog::MetadataError;
+use spi::query::execution;
+use spi::query::execution::{ExecutionError, Output, QueryStateMachineRef};
+use spi::query::logical_planner::DropPolicy;
+
+pub struct DropPolicyTask {
+    stmt: DropPolicy,
+}
+
+impl DropPolicyTask {
+    pub fn new(stmt: DropPolicy) -> Self {
+        Self { stmt }
+    }
+}
+
+#[async_trait]
+impl DDLDefinitionTask for DropPolicyTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> Result<Output, ExecutionError> {
+        let DropPolicy {
+            ref name,
+            ref table,
+            ref if_exists,
+        } = self.stmt;
+
+        check_admin(&query_state_machine)?;
+
+        let catalog = &query_state_machine.catalog;
+        let table_ref = TableReference::from(table.as_str())
+            .resolve(catalog.catalog_name(), catalog.schema_name());
+        let on_table = |policy: &models::meta_data::RowPolicy| {
+            policy.name == *name
+                && policy.is_on(table_ref.catalog, table_ref.schema, table_ref.table)
+        };
+
+        let role = catalog
+            .roles()
+            .context(execution::MetadataSnafu)?
+            .into_iter()
+            .find(|e| e.policies.iter().any(on_table));
+        match role {
+            Some(mut role) => {
+                role.policies.retain(|e| !on_table(e));
+                catalog
+                    .alter_role(role)
+                    .await
+                    .map(|_| Output::Nil(()))
+                    .context(execution::MetadataSnafu)
+            }
+            None if *if_exists => Ok(Output::Nil(())),
+            None => Err(MetadataError::PolicyNotExists {
+                policy_name: name.clone(),
+                table_name: format!("{}.{}", table_ref.schema, table_ref.table),
+            })
+            .context(execution::MetadataSnafu),
+        }
+    }
+}
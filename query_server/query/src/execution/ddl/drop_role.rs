@@ -0,0 +1,40 @@
+use crate::execution::ddl::{check_admin, DDLDefinitionTask};
+use async_trait::async_trait;
+use snafu::ResultExt;
+use spi::catalog::MetadataError;
+use spi::query::execution;
+use spi::query::execution::{ExecutionError, Output, QueryStateMachineRef};
+use spi::query::logical_planner::DropRole;
+
+/// The role is revoked from the users granted it as well
+pub struct DropRoleTask {
+    stmt: DropRole,
+}
+
+impl DropRoleTask {
+    pub fn new(stmt: DropRole) -> Self {
+        Self { stmt }
+    }
+}
+
+#[async_trait]
+impl DDLDefinitionTask for DropRoleTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> Result<Output, ExecutionError> {
+        let DropRole {
+            ref name,
+            ref if_exists,
+        } = self.stmt;
+
+        check_admin(&query_state_machine)?;
+
+        match query_state_machine.catalog.drop_role(name).await {
+            Err(MetadataError::RoleNotExists { .. }) if *if_exists => Ok(Output::Nil(())),
+            res => res
+                .map(|_| Output::Nil(()))
+                .context(execution::MetadataSnafu),
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use crate::execution::ddl::{task_of_user, DDLDefinitionTask};
+use async_trait::async_trait;
+use snafu::ResultExt;
+use spi::catalog::MetadataError;
+use spi::query::execution;
+use spi::query::execution::{ExecutionError, Output, QueryStateMachineRef};
+use spi::query::logical_planner::DropTask;
+
+pub struct DropTaskTask {
+    stmt: DropTask,
+}
+
+impl DropTaskTask {
+    pub fn new(stmt: DropTask) -> Self {
+        Self { stmt }
+    }
+}
+
+#[async_trait]
+impl DDLDefinitionTask for DropTaskTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> Result<Output, ExecutionError> {
+        let DropTask {
+            ref name,
+            ref if_exists,
+        } = self.stmt;
+
+        let res = match task_of_user(&query_state_machine, name) {
+            Ok(_) => query_state_machine
+                .catalog
+                .drop_task(name)
+                .await
+                .context(execution::MetadataSnafu),
+            Err(e) => Err(e),
+        };
+        match res {
+            Err(ExecutionError::Metadata {
+                source: MetadataError::TaskNotExists { .. },
+            }) if *if_exists => Ok(Output::Nil(())),
+            res => res.map(|_| Output::Nil(())),
+        }
+    }
+}
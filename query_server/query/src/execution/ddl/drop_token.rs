@@ -0,0 +1,53 @@
+use crate::execution::ddl::{token_owner, DDLDefinitionTask};
+use async_trait::async_trait;
+use snafu::ResultExt;
+use spi::catalog::MetadataError;
+use spi::query::execution;
+use spi::query::execution::{ExecutionError, Output, QueryStateMachineRef};
+use spi::query::logical_planner::DropToken;
+
+/// The token is revoked, the nodes of a cluster may accept it until their users
+/// are read again from the meta service
+pub struct DropTokenTask {
+    stmt: DropToken,
+}
+
+impl DropTokenTask {
+    pub fn new(stmt: DropToken) -> Self {
+        Self { stmt }
+    }
+}
+
+#[async_trait]
+impl DDLDefinitionTask for DropTokenTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> Result<Output, ExecutionError> {
+        let DropToken {
+            ref name,
+            ref user,
+            ref if_exists,
+        } = self.stmt;
+
+        let mut user = token_owner(&query_state_machine, user)?;
+        if user.token(name).is_none() {
+            if *if_exists {
+                return Ok(Output::Nil(()));
+            }
+            return Err(MetadataError::TokenNotExists {
+                user_name: user.name,
+                token_name: name.clone(),
+            })
+            .context(execution::MetadataSnafu);
+        }
+        user.tokens.retain(|e| e.name != *name);
+
+        query_state_machine
+            .catalog
+            .alter_user(user)
+            .await
+            .map(|_| Output::Nil(()))
+            .context(execution::MetadataSnafu)
+    }
+}
@@ -0,0 +1,45 @@
+use crate::execution::ddl::{check_admin, DDLDefinitionTask};
+use async_trait::async_trait;
+use snafu::ResultExt;
+use spi::catalog::MetadataError;
+use spi::query::execution;
+use spi::query::execution::{ExecutionError, Output, QueryStateMachineRef};
+use spi::query::logical_planner::DropUser;
+
+pub struct DropUserTask {
+    stmt: DropUser,
+}
+
+impl DropUserTask {
+    pub fn new(stmt: DropUser) -> Self {
+        Self { stmt }
+    }
+}
+
+#[async_trait]
+impl DDLDefinitionTask for DropUserTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> Result<Output, ExecutionError> {
+        let DropUser {
+            ref name,
+            ref if_exists,
+        } = self.stmt;
+
+        check_admin(&query_state_machine)?;
+        // The admin would lock itself out
+        if query_state_machine.query.context().user_info().user == *name {
+            return Err(ExecutionError::PermissionDenied {
+                reason: format!("can not drop the current user {}", name),
+            });
+        }
+
+        match query_state_machine.catalog.drop_user(name).await {
+            Err(MetadataError::UserNotExists { .. }) if *if_exists => Ok(Output::Nil(())),
+            res => res
+                .map(|_| Output::Nil(()))
+                .context(execution::MetadataSnafu),
+        }
+    }
+}
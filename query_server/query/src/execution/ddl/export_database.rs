@@ -0,0 +1,282 @@
+use crate::data_source::dump::{
+    write_batch, DumpManifest, DumpTable, DumpWriter, DUMP_VERSION, MANIFEST_FILE,
+};
+use crate::execution::ddl::{check_admin, DDLDefinitionTask};
+use crate::metadata::LocalCatalogMeta;
+use crate::table::ClusterTable;
+use async_trait::async_trait;
+use chrono::Local;
+use coordinator::service::CoordinatorRef;
+use datafusion::arrow::array::{StringArray, UInt64Array};
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::datasource::listing::ListingTableUrl;
+use datafusion::datasource::TableProvider;
+use datafusion::error::DataFusionError;
+use datafusion::execution::context::SessionState;
+use datafusion::logical_expr::{col, lit, Expr};
+use datafusion::physical_plan::execute_stream;
+use datafusion::scalar::ScalarValue;
+use datafusion::sql::TableReference;
+use futures::StreamExt;
+use models::schema::{TableSchema, TIME_FIELD_NAME};
+use object_store::path::Path;
+use object_store::ObjectStore;
+use snafu::ResultExt;
+use spi::catalog::MetaDataRef;
+use spi::query::execution::{
+    ArrowSnafu, ExecutionError, ExternalSnafu, MetadataSnafu, Output, QueryStateMachineRef,
+};
+use spi::query::logical_planner::{DumpFilter, ExportDatabase};
+use std::path::Component;
+use std::sync::Arc;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use trace::info;
+
+pub struct ExportDatabaseTask {
+    stmt: ExportDatabase,
+    dump_dir: String,
+}
+
+impl ExportDatabaseTask {
+    pub fn new(stmt: ExportDatabase, dump_dir: String) -> Self {
+        Self { stmt, dump_dir }
+    }
+}
+
+#[async_trait]
+impl DDLDefinitionTask for ExportDatabaseTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> Result<Output, ExecutionError> {
+        let ExportDatabase {
+            ref database,
+            ref location,
+            ref compression,
+            ref filter,
+        } = self.stmt;
+
+        // All of the rows are exported, regardless of the row policies
+        check_admin(&query_state_machine)?;
+        let catalog = query_state_machine.catalog.clone();
+        catalog.database(database).context(MetadataSnafu)?;
+        let coord = local_coordinator(&catalog)?;
+
+        let session = query_state_machine.session.inner();
+        let state = session.state();
+        let (store, prefix) =
+            resolve_location(location, &self.dump_dir, &state).context(ExternalSnafu)?;
+        let filters = time_filters(filter);
+
+        let mut manifest = DumpManifest {
+            version: DUMP_VERSION,
+            database: database.clone(),
+            compression: compression.to_string(),
+            start_time: filter.start_time,
+            end_time: filter.end_time,
+            created_at: Local::now().timestamp_nanos(),
+            tables: vec![],
+        };
+
+        let tables = catalog
+            .show_tables(&Some(database.clone()))
+            .context(MetadataSnafu)?;
+        for table in tables.into_iter().filter(|e| filter.contains_table(e)) {
+            let schema = match catalog.table(TableReference::Partial {
+                schema: database.as_str(),
+                table: &table,
+            }) {
+                Ok(TableSchema::TsKvTableSchema(schema)) => schema,
+                // External tables are not owned by the database
+                Ok(TableSchema::ExternalTableSchema(_)) => continue,
+                Err(e) => return Err(e).context(MetadataSnafu),
+            };
+
+            let plan = ClusterTable::new(coord.clone(), catalog.catalog_name(), schema.clone())
+                .scan(&state, &None, &filters, None)
+                .await
+                .context(ExternalSnafu)?;
+            let mut stream = execute_stream(plan, session.task_ctx())
+                .await
+                .context(ExternalSnafu)?;
+
+            let file = format!("{}.{}", table, compression.extension());
+            let path = prefix.child(file.as_str());
+            let mut writer = DumpWriter::new(*compression);
+            let mut upload: Option<Box<dyn AsyncWrite + Send + Unpin>> = None;
+            let mut points = 0;
+
+            while let Some(batch) = stream.next().await {
+                let batch = batch.context(ArrowSnafu)?;
+                points += write_batch(&schema, &batch, filter, &mut writer).map_err(external)?;
+                // Tables without points in the time range are not exported
+                if points == 0 {
+                    continue;
+                }
+
+                if upload.is_none() {
+                    let (_, w) = store
+                        .put_multipart(&path)
+                        .await
+                        .map_err(DataFusionError::ObjectStore)
+                        .context(ExternalSnafu)?;
+                    upload = Some(w);
+                }
+                if let Some(upload) = upload.as_mut() {
+                    upload.write_all(&writer.take()).await.map_err(external)?;
+                }
+            }
+
+            let bytes = writer.finish().map_err(external)?;
+            let mut upload = match upload {
+                Some(upload) => upload,
+                None => continue,
+            };
+            upload.write_all(&bytes).await.map_err(external)?;
+            upload.shutdown().await.map_err(external)?;
+
+            info!(
+                "Exported {} points of {}.{} to {}",
+                points, database, table, path
+            );
+            manifest.tables.push(DumpTable {
+                name: table,
+                file,
+                points,
+            });
+        }
+
+        let content = serde_json::to_vec_pretty(&manifest).map_err(external)?;
+        store
+            .put(&prefix.child(MANIFEST_FILE), content.into())
+            .await
+            .map_err(DataFusionError::ObjectStore)
+            .context(ExternalSnafu)?;
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("Table", DataType::Utf8, false),
+            Field::new("File", DataType::Utf8, false),
+            Field::new("Points", DataType::UInt64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from_iter_values(
+                    manifest.tables.iter().map(|e| e.name.as_str()),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    manifest.tables.iter().map(|e| e.file.as_str()),
+                )),
+                Arc::new(UInt64Array::from_iter_values(
+                    manifest.tables.iter().map(|e| e.points),
+                )),
+            ],
+        )
+        .context(ArrowSnafu)?;
+
+        Ok(Output::StreamData(vec![batch]))
+    }
+}
+
+/// The coordinator is needed to scan and write tskv tables directly
+pub(super) fn local_coordinator(catalog: &MetaDataRef) -> Result<CoordinatorRef, ExecutionError> {
+    catalog
+        .as_any()
+        .downcast_ref::<LocalCatalogMeta>()
+        .map(|e| e.coordinator())
+        .ok_or_else(|| DataFusionError::Plan("failed to get meta data".to_string()))
+        .context(ExternalSnafu)
+}
+
+/// Resolves the object store and directory of a dump, a local location is a
+/// directory relative to `dump_dir` which can not be outside of it
+pub(super) fn resolve_location(
+    location: &str,
+    dump_dir: &str,
+    state: &SessionState,
+) -> datafusion::error::Result<(Arc<dyn ObjectStore>, Path)> {
+    let location = match location.strip_prefix("file://") {
+        Some(dir) => local_location(dir, dump_dir)?,
+        None if !location.contains("://") => local_location(location, dump_dir)?,
+        None => location.to_string(),
+    };
+
+    let url = ListingTableUrl::parse(&location)?;
+    let store = state
+        .runtime_env
+        .object_store(url.object_store())
+        .map_err(|e| {
+            DataFusionError::Plan(format!("No object store available for {}: {}", location, e))
+        })?;
+
+    Ok((store, url.prefix().clone()))
+}
+
+fn local_location(dir: &str, dump_dir: &str) -> datafusion::error::Result<String> {
+    let outside = || {
+        DataFusionError::Plan(format!(
+            "Location {} must be a relative directory in the dump directory",
+            dir
+        ))
+    };
+
+    let relative = std::path::Path::new(dir);
+    if relative.is_absolute()
+        || relative
+            .components()
+            .any(|e| !matches!(e, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(outside());
+    }
+
+    // Local directories must exist before they can be resolved into an url
+    let root = std::path::Path::new(dump_dir);
+    let dir = root.join(relative);
+    std::fs::create_dir_all(&dir)?;
+    // Symbolic links can not lead out of the dump directory either
+    let (root, dir) = (root.canonicalize()?, dir.canonicalize()?);
+    if !dir.starts_with(&root) {
+        return Err(outside());
+    }
+
+    Ok(dir.to_string_lossy().to_string())
+}
+
+pub(super) fn external(e: impl std::error::Error + Send + Sync + 'static) -> ExecutionError {
+    ExecutionError::External {
+        source: DataFusionError::External(Box::new(e)),
+    }
+}
+
+fn time_filters(filter: &DumpFilter) -> Vec<Expr> {
+    let time = |t: i64| lit(ScalarValue::TimestampNanosecond(Some(t), None));
+    let mut filters = vec![];
+    if let Some(start_time) = filter.start_time {
+        filters.push(col(TIME_FIELD_NAME).gt_eq(time(start_time)));
+    }
+    if let Some(end_time) = filter.end_time {
+        filters.push(col(TIME_FIELD_NAME).lt(time(end_time)));
+    }
+    filters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_local_location() {
+        let root = tempfile::tempdir().unwrap();
+        let dump_dir = root.path().to_str().unwrap();
+
+        let dir = local_location("db0/2022", dump_dir).unwrap();
+        assert!(std::path::Path::new(&dir).is_dir());
+        assert!(dir.ends_with("db0/2022"));
+        local_location("./db0", dump_dir).unwrap();
+
+        assert!(local_location("/tmp/db0", dump_dir).is_err());
+        assert!(local_location("../db0", dump_dir).is_err());
+        assert!(local_location("db0/../../db0", dump_dir).is_err());
+    }
+}
@@ -0,0 +1,41 @@
+use crate::execution::ddl::export_database::{external, local_coordinator};
+use crate::execution::ddl::DDLDefinitionTask;
+use async_trait::async_trait;
+use snafu::ResultExt;
+use spi::query::execution::MetadataSnafu;
+use spi::query::execution::{ExecutionError, Output, QueryStateMachineRef};
+use spi::query::logical_planner::FlushDatabase;
+use trace::info;
+
+pub struct FlushDatabaseTask {
+    stmt: FlushDatabase,
+}
+
+impl FlushDatabaseTask {
+    pub fn new(stmt: FlushDatabase) -> Self {
+        Self { stmt }
+    }
+}
+
+#[async_trait]
+impl DDLDefinitionTask for FlushDatabaseTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> Result<Output, ExecutionError> {
+        let database_name = self.stmt.database_name.as_str();
+        let catalog = &query_state_machine.catalog;
+        catalog.database(database_name).context(MetadataSnafu)?;
+
+        // Returns once the memcaches are written to tsm files and the version
+        // edits are applied
+        local_coordinator(catalog)?
+            .engine()
+            .flush_database(database_name)
+            .await
+            .map_err(external)?;
+        info!("Flushed database {}", database_name);
+
+        Ok(Output::Nil(()))
+    }
+}
@@ -0,0 +1,52 @@
+use crate::execution::ddl::{check_admin, DDLDefinitionTask};
+use async_trait::async_trait;
+use snafu::ResultExt;
+use spi::catalog::MetadataError;
+use spi::query::execution;
+use spi::query::execution::{ExecutionError, Output, QueryStateMachineRef};
+use spi::query::logical_planner::GrantRoleToGroup;
+
+/// The users of the group are granted the role as they are next authenticated
+/// by the external identity provider
+pub struct GrantRoleToGroupTask {
+    stmt: GrantRoleToGroup,
+}
+
+impl GrantRoleToGroupTask {
+    pub fn new(stmt: GrantRoleToGroup) -> Self {
+        Self { stmt }
+    }
+}
+
+#[async_trait]
+impl DDLDefinitionTask for GrantRoleToGroupTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> Result<Output, ExecutionError> {
+        let GrantRoleToGroup {
+            ref role,
+            ref group,
+        } = self.stmt;
+
+        check_admin(&query_state_machine)?;
+
+        let catalog = &query_state_machine.catalog;
+        let mut role = catalog
+            .role(role)
+            .context(execution::MetadataSnafu)?
+            .ok_or_else(|| MetadataError::RoleNotExists {
+                role_name: role.clone(),
+            })
+            .context(execution::MetadataSnafu)?;
+        if role.groups.contains(group) {
+            return Ok(Output::Nil(()));
+        }
+        role.groups.push(group.clone());
+        catalog
+            .alter_role(role)
+            .await
+            .map(|_| Output::Nil(()))
+            .context(execution::MetadataSnafu)
+    }
+}
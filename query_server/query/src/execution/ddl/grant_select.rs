@@ -0,0 +1,72 @@
+use crate::execution::ddl::{check_admin, DDLDefinitionTask};
+use async_trait::async_trait;
+use models::meta_data::ColumnPrivilege;
+use snafu::ResultExt;
+use spi::catalog::MetadataError;
+use spi::query::execution;
+use spi::query::execution::{ExecutionError, Output, QueryStateMachineRef};
+use spi::query::logical_planner::GrantSelect;
+
+/// The columns are added to those of the table already granted to the role
+pub struct GrantSelectTask {
+    stmt: GrantSelect,
+}
+
+impl GrantSelectTask {
+    pub fn new(stmt: GrantSelect) -> Self {
+        Self { stmt }
+    }
+}
+
+#[async_trait]
+impl DDLDefinitionTask for GrantSelectTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> Result<Output, ExecutionError> {
+        let GrantSelect {
+            ref columns,
+            ref database,
+            ref table,
+            ref role,
+        } = self.stmt;
+
+        check_admin(&query_state_machine)?;
+
+        let catalog = &query_state_machine.catalog;
+        let tenant = catalog.catalog_name();
+        let mut role = catalog
+            .role(role)
+            .context(execution::MetadataSnafu)?
+            .ok_or_else(|| MetadataError::RoleNotExists {
+                role_name: role.clone(),
+            })
+            .context(execution::MetadataSnafu)?;
+
+        let index = role
+            .column_privileges
+            .iter()
+            .position(|e| e.is_on(tenant, database, table))
+            .unwrap_or_else(|| {
+                role.column_privileges.push(ColumnPrivilege {
+                    tenant: tenant.to_string(),
+                    database: database.clone(),
+                    table: table.clone(),
+                    columns: vec![],
+                });
+                role.column_privileges.len() - 1
+            });
+        let privilege = &mut role.column_privileges[index];
+        for column in columns {
+            if !privilege.columns.contains(column) {
+                privilege.columns.push(column.clone());
+            }
+        }
+
+        catalog
+            .alter_role(role)
+            .await
+            .map(|_| Output::Nil(()))
+            .context(execution::MetadataSnafu)
+    }
+}
@@ -0,0 +1,178 @@
+use crate::data_source::dump::{split_lines, DumpManifest, DumpReader, MANIFEST_FILE};
+use crate::execution::ddl::export_database::{external, local_coordinator, resolve_location};
+use crate::execution::ddl::{check_admin, DDLDefinitionTask};
+use async_trait::async_trait;
+use coordinator::service::CoordinatorRef;
+use coordinator::writer::DEFAULT_WRITE_CONSISTENCY;
+use datafusion::arrow::array::{StringArray, UInt64Array};
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::error::DataFusionError;
+use futures::StreamExt;
+use line_protocol::{line_protocol_to_lines, lines_to_points};
+use object_store::path::Path;
+use object_store::ObjectStore;
+use protos::kv_service::WritePointsRpcRequest;
+use snafu::ResultExt;
+use spi::query::execution::{
+    ArrowSnafu, ExecutionError, ExternalSnafu, MetadataSnafu, Output, QueryStateMachineRef,
+};
+use spi::query::logical_planner::{DumpFilter, ImportDatabase};
+use std::sync::Arc;
+use trace::info;
+
+/// Number of lines written into tskv at a time
+const WRITE_BATCH_LINES: usize = 10_000;
+
+pub struct ImportDatabaseTask {
+    stmt: ImportDatabase,
+    dump_dir: String,
+}
+
+impl ImportDatabaseTask {
+    pub fn new(stmt: ImportDatabase, dump_dir: String) -> Self {
+        Self { stmt, dump_dir }
+    }
+}
+
+#[async_trait]
+impl DDLDefinitionTask for ImportDatabaseTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> Result<Output, ExecutionError> {
+        let ImportDatabase {
+            ref database,
+            ref location,
+            ref filter,
+            ref table_mapping,
+        } = self.stmt;
+
+        check_admin(&query_state_machine)?;
+        let catalog = query_state_machine.catalog.clone();
+        catalog.database(database).context(MetadataSnafu)?;
+        let coord = local_coordinator(&catalog)?;
+
+        let state = query_state_machine.session.inner().state();
+        let (store, prefix) =
+            resolve_location(location, &self.dump_dir, &state).context(ExternalSnafu)?;
+
+        let content = read(&store, &prefix.child(MANIFEST_FILE)).await?;
+        let manifest: DumpManifest = serde_json::from_slice(&content).map_err(external)?;
+        let compression = manifest.compression().map_err(external)?;
+
+        let mut imported = vec![];
+        for table in manifest
+            .tables
+            .iter()
+            .filter(|e| filter.contains_table(&e.name))
+        {
+            let target = table_mapping
+                .get(&table.name)
+                .map(|e| e.as_str())
+                .unwrap_or(&table.name);
+            let writer = TableWriter {
+                coord: &coord,
+                tenant: catalog.catalog_name(),
+                database,
+                table: target,
+                filter,
+            };
+
+            // The file is read and written into tskv chunk by chunk
+            let mut reader = DumpReader::new(compression);
+            let mut stream = store
+                .get(&prefix.child(table.file.as_str()))
+                .await
+                .map_err(DataFusionError::ObjectStore)
+                .context(ExternalSnafu)?
+                .into_stream();
+            let mut points = 0;
+            while let Some(data) = stream.next().await {
+                let data = data
+                    .map_err(DataFusionError::ObjectStore)
+                    .context(ExternalSnafu)?;
+                let content = reader.push(&data).map_err(external)?;
+                points += writer.write(&content).await?;
+            }
+            let content = reader.finish().map_err(external)?;
+            points += writer.write(&content).await?;
+
+            info!(
+                "Imported {} points of {}.{} into {}.{}",
+                points, manifest.database, table.name, database, target
+            );
+            imported.push((target, points));
+        }
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("Table", DataType::Utf8, false),
+            Field::new("Points", DataType::UInt64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from_iter_values(
+                    imported.iter().map(|(table, _)| *table),
+                )),
+                Arc::new(UInt64Array::from_iter_values(
+                    imported.iter().map(|(_, points)| *points),
+                )),
+            ],
+        )
+        .context(ArrowSnafu)?;
+
+        Ok(Output::StreamData(vec![batch]))
+    }
+}
+
+/// Writes the lines of a dump file into a table
+struct TableWriter<'a> {
+    coord: &'a CoordinatorRef,
+    tenant: &'a str,
+    database: &'a str,
+    table: &'a str,
+    filter: &'a DumpFilter,
+}
+
+impl TableWriter<'_> {
+    /// Returns the number of points written
+    async fn write(&self, content: &str) -> Result<u64, ExecutionError> {
+        let mut points = 0;
+        for chunk in split_lines(content, WRITE_BATCH_LINES) {
+            let mut lines = line_protocol_to_lines(chunk, 0).map_err(external)?;
+            lines.retain(|e| self.filter.contains_time(e.timestamp));
+            if lines.is_empty() {
+                continue;
+            }
+            for line in lines.iter_mut() {
+                line.measurement = self.table;
+            }
+
+            let req = WritePointsRpcRequest {
+                version: 1,
+                points: lines_to_points(self.database, &lines),
+            };
+            self.coord
+                .write_points(self.tenant, DEFAULT_WRITE_CONSISTENCY, req)
+                .await
+                .map_err(external)?;
+            points += lines.len() as u64;
+        }
+        Ok(points)
+    }
+}
+
+async fn read(store: &Arc<dyn ObjectStore>, path: &Path) -> Result<Vec<u8>, ExecutionError> {
+    let result = store
+        .get(path)
+        .await
+        .map_err(DataFusionError::ObjectStore)
+        .context(ExternalSnafu)?;
+    let bytes = result
+        .bytes()
+        .await
+        .map_err(DataFusionError::ObjectStore)
+        .context(ExternalSnafu)?;
+    Ok(bytes.to_vec())
+}
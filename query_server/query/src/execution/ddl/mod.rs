@@ -1,20 +1,59 @@
+use std::collections::BTreeMap;
+
 use async_trait::async_trait;
+use config::SettingsRef;
 
 use spi::query::dispatcher::{QueryInfo, QueryStatus};
-use spi::query::execution::{Output, QueryExecution, QueryStateMachineRef};
+use spi::query::execution::{self, Output, QueryExecution, QueryStateMachineRef};
 use spi::query::logical_planner::DDLPlan;
 use spi::query::{self, QueryError};
 
+use crate::audit::{AuditEvent, AuditKind, AuditLogRef};
+
+use spi::catalog::MetadataError;
 use spi::query::execution::ExecutionError;
 
 use self::create_table::CreateTableTask;
 use crate::execution::ddl::alter_database::AlterDatabaseTask;
 use crate::execution::ddl::alter_table::AlterTableTask;
+use crate::execution::ddl::alter_task::AlterTaskTask;
+use crate::execution::ddl::alter_user::AlterUserTask;
+use crate::execution::ddl::analyze_table::AnalyzeTableTask;
 use crate::execution::ddl::create_database::CreateDatabaseTask;
+use crate::execution::ddl::create_policy::CreatePolicyTask;
+use crate::execution::ddl::create_role::CreateRoleTask;
+use crate::execution::ddl::create_stream_source::CreateStreamSourceTask;
+use crate::execution::ddl::create_task::CreateTaskTask;
+use crate::execution::ddl::create_token::CreateTokenTask;
+use crate::execution::ddl::create_user::CreateUserTask;
+use crate::execution::ddl::delete_before::DeleteBeforeTask;
 use crate::execution::ddl::describe_database::DescribeDatabaseTask;
 use crate::execution::ddl::describe_table::DescribeTableTask;
+use crate::execution::ddl::drop_policy::DropPolicyTask;
+use crate::execution::ddl::drop_role::DropRoleTask;
+use crate::execution::ddl::drop_task::DropTaskTask;
+use crate::execution::ddl::drop_token::DropTokenTask;
+use crate::execution::ddl::drop_user::DropUserTask;
+use crate::execution::ddl::export_database::ExportDatabaseTask;
+use crate::execution::ddl::flush_database::FlushDatabaseTask;
+use crate::execution::ddl::grant_group::GrantRoleToGroupTask;
+use crate::execution::ddl::grant_role::GrantRoleTask;
+use crate::execution::ddl::grant_select::GrantSelectTask;
+use crate::execution::ddl::import_database::ImportDatabaseTask;
+use crate::execution::ddl::revoke_group::RevokeRoleFromGroupTask;
+use crate::execution::ddl::revoke_role::RevokeRoleTask;
+use crate::execution::ddl::revoke_select::RevokeSelectTask;
+use crate::execution::ddl::show_create_table::ShowCreateTableTask;
 use crate::execution::ddl::show_database::ShowDatabasesTask;
+use crate::execution::ddl::show_grants::ShowGrantsTask;
+use crate::execution::ddl::show_policies::ShowPoliciesTask;
+use crate::execution::ddl::show_roles::ShowRolesTask;
+use crate::execution::ddl::show_shards::ShowShardsTask;
+use crate::execution::ddl::show_stream_sources::ShowStreamSourcesTask;
 use crate::execution::ddl::show_table::ShowTablesTask;
+use crate::execution::ddl::show_tasks::ShowTasksTask;
+use crate::execution::ddl::show_tokens::ShowTokensTask;
+use crate::execution::ddl::show_users::ShowUsersTask;
 use snafu::ResultExt;
 
 use self::create_external_table::CreateExternalTableTask;
@@ -22,14 +61,47 @@ use self::drop_object::DropObjectTask;
 
 mod alter_database;
 mod alter_table;
+mod alter_task;
+mod alter_user;
+mod analyze_table;
 mod create_database;
 mod create_external_table;
+mod create_policy;
+mod create_role;
+mod create_stream_source;
 mod create_table;
+mod create_task;
+mod create_token;
+mod create_user;
+mod delete_before;
 mod describe_database;
 mod describe_table;
 mod drop_object;
+mod drop_policy;
+mod drop_role;
+mod drop_task;
+mod drop_token;
+mod drop_user;
+mod export_database;
+mod flush_database;
+mod grant_group;
+mod grant_role;
+mod grant_select;
+mod import_database;
+mod revoke_group;
+mod revoke_role;
+mod revoke_select;
+mod show_create_table;
 mod show_database;
+mod show_grants;
+mod show_policies;
+mod show_roles;
+mod show_shards;
+mod show_stream_sources;
 mod show_table;
+mod show_tasks;
+mod show_tokens;
+mod show_users;
 
 /// Traits that DDL tasks should implement
 #[async_trait]
@@ -43,15 +115,84 @@ trait DDLDefinitionTask: Send + Sync {
 pub struct DDLExecution {
     task_factory: DDLDefinitionTaskFactory,
     query_state_machine: QueryStateMachineRef,
+    audit_log: AuditLogRef,
 }
 
 impl DDLExecution {
-    pub fn new(query_state_machine: QueryStateMachineRef, plan: DDLPlan) -> Self {
+    pub fn new(
+        query_state_machine: QueryStateMachineRef,
+        plan: DDLPlan,
+        audit_log: AuditLogRef,
+        settings: SettingsRef,
+    ) -> Self {
         Self {
-            task_factory: DDLDefinitionTaskFactory { plan },
+            task_factory: DDLDefinitionTaskFactory { plan, settings },
             query_state_machine,
+            audit_log,
         }
     }
+
+    /// Records the statements changing the definitions, not those only reading them
+    fn audit(&self, result: &Result<Output, QueryError>) {
+        if matches!(
+            self.task_factory.plan,
+            DDLPlan::DescribeTable(_)
+                | DDLPlan::DescribeDatabase(_)
+                | DDLPlan::ShowTables(_)
+                | DDLPlan::ShowCreateTable(_)
+                | DDLPlan::ShowDatabases()
+                | DDLPlan::ShowStreamSources
+                | DDLPlan::ShowUsers
+                | DDLPlan::ShowTokens
+                | DDLPlan::ShowRoles
+                | DDLPlan::ShowPolicies
+                | DDLPlan::ShowGrants(_)
+                | DDLPlan::ShowTasks
+        ) {
+            return;
+        }
+
+        let qsm = &self.query_state_machine;
+        // The passwords and the credentials of the stream sources are never recorded
+        let detail = match &self.task_factory.plan {
+            DDLPlan::CreateUser(plan) => format!("CREATE USER {}", plan.name),
+            DDLPlan::AlterUser(plan) => format!("ALTER USER {}", plan.name),
+            DDLPlan::CreateStreamSource(plan) => format!(
+                "CREATE STREAM SOURCE {} FROM {} WITH ({})",
+                plan.name,
+                plan.connector,
+                redacted_options(&plan.options)
+            ),
+            _ => qsm.query.content().to_string(),
+        };
+        let event = AuditEvent::new(
+            AuditKind::Ddl,
+            qsm.query.context().user_info().user.as_str(),
+            detail,
+        );
+        self.audit_log.record(match result {
+            Ok(_) => event,
+            Err(e) => event.with_error(e),
+        });
+    }
+}
+
+/// The options of a stream source, the values of the secrets are masked
+fn redacted_options(options: &BTreeMap<String, String>) -> String {
+    options
+        .iter()
+        .map(|(key, value)| {
+            let name = key.to_lowercase();
+            let secret = ["password", "secret", "token", "credential"]
+                .iter()
+                .any(|e| name.contains(e));
+            match secret {
+                true => format!("{} = '***'", key),
+                false => format!("{} = '{}'", key, value),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
 }
 
 #[async_trait]
@@ -71,6 +212,7 @@ impl QueryExecution for DDLExecution {
             .context(query::ExecutionSnafu);
 
         query_state_machine.end_schedule();
+        self.audit(&result);
 
         result
     }
@@ -99,6 +241,7 @@ impl QueryExecution for DDLExecution {
 
 struct DDLDefinitionTaskFactory {
     plan: DDLPlan,
+    settings: SettingsRef,
 }
 
 impl DDLDefinitionTaskFactory {
@@ -119,9 +262,186 @@ impl DDLDefinitionTaskFactory {
             }
             DDLPlan::DescribeTable(sub_plan) => Box::new(DescribeTableTask::new(sub_plan.clone())),
             DDLPlan::ShowTables(sub_plan) => Box::new(ShowTablesTask::new(sub_plan.clone())),
+            DDLPlan::ShowShards(sub_plan) => Box::new(ShowShardsTask::new(sub_plan.clone())),
+            DDLPlan::ShowGrants(sub_plan) => Box::new(ShowGrantsTask::new(sub_plan.clone())),
+            DDLPlan::ShowCreateTable(sub_plan) => {
+                Box::new(ShowCreateTableTask::new(sub_plan.clone()))
+            }
             DDLPlan::ShowDatabases() => Box::new(ShowDatabasesTask::new()),
             DDLPlan::AlterDatabase(sub_plan) => Box::new(AlterDatabaseTask::new(sub_plan.clone())),
             DDLPlan::AlterTable(sub_plan) => Box::new(AlterTableTask::new(sub_plan.clone())),
+            DDLPlan::AnalyzeTable(sub_plan) => Box::new(AnalyzeTableTask::new(sub_plan.clone())),
+            DDLPlan::FlushDatabase(sub_plan) => Box::new(FlushDatabaseTask::new(sub_plan.clone())),
+            DDLPlan::DeleteBefore(sub_plan) => Box::new(DeleteBeforeTask::new(sub_plan.clone())),
+            DDLPlan::CreateStreamSource(sub_plan) => {
+                Box::new(CreateStreamSourceTask::new(sub_plan.clone()))
+            }
+            DDLPlan::ShowStreamSources => Box::new(ShowStreamSourcesTask::new()),
+            DDLPlan::CreateUser(sub_plan) => Box::new(CreateUserTask::new(sub_plan.clone())),
+            DDLPlan::AlterUser(sub_plan) => Box::new(AlterUserTask::new(sub_plan.clone())),
+            DDLPlan::DropUser(sub_plan) => Box::new(DropUserTask::new(sub_plan.clone())),
+            DDLPlan::ShowUsers => Box::new(ShowUsersTask::new()),
+            DDLPlan::CreateToken(sub_plan) => Box::new(CreateTokenTask::new(sub_plan.clone())),
+            DDLPlan::DropToken(sub_plan) => Box::new(DropTokenTask::new(sub_plan.clone())),
+            DDLPlan::ShowTokens => Box::new(ShowTokensTask::new()),
+            DDLPlan::CreateRole(sub_plan) => Box::new(CreateRoleTask::new(sub_plan.clone())),
+            DDLPlan::DropRole(sub_plan) => Box::new(DropRoleTask::new(sub_plan.clone())),
+            DDLPlan::GrantRole(sub_plan) => Box::new(GrantRoleTask::new(sub_plan.clone())),
+            DDLPlan::RevokeRole(sub_plan) => Box::new(RevokeRoleTask::new(sub_plan.clone())),
+            DDLPlan::GrantRoleToGroup(sub_plan) => {
+                Box::new(GrantRoleToGroupTask::new(sub_plan.clone()))
+            }
+            DDLPlan::RevokeRoleFromGroup(sub_plan) => {
+                Box::new(RevokeRoleFromGroupTask::new(sub_plan.clone()))
+            }
+            DDLPlan::GrantSelect(sub_plan) => Box::new(GrantSelectTask::new(sub_plan.clone())),
+            DDLPlan::RevokeSelect(sub_plan) => Box::new(RevokeSelectTask::new(sub_plan.clone())),
+            DDLPlan::CreatePolicy(sub_plan) => Box::new(CreatePolicyTask::new(sub_plan.clone())),
+            DDLPlan::DropPolicy(sub_plan) => Box::new(DropPolicyTask::new(sub_plan.clone())),
+            DDLPlan::ShowRoles => Box::new(ShowRolesTask::new()),
+            DDLPlan::ShowPolicies => Box::new(ShowPoliciesTask::new()),
+            DDLPlan::CreateTask(sub_plan) => Box::new(CreateTaskTask::new(sub_plan.clone())),
+            DDLPlan::AlterTask(sub_plan) => Box::new(AlterTaskTask::new(sub_plan.clone())),
+            DDLPlan::DropTask(sub_plan) => Box::new(DropTaskTask::new(sub_plan.clone())),
+            DDLPlan::ShowTasks => Box::new(ShowTasksTask::new()),
+            DDLPlan::ExportDatabase(sub_plan) => Box::new(ExportDatabaseTask::new(
+                sub_plan.clone(),
+                self.settings.config().query.dump_dir.clone(),
+            )),
+            DDLPlan::ImportDatabase(sub_plan) => Box::new(ImportDatabaseTask::new(
+                sub_plan.clone(),
+                self.settings.config().query.dump_dir.clone(),
+            )),
         }
     }
 }
+
+/// Only the admins manage the users
+fn check_admin(query_state_machine: &QueryStateMachineRef) -> Result<(), ExecutionError> {
+    let name = &query_state_machine.query.context().user_info().user;
+    let user = query_state_machine
+        .catalog
+        .user(name)
+        .context(execution::MetadataSnafu)?;
+    match user {
+        Some(user) if user.is_admin => Ok(()),
+        _ => Err(ExecutionError::PermissionDenied {
+            reason: format!("user {} is not an admin", name),
+        }),
+    }
+}
+
+/// The statements reading a table outside of the queries, e.g. `ANALYZE TABLE`, bypass
+/// the row policies and the column privileges, only the admins run them on the tables
+/// restricted by any of them
+fn check_table_unrestricted(
+    query_state_machine: &QueryStateMachineRef,
+    db: &str,
+    table: &str,
+) -> Result<(), ExecutionError> {
+    let catalog = &query_state_machine.catalog;
+    let tenant = catalog.catalog_name();
+    let restricted = catalog
+        .roles()
+        .context(execution::MetadataSnafu)?
+        .iter()
+        .any(|role| {
+            role.policies.iter().any(|e| e.is_on(tenant, db, table))
+                || role
+                    .column_privileges
+                    .iter()
+                    .any(|e| e.is_on(tenant, db, table))
+        });
+    if restricted {
+        check_admin(query_state_machine)?;
+    }
+    Ok(())
+}
+
+/// The task of the tenant, only the owner of it and the admins change it
+fn task_of_user(
+    query_state_machine: &QueryStateMachineRef,
+    name: &str,
+) -> Result<models::task::TaskDefinition, ExecutionError> {
+    let task = query_state_machine
+        .catalog
+        .tasks()
+        .context(execution::MetadataSnafu)?
+        .into_iter()
+        .find(|e| e.name == name)
+        .ok_or_else(|| ExecutionError::Metadata {
+            source: MetadataError::TaskNotExists {
+                task_name: name.to_string(),
+            },
+        })?;
+    if task.owner != query_state_machine.query.context().user_info().user {
+        check_admin(query_state_machine)?;
+    }
+    Ok(task)
+}
+
+/// The user owning the tokens of the statement, the current one if not specified,
+/// only the admins manage the tokens of the other users
+fn token_owner(
+    query_state_machine: &QueryStateMachineRef,
+    user: &Option<String>,
+) -> Result<models::meta_data::UserInfo, ExecutionError> {
+    let current = &query_state_machine.query.context().user_info().user;
+    let name = match user {
+        Some(name) if name != current => {
+            check_admin(query_state_machine)?;
+            name
+        }
+        _ => current,
+    };
+    query_state_machine
+        .catalog
+        .user(name)
+        .context(execution::MetadataSnafu)?
+        .ok_or_else(|| MetadataError::UserNotExists {
+            user_name: name.clone(),
+        })
+        .context(execution::MetadataSnafu)
+}
+
+/// The user the role is granted to or revoked from, both of them must exist
+fn role_and_user(
+    query_state_machine: &QueryStateMachineRef,
+    role: &str,
+    user: &str,
+) -> Result<models::meta_data::UserInfo, ExecutionError> {
+    let catalog = &query_state_machine.catalog;
+    catalog
+        .role(role)
+        .context(execution::MetadataSnafu)?
+        .ok_or_else(|| MetadataError::RoleNotExists {
+            role_name: role.to_string(),
+        })
+        .context(execution::MetadataSnafu)?;
+    catalog
+        .user(user)
+        .context(execution::MetadataSnafu)?
+        .ok_or_else(|| MetadataError::UserNotExists {
+            user_name: user.to_string(),
+        })
+        .context(execution::MetadataSnafu)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_redacted_options() {
+        let options = BTreeMap::from([
+            ("host".to_string(), "broker".to_string()),
+            ("password".to_string(), "secret0".to_string()),
+            ("sasl.Password".to_string(), "secret1".to_string()),
+            ("token".to_string(), "secret2".to_string()),
+        ]);
+        assert_eq!(
+            redacted_options(&options),
+            "host = 'broker', password = '***', sasl.Password = '***', token = '***'"
+        );
+    }
+}
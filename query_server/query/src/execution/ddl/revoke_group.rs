@@ -0,0 +1,51 @@
+use crate::execution::ddl::{check_admin, DDLDefinitionTask};
+use async_trait::async_trait;
+use snafu::ResultExt;
+use spi::catalog::MetadataError;
+use spi::query::execution;
+use spi::query::execution::{ExecutionError, Output, QueryStateMachineRef};
+use spi::query::logical_planner::RevokeRoleFromGroup;
+
+/// The role is revoked from the users of the group as they are next authenticated
+pub struct RevokeRoleFromGroupTask {
+    stmt: RevokeRoleFromGroup,
+}
+
+impl RevokeRoleFromGroupTask {
+    pub fn new(stmt: RevokeRoleFromGroup) -> Self {
+        Self { stmt }
+    }
+}
+
+#[async_trait]
+impl DDLDefinitionTask for RevokeRoleFromGroupTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> Result<Output, ExecutionError> {
+        let RevokeRoleFromGroup {
+            ref role,
+            ref group,
+        } = self.stmt;
+
+        check_admin(&query_state_machine)?;
+
+        let catalog = &query_state_machine.catalog;
+        let mut role = catalog
+            .role(role)
+            .context(execution::MetadataSnafu)?
+            .ok_or_else(|| MetadataError::RoleNotExists {
+                role_name: role.clone(),
+            })
+            .context(execution::MetadataSnafu)?;
+        if !role.groups.contains(group) {
+            return Ok(Output::Nil(()));
+        }
+        role.groups.retain(|e| e != group);
+        catalog
+            .alter_role(role)
+            .await
+            .map(|_| Output::Nil(()))
+            .context(execution::MetadataSnafu)
+    }
+}
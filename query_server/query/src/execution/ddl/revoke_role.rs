@@ -0,0 +1,40 @@
+use crate::execution::ddl::{check_admin, role_and_user, DDLDefinitionTask};
+use async_trait::async_trait;
+use snafu::ResultExt;
+use spi::query::execution;
+use spi::query::execution::{ExecutionError, Output, QueryStateMachineRef};
+use spi::query::logical_planner::RevokeRole;
+
+pub struct RevokeRoleTask {
+    stmt: RevokeRole,
+}
+
+impl RevokeRoleTask {
+    pub fn new(stmt: RevokeRole) -> Self {
+        Self { stmt }
+    }
+}
+
+#[async_trait]
+impl DDLDefinitionTask for RevokeRoleTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> Result<Output, ExecutionError> {
+        let RevokeRole { ref role, ref user } = self.stmt;
+
+        check_admin(&query_state_machine)?;
+
+        let mut user = role_and_user(&query_state_machine, role, user)?;
+        if !user.roles.contains(role) {
+            return Ok(Output::Nil(()));
+        }
+        user.roles.retain(|e| e != role);
+        query_state_machine
+            .catalog
+            .alter_user(user)
+            .await
+            .map(|_| Output::Nil(()))
+            .context(execution::MetadataSnafu)
+    }
+}
@@ -0,0 +1,62 @@
+use crate::execution::ddl::{check_admin, DDLDefinitionTask};
+use async_trait::async_trait;
+use datafusion::sql::TableReference;
+use snafu::ResultExt;
+use spi::catalog::MetadataError;
+use spi::query::execution;
+use spi::query::execution::{ExecutionError, Output, QueryStateMachineRef};
+use spi::query::logical_planner::RevokeSelect;
+
+/// Revoking some of the columns leaves the others readable, even none of them.
+/// Revoking all of the columns lifts the restriction on the columns of the table.
+pub struct RevokeSelectTask {
+    stmt: RevokeSelect,
+}
+
+impl RevokeSelectTask {
+    pub fn new(stmt: RevokeSelect) -> Self {
+        Self { stmt }
+    }
+}
+
+#[async_trait]
+impl DDLDefinitionTask for RevokeSelectTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> Result<Output, ExecutionError> {
+        let RevokeSelect {
+            ref columns,
+            ref table,
+            ref role,
+        } = self.stmt;
+
+        check_admin(&query_state_machine)?;
+
+        let catalog = &query_state_machine.catalog;
+        let table_ref = TableReference::from(table.as_str())
+            .resolve(catalog.catalog_name(), catalog.schema_name());
+        let mut role = catalog
+            .role(role)
+            .context(execution::MetadataSnafu)?
+            .ok_or_else(|| MetadataError::RoleNotExists {
+                role_name: role.clone(),
+            })
+            .context(execution::MetadataSnafu)?;
+
+        let on_table = |e: &models::meta_data::ColumnPrivilege| {
+            e.is_on(table_ref.catalog, table_ref.schema, table_ref.table)
+        };
+        if columns.is_empty() {
+            role.column_privileges.retain(|e| !on_table(e));
+        } else if let Some(privilege) = role.column_privileges.iter_mut().find(|e| on_table(e)) {
+            privilege.columns.retain(|e| !columns.contains(e));
+        }
+
+        catalog
+            .alter_role(role)
+            .await
+            .map(|_| Output::Nil(()))
+            .context(execution::MetadataSnafu)
+    }
+}
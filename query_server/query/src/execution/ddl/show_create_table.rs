@@ -0,0 +1,148 @@
+use crate::execution::ddl::DDLDefinitionTask;
+use async_trait::async_trait;
+use datafusion::arrow::array::StringArray;
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::catalog::TableReference;
+use models::codec::Encoding;
+use models::schema::{TableSchema, TskvTableSchema};
+use snafu::ResultExt;
+use spi::catalog::MetadataError;
+use spi::query::execution::{ArrowSnafu, MetadataSnafu};
+use spi::query::execution::{ExecutionError, Output, QueryStateMachineRef};
+use spi::query::logical_planner::ShowCreateTable;
+use std::sync::Arc;
+
+pub struct ShowCreateTableTask {
+    stmt: ShowCreateTable,
+}
+
+impl ShowCreateTableTask {
+    pub fn new(stmt: ShowCreateTable) -> Self {
+        Self { stmt }
+    }
+}
+
+#[async_trait]
+impl DDLDefinitionTask for ShowCreateTableTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> Result<Output, ExecutionError> {
+        let table_name = self.stmt.table_name.as_str();
+        let schema = match query_state_machine
+            .catalog
+            .table(TableReference::from(table_name))
+            .context(MetadataSnafu)?
+        {
+            TableSchema::TsKvTableSchema(schema) => schema,
+            TableSchema::ExternalTableSchema(_) => {
+                return Err(MetadataError::TableIsNotTsKv {
+                    table_name: table_name.to_string(),
+                })
+                .context(MetadataSnafu)
+            }
+        };
+
+        let output = Arc::new(Schema::new(vec![
+            Field::new("Table", DataType::Utf8, false),
+            Field::new("Create Table", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            output,
+            vec![
+                Arc::new(StringArray::from(vec![schema.name.as_str()])),
+                Arc::new(StringArray::from(vec![create_table_sql(&schema)])),
+            ],
+        )
+        .context(ArrowSnafu)?;
+
+        Ok(Output::StreamData(vec![batch]))
+    }
+}
+
+/// The CREATE TABLE statement of the table, the codecs other than the default
+/// are declared with the fields
+fn create_table_sql(schema: &TskvTableSchema) -> String {
+    let mut columns = vec![];
+    let mut tags = vec![];
+    for column in schema.columns() {
+        if column.column_type.is_tag() {
+            tags.push(quote_ident(&column.name));
+        } else if column.column_type.is_field() {
+            let mut definition = format!(
+                "{} {}",
+                quote_ident(&column.name),
+                column.column_type.to_sql_type_str()
+            );
+            if column.encoding != Encoding::Default {
+                definition.push_str(&format!(" CODEC({})", column.encoding.as_str()));
+            }
+            columns.push(definition);
+        }
+    }
+    if !tags.is_empty() {
+        columns.push(format!("TAGS({})", tags.join(", ")));
+    }
+
+    format!(
+        "CREATE TABLE {} ({})",
+        quote_ident(&schema.name),
+        columns.join(", ")
+    )
+}
+
+/// Quotes the identifiers which would be changed or rejected by the parser
+fn quote_ident(name: &str) -> String {
+    let is_plain = name
+        .chars()
+        .next()
+        .map(|c| c.is_ascii_lowercase() || c == '_')
+        .unwrap_or_default()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if is_plain {
+        name.to_string()
+    } else {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use models::schema::{ColumnType, TableColumn};
+    use models::ValueType;
+
+    use super::*;
+
+    #[test]
+    fn test_create_table_sql() {
+        let schema = TskvTableSchema::new(
+            "public".to_string(),
+            "air".to_string(),
+            vec![
+                TableColumn::new_time_column(0),
+                TableColumn::new_tag_column(1, "station".to_string()),
+                TableColumn::new_tag_column(2, "Region".to_string()),
+                TableColumn::new(
+                    3,
+                    "temperature".to_string(),
+                    ColumnType::Field(ValueType::Float),
+                    Encoding::Gorilla,
+                ),
+                TableColumn::new(
+                    4,
+                    "visibility".to_string(),
+                    ColumnType::Field(ValueType::Unsigned),
+                    Encoding::Default,
+                ),
+            ],
+        );
+        assert_eq!(
+            create_table_sql(&schema),
+            "CREATE TABLE air (temperature DOUBLE CODEC(GORILLA), \
+            visibility BIGINT UNSIGNED, TAGS(station, \"Region\"))"
+        );
+    }
+}
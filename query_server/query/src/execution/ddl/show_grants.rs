@@ -0,0 +1,39 @@
+use crate::execution::ddl::{check_admin, DDLDefinitionTask};
+use crate::privileges;
+use async_trait::async_trait;
+use snafu::ResultExt;
+use spi::query::execution::ExternalSnafu;
+use spi::query::execution::{ExecutionError, Output, QueryStateMachineRef};
+
+/// The privileges of a user or a role, only the admins show those of the others
+pub struct ShowGrantsTask {
+    grantee: Option<String>,
+}
+
+impl ShowGrantsTask {
+    pub fn new(grantee: Option<String>) -> Self {
+        Self { grantee }
+    }
+}
+
+#[async_trait]
+impl DDLDefinitionTask for ShowGrantsTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> Result<Output, ExecutionError> {
+        let current = &query_state_machine.query.context().user_info().user;
+        let grantee = match &self.grantee {
+            Some(name) if name != current => {
+                check_admin(&query_state_machine)?;
+                name
+            }
+            _ => current,
+        };
+
+        let batch = privileges::record_batch(query_state_machine.catalog.as_ref(), Some(grantee))
+            .context(ExternalSnafu)?;
+
+        Ok(Output::StreamData(vec![batch]))
+    }
+}
@@ -0,0 +1,83 @@
+use crate::execution::ddl::{check_admin, DDLDefinitionTask};
+use async_trait::async_trait;
+use datafusion::arrow::array::StringArray;
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::arrow::record_batch::RecordBatch;
+use snafu::ResultExt;
+use spi::query::execution::{ArrowSnafu, MetadataSnafu};
+use spi::query::execution::{ExecutionError, Output, QueryStateMachineRef};
+use std::sync::Arc;
+
+/// The policies on the tables of the tenant
+pub struct ShowPoliciesTask {}
+
+impl ShowPoliciesTask {
+    pub fn new() -> Self {
+        ShowPoliciesTask {}
+    }
+}
+
+#[async_trait]
+impl DDLDefinitionTask for ShowPoliciesTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> Result<Output, ExecutionError> {
+        check_admin(&query_state_machine)?;
+
+        let catalog = &query_state_machine.catalog;
+        let tenant = catalog.catalog_name();
+        let roles = catalog.roles().context(MetadataSnafu)?;
+        let policies = roles
+            .iter()
+            .flat_map(|role| role.policies.iter().map(move |e| (role, e)))
+            .filter(|(_, e)| e.tenant == tenant)
+            .collect::<Vec<_>>();
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("Name", DataType::Utf8, false),
+            Field::new("Database", DataType::Utf8, false),
+            Field::new("Table", DataType::Utf8, false),
+            Field::new("Role", DataType::Utf8, false),
+            Field::new("Predicate", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(
+                    policies
+                        .iter()
+                        .map(|(_, e)| e.name.as_str())
+                        .collect::<Vec<_>>(),
+                )),
+                Arc::new(StringArray::from(
+                    policies
+                        .iter()
+                        .map(|(_, e)| e.database.as_str())
+                        .collect::<Vec<_>>(),
+                )),
+                Arc::new(StringArray::from(
+                    policies
+                        .iter()
+                        .map(|(_, e)| e.table.as_str())
+                        .collect::<Vec<_>>(),
+                )),
+                Arc::new(StringArray::from(
+                    policies
+                        .iter()
+                        .map(|(role, _)| role.name.as_str())
+                        .collect::<Vec<_>>(),
+                )),
+                Arc::new(StringArray::from(
+                    policies
+                        .iter()
+                        .map(|(_, e)| e.predicate.as_str())
+                        .collect::<Vec<_>>(),
+                )),
+            ],
+        )
+        .context(ArrowSnafu)?;
+
+        Ok(Output::StreamData(vec![batch]))
+    }
+}
@@ -0,0 +1,67 @@
+use crate::execution::ddl::{check_admin, DDLDefinitionTask};
+use async_trait::async_trait;
+use datafusion::arrow::array::StringArray;
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::arrow::record_batch::RecordBatch;
+use snafu::ResultExt;
+use spi::query::execution::{ArrowSnafu, MetadataSnafu};
+use spi::query::execution::{ExecutionError, Output, QueryStateMachineRef};
+use std::sync::Arc;
+
+/// The roles with the users and the groups of the external identity providers
+/// granted them
+pub struct ShowRolesTask {}
+
+impl ShowRolesTask {
+    pub fn new() -> Self {
+        ShowRolesTask {}
+    }
+}
+
+#[async_trait]
+impl DDLDefinitionTask for ShowRolesTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> Result<Output, ExecutionError> {
+        check_admin(&query_state_machine)?;
+
+        let catalog = &query_state_machine.catalog;
+        let roles = catalog.roles().context(MetadataSnafu)?;
+        let users = catalog.users().context(MetadataSnafu)?;
+        let grantees = roles
+            .iter()
+            .map(|role| {
+                users
+                    .iter()
+                    .filter(|e| e.roles.contains(&role.name))
+                    .map(|e| e.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            })
+            .collect::<Vec<_>>();
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("Role", DataType::Utf8, false),
+            Field::new("Users", DataType::Utf8, false),
+            Field::new("Groups", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(
+                    roles.iter().map(|e| e.name.as_str()).collect::<Vec<_>>(),
+                )),
+                Arc::new(StringArray::from(
+                    grantees.iter().map(|e| e.as_str()).collect::<Vec<_>>(),
+                )),
+                Arc::new(StringArray::from(
+                    roles.iter().map(|e| e.groups.join(";")).collect::<Vec<_>>(),
+                )),
+            ],
+        )
+        .context(ArrowSnafu)?;
+
+        Ok(Output::StreamData(vec![batch]))
+    }
+}
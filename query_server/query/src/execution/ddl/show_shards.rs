@@ -0,0 +1,32 @@
+use crate::execution::ddl::DDLDefinitionTask;
+use crate::shards;
+use async_trait::async_trait;
+use snafu::ResultExt;
+use spi::query::execution::ExternalSnafu;
+use spi::query::execution::{ExecutionError, Output, QueryStateMachineRef};
+
+pub struct ShowShardsTask {
+    database_name: Option<String>,
+}
+
+impl ShowShardsTask {
+    pub fn new(database_name: Option<String>) -> Self {
+        Self { database_name }
+    }
+}
+
+#[async_trait]
+impl DDLDefinitionTask for ShowShardsTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> Result<Output, ExecutionError> {
+        let batch = shards::record_batch(
+            query_state_machine.catalog.as_ref(),
+            self.database_name.as_deref(),
+        )
+        .context(ExternalSnafu)?;
+
+        Ok(Output::StreamData(vec![batch]))
+    }
+}
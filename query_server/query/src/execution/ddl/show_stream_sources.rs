@@ -0,0 +1,74 @@
+use crate::execution::ddl::DDLDefinitionTask;
+use async_trait::async_trait;
+use datafusion::arrow::array::{ArrayRef, StringArray, UInt64Array};
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::arrow::record_batch::RecordBatch;
+use models::stream_source::StreamSourceStatus;
+use snafu::ResultExt;
+use spi::catalog::MetaDataRef;
+use spi::query::execution::ExternalSnafu;
+use spi::query::execution::MetadataSnafu;
+use spi::query::execution::{ExecutionError, Output, QueryStateMachineRef};
+use std::sync::Arc;
+
+pub struct ShowStreamSourcesTask {}
+
+impl ShowStreamSourcesTask {
+    pub fn new() -> Self {
+        ShowStreamSourcesTask {}
+    }
+}
+
+#[async_trait]
+impl DDLDefinitionTask for ShowStreamSourcesTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> Result<Output, ExecutionError> {
+        show_stream_sources(query_state_machine.catalog.clone())
+    }
+}
+
+fn show_stream_sources(catalog: MetaDataRef) -> Result<Output, ExecutionError> {
+    let sources = catalog.stream_sources().context(MetadataSnafu)?;
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("Name", DataType::Utf8, false),
+        Field::new("Connector", DataType::Utf8, false),
+        Field::new("Format", DataType::Utf8, false),
+        Field::new("Database", DataType::Utf8, false),
+        Field::new("State", DataType::Utf8, false),
+        Field::new("RecordsWritten", DataType::UInt64, false),
+        Field::new("RecordsFailed", DataType::UInt64, false),
+        Field::new("LastError", DataType::Utf8, true),
+    ]));
+
+    let string_column = |f: fn(&StreamSourceStatus) -> String| -> ArrayRef {
+        Arc::new(StringArray::from(sources.iter().map(f).collect::<Vec<_>>()))
+    };
+    let u64_column = |f: fn(&StreamSourceStatus) -> u64| -> ArrayRef {
+        Arc::new(UInt64Array::from(sources.iter().map(f).collect::<Vec<_>>()))
+    };
+
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![
+            string_column(|e| e.definition.name.clone()),
+            string_column(|e| e.definition.connector.to_string()),
+            string_column(|e| e.definition.format.to_string()),
+            string_column(|e| e.definition.database.clone()),
+            string_column(|e| e.state.to_string()),
+            u64_column(|e| e.records_written),
+            u64_column(|e| e.records_failed),
+            Arc::new(StringArray::from(
+                sources
+                    .iter()
+                    .map(|e| e.last_error.clone())
+                    .collect::<Vec<_>>(),
+            )),
+        ],
+    )
+    .map_err(datafusion::error::DataFusionError::ArrowError)
+    .context(ExternalSnafu)?;
+
+    Ok(Output::StreamData(vec![batch]))
+}
@@ -0,0 +1,67 @@
+use crate::execution::ddl::DDLDefinitionTask;
+use async_trait::async_trait;
+use datafusion::arrow::array::{ArrayRef, BooleanArray, StringArray};
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::arrow::record_batch::RecordBatch;
+use models::task::TaskDefinition;
+use snafu::ResultExt;
+use spi::catalog::MetaDataRef;
+use spi::query::execution::ExternalSnafu;
+use spi::query::execution::MetadataSnafu;
+use spi::query::execution::{ExecutionError, Output, QueryStateMachineRef};
+use std::sync::Arc;
+
+pub struct ShowTasksTask {}
+
+impl ShowTasksTask {
+    pub fn new() -> Self {
+        ShowTasksTask {}
+    }
+}
+
+#[async_trait]
+impl DDLDefinitionTask for ShowTasksTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> Result<Output, ExecutionError> {
+        show_tasks(query_state_machine.catalog.clone())
+    }
+}
+
+/// The runs of the tasks are in `system.task_runs`
+fn show_tasks(catalog: MetaDataRef) -> Result<Output, ExecutionError> {
+    let tasks = catalog.tasks().context(MetadataSnafu)?;
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("Name", DataType::Utf8, false),
+        Field::new("Database", DataType::Utf8, false),
+        Field::new("Owner", DataType::Utf8, false),
+        Field::new("Trigger", DataType::Utf8, false),
+        Field::new("Sink", DataType::Utf8, false),
+        Field::new("Enabled", DataType::Boolean, false),
+        Field::new("Query", DataType::Utf8, false),
+    ]));
+
+    let string_column = |f: fn(&TaskDefinition) -> String| -> ArrayRef {
+        Arc::new(StringArray::from(tasks.iter().map(f).collect::<Vec<_>>()))
+    };
+
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![
+            string_column(|e| e.name.clone()),
+            string_column(|e| e.database.clone()),
+            string_column(|e| e.owner.clone()),
+            string_column(|e| e.trigger.to_string()),
+            string_column(|e| e.sink.to_string()),
+            Arc::new(BooleanArray::from(
+                tasks.iter().map(|e| e.enabled).collect::<Vec<_>>(),
+            )),
+            string_column(|e| e.query.clone()),
+        ],
+    )
+    .map_err(datafusion::error::DataFusionError::ArrowError)
+    .context(ExternalSnafu)?;
+
+    Ok(Output::StreamData(vec![batch]))
+}
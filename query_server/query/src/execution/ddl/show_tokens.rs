@@ -0,0 +1,71 @@
+use crate::execution::ddl::{check_admin, DDLDefinitionTask};
+use async_trait::async_trait;
+use datafusion::arrow::array::{StringArray, TimestampMillisecondArray};
+use datafusion::arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+use datafusion::arrow::record_batch::RecordBatch;
+use snafu::ResultExt;
+use spi::query::execution::{ArrowSnafu, MetadataSnafu};
+use spi::query::execution::{ExecutionError, Output, QueryStateMachineRef};
+use std::sync::Arc;
+
+/// The admins see the tokens of all users, the others see their own
+pub struct ShowTokensTask {}
+
+impl ShowTokensTask {
+    pub fn new() -> Self {
+        ShowTokensTask {}
+    }
+}
+
+#[async_trait]
+impl DDLDefinitionTask for ShowTokensTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> Result<Output, ExecutionError> {
+        let current = &query_state_machine.query.context().user_info().user;
+        let is_admin = check_admin(&query_state_machine).is_ok();
+        let users = query_state_machine.catalog.users().context(MetadataSnafu)?;
+        let tokens = users
+            .iter()
+            .filter(|e| is_admin || e.name == *current)
+            .flat_map(|user| user.tokens.iter().map(move |token| (user, token)))
+            .collect::<Vec<_>>();
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("User", DataType::Utf8, false),
+            Field::new("Name", DataType::Utf8, false),
+            Field::new(
+                "CreatedAt",
+                DataType::Timestamp(TimeUnit::Millisecond, None),
+                false,
+            ),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(
+                    tokens
+                        .iter()
+                        .map(|(user, _)| user.name.as_str())
+                        .collect::<Vec<_>>(),
+                )),
+                Arc::new(StringArray::from(
+                    tokens
+                        .iter()
+                        .map(|(_, token)| token.name.as_str())
+                        .collect::<Vec<_>>(),
+                )),
+                Arc::new(TimestampMillisecondArray::from(
+                    tokens
+                        .iter()
+                        .map(|(_, token)| token.created_at)
+                        .collect::<Vec<_>>(),
+                )),
+            ],
+        )
+        .context(ArrowSnafu)?;
+
+        Ok(Output::StreamData(vec![batch]))
+    }
+}
@@ -0,0 +1,47 @@
+use crate::execution::ddl::DDLDefinitionTask;
+use async_trait::async_trait;
+use datafusion::arrow::array::{BooleanArray, StringArray};
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::arrow::record_batch::RecordBatch;
+use snafu::ResultExt;
+use spi::query::execution::{ArrowSnafu, MetadataSnafu};
+use spi::query::execution::{ExecutionError, Output, QueryStateMachineRef};
+use std::sync::Arc;
+
+/// The hashes of the passwords are never shown
+pub struct ShowUsersTask {}
+
+impl ShowUsersTask {
+    pub fn new() -> Self {
+        ShowUsersTask {}
+    }
+}
+
+#[async_trait]
+impl DDLDefinitionTask for ShowUsersTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> Result<Output, ExecutionError> {
+        let users = query_state_machine.catalog.users().context(MetadataSnafu)?;
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("Name", DataType::Utf8, false),
+            Field::new("IsAdmin", DataType::Boolean, false),
+        ]));
+
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(
+                    users.iter().map(|e| e.name.as_str()).collect::<Vec<_>>(),
+                )),
+                Arc::new(BooleanArray::from(
+                    users.iter().map(|e| e.is_admin).collect::<Vec<_>>(),
+                )),
+            ],
+        )
+        .context(ArrowSnafu)?;
+
+        Ok(Output::StreamData(vec![batch]))
+    }
+}
@@ -1,6 +1,9 @@
 use std::sync::Arc;
 
+use crate::audit::AuditLogRef;
+use crate::session::SessionRegistryRef;
 use crate::{dispatcher::query_tracker::QueryTracker, execution::ddl::DDLExecution};
+use config::SettingsRef;
 use datafusion::scheduler::Scheduler;
 use spi::query::{
     execution::{QueryExecution, QueryExecutionFactory, QueryStateMachineRef},
@@ -15,6 +18,9 @@ pub struct SqlQueryExecutionFactory {
     // TODO 需要封装 scheduler
     scheduler: Arc<Scheduler>,
     query_tracker: Arc<QueryTracker>,
+    audit_log: AuditLogRef,
+    sessions: SessionRegistryRef,
+    settings: SettingsRef,
 }
 
 impl SqlQueryExecutionFactory {
@@ -23,11 +29,17 @@ impl SqlQueryExecutionFactory {
         optimizer: Arc<dyn Optimizer + Send + Sync>,
         scheduler: Arc<Scheduler>,
         query_tracker: Arc<QueryTracker>,
+        audit_log: AuditLogRef,
+        sessions: SessionRegistryRef,
+        settings: SettingsRef,
     ) -> Self {
         Self {
             optimizer,
             scheduler,
             query_tracker,
+            audit_log,
+            sessions,
+            settings,
         }
     }
 }
@@ -45,11 +57,18 @@ impl QueryExecutionFactory for SqlQueryExecutionFactory {
                 self.optimizer.clone(),
                 self.scheduler.clone(),
             )),
-            Plan::DDL(ddl_plan) => Arc::new(DDLExecution::new(state_machine, ddl_plan)),
+            Plan::DDL(ddl_plan) => Arc::new(DDLExecution::new(
+                state_machine,
+                ddl_plan,
+                self.audit_log.clone(),
+                self.settings.clone(),
+            )),
             Plan::SYSTEM(sys_plan) => Arc::new(SystemExecution::new(
                 state_machine,
                 sys_plan,
                 self.query_tracker.clone(),
+                self.sessions.clone(),
+                self.settings.clone(),
             )),
         }
     }
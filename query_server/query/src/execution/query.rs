@@ -1,9 +1,11 @@
 use std::sync::Arc;
 
 use async_trait::async_trait;
+use datafusion::physical_plan::ExecutionPlan;
 use datafusion::scheduler::Scheduler;
 use futures::stream::AbortHandle;
-use futures::TryStreamExt;
+use futures::{StreamExt, TryStreamExt};
+use models::arrow_array::unpack_dictionaries;
 use parking_lot::Mutex;
 use snafu::ResultExt;
 use spi::query::dispatcher::{QueryInfo, QueryStatus};
@@ -16,7 +18,9 @@ use spi::query::{
 };
 
 use spi::query::{QueryError, Result};
-use trace::debug;
+use trace::{debug, info_span, Instrument};
+
+use crate::stream::BYTES_SCANNED;
 
 pub struct SqlQueryExecution {
     query_state_machine: QueryStateMachineRef,
@@ -25,6 +29,8 @@ pub struct SqlQueryExecution {
     scheduler: Arc<Scheduler>,
 
     abort_handle: Mutex<Option<AbortHandle>>,
+    /// Kept for the metrics of the running query
+    physical_plan: Mutex<Option<Arc<dyn ExecutionPlan>>>,
 }
 
 impl SqlQueryExecution {
@@ -40,6 +46,7 @@ impl SqlQueryExecution {
             optimizer,
             scheduler,
             abort_handle: Mutex::new(None),
+            physical_plan: Mutex::new(None),
         }
     }
 }
@@ -51,12 +58,14 @@ impl SqlQueryExecution {
         let optimized_physical_plan = self
             .optimizer
             .optimize(&self.plan.df_plan, &self.query_state_machine.session)
+            .instrument(info_span!("optimize"))
             .await?;
         self.query_state_machine.end_optimize();
+        *self.physical_plan.lock() = Some(optimized_physical_plan.clone());
 
         // begin schedule
         self.query_state_machine.begin_schedule();
-        let execution_result = self
+        let stream = self
             .scheduler
             .schedule(
                 optimized_physical_plan,
@@ -64,11 +73,24 @@ impl SqlQueryExecution {
             )
             .context(ScheduleSnafu)?
             .stream()
-            .try_collect::<Vec<_>>()
-            .await
-            .map_err(|source| QueryError::Execution {
-                source: ExecutionError::Arrow { source },
-            })?;
+            .map(|batch| batch.and_then(|batch| unpack_dictionaries(&batch)));
+        // The batches are sent to the sink as produced if any, so they are not held
+        let execution_result = match self.query_state_machine.query.context().batch_sink() {
+            Some(sink) => stream
+                .try_for_each(|batch| sink.append(batch))
+                .instrument(info_span!("execute"))
+                .await
+                .map(|_| vec![]),
+            None => {
+                stream
+                    .try_collect::<Vec<_>>()
+                    .instrument(info_span!("execute"))
+                    .await
+            }
+        }
+        .map_err(|source| QueryError::Execution {
+            source: ExecutionError::Arrow { source },
+        })?;
         self.query_state_machine.end_schedule();
 
         Ok(Output::StreamData(execution_result))
@@ -121,9 +143,28 @@ impl QueryExecution for SqlQueryExecution {
     }
 
     fn status(&self) -> QueryStatus {
+        let bytes_scanned = self
+            .physical_plan
+            .lock()
+            .as_ref()
+            .map(|plan| bytes_scanned(plan.as_ref()))
+            .unwrap_or_default();
         QueryStatus::new(
             self.query_state_machine.state().clone(),
             self.query_state_machine.duration(),
         )
+        .with_bytes_scanned(bytes_scanned)
     }
 }
+
+/// Sum of the bytes read by the scans of the plan
+fn bytes_scanned(plan: &dyn ExecutionPlan) -> u64 {
+    let scanned = plan
+        .metrics()
+        .and_then(|metrics| metrics.sum_by_name(BYTES_SCANNED))
+        .map(|value| value.as_usize() as u64)
+        .unwrap_or_default();
+    plan.children()
+        .iter()
+        .fold(scanned, |sum, child| sum + bytes_scanned(child.as_ref()))
+}
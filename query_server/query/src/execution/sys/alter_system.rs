@@ -0,0 +1,43 @@
+use async_trait::async_trait;
+use config::SettingsRef;
+use spi::query::execution::{ExecutionError, Output, QueryStateMachineRef};
+
+use super::{is_admin, SystemTask};
+
+pub struct AlterSystemSetTask {
+    settings: SettingsRef,
+    name: String,
+    value: String,
+}
+
+impl AlterSystemSetTask {
+    pub fn new(settings: SettingsRef, name: String, value: String) -> Self {
+        Self {
+            settings,
+            name,
+            value,
+        }
+    }
+}
+
+#[async_trait]
+impl SystemTask for AlterSystemSetTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> std::result::Result<Output, ExecutionError> {
+        if !is_admin(&query_state_machine)? {
+            return Err(ExecutionError::PermissionDenied {
+                reason: format!(
+                    "user {} can not change the settings",
+                    query_state_machine.query.context().user_info().user
+                ),
+            });
+        }
+        self.settings
+            .set(&self.name, &self.value)
+            .map_err(|reason| ExecutionError::Settings { reason })?;
+
+        Ok(Output::Nil(()))
+    }
+}
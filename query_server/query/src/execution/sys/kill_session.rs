@@ -0,0 +1,51 @@
+use async_trait::async_trait;
+use spi::query::execution::{ExecutionError, Output, QueryStateMachineRef};
+
+use crate::session::{SessionId, SessionRegistryRef};
+
+use super::is_admin;
+use super::SystemTask;
+
+pub struct KillSessionTask {
+    sessions: SessionRegistryRef,
+
+    session_id: SessionId,
+}
+
+impl KillSessionTask {
+    pub fn new(sessions: SessionRegistryRef, session_id: SessionId) -> Self {
+        Self {
+            sessions,
+            session_id,
+        }
+    }
+}
+
+#[async_trait]
+impl SystemTask for KillSessionTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> std::result::Result<Output, ExecutionError> {
+        let session =
+            self.sessions
+                .session(self.session_id)
+                .ok_or(ExecutionError::SessionNotFound {
+                    session_id: self.session_id,
+                })?;
+
+        // The users kill their own sessions, the admins any of them
+        let user = &query_state_machine.query.context().user_info().user;
+        if session.user().as_ref() != Some(user) && !is_admin(&query_state_machine)? {
+            return Err(ExecutionError::PermissionDenied {
+                reason: format!(
+                    "user {} can not kill the session {} of another user",
+                    user, self.session_id
+                ),
+            });
+        }
+        session.kill();
+
+        Ok(Output::Nil(()))
+    }
+}
@@ -1,10 +1,18 @@
+mod alter_system;
 mod kill_query;
+mod kill_session;
+mod show_diagnostics;
 mod show_queries;
+mod show_sessions;
+mod show_settings;
+mod show_stats;
 
 use std::sync::Arc;
 
 use async_trait::async_trait;
+use config::SettingsRef;
 use snafu::ResultExt;
+use spi::query::execution::MetadataSnafu;
 use spi::query::{self, Result};
 use spi::query::{
     dispatcher::{QueryInfo, QueryStatus},
@@ -13,9 +21,16 @@ use spi::query::{
 };
 
 use crate::dispatcher::query_tracker::QueryTracker;
+use crate::session::SessionRegistryRef;
 
+use self::alter_system::AlterSystemSetTask;
 use self::kill_query::KillQueryTask;
+use self::kill_session::KillSessionTask;
+use self::show_diagnostics::ShowDiagnosticsTask;
 use self::show_queries::ShowQueriesTask;
+use self::show_sessions::ShowSessionsTask;
+use self::show_settings::ShowSettingsTask;
+use self::show_stats::ShowStatsTask;
 
 pub struct SystemExecution {
     task_factory: SystemTaskFactory,
@@ -27,11 +42,15 @@ impl SystemExecution {
         state_machine: QueryStateMachineRef,
         plan: SYSPlan,
         query_tracker: Arc<QueryTracker>,
+        sessions: SessionRegistryRef,
+        settings: SettingsRef,
     ) -> Self {
         Self {
             task_factory: SystemTaskFactory {
                 plan,
                 query_tracker,
+                sessions,
+                settings,
             },
             state_machine,
         }
@@ -88,9 +107,22 @@ trait SystemTask: Send + Sync {
     ) -> std::result::Result<Output, ExecutionError>;
 }
 
+fn is_admin(
+    query_state_machine: &QueryStateMachineRef,
+) -> std::result::Result<bool, ExecutionError> {
+    let name = &query_state_machine.query.context().user_info().user;
+    let user = query_state_machine
+        .catalog
+        .user(name)
+        .context(MetadataSnafu)?;
+    Ok(matches!(user, Some(user) if user.is_admin))
+}
+
 struct SystemTaskFactory {
     plan: SYSPlan,
     query_tracker: Arc<QueryTracker>,
+    sessions: SessionRegistryRef,
+    settings: SettingsRef,
 }
 
 impl SystemTaskFactory {
@@ -100,6 +132,18 @@ impl SystemTaskFactory {
             SYSPlan::KillQuery(query_id) => {
                 Box::new(KillQueryTask::new(self.query_tracker.clone(), *query_id))
             }
+            SYSPlan::ShowSessions => Box::new(ShowSessionsTask::new(self.sessions.clone())),
+            SYSPlan::KillSession(session_id) => {
+                Box::new(KillSessionTask::new(self.sessions.clone(), *session_id))
+            }
+            SYSPlan::ShowSettings => Box::new(ShowSettingsTask::new(self.settings.clone())),
+            SYSPlan::ShowStats(module) => Box::new(ShowStatsTask::new(module.clone())),
+            SYSPlan::ShowDiagnostics => Box::new(ShowDiagnosticsTask::new(self.settings.clone())),
+            SYSPlan::AlterSystemSet { name, value } => Box::new(AlterSystemSetTask::new(
+                self.settings.clone(),
+                name.clone(),
+                value.clone(),
+            )),
         }
     }
 }
@@ -0,0 +1,174 @@
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use config::{Config, SettingsRef};
+use datafusion::arrow::{
+    array::StringBuilder,
+    datatypes::{DataType, Field, Schema},
+    record_batch::RecordBatch,
+};
+use metrics::build_info::{build_info, BuildInfo};
+use snafu::ResultExt;
+use spi::query::execution::{ArrowSnafu, ExecutionError, Output, QueryStateMachineRef};
+
+use super::{is_admin, SystemTask};
+
+const UNKNOWN: &str = "UNKNOWN";
+
+pub struct ShowDiagnosticsTask {
+    settings: SettingsRef,
+}
+
+impl ShowDiagnosticsTask {
+    pub fn new(settings: SettingsRef) -> Self {
+        Self { settings }
+    }
+}
+
+#[async_trait]
+impl SystemTask for ShowDiagnosticsTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> std::result::Result<Output, ExecutionError> {
+        if !is_admin(&query_state_machine)? {
+            return Err(ExecutionError::PermissionDenied {
+                reason: format!(
+                    "user {} can not show the diagnostics",
+                    query_state_machine.query.context().user_info().user
+                ),
+            });
+        }
+        let mut modules = StringBuilder::new();
+        let mut names = StringBuilder::new();
+        let mut values = StringBuilder::new();
+        let config = self.settings.config();
+        for (module, name, value) in diagnostics(&config, build_info(), SystemTime::now()) {
+            modules.append_value(module);
+            names.append_value(name);
+            values.append_value(value);
+        }
+
+        let schema = Schema::new(vec![
+            Field::new("module", DataType::Utf8, false),
+            Field::new("name", DataType::Utf8, false),
+            Field::new("value", DataType::Utf8, false),
+        ]);
+        let batch = RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(modules.finish()),
+                Arc::new(names.finish()),
+                Arc::new(values.finish()),
+            ],
+        )
+        .context(ArrowSnafu)?;
+
+        Ok(Output::StreamData(vec![batch]))
+    }
+}
+
+/// Rows of the build, the runtime and the configuration of the server
+fn diagnostics(
+    config: &Config,
+    build: Option<&BuildInfo>,
+    now: SystemTime,
+) -> Vec<(&'static str, &'static str, String)> {
+    let mut rows = vec![
+        (
+            "build",
+            "version",
+            build.map_or(UNKNOWN.to_string(), |e| e.version.clone()),
+        ),
+        (
+            "build",
+            "revision",
+            build.map_or(UNKNOWN.to_string(), |e| e.revision.clone()),
+        ),
+        ("runtime", "os", std::env::consts::OS.to_string()),
+        ("runtime", "arch", std::env::consts::ARCH.to_string()),
+        (
+            "runtime",
+            "cpus",
+            std::thread::available_parallelism().map_or(UNKNOWN.to_string(), |e| e.to_string()),
+        ),
+        ("runtime", "pid", std::process::id().to_string()),
+    ];
+    if let Some(build) = build {
+        let started_at = DateTime::<Utc>::from(build.started_at);
+        let uptime = now
+            .duration_since(build.started_at)
+            .unwrap_or_default()
+            .as_secs();
+        rows.push((
+            "runtime",
+            "started_at",
+            started_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+        ));
+        rows.push(("runtime", "uptime_secs", uptime.to_string()));
+    }
+    rows.extend([
+        ("config", "storage.path", config.storage.path.clone()),
+        ("config", "wal.enabled", config.wal.enabled.to_string()),
+        ("config", "wal.path", config.wal.path.clone()),
+        (
+            "config",
+            "cache.max_buffer_size",
+            config.cache.max_buffer_size.to_string(),
+        ),
+        ("config", "memory.limit", config.memory.limit.to_string()),
+        (
+            "config",
+            "cluster.node_id",
+            config.cluster.node_id.to_string(),
+        ),
+        (
+            "config",
+            "cluster.meta_service_addr",
+            config.cluster.meta_service_addr.join(","),
+        ),
+        (
+            "config",
+            "security.auth_enabled",
+            config.security.auth_enabled.to_string(),
+        ),
+    ]);
+    rows
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_diagnostics() {
+        let config = config::get_config("../../config/config.toml");
+        let started_at = SystemTime::UNIX_EPOCH + Duration::from_secs(60);
+        let build = BuildInfo {
+            version: "2.0.0".to_string(),
+            revision: "abc".to_string(),
+            started_at,
+        };
+        let rows = diagnostics(&config, Some(&build), started_at + Duration::from_secs(90));
+        let value = |module: &str, name: &str| {
+            rows.iter()
+                .find(|e| e.0 == module && e.1 == name)
+                .map(|e| e.2.as_str())
+        };
+        assert_eq!(value("build", "version"), Some("2.0.0"));
+        assert_eq!(value("runtime", "started_at"), Some("1970-01-01T00:01:00Z"));
+        assert_eq!(value("runtime", "uptime_secs"), Some("90"));
+        assert_eq!(
+            value("config", "storage.path"),
+            Some(config.storage.path.as_str())
+        );
+
+        let rows = diagnostics(&config, None, started_at);
+        assert!(rows.iter().all(|e| e.1 != "uptime_secs"));
+        assert_eq!(rows[0].2, UNKNOWN);
+    }
+}
@@ -1,22 +1,12 @@
 use std::sync::Arc;
 
 use async_trait::async_trait;
-use datafusion::arrow::{
-    array::{StringBuilder, UInt64Builder},
-    datatypes::{DataType, Field, Schema},
-    error::ArrowError,
-    record_batch::RecordBatch,
-};
 use snafu::ResultExt;
-use spi::{
-    query::execution::{ArrowSnafu, ExecutionError, Output, QueryState, QueryStateMachineRef},
-    service::protocol::QueryId,
-};
-use std::time::Duration;
+use spi::query::execution::{ArrowSnafu, ExecutionError, Output, QueryStateMachineRef};
 
 use crate::dispatcher::query_tracker::QueryTracker;
 
-use super::SystemTask;
+use super::{is_admin, SystemTask};
 
 pub struct ShowQueriesTask {
     query_tracker: Arc<QueryTracker>,
@@ -32,92 +22,17 @@ impl ShowQueriesTask {
 impl SystemTask for ShowQueriesTask {
     async fn execute(
         &self,
-        _query_state_machine: QueryStateMachineRef,
+        query_state_machine: QueryStateMachineRef,
     ) -> std::result::Result<Output, ExecutionError> {
-        let mut result_builder = ShowQueriesResultBuilder::new();
-
-        self.query_tracker.running_queries().iter().for_each(|e| {
-            let info = e.info();
-            let status = e.status();
-            result_builder.add_column(
-                info.query_id(),
-                info.user(),
-                info.query(),
-                status.query_state(),
-                status.duration(),
-            )
-        });
-
-        Ok(Output::StreamData(
-            result_builder.build().context(ArrowSnafu)?,
-        ))
-    }
-}
-
-struct ShowQueriesResultBuilder {
-    query_ids: StringBuilder,
-    users: StringBuilder,
-    queries: StringBuilder,
-    states: StringBuilder,
-    durations: UInt64Builder,
-}
-
-impl ShowQueriesResultBuilder {
-    fn new() -> Self {
-        Self {
-            query_ids: StringBuilder::new(),
-            users: StringBuilder::new(),
-            queries: StringBuilder::new(),
-            states: StringBuilder::new(),
-            durations: UInt64Builder::new(),
+        // The admins see the queries of all the users, the others their own only
+        let user = &query_state_machine.query.context().user_info().user;
+        let batch = if is_admin(&query_state_machine)? {
+            self.query_tracker.record_batch(None)
+        } else {
+            self.query_tracker.record_batch(Some(user))
         }
-    }
-
-    #[allow(clippy::too_many_arguments)]
-    fn add_column(
-        &mut self,
-        query_id: QueryId,
-        user: impl AsRef<str>,
-        query: impl AsRef<str>,
-        state: &QueryState,
-        duration: &Duration,
-    ) {
-        self.query_ids.append_value(query_id.to_string());
-        self.users.append_value(user.as_ref());
-        self.queries.append_value(query.as_ref());
-        self.states.append_value(state.to_string());
-        self.durations.append_value(duration.as_millis() as u64);
-    }
-
-    fn build(self) -> Result<Vec<RecordBatch>, ArrowError> {
-        let schema = Schema::new(vec![
-            Field::new("query_id", DataType::Utf8, false),
-            Field::new("user", DataType::Utf8, false),
-            Field::new("query", DataType::Utf8, false),
-            Field::new("state", DataType::Utf8, false),
-            Field::new("duration", DataType::UInt64, false),
-        ]);
-
-        let ShowQueriesResultBuilder {
-            mut query_ids,
-            mut users,
-            mut queries,
-            mut states,
-            mut durations,
-        } = self;
-
-        let schema = Arc::new(schema);
-        let batch = RecordBatch::try_new(
-            schema,
-            vec![
-                Arc::new(query_ids.finish()),
-                Arc::new(users.finish()),
-                Arc::new(queries.finish()),
-                Arc::new(states.finish()),
-                Arc::new(durations.finish()),
-            ],
-        )?;
+        .context(ArrowSnafu)?;
 
-        Ok(vec![batch])
+        Ok(Output::StreamData(vec![batch]))
     }
 }
@@ -0,0 +1,37 @@
+use async_trait::async_trait;
+use snafu::ResultExt;
+use spi::query::execution::{ArrowSnafu, ExecutionError, Output, QueryStateMachineRef};
+
+use crate::session::SessionRegistryRef;
+
+use super::is_admin;
+use super::SystemTask;
+
+pub struct ShowSessionsTask {
+    sessions: SessionRegistryRef,
+}
+
+impl ShowSessionsTask {
+    pub fn new(sessions: SessionRegistryRef) -> Self {
+        Self { sessions }
+    }
+}
+
+#[async_trait]
+impl SystemTask for ShowSessionsTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> std::result::Result<Output, ExecutionError> {
+        // The admins see the sessions of all the users, the others their own only
+        let user = &query_state_machine.query.context().user_info().user;
+        let batch = if is_admin(&query_state_machine)? {
+            self.sessions.record_batch(None)
+        } else {
+            self.sessions.record_batch(Some(user))
+        }
+        .context(ArrowSnafu)?;
+
+        Ok(Output::StreamData(vec![batch]))
+    }
+}
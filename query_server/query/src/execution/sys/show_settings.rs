@@ -0,0 +1,65 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use config::SettingsRef;
+use datafusion::arrow::{
+    array::{BooleanBuilder, StringBuilder},
+    datatypes::{DataType, Field, Schema},
+    record_batch::RecordBatch,
+};
+use snafu::ResultExt;
+use spi::query::execution::{ArrowSnafu, ExecutionError, Output, QueryStateMachineRef};
+
+use super::{is_admin, SystemTask};
+
+pub struct ShowSettingsTask {
+    settings: SettingsRef,
+}
+
+impl ShowSettingsTask {
+    pub fn new(settings: SettingsRef) -> Self {
+        Self { settings }
+    }
+}
+
+#[async_trait]
+impl SystemTask for ShowSettingsTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> std::result::Result<Output, ExecutionError> {
+        if !is_admin(&query_state_machine)? {
+            return Err(ExecutionError::PermissionDenied {
+                reason: format!(
+                    "user {} can not show the settings",
+                    query_state_machine.query.context().user_info().user
+                ),
+            });
+        }
+        let mut names = StringBuilder::new();
+        let mut values = StringBuilder::new();
+        let mut reloadables = BooleanBuilder::new();
+        for setting in self.settings.show() {
+            names.append_value(&setting.name);
+            values.append_value(&setting.value);
+            reloadables.append_value(setting.reloadable);
+        }
+
+        let schema = Schema::new(vec![
+            Field::new("name", DataType::Utf8, false),
+            Field::new("value", DataType::Utf8, false),
+            Field::new("reloadable", DataType::Boolean, false),
+        ]);
+        let batch = RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(names.finish()),
+                Arc::new(values.finish()),
+                Arc::new(reloadables.finish()),
+            ],
+        )
+        .context(ArrowSnafu)?;
+
+        Ok(Output::StreamData(vec![batch]))
+    }
+}
@@ -0,0 +1,72 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use datafusion::arrow::{
+    array::{Float64Builder, StringBuilder},
+    datatypes::{DataType, Field, Schema},
+    record_batch::RecordBatch,
+};
+use metrics::stats::gather_stats;
+use snafu::ResultExt;
+use spi::query::execution::{ArrowSnafu, ExecutionError, Output, QueryStateMachineRef};
+
+use super::{is_admin, SystemTask};
+
+pub struct ShowStatsTask {
+    module: Option<String>,
+}
+
+impl ShowStatsTask {
+    pub fn new(module: Option<String>) -> Self {
+        Self { module }
+    }
+}
+
+#[async_trait]
+impl SystemTask for ShowStatsTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> std::result::Result<Output, ExecutionError> {
+        if !is_admin(&query_state_machine)? {
+            return Err(ExecutionError::PermissionDenied {
+                reason: format!(
+                    "user {} can not show the stats",
+                    query_state_machine.query.context().user_info().user
+                ),
+            });
+        }
+        let mut modules = StringBuilder::new();
+        let mut names = StringBuilder::new();
+        let mut tags = StringBuilder::new();
+        let mut values = Float64Builder::new();
+        for sample in gather_stats() {
+            if matches!(&self.module, Some(module) if *module != sample.module) {
+                continue;
+            }
+            modules.append_value(&sample.module);
+            names.append_value(&sample.name);
+            tags.append_value(&sample.tags);
+            values.append_value(sample.value);
+        }
+
+        let schema = Schema::new(vec![
+            Field::new("module", DataType::Utf8, false),
+            Field::new("name", DataType::Utf8, false),
+            Field::new("tags", DataType::Utf8, false),
+            Field::new("value", DataType::Float64, false),
+        ]);
+        let batch = RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(modules.finish()),
+                Arc::new(names.finish()),
+                Arc::new(tags.finish()),
+                Arc::new(values.finish()),
+            ],
+        )
+        .context(ArrowSnafu)?;
+
+        Ok(Output::StreamData(vec![batch]))
+    }
+}
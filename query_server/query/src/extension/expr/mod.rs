@@ -3,7 +3,7 @@ pub mod func_manager;
 mod aggregate_function;
 pub mod expr_utils;
 mod function_utils;
-mod scalar_function;
+pub mod scalar_function;
 pub mod selector_function;
 
 use spi::query::function::{FunctionMetadataManager, Result};
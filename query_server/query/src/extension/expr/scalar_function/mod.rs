@@ -1,12 +1,14 @@
 #[cfg(test)]
 mod example;
+pub mod time;
 
 use spi::query::function::{FunctionMetadataManager, Result};
 
-pub fn register_udfs(_func_manager: &mut dyn FunctionMetadataManager) -> Result<()> {
+pub fn register_udfs(func_manager: &mut dyn FunctionMetadataManager) -> Result<()> {
     // extend function...
     // eg.
     //   example::register_udf(func_manager)?;
+    time::register_udfs(func_manager)?;
     Ok(())
 }
 
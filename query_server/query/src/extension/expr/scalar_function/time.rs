@@ -0,0 +1,617 @@
+//! Calendar and time zone aware arithmetic of the timestamps.
+//!
+//! * `time_add(time, interval[, tz])`, `time_sub(time, interval[, tz])`: the months and
+//!   days of the interval are added in the local calendar of the time zone, UTC if
+//!   absent, the rest is added as a duration. `time + interval` and `time - interval`
+//!   are rewritten to them.
+//! * `time_bucket(interval, time[, tz])`: the start of the bucket of the time, the
+//!   buckets are aligned to the local midnight of 1970-01-01 of the time zone, or to
+//!   the first day of the months if the interval is in months.
+//! * `time_window(time, interval, offset, tz)`: the start of the bucket of `GROUP BY
+//!   time(interval, offset)`, the buckets are shifted by the offset, the interval and
+//!   the offset are the durations of influxql, e.g. `1d`, `8h`, and `mo` for months.
+//! * `to_timezone(time, tz)`: the local time of the time zone, the time of UTC is
+//!   converted to the wall clock time of the zone, `time AT TIME ZONE tz` is rewritten
+//!   to it.
+
+use std::sync::Arc;
+
+use chrono::{Datelike, Duration, LocalResult, NaiveDate, NaiveDateTime, TimeZone};
+use chrono_tz::Tz;
+use datafusion::{
+    arrow::{
+        array::{
+            Array, ArrayRef, IntervalDayTimeArray, IntervalMonthDayNanoArray,
+            IntervalYearMonthArray, StringArray, TimestampNanosecondArray,
+        },
+        datatypes::{DataType, IntervalUnit, TimeUnit},
+    },
+    error::{DataFusionError, Result as DFResult},
+    logical_expr::{ReturnTypeFunction, ScalarUDF, Signature, TypeSignature, Volatility},
+    physical_expr::functions::make_scalar_function,
+};
+
+use spi::query::function::{FunctionMetadataManager, Result};
+
+pub const TIME_ADD: &str = "time_add";
+pub const TIME_SUB: &str = "time_sub";
+pub const TIME_BUCKET: &str = "time_bucket";
+pub const TIME_WINDOW: &str = "time_window";
+pub const TO_TIMEZONE: &str = "to_timezone";
+
+const NANOS_PER_HOUR: i64 = 3_600_000_000_000;
+const NANOS_PER_DAY: i64 = 24 * NANOS_PER_HOUR;
+
+pub fn register_udfs(func_manager: &mut dyn FunctionMetadataManager) -> Result<()> {
+    func_manager.register_udf(time_add())?;
+    func_manager.register_udf(time_sub())?;
+    func_manager.register_udf(time_bucket())?;
+    func_manager.register_udf(time_window())?;
+    func_manager.register_udf(to_timezone())?;
+    Ok(())
+}
+
+pub fn time_add() -> ScalarUDF {
+    shift_udf(TIME_ADD, 1)
+}
+
+pub fn time_sub() -> ScalarUDF {
+    shift_udf(TIME_SUB, -1)
+}
+
+pub fn time_bucket() -> ScalarUDF {
+    let func = make_scalar_function(|args: &[ArrayRef]| {
+        let intervals = intervals(&args[0])?;
+        let times = timestamps(&args[1])?;
+        let zones = time_zones(args.get(2), times.len())?;
+
+        let result = (0..times.len())
+            .map(|i| match (intervals[i], times.is_null(i), &zones[i]) {
+                (Some(interval), false, Some(tz)) => bucket(interval, times.value(i), tz).map(Some),
+                _ => Ok(None),
+            })
+            .collect::<DFResult<TimestampNanosecondArray>>()?;
+        Ok(Arc::new(result) as ArrayRef)
+    });
+
+    let signatures = interval_types()
+        .into_iter()
+        .flat_map(|interval| {
+            [
+                TypeSignature::Exact(vec![interval.clone(), timestamp_type()]),
+                TypeSignature::Exact(vec![interval, timestamp_type(), DataType::Utf8]),
+            ]
+        })
+        .collect();
+    let signature = Signature::one_of(signatures, Volatility::Immutable);
+    let return_type: ReturnTypeFunction = Arc::new(|_| Ok(Arc::new(timestamp_type())));
+
+    ScalarUDF::new(TIME_BUCKET, &signature, &return_type, &func)
+}
+
+pub fn time_window() -> ScalarUDF {
+    let func = make_scalar_function(|args: &[ArrayRef]| {
+        let times = timestamps(&args[0])?;
+        let intervals = durations(&args[1])?;
+        let offsets = durations(&args[2])?;
+        let zones = time_zones(args.get(3), times.len())?;
+
+        let result = (0..times.len())
+            .map(
+                |i| match (times.is_null(i), intervals[i], offsets[i], &zones[i]) {
+                    (false, Some(interval), Some(offset), Some(tz)) => {
+                        let time = shift(times.value(i), offset.times(-1), tz)?;
+                        shift(bucket(interval, time, tz)?, offset, tz).map(Some)
+                    }
+                    _ => Ok(None),
+                },
+            )
+            .collect::<DFResult<TimestampNanosecondArray>>()?;
+        Ok(Arc::new(result) as ArrayRef)
+    });
+
+    let signature = Signature::exact(
+        vec![
+            timestamp_type(),
+            DataType::Utf8,
+            DataType::Utf8,
+            DataType::Utf8,
+        ],
+        Volatility::Immutable,
+    );
+    let return_type: ReturnTypeFunction = Arc::new(|_| Ok(Arc::new(timestamp_type())));
+
+    ScalarUDF::new(TIME_WINDOW, &signature, &return_type, &func)
+}
+
+pub fn to_timezone() -> ScalarUDF {
+    let func = make_scalar_function(|args: &[ArrayRef]| {
+        let times = timestamps(&args[0])?;
+        let zones = time_zones(args.get(1), times.len())?;
+
+        let result = (0..times.len())
+            .map(|i| match (times.is_null(i), &zones[i]) {
+                (false, Some(tz)) => Some(local_nanos(times.value(i), tz)),
+                _ => None,
+            })
+            .collect::<TimestampNanosecondArray>();
+        Ok(Arc::new(result) as ArrayRef)
+    });
+
+    let signature = Signature::exact(
+        vec![timestamp_type(), DataType::Utf8],
+        Volatility::Immutable,
+    );
+    let return_type: ReturnTypeFunction = Arc::new(|_| Ok(Arc::new(timestamp_type())));
+
+    ScalarUDF::new(TO_TIMEZONE, &signature, &return_type, &func)
+}
+
+/// Whether the string is a duration of influxql, e.g. `1d`, `1h30m`
+pub(crate) fn is_duration(s: &str) -> bool {
+    parse_duration(s).is_some()
+}
+
+fn shift_udf(name: &'static str, sign: i64) -> ScalarUDF {
+    let func = make_scalar_function(move |args: &[ArrayRef]| {
+        let times = timestamps(&args[0])?;
+        let intervals = intervals(&args[1])?;
+        let zones = time_zones(args.get(2), times.len())?;
+
+        let result = (0..times.len())
+            .map(|i| match (times.is_null(i), intervals[i], &zones[i]) {
+                (false, Some(interval), Some(tz)) => {
+                    shift(times.value(i), interval.times(sign), tz).map(Some)
+                }
+                _ => Ok(None),
+            })
+            .collect::<DFResult<TimestampNanosecondArray>>()?;
+        Ok(Arc::new(result) as ArrayRef)
+    });
+
+    let signatures = interval_types()
+        .into_iter()
+        .flat_map(|interval| {
+            [
+                TypeSignature::Exact(vec![timestamp_type(), interval.clone()]),
+                TypeSignature::Exact(vec![timestamp_type(), interval, DataType::Utf8]),
+            ]
+        })
+        .collect();
+    let signature = Signature::one_of(signatures, Volatility::Immutable);
+    let return_type: ReturnTypeFunction = Arc::new(|_| Ok(Arc::new(timestamp_type())));
+
+    ScalarUDF::new(name, &signature, &return_type, &func)
+}
+
+fn timestamp_type() -> DataType {
+    DataType::Timestamp(TimeUnit::Nanosecond, None)
+}
+
+fn interval_types() -> Vec<DataType> {
+    vec![
+        DataType::Interval(IntervalUnit::YearMonth),
+        DataType::Interval(IntervalUnit::DayTime),
+        DataType::Interval(IntervalUnit::MonthDayNano),
+    ]
+}
+
+/// An interval of any unit of arrow
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Interval {
+    months: i32,
+    days: i32,
+    nanos: i64,
+}
+
+impl Interval {
+    fn times(self, sign: i64) -> Self {
+        Self {
+            months: self.months * sign as i32,
+            days: self.days * sign as i32,
+            nanos: self.nanos * sign,
+        }
+    }
+}
+
+fn timestamps(array: &ArrayRef) -> DFResult<&TimestampNanosecondArray> {
+    array
+        .as_any()
+        .downcast_ref::<TimestampNanosecondArray>()
+        .ok_or_else(|| {
+            DataFusionError::Internal(format!(
+                "expect timestamp in nanoseconds, got {}",
+                array.data_type()
+            ))
+        })
+}
+
+fn intervals(array: &ArrayRef) -> DFResult<Vec<Option<Interval>>> {
+    let any = array.as_any();
+    if let Some(array) = any.downcast_ref::<IntervalYearMonthArray>() {
+        return Ok(array
+            .iter()
+            .map(|e| {
+                e.map(|months| Interval {
+                    months,
+                    days: 0,
+                    nanos: 0,
+                })
+            })
+            .collect());
+    }
+    // Days in the high 32 bits, milliseconds in the low 32 bits
+    if let Some(array) = any.downcast_ref::<IntervalDayTimeArray>() {
+        return Ok(array
+            .iter()
+            .map(|e| {
+                e.map(|v| Interval {
+                    months: 0,
+                    days: (v >> 32) as i32,
+                    nanos: v as i32 as i64 * 1_000_000,
+                })
+            })
+            .collect());
+    }
+    // Months, days and nanoseconds from the high bits to the low bits
+    if let Some(array) = any.downcast_ref::<IntervalMonthDayNanoArray>() {
+        return Ok(array
+            .iter()
+            .map(|e| {
+                e.map(|v| Interval {
+                    months: (v >> 96) as i32,
+                    days: (v >> 64) as i32,
+                    nanos: v as i64,
+                })
+            })
+            .collect());
+    }
+
+    Err(DataFusionError::Internal(format!(
+        "expect interval, got {}",
+        array.data_type()
+    )))
+}
+
+/// The durations of influxql, the units are `ns`, `us` (or `u`, `µ`, `µs`), `ms`, `s`,
+/// `m`, `h`, `d`, `w`, and `mo` for the months. The days and the months are those of
+/// the local calendar.
+fn parse_duration(s: &str) -> Option<Interval> {
+    let mut interval = Interval {
+        months: 0,
+        days: 0,
+        nanos: 0,
+    };
+    let mut rest = s.trim();
+    if rest.is_empty() {
+        return None;
+    }
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let n = rest[..digits].parse::<i64>().ok()?;
+        rest = &rest[digits..];
+        let unit = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let (unit, remaining) = rest.split_at(unit);
+        rest = remaining;
+
+        match unit {
+            "mo" => interval.months = interval.months.checked_add(i32::try_from(n).ok()?)?,
+            "w" | "d" => {
+                let days = if unit == "w" { n.checked_mul(7)? } else { n };
+                interval.days = interval.days.checked_add(i32::try_from(days).ok()?)?;
+            }
+            _ => {
+                let unit_nanos = match unit {
+                    "ns" => 1,
+                    "u" | "µ" | "us" | "µs" => 1_000,
+                    "ms" => 1_000_000,
+                    "s" => 1_000_000_000,
+                    "m" => 60_000_000_000,
+                    "h" => NANOS_PER_HOUR,
+                    _ => return None,
+                };
+                interval.nanos = interval.nanos.checked_add(n.checked_mul(unit_nanos)?)?;
+            }
+        }
+    }
+
+    Some(interval)
+}
+
+fn durations(array: &ArrayRef) -> DFResult<Vec<Option<Interval>>> {
+    let array = array
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .ok_or_else(|| DataFusionError::Internal("expect duration".to_string()))?;
+
+    array
+        .iter()
+        .map(|s| match s {
+            Some(s) => parse_duration(s)
+                .map(Some)
+                .ok_or_else(|| DataFusionError::Execution(format!("Invalid duration '{}'", s))),
+            None => Ok(None),
+        })
+        .collect()
+}
+
+/// The time zones of the rows, UTC if absent, `None` for the nulls
+fn time_zones(array: Option<&ArrayRef>, len: usize) -> DFResult<Vec<Option<Tz>>> {
+    let array = match array {
+        Some(array) => array,
+        None => return Ok(vec![Some(Tz::UTC); len]),
+    };
+    let array = array
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .ok_or_else(|| DataFusionError::Internal("expect time zone name".to_string()))?;
+
+    let mut last: Option<(&str, Tz)> = None;
+    array
+        .iter()
+        .map(|name| {
+            let name = match name {
+                Some(name) => name,
+                None => return Ok(None),
+            };
+            match last {
+                Some((last_name, tz)) if last_name == name => Ok(Some(tz)),
+                _ => {
+                    let tz = name.parse::<Tz>().map_err(|_| {
+                        DataFusionError::Execution(format!("Invalid time zone '{}'", name))
+                    })?;
+                    last = Some((name, tz));
+                    Ok(Some(tz))
+                }
+            }
+        })
+        .collect()
+}
+
+/// The wall clock time of the time zone at the time, in nanoseconds
+fn local_nanos(time: i64, tz: &Tz) -> i64 {
+    tz.timestamp_nanos(time).naive_local().timestamp_nanos()
+}
+
+fn overflow() -> DataFusionError {
+    DataFusionError::Execution("timestamp out of range".to_string())
+}
+
+/// The instant of the local time, the earlier one if the local time repeats as the
+/// clocks are set back, or the one after the gap if it is skipped as the clocks are
+/// set forward
+fn from_local(local: &NaiveDateTime, tz: &Tz) -> DFResult<i64> {
+    let instant = match tz.from_local_datetime(local) {
+        LocalResult::Single(e) | LocalResult::Ambiguous(e, _) => e,
+        LocalResult::None => {
+            let mut later = *local;
+            loop {
+                later += Duration::minutes(15);
+                if let Some(e) = tz.from_local_datetime(&later).earliest() {
+                    break e;
+                }
+            }
+        }
+    };
+
+    Ok(instant.naive_utc().timestamp_nanos())
+}
+
+fn add_months(date: NaiveDate, months: i32) -> DFResult<NaiveDate> {
+    let total = date.year() * 12 + date.month0() as i32 + months;
+    let (year, month) = (total.div_euclid(12), total.rem_euclid(12) as u32 + 1);
+    // Clamped to the last day of the month, e.g. 01-31 plus one month is 02-28
+    (1..=date.day())
+        .rev()
+        .find_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+        .ok_or_else(overflow)
+}
+
+fn shift(time: i64, interval: Interval, tz: &Tz) -> DFResult<i64> {
+    let mut time = time;
+    if interval.months != 0 || interval.days != 0 {
+        let local = tz.timestamp_nanos(time).naive_local();
+        let date = add_months(local.date(), interval.months)?
+            .checked_add_signed(Duration::days(interval.days as i64))
+            .ok_or_else(overflow)?;
+        time = from_local(&date.and_time(local.time()), tz)?;
+    }
+
+    time.checked_add(interval.nanos).ok_or_else(overflow)
+}
+
+fn bucket(interval: Interval, time: i64, tz: &Tz) -> DFResult<i64> {
+    let local = tz.timestamp_nanos(time).naive_local();
+
+    if interval.months != 0 {
+        if interval.days != 0 || interval.nanos != 0 || interval.months < 0 {
+            return Err(DataFusionError::Execution(
+                "the interval of the buckets can not mix months with days or less".to_string(),
+            ));
+        }
+        let months = (local.year() - 1970) * 12 + local.month0() as i32;
+        let start = months - months.rem_euclid(interval.months);
+        let date = NaiveDate::from_ymd_opt(
+            1970 + start.div_euclid(12),
+            start.rem_euclid(12) as u32 + 1,
+            1,
+        )
+        .ok_or_else(overflow)?;
+        return from_local(&date.and_hms_opt(0, 0, 0).ok_or_else(overflow)?, tz);
+    }
+
+    let stride = interval.days as i64 * NANOS_PER_DAY + interval.nanos;
+    if stride <= 0 {
+        return Err(DataFusionError::Execution(
+            "the interval of the buckets must be positive".to_string(),
+        ));
+    }
+    let local_nanos = local.timestamp_nanos();
+    let rem = local_nanos.rem_euclid(stride);
+    // The buckets shorter than an hour start at the same offset as the time, not
+    // affected by the local time repeated or skipped
+    if stride < NANOS_PER_HOUR {
+        return Ok(time - rem);
+    }
+    let start = local_nanos - rem;
+    let start = NaiveDateTime::from_timestamp_opt(
+        start.div_euclid(1_000_000_000),
+        start.rem_euclid(1_000_000_000) as u32,
+    )
+    .ok_or_else(overflow)?;
+
+    from_local(&start, tz)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nanos(tz: &Tz, s: &str) -> i64 {
+        let local = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap();
+        from_local(&local, tz).unwrap()
+    }
+
+    fn local(tz: &Tz, time: i64) -> String {
+        tz.timestamp_nanos(time)
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string()
+    }
+
+    const fn interval(months: i32, days: i32, nanos: i64) -> Interval {
+        Interval {
+            months,
+            days,
+            nanos,
+        }
+    }
+
+    #[test]
+    fn test_shift() {
+        let utc = Tz::UTC;
+        let t = nanos(&utc, "2022-01-31 10:00:00");
+        assert_eq!(
+            local(&utc, shift(t, interval(1, 0, 0), &utc).unwrap()),
+            "2022-02-28 10:00:00"
+        );
+        assert_eq!(
+            local(&utc, shift(t, interval(-13, 0, 0), &utc).unwrap()),
+            "2020-12-31 10:00:00"
+        );
+        assert_eq!(
+            local(
+                &utc,
+                shift(t, interval(0, 1, 300_000_000_000), &utc).unwrap()
+            ),
+            "2022-02-01 10:05:00"
+        );
+
+        // A day across the start of the daylight saving time is 23 hours long
+        let ny: Tz = "America/New_York".parse().unwrap();
+        let t = nanos(&ny, "2022-03-12 12:00:00");
+        let next = shift(t, interval(0, 1, 0), &ny).unwrap();
+        assert_eq!(local(&ny, next), "2022-03-13 12:00:00");
+        assert_eq!(next - t, 23 * 3_600_000_000_000);
+        assert_eq!(shift(next, interval(0, 1, 0).times(-1), &ny).unwrap(), t);
+    }
+
+    #[test]
+    fn test_bucket() {
+        let sh: Tz = "Asia/Shanghai".parse().unwrap();
+        let t = nanos(&sh, "2022-11-03 07:26:56");
+        assert_eq!(
+            local(&sh, bucket(interval(0, 1, 0), t, &sh).unwrap()),
+            "2022-11-03 00:00:00"
+        );
+        assert_eq!(
+            local(
+                &sh,
+                bucket(interval(0, 0, 3_600_000_000_000), t, &sh).unwrap()
+            ),
+            "2022-11-03 07:00:00"
+        );
+        assert_eq!(
+            local(&sh, bucket(interval(3, 0, 0), t, &sh).unwrap()),
+            "2022-10-01 00:00:00"
+        );
+        assert_eq!(
+            local(&Tz::UTC, bucket(interval(0, 1, 0), t, &Tz::UTC).unwrap()),
+            "2022-11-02 00:00:00"
+        );
+
+        assert!(bucket(interval(1, 1, 0), t, &sh).is_err());
+        assert!(bucket(interval(0, 0, 0), t, &sh).is_err());
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("1d"), Some(interval(0, 1, 0)));
+        assert_eq!(
+            parse_duration("1h30m"),
+            Some(interval(0, 0, 90 * 60_000_000_000))
+        );
+        assert_eq!(parse_duration("2w"), Some(interval(0, 14, 0)));
+        assert_eq!(parse_duration("3mo"), Some(interval(3, 0, 0)));
+        assert_eq!(parse_duration("10µs"), Some(interval(0, 0, 10_000)));
+        assert_eq!(parse_duration("0s"), Some(interval(0, 0, 0)));
+
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("10"), None);
+        assert_eq!(parse_duration("d"), None);
+        assert_eq!(parse_duration("1y"), None);
+    }
+
+    #[test]
+    fn test_time_window() {
+        let utc = Tz::UTC;
+        let sh: Tz = "Asia/Shanghai".parse().unwrap();
+        let day = interval(0, 1, 0);
+        let offset = interval(0, 0, 8 * NANOS_PER_HOUR);
+        let window = |time: i64, tz: &Tz| {
+            let time = shift(time, offset.times(-1), tz).unwrap();
+            shift(bucket(day, time, tz).unwrap(), offset, tz).unwrap()
+        };
+
+        let t = nanos(&utc, "2022-11-02 23:26:56");
+        assert_eq!(local(&utc, window(t, &utc)), "2022-11-02 08:00:00");
+        let t = nanos(&utc, "2022-11-02 07:26:56");
+        assert_eq!(local(&utc, window(t, &utc)), "2022-11-01 08:00:00");
+        // The buckets start at 08:00 of the local time
+        let t = nanos(&sh, "2022-11-03 07:26:56");
+        assert_eq!(local(&sh, window(t, &sh)), "2022-11-02 08:00:00");
+    }
+
+    #[test]
+    fn test_local_nanos() {
+        let sh: Tz = "Asia/Shanghai".parse().unwrap();
+        let t = nanos(&Tz::UTC, "2022-11-02 23:26:56");
+        assert_eq!(local(&Tz::UTC, local_nanos(t, &sh)), "2022-11-03 07:26:56");
+
+        // Daylight saving time of New York from 2022-03-13
+        let ny: Tz = "America/New_York".parse().unwrap();
+        let t = nanos(&Tz::UTC, "2022-03-13 12:00:00");
+        assert_eq!(local(&Tz::UTC, local_nanos(t, &ny)), "2022-03-13 08:00:00");
+        assert_eq!(local_nanos(t, &Tz::UTC), t);
+    }
+
+    #[test]
+    fn test_intervals() {
+        let array: ArrayRef = Arc::new(IntervalMonthDayNanoArray::from(vec![Some(
+            (2_i128 << 96) | (3_i128 << 64) | 4,
+        )]));
+        assert_eq!(intervals(&array).unwrap(), vec![Some(interval(2, 3, 4))]);
+
+        let array: ArrayRef = Arc::new(IntervalDayTimeArray::from(vec![
+            Some((-1_i64 << 32) | 5),
+            None,
+        ]));
+        assert_eq!(
+            intervals(&array).unwrap(),
+            vec![Some(interval(0, -1, 5_000_000)), None]
+        );
+    }
+}
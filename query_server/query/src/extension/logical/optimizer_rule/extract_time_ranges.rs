@@ -0,0 +1,330 @@
+use std::collections::HashSet;
+
+use datafusion::{
+    common::Column,
+    datasource::source_as_provider,
+    error::Result,
+    logical_expr::{
+        utils::expr_to_columns, Between, BinaryExpr, Expr, LogicalPlan, Operator, TableScan,
+    },
+    optimizer::{OptimizerConfig, OptimizerRule},
+    prelude::lit,
+    scalar::ScalarValue,
+};
+use models::schema::TIME_FIELD;
+use trace::debug;
+
+use crate::table::ClusterTable;
+
+/// Inclusive ranges of the timestamps in nanoseconds, sorted and not overlapping
+type TimeRanges = Vec<(i64, i64)>;
+
+/// Extracts the time ranges from the filters pushed down to the scans of the tskv
+/// tables and passes them to the scans as an explicit filter of the time column,
+/// `time >= a AND time <= b OR ...`, so that only the data in the time ranges is
+/// read.
+///
+/// The filters of the time are extracted through AND, OR, BETWEEN and IN lists,
+/// the filters like `time > now() - interval '1 hour'` are folded to literals
+/// before. The explicit filter is only added if any filter of the time can not
+/// be pushed down to the scan by itself.
+pub struct ExtractTimeRanges {}
+
+impl OptimizerRule for ExtractTimeRanges {
+    fn optimize(
+        &self,
+        plan: &LogicalPlan,
+        optimizer_config: &mut OptimizerConfig,
+    ) -> Result<LogicalPlan> {
+        if let LogicalPlan::TableScan(scan) = plan {
+            let is_tskv_table = source_as_provider(&scan.source)?
+                .as_any()
+                .is::<ClusterTable>();
+            if is_tskv_table {
+                if let Some(filter) = time_filter(scan)? {
+                    debug!("Extract the time ranges of {}: {}", scan.table_name, filter);
+                    let mut filters = scan.filters.clone();
+                    filters.push(filter);
+                    return Ok(LogicalPlan::TableScan(TableScan {
+                        filters,
+                        ..scan.clone()
+                    }));
+                }
+            }
+        }
+
+        datafusion::optimizer::utils::optimize_children(self, plan, optimizer_config)
+    }
+
+    fn name(&self) -> &str {
+        "extract_time_ranges"
+    }
+}
+
+/// The explicit filter of the time ranges of the scan, None if the time ranges
+/// are pushed down already
+fn time_filter(scan: &TableScan) -> Result<Option<Expr>> {
+    let mut is_pushed_down = true;
+    for filter in scan.filters.iter() {
+        let mut columns = HashSet::new();
+        expr_to_columns(filter, &mut columns)?;
+        if columns.iter().any(|e| e.name == TIME_FIELD) && !is_supported_by_domains(filter) {
+            is_pushed_down = false;
+        }
+    }
+    if is_pushed_down {
+        return Ok(None);
+    }
+
+    // The filters are conjunctive, the ones not of the time match any time
+    let time_ranges =
+        scan.filters
+            .iter()
+            .fold(vec![(i64::MIN, i64::MAX)], |ranges, e| match extract(e) {
+                Some(e) => intersect(&ranges, &e),
+                None => ranges,
+            });
+    if time_ranges.is_empty() {
+        // The filters never matched are left to be eliminated
+        return Ok(None);
+    }
+    let column = Column::new(Some(&scan.table_name), TIME_FIELD);
+    let filter = time_ranges_to_expr(&column, &time_ranges);
+
+    Ok(filter.filter(|e| !scan.filters.contains(e)))
+}
+
+/// Whether the domains of the columns of the scan are converted from the filter
+/// with no loss, see `RowExpressionToDomainsVisitor`
+fn is_supported_by_domains(expr: &Expr) -> bool {
+    match expr {
+        Expr::BinaryExpr(BinaryExpr {
+            left,
+            op: Operator::And | Operator::Or,
+            right,
+        }) => is_supported_by_domains(left) && is_supported_by_domains(right),
+        Expr::BinaryExpr(BinaryExpr { left, right, .. }) => matches!(
+            (left.as_ref(), right.as_ref()),
+            (Expr::Column(_), Expr::Literal(_)) | (Expr::Literal(_), Expr::Column(_))
+        ),
+        Expr::Column(_) | Expr::Literal(_) => true,
+        _ => false,
+    }
+}
+
+/// The time ranges the rows matching the filter are in, None if not known
+fn extract(expr: &Expr) -> Option<TimeRanges> {
+    match expr {
+        Expr::BinaryExpr(BinaryExpr { left, op, right }) => match op {
+            Operator::And => match (extract(left), extract(right)) {
+                (Some(left), Some(right)) => Some(intersect(&left, &right)),
+                (left, right) => left.or(right),
+            },
+            Operator::Or => Some(union(extract(left)?, extract(right)?)),
+            _ => match (left.as_ref(), right.as_ref()) {
+                (Expr::Column(c), Expr::Literal(v)) if c.name == TIME_FIELD => {
+                    compare(*op, timestamp_nanos(v)?)
+                }
+                (Expr::Literal(v), Expr::Column(c)) if c.name == TIME_FIELD => {
+                    compare(reverse(*op)?, timestamp_nanos(v)?)
+                }
+                _ => None,
+            },
+        },
+        Expr::Between(Between {
+            expr,
+            negated,
+            low,
+            high,
+        }) => match (expr.as_ref(), low.as_ref(), high.as_ref()) {
+            (Expr::Column(c), Expr::Literal(low), Expr::Literal(high)) if c.name == TIME_FIELD => {
+                let (low, high) = (timestamp_nanos(low)?, timestamp_nanos(high)?);
+                let between = if low <= high {
+                    vec![(low, high)]
+                } else {
+                    vec![]
+                };
+                Some(if *negated {
+                    complement(&between)
+                } else {
+                    between
+                })
+            }
+            _ => None,
+        },
+        Expr::InList {
+            expr,
+            list,
+            negated: false,
+        } => match expr.as_ref() {
+            Expr::Column(c) if c.name == TIME_FIELD => {
+                let points = list
+                    .iter()
+                    .map(|e| match e {
+                        Expr::Literal(v) => timestamp_nanos(v).map(|v| (v, v)),
+                        _ => None,
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+                Some(normalize(points))
+            }
+            _ => None,
+        },
+        Expr::Literal(ScalarValue::Boolean(Some(false))) => Some(vec![]),
+        _ => None,
+    }
+}
+
+fn timestamp_nanos(value: &ScalarValue) -> Option<i64> {
+    match value {
+        ScalarValue::TimestampSecond(Some(v), _) => v.checked_mul(1_000_000_000),
+        ScalarValue::TimestampMillisecond(Some(v), _) => v.checked_mul(1_000_000),
+        ScalarValue::TimestampMicrosecond(Some(v), _) => v.checked_mul(1_000),
+        ScalarValue::TimestampNanosecond(Some(v), _) => Some(*v),
+        _ => None,
+    }
+}
+
+fn reverse(op: Operator) -> Option<Operator> {
+    match op {
+        Operator::Eq | Operator::NotEq => Some(op),
+        Operator::Lt => Some(Operator::Gt),
+        Operator::LtEq => Some(Operator::GtEq),
+        Operator::Gt => Some(Operator::Lt),
+        Operator::GtEq => Some(Operator::LtEq),
+        _ => None,
+    }
+}
+
+/// The time ranges of `time op value`
+fn compare(op: Operator, value: i64) -> Option<TimeRanges> {
+    let ranges = match op {
+        Operator::Eq => vec![(value, value)],
+        Operator::NotEq => complement(&[(value, value)]),
+        Operator::Lt => complement(&[(value, i64::MAX)]),
+        Operator::LtEq => vec![(i64::MIN, value)],
+        Operator::Gt => complement(&[(i64::MIN, value)]),
+        Operator::GtEq => vec![(value, i64::MAX)],
+        _ => return None,
+    };
+    Some(ranges)
+}
+
+/// Sorts and merges the overlapping or adjacent ranges
+fn normalize(mut ranges: TimeRanges) -> TimeRanges {
+    ranges.retain(|(min, max)| min <= max);
+    ranges.sort_unstable();
+    let mut merged: TimeRanges = Vec::with_capacity(ranges.len());
+    for (min, max) in ranges {
+        match merged.last_mut() {
+            Some(last) if min <= last.1.saturating_add(1) => last.1 = last.1.max(max),
+            _ => merged.push((min, max)),
+        }
+    }
+    merged
+}
+
+fn union(mut a: TimeRanges, b: TimeRanges) -> TimeRanges {
+    a.extend(b);
+    normalize(a)
+}
+
+fn intersect(a: &[(i64, i64)], b: &[(i64, i64)]) -> TimeRanges {
+    let ranges = a
+        .iter()
+        .flat_map(|x| b.iter().map(move |y| (x.0.max(y.0), x.1.min(y.1))))
+        .collect();
+    normalize(ranges)
+}
+
+fn complement(ranges: &[(i64, i64)]) -> TimeRanges {
+    let mut result = vec![];
+    let mut next = Some(i64::MIN);
+    for (min, max) in ranges {
+        if let Some(start) = next {
+            if start < *min {
+                result.push((start, min - 1));
+            }
+        }
+        next = max.checked_add(1);
+    }
+    if let Some(start) = next {
+        result.push((start, i64::MAX));
+    }
+    result
+}
+
+/// `time >= a AND time <= b OR ...`, None if all the time is in the ranges
+fn time_ranges_to_expr(column: &Column, time_ranges: &[(i64, i64)]) -> Option<Expr> {
+    let time = || Expr::Column(column.clone());
+    let value = |v: i64| lit(ScalarValue::TimestampNanosecond(Some(v), None));
+    time_ranges
+        .iter()
+        .filter_map(|(min, max)| match (*min, *max) {
+            (i64::MIN, i64::MAX) => None,
+            (min, max) if min == max => Some(time().eq(value(min))),
+            (i64::MIN, max) => Some(time().lt_eq(value(max))),
+            (min, i64::MAX) => Some(time().gt_eq(value(min))),
+            (min, max) => Some(time().gt_eq(value(min)).and(time().lt_eq(value(max)))),
+        })
+        .reduce(|a, b| a.or(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::prelude::{col, in_list};
+
+    use super::*;
+
+    fn ts(v: i64) -> Expr {
+        lit(ScalarValue::TimestampNanosecond(Some(v), None))
+    }
+
+    fn between(expr: Expr, negated: bool, low: Expr, high: Expr) -> Expr {
+        Expr::Between(Between {
+            expr: Box::new(expr),
+            negated,
+            low: Box::new(low),
+            high: Box::new(high),
+        })
+    }
+
+    #[test]
+    fn test_extract() {
+        let time = || col(TIME_FIELD);
+
+        let expr = time().gt(ts(10)).and(time().lt_eq(ts(20)));
+        assert_eq!(extract(&expr), Some(vec![(11, 20)]));
+
+        let expr = between(time(), false, ts(1), ts(5)).or(time().eq(ts(7)));
+        assert_eq!(extract(&expr), Some(vec![(1, 5), (7, 7)]));
+
+        let expr = between(time(), true, ts(1), ts(5));
+        assert_eq!(extract(&expr), Some(vec![(i64::MIN, 0), (6, i64::MAX)]));
+
+        let expr = in_list(time(), vec![ts(3), ts(1), ts(2)], false).and(col("v").gt(lit(1)));
+        assert_eq!(extract(&expr), Some(vec![(1, 3)]));
+
+        // The rows of any time may match
+        let expr = time().gt(ts(10)).or(col("v").gt(lit(1)));
+        assert_eq!(extract(&expr), None);
+
+        let expr = ts(10).gt(time()).and(time().gt(ts(20)));
+        assert_eq!(extract(&expr), Some(vec![]));
+    }
+
+    #[test]
+    fn test_time_ranges_to_expr() {
+        let column = Column::new(Some("m"), TIME_FIELD);
+        let time = || Expr::Column(column.clone());
+        assert_eq!(
+            time_ranges_to_expr(&column, &[(i64::MIN, 0), (5, 5), (7, 9)]),
+            Some(
+                time()
+                    .lt_eq(ts(0))
+                    .or(time().eq(ts(5)))
+                    .or(time().gt_eq(ts(7)).and(time().lt_eq(ts(9))))
+            )
+        );
+        assert_eq!(time_ranges_to_expr(&column, &[(i64::MIN, i64::MAX)]), None);
+    }
+}
@@ -1,7 +1,9 @@
+pub mod extract_time_ranges;
 pub mod implicit_type_conversion;
 pub mod merge_limit_with_sort;
 pub mod projection_push_down;
 pub mod reject_cross_join;
 pub mod rewrite_tag_scan;
+pub mod rewrite_time_arithmetic;
 pub mod transform_bottom_func_to_topk_node;
 pub mod transform_topk_func_to_topk_node;
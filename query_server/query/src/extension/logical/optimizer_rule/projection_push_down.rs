@@ -24,6 +24,7 @@ use std::{
     sync::Arc,
 };
 
+use crate::extension::logical::plan_node::copy_to::{as_copy_to_plan_node, CopyToPlanNode};
 use crate::extension::logical::plan_node::table_writer::{
     as_table_writer_plan_node, TableWriterPlanNode,
 };
@@ -486,10 +487,14 @@ fn optimize_plan(
         | LogicalPlan::Distinct(_)
         | LogicalPlan::Extension { .. } => {
             if let LogicalPlan::Extension(Extension { node }) = plan {
-                if let Some(TableWriterPlanNode { input, .. }) =
-                    as_table_writer_plan_node(node.as_ref())
-                {
-                    // table write node need all schema fields
+                let input = as_table_writer_plan_node(node.as_ref())
+                    .map(|TableWriterPlanNode { input, .. }| input)
+                    .or_else(|| {
+                        as_copy_to_plan_node(node.as_ref())
+                            .map(|CopyToPlanNode { input, .. }| input)
+                    });
+                if let Some(input) = input {
+                    // table write and copy nodes need all schema fields
                     input.schema().fields().iter().for_each(|e| {
                         new_required_columns.insert(e.qualified_column());
                     });
@@ -0,0 +1,186 @@
+use std::sync::Arc;
+
+use datafusion::arrow::datatypes::{DataType, TimeUnit};
+use datafusion::common::DFSchemaRef;
+use datafusion::error::Result;
+use datafusion::logical_expr::expr_rewriter::{ExprRewritable, ExprRewriter};
+use datafusion::logical_expr::{
+    cast, utils, BinaryExpr, Expr, ExprSchemable, LogicalPlan, Operator,
+};
+use datafusion::optimizer::{optimizer::OptimizerRule, OptimizerConfig};
+
+use crate::extension::expr::scalar_function::time::{time_add, time_sub};
+
+/// Rewrites `time + interval`, `interval + time` and `time - interval` to
+/// `time_add` and `time_sub`, which add the months and days of the intervals in
+/// the calendar and support the columns of timestamps
+pub struct RewriteTimeArithmetic;
+
+impl OptimizerRule for RewriteTimeArithmetic {
+    fn optimize(
+        &self,
+        plan: &LogicalPlan,
+        optimizer_config: &mut OptimizerConfig,
+    ) -> Result<LogicalPlan> {
+        let new_inputs = plan
+            .inputs()
+            .iter()
+            .map(|e| self.optimize(e, optimizer_config))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut rewriter = TimeArithmeticRewriter {
+            schemas: plan.all_schemas(),
+        };
+        let expr = plan
+            .expressions()
+            .into_iter()
+            .map(|e| e.rewrite(&mut rewriter))
+            .collect::<Result<Vec<_>>>()?;
+
+        utils::from_plan(plan, &expr, &new_inputs)
+    }
+
+    fn name(&self) -> &str {
+        "rewrite_time_arithmetic"
+    }
+}
+
+struct TimeArithmeticRewriter<'a> {
+    /// input schemas
+    schemas: Vec<&'a DFSchemaRef>,
+}
+
+impl TimeArithmeticRewriter<'_> {
+    fn data_type(&self, expr: &Expr) -> Option<DataType> {
+        self.schemas
+            .iter()
+            .find_map(|schema| expr.get_type(schema).ok())
+    }
+
+    /// The timestamp in nanoseconds, the unit of the arguments of the functions
+    fn time_arg(&self, expr: &Expr) -> Option<Expr> {
+        match self.data_type(expr)? {
+            DataType::Timestamp(TimeUnit::Nanosecond, None) => Some(expr.clone()),
+            DataType::Timestamp(_, None) => Some(cast(
+                expr.clone(),
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+            )),
+            _ => None,
+        }
+    }
+
+    fn is_interval(&self, expr: &Expr) -> bool {
+        matches!(self.data_type(expr), Some(DataType::Interval(_)))
+    }
+}
+
+impl ExprRewriter for TimeArithmeticRewriter<'_> {
+    fn mutate(&mut self, expr: Expr) -> Result<Expr> {
+        let (left, op, right) = match &expr {
+            Expr::BinaryExpr(BinaryExpr { left, op, right })
+                if matches!(op, Operator::Plus | Operator::Minus) =>
+            {
+                (left, *op, right)
+            }
+            _ => return Ok(expr),
+        };
+
+        let (fun, args) = if self.is_interval(right) {
+            match self.time_arg(left) {
+                Some(time) if op == Operator::Plus => (time_add(), vec![time, *right.clone()]),
+                Some(time) => (time_sub(), vec![time, *right.clone()]),
+                None => return Ok(expr),
+            }
+        } else if op == Operator::Plus && self.is_interval(left) {
+            match self.time_arg(right) {
+                Some(time) => (time_add(), vec![time, *left.clone()]),
+                None => return Ok(expr),
+            }
+        } else {
+            return Ok(expr);
+        };
+
+        Ok(Expr::ScalarUDF {
+            fun: Arc::new(fun),
+            args,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use datafusion::common::{DFField, DFSchema};
+    use datafusion::prelude::{col, lit};
+    use datafusion::scalar::ScalarValue;
+
+    use super::*;
+    use crate::extension::expr::scalar_function::time::{TIME_ADD, TIME_SUB};
+
+    fn expr_test_schema() -> DFSchemaRef {
+        Arc::new(
+            DFSchema::new_with_metadata(
+                vec![
+                    DFField::new(
+                        None,
+                        "time",
+                        DataType::Timestamp(TimeUnit::Nanosecond, None),
+                        false,
+                    ),
+                    DFField::new(
+                        None,
+                        "ms",
+                        DataType::Timestamp(TimeUnit::Millisecond, None),
+                        false,
+                    ),
+                    DFField::new(None, "v", DataType::Int64, true),
+                ],
+                HashMap::new(),
+            )
+            .unwrap(),
+        )
+    }
+
+    fn five_minutes() -> Expr {
+        lit(ScalarValue::IntervalDayTime(Some(5 * 60 * 1000)))
+    }
+
+    fn function_name(expr: &Expr) -> Option<&str> {
+        match expr {
+            Expr::ScalarUDF { fun, .. } => Some(&fun.name),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_rewrite_time_arithmetic() {
+        let schema = expr_test_schema();
+        let mut rewriter = TimeArithmeticRewriter {
+            schemas: vec![&schema],
+        };
+
+        let cases = [
+            (col("time") + five_minutes(), Some(TIME_ADD)),
+            (five_minutes() + col("time"), Some(TIME_ADD)),
+            (col("time") - five_minutes(), Some(TIME_SUB)),
+            (col("ms") + five_minutes(), Some(TIME_ADD)),
+            (five_minutes() - col("time"), None),
+            (col("v") + lit(1_i64), None),
+        ];
+        for (expr, expected) in cases {
+            let rewritten = expr.rewrite(&mut rewriter).unwrap();
+            assert_eq!(function_name(&rewritten), expected);
+        }
+
+        // Nested in other expressions
+        let expr = col("time").gt(col("time") - five_minutes());
+        let rewritten = expr.rewrite(&mut rewriter).unwrap();
+        match rewritten {
+            Expr::BinaryExpr(BinaryExpr { right, .. }) => {
+                assert_eq!(function_name(&right), Some(TIME_SUB))
+            }
+            other => panic!("unexpected expr: {}", other),
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use std::{
+    any::Any,
+    fmt::{self, Debug},
+    sync::Arc,
+};
+
+use datafusion::{
+    common::{DFSchema, DFSchemaRef},
+    error::DataFusionError,
+    logical_expr::{utils::exprlist_to_fields, LogicalPlan, UserDefinedLogicalNode},
+    prelude::Expr,
+};
+
+use crate::data_source::file_sink::FileFormat;
+
+#[derive(Clone)]
+pub struct CopyToPlanNode {
+    /// local path or object store url
+    pub location: String,
+    pub file_format: FileFormat,
+    pub input: Arc<LogicalPlan>,
+    pub output_exprs: Vec<Expr>,
+    pub schema: DFSchemaRef,
+}
+
+impl CopyToPlanNode {
+    pub fn try_new(
+        location: String,
+        file_format: FileFormat,
+        input: Arc<LogicalPlan>,
+        output_exprs: Vec<Expr>,
+    ) -> Result<Self, DataFusionError> {
+        let schema = Arc::new(DFSchema::new_with_metadata(
+            exprlist_to_fields(&output_exprs, input.as_ref())?,
+            input.schema().metadata().clone(),
+        )?);
+
+        Ok(Self {
+            location,
+            file_format,
+            input,
+            output_exprs,
+            schema,
+        })
+    }
+}
+
+impl Debug for CopyToPlanNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_for_explain(f)
+    }
+}
+
+impl UserDefinedLogicalNode for CopyToPlanNode {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn inputs(&self) -> Vec<&LogicalPlan> {
+        vec![&self.input]
+    }
+
+    fn schema(&self) -> &DFSchemaRef {
+        &self.schema
+    }
+
+    fn expressions(&self) -> Vec<Expr> {
+        self.output_exprs.clone()
+    }
+
+    fn fmt_for_explain(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let out_exprs: Vec<String> = self.output_exprs.iter().map(|e| e.to_string()).collect();
+        write!(
+            f,
+            "CopyTo: location={}, format={}, output=[{}]",
+            self.location,
+            self.file_format,
+            out_exprs.join(",")
+        )
+    }
+
+    fn from_template(
+        &self,
+        exprs: &[Expr],
+        inputs: &[LogicalPlan],
+    ) -> Arc<dyn UserDefinedLogicalNode> {
+        debug_assert_eq!(inputs.len(), 1, "input size inconsistent");
+        Arc::new(CopyToPlanNode {
+            location: self.location.clone(),
+            file_format: self.file_format,
+            input: Arc::new(inputs[0].clone()),
+            output_exprs: exprs.to_vec(),
+            schema: self.schema.clone(),
+        })
+    }
+}
+
+pub fn as_copy_to_plan_node(node: &dyn UserDefinedLogicalNode) -> Option<&CopyToPlanNode> {
+    node.as_any().downcast_ref::<CopyToPlanNode>()
+}
@@ -0,0 +1,100 @@
+use std::{
+    any::Any,
+    fmt::{self, Debug, Display},
+    sync::Arc,
+};
+
+use datafusion::{
+    common::{DFSchemaRef, ToDFSchema},
+    error::DataFusionError,
+    logical_expr::{LogicalPlan, UserDefinedLogicalNode},
+    prelude::Expr,
+};
+
+/// The formats of `EXPLAIN (FORMAT <format>)` other than text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExplainFormat {
+    Json,
+    Graphviz,
+}
+
+impl Display for ExplainFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExplainFormat::Json => write!(f, "json"),
+            ExplainFormat::Graphviz => write!(f, "graphviz"),
+        }
+    }
+}
+
+/// Explains the optimized logical plan and the physical plan of the input
+/// in the format, the input is planned but never executed
+#[derive(Clone)]
+pub struct ExplainFormatPlanNode {
+    pub format: ExplainFormat,
+    pub input: Arc<LogicalPlan>,
+    pub schema: DFSchemaRef,
+}
+
+impl ExplainFormatPlanNode {
+    pub fn try_new(
+        format: ExplainFormat,
+        input: Arc<LogicalPlan>,
+    ) -> Result<Self, DataFusionError> {
+        let schema = LogicalPlan::explain_schema().to_dfschema_ref()?;
+
+        Ok(Self {
+            format,
+            input,
+            schema,
+        })
+    }
+}
+
+impl Debug for ExplainFormatPlanNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_for_explain(f)
+    }
+}
+
+impl UserDefinedLogicalNode for ExplainFormatPlanNode {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn inputs(&self) -> Vec<&LogicalPlan> {
+        vec![&self.input]
+    }
+
+    fn schema(&self) -> &DFSchemaRef {
+        &self.schema
+    }
+
+    fn expressions(&self) -> Vec<Expr> {
+        vec![]
+    }
+
+    fn fmt_for_explain(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ExplainFormat: format={}", self.format)
+    }
+
+    fn from_template(
+        &self,
+        exprs: &[Expr],
+        inputs: &[LogicalPlan],
+    ) -> Arc<dyn UserDefinedLogicalNode> {
+        assert_eq!(inputs.len(), 1, "input size inconsistent");
+        assert_eq!(exprs.len(), 0, "expr size inconsistent");
+        Arc::new(ExplainFormatPlanNode {
+            format: self.format,
+            input: Arc::new(inputs[0].clone()),
+            schema: self.schema.clone(),
+        })
+    }
+}
+
+pub fn as_explain_format_plan_node(
+    node: &dyn UserDefinedLogicalNode,
+) -> Option<&ExplainFormatPlanNode> {
+    node.as_any().downcast_ref::<ExplainFormatPlanNode>()
+}
@@ -0,0 +1,164 @@
+use std::sync::Arc;
+
+use datafusion::{
+    error::Result,
+    execution::context::SessionConfig,
+    logical_expr::JoinType,
+    physical_optimizer::PhysicalOptimizerRule,
+    physical_plan::{
+        coalesce_batches::CoalesceBatchesExec, coalesce_partitions::CoalescePartitionsExec,
+        expressions::Column, filter::FilterExec, hash_join::HashJoinExec,
+        projection::ProjectionExec, repartition::RepartitionExec, ExecutionPlan,
+    },
+};
+use trace::debug;
+
+use crate::extension::physical::plan_node::runtime_filter::{
+    RuntimeFilter, RuntimeFilterBuildExec, RuntimeFilterRef,
+};
+use crate::tskv_exec::TskvExec;
+
+/// Pushes the join keys of the build side of a hash join into the tskv scan of
+/// the probe side as a runtime filter, so that the series the tags of which never
+/// match are skipped, e.g. a small dimension table joined with a large table on
+/// a tag.
+///
+/// The filter is only pushed down if the rows of the probe side not matched are
+/// not returned by the join.
+pub struct PushDownRuntimeFilter {}
+
+impl PhysicalOptimizerRule for PushDownRuntimeFilter {
+    fn optimize(
+        &self,
+        plan: Arc<dyn ExecutionPlan>,
+        config: &SessionConfig,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let children = plan.children();
+        let optimized = children
+            .iter()
+            .map(|e| self.optimize(e.clone(), config))
+            .collect::<Result<Vec<_>>>()?;
+        let plan = if children
+            .iter()
+            .zip(optimized.iter())
+            .all(|(a, b)| Arc::ptr_eq(a, b))
+        {
+            plan
+        } else {
+            plan.with_new_children(optimized)?
+        };
+
+        match plan.as_any().downcast_ref::<HashJoinExec>() {
+            Some(join) => Ok(push_down(&plan, join)?.unwrap_or(plan)),
+            None => Ok(plan),
+        }
+    }
+
+    fn name(&self) -> &str {
+        "push_down_runtime_filter"
+    }
+}
+
+fn push_down(
+    plan: &Arc<dyn ExecutionPlan>,
+    join: &HashJoinExec,
+) -> Result<Option<Arc<dyn ExecutionPlan>>> {
+    let is_probe_filterable = matches!(
+        join.join_type(),
+        JoinType::Inner
+            | JoinType::Left
+            | JoinType::LeftSemi
+            | JoinType::LeftAnti
+            | JoinType::RightSemi
+    );
+    if !is_probe_filterable || *join.null_equals_null() {
+        return Ok(None);
+    }
+
+    let partitions = build_input(join.left())
+        .output_partitioning()
+        .partition_count();
+    for (build_key, probe_key) in join.on() {
+        let filter = Arc::new(RuntimeFilter::new(partitions));
+        if let Some(probe) = push_down_probe(join.right(), probe_key, &filter)? {
+            debug!(
+                "Push down the runtime filter on {} of the hash join",
+                probe_key
+            );
+            let build = wrap_build(join.left(), build_key, filter)?;
+            return Ok(Some(plan.clone().with_new_children(vec![build, probe])?));
+        }
+    }
+    Ok(None)
+}
+
+/// The nodes passing the rows of the input through, with the same schema
+fn is_pass_through(plan: &Arc<dyn ExecutionPlan>) -> bool {
+    let any = plan.as_any();
+    any.is::<RepartitionExec>()
+        || any.is::<CoalesceBatchesExec>()
+        || any.is::<CoalescePartitionsExec>()
+}
+
+/// The input of the build side all the partitions of which are read before the
+/// probe side is
+fn build_input(plan: &Arc<dyn ExecutionPlan>) -> Arc<dyn ExecutionPlan> {
+    let mut plan = plan.clone();
+    while is_pass_through(&plan) {
+        plan = plan.children()[0].clone();
+    }
+    plan
+}
+
+fn wrap_build(
+    plan: &Arc<dyn ExecutionPlan>,
+    column: &Column,
+    filter: RuntimeFilterRef,
+) -> Result<Arc<dyn ExecutionPlan>> {
+    if is_pass_through(plan) {
+        let input = wrap_build(&plan.children()[0], column, filter)?;
+        return plan.clone().with_new_children(vec![input]);
+    }
+    Ok(Arc::new(RuntimeFilterBuildExec::new(
+        plan.clone(),
+        column.clone(),
+        filter,
+    )))
+}
+
+/// The probe side with the scan of the column filtered, None if the column is not
+/// a tag scanned from tskv as is
+fn push_down_probe(
+    plan: &Arc<dyn ExecutionPlan>,
+    column: &Column,
+    filter: &RuntimeFilterRef,
+) -> Result<Option<Arc<dyn ExecutionPlan>>> {
+    let any = plan.as_any();
+    if let Some(scan) = any.downcast_ref::<TskvExec>() {
+        return Ok(scan
+            .with_runtime_filter(column.name(), filter.clone())
+            .map(|e| Arc::new(e) as Arc<dyn ExecutionPlan>));
+    }
+
+    let input_column = if is_pass_through(plan) || any.is::<FilterExec>() {
+        Some(column.clone())
+    } else if let Some(projection) = any.downcast_ref::<ProjectionExec>() {
+        // Not through a cast, the scan would compare the values of the build side with
+        // the tags before the cast, e.g. 1 with the tag '01' cast to 1
+        projection.expr()[column.index()]
+            .0
+            .as_any()
+            .downcast_ref::<Column>()
+            .cloned()
+    } else {
+        None
+    };
+
+    match input_column {
+        Some(input_column) => match push_down_probe(&plan.children()[0], &input_column, filter)? {
+            Some(input) => Ok(Some(plan.clone().with_new_children(vec![input])?)),
+            None => Ok(None),
+        },
+        None => Ok(None),
+    }
+}
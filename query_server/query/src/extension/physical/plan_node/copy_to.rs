@@ -0,0 +1,185 @@
+use async_trait::async_trait;
+use datafusion::{
+    arrow::datatypes::{Field, Schema, SchemaRef},
+    datasource::listing::ListingTableUrl,
+    error::DataFusionError,
+    execution::context::TaskContext,
+    physical_expr::PhysicalSortExpr,
+    physical_plan::{
+        metrics::{ExecutionPlanMetricsSet, MetricsSet},
+        stream::RecordBatchStreamAdapter,
+        DisplayFormatType, Distribution, ExecutionPlan, Partitioning, SendableRecordBatchStream,
+        Statistics,
+    },
+};
+use futures::TryStreamExt;
+use spi::query::AFFECTED_ROWS;
+use std::{any::Any, fmt::Debug, sync::Arc};
+
+use datafusion::error::Result;
+use trace::debug;
+
+use crate::data_source::file_sink::{FileFormat, FileRecordBatchSinkProvider};
+use crate::data_source::sink::RecordBatchSinkProvider;
+
+use super::table_writer::{do_write, TableWriterMetrics};
+
+/// Writes the output of the input plan into files, one file per partition
+pub struct CopyToExec {
+    input: Arc<dyn ExecutionPlan>,
+    location: String,
+    file_format: FileFormat,
+    /// Execution metrics
+    metrics: ExecutionPlanMetricsSet,
+
+    schema: SchemaRef,
+}
+
+impl CopyToExec {
+    pub fn new(input: Arc<dyn ExecutionPlan>, location: String, file_format: FileFormat) -> Self {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            AFFECTED_ROWS.0,
+            AFFECTED_ROWS.1,
+            false,
+        )]));
+
+        Self {
+            input,
+            location,
+            file_format,
+            metrics: ExecutionPlanMetricsSet::new(),
+            schema,
+        }
+    }
+
+    /// Local directories must exist before they can be resolved into an url
+    fn sink_provider(&self, context: &TaskContext) -> Result<FileRecordBatchSinkProvider> {
+        if !self.location.contains("://") {
+            std::fs::create_dir_all(&self.location)?;
+        }
+
+        let table_url = ListingTableUrl::parse(&self.location)?;
+        let store = context
+            .runtime_env()
+            .object_store(table_url.object_store())
+            .map_err(|e| {
+                DataFusionError::Plan(format!(
+                    "No object store available for {}: {}",
+                    self.location, e
+                ))
+            })?;
+
+        Ok(FileRecordBatchSinkProvider::new(
+            store,
+            table_url.prefix().clone(),
+            self.file_format,
+            self.input.schema(),
+        ))
+    }
+}
+
+impl Debug for CopyToExec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.fmt_as(DisplayFormatType::Default, f)
+    }
+}
+
+#[async_trait]
+impl ExecutionPlan for CopyToExec {
+    /// Return a reference to Any that can be used for downcasting
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn output_partitioning(&self) -> Partitioning {
+        self.input.output_partitioning()
+    }
+
+    fn output_ordering(&self) -> Option<&[PhysicalSortExpr]> {
+        None
+    }
+
+    fn relies_on_input_order(&self) -> bool {
+        false
+    }
+
+    fn benefits_from_input_partitioning(&self) -> bool {
+        false
+    }
+
+    fn required_child_distribution(&self) -> Distribution {
+        Distribution::UnspecifiedDistribution
+    }
+
+    fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+        vec![self.input.clone()]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(CopyToExec {
+            input: children[0].clone(),
+            location: self.location.clone(),
+            file_format: self.file_format,
+            metrics: self.metrics.clone(),
+            schema: self.schema.clone(),
+        }))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        debug!(
+            "Start CopyToExec::execute for partition {} of context session_id {} and task_id {:?}",
+            partition,
+            context.session_id(),
+            context.task_id()
+        );
+
+        let record_batch_sink = self
+            .sink_provider(&context)?
+            .create_batch_sink(&self.metrics, partition);
+        let input = self.input.execute(partition, context)?;
+
+        let metrics = TableWriterMetrics::new(&self.metrics, partition);
+
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            self.schema(),
+            futures::stream::once(do_write(
+                self.schema.clone(),
+                input,
+                record_batch_sink,
+                metrics,
+            ))
+            .try_flatten(),
+        )))
+    }
+
+    fn fmt_as(&self, t: DisplayFormatType, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match t {
+            DisplayFormatType::Default => {
+                write!(
+                    f,
+                    "CopyToExec: location={}, format={}",
+                    self.location, self.file_format
+                )
+            }
+        }
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+
+    fn statistics(&self) -> Statistics {
+        self.input.statistics()
+    }
+}
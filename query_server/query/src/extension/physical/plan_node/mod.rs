@@ -1,3 +1,5 @@
+pub mod copy_to;
+pub mod runtime_filter;
 pub mod table_writer;
 pub mod tag_scan;
 pub mod topk;
@@ -0,0 +1,428 @@
+use std::{
+    any::Any,
+    collections::hash_map::DefaultHasher,
+    collections::HashSet,
+    hash::{Hash, Hasher},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use datafusion::{
+    arrow::{
+        array::{Array, ArrayRef, StringArray},
+        compute::cast,
+        datatypes::{DataType, SchemaRef},
+        error::Result as ArrowResult,
+        record_batch::RecordBatch,
+    },
+    error::Result,
+    execution::context::TaskContext,
+    physical_expr::PhysicalSortExpr,
+    physical_plan::{
+        expressions::Column, DisplayFormatType, ExecutionPlan, Partitioning, RecordBatchStream,
+        SendableRecordBatchStream, Statistics,
+    },
+    scalar::ScalarValue,
+};
+use futures::{Stream, StreamExt};
+use models::predicate::domain::Domain;
+use parking_lot::Mutex;
+use trace::debug;
+
+/// Max number of the distinct join keys pushed down as a list of values, the tags
+/// of which are looked up in the index
+const IN_LIST_MAX_VALUES: usize = 1024;
+/// Max number of the distinct join keys of a bloom filter, no filter is built for
+/// the larger build sides which hardly skip any series
+const BLOOM_FILTER_MAX_VALUES: usize = 1 << 20;
+const BLOOM_FILTER_BITS_PER_VALUE: usize = 10;
+const BLOOM_FILTER_NUM_HASHES: u64 = 7;
+
+/// The join keys of the build side of a hash join, the rows of the probe side
+/// the keys of which are not contained never match.
+#[derive(Debug)]
+pub enum JoinKeyFilter {
+    InList(HashSet<String>),
+    Bloom(BloomFilter),
+}
+
+impl JoinKeyFilter {
+    /// False if the value is not any of the join keys, a bloom filter may give
+    /// false positives
+    pub fn contains(&self, value: &str) -> bool {
+        match self {
+            Self::InList(values) => values.contains(value),
+            Self::Bloom(filter) => filter.contains(hash_value(value)),
+        }
+    }
+
+    /// The domain of the values of the list, pushed down to the index
+    pub fn to_domain(&self) -> Option<Domain> {
+        match self {
+            Self::InList(values) => {
+                let values: Vec<ScalarValue> = values
+                    .iter()
+                    .map(|e| ScalarValue::Utf8(Some(e.clone())))
+                    .collect();
+                let values: Vec<&ScalarValue> = values.iter().collect();
+                Some(Domain::of_values(&DataType::Utf8, true, &values))
+            }
+            Self::Bloom(_) => None,
+        }
+    }
+}
+
+/// A bloom filter of the hashes of the values, at a false positive rate of about 1%
+#[derive(Debug)]
+pub struct BloomFilter {
+    bits: Vec<u64>,
+}
+
+impl BloomFilter {
+    fn new(hashes: &HashSet<u64>) -> Self {
+        let num_bits = (hashes.len() * BLOOM_FILTER_BITS_PER_VALUE)
+            .next_power_of_two()
+            .max(64);
+        let mut bits = vec![0_u64; num_bits / 64];
+        for hash in hashes {
+            for i in bit_indices(num_bits, *hash) {
+                bits[i / 64] |= 1 << (i % 64);
+            }
+        }
+        Self { bits }
+    }
+
+    fn contains(&self, hash: u64) -> bool {
+        bit_indices(self.bits.len() * 64, hash).all(|i| self.bits[i / 64] & (1 << (i % 64)) != 0)
+    }
+}
+
+/// The bits of a value, by the double hashing of the halves of its hash
+fn bit_indices(num_bits: usize, hash: u64) -> impl Iterator<Item = usize> {
+    let (h1, h2) = (hash & u32::MAX as u64, hash >> 32);
+    (0..BLOOM_FILTER_NUM_HASHES)
+        .map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % num_bits as u64) as usize)
+}
+
+fn hash_value(value: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The distinct join keys collected from a partition of the build side
+#[derive(Debug)]
+struct JoinKeyCollector {
+    /// Empty once there are more than [`IN_LIST_MAX_VALUES`] values
+    values: HashSet<String>,
+    is_in_list: bool,
+    hashes: HashSet<u64>,
+    /// Too many values or the type of the keys is not supported
+    is_disabled: bool,
+}
+
+impl Default for JoinKeyCollector {
+    fn default() -> Self {
+        Self {
+            values: HashSet::new(),
+            is_in_list: true,
+            hashes: HashSet::new(),
+            is_disabled: false,
+        }
+    }
+}
+
+impl JoinKeyCollector {
+    fn add_array(&mut self, array: &ArrayRef) {
+        if self.is_disabled {
+            return;
+        }
+        let array = match cast(array, &DataType::Utf8) {
+            Ok(array) => array,
+            Err(_) => return self.disable(),
+        };
+        let array = match array.as_any().downcast_ref::<StringArray>() {
+            Some(array) => array,
+            None => return self.disable(),
+        };
+        for value in array.iter().flatten() {
+            self.hashes.insert(hash_value(value));
+            if self.is_in_list {
+                self.values.insert(value.to_string());
+            }
+        }
+        self.check_limits();
+    }
+
+    fn merge(&mut self, other: JoinKeyCollector) {
+        self.is_disabled |= other.is_disabled;
+        if self.is_disabled {
+            return;
+        }
+        self.hashes.extend(other.hashes);
+        self.is_in_list &= other.is_in_list;
+        if self.is_in_list {
+            self.values.extend(other.values);
+        }
+        self.check_limits();
+    }
+
+    fn check_limits(&mut self) {
+        if self.hashes.len() > BLOOM_FILTER_MAX_VALUES {
+            self.disable();
+        } else if self.values.len() > IN_LIST_MAX_VALUES {
+            self.is_in_list = false;
+            self.values.clear();
+        }
+    }
+
+    fn disable(&mut self) {
+        self.is_disabled = true;
+        self.values.clear();
+        self.hashes.clear();
+    }
+
+    fn finish(self) -> Option<JoinKeyFilter> {
+        if self.is_disabled {
+            None
+        } else if self.is_in_list {
+            Some(JoinKeyFilter::InList(self.values))
+        } else {
+            Some(JoinKeyFilter::Bloom(BloomFilter::new(&self.hashes)))
+        }
+    }
+}
+
+#[derive(Debug)]
+struct BuildState {
+    /// The partitions of the build side not finished yet
+    remaining: usize,
+    keys: JoinKeyCollector,
+    is_failed: bool,
+    filter: Option<Arc<JoinKeyFilter>>,
+}
+
+/// The filter of the join keys shared by the build side and the probe side of a
+/// hash join, available once all the partitions of the build side are read.
+///
+/// The probe side is not filtered if the filter is not available when scanned.
+#[derive(Debug)]
+pub struct RuntimeFilter {
+    state: Mutex<BuildState>,
+}
+
+pub type RuntimeFilterRef = Arc<RuntimeFilter>;
+
+impl RuntimeFilter {
+    pub fn new(partitions: usize) -> Self {
+        Self {
+            state: Mutex::new(BuildState {
+                remaining: partitions,
+                keys: JoinKeyCollector::default(),
+                is_failed: false,
+                filter: None,
+            }),
+        }
+    }
+
+    /// The filter of the join keys, None if the build side is not finished yet
+    pub fn get(&self) -> Option<Arc<JoinKeyFilter>> {
+        self.state.lock().filter.clone()
+    }
+
+    fn finish_partition(&self, keys: JoinKeyCollector) {
+        let mut state = self.state.lock();
+        if state.is_failed {
+            return;
+        }
+        state.keys.merge(keys);
+        state.remaining = state.remaining.saturating_sub(1);
+        if state.remaining == 0 {
+            let keys = std::mem::take(&mut state.keys);
+            state.filter = keys.finish().map(Arc::new);
+            debug!(
+                "Runtime filter is built: {:?}",
+                state.filter.as_ref().map(|e| match e.as_ref() {
+                    JoinKeyFilter::InList(values) => format!("in list of {}", values.len()),
+                    JoinKeyFilter::Bloom(filter) =>
+                        format!("bloom of {} bits", filter.bits.len() * 64),
+                })
+            );
+        }
+    }
+
+    fn fail(&self) {
+        self.state.lock().is_failed = true;
+    }
+}
+
+/// Collects the join keys of the build side of a hash join into a [`RuntimeFilter`],
+/// the batches are passed through.
+#[derive(Debug)]
+pub struct RuntimeFilterBuildExec {
+    input: Arc<dyn ExecutionPlan>,
+    /// The join key of the build side
+    column: Column,
+    filter: RuntimeFilterRef,
+}
+
+impl RuntimeFilterBuildExec {
+    pub fn new(input: Arc<dyn ExecutionPlan>, column: Column, filter: RuntimeFilterRef) -> Self {
+        Self {
+            input,
+            column,
+            filter,
+        }
+    }
+}
+
+impl ExecutionPlan for RuntimeFilterBuildExec {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.input.schema()
+    }
+
+    fn output_partitioning(&self) -> Partitioning {
+        self.input.output_partitioning()
+    }
+
+    fn output_ordering(&self) -> Option<&[PhysicalSortExpr]> {
+        self.input.output_ordering()
+    }
+
+    fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+        vec![self.input.clone()]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(Self::new(
+            children[0].clone(),
+            self.column.clone(),
+            self.filter.clone(),
+        )))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let input = self.input.execute(partition, context)?;
+        Ok(Box::pin(RuntimeFilterBuildStream {
+            input,
+            column: self.column.index(),
+            keys: Some(JoinKeyCollector::default()),
+            filter: self.filter.clone(),
+        }))
+    }
+
+    fn fmt_as(&self, t: DisplayFormatType, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match t {
+            DisplayFormatType::Default => {
+                write!(f, "RuntimeFilterBuildExec: on={}", self.column)
+            }
+        }
+    }
+
+    fn statistics(&self) -> Statistics {
+        self.input.statistics()
+    }
+}
+
+struct RuntimeFilterBuildStream {
+    input: SendableRecordBatchStream,
+    column: usize,
+    /// Taken once the partition is finished
+    keys: Option<JoinKeyCollector>,
+    filter: RuntimeFilterRef,
+}
+
+impl Stream for RuntimeFilterBuildStream {
+    type Item = ArrowResult<RecordBatch>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let poll = self.input.poll_next_unpin(cx);
+        match &poll {
+            Poll::Ready(Some(Ok(batch))) => {
+                let column = self.column;
+                if let Some(keys) = self.keys.as_mut() {
+                    keys.add_array(batch.column(column));
+                }
+            }
+            Poll::Ready(Some(Err(_))) => self.filter.fail(),
+            Poll::Ready(None) => {
+                if let Some(keys) = self.keys.take() {
+                    self.filter.finish_partition(keys);
+                }
+            }
+            Poll::Pending => {}
+        }
+        poll
+    }
+}
+
+impl RecordBatchStream for RuntimeFilterBuildStream {
+    fn schema(&self) -> SchemaRef {
+        self.input.schema()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(values: &[&str]) -> JoinKeyCollector {
+        let mut keys = JoinKeyCollector::default();
+        let array: ArrayRef = Arc::new(StringArray::from(values.to_vec()));
+        keys.add_array(&array);
+        keys
+    }
+
+    #[test]
+    fn test_runtime_filter_in_list() {
+        let filter = RuntimeFilter::new(2);
+        filter.finish_partition(keys(&["a", "b"]));
+        assert!(filter.get().is_none());
+        filter.finish_partition(keys(&["b", "c"]));
+
+        let filter = filter.get().unwrap();
+        assert!(matches!(filter.as_ref(), JoinKeyFilter::InList(values) if values.len() == 3));
+        assert!(filter.contains("a") && filter.contains("c"));
+        assert!(!filter.contains("d"));
+        assert!(filter.to_domain().is_some());
+    }
+
+    #[test]
+    fn test_runtime_filter_bloom() {
+        let values: Vec<String> = (0..IN_LIST_MAX_VALUES * 2)
+            .map(|i| format!("host_{}", i))
+            .collect();
+        let filter = RuntimeFilter::new(1);
+        filter.finish_partition(keys(&values.iter().map(|e| e.as_str()).collect::<Vec<_>>()));
+
+        let filter = filter.get().unwrap();
+        assert!(matches!(filter.as_ref(), JoinKeyFilter::Bloom(_)));
+        assert!(values.iter().all(|e| filter.contains(e)));
+        let false_positives = (0..1000)
+            .filter(|i| filter.contains(&format!("other_{}", i)))
+            .count();
+        assert!(false_positives < 50);
+        assert!(filter.to_domain().is_none());
+    }
+
+    #[test]
+    fn test_runtime_filter_failed() {
+        let filter = RuntimeFilter::new(2);
+        filter.finish_partition(keys(&["a"]));
+        filter.fail();
+        filter.finish_partition(keys(&["b"]));
+        assert!(filter.get().is_none());
+    }
+}
@@ -183,7 +183,7 @@ impl ExecutionPlan for TableWriterExec {
     }
 }
 
-async fn do_write(
+pub(crate) async fn do_write(
     schema: SchemaRef,
     mut input: SendableRecordBatchStream,
     record_batch_sink: Box<dyn RecordBatchSink>,
@@ -205,6 +205,11 @@ async fn do_write(
         metrics.record_bytes_writed(size);
     }
 
+    record_batch_sink
+        .finish()
+        .await
+        .map_err(|e| DataFusionError::External(Box::new(e)))?;
+
     metrics.done();
 
     aggregate_statistiction(schema, metrics)
@@ -0,0 +1,63 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use datafusion::{
+    execution::context::SessionState,
+    logical_expr::{LogicalPlan, UserDefinedLogicalNode},
+    physical_plan::{displayable, planner::ExtensionPlanner, ExecutionPlan, PhysicalPlanner},
+};
+use trace::debug;
+use trace::trace;
+
+use crate::extension::{
+    logical::plan_node::copy_to::{as_copy_to_plan_node, CopyToPlanNode},
+    physical::plan_node::copy_to::CopyToExec,
+};
+
+use datafusion::error::Result;
+
+/// Physical planner for CopyTo nodes
+pub struct CopyToPlanner {}
+
+#[async_trait]
+impl ExtensionPlanner for CopyToPlanner {
+    /// Create a physical plan for an extension node
+    async fn plan_extension(
+        &self,
+        _planner: &dyn PhysicalPlanner,
+        node: &dyn UserDefinedLogicalNode,
+        _logical_inputs: &[&LogicalPlan],
+        physical_inputs: &[Arc<dyn ExecutionPlan>],
+        _session_state: &SessionState,
+    ) -> Result<Option<Arc<dyn ExecutionPlan>>> {
+        Ok(
+            if let Some(CopyToPlanNode {
+                location,
+                file_format,
+                ..
+            }) = as_copy_to_plan_node(node)
+            {
+                debug!("Input user defined logical node: CopyToPlanNode");
+                trace!("Full input user defined logical plan:\n{:?}", node);
+
+                // The directory and the object store are resolved once executed, so
+                // that planning has no side effects, e.g. by EXPLAIN
+                let result = Arc::new(CopyToExec::new(
+                    physical_inputs[0].clone(),
+                    location.clone(),
+                    *file_format,
+                ));
+
+                debug!(
+                    "After Apply CopyToPlanner. Transformed physical plan: {}",
+                    displayable(result.as_ref()).indent()
+                );
+                trace!("Full transformed physical plan:\n {:?}", result);
+
+                Some(result)
+            } else {
+                None
+            },
+        )
+    }
+}
@@ -0,0 +1,196 @@
+use std::fmt::{self, Display, Write};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use datafusion::{
+    arrow::{array::StringArray, record_batch::RecordBatch},
+    execution::context::SessionState,
+    logical_expr::{LogicalPlan, UserDefinedLogicalNode},
+    physical_plan::{
+        memory::MemoryExec, planner::ExtensionPlanner, DisplayFormatType, ExecutionPlan,
+        PhysicalPlanner,
+    },
+};
+use serde_json::{json, Value};
+use trace::debug;
+
+use crate::extension::logical::plan_node::explain_format::{
+    as_explain_format_plan_node, ExplainFormat, ExplainFormatPlanNode,
+};
+
+use datafusion::error::Result;
+
+/// Physical planner for ExplainFormat nodes
+pub struct ExplainFormatPlanner {}
+
+#[async_trait]
+impl ExtensionPlanner for ExplainFormatPlanner {
+    /// Create a physical plan for an extension node
+    async fn plan_extension(
+        &self,
+        _planner: &dyn PhysicalPlanner,
+        node: &dyn UserDefinedLogicalNode,
+        logical_inputs: &[&LogicalPlan],
+        physical_inputs: &[Arc<dyn ExecutionPlan>],
+        session_state: &SessionState,
+    ) -> Result<Option<Arc<dyn ExecutionPlan>>> {
+        let format = match as_explain_format_plan_node(node) {
+            Some(ExplainFormatPlanNode { format, .. }) => *format,
+            None => return Ok(None),
+        };
+        debug!("Input user defined logical node: ExplainFormatPlanNode");
+
+        // The physical optimizers are applied to the whole plan after planning,
+        // the input explained must be optimized here as it is never executed
+        let physical_plan = session_state
+            .physical_optimizers
+            .iter()
+            .try_fold(physical_inputs[0].clone(), |plan, rule| {
+                rule.optimize(plan, &session_state.config)
+            })?;
+
+        let logical_tree = PlanTree::from_logical(logical_inputs[0]);
+        let physical_tree = PlanTree::from_physical(physical_plan.as_ref());
+
+        let schema = LogicalPlan::explain_schema();
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec!["logical_plan", "physical_plan"])),
+                Arc::new(StringArray::from(vec![
+                    logical_tree.render(format, "logical_plan"),
+                    physical_tree.render(format, "physical_plan"),
+                ])),
+            ],
+        )?;
+
+        Ok(Some(Arc::new(MemoryExec::try_new(
+            &[vec![batch]],
+            schema,
+            None,
+        )?)))
+    }
+}
+
+/// The nodes of a plan with their one line descriptions
+#[derive(Debug, PartialEq)]
+struct PlanTree {
+    name: String,
+    children: Vec<PlanTree>,
+}
+
+impl PlanTree {
+    /// The descriptions of the table scans include the filters pushed down
+    fn from_logical(plan: &LogicalPlan) -> Self {
+        Self {
+            name: plan.display().to_string(),
+            children: plan.inputs().into_iter().map(Self::from_logical).collect(),
+        }
+    }
+
+    /// The descriptions of the tskv scans include the predicates pushed down
+    fn from_physical(plan: &dyn ExecutionPlan) -> Self {
+        struct Node<'a>(&'a dyn ExecutionPlan);
+
+        impl Display for Node<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.0.fmt_as(DisplayFormatType::Default, f)
+            }
+        }
+
+        Self {
+            name: Node(plan).to_string(),
+            children: plan
+                .children()
+                .iter()
+                .map(|e| Self::from_physical(e.as_ref()))
+                .collect(),
+        }
+    }
+
+    fn render(&self, format: ExplainFormat, graph_name: &str) -> String {
+        match format {
+            ExplainFormat::Json => self.to_json().to_string(),
+            ExplainFormat::Graphviz => self.to_graphviz(graph_name),
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "name": self.name,
+            "children": self.children.iter().map(|e| e.to_json()).collect::<Vec<_>>(),
+        })
+    }
+
+    /// The nodes are numbered in pre-order, the edges point from the parents to the children
+    fn to_graphviz(&self, graph_name: &str) -> String {
+        fn write_node(tree: &PlanTree, next_id: &mut usize, out: &mut String) {
+            let id = *next_id;
+            *next_id += 1;
+            let label = tree
+                .name
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            let _ = writeln!(out, "  {} [shape=box label=\"{}\"]", id, label);
+            for child in &tree.children {
+                let _ = writeln!(out, "  {} -> {}", id, *next_id);
+                write_node(child, next_id, out);
+            }
+        }
+
+        let mut out = format!("digraph {} {{\n", graph_name);
+        write_node(self, &mut 0, &mut out);
+        out.push('}');
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree() -> PlanTree {
+        PlanTree {
+            name: "Projection: m.f0".to_string(),
+            children: vec![PlanTree {
+                name: "TableScan: m projection=[f0], partial_filters=[m.t0 = Utf8(\"a\")]"
+                    .to_string(),
+                children: vec![],
+            }],
+        }
+    }
+
+    #[test]
+    fn test_plan_tree_to_json() {
+        assert_eq!(
+            tree().render(ExplainFormat::Json, "logical_plan"),
+            r#"{"children":[{"children":[],"name":"TableScan: m projection=[f0], partial_filters=[m.t0 = Utf8(\"a\")]"}],"name":"Projection: m.f0"}"#
+        );
+    }
+
+    #[test]
+    fn test_plan_tree_to_graphviz() {
+        assert_eq!(
+            tree().render(ExplainFormat::Graphviz, "logical_plan"),
+            "digraph logical_plan {\n  \
+            0 [shape=box label=\"Projection: m.f0\"]\n  \
+            0 -> 1\n  \
+            1 [shape=box label=\"TableScan: m projection=[f0], partial_filters=[m.t0 = Utf8(\\\"a\\\")]\"]\n\
+            }"
+        );
+    }
+
+    #[test]
+    fn test_plan_tree_from_logical() {
+        let plan = datafusion::logical_expr::LogicalPlanBuilder::empty(false)
+            .limit(0, Some(1))
+            .unwrap()
+            .build()
+            .unwrap();
+        let tree = PlanTree::from_logical(&plan);
+        assert_eq!(tree.name, plan.display().to_string());
+        assert_eq!(tree.children.len(), 1);
+        assert!(tree.children[0].children.is_empty());
+    }
+}
@@ -1,4 +1,6 @@
 //! logical paln to physical plan transform rule
+pub mod copy_to;
+pub mod explain_format;
 pub mod table_writer;
 pub mod tag_scan;
 pub mod topk;
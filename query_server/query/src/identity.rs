@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use config::{LdapConfig, OidcConfig};
+use ldap3::{ldap_escape, LdapConnAsync, LdapConnSettings, Scope, SearchEntry};
+use parking_lot::Mutex;
+use sha2::{Digest, Sha256};
+
+use crate::auth::{JwtValidator, JwtValidatorRef};
+
+pub type IdentityProviderRef = Arc<dyn IdentityProvider>;
+
+/// User authenticated by an external identity provider, and the groups of it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExternalIdentity {
+    pub user: String,
+    pub groups: Vec<String>,
+}
+
+/// Authenticates the users not in the database, the groups of the users are
+/// mapped to the roles granted to them
+#[async_trait]
+pub trait IdentityProvider: Send + Sync {
+    /// Such as `ldap`, the provider of the users authenticated by it
+    fn name(&self) -> &str;
+
+    /// `None` if the provider does not authenticate the users by passwords
+    async fn authenticate_password(
+        &self,
+        _user: &str,
+        _password: &str,
+    ) -> Option<Result<ExternalIdentity, String>> {
+        None
+    }
+
+    /// `None` if the provider does not authenticate the users by tokens
+    fn authenticate_token(&self, _token: &str) -> Option<Result<ExternalIdentity, String>> {
+        None
+    }
+}
+
+/// The most users kept authenticated, the expired ones are evicted once reached
+const MAX_AUTHENTICATED: usize = 4096;
+
+/// Searches the user by the bind user, then binds as the user found by the password
+pub struct LdapProvider {
+    config: LdapConfig,
+    /// The users authenticated recently by the digest of the name and the password,
+    /// as the clients send the password with each of their requests
+    authenticated: Mutex<HashMap<[u8; 32], (ExternalIdentity, Instant)>>,
+}
+
+impl LdapProvider {
+    pub fn new(config: &LdapConfig) -> Self {
+        Self {
+            config: config.clone(),
+            authenticated: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn cache_key(user: &str, password: &str) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(user.as_bytes());
+        hasher.update([0]);
+        hasher.update(password.as_bytes());
+        hasher.finalize().into()
+    }
+
+    fn cached(&self, key: &[u8; 32]) -> Option<ExternalIdentity> {
+        let ttl = Duration::from_secs(self.config.cache_ttl_secs);
+        match self.authenticated.lock().get(key) {
+            Some((identity, authenticated)) if authenticated.elapsed() < ttl => {
+                Some(identity.clone())
+            }
+            _ => None,
+        }
+    }
+
+    fn cache(&self, key: [u8; 32], identity: ExternalIdentity) {
+        let ttl = Duration::from_secs(self.config.cache_ttl_secs);
+        if ttl.is_zero() {
+            return;
+        }
+        let mut authenticated = self.authenticated.lock();
+        if authenticated.len() >= MAX_AUTHENTICATED {
+            authenticated.retain(|_, (_, e)| e.elapsed() < ttl);
+            if authenticated.len() >= MAX_AUTHENTICATED {
+                authenticated.clear();
+            }
+        }
+        authenticated.insert(key, (identity, Instant::now()));
+    }
+
+    async fn authenticate(&self, user: &str, password: &str) -> Result<ExternalIdentity, String> {
+        // An empty password is an unauthenticated bind, which always succeeds
+        if password.is_empty() {
+            return Err("empty password".to_string());
+        }
+        let config = &self.config;
+        let settings = LdapConnSettings::new()
+            .set_starttls(config.starttls)
+            .set_conn_timeout(Duration::from_millis(config.timeout_ms));
+        let (conn, mut ldap) = LdapConnAsync::with_settings(settings, &config.url)
+            .await
+            .map_err(|e| e.to_string())?;
+        ldap3::drive!(conn);
+
+        ldap.simple_bind(&config.bind_dn, &config.bind_password)
+            .await
+            .and_then(|e| e.success())
+            .map_err(|e| format!("failed to bind as {}: {}", config.bind_dn, e))?;
+        let filter = config.user_filter.replace("{username}", &ldap_escape(user));
+        let (entries, _) = ldap
+            .search(
+                &config.user_base_dn,
+                Scope::Subtree,
+                &filter,
+                vec![config.group_attribute.as_str()],
+            )
+            .await
+            .and_then(|e| e.success())
+            .map_err(|e| e.to_string())?;
+        let mut entries = entries.into_iter();
+        let entry = match (entries.next(), entries.next()) {
+            (Some(entry), None) => SearchEntry::construct(entry),
+            (None, _) => return Err(format!("user {} not found", user)),
+            _ => return Err(format!("user {} is not unique", user)),
+        };
+
+        ldap.simple_bind(&entry.dn, password)
+            .await
+            .and_then(|e| e.success())
+            .map_err(|_| format!("invalid password of user {}", user))?;
+        let _ = ldap.unbind().await;
+
+        Ok(ExternalIdentity {
+            user: user.to_string(),
+            groups: entry
+                .attrs
+                .get(&config.group_attribute)
+                .cloned()
+                .unwrap_or_default(),
+        })
+    }
+}
+
+#[async_trait]
+impl IdentityProvider for LdapProvider {
+    fn name(&self) -> &str {
+        "ldap"
+    }
+
+    async fn authenticate_password(
+        &self,
+        user: &str,
+        password: &str,
+    ) -> Option<Result<ExternalIdentity, String>> {
+        let key = Self::cache_key(user, password);
+        if let Some(identity) = self.cached(&key) {
+            return Some(Ok(identity));
+        }
+
+        // A server not responding must not hold the connections of the clients
+        let timeout = Duration::from_millis(self.config.timeout_ms);
+        let res = match tokio::time::timeout(timeout, self.authenticate(user, password)).await {
+            Ok(res) => res,
+            Err(_) => Err(format!("timed out after {:?}", timeout)),
+        };
+        if let Ok(identity) = &res {
+            self.cache(key, identity.clone());
+        }
+        Some(res)
+    }
+}
+
+/// Validates the ID tokens of the issuer
+pub struct OidcProvider {
+    validator: JwtValidatorRef,
+    groups_claim: String,
+    user_claim: String,
+}
+
+impl OidcProvider {
+    /// The keys of the issuer are fetched periodically
+    pub fn start(config: &OidcConfig) -> Self {
+        let validator = Arc::new(JwtValidator::discovered(config));
+        validator.start();
+        Self {
+            validator,
+            groups_claim: config.groups_claim.clone(),
+            user_claim: config.user_claim.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl IdentityProvider for OidcProvider {
+    fn name(&self) -> &str {
+        "oidc"
+    }
+
+    fn authenticate_token(&self, token: &str) -> Option<Result<ExternalIdentity, String>> {
+        let claims = match self.validator.validate_claims(token) {
+            Ok(claims) => claims,
+            Err(e) => return Some(Err(e)),
+        };
+        let user = match claims.get(&self.user_claim) {
+            Some(serde_json::Value::String(user)) => user.clone(),
+            _ => return Some(Err(format!("missing claim {}", self.user_claim))),
+        };
+        // A single group might not be an array
+        let groups = match claims.get(&self.groups_claim) {
+            Some(serde_json::Value::Array(groups)) => groups
+                .iter()
+                .filter_map(|e| e.as_str().map(|e| e.to_string()))
+                .collect(),
+            Some(serde_json::Value::String(group)) => vec![group.clone()],
+            _ => vec![],
+        };
+        Some(Ok(ExternalIdentity { user, groups }))
+    }
+}
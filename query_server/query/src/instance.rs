@@ -1,34 +1,217 @@
 use std::sync::Arc;
 
 use async_trait::async_trait;
+use config::SettingsRef;
+use coordinator::service::CoordinatorRef;
+use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::datasource::object_store::ObjectStoreRegistry;
+use datafusion::execution::memory_manager::MemoryManagerConfig;
+use datafusion::execution::runtime_env::{RuntimeConfig, RuntimeEnv};
 use datafusion::scheduler::Scheduler;
+use datafusion::sql::TableReference;
+use models::auth::{hash_api_token, hash_password, verify_password, API_TOKEN_PREFIX};
+use models::schema::TableSchema;
+use models::Timestamp;
+use object_store::local::LocalFileSystem;
 use spi::{
-    query::{dispatcher::QueryDispatcher, session::IsiphoSessionCtxFactory},
-    server::dbms::DatabaseManagerSystem,
+    catalog::{MetaDataRef, MetadataError},
+    query::{dispatcher::QueryDispatcher, session::IsiphoSessionCtxFactory, QueryError},
+    server::dbms::{DatabaseManagerSystem, WrittenPoints},
     server::BuildSnafu,
-    server::Result,
     server::{LoadFunctionSnafu, MetaDataSnafu, QuerySnafu},
-    service::protocol::{Query, QueryHandle, QueryId},
+    server::{Result, ServerError},
+    service::protocol::{Query, QueryHandle, QueryId, UserInfo},
 };
+use tokio::sync::Mutex;
+use trace::{debug, warn};
 
 use tskv::kv_option::Options;
 
+use crate::audit::AuditLogRef;
+use crate::auth::{JwtValidator, JwtValidatorRef, LocalUserStore, UserStoreRef};
+use crate::column_conversion::ColumnConversions;
+use crate::connector::StreamSourceManager;
+use crate::data_source::cloud_store::CloudObjectStoreProvider;
+use crate::data_source::decompress_store::DecompressObjectStore;
 use crate::dispatcher::manager::SimpleQueryDispatcherBuilder;
 use crate::extension::expr::load_all_functions;
 use crate::function::simple_func_manager::SimpleFunctionMetadataManager;
-use crate::metadata::LocalCatalogMeta;
+use crate::identity::{ExternalIdentity, IdentityProviderRef, LdapProvider, OidcProvider};
+use crate::metadata::{LocalCatalogMeta, RemoteCatalogMeta};
+use crate::session::{SessionRegistry, SessionRegistryRef};
+use crate::shards;
 use crate::sql::optimizer::CascadeOptimizerBuilder;
 use crate::sql::parser::DefaultParser;
+use crate::task::store::{LocalTaskStore, RemoteTaskStore, TaskStoreRef};
+use crate::task::{TaskManager, TaskManagerRef, TaskRunLog};
+use crate::utils::point_util::{cast_record_batch_to_table, record_batch_to_points_flat_buffer};
+use crate::write_schema;
 use snafu::ResultExt;
-use tskv::engine::EngineRef;
 
 pub struct Cnosdbms {
     // query dispatcher & query execution
     query_dispatcher: Arc<dyn QueryDispatcher>,
+    meta: MetaDataRef,
+    auth_enabled: bool,
+    jwt: Option<JwtValidatorRef>,
+    /// Authenticate the users not in the database
+    providers: Vec<IdentityProviderRef>,
+    /// Serializes the creation of the schema on write
+    write_schema_lock: Mutex<()>,
+    tasks: TaskManagerRef,
+    sessions: SessionRegistryRef,
+}
+
+impl Drop for Cnosdbms {
+    fn drop(&mut self) {
+        self.tasks.stop();
+    }
+}
+
+impl Cnosdbms {
+    /// The sessions of the clients connected to the server
+    pub fn sessions(&self) -> SessionRegistryRef {
+        self.sessions.clone()
+    }
+
+    /// The user authenticated by the provider is created as it first connects,
+    /// the roles of it are those the groups of it are mapped to. The user is saved
+    /// in the background, the authentication does not wait for the meta service
+    fn external_user(
+        &self,
+        provider: &str,
+        identity: ExternalIdentity,
+    ) -> Result<models::meta_data::UserInfo> {
+        let roles = self
+            .meta
+            .roles()
+            .context(MetaDataSnafu)?
+            .into_iter()
+            .filter(|e| e.groups.iter().any(|g| identity.groups.contains(g)))
+            .map(|e| e.name)
+            .collect::<Vec<_>>();
+
+        let (user, created) = match self.meta.user(&identity.user).context(MetaDataSnafu)? {
+            Some(user) if user.provider.as_deref() != Some(provider) => {
+                return Err(ServerError::Auth {
+                    reason: format!("user {} is not of provider {}", user.name, provider),
+                })
+            }
+            Some(user) if user.roles == roles => return Ok(user),
+            Some(mut user) => {
+                user.roles = roles;
+                (user, false)
+            }
+            None => {
+                let user = models::meta_data::UserInfo {
+                    name: identity.user,
+                    is_admin: false,
+                    password_hash: String::new(),
+                    tokens: vec![],
+                    roles,
+                    provider: Some(provider.to_string()),
+                };
+                (user, true)
+            }
+        };
+
+        let meta = self.meta.clone();
+        let saved = user.clone();
+        tokio::spawn(async move {
+            let name = saved.name.clone();
+            let res = match created {
+                true => meta.create_user(saved).await,
+                false => meta.alter_user(saved).await,
+            };
+            match res {
+                // Created by another node of the cluster
+                Ok(_) | Err(MetadataError::UserAlreadyExists { .. }) => {}
+                Err(e) => warn!("Failed to save the user {}: {}", name, e),
+            }
+        });
+        Ok(user)
+    }
 }
 
 #[async_trait]
 impl DatabaseManagerSystem for Cnosdbms {
+    async fn authenticate(&self, user_info: &UserInfo) -> Result<()> {
+        if !self.auth_enabled {
+            return Ok(());
+        }
+        let auth_err = || ServerError::Auth {
+            reason: format!("invalid password of user {}", user_info.user),
+        };
+        let user = self.meta.user(&user_info.user).context(MetaDataSnafu)?;
+        if let Some(user) = &user {
+            if user.provider.is_none() {
+                return match verify_password(&user_info.password, &user.password_hash) {
+                    true => Ok(()),
+                    false => Err(auth_err()),
+                };
+            }
+        }
+
+        // The users of the providers, and those not in the database
+        for provider in &self.providers {
+            if matches!(&user, Some(user) if user.provider.as_deref() != Some(provider.name())) {
+                continue;
+            }
+            match provider
+                .authenticate_password(&user_info.user, &user_info.password)
+                .await
+            {
+                Some(Ok(identity)) => {
+                    self.external_user(provider.name(), identity)?;
+                    return Ok(());
+                }
+                Some(Err(e)) => debug!(
+                    "User {} is not authenticated by {}: {}",
+                    user_info.user,
+                    provider.name(),
+                    e
+                ),
+                None => {}
+            }
+        }
+        Err(auth_err())
+    }
+
+    fn authenticate_token(&self, token: &str) -> Result<UserInfo> {
+        let auth_err = |reason: String| ServerError::Auth { reason };
+        let users = self.meta.users().context(MetaDataSnafu)?;
+        let user = if token.starts_with(API_TOKEN_PREFIX) {
+            let token_hash = hash_api_token(token);
+            users
+                .into_iter()
+                .find(|e| e.tokens.iter().any(|t| t.token_hash == token_hash))
+                .ok_or_else(|| auth_err("invalid api token".to_string()))?
+        } else if let Some(name) = self.jwt.as_ref().and_then(|e| e.validate(token).ok()) {
+            users
+                .into_iter()
+                .find(|e| e.name == name)
+                .ok_or_else(|| auth_err(format!("user {} of the jwt not exists", name)))?
+        } else {
+            let mut identity = None;
+            for provider in &self.providers {
+                if let Some(Ok(e)) = provider.authenticate_token(token) {
+                    identity = Some((provider, e));
+                    break;
+                }
+            }
+            match identity {
+                Some((provider, identity)) => self.external_user(provider.name(), identity)?,
+                None => return Err(auth_err("invalid jwt".to_string())),
+            }
+        };
+
+        Ok(UserInfo {
+            user: user.name,
+            password: String::new(),
+        })
+    }
+
     async fn execute(&self, query: &Query) -> Result<QueryHandle> {
         let id = self.query_dispatcher.create_query_id();
 
@@ -41,6 +224,85 @@ impl DatabaseManagerSystem for Cnosdbms {
         Ok(QueryHandle::new(id, query.clone(), result))
     }
 
+    async fn resolve_schema_on_write(
+        &self,
+        tenant: &str,
+        points: Vec<u8>,
+        now: i64,
+    ) -> Result<WrittenPoints> {
+        let meta = self.meta.with_catalog(tenant);
+        let written = write_schema::resolve_points(&meta, points, now)?;
+        let tables = write_schema::written_tables(&written.points)?;
+        write_schema::create_written_schema(&meta, &self.write_schema_lock, &tables).await?;
+        Ok(written)
+    }
+
+    fn record_batch_to_points(
+        &self,
+        tenant: &str,
+        database: &str,
+        table: &str,
+        batch: &RecordBatch,
+    ) -> Result<Vec<u8>> {
+        let write_err = |reason: String| ServerError::RecordBatchWrite {
+            table: table.to_string(),
+            reason,
+        };
+        let schema = match self
+            .meta
+            .with_catalog(tenant)
+            .with_database(database)
+            .table(TableReference::from(table))
+            .context(MetaDataSnafu)?
+        {
+            TableSchema::TsKvTableSchema(schema) => schema,
+            _ => return Err(write_err("the table is not stored in tskv".to_string())),
+        };
+        let batch =
+            cast_record_batch_to_table(batch, &schema).map_err(|e| write_err(e.to_string()))?;
+        record_batch_to_points_flat_buffer(&batch, schema).map_err(|e| write_err(e.to_string()))
+    }
+
+    fn is_admin(&self, user: &str) -> Result<bool> {
+        let user = self.meta.user(user).context(MetaDataSnafu)?;
+        Ok(matches!(user, Some(user) if user.is_admin))
+    }
+
+    fn database_names(&self, tenant: &str) -> Result<Vec<String>> {
+        self.meta
+            .with_catalog(tenant)
+            .database_names()
+            .context(MetaDataSnafu)
+    }
+
+    fn table_names(&self, tenant: &str, database: &str) -> Result<Vec<String>> {
+        self.meta
+            .with_catalog(tenant)
+            .show_tables(&Some(database.to_string()))
+            .context(MetaDataSnafu)
+    }
+
+    fn table_schema(&self, tenant: &str, database: &str, table: &str) -> Result<SchemaRef> {
+        let schema = self
+            .meta
+            .with_catalog(tenant)
+            .with_database(database)
+            .table(TableReference::from(table))
+            .context(MetaDataSnafu)?;
+        Ok(match schema {
+            TableSchema::TsKvTableSchema(schema) => schema.to_arrow_schema(),
+            TableSchema::ExternalTableSchema(schema) => Arc::new(schema.schema),
+        })
+    }
+
+    fn time_partitions(&self, tenant: &str, database: &str) -> Result<Vec<(Timestamp, Timestamp)>> {
+        let meta = self.meta.with_catalog(tenant);
+        shards::time_partitions(meta.as_ref(), database).map_err(|e| ServerError::Partition {
+            database: database.to_string(),
+            reason: e.to_string(),
+        })
+    }
+
     fn metrics(&self) -> String {
         let infos = self.query_dispatcher.running_query_infos();
         let status = self.query_dispatcher.running_query_status();
@@ -65,19 +327,101 @@ impl DatabaseManagerSystem for Cnosdbms {
     }
 }
 
-pub fn make_cnosdbms(engine: EngineRef, options: Options) -> Result<Cnosdbms> {
+pub async fn make_cnosdbms(
+    coord: CoordinatorRef,
+    options: Options,
+    audit_log: AuditLogRef,
+    settings: SettingsRef,
+) -> Result<Cnosdbms> {
     // todo: add query config
-    // for now only support local mode
     let mut function_manager = SimpleFunctionMetadataManager::default();
     load_all_functions(&mut function_manager).context(LoadFunctionSnafu)?;
 
-    let meta = Arc::new(
-        LocalCatalogMeta::new_with_default(engine, Arc::new(function_manager))
+    // The metadata is owned by the meta service in a cluster
+    let cluster = coord.connections().map(|e| (coord.node_id(), e.meta()));
+    // Every node runs the stream sources created on it
+    let stream_sources = Arc::new(
+        StreamSourceManager::open(coord.clone(), &options.storage.path)
+            .map_err(|e| MetadataError::InternalError {
+                error_msg: format!("failed to open stream sources: {}", e),
+            })
             .context(MetaDataSnafu)?,
     );
+    let conversions = ColumnConversions::open(coord.clone(), &options.storage.path)
+        .map_err(|e| MetadataError::InternalError {
+            error_msg: format!("failed to open column conversions: {}", e),
+        })
+        .context(MetaDataSnafu)?;
+    let (meta, task_store): (MetaDataRef, TaskStoreRef) = match &cluster {
+        None => {
+            let users: UserStoreRef = Arc::new(
+                LocalUserStore::open(&options.storage.path)
+                    .map_err(|e| MetadataError::InternalError {
+                        error_msg: format!("failed to open users: {}", e),
+                    })
+                    .context(MetaDataSnafu)?,
+            );
+            let tasks: TaskStoreRef = Arc::new(
+                LocalTaskStore::open(&options.storage.path)
+                    .map_err(|e| MetadataError::InternalError {
+                        error_msg: format!("failed to open tasks: {}", e),
+                    })
+                    .context(MetaDataSnafu)?,
+            );
+            let meta = LocalCatalogMeta::new_with_default(
+                coord,
+                Arc::new(function_manager),
+                stream_sources,
+                conversions,
+                users,
+                tasks.clone(),
+            )
+            .await
+            .context(MetaDataSnafu)?;
+            (Arc::new(meta), tasks)
+        }
+        Some((_, client)) => {
+            let tasks: TaskStoreRef = RemoteTaskStore::open(client.clone())
+                .await
+                .context(MetaDataSnafu)?;
+            let meta = RemoteCatalogMeta::new_with_default(
+                coord,
+                Arc::new(function_manager),
+                stream_sources,
+                conversions,
+                tasks.clone(),
+            )
+            .await
+            .context(MetaDataSnafu)?;
+            (Arc::new(meta), tasks)
+        }
+    };
+
+    let object_store_registry = ObjectStoreRegistry::new_with_provider(Some(Arc::new(
+        CloudObjectStoreProvider::new(options.query.object_store.clone()),
+    )));
+    // Local zstd files are decompressed by the store, the cloud ones by the provider
+    object_store_registry.register_store(
+        "file",
+        "",
+        Arc::new(DecompressObjectStore::new(Arc::new(LocalFileSystem::new()))),
+    );
+    let mut runtime_config =
+        RuntimeConfig::new().with_object_store_registry(Arc::new(object_store_registry));
+    // The queries are limited to their share of the memory budget
+    let query_memory_limit = settings.config().memory.query_limit();
+    if query_memory_limit > 0 {
+        let memory_manager = MemoryManagerConfig::try_new_limit(query_memory_limit as usize, 1.0)
+            .map_err(|e| QueryError::BuildQueryDispatcher { err: e.to_string() })
+            .context(BuildSnafu)?;
+        runtime_config = runtime_config.with_memory_manager(memory_manager);
+    }
+    let runtime = RuntimeEnv::new(runtime_config)
+        .map_err(|e| QueryError::BuildQueryDispatcher { err: e.to_string() })
+        .context(BuildSnafu)?;
 
     // TODO session config need load global system config
-    let session_factory = Arc::new(IsiphoSessionCtxFactory::default());
+    let session_factory = Arc::new(IsiphoSessionCtxFactory::new(Arc::new(runtime)));
     let parser = Arc::new(DefaultParser::default());
     let optimizer = Arc::new(CascadeOptimizerBuilder::default().build());
     // TODO wrap, and num_threads configurable
@@ -85,31 +429,112 @@ pub fn make_cnosdbms(engine: EngineRef, options: Options) -> Result<Cnosdbms> {
 
     let queries_limit = options.query.max_server_connections;
 
+    let security = settings.config().security.clone();
+    create_admin(&meta, &security.admin_user, &security.admin_password)
+        .await
+        .context(MetaDataSnafu)?;
+    let jwt = match &security.jwt {
+        Some(config) => {
+            let jwt = Arc::new(JwtValidator::new(config).map_err(|e| ServerError::Auth {
+                reason: format!("failed to load jwks: {}", e),
+            })?);
+            jwt.start();
+            Some(jwt)
+        }
+        None => None,
+    };
+    let mut providers: Vec<IdentityProviderRef> = vec![];
+    if let Some(config) = &security.ldap {
+        providers.push(Arc::new(LdapProvider::new(config)));
+    }
+    if let Some(config) = &security.oidc {
+        providers.push(Arc::new(OidcProvider::start(config)));
+    }
+
+    let task_runs = Arc::new(TaskRunLog::with_store(task_store.clone()));
+    let sessions = Arc::new(SessionRegistry::new(&settings.config().query));
     let simple_query_dispatcher = SimpleQueryDispatcherBuilder::default()
-        .with_metadata(meta)
+        .with_metadata(meta.clone())
         .with_session_factory(session_factory)
         .with_parser(parser)
         .with_optimizer(optimizer)
         .with_scheduler(scheduler)
         .with_queries_limit(queries_limit)
+        .with_plan_cache_capacity(options.query.plan_cache_capacity)
+        .with_audit_log(audit_log)
+        .with_task_runs(task_runs.clone())
+        .with_sessions(sessions.clone())
+        .with_settings(settings)
         .build()
         .context(BuildSnafu)?;
+    let query_dispatcher: Arc<dyn QueryDispatcher> = Arc::new(simple_query_dispatcher);
+
+    let tasks = Arc::new(TaskManager::new(
+        task_store,
+        meta.clone(),
+        query_dispatcher.clone(),
+        task_runs,
+        cluster,
+    ));
+    tasks.start();
 
     Ok(Cnosdbms {
-        query_dispatcher: Arc::new(simple_query_dispatcher),
+        query_dispatcher,
+        meta,
+        auth_enabled: security.auth_enabled,
+        jwt,
+        providers,
+        write_schema_lock: Mutex::new(()),
+        tasks,
+        sessions,
     })
 }
 
+/// Creates the admin of the configuration if there is no user, the admin
+/// creates the other users
+async fn create_admin(
+    meta: &MetaDataRef,
+    name: &str,
+    password: &str,
+) -> std::result::Result<(), MetadataError> {
+    if !meta.users()?.is_empty() {
+        return Ok(());
+    }
+    if password.is_empty() {
+        warn!("The password of the admin user {} is empty", name);
+    }
+    let admin = models::meta_data::UserInfo {
+        name: name.to_string(),
+        is_admin: true,
+        password_hash: hash_password(password),
+        tokens: vec![],
+        roles: vec![],
+        provider: None,
+    };
+    match meta.create_user(admin).await {
+        // Created by another node of the cluster
+        Ok(_) | Err(MetadataError::UserAlreadyExists { .. }) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use chrono::Utc;
-    use config::get_config;
+    use config::{get_config, Settings};
     use std::ops::DerefMut;
+    use std::path::Path;
     use trace::debug;
 
     use super::*;
+    use crate::audit::AuditLog;
+    use crate::identity::IdentityProvider;
+    use coordinator::service::LocalCoordinator;
     use datafusion::arrow::{
-        datatypes::Schema, record_batch::RecordBatch, util::pretty::pretty_format_batches,
+        array::{BooleanArray, StringArray, UInt64Array},
+        datatypes::Schema,
+        record_batch::RecordBatch,
+        util::pretty::pretty_format_batches,
     };
     use spi::{
         catalog::DEFAULT_CATALOG,
@@ -135,6 +560,27 @@ mod tests {
         };
     }
 
+    /// The dbms on the mock engine, storing the data under `dir` if given,
+    /// with the audit log and the settings in memory
+    async fn make_test_dbms(dir: Option<&Path>) -> (Cnosdbms, Arc<AuditLog>, Arc<Settings>) {
+        let mut config = get_config("../../config/config.toml");
+        if let Some(dir) = dir {
+            config.storage.path = dir.to_string_lossy().to_string();
+        }
+        let opt = Options::from(&config);
+        let audit_log = Arc::new(AuditLog::memory());
+        let settings = Arc::new(Settings::memory(config));
+        let db = make_cnosdbms(
+            Arc::new(LocalCoordinator::new(Arc::new(MockEngine::default()))),
+            opt,
+            audit_log.clone(),
+            settings.clone(),
+        )
+        .await
+        .unwrap();
+        (db, audit_log, settings)
+    }
+
     async fn exec_sql(db: &Cnosdbms, sql: &str) -> Vec<RecordBatch> {
         let user = UserInfo {
             user: DEFAULT_CATALOG.to_string(),
@@ -163,9 +609,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_simple_sql() {
-        let config = get_config("../../config/config.toml");
-        let opt = Options::from(&config);
-        let db = make_cnosdbms(Arc::new(MockEngine::default()), opt).unwrap();
+        let (db, ..) = make_test_dbms(None).await;
 
         let mut result = exec_sql(&db, "SELECT * FROM (VALUES (1, 'one'), (2, 'two'), (3, 'three')) AS t (num,letter) order by num").await;
 
@@ -218,9 +662,7 @@ mod tests {
     #[ignore]
     async fn test_topk_sql() {
         // trace::init_default_global_tracing("/tmp", "test_rust.log", "debug");
-        let config = get_config("../../config/config.toml");
-        let opt = Options::from(&config);
-        let db = make_cnosdbms(Arc::new(MockEngine::default()), opt).unwrap();
+        let (db, ..) = make_test_dbms(None).await;
 
         let sql = format!(
             "SELECT * FROM
@@ -252,9 +694,7 @@ mod tests {
     #[tokio::test]
     async fn test_topk_desc_sql() {
         // trace::init_default_global_tracing("/tmp", "test_rust.log", "debug");
-        let config = get_config("../../config/config.toml");
-        let opt = Options::from(&config);
-        let db = make_cnosdbms(Arc::new(MockEngine::default()), opt).unwrap();
+        let (db, ..) = make_test_dbms(None).await;
 
         let mut result = exec_sql(
             &db,
@@ -286,9 +726,7 @@ mod tests {
     #[tokio::test]
     #[ignore]
     async fn test_create_external_csv_table() {
-        let config = get_config("../../config/config.toml");
-        let opt = Options::from(&config);
-        let db = make_cnosdbms(Arc::new(MockEngine::default()), opt).unwrap();
+        let (db, ..) = make_test_dbms(None).await;
 
         assert_batches_eq!(
             vec!["++", "++", "++",],
@@ -340,9 +778,7 @@ mod tests {
     #[tokio::test]
     #[ignore]
     async fn test_create_external_parquet_table() {
-        let config = get_config("../../config/config.toml");
-        let opt = Options::from(&config);
-        let db = make_cnosdbms(Arc::new(MockEngine::default()), opt).unwrap();
+        let (db, ..) = make_test_dbms(None).await;
 
         assert_batches_eq!(
             vec!["++", "++", "++",],
@@ -386,9 +822,7 @@ mod tests {
     #[tokio::test]
     #[ignore]
     async fn test_create_external_json_table() {
-        let config = get_config("../config/config.toml");
-        let opt = Options::from(&config);
-        let db = make_cnosdbms(Arc::new(MockEngine::default()), opt).unwrap();
+        let (db, ..) = make_test_dbms(None).await;
 
         assert_batches_eq!(
             vec!["++", "++", "++",],
@@ -426,4 +860,332 @@ mod tests {
             .deref_mut()
         );
     }
+
+    #[tokio::test]
+    async fn test_audit_ddl() {
+        let (db, audit_log, _) = make_test_dbms(None).await;
+
+        exec_sql(&db, "CREATE DATABASE IF NOT EXISTS db_audit").await;
+        // Only reads the definitions
+        exec_sql(&db, "DESCRIBE DATABASE db_audit").await;
+        assert_eq!(audit_log.events().len(), 1);
+
+        let mut result = exec_sql(
+            &db,
+            "SELECT detail FROM system.audit_log WHERE kind = 'ddl' AND success",
+        )
+        .await;
+        let expected = vec![
+            "+----------------------------------------+",
+            "| detail                                 |",
+            "+----------------------------------------+",
+            "| CREATE DATABASE IF NOT EXISTS db_audit |",
+            "+----------------------------------------+",
+        ];
+        assert_batches_eq!(expected, result.deref_mut());
+    }
+
+    #[tokio::test]
+    async fn test_system_settings() {
+        let (db, _, settings) = make_test_dbms(None).await;
+
+        exec_sql(&db, "ALTER SYSTEM SET cache.max_buffer_size = 2097152").await;
+        assert_eq!(settings.config().cache.max_buffer_size, 2097152);
+
+        let result = exec_sql(&db, "SHOW SETTINGS").await;
+        assert_eq!(result[0].num_rows(), settings.show().len());
+    }
+
+    #[tokio::test]
+    async fn test_system_queries() {
+        let (db, ..) = make_test_dbms(None).await;
+
+        // The query is planned before being tracked, so it is not listed itself
+        let result = exec_sql(&db, "SELECT query, bytes_scanned FROM system.queries").await;
+        assert_eq!(result.iter().map(|b| b.num_rows()).sum::<usize>(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_system_database_stats() {
+        let (db, ..) = make_test_dbms(None).await;
+
+        // Not counted as no database is scanned
+        exec_sql(&db, "SELECT 1").await;
+        metrics::database_stats::record_query("stats_sql_tenant", "public", 100, true);
+        let result = exec_sql(
+            &db,
+            "SELECT tenant, queries, bytes_scanned FROM system.database_stats \
+            WHERE tenant = 'stats_sql_tenant' AND database = 'public'",
+        )
+        .await;
+        // The admins see the databases of the other tenants
+        let expected = vec![
+            "+------------------+---------+---------------+",
+            "| tenant           | queries | bytes_scanned |",
+            "+------------------+---------+---------------+",
+            "| stats_sql_tenant | 1       | 100           |",
+            "+------------------+---------+---------------+",
+        ];
+        assert_batches_eq!(expected, &result);
+    }
+
+    #[tokio::test]
+    async fn test_system_tenant_usage() {
+        let (db, ..) = make_test_dbms(None).await;
+
+        metrics::tenant_usage::record_tenant_write("usage_sql_tenant", 3, 100);
+        let result = exec_sql(
+            &db,
+            "SELECT points_written FROM system.tenant_usage WHERE tenant = 'usage_sql_tenant'",
+        )
+        .await;
+        let points = result[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .unwrap();
+        assert_eq!(points.value(0), 3);
+    }
+
+    #[tokio::test]
+    async fn test_user() {
+        let dir = tempfile::tempdir().unwrap();
+        let (db, ..) = make_test_dbms(Some(dir.path())).await;
+        let user_info = |user: &str, password: &str| UserInfo {
+            user: user.to_string(),
+            password: password.to_string(),
+        };
+
+        // The admin of the configuration is created on the first start
+        db.authenticate(&user_info(DEFAULT_CATALOG, ""))
+            .await
+            .unwrap();
+        assert!(db.authenticate(&user_info("u1", "secret")).await.is_err());
+
+        exec_sql(&db, "CREATE USER u1 WITH (password = 'secret')").await;
+        exec_sql(
+            &db,
+            "CREATE USER IF NOT EXISTS u1 WITH (password = 'other')",
+        )
+        .await;
+        db.authenticate(&user_info("u1", "secret")).await.unwrap();
+        assert!(db.authenticate(&user_info("u1", "other")).await.is_err());
+
+        exec_sql(
+            &db,
+            "ALTER USER u1 WITH (password = 'other', is_admin = true)",
+        )
+        .await;
+        db.authenticate(&user_info("u1", "other")).await.unwrap();
+
+        let result = exec_sql(&db, "SHOW USERS").await;
+        let expected = vec![
+            "+--------+---------+",
+            "| Name   | IsAdmin |",
+            "+--------+---------+",
+            "| cnosdb | true    |",
+            "| u1     | true    |",
+            "+--------+---------+",
+        ];
+        assert_batches_eq!(expected, &result);
+
+        exec_sql(&db, "DROP USER u1").await;
+        exec_sql(&db, "DROP USER IF EXISTS u1").await;
+        assert!(db.authenticate(&user_info("u1", "other")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_token() {
+        let dir = tempfile::tempdir().unwrap();
+        let (db, ..) = make_test_dbms(Some(dir.path())).await;
+
+        exec_sql(&db, "CREATE USER u1 WITH (password = 'secret')").await;
+        let result = exec_sql(&db, "CREATE TOKEN agent FOR USER u1").await;
+        let token = result[0]
+            .column(1)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap()
+            .value(0)
+            .to_string();
+        assert_eq!(db.authenticate_token(&token).unwrap().user, "u1");
+        assert!(db.authenticate_token("cnosdb_invalid").is_err());
+        // No JWKS is configured
+        assert!(db.authenticate_token("a.b.c").is_err());
+
+        let result = exec_sql(&db, "SHOW TOKENS").await;
+        assert_eq!(result[0].num_rows(), 1);
+
+        // The tokens are kept as the user is altered
+        exec_sql(&db, "ALTER USER u1 WITH (is_admin = true)").await;
+        assert_eq!(db.authenticate_token(&token).unwrap().user, "u1");
+
+        exec_sql(&db, "DROP TOKEN agent FOR USER u1").await;
+        exec_sql(&db, "DROP TOKEN IF EXISTS agent FOR USER u1").await;
+        assert!(db.authenticate_token(&token).is_err());
+    }
+
+    struct TestProvider;
+
+    #[async_trait]
+    impl IdentityProvider for TestProvider {
+        fn name(&self) -> &str {
+            "test"
+        }
+
+        async fn authenticate_password(
+            &self,
+            user: &str,
+            password: &str,
+        ) -> Option<std::result::Result<ExternalIdentity, String>> {
+            Some(match password {
+                "secret" => Ok(ExternalIdentity {
+                    user: user.to_string(),
+                    groups: vec!["eu_team".to_string()],
+                }),
+                _ => Err("invalid password".to_string()),
+            })
+        }
+
+        fn authenticate_token(
+            &self,
+            token: &str,
+        ) -> Option<std::result::Result<ExternalIdentity, String>> {
+            Some(match token {
+                "t1" => Ok(ExternalIdentity {
+                    user: "alice".to_string(),
+                    groups: vec![],
+                }),
+                _ => Err("invalid token".to_string()),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_external_identity() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut db, ..) = make_test_dbms(Some(dir.path())).await;
+        db.providers = vec![Arc::new(TestProvider)];
+        let user_info = |user: &str, password: &str| UserInfo {
+            user: user.to_string(),
+            password: password.to_string(),
+        };
+
+        exec_sql(&db, "CREATE ROLE eu").await;
+        exec_sql(&db, "GRANT ROLE eu TO GROUP 'eu_team'").await;
+
+        // The user is created as it is first authenticated
+        db.authenticate(&user_info("bob", "secret")).await.unwrap();
+        // Saved in the background
+        tokio::task::yield_now().await;
+        let bob = db.meta.user("bob").unwrap().unwrap();
+        assert_eq!(bob.provider.as_deref(), Some("test"));
+        assert_eq!(bob.roles, vec!["eu"]);
+        assert!(db.authenticate(&user_info("bob", "other")).await.is_err());
+
+        // The local users are not authenticated by the providers
+        exec_sql(&db, "CREATE USER u1 WITH (password = 'other')").await;
+        assert!(db.authenticate(&user_info("u1", "secret")).await.is_err());
+        db.authenticate(&user_info("u1", "other")).await.unwrap();
+
+        // The roles follow the groups
+        exec_sql(&db, "REVOKE ROLE eu FROM GROUP 'eu_team'").await;
+        db.authenticate(&user_info("bob", "secret")).await.unwrap();
+        tokio::task::yield_now().await;
+        assert!(db.meta.user("bob").unwrap().unwrap().roles.is_empty());
+
+        assert_eq!(db.authenticate_token("t1").unwrap().user, "alice");
+        assert!(db.authenticate_token("t2").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_role() {
+        let dir = tempfile::tempdir().unwrap();
+        let (db, ..) = make_test_dbms(Some(dir.path())).await;
+
+        exec_sql(&db, "CREATE USER u1 WITH (password = 'secret')").await;
+        exec_sql(&db, "CREATE ROLE eu").await;
+        exec_sql(&db, "CREATE ROLE IF NOT EXISTS eu").await;
+        exec_sql(&db, "GRANT ROLE eu TO u1").await;
+        assert_eq!(db.meta.user("u1").unwrap().unwrap().roles, vec!["eu"]);
+
+        let result = exec_sql(&db, "SHOW ROLES").await;
+        assert_eq!(result[0].num_rows(), 1);
+        let users = result[0]
+            .column(1)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(users.value(0), "u1");
+
+        // The tables of the policies and the privileges must exist
+        let user = UserInfo {
+            user: DEFAULT_CATALOG.to_string(),
+            password: "todo".to_string(),
+        };
+        let query = Query::new(
+            ContextBuilder::new(user).build(),
+            "CREATE POLICY eu_only ON cpu TO eu USING (region = 'eu')".to_string(),
+        );
+        assert!(db.execute(&query).await.is_err());
+        let query = Query::new(
+            query.context().clone(),
+            "GRANT SELECT (region) ON cpu TO eu".to_string(),
+        );
+        assert!(db.execute(&query).await.is_err());
+        exec_sql(&db, "REVOKE SELECT ON cpu FROM eu").await;
+
+        exec_sql(&db, "REVOKE ROLE eu FROM u1").await;
+        assert!(db.meta.user("u1").unwrap().unwrap().roles.is_empty());
+
+        // Dropping the role revokes it
+        exec_sql(&db, "GRANT ROLE eu TO u1").await;
+        exec_sql(&db, "DROP ROLE eu").await;
+        exec_sql(&db, "DROP ROLE IF EXISTS eu").await;
+        assert!(db.meta.user("u1").unwrap().unwrap().roles.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_task() {
+        let dir = tempfile::tempdir().unwrap();
+        let (db, ..) = make_test_dbms(Some(dir.path())).await;
+
+        let sql =
+            "CREATE TASK t0 EVERY '1h' INTO cpu_hourly AS SELECT * FROM cpu WHERE time > $last_run";
+        exec_sql(&db, sql).await;
+        exec_sql(&db, &sql.replace("TASK t0", "TASK IF NOT EXISTS t0")).await;
+        exec_sql(&db, "ALTER TASK t0 DISABLE").await;
+
+        let result = exec_sql(&db, "SHOW TASKS").await;
+        assert_eq!(result[0].num_rows(), 1);
+        let enabled = result[0]
+            .column(5)
+            .as_any()
+            .downcast_ref::<BooleanArray>()
+            .unwrap();
+        assert!(!enabled.value(0));
+        let task = &db.meta.tasks().unwrap()[0];
+        assert_eq!(task.owner, DEFAULT_CATALOG);
+        assert_eq!(task.database, "public");
+
+        exec_sql(&db, "DROP TASK t0").await;
+        exec_sql(&db, "DROP TASK IF EXISTS t0").await;
+        assert!(db.meta.tasks().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_sessions() {
+        let dir = tempfile::tempdir().unwrap();
+        let (db, ..) = make_test_dbms(Some(dir.path())).await;
+
+        let sessions = db.sessions();
+        let session = sessions.open("127.0.0.1:5000".to_string(), "http").unwrap();
+        sessions.authenticate(session.session(), "u1").unwrap();
+        let result = exec_sql(&db, "SHOW SESSIONS").await;
+        assert_eq!(result[0].num_rows(), 1);
+
+        let sql = format!("KILL SESSION {}", session.session().id());
+        exec_sql(&db, &sql).await;
+        assert!(session.session().is_killed());
+    }
 }
@@ -20,13 +20,16 @@ use tskv::{
     error::IndexErrSnafu,
     memcache::DataType,
     tseries_family::{ColumnFile, SuperVersion, TimeRange},
-    tsm::{BlockMetaIterator, DataBlock, TsmReader},
+    tsm::{BlockMeta, BlockMetaIterator, DataBlock, TsmReader, ValueBounds},
     ColumnFileId, Error,
 };
 
 use datafusion::arrow::{
-    array::{BooleanBuilder, Float64Builder, Int64Builder, StringBuilder, UInt64Builder},
-    datatypes::SchemaRef,
+    array::{
+        BooleanBuilder, Float64Builder, Int64Builder, StringBuilder, StringDictionaryBuilder,
+        UInt64Builder,
+    },
+    datatypes::{Int32Type, SchemaRef},
     record_batch::RecordBatch,
 };
 
@@ -60,34 +63,89 @@ pub struct QueryOption {
 pub struct FieldFileLocation {
     reader: TsmReader,
     block_it: BlockMetaIterator,
+    /// Only the rows in the time range are read, the values of a block are not
+    /// decoded if none of its timestamps is in the range
+    time_range: TimeRange,
+    /// The blocks the values of which never match are skipped, None if the
+    /// values skipped may be overwritten by the values of the other locations
+    value_domain: Option<Domain>,
 
     read_index: usize,
     data_block: DataBlock,
 }
 
 impl FieldFileLocation {
-    pub fn new(reader: TsmReader, block_it: BlockMetaIterator, vtype: ValueType) -> Self {
+    pub fn new(
+        reader: TsmReader,
+        block_it: BlockMetaIterator,
+        time_range: TimeRange,
+        value_domain: Option<Domain>,
+        vtype: ValueType,
+    ) -> Self {
         Self {
             reader,
             block_it,
+            time_range,
+            value_domain,
             read_index: 0,
             data_block: DataBlock::new(0, vtype),
         }
     }
 
     pub fn peek(&mut self) -> Result<Option<DataType>, Error> {
-        if self.read_index >= self.data_block.len() {
-            if let Some(meta) = self.block_it.next() {
-                self.read_index = 0;
-                self.data_block = self.reader.get_data_block(&meta)?;
-            } else {
-                return Ok(None);
-            }
+        while self.read_index >= self.data_block.len() {
+            let meta = match self.block_it.next() {
+                Some(meta) => meta,
+                None => return Ok(None),
+            };
+            self.read_block(&meta)?;
         }
 
         Ok(self.data_block.get(self.read_index))
     }
 
+    /// Skips the values before the timestamp, the blocks all the values of which
+    /// are before it are not decoded
+    pub fn seek(&mut self, ts: i64) -> Result<(), Error> {
+        loop {
+            let remaining = &self.data_block.ts()[self.read_index.min(self.data_block.len())..];
+            self.read_index += remaining.partition_point(|e| *e < ts);
+            if self.read_index < self.data_block.len() {
+                return Ok(());
+            }
+
+            let meta = match self.block_it.next() {
+                Some(meta) => meta,
+                None => return Ok(()),
+            };
+            if meta.max_ts() < ts {
+                continue;
+            }
+            self.read_block(&meta)?;
+        }
+    }
+
+    fn read_block(&mut self, meta: &BlockMeta) -> Result<(), Error> {
+        self.read_index = 0;
+        self.data_block = match (self.time_range.is_boundless(), &self.value_domain) {
+            (true, None) => self.reader.get_data_block(meta)?,
+            (_, value_domain) => {
+                let value_filter = |bounds: &ValueBounds| match value_domain {
+                    Some(domain) => may_match(domain, bounds),
+                    None => true,
+                };
+                match self
+                    .reader
+                    .get_data_block_in_range(meta, &self.time_range, value_filter)?
+                {
+                    Some(data_block) => data_block,
+                    None => DataBlock::new(0, meta.field_type()),
+                }
+            }
+        };
+        Ok(())
+    }
+
     pub fn next(&mut self) {
         self.read_index += 1;
     }
@@ -101,6 +159,8 @@ pub trait Cursor: Send + Sync {
 
     fn next(&mut self, ts: i64);
     fn peek(&mut self) -> Result<Option<DataType>, Error>;
+    /// Skips the values before the timestamp
+    fn seek(&mut self, ts: i64) -> Result<(), Error>;
 }
 
 //-----------Time Cursor----------------
@@ -128,6 +188,10 @@ impl Cursor for TimeCursor {
 
     fn next(&mut self, _ts: i64) {}
 
+    fn seek(&mut self, _ts: i64) -> Result<(), Error> {
+        Ok(())
+    }
+
     fn val_type(&self) -> ValueType {
         ValueType::Integer
     }
@@ -139,11 +203,12 @@ impl Cursor for TimeCursor {
 //-----------Tag Cursor----------------
 pub struct TagCursor {
     name: String,
-    value: String,
+    /// None if the series has no such tag, it's read as NULL
+    value: Option<String>,
 }
 
 impl TagCursor {
-    pub fn new(value: String, name: String) -> Self {
+    pub fn new(value: Option<String>, name: String) -> Self {
         Self { name, value }
     }
 }
@@ -154,13 +219,20 @@ impl Cursor for TagCursor {
     }
 
     fn peek(&mut self) -> Result<Option<DataType>, Error> {
-        let data = DataType::Str(0, MiniVec::from(self.value.as_bytes()));
+        let data = self
+            .value
+            .as_ref()
+            .map(|value| DataType::Str(0, MiniVec::from(value.as_bytes())));
 
-        Ok(Some(data))
+        Ok(data)
     }
 
     fn next(&mut self, _ts: i64) {}
 
+    fn seek(&mut self, _ts: i64) -> Result<(), Error> {
+        Ok(())
+    }
+
     fn val_type(&self) -> ValueType {
         ValueType::String
     }
@@ -236,6 +308,21 @@ impl FieldCursor {
             mem_data.len()
         );
 
+        // The blocks are only skipped by the values for the numeric predicates
+        let value_domain = match vtype {
+            ValueType::Integer | ValueType::Unsigned => iterator
+                .option
+                .fields_filter
+                .domains()
+                .and_then(|e| e.get(&name))
+                .filter(|e| !matches!(e, Domain::All))
+                .cloned(),
+            _ => None,
+        };
+        // The time ranges of the locations, a value of a location overwrites the
+        // values of the same timestamp of the locations before
+        let mut location_ranges: Vec<TimeRange> = vec![];
+
         // get data from levelinfo
         let mut locations = vec![];
         for level in version.version.levels_info.iter().rev() {
@@ -255,10 +342,23 @@ impl FieldCursor {
                         file.file_path().display()
                     );
 
+                    let file_range = TimeRange::new(
+                        file.time_range().min_ts.max(time_range.min_ts),
+                        file.time_range().max_ts.min(time_range.max_ts),
+                    );
+                    let is_overwriting = location_ranges.iter().any(|e| e.overlaps(&file_range));
+                    location_ranges.push(file_range);
+
                     let tsm_reader = iterator.get_tsm_reader(file.clone())?;
                     for idx in tsm_reader.index_iterator_opt(field_id) {
                         let block_it = idx.block_iterator_opt(time_range);
-                        let location = FieldFileLocation::new(tsm_reader.clone(), block_it, vtype);
+                        let location = FieldFileLocation::new(
+                            tsm_reader.clone(),
+                            block_it,
+                            *time_range,
+                            value_domain.clone().filter(|_| !is_overwriting),
+                            vtype,
+                        );
                         locations.push(location);
                     }
                 }
@@ -337,6 +437,15 @@ impl Cursor for FieldCursor {
         }
     }
 
+    fn seek(&mut self, ts: i64) -> Result<(), Error> {
+        self.cache_index += self.cache_data[self.cache_index.min(self.cache_data.len())..]
+            .partition_point(|e| e.timestamp() < ts);
+        for loc in self.locations.iter_mut() {
+            loc.seek(ts)?;
+        }
+        Ok(())
+    }
+
     fn val_type(&self) -> ValueType {
         self.value_type
     }
@@ -346,6 +455,86 @@ impl Cursor for FieldCursor {
     }
 }
 
+/// Whether any value in the bounds may be in the domain
+fn may_match(domain: &Domain, bounds: &ValueBounds) -> bool {
+    let (min, max) = match *bounds {
+        ValueBounds::Integer(min, max) => (min as i128, max as i128),
+        ValueBounds::Unsigned(min, max) => (min as i128, max as i128),
+    };
+    let to_i128 = |v: &ScalarValue| match v {
+        ScalarValue::Int64(Some(v)) => Some(*v as i128),
+        ScalarValue::UInt64(Some(v)) => Some(*v as i128),
+        _ => None,
+    };
+
+    match domain {
+        Domain::Range(range_set) => range_set.low_indexed_ranges().into_iter().any(|(_, e)| {
+            let range: &Range = e;
+            let above_low = match range.start_bound() {
+                Bound::Unbounded => true,
+                Bound::Included(v) => to_i128(v).map_or(true, |v| v <= max),
+                Bound::Excluded(v) => to_i128(v).map_or(true, |v| v < max),
+            };
+            let below_high = match range.end_bound() {
+                Bound::Unbounded => true,
+                Bound::Included(v) => to_i128(v).map_or(true, |v| v >= min),
+                Bound::Excluded(v) => to_i128(v).map_or(true, |v| v > min),
+            };
+            above_low && below_high
+        }),
+        Domain::Equtable(vals) => {
+            let mut values = vals.entries().into_iter().map(|e| to_i128(e.value()));
+            if vals.is_white_list() {
+                values.any(|v| v.map_or(true, |v| min <= v && v <= max))
+            } else {
+                // eg. all the values of the block are excluded by f != xxx
+                min != max || !values.any(|v| v == Some(min))
+            }
+        }
+        Domain::All => true,
+        Domain::None => false,
+    }
+}
+
+/// Whether the value is in the domain, true if the value can not be compared with
+/// the values of the domain
+fn value_matches(domain: &Domain, data: &DataType) -> bool {
+    let value = match data {
+        DataType::U64(_, v) => ScalarValue::UInt64(Some(*v)),
+        DataType::I64(_, v) => ScalarValue::Int64(Some(*v)),
+        DataType::F64(_, v) => ScalarValue::Float64(Some(*v)),
+        DataType::Bool(_, v) => ScalarValue::Boolean(Some(*v)),
+        DataType::Str(_, v) => ScalarValue::Utf8(Some(String::from_utf8_lossy(v).to_string())),
+    };
+
+    match domain {
+        Domain::Range(range_set) => range_set.low_indexed_ranges().into_iter().any(|(_, e)| {
+            let range: &Range = e;
+            let above_low = match range.start_bound() {
+                Bound::Unbounded => true,
+                Bound::Included(v) => v.partial_cmp(&value).map_or(true, |o| o.is_le()),
+                Bound::Excluded(v) => v.partial_cmp(&value).map_or(true, |o| o.is_lt()),
+            };
+            let below_high = match range.end_bound() {
+                Bound::Unbounded => true,
+                Bound::Included(v) => v.partial_cmp(&value).map_or(true, |o| o.is_ge()),
+                Bound::Excluded(v) => v.partial_cmp(&value).map_or(true, |o| o.is_gt()),
+            };
+            above_low && below_high
+        }),
+        Domain::Equtable(vals) => {
+            let mut entries = vals.entries().into_iter();
+            if vals.is_white_list() {
+                entries.any(|e| e.value().partial_cmp(&value).map_or(true, |o| o.is_eq()))
+            } else {
+                !entries.any(|e| e.value().partial_cmp(&value).map_or(false, |o| o.is_eq()))
+            }
+        }
+        Domain::All => true,
+        Domain::None => false,
+    }
+}
+
 pub fn filter_to_time_ranges(time_domain: &ColumnDomains<String>) -> Vec<TimeRange> {
     if time_domain.is_none() {
         // Does not contain any data, and returns an empty array directly
@@ -420,6 +609,9 @@ pub struct RowIterator {
     engine: EngineRef,
     option: QueryOption,
     columns: Vec<CursorPtr>,
+    /// The indexes of the field columns with a predicate and their domains, a row
+    /// is only read if the values of these columns are in the domains
+    predicates: Vec<(usize, Domain)>,
     version: Option<Arc<SuperVersion>>,
 
     open_files: HashMap<ColumnFileId, TsmReader>,
@@ -434,8 +626,6 @@ impl RowIterator {
         option: QueryOption,
         batch_size: usize,
     ) -> Result<Self, Error> {
-        let version = engine.get_db_version(&option.table_schema.db)?;
-
         let series = engine
             .get_series_id_by_filter(
                 &option.table_schema.db,
@@ -446,10 +636,42 @@ impl RowIterator {
 
         debug!("series number: {}", series.len());
 
+        Self::with_series(metrics, engine, option, series, batch_size)
+    }
+
+    /// Iterates the rows of the series, the tags filter of the option is ignored
+    pub fn with_series(
+        metrics: TskvSourceMetrics,
+        engine: EngineRef,
+        option: QueryOption,
+        series: Vec<SeriesId>,
+        batch_size: usize,
+    ) -> Result<Self, Error> {
+        let version = engine.get_db_version(&option.table_schema.db)?;
+        if let Some(version) = &version {
+            version.record_read();
+        }
+
+        let predicates = match option.fields_filter.domains() {
+            Some(domains) => option
+                .table_schema
+                .columns()
+                .iter()
+                .enumerate()
+                .filter(|(_, e)| e.column_type.is_field())
+                .filter_map(|(i, e)| match domains.get(&e.name) {
+                    Some(Domain::All) | None => None,
+                    Some(domain) => Some((i, domain.clone())),
+                })
+                .collect(),
+            None => vec![],
+        };
+
         Ok(Self {
             series,
             engine,
             option,
+            predicates,
             version,
             batch_size,
 
@@ -488,11 +710,20 @@ impl RowIterator {
                 let column: CursorPtr = match item.column_type {
                     ColumnType::Time => Box::new(TimeCursor::new(0, field_name)),
 
-                    ColumnType::Tag => Box::new(TagCursor::new(
-                        String::from_utf8(key.tag_val(&item.name))
-                            .map_err(|_| Error::ErrCharacterSet)?,
-                        field_name,
-                    )),
+                    ColumnType::Tag => {
+                        let value = match key
+                            .tags()
+                            .iter()
+                            .find(|tag| tag.key == item.name.as_bytes())
+                        {
+                            Some(tag) => Some(
+                                String::from_utf8(tag.value.clone())
+                                    .map_err(|_| Error::ErrCharacterSet)?,
+                            ),
+                            None => None,
+                        };
+                        Box::new(TagCursor::new(value, field_name))
+                    }
 
                     ColumnType::Field(vtype) => match vtype {
                         ValueType::Unknown => todo!(),
@@ -539,42 +770,46 @@ impl RowIterator {
         debug!("======collect_row_data=========");
         let timer = self.metrics.elapsed_field_scan().timer();
 
-        let mut min_time = i64::MAX;
-        let mut values = Vec::with_capacity(self.columns.len());
-        for column in self.columns.iter_mut() {
-            let val = column.peek()?;
-            if let Some(ref data) = val {
-                if column.is_field() {
-                    min_time = min_num(min_time, data.timestamp());
-                }
+        let min_time = if self.predicates.is_empty() {
+            self.next_time()?
+        } else {
+            self.next_matched_time()?
+        };
+        debug!("min time {:?}", min_time);
+        let min_time = match min_time {
+            Some(min_time) => min_time,
+            None => {
+                timer.done();
+                self.columns.clear();
+                return Ok(None);
             }
-            values.push(val)
-        }
+        };
 
-        for (column, value) in self.columns.iter_mut().zip(values.iter_mut()) {
-            debug!("field: {} value {:?}", column.name(), value);
+        // The values before the time are skipped, they are not in the rows matched
+        let mut bytes_scanned = 0;
+        let mut values = Vec::with_capacity(self.columns.len());
+        for column in self.columns.iter_mut() {
             if !column.is_field() {
+                values.push(column.peek()?);
                 continue;
             }
 
-            if let Some(data) = value {
-                let ts = data.timestamp();
-                if ts == min_time {
-                    column.next(ts);
-                } else {
-                    *value = None;
+            column.seek(min_time)?;
+            let value = match column.peek()? {
+                Some(data) if data.timestamp() == min_time => {
+                    bytes_scanned += data.size();
+                    column.next(min_time);
+                    Some(data)
                 }
-            }
+                _ => None,
+            };
+            debug!("field: {} value {:?}", column.name(), value);
+            values.push(value);
         }
+        self.metrics.bytes_scanned().add(bytes_scanned);
 
         timer.done();
 
-        debug!("min time {}", min_time);
-        if min_time == i64::MAX {
-            self.columns.clear();
-            return Ok(None);
-        }
-
         let timer = self.metrics.elapsed_point_to_record_batch().timer();
 
         for (i, value) in values.into_iter().enumerate() {
@@ -588,6 +823,24 @@ impl RowIterator {
                 continue;
             }
 
+            if self.option.table_schema.columns()[i].column_type.is_tag() {
+                let tag_builder = builder[i]
+                    .as_any_mut()
+                    .downcast_mut::<StringDictionaryBuilder<Int32Type>>()
+                    .unwrap();
+                if let Some(DataType::Str(_, val)) = value {
+                    tag_builder
+                        .append(std::str::from_utf8(&val).map_err(|_| Error::ErrCharacterSet)?)
+                        .map_err(|err| Error::DataFusionNew {
+                            reason: err.to_string(),
+                        })?;
+                } else {
+                    tag_builder.append_null();
+                }
+
+                continue;
+            }
+
             match self.columns[i].val_type() {
                 ValueType::Unknown => {
                     return Err(Error::UnKnowType);
@@ -657,6 +910,52 @@ impl RowIterator {
         Ok(Some(()))
     }
 
+    /// The time of the next row, the min time of the values of the fields
+    fn next_time(&mut self) -> Result<Option<i64>, Error> {
+        let mut min_time = i64::MAX;
+        for column in self.columns.iter_mut().filter(|e| e.is_field()) {
+            if let Some(data) = column.peek()? {
+                min_time = min_num(min_time, data.timestamp());
+            }
+        }
+
+        Ok(Some(min_time).filter(|e| *e != i64::MAX))
+    }
+
+    /// The time of the next row the values of the fields with a predicate of which
+    /// are all in the domains. Only the fields with a predicate are decoded until a
+    /// row is matched, the other fields are then read at the time of the row.
+    fn next_matched_time(&mut self) -> Result<Option<i64>, Error> {
+        if self.columns.is_empty() {
+            return Ok(None);
+        }
+
+        let mut ts = i64::MIN;
+        'rows: loop {
+            for (i, domain) in self.predicates.iter() {
+                let column = &mut self.columns[*i];
+                column.seek(ts)?;
+                let data = match column.peek()? {
+                    Some(data) => data,
+                    None => return Ok(None),
+                };
+                if data.timestamp() > ts {
+                    ts = data.timestamp();
+                    continue 'rows;
+                }
+                if !value_matches(domain, &data) {
+                    match ts.checked_add(1) {
+                        Some(next) => ts = next,
+                        None => return Ok(None),
+                    }
+                    continue 'rows;
+                }
+            }
+
+            return Ok(Some(ts));
+        }
+    }
+
     fn next_row(&mut self, builder: &mut [ArrayBuilderPtr]) -> Result<Option<()>, Error> {
         loop {
             if self.columns.is_empty() && self.next_series()?.is_none() {
@@ -676,10 +975,9 @@ impl RowIterator {
             debug!("schema info {:02X} {}", item.id, item.name);
 
             match item.column_type {
-                ColumnType::Tag => builders.push(Box::new(StringBuilder::with_capacity(
-                    self.batch_size,
-                    self.batch_size * 32,
-                ))),
+                ColumnType::Tag => {
+                    builders.push(Box::new(StringDictionaryBuilder::<Int32Type>::new()))
+                }
                 ColumnType::Time => builders.push(Box::new(
                     TimestampNanosecondBuilder::with_capacity(self.batch_size),
                 )),
@@ -757,3 +1055,56 @@ impl Iterator for RowIterator {
         result
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use datafusion::arrow::datatypes::DataType;
+    use tskv::memcache::DataType as TskvDataType;
+
+    use super::*;
+
+    #[test]
+    fn test_may_match() {
+        let gt = |v: i64| {
+            Domain::of_ranges(&[Range::gt(&DataType::Int64, &ScalarValue::Int64(Some(v)))]).unwrap()
+        };
+        let bounds = ValueBounds::Integer(5, 10);
+        assert!(may_match(&gt(9), &bounds));
+        assert!(!may_match(&gt(10), &bounds));
+
+        let one = ScalarValue::UInt64(Some(1));
+        let eq = Domain::of_values(&DataType::UInt64, true, &[&one]);
+        let ne = Domain::of_values(&DataType::UInt64, false, &[&one]);
+        assert!(may_match(&eq, &ValueBounds::Unsigned(0, 1)));
+        assert!(!may_match(&eq, &ValueBounds::Unsigned(2, 3)));
+        assert!(!may_match(&ne, &ValueBounds::Unsigned(1, 1)));
+        assert!(may_match(&ne, &ValueBounds::Unsigned(1, 2)));
+
+        assert!(may_match(&Domain::All, &bounds));
+        assert!(!may_match(&Domain::None, &bounds));
+    }
+
+    #[test]
+    fn test_value_matches() {
+        let gt = Domain::of_ranges(&[Range::gt(
+            &DataType::Float64,
+            &ScalarValue::Float64(Some(1.5)),
+        )])
+        .unwrap();
+        assert!(value_matches(&gt, &TskvDataType::F64(0, 2.0)));
+        assert!(!value_matches(&gt, &TskvDataType::F64(0, 1.5)));
+
+        let a = ScalarValue::Utf8(Some("a".to_string()));
+        let eq = Domain::of_values(&DataType::Utf8, true, &[&a]);
+        let ne = Domain::of_values(&DataType::Utf8, false, &[&a]);
+        let string = |v: &str| TskvDataType::Str(0, MiniVec::from(v.as_bytes()));
+        assert!(value_matches(&eq, &string("a")));
+        assert!(!value_matches(&eq, &string("b")));
+        assert!(!value_matches(&ne, &string("a")));
+        assert!(value_matches(&ne, &string("b")));
+
+        // Not comparable
+        assert!(value_matches(&gt, &TskvDataType::I64(0, 0)));
+        assert!(!value_matches(&Domain::None, &TskvDataType::I64(0, 0)));
+    }
+}
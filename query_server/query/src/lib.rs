@@ -1,16 +1,28 @@
 extern crate core;
 
+pub mod audit;
+pub mod auth;
 pub mod catalog;
+pub mod column_conversion;
+mod connector;
 mod data_source;
+pub mod database_stats;
 pub mod dispatcher;
 mod execution;
 pub mod extension;
 pub mod function;
+pub mod identity;
 pub mod instance;
 mod iterator;
 pub mod metadata;
+pub mod privileges;
+pub mod session;
+pub mod shards;
 pub mod sql;
 mod stream;
 mod table;
+pub mod task;
+pub mod tenant_usage;
 mod tskv_exec;
 mod utils;
+mod write_schema;
@@ -1,6 +1,17 @@
+use async_trait::async_trait;
 use std::any::Any;
 
+use crate::audit::{AuditLog, AuditLogRef, AUDIT_TABLE, SYSTEM_DATABASE};
+use crate::auth::{RemoteUserStore, UserStoreRef};
 use crate::catalog::{Database, UserCatalog, UserCatalogRef};
+use crate::column_conversion::{ColumnConversions, ColumnConversionsRef, COLUMN_CONVERSIONS_TABLE};
+use crate::connector::StreamSourceManagerRef;
+use crate::database_stats::{self, DATABASE_STATS_TABLE};
+use crate::dispatcher::plan_cache::ResolvedTable;
+use crate::dispatcher::query_tracker::{QueryTracker, QUERIES_TABLE};
+use crate::privileges::{self, PRIVILEGES_TABLE};
+use crate::shards::{self, SHARDS_TABLE};
+use crate::tenant_usage::{self, TENANT_USAGE_TABLE};
 use datafusion::arrow::datatypes::DataType;
 use datafusion::physical_plan::common::SizedRecordBatchStream;
 use datafusion::physical_plan::metrics::{ExecutionPlanMetricsSet, MemTrackingMetrics};
@@ -11,48 +22,468 @@ use datafusion::{
     sql::{planner::ContextProvider, TableReference},
 };
 
-use models::schema::{TableColumn, TableSchema};
+use models::schema::{TableColumn, TableSchema, TableStatistics, TskvTableSchema};
 
 use datafusion::arrow::record_batch::RecordBatch;
 
 use crate::table::ClusterTable;
-use datafusion::datasource::listing::{ListingTable, ListingTableConfig, ListingTableUrl};
-use datafusion::datasource::provider_as_source;
+use crate::task::store::TaskStoreRef;
+use crate::task::{TaskRunLog, TaskRunLogRef, TASK_RUNS_TABLE};
+use datafusion::datasource::{provider_as_source, MemTable};
 use models::schema::DatabaseSchema;
-
+use models::stream_source::{StreamSourceDefinition, StreamSourceStatus};
+use models::task::TaskDefinition;
+
+use crate::data_source::shard_scan::ShardScanContext;
+use coordinator::connection::NodeConnections;
+use coordinator::service::CoordinatorRef;
+use meta::error::MetaError;
+use meta::meta_client::MetaClientRef;
+use models::meta_data::{NodeId, RoleInfo, TenantMetaData, UserInfo};
+use parking_lot::Mutex;
 use spi::catalog::{
     MetaData, MetaDataRef, MetadataError, Result, DEFAULT_CATALOG, DEFAULT_DATABASE,
 };
 use spi::query::function::FuncMetaManagerRef;
 use std::sync::Arc;
-use tskv::engine::EngineRef;
 
 /// remote meta
-pub struct RemoteCatalogMeta {}
+///
+/// The metadata is owned by the meta service, the tenant is the name of the catalog.
+/// Changes are also applied to the local engine, so that the data of the node is
+/// organized in the same way as the standalone mode.
+#[derive(Clone)]
+pub struct RemoteCatalogMeta {
+    local: LocalCatalogMeta,
+    node_id: NodeId,
+    client: MetaClientRef,
+    connections: Arc<NodeConnections>,
+}
+
+impl RemoteCatalogMeta {
+    pub async fn new_with_default(
+        coord: CoordinatorRef,
+        func_manager: FuncMetaManagerRef,
+        stream_sources: StreamSourceManagerRef,
+        conversions: ColumnConversionsRef,
+        tasks: TaskStoreRef,
+    ) -> Result<Self> {
+        let connections = coord.connections().ok_or_else(|| MetadataError::External {
+            message: "the node is not in a cluster".to_string(),
+        })?;
+        let client = connections.meta();
+        let users = RemoteUserStore::open(client.clone()).await?;
+        let meta = Self {
+            node_id: coord.node_id(),
+            local: LocalCatalogMeta::new_with_default(
+                coord,
+                func_manager,
+                stream_sources,
+                conversions,
+                users,
+                tasks,
+            )
+            .await?,
+            client,
+            connections,
+        };
+        match meta.client.create_tenant(meta.catalog_name()).await {
+            Ok(_) | Err(MetaError::TenantAlreadyExists { .. }) => {}
+            Err(e) => return Err(meta_error(e)),
+        }
+        let database_name = meta.schema_name().to_string();
+        if let Err(e) = meta
+            .create_database(&database_name, DatabaseSchema::new(&database_name))
+            .await
+        {
+            match e {
+                MetadataError::DatabaseAlreadyExists { .. } => {}
+                _ => return Err(e),
+            }
+        };
+        Ok(meta)
+    }
+
+    pub fn coordinator(&self) -> CoordinatorRef {
+        self.local.coordinator()
+    }
+
+    pub fn client(&self) -> MetaClientRef {
+        self.client.clone()
+    }
+
+    /// Tables of the tenant are scanned on the data nodes owning their shards
+    pub fn shard_scan_context(&self) -> ShardScanContext {
+        ShardScanContext {
+            node_id: self.node_id,
+            tenant: self.catalog_name().to_string(),
+            connections: self.connections.clone(),
+        }
+    }
+
+    /// Metadata of the tenant cached by the meta client, which loads all the tenants
+    /// and reloads them once the meta service changes
+    pub(crate) fn tenant_meta(&self) -> Result<Arc<TenantMetaData>> {
+        self.client
+            .tenant_meta(self.catalog_name())
+            .ok_or_else(|| MetadataError::External {
+                message: format!("tenant {} not found", self.catalog_name()),
+            })
+    }
+
+    /// Applies the change to the local engine, it may already be applied by the other sessions
+    fn apply_local(&self, res: Result<()>) -> Result<()> {
+        match res {
+            Ok(_)
+            | Err(MetadataError::DatabaseAlreadyExists { .. })
+            | Err(MetadataError::TableAlreadyExists { .. })
+            | Err(MetadataError::DatabaseNotExists { .. })
+            | Err(MetadataError::TableNotExists { .. }) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Publishes the change of the table to the meta service, which rejects it if the
+    /// table is altered by another node meanwhile, the change is retried by the user
+    async fn update_table(
+        &self,
+        table_name: &str,
+        alter: impl FnOnce(&mut TskvTableSchema) + Send,
+    ) -> Result<()> {
+        let mut schema = match self.table(table_name.into())? {
+            TableSchema::TsKvTableSchema(schema) => schema,
+            TableSchema::ExternalTableSchema(_) => {
+                return Err(MetadataError::TableIsNotTsKv {
+                    table_name: table_name.to_string(),
+                })
+            }
+        };
+        let version = schema.schema_id;
+        alter(&mut schema);
+        self.client
+            .update_table(
+                self.catalog_name(),
+                &TableSchema::TsKvTableSchema(schema),
+                version,
+            )
+            .await
+            .map_err(meta_error)
+    }
+}
+
+#[async_trait]
+impl MetaData for RemoteCatalogMeta {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn with_catalog(&self, catalog_name: &str) -> Arc<dyn MetaData> {
+        let mut metadata = self.clone();
+        metadata.local.catalog_name = catalog_name.to_string();
+
+        Arc::new(metadata)
+    }
+
+    fn with_database(&self, database: &str) -> Arc<dyn MetaData> {
+        let mut metadata = self.clone();
+        metadata.local.database_name = database.to_string();
+
+        Arc::new(metadata)
+    }
+
+    fn catalog_name(&self) -> &str {
+        self.local.catalog_name()
+    }
+
+    fn schema_name(&self) -> &str {
+        self.local.schema_name()
+    }
+
+    fn table(&self, table: TableReference) -> Result<TableSchema> {
+        let name = table.resolve(self.catalog_name(), self.schema_name());
+        let meta = self.tenant_meta()?;
+        meta.database(name.schema)
+            .ok_or_else(|| MetadataError::DatabaseNotExists {
+                database_name: name.schema.to_string(),
+            })?
+            .tables
+            .get(name.table)
+            .cloned()
+            .ok_or_else(|| MetadataError::TableNotExists {
+                table_name: name.table.to_string(),
+            })
+    }
+
+    fn database(&self, name: &str) -> Result<DatabaseSchema> {
+        self.tenant_meta()?
+            .database(name)
+            .map(|e| e.schema.clone())
+            .ok_or(MetadataError::DatabaseNotExists {
+                database_name: name.to_string(),
+            })
+    }
+
+    fn function(&self) -> FuncMetaManagerRef {
+        self.local.function()
+    }
+
+    async fn drop_table(&self, name: &str) -> Result<()> {
+        let table: TableReference = name.into();
+        let name = table.resolve(self.catalog_name(), self.schema_name());
+        self.client
+            .drop_table(self.catalog_name(), name.schema, name.table)
+            .await
+            .map_err(meta_error)?;
+        self.apply_local(
+            self.local
+                .drop_table(&format!("{}.{}", name.schema, name.table))
+                .await,
+        )
+    }
+
+    async fn drop_database(&self, name: &str) -> Result<()> {
+        self.client
+            .drop_db(self.catalog_name(), name)
+            .await
+            .map_err(meta_error)?;
+        self.apply_local(self.local.drop_database(name).await)
+    }
+
+    async fn create_table(&self, name: &str, table_schema: TableSchema) -> Result<()> {
+        self.client
+            .create_table(self.catalog_name(), &table_schema)
+            .await
+            .map_err(meta_error)?;
+        self.apply_local(self.local.create_table(name, table_schema).await)
+    }
+
+    async fn create_database(&self, name: &str, database: DatabaseSchema) -> Result<()> {
+        self.client
+            .create_db(self.catalog_name(), &database)
+            .await
+            .map_err(meta_error)?;
+        self.apply_local(self.local.create_database(name, database).await)
+    }
+
+    fn database_names(&self) -> Result<Vec<String>> {
+        Ok(self.tenant_meta()?.database_names())
+    }
+
+    fn show_tables(&self, name: &Option<String>) -> Result<Vec<String>> {
+        let database_name = match name {
+            None => self.schema_name(),
+            Some(v) => v.as_str(),
+        };
+
+        self.tenant_meta()?
+            .table_names(database_name)
+            .ok_or_else(|| MetadataError::DatabaseNotExists {
+                database_name: database_name.to_string(),
+            })
+    }
+
+    async fn alter_database(&self, database: DatabaseSchema) -> Result<()> {
+        self.client
+            .alter_db(self.catalog_name(), &database)
+            .await
+            .map_err(meta_error)?;
+        self.local.alter_database(database).await
+    }
+
+    async fn alter_table_add_column(&self, table_name: &str, column: TableColumn) -> Result<()> {
+        let added = column.clone();
+        self.update_table(table_name, |schema| schema.add_column(added))
+            .await?;
+        self.apply_local(self.local.alter_table_add_column(table_name, column).await)
+    }
+
+    async fn alter_table_alter_column(
+        &self,
+        table_name: &str,
+        column_name: &str,
+        new_column: TableColumn,
+    ) -> Result<()> {
+        let changed = new_column.clone();
+        self.update_table(table_name, |schema| {
+            schema.change_column(column_name, changed)
+        })
+        .await?;
+        self.apply_local(
+            self.local
+                .alter_table_alter_column(table_name, column_name, new_column)
+                .await,
+        )
+    }
+
+    async fn alter_table_drop_column(&self, table_name: &str, column_name: &str) -> Result<()> {
+        self.update_table(table_name, |schema| schema.drop_column(column_name))
+            .await?;
+        self.apply_local(
+            self.local
+                .alter_table_drop_column(table_name, column_name)
+                .await,
+        )
+    }
+
+    async fn alter_table_statistics(
+        &self,
+        table_name: &str,
+        statistics: TableStatistics,
+    ) -> Result<()> {
+        let analyzed = statistics.clone();
+        self.update_table(table_name, |schema| schema.set_statistics(analyzed))
+            .await?;
+        self.apply_local(
+            self.local
+                .alter_table_statistics(table_name, statistics)
+                .await,
+        )
+    }
+
+    fn create_stream_source(&self, source: StreamSourceDefinition) -> Result<()> {
+        self.database(&source.database)?;
+        self.local
+            .stream_sources
+            .create(self.catalog_name(), source)
+    }
+
+    fn drop_stream_source(&self, name: &str) -> Result<()> {
+        self.local.drop_stream_source(name)
+    }
+
+    fn stream_sources(&self) -> Result<Vec<StreamSourceStatus>> {
+        self.local.stream_sources()
+    }
+
+    async fn create_user(&self, user: UserInfo) -> Result<()> {
+        self.local.create_user(user).await
+    }
+
+    async fn alter_user(&self, user: UserInfo) -> Result<()> {
+        self.local.alter_user(user).await
+    }
+
+    async fn drop_user(&self, name: &str) -> Result<()> {
+        self.local.drop_user(name).await
+    }
+
+    fn user(&self, name: &str) -> Result<Option<UserInfo>> {
+        self.local.user(name)
+    }
+
+    fn users(&self) -> Result<Vec<UserInfo>> {
+        self.local.users()
+    }
+
+    async fn create_role(&self, role: RoleInfo) -> Result<()> {
+        self.local.create_role(role).await
+    }
+
+    async fn alter_role(&self, role: RoleInfo) -> Result<()> {
+        self.local.alter_role(role).await
+    }
+
+    async fn drop_role(&self, name: &str) -> Result<()> {
+        self.local.drop_role(name).await
+    }
+
+    fn role(&self, name: &str) -> Result<Option<RoleInfo>> {
+        self.local.role(name)
+    }
+
+    fn roles(&self) -> Result<Vec<RoleInfo>> {
+        self.local.roles()
+    }
+
+    async fn create_task(&self, task: TaskDefinition) -> Result<()> {
+        self.database(&task.database)?;
+        self.local.tasks.create_task(task).await
+    }
+
+    async fn alter_task(&self, task: TaskDefinition) -> Result<()> {
+        self.local.alter_task(task).await
+    }
+
+    async fn drop_task(&self, name: &str) -> Result<()> {
+        self.local.drop_task(name).await
+    }
+
+    fn tasks(&self) -> Result<Vec<TaskDefinition>> {
+        self.local.tasks()
+    }
+}
+
+pub(crate) fn meta_error(e: MetaError) -> MetadataError {
+    match e {
+        MetaError::DatabaseAlreadyExists { database } => MetadataError::DatabaseAlreadyExists {
+            database_name: database,
+        },
+        MetaError::DatabaseNotFound { database } => MetadataError::DatabaseNotExists {
+            database_name: database,
+        },
+        MetaError::TableAlreadyExists { table } => {
+            MetadataError::TableAlreadyExists { table_name: table }
+        }
+        MetaError::TableNotFound { table } => MetadataError::TableNotExists { table_name: table },
+        MetaError::UserAlreadyExists { user } => {
+            MetadataError::UserAlreadyExists { user_name: user }
+        }
+        MetaError::UserNotFound { user } => MetadataError::UserNotExists { user_name: user },
+        MetaError::RoleAlreadyExists { role } => {
+            MetadataError::RoleAlreadyExists { role_name: role }
+        }
+        MetaError::RoleNotFound { role } => MetadataError::RoleNotExists { role_name: role },
+        MetaError::TaskAlreadyExists { task } => {
+            MetadataError::TaskAlreadyExists { task_name: task }
+        }
+        MetaError::TaskNotFound { task } => MetadataError::TaskNotExists { task_name: task },
+        e => MetadataError::External {
+            message: e.to_string(),
+        },
+    }
+}
 
 /// local meta
 #[derive(Clone)]
 pub struct LocalCatalogMeta {
     catalog_name: String,
     database_name: String,
-    engine: EngineRef,
+    coord: CoordinatorRef,
     catalog: UserCatalogRef,
     func_manager: FuncMetaManagerRef,
+    stream_sources: StreamSourceManagerRef,
+    conversions: ColumnConversionsRef,
+    users: UserStoreRef,
+    tasks: TaskStoreRef,
 }
 
 impl LocalCatalogMeta {
-    pub fn new_with_default(engine: EngineRef, func_manager: FuncMetaManagerRef) -> Result<Self> {
+    pub async fn new_with_default(
+        coord: CoordinatorRef,
+        func_manager: FuncMetaManagerRef,
+        stream_sources: StreamSourceManagerRef,
+        conversions: ColumnConversionsRef,
+        users: UserStoreRef,
+        tasks: TaskStoreRef,
+    ) -> Result<Self> {
         let meta = Self {
             catalog_name: DEFAULT_CATALOG.to_string(),
             database_name: DEFAULT_DATABASE.to_string(),
-            engine: engine.clone(),
-            catalog: Arc::new(UserCatalog::new(engine)),
+            catalog: Arc::new(UserCatalog::new(coord.engine())),
             func_manager,
+            stream_sources,
+            conversions,
+            users,
+            tasks,
+            coord,
         };
-        if let Err(e) = meta.create_database(
-            &meta.database_name,
-            DatabaseSchema::new(&meta.database_name),
-        ) {
+        if let Err(e) = meta
+            .create_database(
+                &meta.database_name,
+                DatabaseSchema::new(&meta.database_name),
+            )
+            .await
+        {
             match e {
                 MetadataError::DatabaseAlreadyExists { .. } => {}
                 _ => return Err(e),
@@ -60,8 +491,17 @@ impl LocalCatalogMeta {
         };
         Ok(meta)
     }
+
+    pub fn coordinator(&self) -> CoordinatorRef {
+        self.coord.clone()
+    }
+
+    pub fn column_conversions(&self) -> ColumnConversionsRef {
+        self.conversions.clone()
+    }
 }
 
+#[async_trait]
 impl MetaData for LocalCatalogMeta {
     fn as_any(&self) -> &dyn Any {
         self
@@ -109,7 +549,8 @@ impl MetaData for LocalCatalogMeta {
     }
 
     fn database(&self, name: &str) -> Result<DatabaseSchema> {
-        self.engine
+        self.coord
+            .engine()
             .get_db_schema(name)
             .ok_or(MetadataError::DatabaseNotExists {
                 database_name: name.to_string(),
@@ -120,7 +561,7 @@ impl MetaData for LocalCatalogMeta {
         self.func_manager.clone()
     }
 
-    fn drop_table(&self, name: &str) -> Result<()> {
+    async fn drop_table(&self, name: &str) -> Result<()> {
         let table: TableReference = name.into();
         let name = table.resolve(self.catalog_name.as_str(), self.database_name.as_str());
         self.catalog
@@ -132,11 +573,11 @@ impl MetaData for LocalCatalogMeta {
             .map(|_| ())
     }
 
-    fn drop_database(&self, name: &str) -> Result<()> {
+    async fn drop_database(&self, name: &str) -> Result<()> {
         self.catalog.deregister_schema(name).map(|_| ())
     }
 
-    fn create_table(&self, name: &str, table_schema: TableSchema) -> Result<()> {
+    async fn create_table(&self, name: &str, table_schema: TableSchema) -> Result<()> {
         let table: TableReference = name.into();
         let table_ref = table.resolve(self.catalog_name.as_str(), self.database_name.as_str());
 
@@ -150,8 +591,8 @@ impl MetaData for LocalCatalogMeta {
             .map(|_| ())
     }
 
-    fn create_database(&self, name: &str, database: DatabaseSchema) -> Result<()> {
-        let user_schema = Database::new(name.to_string(), self.engine.clone(), database);
+    async fn create_database(&self, name: &str, database: DatabaseSchema) -> Result<()> {
+        let user_schema = Database::new(name.to_string(), self.coord.engine(), database);
         self.catalog
             .register_schema(name, Arc::new(user_schema))
             .map(|_| ())
@@ -175,15 +616,16 @@ impl MetaData for LocalCatalogMeta {
             .table_names()
     }
 
-    fn alter_database(&self, database: DatabaseSchema) -> Result<()> {
-        self.engine
+    async fn alter_database(&self, database: DatabaseSchema) -> Result<()> {
+        self.coord
+            .engine()
             .alter_database(&database)
             .map_err(|e| MetadataError::External {
                 message: format!("{}", e),
             })
     }
 
-    fn alter_table_add_column(&self, table_name: &str, column: TableColumn) -> Result<()> {
+    async fn alter_table_add_column(&self, table_name: &str, column: TableColumn) -> Result<()> {
         let table_ref = TableReference::from(table_name)
             .resolve(self.catalog_name.as_str(), self.database_name.as_str());
         self.catalog
@@ -194,7 +636,7 @@ impl MetaData for LocalCatalogMeta {
             .table_add_column(table_ref.table, column)
     }
 
-    fn alter_table_alter_column(
+    async fn alter_table_alter_column(
         &self,
         table_name: &str,
         column_name: &str,
@@ -210,7 +652,7 @@ impl MetaData for LocalCatalogMeta {
             .table_alter_column(table_ref.table, column_name, new_column)
     }
 
-    fn alter_table_drop_column(&self, table_name: &str, column_name: &str) -> Result<()> {
+    async fn alter_table_drop_column(&self, table_name: &str, column_name: &str) -> Result<()> {
         let table_ref = TableReference::from(table_name)
             .resolve(self.catalog_name.as_str(), self.database_name.as_str());
 
@@ -221,16 +663,211 @@ impl MetaData for LocalCatalogMeta {
             })?
             .table_drop_column(table_name, column_name)
     }
+
+    async fn alter_table_statistics(
+        &self,
+        table_name: &str,
+        statistics: TableStatistics,
+    ) -> Result<()> {
+        let table_ref = TableReference::from(table_name)
+            .resolve(self.catalog_name.as_str(), self.database_name.as_str());
+
+        self.catalog
+            .schema(table_ref.schema)
+            .ok_or_else(|| MetadataError::DatabaseNotExists {
+                database_name: table_ref.schema.to_string(),
+            })?
+            .table_set_statistics(table_ref.table, statistics)
+    }
+
+    fn create_stream_source(&self, source: StreamSourceDefinition) -> Result<()> {
+        self.database(&source.database)?;
+        self.stream_sources.create(&self.catalog_name, source)
+    }
+
+    fn drop_stream_source(&self, name: &str) -> Result<()> {
+        self.stream_sources.remove(name)
+    }
+
+    fn stream_sources(&self) -> Result<Vec<StreamSourceStatus>> {
+        Ok(self.stream_sources.list())
+    }
+
+    async fn create_user(&self, user: UserInfo) -> Result<()> {
+        self.users.create_user(user).await
+    }
+
+    async fn alter_user(&self, user: UserInfo) -> Result<()> {
+        self.users.alter_user(user).await
+    }
+
+    async fn drop_user(&self, name: &str) -> Result<()> {
+        self.users.drop_user(name).await
+    }
+
+    fn user(&self, name: &str) -> Result<Option<UserInfo>> {
+        self.users.user(name)
+    }
+
+    fn users(&self) -> Result<Vec<UserInfo>> {
+        self.users.users()
+    }
+
+    async fn create_role(&self, role: RoleInfo) -> Result<()> {
+        self.users.create_role(role).await
+    }
+
+    async fn alter_role(&self, role: RoleInfo) -> Result<()> {
+        self.users.alter_role(role).await
+    }
+
+    async fn drop_role(&self, name: &str) -> Result<()> {
+        self.users.drop_role(name).await
+    }
+
+    fn role(&self, name: &str) -> Result<Option<RoleInfo>> {
+        self.users.role(name)
+    }
+
+    fn roles(&self) -> Result<Vec<RoleInfo>> {
+        self.users.roles()
+    }
+
+    async fn create_task(&self, task: TaskDefinition) -> Result<()> {
+        self.database(&task.database)?;
+        self.tasks.create_task(task).await
+    }
+
+    async fn alter_task(&self, task: TaskDefinition) -> Result<()> {
+        self.tasks.alter_task(task).await
+    }
+
+    async fn drop_task(&self, name: &str) -> Result<()> {
+        self.tasks.drop_task(&self.catalog_name, name).await
+    }
+
+    fn tasks(&self) -> Result<Vec<TaskDefinition>> {
+        Ok(self
+            .tasks
+            .tasks()?
+            .into_iter()
+            .filter(|e| e.tenant == self.catalog_name)
+            .collect())
+    }
 }
 
 pub struct MetadataProvider {
     meta: MetaDataRef,
+    audit_log: AuditLogRef,
+    query_tracker: Arc<QueryTracker>,
+    task_runs: TaskRunLogRef,
+    /// The user the query is run by
+    user: String,
+    /// The tables resolved by the planner, `None` once a system table is resolved,
+    /// the rows of which are fixed in the plan
+    resolved_tables: Mutex<Option<Vec<ResolvedTable>>>,
 }
 
 impl MetadataProvider {
     #[inline(always)]
-    pub fn new(meta: MetaDataRef) -> Self {
-        Self { meta }
+    pub fn new(
+        meta: MetaDataRef,
+        audit_log: AuditLogRef,
+        query_tracker: Arc<QueryTracker>,
+        task_runs: TaskRunLogRef,
+        user: String,
+    ) -> Self {
+        Self {
+            meta,
+            audit_log,
+            query_tracker,
+            task_runs,
+            user,
+            resolved_tables: Mutex::new(Some(vec![])),
+        }
+    }
+
+    /// The tables resolved so far with their schemas, `None` if the plan must not be cached
+    pub fn resolved_tables(&self) -> Option<Vec<ResolvedTable>> {
+        self.resolved_tables.lock().clone()
+    }
+
+    fn queries_table(&self) -> datafusion::common::Result<Arc<dyn TableSource>> {
+        // The admins see the queries of all the users, the others their own only
+        let user = (!self.is_admin()?).then_some(self.user.as_str());
+        let batch = self.query_tracker.record_batch(user)?;
+        let table = MemTable::try_new(QueryTracker::schema(), vec![vec![batch]])?;
+        Ok(provider_as_source(Arc::new(table)))
+    }
+
+    fn database_stats_table(&self) -> datafusion::common::Result<Arc<dyn TableSource>> {
+        // The admins see the databases of all the tenants, the others those of the tenant only
+        let tenant = (!self.is_admin()?).then_some(self.meta.catalog_name());
+        let batch = database_stats::record_batch(tenant)?;
+        let table = MemTable::try_new(database_stats::schema(), vec![vec![batch]])?;
+        Ok(provider_as_source(Arc::new(table)))
+    }
+
+    fn tenant_usage_table(&self) -> datafusion::common::Result<Arc<dyn TableSource>> {
+        let batch = tenant_usage::record_batch()?;
+        let table = MemTable::try_new(tenant_usage::schema(), vec![vec![batch]])?;
+        Ok(provider_as_source(Arc::new(table)))
+    }
+
+    fn shards_table(&self) -> datafusion::common::Result<Arc<dyn TableSource>> {
+        let batch = shards::record_batch(self.meta.as_ref(), None)?;
+        let table = MemTable::try_new(shards::schema(), vec![vec![batch]])?;
+        Ok(provider_as_source(Arc::new(table)))
+    }
+
+    fn is_admin(&self) -> datafusion::common::Result<bool> {
+        Ok(self
+            .meta
+            .user(&self.user)
+            .map_err(|e| DataFusionError::External(Box::new(e)))?
+            .map_or(false, |e| e.is_admin))
+    }
+
+    /// The privileges of all the users and the roles for the admins, only those of
+    /// the current user for the others
+    fn privileges_table(&self) -> datafusion::common::Result<Arc<dyn TableSource>> {
+        let grantee = (!self.is_admin()?).then_some(self.user.as_str());
+        let batch = privileges::record_batch(self.meta.as_ref(), grantee)?;
+        let table = MemTable::try_new(privileges::schema(), vec![vec![batch]])?;
+        Ok(provider_as_source(Arc::new(table)))
+    }
+
+    /// The runs of the tasks of the tenant by this node
+    fn task_runs_table(&self) -> datafusion::common::Result<Arc<dyn TableSource>> {
+        let batch = self.task_runs.record_batch(self.meta.catalog_name())?;
+        let table = MemTable::try_new(TaskRunLog::schema(), vec![vec![batch]])?;
+        Ok(provider_as_source(Arc::new(table)))
+    }
+
+    /// The conversions of the columns of the tables of the tenant, run on a single
+    /// node only
+    fn column_conversions_table(&self) -> datafusion::common::Result<Arc<dyn TableSource>> {
+        let batch = match self.meta.as_any().downcast_ref::<LocalCatalogMeta>() {
+            Some(local) => local
+                .column_conversions()
+                .record_batch(self.meta.catalog_name())?,
+            None => RecordBatch::new_empty(ColumnConversions::schema()),
+        };
+        let table = MemTable::try_new(ColumnConversions::schema(), vec![vec![batch]])?;
+        Ok(provider_as_source(Arc::new(table)))
+    }
+
+    /// Only the admins read the audit events
+    fn audit_table(&self) -> datafusion::common::Result<Arc<dyn TableSource>> {
+        if !self.is_admin()? {
+            return Err(DataFusionError::Plan(format!(
+                "permission denied: user {} can not read {}.{}",
+                self.user, SYSTEM_DATABASE, AUDIT_TABLE
+            )));
+        }
+        let batch = self.audit_log.record_batch()?;
+        let table = MemTable::try_new(AuditLog::schema(), vec![vec![batch]])?;
+        Ok(provider_as_source(Arc::new(table)))
     }
 }
 impl ContextProvider for MetadataProvider {
@@ -238,25 +875,53 @@ impl ContextProvider for MetadataProvider {
         &self,
         name: TableReference,
     ) -> datafusion::common::Result<Arc<dyn TableSource>> {
+        let resolved = name.resolve(self.meta.catalog_name(), self.meta.schema_name());
+        if resolved.schema == SYSTEM_DATABASE {
+            *self.resolved_tables.lock() = None;
+        }
+        if resolved.schema == SYSTEM_DATABASE && resolved.table == AUDIT_TABLE {
+            return self.audit_table();
+        }
+        if resolved.schema == SYSTEM_DATABASE && resolved.table == QUERIES_TABLE {
+            return self.queries_table();
+        }
+        if resolved.schema == SYSTEM_DATABASE && resolved.table == DATABASE_STATS_TABLE {
+            return self.database_stats_table();
+        }
+        if resolved.schema == SYSTEM_DATABASE && resolved.table == TENANT_USAGE_TABLE {
+            return self.tenant_usage_table();
+        }
+        if resolved.schema == SYSTEM_DATABASE && resolved.table == SHARDS_TABLE {
+            return self.shards_table();
+        }
+        if resolved.schema == SYSTEM_DATABASE && resolved.table == PRIVILEGES_TABLE {
+            return self.privileges_table();
+        }
+        if resolved.schema == SYSTEM_DATABASE && resolved.table == TASK_RUNS_TABLE {
+            return self.task_runs_table();
+        }
+        if resolved.schema == SYSTEM_DATABASE && resolved.table == COLUMN_CONVERSIONS_TABLE {
+            return self.column_conversions_table();
+        }
+
         match self.meta.table(name) {
             Ok(table) => {
-                // todo: we need a DataSourceManager to get engine and build table provider
-                let local_catalog_meta = self
-                    .meta
-                    .as_any()
-                    .downcast_ref::<LocalCatalogMeta>()
-                    .ok_or_else(|| DataFusionError::Plan("failed to get meta data".to_string()))?;
+                if let Some(tables) = self.resolved_tables.lock().as_mut() {
+                    tables.push(ResolvedTable {
+                        database: resolved.schema.to_string(),
+                        table: resolved.table.to_string(),
+                        schema: table.clone(),
+                    });
+                }
                 match table {
-                    TableSchema::TsKvTableSchema(schema) => Ok(provider_as_source(Arc::new(
-                        ClusterTable::new(local_catalog_meta.engine.clone(), schema),
-                    ))),
+                    TableSchema::TsKvTableSchema(schema) => {
+                        let table = cluster_table(&self.meta, schema).ok_or_else(|| {
+                            DataFusionError::Plan("failed to get meta data".to_string())
+                        })?;
+                        Ok(provider_as_source(Arc::new(table)))
+                    }
                     TableSchema::ExternalTableSchema(schema) => {
-                        let table_path = ListingTableUrl::parse(&schema.location)?;
-                        let options = schema.table_options()?;
-                        let config = ListingTableConfig::new(table_path)
-                            .with_listing_options(options)
-                            .with_schema(Arc::new(schema.schema));
-                        Ok(provider_as_source(Arc::new(ListingTable::try_new(config)?)))
+                        Ok(provider_as_source(Arc::new(schema.table_provider()?)))
                     }
                 }
             }
@@ -286,6 +951,25 @@ impl ContextProvider for MetadataProvider {
     }
 }
 
+/// The tskv table scanned by this node, or by the data nodes owning the shards
+/// of the table in a cluster
+// todo: we need a DataSourceManager to get engine and build table provider
+pub(crate) fn cluster_table(meta: &MetaDataRef, schema: TskvTableSchema) -> Option<ClusterTable> {
+    let database_options = meta.database(&schema.db).ok().map(|e| e.config);
+    let any = meta.as_any();
+    let table = if let Some(local) = any.downcast_ref::<LocalCatalogMeta>() {
+        ClusterTable::new(local.coordinator(), meta.catalog_name(), schema)
+    } else {
+        let remote = any.downcast_ref::<RemoteCatalogMeta>()?;
+        ClusterTable::new(remote.coordinator(), meta.catalog_name(), schema)
+            .with_shard_scan(remote.shard_scan_context())
+    };
+    Some(match database_options {
+        Some(options) => table.with_database_options(options),
+        None => table,
+    })
+}
+
 pub fn stream_from_batches(batches: Vec<Arc<RecordBatch>>) -> SendableRecordBatchStream {
     let dummy_metrics = ExecutionPlanMetricsSet::new();
     let mem_metrics = MemTrackingMetrics::new(&dummy_metrics, 0);
@@ -0,0 +1,261 @@
+use std::sync::Arc;
+
+use datafusion::arrow::array::StringBuilder;
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::error::{DataFusionError, Result};
+use models::meta_data::{RoleInfo, UserInfo};
+use spi::catalog::MetaData;
+
+/// Table of the privileges granted to the users and the roles of the tenant,
+/// `SELECT * FROM system.privileges`, or `SHOW GRANTS [FOR <user or role>]`.
+///
+/// The privileges of the roles granted to a user are listed for the user too,
+/// `granted_via` is the role they are granted by.
+pub const PRIVILEGES_TABLE: &str = "privileges";
+
+const GRANTEE_USER: &str = "user";
+const GRANTEE_ROLE: &str = "role";
+
+const PRIVILEGE_ADMIN: &str = "ADMIN";
+const PRIVILEGE_ROLE: &str = "ROLE";
+const PRIVILEGE_SELECT: &str = "SELECT";
+const PRIVILEGE_POLICY: &str = "POLICY";
+const PRIVILEGE_GROUP: &str = "GROUP";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct GrantRow {
+    grantee: String,
+    grantee_type: &'static str,
+    privilege: &'static str,
+    /// The role granted, the table the columns or the rows of which are granted,
+    /// or the group the role is granted to
+    object: Option<String>,
+    /// The columns granted, or the policy of the rows granted
+    detail: Option<String>,
+    granted_via: Option<String>,
+}
+
+pub fn schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("grantee", DataType::Utf8, false),
+        Field::new("grantee_type", DataType::Utf8, false),
+        Field::new("privilege", DataType::Utf8, false),
+        Field::new("object", DataType::Utf8, true),
+        Field::new("detail", DataType::Utf8, true),
+        Field::new("granted_via", DataType::Utf8, true),
+    ]))
+}
+
+/// The privileges of the user or the role, or of all the users and the roles if
+/// `None`
+pub fn record_batch(meta: &dyn MetaData, grantee: Option<&str>) -> Result<RecordBatch> {
+    let tenant = meta.catalog_name();
+    let roles = meta.roles().map_err(external)?;
+
+    let mut rows = vec![];
+    match grantee {
+        Some(name) => {
+            if let Some(user) = meta.user(name).map_err(external)? {
+                rows.extend(user_rows(&user, &roles, tenant));
+            } else if let Some(role) = roles.iter().find(|e| e.name == name) {
+                rows.extend(role_rows(role, tenant));
+            } else {
+                return Err(DataFusionError::Plan(format!(
+                    "user or role {} not found",
+                    name
+                )));
+            }
+        }
+        None => {
+            for user in meta.users().map_err(external)? {
+                rows.extend(user_rows(&user, &roles, tenant));
+            }
+            for role in roles.iter() {
+                rows.extend(role_rows(role, tenant));
+            }
+        }
+    }
+
+    to_record_batch(&rows)
+}
+
+fn user_rows(user: &UserInfo, roles: &[RoleInfo], tenant: &str) -> Vec<GrantRow> {
+    let row = |privilege, object: Option<String>, detail, granted_via| GrantRow {
+        grantee: user.name.clone(),
+        grantee_type: GRANTEE_USER,
+        privilege,
+        object,
+        detail,
+        granted_via,
+    };
+
+    let mut rows = vec![];
+    if user.is_admin {
+        rows.push(row(PRIVILEGE_ADMIN, None, None, None));
+    }
+    for name in user.roles.iter() {
+        rows.push(row(PRIVILEGE_ROLE, Some(name.clone()), None, None));
+        if let Some(role) = roles.iter().find(|e| &e.name == name) {
+            for (privilege, object, detail) in table_privileges(role, tenant) {
+                rows.push(row(
+                    privilege,
+                    Some(object),
+                    Some(detail),
+                    Some(name.clone()),
+                ));
+            }
+        }
+    }
+    rows
+}
+
+fn role_rows(role: &RoleInfo, tenant: &str) -> Vec<GrantRow> {
+    let row = |privilege, object, detail| GrantRow {
+        grantee: role.name.clone(),
+        grantee_type: GRANTEE_ROLE,
+        privilege,
+        object: Some(object),
+        detail,
+        granted_via: None,
+    };
+
+    let mut rows = table_privileges(role, tenant)
+        .into_iter()
+        .map(|(privilege, object, detail)| row(privilege, object, Some(detail)))
+        .collect::<Vec<_>>();
+    for group in role.groups.iter() {
+        rows.push(row(PRIVILEGE_GROUP, group.clone(), None));
+    }
+    rows
+}
+
+/// The privileges of the role on the tables of the tenant
+fn table_privileges(role: &RoleInfo, tenant: &str) -> Vec<(&'static str, String, String)> {
+    let columns = role
+        .column_privileges
+        .iter()
+        .filter(|e| e.tenant == tenant)
+        .map(|e| {
+            (
+                PRIVILEGE_SELECT,
+                format!("{}.{}", e.database, e.table),
+                e.columns.join(","),
+            )
+        });
+    let policies = role
+        .policies
+        .iter()
+        .filter(|e| e.tenant == tenant)
+        .map(|e| {
+            (
+                PRIVILEGE_POLICY,
+                format!("{}.{}", e.database, e.table),
+                format!("{} USING ({})", e.name, e.predicate),
+            )
+        });
+    columns.chain(policies).collect()
+}
+
+fn to_record_batch(rows: &[GrantRow]) -> Result<RecordBatch> {
+    let mut grantees = StringBuilder::new();
+    let mut grantee_types = StringBuilder::new();
+    let mut privileges = StringBuilder::new();
+    let mut objects = StringBuilder::new();
+    let mut details = StringBuilder::new();
+    let mut granted_vias = StringBuilder::new();
+    for row in rows {
+        grantees.append_value(&row.grantee);
+        grantee_types.append_value(row.grantee_type);
+        privileges.append_value(row.privilege);
+        objects.append_option(row.object.as_deref());
+        details.append_option(row.detail.as_deref());
+        granted_vias.append_option(row.granted_via.as_deref());
+    }
+
+    Ok(RecordBatch::try_new(
+        schema(),
+        vec![
+            Arc::new(grantees.finish()),
+            Arc::new(grantee_types.finish()),
+            Arc::new(privileges.finish()),
+            Arc::new(objects.finish()),
+            Arc::new(details.finish()),
+            Arc::new(granted_vias.finish()),
+        ],
+    )?)
+}
+
+fn external(e: impl std::error::Error + Send + Sync + 'static) -> DataFusionError {
+    DataFusionError::External(Box::new(e))
+}
+
+#[cfg(test)]
+mod test {
+    use models::meta_data::{ColumnPrivilege, RowPolicy};
+
+    use super::*;
+
+    #[test]
+    fn test_user_rows() {
+        let role = RoleInfo {
+            name: "analyst".to_string(),
+            policies: vec![RowPolicy {
+                name: "east".to_string(),
+                tenant: "cnosdb".to_string(),
+                database: "public".to_string(),
+                table: "air".to_string(),
+                predicate: "region = 'east'".to_string(),
+            }],
+            column_privileges: vec![
+                ColumnPrivilege {
+                    tenant: "cnosdb".to_string(),
+                    database: "public".to_string(),
+                    table: "air".to_string(),
+                    columns: vec!["time".to_string(), "pressure".to_string()],
+                },
+                ColumnPrivilege {
+                    tenant: "other".to_string(),
+                    database: "public".to_string(),
+                    table: "air".to_string(),
+                    columns: vec!["time".to_string()],
+                },
+            ],
+            groups: vec!["ops".to_string()],
+        };
+        let user = UserInfo {
+            name: "bob".to_string(),
+            is_admin: false,
+            password_hash: String::new(),
+            tokens: vec![],
+            roles: vec!["analyst".to_string()],
+            provider: None,
+        };
+
+        let rows = user_rows(&user, &[role.clone()], "cnosdb");
+        let summary = rows
+            .iter()
+            .map(|e| (e.privilege, e.detail.as_deref(), e.granted_via.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                (PRIVILEGE_ROLE, None, None),
+                (PRIVILEGE_SELECT, Some("time,pressure"), Some("analyst")),
+                (
+                    PRIVILEGE_POLICY,
+                    Some("east USING (region = 'east')"),
+                    Some("analyst")
+                ),
+            ]
+        );
+
+        let rows = role_rows(&role, "cnosdb");
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[2].privilege, PRIVILEGE_GROUP);
+        assert_eq!(rows[2].object.as_deref(), Some("ops"));
+
+        let batch = to_record_batch(&rows).unwrap();
+        assert_eq!(batch.schema(), schema());
+    }
+}
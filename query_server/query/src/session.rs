@@ -0,0 +1,485 @@
+//! Sessions of the clients connected to the server, `SHOW SESSIONS`.
+//!
+//! A session lives as long as the connection of the client, the user of it is
+//! known once a request of the connection is authenticated, the connection may not
+//! be used by another user then. `KILL SESSION` closes the connection, which
+//! cancels the queries running on it.
+//!
+//! The connections of the server, by `max_server_connections`, and of each user are
+//! limited, and those without a request for longer than the idle timeout are closed,
+//! whether by http or grpc.
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use config::QueryConfig;
+
+use datafusion::arrow::array::{StringBuilder, TimestampNanosecondBuilder, UInt64Builder};
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use datafusion::arrow::error::ArrowError;
+use datafusion::arrow::record_batch::RecordBatch;
+use models::error_code::ErrorCode;
+use parking_lot::{Mutex, RwLock};
+use snafu::Snafu;
+use tokio::sync::Notify;
+
+pub type SessionId = u64;
+pub type SessionRef = Arc<Session>;
+pub type SessionRegistryRef = Arc<SessionRegistry>;
+
+/// The idle timeout reloaded is applied within
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Snafu)]
+pub enum SessionError {
+    #[snafu(display("Too many connections, {} at most", max))]
+    TooManyConnections { max: usize },
+
+    #[snafu(display("Too many connections of user {}, {} at most", user, max))]
+    TooManyUserConnections { user: String, max: usize },
+
+    #[snafu(display("Connection of user {} may not be used by user {}", session_user, user))]
+    UserMismatch { session_user: String, user: String },
+}
+
+impl SessionError {
+    pub fn error_code(&self) -> ErrorCode {
+        match self {
+            Self::TooManyConnections { .. } | Self::TooManyUserConnections { .. } => {
+                ErrorCode::TooManyConnections
+            }
+            Self::UserMismatch { .. } => ErrorCode::PermissionDenied,
+        }
+    }
+}
+
+pub struct Session {
+    id: SessionId,
+    client_addr: String,
+    protocol: &'static str,
+    /// Unix timestamp in nanoseconds
+    connected_at: i64,
+    user: RwLock<Option<String>>,
+    /// The queries running and the time they started by the ids of them, the
+    /// requests of a connection may be in flight at the same time over http/2
+    queries: Mutex<BTreeMap<u64, (String, Instant)>>,
+    next_query_id: AtomicU64,
+    /// Requests in flight and the time the last one finished
+    requests: AtomicUsize,
+    last_active: Mutex<Instant>,
+    killed: AtomicBool,
+    kill: Notify,
+}
+
+impl Session {
+    pub fn id(&self) -> SessionId {
+        self.id
+    }
+
+    pub fn user(&self) -> Option<String> {
+        self.user.read().clone()
+    }
+
+    /// Marks the session active until the guard is dropped
+    pub fn begin_request(self: &Arc<Self>) -> SessionRequest {
+        self.requests.fetch_add(1, Ordering::AcqRel);
+        SessionRequest {
+            session: self.clone(),
+        }
+    }
+
+    /// The time the session is idle for the timeout, later than now if a request
+    /// is in flight
+    pub fn idle_deadline(&self, timeout: Duration) -> Instant {
+        if self.requests.load(Ordering::Acquire) > 0 {
+            return Instant::now() + timeout;
+        }
+        *self.last_active.lock() + timeout
+    }
+
+    /// Records the query running on the session until the guard is dropped
+    pub fn begin_query(self: &Arc<Self>, sql: &str) -> SessionQuery {
+        let id = self.next_query_id.fetch_add(1, Ordering::Relaxed);
+        self.queries
+            .lock()
+            .insert(id, (sql.to_string(), Instant::now()));
+        SessionQuery {
+            session: self.clone(),
+            id,
+        }
+    }
+
+    /// The query running the longest if any
+    fn query(&self) -> Option<(String, Instant)> {
+        self.queries.lock().values().next().cloned()
+    }
+
+    /// Closes the connection of the session
+    pub fn kill(&self) {
+        self.killed.store(true, Ordering::Release);
+        self.kill.notify_waiters();
+    }
+
+    pub fn is_killed(&self) -> bool {
+        self.killed.load(Ordering::Acquire)
+    }
+
+    /// Resolved once the session is killed
+    pub async fn killed(&self) {
+        loop {
+            let notified = self.kill.notified();
+            if self.is_killed() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+/// Records the time the request of the session finished
+pub struct SessionRequest {
+    session: SessionRef,
+}
+
+impl Drop for SessionRequest {
+    fn drop(&mut self) {
+        *self.session.last_active.lock() = Instant::now();
+        self.session.requests.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Clears the query of the session once it is finished
+pub struct SessionQuery {
+    session: SessionRef,
+    id: u64,
+}
+
+impl Drop for SessionQuery {
+    fn drop(&mut self) {
+        self.session.queries.lock().remove(&self.id);
+    }
+}
+
+/// Unregisters the session once the connection is closed
+pub struct SessionGuard {
+    registry: SessionRegistryRef,
+    session: SessionRef,
+}
+
+impl SessionGuard {
+    pub fn session(&self) -> &SessionRef {
+        &self.session
+    }
+
+    /// The time the session is closed for being idle, None if never
+    pub fn idle_deadline(&self) -> Option<Instant> {
+        self.registry
+            .idle_timeout()
+            .map(|timeout| self.session.idle_deadline(timeout))
+    }
+
+    /// Resolved once the session is killed or idle for the timeout, for the
+    /// connections closed at once rather than gracefully
+    pub fn closed(&self) -> impl Future<Output = ()> + Send + 'static {
+        let registry = self.registry.clone();
+        let session = self.session.clone();
+        async move {
+            loop {
+                let deadline = registry
+                    .idle_timeout()
+                    .map(|timeout| session.idle_deadline(timeout));
+                let wakeup = deadline.unwrap_or_else(|| Instant::now() + IDLE_CHECK_INTERVAL);
+                tokio::select! {
+                    _ = session.killed() => return,
+                    _ = tokio::time::sleep_until(wakeup.into()) => {
+                        if deadline.map_or(false, |e| e <= Instant::now()) {
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        self.registry.sessions.write().remove(&self.session.id);
+    }
+}
+
+/// The limits are 0 for none, as of a registry by default
+#[derive(Default)]
+pub struct SessionRegistry {
+    next_id: AtomicU64,
+    sessions: RwLock<BTreeMap<SessionId, SessionRef>>,
+    max_connections: AtomicUsize,
+    max_connections_per_user: AtomicUsize,
+    idle_timeout_secs: AtomicU64,
+}
+
+impl SessionRegistry {
+    pub fn new(config: &QueryConfig) -> Self {
+        let registry = Self::default();
+        registry.update(config);
+        registry
+    }
+
+    /// Applies the limits reloaded, the connections over the limits are kept
+    pub fn update(&self, config: &QueryConfig) {
+        self.max_connections
+            .store(config.max_server_connections as usize, Ordering::Relaxed);
+        self.max_connections_per_user
+            .store(config.max_connections_per_user, Ordering::Relaxed);
+        self.idle_timeout_secs
+            .store(config.idle_session_timeout_secs, Ordering::Relaxed);
+    }
+
+    /// Connections without a request for longer are closed
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        match self.idle_timeout_secs.load(Ordering::Relaxed) {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        }
+    }
+
+    /// Registers the session of a connection accepted, unless the connections are
+    /// at the limit
+    pub fn open(
+        self: &Arc<Self>,
+        client_addr: String,
+        protocol: &'static str,
+    ) -> Result<SessionGuard, SessionError> {
+        let mut sessions = self.sessions.write();
+        let max = self.max_connections.load(Ordering::Relaxed);
+        if max > 0 && sessions.len() >= max {
+            return Err(SessionError::TooManyConnections { max });
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let connected_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |e| e.as_nanos() as i64);
+        let session = Arc::new(Session {
+            id,
+            client_addr,
+            protocol,
+            connected_at,
+            user: RwLock::new(None),
+            queries: Mutex::new(BTreeMap::new()),
+            next_query_id: AtomicU64::new(0),
+            requests: AtomicUsize::new(0),
+            last_active: Mutex::new(Instant::now()),
+            killed: AtomicBool::new(false),
+            kill: Notify::new(),
+        });
+        sessions.insert(id, session.clone());
+
+        Ok(SessionGuard {
+            registry: self.clone(),
+            session,
+        })
+    }
+
+    /// Records the user a request of the session is authenticated as, unless the
+    /// session is of another user or the other sessions of the user are at the limit
+    pub fn authenticate(&self, session: &Session, user: &str) -> Result<(), SessionError> {
+        let check_user = |session_user: Option<&str>| match session_user {
+            Some(session_user) if session_user != user => Err(SessionError::UserMismatch {
+                session_user: session_user.to_string(),
+                user: user.to_string(),
+            }),
+            session_user => Ok(session_user.is_some()),
+        };
+        if check_user(session.user.read().as_deref())? {
+            return Ok(());
+        }
+
+        let sessions = self.sessions.write();
+        // Another request of the connection may be authenticated meanwhile
+        let mut session_user = session.user.write();
+        if check_user(session_user.as_deref())? {
+            return Ok(());
+        }
+        let max = self.max_connections_per_user.load(Ordering::Relaxed);
+        if max > 0 {
+            let count = sessions
+                .values()
+                .filter(|e| e.id != session.id && e.user.read().as_deref() == Some(user))
+                .count();
+            if count >= max {
+                return Err(SessionError::TooManyUserConnections {
+                    user: user.to_string(),
+                    max,
+                });
+            }
+        }
+        *session_user = Some(user.to_string());
+        Ok(())
+    }
+
+    pub fn session(&self, id: SessionId) -> Option<SessionRef> {
+        self.sessions.read().get(&id).cloned()
+    }
+
+    pub fn sessions(&self) -> Vec<SessionRef> {
+        self.sessions.read().values().cloned().collect()
+    }
+
+    pub fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("session_id", DataType::UInt64, false),
+            Field::new("user", DataType::Utf8, true),
+            Field::new("client_addr", DataType::Utf8, false),
+            Field::new("protocol", DataType::Utf8, false),
+            Field::new(
+                "connected_at",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+            Field::new("state", DataType::Utf8, false),
+            Field::new("query", DataType::Utf8, true),
+            Field::new("query_duration", DataType::UInt64, true),
+        ]))
+    }
+
+    /// The sessions as the rows of `SHOW SESSIONS`, those of the user only if given,
+    /// the query is the one running the longest, its duration is in milliseconds
+    pub fn record_batch(&self, user: Option<&str>) -> Result<RecordBatch, ArrowError> {
+        let mut ids = UInt64Builder::new();
+        let mut users = StringBuilder::new();
+        let mut client_addrs = StringBuilder::new();
+        let mut protocols = StringBuilder::new();
+        let mut connected_ats = TimestampNanosecondBuilder::new();
+        let mut states = StringBuilder::new();
+        let mut queries = StringBuilder::new();
+        let mut durations = UInt64Builder::new();
+        for session in self.sessions() {
+            let session_user = session.user();
+            if user.is_some() && session_user.as_deref() != user {
+                continue;
+            }
+            let query = session.query();
+            ids.append_value(session.id);
+            users.append_option(session_user);
+            client_addrs.append_value(&session.client_addr);
+            protocols.append_value(session.protocol);
+            connected_ats.append_value(session.connected_at);
+            states.append_value(if query.is_some() { "active" } else { "idle" });
+            queries.append_option(query.as_ref().map(|(sql, _)| sql));
+            durations.append_option(
+                query
+                    .as_ref()
+                    .map(|(_, started)| started.elapsed().as_millis() as u64),
+            );
+        }
+
+        RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(ids.finish()),
+                Arc::new(users.finish()),
+                Arc::new(client_addrs.finish()),
+                Arc::new(protocols.finish()),
+                Arc::new(connected_ats.finish()),
+                Arc::new(states.finish()),
+                Arc::new(queries.finish()),
+                Arc::new(durations.finish()),
+            ],
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use datafusion::arrow::array::{Array, StringArray};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_session_registry() {
+        let registry = Arc::new(SessionRegistry::default());
+        let s1 = registry.open("127.0.0.1:5000".to_string(), "http").unwrap();
+        let s2 = registry.open("127.0.0.1:5001".to_string(), "http").unwrap();
+        registry.authenticate(s1.session(), "alice").unwrap();
+        registry.authenticate(s2.session(), "bob").unwrap();
+        // The connection of a user may not be used by another
+        assert!(matches!(
+            registry.authenticate(s1.session(), "bob"),
+            Err(SessionError::UserMismatch { .. })
+        ));
+        assert_eq!(s1.session().user().as_deref(), Some("alice"));
+
+        {
+            // The requests of a connection running at the same time
+            let q1 = s1.session().begin_query("SELECT 1");
+            let _q2 = s1.session().begin_query("SELECT 2");
+            drop(q1);
+            assert_eq!(s1.session().query().unwrap().0, "SELECT 2");
+            let batch = registry.record_batch(None).unwrap();
+            assert_eq!(batch.num_rows(), 2);
+            let states = batch
+                .column(5)
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .unwrap();
+            assert_eq!(states.value(0), "active");
+            assert_eq!(states.value(1), "idle");
+        }
+        let batch = registry.record_batch(Some("alice")).unwrap();
+        assert_eq!(batch.num_rows(), 1);
+        assert!(batch.column(6).is_null(0));
+
+        let session = registry.session(s2.session().id()).unwrap();
+        let closed = s2.closed();
+        session.kill();
+        session.killed().await;
+        closed.await;
+        assert!(s2.session().is_killed());
+
+        drop(s2);
+        assert_eq!(registry.sessions().len(), 1);
+    }
+
+    #[test]
+    fn test_session_limits() {
+        let mut config = config::get_config("../../config/config.toml").query;
+        config.max_server_connections = 2;
+        config.max_connections_per_user = 1;
+        config.idle_session_timeout_secs = 0;
+        let registry = Arc::new(SessionRegistry::new(&config));
+        assert!(registry.idle_timeout().is_none());
+        let s1 = registry.open("127.0.0.1:5000".to_string(), "http").unwrap();
+        let s2 = registry.open("127.0.0.1:5001".to_string(), "http").unwrap();
+        assert!(matches!(
+            registry.open("127.0.0.1:5002".to_string(), "http"),
+            Err(SessionError::TooManyConnections { max: 2 })
+        ));
+
+        registry.authenticate(s1.session(), "alice").unwrap();
+        registry.authenticate(s1.session(), "alice").unwrap();
+        assert!(matches!(
+            registry.authenticate(s2.session(), "alice"),
+            Err(SessionError::TooManyUserConnections { .. })
+        ));
+        assert!(s2.session().user().is_none());
+        drop(s1);
+        registry.authenticate(s2.session(), "alice").unwrap();
+
+        config.max_server_connections = 0;
+        config.idle_session_timeout_secs = 60;
+        registry.update(&config);
+        assert_eq!(registry.idle_timeout(), Some(Duration::from_secs(60)));
+        let timeout = Duration::from_secs(60);
+        let session = s2.session();
+        let idle = session.idle_deadline(timeout);
+        {
+            let _request = session.begin_request();
+            assert!(session.idle_deadline(timeout) >= idle);
+        }
+        assert!(session.idle_deadline(timeout) >= idle);
+        let _s3 = registry.open("127.0.0.1:5002".to_string(), "http").unwrap();
+    }
+}
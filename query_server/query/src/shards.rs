@@ -0,0 +1,339 @@
+use std::sync::Arc;
+
+use datafusion::arrow::array::{StringBuilder, TimestampNanosecondBuilder, UInt64Builder};
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::error::{DataFusionError, Result};
+use models::meta_data::DatabaseInfo;
+use models::Timestamp;
+use spi::catalog::MetaData;
+use tskv::tseries_family::SuperVersion;
+
+use crate::metadata::{LocalCatalogMeta, RemoteCatalogMeta};
+
+/// Table of the shards of the databases of the tenant, `SELECT * FROM system.shards`,
+/// or `SHOW SHARDS [ON <database>]`.
+///
+/// In a cluster a shard is a replication set of a bucket, the time partition of
+/// the database, placed on the vnodes of the data nodes. On a single node a shard
+/// is a ts family of the local engine, the time range of which is of its data.
+pub const SHARDS_TABLE: &str = "shards";
+
+const STATE_ACTIVE: &str = "active";
+const STATE_SEALED: &str = "sealed";
+const STATE_EXPIRED: &str = "expired";
+const STATE_FUTURE: &str = "future";
+const STATE_IDLE: &str = "idle";
+const STATE_FLUSHING: &str = "flushing";
+const STATE_COMPACTING: &str = "compacting";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ShardRow {
+    database: String,
+    shard_id: u64,
+    bucket_id: Option<u64>,
+    start_time: Option<Timestamp>,
+    end_time: Option<Timestamp>,
+    state: &'static str,
+    size: Option<u64>,
+    file_count: Option<u64>,
+    /// `<vnode id>@<node id>` of the replicas
+    vnodes: Option<String>,
+}
+
+pub fn schema() -> SchemaRef {
+    let time = || DataType::Timestamp(TimeUnit::Nanosecond, None);
+    Arc::new(Schema::new(vec![
+        Field::new("database", DataType::Utf8, false),
+        Field::new("shard_id", DataType::UInt64, false),
+        Field::new("bucket_id", DataType::UInt64, true),
+        Field::new("start_time", time(), true),
+        Field::new("end_time", time(), true),
+        Field::new("state", DataType::Utf8, false),
+        Field::new("size", DataType::UInt64, true),
+        Field::new("file_count", DataType::UInt64, true),
+        Field::new("vnodes", DataType::Utf8, true),
+    ]))
+}
+
+/// The shards of the database, or of all the databases of the tenant if `None`
+pub fn record_batch(meta: &dyn MetaData, database: Option<&str>) -> Result<RecordBatch> {
+    to_record_batch(&shard_rows(meta, database)?)
+}
+
+/// The time ranges `[start, end)` the database is partitioned by, those of the
+/// buckets in a cluster, or the time range of the data on a single node split by
+/// the shard duration of the database. A table is scanned by them in parallel.
+pub fn time_partitions(meta: &dyn MetaData, database: &str) -> Result<Vec<(Timestamp, Timestamp)>> {
+    let duration = meta
+        .database(database)
+        .map_err(external)?
+        .config
+        .vnode_duration_or_default()
+        .to_nanoseconds();
+    let rows = shard_rows(meta, Some(database))?;
+    Ok(partitions(&rows, duration))
+}
+
+fn shard_rows(meta: &dyn MetaData, database: Option<&str>) -> Result<Vec<ShardRow>> {
+    let databases = match database {
+        Some(db) => vec![db.to_string()],
+        None => meta.database_names().map_err(external)?,
+    };
+    let now = chrono::Utc::now().timestamp_nanos();
+
+    let mut rows = vec![];
+    if let Some(remote) = meta.as_any().downcast_ref::<RemoteCatalogMeta>() {
+        let tenant = remote.tenant_meta().map_err(external)?;
+        for db in databases.iter() {
+            let info = tenant
+                .database(db)
+                .ok_or_else(|| DataFusionError::Plan(format!("database {} not found", db)))?;
+            rows.extend(bucket_rows(info, now));
+        }
+    } else if let Some(local) = meta.as_any().downcast_ref::<LocalCatalogMeta>() {
+        let engine = local.coordinator().engine();
+        for db in databases.iter() {
+            meta.database(db).map_err(external)?;
+            if let Some(version) = engine.get_db_version(db).map_err(external)? {
+                rows.push(ts_family_row(db, &version));
+            }
+        }
+    } else {
+        return Err(DataFusionError::Plan("failed to get meta data".to_string()));
+    }
+
+    Ok(rows)
+}
+
+/// The rows of the buckets are partitioned by the buckets, the replication sets of
+/// a bucket share the time range of it. The time range of a ts family, the end of
+/// which is inclusive, is split at the multiples of the duration.
+fn partitions(rows: &[ShardRow], duration: i64) -> Vec<(Timestamp, Timestamp)> {
+    let mut partitions = vec![];
+    for row in rows {
+        let (start, end) = match (row.start_time, row.end_time) {
+            (Some(start), Some(end)) => (start, end),
+            _ => continue,
+        };
+        if row.bucket_id.is_some() {
+            partitions.push((start, end));
+            continue;
+        }
+        let end = end.saturating_add(1);
+        let mut lower = start;
+        while lower < end {
+            let upper = if duration > 0 {
+                (lower - lower.rem_euclid(duration))
+                    .saturating_add(duration)
+                    .min(end)
+            } else {
+                end
+            };
+            partitions.push((lower, upper));
+            lower = upper;
+        }
+    }
+    partitions.sort_unstable();
+    partitions.dedup();
+    partitions
+}
+
+/// A row per replication set of the buckets of the database
+fn bucket_rows(info: &DatabaseInfo, now: Timestamp) -> Vec<ShardRow> {
+    // Only the ttl set explicitly is enforced by the retention
+    let cutoff = info
+        .schema
+        .config
+        .ttl()
+        .as_ref()
+        .map(|ttl| now.saturating_sub(ttl.to_nanoseconds()));
+    let mut rows = vec![];
+    for bucket in info.buckets.iter() {
+        let state = if bucket.start_time > now {
+            STATE_FUTURE
+        } else if bucket.contains(now) {
+            STATE_ACTIVE
+        } else if cutoff.map_or(false, |e| bucket.end_time <= e) {
+            STATE_EXPIRED
+        } else {
+            STATE_SEALED
+        };
+        for set in bucket.shard_group.iter() {
+            let vnodes = set
+                .vnodes
+                .iter()
+                .map(|e| format!("{}@{}", e.id, e.node_id))
+                .collect::<Vec<_>>()
+                .join(",");
+            rows.push(ShardRow {
+                database: info.schema.name.clone(),
+                shard_id: set.id as u64,
+                bucket_id: Some(bucket.id as u64),
+                start_time: Some(bucket.start_time),
+                end_time: Some(bucket.end_time),
+                state,
+                size: None,
+                file_count: None,
+                vnodes: Some(vnodes),
+            });
+        }
+    }
+    rows
+}
+
+fn ts_family_row(database: &str, version: &SuperVersion) -> ShardRow {
+    let files = version
+        .version
+        .levels_info()
+        .iter()
+        .flat_map(|e| e.files.iter())
+        .filter(|e| !e.is_deleted())
+        .collect::<Vec<_>>();
+    let state = if files.iter().any(|e| e.is_compacting()) {
+        STATE_COMPACTING
+    } else if version.caches.immut_cache.iter().any(|e| !e.read().flushed) {
+        STATE_FLUSHING
+    } else if !version.caches.mut_cache.read().is_empty() {
+        STATE_ACTIVE
+    } else {
+        STATE_IDLE
+    };
+    let time_range = version.time_range();
+
+    ShardRow {
+        database: database.to_string(),
+        shard_id: version.ts_family_id as u64,
+        bucket_id: None,
+        start_time: time_range.map(|e| e.min_ts),
+        end_time: time_range.map(|e| e.max_ts),
+        state,
+        size: Some(files.iter().map(|e| e.size()).sum()),
+        file_count: Some(files.len() as u64),
+        vnodes: None,
+    }
+}
+
+fn to_record_batch(rows: &[ShardRow]) -> Result<RecordBatch> {
+    let mut databases = StringBuilder::new();
+    let mut shard_ids = UInt64Builder::with_capacity(rows.len());
+    let mut bucket_ids = UInt64Builder::with_capacity(rows.len());
+    let mut start_times = TimestampNanosecondBuilder::with_capacity(rows.len());
+    let mut end_times = TimestampNanosecondBuilder::with_capacity(rows.len());
+    let mut states = StringBuilder::new();
+    let mut sizes = UInt64Builder::with_capacity(rows.len());
+    let mut file_counts = UInt64Builder::with_capacity(rows.len());
+    let mut vnodes = StringBuilder::new();
+    for row in rows {
+        databases.append_value(&row.database);
+        shard_ids.append_value(row.shard_id);
+        bucket_ids.append_option(row.bucket_id);
+        start_times.append_option(row.start_time);
+        end_times.append_option(row.end_time);
+        states.append_value(row.state);
+        sizes.append_option(row.size);
+        file_counts.append_option(row.file_count);
+        vnodes.append_option(row.vnodes.as_deref());
+    }
+
+    Ok(RecordBatch::try_new(
+        schema(),
+        vec![
+            Arc::new(databases.finish()),
+            Arc::new(shard_ids.finish()),
+            Arc::new(bucket_ids.finish()),
+            Arc::new(start_times.finish()),
+            Arc::new(end_times.finish()),
+            Arc::new(states.finish()),
+            Arc::new(sizes.finish()),
+            Arc::new(file_counts.finish()),
+            Arc::new(vnodes.finish()),
+        ],
+    )?)
+}
+
+fn external(e: impl std::error::Error + Send + Sync + 'static) -> DataFusionError {
+    DataFusionError::External(Box::new(e))
+}
+
+#[cfg(test)]
+mod test {
+    use models::meta_data::{BucketInfo, ReplicationSet, VnodeInfo};
+    use models::schema::{DatabaseSchema, Duration, DurationUnit};
+
+    use super::*;
+
+    fn bucket(id: u32, start_time: Timestamp, end_time: Timestamp) -> BucketInfo {
+        BucketInfo {
+            id,
+            start_time,
+            end_time,
+            shard_group: vec![ReplicationSet {
+                id: id * 10,
+                vnodes: vec![
+                    VnodeInfo { id: 1, node_id: 1 },
+                    VnodeInfo { id: 2, node_id: 2 },
+                ],
+            }],
+        }
+    }
+
+    #[test]
+    fn test_bucket_rows() {
+        let day = 24 * 3600 * 1_000_000_000_i64;
+        let mut db_schema = DatabaseSchema::new("db1");
+        db_schema.config.with_ttl(Duration {
+            time_num: 1,
+            unit: DurationUnit::Day,
+        });
+        let mut info = DatabaseInfo::new(db_schema);
+        info.buckets = vec![
+            bucket(1, 0, day),
+            bucket(2, 2 * day, 3 * day),
+            bucket(3, 3 * day, 4 * day),
+            bucket(4, 4 * day, 5 * day),
+        ];
+
+        let rows = bucket_rows(&info, 3 * day + 1);
+        let states = rows.iter().map(|e| e.state).collect::<Vec<_>>();
+        assert_eq!(
+            states,
+            vec![STATE_EXPIRED, STATE_SEALED, STATE_ACTIVE, STATE_FUTURE]
+        );
+        assert_eq!(rows[2].shard_id, 30);
+        assert_eq!(rows[2].vnodes.as_deref(), Some("1@1,2@2"));
+
+        let batch = to_record_batch(&rows).unwrap();
+        assert_eq!(batch.num_rows(), 4);
+        assert_eq!(batch.schema(), schema());
+    }
+
+    #[test]
+    fn test_partitions() {
+        let day = 24 * 3600 * 1_000_000_000_i64;
+        let mut info = DatabaseInfo::new(DatabaseSchema::new("db1"));
+        info.buckets = vec![bucket(1, 0, day), bucket(2, day, 2 * day)];
+        info.buckets[0].shard_group.push(ReplicationSet {
+            id: 11,
+            vnodes: vec![VnodeInfo { id: 3, node_id: 3 }],
+        });
+        let rows = bucket_rows(&info, 0);
+        assert_eq!(partitions(&rows, day), vec![(0, day), (day, 2 * day)]);
+
+        let row = ShardRow {
+            database: "db1".to_string(),
+            shard_id: 1,
+            bucket_id: None,
+            start_time: Some(day / 2),
+            end_time: Some(2 * day),
+            state: STATE_IDLE,
+            size: Some(0),
+            file_count: Some(0),
+            vnodes: None,
+        };
+        assert_eq!(
+            partitions(&[row], day),
+            vec![(day / 2, day), (day, 2 * day), (2 * day, 2 * day + 1)]
+        );
+    }
+}
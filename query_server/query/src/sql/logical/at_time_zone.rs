@@ -0,0 +1,93 @@
+use chrono_tz::Tz;
+use datafusion::sql::sqlparser::ast::{
+    Expr, Function, FunctionArg, FunctionArgExpr, Ident, ObjectName, Statement, Value,
+};
+use spi::query::logical_planner::{LogicalPlannerError, Result};
+
+use crate::extension::expr::scalar_function::time::TO_TIMEZONE;
+use crate::sql::logical::visitor::{visit_statement, VisitorMut};
+
+/// Rewrites `<time> AT TIME ZONE '<time zone>'` of the select statements, including
+/// those of the CTEs and subqueries, to
+/// `to_timezone(<time>, '<time zone>')`, which is not planned by datafusion
+pub fn rewrite_at_time_zone(statement: &mut Statement) -> Result<()> {
+    visit_statement(statement, &mut AtTimeZone)
+}
+
+struct AtTimeZone;
+
+impl VisitorMut for AtTimeZone {
+    fn post_visit_expr(&mut self, expr: &mut Expr) -> Result<()> {
+        if let Expr::AtTimeZone {
+            timestamp,
+            time_zone,
+        } = expr
+        {
+            time_zone
+                .parse::<Tz>()
+                .map_err(|_| LogicalPlannerError::Semantic {
+                    err: format!("Invalid time zone '{}'", time_zone),
+                })?;
+            let args = vec![
+                timestamp.as_ref().clone(),
+                Expr::Value(Value::SingleQuotedString(time_zone.clone())),
+            ];
+            *expr = Expr::Function(Function {
+                name: ObjectName(vec![Ident::new(TO_TIMEZONE)]),
+                args: args
+                    .into_iter()
+                    .map(|e| FunctionArg::Unnamed(FunctionArgExpr::Expr(e)))
+                    .collect(),
+                over: None,
+                distinct: false,
+                special: false,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use spi::query::ast::ExtStatement;
+
+    use super::*;
+    use crate::sql::parser::ExtParser;
+
+    fn rewrite(sql: &str) -> Result<String> {
+        let mut statement = match ExtParser::parse_sql(sql).unwrap().pop_front().unwrap() {
+            ExtStatement::SqlStatement(statement) => *statement,
+            _ => panic!("expect a sql statement"),
+        };
+        rewrite_at_time_zone(&mut statement)?;
+        Ok(statement.to_string())
+    }
+
+    #[test]
+    fn test_rewrite_at_time_zone() {
+        assert_eq!(
+            rewrite(
+                "SELECT time AT TIME ZONE 'Asia/Shanghai' AS t, f0 FROM m \
+                WHERE date_part('hour', time AT TIME ZONE 'UTC') > 1"
+            )
+            .unwrap(),
+            "SELECT to_timezone(time, 'Asia/Shanghai') AS t, f0 FROM m \
+            WHERE date_part('hour', to_timezone(time, 'UTC')) > 1"
+        );
+        assert_eq!(
+            rewrite("SELECT time FROM m ORDER BY time AT TIME ZONE 'Asia/Shanghai'").unwrap(),
+            "SELECT time FROM m ORDER BY to_timezone(time, 'Asia/Shanghai')"
+        );
+        assert_eq!(
+            rewrite(
+                "SELECT * FROM (SELECT time AT TIME ZONE 'UTC' AS t FROM m) AS s \
+                WHERE t IN (SELECT time AT TIME ZONE 'UTC' FROM n)"
+            )
+            .unwrap(),
+            "SELECT * FROM (SELECT to_timezone(time, 'UTC') AS t FROM m) AS s \
+            WHERE t IN (SELECT to_timezone(time, 'UTC') FROM n)"
+        );
+
+        assert!(rewrite("SELECT time AT TIME ZONE 'Mars/Base' FROM m").is_err());
+    }
+}
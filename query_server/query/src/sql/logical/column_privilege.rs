@@ -0,0 +1,165 @@
+use std::collections::{HashMap, HashSet};
+
+use datafusion::common::Column;
+use datafusion::datasource::source_as_provider;
+use datafusion::error::Result as DFResult;
+use datafusion::logical_expr::utils::expr_to_columns;
+use datafusion::logical_expr::{LogicalPlan, Subquery};
+use datafusion::prelude::Expr;
+use models::meta_data::ColumnPrivilege;
+use snafu::ResultExt;
+use spi::catalog::MetaDataRef;
+use spi::query::logical_planner::{
+    ExternalSnafu, LogicalPlannerError, MetadataSnafu, Plan, Result,
+};
+
+use crate::extension::expr::expr_utils::find_exprs_in_exprs_deeply_nested;
+use crate::table::ClusterTable;
+
+/// Reject the query reading the columns not granted to the roles of the user,
+/// the admins read all of the columns.
+///
+/// The readable columns of a table are those granted to any of the roles of the
+/// user, none of them if the table has columns granted to the other roles only.
+/// The tables without columns granted are not restricted.
+pub fn check_column_privileges(plan: &Plan, catalog: &MetaDataRef, user: &str) -> Result<()> {
+    let query = match plan {
+        Plan::Query(query) => query,
+        _ => return Ok(()),
+    };
+
+    let user = match catalog.user(user).context(MetadataSnafu)? {
+        Some(user) if !user.is_admin => user,
+        _ => return Ok(()),
+    };
+    let mut restricted = vec![];
+    let mut privileges = vec![];
+    for role in catalog.roles().context(MetadataSnafu)? {
+        if user.roles.contains(&role.name) {
+            privileges.extend(role.column_privileges.iter().cloned());
+        }
+        restricted.extend(role.column_privileges);
+    }
+    if restricted.is_empty() {
+        return Ok(());
+    }
+
+    let mut checker = ColumnChecker {
+        privileges: &privileges,
+        restricted: &restricted,
+        tables: HashMap::new(),
+    };
+    checker
+        .collect_tables(&query.df_plan)
+        .context(ExternalSnafu)?;
+    if checker.tables.is_empty() {
+        return Ok(());
+    }
+
+    let mut denied = None;
+    visit_plans(&query.df_plan, &mut |plan| {
+        let mut columns = HashSet::new();
+        for expr in plan.expressions() {
+            expr_to_columns(&expr, &mut columns)?;
+        }
+        if denied.is_none() {
+            denied = columns.iter().find_map(|e| checker.denied(e));
+        }
+        Ok(())
+    })
+    .context(ExternalSnafu)?;
+
+    match denied {
+        Some((table, column)) => Err(LogicalPlannerError::PermissionDenied {
+            reason: format!(
+                "column {} of table {} is not granted to user {}",
+                column, table, user.name
+            ),
+        }),
+        None => Ok(()),
+    }
+}
+
+struct ColumnChecker<'a> {
+    /// The privileges of the roles of the user
+    privileges: &'a [ColumnPrivilege],
+    /// The privileges of all of the roles, the tables of which are restricted
+    restricted: &'a [ColumnPrivilege],
+    /// The qualifiers of the restricted tables, with the names and readable columns of them
+    tables: HashMap<String, (String, HashSet<&'a str>)>,
+}
+
+impl<'a> ColumnChecker<'a> {
+    fn collect_tables(&mut self, plan: &LogicalPlan) -> DFResult<()> {
+        let (privileges, restricted) = (self.privileges, self.restricted);
+        visit_plans(plan, &mut |plan| {
+            match plan {
+                LogicalPlan::TableScan(scan) => {
+                    let provider = source_as_provider(&scan.source)?;
+                    if let Some(table) = provider.as_any().downcast_ref::<ClusterTable>() {
+                        let schema = table.table_schema();
+                        let is_on = |e: &&ColumnPrivilege| {
+                            e.is_on(table.tenant(), &schema.db, &schema.name)
+                        };
+                        // None of the columns is readable without a privilege of the user,
+                        // or once all of the columns granted are revoked
+                        if restricted.iter().any(|e| is_on(&e)) {
+                            let columns = privileges
+                                .iter()
+                                .filter(is_on)
+                                .flat_map(|e| e.columns.iter().map(|e| e.as_str()))
+                                .collect();
+                            let name = format!("{}.{}", schema.db, schema.name);
+                            self.tables.insert(scan.table_name.clone(), (name, columns));
+                        }
+                    }
+                }
+                // The columns of the table read through an alias
+                LogicalPlan::SubqueryAlias(alias) => {
+                    if let LogicalPlan::TableScan(scan) = alias.input.as_ref() {
+                        if let Some(table) = self.tables.get(&scan.table_name).cloned() {
+                            self.tables.insert(alias.alias.clone(), table);
+                        }
+                    }
+                }
+                _ => {}
+            }
+            Ok(())
+        })
+    }
+
+    fn denied(&self, column: &Column) -> Option<(String, String)> {
+        let (table, columns) = self.tables.get(column.relation.as_ref()?)?;
+        if columns.contains(column.name.as_str()) {
+            None
+        } else {
+            Some((table.clone(), column.name.clone()))
+        }
+    }
+}
+
+/// Visit the plan, the inputs and the subqueries of it, the inputs first
+pub(crate) fn visit_plans(
+    plan: &LogicalPlan,
+    f: &mut dyn FnMut(&LogicalPlan) -> DFResult<()>,
+) -> DFResult<()> {
+    for input in plan.inputs() {
+        visit_plans(input, f)?;
+    }
+    let subqueries = find_exprs_in_exprs_deeply_nested(&plan.expressions(), &|e| {
+        matches!(
+            e,
+            Expr::Exists { .. } | Expr::InSubquery { .. } | Expr::ScalarSubquery(_)
+        )
+    });
+    for expr in subqueries {
+        let subquery: &Subquery = match &expr {
+            Expr::Exists { subquery, .. } => subquery,
+            Expr::InSubquery { subquery, .. } => subquery,
+            Expr::ScalarSubquery(subquery) => subquery,
+            _ => continue,
+        };
+        visit_plans(&subquery.subquery, f)?;
+    }
+    f(plan)
+}
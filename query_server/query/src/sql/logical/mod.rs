@@ -1,2 +1,7 @@
+pub mod at_time_zone;
+pub mod column_privilege;
 pub mod optimizer;
 pub mod planner;
+pub mod row_policy;
+pub mod time_window;
+pub mod visitor;
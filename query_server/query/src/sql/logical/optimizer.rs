@@ -23,9 +23,9 @@ use snafu::ResultExt;
 use spi::query::LogicalOptimizeSnafu;
 
 use crate::extension::logical::optimizer_rule::{
-    implicit_type_conversion::ImplicitTypeConversion,
+    extract_time_ranges::ExtractTimeRanges, implicit_type_conversion::ImplicitTypeConversion,
     projection_push_down::ProjectionPushDownAdapter, reject_cross_join::RejectCrossJoin,
-    rewrite_tag_scan::RewriteTagScan,
+    rewrite_tag_scan::RewriteTagScan, rewrite_time_arithmetic::RewriteTimeArithmetic,
     transform_bottom_func_to_topk_node::TransformBottomFuncToTopkNodeRule,
     transform_topk_func_to_topk_node::TransformTopkFuncToTopkNodeRule,
 };
@@ -55,6 +55,7 @@ impl Default for DefaultLogicalOptimizer {
             Arc::new(RejectCrossJoin {}),
             // data type conv
             Arc::new(ImplicitTypeConversion {}),
+            Arc::new(RewriteTimeArithmetic {}),
             // df default rules start
             Arc::new(TypeCoercion::new()),
             Arc::new(SimplifyExpressions::new()),
@@ -80,6 +81,7 @@ impl Default for DefaultLogicalOptimizer {
             Arc::new(SingleDistinctToGroupBy::new()),
             // df default rules end
             // cnosdb rules
+            Arc::new(ExtractTimeRanges {}),
             Arc::new(TransformBottomFuncToTopkNodeRule {}),
             Arc::new(TransformTopkFuncToTopkNodeRule {}),
         ];
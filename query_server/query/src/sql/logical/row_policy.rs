@@ -0,0 +1,457 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use datafusion::arrow::datatypes::DataType;
+use datafusion::common::DFSchema;
+use datafusion::datasource::source_as_provider;
+use datafusion::error::{DataFusionError, Result as DFResult};
+use datafusion::logical_expr::expr_rewriter::{ExprRewritable, ExprRewriter};
+use datafusion::logical_expr::logical_plan::Analyze;
+use datafusion::logical_expr::{
+    utils, AggregateUDF, Explain, LogicalPlan, LogicalPlanBuilder, ScalarUDF, Subquery, TableScan,
+    TableSource,
+};
+use datafusion::prelude::{lit, or, Expr};
+use datafusion::sql::planner::{ContextProvider, SqlToRel};
+use datafusion::sql::sqlparser::dialect::GenericDialect;
+use datafusion::sql::sqlparser::parser::Parser;
+use datafusion::sql::TableReference;
+use models::meta_data::{RoleInfo, RowPolicy, UserInfo};
+use snafu::ResultExt;
+use spi::catalog::MetaDataRef;
+use spi::query::logical_planner::{ExternalSnafu, MetadataSnafu, Plan, QueryPlan, Result};
+
+use crate::table::ClusterTable;
+
+/// Plan the predicate of a policy against the columns of the table
+pub fn predicate_expr(predicate: &str, schema: &DFSchema) -> DFResult<Expr> {
+    let expr = Parser::new(&GenericDialect {})
+        .try_with_sql(predicate)?
+        .parse_expr()?;
+    SqlToRel::new(&NoTables).sql_to_rex(expr, schema, &mut HashMap::new())
+}
+
+/// Restrict the rows of the tables read by the query to those the policies
+/// of the roles of the user allow, the admins read all of the rows.
+///
+/// The rows of a table are those matching any of the policies of the roles of the
+/// user, none of them if the table has policies of the other roles only. The tables
+/// without policies are not restricted.
+///
+/// The other reads of the rows, e.g. `EXPORT DATABASE`, `/api/v1/changes` and the
+/// partition export, are restricted to the admins.
+pub fn apply_row_policies(plan: Plan, catalog: &MetaDataRef, user: &str) -> Result<Plan> {
+    let query = match plan {
+        Plan::Query(query) => query,
+        _ => return Ok(plan),
+    };
+
+    let user = match catalog.user(user).context(MetadataSnafu)? {
+        Some(user) => user,
+        None => return Ok(Plan::Query(query)),
+    };
+    let roles = catalog.roles().context(MetadataSnafu)?;
+    let (policies, restricted) = match policies_of(&user, roles) {
+        Some(e) => e,
+        None => return Ok(Plan::Query(query)),
+    };
+
+    let df_plan = RowPolicyRewriter {
+        policies: &policies,
+        restricted: &restricted,
+        table_of: cluster_table_of,
+    }
+    .rewrite(&query.df_plan)
+    .context(ExternalSnafu)?;
+    Ok(Plan::Query(QueryPlan { df_plan }))
+}
+
+/// The policies of the roles of the user and those of all of the roles, None if
+/// the rows read by the user are not restricted
+fn policies_of(user: &UserInfo, roles: Vec<RoleInfo>) -> Option<(Vec<RowPolicy>, Vec<RowPolicy>)> {
+    if user.is_admin {
+        return None;
+    }
+    let mut restricted = vec![];
+    let mut policies = vec![];
+    for role in roles {
+        if user.roles.contains(&role.name) {
+            policies.extend(role.policies.iter().cloned());
+        }
+        restricted.extend(role.policies);
+    }
+    if restricted.is_empty() {
+        return None;
+    }
+    Some((policies, restricted))
+}
+
+/// The tenant, the database and the name of the table scanned
+type TableOf = fn(&TableScan) -> DFResult<Option<(String, String, String)>>;
+
+/// The tables of the other providers, e.g. the system tables, are not restricted
+fn cluster_table_of(scan: &TableScan) -> DFResult<Option<(String, String, String)>> {
+    let provider = source_as_provider(&scan.source)?;
+    Ok(provider
+        .as_any()
+        .downcast_ref::<ClusterTable>()
+        .map(|table| {
+            let schema = table.table_schema();
+            (
+                table.tenant().to_string(),
+                schema.db.clone(),
+                schema.name.clone(),
+            )
+        }))
+}
+
+struct RowPolicyRewriter<'a> {
+    /// The policies of the roles of the user
+    policies: &'a [RowPolicy],
+    /// The policies of all of the roles, the tables of which are restricted
+    restricted: &'a [RowPolicy],
+    table_of: TableOf,
+}
+
+impl<'a> RowPolicyRewriter<'a> {
+    fn rewrite(&self, plan: &LogicalPlan) -> DFResult<LogicalPlan> {
+        match plan {
+            LogicalPlan::TableScan(scan) => {
+                let (tenant, db, name) = match (self.table_of)(scan)? {
+                    Some(table) => table,
+                    None => return Ok(plan.clone()),
+                };
+                let is_on = |e: &&RowPolicy| e.is_on(&tenant, &db, &name);
+                if !self.restricted.iter().any(|e| is_on(&e)) {
+                    return Ok(plan.clone());
+                }
+                let predicates = self
+                    .policies
+                    .iter()
+                    .filter(is_on)
+                    .map(|e| predicate_expr(&e.predicate, &scan.projected_schema))
+                    .collect::<DFResult<Vec<_>>>()?;
+
+                // None of the rows is readable without a policy of the user on the table
+                let predicate = predicates
+                    .into_iter()
+                    .reduce(or)
+                    .unwrap_or_else(|| lit(false));
+                LogicalPlanBuilder::from(plan.clone())
+                    .filter(predicate)?
+                    .build()
+            }
+            LogicalPlan::Explain(explain) => Ok(LogicalPlan::Explain(Explain {
+                plan: Arc::new(self.rewrite(&explain.plan)?),
+                ..explain.clone()
+            })),
+            LogicalPlan::Analyze(analyze) => Ok(LogicalPlan::Analyze(Analyze {
+                input: Arc::new(self.rewrite(&analyze.input)?),
+                ..analyze.clone()
+            })),
+            _ => {
+                let new_inputs = plan
+                    .inputs()
+                    .into_iter()
+                    .map(|e| self.rewrite(e))
+                    .collect::<DFResult<Vec<_>>>()?;
+                // The tables read by the subqueries
+                let expr = plan
+                    .expressions()
+                    .into_iter()
+                    .map(|e| e.rewrite(&mut SubqueryRewriter { plan: self }))
+                    .collect::<DFResult<Vec<_>>>()?;
+
+                utils::from_plan(plan, &expr, &new_inputs)
+            }
+        }
+    }
+}
+
+struct SubqueryRewriter<'a, 'b> {
+    plan: &'b RowPolicyRewriter<'a>,
+}
+
+impl<'a, 'b> SubqueryRewriter<'a, 'b> {
+    fn subquery(&self, subquery: Subquery) -> DFResult<Subquery> {
+        Ok(Subquery {
+            subquery: Arc::new(self.plan.rewrite(&subquery.subquery)?),
+        })
+    }
+}
+
+impl<'a, 'b> ExprRewriter for SubqueryRewriter<'a, 'b> {
+    fn mutate(&mut self, expr: Expr) -> DFResult<Expr> {
+        match expr {
+            Expr::Exists { subquery, negated } => Ok(Expr::Exists {
+                subquery: self.subquery(subquery)?,
+                negated,
+            }),
+            Expr::InSubquery {
+                expr,
+                subquery,
+                negated,
+            } => Ok(Expr::InSubquery {
+                expr,
+                subquery: self.subquery(subquery)?,
+                negated,
+            }),
+            Expr::ScalarSubquery(subquery) => Ok(Expr::ScalarSubquery(self.subquery(subquery)?)),
+            expr => Ok(expr),
+        }
+    }
+}
+
+/// The predicates only refer to the columns of the table
+struct NoTables;
+
+impl ContextProvider for NoTables {
+    fn get_table_provider(&self, name: TableReference) -> DFResult<Arc<dyn TableSource>> {
+        Err(DataFusionError::Plan(format!(
+            "Table {:?} not allowed in the predicate of a policy",
+            name
+        )))
+    }
+
+    fn get_function_meta(&self, _name: &str) -> Option<Arc<ScalarUDF>> {
+        None
+    }
+
+    fn get_aggregate_meta(&self, _name: &str) -> Option<Arc<AggregateUDF>> {
+        None
+    }
+
+    fn get_variable_type(&self, _variable_names: &[String]) -> Option<DataType> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::arrow::datatypes::{Field, Schema};
+    use datafusion::datasource::empty::EmptyTable;
+    use datafusion::datasource::provider_as_source;
+    use datafusion::logical_expr::{in_subquery, JoinType};
+    use datafusion::prelude::col;
+
+    use super::*;
+
+    fn policy(name: &str, table: &str, predicate: &str) -> RowPolicy {
+        RowPolicy {
+            name: name.to_string(),
+            tenant: "cnosdb".to_string(),
+            database: "db1".to_string(),
+            table: table.to_string(),
+            predicate: predicate.to_string(),
+        }
+    }
+
+    fn role(name: &str, policies: Vec<RowPolicy>) -> RoleInfo {
+        RoleInfo {
+            name: name.to_string(),
+            policies,
+            column_privileges: vec![],
+            groups: vec![],
+        }
+    }
+
+    fn user(is_admin: bool) -> UserInfo {
+        UserInfo {
+            name: "u1".to_string(),
+            is_admin,
+            password_hash: String::new(),
+            tokens: vec![],
+            roles: vec!["eu".to_string(), "us".to_string()],
+            provider: None,
+        }
+    }
+
+    /// The user is granted eu and us, the table disk is restricted by the policy of
+    /// another role only and net by none
+    fn roles() -> Vec<RoleInfo> {
+        vec![
+            role("eu", vec![policy("cpu_eu", "cpu", "region = 'eu'")]),
+            role(
+                "us",
+                vec![
+                    policy("cpu_us", "cpu", "region = 'us'"),
+                    policy("mem_us", "mem", "region = 'us'"),
+                ],
+            ),
+            role(
+                "other",
+                vec![
+                    policy("mem_eu", "mem", "region = 'eu'"),
+                    policy("disk_eu", "disk", "region = 'eu'"),
+                ],
+            ),
+        ]
+    }
+
+    fn scan(name: &str) -> LogicalPlanBuilder {
+        let schema = Schema::new(vec![
+            Field::new("region", DataType::Utf8, true),
+            Field::new("value", DataType::Int64, true),
+        ]);
+        let table = provider_as_source(Arc::new(EmptyTable::new(Arc::new(schema))));
+        LogicalPlanBuilder::scan(name, table, None).unwrap()
+    }
+
+    fn rewrite(plan: LogicalPlan) -> LogicalPlan {
+        let (policies, restricted) = policies_of(&user(false), roles()).unwrap();
+        RowPolicyRewriter {
+            policies: &policies,
+            restricted: &restricted,
+            table_of: |scan| {
+                Ok(Some((
+                    "cnosdb".to_string(),
+                    "db1".to_string(),
+                    scan.table_name.clone(),
+                )))
+            },
+        }
+        .rewrite(&plan)
+        .unwrap()
+    }
+
+    /// The predicates of the filters on the table scans, those of the subqueries included
+    fn scan_filters(plan: &LogicalPlan, filters: &mut Vec<(String, Expr)>) {
+        if let LogicalPlan::Filter(filter) = plan {
+            if let LogicalPlan::TableScan(scan) = filter.input().as_ref() {
+                filters.push((scan.table_name.clone(), filter.predicate().clone()));
+            }
+        }
+        for expr in plan.expressions() {
+            if let Expr::InSubquery { subquery, .. } = expr {
+                scan_filters(&subquery.subquery, filters);
+            }
+        }
+        for input in plan.inputs() {
+            scan_filters(input, filters);
+        }
+    }
+
+    fn filters_of(plan: &LogicalPlan) -> Vec<(String, Expr)> {
+        let mut filters = vec![];
+        scan_filters(plan, &mut filters);
+        filters
+    }
+
+    fn region_is(table: &str, region: &str) -> Expr {
+        col(&format!("{}.region", table)).eq(lit(region))
+    }
+
+    #[test]
+    fn test_policies_of() {
+        assert!(policies_of(&user(true), roles()).is_none());
+        assert!(policies_of(&user(false), vec![role("eu", vec![])]).is_none());
+
+        let (policies, restricted) = policies_of(&user(false), roles()).unwrap();
+        let names = |e: &[RowPolicy]| e.iter().map(|e| e.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&policies), vec!["cpu_eu", "cpu_us", "mem_us"]);
+        assert_eq!(restricted.len(), 5);
+    }
+
+    #[test]
+    fn test_rewrite_table() {
+        let plan = scan("cpu")
+            .project(vec![col("region")])
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(
+            filters_of(&rewrite(plan)),
+            vec![(
+                "cpu".to_string(),
+                or(region_is("cpu", "eu"), region_is("cpu", "us"))
+            )]
+        );
+
+        // None of the rows of a table restricted by the policies of the other roles only
+        let plan = scan("disk").build().unwrap();
+        assert_eq!(
+            filters_of(&rewrite(plan)),
+            vec![("disk".to_string(), lit(false))]
+        );
+
+        let plan = scan("net").build().unwrap();
+        let rewritten = rewrite(plan.clone());
+        assert!(filters_of(&rewritten).is_empty());
+        assert_eq!(format!("{:?}", rewritten), format!("{:?}", plan));
+    }
+
+    #[test]
+    fn test_rewrite_subquery_and_join() {
+        let mem = scan("mem")
+            .project(vec![col("region")])
+            .unwrap()
+            .build()
+            .unwrap();
+        let plan = scan("cpu")
+            .filter(in_subquery(col("cpu.region"), Arc::new(mem)))
+            .unwrap()
+            .build()
+            .unwrap();
+        let mut filters = filters_of(&rewrite(plan));
+        filters.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            filters,
+            vec![
+                (
+                    "cpu".to_string(),
+                    or(region_is("cpu", "eu"), region_is("cpu", "us"))
+                ),
+                ("mem".to_string(), region_is("mem", "us")),
+            ]
+        );
+
+        let net = scan("net").build().unwrap();
+        let plan = scan("disk")
+            .join(
+                &net,
+                JoinType::Inner,
+                (vec!["disk.region"], vec!["net.region"]),
+                None,
+            )
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(
+            filters_of(&rewrite(plan)),
+            vec![("disk".to_string(), lit(false))]
+        );
+    }
+
+    #[test]
+    fn test_rewrite_explain() {
+        let plan = scan("mem").explain(false, false).unwrap().build().unwrap();
+        let explain = match rewrite(plan) {
+            LogicalPlan::Explain(explain) => explain,
+            plan => panic!("expected an explain, got {:?}", plan),
+        };
+        assert_eq!(
+            filters_of(&explain.plan),
+            vec![("mem".to_string(), region_is("mem", "us"))]
+        );
+    }
+
+    #[test]
+    fn test_predicate_expr() {
+        let schema = Schema::new(vec![
+            Field::new("region", DataType::Utf8, true),
+            Field::new("usage", DataType::Float64, true),
+        ]);
+        let schema = DFSchema::try_from_qualified_schema("cpu", &schema).unwrap();
+
+        let expr = predicate_expr("region = 'eu' AND usage < 10", &schema).unwrap();
+        assert_eq!(
+            expr,
+            col("cpu.region")
+                .eq(lit("eu"))
+                .and(col("cpu.usage").lt(lit(10_i64)))
+        );
+
+        assert!(predicate_expr("host = 'h1'", &schema).is_err());
+        assert!(predicate_expr("region IN (SELECT region FROM t)", &schema).is_err());
+    }
+}
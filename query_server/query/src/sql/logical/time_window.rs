@@ -0,0 +1,171 @@
+use chrono_tz::Tz;
+use datafusion::sql::sqlparser::ast::{
+    Expr, Function, FunctionArg, FunctionArgExpr, Ident, ObjectName, Select, SelectItem, Statement,
+    Value,
+};
+use models::schema::TIME_FIELD_NAME;
+use spi::query::logical_planner::{LogicalPlannerError, Result};
+
+use crate::extension::expr::scalar_function::time::{is_duration, TIME_WINDOW};
+use crate::sql::logical::visitor::{visit_statement, VisitorMut};
+use crate::sql::parser::normalize_ident;
+
+/// Rewrites `GROUP BY time(<interval>[, <offset>])` of the select statements, including
+/// those of the CTEs and subqueries, to
+/// `time_window(time, <interval>, <offset>, <time zone>)`, the buckets are aligned to
+/// the local midnight of the time zone of the session, UTC if absent, and shifted by
+/// the offset.
+///
+/// The `time` selected is the start of the bucket.
+pub fn rewrite_group_by_time(statement: &mut Statement, time_zone: Option<&str>) -> Result<()> {
+    let time_zone = match time_zone {
+        Some(time_zone) => {
+            time_zone
+                .parse::<Tz>()
+                .map_err(|_| LogicalPlannerError::Semantic {
+                    err: format!("Invalid time zone '{}'", time_zone),
+                })?;
+            time_zone
+        }
+        None => "UTC",
+    };
+
+    visit_statement(statement, &mut GroupByTime { time_zone })
+}
+
+struct GroupByTime<'a> {
+    time_zone: &'a str,
+}
+
+impl VisitorMut for GroupByTime<'_> {
+    fn pre_visit_select(&mut self, select: &mut Select) -> Result<()> {
+        rewrite_select(select, self.time_zone)
+    }
+}
+
+fn rewrite_select(select: &mut Select, time_zone: &str) -> Result<()> {
+    let window = match select.group_by.iter_mut().find_map(|e| match e {
+        Expr::Function(function) if is_group_by_time(function) => Some(function),
+        _ => None,
+    }) {
+        Some(function) => function,
+        None => return Ok(()),
+    };
+
+    let durations = window
+        .args
+        .iter()
+        .map(|arg| match arg {
+            FunctionArg::Unnamed(FunctionArgExpr::Expr(Expr::Value(
+                Value::SingleQuotedString(s),
+            ))) if is_duration(s) => Ok(s.clone()),
+            _ => Err(LogicalPlannerError::Semantic {
+                err: format!(
+                    "Invalid argument {} of GROUP BY time(), expect a duration",
+                    arg
+                ),
+            }),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let (interval, offset) = match durations.as_slice() {
+        [interval] => (interval.clone(), "0s".to_string()),
+        [interval, offset] => (interval.clone(), offset.clone()),
+        _ => {
+            return Err(LogicalPlannerError::Semantic {
+                err: "GROUP BY time() expects an interval and an optional offset".to_string(),
+            })
+        }
+    };
+
+    window.name = ObjectName(vec![Ident::new(TIME_WINDOW)]);
+    window.args = vec![
+        Expr::Identifier(Ident::new(TIME_FIELD_NAME)),
+        Expr::Value(Value::SingleQuotedString(interval)),
+        Expr::Value(Value::SingleQuotedString(offset)),
+        Expr::Value(Value::SingleQuotedString(time_zone.to_string())),
+    ]
+    .into_iter()
+    .map(|e| FunctionArg::Unnamed(FunctionArgExpr::Expr(e)))
+    .collect();
+    let window = Expr::Function(window.clone());
+
+    // The time selected is replaced by the bucket, as the time is not grouped by
+    for item in select.projection.iter_mut() {
+        if let SelectItem::UnnamedExpr(Expr::Identifier(ident)) = item {
+            if normalize_ident(ident) == TIME_FIELD_NAME {
+                *item = SelectItem::ExprWithAlias {
+                    expr: window.clone(),
+                    alias: Ident::new(TIME_FIELD_NAME),
+                };
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn is_group_by_time(function: &Function) -> bool {
+    match function.name.0.as_slice() {
+        [name] => normalize_ident(name) == TIME_FIELD_NAME,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use spi::query::ast::ExtStatement;
+
+    use super::*;
+    use crate::sql::parser::ExtParser;
+
+    fn rewrite(sql: &str, time_zone: Option<&str>) -> Result<String> {
+        let mut statement = match ExtParser::parse_sql(sql).unwrap().pop_front().unwrap() {
+            ExtStatement::SqlStatement(statement) => *statement,
+            _ => panic!("expect a sql statement"),
+        };
+        rewrite_group_by_time(&mut statement, time_zone)?;
+        Ok(statement.to_string())
+    }
+
+    #[test]
+    fn test_rewrite_group_by_time() {
+        assert_eq!(
+            rewrite(
+                "SELECT time, avg(f0) FROM m GROUP BY time('1d', OFFSET '8h')",
+                Some("Asia/Shanghai")
+            )
+            .unwrap(),
+            "SELECT time_window(time, '1d', '8h', 'Asia/Shanghai') AS time, avg(f0) \
+            FROM m GROUP BY time_window(time, '1d', '8h', 'Asia/Shanghai')"
+        );
+        assert_eq!(
+            rewrite("SELECT t0, count(f0) FROM m GROUP BY t0, time('1h')", None).unwrap(),
+            "SELECT t0, count(f0) FROM m GROUP BY t0, time_window(time, '1h', '0s', 'UTC')"
+        );
+        assert_eq!(
+            rewrite(
+                "WITH c AS (SELECT time, count(f0) AS n FROM m GROUP BY time('1h')) \
+                SELECT * FROM (SELECT time, max(n) FROM c GROUP BY time('1d')) AS d",
+                None
+            )
+            .unwrap(),
+            "WITH c AS (SELECT time_window(time, '1h', '0s', 'UTC') AS time, count(f0) AS n \
+            FROM m GROUP BY time_window(time, '1h', '0s', 'UTC')) \
+            SELECT * FROM (SELECT time_window(time, '1d', '0s', 'UTC') AS time, max(n) \
+            FROM c GROUP BY time_window(time, '1d', '0s', 'UTC')) AS d"
+        );
+        // Not grouped by time
+        assert_eq!(
+            rewrite("SELECT time, f0 FROM m", None).unwrap(),
+            "SELECT time, f0 FROM m"
+        );
+
+        assert!(rewrite("SELECT count(f0) FROM m GROUP BY time('1x')", None).is_err());
+        assert!(rewrite("SELECT count(f0) FROM m GROUP BY time(1)", None).is_err());
+        assert!(rewrite(
+            "SELECT count(f0) FROM m GROUP BY time('1d')",
+            Some("Mars/Base")
+        )
+        .is_err());
+    }
+}
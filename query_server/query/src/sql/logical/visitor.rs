@@ -0,0 +1,218 @@
+use datafusion::sql::sqlparser::ast::{
+    Expr, FunctionArg, FunctionArgExpr, JoinConstraint, JoinOperator, Query, Select, SelectItem,
+    SetExpr, Statement, TableFactor, TableWithJoins,
+};
+use spi::query::logical_planner::Result;
+
+/// Rewrites the select statements before they are planned, see [`visit_statement`]
+pub trait VisitorMut {
+    /// Called for each select, before the expressions of it are visited
+    fn pre_visit_select(&mut self, _select: &mut Select) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called for each expression, after the expressions in it are visited
+    fn post_visit_expr(&mut self, _expr: &mut Expr) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Visits every select and expression of the query of the statement, including those
+/// of the CTEs, derived tables, join conditions and subqueries
+pub fn visit_statement<V: VisitorMut>(statement: &mut Statement, visitor: &mut V) -> Result<()> {
+    match statement {
+        Statement::Query(query) => visit_query(query, visitor),
+        Statement::Explain { statement, .. } => visit_statement(statement, visitor),
+        Statement::Insert { source, .. } => visit_query(source, visitor),
+        _ => Ok(()),
+    }
+}
+
+fn visit_query<V: VisitorMut>(query: &mut Query, visitor: &mut V) -> Result<()> {
+    if let Some(with) = query.with.as_mut() {
+        for cte in with.cte_tables.iter_mut() {
+            visit_query(&mut cte.query, visitor)?;
+        }
+    }
+    visit_set_expr(&mut query.body, visitor)?;
+    for order_by in query.order_by.iter_mut() {
+        visit_expr(&mut order_by.expr, visitor)?;
+    }
+    Ok(())
+}
+
+fn visit_set_expr<V: VisitorMut>(set_expr: &mut SetExpr, visitor: &mut V) -> Result<()> {
+    match set_expr {
+        SetExpr::Select(select) => visit_select(select, visitor),
+        SetExpr::Query(query) => visit_query(query, visitor),
+        SetExpr::SetOperation { left, right, .. } => {
+            visit_set_expr(left, visitor)?;
+            visit_set_expr(right, visitor)
+        }
+        _ => Ok(()),
+    }
+}
+
+fn visit_select<V: VisitorMut>(select: &mut Select, visitor: &mut V) -> Result<()> {
+    visitor.pre_visit_select(select)?;
+
+    for table in select.from.iter_mut() {
+        visit_table_with_joins(table, visitor)?;
+    }
+    for item in select.projection.iter_mut() {
+        match item {
+            SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. } => {
+                visit_expr(expr, visitor)?
+            }
+            _ => {}
+        }
+    }
+    for expr in select
+        .selection
+        .iter_mut()
+        .chain(select.group_by.iter_mut())
+        .chain(select.having.iter_mut())
+    {
+        visit_expr(expr, visitor)?;
+    }
+    Ok(())
+}
+
+fn visit_table_with_joins<V: VisitorMut>(
+    table: &mut TableWithJoins,
+    visitor: &mut V,
+) -> Result<()> {
+    visit_table_factor(&mut table.relation, visitor)?;
+    for join in table.joins.iter_mut() {
+        visit_table_factor(&mut join.relation, visitor)?;
+        match &mut join.join_operator {
+            JoinOperator::Inner(JoinConstraint::On(expr))
+            | JoinOperator::LeftOuter(JoinConstraint::On(expr))
+            | JoinOperator::RightOuter(JoinConstraint::On(expr))
+            | JoinOperator::FullOuter(JoinConstraint::On(expr)) => visit_expr(expr, visitor)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn visit_table_factor<V: VisitorMut>(table: &mut TableFactor, visitor: &mut V) -> Result<()> {
+    match table {
+        TableFactor::Derived { subquery, .. } => visit_query(subquery, visitor),
+        _ => Ok(()),
+    }
+}
+
+fn visit_expr<V: VisitorMut>(expr: &mut Expr, visitor: &mut V) -> Result<()> {
+    match expr {
+        Expr::BinaryOp { left, right, .. } => {
+            visit_expr(left, visitor)?;
+            visit_expr(right, visitor)?;
+        }
+        Expr::UnaryOp { expr, .. }
+        | Expr::Nested(expr)
+        | Expr::Cast { expr, .. }
+        | Expr::TryCast { expr, .. }
+        | Expr::IsNull(expr)
+        | Expr::IsNotNull(expr) => visit_expr(expr, visitor)?,
+        Expr::AtTimeZone { timestamp, .. } => visit_expr(timestamp, visitor)?,
+        Expr::Between {
+            expr, low, high, ..
+        } => {
+            visit_expr(expr, visitor)?;
+            visit_expr(low, visitor)?;
+            visit_expr(high, visitor)?;
+        }
+        Expr::InList { expr, list, .. } => {
+            visit_expr(expr, visitor)?;
+            for e in list.iter_mut() {
+                visit_expr(e, visitor)?;
+            }
+        }
+        Expr::InSubquery { expr, subquery, .. } => {
+            visit_expr(expr, visitor)?;
+            visit_query(subquery, visitor)?;
+        }
+        Expr::Exists { subquery, .. } | Expr::Subquery(subquery) => visit_query(subquery, visitor)?,
+        Expr::Case {
+            operand,
+            conditions,
+            results,
+            else_result,
+        } => {
+            for e in operand
+                .iter_mut()
+                .map(|e| e.as_mut())
+                .chain(conditions.iter_mut())
+                .chain(results.iter_mut())
+                .chain(else_result.iter_mut().map(|e| e.as_mut()))
+            {
+                visit_expr(e, visitor)?;
+            }
+        }
+        Expr::Function(function) => {
+            for arg in function.args.iter_mut() {
+                match arg {
+                    FunctionArg::Named {
+                        arg: FunctionArgExpr::Expr(expr),
+                        ..
+                    }
+                    | FunctionArg::Unnamed(FunctionArgExpr::Expr(expr)) => {
+                        visit_expr(expr, visitor)?
+                    }
+                    _ => {}
+                }
+            }
+        }
+        _ => {}
+    }
+
+    visitor.post_visit_expr(expr)
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::sql::sqlparser::ast::{Ident, ObjectName};
+    use spi::query::ast::ExtStatement;
+
+    use super::*;
+    use crate::sql::parser::ExtParser;
+
+    /// Renames every function to `f` and counts the selects
+    #[derive(Default)]
+    struct Rename {
+        selects: usize,
+    }
+
+    impl VisitorMut for Rename {
+        fn pre_visit_select(&mut self, _select: &mut Select) -> Result<()> {
+            self.selects += 1;
+            Ok(())
+        }
+
+        fn post_visit_expr(&mut self, expr: &mut Expr) -> Result<()> {
+            if let Expr::Function(function) = expr {
+                function.name = ObjectName(vec![Ident::new("f")]);
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_visit_statement() {
+        let sql = "WITH c AS (SELECT now() AS t FROM m) \
+            SELECT a.t FROM (SELECT now() AS t FROM m) AS a \
+            JOIN c ON a.t = now() \
+            WHERE a.t IN (SELECT now() FROM m) AND EXISTS (SELECT 1 FROM m WHERE time > now()) \
+            ORDER BY now()";
+        let mut statement = match ExtParser::parse_sql(sql).unwrap().pop_front().unwrap() {
+            ExtStatement::SqlStatement(statement) => *statement,
+            _ => panic!("expect a sql statement"),
+        };
+
+        let mut visitor = Rename::default();
+        visit_statement(&mut statement, &mut visitor).unwrap();
+        assert_eq!(visitor.selects, 5);
+        assert_eq!(statement.to_string(), sql.replace("now()", "f()"));
+    }
+}
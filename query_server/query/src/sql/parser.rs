@@ -3,7 +3,7 @@ use std::str::FromStr;
 
 use datafusion::sql::parser::CreateExternalTable;
 use datafusion::sql::sqlparser::{
-    ast::{DataType, Ident, ObjectName, Value},
+    ast::{AnalyzeFormat, DataType, Ident, ObjectName, Statement, Value},
     dialect::{keywords::Keyword, Dialect, GenericDialect},
     parser::{Parser, ParserError},
     tokenizer::{Token, Tokenizer},
@@ -11,8 +11,13 @@ use datafusion::sql::sqlparser::{
 use models::codec::Encoding;
 use snafu::ResultExt;
 use spi::query::ast::{
-    AlterDatabase, AlterTable, AlterTableAction, ColumnOption, CreateDatabase, CreateTable,
-    DatabaseOptions, DescribeDatabase, DescribeTable, DropObject, ExtStatement, ObjectType,
+    AlterDatabase, AlterSystemSet, AlterTable, AlterTableAction, AlterTask, AlterUser,
+    AnalyzeTable, ColumnOption, CopySource, CopyTo, CreateDatabase, CreatePolicy, CreateRole,
+    CreateStreamSource, CreateTable, CreateTask, CreateToken, CreateUser, DatabaseOptions,
+    DeleteBefore, DescribeDatabase, DescribeTable, DropObject, DropPolicy, DropRole, DropTask,
+    DropToken, DropUser, ExportDatabase, ExtStatement, FlushDatabase, GrantRole, GrantRoleToGroup,
+    GrantSelect, ImportDatabase, ObjectType, RevokeRole, RevokeRoleFromGroup, RevokeSelect,
+    ShowCreateTable, TaskSink,
 };
 use spi::query::parser::Parser as CnosdbParser;
 use spi::query::ParserSnafu;
@@ -38,14 +43,92 @@ enum CnosKeyWord {
     #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
     SHARD,
     #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    SHARDS,
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    GRANTS,
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
     VNODE_DURATION,
     #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    DURATION,
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
     REPLICA,
     #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
     PRECISION,
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    AUTO_CREATE_SCHEMA,
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    FIELD_TYPE_CONFLICT,
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    FUTURE_TOLERANCE,
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    PAST_TOLERANCE,
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    OUT_OF_WINDOW,
 
     #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
     QUERIES,
+
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    STREAM,
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    SOURCE,
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    SOURCES,
+
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    EXPORT,
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    IMPORT,
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    FLUSH,
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    BEFORE,
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    STATS,
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    DIAGNOSTICS,
+
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    SYSTEM,
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    SETTINGS,
+
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    USER,
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    USERS,
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    TOKEN,
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    TOKENS,
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    ROLE,
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    ROLES,
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    POLICY,
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    POLICIES,
+
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    TASK,
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    TASKS,
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    EVERY,
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    DATA,
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    WEBHOOK,
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    ENABLE,
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    DISABLE,
+
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    SESSION,
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    SESSIONS,
 }
 
 impl FromStr for CnosKeyWord {
@@ -58,10 +141,46 @@ impl FromStr for CnosKeyWord {
             "FIELD" => Ok(CnosKeyWord::FIELD),
             "TTL" => Ok(CnosKeyWord::TTL),
             "SHARD" => Ok(CnosKeyWord::SHARD),
+            "SHARDS" => Ok(CnosKeyWord::SHARDS),
+            "GRANTS" => Ok(CnosKeyWord::GRANTS),
             "VNODE_DURATION" => Ok(CnosKeyWord::VNODE_DURATION),
+            "DURATION" => Ok(CnosKeyWord::DURATION),
             "REPLICA" => Ok(CnosKeyWord::REPLICA),
             "PRECISION" => Ok(CnosKeyWord::PRECISION),
+            "AUTO_CREATE_SCHEMA" => Ok(CnosKeyWord::AUTO_CREATE_SCHEMA),
+            "FIELD_TYPE_CONFLICT" => Ok(CnosKeyWord::FIELD_TYPE_CONFLICT),
+            "FUTURE_TOLERANCE" => Ok(CnosKeyWord::FUTURE_TOLERANCE),
+            "PAST_TOLERANCE" => Ok(CnosKeyWord::PAST_TOLERANCE),
+            "OUT_OF_WINDOW" => Ok(CnosKeyWord::OUT_OF_WINDOW),
             "DATABASES" => Ok(CnosKeyWord::DATABASES),
+            "STREAM" => Ok(CnosKeyWord::STREAM),
+            "SOURCE" => Ok(CnosKeyWord::SOURCE),
+            "SOURCES" => Ok(CnosKeyWord::SOURCES),
+            "EXPORT" => Ok(CnosKeyWord::EXPORT),
+            "IMPORT" => Ok(CnosKeyWord::IMPORT),
+            "FLUSH" => Ok(CnosKeyWord::FLUSH),
+            "BEFORE" => Ok(CnosKeyWord::BEFORE),
+            "STATS" => Ok(CnosKeyWord::STATS),
+            "DIAGNOSTICS" => Ok(CnosKeyWord::DIAGNOSTICS),
+            "SYSTEM" => Ok(CnosKeyWord::SYSTEM),
+            "SETTINGS" => Ok(CnosKeyWord::SETTINGS),
+            "USER" => Ok(CnosKeyWord::USER),
+            "USERS" => Ok(CnosKeyWord::USERS),
+            "TOKEN" => Ok(CnosKeyWord::TOKEN),
+            "TOKENS" => Ok(CnosKeyWord::TOKENS),
+            "ROLE" => Ok(CnosKeyWord::ROLE),
+            "ROLES" => Ok(CnosKeyWord::ROLES),
+            "POLICY" => Ok(CnosKeyWord::POLICY),
+            "POLICIES" => Ok(CnosKeyWord::POLICIES),
+            "TASK" => Ok(CnosKeyWord::TASK),
+            "TASKS" => Ok(CnosKeyWord::TASKS),
+            "EVERY" => Ok(CnosKeyWord::EVERY),
+            "DATA" => Ok(CnosKeyWord::DATA),
+            "WEBHOOK" => Ok(CnosKeyWord::WEBHOOK),
+            "ENABLE" => Ok(CnosKeyWord::ENABLE),
+            "DISABLE" => Ok(CnosKeyWord::DISABLE),
+            "SESSION" => Ok(CnosKeyWord::SESSION),
+            "SESSIONS" => Ok(CnosKeyWord::SESSIONS),
             _ => Err(ParserError::ParserError(format!(
                 "fail parse {} to CnosKeyWord",
                 s
@@ -102,7 +221,7 @@ impl<'a> ExtParser<'a> {
     /// Parse the specified tokens with dialect
     fn new_with_dialect(sql: &str, dialect: &'a dyn Dialect) -> Result<Self> {
         let mut tokenizer = Tokenizer::new(dialect, sql);
-        let tokens = tokenizer.tokenize()?;
+        let tokens = strip_time_window_offset(tokenizer.tokenize()?);
 
         Ok(ExtParser {
             parser: Parser::new(tokens, dialect),
@@ -169,6 +288,38 @@ impl<'a> ExtParser<'a> {
                     self.parser.next_token();
                     self.parse_create()
                 }
+                Keyword::COPY => {
+                    self.parser.next_token();
+                    self.parse_copy()
+                }
+                Keyword::GRANT => {
+                    self.parser.next_token();
+                    self.parse_grant()
+                }
+                Keyword::EXPLAIN => {
+                    self.parser.next_token();
+                    self.parse_explain()
+                }
+                Keyword::REVOKE => {
+                    self.parser.next_token();
+                    self.parse_revoke()
+                }
+                Keyword::ANALYZE => {
+                    self.parser.next_token();
+                    self.parse_analyze_table()
+                }
+                Keyword::DELETE if self.is_delete_before() => {
+                    self.parser.next_token();
+                    self.parse_delete_before()
+                }
+                Keyword::KILL if self.is_kill_session() => {
+                    self.parser.next_token();
+                    self.parser.next_token();
+                    self.parse_kill_session()
+                }
+                _ if self.parse_cnos_keyword(CnosKeyWord::EXPORT) => self.parse_export_database(),
+                _ if self.parse_cnos_keyword(CnosKeyWord::IMPORT) => self.parse_import_database(),
+                _ if self.parse_cnos_keyword(CnosKeyWord::FLUSH) => self.parse_flush_database(),
                 _ => Ok(ExtStatement::SqlStatement(Box::new(
                     self.parser.parse_statement()?,
                 ))),
@@ -191,12 +342,41 @@ impl<'a> ExtParser<'a> {
     fn parse_show(&mut self) -> Result<ExtStatement> {
         if self.parser.parse_keyword(Keyword::TABLES) {
             self.parse_show_tables()
+        } else if self.parser.parse_keyword(Keyword::CREATE) {
+            self.parse_show_create_table()
         } else if self.parse_cnos_keyword(CnosKeyWord::DATABASES) {
             self.parse_show_databases()
         } else if self.parse_cnos_keyword(CnosKeyWord::QUERIES) {
             self.parse_show_queries()
+        } else if self.parse_cnos_keyword(CnosKeyWord::STREAM) {
+            self.parse_show_stream_sources()
+        } else if self.parse_cnos_keyword(CnosKeyWord::SETTINGS) {
+            Ok(ExtStatement::ShowSettings)
+        } else if self.parse_cnos_keyword(CnosKeyWord::STATS) {
+            self.parse_show_stats()
+        } else if self.parse_cnos_keyword(CnosKeyWord::DIAGNOSTICS) {
+            Ok(ExtStatement::ShowDiagnostics)
+        } else if self.parse_cnos_keyword(CnosKeyWord::USERS) {
+            Ok(ExtStatement::ShowUsers)
+        } else if self.parse_cnos_keyword(CnosKeyWord::TOKENS) {
+            Ok(ExtStatement::ShowTokens)
+        } else if self.parse_cnos_keyword(CnosKeyWord::ROLES) {
+            Ok(ExtStatement::ShowRoles)
+        } else if self.parse_cnos_keyword(CnosKeyWord::POLICIES) {
+            Ok(ExtStatement::ShowPolicies)
+        } else if self.parse_cnos_keyword(CnosKeyWord::SHARDS) {
+            self.parse_show_shards()
+        } else if self.parse_cnos_keyword(CnosKeyWord::GRANTS) {
+            self.parse_show_grants()
+        } else if self.parse_cnos_keyword(CnosKeyWord::TASKS) {
+            Ok(ExtStatement::ShowTasks)
+        } else if self.parse_cnos_keyword(CnosKeyWord::SESSIONS) {
+            Ok(ExtStatement::ShowSessions)
         } else {
-            self.expected("tables/databases", self.parser.peek_token())
+            self.expected(
+                "tables/create table/databases/stream sources/settings/users/tokens/roles/policies/shards/grants/tasks/sessions",
+                self.parser.peek_token(),
+            )
         }
     }
 
@@ -204,6 +384,13 @@ impl<'a> ExtParser<'a> {
         Ok(ExtStatement::ShowQueries)
     }
 
+    fn parse_show_stream_sources(&mut self) -> Result<ExtStatement> {
+        if !self.parse_cnos_keyword(CnosKeyWord::SOURCES) {
+            return self.expected("SOURCES after SHOW STREAM", self.parser.peek_token());
+        }
+        Ok(ExtStatement::ShowStreamSources)
+    }
+
     fn parse_show_databases(&mut self) -> Result<ExtStatement> {
         Ok(ExtStatement::ShowDatabases())
     }
@@ -217,6 +404,157 @@ impl<'a> ExtParser<'a> {
         }
     }
 
+    fn parse_show_shards(&mut self) -> Result<ExtStatement> {
+        if self.consume_token(&Token::make_keyword("ON")) {
+            let database_name = self.parser.parse_object_name()?;
+            Ok(ExtStatement::ShowShards(Some(database_name)))
+        } else {
+            Ok(ExtStatement::ShowShards(None))
+        }
+    }
+
+    fn parse_show_grants(&mut self) -> Result<ExtStatement> {
+        if self.parser.parse_keyword(Keyword::FOR) {
+            let grantee = self.parser.parse_identifier()?;
+            Ok(ExtStatement::ShowGrants(Some(grantee)))
+        } else {
+            Ok(ExtStatement::ShowGrants(None))
+        }
+    }
+
+    /// Parse a SQL SHOW STATS statement, the module is lowercased
+    fn parse_show_stats(&mut self) -> Result<ExtStatement> {
+        if self.parser.parse_keyword(Keyword::FOR) {
+            let module = self.parse_string_value()?;
+            Ok(ExtStatement::ShowStats(Some(module.to_lowercase())))
+        } else {
+            Ok(ExtStatement::ShowStats(None))
+        }
+    }
+
+    /// Parse a SQL SHOW CREATE TABLE statement
+    fn parse_show_create_table(&mut self) -> Result<ExtStatement> {
+        self.parser.expect_keyword(Keyword::TABLE)?;
+        let table_name = self.parser.parse_object_name()?;
+        Ok(ExtStatement::ShowCreateTable(ShowCreateTable {
+            table_name,
+        }))
+    }
+
+    /// Parse a SQL ANALYZE TABLE statement
+    fn parse_analyze_table(&mut self) -> Result<ExtStatement> {
+        self.parser.expect_keyword(Keyword::TABLE)?;
+        let table_name = self.parser.parse_object_name()?;
+        Ok(ExtStatement::AnalyzeTable(AnalyzeTable { table_name }))
+    }
+
+    /// Parse a SQL FLUSH DATABASE statement
+    fn parse_flush_database(&mut self) -> Result<ExtStatement> {
+        self.parser.expect_keyword(Keyword::DATABASE)?;
+        let database_name = self.parser.parse_object_name()?;
+        Ok(ExtStatement::FlushDatabase(FlushDatabase { database_name }))
+    }
+
+    fn is_kill_session(&self) -> bool {
+        self.parser
+            .peek_nth_token(1)
+            .to_string()
+            .parse::<CnosKeyWord>()
+            == Ok(CnosKeyWord::SESSION)
+    }
+
+    /// Parse a SQL KILL SESSION <id> statement, `KILL [QUERY] <id>` is parsed by sqlparser
+    fn parse_kill_session(&mut self) -> Result<ExtStatement> {
+        let id = self.parser.parse_literal_uint()?;
+        Ok(ExtStatement::KillSession(id))
+    }
+
+    /// `DELETE FROM <database> BEFORE ...`, the other DELETE statements are left
+    /// to sqlparser
+    fn is_delete_before(&self) -> bool {
+        matches!(self.parser.peek_nth_token(1), Token::Word(w) if w.keyword == Keyword::FROM)
+            && self
+                .parser
+                .peek_nth_token(3)
+                .to_string()
+                .parse::<CnosKeyWord>()
+                == Ok(CnosKeyWord::BEFORE)
+    }
+
+    /// Parse a SQL DELETE FROM <database> BEFORE statement, the timestamp is a RFC3339
+    /// string or nanoseconds
+    fn parse_delete_before(&mut self) -> Result<ExtStatement> {
+        self.parser.expect_keyword(Keyword::FROM)?;
+        let database_name = self.parser.parse_object_name()?;
+        if !self.parse_cnos_keyword(CnosKeyWord::BEFORE) {
+            return self.expected("BEFORE", self.parser.peek_token());
+        }
+        let before = if matches!(self.parser.peek_token(), Token::Number(..)) {
+            self.parser.parse_number_value()?.to_string()
+        } else {
+            self.parse_string_value()?
+        };
+        Ok(ExtStatement::DeleteBefore(DeleteBefore {
+            database_name,
+            before,
+        }))
+    }
+
+    /// Parse a SQL EXPLAIN statement, the options may be given in parentheses
+    /// `EXPLAIN (ANALYZE, VERBOSE, FORMAT { TEXT | JSON | GRAPHVIZ | DOT }) <statement>`
+    fn parse_explain(&mut self) -> Result<ExtStatement> {
+        let has_options = self.parser.peek_token() == Token::LParen
+            && matches!(
+                self.parser.peek_nth_token(1),
+                Token::Word(w) if matches!(w.keyword, Keyword::ANALYZE | Keyword::VERBOSE | Keyword::FORMAT)
+            );
+        if !has_options {
+            return Ok(ExtStatement::SqlStatement(Box::new(
+                self.parser.parse_explain(false)?,
+            )));
+        }
+
+        self.parser.expect_token(&Token::LParen)?;
+        let (mut analyze, mut verbose, mut format) = (false, false, None);
+        loop {
+            if self.parser.parse_keyword(Keyword::ANALYZE) {
+                analyze = true;
+            } else if self.parser.parse_keyword(Keyword::VERBOSE) {
+                verbose = true;
+            } else if self.parser.parse_keyword(Keyword::FORMAT) {
+                format = Some(self.parse_explain_format()?);
+            } else {
+                return self.expected("ANALYZE, VERBOSE or FORMAT", self.parser.peek_token());
+            }
+            if !self.consume_token(&Token::Comma) {
+                break;
+            }
+        }
+        self.parser.expect_token(&Token::RParen)?;
+
+        let statement = self.parser.parse_statement()?;
+        Ok(ExtStatement::SqlStatement(Box::new(Statement::Explain {
+            describe_alias: false,
+            analyze,
+            verbose,
+            statement: Box::new(statement),
+            format,
+        })))
+    }
+
+    fn parse_explain_format(&mut self) -> Result<AnalyzeFormat> {
+        let format = self.parser.parse_identifier()?;
+        match format.value.to_uppercase().as_str() {
+            "TEXT" => Ok(AnalyzeFormat::TEXT),
+            "JSON" => Ok(AnalyzeFormat::JSON),
+            "GRAPHVIZ" | "DOT" => Ok(AnalyzeFormat::GRAPHVIZ),
+            _ => parser_err!(format!(
+                "Expected TEXT, JSON, GRAPHVIZ or DOT, found: {}",
+                format
+            )),
+        }
+    }
+
     /// Parse a SQL DESCRIBE DATABASE statement
     fn parse_describe_database(&mut self) -> Result<ExtStatement> {
         debug!("Parse Describe DATABASE statement");
@@ -252,9 +590,64 @@ impl<'a> ExtParser<'a> {
             self.parse_alter_table()
         } else if self.parser.parse_keyword(Keyword::DATABASE) {
             self.parse_alter_database()
+        } else if self.parse_cnos_keyword(CnosKeyWord::SYSTEM) {
+            self.parse_alter_system()
+        } else if self.parse_cnos_keyword(CnosKeyWord::USER) {
+            self.parse_alter_user()
+        } else if self.parse_cnos_keyword(CnosKeyWord::TASK) {
+            self.parse_alter_task()
+        } else {
+            self.expected(
+                "TABLE or DATABASE or SYSTEM or USER or TASK",
+                self.parser.peek_token(),
+            )
+        }
+    }
+
+    /// Parse `ALTER USER name WITH (password = '...', is_admin = false)`
+    fn parse_alter_user(&mut self) -> Result<ExtStatement> {
+        let name = self.parser.parse_identifier()?;
+        let options = self.parser.parse_options(Keyword::WITH)?;
+        if options.is_empty() {
+            return self.expected("WITH options after ALTER USER", self.parser.peek_token());
+        }
+
+        Ok(ExtStatement::AlterUser(AlterUser { name, options }))
+    }
+
+    /// Parse `ALTER TASK name { ENABLE | DISABLE }`
+    fn parse_alter_task(&mut self) -> Result<ExtStatement> {
+        let name = self.parser.parse_identifier()?;
+        let enabled = if self.parse_cnos_keyword(CnosKeyWord::ENABLE) {
+            true
+        } else if self.parse_cnos_keyword(CnosKeyWord::DISABLE) {
+            false
         } else {
-            self.expected("TABLE or DATABASE", self.parser.peek_token())
+            return self.expected("ENABLE or DISABLE", self.parser.peek_token());
+        };
+
+        Ok(ExtStatement::AlterTask(AlterTask { name, enabled }))
+    }
+
+    /// Parse `ALTER SYSTEM SET <section>.<key> = <value>`, the value is a number,
+    /// a string or a word, e.g. `debug`
+    fn parse_alter_system(&mut self) -> Result<ExtStatement> {
+        self.parser.expect_keyword(Keyword::SET)?;
+        let name = self.parser.parse_object_name()?;
+        if !self.parser.consume_token(&Token::Eq) && !self.parser.parse_keyword(Keyword::TO) {
+            return self.expected("= or TO", self.parser.peek_token());
         }
+        let value = match self.parser.next_token() {
+            Token::Number(n, _) => n,
+            Token::SingleQuotedString(s) => s,
+            Token::Word(w) => w.value,
+            t => return self.expected("value", t),
+        };
+
+        Ok(ExtStatement::AlterSystemSet(AlterSystemSet {
+            name: name.to_string(),
+            value,
+        }))
     }
 
     fn parse_alter_table(&mut self) -> Result<ExtStatement> {
@@ -305,9 +698,30 @@ impl<'a> ExtParser<'a> {
         }))
     }
 
+    /// ALTER TABLE table_name ALTER column_name SET { CODEC(...) | TAG | FIELD data_type [CODEC(...)] }
     fn parse_alter_table_alter_column(&mut self, table_name: ObjectName) -> Result<ExtStatement> {
         let column_name = self.parser.parse_identifier()?;
         self.parser.expect_keyword(Keyword::SET)?;
+        if self.parse_cnos_keyword(CnosKeyWord::TAG) {
+            let column = ColumnOption::new_tag(column_name);
+            return Ok(ExtStatement::AlterTable(AlterTable {
+                table_name,
+                alter_action: AlterTableAction::ConvertColumn { column },
+            }));
+        }
+        if self.parse_cnos_keyword(CnosKeyWord::FIELD) {
+            let data_type = self.parse_column_type()?;
+            let encoding = if self.peek_cnos_keyword().eq(&Ok(CnosKeyWord::CODEC)) {
+                Some(self.parse_codec_type()?)
+            } else {
+                None
+            };
+            let column = ColumnOption::new_field(column_name, data_type, encoding);
+            return Ok(ExtStatement::AlterTable(AlterTable {
+                table_name,
+                alter_action: AlterTableAction::ConvertColumn { column },
+            }));
+        }
         let encoding = self.parse_codec_type()?;
         Ok(ExtStatement::AlterTable(AlterTable {
             table_name,
@@ -318,15 +732,21 @@ impl<'a> ExtParser<'a> {
         }))
     }
 
+    /// Parse `ALTER DATABASE name SET option [, option ...]`
     fn parse_alter_database(&mut self) -> Result<ExtStatement> {
         let database_name = self.parser.parse_object_name()?;
         self.parser.expect_keyword(Keyword::SET)?;
         let mut options = DatabaseOptions::default();
-        if !self.parse_database_option(&mut options)? {
-            return parser_err!(format!(
-                "expected database option, but found {}",
-                self.parser.peek_token()
-            ));
+        loop {
+            if !self.parse_database_option(&mut options)? {
+                return parser_err!(format!(
+                    "expected database option, but found {}",
+                    self.parser.peek_token()
+                ));
+            }
+            if !self.consume_token(&Token::Comma) {
+                break;
+            }
         }
         Ok(ExtStatement::AlterDatabase(AlterDatabase {
             name: database_name,
@@ -334,11 +754,73 @@ impl<'a> ExtParser<'a> {
         }))
     }
 
+    /// Parse a SQL COPY statement
+    ///
+    /// COPY { table_name | ( query ) } TO 'location' [ FORMAT { CSV | PARQUET | JSON } ]
+    fn parse_copy(&mut self) -> Result<ExtStatement> {
+        let source = if self.consume_token(&Token::LParen) {
+            let query = self.parser.parse_query()?;
+            self.parser.expect_token(&Token::RParen)?;
+            CopySource::Query(Box::new(query))
+        } else {
+            CopySource::Table(self.parser.parse_object_name()?)
+        };
+
+        self.parser.expect_keyword(Keyword::TO)?;
+        let location = self.parser.parse_literal_string()?;
+
+        let file_type = if self.parser.parse_keyword(Keyword::FORMAT) {
+            self.parse_file_format()?
+        } else {
+            "CSV".to_string()
+        };
+
+        Ok(ExtStatement::Copy(CopyTo {
+            source,
+            location,
+            file_type,
+        }))
+    }
+
+    /// Parse a SQL EXPORT DATABASE statement
+    ///
+    /// EXPORT DATABASE name TO 'location' [WITH (key = 'value', ...)]
+    fn parse_export_database(&mut self) -> Result<ExtStatement> {
+        self.parser.expect_keyword(Keyword::DATABASE)?;
+        let database = self.parser.parse_identifier()?;
+        self.parser.expect_keyword(Keyword::TO)?;
+        let location = self.parser.parse_literal_string()?;
+        let options = self.parser.parse_options(Keyword::WITH)?;
+
+        Ok(ExtStatement::ExportDatabase(ExportDatabase {
+            database,
+            location,
+            options,
+        }))
+    }
+
+    /// Parse a SQL IMPORT DATABASE statement
+    ///
+    /// IMPORT DATABASE name FROM 'location' [WITH (key = 'value', ...)]
+    fn parse_import_database(&mut self) -> Result<ExtStatement> {
+        self.parser.expect_keyword(Keyword::DATABASE)?;
+        let database = self.parser.parse_identifier()?;
+        self.parser.expect_keyword(Keyword::FROM)?;
+        let location = self.parser.parse_literal_string()?;
+        let options = self.parser.parse_options(Keyword::WITH)?;
+
+        Ok(ExtStatement::ImportDatabase(ImportDatabase {
+            database,
+            location,
+            options,
+        }))
+    }
+
     /// Parses the set of
     fn parse_file_compression_type(&mut self) -> Result<String, ParserError> {
         match self.parser.next_token() {
             Token::Word(w) => parse_file_compression_type(&w.value),
-            unexpected => self.expected("one of GZIP, BZIP2", unexpected),
+            unexpected => self.expected("one of GZIP, BZIP2, ZSTD", unexpected),
         }
     }
 
@@ -381,7 +863,7 @@ impl<'a> ExtParser<'a> {
     fn parse_file_format(&mut self) -> Result<String, ParserError> {
         match self.parser.next_token() {
             Token::Word(w) => parse_file_type(&w.value),
-            unexpected => self.expected("one of PARQUET, NDJSON, or CSV", unexpected),
+            unexpected => self.expected("one of PARQUET, NDJSON, JSON, AVRO or CSV", unexpected),
         }
     }
 
@@ -481,11 +963,11 @@ impl<'a> ExtParser<'a> {
     fn parse_database_options(&mut self) -> Result<DatabaseOptions> {
         if self.parser.parse_keyword(Keyword::WITH) {
             let mut options = DatabaseOptions::default();
-            loop {
-                if !self.parse_database_option(&mut options)? {
-                    return Ok(options);
-                }
+            // The options may be separated by commas
+            while self.parse_database_option(&mut options)? {
+                self.consume_token(&Token::Comma);
             }
+            return Ok(options);
         }
         Ok(DatabaseOptions::default())
     }
@@ -494,13 +976,28 @@ impl<'a> ExtParser<'a> {
         if self.parse_cnos_keyword(CnosKeyWord::TTL) {
             options.ttl = Some(self.parse_string_value()?);
         } else if self.parse_cnos_keyword(CnosKeyWord::SHARD) {
-            options.shard_num = Some(self.parse_u64()?);
+            // `SHARD DURATION` is the duration of the vnodes
+            if self.parse_cnos_keyword(CnosKeyWord::DURATION) {
+                options.vnode_duration = Some(self.parse_string_value()?);
+            } else {
+                options.shard_num = Some(self.parse_u64()?);
+            }
         } else if self.parse_cnos_keyword(CnosKeyWord::VNODE_DURATION) {
             options.vnode_duration = Some(self.parse_string_value()?);
         } else if self.parse_cnos_keyword(CnosKeyWord::REPLICA) {
             options.replica = Some(self.parse_u64()?);
         } else if self.parse_cnos_keyword(CnosKeyWord::PRECISION) {
             options.precision = Some(self.parse_string_value()?);
+        } else if self.parse_cnos_keyword(CnosKeyWord::AUTO_CREATE_SCHEMA) {
+            options.auto_create_schema = Some(self.parse_bool_value()?);
+        } else if self.parse_cnos_keyword(CnosKeyWord::FIELD_TYPE_CONFLICT) {
+            options.field_type_conflict = Some(self.parse_string_value()?);
+        } else if self.parse_cnos_keyword(CnosKeyWord::FUTURE_TOLERANCE) {
+            options.future_tolerance = Some(self.parse_string_value()?);
+        } else if self.parse_cnos_keyword(CnosKeyWord::PAST_TOLERANCE) {
+            options.past_tolerance = Some(self.parse_string_value()?);
+        } else if self.parse_cnos_keyword(CnosKeyWord::OUT_OF_WINDOW) {
+            options.out_of_window = Some(self.parse_string_value()?);
         } else {
             return Ok(false);
         }
@@ -520,6 +1017,14 @@ impl<'a> ExtParser<'a> {
         }
     }
 
+    fn parse_bool_value(&mut self) -> Result<bool> {
+        let value = self.parser.parse_value()?;
+        match value {
+            Value::Boolean(b) => Ok(b),
+            _ => parser_err!(format!("expected true or false, but found : {}", value)),
+        }
+    }
+
     fn parse_string_value(&mut self) -> Result<String> {
         let value = self.parser.parse_value()?;
         match value {
@@ -541,6 +1046,201 @@ impl<'a> ExtParser<'a> {
         }))
     }
 
+    /// Parse a SQL CREATE STREAM SOURCE statement
+    ///
+    /// CREATE STREAM SOURCE [IF NOT EXISTS] name FROM connector WITH (key = 'value', ...)
+    fn parse_create_stream_source(&mut self) -> Result<ExtStatement> {
+        if !self.parse_cnos_keyword(CnosKeyWord::SOURCE) {
+            return self.expected("SOURCE after CREATE STREAM", self.parser.peek_token());
+        }
+        let if_not_exists =
+            self.parser
+                .parse_keywords(&[Keyword::IF, Keyword::NOT, Keyword::EXISTS]);
+        let name = self.parser.parse_object_name()?;
+        self.parser.expect_keyword(Keyword::FROM)?;
+        let connector = self.parser.parse_identifier()?;
+        let options = self.parser.parse_options(Keyword::WITH)?;
+
+        Ok(ExtStatement::CreateStreamSource(CreateStreamSource {
+            name,
+            if_not_exists,
+            connector,
+            options,
+        }))
+    }
+
+    /// Parse a SQL CREATE TASK statement
+    ///
+    /// CREATE TASK [IF NOT EXISTS] name
+    ///     { EVERY '<duration>' | ON DATA IN table [EVERY '<duration>'] }
+    ///     { INTO table | WEBHOOK '<url>' } AS query
+    fn parse_create_task(&mut self) -> Result<ExtStatement> {
+        let if_not_exists =
+            self.parser
+                .parse_keywords(&[Keyword::IF, Keyword::NOT, Keyword::EXISTS]);
+        let name = self.parser.parse_identifier()?;
+        let on_data = if self.parser.parse_keyword(Keyword::ON) {
+            if !self.parse_cnos_keyword(CnosKeyWord::DATA) {
+                return self.expected("DATA after ON", self.parser.peek_token());
+            }
+            self.parser.expect_keyword(Keyword::IN)?;
+            Some(self.parser.parse_object_name()?)
+        } else {
+            None
+        };
+        let every = if self.parse_cnos_keyword(CnosKeyWord::EVERY) {
+            Some(self.parse_string_value()?)
+        } else {
+            None
+        };
+        if on_data.is_none() && every.is_none() {
+            return self.expected("EVERY or ON DATA", self.parser.peek_token());
+        }
+        let sink = if self.parser.parse_keyword(Keyword::INTO) {
+            TaskSink::Table(self.parser.parse_object_name()?)
+        } else if self.parse_cnos_keyword(CnosKeyWord::WEBHOOK) {
+            TaskSink::Webhook(self.parse_string_value()?)
+        } else {
+            return self.expected("INTO or WEBHOOK", self.parser.peek_token());
+        };
+        self.parser.expect_keyword(Keyword::AS)?;
+        let query = self.parser.parse_query()?;
+
+        Ok(ExtStatement::CreateTask(CreateTask {
+            name,
+            if_not_exists,
+            every,
+            on_data,
+            sink,
+            query: Box::new(query),
+        }))
+    }
+
+    /// Parse a SQL CREATE USER statement
+    ///
+    /// CREATE USER [IF NOT EXISTS] name WITH (password = '...', is_admin = true)
+    fn parse_create_user(&mut self) -> Result<ExtStatement> {
+        let if_not_exists =
+            self.parser
+                .parse_keywords(&[Keyword::IF, Keyword::NOT, Keyword::EXISTS]);
+        let name = self.parser.parse_identifier()?;
+        let options = self.parser.parse_options(Keyword::WITH)?;
+
+        Ok(ExtStatement::CreateUser(CreateUser {
+            name,
+            if_not_exists,
+            options,
+        }))
+    }
+
+    /// Parse `GRANT ROLE role TO {user | GROUP 'group'}` or `GRANT SELECT (column, ...) ON table TO role`
+    fn parse_grant(&mut self) -> Result<ExtStatement> {
+        if self.parser.parse_keyword(Keyword::SELECT) {
+            let columns = self.parse_select_columns()?;
+            if columns.is_empty() {
+                return self.expected("columns after GRANT SELECT", self.parser.peek_token());
+            }
+            self.parser.expect_keyword(Keyword::ON)?;
+            let table = self.parser.parse_object_name()?;
+            self.parser.expect_keyword(Keyword::TO)?;
+            let role = self.parser.parse_identifier()?;
+            return Ok(ExtStatement::GrantSelect(GrantSelect {
+                columns,
+                table,
+                role,
+            }));
+        }
+        if !self.parse_cnos_keyword(CnosKeyWord::ROLE) {
+            return self.expected("ROLE or SELECT after GRANT", self.parser.peek_token());
+        }
+        let role = self.parser.parse_identifier()?;
+        self.parser.expect_keyword(Keyword::TO)?;
+        if self.parser.parse_keyword(Keyword::GROUP) {
+            let group = self.parser.parse_literal_string()?;
+            return Ok(ExtStatement::GrantRoleToGroup(GrantRoleToGroup {
+                role,
+                group,
+            }));
+        }
+        let user = self.parser.parse_identifier()?;
+        Ok(ExtStatement::GrantRole(GrantRole { role, user }))
+    }
+
+    /// Parse `REVOKE ROLE role FROM {user | GROUP 'group'}` or `REVOKE SELECT [(column, ...)] ON table FROM role`
+    fn parse_revoke(&mut self) -> Result<ExtStatement> {
+        if self.parser.parse_keyword(Keyword::SELECT) {
+            let columns = self.parse_select_columns()?;
+            self.parser.expect_keyword(Keyword::ON)?;
+            let table = self.parser.parse_object_name()?;
+            self.parser.expect_keyword(Keyword::FROM)?;
+            let role = self.parser.parse_identifier()?;
+            return Ok(ExtStatement::RevokeSelect(RevokeSelect {
+                columns,
+                table,
+                role,
+            }));
+        }
+        if !self.parse_cnos_keyword(CnosKeyWord::ROLE) {
+            return self.expected("ROLE or SELECT after REVOKE", self.parser.peek_token());
+        }
+        let role = self.parser.parse_identifier()?;
+        self.parser.expect_keyword(Keyword::FROM)?;
+        if self.parser.parse_keyword(Keyword::GROUP) {
+            let group = self.parser.parse_literal_string()?;
+            return Ok(ExtStatement::RevokeRoleFromGroup(RevokeRoleFromGroup {
+                role,
+                group,
+            }));
+        }
+        let user = self.parser.parse_identifier()?;
+        Ok(ExtStatement::RevokeRole(RevokeRole { role, user }))
+    }
+
+    /// Parse the optional `(column, ...)` of the privileges on the columns
+    fn parse_select_columns(&mut self) -> Result<Vec<Ident>> {
+        if !self.consume_token(&Token::LParen) {
+            return Ok(vec![]);
+        }
+        let columns = self
+            .parser
+            .parse_comma_separated(Parser::parse_identifier)?;
+        self.parser.expect_token(&Token::RParen)?;
+        Ok(columns)
+    }
+
+    /// Parse a SQL CREATE POLICY statement
+    ///
+    /// CREATE POLICY name ON table TO role USING (predicate)
+    fn parse_create_policy(&mut self) -> Result<ExtStatement> {
+        let name = self.parser.parse_identifier()?;
+        self.parser.expect_keyword(Keyword::ON)?;
+        let table = self.parser.parse_object_name()?;
+        self.parser.expect_keyword(Keyword::TO)?;
+        let role = self.parser.parse_identifier()?;
+        self.parser.expect_keyword(Keyword::USING)?;
+        self.parser.expect_token(&Token::LParen)?;
+        let predicate = self.parser.parse_expr()?;
+        self.parser.expect_token(&Token::RParen)?;
+
+        Ok(ExtStatement::CreatePolicy(CreatePolicy {
+            name,
+            table,
+            role,
+            predicate,
+        }))
+    }
+
+    /// Parse `[FOR USER user]` of the token statements
+    fn parse_token_user(&mut self) -> Result<Option<Ident>> {
+        if !self.parser.parse_keyword(Keyword::FOR) {
+            return Ok(None);
+        }
+        if !self.parse_cnos_keyword(CnosKeyWord::USER) {
+            return self.expected("USER after FOR", self.parser.peek_token());
+        }
+        Ok(Some(self.parser.parse_identifier()?))
+    }
+
     /// Parse a SQL CREATE statement
     fn parse_create(&mut self) -> Result<ExtStatement> {
         // Currently only supports the creation of external tables
@@ -550,6 +1250,27 @@ impl<'a> ExtParser<'a> {
             self.parse_create_table()
         } else if self.parser.parse_keyword(Keyword::DATABASE) {
             self.parse_create_database()
+        } else if self.parse_cnos_keyword(CnosKeyWord::STREAM) {
+            self.parse_create_stream_source()
+        } else if self.parse_cnos_keyword(CnosKeyWord::USER) {
+            self.parse_create_user()
+        } else if self.parse_cnos_keyword(CnosKeyWord::TOKEN) {
+            let name = self.parser.parse_identifier()?;
+            let user = self.parse_token_user()?;
+            Ok(ExtStatement::CreateToken(CreateToken { name, user }))
+        } else if self.parse_cnos_keyword(CnosKeyWord::ROLE) {
+            let if_not_exists =
+                self.parser
+                    .parse_keywords(&[Keyword::IF, Keyword::NOT, Keyword::EXISTS]);
+            let name = self.parser.parse_identifier()?;
+            Ok(ExtStatement::CreateRole(CreateRole {
+                name,
+                if_not_exists,
+            }))
+        } else if self.parse_cnos_keyword(CnosKeyWord::POLICY) {
+            self.parse_create_policy()
+        } else if self.parse_cnos_keyword(CnosKeyWord::TASK) {
+            self.parse_create_task()
         } else {
             self.expected("an object type after CREATE", self.parser.peek_token())
         }
@@ -557,12 +1278,56 @@ impl<'a> ExtParser<'a> {
 
     /// Parse a SQL DROP statement
     fn parse_drop(&mut self) -> Result<ExtStatement> {
-        let obj_type = if self.parser.parse_keyword(Keyword::TABLE) {
+        if self.parse_cnos_keyword(CnosKeyWord::USER) {
+            let if_exists = self.parser.parse_keywords(&[Keyword::IF, Keyword::EXISTS]);
+            let name = self.parser.parse_identifier()?;
+            return Ok(ExtStatement::DropUser(DropUser { name, if_exists }));
+        }
+        if self.parse_cnos_keyword(CnosKeyWord::ROLE) {
+            let if_exists = self.parser.parse_keywords(&[Keyword::IF, Keyword::EXISTS]);
+            let name = self.parser.parse_identifier()?;
+            return Ok(ExtStatement::DropRole(DropRole { name, if_exists }));
+        }
+        if self.parse_cnos_keyword(CnosKeyWord::POLICY) {
+            let if_exists = self.parser.parse_keywords(&[Keyword::IF, Keyword::EXISTS]);
+            let name = self.parser.parse_identifier()?;
+            self.parser.expect_keyword(Keyword::ON)?;
+            let table = self.parser.parse_object_name()?;
+            return Ok(ExtStatement::DropPolicy(DropPolicy {
+                name,
+                table,
+                if_exists,
+            }));
+        }
+        if self.parse_cnos_keyword(CnosKeyWord::TASK) {
+            let if_exists = self.parser.parse_keywords(&[Keyword::IF, Keyword::EXISTS]);
+            let name = self.parser.parse_identifier()?;
+            return Ok(ExtStatement::DropTask(DropTask { name, if_exists }));
+        }
+        if self.parse_cnos_keyword(CnosKeyWord::TOKEN) {
+            let if_exists = self.parser.parse_keywords(&[Keyword::IF, Keyword::EXISTS]);
+            let name = self.parser.parse_identifier()?;
+            let user = self.parse_token_user()?;
+            return Ok(ExtStatement::DropToken(DropToken {
+                name,
+                user,
+                if_exists,
+            }));
+        }
+        let obj_type = if self.parser.parse_keyword(Keyword::TABLE) {
             ObjectType::Table
         } else if self.parser.parse_keyword(Keyword::DATABASE) {
             ObjectType::Database
+        } else if self.parse_cnos_keyword(CnosKeyWord::STREAM) {
+            if !self.parse_cnos_keyword(CnosKeyWord::SOURCE) {
+                return self.expected("SOURCE after DROP STREAM", self.parser.peek_token());
+            }
+            ObjectType::StreamSource
         } else {
-            return self.expected("TABLE,DATABASE after DROP", self.parser.peek_token());
+            return self.expected(
+                "TABLE,DATABASE,STREAM SOURCE,USER,TOKEN,ROLE,POLICY,TASK after DROP",
+                self.parser.peek_token(),
+            );
         };
         let if_exist = self.parser.parse_keywords(&[Keyword::IF, Keyword::EXISTS]);
         let object_name = self.parser.parse_object_name()?;
@@ -670,14 +1435,19 @@ impl<'a> ExtParser<'a> {
         match token {
             Token::Word(w) => match w.keyword {
                 Keyword::TIMESTAMP => parser_err!(format!("already have timestamp column")),
-                Keyword::BIGINT => {
+                // The integers are stored in 64 bits whatever the width declared
+                Keyword::BIGINT
+                | Keyword::INT
+                | Keyword::INTEGER
+                | Keyword::SMALLINT
+                | Keyword::TINYINT => {
                     if self.parser.parse_keyword(Keyword::UNSIGNED) {
                         Ok(DataType::UnsignedBigInt(None))
                     } else {
                         Ok(DataType::BigInt(None))
                     }
                 }
-                Keyword::DOUBLE => Ok(DataType::Double),
+                Keyword::DOUBLE | Keyword::FLOAT | Keyword::REAL => Ok(DataType::Double),
                 Keyword::STRING => Ok(DataType::String),
                 Keyword::BOOLEAN => Ok(DataType::Boolean),
                 _ => parser_err!(format!("{} is not a supported type", w)),
@@ -724,6 +1494,46 @@ fn parse_file_type(s: &str) -> Result<String, ParserError> {
     Ok(s.to_uppercase())
 }
 
+/// Drops the `OFFSET` of `time(<interval>, OFFSET <offset>)` of `GROUP BY`, which is
+/// not an expression of sql, the offset is the second argument of `time()`
+fn strip_time_window_offset(tokens: Vec<Token>) -> Vec<Token> {
+    fn last_token(tokens: &[Token]) -> Option<&Token> {
+        tokens
+            .iter()
+            .rev()
+            .find(|e| !matches!(e, Token::Whitespace(_)))
+    }
+
+    let mut result = Vec::with_capacity(tokens.len());
+    // The depth of the parentheses in `time(`, 0 outside of it
+    let mut depth = 0;
+    for token in tokens {
+        if depth > 0 {
+            match &token {
+                Token::LParen => depth += 1,
+                Token::RParen => depth -= 1,
+                Token::Word(w)
+                    if depth == 1
+                        && w.keyword == Keyword::OFFSET
+                        && last_token(&result) == Some(&Token::Comma) =>
+                {
+                    continue
+                }
+                _ => {}
+            }
+        } else if token == Token::LParen {
+            if let Some(Token::Word(w)) = last_token(&result) {
+                if w.quote_style.is_none() && w.value.eq_ignore_ascii_case("time") {
+                    depth = 1;
+                }
+            }
+        }
+        result.push(token);
+    }
+
+    result
+}
+
 /// Normalize a SQL object name
 pub fn normalize_sql_object_name(sql_object_name: &ObjectName) -> String {
     sql_object_name
@@ -891,6 +1701,35 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_create_table_numeric_aliases() {
+        let sql = "CREATE TABLE test\
+            (a INT, b INTEGER UNSIGNED, c SMALLINT, d TINYINT UNSIGNED, e FLOAT, f REAL,\
+            TAGS(g))";
+        let statements = ExtParser::parse_sql(sql).unwrap();
+        match &statements[0] {
+            ExtStatement::CreateTable(CreateTable { columns, .. }) => {
+                let types = columns
+                    .iter()
+                    .filter(|e| !e.is_tag)
+                    .map(|e| e.data_type.clone())
+                    .collect::<Vec<_>>();
+                assert_eq!(
+                    types,
+                    vec![
+                        DataType::BigInt(None),
+                        DataType::UnsignedBigInt(None),
+                        DataType::BigInt(None),
+                        DataType::UnsignedBigInt(None),
+                        DataType::Double,
+                        DataType::Double,
+                    ]
+                );
+            }
+            _ => panic!("failed"),
+        }
+    }
+
     #[test]
     fn test_insert_values() {
         let sql = "insert public.test(TIME, ta, tb, fa, fb)
@@ -1018,19 +1857,53 @@ mod tests {
 
     #[test]
     fn test_create_database() {
-        let sql = "CREATE DATABASE test WITH TTl '10d' SHARD 5 VNOdE_DURATiON '3d' REPLICA 10 pRECISIOn 'us';";
+        let sql = "CREATE DATABASE test WITH TTl '10d' SHARD 5 VNOdE_DURATiON '3d' REPLICA 10 pRECISIOn 'us' AUTO_CREATE_SCHEMA false FIELD_TYPE_CONFLICT 'coerce' FUTURE_TOLERANCE '1h' OUT_OF_WINDOW 'clamp';";
         let statements = ExtParser::parse_sql(sql).unwrap();
         assert_eq!(statements.len(), 1);
         match statements[0] {
             ExtStatement::CreateDatabase(ref stmt) => {
                 let ans = format!("{:?}", stmt);
                 println!("{ans}");
-                let expectd = r#"CreateDatabase { name: ObjectName([Ident { value: "test", quote_style: None }]), if_not_exists: false, options: DatabaseOptions { ttl: Some("10d"), shard_num: Some(5), vnode_duration: Some("3d"), replica: Some(10), precision: Some("us") } }"#;
+                let expectd = r#"CreateDatabase { name: ObjectName([Ident { value: "test", quote_style: None }]), if_not_exists: false, options: DatabaseOptions { ttl: Some("10d"), shard_num: Some(5), vnode_duration: Some("3d"), replica: Some(10), precision: Some("us"), auto_create_schema: Some(false), field_type_conflict: Some("coerce"), future_tolerance: Some("1h"), past_tolerance: None, out_of_window: Some("clamp") } }"#;
                 assert_eq!(ans, expectd);
             }
             _ => panic!("impossible"),
         }
     }
+    #[test]
+    fn test_create_database_options_with_commas() {
+        let sql = "CREATE DATABASE test WITH TTL '30d', SHARD 4, SHARD DURATION '7d', REPLICA 3, PRECISION 'ms'";
+        let statements = ExtParser::parse_sql(sql).unwrap();
+        match &statements[0] {
+            ExtStatement::CreateDatabase(stmt) => {
+                assert_eq!(stmt.options.ttl.as_deref(), Some("30d"));
+                assert_eq!(stmt.options.shard_num, Some(4));
+                assert_eq!(stmt.options.vnode_duration.as_deref(), Some("7d"));
+                assert_eq!(stmt.options.replica, Some(3));
+                assert_eq!(stmt.options.precision.as_deref(), Some("ms"));
+            }
+            _ => panic!("impossible"),
+        }
+    }
+
+    #[test]
+    fn test_alter_database() {
+        let sql = "ALTER DATABASE test SET TTL '90d', SHARD DURATION '1d'";
+        let statements = ExtParser::parse_sql(sql).unwrap();
+        match &statements[0] {
+            ExtStatement::AlterDatabase(stmt) => {
+                assert_eq!(stmt.name, ObjectName(vec![Ident::from("test")]));
+                assert_eq!(stmt.options.ttl.as_deref(), Some("90d"));
+                assert_eq!(stmt.options.vnode_duration.as_deref(), Some("1d"));
+                assert_eq!(stmt.options.shard_num, None);
+            }
+            _ => panic!("impossible"),
+        }
+
+        assert!(ExtParser::parse_sql("ALTER DATABASE test SET TTL '90d',").is_err());
+        assert!(ExtParser::parse_sql("ALTER DATABASE test SET").is_err());
+    }
+
     #[test]
     #[should_panic]
     fn test_create_table_without_fields() {
@@ -1038,6 +1911,347 @@ mod tests {
         ExtParser::parse_sql(sql).unwrap();
     }
 
+    #[test]
+    fn test_copy_to() {
+        let sql = r#"
+            COPY (SELECT * FROM air WHERE time > 0) TO '/tmp/air' FORMAT parquet;
+            COPY public.air TO 's3://bucket/air';
+        "#;
+        let statements = ExtParser::parse_sql(sql).unwrap();
+        assert_eq!(statements.len(), 2);
+        match &statements[0] {
+            ExtStatement::Copy(CopyTo {
+                source: CopySource::Query(query),
+                location,
+                file_type,
+            }) => {
+                assert_eq!(query.to_string(), "SELECT * FROM air WHERE time > 0");
+                assert_eq!(location, "/tmp/air");
+                assert_eq!(file_type, "PARQUET");
+            }
+            _ => panic!("failed"),
+        }
+        match &statements[1] {
+            ExtStatement::Copy(CopyTo {
+                source: CopySource::Table(table_name),
+                location,
+                file_type,
+            }) => {
+                assert_eq!(table_name.to_string(), "public.air");
+                assert_eq!(location, "s3://bucket/air");
+                assert_eq!(file_type, "CSV");
+            }
+            _ => panic!("failed"),
+        }
+    }
+
+    #[test]
+    fn test_stream_source() {
+        let sql = r#"
+            CREATE STREAM SOURCE IF NOT EXISTS metrics FROM kafka
+                WITH (brokers = 'localhost:9092', topic = 'metrics', format = 'json');
+            SHOW STREAM SOURCES;
+            DROP STREAM SOURCE IF EXISTS metrics;
+        "#;
+        let statements = ExtParser::parse_sql(sql).unwrap();
+        assert_eq!(statements.len(), 3);
+        match &statements[0] {
+            ExtStatement::CreateStreamSource(CreateStreamSource {
+                name,
+                if_not_exists,
+                connector,
+                options,
+            }) => {
+                assert_eq!(name.to_string(), "metrics");
+                assert!(*if_not_exists);
+                assert_eq!(connector.value, "kafka");
+                assert_eq!(
+                    options
+                        .iter()
+                        .map(|e| e.name.value.as_str())
+                        .collect::<Vec<_>>(),
+                    vec!["brokers", "topic", "format"]
+                );
+            }
+            _ => panic!("failed"),
+        }
+        assert_eq!(statements[1], ExtStatement::ShowStreamSources);
+        match &statements[2] {
+            ExtStatement::Drop(DropObject {
+                object_name,
+                if_exist,
+                obj_type,
+            }) => {
+                assert_eq!(object_name.to_string(), "metrics");
+                assert!(*if_exist);
+                assert_eq!(*obj_type, ObjectType::StreamSource);
+            }
+            _ => panic!("failed"),
+        }
+
+        assert!(ExtParser::parse_sql("CREATE STREAM metrics FROM kafka").is_err());
+    }
+
+    #[test]
+    fn test_user() {
+        let sql = r#"
+            CREATE USER IF NOT EXISTS u1 WITH (password = 'secret', is_admin = true);
+            ALTER USER u1 WITH (is_admin = false);
+            SHOW USERS;
+            DROP USER IF EXISTS u1;
+        "#;
+        let statements = ExtParser::parse_sql(sql).unwrap();
+        assert_eq!(statements.len(), 4);
+        match &statements[0] {
+            ExtStatement::CreateUser(CreateUser {
+                name,
+                if_not_exists,
+                options,
+            }) => {
+                assert_eq!(name.value, "u1");
+                assert!(*if_not_exists);
+                assert_eq!(
+                    options
+                        .iter()
+                        .map(|e| e.name.value.as_str())
+                        .collect::<Vec<_>>(),
+                    vec!["password", "is_admin"]
+                );
+            }
+            _ => panic!("failed"),
+        }
+        match &statements[1] {
+            ExtStatement::AlterUser(AlterUser { name, options }) => {
+                assert_eq!(name.value, "u1");
+                assert_eq!(options.len(), 1);
+            }
+            _ => panic!("failed"),
+        }
+        assert_eq!(statements[2], ExtStatement::ShowUsers);
+        assert_eq!(
+            statements[3],
+            ExtStatement::DropUser(DropUser {
+                name: Ident::new("u1"),
+                if_exists: true,
+            })
+        );
+
+        assert!(ExtParser::parse_sql("ALTER USER u1").is_err());
+    }
+
+    #[test]
+    fn test_token() {
+        let sql = r#"
+            CREATE TOKEN agent;
+            CREATE TOKEN agent FOR USER u1;
+            SHOW TOKENS;
+            DROP TOKEN IF EXISTS agent FOR USER u1;
+        "#;
+        let statements = ExtParser::parse_sql(sql).unwrap();
+        assert_eq!(statements.len(), 4);
+        assert_eq!(
+            statements[0],
+            ExtStatement::CreateToken(CreateToken {
+                name: Ident::new("agent"),
+                user: None,
+            })
+        );
+        assert_eq!(
+            statements[1],
+            ExtStatement::CreateToken(CreateToken {
+                name: Ident::new("agent"),
+                user: Some(Ident::new("u1")),
+            })
+        );
+        assert_eq!(statements[2], ExtStatement::ShowTokens);
+        assert_eq!(
+            statements[3],
+            ExtStatement::DropToken(DropToken {
+                name: Ident::new("agent"),
+                user: Some(Ident::new("u1")),
+                if_exists: true,
+            })
+        );
+
+        assert!(ExtParser::parse_sql("CREATE TOKEN agent FOR u1").is_err());
+    }
+
+    #[test]
+    fn test_role_and_policy() {
+        let sql = r#"
+            CREATE ROLE IF NOT EXISTS eu;
+            GRANT ROLE eu TO u1;
+            CREATE POLICY eu_only ON db1.cpu TO eu USING (region = 'eu' AND host <> 'h1');
+            SHOW POLICIES;
+            DROP POLICY IF EXISTS eu_only ON db1.cpu;
+            REVOKE ROLE eu FROM u1;
+            DROP ROLE eu;
+            SHOW ROLES;
+        "#;
+        let statements = ExtParser::parse_sql(sql).unwrap();
+        assert_eq!(statements.len(), 8);
+        assert_eq!(
+            statements[0],
+            ExtStatement::CreateRole(CreateRole {
+                name: Ident::new("eu"),
+                if_not_exists: true,
+            })
+        );
+        assert_eq!(
+            statements[1],
+            ExtStatement::GrantRole(GrantRole {
+                role: Ident::new("eu"),
+                user: Ident::new("u1"),
+            })
+        );
+        match &statements[2] {
+            ExtStatement::CreatePolicy(CreatePolicy {
+                name,
+                table,
+                role,
+                predicate,
+            }) => {
+                assert_eq!(name.value, "eu_only");
+                assert_eq!(table.to_string(), "db1.cpu");
+                assert_eq!(role.value, "eu");
+                assert_eq!(predicate.to_string(), "region = 'eu' AND host <> 'h1'");
+            }
+            _ => panic!("failed"),
+        }
+        assert_eq!(statements[3], ExtStatement::ShowPolicies);
+        match &statements[4] {
+            ExtStatement::DropPolicy(DropPolicy {
+                name, if_exists, ..
+            }) => {
+                assert_eq!(name.value, "eu_only");
+                assert!(*if_exists);
+            }
+            _ => panic!("failed"),
+        }
+        assert_eq!(
+            statements[5],
+            ExtStatement::RevokeRole(RevokeRole {
+                role: Ident::new("eu"),
+                user: Ident::new("u1"),
+            })
+        );
+        assert_eq!(
+            statements[6],
+            ExtStatement::DropRole(DropRole {
+                name: Ident::new("eu"),
+                if_exists: false,
+            })
+        );
+        assert_eq!(statements[7], ExtStatement::ShowRoles);
+
+        assert!(ExtParser::parse_sql("GRANT ALL ON cpu TO u1").is_err());
+        assert!(ExtParser::parse_sql("CREATE POLICY p ON cpu TO eu USING region = 'eu'").is_err());
+    }
+
+    #[test]
+    fn test_column_privileges() {
+        let sql = r#"
+            GRANT SELECT (time, region, usage) ON db1.cpu TO eu;
+            REVOKE SELECT (usage) ON cpu FROM eu;
+            REVOKE SELECT ON cpu FROM eu;
+        "#;
+        let statements = ExtParser::parse_sql(sql).unwrap();
+        assert_eq!(statements.len(), 3);
+        match &statements[0] {
+            ExtStatement::GrantSelect(GrantSelect {
+                columns,
+                table,
+                role,
+            }) => {
+                assert_eq!(
+                    columns,
+                    &vec![
+                        Ident::new("time"),
+                        Ident::new("region"),
+                        Ident::new("usage")
+                    ]
+                );
+                assert_eq!(table.to_string(), "db1.cpu");
+                assert_eq!(role.value, "eu");
+            }
+            _ => panic!("failed"),
+        }
+        match &statements[1] {
+            ExtStatement::RevokeSelect(RevokeSelect { columns, role, .. }) => {
+                assert_eq!(columns, &vec![Ident::new("usage")]);
+                assert_eq!(role.value, "eu");
+            }
+            _ => panic!("failed"),
+        }
+        match &statements[2] {
+            ExtStatement::RevokeSelect(RevokeSelect { columns, .. }) => {
+                assert!(columns.is_empty());
+            }
+            _ => panic!("failed"),
+        }
+
+        assert!(ExtParser::parse_sql("GRANT SELECT ON cpu TO eu").is_err());
+        assert!(ExtParser::parse_sql("GRANT ROLE eu TO GROUP eu_team").is_err());
+
+        let sql = r#"
+            GRANT ROLE eu TO GROUP 'cn=eu,ou=groups,dc=example,dc=com';
+            REVOKE ROLE eu FROM GROUP 'cn=eu,ou=groups,dc=example,dc=com';
+        "#;
+        let statements = ExtParser::parse_sql(sql).unwrap();
+        assert_eq!(
+            statements[0],
+            ExtStatement::GrantRoleToGroup(GrantRoleToGroup {
+                role: Ident::new("eu"),
+                group: "cn=eu,ou=groups,dc=example,dc=com".to_string(),
+            })
+        );
+        assert_eq!(
+            statements[1],
+            ExtStatement::RevokeRoleFromGroup(RevokeRoleFromGroup {
+                role: Ident::new("eu"),
+                group: "cn=eu,ou=groups,dc=example,dc=com".to_string(),
+            })
+        );
+        assert!(ExtParser::parse_sql("GRANT SELECT (usage) ON cpu FROM eu").is_err());
+    }
+
+    #[test]
+    fn test_export_import_database() {
+        let sql = r#"
+            EXPORT DATABASE db1 TO '/tmp/dump' WITH (start_time = '2022-01-01T00:00:00Z', compression = 'none');
+            IMPORT DATABASE db2 FROM '/tmp/dump';
+        "#;
+        let statements = ExtParser::parse_sql(sql).unwrap();
+        assert_eq!(statements.len(), 2);
+        match &statements[0] {
+            ExtStatement::ExportDatabase(ExportDatabase {
+                database,
+                location,
+                options,
+            }) => {
+                assert_eq!(database.value, "db1");
+                assert_eq!(location, "/tmp/dump");
+                assert_eq!(options.len(), 2);
+            }
+            _ => panic!("failed"),
+        }
+        match &statements[1] {
+            ExtStatement::ImportDatabase(ImportDatabase {
+                database,
+                location,
+                options,
+            }) => {
+                assert_eq!(database.value, "db2");
+                assert_eq!(location, "/tmp/dump");
+                assert!(options.is_empty());
+            }
+            _ => panic!("failed"),
+        }
+
+        assert!(ExtParser::parse_sql("EXPORT db1 TO '/tmp/dump'").is_err());
+        assert!(ExtParser::parse_sql("IMPORT DATABASE db1 TO '/tmp/dump'").is_err());
+    }
+
     #[test]
     fn test_alter_table() {
         let sql = r#"
@@ -1110,4 +2324,285 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_alter_table_convert_column() {
+        let sql = r#"
+            ALTER TABLE m ALTER f SET TAG;
+            ALTER TABLE m ALTER t SET FIELD DOUBLE CODEC(GORILLA);
+            ALTER TABLE m ALTER t SET FIELD STRING;
+        "#;
+        let actions: Vec<AlterTableAction> = ExtParser::parse_sql(sql)
+            .unwrap()
+            .into_iter()
+            .map(|s| match s {
+                ExtStatement::AlterTable(s) => s.alter_action,
+                _ => panic!("Expect AlterTable"),
+            })
+            .collect();
+        assert_eq!(
+            actions,
+            vec![
+                AlterTableAction::ConvertColumn {
+                    column: ColumnOption::new_tag(Ident::from("f")),
+                },
+                AlterTableAction::ConvertColumn {
+                    column: ColumnOption::new_field(
+                        Ident::from("t"),
+                        DataType::Double,
+                        Some(Encoding::Gorilla)
+                    ),
+                },
+                AlterTableAction::ConvertColumn {
+                    column: ColumnOption::new_field(Ident::from("t"), DataType::String, None),
+                },
+            ]
+        );
+
+        assert!(ExtParser::parse_sql("ALTER TABLE m ALTER t SET FIELD").is_err());
+    }
+
+    #[test]
+    fn test_show_create_table() {
+        let statements = ExtParser::parse_sql("SHOW CREATE TABLE db.m").unwrap();
+        assert_eq!(
+            statements,
+            vec![ExtStatement::ShowCreateTable(ShowCreateTable {
+                table_name: ObjectName(vec![Ident::from("db"), Ident::from("m")]),
+            })]
+        );
+        assert!(ExtParser::parse_sql("SHOW CREATE m").is_err());
+    }
+
+    #[test]
+    fn test_analyze_table() {
+        let statements = ExtParser::parse_sql("ANALYZE TABLE db.m").unwrap();
+        assert_eq!(
+            statements,
+            vec![ExtStatement::AnalyzeTable(AnalyzeTable {
+                table_name: ObjectName(vec![Ident::from("db"), Ident::from("m")]),
+            })]
+        );
+        assert!(ExtParser::parse_sql("ANALYZE m").is_err());
+    }
+
+    #[test]
+    fn test_show_shards() {
+        let statements = ExtParser::parse_sql("SHOW SHARDS; SHOW SHARDS ON db1").unwrap();
+        assert_eq!(
+            statements,
+            vec![
+                ExtStatement::ShowShards(None),
+                ExtStatement::ShowShards(Some(ObjectName(vec![Ident::from("db1")]))),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_show_grants() {
+        let statements = ExtParser::parse_sql("SHOW GRANTS; SHOW GRANTS FOR bob").unwrap();
+        assert_eq!(
+            statements,
+            vec![
+                ExtStatement::ShowGrants(None),
+                ExtStatement::ShowGrants(Some(Ident::from("bob"))),
+            ]
+        );
+        assert!(ExtParser::parse_sql("SHOW GRANTS FOR").is_err());
+    }
+
+    #[test]
+    fn test_task() {
+        let sql = r#"
+            CREATE TASK IF NOT EXISTS downsample EVERY '1h' INTO air_hourly
+                AS SELECT date_bin(INTERVAL '1 hour', time) AS time, avg(pressure) AS pressure
+                FROM air GROUP BY 1;
+            CREATE TASK alert ON DATA IN air WEBHOOK 'http://localhost:8080/alert'
+                AS SELECT * FROM air WHERE pressure > 100;
+            ALTER TASK alert DISABLE;
+            SHOW TASKS;
+            DROP TASK IF EXISTS alert;
+        "#;
+        let statements = ExtParser::parse_sql(sql).unwrap();
+        assert_eq!(statements.len(), 5);
+        match &statements[0] {
+            ExtStatement::CreateTask(stmt) => {
+                assert_eq!(stmt.name, Ident::from("downsample"));
+                assert!(stmt.if_not_exists);
+                assert_eq!(stmt.every.as_deref(), Some("1h"));
+                assert_eq!(stmt.on_data, None);
+                assert_eq!(
+                    stmt.sink,
+                    TaskSink::Table(ObjectName(vec![Ident::from("air_hourly")]))
+                );
+            }
+            _ => panic!("expect CreateTask"),
+        }
+        match &statements[1] {
+            ExtStatement::CreateTask(stmt) => {
+                assert_eq!(stmt.every, None);
+                assert_eq!(stmt.on_data, Some(ObjectName(vec![Ident::from("air")])));
+                assert_eq!(
+                    stmt.sink,
+                    TaskSink::Webhook("http://localhost:8080/alert".to_string())
+                );
+                assert_eq!(
+                    stmt.query.to_string(),
+                    "SELECT * FROM air WHERE pressure > 100"
+                );
+            }
+            _ => panic!("expect CreateTask"),
+        }
+        assert_eq!(
+            statements[2],
+            ExtStatement::AlterTask(AlterTask {
+                name: Ident::from("alert"),
+                enabled: false,
+            })
+        );
+        assert_eq!(statements[3], ExtStatement::ShowTasks);
+        assert_eq!(
+            statements[4],
+            ExtStatement::DropTask(DropTask {
+                name: Ident::from("alert"),
+                if_exists: true,
+            })
+        );
+
+        assert!(ExtParser::parse_sql("CREATE TASK t0 INTO air AS SELECT 1").is_err());
+        assert!(ExtParser::parse_sql("CREATE TASK t0 EVERY '1m' AS SELECT 1").is_err());
+        assert!(ExtParser::parse_sql("CREATE TASK t0 EVERY '1m' INTO t AS DROP TABLE t").is_err());
+    }
+
+    #[test]
+    fn test_show_stats_and_diagnostics() {
+        let statements =
+            ExtParser::parse_sql("SHOW STATS; SHOW STATS FOR 'TSKV'; SHOW DIAGNOSTICS").unwrap();
+        assert_eq!(
+            statements,
+            vec![
+                ExtStatement::ShowStats(None),
+                ExtStatement::ShowStats(Some("tskv".to_string())),
+                ExtStatement::ShowDiagnostics,
+            ]
+        );
+        assert!(ExtParser::parse_sql("SHOW STATS FOR tskv").is_err());
+    }
+
+    #[test]
+    fn test_sessions() {
+        let statements = ExtParser::parse_sql("SHOW SESSIONS; KILL SESSION 3; KILL 3").unwrap();
+        assert_eq!(statements[0], ExtStatement::ShowSessions);
+        assert_eq!(statements[1], ExtStatement::KillSession(3));
+        assert!(matches!(statements[2], ExtStatement::SqlStatement(_)));
+        assert!(ExtParser::parse_sql("KILL SESSION s1").is_err());
+    }
+
+    #[test]
+    fn test_flush_database() {
+        let statements = ExtParser::parse_sql("FLUSH DATABASE db1").unwrap();
+        assert_eq!(
+            statements,
+            vec![ExtStatement::FlushDatabase(FlushDatabase {
+                database_name: ObjectName(vec![Ident::from("db1")]),
+            })]
+        );
+        assert!(ExtParser::parse_sql("FLUSH db1").is_err());
+    }
+
+    #[test]
+    fn test_delete_before() {
+        let sql = "DELETE FROM db1 BEFORE '2022-01-01T00:00:00Z'; DELETE FROM db1 BEFORE 1000";
+        let statements = ExtParser::parse_sql(sql).unwrap();
+        assert_eq!(
+            statements[0],
+            ExtStatement::DeleteBefore(DeleteBefore {
+                database_name: ObjectName(vec![Ident::from("db1")]),
+                before: "2022-01-01T00:00:00Z".to_string(),
+            })
+        );
+        match &statements[1] {
+            ExtStatement::DeleteBefore(stmt) => assert_eq!(stmt.before, "1000"),
+            _ => panic!("expected delete before"),
+        }
+
+        // The DELETE of the rows of a table is not taken
+        let statements = ExtParser::parse_sql("DELETE FROM air WHERE time < 10").unwrap();
+        assert!(matches!(statements[0], ExtStatement::SqlStatement(_)));
+        assert!(ExtParser::parse_sql("DELETE FROM db1 BEFORE").is_err());
+    }
+
+    #[test]
+    fn test_group_by_time_offset() {
+        let with_offset = ExtParser::parse_sql(
+            "SELECT time, avg(f0) FROM m GROUP BY time('1d', OFFSET '8h') LIMIT 10 OFFSET 1",
+        )
+        .unwrap();
+        let without_offset = ExtParser::parse_sql(
+            "SELECT time, avg(f0) FROM m GROUP BY time('1d', '8h') LIMIT 10 OFFSET 1",
+        )
+        .unwrap();
+        assert_eq!(with_offset, without_offset);
+    }
+
+    #[test]
+    fn test_explain_format() {
+        let explain = |sql: &str| match ExtParser::parse_sql(sql).unwrap().pop_front().unwrap() {
+            ExtStatement::SqlStatement(statement) => match *statement {
+                Statement::Explain {
+                    analyze,
+                    verbose,
+                    format,
+                    ..
+                } => (analyze, verbose, format),
+                _ => panic!("expect an explain statement"),
+            },
+            _ => panic!("expect a sql statement"),
+        };
+
+        assert_eq!(
+            explain("EXPLAIN (FORMAT json) SELECT * FROM m"),
+            (false, false, Some(AnalyzeFormat::JSON))
+        );
+        assert_eq!(
+            explain("EXPLAIN (VERBOSE, FORMAT dot) SELECT * FROM m"),
+            (false, true, Some(AnalyzeFormat::GRAPHVIZ))
+        );
+        assert_eq!(
+            explain("EXPLAIN FORMAT GRAPHVIZ SELECT * FROM m"),
+            (false, false, Some(AnalyzeFormat::GRAPHVIZ))
+        );
+        assert_eq!(
+            explain("EXPLAIN ANALYZE SELECT * FROM m"),
+            (true, false, None)
+        );
+        assert_eq!(explain("EXPLAIN (SELECT 1)"), (false, false, None));
+
+        assert!(ExtParser::parse_sql("EXPLAIN (FORMAT xml) SELECT * FROM m").is_err());
+    }
+
+    #[test]
+    fn test_system_settings() {
+        let sql = r#"
+            SHOW SETTINGS;
+            ALTER SYSTEM SET cache.max_buffer_size = 134217728;
+            ALTER SYSTEM SET log.level TO 'info,tskv=debug';
+        "#;
+        let statements = ExtParser::parse_sql(sql).unwrap();
+        assert_eq!(
+            statements,
+            vec![
+                ExtStatement::ShowSettings,
+                ExtStatement::AlterSystemSet(AlterSystemSet {
+                    name: "cache.max_buffer_size".to_string(),
+                    value: "134217728".to_string(),
+                }),
+                ExtStatement::AlterSystemSet(AlterSystemSet {
+                    name: "log.level".to_string(),
+                    value: "info,tskv=debug".to_string(),
+                }),
+            ]
+        );
+        assert!(ExtParser::parse_sql("ALTER SYSTEM SET log.level").is_err());
+    }
 }
@@ -17,8 +17,10 @@ use snafu::ResultExt;
 use spi::query::{physical_planner::PhysicalPlanner, Result};
 use spi::query::{session::IsiphoSessionCtx, PhysicalPlanerSnafu};
 
+use crate::extension::physical::optimizer_rule::runtime_filter::PushDownRuntimeFilter;
 use crate::extension::physical::transform_rule::{
-    table_writer::TableWriterPlanner, tag_scan::TagScanPlanner, topk::TopKPlanner,
+    copy_to::CopyToPlanner, explain_format::ExplainFormatPlanner, table_writer::TableWriterPlanner,
+    tag_scan::TagScanPlanner, topk::TopKPlanner,
 };
 
 use super::optimizer::PhysicalOptimizer;
@@ -57,6 +59,8 @@ impl Default for DefaultPhysicalPlanner {
             Arc::new(TableWriterPlanner {}),
             Arc::new(TopKPlanner {}),
             Arc::new(TagScanPlanner {}),
+            Arc::new(CopyToPlanner {}),
+            Arc::new(ExplainFormatPlanner {}),
         ];
 
         let ext_physical_optimizer_rules: Vec<Arc<dyn PhysicalOptimizerRule + Send + Sync>> = vec![
@@ -66,6 +70,7 @@ impl Default for DefaultPhysicalPlanner {
             Arc::new(CoalesceBatches::new(4 * 1024)),
             Arc::new(Repartition::new()),
             Arc::new(AddCoalescePartitionsExec::new()),
+            Arc::new(PushDownRuntimeFilter {}),
         ];
 
         Self {
@@ -1,8 +1,9 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::option::Option;
+use std::str::FromStr;
 use std::sync::Arc;
 
-use datafusion::common::{DFField, ToDFSchema};
+use datafusion::common::{DFField, DFSchema, ToDFSchema};
 use datafusion::datasource::{source_as_provider, TableProvider};
 use datafusion::error::DataFusionError;
 use datafusion::logical_expr::logical_plan::Analyze;
@@ -15,39 +16,58 @@ use datafusion::scalar::ScalarValue;
 use datafusion::sql::parser::CreateExternalTable as AstCreateExternalTable;
 use datafusion::sql::planner::{ContextProvider, SqlToRel};
 use datafusion::sql::sqlparser::ast::{
-    DataType as SQLDataType, Ident, ObjectName, Query, Statement,
+    AnalyzeFormat, DataType as SQLDataType, Ident, ObjectName, Query, SqlOption, Statement, Value,
 };
 use datafusion::sql::TableReference;
 use models::schema::{ColumnType, TableColumn, TIME_FIELD_NAME};
+use models::stream_source::{ConnectorType, PayloadFormat};
+use models::task::{TaskSink, TaskTrigger};
 use models::utils::SeqIdGenerator;
 use models::{ColumnId, ValueType};
 use snafu::ResultExt;
 use spi::query::ast::{
     AlterDatabase as ASTAlterDatabase, AlterTable as ASTAlterTable,
-    AlterTableAction as ASTAlterTableAction, ColumnOption, CreateDatabase as ASTCreateDatabase,
-    CreateTable as ASTCreateTable, DatabaseOptions as ASTDatabaseOptions,
+    AlterTableAction as ASTAlterTableAction, AlterUser as ASTAlterUser, ColumnOption, CopySource,
+    CopyTo, CreateDatabase as ASTCreateDatabase, CreatePolicy as ASTCreatePolicy,
+    CreateStreamSource as ASTCreateStreamSource, CreateTable as ASTCreateTable,
+    CreateTask as ASTCreateTask, CreateUser as ASTCreateUser,
+    DatabaseOptions as ASTDatabaseOptions, DeleteBefore as ASTDeleteBefore,
     DescribeDatabase as DescribeDatabaseOptions, DescribeTable as DescribeTableOptions, DropObject,
-    ExtStatement,
+    ExportDatabase as ASTExportDatabase, ExtStatement, FlushDatabase as ASTFlushDatabase,
+    GrantSelect as ASTGrantSelect, ImportDatabase as ASTImportDatabase, TaskSink as ASTTaskSink,
 };
 use spi::query::logical_planner::{
     self, affected_row_expr, merge_affected_row_expr, AlterDatabase, AlterTable, AlterTableAction,
-    CreateDatabase, CreateTable, DDLPlan, DescribeDatabase, DescribeTable, DropPlan, ExternalSnafu,
-    LogicalPlanner, LogicalPlannerError, Plan, QueryPlan, SYSPlan, MISMATCHED_COLUMNS,
-    MISSING_COLUMN,
+    AlterTask, AlterUser, AnalyzeTable, CreateDatabase, CreatePolicy, CreateRole,
+    CreateStreamSource, CreateTable, CreateTask, CreateToken, CreateUser, DDLPlan, DeleteBefore,
+    DescribeDatabase, DescribeTable, DropPlan, DropPolicy, DropRole, DropTask, DropToken, DropUser,
+    DumpCompression, DumpFilter, ExportDatabase, ExternalSnafu, FlushDatabase, GrantRole,
+    GrantRoleToGroup, GrantSelect, ImportDatabase, LogicalPlanner, LogicalPlannerError, Plan,
+    QueryPlan, RevokeRole, RevokeRoleFromGroup, RevokeSelect, SYSPlan, ShowCreateTable,
+    MISMATCHED_COLUMNS, MISSING_COLUMN,
 };
-use spi::query::session::IsiphoSessionCtx;
+use spi::query::session::{time_zone, IsiphoSessionCtx};
 
-use models::schema::{DatabaseOptions, Duration, Precision};
+use models::schema::{DatabaseOptions, Duration, FieldTypeConflict, OutOfWindow, Precision};
 use spi::catalog::MetadataError;
 use spi::query::logical_planner::Result;
 use spi::query::UNEXPECTED_EXTERNAL_PLAN;
 use trace::debug;
 
+use crate::data_source::file_sink::FileFormat;
+use crate::extension::logical::plan_node::copy_to::CopyToPlanNode;
+use crate::extension::logical::plan_node::explain_format::{ExplainFormat, ExplainFormatPlanNode};
 use crate::extension::logical::plan_node::table_writer::TableWriterPlanNode;
+use crate::sql::logical::at_time_zone::rewrite_at_time_zone;
+use crate::sql::logical::row_policy::predicate_expr;
+use crate::sql::logical::time_window::rewrite_group_by_time;
 use crate::sql::parser::{normalize_ident, normalize_sql_object_name};
 use crate::table::ClusterTable;
 use spi::query::logical_planner::MetadataSnafu;
 
+/// Seconds between the checks of the table of a task triggered by data
+const DEFAULT_TASK_DATA_INTERVAL: u64 = 10;
+
 /// CnosDB SQL query planner
 #[derive(Debug)]
 pub struct SqlPlaner<S> {
@@ -60,6 +80,10 @@ impl<S: ContextProvider> SqlPlaner<S> {
         SqlPlaner { schema_provider }
     }
 
+    pub fn schema_provider(&self) -> &S {
+        &self.schema_provider
+    }
+
     /// Generate a logical plan from an  Extent SQL statement
     pub(crate) fn statement_to_plan(&self, statement: ExtStatement) -> Result<Plan> {
         match statement {
@@ -67,17 +91,128 @@ impl<S: ContextProvider> SqlPlaner<S> {
             ExtStatement::CreateExternalTable(stmt) => self.external_table_to_plan(stmt),
             ExtStatement::CreateTable(stmt) => self.create_table_to_plan(stmt),
             ExtStatement::CreateDatabase(stmt) => self.database_to_plan(stmt),
-            ExtStatement::CreateUser(_) => todo!(),
+            ExtStatement::CreateUser(stmt) => self.create_user_to_plan(stmt),
+            ExtStatement::CreateStreamSource(stmt) => self.stream_source_to_plan(stmt),
             ExtStatement::Drop(s) => self.drop_object_to_plan(s),
-            ExtStatement::DropUser(_) => todo!(),
+            ExtStatement::DropUser(stmt) => Ok(Plan::DDL(DDLPlan::DropUser(DropUser {
+                name: normalize_ident(&stmt.name),
+                if_exists: stmt.if_exists,
+            }))),
+            ExtStatement::AlterUser(stmt) => self.alter_user_to_plan(stmt),
+            ExtStatement::CreateToken(stmt) => Ok(Plan::DDL(DDLPlan::CreateToken(CreateToken {
+                name: normalize_ident(&stmt.name),
+                user: stmt.user.as_ref().map(normalize_ident),
+            }))),
+            ExtStatement::DropToken(stmt) => Ok(Plan::DDL(DDLPlan::DropToken(DropToken {
+                name: normalize_ident(&stmt.name),
+                user: stmt.user.as_ref().map(normalize_ident),
+                if_exists: stmt.if_exists,
+            }))),
+            ExtStatement::CreateRole(stmt) => Ok(Plan::DDL(DDLPlan::CreateRole(CreateRole {
+                name: normalize_ident(&stmt.name),
+                if_not_exists: stmt.if_not_exists,
+            }))),
+            ExtStatement::DropRole(stmt) => Ok(Plan::DDL(DDLPlan::DropRole(DropRole {
+                name: normalize_ident(&stmt.name),
+                if_exists: stmt.if_exists,
+            }))),
+            ExtStatement::GrantRole(stmt) => Ok(Plan::DDL(DDLPlan::GrantRole(GrantRole {
+                role: normalize_ident(&stmt.role),
+                user: normalize_ident(&stmt.user),
+            }))),
+            ExtStatement::RevokeRole(stmt) => Ok(Plan::DDL(DDLPlan::RevokeRole(RevokeRole {
+                role: normalize_ident(&stmt.role),
+                user: normalize_ident(&stmt.user),
+            }))),
+            ExtStatement::GrantRoleToGroup(stmt) => {
+                Ok(Plan::DDL(DDLPlan::GrantRoleToGroup(GrantRoleToGroup {
+                    role: normalize_ident(&stmt.role),
+                    group: stmt.group,
+                })))
+            }
+            ExtStatement::RevokeRoleFromGroup(stmt) => Ok(Plan::DDL(DDLPlan::RevokeRoleFromGroup(
+                RevokeRoleFromGroup {
+                    role: normalize_ident(&stmt.role),
+                    group: stmt.group,
+                },
+            ))),
+            ExtStatement::GrantSelect(stmt) => self.grant_select_to_plan(stmt),
+            ExtStatement::RevokeSelect(stmt) => {
+                Ok(Plan::DDL(DDLPlan::RevokeSelect(RevokeSelect {
+                    columns: stmt.columns.iter().map(normalize_ident).collect(),
+                    table: normalize_sql_object_name(&stmt.table),
+                    role: normalize_ident(&stmt.role),
+                })))
+            }
+            ExtStatement::CreatePolicy(stmt) => self.create_policy_to_plan(stmt),
+            ExtStatement::DropPolicy(stmt) => Ok(Plan::DDL(DDLPlan::DropPolicy(DropPolicy {
+                name: normalize_ident(&stmt.name),
+                table: normalize_sql_object_name(&stmt.table),
+                if_exists: stmt.if_exists,
+            }))),
+            ExtStatement::CreateTask(stmt) => self.create_task_to_plan(stmt),
+            ExtStatement::AlterTask(stmt) => Ok(Plan::DDL(DDLPlan::AlterTask(AlterTask {
+                name: normalize_ident(&stmt.name),
+                enabled: stmt.enabled,
+            }))),
+            ExtStatement::DropTask(stmt) => Ok(Plan::DDL(DDLPlan::DropTask(DropTask {
+                name: normalize_ident(&stmt.name),
+                if_exists: stmt.if_exists,
+            }))),
             ExtStatement::DescribeTable(stmt) => self.table_to_describe(stmt),
             ExtStatement::DescribeDatabase(stmt) => self.database_to_describe(stmt),
             ExtStatement::ShowDatabases() => self.database_to_show(),
             ExtStatement::ShowTables(stmt) => self.table_to_show(stmt),
+            ExtStatement::ShowShards(database) => Ok(Plan::DDL(DDLPlan::ShowShards(
+                database.map(|db_name| normalize_sql_object_name(&db_name)),
+            ))),
+            ExtStatement::ShowGrants(grantee) => Ok(Plan::DDL(DDLPlan::ShowGrants(
+                grantee.map(|name| normalize_ident(&name)),
+            ))),
+            ExtStatement::ShowCreateTable(stmt) => {
+                Ok(Plan::DDL(DDLPlan::ShowCreateTable(ShowCreateTable {
+                    table_name: normalize_sql_object_name(&stmt.table_name),
+                })))
+            }
+            ExtStatement::ShowStreamSources => Ok(Plan::DDL(DDLPlan::ShowStreamSources)),
+            ExtStatement::ShowUsers => Ok(Plan::DDL(DDLPlan::ShowUsers)),
+            ExtStatement::ShowTokens => Ok(Plan::DDL(DDLPlan::ShowTokens)),
+            ExtStatement::ShowRoles => Ok(Plan::DDL(DDLPlan::ShowRoles)),
+            ExtStatement::ShowPolicies => Ok(Plan::DDL(DDLPlan::ShowPolicies)),
+            ExtStatement::ShowTasks => Ok(Plan::DDL(DDLPlan::ShowTasks)),
             ExtStatement::AlterDatabase(stmt) => self.database_to_alter(stmt),
             ExtStatement::AlterTable(stmt) => self.table_to_alter(stmt),
+            ExtStatement::AnalyzeTable(stmt) => {
+                Ok(Plan::DDL(DDLPlan::AnalyzeTable(AnalyzeTable {
+                    table_name: normalize_sql_object_name(&stmt.table_name),
+                })))
+            }
+            ExtStatement::FlushDatabase(ASTFlushDatabase { database_name }) => {
+                Ok(Plan::DDL(DDLPlan::FlushDatabase(FlushDatabase {
+                    database_name: normalize_sql_object_name(&database_name),
+                })))
+            }
+            ExtStatement::DeleteBefore(ASTDeleteBefore {
+                database_name,
+                before,
+            }) => Ok(Plan::DDL(DDLPlan::DeleteBefore(DeleteBefore {
+                database_name: normalize_sql_object_name(&database_name),
+                before: parse_timestamp("BEFORE", before)?,
+            }))),
+            ExtStatement::Copy(stmt) => self.copy_to_plan(stmt),
+            ExtStatement::ExportDatabase(stmt) => self.export_database_to_plan(stmt),
+            ExtStatement::ImportDatabase(stmt) => self.import_database_to_plan(stmt),
             // system statement
             ExtStatement::ShowQueries => Ok(Plan::SYSTEM(SYSPlan::ShowQueries)),
+            ExtStatement::ShowSettings => Ok(Plan::SYSTEM(SYSPlan::ShowSettings)),
+            ExtStatement::ShowStats(module) => Ok(Plan::SYSTEM(SYSPlan::ShowStats(module))),
+            ExtStatement::ShowDiagnostics => Ok(Plan::SYSTEM(SYSPlan::ShowDiagnostics)),
+            ExtStatement::ShowSessions => Ok(Plan::SYSTEM(SYSPlan::ShowSessions)),
+            ExtStatement::KillSession(id) => Ok(Plan::SYSTEM(SYSPlan::KillSession(id))),
+            ExtStatement::AlterSystemSet(stmt) => Ok(Plan::SYSTEM(SYSPlan::AlterSystemSet {
+                name: stmt.name,
+                value: stmt.value,
+            })),
         }
     }
 
@@ -95,8 +230,8 @@ impl<S: ContextProvider> SqlPlaner<S> {
                 statement,
                 analyze,
                 describe_alias: _,
-                format: _,
-            } => self.explain_statement_to_plan(verbose, analyze, *statement),
+                format,
+            } => self.explain_statement_to_plan(verbose, analyze, format, *statement),
             Statement::Insert {
                 table_name: ref sql_object_name,
                 columns: ref sql_column_names,
@@ -112,10 +247,13 @@ impl<S: ContextProvider> SqlPlaner<S> {
 
     /// Generate a plan for EXPLAIN ... that will print out a plan
     ///
+    /// The plans in json or graphviz are the optimized logical plan and the physical plan,
+    /// regardless of verbose
     pub fn explain_statement_to_plan(
         &self,
         verbose: bool,
         analyze: bool,
+        format: Option<AnalyzeFormat>,
         statement: Statement,
     ) -> Result<Plan> {
         let plan = self.df_sql_to_plan(statement)?;
@@ -133,7 +271,24 @@ impl<S: ContextProvider> SqlPlaner<S> {
             .to_dfschema_ref()
             .context(ExternalSnafu)?;
 
-        let df_plan = if analyze {
+        let format = match format {
+            None | Some(AnalyzeFormat::TEXT) => None,
+            Some(_) if analyze => {
+                return Err(LogicalPlannerError::NotImplemented {
+                    err: "EXPLAIN ANALYZE in a format other than text".to_string(),
+                })
+            }
+            Some(AnalyzeFormat::JSON) => Some(ExplainFormat::Json),
+            Some(AnalyzeFormat::GRAPHVIZ) => Some(ExplainFormat::Graphviz),
+        };
+
+        let df_plan = if let Some(format) = format {
+            let node =
+                ExplainFormatPlanNode::try_new(format, input_df_plan).context(ExternalSnafu)?;
+            LogicalPlan::Extension(Extension {
+                node: Arc::new(node),
+            })
+        } else if analyze {
             LogicalPlan::Analyze(Analyze {
                 verbose,
                 input: input_df_plan,
@@ -256,6 +411,191 @@ impl<S: ContextProvider> SqlPlaner<S> {
         Ok(Plan::Query(QueryPlan { df_plan }))
     }
 
+    /// Generate a logical plan from a COPY ... TO statement
+    fn copy_to_plan(&self, stmt: CopyTo) -> Result<Plan> {
+        let CopyTo {
+            source,
+            location,
+            file_type,
+        } = stmt;
+
+        let source_plan = match source {
+            CopySource::Query(query) => SqlToRel::new(&self.schema_provider)
+                .query_to_plan(*query, &mut HashMap::new())
+                .context(ExternalSnafu)?,
+            CopySource::Table(table_name) => {
+                let table_name = normalize_sql_object_name(&table_name);
+                let table_source = self.get_table_source(&table_name)?;
+                LogicalPlanBuilder::scan(table_name, table_source, None)
+                    .and_then(|builder| builder.build())
+                    .context(ExternalSnafu)?
+            }
+        };
+
+        let file_format = FileFormat::from_str(&file_type)
+            .map_err(|err| LogicalPlannerError::Semantic { err })?;
+
+        let df_plan =
+            copy_to_plan_node(location, file_format, source_plan).context(ExternalSnafu)?;
+
+        debug!("Copy plan:\n{}", df_plan.display_indent_schema());
+
+        Ok(Plan::Query(QueryPlan { df_plan }))
+    }
+
+    /// Generate a logical plan from a CREATE STREAM SOURCE statement
+    fn stream_source_to_plan(&self, stmt: ASTCreateStreamSource) -> Result<Plan> {
+        let ASTCreateStreamSource {
+            name,
+            if_not_exists,
+            connector,
+            options,
+        } = stmt;
+
+        let connector = ConnectorType::from_str(&connector.value)
+            .map_err(|err| LogicalPlannerError::Semantic { err })?;
+
+        let mut plan_options = sql_options_to_map(options)?;
+
+        let format = match plan_options.remove("format") {
+            Some(format) => PayloadFormat::from_str(&format)
+                .map_err(|err| LogicalPlannerError::Semantic { err })?,
+            None => PayloadFormat::LineProtocol,
+        };
+        let database = plan_options.remove("database");
+        let table = plan_options.remove("table");
+
+        Ok(Plan::DDL(DDLPlan::CreateStreamSource(CreateStreamSource {
+            name: normalize_sql_object_name(&name),
+            if_not_exists,
+            connector,
+            format,
+            database,
+            table,
+            options: plan_options,
+        })))
+    }
+
+    /// Generate a logical plan from a CREATE TASK statement, a task triggered by data
+    /// checks the table every 10 seconds if no interval is given
+    fn create_task_to_plan(&self, stmt: ASTCreateTask) -> Result<Plan> {
+        let ASTCreateTask {
+            name,
+            if_not_exists,
+            every,
+            on_data,
+            sink,
+            query,
+        } = stmt;
+
+        let interval = match every {
+            Some(every) => task_interval(&every)?,
+            None => DEFAULT_TASK_DATA_INTERVAL,
+        };
+        let trigger = match on_data {
+            Some(table) => TaskTrigger::OnData {
+                table: table.to_string(),
+                interval,
+            },
+            None => TaskTrigger::Schedule { interval },
+        };
+        let sink = match sink {
+            ASTTaskSink::Table(table) => TaskSink::Table(table.to_string()),
+            ASTTaskSink::Webhook(url) => {
+                url::Url::parse(&url).map_err(|e| LogicalPlannerError::Semantic {
+                    err: format!("invalid webhook url {}: {}", url, e),
+                })?;
+                TaskSink::Webhook(url)
+            }
+        };
+
+        Ok(Plan::DDL(DDLPlan::CreateTask(CreateTask {
+            name: normalize_ident(&name),
+            if_not_exists,
+            query: query.to_string(),
+            trigger,
+            sink,
+        })))
+    }
+
+    /// Generate a logical plan from a CREATE USER statement, the password is required
+    fn create_user_to_plan(&self, stmt: ASTCreateUser) -> Result<Plan> {
+        let ASTCreateUser {
+            name,
+            if_not_exists,
+            options,
+        } = stmt;
+
+        let (password, is_admin) = user_options(options)?;
+        let password = password.ok_or_else(|| LogicalPlannerError::Semantic {
+            err: "Option password is required".to_string(),
+        })?;
+
+        Ok(Plan::DDL(DDLPlan::CreateUser(CreateUser {
+            name: normalize_ident(&name),
+            if_not_exists,
+            password,
+            is_admin: is_admin.unwrap_or(false),
+        })))
+    }
+
+    fn alter_user_to_plan(&self, stmt: ASTAlterUser) -> Result<Plan> {
+        let ASTAlterUser { name, options } = stmt;
+        let (password, is_admin) = user_options(options)?;
+
+        Ok(Plan::DDL(DDLPlan::AlterUser(AlterUser {
+            name: normalize_ident(&name),
+            password,
+            is_admin,
+        })))
+    }
+
+    fn export_database_to_plan(&self, stmt: ASTExportDatabase) -> Result<Plan> {
+        let ASTExportDatabase {
+            database,
+            location,
+            options,
+        } = stmt;
+
+        let mut options = sql_options_to_map(options)?;
+        let compression = match options.remove("compression") {
+            Some(compression) => DumpCompression::from_str(&compression)
+                .map_err(|err| LogicalPlannerError::Semantic { err })?,
+            None => DumpCompression::Gzip,
+        };
+        let filter = dump_filter(options)?;
+
+        Ok(Plan::DDL(DDLPlan::ExportDatabase(ExportDatabase {
+            database: normalize_ident(&database),
+            location,
+            compression,
+            filter,
+        })))
+    }
+
+    fn import_database_to_plan(&self, stmt: ASTImportDatabase) -> Result<Plan> {
+        let ASTImportDatabase {
+            database,
+            location,
+            options,
+        } = stmt;
+
+        let mut options = sql_options_to_map(options)?;
+        let table_mapping = options
+            .remove("table_mapping")
+            .map(|e| table_mapping(&e))
+            .transpose()?
+            .unwrap_or_default();
+        let filter = dump_filter(options)?;
+
+        Ok(Plan::DDL(DDLPlan::ImportDatabase(ImportDatabase {
+            database: normalize_ident(&database),
+            location,
+            filter,
+            table_mapping,
+        })))
+    }
+
     fn drop_object_to_plan(&self, stmt: DropObject) -> Result<Plan> {
         Ok(Plan::DDL(DDLPlan::Drop(DropPlan {
             if_exist: stmt.if_exist,
@@ -346,6 +686,63 @@ impl<S: ContextProvider> SqlPlaner<S> {
         })))
     }
 
+    /// The columns must be those of the table
+    fn grant_select_to_plan(&self, stmt: ASTGrantSelect) -> Result<Plan> {
+        let table_name = normalize_sql_object_name(&stmt.table);
+        let table_provider = self.get_table_provider(&table_name)?;
+        let table_schema = table_provider
+            .as_any()
+            .downcast_ref::<ClusterTable>()
+            .ok_or_else(|| MetadataError::TableIsNotTsKv {
+                table_name: table_name.to_string(),
+            })
+            .context(MetadataSnafu)?
+            .table_schema();
+
+        let columns = stmt.columns.iter().map(normalize_ident).collect::<Vec<_>>();
+        if let Some(column) = columns.iter().find(|e| !table_schema.contains_column(e)) {
+            return Err(LogicalPlannerError::Semantic {
+                err: format!("column {} not found in table {}", column, table_schema.name),
+            });
+        }
+
+        Ok(Plan::DDL(DDLPlan::GrantSelect(GrantSelect {
+            columns,
+            database: table_schema.db.clone(),
+            table: table_schema.name.clone(),
+            role: normalize_ident(&stmt.role),
+        })))
+    }
+
+    /// The predicate must be a valid filter on the columns of the table
+    fn create_policy_to_plan(&self, stmt: ASTCreatePolicy) -> Result<Plan> {
+        let table_name = normalize_sql_object_name(&stmt.table);
+        let table_provider = self.get_table_provider(&table_name)?;
+        let table_schema = table_provider
+            .as_any()
+            .downcast_ref::<ClusterTable>()
+            .ok_or_else(|| MetadataError::TableIsNotTsKv {
+                table_name: table_name.to_string(),
+            })
+            .context(MetadataSnafu)?
+            .table_schema();
+
+        let df_schema =
+            DFSchema::try_from_qualified_schema(&table_schema.name, &table_provider.schema())
+                .context(ExternalSnafu)?;
+        // Keep the sql text, which is planned again against the table when querying
+        let predicate = stmt.predicate.to_string();
+        predicate_expr(&predicate, &df_schema).context(ExternalSnafu)?;
+
+        Ok(Plan::DDL(DDLPlan::CreatePolicy(CreatePolicy {
+            name: normalize_ident(&stmt.name),
+            database: table_schema.db.clone(),
+            table: table_schema.name.clone(),
+            role: normalize_ident(&stmt.role),
+            predicate,
+        })))
+    }
+
     fn table_to_alter(&self, statement: ASTAlterTable) -> Result<Plan> {
         let table_name = normalize_sql_object_name(&statement.table_name);
         let table_provider = self.get_table_provider(&table_name)?;
@@ -427,6 +824,15 @@ impl<S: ContextProvider> SqlPlaner<S> {
                         err: format!("can't modify codec type of {} column", TIME_FIELD_NAME),
                     });
                 }
+                if !column.column_type.supports_encoding(&encoding) {
+                    return Err(LogicalPlannerError::Semantic {
+                        err: format!(
+                            "Unsupported encoding type {} for {}",
+                            encoding.as_str(),
+                            column.column_type.to_sql_type_str()
+                        ),
+                    });
+                }
                 let mut new_column = column.clone();
                 new_column.encoding = encoding;
 
@@ -435,6 +841,48 @@ impl<S: ContextProvider> SqlPlaner<S> {
                     new_column,
                 }
             }
+
+            ASTAlterTableAction::ConvertColumn { column } => {
+                let column_name = normalize_ident(&column.name);
+                let old_column = table_schema.column(&column_name).ok_or_else(|| {
+                    LogicalPlannerError::Semantic {
+                        err: format!(
+                            "column {} not exists in table {}",
+                            column_name, &table_schema.name
+                        ),
+                    }
+                })?;
+
+                if old_column.column_type.is_time() {
+                    return Err(LogicalPlannerError::Semantic {
+                        err: format!("can't convert {} column", TIME_FIELD_NAME),
+                    });
+                }
+
+                if old_column.column_type.is_tag() == column.is_tag {
+                    return Err(LogicalPlannerError::Semantic {
+                        err: format!(
+                            "column {} is already a {}",
+                            column_name,
+                            if column.is_tag { "tag" } else { "field" }
+                        ),
+                    });
+                }
+
+                if column.is_tag && table_schema.field_num() == 1 {
+                    return Err(LogicalPlannerError::Semantic {
+                        err: "table must hava a field".to_string(),
+                    });
+                }
+
+                // The column keeps its id, the points of the new series are written with it
+                let new_column = Self::column_opt_to_table_column(column, old_column.id)?;
+
+                AlterTableAction::ConvertColumn {
+                    column_name,
+                    new_column,
+                }
+            }
         };
         Ok(Plan::DDL(DDLPlan::AlterTable(AlterTable {
             table_name,
@@ -459,6 +907,9 @@ impl<S: ContextProvider> SqlPlaner<S> {
             options,
         } = stmt;
         let options = self.make_database_option(options)?;
+        options
+            .validate()
+            .map_err(|err| LogicalPlannerError::Semantic { err })?;
         Ok(Plan::DDL(DDLPlan::CreateDatabase(CreateDatabase {
             name: normalize_sql_object_name(&name),
             if_not_exists,
@@ -478,7 +929,7 @@ impl<S: ContextProvider> SqlPlaner<S> {
     fn make_database_option(&self, options: ASTDatabaseOptions) -> Result<DatabaseOptions> {
         let mut plan_options = DatabaseOptions::default();
         if let Some(ttl) = options.ttl {
-            plan_options.with_ttl(self.str_to_duration(&ttl)?);
+            plan_options.with_ttl(self.positive_duration("TTL", &ttl)?);
         }
         if let Some(replica) = options.replica {
             plan_options.with_replica(replica);
@@ -487,7 +938,8 @@ impl<S: ContextProvider> SqlPlaner<S> {
             plan_options.with_shard_num(shard_num);
         }
         if let Some(vnode_duration) = options.vnode_duration {
-            plan_options.with_vnode_duration(self.str_to_duration(&vnode_duration)?);
+            plan_options
+                .with_vnode_duration(self.positive_duration("SHARD DURATION", &vnode_duration)?);
         }
         if let Some(precision) = options.precision {
             plan_options.with_precision(Precision::new(&precision).ok_or(
@@ -499,6 +951,35 @@ impl<S: ContextProvider> SqlPlaner<S> {
                 },
             )?);
         }
+        if let Some(auto_create_schema) = options.auto_create_schema {
+            plan_options.with_auto_create_schema(auto_create_schema);
+        }
+        if let Some(field_type_conflict) = options.field_type_conflict {
+            let policy = FieldTypeConflict::new(&field_type_conflict).ok_or(
+                LogicalPlannerError::Semantic {
+                    err: format!(
+                        "{} is not a valid field type conflict policy, use like 'reject', 'coerce', 'suffix'",
+                        field_type_conflict
+                    ),
+                },
+            )?;
+            plan_options.with_field_type_conflict(policy);
+        }
+        if let Some(future_tolerance) = options.future_tolerance {
+            plan_options.with_future_tolerance(self.str_to_duration(&future_tolerance)?);
+        }
+        if let Some(past_tolerance) = options.past_tolerance {
+            plan_options.with_past_tolerance(self.str_to_duration(&past_tolerance)?);
+        }
+        if let Some(out_of_window) = options.out_of_window {
+            let policy = OutOfWindow::new(&out_of_window).ok_or(LogicalPlannerError::Semantic {
+                err: format!(
+                    "{} is not a valid out of window policy, use like 'reject', 'clamp'",
+                    out_of_window
+                ),
+            })?;
+            plan_options.with_out_of_window(policy);
+        }
         Ok(plan_options)
     }
 
@@ -507,7 +988,7 @@ impl<S: ContextProvider> SqlPlaner<S> {
             None => {
                 return Err(LogicalPlannerError::Semantic {
                     err: format!(
-                        "{} is not a valid precision, use like 'ms', 'us', 'ns'",
+                        "{} is not a valid duration, use like '90d', '12h', '30m'",
                         text
                     ),
                 })
@@ -517,6 +998,17 @@ impl<S: ContextProvider> SqlPlaner<S> {
         Ok(duration)
     }
 
+    /// The ttl and the duration of the shards must not be zero
+    fn positive_duration(&self, option: &str, text: &str) -> Result<Duration> {
+        let duration = self.str_to_duration(text)?;
+        if duration.time_num == 0 {
+            return Err(LogicalPlannerError::Semantic {
+                err: format!("{} must be greater than 0, found {}", option, text),
+            });
+        }
+        Ok(duration)
+    }
+
     fn make_data_type(data_type: &SQLDataType) -> Result<ColumnType> {
         match data_type {
             // todo : should support get time unit for database
@@ -596,6 +1088,145 @@ impl<S: ContextProvider> SqlPlaner<S> {
     }
 }
 
+/// Password and whether is an admin, of `WITH (password = '...', is_admin = true)`
+fn user_options(options: Vec<SqlOption>) -> Result<(Option<String>, Option<bool>)> {
+    let mut options = sql_options_to_map(options)?;
+    let password = options.remove("password");
+    let is_admin = options
+        .remove("is_admin")
+        .map(|e| {
+            e.parse::<bool>()
+                .map_err(|_| LogicalPlannerError::Semantic {
+                    err: format!("Invalid value {} of option is_admin", e),
+                })
+        })
+        .transpose()?;
+    if let Some(key) = options.keys().next() {
+        return Err(LogicalPlannerError::Semantic {
+            err: format!("Unknown option {} of user", key),
+        });
+    }
+    Ok((password, is_admin))
+}
+
+/// The interval of a task in seconds, `<n>{s|m|h|d}`
+fn task_interval(text: &str) -> Result<u64> {
+    let err = || LogicalPlannerError::Semantic {
+        err: format!("Invalid interval {} of task, expect <n>{{s|m|h|d}}", text),
+    };
+    let digits = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let (num, unit) = text.split_at(digits);
+    let num = num.parse::<u64>().map_err(|_| err())?;
+    let unit_secs = match unit.to_lowercase().as_str() {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(err()),
+    };
+    match num.checked_mul(unit_secs) {
+        Some(secs) if secs > 0 => Ok(secs),
+        _ => Err(err()),
+    }
+}
+
+/// Converts `WITH (key = value, ...)` into a map, keys are normalized
+fn sql_options_to_map(options: Vec<SqlOption>) -> Result<BTreeMap<String, String>> {
+    let mut result = BTreeMap::new();
+    for option in options {
+        let key = normalize_ident(&option.name);
+        let value = match option.value {
+            Value::SingleQuotedString(s) | Value::DoubleQuotedString(s) => s,
+            Value::Number(n, _) => n,
+            Value::Boolean(b) => b.to_string(),
+            v => {
+                return Err(LogicalPlannerError::Semantic {
+                    err: format!("Invalid value {} of option {}", v, key),
+                })
+            }
+        };
+        if result.insert(key.clone(), value).is_some() {
+            return Err(LogicalPlannerError::Semantic {
+                err: format!("Option {} is specified more than once", key),
+            });
+        }
+    }
+    Ok(result)
+}
+
+/// Nanoseconds of the timestamp given as nanoseconds or a RFC3339 string
+fn parse_timestamp(key: &str, value: String) -> Result<i64> {
+    if let Ok(ns) = value.parse::<i64>() {
+        return Ok(ns);
+    }
+    chrono::DateTime::parse_from_rfc3339(&value)
+        .map(|t| t.timestamp_nanos())
+        .map_err(|_| LogicalPlannerError::Semantic {
+            err: format!(
+                "Invalid {} {}, expected nanoseconds or a RFC3339 timestamp",
+                key, value
+            ),
+        })
+}
+
+/// Takes `start_time`, `end_time` and `tables` out of the options, the others are not supported
+fn dump_filter(mut options: BTreeMap<String, String>) -> Result<DumpFilter> {
+    let start_time = options
+        .remove("start_time")
+        .map(|e| parse_timestamp("start_time", e))
+        .transpose()?;
+    let end_time = options
+        .remove("end_time")
+        .map(|e| parse_timestamp("end_time", e))
+        .transpose()?;
+    let tables = options
+        .remove("tables")
+        .map(|e| {
+            e.split(',')
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    if let Some(key) = options.keys().next() {
+        return Err(LogicalPlannerError::Semantic {
+            err: format!("Unknown option {}", key),
+        });
+    }
+
+    Ok(DumpFilter {
+        start_time,
+        end_time,
+        tables,
+    })
+}
+
+/// Parses `table_mapping` of `IMPORT DATABASE`, e.g. `cpu:cpu_2022, mem:mem_2022`
+fn table_mapping(value: &str) -> Result<BTreeMap<String, String>> {
+    let mut mapping = BTreeMap::new();
+    for pair in value.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (from, to) = pair
+            .split_once(':')
+            .map(|(from, to)| (from.trim(), to.trim()))
+            .filter(|(from, to)| !from.is_empty() && !to.is_empty())
+            .ok_or_else(|| LogicalPlannerError::Semantic {
+                err: format!(
+                    "Invalid table mapping {}, expected source_table:target_table",
+                    pair
+                ),
+            })?;
+        if mapping.insert(from.to_string(), to.to_string()).is_some() {
+            return Err(LogicalPlannerError::Semantic {
+                err: format!("Table {} is mapped more than once", from),
+            });
+        }
+    }
+    Ok(mapping)
+}
+
 fn semantic_check(
     insert_columns: &[String],
     source_plan: &LogicalPlan,
@@ -680,12 +1311,42 @@ fn table_write_plan_node(
         .build()
 }
 
+fn copy_to_plan_node(
+    location: String,
+    file_format: FileFormat,
+    input: LogicalPlan,
+) -> std::result::Result<LogicalPlan, DataFusionError> {
+    // output variable for copy operation, every row counts even if its columns are null
+    let affected_row_expr = affected_row_expr(lit(true));
+
+    // construct copy to logical node
+    let node = Arc::new(CopyToPlanNode::try_new(
+        location,
+        file_format,
+        Arc::new(input),
+        vec![affected_row_expr],
+    )?);
+
+    let df_plan = LogicalPlan::Extension(Extension { node });
+
+    let group_expr: Vec<Expr> = vec![];
+
+    LogicalPlanBuilder::from(df_plan)
+        .aggregate(group_expr, vec![merge_affected_row_expr()])?
+        .build()
+}
+
 impl<S: ContextProvider> LogicalPlanner for SqlPlaner<S> {
     fn create_logical_plan(
         &self,
-        statement: ExtStatement,
-        _session: &IsiphoSessionCtx,
+        mut statement: ExtStatement,
+        session: &IsiphoSessionCtx,
     ) -> Result<Plan> {
+        if let ExtStatement::SqlStatement(statement) = &mut statement {
+            let time_zone = time_zone(&session.inner().state());
+            rewrite_group_by_time(statement, time_zone.as_deref())?;
+            rewrite_at_time_zone(statement)?;
+        }
         self.statement_to_plan(statement)
     }
 }
@@ -866,13 +1527,90 @@ mod tests {
         if let Plan::DDL(DDLPlan::CreateDatabase(create)) = plan {
             let ans = format!("{:?}", create);
             println!("{ans}");
-            let expected = r#"CreateDatabase { name: "test", if_not_exists: false, options: DatabaseOptions { ttl: Some(Duration { time_num: 10, unit: Day }), shard_num: Some(5), vnode_duration: Some(Duration { time_num: 3, unit: Day }), replica: Some(10), precision: Some(US) } }"#;
+            let expected = r#"CreateDatabase { name: "test", if_not_exists: false, options: DatabaseOptions { ttl: Some(Duration { time_num: 10, unit: Day }), shard_num: Some(5), vnode_duration: Some(Duration { time_num: 3, unit: Day }), replica: Some(10), precision: Some(US), auto_create_schema: None, field_type_conflict: None, future_tolerance: None, past_tolerance: None, out_of_window: None } }"#;
             assert_eq!(ans, expected);
         } else {
             panic!("expected create table plan")
         }
     }
 
+    #[test]
+    fn test_create_database_out_of_range() {
+        let planner = SqlPlaner::new(MockContext {});
+        for sql in [
+            "CREATE DATABASE test WITH SHARD 0",
+            "CREATE DATABASE test WITH SHARD 1025",
+            "CREATE DATABASE test WITH REPLICA 0",
+            "CREATE DATABASE test WITH REPLICA 17",
+            "CREATE DATABASE test WITH TTL '1d', SHARD DURATION '7d'",
+            "CREATE DATABASE test WITH PRECISION 's'",
+        ] {
+            let mut statements = ExtParser::parse_sql(sql).unwrap();
+            assert!(planner
+                .statement_to_plan(statements.pop_back().unwrap())
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn test_delete_before() {
+        let planner = SqlPlaner::new(MockContext {});
+        let sql = "DELETE FROM test BEFORE '1970-01-01T00:00:01Z'";
+        let mut statements = ExtParser::parse_sql(sql).unwrap();
+        match planner.statement_to_plan(statements.pop_front().unwrap()) {
+            Ok(Plan::DDL(DDLPlan::DeleteBefore(delete))) => assert_eq!(
+                delete,
+                DeleteBefore {
+                    database_name: "test".to_string(),
+                    before: 1_000_000_000,
+                }
+            ),
+            _ => panic!("expected delete before plan"),
+        }
+
+        let mut statements = ExtParser::parse_sql("DELETE FROM test BEFORE 'yesterday'").unwrap();
+        assert!(planner
+            .statement_to_plan(statements.pop_back().unwrap())
+            .is_err());
+    }
+
+    #[test]
+    fn test_alter_database() {
+        let planner = SqlPlaner::new(MockContext {});
+        let sql = "ALTER DATABASE test SET TTL '90d', SHARD DURATION '12h'";
+        let mut statements = ExtParser::parse_sql(sql).unwrap();
+        let plan = planner
+            .statement_to_plan(statements.pop_back().unwrap())
+            .unwrap();
+        match plan {
+            Plan::DDL(DDLPlan::AlterDatabase(alter)) => {
+                assert_eq!(alter.database_name, "test");
+                assert_eq!(
+                    alter.database_options.ttl_or_default().to_string(),
+                    "90 Days"
+                );
+                assert_eq!(
+                    alter
+                        .database_options
+                        .vnode_duration_or_default()
+                        .to_string(),
+                    "12 Hours"
+                );
+            }
+            _ => panic!("expected alter database plan"),
+        }
+
+        for sql in [
+            "ALTER DATABASE test SET TTL '0d'",
+            "ALTER DATABASE test SET SHARD DURATION '1w'",
+        ] {
+            let mut statements = ExtParser::parse_sql(sql).unwrap();
+            assert!(planner
+                .statement_to_plan(statements.pop_back().unwrap())
+                .is_err());
+        }
+    }
+
     #[test]
     #[should_panic(expected = "Field or Tag name should not have same")]
     fn test_create_table_filed_name_same() {
@@ -912,6 +1650,195 @@ mod tests {
             .unwrap();
     }
 
+    #[test]
+    fn test_copy_to() {
+        let sql = "COPY (SELECT field_int FROM test_tb) TO '/tmp/test_tb' FORMAT json";
+        let mut statements = ExtParser::parse_sql(sql).unwrap();
+        assert_eq!(statements.len(), 1);
+        let test = MockContext {};
+        let planner = SqlPlaner::new(test);
+        let plan = planner
+            .statement_to_plan(statements.pop_back().unwrap())
+            .unwrap();
+
+        match plan {
+            Plan::Query(QueryPlan {
+                df_plan: LogicalPlan::Aggregate(Aggregate { input, .. }),
+            }) => match input.as_ref() {
+                LogicalPlan::Extension(Extension { node }) => {
+                    match node.as_any().downcast_ref::<CopyToPlanNode>() {
+                        Some(CopyToPlanNode {
+                            location,
+                            file_format,
+                            ..
+                        }) => {
+                            assert_eq!(location, "/tmp/test_tb");
+                            assert_eq!(*file_format, FileFormat::Json);
+                        }
+                        _ => panic!(),
+                    }
+                }
+                _ => panic!(),
+            },
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_user() {
+        let planner = SqlPlaner::new(MockContext {});
+        let plan = |sql: &str| {
+            let mut statements = ExtParser::parse_sql(sql).unwrap();
+            planner.statement_to_plan(statements.pop_back().unwrap())
+        };
+
+        match plan("CREATE USER U1 WITH (password = 'secret')").unwrap() {
+            Plan::DDL(DDLPlan::CreateUser(plan)) => {
+                assert_eq!(plan.name, "u1");
+                assert_eq!(plan.password, "secret");
+                assert!(!plan.is_admin);
+            }
+            _ => panic!(),
+        }
+        match plan("ALTER USER u1 WITH (is_admin = true)").unwrap() {
+            Plan::DDL(DDLPlan::AlterUser(plan)) => {
+                assert_eq!(plan.password, None);
+                assert_eq!(plan.is_admin, Some(true));
+            }
+            _ => panic!(),
+        }
+
+        assert!(plan("CREATE USER u1").is_err());
+        assert!(plan("CREATE USER u1 WITH (password = 'a', admin = true)").is_err());
+        assert!(plan("ALTER USER u1 WITH (is_admin = 'yes')").is_err());
+    }
+
+    #[test]
+    fn test_create_task() {
+        let planner = SqlPlaner::new(MockContext {});
+        let plan = |sql: &str| {
+            let mut statements = ExtParser::parse_sql(sql).unwrap();
+            planner.statement_to_plan(statements.pop_back().unwrap())
+        };
+
+        match plan("CREATE TASK T0 EVERY '2m' INTO t1 AS SELECT * FROM t0 WHERE time > $last_run")
+            .unwrap()
+        {
+            Plan::DDL(DDLPlan::CreateTask(plan)) => {
+                assert_eq!(plan.name, "t0");
+                assert_eq!(plan.trigger, TaskTrigger::Schedule { interval: 120 });
+                assert_eq!(plan.sink, TaskSink::Table("t1".to_string()));
+                assert_eq!(plan.query, "SELECT * FROM t0 WHERE time > $last_run");
+            }
+            _ => panic!(),
+        }
+        match plan("CREATE TASK t0 ON DATA IN t0 WEBHOOK 'http://localhost/alert' AS SELECT 1")
+            .unwrap()
+        {
+            Plan::DDL(DDLPlan::CreateTask(plan)) => {
+                assert_eq!(
+                    plan.trigger,
+                    TaskTrigger::OnData {
+                        table: "t0".to_string(),
+                        interval: DEFAULT_TASK_DATA_INTERVAL,
+                    }
+                );
+            }
+            _ => panic!(),
+        }
+
+        assert!(plan("CREATE TASK t0 EVERY '0s' INTO t1 AS SELECT 1").is_err());
+        assert!(plan("CREATE TASK t0 EVERY '1w' INTO t1 AS SELECT 1").is_err());
+        assert!(plan("CREATE TASK t0 EVERY '1m' WEBHOOK 'alert' AS SELECT 1").is_err());
+    }
+
+    #[test]
+    fn test_create_stream_source() {
+        let sql = "CREATE STREAM SOURCE metrics FROM kafka WITH (brokers = 'localhost:9092', topic = 'metrics', format = 'json', table = 'cpu')";
+        let mut statements = ExtParser::parse_sql(sql).unwrap();
+        let planner = SqlPlaner::new(MockContext {});
+        let plan = planner
+            .statement_to_plan(statements.pop_back().unwrap())
+            .unwrap();
+
+        match plan {
+            Plan::DDL(DDLPlan::CreateStreamSource(plan)) => {
+                assert_eq!(plan.name, "metrics");
+                assert_eq!(plan.connector, ConnectorType::Kafka);
+                assert_eq!(plan.format, PayloadFormat::Json);
+                assert_eq!(plan.database, None);
+                assert_eq!(plan.table.as_deref(), Some("cpu"));
+                assert_eq!(
+                    plan.options.keys().collect::<Vec<_>>(),
+                    vec!["brokers", "topic"]
+                );
+            }
+            _ => panic!(),
+        }
+
+        let sql = "CREATE STREAM SOURCE metrics FROM rabbitmq WITH (topic = 'metrics')";
+        let mut statements = ExtParser::parse_sql(sql).unwrap();
+        assert!(planner
+            .statement_to_plan(statements.pop_back().unwrap())
+            .is_err());
+    }
+
+    #[test]
+    fn test_export_database() {
+        let sql = "EXPORT DATABASE db1 TO '/tmp/dump' WITH (start_time = '1970-01-01T00:00:01Z', end_time = 2000000000, tables = 'cpu, mem')";
+        let mut statements = ExtParser::parse_sql(sql).unwrap();
+        let planner = SqlPlaner::new(MockContext {});
+        let plan = planner
+            .statement_to_plan(statements.pop_back().unwrap())
+            .unwrap();
+
+        match plan {
+            Plan::DDL(DDLPlan::ExportDatabase(plan)) => {
+                assert_eq!(plan.database, "db1");
+                assert_eq!(plan.location, "/tmp/dump");
+                assert_eq!(plan.compression, DumpCompression::Gzip);
+                assert_eq!(plan.filter.start_time, Some(1_000_000_000));
+                assert_eq!(plan.filter.end_time, Some(2_000_000_000));
+                assert_eq!(plan.filter.tables, vec!["cpu", "mem"]);
+            }
+            _ => panic!(),
+        }
+
+        let sql = "IMPORT DATABASE db2 FROM '/tmp/dump' WITH (tables = 'cpu', table_mapping = 'cpu:cpu_2022, mem : mem_2022')";
+        let mut statements = ExtParser::parse_sql(sql).unwrap();
+        let plan = planner
+            .statement_to_plan(statements.pop_back().unwrap())
+            .unwrap();
+        match plan {
+            Plan::DDL(DDLPlan::ImportDatabase(plan)) => {
+                assert_eq!(plan.database, "db2");
+                assert_eq!(plan.filter.tables, vec!["cpu"]);
+                assert_eq!(
+                    plan.table_mapping,
+                    BTreeMap::from([
+                        ("cpu".to_string(), "cpu_2022".to_string()),
+                        ("mem".to_string(), "mem_2022".to_string())
+                    ])
+                );
+            }
+            _ => panic!(),
+        }
+
+        for sql in [
+            "EXPORT DATABASE db1 TO '/tmp/dump' WITH (start_time = 'yesterday')",
+            "EXPORT DATABASE db1 TO '/tmp/dump' WITH (compression = 'lz4')",
+            "EXPORT DATABASE db1 TO '/tmp/dump' WITH (table_mapping = 'cpu:cpu2')",
+            "IMPORT DATABASE db1 FROM '/tmp/dump' WITH (compression = 'gzip')",
+            "IMPORT DATABASE db1 FROM '/tmp/dump' WITH (table_mapping = 'cpu')",
+            "IMPORT DATABASE db1 FROM '/tmp/dump' WITH (table_mapping = 'cpu:a, cpu:b')",
+        ] {
+            let mut statements = ExtParser::parse_sql(sql).unwrap();
+            assert!(planner
+                .statement_to_plan(statements.pop_back().unwrap())
+                .is_err());
+        }
+    }
+
     #[test]
     fn test_insert_select() {
         let sql = "insert test_tb(field_int, field_string)
@@ -10,14 +10,19 @@ use datafusion::{
 use futures::Stream;
 use models::codec::Encoding;
 use models::{
-    predicate::domain::PredicateRef,
+    predicate::domain::{ColumnDomains, Domain, PredicateRef},
     schema::{ColumnType, TableColumn, TskvTableSchema, TIME_FIELD},
+    SeriesId,
 };
+use snafu::ResultExt;
+use std::sync::Arc;
+use trace::debug;
 
 use tskv::engine::EngineRef;
 
-use tskv::Error;
+use tskv::{error::IndexErrSnafu, Error};
 
+use crate::extension::physical::plan_node::runtime_filter::JoinKeyFilter;
 use crate::iterator::{QueryOption, RowIterator};
 
 #[allow(dead_code)]
@@ -32,6 +37,8 @@ pub struct TableScanStream {
 }
 
 impl TableScanStream {
+    /// The series of the tag values not in `join_keys`, the keys of the build side of
+    /// a hash join, are skipped
     pub fn new(
         table_schema: TskvTableSchema,
         proj_schema: SchemaRef,
@@ -39,6 +46,7 @@ impl TableScanStream {
         batch_size: usize,
         store_engine: EngineRef,
         metrics: TableScanMetrics,
+        join_keys: Option<(String, Arc<JoinKeyFilter>)>,
     ) -> Result<Self, Error> {
         let mut proj_fileds = Vec::with_capacity(proj_schema.fields().len());
         for item in proj_schema.fields().iter() {
@@ -78,10 +86,14 @@ impl TableScanStream {
             ColumnType::Time => Some(e.name.clone()),
             _ => None,
         });
-        let tags_filter = filter.translate_column(|e| match e.column_type {
+        let mut tags_filter = filter.translate_column(|e| match e.column_type {
             ColumnType::Tag => Some(e.name.clone()),
             _ => None,
         });
+        // The join keys in a list are looked up in the index, the others are
+        // checked on the series keys
+        let join_keys =
+            join_keys.filter(|(tag, keys)| !push_down_join_keys(&mut tags_filter, tag, keys));
         let fields_filter = filter.translate_column(|e| match e.column_type {
             ColumnType::Field(_) => Some(e.name.clone()),
             _ => None,
@@ -94,14 +106,31 @@ impl TableScanStream {
             fields_filter,
         };
 
-        let iterator = match RowIterator::new(
-            metrics.tskv_metrics(),
-            store_engine.clone(),
-            option,
-            batch_size,
-        ) {
-            Ok(it) => it,
-            Err(err) => return Err(err),
+        let iterator = match join_keys {
+            None => RowIterator::new(
+                metrics.tskv_metrics(),
+                store_engine.clone(),
+                option,
+                batch_size,
+            )?,
+            Some((tag, keys)) => {
+                let series = store_engine
+                    .get_series_id_by_filter(
+                        &option.table_schema.db,
+                        &option.table_schema.name,
+                        &option.tags_filter,
+                    )
+                    .context(IndexErrSnafu)?;
+                let series =
+                    filter_series(&store_engine, &option.table_schema.db, series, &tag, &keys)?;
+                RowIterator::with_series(
+                    metrics.tskv_metrics(),
+                    store_engine.clone(),
+                    option,
+                    series,
+                    batch_size,
+                )?
+            }
         };
 
         Ok(Self {
@@ -114,6 +143,67 @@ impl TableScanStream {
     }
 }
 
+/// Intersects the domain of the tag with the join keys in a list, false if the keys
+/// can't be looked up in the index
+fn push_down_join_keys(
+    tags_filter: &mut ColumnDomains<String>,
+    tag: &str,
+    keys: &JoinKeyFilter,
+) -> bool {
+    let domain = match keys.to_domain() {
+        Some(domain) => domain,
+        None => return false,
+    };
+    // The ranges of the values are not intersected with a list
+    let is_equtable = match tags_filter.domains() {
+        Some(domains) => matches!(
+            domains.get(tag),
+            None | Some(Domain::All) | Some(Domain::Equtable(_))
+        ),
+        None => true,
+    };
+    if is_equtable {
+        tags_filter.insert_or_intersect(tag.to_string(), &domain);
+    }
+    is_equtable
+}
+
+/// The series the values of the tag of which are in the join keys, the series
+/// without the tag never match
+fn filter_series(
+    engine: &EngineRef,
+    db: &str,
+    series: Vec<SeriesId>,
+    tag: &str,
+    keys: &JoinKeyFilter,
+) -> Result<Vec<SeriesId>, Error> {
+    let total = series.len();
+    let mut filtered = Vec::with_capacity(total);
+    for id in series {
+        let key = match engine.get_series_key(db, id).context(IndexErrSnafu)? {
+            Some(key) => key,
+            None => continue,
+        };
+        let is_matched = key
+            .tags()
+            .iter()
+            .find(|e| e.key == tag.as_bytes())
+            .and_then(|e| std::str::from_utf8(&e.value).ok())
+            .map(|value| keys.contains(value))
+            .unwrap_or_default();
+        if is_matched {
+            filtered.push(id);
+        }
+    }
+    debug!(
+        "Runtime filter on {} skipped {} of {} series",
+        tag,
+        total - filtered.len(),
+        total
+    );
+    Ok(filtered)
+}
+
 impl Stream for TableScanStream {
     type Item = Result<RecordBatch, ArrowError>;
 
@@ -200,12 +290,16 @@ impl TableScanMetrics {
     }
 }
 
+/// Name of the metric of the bytes of the field values read by a scan
+pub const BYTES_SCANNED: &str = "bytes_scanned";
+
 /// Stores metrics about the table writer execution.
 #[derive(Debug)]
 pub struct TskvSourceMetrics {
     elapsed_point_to_record_batch: metrics::Time,
     elapsed_field_scan: metrics::Time,
     elapsed_series_scan: metrics::Time,
+    bytes_scanned: metrics::Count,
 }
 
 impl TskvSourceMetrics {
@@ -220,10 +314,13 @@ impl TskvSourceMetrics {
         let elapsed_series_scan =
             MetricBuilder::new(metrics).subset_time("elapsed_series_scan", partition);
 
+        let bytes_scanned = MetricBuilder::new(metrics).counter(BYTES_SCANNED, partition);
+
         Self {
             elapsed_point_to_record_batch,
             elapsed_field_scan,
             elapsed_series_scan,
+            bytes_scanned,
         }
     }
 
@@ -238,4 +335,8 @@ impl TskvSourceMetrics {
     pub fn elapsed_series_scan(&self) -> &metrics::Time {
         &self.elapsed_series_scan
     }
+
+    pub fn bytes_scanned(&self) -> &metrics::Count {
+        &self.bytes_scanned
+    }
 }
@@ -1,6 +1,7 @@
 use std::{any::Any, sync::Arc};
 
 use async_trait::async_trait;
+use coordinator::service::CoordinatorRef;
 use datafusion::{
     arrow::datatypes::SchemaRef,
     common::DFSchemaRef,
@@ -8,23 +9,33 @@ use datafusion::{
     error::{DataFusionError, Result},
     execution::context::SessionState,
     logical_expr::{Expr, TableProviderFilterPushDown},
-    physical_plan::{project_schema, ExecutionPlan},
+    physical_plan::{empty::EmptyExec, project_schema, ExecutionPlan},
 };
+use models::consistency_level::ConsistencyLevel;
 use models::predicate::domain::{Predicate, PredicateRef};
-use models::schema::TskvTableSchema;
+use models::schema::{DatabaseOptions, TskvTableSchema, TIME_FIELD};
+use protos::kv_service::{ScanTableRequest, TimeRange};
 use spi::catalog::MetadataError;
-use tskv::engine::EngineRef;
+use spi::query::session::read_consistency;
+use tskv::tseries_family::TimeRange as DataTimeRange;
 
 use crate::{
+    data_source::shard_scan::{ShardScanContext, ShardScanExec},
     data_source::tskv_sink::TskvRecordBatchSinkProvider,
     extension::physical::plan_node::{table_writer::TableWriterExec, tag_scan::TagScanExec},
-    tskv_exec::TskvExec,
+    iterator::filter_to_time_ranges,
+    tskv_exec::{scan_statistics, TskvExec},
 };
 
 #[derive(Clone)]
 pub struct ClusterTable {
-    engine: EngineRef,
+    coord: CoordinatorRef,
+    tenant: String,
     schema: TskvTableSchema,
+    /// The shards of the table are scanned on the data nodes in a cluster
+    shard_scan: Option<ShardScanContext>,
+    /// The rows inserted are resolved by the options of the database
+    database_options: Option<DatabaseOptions>,
 }
 
 impl ClusterTable {
@@ -32,19 +43,102 @@ impl ClusterTable {
         &self,
         projection: &Option<Vec<usize>>,
         predicate: PredicateRef,
+        consistency: ConsistencyLevel,
     ) -> Result<Arc<dyn ExecutionPlan>> {
         let proj_schema = self.project_schema(projection)?;
+        let filter_time_ranges = filter_to_time_ranges(
+            &predicate
+                .filter()
+                .translate_column(|c| (c.name == TIME_FIELD).then(|| c.name.clone())),
+        );
 
-        Ok(Arc::new(TskvExec::new(
-            self.schema.clone(),
-            proj_schema,
-            predicate,
-            self.engine.clone(),
-        )))
+        if let Some(context) = &self.shard_scan {
+            let time_ranges: Vec<TimeRange> = filter_time_ranges
+                .into_iter()
+                .map(|e| TimeRange {
+                    min_ts: e.min_ts,
+                    max_ts: e.max_ts,
+                })
+                .collect();
+            let targets = context
+                .plan(&self.schema, &time_ranges, consistency)
+                .await?;
+            if targets.is_empty() {
+                return Ok(Arc::new(EmptyExec::new(false, proj_schema)));
+            }
+
+            let request = ScanTableRequest {
+                database: self.schema.db.clone(),
+                table: self.schema.name.clone(),
+                columns: proj_schema
+                    .fields()
+                    .iter()
+                    .map(|e| e.name().clone())
+                    .collect(),
+                time_ranges,
+                shards: vec![],
+            };
+            let key_columns = self
+                .schema
+                .columns()
+                .iter()
+                .filter(|e| e.column_type.is_tag() || e.column_type.is_time())
+                .map(|e| e.name.clone())
+                .collect();
+            let statistics = scan_statistics(&self.schema, &proj_schema, None);
+            return Ok(Arc::new(
+                ShardScanExec::new(
+                    context.clone(),
+                    self.coord.engine(),
+                    request,
+                    targets,
+                    proj_schema,
+                    self.schema.to_arrow_schema(),
+                    key_columns,
+                )
+                .with_statistics(statistics),
+            ));
+        }
+
+        // The scan is pruned if none of the files and the caches has data in the
+        // time ranges of the filter
+        let engine = self.coord.engine();
+        let time_range = match engine.get_db_version(&self.schema.db) {
+            Ok(Some(version)) => {
+                if !filter_time_ranges.iter().any(|e| version.overlaps(e)) {
+                    return Ok(Arc::new(EmptyExec::new(false, proj_schema)));
+                }
+                version
+                    .time_range()
+                    .map(|e| bound_time_range(e, &filter_time_ranges))
+            }
+            _ => None,
+        };
+
+        Ok(Arc::new(
+            TskvExec::new(self.schema.clone(), proj_schema, predicate, engine)
+                .with_time_range(time_range),
+        ))
     }
 
-    pub fn new(engine: EngineRef, schema: TskvTableSchema) -> Self {
-        ClusterTable { engine, schema }
+    pub fn new(coord: CoordinatorRef, tenant: &str, schema: TskvTableSchema) -> Self {
+        ClusterTable {
+            coord,
+            tenant: tenant.to_string(),
+            schema,
+            shard_scan: None,
+            database_options: None,
+        }
+    }
+
+    pub fn with_shard_scan(mut self, context: ShardScanContext) -> Self {
+        self.shard_scan = Some(context);
+        self
+    }
+
+    pub fn with_database_options(mut self, options: DatabaseOptions) -> Self {
+        self.database_options = Some(options);
+        self
     }
 
     pub async fn write(
@@ -52,10 +146,10 @@ impl ClusterTable {
         _state: &SessionState,
         input: Arc<dyn ExecutionPlan>,
     ) -> Result<Arc<dyn ExecutionPlan>> {
-        let record_batch_sink_privider = Arc::new(TskvRecordBatchSinkProvider::new(
-            self.engine.clone(),
-            self.schema.clone(),
-        ));
+        let record_batch_sink_privider = Arc::new(
+            TskvRecordBatchSinkProvider::new(self.coord.clone(), &self.tenant, self.schema.clone())
+                .with_database_options(self.database_options.clone()),
+        );
 
         Ok(Arc::new(TableWriterExec::new(
             input,
@@ -81,10 +175,14 @@ impl ClusterTable {
             Arc::new(self.schema.clone()),
             Arc::new(projected_schema.as_ref().into()),
             filter,
-            self.engine.clone(),
+            self.coord.engine(),
         )))
     }
 
+    pub fn tenant(&self) -> &str {
+        &self.tenant
+    }
+
     pub fn table_schema(&self) -> &TskvTableSchema {
         &self.schema
     }
@@ -113,7 +211,7 @@ impl TableProvider for ClusterTable {
 
     async fn scan(
         &self,
-        _ctx: &SessionState,
+        ctx: &SessionState,
         projection: &Option<Vec<usize>>,
         filters: &[Expr],
         limit: Option<usize>,
@@ -124,13 +222,23 @@ impl TableProvider for ClusterTable {
                 .push_down_filter(filters, &self.schema),
         );
 
-        return self.create_physical_plan(projection, filter).await;
+        return self
+            .create_physical_plan(projection, filter, read_consistency(ctx)?)
+            .await;
     }
     fn supports_filter_pushdown(&self, _: &Expr) -> Result<TableProviderFilterPushDown> {
         Ok(TableProviderFilterPushDown::Inexact)
     }
 }
 
+/// The time range of the data bounded by the span of the time ranges scanned,
+/// which are not empty
+fn bound_time_range(data: DataTimeRange, scanned: &[DataTimeRange]) -> DataTimeRange {
+    let min_ts = scanned.iter().map(|e| e.min_ts).min().unwrap_or(i64::MIN);
+    let max_ts = scanned.iter().map(|e| e.max_ts).max().unwrap_or(i64::MAX);
+    DataTimeRange::new(data.min_ts.max(min_ts), data.max_ts.min(max_ts))
+}
+
 /// Check the validity of the projection
 ///
 /// 1. If the projection contains the time column, it must contain the field column, otherwise an error will be reported
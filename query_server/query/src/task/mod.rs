@@ -0,0 +1,618 @@
+//! Tasks, pipelines of a query run on a schedule or once data arrives, the rows of
+//! which are written to a table or posted to a webhook
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use datafusion::arrow::array::{
+    Array, StringBuilder, TimestampNanosecondArray, TimestampNanosecondBuilder, UInt64Array,
+    UInt64Builder,
+};
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use datafusion::arrow::error::ArrowError;
+use datafusion::arrow::record_batch::RecordBatch;
+use meta::meta_client::MetaClientRef;
+use models::meta_data::{NodeId, NodeStatus};
+use models::task::{
+    TaskDefinition, TaskProgress, TaskRun, TaskRunState, TaskSink, TaskTrigger,
+    LAST_RUN_PLACEHOLDER, NOW_PLACEHOLDER,
+};
+use models::Timestamp;
+use parking_lot::Mutex;
+use spi::catalog::MetaDataRef;
+use spi::query::dispatcher::QueryDispatcher;
+use spi::query::execution::Output;
+use spi::service::protocol::{ContextBuilder, Query, UserInfo};
+use spi::service::serializer::{JsonSerializer, RecordBatchSerializer};
+use tokio_util::sync::CancellationToken;
+use trace::{debug, warn};
+
+use self::store::TaskStoreRef;
+
+pub mod store;
+
+pub type TaskRunLogRef = Arc<TaskRunLog>;
+pub type TaskManagerRef = Arc<TaskManager>;
+
+/// Table of the runs of the tasks of the tenant, `SELECT * FROM system.task_runs`
+pub const TASK_RUNS_TABLE: &str = "task_runs";
+
+/// Latest runs kept in memory for the runs table
+const MAX_RECENT_RUNS: usize = 10000;
+/// The tasks are reloaded and the due ones started every tick
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+/// Timeouts of connecting to a webhook and of posting to it
+const WEBHOOK_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// History of the runs of the tasks, read from the progresses of the tasks saved
+/// in the store if any, so that the runs of all the nodes are kept across restarts,
+/// otherwise the runs of this node are kept in memory
+#[derive(Default)]
+pub struct TaskRunLog {
+    store: Option<TaskStoreRef>,
+    recent: Mutex<VecDeque<TaskRun>>,
+}
+
+impl TaskRunLog {
+    pub fn with_store(store: TaskStoreRef) -> Self {
+        Self {
+            store: Some(store),
+            recent: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Only kept in memory without a store, the runs are saved with the progresses
+    /// of the tasks otherwise
+    pub fn record(&self, run: TaskRun) {
+        if self.store.is_some() {
+            return;
+        }
+        let mut recent = self.recent.lock();
+        if recent.len() == MAX_RECENT_RUNS {
+            recent.pop_front();
+        }
+        recent.push_back(run);
+    }
+
+    /// The latest runs of the tasks of the tenant
+    pub fn runs(&self, tenant: &str) -> Vec<TaskRun> {
+        let store = match &self.store {
+            Some(store) => store,
+            None => {
+                return self
+                    .recent
+                    .lock()
+                    .iter()
+                    .filter(|e| e.tenant == tenant)
+                    .cloned()
+                    .collect()
+            }
+        };
+        let mut runs = match store.progresses() {
+            Ok(progresses) => progresses
+                .into_iter()
+                .filter(|e| e.tenant == tenant)
+                .flat_map(|e| e.runs)
+                .collect::<Vec<_>>(),
+            Err(e) => {
+                warn!("Failed to load the runs of the tasks: {}", e);
+                vec![]
+            }
+        };
+        runs.sort_by_key(|e| e.started_at);
+        runs
+    }
+
+    pub fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("task", DataType::Utf8, false),
+            Field::new(
+                "started_at",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+            Field::new("duration_ms", DataType::UInt64, false),
+            Field::new("state", DataType::Utf8, false),
+            Field::new("rows", DataType::UInt64, false),
+            Field::new("error", DataType::Utf8, true),
+        ]))
+    }
+
+    /// The latest runs of the tasks of the tenant as the rows of the runs table
+    pub fn record_batch(&self, tenant: &str) -> Result<RecordBatch, ArrowError> {
+        let runs = self.runs(tenant);
+        let mut tasks = StringBuilder::new();
+        let mut started_ats = TimestampNanosecondBuilder::with_capacity(runs.len());
+        let mut durations = UInt64Builder::with_capacity(runs.len());
+        let mut states = StringBuilder::new();
+        let mut rows = UInt64Builder::with_capacity(runs.len());
+        let mut errors = StringBuilder::new();
+        for run in runs.iter() {
+            tasks.append_value(&run.task);
+            started_ats.append_value(run.started_at);
+            durations.append_value(run.duration_ms);
+            states.append_value(run.state.to_string());
+            rows.append_value(run.rows);
+            errors.append_option(run.error.as_deref());
+        }
+
+        RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(tasks.finish()),
+                Arc::new(started_ats.finish()),
+                Arc::new(durations.finish()),
+                Arc::new(states.finish()),
+                Arc::new(rows.finish()),
+                Arc::new(errors.finish()),
+            ],
+        )
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct TaskState {
+    /// Nanoseconds, the task is run once it passes
+    next_run: Timestamp,
+    /// Start of the last successful run, the value of `$last_run`
+    last_run: Timestamp,
+    /// The latest time of the rows of the table of the trigger seen by the last
+    /// successful run
+    watermark: Option<Timestamp>,
+    running: bool,
+}
+
+/// Runs the enabled tasks of all the tenants as they are due.
+///
+/// The progress of a task is saved to the store after every run, the runs resume
+/// from it once the node restarts. In a cluster every task is run by one of the
+/// healthy data nodes.
+pub struct TaskManager {
+    store: TaskStoreRef,
+    meta: MetaDataRef,
+    dispatcher: Arc<dyn QueryDispatcher>,
+    runs: TaskRunLogRef,
+    /// This node and the meta client, if in a cluster
+    cluster: Option<(NodeId, MetaClientRef)>,
+    http: reqwest::Client,
+    states: Mutex<HashMap<(String, String), TaskState>>,
+    cancel: CancellationToken,
+}
+
+impl TaskManager {
+    pub fn new(
+        store: TaskStoreRef,
+        meta: MetaDataRef,
+        dispatcher: Arc<dyn QueryDispatcher>,
+        runs: TaskRunLogRef,
+        cluster: Option<(NodeId, MetaClientRef)>,
+    ) -> Self {
+        Self {
+            store,
+            meta,
+            dispatcher,
+            runs,
+            cluster,
+            http: reqwest::Client::builder()
+                .connect_timeout(WEBHOOK_CONNECT_TIMEOUT)
+                .timeout(WEBHOOK_TIMEOUT)
+                .build()
+                .expect("build the http client of the webhooks"),
+            states: Mutex::new(HashMap::new()),
+            cancel: CancellationToken::new(),
+        }
+    }
+
+    pub fn start(self: &Arc<Self>) {
+        let manager = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(TICK_INTERVAL);
+            loop {
+                tokio::select! {
+                    _ = manager.cancel.cancelled() => return,
+                    _ = ticker.tick() => manager.tick().await,
+                }
+            }
+        });
+    }
+
+    pub fn stop(&self) {
+        self.cancel.cancel();
+    }
+
+    async fn tick(self: &Arc<Self>) {
+        let tasks = match self.store.tasks() {
+            Ok(tasks) => tasks,
+            Err(e) => {
+                warn!("Failed to load the tasks: {}", e);
+                return;
+            }
+        };
+
+        let now = now();
+        let due = {
+            let mut states = self.states.lock();
+            states.retain(|(tenant, name), _| {
+                tasks.iter().any(|e| &e.tenant == tenant && &e.name == name)
+            });
+            if tasks.iter().any(|e| !states.contains_key(&key(e))) {
+                self.resume(&tasks, &mut states);
+            }
+            tasks
+                .into_iter()
+                .filter(|task| {
+                    let state = states.entry(key(task)).or_default();
+                    if !task.enabled || state.running || state.next_run > now {
+                        return false;
+                    }
+                    state.next_run = now + task.trigger.interval() as i64 * 1_000_000_000;
+                    true
+                })
+                .collect::<Vec<_>>()
+        };
+        if due.is_empty() {
+            return;
+        }
+
+        for task in self.placed_here(due).await {
+            if let Some(state) = self.states.lock().get_mut(&key(&task)) {
+                state.running = true;
+            }
+            let manager = self.clone();
+            tokio::spawn(async move { manager.run(task).await });
+        }
+    }
+
+    /// The states of the tasks not seen yet are resumed from their progresses, the
+    /// next run is due an interval after the last one
+    fn resume(&self, tasks: &[TaskDefinition], states: &mut HashMap<(String, String), TaskState>) {
+        let progresses = match self.store.progresses() {
+            Ok(progresses) => progresses,
+            Err(e) => {
+                warn!("Failed to load the progresses of the tasks: {}", e);
+                vec![]
+            }
+        };
+        for task in tasks.iter().filter(|e| !states.contains_key(&key(e))) {
+            let mut state = TaskState::default();
+            let progress = progresses
+                .iter()
+                .find(|e| e.tenant == task.tenant && e.task == task.name);
+            if let Some(progress) = progress {
+                state.last_run = progress.last_run;
+                state.watermark = progress.watermark;
+                if let Some(run) = progress.runs.last() {
+                    state.next_run =
+                        run.started_at + task.trigger.interval() as i64 * 1_000_000_000;
+                }
+            }
+            states.insert(key(task), state);
+        }
+    }
+
+    /// The tasks run by this node, every task is placed on one of the healthy
+    /// data nodes by the hash of its name
+    async fn placed_here(&self, tasks: Vec<TaskDefinition>) -> Vec<TaskDefinition> {
+        let (node_id, client) = match &self.cluster {
+            Some(cluster) => cluster,
+            None => return tasks,
+        };
+        let mut nodes = match client.data_nodes().await {
+            Ok(nodes) => nodes
+                .into_iter()
+                .filter(|e| e.status == NodeStatus::Healthy)
+                .map(|e| e.id)
+                .collect::<Vec<_>>(),
+            Err(e) => {
+                warn!("Failed to get the data nodes to place the tasks: {}", e);
+                return vec![];
+            }
+        };
+        nodes.sort_unstable();
+        tasks
+            .into_iter()
+            .filter(|e| placement(e, &nodes) == Some(*node_id))
+            .collect()
+    }
+
+    async fn run(&self, task: TaskDefinition) {
+        let mut progress = self
+            .store
+            .progresses()
+            .ok()
+            .and_then(|e| {
+                e.into_iter()
+                    .find(|e| e.tenant == task.tenant && e.task == task.name)
+            })
+            .unwrap_or_else(|| TaskProgress::new(&task.tenant, &task.name));
+        // The task may be run by another node since this node ran it last time
+        let state = {
+            let mut states = self.states.lock();
+            let state = states.entry(key(&task)).or_default();
+            state.last_run = state.last_run.max(progress.last_run);
+            state.watermark = state.watermark.max(progress.watermark);
+            *state
+        };
+        let started_at = now();
+        let start = Instant::now();
+        let result = self.execute(&task, &state, started_at).await;
+
+        let mut run = TaskRun {
+            tenant: task.tenant.clone(),
+            task: task.name.clone(),
+            started_at,
+            duration_ms: start.elapsed().as_millis() as u64,
+            state: TaskRunState::Succeeded,
+            rows: 0,
+            error: None,
+        };
+        {
+            let mut states = self.states.lock();
+            let state = states.entry(key(&task)).or_default();
+            state.running = false;
+            match result {
+                Ok(Some((rows, watermark))) => {
+                    run.rows = rows;
+                    state.last_run = started_at;
+                    state.watermark = watermark.or(state.watermark);
+                }
+                Ok(None) => run.state = TaskRunState::Skipped,
+                Err(e) => {
+                    debug!("Task {} of tenant {} failed: {}", task.name, task.tenant, e);
+                    run.state = TaskRunState::Failed;
+                    run.error = Some(e);
+                }
+            }
+            progress.last_run = state.last_run;
+            progress.watermark = state.watermark;
+        }
+        progress.record(run.clone());
+        self.runs.record(run);
+        if let Err(e) = self.store.save_progress(progress).await {
+            warn!(
+                "Failed to save the progress of task {} of tenant {}: {}",
+                task.name, task.tenant, e
+            );
+        }
+    }
+
+    /// The rows written or posted and the latest time of the rows of the table of
+    /// the trigger, `None` if there is no new row in the table
+    async fn execute(
+        &self,
+        task: &TaskDefinition,
+        state: &TaskState,
+        now: Timestamp,
+    ) -> Result<Option<(u64, Option<Timestamp>)>, String> {
+        if self
+            .meta
+            .user(&task.owner)
+            .map_err(|e| e.to_string())?
+            .is_none()
+        {
+            return Err(format!("owner {} of the task not exists", task.owner));
+        }
+        // The node posts to the url regardless of where it is, only the admins do
+        if matches!(task.sink, TaskSink::Webhook(_)) && !self.is_admin(&task.owner)? {
+            return Err(format!(
+                "owner {} of the task posting to a webhook is not an admin",
+                task.owner
+            ));
+        }
+
+        let latest = match &task.trigger {
+            TaskTrigger::OnData { table, .. } => {
+                let sql = format!("SELECT max(time) FROM {}", table);
+                let latest = max_time(&self.query(task, sql).await?);
+                if latest <= state.watermark {
+                    return Ok(None);
+                }
+                latest
+            }
+            TaskTrigger::Schedule { .. } => None,
+        };
+
+        let sql = substitute(&task.query, state.last_run, now);
+        let rows = match &task.sink {
+            TaskSink::Table(table) => {
+                let sql = format!("INSERT INTO {} {}", table, sql);
+                written_rows(&self.query(task, sql).await?)
+            }
+            TaskSink::Webhook(url) => {
+                let batches = self.query(task, sql).await?;
+                let rows = batches.iter().map(|e| e.num_rows() as u64).sum::<u64>();
+                if rows > 0 {
+                    self.post(task, url, &batches).await?;
+                }
+                rows
+            }
+        };
+        Ok(Some((rows, latest)))
+    }
+
+    fn is_admin(&self, user: &str) -> Result<bool, String> {
+        let user = self.meta.user(user).map_err(|e| e.to_string())?;
+        Ok(matches!(user, Some(user) if user.is_admin))
+    }
+
+    /// Runs the sql in the database of the task as the owner of it
+    async fn query(&self, task: &TaskDefinition, sql: String) -> Result<Vec<RecordBatch>, String> {
+        let user_info = UserInfo {
+            user: task.owner.clone(),
+            password: String::new(),
+        };
+        let context = ContextBuilder::new(user_info)
+            .with_database(Some(task.database.clone()))
+            .build();
+        let query = Query::new(context, sql);
+        let id = self.dispatcher.create_query_id();
+        let outputs = self
+            .dispatcher
+            .execute_query(id, &query)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(outputs
+            .into_iter()
+            .flat_map(|e| match e {
+                Output::StreamData(batches) => batches,
+                Output::Nil(_) => vec![],
+            })
+            .collect())
+    }
+
+    async fn post(
+        &self,
+        task: &TaskDefinition,
+        url: &str,
+        batches: &[RecordBatch],
+    ) -> Result<(), String> {
+        let rows = JsonSerializer
+            .serialize(batches)
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| {
+                serde_json::from_slice::<serde_json::Value>(&bytes).map_err(|e| e.to_string())
+            })?;
+        let body = serde_json::json!({ "task": task.name, "rows": rows });
+        self.http
+            .post(url)
+            .json(&body)
+            .send()
+            .await
+            .and_then(|e| e.error_for_status())
+            .map(|_| ())
+            .map_err(|e| format!("failed to post to {}: {}", url, e))
+    }
+}
+
+fn key(task: &TaskDefinition) -> (String, String) {
+    (task.tenant.clone(), task.name.clone())
+}
+
+fn now() -> Timestamp {
+    Utc::now().timestamp_nanos()
+}
+
+/// The placeholders are replaced by timestamp literals
+fn substitute(query: &str, last_run: Timestamp, now: Timestamp) -> String {
+    query
+        .replace(
+            LAST_RUN_PLACEHOLDER,
+            &format!("CAST({} AS TIMESTAMP)", last_run),
+        )
+        .replace(NOW_PLACEHOLDER, &format!("CAST({} AS TIMESTAMP)", now))
+}
+
+/// The node of the sorted nodes running the task, FNV-1a is stable across the nodes
+fn placement(task: &TaskDefinition, nodes: &[NodeId]) -> Option<NodeId> {
+    if nodes.is_empty() {
+        return None;
+    }
+    let hash = format!("{}/{}", task.tenant, task.name)
+        .bytes()
+        .fold(0xcbf29ce484222325_u64, |hash, b| {
+            (hash ^ b as u64).wrapping_mul(0x100000001b3)
+        });
+    Some(nodes[(hash % nodes.len() as u64) as usize])
+}
+
+/// The result of `SELECT max(time)`
+fn max_time(batches: &[RecordBatch]) -> Option<Timestamp> {
+    batches
+        .iter()
+        .filter(|e| e.num_columns() > 0)
+        .filter_map(|e| {
+            e.column(0)
+                .as_any()
+                .downcast_ref::<TimestampNanosecondArray>()
+        })
+        .flat_map(|e| e.iter().flatten())
+        .max()
+}
+
+/// The result of `INSERT`, the rows written by every partition
+fn written_rows(batches: &[RecordBatch]) -> u64 {
+    batches
+        .iter()
+        .filter(|e| e.num_columns() > 0)
+        .filter_map(|e| e.column(0).as_any().downcast_ref::<UInt64Array>())
+        .flat_map(|e| e.iter().flatten())
+        .sum()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn task(name: &str) -> TaskDefinition {
+        TaskDefinition {
+            name: name.to_string(),
+            tenant: "cnosdb".to_string(),
+            database: "public".to_string(),
+            owner: "cnosdb".to_string(),
+            query: "SELECT * FROM air WHERE time > $last_run AND time <= $now".to_string(),
+            trigger: TaskTrigger::Schedule { interval: 60 },
+            sink: TaskSink::Table("air_copy".to_string()),
+            enabled: true,
+        }
+    }
+
+    #[test]
+    fn test_substitute() {
+        assert_eq!(
+            substitute(&task("t0").query, 0, 1000),
+            "SELECT * FROM air WHERE time > CAST(0 AS TIMESTAMP) AND time <= CAST(1000 AS TIMESTAMP)"
+        );
+    }
+
+    #[test]
+    fn test_placement() {
+        assert_eq!(placement(&task("t0"), &[]), None);
+        let nodes = [1, 2, 3];
+        let placed = (0..30)
+            .map(|i| placement(&task(&format!("t{}", i)), &nodes).unwrap())
+            .collect::<Vec<_>>();
+        assert!(nodes.iter().all(|n| placed.contains(n)));
+        assert_eq!(placement(&task("t0"), &nodes), Some(placed[0]));
+    }
+
+    #[test]
+    fn test_task_run_log() {
+        let runs = TaskRunLog::default();
+        for tenant in ["cnosdb", "other"] {
+            runs.record(TaskRun {
+                tenant: tenant.to_string(),
+                task: "t0".to_string(),
+                started_at: 1000,
+                duration_ms: 5,
+                state: TaskRunState::Failed,
+                rows: 0,
+                error: Some("table air not exists".to_string()),
+            });
+        }
+        let batch = runs.record_batch("cnosdb").unwrap();
+        assert_eq!(batch.num_rows(), 1);
+        assert_eq!(batch.schema(), TaskRunLog::schema());
+    }
+
+    #[test]
+    fn test_max_time() {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "max(time)",
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            true,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![Arc::new(TimestampNanosecondArray::from(vec![
+                None,
+                Some(7),
+            ]))],
+        )
+        .unwrap();
+        assert_eq!(max_time(&[batch]), Some(7));
+        assert_eq!(max_time(&[]), None);
+    }
+}
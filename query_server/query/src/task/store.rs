@@ -0,0 +1,324 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use meta::error::MetaResult;
+use meta::meta_client::MetaClientRef;
+use models::task::{TaskDefinition, TaskProgress};
+use parking_lot::RwLock;
+use serde::Serialize;
+use spi::catalog::{MetadataError, Result};
+use trace::warn;
+
+use crate::auth::{load, save};
+use crate::metadata::meta_error;
+
+pub type TaskStoreRef = Arc<dyn TaskStore>;
+
+/// File of the tasks in the standalone mode
+pub const TASKS_FILE: &str = "tasks.json";
+/// File of the progresses of the tasks in the standalone mode
+pub const TASK_PROGRESSES_FILE: &str = "task_progresses.json";
+/// Tasks read from the meta service are cached, those changed by the other nodes
+/// are seen once they are reloaded
+const TASKS_RELOAD_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Definitions of the tasks of all the tenants
+#[async_trait]
+pub trait TaskStore: Send + Sync {
+    async fn create_task(&self, task: TaskDefinition) -> Result<()>;
+    async fn alter_task(&self, task: TaskDefinition) -> Result<()>;
+    async fn drop_task(&self, tenant: &str, name: &str) -> Result<()>;
+    fn tasks(&self) -> Result<Vec<TaskDefinition>>;
+    /// Saves the progress of the task after a run, the progress of a task dropped
+    /// is discarded
+    async fn save_progress(&self, progress: TaskProgress) -> Result<()>;
+    fn progresses(&self) -> Result<Vec<TaskProgress>>;
+}
+
+type TaskKey = (String, String);
+
+/// Tasks of the standalone mode, saved to the json files of the directory
+pub struct LocalTaskStore {
+    dir: Option<PathBuf>,
+    /// (tenant, name) -> task
+    tasks: RwLock<BTreeMap<TaskKey, TaskDefinition>>,
+    /// (tenant, name) -> progress
+    progresses: RwLock<BTreeMap<TaskKey, TaskProgress>>,
+}
+
+impl LocalTaskStore {
+    /// Keeps the tasks in memory only
+    pub fn memory() -> Self {
+        Self {
+            dir: None,
+            tasks: RwLock::new(BTreeMap::new()),
+            progresses: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    /// Saves the tasks to the files in the directory, the tasks saved before are loaded
+    pub fn open(dir: impl AsRef<Path>) -> std::io::Result<Self> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+        let tasks: Vec<TaskDefinition> = load(&dir.join(TASKS_FILE))?;
+        let progresses: Vec<TaskProgress> = load(&dir.join(TASK_PROGRESSES_FILE))?;
+
+        Ok(Self {
+            dir: Some(dir.to_path_buf()),
+            tasks: RwLock::new(
+                tasks
+                    .into_iter()
+                    .map(|e| ((e.tenant.clone(), e.name.clone()), e))
+                    .collect(),
+            ),
+            progresses: RwLock::new(
+                progresses
+                    .into_iter()
+                    .map(|e| ((e.tenant.clone(), e.task.clone()), e))
+                    .collect(),
+            ),
+        })
+    }
+
+    fn save<T: Serialize>(&self, file: &str, values: &BTreeMap<TaskKey, T>) -> Result<()> {
+        match &self.dir {
+            Some(dir) => save(&dir.join(file), values.values().collect()),
+            None => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl TaskStore for LocalTaskStore {
+    async fn create_task(&self, task: TaskDefinition) -> Result<()> {
+        let mut tasks = self.tasks.write();
+        let key = (task.tenant.clone(), task.name.clone());
+        if tasks.contains_key(&key) {
+            return Err(MetadataError::TaskAlreadyExists {
+                task_name: task.name,
+            });
+        }
+        let mut new_tasks = tasks.clone();
+        new_tasks.insert(key, task);
+        self.save(TASKS_FILE, &new_tasks)?;
+        *tasks = new_tasks;
+        Ok(())
+    }
+
+    async fn alter_task(&self, task: TaskDefinition) -> Result<()> {
+        let mut tasks = self.tasks.write();
+        let key = (task.tenant.clone(), task.name.clone());
+        if !tasks.contains_key(&key) {
+            return Err(MetadataError::TaskNotExists {
+                task_name: task.name,
+            });
+        }
+        let mut new_tasks = tasks.clone();
+        new_tasks.insert(key, task);
+        self.save(TASKS_FILE, &new_tasks)?;
+        *tasks = new_tasks;
+        Ok(())
+    }
+
+    async fn drop_task(&self, tenant: &str, name: &str) -> Result<()> {
+        let mut tasks = self.tasks.write();
+        let key = (tenant.to_string(), name.to_string());
+        if !tasks.contains_key(&key) {
+            return Err(MetadataError::TaskNotExists {
+                task_name: name.to_string(),
+            });
+        }
+        let mut new_tasks = tasks.clone();
+        new_tasks.remove(&key);
+        self.save(TASKS_FILE, &new_tasks)?;
+        *tasks = new_tasks;
+
+        let mut progresses = self.progresses.write();
+        if progresses.contains_key(&key) {
+            let mut new_progresses = progresses.clone();
+            new_progresses.remove(&key);
+            self.save(TASK_PROGRESSES_FILE, &new_progresses)?;
+            *progresses = new_progresses;
+        }
+        Ok(())
+    }
+
+    fn tasks(&self) -> Result<Vec<TaskDefinition>> {
+        Ok(self.tasks.read().values().cloned().collect())
+    }
+
+    async fn save_progress(&self, progress: TaskProgress) -> Result<()> {
+        // Locked before the progresses, as the tasks are dropped
+        let tasks = self.tasks.read();
+        let key = (progress.tenant.clone(), progress.task.clone());
+        if !tasks.contains_key(&key) {
+            return Err(MetadataError::TaskNotExists {
+                task_name: progress.task,
+            });
+        }
+        let mut progresses = self.progresses.write();
+        let mut new_progresses = progresses.clone();
+        new_progresses.insert(key, progress);
+        self.save(TASK_PROGRESSES_FILE, &new_progresses)?;
+        *progresses = new_progresses;
+        Ok(())
+    }
+
+    fn progresses(&self) -> Result<Vec<TaskProgress>> {
+        Ok(self.progresses.read().values().cloned().collect())
+    }
+}
+
+/// Tasks of a cluster, owned by the meta service
+pub struct RemoteTaskStore {
+    client: MetaClientRef,
+    tasks: RwLock<Vec<TaskDefinition>>,
+    progresses: RwLock<Vec<TaskProgress>>,
+}
+
+impl RemoteTaskStore {
+    /// The tasks are loaded before it returns and reloaded in the background,
+    /// so that they are read without waiting for the meta service
+    pub async fn open(client: MetaClientRef) -> Result<Arc<Self>> {
+        let store = Arc::new(Self {
+            client,
+            tasks: RwLock::new(vec![]),
+            progresses: RwLock::new(vec![]),
+        });
+        store.reload().await?;
+
+        let weak = Arc::downgrade(&store);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(TASKS_RELOAD_INTERVAL);
+            loop {
+                ticker.tick().await;
+                let store = match weak.upgrade() {
+                    Some(store) => store,
+                    None => return,
+                };
+                if let Err(e) = store.reload().await {
+                    warn!("Failed to reload tasks: {}", e);
+                }
+            }
+        });
+        Ok(store)
+    }
+
+    async fn reload(&self) -> Result<()> {
+        let tasks = self.client.tasks().await.map_err(meta_error)?;
+        let progresses = self.client.task_progresses().await.map_err(meta_error)?;
+        *self.tasks.write() = tasks;
+        *self.progresses.write() = progresses;
+        Ok(())
+    }
+
+    /// The change is seen by the reads of this node once it returns
+    async fn changed(&self, res: MetaResult<()>) -> Result<()> {
+        res.map_err(meta_error)?;
+        self.reload().await
+    }
+}
+
+#[async_trait]
+impl TaskStore for RemoteTaskStore {
+    async fn create_task(&self, task: TaskDefinition) -> Result<()> {
+        self.changed(self.client.create_task(&task).await).await
+    }
+
+    async fn alter_task(&self, task: TaskDefinition) -> Result<()> {
+        self.changed(self.client.alter_task(&task).await).await
+    }
+
+    async fn drop_task(&self, tenant: &str, name: &str) -> Result<()> {
+        self.changed(self.client.drop_task(tenant, name).await)
+            .await
+    }
+
+    fn tasks(&self) -> Result<Vec<TaskDefinition>> {
+        Ok(self.tasks.read().clone())
+    }
+
+    async fn save_progress(&self, progress: TaskProgress) -> Result<()> {
+        self.client
+            .set_task_progress(&progress)
+            .await
+            .map_err(meta_error)?;
+        // Seen by the reads of this node before the next reload
+        let mut progresses = self.progresses.write();
+        progresses.retain(|e| !(e.tenant == progress.tenant && e.task == progress.task));
+        progresses.push(progress);
+        Ok(())
+    }
+
+    fn progresses(&self) -> Result<Vec<TaskProgress>> {
+        Ok(self.progresses.read().clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use models::task::{TaskSink, TaskTrigger};
+
+    use super::*;
+
+    fn task(tenant: &str, name: &str) -> TaskDefinition {
+        TaskDefinition {
+            name: name.to_string(),
+            tenant: tenant.to_string(),
+            database: "public".to_string(),
+            owner: tenant.to_string(),
+            query: "SELECT 1".to_string(),
+            trigger: TaskTrigger::Schedule { interval: 60 },
+            sink: TaskSink::Table("t".to_string()),
+            enabled: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_local_task_store() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let store = LocalTaskStore::open(dir.path()).unwrap();
+            store.create_task(task("cnosdb", "t0")).await.unwrap();
+            store.create_task(task("other", "t0")).await.unwrap();
+            assert!(matches!(
+                store.create_task(task("cnosdb", "t0")).await,
+                Err(MetadataError::TaskAlreadyExists { .. })
+            ));
+
+            let mut disabled = task("cnosdb", "t0");
+            disabled.enabled = false;
+            store.alter_task(disabled).await.unwrap();
+            store.drop_task("other", "t0").await.unwrap();
+            assert!(matches!(
+                store.drop_task("other", "t0").await,
+                Err(MetadataError::TaskNotExists { .. })
+            ));
+        }
+
+        {
+            let store = LocalTaskStore::open(dir.path()).unwrap();
+            let tasks = store.tasks().unwrap();
+            assert_eq!(tasks.len(), 1);
+            assert!(!tasks[0].enabled);
+
+            let mut progress = TaskProgress::new("cnosdb", "t0");
+            progress.last_run = 1000;
+            store.save_progress(progress).await.unwrap();
+            assert!(matches!(
+                store.save_progress(TaskProgress::new("other", "t0")).await,
+                Err(MetadataError::TaskNotExists { .. })
+            ));
+        }
+
+        let store = LocalTaskStore::open(dir.path()).unwrap();
+        let progresses = store.progresses().unwrap();
+        assert_eq!(progresses.len(), 1);
+        assert_eq!(progresses[0].last_run, 1000);
+        store.drop_task("cnosdb", "t0").await.unwrap();
+        assert!(store.progresses().unwrap().is_empty());
+    }
+}
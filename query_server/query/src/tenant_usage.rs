@@ -0,0 +1,52 @@
+use std::sync::Arc;
+
+use datafusion::arrow::array::{StringBuilder, UInt64Builder};
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use datafusion::arrow::error::ArrowError;
+use datafusion::arrow::record_batch::RecordBatch;
+use metrics::tenant_usage::tenant_usage;
+
+/// Table of the writes of the tenants on this node since started and the data they
+/// store, `SELECT * FROM system.tenant_usage`
+pub const TENANT_USAGE_TABLE: &str = "tenant_usage";
+
+pub fn schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("tenant", DataType::Utf8, false),
+        Field::new("points_written", DataType::UInt64, false),
+        Field::new("bytes_written", DataType::UInt64, false),
+        Field::new("writes_rate_limited", DataType::UInt64, false),
+        Field::new("writes_over_quota", DataType::UInt64, false),
+        Field::new("stored_bytes", DataType::UInt64, false),
+    ]))
+}
+
+pub fn record_batch() -> Result<RecordBatch, ArrowError> {
+    let usage = tenant_usage();
+    let mut tenants = StringBuilder::new();
+    let mut points_written = UInt64Builder::with_capacity(usage.len());
+    let mut bytes_written = UInt64Builder::with_capacity(usage.len());
+    let mut writes_rate_limited = UInt64Builder::with_capacity(usage.len());
+    let mut writes_over_quota = UInt64Builder::with_capacity(usage.len());
+    let mut stored_bytes = UInt64Builder::with_capacity(usage.len());
+    for (tenant, e) in usage.iter() {
+        tenants.append_value(tenant);
+        points_written.append_value(e.points_written);
+        bytes_written.append_value(e.bytes_written);
+        writes_rate_limited.append_value(e.writes_rate_limited);
+        writes_over_quota.append_value(e.writes_over_quota);
+        stored_bytes.append_value(e.stored_bytes);
+    }
+
+    RecordBatch::try_new(
+        schema(),
+        vec![
+            Arc::new(tenants.finish()),
+            Arc::new(points_written.finish()),
+            Arc::new(bytes_written.finish()),
+            Arc::new(writes_rate_limited.finish()),
+            Arc::new(writes_over_quota.finish()),
+            Arc::new(stored_bytes.finish()),
+        ],
+    )
+}
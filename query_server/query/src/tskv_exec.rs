@@ -5,20 +5,25 @@ use std::{
 };
 
 use datafusion::{
-    arrow::datatypes::SchemaRef,
+    arrow::{datatypes::SchemaRef, error::ArrowError},
     error::{DataFusionError, Result},
     execution::context::TaskContext,
     physical_expr::PhysicalSortExpr,
     physical_plan::{
-        metrics::ExecutionPlanMetricsSet, DisplayFormatType, ExecutionPlan, Partitioning,
-        SendableRecordBatchStream, Statistics,
+        metrics::ExecutionPlanMetricsSet, stream::RecordBatchStreamAdapter, ColumnStatistics,
+        DisplayFormatType, ExecutionPlan, Partitioning, SendableRecordBatchStream, Statistics,
     },
+    scalar::ScalarValue,
 };
 use models::predicate::domain::PredicateRef;
 use models::schema::TskvTableSchema;
 
+use futures::TryStreamExt;
+
+use crate::extension::physical::plan_node::runtime_filter::RuntimeFilterRef;
 use crate::stream::{TableScanMetrics, TableScanStream};
 use tskv::engine::EngineRef;
+use tskv::tseries_family::TimeRange;
 
 #[derive(Debug, Clone)]
 pub struct TskvExec {
@@ -28,6 +33,10 @@ pub struct TskvExec {
     proj_schema: SchemaRef,
     filter: PredicateRef,
     engine: EngineRef,
+    /// The tag filtered by the join keys of a hash join this scan is probed by
+    runtime_filter: Option<(String, RuntimeFilterRef)>,
+    /// The time range of the files and the caches scanned, bounds the statistics
+    time_range: Option<TimeRange>,
 
     /// Execution metrics
     metrics: ExecutionPlanMetricsSet,
@@ -47,12 +56,36 @@ impl TskvExec {
             proj_schema,
             filter,
             engine,
+            runtime_filter: None,
+            time_range: None,
             metrics,
         }
     }
+
+    pub(crate) fn with_time_range(mut self, time_range: Option<TimeRange>) -> Self {
+        self.time_range = time_range;
+        self
+    }
     pub fn filter(&self) -> PredicateRef {
         self.filter.clone()
     }
+
+    /// The scan skipping the series the values of the tag of which are not join keys,
+    /// None if the column is not a tag
+    pub(crate) fn with_runtime_filter(
+        &self,
+        column: &str,
+        filter: RuntimeFilterRef,
+    ) -> Option<Self> {
+        let column = self.table_schema.column(column)?;
+        if !column.column_type.is_tag() {
+            return None;
+        }
+        Some(Self {
+            runtime_filter: Some((column.name.clone(), filter)),
+            ..self.clone()
+        })
+    }
 }
 
 impl ExecutionPlan for TskvExec {
@@ -85,6 +118,8 @@ impl ExecutionPlan for TskvExec {
             proj_schema: self.proj_schema.clone(),
             filter: self.filter.clone(),
             engine: self.engine.clone(),
+            runtime_filter: self.runtime_filter.clone(),
+            time_range: self.time_range,
             metrics: self.metrics.clone(),
         }))
     }
@@ -98,17 +133,51 @@ impl ExecutionPlan for TskvExec {
 
         let metrics = TableScanMetrics::new(&self.metrics, partition);
 
-        let table_stream = TableScanStream::new(
+        let (tag, runtime_filter) = match &self.runtime_filter {
+            Some(runtime_filter) => runtime_filter.clone(),
+            None => {
+                let table_stream = TableScanStream::new(
+                    self.table_schema.clone(),
+                    self.schema(),
+                    self.filter(),
+                    batch_size,
+                    self.engine.clone(),
+                    metrics,
+                    None,
+                )
+                .map_err(|err| DataFusionError::External(Box::new(err)))?;
+
+                return Ok(Box::pin(table_stream));
+            }
+        };
+
+        // The series are looked up on the first poll, after the build side of the
+        // join is read
+        let (table_schema, schema, filter, engine) = (
             self.table_schema.clone(),
             self.schema(),
             self.filter(),
-            batch_size,
             self.engine.clone(),
-            metrics,
-        )
-        .map_err(|err| DataFusionError::External(Box::new(err)))?;
+        );
+        let table_stream = futures::stream::once(async move {
+            let join_keys = runtime_filter.get().map(|keys| (tag, keys));
+            TableScanStream::new(
+                table_schema,
+                schema,
+                filter,
+                batch_size,
+                engine,
+                metrics,
+                join_keys,
+            )
+            .map_err(|err| ArrowError::ExternalError(Box::new(err)))
+        })
+        .try_flatten();
 
-        Ok(Box::pin(table_stream))
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            self.schema(),
+            table_stream,
+        )))
     }
 
     fn fmt_as(&self, t: DisplayFormatType, f: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -126,14 +195,17 @@ impl ExecutionPlan for TskvExec {
                     "TskvExec: {}, projection=[{}]",
                     PredicateDisplay(&filter),
                     fields.join(","),
-                )
+                )?;
+                if let Some((tag, _)) = &self.runtime_filter {
+                    write!(f, ", runtime_filter={}", tag)?;
+                }
+                Ok(())
             }
         }
     }
 
     fn statistics(&self) -> Statistics {
-        // TODO
-        Statistics::default()
+        scan_statistics(&self.table_schema, &self.proj_schema, self.time_range)
     }
 
     fn metrics(&self) -> Option<datafusion::physical_plan::metrics::MetricsSet> {
@@ -141,6 +213,50 @@ impl ExecutionPlan for TskvExec {
     }
 }
 
+/// The statistics of a scan of the table, collected by `ANALYZE TABLE`, the time
+/// of which is bounded by the time range of the data scanned if known.
+///
+/// They are never exact, the rows written or deleted since analyzed and the
+/// filters pushed down are not counted.
+pub(crate) fn scan_statistics(
+    table_schema: &TskvTableSchema,
+    proj_schema: &SchemaRef,
+    time_range: Option<TimeRange>,
+) -> Statistics {
+    let statistics = table_schema.statistics();
+    let (min_time, max_time) = match (statistics, time_range) {
+        (_, Some(time_range)) => (Some(time_range.min_ts), Some(time_range.max_ts)),
+        (Some(statistics), None) => (statistics.min_time, statistics.max_time),
+        (None, None) => return Statistics::default(),
+    };
+
+    let column_statistics = proj_schema
+        .fields()
+        .iter()
+        .map(|field| match table_schema.column(field.name()) {
+            Some(column) if column.column_type.is_time() => ColumnStatistics {
+                min_value: min_time.map(|e| ScalarValue::TimestampNanosecond(Some(e), None)),
+                max_value: max_time.map(|e| ScalarValue::TimestampNanosecond(Some(e), None)),
+                ..Default::default()
+            },
+            Some(column) if column.column_type.is_tag() => ColumnStatistics {
+                distinct_count: statistics
+                    .and_then(|e| e.tag_ndvs.get(&column.name))
+                    .map(|e| *e as usize),
+                ..Default::default()
+            },
+            _ => ColumnStatistics::default(),
+        })
+        .collect();
+
+    Statistics {
+        num_rows: statistics.map(|e| e.row_count as usize),
+        total_byte_size: None,
+        column_statistics: Some(column_statistics),
+        is_exact: false,
+    }
+}
+
 /// A wrapper to customize PredicateRef display
 #[derive(Debug)]
 struct PredicateDisplay<'a>(&'a PredicateRef);
@@ -156,3 +272,74 @@ impl<'a> Display for PredicateDisplay<'a> {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use models::schema::{ColumnType, TableColumn, TableStatistics};
+    use models::{codec::Encoding, ValueType};
+
+    use super::*;
+
+    #[test]
+    fn test_scan_statistics() {
+        let mut schema = TskvTableSchema::new(
+            "public".to_string(),
+            "m".to_string(),
+            vec![
+                TableColumn::new_time_column(0),
+                TableColumn::new_tag_column(1, "t0".to_string()),
+                TableColumn::new(
+                    2,
+                    "f0".to_string(),
+                    ColumnType::Field(ValueType::Float),
+                    Encoding::Default,
+                ),
+            ],
+        );
+        let proj_schema = schema.to_arrow_schema();
+        assert_eq!(
+            scan_statistics(&schema, &proj_schema, None),
+            Statistics::default()
+        );
+
+        schema.set_statistics(TableStatistics {
+            analyzed_at: 0,
+            row_count: 100,
+            min_time: Some(1),
+            max_time: Some(99),
+            tag_ndvs: BTreeMap::from([("t0".to_string(), 10)]),
+        });
+        let statistics = scan_statistics(&schema, &proj_schema, None);
+        assert_eq!(statistics.num_rows, Some(100));
+        assert!(!statistics.is_exact);
+        assert_eq!(
+            statistics.column_statistics.unwrap(),
+            vec![
+                ColumnStatistics {
+                    min_value: Some(ScalarValue::TimestampNanosecond(Some(1), None)),
+                    max_value: Some(ScalarValue::TimestampNanosecond(Some(99), None)),
+                    ..Default::default()
+                },
+                ColumnStatistics {
+                    distinct_count: Some(10),
+                    ..Default::default()
+                },
+                ColumnStatistics::default(),
+            ]
+        );
+
+        // The time range of the data scanned is preferred
+        let statistics = scan_statistics(&schema, &proj_schema, Some(TimeRange::new(10, 20)));
+        assert_eq!(statistics.num_rows, Some(100));
+        assert_eq!(
+            statistics.column_statistics.unwrap()[0],
+            ColumnStatistics {
+                min_value: Some(ScalarValue::TimestampNanosecond(Some(10), None)),
+                max_value: Some(ScalarValue::TimestampNanosecond(Some(20), None)),
+                ..Default::default()
+            }
+        );
+    }
+}
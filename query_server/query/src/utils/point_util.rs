@@ -1,13 +1,17 @@
+use std::sync::Arc;
+
 use datafusion::arrow::{
     array::{
         Array, ArrayRef, BooleanArray, Float64Array, Int64Array, StringArray,
         TimestampMicrosecondArray, TimestampMillisecondArray, TimestampNanosecondArray,
         TimestampSecondArray, UInt64Array,
     },
-    datatypes::{DataType as ArrowDataType, Field, TimeUnit},
+    compute::cast,
+    datatypes::{DataType as ArrowDataType, Field, Schema, TimeUnit},
     record_batch::RecordBatch,
 };
 use flatbuffers::{self, FlatBufferBuilder, Vector, WIPOffset};
+use models::arrow_array::unpack_dictionaries;
 use models::schema::{is_time_column, ColumnType, TableColumn, TskvTableSchema, TIME_FIELD_NAME};
 use models::{define_result, ValueType};
 use paste::paste;
@@ -103,6 +107,10 @@ pub fn record_batch_to_points_flat_buffer(
 ) -> Result<Vec<u8>> {
     let mut fbb = FlatBufferBuilder::new();
 
+    let record_batch =
+        &unpack_dictionaries(record_batch).map_err(|err| PointUtilError::ToPointsFlatBuffer {
+            err: err.to_string(),
+        })?;
     let record_schema = record_batch.schema();
     let column_schemas = record_schema.fields();
 
@@ -148,6 +156,43 @@ pub fn record_batch_to_points_flat_buffer(
     )
 }
 
+/// Casts the columns of the record batch written by a client to the types of the
+/// columns of the same names of the table, e.g. the timestamps of other units and
+/// the tags not dictionary encoded
+pub fn cast_record_batch_to_table(
+    record_batch: &RecordBatch,
+    table_schema: &TskvTableSchema,
+) -> Result<RecordBatch> {
+    let record_schema = record_batch.schema();
+    let mut fields = Vec::with_capacity(record_schema.fields().len());
+    let mut columns = Vec::with_capacity(record_schema.fields().len());
+    for (field, array) in record_schema.fields().iter().zip(record_batch.columns()) {
+        let column =
+            table_schema
+                .column(field.name())
+                .ok_or_else(|| PointUtilError::ColumnNotFound {
+                    col: field.name().clone(),
+                })?;
+        let data_type = ArrowDataType::from(column.column_type);
+        let array = if array.data_type() == &data_type {
+            array.clone()
+        } else {
+            cast(array, &data_type).map_err(|_| PointUtilError::InvalidArrayType {
+                expected: data_type.to_string(),
+                found: array.data_type().to_string(),
+            })?
+        };
+        fields.push(Field::new(field.name(), data_type, field.is_nullable()));
+        columns.push(array);
+    }
+
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).map_err(|err| {
+        PointUtilError::ToPointsFlatBuffer {
+            err: err.to_string(),
+        }
+    })
+}
+
 /// Construct row-based points flatbuffer from column-based data wip_offset
 fn construct_row_based_points(
     fbb: &mut FlatBufferBuilder,
@@ -284,3 +329,80 @@ macro_rules! define_extract_time_column_from_func {
 }
 
 define_extract_time_column_from_func!(Second, Millisecond, Microsecond, Nanosecond);
+
+#[cfg(test)]
+mod tests {
+    use datafusion::arrow::array::DictionaryArray;
+    use datafusion::arrow::datatypes::Int32Type;
+    use models::schema::TableColumn;
+
+    use super::*;
+
+    fn table_schema() -> TskvTableSchema {
+        TskvTableSchema::new(
+            "db".to_string(),
+            "cpu".to_string(),
+            vec![
+                TableColumn::new_time_column(0),
+                TableColumn::new_tag_column(1, "host".to_string()),
+                TableColumn::new_with_default(
+                    "usage".to_string(),
+                    ColumnType::Field(ValueType::Float),
+                ),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_cast_record_batch_to_table() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(
+                "time",
+                ArrowDataType::Timestamp(TimeUnit::Millisecond, None),
+                false,
+            ),
+            Field::new("host", ArrowDataType::Utf8, true),
+            Field::new("usage", ArrowDataType::Int64, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(TimestampMillisecondArray::from(vec![1, 2])),
+                Arc::new(StringArray::from(vec![Some("a"), None])),
+                Arc::new(Int64Array::from(vec![Some(1), Some(2)])),
+            ],
+        )
+        .unwrap();
+
+        let batch = cast_record_batch_to_table(&batch, &table_schema()).unwrap();
+        let times = cast_arrow_array::<TimestampNanosecondArray>(batch.column(0)).unwrap();
+        assert_eq!(times.values(), &[1_000_000, 2_000_000]);
+        let hosts = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<DictionaryArray<Int32Type>>()
+            .unwrap();
+        assert!(hosts.is_null(1));
+        let usages = cast_arrow_array::<Float64Array>(batch.column(2)).unwrap();
+        assert_eq!(usages.values(), &[1.0, 2.0]);
+
+        let points = record_batch_to_points_flat_buffer(&batch, table_schema()).unwrap();
+        let points = flatbuffers::root::<Points>(&points).unwrap();
+        assert_eq!(points.points().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_cast_record_batch_to_table_unknown_column() {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "region",
+            ArrowDataType::Utf8,
+            true,
+        )]));
+        let batch =
+            RecordBatch::try_new(schema, vec![Arc::new(StringArray::from(vec!["a"]))]).unwrap();
+        assert!(matches!(
+            cast_record_batch_to_table(&batch, &table_schema()),
+            Err(PointUtilError::ColumnNotFound { .. })
+        ));
+    }
+}
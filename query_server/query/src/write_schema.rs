@@ -0,0 +1,734 @@
+//! The tables and the columns of the points not in the database are created
+//! before the points are written, if the database creates the schema on write.
+//! The timestamps out of the window allowed and the fields the types of which
+//! differ from the columns are resolved before by the policy of the database.
+
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use datafusion::sql::TableReference;
+use flatbuffers::FlatBufferBuilder;
+use models::schema::{
+    ColumnType, DatabaseOptions, FieldTypeConflict, OutOfWindow, TableColumn, TableSchema,
+    TskvTableSchema, TIME_FIELD_NAME,
+};
+use models::{ColumnId, ValueType};
+use protos::models as fb_models;
+use snafu::ResultExt;
+use spi::catalog::{MetaDataRef, MetadataError};
+use spi::server::dbms::{ConflictResolution, FieldConflict, TimestampOutOfWindow, WrittenPoints};
+use spi::server::{MetaDataSnafu, Result, ServerError};
+use tokio::sync::Mutex;
+
+/// Integers of the magnitude not greater are exact in f64
+const MAX_SAFE_INTEGER: u64 = 1 << f64::MANTISSA_DIGITS;
+
+/// Resolves the points by the policies of the databases: the timestamps out of the
+/// window allowed and the fields the types of which differ from the columns. The
+/// points are encoded again only if any of them is changed.
+pub(crate) fn resolve_points(
+    meta: &MetaDataRef,
+    points: Vec<u8>,
+    now: i64,
+) -> Result<WrittenPoints> {
+    resolve_points_by(
+        points,
+        now,
+        |db| stored_database(meta, db),
+        |db, tab| stored_table(meta, db, tab),
+    )
+}
+
+/// Same as `resolve_points`, the points are those of the table, e.g. the rows
+/// inserted by sql
+pub(crate) fn resolve_table_points(
+    options: &DatabaseOptions,
+    table: &TskvTableSchema,
+    points: Vec<u8>,
+    now: i64,
+) -> Result<WrittenPoints> {
+    resolve_points_by(
+        points,
+        now,
+        |_| Ok(options.clone()),
+        |_, _| Ok(Some(table.clone())),
+    )
+}
+
+fn resolve_points_by(
+    points: Vec<u8>,
+    now: i64,
+    mut database: impl FnMut(&[u8]) -> Result<DatabaseOptions>,
+    mut table: impl FnMut(&[u8], &[u8]) -> Result<Option<TskvTableSchema>>,
+) -> Result<WrittenPoints> {
+    let invalid = |reason: String| ServerError::SchemaOnWrite { reason };
+    let fb_points = flatbuffers::root::<fb_models::Points>(&points)
+        .map_err(|e| invalid(format!("invalid points: {}", e)))?;
+
+    let mut databases: HashMap<&[u8], DatabaseOptions> = HashMap::new();
+    let mut tables: HashMap<(&[u8], &[u8]), TskvTableSchema> = HashMap::new();
+    let mut rewrite = PointsRewrite::default();
+    let mut conflicts = vec![];
+    let mut out_of_window = vec![];
+    for (i, point) in fb_points.points().into_iter().flatten().enumerate() {
+        let (db, tab) = (
+            point.db().unwrap_or_default(),
+            point.tab().unwrap_or_default(),
+        );
+        let options = match databases.entry(db) {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(database(db)?),
+        };
+
+        let (min, max) = timestamp_window(options, now);
+        let timestamp = point.timestamp();
+        if timestamp < min || timestamp > max {
+            let clamped = match options.out_of_window_or_default() {
+                OutOfWindow::Reject => {
+                    rewrite.rejected.insert(i);
+                    None
+                }
+                OutOfWindow::Clamp => {
+                    let clamped = timestamp.clamp(min, max);
+                    rewrite.timestamps.insert(i, clamped);
+                    Some(clamped)
+                }
+            };
+            out_of_window.push(TimestampOutOfWindow {
+                point: i,
+                table: String::from_utf8_lossy(tab).to_string(),
+                timestamp,
+                clamped,
+            });
+            if clamped.is_none() {
+                continue;
+            }
+        }
+
+        // The columns of a new table are those of the points written before
+        let table = match tables.entry((db, tab)) {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(table(db, tab)?.unwrap_or_else(|| {
+                TskvTableSchema::new(
+                    String::from_utf8_lossy(db).to_string(),
+                    String::from_utf8_lossy(tab).to_string(),
+                    vec![TableColumn::new_time_column(0)],
+                )
+            })),
+        };
+        let policy = options.field_type_conflict_or_default();
+        let mut point_conflicts = resolve_field_conflicts(i, &point, table, policy, &mut rewrite);
+        if point_conflicts
+            .iter()
+            .any(|e| e.resolution == ConflictResolution::Rejected)
+        {
+            rewrite.rejected.insert(i);
+        } else {
+            add_written_columns(i, &point, table, &rewrite);
+        }
+        conflicts.append(&mut point_conflicts);
+    }
+
+    let points = if rewrite.is_empty() {
+        points
+    } else {
+        encode_rewritten_points(&fb_points, &rewrite)
+    };
+    Ok(WrittenPoints {
+        points,
+        conflicts,
+        out_of_window,
+    })
+}
+
+/// The changes of the points written
+#[derive(Default)]
+struct PointsRewrite {
+    /// Indexes of the points not written
+    rejected: HashSet<usize>,
+    /// The fields resolved by the indexes of the points and the fields
+    fields: HashMap<(usize, usize), ResolvedField>,
+    /// The timestamps clamped by the indexes of the points
+    timestamps: HashMap<usize, i64>,
+}
+
+impl PointsRewrite {
+    fn is_empty(&self) -> bool {
+        self.rejected.is_empty() && self.fields.is_empty() && self.timestamps.is_empty()
+    }
+}
+
+/// A field of a point written by the resolution of the conflict
+struct ResolvedField {
+    name: String,
+    type_: fb_models::FieldType,
+    value: Vec<u8>,
+}
+
+/// The timestamps allowed by the database, `now` in nanoseconds
+fn timestamp_window(options: &DatabaseOptions, now: i64) -> (i64, i64) {
+    let min = options
+        .past_tolerance()
+        .as_ref()
+        .map_or(i64::MIN, |e| now.saturating_sub(e.to_nanoseconds()));
+    let max = options
+        .future_tolerance()
+        .as_ref()
+        .map_or(i64::MAX, |e| now.saturating_add(e.to_nanoseconds()));
+    (min, max)
+}
+
+/// Resolves the fields of the point the types of which differ from the columns
+/// of the table
+fn resolve_field_conflicts(
+    i: usize,
+    point: &fb_models::Point,
+    table: &TskvTableSchema,
+    policy: FieldTypeConflict,
+    rewrite: &mut PointsRewrite,
+) -> Vec<FieldConflict> {
+    let mut conflicts = vec![];
+    for (j, field) in point.fields().into_iter().flatten().enumerate() {
+        let name = String::from_utf8_lossy(field.name().unwrap_or_default()).to_string();
+        let value_type = ValueType::from(field.type_());
+        let column_type = match table.column(&name) {
+            Some(column) if column.column_type != ColumnType::Field(value_type) => {
+                column.column_type
+            }
+            _ => continue,
+        };
+        let value = field.value().unwrap_or_default();
+        let resolution = match (policy, column_type) {
+            (FieldTypeConflict::Coerce, ColumnType::Field(column_value_type)) => {
+                match coerce(value, value_type, column_value_type) {
+                    Some(value) => {
+                        rewrite.fields.insert(
+                            (i, j),
+                            ResolvedField {
+                                name: name.clone(),
+                                type_: field_type(column_value_type),
+                                value,
+                            },
+                        );
+                        ConflictResolution::Coerced
+                    }
+                    None => ConflictResolution::Rejected,
+                }
+            }
+            (FieldTypeConflict::Suffix, _) => {
+                let column = format!("{}_{}", name, value_type.to_string().to_lowercase());
+                match table.column(&column) {
+                    Some(e) if e.column_type != ColumnType::Field(value_type) => {
+                        ConflictResolution::Rejected
+                    }
+                    _ => {
+                        rewrite.fields.insert(
+                            (i, j),
+                            ResolvedField {
+                                name: column.clone(),
+                                type_: field.type_(),
+                                value: value.to_vec(),
+                            },
+                        );
+                        ConflictResolution::Suffixed { column }
+                    }
+                }
+            }
+            _ => ConflictResolution::Rejected,
+        };
+        conflicts.push(FieldConflict {
+            point: i,
+            table: table.name.clone(),
+            field: name,
+            column_type,
+            value_type,
+            resolution,
+        });
+    }
+    conflicts
+}
+
+/// Adds the columns of the point written not in the table, so that the points after
+/// it in the same write are resolved by them, e.g. those of a new table
+fn add_written_columns(
+    i: usize,
+    point: &fb_models::Point,
+    table: &mut TskvTableSchema,
+    rewrite: &PointsRewrite,
+) {
+    let name = |v: Option<&[u8]>| String::from_utf8_lossy(v.unwrap_or_default()).to_string();
+    let tags = point
+        .tags()
+        .into_iter()
+        .flatten()
+        .map(|tag| (name(tag.key()), ColumnType::Tag));
+    let fields = point
+        .fields()
+        .into_iter()
+        .flatten()
+        .enumerate()
+        .map(|(j, field)| match rewrite.fields.get(&(i, j)) {
+            Some(e) => (e.name.clone(), ColumnType::Field(ValueType::from(e.type_))),
+            None => (
+                name(field.name()),
+                ColumnType::Field(ValueType::from(field.type_())),
+            ),
+        });
+    for (name, column_type) in tags.chain(fields) {
+        if !table.contains_column(&name) {
+            let mut column = TableColumn::new_with_default(name, column_type);
+            column.id = table.columns().len() as ColumnId;
+            table.add_column(column);
+        }
+    }
+}
+
+/// The options of the database, the points of the databases not exist are rejected
+fn stored_database(meta: &MetaDataRef, db: &[u8]) -> Result<DatabaseOptions> {
+    meta.database(&String::from_utf8_lossy(db))
+        .map(|db| db.config)
+        .context(MetaDataSnafu)
+}
+
+/// The table in the database, `None` if the table not exists
+fn stored_table(meta: &MetaDataRef, db: &[u8], tab: &[u8]) -> Result<Option<TskvTableSchema>> {
+    let (db, tab) = (String::from_utf8_lossy(db), String::from_utf8_lossy(tab));
+    match meta
+        .with_database(&db)
+        .table(TableReference::from(tab.as_ref()))
+    {
+        Ok(TableSchema::TsKvTableSchema(table)) => Ok(Some(table)),
+        Ok(_) | Err(MetadataError::TableNotExists { .. }) => Ok(None),
+        Err(e) => Err(e).context(MetaDataSnafu),
+    }
+}
+
+/// The value converted to the type, `None` if the value changes by the conversion
+fn coerce(value: &[u8], from: ValueType, to: ValueType) -> Option<Vec<u8>> {
+    let bytes = <[u8; 8]>::try_from(value).ok()?;
+    let coerced = match (from, to) {
+        (ValueType::Integer, ValueType::Float) => {
+            let v = i64::from_be_bytes(bytes);
+            if v.unsigned_abs() > MAX_SAFE_INTEGER {
+                return None;
+            }
+            (v as f64).to_be_bytes()
+        }
+        (ValueType::Unsigned, ValueType::Float) => {
+            let v = u64::from_be_bytes(bytes);
+            if v > MAX_SAFE_INTEGER {
+                return None;
+            }
+            (v as f64).to_be_bytes()
+        }
+        (ValueType::Integer, ValueType::Unsigned) => {
+            u64::try_from(i64::from_be_bytes(bytes)).ok()?.to_be_bytes()
+        }
+        (ValueType::Unsigned, ValueType::Integer) => {
+            i64::try_from(u64::from_be_bytes(bytes)).ok()?.to_be_bytes()
+        }
+        (ValueType::Float, ValueType::Integer) => {
+            let v = f64::from_be_bytes(bytes);
+            if v.fract() != 0.0 || v.abs() > MAX_SAFE_INTEGER as f64 {
+                return None;
+            }
+            (v as i64).to_be_bytes()
+        }
+        (ValueType::Float, ValueType::Unsigned) => {
+            let v = f64::from_be_bytes(bytes);
+            if v.fract() != 0.0 || !(0.0..=MAX_SAFE_INTEGER as f64).contains(&v) {
+                return None;
+            }
+            (v as u64).to_be_bytes()
+        }
+        _ => return None,
+    };
+    Some(coerced.to_vec())
+}
+
+fn field_type(value_type: ValueType) -> fb_models::FieldType {
+    match value_type {
+        ValueType::Float => fb_models::FieldType::Float,
+        ValueType::Integer => fb_models::FieldType::Integer,
+        ValueType::Unsigned => fb_models::FieldType::Unsigned,
+        ValueType::Boolean => fb_models::FieldType::Boolean,
+        ValueType::String => fb_models::FieldType::String,
+        ValueType::Unknown => fb_models::FieldType::Unknown,
+    }
+}
+
+/// Encodes the points not rejected, with the fields and the timestamps resolved
+fn encode_rewritten_points(fb_points: &fb_models::Points, rewrite: &PointsRewrite) -> Vec<u8> {
+    let mut fbb = FlatBufferBuilder::new();
+    let mut point_offsets = vec![];
+    for (i, point) in fb_points.points().into_iter().flatten().enumerate() {
+        if rewrite.rejected.contains(&i) {
+            continue;
+        }
+        let mut tags = vec![];
+        for tag in point.tags().into_iter().flatten() {
+            let args = fb_models::TagArgs {
+                key: tag.key().map(|e| fbb.create_vector(e)),
+                value: tag.value().map(|e| fbb.create_vector(e)),
+            };
+            tags.push(fb_models::Tag::create(&mut fbb, &args));
+        }
+        let mut fields = vec![];
+        for (j, field) in point.fields().into_iter().flatten().enumerate() {
+            let args = match rewrite.fields.get(&(i, j)) {
+                Some(e) => fb_models::FieldArgs {
+                    name: Some(fbb.create_vector(e.name.as_bytes())),
+                    type_: e.type_,
+                    value: Some(fbb.create_vector(&e.value)),
+                },
+                None => fb_models::FieldArgs {
+                    name: field.name().map(|e| fbb.create_vector(e)),
+                    type_: field.type_(),
+                    value: field.value().map(|e| fbb.create_vector(e)),
+                },
+            };
+            fields.push(fb_models::Field::create(&mut fbb, &args));
+        }
+        let args = fb_models::PointArgs {
+            db: point.db().map(|e| fbb.create_vector(e)),
+            tab: point.tab().map(|e| fbb.create_vector(e)),
+            tags: Some(fbb.create_vector(&tags)),
+            fields: Some(fbb.create_vector(&fields)),
+            timestamp: rewrite
+                .timestamps
+                .get(&i)
+                .copied()
+                .unwrap_or_else(|| point.timestamp()),
+        };
+        point_offsets.push(fb_models::Point::create(&mut fbb, &args));
+    }
+
+    let args = fb_models::PointsArgs {
+        db: fb_points.db().map(|e| fbb.create_vector(e)),
+        points: Some(fbb.create_vector(&point_offsets)),
+    };
+    let points = fb_models::Points::create(&mut fbb, &args);
+    fbb.finish(points, None);
+    fbb.finished_data().to_vec()
+}
+
+/// The tables the points are written to, the columns of which are those of the
+/// points, in the order of the databases and the names
+pub(crate) fn written_tables(points: &[u8]) -> Result<Vec<TskvTableSchema>> {
+    let invalid = |reason: String| ServerError::SchemaOnWrite { reason };
+    let utf8 = |v: Option<&[u8]>| String::from_utf8(v.unwrap_or_default().to_vec());
+    let fb_points = flatbuffers::root::<fb_models::Points>(points)
+        .map_err(|e| invalid(format!("invalid points: {}", e)))?;
+
+    let mut tables: BTreeMap<(String, String), TskvTableSchema> = BTreeMap::new();
+    for point in fb_points.points().into_iter().flatten() {
+        let (db, tab) = match (utf8(point.db()), utf8(point.tab())) {
+            (Ok(db), Ok(tab)) if !tab.is_empty() => (db, tab),
+            _ => return Err(invalid("invalid database or table of point".to_string())),
+        };
+        let table = tables.entry((db.clone(), tab.clone())).or_insert_with(|| {
+            TskvTableSchema::new(db, tab, vec![TableColumn::new_time_column(0)])
+        });
+
+        let tags = point.tags().into_iter().flatten().map(|tag| {
+            let key = utf8(tag.key()).map_err(|e| invalid(e.to_string()))?;
+            Ok((key, ColumnType::Tag))
+        });
+        let fields = point.fields().into_iter().flatten().map(|field| {
+            let name = utf8(field.name()).map_err(|e| invalid(e.to_string()))?;
+            Ok((name, ColumnType::from_i32(field.type_().0)))
+        });
+        for column in tags.chain(fields) {
+            let (name, column_type) = column?;
+            if !table.contains_column(&name) {
+                let mut column = TableColumn::new_with_default(name, column_type);
+                column.id = table.columns().len() as ColumnId;
+                table.add_column(column);
+            }
+        }
+    }
+
+    Ok(tables.into_values().collect())
+}
+
+/// Creates the tables and the columns written not in the database. The creation
+/// is serialized by the lock on the node, those by the other nodes of the cluster
+/// are found by reading the schema again as the creation fails.
+///
+/// The databases are not created on write, the points of those not exist are rejected.
+pub(crate) async fn create_written_schema(
+    meta: &MetaDataRef,
+    lock: &Mutex<()>,
+    tables: &[TskvTableSchema],
+) -> Result<()> {
+    for table in tables {
+        let db = meta.database(&table.db).context(MetaDataSnafu)?;
+        let meta = meta.with_database(&table.db);
+        if missing_columns(&meta, table)?.map_or(false, |e| e.is_empty()) {
+            continue;
+        }
+        if !db.config.auto_create_schema_or_default() {
+            return Err(ServerError::SchemaOnWrite {
+                reason: format!(
+                    "the schema of table {} is not created on write to database {}",
+                    table.name, table.db
+                ),
+            });
+        }
+
+        let _guard = lock.lock().await;
+        match missing_columns(&meta, table)? {
+            None => {
+                match meta
+                    .create_table(&table.name, TableSchema::TsKvTableSchema(table.clone()))
+                    .await
+                {
+                    Ok(_) => {}
+                    Err(MetadataError::TableAlreadyExists { .. }) => {
+                        add_columns(&meta, table).await?;
+                    }
+                    Err(e) => return Err(e).context(MetaDataSnafu),
+                }
+            }
+            Some(_) => add_columns(&meta, table).await?,
+        }
+    }
+    Ok(())
+}
+
+/// The columns of the table not in the database, `None` if the table not exists
+fn missing_columns(
+    meta: &MetaDataRef,
+    table: &TskvTableSchema,
+) -> Result<Option<Vec<TableColumn>>> {
+    let existing = match meta.table(TableReference::from(table.name.as_str())) {
+        Ok(TableSchema::TsKvTableSchema(e)) => e,
+        Ok(_) => {
+            return Err(MetadataError::TableIsNotTsKv {
+                table_name: table.name.clone(),
+            })
+            .context(MetaDataSnafu)
+        }
+        Err(MetadataError::TableNotExists { .. }) => return Ok(None),
+        Err(e) => return Err(e).context(MetaDataSnafu),
+    };
+
+    Ok(Some(
+        table
+            .columns()
+            .iter()
+            .filter(|e| e.name != TIME_FIELD_NAME && !existing.contains_column(&e.name))
+            .cloned()
+            .collect(),
+    ))
+}
+
+async fn add_columns(meta: &MetaDataRef, table: &TskvTableSchema) -> Result<()> {
+    for column in missing_columns(meta, table)?.unwrap_or_default() {
+        let name = column.name.clone();
+        if let Err(e) = meta.alter_table_add_column(&table.name, column).await {
+            // Added by another node of the cluster
+            let added = missing_columns(meta, table)?
+                .map_or(false, |missing| missing.iter().all(|c| c.name != name));
+            if !added {
+                return Err(e).context(MetaDataSnafu);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use line_protocol::{line_protocol_to_lines, lines_to_points};
+    use models::schema::Duration;
+    use models::ValueType;
+
+    use super::*;
+
+    #[test]
+    fn test_written_tables() {
+        let lines = line_protocol_to_lines(
+            "cpu,host=a usage=1.5,count=3i 1\n\
+             mem,host=a free=2u 1\n\
+             cpu,host=b,region=r1 usage=0.5,idle=true 2",
+            0,
+        )
+        .unwrap();
+        let points = lines_to_points("db1", &lines);
+
+        let tables = written_tables(&points).unwrap();
+        let names = tables
+            .iter()
+            .map(|e| (e.db.as_str(), e.name.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec![("db1", "cpu"), ("db1", "mem")]);
+
+        let cpu = &tables[0];
+        let columns = cpu
+            .columns()
+            .iter()
+            .map(|e| (e.name.as_str(), e.column_type))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            columns,
+            vec![
+                (TIME_FIELD_NAME, ColumnType::Time),
+                ("host", ColumnType::Tag),
+                ("usage", ColumnType::Field(ValueType::Float)),
+                ("count", ColumnType::Field(ValueType::Integer)),
+                ("region", ColumnType::Tag),
+                ("idle", ColumnType::Field(ValueType::Boolean)),
+            ]
+        );
+        let ids = cpu.columns().iter().map(|e| e.id).collect::<Vec<_>>();
+        assert_eq!(ids, vec![0, 1, 2, 3, 4, 5]);
+
+        let mem = &tables[1];
+        assert_eq!(
+            mem.column("free").map(|e| e.column_type),
+            Some(ColumnType::Field(ValueType::Unsigned))
+        );
+    }
+
+    #[test]
+    fn test_coerce() {
+        let int = |v: i64| v.to_be_bytes().to_vec();
+        let float = |v: f64| v.to_be_bytes().to_vec();
+        let unsigned = |v: u64| v.to_be_bytes().to_vec();
+
+        assert_eq!(
+            coerce(&int(3), ValueType::Integer, ValueType::Float),
+            Some(float(3.0))
+        );
+        assert_eq!(
+            coerce(&int(i64::MAX), ValueType::Integer, ValueType::Float),
+            None
+        );
+        assert_eq!(
+            coerce(&int(-1), ValueType::Integer, ValueType::Unsigned),
+            None
+        );
+        assert_eq!(
+            coerce(&unsigned(u64::MAX), ValueType::Unsigned, ValueType::Integer),
+            None
+        );
+        assert_eq!(
+            coerce(&float(2.0), ValueType::Float, ValueType::Integer),
+            Some(int(2))
+        );
+        assert_eq!(
+            coerce(&float(2.5), ValueType::Float, ValueType::Integer),
+            None
+        );
+        assert_eq!(
+            coerce(&float(-2.0), ValueType::Float, ValueType::Unsigned),
+            None
+        );
+        assert_eq!(coerce(&[1], ValueType::Boolean, ValueType::Integer), None);
+    }
+
+    #[test]
+    fn test_encode_rewritten_points() {
+        let lines =
+            line_protocol_to_lines("cpu,host=a usage=1i 1\ncpu,host=b usage=2i 2", 0).unwrap();
+        let points = lines_to_points("db1", &lines);
+        let fb_points = flatbuffers::root::<fb_models::Points>(&points).unwrap();
+
+        let mut rewrite = PointsRewrite::default();
+        rewrite.rejected.insert(0);
+        rewrite.timestamps.insert(1, 3);
+        rewrite.fields.insert(
+            (1, 0),
+            ResolvedField {
+                name: "usage_integer".to_string(),
+                type_: fb_models::FieldType::Integer,
+                value: 2_i64.to_be_bytes().to_vec(),
+            },
+        );
+        let encoded = encode_rewritten_points(&fb_points, &rewrite);
+
+        let tables = written_tables(&encoded).unwrap();
+        assert_eq!(tables.len(), 1);
+        let names = tables[0]
+            .columns()
+            .iter()
+            .map(|e| e.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec![TIME_FIELD_NAME, "host", "usage_integer"]);
+
+        let fb_points = flatbuffers::root::<fb_models::Points>(&encoded).unwrap();
+        let points = fb_points.points().unwrap();
+        assert_eq!(points.len(), 1);
+        assert_eq!(points.get(0).timestamp(), 3);
+        assert_eq!(fb_points.db(), Some(&b"db1"[..]));
+    }
+
+    #[test]
+    fn test_timestamp_window() {
+        let mut options = DatabaseOptions::default();
+        assert_eq!(timestamp_window(&options, 0), (i64::MIN, i64::MAX));
+
+        options.with_future_tolerance(Duration::new("1m").unwrap());
+        options.with_past_tolerance(Duration::new("1h").unwrap());
+        assert_eq!(
+            timestamp_window(&options, 0),
+            (-3_600_000_000_000, 60_000_000_000)
+        );
+        assert_eq!(timestamp_window(&options, i64::MAX).1, i64::MAX);
+    }
+
+    #[test]
+    fn test_resolve_table_points() {
+        let lines = line_protocol_to_lines(
+            "cpu,host=a usage=1.5 1
+cpu,host=b usage=2.5 2",
+            0,
+        )
+        .unwrap();
+        let points = lines_to_points("db1", &lines);
+        let table = written_tables(&points).unwrap().remove(0);
+
+        // Rejected out of the window by default
+        let mut options = DatabaseOptions::default();
+        options.with_past_tolerance(Duration::new("1m").unwrap());
+        let now = 60_000_000_002;
+        let written = resolve_table_points(&options, &table, points.clone(), now).unwrap();
+        assert_eq!(written.out_of_window.len(), 1);
+        assert_eq!(written.out_of_window[0].timestamp, 1);
+        assert_eq!(written.out_of_window[0].clamped, None);
+        let fb_points = flatbuffers::root::<fb_models::Points>(&written.points).unwrap();
+        assert_eq!(fb_points.points().unwrap().len(), 1);
+
+        let written = resolve_table_points(&options, &table, points.clone(), 0).unwrap();
+        assert!(written.out_of_window.is_empty());
+        assert_eq!(written.points, points);
+    }
+
+    #[test]
+    fn test_resolve_new_table_points() {
+        let lines = line_protocol_to_lines(
+            "cpu,host=a usage=1i 1\ncpu,host=b usage=2.5 2\ncpu,host=c usage=3i 3",
+            0,
+        )
+        .unwrap();
+        let points = lines_to_points("db1", &lines);
+
+        // The later points conflict with the types of the first, the table is new
+        let mut options = DatabaseOptions::default();
+        options.with_field_type_conflict(FieldTypeConflict::Reject);
+        let written =
+            resolve_points_by(points, 0, |_| Ok(options.clone()), |_, _| Ok(None)).unwrap();
+        let conflicts = written
+            .conflicts
+            .iter()
+            .map(|e| (e.point, e.field.as_str(), e.resolution.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(conflicts, vec![(1, "usage", ConflictResolution::Rejected)]);
+        let tables = written_tables(&written.points).unwrap();
+        assert_eq!(
+            tables[0].column("usage").map(|e| e.column_type),
+            Some(ColumnType::Field(ValueType::Integer))
+        );
+    }
+}
@@ -1,7 +1,12 @@
 use crate::query::function::FuncMetaManagerRef;
+use async_trait::async_trait;
 use datafusion::catalog::catalog::CatalogProvider;
 use datafusion::catalog::TableReference;
-use models::schema::{DatabaseSchema, TableColumn, TableSchema};
+use models::error_code::ErrorCode;
+use models::meta_data::{RoleInfo, UserInfo};
+use models::schema::{DatabaseSchema, TableColumn, TableSchema, TableStatistics};
+use models::stream_source::{StreamSourceDefinition, StreamSourceStatus};
+use models::task::TaskDefinition;
 use snafu::Snafu;
 use std::any::Any;
 use std::sync::Arc;
@@ -14,6 +19,9 @@ pub type CatalogRef = Arc<dyn CatalogProvider>;
 pub const DEFAULT_DATABASE: &str = "public";
 pub const DEFAULT_CATALOG: &str = "cnosdb";
 
+/// Metadata of a tenant. The changes are async, those of a cluster wait for the meta
+/// service, while the reads are served from the metadata cached on the node
+#[async_trait]
 pub trait MetaData: Send + Sync {
     fn as_any(&self) -> &dyn Any;
     fn with_catalog(&self, catalog: &str) -> Arc<dyn MetaData>;
@@ -23,21 +31,47 @@ pub trait MetaData: Send + Sync {
     fn table(&self, name: TableReference) -> Result<TableSchema>;
     fn database(&self, name: &str) -> Result<DatabaseSchema>;
     fn function(&self) -> FuncMetaManagerRef;
-    fn drop_table(&self, name: &str) -> Result<()>;
-    fn drop_database(&self, name: &str) -> Result<()>;
-    fn create_table(&self, name: &str, table: TableSchema) -> Result<()>;
-    fn create_database(&self, name: &str, database: DatabaseSchema) -> Result<()>;
+    async fn drop_table(&self, name: &str) -> Result<()>;
+    async fn drop_database(&self, name: &str) -> Result<()>;
+    async fn create_table(&self, name: &str, table: TableSchema) -> Result<()>;
+    async fn create_database(&self, name: &str, database: DatabaseSchema) -> Result<()>;
     fn database_names(&self) -> Result<Vec<String>>;
     fn show_tables(&self, database_name: &Option<String>) -> Result<Vec<String>>;
-    fn alter_database(&self, database: DatabaseSchema) -> Result<()>;
-    fn alter_table_add_column(&self, table_name: &str, column: TableColumn) -> Result<()>;
-    fn alter_table_alter_column(
+    async fn alter_database(&self, database: DatabaseSchema) -> Result<()>;
+    async fn alter_table_add_column(&self, table_name: &str, column: TableColumn) -> Result<()>;
+    async fn alter_table_alter_column(
         &self,
         table_name: &str,
         column_name: &str,
         new_column: TableColumn,
     ) -> Result<()>;
-    fn alter_table_drop_column(&self, table_name: &str, column_name: &str) -> Result<()>;
+    async fn alter_table_drop_column(&self, table_name: &str, column_name: &str) -> Result<()>;
+    async fn alter_table_statistics(
+        &self,
+        table_name: &str,
+        statistics: TableStatistics,
+    ) -> Result<()>;
+    fn create_stream_source(&self, source: StreamSourceDefinition) -> Result<()>;
+    fn drop_stream_source(&self, name: &str) -> Result<()>;
+    fn stream_sources(&self) -> Result<Vec<StreamSourceStatus>>;
+    async fn create_user(&self, user: UserInfo) -> Result<()>;
+    async fn alter_user(&self, user: UserInfo) -> Result<()>;
+    async fn drop_user(&self, name: &str) -> Result<()>;
+    fn user(&self, name: &str) -> Result<Option<UserInfo>>;
+    fn users(&self) -> Result<Vec<UserInfo>>;
+
+    async fn create_role(&self, role: RoleInfo) -> Result<()>;
+    async fn alter_role(&self, role: RoleInfo) -> Result<()>;
+    /// The role is revoked from the users as well
+    async fn drop_role(&self, name: &str) -> Result<()>;
+    fn role(&self, name: &str) -> Result<Option<RoleInfo>>;
+    fn roles(&self) -> Result<Vec<RoleInfo>>;
+
+    async fn create_task(&self, task: TaskDefinition) -> Result<()>;
+    async fn alter_task(&self, task: TaskDefinition) -> Result<()>;
+    async fn drop_task(&self, name: &str) -> Result<()>;
+    /// The tasks of the tenant
+    fn tasks(&self) -> Result<Vec<TaskDefinition>>;
 }
 
 #[derive(Debug, Snafu)]
@@ -61,9 +95,92 @@ pub enum MetadataError {
     #[snafu(display("Database {} not exists.", database_name))]
     DatabaseNotExists { database_name: String },
 
+    #[snafu(display("Stream source {} already exists.", source_name))]
+    StreamSourceAlreadyExists { source_name: String },
+
+    #[snafu(display("Stream source {} not exists.", source_name))]
+    StreamSourceNotExists { source_name: String },
+
+    #[snafu(display("Invalid stream source {}: {}.", source_name, error_msg))]
+    InvalidStreamSource {
+        source_name: String,
+        error_msg: String,
+    },
+
+    #[snafu(display("User {} already exists.", user_name))]
+    UserAlreadyExists { user_name: String },
+
+    #[snafu(display("User {} not exists.", user_name))]
+    UserNotExists { user_name: String },
+
+    #[snafu(display("Role {} already exists.", role_name))]
+    RoleAlreadyExists { role_name: String },
+
+    #[snafu(display("Role {} not exists.", role_name))]
+    RoleNotExists { role_name: String },
+
+    #[snafu(display("Policy {} on table {} already exists.", policy_name, table_name))]
+    PolicyAlreadyExists {
+        policy_name: String,
+        table_name: String,
+    },
+
+    #[snafu(display("Policy {} on table {} not exists.", policy_name, table_name))]
+    PolicyNotExists {
+        policy_name: String,
+        table_name: String,
+    },
+
+    #[snafu(display("Task {} already exists.", task_name))]
+    TaskAlreadyExists { task_name: String },
+
+    #[snafu(display("Task {} not exists.", task_name))]
+    TaskNotExists { task_name: String },
+
+    #[snafu(display("Token {} of user {} already exists.", token_name, user_name))]
+    TokenAlreadyExists {
+        user_name: String,
+        token_name: String,
+    },
+
+    #[snafu(display("Token {} of user {} not exists.", token_name, user_name))]
+    TokenNotExists {
+        user_name: String,
+        token_name: String,
+    },
+
     #[snafu(display("Internal Error: {}.", error_msg))]
     InternalError { error_msg: String },
 
     #[snafu(display("Invalid schema: {}.", error_msg))]
     InvalidSchema { error_msg: String },
 }
+
+impl MetadataError {
+    pub fn error_code(&self) -> ErrorCode {
+        match self {
+            Self::External { .. } => ErrorCode::MetaUnknown,
+            Self::TableAlreadyExists { .. } => ErrorCode::TableAlreadyExists,
+            Self::TableNotExists { .. } => ErrorCode::TableNotFound,
+            Self::TableIsNotTsKv { .. } => ErrorCode::TableNotTskv,
+            Self::DatabaseAlreadyExists { .. } => ErrorCode::DatabaseAlreadyExists,
+            Self::DatabaseNotExists { .. } => ErrorCode::DatabaseNotFound,
+            Self::StreamSourceNotExists { .. }
+            | Self::UserNotExists { .. }
+            | Self::RoleNotExists { .. }
+            | Self::PolicyNotExists { .. }
+            | Self::TaskNotExists { .. }
+            | Self::TokenNotExists { .. } => ErrorCode::ObjectNotFound,
+            Self::StreamSourceAlreadyExists { .. }
+            | Self::UserAlreadyExists { .. }
+            | Self::RoleAlreadyExists { .. }
+            | Self::PolicyAlreadyExists { .. }
+            | Self::TaskAlreadyExists { .. }
+            | Self::TokenAlreadyExists { .. } => ErrorCode::ObjectAlreadyExists,
+            Self::InvalidStreamSource { .. } | Self::InvalidSchema { .. } => {
+                ErrorCode::InvalidSchema
+            }
+            Self::InternalError { .. } => ErrorCode::MetaInternal,
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use std::fmt;
 
-use datafusion::sql::sqlparser::ast::{DataType, Ident, ObjectName};
+use datafusion::sql::sqlparser::ast::{DataType, Expr, Ident, ObjectName, Query, SqlOption};
 use datafusion::sql::{parser::CreateExternalTable, sqlparser::ast::Statement};
 use models::codec::Encoding;
 
@@ -14,20 +14,101 @@ pub enum ExtStatement {
     CreateTable(CreateTable),
     CreateDatabase(CreateDatabase),
     CreateUser(CreateUser),
+    CreateStreamSource(CreateStreamSource),
 
     Drop(DropObject),
     DropUser(DropUser),
+    AlterUser(AlterUser),
+    CreateToken(CreateToken),
+    DropToken(DropToken),
+    CreateRole(CreateRole),
+    DropRole(DropRole),
+    GrantRole(GrantRole),
+    RevokeRole(RevokeRole),
+    GrantRoleToGroup(GrantRoleToGroup),
+    RevokeRoleFromGroup(RevokeRoleFromGroup),
+    GrantSelect(GrantSelect),
+    RevokeSelect(RevokeSelect),
+    CreatePolicy(CreatePolicy),
+    DropPolicy(DropPolicy),
+    CreateTask(CreateTask),
+    AlterTask(AlterTask),
+    DropTask(DropTask),
 
     DescribeTable(DescribeTable),
     DescribeDatabase(DescribeDatabase),
     ShowDatabases(),
     ShowTables(Option<ObjectName>),
+    ShowShards(Option<ObjectName>),
+    ShowCreateTable(ShowCreateTable),
+    ShowStreamSources,
+    ShowUsers,
+    ShowTokens,
+    ShowRoles,
+    ShowPolicies,
+    ShowTasks,
+    /// `SHOW GRANTS [FOR <user or role>]`
+    ShowGrants(Option<Ident>),
     //todo:  insert/update/alter
+    Copy(CopyTo),
+    ExportDatabase(ExportDatabase),
+    ImportDatabase(ImportDatabase),
 
     // system cmd
     ShowQueries,
+    ShowSettings,
+    /// `SHOW STATS [FOR '<module>']`
+    ShowStats(Option<String>),
+    ShowDiagnostics,
+    ShowSessions,
+    /// `KILL SESSION <id>`
+    KillSession(u64),
+    AlterSystemSet(AlterSystemSet),
     AlterDatabase(AlterDatabase),
     AlterTable(AlterTable),
+    AnalyzeTable(AnalyzeTable),
+    FlushDatabase(FlushDatabase),
+    DeleteBefore(DeleteBefore),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateStreamSource {
+    pub name: ObjectName,
+    pub if_not_exists: bool,
+    /// Name of the connector, such as KAFKA
+    pub connector: Ident,
+    pub options: Vec<SqlOption>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportDatabase {
+    pub database: Ident,
+    /// local path or object store url of the dump directory
+    pub location: String,
+    pub options: Vec<SqlOption>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportDatabase {
+    pub database: Ident,
+    /// local path or object store url of the dump directory
+    pub location: String,
+    pub options: Vec<SqlOption>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CopyTo {
+    pub source: CopySource,
+    /// local path or object store url
+    pub location: String,
+    /// CSV, PARQUET, JSON
+    pub file_type: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CopySource {
+    Table(ObjectName),
+    Query(Box<Query>),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -45,11 +126,22 @@ pub enum AlterTableAction {
         column_name: Ident,
         encoding: Encoding,
     },
+    /// Converts a field to a tag or a tag to a field, the column is named by `column`
+    ConvertColumn {
+        column: ColumnOption,
+    },
     DropColumn {
         column_name: Ident,
     },
 }
 
+/// `ALTER SYSTEM SET <section>.<key> = <value>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlterSystemSet {
+    pub name: String,
+    pub value: String,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct AlterDatabase {
     pub name: ObjectName,
@@ -69,10 +161,152 @@ pub struct DescribeObject {
     pub obj_type: ObjectType,
 }
 
+/// `DROP USER [IF EXISTS] name`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DropUser {
+    pub name: Ident,
+    pub if_exists: bool,
+}
+
+/// `CREATE USER [IF NOT EXISTS] name WITH (password = '...', is_admin = true)`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateUser {
+    pub name: Ident,
+    pub if_not_exists: bool,
+    pub options: Vec<SqlOption>,
+}
+
+/// `ALTER USER name WITH (password = '...', is_admin = false)`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlterUser {
+    pub name: Ident,
+    pub options: Vec<SqlOption>,
+}
+/// `CREATE TOKEN name [FOR USER user]`
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct DropUser {}
+pub struct CreateToken {
+    pub name: Ident,
+    pub user: Option<Ident>,
+}
+
+/// `DROP TOKEN [IF EXISTS] name [FOR USER user]`
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct CreateUser {}
+pub struct DropToken {
+    pub name: Ident,
+    pub user: Option<Ident>,
+    pub if_exists: bool,
+}
+
+/// `CREATE ROLE [IF NOT EXISTS] name`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateRole {
+    pub name: Ident,
+    pub if_not_exists: bool,
+}
+
+/// `DROP ROLE [IF EXISTS] name`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DropRole {
+    pub name: Ident,
+    pub if_exists: bool,
+}
+
+/// `GRANT ROLE role TO user`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrantRole {
+    pub role: Ident,
+    pub user: Ident,
+}
+
+/// `REVOKE ROLE role FROM user`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RevokeRole {
+    pub role: Ident,
+    pub user: Ident,
+}
+
+/// `GRANT ROLE role TO GROUP 'group'`, the group of the external identity providers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrantRoleToGroup {
+    pub role: Ident,
+    pub group: String,
+}
+
+/// `REVOKE ROLE role FROM GROUP 'group'`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RevokeRoleFromGroup {
+    pub role: Ident,
+    pub group: String,
+}
+
+/// `GRANT SELECT (column, ...) ON table TO role`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrantSelect {
+    pub columns: Vec<Ident>,
+    pub table: ObjectName,
+    pub role: Ident,
+}
+
+/// `REVOKE SELECT [(column, ...)] ON table FROM role`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RevokeSelect {
+    /// All of the columns if empty
+    pub columns: Vec<Ident>,
+    pub table: ObjectName,
+    pub role: Ident,
+}
+
+/// `CREATE POLICY name ON table TO role USING (predicate)`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreatePolicy {
+    pub name: Ident,
+    pub table: ObjectName,
+    pub role: Ident,
+    pub predicate: Expr,
+}
+
+/// `DROP POLICY [IF EXISTS] name ON table`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DropPolicy {
+    pub name: Ident,
+    pub table: ObjectName,
+    pub if_exists: bool,
+}
+
+/// `CREATE TASK [IF NOT EXISTS] name
+///     { EVERY '<duration>' | ON DATA IN table [EVERY '<duration>'] }
+///     { INTO table | WEBHOOK '<url>' } AS query`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateTask {
+    pub name: Ident,
+    pub if_not_exists: bool,
+    pub every: Option<String>,
+    /// The table, rows written to which trigger the task
+    pub on_data: Option<ObjectName>,
+    pub sink: TaskSink,
+    pub query: Box<Query>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TaskSink {
+    Table(ObjectName),
+    Webhook(String),
+}
+
+/// `ALTER TASK name { ENABLE | DISABLE }`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlterTask {
+    pub name: Ident,
+    pub enabled: bool,
+}
+
+/// `DROP TASK [IF EXISTS] name`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DropTask {
+    pub name: Ident,
+    pub if_exists: bool,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CreateDatabase {
     pub name: ObjectName,
@@ -126,6 +360,16 @@ pub struct DatabaseOptions {
     pub replica: Option<u64>,
     // timestamp percision
     pub precision: Option<String>,
+    // create the tables and the columns written not exist
+    pub auto_create_schema: Option<bool>,
+    // write of the fields the types of which differ from the columns
+    pub field_type_conflict: Option<String>,
+    // timestamps written later than now by more are out of the window
+    pub future_tolerance: Option<String>,
+    // timestamps written earlier than now by more are out of the window
+    pub past_tolerance: Option<String>,
+    // write of the points the timestamps of which are out of the window
+    pub out_of_window: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -133,6 +377,31 @@ pub struct DescribeTable {
     pub table_name: ObjectName,
 }
 
+/// `SHOW CREATE TABLE <table>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShowCreateTable {
+    pub table_name: ObjectName,
+}
+
+/// `ANALYZE TABLE <table>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnalyzeTable {
+    pub table_name: ObjectName,
+}
+
+/// `FLUSH DATABASE <database>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlushDatabase {
+    pub database_name: ObjectName,
+}
+
+/// `DELETE FROM <database> BEFORE { '<timestamp>' | <nanoseconds> }`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeleteBefore {
+    pub database_name: ObjectName,
+    pub before: String,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DescribeDatabase {
     pub database_name: ObjectName,
@@ -148,6 +417,7 @@ pub struct ShowTables {
 pub enum ObjectType {
     Table,
     Database,
+    StreamSource,
 }
 
 impl fmt::Display for ObjectType {
@@ -155,6 +425,7 @@ impl fmt::Display for ObjectType {
         f.write_str(match self {
             ObjectType::Table => "TABLE",
             ObjectType::Database => "DATABASE",
+            ObjectType::StreamSource => "STREAM SOURCE",
         })
     }
 }
@@ -59,11 +59,22 @@ impl QueryInfo {
 pub struct QueryStatus {
     state: QueryState,
     duration: Duration,
+    bytes_scanned: u64,
 }
 
 impl QueryStatus {
     pub fn new(state: QueryState, duration: Duration) -> Self {
-        Self { state, duration }
+        Self {
+            state,
+            duration,
+            bytes_scanned: 0,
+        }
+    }
+
+    /// Bytes of the values read from the storage so far
+    pub fn with_bytes_scanned(mut self, bytes_scanned: u64) -> Self {
+        self.bytes_scanned = bytes_scanned;
+        self
     }
 
     pub fn query_state(&self) -> &QueryState {
@@ -73,4 +84,8 @@ impl QueryStatus {
     pub fn duration(&self) -> &Duration {
         &self.duration
     }
+
+    pub fn bytes_scanned(&self) -> u64 {
+        self.bytes_scanned
+    }
 }
@@ -6,6 +6,7 @@ use async_trait::async_trait;
 use datafusion::arrow::error::ArrowError;
 use datafusion::arrow::record_batch::RecordBatch;
 use datafusion::error::DataFusionError;
+use models::error_code::ErrorCode;
 use snafu::Snafu;
 
 use crate::catalog::{MetaData, MetaDataRef};
@@ -13,7 +14,7 @@ use crate::service::protocol::QueryId;
 use crate::{catalog::MetadataError, service::protocol::Query};
 
 use super::dispatcher::{QueryInfo, QueryStatus};
-use super::{logical_planner::Plan, session::IsiphoSessionCtx, Result};
+use super::{datafusion_error_code, logical_planner::Plan, session::IsiphoSessionCtx, Result};
 
 #[derive(Debug, Snafu)]
 #[snafu(visibility(pub))]
@@ -29,6 +30,29 @@ pub enum ExecutionError {
 
     #[snafu(display("Query not found: {:?}", query_id))]
     QueryNotFound { query_id: QueryId },
+
+    #[snafu(display("Session not found: {}", session_id))]
+    SessionNotFound { session_id: u64 },
+
+    #[snafu(display("Settings err: {}", reason))]
+    Settings { reason: String },
+
+    #[snafu(display("Permission denied: {}", reason))]
+    PermissionDenied { reason: String },
+}
+
+impl ExecutionError {
+    pub fn error_code(&self) -> ErrorCode {
+        match self {
+            Self::External { source } => datafusion_error_code(source),
+            Self::Arrow { .. } | Self::QueryNotFound { .. } | Self::SessionNotFound { .. } => {
+                ErrorCode::QueryExecution
+            }
+            Self::Metadata { source } => source.error_code(),
+            Self::Settings { .. } => ErrorCode::InvalidParameter,
+            Self::PermissionDenied { .. } => ErrorCode::PermissionDenied,
+        }
+    }
 }
 
 #[async_trait]
@@ -55,6 +79,15 @@ pub enum Output {
     Nil(()),
 }
 
+pub type BatchSinkRef = Arc<dyn BatchSink>;
+
+/// Receives the record batches of the results of a query as they are produced,
+/// instead of them being collected in memory into the output
+#[async_trait]
+pub trait BatchSink: Send + Sync {
+    async fn append(&self, batch: RecordBatch) -> std::result::Result<(), ArrowError>;
+}
+
 pub trait QueryExecutionFactory {
     fn create_query_execution(
         &self,
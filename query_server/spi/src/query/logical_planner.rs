@@ -2,6 +2,7 @@ use crate::{catalog::MetadataError, service::protocol::QueryId};
 
 use super::{
     ast::{ExtStatement, ObjectType},
+    datafusion_error_code,
     session::IsiphoSessionCtx,
     AFFECTED_ROWS,
 };
@@ -11,9 +12,15 @@ use datafusion::{
     logical_expr::{AggregateFunction, CreateExternalTable, LogicalPlan as DFPlan},
     prelude::{col, Expr},
 };
+use models::error_code::ErrorCode;
 use models::schema::DatabaseOptions;
+use models::stream_source::{ConnectorType, PayloadFormat};
+use models::task::{TaskSink, TaskTrigger};
 use models::{define_result, schema::TableColumn};
 use snafu::Snafu;
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+use std::str::FromStr;
 
 define_result!(LogicalPlannerError);
 
@@ -35,6 +42,21 @@ pub enum LogicalPlannerError {
 
     #[snafu(display("This feature is not implemented: {}", err))]
     NotImplemented { err: String },
+
+    #[snafu(display("Permission denied: {}", reason))]
+    PermissionDenied { reason: String },
+}
+
+impl LogicalPlannerError {
+    pub fn error_code(&self) -> ErrorCode {
+        match self {
+            Self::External { source } => datafusion_error_code(source),
+            Self::Semantic { .. } => ErrorCode::Semantic,
+            Self::Metadata { source } => source.error_code(),
+            Self::NotImplemented { .. } => ErrorCode::NotImplemented,
+            Self::PermissionDenied { .. } => ErrorCode::PermissionDenied,
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -68,17 +90,104 @@ pub enum DDLPlan {
 
     ShowTables(Option<String>),
 
+    /// The shards of a database, or of all the databases
+    ShowShards(Option<String>),
+
+    /// The privileges of a user or a role, of the current user if not specified
+    ShowGrants(Option<String>),
+
+    ShowCreateTable(ShowCreateTable),
+
     ShowDatabases(),
 
     AlterDatabase(AlterDatabase),
 
     AlterTable(AlterTable),
+
+    /// Collect the statistics of a table for the optimizer
+    AnalyzeTable(AnalyzeTable),
+
+    /// Flush the memcaches of a database to tsm files
+    FlushDatabase(FlushDatabase),
+
+    /// Remove the data files of a database before a timestamp
+    DeleteBefore(DeleteBefore),
+
+    /// Remove the data of a shard
+    CreateStreamSource(CreateStreamSource),
+
+    ShowStreamSources,
+
+    CreateUser(CreateUser),
+
+    AlterUser(AlterUser),
+
+    DropUser(DropUser),
+
+    ShowUsers,
+
+    CreateToken(CreateToken),
+
+    DropToken(DropToken),
+
+    ShowTokens,
+
+    CreateRole(CreateRole),
+
+    DropRole(DropRole),
+
+    GrantRole(GrantRole),
+
+    RevokeRole(RevokeRole),
+
+    GrantRoleToGroup(GrantRoleToGroup),
+
+    RevokeRoleFromGroup(RevokeRoleFromGroup),
+
+    GrantSelect(GrantSelect),
+
+    RevokeSelect(RevokeSelect),
+
+    CreatePolicy(CreatePolicy),
+
+    DropPolicy(DropPolicy),
+
+    CreateTask(CreateTask),
+
+    AlterTask(AlterTask),
+
+    DropTask(DropTask),
+
+    /// The tasks of the tenant
+    ShowTasks,
+
+    ShowRoles,
+
+    ShowPolicies,
+
+    ExportDatabase(ExportDatabase),
+
+    ImportDatabase(ImportDatabase),
 }
 
 #[derive(Debug, Clone)]
 pub enum SYSPlan {
     ShowQueries,
     KillQuery(QueryId),
+    ShowSettings,
+    /// The samples of the metrics, of a module if given
+    ShowStats(Option<String>),
+    /// The build, the runtime and the main configuration of the server
+    ShowDiagnostics,
+    /// The sessions of the clients connected, those of the user unless an admin
+    ShowSessions,
+    /// Closes the connection of the session, which cancels the query running on it
+    KillSession(u64),
+    /// Changes a reloadable setting of the configuration
+    AlterSystemSet {
+        name: String,
+        value: String,
+    },
 }
 
 #[derive(Debug, Clone)]
@@ -146,6 +255,266 @@ pub struct CreateDatabase {
     pub options: DatabaseOptions,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateStreamSource {
+    pub name: String,
+
+    pub if_not_exists: bool,
+
+    pub connector: ConnectorType,
+
+    pub format: PayloadFormat,
+    /// Target database, the current database of the session if not specified
+    pub database: Option<String>,
+
+    pub table: Option<String>,
+    /// Connector specific options
+    pub options: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateUser {
+    pub name: String,
+
+    pub if_not_exists: bool,
+    /// Plain text, hashed before stored
+    pub password: String,
+
+    pub is_admin: bool,
+}
+
+/// Only the specified attributes are changed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlterUser {
+    pub name: String,
+
+    pub password: Option<String>,
+
+    pub is_admin: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DropUser {
+    pub name: String,
+
+    pub if_exists: bool,
+}
+
+/// The token is of the current user if no user is specified
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateToken {
+    pub name: String,
+
+    pub user: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DropToken {
+    pub name: String,
+
+    pub user: Option<String>,
+
+    pub if_exists: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateRole {
+    pub name: String,
+
+    pub if_not_exists: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DropRole {
+    pub name: String,
+
+    pub if_exists: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrantRole {
+    pub role: String,
+
+    pub user: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RevokeRole {
+    pub role: String,
+
+    pub user: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrantRoleToGroup {
+    pub role: String,
+
+    pub group: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RevokeRoleFromGroup {
+    pub role: String,
+
+    pub group: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrantSelect {
+    pub columns: Vec<String>,
+
+    pub database: String,
+
+    pub table: String,
+
+    pub role: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RevokeSelect {
+    /// All of the columns if empty
+    pub columns: Vec<String>,
+    /// Resolved by the database of the session if not qualified
+    pub table: String,
+
+    pub role: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreatePolicy {
+    pub name: String,
+
+    pub database: String,
+
+    pub table: String,
+
+    pub role: String,
+    /// Validated against the columns of the table
+    pub predicate: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DropPolicy {
+    pub name: String,
+    /// Resolved by the database of the session if not qualified
+    pub table: String,
+
+    pub if_exists: bool,
+}
+
+/// The task is of the database of the session, run by the current user
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateTask {
+    pub name: String,
+
+    pub if_not_exists: bool,
+
+    pub query: String,
+
+    pub trigger: TaskTrigger,
+
+    pub sink: TaskSink,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlterTask {
+    pub name: String,
+
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DropTask {
+    pub name: String,
+
+    pub if_exists: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpCompression {
+    Gzip,
+    Uncompressed,
+}
+
+impl DumpCompression {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Gzip => "lp.gz",
+            Self::Uncompressed => "lp",
+        }
+    }
+}
+
+impl FromStr for DumpCompression {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "GZIP" | "GZ" => Ok(Self::Gzip),
+            "NONE" | "UNCOMPRESSED" => Ok(Self::Uncompressed),
+            _ => Err(format!(
+                "compression {} is not supported, expected one of GZIP, NONE",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for DumpCompression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Gzip => "GZIP",
+            Self::Uncompressed => "NONE",
+        })
+    }
+}
+
+/// Filters of `EXPORT DATABASE` and `IMPORT DATABASE`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DumpFilter {
+    /// Inclusive lower bound of time in nanoseconds
+    pub start_time: Option<i64>,
+    /// Exclusive upper bound of time in nanoseconds
+    pub end_time: Option<i64>,
+    /// All tables if empty
+    pub tables: Vec<String>,
+}
+
+impl DumpFilter {
+    pub fn contains_time(&self, time: i64) -> bool {
+        self.start_time.map(|t| time >= t).unwrap_or(true)
+            && self.end_time.map(|t| time < t).unwrap_or(true)
+    }
+
+    pub fn contains_table(&self, table: &str) -> bool {
+        self.tables.is_empty() || self.tables.iter().any(|e| e == table)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportDatabase {
+    pub database: String,
+
+    pub location: String,
+
+    pub compression: DumpCompression,
+
+    pub filter: DumpFilter,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportDatabase {
+    /// Target database, may differ from the database in the manifest
+    pub database: String,
+
+    pub location: String,
+
+    pub filter: DumpFilter,
+
+    /// Tables of the dump imported into the tables of other names
+    pub table_mapping: BTreeMap<String, String>,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DescribeDatabase {
     pub database_name: String,
@@ -156,6 +525,28 @@ pub struct DescribeTable {
     pub table_name: String,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShowCreateTable {
+    pub table_name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnalyzeTable {
+    pub table_name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlushDatabase {
+    pub database_name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeleteBefore {
+    pub database_name: String,
+    /// Nanoseconds
+    pub before: i64,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ShowTables {
     pub database_name: String,
@@ -182,6 +573,12 @@ pub enum AlterTableAction {
         column_name: String,
         new_column: TableColumn,
     },
+    /// Converts a field to a tag or a tag to a field, the existing points are
+    /// rewritten into the series of the new tags in the background
+    ConvertColumn {
+        column_name: String,
+        new_column: TableColumn,
+    },
     DropColumn {
         column_name: String,
     },
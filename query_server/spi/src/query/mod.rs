@@ -2,6 +2,7 @@ use datafusion::{
     arrow::datatypes::DataType, error::DataFusionError, sql::sqlparser::parser::ParserError,
 };
 use models::define_result;
+use models::error_code::ErrorCode;
 use snafu::Snafu;
 
 use self::{execution::ExecutionError, logical_planner::LogicalPlannerError};
@@ -71,3 +72,35 @@ pub enum QueryError {
     #[snafu(display("The query server has been closed"))]
     Closed,
 }
+
+impl QueryError {
+    pub fn error_code(&self) -> ErrorCode {
+        match self {
+            Self::LogicalPlanner { source } => source.error_code(),
+            Self::Parser { .. } => ErrorCode::SqlParse,
+            Self::Analyzer { .. } => ErrorCode::Semantic,
+            Self::LogicalOptimize { source }
+            | Self::PhysicalPlaner { source }
+            | Self::Optimizer { source }
+            | Self::Schedule { source } => datafusion_error_code(source),
+            Self::Execution { source } => source.error_code(),
+            Self::RequestLimit => ErrorCode::QueryLimitExceeded,
+            Self::MultiStatement { .. } => ErrorCode::MultiStatement,
+            Self::Cancel => ErrorCode::QueryCanceled,
+            Self::BuildQueryDispatcher { .. } | Self::Internal { .. } | Self::Closed => {
+                ErrorCode::QueryInternal
+            }
+        }
+    }
+}
+
+/// The code of the errors raised by datafusion, the errors of the plans are caused
+/// by the sql
+pub fn datafusion_error_code(e: &DataFusionError) -> ErrorCode {
+    match e {
+        DataFusionError::SQL(_) => ErrorCode::SqlParse,
+        DataFusionError::Plan(_) | DataFusionError::SchemaError(_) => ErrorCode::Semantic,
+        DataFusionError::NotImplemented(_) => ErrorCode::NotImplemented,
+        _ => ErrorCode::QueryExecution,
+    }
+}
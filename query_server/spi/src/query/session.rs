@@ -1,11 +1,21 @@
+use std::sync::Arc;
+
 use datafusion::{
     config::OPT_OPTIMIZER_SKIP_FAILED_RULES,
-    execution::context,
+    error::{DataFusionError, Result as DFResult},
+    execution::{context::SessionState, runtime_env::RuntimeEnv},
     prelude::{SessionConfig, SessionContext},
+    scalar::ScalarValue,
 };
+use models::consistency_level::ConsistencyLevel;
 
 use crate::service::protocol::Context;
 
+/// Consistency level of the reads of the replicated shards, `ONE` if absent
+pub const OPT_READ_CONSISTENCY: &str = "cnosdb.read_consistency";
+/// Time zone of the buckets of `GROUP BY time(...)`, UTC if absent
+pub const OPT_TIME_ZONE: &str = "cnosdb.time_zone";
+
 #[derive(Clone)]
 pub struct IsiphoSessionCtx {
     // todo
@@ -32,13 +42,19 @@ impl IsiphoSessionCtx {
 #[derive(Default)]
 pub struct IsiphoSessionCtxFactory {
     // TODO global config
+    /// Shared by all sessions, so that object stores are registered once
+    runtime: Arc<RuntimeEnv>,
 }
 
 impl IsiphoSessionCtxFactory {
+    pub fn new(runtime: Arc<RuntimeEnv>) -> Self {
+        Self { runtime }
+    }
+
     pub fn create_isipho_session_ctx(&self, context: Context) -> IsiphoSessionCtx {
         let isipho_ctx = context.session_config().to_owned();
         // TODO Use global configuration as the default configuration for session
-        let df_session_state = context::default_session_builder(isipho_ctx.inner);
+        let df_session_state = SessionState::with_config_rt(isipho_ctx.inner, self.runtime.clone());
         let df_session_ctx = SessionContext::with_state(df_session_state);
 
         IsiphoSessionCtx {
@@ -78,4 +94,36 @@ impl IsiphoSessionConfig {
         self.inner = self.inner.with_target_partitions(n);
         self
     }
+
+    pub fn with_read_consistency(self, level: ConsistencyLevel) -> Self {
+        self.inner.config_options.write().set(
+            OPT_READ_CONSISTENCY,
+            ScalarValue::Utf8(Some(level.to_string())),
+        );
+        self
+    }
+
+    pub fn with_time_zone(self, time_zone: String) -> Self {
+        self.inner
+            .config_options
+            .write()
+            .set(OPT_TIME_ZONE, ScalarValue::Utf8(Some(time_zone)));
+        self
+    }
+}
+
+/// The read consistency level of the session, an invalid one is an error
+pub fn read_consistency(state: &SessionState) -> DFResult<ConsistencyLevel> {
+    match state.config.config_options.read().get(OPT_READ_CONSISTENCY) {
+        Some(ScalarValue::Utf8(Some(level))) => level.parse().map_err(DataFusionError::Plan),
+        _ => Ok(ConsistencyLevel::default()),
+    }
+}
+
+/// The time zone of the session, e.g. `Asia/Shanghai`
+pub fn time_zone(state: &SessionState) -> Option<String> {
+    match state.config.config_options.read().get(OPT_TIME_ZONE) {
+        Some(ScalarValue::Utf8(Some(time_zone))) => Some(time_zone),
+        _ => None,
+    }
 }
@@ -1,8 +1,14 @@
+use std::collections::HashSet;
 use std::sync::Arc;
 
 use async_trait::async_trait;
+use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::arrow::record_batch::RecordBatch;
+use models::schema::ColumnType;
+use models::{Timestamp, ValueType};
+use serde::Serialize;
 
-use crate::service::protocol::{Query, QueryHandle, QueryId};
+use crate::service::protocol::{Query, QueryHandle, QueryId, UserInfo};
 
 use super::Result;
 
@@ -10,7 +16,103 @@ pub type DBMSRef = Arc<dyn DatabaseManagerSystem + Send + Sync>;
 
 #[async_trait]
 pub trait DatabaseManagerSystem {
+    /// Checks the password of the user, always passes if the authentication is disabled
+    async fn authenticate(&self, user_info: &UserInfo) -> Result<()>;
+    /// The user an API token or a JWT is issued to, the password of which is empty
+    fn authenticate_token(&self, token: &str) -> Result<UserInfo>;
     async fn execute(&self, query: &Query) -> Result<QueryHandle>;
+    /// Resolves the timestamps of the points out of the window allowed and the fields
+    /// conflicting with the types of the columns, `now` in nanoseconds, and creates the tables and the columns of the points not in the databases
+    /// of the tenant, before the points are written
+    async fn resolve_schema_on_write(
+        &self,
+        tenant: &str,
+        points: Vec<u8>,
+        now: i64,
+    ) -> Result<WrittenPoints>;
+    /// Encodes the rows of the record batch as the points of the table, the columns
+    /// of the batch are mapped to those of the table by the names and cast to the
+    /// types of them
+    fn record_batch_to_points(
+        &self,
+        tenant: &str,
+        database: &str,
+        table: &str,
+        batch: &RecordBatch,
+    ) -> Result<Vec<u8>>;
+    /// Whether the user is an admin, those not in the database are not
+    fn is_admin(&self, user: &str) -> Result<bool>;
+    /// Names of the databases of the tenant
+    fn database_names(&self, tenant: &str) -> Result<Vec<String>>;
+    /// Names of the tables of the database of the tenant
+    fn table_names(&self, tenant: &str, database: &str) -> Result<Vec<String>>;
+    /// The arrow schema of the table
+    fn table_schema(&self, tenant: &str, database: &str, table: &str) -> Result<SchemaRef>;
+    /// The time ranges `[start, end)` of the shards of the database, the tables of
+    /// which are scanned by them in parallel
+    fn time_partitions(&self, tenant: &str, database: &str) -> Result<Vec<(Timestamp, Timestamp)>>;
     fn metrics(&self) -> String;
     fn cancel(&self, query_id: &QueryId);
 }
+
+/// The points to write after the schema on write is resolved
+#[derive(Debug)]
+pub struct WrittenPoints {
+    pub points: Vec<u8>,
+    /// The fields of the points the types of which differ from the columns
+    pub conflicts: Vec<FieldConflict>,
+    /// The points the timestamps of which are out of the window allowed
+    pub out_of_window: Vec<TimestampOutOfWindow>,
+}
+
+impl WrittenPoints {
+    /// Number of the points not written, for the conflicts of the fields or the
+    /// timestamps out of the window
+    pub fn rejected_points(&self) -> usize {
+        let conflicts = self
+            .conflicts
+            .iter()
+            .filter(|e| e.resolution == ConflictResolution::Rejected)
+            .map(|e| e.point);
+        let out_of_window = self
+            .out_of_window
+            .iter()
+            .filter(|e| e.clamped.is_none())
+            .map(|e| e.point);
+        conflicts.chain(out_of_window).collect::<HashSet<_>>().len()
+    }
+}
+
+/// A field of a point the type of which differs from the column in the database
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldConflict {
+    /// Index of the point in the write
+    pub point: usize,
+    pub table: String,
+    pub field: String,
+    pub column_type: ColumnType,
+    pub value_type: ValueType,
+    pub resolution: ConflictResolution,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictResolution {
+    /// The point is not written
+    Rejected,
+    /// The value is converted to the type of the column
+    Coerced,
+    /// The value is written to the column named by the field and its type
+    Suffixed { column: String },
+}
+
+/// A point the timestamp of which is out of the window allowed by the database
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TimestampOutOfWindow {
+    /// Index of the point in the write
+    pub point: usize,
+    pub table: String,
+    pub timestamp: i64,
+    /// The timestamp written, `None` if the point is rejected
+    pub clamped: Option<i64>,
+}
@@ -1,6 +1,7 @@
 use crate::catalog::MetadataError;
 use crate::query::{function, QueryError};
 use models::define_result;
+use models::error_code::ErrorCode;
 use snafu::Snafu;
 
 pub mod dbms;
@@ -21,4 +22,29 @@ pub enum ServerError {
 
     #[snafu(display("Failed init meta data, err :{}", source))]
     MetaData { source: MetadataError },
+
+    #[snafu(display("Authentication failed: {}", reason))]
+    Auth { reason: String },
+
+    #[snafu(display("Failed to create the schema on write: {}", reason))]
+    SchemaOnWrite { reason: String },
+
+    #[snafu(display("Failed to write the record batch to table {}: {}", table, reason))]
+    RecordBatchWrite { table: String, reason: String },
+
+    #[snafu(display("Failed to get the partitions of database {}: {}", database, reason))]
+    Partition { database: String, reason: String },
+}
+
+impl ServerError {
+    pub fn error_code(&self) -> ErrorCode {
+        match self {
+            Self::Query { source } | Self::Build { source } => source.error_code(),
+            Self::MetaData { source } => source.error_code(),
+            Self::Auth { .. } => ErrorCode::AuthFailed,
+            Self::SchemaOnWrite { .. } => ErrorCode::InvalidSchema,
+            Self::RecordBatchWrite { .. } => ErrorCode::InvalidPoint,
+            Self::LoadFunction { .. } | Self::Partition { .. } => ErrorCode::QueryInternal,
+        }
+    }
 }
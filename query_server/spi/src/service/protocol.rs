@@ -1,7 +1,9 @@
 use std::sync::atomic::{AtomicU64, Ordering};
 
+use models::consistency_level::ConsistencyLevel;
+
 use crate::catalog::DEFAULT_DATABASE;
-use crate::query::execution::Output;
+use crate::query::execution::{BatchSinkRef, Output};
 use crate::query::session::IsiphoSessionConfig;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -42,6 +44,7 @@ pub struct Context {
     user_info: UserInfo,
     database: String,
     session_config: IsiphoSessionConfig,
+    batch_sink: Option<BatchSinkRef>,
 }
 
 impl Context {
@@ -60,6 +63,11 @@ impl Context {
     pub fn session_config(&self) -> &IsiphoSessionConfig {
         &self.session_config
     }
+
+    /// The sink the results of the query are sent to as produced, if any
+    pub fn batch_sink(&self) -> Option<&BatchSinkRef> {
+        self.batch_sink.as_ref()
+    }
 }
 
 pub struct ContextBuilder {
@@ -93,11 +101,26 @@ impl ContextBuilder {
         self
     }
 
+    pub fn with_read_consistency(mut self, level: Option<ConsistencyLevel>) -> Self {
+        if let Some(level) = level {
+            self.session_config = self.session_config.with_read_consistency(level);
+        }
+        self
+    }
+
+    pub fn with_time_zone(mut self, time_zone: Option<String>) -> Self {
+        if let Some(time_zone) = time_zone {
+            self.session_config = self.session_config.with_time_zone(time_zone);
+        }
+        self
+    }
+
     pub fn build(self) -> Context {
         Context {
             user_info: self.user_info,
             database: self.database,
             session_config: self.session_config,
+            batch_sink: None,
         }
     }
 }
@@ -121,6 +144,12 @@ impl Query {
     pub fn content(&self) -> &str {
         self.content.as_str()
     }
+
+    /// The results of the query are sent to the sink, the output is left empty
+    pub fn with_batch_sink(mut self, sink: BatchSinkRef) -> Self {
+        self.context.batch_sink = Some(sink);
+        self
+    }
 }
 
 pub struct QueryHandle {
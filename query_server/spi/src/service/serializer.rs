@@ -0,0 +1,310 @@
+//! Record batch serializers shared by the protocols which return query results
+
+use datafusion::arrow::array::{
+    as_boolean_array, as_largestring_array, as_primitive_array, as_string_array, ArrayRef,
+};
+use datafusion::arrow::compute::cast;
+use datafusion::arrow::csv::writer::WriterBuilder;
+use datafusion::arrow::datatypes::{
+    DataType, Float32Type, Float64Type, Int16Type, Int32Type, Int64Type, Int8Type, UInt16Type,
+    UInt32Type, UInt64Type, UInt8Type,
+};
+use datafusion::arrow::error::{ArrowError, Result as ArrowResult};
+use datafusion::arrow::ipc::writer::StreamWriter;
+use datafusion::arrow::json::{ArrayWriter, LineDelimitedWriter};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::arrow::util::display::array_value_to_string;
+use datafusion::arrow::util::pretty::pretty_format_batches;
+use rmp::encode;
+
+/// Converts record batches into the bytes of a wire format
+pub trait RecordBatchSerializer: Send + Sync {
+    fn serialize(&self, batches: &[RecordBatch]) -> ArrowResult<Vec<u8>>;
+}
+
+/// Delimiter separated values with a header line
+pub struct CsvSerializer {
+    delimiter: u8,
+}
+
+impl CsvSerializer {
+    pub fn new(delimiter: u8) -> Self {
+        Self { delimiter }
+    }
+}
+
+impl RecordBatchSerializer for CsvSerializer {
+    fn serialize(&self, batches: &[RecordBatch]) -> ArrowResult<Vec<u8>> {
+        let mut bytes = vec![];
+        {
+            let builder = WriterBuilder::new()
+                .has_headers(true)
+                .with_delimiter(self.delimiter);
+            let mut writer = builder.build(&mut bytes);
+            for batch in batches {
+                writer.write(batch)?;
+            }
+        }
+        Ok(bytes)
+    }
+}
+
+macro_rules! batches_to_json {
+    ($WRITER: ident, $batches: expr) => {{
+        let mut bytes = vec![];
+        {
+            let mut writer = $WRITER::new(&mut bytes);
+            writer.write_batches($batches)?;
+            writer.finish()?;
+        }
+        Ok(bytes)
+    }};
+}
+
+/// A single json array of row objects
+pub struct JsonSerializer;
+
+impl RecordBatchSerializer for JsonSerializer {
+    fn serialize(&self, batches: &[RecordBatch]) -> ArrowResult<Vec<u8>> {
+        batches_to_json!(ArrayWriter, batches)
+    }
+}
+
+/// One json object per line
+pub struct NdJsonSerializer;
+
+impl RecordBatchSerializer for NdJsonSerializer {
+    fn serialize(&self, batches: &[RecordBatch]) -> ArrowResult<Vec<u8>> {
+        batches_to_json!(LineDelimitedWriter, batches)
+    }
+}
+
+/// Arrow IPC streaming format
+pub struct ArrowIpcSerializer;
+
+impl RecordBatchSerializer for ArrowIpcSerializer {
+    fn serialize(&self, batches: &[RecordBatch]) -> ArrowResult<Vec<u8>> {
+        let schema = match batches.first() {
+            Some(batch) => batch.schema(),
+            None => return Ok(vec![]),
+        };
+
+        let mut bytes = vec![];
+        {
+            let mut writer = StreamWriter::try_new(&mut bytes, &schema)?;
+            for batch in batches {
+                writer.write(batch)?;
+            }
+            writer.finish()?;
+        }
+        Ok(bytes)
+    }
+}
+
+/// Human readable ascii table
+pub struct TableSerializer;
+
+impl RecordBatchSerializer for TableSerializer {
+    fn serialize(&self, batches: &[RecordBatch]) -> ArrowResult<Vec<u8>> {
+        Ok(pretty_format_batches(batches)?.to_string().into_bytes())
+    }
+}
+
+/// MessagePack array of row maps, the binary counterpart of [`JsonSerializer`]
+///
+/// Timestamps and dates are encoded as integers in their own unit,
+/// other types that msgpack can not represent natively are encoded as strings.
+pub struct MessagePackSerializer;
+
+impl RecordBatchSerializer for MessagePackSerializer {
+    fn serialize(&self, batches: &[RecordBatch]) -> ArrowResult<Vec<u8>> {
+        let num_rows: usize = batches.iter().map(|b| b.num_rows()).sum();
+
+        let mut bytes = vec![];
+        encode::write_array_len(&mut bytes, num_rows as u32).map_err(msgpack_err)?;
+
+        for batch in batches {
+            let schema = batch.schema();
+            let columns = batch
+                .columns()
+                .iter()
+                .map(normalize_msgpack_column)
+                .collect::<ArrowResult<Vec<_>>>()?;
+
+            for row in 0..batch.num_rows() {
+                encode::write_map_len(&mut bytes, columns.len() as u32).map_err(msgpack_err)?;
+                for (field, column) in schema.fields().iter().zip(columns.iter()) {
+                    encode::write_str(&mut bytes, field.name()).map_err(msgpack_err)?;
+                    write_msgpack_value(&mut bytes, column, row)?;
+                }
+            }
+        }
+
+        Ok(bytes)
+    }
+}
+
+fn msgpack_err<E: std::error::Error + Send + Sync + 'static>(e: E) -> ArrowError {
+    ArrowError::ExternalError(Box::new(e))
+}
+
+/// Cast temporal columns to their integer representation
+fn normalize_msgpack_column(column: &ArrayRef) -> ArrowResult<ArrayRef> {
+    match column.data_type() {
+        DataType::Timestamp(_, _) | DataType::Date64 | DataType::Time64(_) => {
+            cast(column, &DataType::Int64)
+        }
+        DataType::Date32 | DataType::Time32(_) => cast(column, &DataType::Int32),
+        _ => Ok(column.clone()),
+    }
+}
+
+macro_rules! write_primitive {
+    ($WRITE_FN: ident, $TYPE: ty, $CAST: ty, $wr: expr, $array: expr, $row: expr) => {
+        encode::$WRITE_FN(
+            $wr,
+            as_primitive_array::<$TYPE>($array).value($row) as $CAST,
+        )
+        .map(|_| ())
+        .map_err(msgpack_err)
+    };
+}
+
+#[allow(clippy::unnecessary_cast)]
+fn write_msgpack_value(wr: &mut Vec<u8>, array: &ArrayRef, row: usize) -> ArrowResult<()> {
+    if array.is_null(row) {
+        return encode::write_nil(wr).map_err(msgpack_err);
+    }
+
+    match array.data_type() {
+        DataType::Boolean => {
+            encode::write_bool(wr, as_boolean_array(array).value(row)).map_err(msgpack_err)
+        }
+        DataType::Int8 => write_primitive!(write_sint, Int8Type, i64, wr, array, row),
+        DataType::Int16 => write_primitive!(write_sint, Int16Type, i64, wr, array, row),
+        DataType::Int32 => write_primitive!(write_sint, Int32Type, i64, wr, array, row),
+        DataType::Int64 => write_primitive!(write_sint, Int64Type, i64, wr, array, row),
+        DataType::UInt8 => write_primitive!(write_uint, UInt8Type, u64, wr, array, row),
+        DataType::UInt16 => write_primitive!(write_uint, UInt16Type, u64, wr, array, row),
+        DataType::UInt32 => write_primitive!(write_uint, UInt32Type, u64, wr, array, row),
+        DataType::UInt64 => write_primitive!(write_uint, UInt64Type, u64, wr, array, row),
+        DataType::Float32 => write_primitive!(write_f32, Float32Type, f32, wr, array, row),
+        DataType::Float64 => write_primitive!(write_f64, Float64Type, f64, wr, array, row),
+        DataType::Utf8 => {
+            encode::write_str(wr, as_string_array(array).value(row)).map_err(msgpack_err)
+        }
+        DataType::LargeUtf8 => {
+            encode::write_str(wr, as_largestring_array(array).value(row)).map_err(msgpack_err)
+        }
+        _ => encode::write_str(wr, &array_value_to_string(array, row)?).map_err(msgpack_err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use datafusion::arrow::array::{Int32Array, StringArray};
+    use datafusion::arrow::datatypes::{Field, Schema};
+    use datafusion::arrow::ipc::reader::StreamReader;
+    use datafusion::from_slice::FromSlice;
+
+    use super::*;
+
+    fn test_batches() -> Vec<RecordBatch> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int32, false),
+            Field::new("b", DataType::Int32, false),
+            Field::new("c", DataType::Int32, false),
+        ]));
+
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int32Array::from_slice([1, 2, 3])),
+                Arc::new(Int32Array::from_slice([4, 5, 6])),
+                Arc::new(Int32Array::from_slice([7, 8, 9])),
+            ],
+        )
+        .unwrap();
+
+        vec![batch]
+    }
+
+    #[test]
+    fn test_csv_serializer() {
+        let batches = vec![];
+        assert_eq!(
+            "".as_bytes(),
+            CsvSerializer::new(b',').serialize(&batches).unwrap()
+        );
+
+        let batches = test_batches();
+        let r = CsvSerializer::new(b',').serialize(&batches).unwrap();
+        assert_eq!("a,b,c\n1,4,7\n2,5,8\n3,6,9\n".as_bytes(), r);
+
+        let r = CsvSerializer::new(b'\t').serialize(&batches).unwrap();
+        assert_eq!("a\tb\tc\n1\t4\t7\n2\t5\t8\n3\t6\t9\n".as_bytes(), r);
+    }
+
+    #[test]
+    fn test_json_serializer() -> ArrowResult<()> {
+        let batches = vec![];
+        assert_eq!("".as_bytes(), JsonSerializer.serialize(&batches)?);
+        assert_eq!("".as_bytes(), NdJsonSerializer.serialize(&batches)?);
+
+        let batches = test_batches();
+        assert_eq!(
+            "[{\"a\":1,\"b\":4,\"c\":7},{\"a\":2,\"b\":5,\"c\":8},{\"a\":3,\"b\":6,\"c\":9}]"
+                .as_bytes(),
+            JsonSerializer.serialize(&batches)?
+        );
+        assert_eq!(
+            "{\"a\":1,\"b\":4,\"c\":7}\n{\"a\":2,\"b\":5,\"c\":8}\n{\"a\":3,\"b\":6,\"c\":9}\n"
+                .as_bytes(),
+            NdJsonSerializer.serialize(&batches)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_message_pack_serializer() -> ArrowResult<()> {
+        assert_eq!(vec![0x90], MessagePackSerializer.serialize(&[])?);
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int32, true),
+            Field::new("b", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int32Array::from(vec![Some(1), None])),
+                Arc::new(StringArray::from_slice(["x", "y"])),
+            ],
+        )?;
+
+        let bytes = MessagePackSerializer.serialize(&[batch])?;
+        // [{"a": 1, "b": "x"}, {"a": nil, "b": "y"}]
+        assert_eq!(
+            vec![
+                0x92, 0x82, 0xa1, b'a', 0x01, 0xa1, b'b', 0xa1, b'x', 0x82, 0xa1, b'a', 0xc0, 0xa1,
+                b'b', 0xa1, b'y'
+            ],
+            bytes
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_arrow_ipc_serializer() -> ArrowResult<()> {
+        assert!(ArrowIpcSerializer.serialize(&[])?.is_empty());
+
+        let batches = test_batches();
+        let bytes = ArrowIpcSerializer.serialize(&batches)?;
+
+        let reader = StreamReader::try_new(bytes.as_slice(), None)?;
+        let actual = reader.collect::<ArrowResult<Vec<_>>>()?;
+        assert_eq!(batches, actual);
+        Ok(())
+    }
+}
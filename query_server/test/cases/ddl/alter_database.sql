@@ -16,6 +16,8 @@ DESCRIBE DATABASE test;
 
 ALTER DATABASE test Set VNODE_DURATION '100d';
 
+ALTER DATABASE test Set VNODE_DURATION '20d';
+
 DESCRIBE DATABASE test;
 
 ALTER DATABASE test Set REPLICA 12;
@@ -25,3 +27,19 @@ DESCRIBE DATABASE test;
 ALTER DATABASE test Set PRECision 'ms';
 
 DESCRIBE DATABASE test;
+
+ALTER DATABASE test Set AUTO_CREATE_SCHEMA false;
+
+DESCRIBE DATABASE test;
+
+ALTER DATABASE test Set FIELD_TYPE_CONFLICT 'coerce';
+
+DESCRIBE DATABASE test;
+
+ALTER DATABASE test Set FUTURE_TOLERANCE '1h';
+
+ALTER DATABASE test Set PAST_TOLERANCE '7d';
+
+ALTER DATABASE test Set OUT_OF_WINDOW 'clamp';
+
+DESCRIBE DATABASE test;
@@ -16,3 +16,50 @@ CREATE EXTERNAL TABLE
     LOCATION 'query_server/query/tests/data/csv/decimal_data.csv';
 
 DESCRIBE TABLE create_external_table.cpu;
+
+CREATE EXTERNAL TABLE cpu_ndjson
+    STORED AS NDJSON
+    LOCATION 'query_server/query/tests/data/ndjson/cpu.ndjson';
+
+DESCRIBE TABLE create_external_table.cpu_ndjson;
+
+SELECT host, cores FROM cpu_ndjson WHERE usage > 1;
+
+CREATE EXTERNAL TABLE cpu_avro
+    STORED AS AVRO
+    LOCATION 'query_server/query/tests/data/avro/cpu.avro';
+
+DESCRIBE TABLE create_external_table.cpu_avro;
+
+SELECT host, cores FROM cpu_avro WHERE usage > 1;
+
+CREATE EXTERNAL TABLE cpu_gzip
+    STORED AS NDJSON
+    LOCATION 'query_server/query/tests/data/ndjson/cpu.ndjson.gz';
+
+SELECT host, cores FROM cpu_gzip WHERE usage > 1;
+
+CREATE EXTERNAL TABLE cpu_zstd
+    STORED AS NDJSON
+    COMPRESSION TYPE ZSTD
+    LOCATION 'query_server/query/tests/data/ndjson/cpu.ndjson.zst';
+
+SELECT host, cores FROM cpu_zstd WHERE usage > 1;
+
+CREATE EXTERNAL TABLE cpu_partitioned
+    STORED AS CSV
+    WITH HEADER ROW
+    LOCATION 'query_server/query/tests/data/partitioned/';
+
+DESCRIBE TABLE create_external_table.cpu_partitioned;
+
+SELECT host, usage, year FROM cpu_partitioned WHERE year = '2022';
+
+CREATE EXTERNAL TABLE cpu_inferred
+    STORED AS CSV
+    WITH HEADER ROW
+    LOCATION 'query_server/query/tests/data/csv/cpu.csv';
+
+DESCRIBE TABLE create_external_table.cpu_inferred;
+
+SELECT host, usage, cores FROM cpu_inferred;
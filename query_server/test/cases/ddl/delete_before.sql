@@ -0,0 +1,23 @@
+--#DATABASE=delete_before
+DROP DATABASE IF EXISTS delete_before;
+DROP USER IF EXISTS delete_before_user;
+CREATE DATABASE delete_before;
+
+CREATE TABLE air(visibility BIGINT, TAGS(station));
+INSERT air(TIME, station, visibility) VALUES (1, 'a', 1), (2, 'b', 2), (3, 'a', 3), (4, 'b', 4);
+FLUSH DATABASE delete_before;
+INSERT air(TIME, station, visibility) VALUES (5, 'a', 5), (6, 'b', 6);
+
+-- the points before the timestamp in the file and in the cache are deleted
+DELETE FROM delete_before BEFORE 3;
+SELECT time, station, visibility FROM air ORDER BY time;
+-- the file all the points of which are before the timestamp is removed
+DELETE FROM delete_before BEFORE 5;
+SELECT time, station, visibility FROM air ORDER BY time;
+
+CREATE USER IF NOT EXISTS delete_before_user WITH (password = 'delete_before_pass', is_admin = false);
+
+--#USER_NAME=delete_before_user
+--#PASSWORD=delete_before_pass
+-- only the admins delete the data of a database
+DELETE FROM delete_before BEFORE 6;
@@ -0,0 +1,15 @@
+--#DATABASE=show_create_table
+DROP DATABASE IF EXISTS show_create_table;
+CREATE DATABASE show_create_table;
+CREATE TABLE test (
+    f0 BIGINT CODEC(DELTA),
+    f1 DOUBLE,
+    f2 STRING CODEC(ZSTD),
+    TAGS(t0, t1)
+);
+SHOW CREATE TABLE test;
+
+ALTER TABLE test ALTER f1 SET CODEC(GORILLA);
+ALTER TABLE test ALTER f0 SET CODEC(GORILLA);
+ALTER TABLE test ALTER f2 SET CODEC(DEFAULT);
+SHOW CREATE TABLE test;
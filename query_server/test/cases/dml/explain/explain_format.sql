@@ -0,0 +1,3 @@
+EXPLAIN (FORMAT json) SELECT * FROM (VALUES  (9, 'nine'),(2, 'two'), (1, 'one'), (3, 'three')) AS t (num,letter) order by num desc limit 2;
+
+EXPLAIN (FORMAT graphviz) SELECT * FROM (VALUES  (9, 'nine'),(2, 'two'), (1, 'one'), (3, 'three')) AS t (num,letter) order by num desc limit 2;
@@ -0,0 +1,16 @@
+--#DATABASE=group_by_time
+DROP DATABASE IF EXISTS group_by_time;
+CREATE DATABASE group_by_time;
+
+CREATE TABLE IF NOT EXISTS m(f0 BIGINT, TAGS(t0));
+
+INSERT m(TIME, f0, t0) VALUES(1667431616000000000, 2, 'a');
+INSERT m(TIME, f0, t0) VALUES(1667374016000000000, 3, 'a');
+INSERT m(TIME, f0, t0) VALUES(1667332800000000000, 4, 'a');
+
+SELECT time, sum(f0) AS s FROM m GROUP BY time('1d') ORDER BY time;
+SELECT time, sum(f0) AS s FROM m GROUP BY time('1d', OFFSET '8h') ORDER BY time;
+SELECT time, sum(f0) AS s FROM m GROUP BY time('1x') ORDER BY time;
+
+--#TIME_ZONE=Asia/Shanghai
+SELECT time, sum(f0) AS s FROM m GROUP BY time('1d') ORDER BY time;
@@ -0,0 +1,46 @@
+--#DATABASE=row_policy
+DROP DATABASE IF EXISTS row_policy;
+DROP USER IF EXISTS row_policy_user;
+DROP ROLE IF EXISTS row_policy_eu;
+DROP ROLE IF EXISTS row_policy_us;
+DROP ROLE IF EXISTS row_policy_other;
+CREATE DATABASE row_policy;
+
+CREATE TABLE cpu(usage BIGINT, TAGS(region));
+INSERT cpu(TIME, region, usage) VALUES (1, 'eu', 1), (2, 'us', 2), (3, 'ap', 3);
+CREATE TABLE mem(used BIGINT, TAGS(region));
+INSERT mem(TIME, region, used) VALUES (1, 'eu', 10), (2, 'us', 20), (3, 'ap', 30);
+CREATE TABLE disk(free BIGINT, TAGS(region));
+INSERT disk(TIME, region, free) VALUES (1, 'eu', 100), (2, 'us', 200);
+CREATE TABLE net(bytes BIGINT, TAGS(region));
+INSERT net(TIME, region, bytes) VALUES (1, 'eu', 1000), (2, 'ap', 3000);
+
+CREATE USER IF NOT EXISTS row_policy_user WITH (password = 'row_policy_pass', is_admin = false);
+CREATE ROLE IF NOT EXISTS row_policy_eu;
+CREATE ROLE IF NOT EXISTS row_policy_us;
+CREATE ROLE IF NOT EXISTS row_policy_other;
+GRANT ROLE row_policy_eu TO row_policy_user;
+GRANT ROLE row_policy_us TO row_policy_user;
+CREATE POLICY cpu_eu ON row_policy.cpu TO row_policy_eu USING (region = 'eu');
+CREATE POLICY cpu_us ON row_policy.cpu TO row_policy_us USING (region = 'us');
+CREATE POLICY mem_us ON row_policy.mem TO row_policy_us USING (region = 'us');
+CREATE POLICY mem_eu ON row_policy.mem TO row_policy_other USING (region = 'eu');
+CREATE POLICY disk_eu ON row_policy.disk TO row_policy_other USING (region = 'eu');
+
+-- the admins read all of the rows
+SELECT region, usage FROM cpu ORDER BY region;
+SELECT region, usage FROM cpu WHERE region IN (SELECT region FROM mem) ORDER BY region;
+
+--#USER_NAME=row_policy_user
+--#PASSWORD=row_policy_pass
+-- the rows of any of the policies of the roles of the user
+SELECT region, usage FROM cpu ORDER BY region;
+SELECT region, used FROM mem ORDER BY region;
+-- none of the rows of a table with the policies of the other roles only
+SELECT count(free) AS n FROM disk;
+-- the tables without policies are not restricted
+SELECT region, bytes FROM net ORDER BY region;
+-- the tables of the subqueries and of the joins are restricted as well
+SELECT region, usage FROM cpu WHERE region IN (SELECT region FROM mem) ORDER BY region;
+SELECT cpu.region AS region, usage, used FROM cpu JOIN mem ON cpu.region = mem.region ORDER BY region;
+SELECT count(usage) AS n FROM cpu WHERE region NOT IN ('eu', 'us');
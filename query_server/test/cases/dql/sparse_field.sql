@@ -0,0 +1,15 @@
+--#DATABASE=sparse_field
+--#SORT=true
+drop database if exists sparse_field;
+create database sparse_field;
+
+CREATE TABLE IF NOT EXISTS sparse(f0 BIGINT, f1 DOUBLE, f2 STRING, TAGS(t0, t1));
+
+INSERT sparse(TIME, t0, f0) VALUES (1, 'a', 1);
+INSERT sparse(TIME, t0, t1, f1) VALUES (2, 'a', 'b', 2.5);
+INSERT sparse(TIME, t1, f2) VALUES (3, 'b', 'c');
+
+-- fields and tags a point did not write are NULL
+select * from sparse;
+select count(f0), count(f1), count(f2) from sparse;
+select t0, t1, f1 from sparse where f1 is not null;
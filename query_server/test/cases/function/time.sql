@@ -0,0 +1,17 @@
+--#DATABASE=time_func
+--#SORT=true
+drop database if exists time_func;
+create database time_func;
+
+CREATE TABLE IF NOT EXISTS m(f0 BIGINT, TAGS(t0));
+
+INSERT m(TIME, f0, t0) VALUES(1643623200000000000, 1, 'a');
+INSERT m(TIME, f0, t0) VALUES(1667431616000000000, 2, 'a');
+
+select time + interval '1 month' as t, f0 from m;
+select time - interval '1 day' as t, f0 from m;
+select time_bucket(interval '1 day', time) as b, f0 from m;
+select time_bucket(interval '1 day', time, 'Asia/Shanghai') as b, f0 from m;
+select time_bucket(interval '3 month', time, 'Asia/Shanghai') as b, f0 from m;
+select time AT TIME ZONE 'Asia/Shanghai' as t, f0 from m;
+select to_timezone(time, 'America/New_York') as t, f0 from m;
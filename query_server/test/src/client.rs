@@ -145,6 +145,11 @@ impl Client {
         if instruction.pretty() {
             http_query.push_str("&pretty=true");
         }
+
+        if let Some(time_zone) = instruction.time_zone() {
+            http_query.push_str("&tz=");
+            http_query.push_str(time_zone);
+        }
         url.set_query(Some(http_query.as_str()));
         url
     }
@@ -157,7 +162,10 @@ impl Client {
 
         self.client
             .request(Method::POST, url)
-            .basic_auth::<&str, &str>(query.instruction().user_name(), None)
+            .basic_auth::<&str, &str>(
+                query.instruction().user_name(),
+                query.instruction().password(),
+            )
             .body(body)
             .build()
     }
@@ -189,7 +197,10 @@ impl Client {
         Ok(self
             .client
             .request(Method::POST, url)
-            .basic_auth::<&str, &str>(line_protocol.instruction().user_name(), None)
+            .basic_auth::<&str, &str>(
+                line_protocol.instruction().user_name(),
+                line_protocol.instruction().password(),
+            )
             .body(body)
             .build()?)
     }
@@ -18,8 +18,12 @@ pub struct Instruction {
     pretty: bool,
     /// set user_name
     user_name: String,
+    /// set the password of the user
+    password: Option<String>,
     /// set how long to timeout
     time_out: Option<u64>,
+    /// set the time zone of the session
+    time_zone: Option<String>,
 }
 
 impl Default for Instruction {
@@ -29,7 +33,9 @@ impl Default for Instruction {
             sort: false,
             pretty: true,
             user_name: "cnosdb".to_string(),
+            password: None,
             time_out: None,
+            time_zone: None,
         }
     }
 }
@@ -88,10 +94,18 @@ impl Instruction {
         &self.user_name
     }
 
+    pub fn password(&self) -> Option<&str> {
+        self.password.as_deref()
+    }
+
     pub fn time_out(&self) -> Option<u64> {
         self.time_out
     }
 
+    pub fn time_zone(&self) -> Option<&str> {
+        self.time_zone.as_deref()
+    }
+
     /// parse line to modify instruction
     pub fn parse_and_change(&mut self, line: &str) {
         if let Ok((_, dbname)) = instruction_parse_identity("DATABASE")(line) {
@@ -102,6 +116,10 @@ impl Instruction {
             self.user_name = user_name.to_string();
         }
 
+        if let Ok((_, password)) = instruction_parse_str("PASSWORD")(line) {
+            self.password = Some(password.to_string());
+        }
+
         if let Ok((_, pretty)) = instruction_parse_to::<bool>("PRETTY")(line) {
             self.pretty = pretty;
         }
@@ -113,6 +131,10 @@ impl Instruction {
         if let Ok((_, timeout)) = instruction_parse_to::<u64>("TIMEOUT")(line) {
             self.time_out = Some(timeout)
         }
+
+        if let Ok((_, time_zone)) = instruction_parse_str("TIME_ZONE")(line) {
+            self.time_zone = Some(time_zone.to_string())
+        }
     }
 }
 
@@ -311,6 +333,11 @@ fn test_parse_instruction() {
     instruction.parse_and_change(line);
     assert_eq!(instruction.user_name, "hello");
 
+    assert_eq!(instruction.password, None);
+    let line = r##"--#PASSWORD = secret"##;
+    instruction.parse_and_change(line);
+    assert_eq!(instruction.password.as_deref(), Some("secret"));
+
     let line = r##"--#SORT = true"##;
     instruction.parse_and_change(line);
     assert!(instruction.sort);
@@ -323,6 +350,11 @@ fn test_parse_instruction() {
     let line = r##"--#TIMEOUT = 10"##;
     instruction.parse_and_change(line);
     assert_eq!(instruction.time_out, Some(10));
+
+    assert_eq!(instruction.time_zone, None);
+    let line = r##"--#TIME_ZONE = Asia/Shanghai"##;
+    instruction.parse_and_change(line);
+    assert_eq!(instruction.time_zone.as_deref(), Some("Asia/Shanghai"));
 }
 
 #[test]
@@ -0,0 +1,482 @@
+use std::io::Cursor;
+use std::time::Duration;
+
+use datafusion::arrow::ipc::reader::StreamReader;
+use datafusion::arrow::record_batch::RecordBatch;
+use http_protocol::header::{ACCEPT, APPLICATION_ARROW, APPLICATION_JSON, RETRY_AFTER};
+use http_protocol::parameter::{SqlParam, WriteParam};
+use http_protocol::response::ErrorResponse;
+use reqwest::{RequestBuilder, Response};
+use serde_json::{Map, Value};
+use snafu::ResultExt;
+
+use crate::error::{ArrowSnafu, Error, JsonSnafu, RequestSnafu, Result};
+use crate::point::{Point, Precision};
+
+pub const DEFAULT_HOST: &str = "127.0.0.1";
+pub const DEFAULT_PORT: u16 = 31007;
+pub const DEFAULT_USER: &str = "cnosdb";
+pub const DEFAULT_DATABASE: &str = "public";
+
+const API_V1_SQL_PATH: &str = "/api/v1/sql";
+const API_V1_WRITE_PATH: &str = "/api/v1/write";
+
+/// How the failed reads are sent again, the backoff doubles after each attempt, the
+/// wait asked by the server by `Retry-After` is honored up to `max_backoff`
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: usize,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The wait before the `retry`th retry, counted from 0
+    pub fn backoff(&self, retry: usize) -> Duration {
+        let factor = 1_u32.checked_shl(retry as u32).unwrap_or(u32::MAX);
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+
+    /// The wait before the `retry`th retry of a request the server asked to retry
+    /// after `retry_after`, if any
+    pub fn wait(&self, retry: usize, retry_after: Option<Duration>) -> Duration {
+        match retry_after {
+            Some(retry_after) => retry_after.min(self.max_backoff),
+            None => self.backoff(retry),
+        }
+    }
+}
+
+/// The first keywords of the statements only reading, which are retried
+const READ_STATEMENTS: [&str; 6] = ["SELECT", "SHOW", "DESCRIBE", "DESC", "EXPLAIN", "WITH"];
+
+/// Whether the statement only reads, so it is safe to run again
+fn is_read(sql: &str) -> bool {
+    let keyword = sql
+        .trim_start_matches(|c: char| c.is_whitespace() || c == '(')
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .next()
+        .unwrap_or_default();
+    READ_STATEMENTS
+        .iter()
+        .any(|e| e.eq_ignore_ascii_case(keyword))
+}
+
+pub struct ClientBuilder {
+    host: String,
+    port: u16,
+    https: bool,
+    user: String,
+    password: Option<String>,
+    database: String,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    pool_max_idle_per_host: usize,
+    pool_idle_timeout: Option<Duration>,
+    retry: RetryPolicy,
+}
+
+impl Default for ClientBuilder {
+    fn default() -> Self {
+        Self {
+            host: DEFAULT_HOST.to_string(),
+            port: DEFAULT_PORT,
+            https: false,
+            user: DEFAULT_USER.to_string(),
+            password: None,
+            database: DEFAULT_DATABASE.to_string(),
+            timeout: None,
+            connect_timeout: None,
+            pool_max_idle_per_host: usize::MAX,
+            pool_idle_timeout: Some(Duration::from_secs(90)),
+            retry: RetryPolicy::default(),
+        }
+    }
+}
+
+impl ClientBuilder {
+    pub fn host(mut self, host: impl Into<String>) -> Self {
+        self.host = host.into();
+        self
+    }
+
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    pub fn https(mut self, https: bool) -> Self {
+        self.https = https;
+        self
+    }
+
+    pub fn user(mut self, user: impl Into<String>) -> Self {
+        self.user = user.into();
+        self
+    }
+
+    pub fn password(mut self, password: impl Into<String>) -> Self {
+        self.password = Some(password.into());
+        self
+    }
+
+    /// The database written to and queried by default
+    pub fn database(mut self, database: impl Into<String>) -> Self {
+        self.database = database.into();
+        self
+    }
+
+    /// Timeout of each attempt of a request
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Max idle connections kept in the pool for reuse
+    pub fn pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.pool_max_idle_per_host = max;
+        self
+    }
+
+    /// Idle connections are closed after the timeout, never if `None`
+    pub fn pool_idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.pool_idle_timeout = timeout;
+        self
+    }
+
+    pub fn retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn build(self) -> Result<Client> {
+        let mut builder = reqwest::Client::builder()
+            .pool_max_idle_per_host(self.pool_max_idle_per_host)
+            .pool_idle_timeout(self.pool_idle_timeout);
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        let http_client = builder.build().map_err(|e| Error::Config {
+            reason: e.to_string(),
+        })?;
+
+        let scheme = if self.https { "https" } else { "http" };
+        Ok(Client {
+            http_client,
+            base_url: format!("{}://{}:{}", scheme, self.host, self.port),
+            user: self.user,
+            password: self.password,
+            database: self.database,
+            retry: self.retry,
+        })
+    }
+}
+
+/// Client of the http api of CnosDB.
+///
+/// The connections are pooled and shared by the clones of the client. The queries
+/// only reading failed as the server is unreachable or busy are sent again by the
+/// retry policy. The writes and the other statements are not, as they may have been
+/// applied even if failed, e.g. timed out.
+#[derive(Clone)]
+pub struct Client {
+    http_client: reqwest::Client,
+    base_url: String,
+    user: String,
+    password: Option<String>,
+    database: String,
+    retry: RetryPolicy,
+}
+
+impl Client {
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::default()
+    }
+
+    pub fn database(&self) -> &str {
+        &self.database
+    }
+
+    /// A client of another database sharing the connections of this one
+    pub fn with_database(&self, database: impl Into<String>) -> Self {
+        Self {
+            database: database.into(),
+            ..self.clone()
+        }
+    }
+
+    /// Writes the lines of the line protocol
+    pub async fn write_lines(&self, lines: impl Into<String>, precision: Precision) -> Result<()> {
+        let lines = lines.into();
+        let param = WriteParam {
+            db: self.database.clone(),
+            consistency: None,
+            precision: Some(precision.as_str().to_string()),
+        };
+
+        self.send(false, || {
+            self.post(API_V1_WRITE_PATH)
+                .query(&param)
+                .body(lines.clone())
+        })
+        .await
+        .map(|_| ())
+    }
+
+    pub async fn write_points(&self, points: &[Point], precision: Precision) -> Result<()> {
+        let lines = points
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        self.write_lines(lines, precision).await
+    }
+
+    /// Runs the statement and fetches the results as arrow record batches, empty
+    /// if the statement returns no results
+    pub async fn query(&self, sql: impl Into<String>) -> Result<Vec<RecordBatch>> {
+        let body = self.sql(sql.into(), APPLICATION_ARROW).await?;
+        if body.is_empty() {
+            return Ok(vec![]);
+        }
+
+        StreamReader::try_new(Cursor::new(body), None)
+            .and_then(|reader| reader.collect::<Result<Vec<_>, _>>())
+            .context(ArrowSnafu)
+    }
+
+    /// Runs the statement and fetches the results as json objects, one per row
+    pub async fn query_json(&self, sql: impl Into<String>) -> Result<Vec<Map<String, Value>>> {
+        let body = self.sql(sql.into(), APPLICATION_JSON).await?;
+        if body.is_empty() {
+            return Ok(vec![]);
+        }
+
+        serde_json::from_slice(&body).context(JsonSnafu)
+    }
+
+    async fn sql(&self, sql: String, accept: &str) -> Result<Vec<u8>> {
+        let param = SqlParam {
+            db: Some(self.database.clone()),
+            chunked: None,
+            target_partitions: None,
+            format: None,
+            consistency: None,
+            tz: None,
+            page_size: None,
+            cursor: None,
+        };
+
+        self.send(is_read(&sql), || {
+            self.post(API_V1_SQL_PATH)
+                .header(ACCEPT, accept)
+                .query(&param)
+                .body(sql.clone())
+        })
+        .await
+    }
+
+    fn post(&self, path: &str) -> RequestBuilder {
+        self.http_client
+            .post(format!("{}{}", self.base_url, path))
+            .basic_auth(&self.user, self.password.as_deref())
+    }
+
+    /// Sends the request built by `request`, if `retryable` until it succeeds, fails
+    /// with an error not retryable or the retries run out
+    async fn send(&self, retryable: bool, request: impl Fn() -> RequestBuilder) -> Result<Vec<u8>> {
+        let mut retry = 0;
+        loop {
+            let err = match request().send().await.context(RequestSnafu) {
+                Ok(resp) => match check_response(resp).await {
+                    Ok(body) => return Ok(body),
+                    Err(e) => e,
+                },
+                Err(e) => e,
+            };
+            if !retryable || retry >= self.retry.max_retries || !err.is_retryable() {
+                return Err(err);
+            }
+
+            let retry_after = match &err {
+                Error::Response { retry_after, .. } => *retry_after,
+                _ => None,
+            };
+            tokio::time::sleep(self.retry.wait(retry, retry_after)).await;
+            retry += 1;
+        }
+    }
+}
+
+async fn check_response(resp: Response) -> Result<Vec<u8>> {
+    let status = resp.status();
+    if status.is_success() {
+        return resp.bytes().await.map(|e| e.to_vec()).context(RequestSnafu);
+    }
+
+    let retry_after = resp
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|e| e.to_str().ok())
+        .and_then(|e| e.parse::<u64>().ok())
+        .map(Duration::from_secs);
+    let body = resp.text().await.context(RequestSnafu)?;
+    // The error response of the server, the body as it is otherwise
+    let (message, code, detail) = match serde_json::from_str::<ErrorResponse>(&body) {
+        Ok(e) => (
+            e.error_message().to_string(),
+            Some(e.error_code().to_string()),
+            e.error_detail().map(|e| e.to_string()),
+        ),
+        Err(_) => (body, None, None),
+    };
+
+    Err(Error::Response {
+        status,
+        message,
+        code,
+        detail,
+        retry_after,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let retry = RetryPolicy {
+            max_retries: 10,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(1),
+        };
+        assert_eq!(retry.backoff(0), Duration::from_millis(100));
+        assert_eq!(retry.backoff(2), Duration::from_millis(400));
+        assert_eq!(retry.backoff(4), Duration::from_secs(1));
+        assert_eq!(retry.backoff(64), Duration::from_secs(1));
+
+        assert_eq!(
+            retry.wait(0, Some(Duration::from_millis(500))),
+            Duration::from_millis(500)
+        );
+        assert_eq!(
+            retry.wait(0, Some(Duration::from_secs(3600))),
+            Duration::from_secs(1)
+        );
+        assert_eq!(retry.wait(2, None), Duration::from_millis(400));
+    }
+
+    #[test]
+    fn test_is_read() {
+        assert!(is_read("SELECT a FROM t"));
+        assert!(is_read("  (select 1) UNION (SELECT 2)"));
+        assert!(is_read("show tables"));
+        assert!(is_read("WITH c AS (SELECT 1) SELECT * FROM c"));
+        assert!(!is_read("INSERT INTO t(time, a) VALUES (1, 1)"));
+        assert!(!is_read("DROP TABLE t"));
+        assert!(!is_read("SELECTED"));
+    }
+
+    /// Answers the connections with the responses in order, one per connection
+    async fn serve(responses: Vec<&'static str>) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            for resp in responses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buf = [0_u8; 4096];
+                let _ = stream.read(&mut buf).await.unwrap();
+                stream.write_all(resp.as_bytes()).await.unwrap();
+                stream.shutdown().await.unwrap();
+            }
+        });
+        port
+    }
+
+    fn client(port: u16, max_retries: usize) -> Client {
+        Client::builder()
+            .port(port)
+            .pool_max_idle_per_host(0)
+            .retry_policy(RetryPolicy {
+                max_retries,
+                initial_backoff: Duration::from_millis(1),
+                max_backoff: Duration::from_millis(1),
+            })
+            .build()
+            .unwrap()
+    }
+
+    const UNAVAILABLE: &str = "HTTP/1.1 503 Service Unavailable\r\nconnection: close\r\ncontent-length: 54\r\n\r\n{\"error_code\":\"0200043\",\"error_message\":\"server busy\"}";
+    const BAD_REQUEST: &str =
+        "HTTP/1.1 422 Unprocessable Entity\r\nconnection: close\r\ncontent-length: 3\r\n\r\nbad";
+    const JSON_OK: &str =
+        "HTTP/1.1 200 OK\r\nconnection: close\r\ncontent-length: 17\r\n\r\n[{\"a\":1},{\"a\":2}]";
+
+    #[tokio::test]
+    async fn test_retry() {
+        let port = serve(vec![UNAVAILABLE, UNAVAILABLE, JSON_OK]).await;
+        let rows = client(port, 3).query_json("SELECT a FROM t").await.unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1]["a"], 2);
+
+        let port = serve(vec![UNAVAILABLE, UNAVAILABLE]).await;
+        let err = client(port, 1)
+            .query_json("SELECT a FROM t")
+            .await
+            .unwrap_err();
+        match err {
+            Error::Response {
+                status,
+                ref message,
+                ..
+            } => {
+                assert_eq!(status, reqwest::StatusCode::SERVICE_UNAVAILABLE);
+                assert_eq!(message, "server busy");
+                assert_eq!(err.error_code(), Some("0200043"));
+            }
+            e => panic!("unexpected error: {}", e),
+        }
+
+        // The writes are not retried
+        let port = serve(vec![UNAVAILABLE, JSON_OK]).await;
+        let err = client(port, 3)
+            .write_lines("t a=1i 1", Precision::Seconds)
+            .await
+            .unwrap_err();
+        assert!(err.is_retryable());
+
+        // Not retried
+        let port = serve(vec![BAD_REQUEST, JSON_OK]).await;
+        let err = client(port, 3)
+            .write_points(&[Point::new("t").field("a", 1_i64)], Precision::Seconds)
+            .await
+            .unwrap_err();
+        assert!(!err.is_retryable());
+        assert_eq!(err.error_code(), None);
+    }
+}
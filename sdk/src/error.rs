@@ -0,0 +1,62 @@
+use std::time::Duration;
+
+use reqwest::StatusCode;
+use snafu::Snafu;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub(crate)))]
+pub enum Error {
+    #[snafu(display("Invalid client config: {}", reason))]
+    Config { reason: String },
+
+    #[snafu(display("Failed to send the request: {}", source))]
+    Request { source: reqwest::Error },
+
+    #[snafu(display("Request failed with status {}: {}", status, message))]
+    Response {
+        status: StatusCode,
+        message: String,
+        // The error code of the server, see `models::error_code::ErrorCode`
+        code: Option<String>,
+        // The cause of the error reported by the server
+        detail: Option<String>,
+        // The Retry-After header of the response
+        retry_after: Option<Duration>,
+    },
+
+    #[snafu(display("Failed to decode the arrow results: {}", source))]
+    Arrow {
+        source: datafusion::arrow::error::ArrowError,
+    },
+
+    #[snafu(display("Failed to decode the json results: {}", source))]
+    Json { source: serde_json::Error },
+}
+
+impl Error {
+    /// The error code of the server, the clients may branch on it rather than on
+    /// the message
+    pub fn error_code(&self) -> Option<&str> {
+        match self {
+            Self::Response { code, .. } => code.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Whether the request may succeed if sent again, e.g. the server is
+    /// unreachable, overloaded or rate limits the requests
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Request { source } => source.is_connect() || source.is_timeout(),
+            Self::Response { status, .. } => {
+                *status == StatusCode::TOO_MANY_REQUESTS
+                    || *status == StatusCode::SERVICE_UNAVAILABLE
+                    || *status == StatusCode::BAD_GATEWAY
+                    || *status == StatusCode::GATEWAY_TIMEOUT
+            }
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,35 @@
+//! Rust client of CnosDB.
+//!
+//! ```no_run
+//! use cnosdb_client::{Client, Point, Precision};
+//!
+//! # async fn example() -> cnosdb_client::Result<()> {
+//! let client = Client::builder()
+//!     .host("127.0.0.1")
+//!     .port(31007)
+//!     .user("cnosdb")
+//!     .database("public")
+//!     .build()?;
+//!
+//! let point = Point::new("air")
+//!     .tag("station", "XiaoMaiDao")
+//!     .field("temperature", 68.5)
+//!     .timestamp(1667456411);
+//! client.write_points(&[point], Precision::Seconds).await?;
+//!
+//! let batches = client.query("SELECT * FROM air").await?;
+//! let rows = client.query_json("SELECT count(*) AS n FROM air").await?;
+//! # Ok(())
+//! # }
+//! ```
+
+mod client;
+mod error;
+mod point;
+
+pub use client::{
+    Client, ClientBuilder, RetryPolicy, DEFAULT_DATABASE, DEFAULT_HOST, DEFAULT_PORT, DEFAULT_USER,
+};
+pub use datafusion::arrow::record_batch::RecordBatch;
+pub use error::{Error, Result};
+pub use point::{FieldValue, Point, Precision};
@@ -0,0 +1,178 @@
+use std::fmt::{self, Display, Write};
+
+/// Value of a field of a point
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    UInteger(u64),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    String(String),
+}
+
+macro_rules! impl_from_for_field_value {
+    ($($t:ty => $variant:ident),* $(,)?) => {
+        $(
+            impl From<$t> for FieldValue {
+                fn from(value: $t) -> Self {
+                    FieldValue::$variant(value.into())
+                }
+            }
+        )*
+    };
+}
+
+impl_from_for_field_value!(
+    u64 => UInteger,
+    u32 => UInteger,
+    i64 => Integer,
+    i32 => Integer,
+    f64 => Float,
+    f32 => Float,
+    bool => Boolean,
+    String => String,
+    &str => String,
+);
+
+/// Unit of the timestamps of the written points
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Precision {
+    #[default]
+    Nanoseconds,
+    Microseconds,
+    Milliseconds,
+    Seconds,
+}
+
+impl Precision {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Nanoseconds => "ns",
+            Self::Microseconds => "us",
+            Self::Milliseconds => "ms",
+            Self::Seconds => "s",
+        }
+    }
+}
+
+/// A point written to a table, the tags and fields are named by the columns.
+///
+/// ```
+/// use cnosdb_client::Point;
+///
+/// let point = Point::new("air")
+///     .tag("station", "XiaoMaiDao")
+///     .field("temperature", 68.5)
+///     .field("pressure", 1013_u64)
+///     .timestamp(1667456411000000000);
+/// assert_eq!(
+///     point.to_string(),
+///     "air,station=XiaoMaiDao temperature=68.5,pressure=1013u 1667456411000000000"
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Point {
+    table: String,
+    tags: Vec<(String, String)>,
+    fields: Vec<(String, FieldValue)>,
+    timestamp: Option<i64>,
+}
+
+impl Point {
+    pub fn new(table: impl Into<String>) -> Self {
+        Self {
+            table: table.into(),
+            tags: vec![],
+            fields: vec![],
+            timestamp: None,
+        }
+    }
+
+    pub fn tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.tags.push((key.into(), value.into()));
+        self
+    }
+
+    pub fn field(mut self, key: impl Into<String>, value: impl Into<FieldValue>) -> Self {
+        self.fields.push((key.into(), value.into()));
+        self
+    }
+
+    /// Timestamp in the precision of the write, the time the server receives
+    /// the point if absent
+    pub fn timestamp(mut self, timestamp: i64) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    pub fn has_fields(&self) -> bool {
+        !self.fields.is_empty()
+    }
+}
+
+/// Writes the point as a line of the line protocol, escaping special characters
+impl Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_escaped(f, &self.table, &[',', ' '])?;
+        for (k, v) in self.tags.iter() {
+            f.write_char(',')?;
+            write_escaped(f, k, &[',', '=', ' '])?;
+            f.write_char('=')?;
+            write_escaped(f, v, &[',', '=', ' '])?;
+        }
+        for (i, (k, v)) in self.fields.iter().enumerate() {
+            f.write_char(if i == 0 { ' ' } else { ',' })?;
+            write_escaped(f, k, &[',', '=', ' '])?;
+            f.write_char('=')?;
+            match v {
+                FieldValue::UInteger(v) => write!(f, "{}u", v)?,
+                FieldValue::Integer(v) => write!(f, "{}i", v)?,
+                FieldValue::Float(v) => write!(f, "{}", v)?,
+                FieldValue::Boolean(v) => write!(f, "{}", v)?,
+                FieldValue::String(v) => {
+                    f.write_char('"')?;
+                    write_escaped(f, v, &['"', '\\'])?;
+                    f.write_char('"')?;
+                }
+            }
+        }
+        if let Some(timestamp) = self.timestamp {
+            write!(f, " {}", timestamp)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_escaped(f: &mut fmt::Formatter<'_>, s: &str, special: &[char]) -> fmt::Result {
+    for c in s.chars() {
+        if special.contains(&c) {
+            f.write_char('\\')?;
+        }
+        f.write_char(c)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_point_to_line() {
+        let point = Point::new("air quality")
+            .tag("station", "a,b=c")
+            .field("f", 1.5)
+            .field("i", -2_i64)
+            .field("b", true)
+            .field("s", "say \"hi\"")
+            .timestamp(1);
+        assert_eq!(
+            point.to_string(),
+            r#"air\ quality,station=a\,b\=c f=1.5,i=-2i,b=true,s="say \"hi\"" 1"#
+        );
+
+        let point = Point::new("m").field("f", 1_u64);
+        assert_eq!(point.to_string(), "m f=1u");
+        assert!(!Point::new("m").has_fields());
+    }
+}
@@ -0,0 +1,396 @@
+//! Change data capture, replays the committed writes of a database from the wal.
+//!
+//! An offset is the position of an entry in the wal, so a consumer resumes
+//! from the `next_offset` of the last batch it has processed, even across
+//! restarts of the server. The files of the wal from the offset of a consumer on
+//! are kept, see [`ChangeConsumers`].
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use models::codec::Encoding;
+use parking_lot::Mutex;
+use protos::models as fb_models;
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+use trace::warn;
+
+use crate::error::{self, Error, Result};
+use crate::file_system::file_manager;
+use crate::file_utils;
+use crate::tsm::codec::get_str_codec;
+use crate::tsm::DecodeSnafu;
+use crate::wal::{WalEntryType, WalReader};
+
+/// Position of an entry in the wal, displayed as `<file id>:<position>`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ChangeOffset {
+    pub file_id: u64,
+    pub pos: u64,
+}
+
+impl ChangeOffset {
+    /// The first entry still kept in the wal
+    pub const EARLIEST: ChangeOffset = ChangeOffset { file_id: 0, pos: 0 };
+
+    pub fn new(file_id: u64, pos: u64) -> Self {
+        Self { file_id, pos }
+    }
+}
+
+impl Display for ChangeOffset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file_id, self.pos)
+    }
+}
+
+impl FromStr for ChangeOffset {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("earliest") {
+            return Ok(Self::EARLIEST);
+        }
+
+        let invalid = || format!("invalid offset '{}', expected <file id>:<position>", s);
+        let (file_id, pos) = s.split_once(':').ok_or_else(invalid)?;
+        Ok(Self {
+            file_id: file_id.parse().map_err(|_| invalid())?,
+            pos: pos.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// A write committed into the wal
+#[derive(Debug, Clone)]
+pub struct ChangeRecord {
+    pub offset: ChangeOffset,
+    /// Flatbuffers `Points`
+    pub points: Vec<u8>,
+    /// Replayed from another replica rather than written by a client
+    pub replayed: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChangeBatch {
+    pub records: Vec<ChangeRecord>,
+    /// Offset to read the following records from
+    pub next_offset: ChangeOffset,
+    /// The file of the offset read from is removed, the writes between it and the
+    /// first record are lost
+    pub truncated: bool,
+}
+
+/// The file of the offsets of the consumers, in the directory of the storage
+pub const CONSUMERS_FILE: &str = "change_consumers.json";
+
+/// A consumer not reading the changes for so long is forgotten, the files of the
+/// wal kept for it are then removed once flushed
+pub const CONSUMER_EXPIRY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The offset read from by a consumer of the changes of a database, e.g. a replica
+/// or a standby cluster
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ConsumerOffset {
+    consumer: String,
+    database: String,
+    /// The directory of the wal of the database
+    dir: PathBuf,
+    file_id: u64,
+    pos: u64,
+    #[serde(skip, default = "Instant::now")]
+    read_at: Instant,
+}
+
+/// The offsets of the consumers of the changes, the files of the wal from the
+/// offsets on are not removed even if flushed, until the consumers read on or
+/// expire, see [`CONSUMER_EXPIRY`]. The offsets are saved once a consumer moves
+/// to another file, so they are kept across restarts.
+#[derive(Debug)]
+pub struct ChangeConsumers {
+    path: PathBuf,
+    offsets: Mutex<Vec<ConsumerOffset>>,
+}
+
+impl ChangeConsumers {
+    pub fn open(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let offsets = match std::fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                warn!("Ignore the change consumers '{}': {}", path.display(), e);
+                vec![]
+            }),
+            Err(_) => vec![],
+        };
+        Self {
+            path,
+            offsets: Mutex::new(offsets),
+        }
+    }
+
+    /// Keeps the files of the wal in the directory from the offset on for the
+    /// consumer of the changes of the database
+    pub fn commit(
+        &self,
+        consumer: &str,
+        database: &str,
+        dir: &Path,
+        offset: ChangeOffset,
+    ) -> Result<()> {
+        let mut offsets = self.offsets.lock();
+        let moved = match offsets
+            .iter_mut()
+            .find(|e| e.consumer == consumer && e.database == database)
+        {
+            Some(e) => {
+                let moved = e.file_id != offset.file_id || e.dir != dir;
+                e.dir = dir.to_path_buf();
+                e.file_id = offset.file_id;
+                e.pos = offset.pos;
+                e.read_at = Instant::now();
+                moved
+            }
+            None => {
+                offsets.push(ConsumerOffset {
+                    consumer: consumer.to_string(),
+                    database: database.to_string(),
+                    dir: dir.to_path_buf(),
+                    file_id: offset.file_id,
+                    pos: offset.pos,
+                    read_at: Instant::now(),
+                });
+                true
+            }
+        };
+        if !moved {
+            return Ok(());
+        }
+
+        let content =
+            serde_json::to_string(&*offsets).map_err(|e| Error::IO { source: e.into() })?;
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, content).context(error::IOSnafu)?;
+        std::fs::rename(&tmp, &self.path).context(error::IOSnafu)
+    }
+
+    /// The min id of the files of the wal kept of each directory, the consumers
+    /// expired are forgotten
+    pub fn retained(&self) -> HashMap<PathBuf, u64> {
+        let mut offsets = self.offsets.lock();
+        offsets.retain(|e| {
+            let expired = e.read_at.elapsed() > CONSUMER_EXPIRY;
+            if expired {
+                warn!(
+                    "Change consumer '{}' of '{}' expires at {}:{}",
+                    e.consumer, e.database, e.file_id, e.pos
+                );
+            }
+            !expired
+        });
+
+        let mut retained = HashMap::new();
+        for e in offsets.iter() {
+            let file_id = retained.entry(e.dir.clone()).or_insert(e.file_id);
+            *file_id = (*file_id).min(e.file_id);
+        }
+        retained
+    }
+}
+
+/// Reads at most `limit` writes of `database` at or after `from`
+pub fn read_changes(
+    wal_dir: impl AsRef<Path>,
+    database: &str,
+    from: ChangeOffset,
+    limit: usize,
+) -> Result<ChangeBatch> {
+    let wal_dir = wal_dir.as_ref();
+    let mut file_ids = file_manager::list_file_names(wal_dir)
+        .iter()
+        .filter(|e| file_utils::check_wal_file_name(e))
+        .map(|e| file_utils::get_wal_file_id(e))
+        .collect::<Result<Vec<_>>>()?;
+    file_ids.sort_unstable();
+    let truncated = from != ChangeOffset::EARLIEST && !file_ids.contains(&from.file_id);
+    file_ids.retain(|id| *id >= from.file_id);
+
+    let mut records = Vec::new();
+    let mut next_offset = from;
+    let decoder = get_str_codec(Encoding::Zstd);
+
+    for file_id in file_ids {
+        let path = file_utils::make_wal_file(wal_dir, file_id);
+        let file = file_manager::get_file_manager().open_file(&path)?;
+        if file.is_empty() {
+            continue;
+        }
+        let file_len = file.len();
+        let mut reader = WalReader::new(file.into())?;
+        if file_id == from.file_id {
+            reader.set_pos(from.pos.min(file_len));
+        }
+        next_offset = ChangeOffset::new(file_id, reader.pos());
+
+        while records.len() < limit {
+            let offset = ChangeOffset::new(file_id, reader.pos());
+            let entry = match reader.next_wal_entry() {
+                Ok(Some(e)) => e,
+                // The rest of the file is not written yet
+                Ok(None) | Err(Error::WalTruncated) => break,
+                Err(e) => return Err(e),
+            };
+            next_offset = ChangeOffset::new(file_id, reader.pos());
+
+            let replayed = match entry.typ {
+                WalEntryType::Write => false,
+                WalEntryType::Replay => true,
+                _ => continue,
+            };
+            let mut buf = Vec::new();
+            decoder.decode(&entry.buf, &mut buf).context(DecodeSnafu)?;
+            let points = match buf.pop() {
+                Some(points) => points.to_vec(),
+                None => continue,
+            };
+            let db = flatbuffers::root::<fb_models::Points>(&points)
+                .context(error::InvalidFlatbufferSnafu)?
+                .db()
+                .map(|e| e.to_vec())
+                .unwrap_or_default();
+            if db == database.as_bytes() {
+                records.push(ChangeRecord {
+                    offset,
+                    points,
+                    replayed,
+                });
+            }
+        }
+
+        if records.len() >= limit {
+            break;
+        }
+    }
+
+    Ok(ChangeBatch {
+        records,
+        next_offset,
+        truncated,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use config::get_config;
+
+    use super::*;
+    use crate::kv_option::WalOptions;
+    use crate::wal::WalManager;
+
+    async fn write_points(mgr: &mut WalManager, db: &str, typ: WalEntryType) {
+        let mut fbb = flatbuffers::FlatBufferBuilder::new();
+        let fb_db = fbb.create_vector(db.as_bytes());
+        let fb_points = fbb.create_vector::<flatbuffers::WIPOffset<fb_models::Point>>(&[]);
+        let points = fb_models::Points::create(
+            &mut fbb,
+            &fb_models::PointsArgs {
+                db: Some(fb_db),
+                points: Some(fb_points),
+            },
+        );
+        fbb.finish(points, None);
+
+        let mut enc_points = Vec::new();
+        get_str_codec(Encoding::Zstd)
+            .encode(&[fbb.finished_data()], &mut enc_points)
+            .unwrap();
+        mgr.write(typ, &enc_points).await.unwrap();
+    }
+
+    #[test]
+    fn test_change_consumers() {
+        let dir = "/tmp/test/cdc/consumers";
+        let _ = std::fs::remove_dir_all(dir);
+        std::fs::create_dir_all(dir).unwrap();
+        let path = Path::new(dir).join("consumers.json");
+        let (wal_a, wal_b) = (Path::new("/wal/a"), Path::new("/wal/b"));
+
+        let consumers = ChangeConsumers::open(&path);
+        consumers
+            .commit("replica-1", "db0", wal_a, ChangeOffset::new(3, 0))
+            .unwrap();
+        consumers
+            .commit("replica-2", "db0", wal_a, ChangeOffset::new(5, 0))
+            .unwrap();
+        consumers
+            .commit("replica-1", "db1", wal_b, ChangeOffset::new(2, 8))
+            .unwrap();
+        consumers
+            .commit("replica-1", "db0", wal_a, ChangeOffset::new(4, 16))
+            .unwrap();
+        let expected = HashMap::from([(wal_a.to_path_buf(), 4), (wal_b.to_path_buf(), 2)]);
+        assert_eq!(consumers.retained(), expected);
+
+        // Kept across restarts
+        let consumers = ChangeConsumers::open(&path);
+        assert_eq!(consumers.retained(), expected);
+    }
+
+    #[test]
+    fn test_parse_offset() {
+        assert_eq!(
+            ChangeOffset::from_str("3:1024").unwrap(),
+            ChangeOffset::new(3, 1024)
+        );
+        assert_eq!(
+            ChangeOffset::from_str("earliest").unwrap(),
+            ChangeOffset::EARLIEST
+        );
+        assert_eq!(ChangeOffset::new(3, 1024).to_string(), "3:1024");
+        assert!(ChangeOffset::from_str("3").is_err());
+        assert!(ChangeOffset::from_str("a:1").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_read_changes() {
+        let dir = "/tmp/test/cdc/1".to_string();
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut global_config = get_config("../config/config.toml");
+        global_config.wal.path = dir.clone();
+        let mut mgr = WalManager::new(Arc::new(WalOptions::from(&global_config)));
+
+        for db in ["db", "other", "db"] {
+            write_points(&mut mgr, db, WalEntryType::Write).await;
+        }
+        write_points(&mut mgr, "db", WalEntryType::Replay).await;
+
+        let batch = read_changes(&dir, "db", ChangeOffset::EARLIEST, 2).unwrap();
+        assert_eq!(batch.records.len(), 2);
+        assert!(batch.records[0].offset < batch.records[1].offset);
+        assert!(batch.records[1].offset < batch.next_offset);
+        assert!(!batch.records[1].replayed);
+        assert!(!batch.truncated);
+
+        // Resume from the last batch
+        let batch = read_changes(&dir, "db", batch.next_offset, 10).unwrap();
+        assert_eq!(batch.records.len(), 1);
+        assert!(batch.records[0].replayed);
+        assert!(!batch.truncated);
+
+        // Nothing new
+        let next = read_changes(&dir, "db", batch.next_offset, 10).unwrap();
+        assert!(next.records.is_empty());
+        assert_eq!(next.next_offset, batch.next_offset);
+
+        let batch = read_changes(&dir, "other", ChangeOffset::EARLIEST, 10).unwrap();
+        assert_eq!(batch.records.len(), 1);
+
+        // The file of the offset is removed
+        let batch = read_changes(&dir, "db", ChangeOffset::new(0, 128), 10).unwrap();
+        assert!(batch.truncated);
+        assert_eq!(batch.records.len(), 3);
+    }
+}
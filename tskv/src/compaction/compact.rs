@@ -7,24 +7,26 @@ use std::{
 };
 
 use evmap::new;
-use models::{FieldId, Timestamp, ValueType};
+use models::{codec::Encoding, FieldId, Timestamp, ValueType};
 use snafu::ResultExt;
 use trace::{debug, error, info, trace};
 
 use crate::file_system::file_manager::{self, get_file_manager};
 use crate::{
-    compaction::CompactReq,
+    compaction::{CompactReq, CompactionThrottle},
     context::GlobalContext,
     error::{self, Result},
     file_system::DmaFile,
     file_utils,
-    kv_option::Options,
+    kv_option::{LevelCompression, Options},
     memcache::DataType,
     summary::{CompactMeta, VersionEdit},
     tseries_family::{ColumnFile, TimeRange},
     tsm::{
-        self, BlockMeta, BlockMetaIterator, ColumnReader, DataBlock, Index, IndexIterator,
-        IndexMeta, IndexReader, TsmReader, TsmWriter,
+        self,
+        codec::{get_encoding, DataBlockEncoding},
+        BlockMeta, BlockMetaIterator, ColumnReader, DataBlock, Index, IndexIterator, IndexMeta,
+        IndexReader, TsmReader, TsmWriter,
     },
     Error, LevelId,
 };
@@ -429,6 +431,7 @@ pub fn overlaps_tuples(r1: (i64, i64), r2: (i64, i64)) -> bool {
 pub fn run_compaction_job(
     request: CompactReq,
     kernel: Arc<GlobalContext>,
+    throttle: &CompactionThrottle,
 ) -> Result<Option<VersionEdit>> {
     info!(
         "Compaction: Running compaction job on ts_family: {} and files: [ {} ]",
@@ -480,6 +483,7 @@ pub fn run_compaction_job(
         max_datablock_values: max_data_block_size,
         ..Default::default()
     };
+    let compression = storage_opt.compression.database(&request.database);
     let tsm_dir = storage_opt.tsm_dir(&request.database, tsf_id);
     let mut tsm_writer = tsm::new_tsm_writer(&tsm_dir, kernel.file_id_next(), false, 0)?;
     info!("Compaction: File {} been created.", tsm_writer.sequence());
@@ -487,17 +491,24 @@ pub fn run_compaction_job(
     version_edit.tsf_id = tsf_id;
     for next_blk in iter.flatten() {
         trace!("===============================");
+        let written = tsm_writer.size();
         let write_ret = match next_blk {
             CompactingBlock::DataBlock {
                 field_id: fid,
-                data_block: b,
+                data_block: mut b,
                 ..
             } => {
-                // TODO: let enc = b.encodings();
+                recompress(&mut b, &compression, request.out_level);
                 tsm_writer.write_block(fid, &b)
             }
-            CompactingBlock::Raw { meta, raw, .. } => tsm_writer.write_raw(&meta, &raw),
+            CompactingBlock::Raw { meta, raw, .. } => {
+                match recompress_raw(&meta, &raw, &compression, request.out_level)? {
+                    Some(b) => tsm_writer.write_block(meta.field_id(), &b),
+                    None => tsm_writer.write_raw(&meta, &raw),
+                }
+            }
         };
+        throttle.acquire(tsm_writer.size().saturating_sub(written));
         if let Err(e) = write_ret {
             match e {
                 tsm::WriteTsmError::IO { source } => {
@@ -551,6 +562,54 @@ pub fn run_compaction_job(
     Ok(Some(version_edit))
 }
 
+/// Whether the strings of the block compressed by the codec are compressed by the
+/// codec of the level instead, the strings of the other codecs are chosen by the
+/// columns
+fn is_recompressed(codec: Encoding, compression: &LevelCompression, level: LevelId) -> bool {
+    // The strings compressed by snappy were tagged GORILLA before
+    let codec = match codec {
+        Encoding::Gorilla => Encoding::Snappy,
+        _ => codec,
+    };
+    let level_codec = compression.codec(level);
+    level_codec != codec && (codec == compression.fast_codec || codec == Encoding::Default)
+}
+
+fn recompress(block: &mut DataBlock, compression: &LevelCompression, level: LevelId) {
+    if block.field_type() != ValueType::String {
+        return;
+    }
+    let (ts_encoding, val_encoding) = block.encodings().split();
+    if is_recompressed(val_encoding, compression, level) {
+        block.set_encodings(DataBlockEncoding::new(
+            ts_encoding,
+            compression.codec(level),
+        ));
+    }
+}
+
+/// The raw block decoded to be compressed by the codec of the level, None if it
+/// is written as it is
+fn recompress_raw(
+    meta: &BlockMeta,
+    raw: &[u8],
+    compression: &LevelCompression,
+    level: LevelId,
+) -> Result<Option<DataBlock>> {
+    if meta.field_type() != ValueType::String {
+        return Ok(None);
+    }
+    let val_off = meta.val_off() - meta.offset();
+    let codec = get_encoding(raw.get(val_off as usize + 4..).unwrap_or_default());
+    if !is_recompressed(codec, compression, level) {
+        return Ok(None);
+    }
+    let mut block =
+        tsm::decode_data_block(raw, meta.field_type(), val_off).context(error::ReadTsmSnafu)?;
+    recompress(&mut block, compression, level);
+    Ok(Some(block))
+}
+
 fn new_compact_meta(tsm_writer: &TsmWriter, level: LevelId) -> CompactMeta {
     CompactMeta {
         file_id: tsm_writer.sequence(),
@@ -579,15 +638,16 @@ mod test {
         },
     };
 
-    use models::{FieldId, Timestamp, ValueType};
+    use models::{codec::Encoding, FieldId, Timestamp, ValueType};
     use utils::BloomFilter;
 
+    use super::recompress;
     use crate::file_system::file_manager;
     use crate::{
-        compaction::{run_compaction_job, CompactReq},
+        compaction::{run_compaction_job, CompactReq, CompactionThrottle},
         context::GlobalContext,
         file_utils,
-        kv_option::Options,
+        kv_option::{LevelCompression, Options},
         summary::VersionEdit,
         tseries_family::{ColumnFile, LevelInfo, TimeRange, Version},
         tsm::{self, codec::DataBlockEncoding, DataBlock, Tombstone, TsmReader, TsmTombstone},
@@ -747,7 +807,10 @@ mod test {
         let (next_file_id, files) = write_data_blocks_to_column_file(&dir, data, 1, opt.clone());
         let (compact_req, kernel) =
             prepare_compact_req_and_kernel(database, opt, next_file_id, files);
-        let version_edit = run_compaction_job(compact_req, kernel).unwrap().unwrap();
+        let version_edit =
+            run_compaction_job(compact_req, kernel, &CompactionThrottle::unlimited())
+                .unwrap()
+                .unwrap();
         check_column_file(dir, version_edit, expected_data);
     }
 
@@ -786,7 +849,10 @@ mod test {
         let (next_file_id, files) = write_data_blocks_to_column_file(&dir, data, 1, opt.clone());
         let (compact_req, kernel) =
             prepare_compact_req_and_kernel(database, opt, next_file_id, files);
-        let version_edit = run_compaction_job(compact_req, kernel).unwrap().unwrap();
+        let version_edit =
+            run_compaction_job(compact_req, kernel, &CompactionThrottle::unlimited())
+                .unwrap()
+                .unwrap();
         check_column_file(dir, version_edit, expected_data);
     }
 
@@ -825,7 +891,10 @@ mod test {
         let (next_file_id, files) = write_data_blocks_to_column_file(&dir, data, 1, opt.clone());
         let (compact_req, kernel) =
             prepare_compact_req_and_kernel(database, opt, next_file_id, files);
-        let version_edit = run_compaction_job(compact_req, kernel).unwrap().unwrap();
+        let version_edit =
+            run_compaction_job(compact_req, kernel, &CompactionThrottle::unlimited())
+                .unwrap()
+                .unwrap();
         check_column_file(dir, version_edit, expected_data);
     }
 
@@ -1041,7 +1110,10 @@ mod test {
         let (compact_req, kernel) =
             prepare_compact_req_and_kernel(database, opt, next_file_id, column_files);
 
-        let version_edit = run_compaction_job(compact_req, kernel).unwrap().unwrap();
+        let version_edit =
+            run_compaction_job(compact_req, kernel, &CompactionThrottle::unlimited())
+                .unwrap()
+                .unwrap();
 
         check_column_file(dir, version_edit, expected_data);
     }
@@ -1190,8 +1262,41 @@ mod test {
         let (compact_req, kernel) =
             prepare_compact_req_and_kernel(database, opt, next_file_id, column_files);
 
-        let version_edit = run_compaction_job(compact_req, kernel).unwrap().unwrap();
+        let version_edit =
+            run_compaction_job(compact_req, kernel, &CompactionThrottle::unlimited())
+                .unwrap()
+                .unwrap();
 
         check_column_file(dir, version_edit, expected_data);
     }
+
+    #[test]
+    fn test_recompress() {
+        let compression = LevelCompression {
+            fast_codec: Encoding::Snappy,
+            strong_codec: Encoding::Zstd,
+            strong_level: 3,
+        };
+        let str_block = |codec: Encoding| DataBlock::Str {
+            ts: vec![1, 2],
+            val: vec![MiniVec::from(&b"a"[..]), MiniVec::from(&b"b"[..])],
+            enc: DataBlockEncoding::new(Encoding::Default, codec),
+        };
+
+        let mut block = str_block(Encoding::Snappy);
+        recompress(&mut block, &compression, 2);
+        assert_eq!(block.encodings().split().1, Encoding::Snappy);
+        recompress(&mut block, &compression, 3);
+        assert_eq!(block.encodings().split().1, Encoding::Zstd);
+
+        // The snappy tagged GORILLA before
+        let mut block = str_block(Encoding::Gorilla);
+        recompress(&mut block, &compression, 4);
+        assert_eq!(block.encodings().split().1, Encoding::Zstd);
+
+        // The codec chosen by the column is kept
+        let mut block = str_block(Encoding::Gzip);
+        recompress(&mut block, &compression, 4);
+        assert_eq!(block.encodings().split().1, Encoding::Gzip);
+    }
 }
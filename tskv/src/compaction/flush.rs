@@ -8,6 +8,7 @@ use std::{
     sync::Arc,
 };
 
+use metrics::incr_compaction_backlog;
 use models::codec::Encoding;
 use models::schema::TskvTableSchema;
 use models::utils::split_id;
@@ -26,7 +27,7 @@ use tokio::{
         oneshot::Sender,
     },
 };
-use trace::{debug, error, info, log_error, warn};
+use trace::{debug, error, info, info_span, log_error, warn};
 
 use crate::{
     compaction::FlushReq,
@@ -34,7 +35,7 @@ use crate::{
     database::Database,
     error::{self, Error, Result},
     index::IndexResult,
-    kv_option::Options,
+    kv_option::{LevelCompression, Options},
     memcache::{DataType, FieldVal, MemCache, SeriesData},
     summary::{CompactMeta, SummaryTask, VersionEdit},
     tseries_family::{LevelInfo, Version},
@@ -108,10 +109,12 @@ impl FlushTask {
         }
 
         let mut max_level_ts = version.max_level_ts;
+        let compression = version.storage_opt.compression.database(&version.database);
         let mut compact_metas = self.flush_mem_caches(
             flushing_mems_data,
             max_level_ts,
             tsm::MAX_BLOCK_VALUES as usize,
+            &compression,
         )?;
         let mut edit = VersionEdit::new();
         for cm in compact_metas.iter_mut() {
@@ -125,7 +128,7 @@ impl FlushTask {
         version_edits.push(edit);
 
         for mem in flushing_mems.iter_mut() {
-            mem.flushed = true;
+            mem.mark_flushed();
         }
 
         Ok(())
@@ -138,6 +141,7 @@ impl FlushTask {
         mut caches_data: HashMap<SeriesId, Vec<Arc<RwLock<SeriesData>>>>,
         max_level_ts: Timestamp,
         data_block_size: usize,
+        compression: &LevelCompression,
     ) -> Result<Vec<CompactMeta>> {
         let mut delta_writer: Option<TsmWriter> = None;
         let mut tsm_writer: Option<TsmWriter> = None;
@@ -155,17 +159,15 @@ impl FlushTask {
                     self.build_codec_map(sch_cols, &mut field_id_code_type_map);
                     // Iterates [ RowData ]
                     for row in rows.iter() {
-                        // Iterates RowData -> [ Option<FieldVal>, column_id ]
-                        for (val, col) in row.fields.iter().zip(sch_cols.fields().iter()) {
-                            if let Some(v) = val {
-                                schema_columns_value_type_map
-                                    .entry(col.id)
-                                    .or_insert_with(|| v.value_type());
-                                column_values_map
-                                    .entry(col.id)
-                                    .or_insert_with(Vec::new)
-                                    .push((row.ts, v.clone()));
-                            }
+                        // Iterates RowData -> [ (column_id, FieldVal) ], only written fields
+                        for (col_id, v) in row.fields.iter() {
+                            schema_columns_value_type_map
+                                .entry(*col_id)
+                                .or_insert_with(|| v.value_type());
+                            column_values_map
+                                .entry(*col_id)
+                                .or_insert_with(Vec::new)
+                                .push((row.ts, v.clone()));
                         }
                     }
                 }
@@ -183,13 +185,16 @@ impl FlushTask {
             // Write the merged data into files.
             for (field_id, dlt_blks, tsm_blks) in merged_series_data {
                 let (table_field_id, _) = split_id(field_id);
-                let encoding = DataBlockEncoding::new(
-                    Encoding::Default,
-                    field_id_code_type_map
-                        .get(&table_field_id)
-                        .copied()
-                        .unwrap_or_default(),
-                );
+                let mut val_encoding = field_id_code_type_map
+                    .get(&table_field_id)
+                    .copied()
+                    .unwrap_or_default();
+                let is_string =
+                    schema_columns_value_type_map.get(&table_field_id) == Some(&ValueType::String);
+                if is_string && val_encoding == Encoding::Default {
+                    val_encoding = compression.fast_codec;
+                }
+                let encoding = DataBlockEncoding::new(Encoding::Default, val_encoding);
 
                 if !dlt_blks.is_empty() {
                     if delta_writer.is_none() {
@@ -356,6 +361,7 @@ pub fn run_flush_memtable_job(
     summary_task_sender: UnboundedSender<SummaryTask>,
     compact_task_sender: UnboundedSender<TseriesFamilyId>,
 ) -> Result<()> {
+    let _span = info_span!("flush", mems = req.mems.len()).entered();
     let mut tsf_caches: HashMap<TseriesFamilyId, Vec<Arc<RwLock<MemCache>>>> = HashMap::new();
     {
         info!("Flush: Running flush job on {} MemCaches", req.mems.len());
@@ -395,6 +401,8 @@ pub fn run_flush_memtable_job(
 
             if let Err(e) = compact_task_sender.send(*tsf_id) {
                 warn!("failed to send compact task, {}", e);
+            } else {
+                incr_compaction_backlog();
             }
         }
     }
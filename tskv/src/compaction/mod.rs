@@ -1,12 +1,14 @@
 mod compact;
 mod flush;
 mod picker;
+mod throttle;
 
 pub use compact::*;
 pub use flush::*;
 use parking_lot::RwLock;
 pub use picker::*;
 use std::sync::Arc;
+pub use throttle::*;
 
 use crate::{
     kv_option::StorageOptions,
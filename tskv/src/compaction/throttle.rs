@@ -0,0 +1,135 @@
+//! Throttles the compactions, so the background merges do not starve the IO of
+//! the writes and the queries.
+//!
+//! The bytes written by all the compactions are limited per second, and the
+//! compactions of at most the number of ts families run at the same time. Both
+//! limits are changed on the fly by `ALTER SYSTEM SET`.
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use tokio::sync::Notify;
+
+use crate::kv_option::CompactionOptions;
+
+/// Bytes written without waiting after idle, of the rate of a second
+const MAX_BURST: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
+pub struct CompactionThrottle {
+    options: Arc<CompactionOptions>,
+    /// Time the bytes acquired before are written by the rate
+    next: Mutex<Instant>,
+    running: Mutex<usize>,
+    released: Notify,
+}
+
+impl CompactionThrottle {
+    pub fn new(options: Arc<CompactionOptions>) -> Self {
+        Self {
+            options,
+            next: Mutex::new(Instant::now()),
+            running: Mutex::new(0),
+            released: Notify::new(),
+        }
+    }
+
+    /// Not limited
+    pub fn unlimited() -> Self {
+        Self::new(Arc::new(CompactionOptions::default()))
+    }
+
+    /// Blocks the compaction to write the bytes at the rate limited
+    pub fn acquire(&self, bytes: u64) {
+        let wait = self.delay(bytes, Instant::now());
+        if !wait.is_zero() {
+            std::thread::sleep(wait);
+        }
+    }
+
+    /// Time to wait before the bytes are written at `now`
+    fn delay(&self, bytes: u64, now: Instant) -> Duration {
+        let rate = self.options.max_compact_bytes_per_sec();
+        if rate == 0 || bytes == 0 {
+            return Duration::ZERO;
+        }
+        // The bytes are written ahead of the rate by the burst at most
+        let mut next = self.next.lock();
+        let start = (*next).max(now);
+        *next = start + Duration::from_secs_f64(bytes as f64 / rate as f64);
+        next.saturating_duration_since(now + MAX_BURST)
+    }
+
+    /// Waits until fewer compactions than the limit are running, the compaction
+    /// is counted running until the permit is dropped
+    pub async fn start_compaction(self: &Arc<Self>) -> CompactionPermit {
+        loop {
+            let released = self.released.notified();
+            {
+                let mut running = self.running.lock();
+                if *running < self.options.max_concurrent_compactions() {
+                    *running += 1;
+                    return CompactionPermit {
+                        throttle: self.clone(),
+                    };
+                }
+            }
+            released.await;
+        }
+    }
+
+    /// Wakes the compactions waiting once the limits are changed
+    pub fn limits_changed(&self) {
+        self.released.notify_waiters();
+    }
+}
+
+pub struct CompactionPermit {
+    throttle: Arc<CompactionThrottle>,
+}
+
+impl Drop for CompactionPermit {
+    fn drop(&mut self) {
+        *self.throttle.running.lock() -= 1;
+        self.throttle.released.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use config::StorageConfig;
+
+    use super::*;
+
+    #[test]
+    fn test_delay() {
+        let throttle = CompactionThrottle::unlimited();
+        let now = Instant::now();
+        assert_eq!(throttle.delay(1 << 30, now), Duration::ZERO);
+
+        let mut config: StorageConfig = config::get_config("../config/config.toml").storage;
+        config.max_compact_mb_per_sec = 1;
+        throttle.options.update(&config);
+        let now = Instant::now();
+        *throttle.next.lock() = now;
+        // A burst of a second is not delayed
+        assert_eq!(throttle.delay(1024 * 1024, now), Duration::ZERO);
+        assert_eq!(throttle.delay(512 * 1024, now), Duration::from_millis(500));
+    }
+
+    #[tokio::test]
+    async fn test_start_compaction() {
+        let throttle = Arc::new(CompactionThrottle::unlimited());
+        let permit = throttle.start_compaction().await;
+        let waiting = {
+            let throttle = throttle.clone();
+            tokio::spawn(async move { throttle.start_compaction().await })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiting.is_finished());
+
+        drop(permit);
+        let _permit = waiting.await.unwrap();
+        assert_eq!(*throttle.running.lock(), 1);
+    }
+}
@@ -3,6 +3,7 @@ use std::sync::{
     atomic::{AtomicU32, AtomicU64, Ordering},
     Arc,
 };
+use tokio::sync::Notify;
 
 #[derive(Default, Debug)]
 pub struct GlobalContext {
@@ -10,6 +11,9 @@ pub struct GlobalContext {
     mem_seq: AtomicU64,
     last_seq: AtomicU64,
     tsfamily_id: AtomicU32,
+    /// Bytes of the immutable memcaches not flushed yet
+    flush_pending_size: AtomicU64,
+    flushed: Notify,
 }
 
 impl GlobalContext {
@@ -19,6 +23,8 @@ impl GlobalContext {
             mem_seq: AtomicU64::new(0),
             last_seq: AtomicU64::new(0),
             tsfamily_id: AtomicU32::new(0),
+            flush_pending_size: AtomicU64::new(0),
+            flushed: Notify::new(),
         }
     }
 }
@@ -83,4 +89,41 @@ impl GlobalContext {
             }
         }
     }
+
+    pub fn flush_pending_size(&self) -> u64 {
+        self.flush_pending_size.load(Ordering::Acquire)
+    }
+
+    /// The memcache of the bytes is pending flush until the guard is dropped, once
+    /// it is flushed or dropped with its ts family
+    pub fn flush_pending(self: &Arc<Self>, size: u64) -> FlushPending {
+        self.flush_pending_size.fetch_add(size, Ordering::AcqRel);
+        FlushPending {
+            ctx: self.clone(),
+            size,
+        }
+    }
+
+    /// Completes once the next flush is done, the wakeups are not missed once it is
+    /// created, so create it before checking the size pending flush
+    pub fn flushed(&self) -> tokio::sync::futures::Notified<'_> {
+        self.flushed.notified()
+    }
+}
+
+/// The bytes of a memcache counted as pending flush, released as it is dropped
+#[derive(Debug)]
+pub struct FlushPending {
+    ctx: Arc<GlobalContext>,
+    size: u64,
+}
+
+impl Drop for FlushPending {
+    /// The writers waiting are woken
+    fn drop(&mut self) {
+        self.ctx
+            .flush_pending_size
+            .fetch_sub(self.size, Ordering::AcqRel);
+        self.ctx.flushed.notify_waiters();
+    }
 }
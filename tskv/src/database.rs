@@ -12,7 +12,7 @@ use tokio::sync::watch::Receiver;
 use tokio::sync::{mpsc::UnboundedSender, oneshot};
 
 use ::models::{FieldInfo, InMemPoint, Tag, ValueType};
-use models::schema::{DatabaseSchema, TableColumn, TableSchema, TskvTableSchema};
+use models::schema::{DatabaseSchema, TableColumn, TableSchema, TableStatistics, TskvTableSchema};
 use models::utils::{split_id, unite_id};
 use models::{ColumnId, SchemaId, SeriesId, SeriesKey, Timestamp};
 use protos::models::{Point, Points};
@@ -44,6 +44,9 @@ pub struct Database {
     index: Arc<db_index::DBIndex>,
     ts_families: HashMap<TseriesFamilyId, Arc<RwLock<TseriesFamily>>>,
     opt: Arc<Options>,
+    /// Held shared by the writes from resolving the series to the memcache, and
+    /// exclusively by the series gc removing the series from the index
+    write_barrier: Arc<tokio::sync::RwLock<()>>,
 }
 
 impl Database {
@@ -56,6 +59,7 @@ impl Database {
             name: schema.name,
             ts_families: HashMap::new(),
             opt,
+            write_barrier: Arc::new(tokio::sync::RwLock::new(())),
         };
         Ok(db)
     }
@@ -75,7 +79,7 @@ impl Database {
         let tf = TseriesFamily::new(
             ver.tf_id(),
             ver.database().to_string(),
-            MemCache::new(ver.tf_id(), self.opt.cache.max_buffer_size, ver.last_seq),
+            MemCache::new(ver.tf_id(), self.opt.cache.max_buffer_size(), ver.last_seq),
             ver.clone(),
             self.opt.cache.clone(),
             self.opt.storage.clone(),
@@ -89,7 +93,7 @@ impl Database {
         if let Some(tf) = self.ts_families.get(&tf_id) {
             let mem = Arc::new(RwLock::new(MemCache::new(
                 tf_id,
-                self.opt.cache.max_buffer_size,
+                self.opt.cache.max_buffer_size(),
                 seq,
             )));
             let mut tf = tf.write();
@@ -118,7 +122,7 @@ impl Database {
         let tf = TseriesFamily::new(
             tsf_id,
             self.name.clone(),
-            MemCache::new(tsf_id, self.opt.cache.max_buffer_size, seq_no),
+            MemCache::new(tsf_id, self.opt.cache.max_buffer_size(), seq_no),
             ver,
             self.opt.cache.clone(),
             self.opt.storage.clone(),
@@ -271,7 +275,8 @@ impl Database {
         Ok(id)
     }
 
-    pub fn version_edit(&self, last_seq: u64) -> (Vec<VersionEdit>, Vec<VersionEdit>) {
+    /// Edits of the ts families and the files, with the seq persisted of each ts family
+    pub fn version_edit(&self) -> (Vec<VersionEdit>, Vec<VersionEdit>) {
         let mut edits = vec![];
         let mut files = vec![];
 
@@ -289,7 +294,7 @@ impl Database {
                 for file in files.files.iter() {
                     let mut meta = CompactMeta::from(file.as_ref());
                     meta.tsf_id = files.tsf_id;
-                    meta.high_seq = last_seq;
+                    meta.high_seq = version.last_seq;
                     edit.add_file(meta, max_level_ts);
                 }
             }
@@ -320,6 +325,14 @@ impl Database {
         Ok(())
     }
 
+    pub fn set_table_statistics(
+        &self,
+        table: &str,
+        statistics: TableStatistics,
+    ) -> IndexResult<()> {
+        self.index.set_table_statistics(table, statistics)
+    }
+
     pub fn get_series_key(&self, sid: u64) -> IndexResult<Option<SeriesKey>> {
         self.index.get_series_key(sid)
     }
@@ -359,6 +372,10 @@ impl Database {
         self.index.clone()
     }
 
+    pub fn write_barrier(&self) -> Arc<tokio::sync::RwLock<()>> {
+        self.write_barrier.clone()
+    }
+
     // todo: will delete in cluster version
     pub fn get_tsfamily_random(&self) -> Option<Arc<RwLock<TseriesFamily>>> {
         if let Some((_, v)) = self.ts_families.iter().next() {
@@ -0,0 +1,150 @@
+//! Rejects the writes before the volumes of the data and the wal are full, so a
+//! flush or a wal write is not failed for no space left.
+//!
+//! The writes are accepted again only once the free space is well above the
+//! threshold, so the node does not flap around it.
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use metrics::{set_disk_free_space, set_read_only};
+use parking_lot::RwLock;
+use tokio::runtime::Runtime;
+use trace::{error, info, warn};
+
+use crate::error::{Error, Result};
+
+/// Interval of checking the free space of the volumes
+pub const DISK_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Debug)]
+pub struct DiskWatchdog {
+    /// Directories written to, named
+    dirs: Vec<(String, PathBuf)>,
+    min_free_space: u64,
+    resume_free_space: u64,
+    read_only: AtomicBool,
+    /// Why the writes are rejected
+    reason: RwLock<String>,
+}
+
+impl DiskWatchdog {
+    pub fn new(dirs: Vec<(String, PathBuf)>, min_free_space: u64, resume_free_space: u64) -> Self {
+        Self {
+            dirs,
+            min_free_space,
+            resume_free_space: resume_free_space.max(min_free_space),
+            read_only: AtomicBool::new(false),
+            reason: RwLock::new(String::new()),
+        }
+    }
+
+    pub fn start(self: Arc<Self>, runtime: &Runtime) {
+        runtime.spawn(async move {
+            let mut ticker = tokio::time::interval(DISK_CHECK_INTERVAL);
+            loop {
+                ticker.tick().await;
+                self.check();
+            }
+        });
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only.load(Ordering::Relaxed)
+    }
+
+    /// Fails with `DiskFull` if the writes are rejected
+    pub fn check_writable(&self) -> Result<()> {
+        if self.is_read_only() {
+            return Err(Error::DiskFull {
+                reason: self.reason.read().clone(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Probes the free space of the volumes, the ones failed to probe are skipped
+    pub fn check(&self) {
+        let mut spaces = Vec::with_capacity(self.dirs.len());
+        for (name, dir) in self.dirs.iter() {
+            match free_space(dir) {
+                Ok(free) => {
+                    set_disk_free_space(name, free);
+                    spaces.push((name.as_str(), free));
+                }
+                Err(e) => error!("Failed to get free space of {}: {}", dir.display(), e),
+            }
+        }
+        self.update(&spaces);
+    }
+
+    fn update(&self, spaces: &[(&str, u64)]) {
+        if self.is_read_only() {
+            if spaces
+                .iter()
+                .all(|(_, free)| *free >= self.resume_free_space)
+            {
+                self.read_only.store(false, Ordering::Relaxed);
+                set_read_only(false);
+                info!("Free space of disk is recovered, writes are accepted");
+            }
+            return;
+        }
+
+        if let Some((name, free)) = spaces.iter().find(|(_, free)| *free < self.min_free_space) {
+            let reason = format!(
+                "{} bytes free of {} is less than {}",
+                free, name, self.min_free_space
+            );
+            warn!("Writes are rejected until recovered: {}", reason);
+            *self.reason.write() = reason;
+            self.read_only.store(true, Ordering::Relaxed);
+            set_read_only(true);
+        }
+    }
+}
+
+/// Bytes available to the unprivileged users on the volume of the path
+fn free_space(path: &Path) -> std::io::Result<u64> {
+    let path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_hysteresis() {
+        let watchdog = DiskWatchdog::new(vec![], 100, 200);
+        watchdog.update(&[("data", 150), ("wal", 500)]);
+        assert!(watchdog.check_writable().is_ok());
+
+        watchdog.update(&[("data", 99), ("wal", 500)]);
+        assert!(matches!(
+            watchdog.check_writable(),
+            Err(Error::DiskFull { .. })
+        ));
+        // Still rejected between the thresholds
+        watchdog.update(&[("data", 150), ("wal", 500)]);
+        assert!(watchdog.is_read_only());
+
+        watchdog.update(&[("data", 200), ("wal", 500)]);
+        assert!(watchdog.check_writable().is_ok());
+    }
+
+    #[test]
+    fn test_free_space() {
+        let dir = std::env::temp_dir();
+        assert!(free_space(&dir).unwrap() > 0);
+        assert!(free_space(&dir.join("not_exists")).is_err());
+    }
+}
@@ -1,13 +1,15 @@
+use crate::cdc::{ChangeBatch, ChangeOffset};
 use crate::error::Result;
 use crate::index::IndexResult;
+use crate::partition::PartitionManifest;
 use crate::tseries_family::SuperVersion;
 use crate::tsm::DataBlock;
-use crate::{Options, TimeRange, TsKv};
+use crate::{Options, TimeRange, TsKv, VerifyReport};
 use async_trait::async_trait;
 use datafusion::prelude::Column;
 use models::codec::Encoding;
 use models::predicate::domain::{ColumnDomains, PredicateRef};
-use models::schema::{DatabaseSchema, TableColumn, TableSchema, TskvTableSchema};
+use models::schema::{DatabaseSchema, TableColumn, TableSchema, TableStatistics, TskvTableSchema};
 use models::{ColumnId, FieldId, FieldInfo, SeriesId, SeriesKey, Tag, Timestamp, ValueType};
 use protos::{
     kv_service::{WritePointsRpcRequest, WritePointsRpcResponse, WriteRowsRpcRequest},
@@ -15,15 +17,59 @@ use protos::{
 };
 use std::collections::{BTreeMap, HashMap};
 use std::fmt::Debug;
+use std::path::Path;
 use std::sync::Arc;
+use tokio::sync::broadcast;
 use trace::{debug, info};
 
 pub type EngineRef = Arc<dyn Engine>;
 
+/// Notification of points written into a database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WriteEvent {
+    pub database: String,
+    /// Sequence number of the write in wal, 0 if wal is disabled
+    pub seq: u64,
+}
+
+/// Bytes used by a database on this node
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DatabaseUsage {
+    /// Bytes of the tsm files
+    pub tsm_bytes: u64,
+    /// Bytes of the points in the caches not flushed yet
+    pub cache_bytes: u64,
+    /// Bytes of the wal of the database, 0 if the database has no wal of its own,
+    /// the wal shared by the databases is not accounted to any of them
+    pub wal_bytes: u64,
+}
+
+impl DatabaseUsage {
+    pub fn total(&self) -> u64 {
+        self.tsm_bytes + self.cache_bytes + self.wal_bytes
+    }
+}
+
+/// Data deleted from a database by a time range
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DeletedData {
+    /// Number of the files removed as a whole
+    pub files: usize,
+    /// Number of the series the points of which are deleted by tombstones
+    pub series: usize,
+}
+
 #[async_trait]
 pub trait Engine: Send + Sync + Debug {
     async fn write(&self, write_batch: WritePointsRpcRequest) -> Result<WritePointsRpcResponse>;
 
+    /// Same as `write`, for the writes replayed from another replica, which are
+    /// not served to the replicas again
+    async fn write_replayed(
+        &self,
+        write_batch: WritePointsRpcRequest,
+    ) -> Result<WritePointsRpcResponse>;
+
     async fn write_from_wal(
         &self,
         write_batch: WritePointsRpcRequest,
@@ -58,6 +104,14 @@ pub trait Engine: Send + Sync + Debug {
         new_column: TableColumn,
     ) -> Result<()>;
 
+    /// Stores the statistics collected by `ANALYZE TABLE` with the schema of the table
+    fn set_table_statistics(
+        &self,
+        database: &str,
+        table: &str,
+        statistics: TableStatistics,
+    ) -> Result<()>;
+
     fn delete_columns(
         &self,
         database: &str,
@@ -73,6 +127,9 @@ pub trait Engine: Send + Sync + Debug {
         time_range: &TimeRange,
     ) -> Result<()>;
 
+    /// Removes the series from the index, their data should have been deleted
+    fn delete_series_index(&self, database: &str, series_ids: &[SeriesId]) -> Result<()>;
+
     fn get_table_schema(&self, db: &str, tab: &str) -> Result<Option<TableSchema>>;
 
     fn get_series_id_by_filter(
@@ -84,6 +141,44 @@ pub trait Engine: Send + Sync + Debug {
     fn get_series_id_list(&self, db: &str, tab: &str, tags: &[Tag]) -> IndexResult<Vec<u64>>;
     fn get_series_key(&self, db: &str, sid: SeriesId) -> IndexResult<Option<SeriesKey>>;
     fn get_db_version(&self, db: &str) -> Result<Option<Arc<SuperVersion>>>;
+
+    /// Bytes used by `db` on this node, including the caches and the wal. The wal
+    /// directory is walked, so it is called on the blocking threads
+    fn database_usage(&self, db: &str) -> Result<DatabaseUsage>;
+
+    /// Receive a [`WriteEvent`] after each successful write
+    fn subscribe_writes(&self) -> broadcast::Receiver<WriteEvent>;
+
+    /// Read the writes of `db` committed into wal at or after `from`, see [`crate::cdc`]
+    fn read_changes(&self, db: &str, from: ChangeOffset, limit: usize) -> Result<ChangeBatch>;
+
+    /// Keeps the wal of `db` from `offset` on for the consumer of the changes, see
+    /// [`crate::cdc::ChangeConsumers`]
+    fn retain_changes(&self, consumer: &str, db: &str, offset: ChangeOffset) -> Result<()>;
+
+    /// Verifies the integrity of the files of `db`, see [`crate::verify`]
+    fn verify(&self, db: &str) -> Result<VerifyReport>;
+
+    /// Exports the data of `db` in the time range to `dir` with a manifest, see
+    /// [`crate::partition`]
+    fn export_partition(
+        &self,
+        db: &str,
+        time_range: &TimeRange,
+        dir: &Path,
+    ) -> Result<PartitionManifest>;
+
+    /// Imports the data exported to `dir` into `db`
+    async fn import_partition(&self, db: &str, dir: &Path) -> Result<PartitionManifest>;
+
+    /// Flushes the caches of `db` to tsm files, returns once the data is durable
+    /// without the wal
+    async fn flush_database(&self, db: &str) -> Result<()>;
+
+    /// Deletes the points of `db` before the timestamp. The files all the data of
+    /// which is before it are removed, the points in the caches and in the other files
+    /// are deleted by tombstones and their space is reclaimed by the compactions.
+    async fn delete_before(&self, db: &str, before: Timestamp) -> Result<DeletedData>;
 }
 
 #[derive(Debug, Default)]
@@ -104,6 +199,13 @@ impl Engine for MockEngine {
         })
     }
 
+    async fn write_replayed(
+        &self,
+        write_batch: WritePointsRpcRequest,
+    ) -> Result<WritePointsRpcResponse> {
+        self.write(write_batch).await
+    }
+
     async fn write_from_wal(
         &self,
         write_batch: WritePointsRpcRequest,
@@ -165,6 +267,10 @@ impl Engine for MockEngine {
         todo!()
     }
 
+    fn delete_series_index(&self, database: &str, series_ids: &[SeriesId]) -> Result<()> {
+        Ok(())
+    }
+
     fn get_table_schema(&self, db: &str, tab: &str) -> Result<Option<TableSchema>> {
         debug!("get_table_schema db:{:?}, table:{:?}", db, tab);
         Ok(Some(TableSchema::TsKvTableSchema(TskvTableSchema::new(
@@ -195,6 +301,62 @@ impl Engine for MockEngine {
         todo!()
     }
 
+    fn database_usage(&self, db: &str) -> Result<DatabaseUsage> {
+        Ok(DatabaseUsage::default())
+    }
+
+    fn subscribe_writes(&self) -> broadcast::Receiver<WriteEvent> {
+        broadcast::channel(1).1
+    }
+
+    fn read_changes(&self, db: &str, from: ChangeOffset, limit: usize) -> Result<ChangeBatch> {
+        Ok(ChangeBatch {
+            records: vec![],
+            next_offset: from,
+            truncated: false,
+        })
+    }
+
+    fn retain_changes(&self, consumer: &str, db: &str, offset: ChangeOffset) -> Result<()> {
+        Ok(())
+    }
+
+    fn verify(&self, db: &str) -> Result<VerifyReport> {
+        Ok(VerifyReport {
+            database: db.to_string(),
+            ..Default::default()
+        })
+    }
+
+    fn export_partition(
+        &self,
+        db: &str,
+        time_range: &TimeRange,
+        dir: &Path,
+    ) -> Result<PartitionManifest> {
+        Ok(PartitionManifest {
+            database: db.to_string(),
+            min_ts: time_range.min_ts,
+            max_ts: time_range.max_ts,
+            ..Default::default()
+        })
+    }
+
+    async fn import_partition(&self, db: &str, dir: &Path) -> Result<PartitionManifest> {
+        Ok(PartitionManifest {
+            database: db.to_string(),
+            ..Default::default()
+        })
+    }
+
+    async fn flush_database(&self, db: &str) -> Result<()> {
+        Ok(())
+    }
+
+    async fn delete_before(&self, db: &str, before: Timestamp) -> Result<DeletedData> {
+        Ok(DeletedData::default())
+    }
+
     fn alter_database(&self, schema: &DatabaseSchema) -> Result<()> {
         todo!()
     }
@@ -216,4 +378,13 @@ impl Engine for MockEngine {
     ) -> Result<()> {
         todo!()
     }
+
+    fn set_table_statistics(
+        &self,
+        database: &str,
+        table: &str,
+        statistics: TableStatistics,
+    ) -> Result<()> {
+        todo!()
+    }
 }
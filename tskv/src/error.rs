@@ -1,5 +1,6 @@
 use std::path::{Path, PathBuf};
 
+use models::error_code::ErrorCode;
 use models::SeriesId;
 use snafu::Snafu;
 
@@ -37,6 +38,23 @@ pub enum Error {
     #[snafu(display("async file system stopped"))]
     Cancel,
 
+    #[snafu(display("Writes are rejected for the low free space of disk: {}", reason))]
+    DiskFull { reason: String },
+
+    #[snafu(display(
+        "Writes are stalled for the flushes falling behind, {} bytes pending flush after {} ms",
+        pending,
+        timeout_ms
+    ))]
+    WriteStalled { pending: u64, timeout_ms: u64 },
+
+    #[snafu(display(
+        "{} caches flushing in the background are not flushed after {} ms",
+        caches,
+        timeout_ms
+    ))]
+    FlushTimeout { caches: usize, timeout_ms: u64 },
+
     #[snafu(display("fails to send to channel"))]
     Send,
 
@@ -114,4 +132,39 @@ pub enum Error {
 
     #[snafu(display("table not found for {}", table_name))]
     NotFoundTable { table_name: String },
+
+    #[snafu(display("change stream is not available when wal is disabled"))]
+    WalDisabled,
+
+    #[snafu(display("cluster error: {}", reason))]
+    Cluster { reason: String },
+
+    #[snafu(display("invalid partition: {}", reason))]
+    Partition { reason: String },
+
+    #[snafu(display("unable to repair the file: {}", reason))]
+    Repair { reason: String },
+}
+
+impl Error {
+    pub fn error_code(&self) -> ErrorCode {
+        match self {
+            Error::InvalidPoint | Error::InvalidFlatbuffer { .. } => ErrorCode::InvalidPoint,
+            Error::DatabaseNotFound { .. } => ErrorCode::TskvDatabaseNotFound,
+            Error::NotFoundTable { .. } | Error::NotFoundField { .. } => {
+                ErrorCode::TskvTableNotFound
+            }
+            Error::WriteStalled { .. } => ErrorCode::WriteStalled,
+            Error::DiskFull { .. } => ErrorCode::DiskFull,
+            Error::IO { .. }
+            | Error::OpenFile { .. }
+            | Error::ReadFile { .. }
+            | Error::WriteFile { .. }
+            | Error::SyncFile { .. } => ErrorCode::TskvIo,
+            Error::ReadTsm { .. } | Error::Decode { .. } | Error::WalTruncated => {
+                ErrorCode::TskvCorrupted
+            }
+            _ => ErrorCode::TskvUnknown,
+        }
+    }
 }
@@ -101,6 +101,14 @@ impl FileManager {
         }
     }
 
+    pub fn cache_hit_count(&self) -> u64 {
+        self.file_system.hit_count()
+    }
+
+    pub fn cache_miss_count(&self) -> u64 {
+        self.file_system.miss_count()
+    }
+
     pub async fn sync_all(&self, sync: file_system::FileSync) -> Result<()> {
         self.file_system
             .sync_all(sync)
@@ -175,6 +175,17 @@ where
     Some((PathBuf::from(max_file_name), max_id))
 }
 
+/// Total bytes of the files under the directory, 0 if not exists
+pub fn dir_size(dir: impl AsRef<Path>) -> u64 {
+    walkdir::WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.metadata().ok())
+        .filter(|m| m.is_file())
+        .map(|m| m.len())
+        .sum()
+}
+
 #[cfg(test)]
 mod test {
     use std::path::PathBuf;
@@ -185,6 +196,17 @@ mod test {
         get_wal_file_id, make_schema_file, make_wal_file,
     };
 
+    #[test]
+    fn test_dir_size() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(file_utils::dir_size(dir.path().join("not_exists")), 0);
+
+        std::fs::write(make_wal_file(dir.path(), 1), [0_u8; 10]).unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        std::fs::write(make_wal_file(dir.path().join("sub"), 2), [0_u8; 5]).unwrap();
+        assert_eq!(file_utils::dir_size(dir.path()), 15);
+    }
+
     #[test]
     fn test_get_file_id() {
         let summary_file_name = "summary-000123";
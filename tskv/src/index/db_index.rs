@@ -23,7 +23,9 @@ use config::Config;
 use datafusion::arrow::datatypes::{DataType, ToByteSlice};
 use datafusion::parquet::data_type::AsBytes;
 use models::codec::Encoding;
-use models::schema::{ColumnType, DatabaseSchema, TableColumn, TableSchema, TskvTableSchema};
+use models::schema::{
+    ColumnType, DatabaseSchema, TableColumn, TableSchema, TableStatistics, TskvTableSchema,
+};
 use models::{
     tag::TagFromParts, utils, ColumnId, FieldId, FieldInfo, SeriesId, SeriesKey, Tag, ValueType,
 };
@@ -168,9 +170,13 @@ impl DBIndex {
     }
 
     pub fn add_series_if_not_exists(&self, info: &Point) -> IndexResult<u64> {
-        let mut series_key = SeriesKey::from_flatbuffer(info)
+        let series_key = SeriesKey::from_flatbuffer(info)
             .map_err(|e| IndexError::FieldType { msg: e.to_string() })?;
+        self.add_series_key_if_not_exists(series_key)
+    }
 
+    /// Id of the series of the key, the series is added if not exists
+    pub fn add_series_key_if_not_exists(&self, mut series_key: SeriesKey) -> IndexResult<u64> {
         let (hash_id, _) = utils::split_id(series_key.hash());
         let stroage_key = format!("{}{}", SERIES_KEY_PREFIX, hash_id);
 
@@ -433,6 +439,14 @@ impl DBIndex {
         Ok(())
     }
 
+    /// The statistics are not a change of the columns, the schema id is kept
+    pub fn set_table_statistics(&self, tab: &str, statistics: TableStatistics) -> IndexResult<()> {
+        let mut schema = self.get_tskv_table_schema(tab)?;
+        schema.set_statistics(statistics);
+        self.store_table_schema(tab, &TableSchema::TsKvTableSchema(schema))?;
+        Ok(())
+    }
+
     pub fn get_table_schema(&self, tab: &str) -> IndexResult<Option<TableSchema>> {
         if let Some(fields) = self.table_schema.read().get(tab) {
             return Ok(Some(fields.clone()));
@@ -503,6 +517,30 @@ impl DBIndex {
         Ok(())
     }
 
+    /// Approximate bytes of the series keys cached
+    pub fn series_cache_size(&self) -> u64 {
+        let size = |key: &SeriesKey| {
+            std::mem::size_of::<SeriesKey>()
+                + key.table().len()
+                + key
+                    .tags()
+                    .iter()
+                    .map(|e| std::mem::size_of::<Tag>() + e.key.len() + e.value.len())
+                    .sum::<usize>()
+        };
+        self.series_cache
+            .read()
+            .values()
+            .flatten()
+            .map(|e| size(e) as u64)
+            .sum()
+    }
+
+    /// Drops the series keys cached, they are loaded from the storage once used
+    pub fn clear_series_cache(&self) {
+        self.series_cache.write().clear();
+    }
+
     pub fn get_series_key(&self, sid: u64) -> IndexResult<Option<SeriesKey>> {
         let (hash_id, _) = utils::split_id(sid);
         let stroage_key = format!("{}{}", SERIES_KEY_PREFIX, hash_id);
@@ -534,9 +572,30 @@ impl DBIndex {
         }
     }
 
+    /// Keys of all the series stored in the index, not through the cache
+    pub fn series_keys(&self) -> IndexResult<Vec<SeriesKey>> {
+        let mut series_keys = vec![];
+        for item in self.storage.prefix(SERIES_KEY_PREFIX.as_bytes()) {
+            let (key, data) = item?;
+            let keys = bincode::deserialize::<Vec<SeriesKey>>(&data).map_err(|e| {
+                IndexError::IndexStroage {
+                    msg: format!(
+                        "deserialize {} failed: {}",
+                        String::from_utf8_lossy(&key),
+                        e
+                    ),
+                }
+            })?;
+            series_keys.extend(keys);
+        }
+        Ok(series_keys)
+    }
+
     pub fn del_series_info(&self, sid: u64) -> IndexResult<()> {
         let (hash_id, _) = utils::split_id(sid);
-        self.series_cache.write().remove(&hash_id);
+        // The series of the hash are not added until removed from the storage
+        let mut series_cache = self.series_cache.write();
+        series_cache.remove(&hash_id);
 
         let stroage_key = format!("{}{}", SERIES_KEY_PREFIX, hash_id);
         if let Some(data) = self.storage.get(stroage_key.as_bytes())? {
@@ -6,6 +6,13 @@ use std::path::{Path, PathBuf};
 
 use sled;
 
+/// Format version of the indexes written, the indexes written before the version
+/// is recorded are of version 0, in the same layout as version 1
+pub const INDEX_VERSION: u8 = 1;
+/// Tree of the format of the index, apart from the series and the schemas
+const FORMAT_TREE: &str = "format";
+const FORMAT_VERSION_KEY: &[u8] = b"version";
+
 #[derive(Debug)]
 pub struct IndexEngine {
     db: sled::Db,
@@ -27,10 +34,47 @@ impl IndexEngine {
         });
         db.set_merge_operator(concatenate_merge);
 
-        Self {
+        let engine = Self {
             db,
             dir: index_dir.into(),
+        };
+        if !engine.db.was_recovered() {
+            engine.upgrade_format().unwrap_or_else(|err| {
+                panic!("init index at '{}' failed: {}", index_dir.display(), err)
+            });
+        }
+        match engine.format_version() {
+            Ok(version) if version <= INDEX_VERSION => engine,
+            Ok(version) => panic!(
+                "index at '{}' is of version {}, newer than {}",
+                index_dir.display(),
+                version,
+                INDEX_VERSION
+            ),
+            Err(err) => panic!("open index at '{}' failed: {}", index_dir.display(), err),
+        }
+    }
+
+    /// Format version of the index
+    pub fn format_version(&self) -> Result<u8, sled::Error> {
+        let version = self
+            .db
+            .open_tree(FORMAT_TREE)?
+            .get(FORMAT_VERSION_KEY)?
+            .and_then(|v| v.first().copied());
+        Ok(version.unwrap_or(0))
+    }
+
+    /// Records the index in the current format version, returns false if it is
+    /// already
+    pub fn upgrade_format(&self) -> Result<bool, sled::Error> {
+        if self.format_version()? >= INDEX_VERSION {
+            return Ok(false);
         }
+        let tree = self.db.open_tree(FORMAT_TREE)?;
+        tree.insert(FORMAT_VERSION_KEY, vec![INDEX_VERSION])?;
+        tree.flush()?;
+        Ok(true)
     }
 
     pub fn set(&self, key: &[u8], value: &[u8]) -> Result<(), sled::Error> {
@@ -1,11 +1,21 @@
 #![allow(dead_code)]
 
-use std::{path::PathBuf, sync::Arc};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU16, AtomicU64, AtomicUsize, Ordering};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
-use config::Config;
+use config::{
+    CacheConfig, ClusterConfig, CompressionConfig, Config, LevelCompressionConfig,
+    ObjectStoreConfig, StorageConfig,
+};
+use models::codec::Encoding;
 use serde::{Deserialize, Serialize};
 
-use crate::{file_system, index::IndexConfig, summary};
+use crate::{file_system, index::IndexConfig, summary, LevelId};
 
 const SUMMARY_PATH: &str = "summary";
 const INDEX_PATH: &str = "index";
@@ -18,6 +28,7 @@ pub struct Options {
     pub storage: Arc<StorageOptions>,
     pub wal: Arc<WalOptions>,
     pub cache: Arc<CacheOptions>,
+    pub compaction: Arc<CompactionOptions>,
     pub query: Arc<QueryOptions>,
 }
 
@@ -27,6 +38,7 @@ impl From<&Config> for Options {
             storage: Arc::new(StorageOptions::from(config)),
             wal: Arc::new(WalOptions::from(config)),
             cache: Arc::new(CacheOptions::from(config)),
+            compaction: Arc::new(CompactionOptions::from(config)),
             query: Arc::new(QueryOptions::from(config)),
         }
     }
@@ -44,6 +56,13 @@ pub struct StorageOptions {
     pub dio_max_non_resident: usize,
     pub dio_page_len_scale: usize,
     pub strict_write: bool,
+    pub min_free_space: u64,
+    pub resume_free_space: u64,
+    pub compression: CompressionOptions,
+    /// Zero to disable
+    pub series_gc_interval: Duration,
+    /// Zero to disable
+    pub retention_interval: Duration,
 }
 
 impl StorageOptions {
@@ -63,8 +82,12 @@ impl StorageOptions {
         self.path.join(INDEX_PATH).join(database)
     }
 
+    pub fn data_dir(&self) -> PathBuf {
+        self.path.join(DATA_PATH)
+    }
+
     pub fn database_dir(&self, database: &str) -> PathBuf {
-        self.path.join(DATA_PATH).join(database)
+        self.data_dir().join(database)
     }
 
     pub fn tsm_dir(&self, database: &str, ts_family_id: u32) -> PathBuf {
@@ -101,6 +124,70 @@ impl From<&Config> for StorageOptions {
             dio_max_non_resident: config.storage.dio_max_non_resident,
             dio_page_len_scale: config.storage.dio_page_len_scale,
             strict_write: config.storage.strict_write,
+            min_free_space: config.storage.min_free_space,
+            resume_free_space: config.storage.resume_free_space,
+            compression: CompressionOptions::from(&config.storage.compression),
+            series_gc_interval: Duration::from_secs(config.storage.series_gc_interval),
+            retention_interval: Duration::from_secs(config.storage.retention_interval),
+        }
+    }
+}
+
+/// Codecs of the strings of the columns of the DEFAULT codec by the levels
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressionOptions {
+    levels: LevelCompression,
+    databases: HashMap<String, LevelCompression>,
+}
+
+impl CompressionOptions {
+    pub fn database(&self, database: &str) -> LevelCompression {
+        self.databases.get(database).copied().unwrap_or(self.levels)
+    }
+}
+
+impl From<&CompressionConfig> for CompressionOptions {
+    fn from(config: &CompressionConfig) -> Self {
+        Self {
+            levels: LevelCompression::from(&config.levels),
+            databases: config
+                .databases
+                .iter()
+                .map(|(db, e)| (db.clone(), LevelCompression::from(e)))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LevelCompression {
+    pub fast_codec: Encoding,
+    pub strong_codec: Encoding,
+    pub strong_level: LevelId,
+}
+
+impl LevelCompression {
+    /// Codec of the strings of the files of the level
+    pub fn codec(&self, level: LevelId) -> Encoding {
+        if level >= self.strong_level {
+            self.strong_codec
+        } else {
+            self.fast_codec
+        }
+    }
+}
+
+/// The codecs are checked when the configuration is loaded, see `Config::check`
+impl From<&LevelCompressionConfig> for LevelCompression {
+    fn from(config: &LevelCompressionConfig) -> Self {
+        let codec = |name: &str| match name.parse::<Encoding>() {
+            Ok(e) if e.is_string_encoding() && e != Encoding::Default => e,
+            _ => panic!("'{}' is not a codec of the strings", name),
+        };
+        Self {
+            fast_codec: codec(&config.fast_codec),
+            strong_codec: codec(&config.strong_codec),
+            strong_level: config.strong_level,
         }
     }
 }
@@ -108,12 +195,20 @@ impl From<&Config> for StorageOptions {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct QueryOptions {
     pub max_server_connections: u32,
+    pub slow_write_threshold_ms: u64,
+    pub plan_cache_capacity: usize,
+    pub object_store: ObjectStoreConfig,
+    pub cluster: ClusterConfig,
 }
 
 impl From<&Config> for QueryOptions {
     fn from(config: &Config) -> Self {
         Self {
             max_server_connections: config.query.max_server_connections,
+            slow_write_threshold_ms: config.query.slow_write_threshold_ms,
+            plan_cache_capacity: config.query.plan_cache_capacity,
+            object_store: config.object_store.clone(),
+            cluster: config.cluster.clone(),
         }
     }
 }
@@ -123,6 +218,29 @@ pub struct WalOptions {
     pub enabled: bool,
     pub path: PathBuf,
     pub sync: bool,
+    pub sync_thread: bool,
+    /// Directories of the wal of the databases not in `path`
+    pub database_paths: HashMap<String, PathBuf>,
+}
+
+impl WalOptions {
+    /// Directory of the wal of the database
+    pub fn database_dir(&self, database: &str) -> &Path {
+        self.database_paths
+            .get(database)
+            .map_or(self.path.as_path(), |e| e.as_path())
+    }
+
+    /// All the directories of the wal, `path` first
+    pub fn dirs(&self) -> Vec<&Path> {
+        let mut dirs = vec![self.path.as_path()];
+        for dir in self.database_paths.values() {
+            if !dirs.contains(&dir.as_path()) {
+                dirs.push(dir.as_path());
+            }
+        }
+        dirs
+    }
 }
 
 impl From<&Config> for WalOptions {
@@ -131,21 +249,106 @@ impl From<&Config> for WalOptions {
             enabled: config.wal.enabled,
             path: PathBuf::from(config.wal.path.clone()),
             sync: config.wal.sync,
+            sync_thread: config.wal.sync_thread,
+            database_paths: config
+                .wal
+                .databases
+                .iter()
+                .map(|(db, e)| (db.clone(), PathBuf::from(&e.path)))
+                .collect(),
         }
     }
 }
 
-#[derive(Debug, Clone)]
+/// Reloaded on the fly, the new memcaches are of the buffer size changed
+#[derive(Debug)]
 pub struct CacheOptions {
-    pub max_buffer_size: u64,
-    pub max_immutable_number: u16,
+    max_buffer_size: AtomicU64,
+    max_immutable_number: AtomicU16,
+    max_flush_pending_size: AtomicU64,
+    write_stall_timeout_ms: AtomicU64,
+}
+
+impl CacheOptions {
+    pub fn max_buffer_size(&self) -> u64 {
+        self.max_buffer_size.load(Ordering::Relaxed)
+    }
+
+    pub fn max_immutable_number(&self) -> u16 {
+        self.max_immutable_number.load(Ordering::Relaxed)
+    }
+
+    pub fn max_flush_pending_size(&self) -> u64 {
+        self.max_flush_pending_size.load(Ordering::Relaxed)
+    }
+
+    pub fn write_stall_timeout_ms(&self) -> u64 {
+        self.write_stall_timeout_ms.load(Ordering::Relaxed)
+    }
+
+    pub fn update(&self, config: &CacheConfig) {
+        self.max_buffer_size
+            .store(config.max_buffer_size, Ordering::Relaxed);
+        self.max_immutable_number
+            .store(config.max_immutable_number, Ordering::Relaxed);
+        self.max_flush_pending_size
+            .store(config.max_flush_pending_size, Ordering::Relaxed);
+        self.write_stall_timeout_ms
+            .store(config.write_stall_timeout_ms, Ordering::Relaxed);
+    }
 }
 
 impl From<&Config> for CacheOptions {
     fn from(config: &Config) -> Self {
         Self {
-            max_buffer_size: config.cache.max_buffer_size,
-            max_immutable_number: config.cache.max_immutable_number,
+            max_buffer_size: AtomicU64::new(config.cache.max_buffer_size),
+            max_immutable_number: AtomicU16::new(config.cache.max_immutable_number),
+            max_flush_pending_size: AtomicU64::new(config.cache.max_flush_pending_size),
+            write_stall_timeout_ms: AtomicU64::new(config.cache.write_stall_timeout_ms),
+        }
+    }
+}
+
+/// Reloaded on the fly, the compactions running are throttled by the limits changed
+#[derive(Debug)]
+pub struct CompactionOptions {
+    max_compact_mb_per_sec: AtomicU64,
+    max_concurrent_compactions: AtomicUsize,
+}
+
+impl CompactionOptions {
+    /// Bytes written by all the compactions per second, 0 if not limited
+    pub fn max_compact_bytes_per_sec(&self) -> u64 {
+        self.max_compact_mb_per_sec.load(Ordering::Relaxed) * 1024 * 1024
+    }
+
+    pub fn max_concurrent_compactions(&self) -> usize {
+        self.max_concurrent_compactions
+            .load(Ordering::Relaxed)
+            .max(1)
+    }
+
+    pub fn update(&self, config: &StorageConfig) {
+        self.max_compact_mb_per_sec
+            .store(config.max_compact_mb_per_sec, Ordering::Relaxed);
+        self.max_concurrent_compactions
+            .store(config.max_concurrent_compactions, Ordering::Relaxed);
+    }
+}
+
+impl Default for CompactionOptions {
+    fn default() -> Self {
+        Self {
+            max_compact_mb_per_sec: AtomicU64::new(0),
+            max_concurrent_compactions: AtomicUsize::new(1),
         }
     }
 }
+
+impl From<&Config> for CompactionOptions {
+    fn from(config: &Config) -> Self {
+        let options = Self::default();
+        options.update(&config.storage);
+        options
+    }
+}
@@ -1,7 +1,14 @@
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Duration;
-use std::{collections::HashMap, panic, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    panic,
+    path::Path,
+    sync::Arc,
+};
 
 use crate::tsm::codec::get_str_codec;
+use config::{CacheConfig, QueryConfig, StorageConfig};
 use datafusion::prelude::Column;
 use flatbuffers::FlatBufferBuilder;
 use futures::stream::SelectNextSome;
@@ -23,9 +30,14 @@ use tokio::{
 };
 
 use crate::error::SendSnafu;
-use metrics::{incr_compaction_failed, incr_compaction_success, sample_tskv_compaction_duration};
+use metrics::{
+    decr_compaction_backlog, incr_compaction_backlog, incr_compaction_failed,
+    incr_compaction_success, incr_series_gc_removed, incr_write_points, incr_write_stalls,
+    sample_tskv_compaction_duration, set_flush_pending_size, set_memcache_size,
+    set_page_cache_counts, set_wal_size,
+};
 use models::codec::Encoding;
-use models::schema::{DatabaseSchema, TableColumn, TableSchema};
+use models::schema::{DatabaseSchema, TableColumn, TableSchema, TableStatistics};
 use models::{
     utils::unite_id, ColumnId, FieldId, FieldInfo, InMemPoint, SeriesId, SeriesKey, Tag, Timestamp,
     ValueType,
@@ -34,7 +46,8 @@ use protos::{
     kv_service::{WritePointsRpcRequest, WritePointsRpcResponse, WriteRowsRpcRequest},
     models as fb_models,
 };
-use trace::{debug, error, info, trace, warn};
+use trace::{debug, error, info, info_span, trace, warn, Instrument};
+use utils::{MemoryBudget, MemoryPoolKind};
 
 use crate::database::Database;
 use crate::file_system::file_manager::{self, init_file_manager, FileManager};
@@ -43,26 +56,51 @@ use crate::index::index_manger;
 use crate::index::IndexError::TableNotFound;
 use crate::Error::{DatabaseNotFound, IndexErr};
 use crate::{
-    compaction::{self, run_flush_memtable_job, CompactReq, FlushReq},
+    cdc::{self, ChangeBatch, ChangeConsumers, ChangeOffset},
+    compaction::{self, run_flush_memtable_job, CompactReq, CompactionThrottle, FlushReq},
     context::GlobalContext,
     database,
-    engine::Engine,
+    disk_watchdog::DiskWatchdog,
+    engine::{DatabaseUsage, DeletedData, Engine, WriteEvent},
     error::{self, IndexErrSnafu, Result},
     file_utils,
     index::{db_index, IndexResult},
     kv_option::Options,
     memcache::{DataType, MemCache},
+    partition::{self, PartitionManifest},
     record_file::Reader,
+    retention,
+    series_gc::SeriesGc,
     summary,
     summary::{Summary, SummaryProcessor, SummaryTask, VersionEdit},
-    tseries_family::{SuperVersion, TimeRange, Version},
+    tseries_family::{SuperVersion, TimeRange, TseriesFamily, Version},
     tsm::{DataBlock, TsmTombstone, MAX_BLOCK_VALUES},
+    verify::{self, VerifyReport},
     version_set,
     version_set::VersionSet,
-    wal::{self, WalEntryType, WalManager, WalTask},
+    wal::{self, PendingWrites, SyncTask, WalEntryType, WalManager, WalSyncer, WalTask},
     Error, Task, TseriesFamilyId,
 };
 
+/// Number of write events buffered for each subscriber before it lags
+const WRITE_EVENT_CAPACITY: usize = 1024;
+
+/// Max bytes of the writes queued coalesced into a batch of the wal
+const WAL_BATCH_SIZE: usize = 4 * 1024 * 1024;
+
+/// Interval of removing the wal files all the writes of which are persisted
+const WAL_CHECKPOINT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Interval of sampling the usage of memcaches, wal and page cache
+const METRICS_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Interval of accounting the memcaches and the caches against the memory budget
+const MEMORY_BUDGET_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Interval of checking the edits of the flushes waited for are applied, which
+/// are not notified
+const FLUSH_WAIT_INTERVAL: Duration = Duration::from_millis(100);
+
 #[derive(Debug)]
 pub struct TsKv {
     options: Arc<Options>,
@@ -75,6 +113,24 @@ pub struct TsKv {
     compact_task_sender: UnboundedSender<TseriesFamilyId>,
     summary_task_sender: UnboundedSender<SummaryTask>,
     close_sender: BroadcastSender<UnboundedSender<()>>,
+    write_notifier: BroadcastSender<WriteEvent>,
+    disk_watchdog: Arc<DiskWatchdog>,
+    compaction_throttle: Arc<CompactionThrottle>,
+    series_gc: Arc<SeriesGc>,
+    pending_writes: Arc<PendingWrites>,
+    change_consumers: Arc<ChangeConsumers>,
+    /// Writes taking longer are logged, 0 to disable
+    slow_write_threshold_ms: AtomicU64,
+}
+
+/// Time taken by each step of a write, logged if the write is slow
+#[derive(Debug, Default)]
+struct WriteTimings {
+    stall: Duration,
+    decode: Duration,
+    schema_check: Duration,
+    wal: Duration,
+    memcache: Duration,
 }
 
 impl TsKv {
@@ -91,19 +147,43 @@ impl TsKv {
         let (wal_sender, wal_receiver) = mpsc::unbounded_channel();
         let (summary_task_sender, summary_task_receiver) = mpsc::unbounded_channel();
         let (close_sender, _close_receiver) = broadcast::channel(1);
+        let (write_notifier, _) = broadcast::channel(WRITE_EVENT_CAPACITY);
         let (version_set, summary) =
             Self::recover_summary(shared_options.clone(), flush_task_sender.clone()).await;
         let wal_cfg = shared_options.wal.clone();
+        let mut disk_dirs = vec![
+            ("data".to_string(), shared_options.storage.path.clone()),
+            ("wal".to_string(), shared_options.wal.path.clone()),
+        ];
+        for (db, dir) in shared_options.wal.database_paths.iter() {
+            disk_dirs.push((format!("wal.{}", db), dir.clone()));
+        }
+        let disk_watchdog = Arc::new(DiskWatchdog::new(
+            disk_dirs,
+            shared_options.storage.min_free_space,
+            shared_options.storage.resume_free_space,
+        ));
+        let compaction_throttle =
+            Arc::new(CompactionThrottle::new(shared_options.compaction.clone()));
         let core = Self {
             version_set,
             global_ctx: summary.global_context(),
             runtime,
             wal_sender,
-            options: shared_options,
+            options: shared_options.clone(),
             flush_task_sender: flush_task_sender.clone(),
             compact_task_sender: compact_task_sender.clone(),
             summary_task_sender: summary_task_sender.clone(),
             close_sender,
+            write_notifier,
+            disk_watchdog,
+            compaction_throttle,
+            series_gc: Arc::new(SeriesGc::default()),
+            pending_writes: Arc::new(PendingWrites::default()),
+            change_consumers: Arc::new(ChangeConsumers::open(
+                shared_options.storage.path.join(cdc::CONSUMERS_FILE),
+            )),
+            slow_write_threshold_ms: AtomicU64::new(shared_options.query.slow_write_threshold_ms),
         };
 
         let wal_manager = core.recover_wal().await;
@@ -122,10 +202,20 @@ impl TsKv {
             summary_task_sender.clone(),
         );
         core.run_summary_job(summary, summary_task_receiver);
+        core.run_metrics_job();
+        core.run_series_gc_job();
+        core.run_retention_job();
+        core.disk_watchdog.check();
+        core.disk_watchdog.clone().start(&core.runtime);
         Ok(core)
     }
 
+    /// Flushes the caches before closing the wal, so the data buffered is not
+    /// replayed from the wal on the next start
     pub async fn close(&self) {
+        if let Err(e) = self.flush_all().await {
+            error!("Failed to flush caches on close: {}", e);
+        }
         let (tx, mut rx) = mpsc::unbounded_channel();
         if let Err(e) = self.close_sender.send(tx) {
             error!("Failed to broadcast close signal: {:?}", e);
@@ -136,6 +226,165 @@ impl TsKv {
         info!("TsKv closed");
     }
 
+    /// Flushes the indexes and the caches of all the ts families to tsm files,
+    /// returns once the version edits are applied to the summary
+    pub async fn flush_all(&self) -> Result<()> {
+        let mut mems = vec![];
+        let mut indexes = vec![];
+        let mut flushing = vec![];
+        for db in self.version_set.read().get_all_db().values() {
+            let db = db.read();
+            db.for_each_ts_family(|(_, tsf)| {
+                flushing.extend(flushing_caches(tsf));
+                mems.append(&mut tsf.write().take_unflushed_caches(&self.global_ctx));
+            });
+            indexes.push(db.get_index());
+        }
+        // The indexes are written to the disks on the blocking threads
+        tokio::task::spawn_blocking(move || {
+            indexes
+                .iter()
+                .try_for_each(|index| index.flush().context(error::IndexErrSnafu))
+        })
+        .await
+        .map_err(|e| Error::ThreadJoin {
+            reason: e.to_string(),
+        })??;
+        self.flush_caches(mems).await?;
+        self.wait_for_flushing(flushing).await
+    }
+
+    async fn flush_caches(
+        &self,
+        mems: Vec<(TseriesFamilyId, Arc<RwLock<MemCache>>)>,
+    ) -> Result<()> {
+        if mems.is_empty() {
+            return Ok(());
+        }
+
+        info!("Flushing {} caches", mems.len());
+        run_flush_memtable_job(
+            FlushReq::new(mems),
+            self.global_ctx.clone(),
+            self.version_set.clone(),
+            self.summary_task_sender.clone(),
+            self.compact_task_sender.clone(),
+        )?;
+        // Summary tasks are applied in order, so the edits of the flush are applied
+        // once the empty task is
+        let (cb, rx) = oneshot::channel();
+        self.summary_task_sender
+            .send(SummaryTask { edits: vec![], cb })
+            .map_err(|_| Error::Send)?;
+        rx.await.context(error::ReceiveSnafu)?
+    }
+
+    /// Waits for the caches taken by the flushes in the background to be flushed,
+    /// and the edits of the flushes to be applied, at most the write stall timeout
+    async fn wait_for_flushing(
+        &self,
+        flushing: Vec<(Arc<RwLock<TseriesFamily>>, Arc<RwLock<MemCache>>)>,
+    ) -> Result<()> {
+        let timeout_ms = self.options.cache.write_stall_timeout_ms();
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+        loop {
+            let flushed = self.global_ctx.flushed();
+            let caches = flushing
+                .iter()
+                .filter(|(tsf, cache)| {
+                    let cache = cache.read();
+                    !cache.flushed || tsf.read().version().last_seq < cache.seq_no()
+                })
+                .count();
+            if caches == 0 {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                return Err(Error::FlushTimeout { caches, timeout_ms });
+            }
+            let _ = tokio::time::timeout(FLUSH_WAIT_INTERVAL, flushed).await;
+        }
+    }
+
+    /// Applies the cache sizes reloaded, the memcaches allocated are kept
+    pub fn reload_cache_options(&self, config: &CacheConfig) {
+        self.options.cache.update(config);
+    }
+
+    /// Applies the limits of the compactions reloaded, the compactions waiting
+    /// are woken up to check them again
+    pub fn reload_storage_options(&self, config: &StorageConfig) {
+        self.options.compaction.update(config);
+        self.compaction_throttle.limits_changed();
+    }
+
+    /// Applies the threshold of the slow writes reloaded
+    pub fn reload_query_options(&self, config: &QueryConfig) {
+        self.slow_write_threshold_ms
+            .store(config.slow_write_threshold_ms, Ordering::Relaxed);
+    }
+
+    fn log_slow_write(&self, db: &str, points: u64, bytes: usize, timings: &WriteTimings) {
+        let threshold = self.slow_write_threshold_ms.load(Ordering::Relaxed);
+        let total =
+            timings.stall + timings.decode + timings.schema_check + timings.wal + timings.memcache;
+        if threshold == 0 || total.as_millis() as u64 <= threshold {
+            return;
+        }
+        warn!(
+            "Slow write of {} ms to {}, {} points of {} bytes: stall {} ms, decode {} ms, schema check {} ms, wal {} ms, memcache {} ms",
+            total.as_millis(),
+            db,
+            points,
+            bytes,
+            timings.stall.as_millis(),
+            timings.decode.as_millis(),
+            timings.schema_check.as_millis(),
+            timings.wal.as_millis(),
+            timings.memcache.as_millis(),
+        );
+    }
+
+    /// Waits for the flushes once the memcaches pending flush exceed the limit, so the
+    /// memory does not grow unbounded if the flushes fall behind the writes, fails with
+    /// `WriteStalled` if they do not catch up in time
+    async fn wait_for_flushes(&self) -> Result<()> {
+        let limit = self.options.cache.max_flush_pending_size();
+        if limit == 0 || self.global_ctx.flush_pending_size() < limit {
+            return Ok(());
+        }
+        incr_write_stalls();
+        // The immutable caches fewer than `max_immutable_number` of a ts family are
+        // not flushed otherwise
+        let mut mems = vec![];
+        for db in self.version_set.read().get_all_db().values() {
+            db.read().for_each_ts_family(|(_, tsf)| {
+                mems.append(&mut tsf.write().take_immutable_caches(&self.global_ctx));
+            });
+        }
+        if !mems.is_empty() {
+            info!("Writes stalled, flushing {} caches", mems.len());
+            self.flush_task_sender
+                .send(FlushReq::new(mems))
+                .map_err(|_| Error::Send)?;
+        }
+        let timeout_ms = self.options.cache.write_stall_timeout_ms();
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+        loop {
+            let flushed = self.global_ctx.flushed();
+            let pending = self.global_ctx.flush_pending_size();
+            if pending < limit {
+                return Ok(());
+            }
+            if tokio::time::timeout_at(deadline, flushed).await.is_err() {
+                return Err(Error::WriteStalled {
+                    pending,
+                    timeout_ms,
+                });
+            }
+        }
+    }
+
     async fn recover_summary(
         opt: Arc<Options>,
         flush_task_sender: UnboundedSender<FlushReq>,
@@ -160,10 +409,24 @@ impl TsKv {
     }
 
     async fn recover_wal(&self) -> WalManager {
-        let wal_manager = WalManager::new(self.options.wal.clone());
-
+        let mut wal_manager = WalManager::new(self.options.wal.clone());
+
+        // The writes of a ts family at or before its last seq are persisted
+        let (mut checkpoint, mut last_seq) = (None, 0);
+        for db in self.version_set.read().get_all_db().values() {
+            db.read().for_each_ts_family(|(_, tsf)| {
+                let seq = tsf.read().version().last_seq;
+                checkpoint = Some(checkpoint.map_or(seq, |e: u64| e.min(seq)));
+                last_seq = last_seq.max(seq);
+            });
+        }
         wal_manager
-            .recover(self, self.global_ctx.clone())
+            .recover(
+                self,
+                checkpoint.unwrap_or(0),
+                last_seq,
+                &self.change_consumers.retained(),
+            )
             .await
             .unwrap();
 
@@ -172,26 +435,90 @@ impl TsKv {
 
     fn run_wal_job(&self, mut wal_manager: WalManager, mut receiver: UnboundedReceiver<WalTask>) {
         warn!("job 'WAL' starting.");
+        // The writes are synced in the dedicated thread if enabled
+        let syncer = if self.options.wal.sync && self.options.wal.sync_thread {
+            match WalSyncer::start() {
+                Ok(syncer) => Some(syncer),
+                Err(e) => {
+                    error!("Failed to start the WAL sync thread, sync inline: {:?}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
         let mut close_receiver = self.close_sender.subscribe();
+        let version_set = self.version_set.clone();
+        let pending_writes = self.pending_writes.clone();
+        let change_consumers = self.change_consumers.clone();
         let f = async move {
+            let mut checkpoint_ticker = tokio::time::interval(WAL_CHECKPOINT_INTERVAL);
             loop {
                 tokio::select! {
+                    _ = checkpoint_ticker.tick() => {
+                        // The writes in the wal not yet in the memcache are not persisted
+                        let applied_seq = pending_writes
+                            .applied_seq()
+                            .unwrap_or_else(|| wal_manager.current_seq_no());
+                        let checkpoint = wal_checkpoint(&version_set)
+                            .map_or(applied_seq, |e| e.min(applied_seq));
+                        wal_manager.truncate(checkpoint, &change_consumers.retained());
+                    }
                     wal_task = receiver.recv() => {
-                        match wal_task {
-                            Some(WalTask::Write { points, cb }) => {
-                                // write wal
-                                let ret = wal_manager.write(WalEntryType::Write, &points).await;
-                                let send_ret = cb.send(ret);
-                                match send_ret {
-                                    Ok(wal_result) => {}
-                                    Err(err) => {
+                        let mut tasks = match wal_task {
+                            Some(task) => vec![task],
+                            None => break,
+                        };
+                        // Coalesces the writes queued meanwhile, they share a single fsync
+                        let mut batch_size = tasks.iter().map(|e| e.size()).sum::<usize>();
+                        while batch_size < WAL_BATCH_SIZE {
+                            match receiver.try_recv() {
+                                Ok(task) => {
+                                    batch_size += task.size();
+                                    tasks.push(task);
+                                }
+                                Err(_) => break,
+                            }
+                        }
+                        let entries = tasks
+                            .iter()
+                            .map(|WalTask::Write { database, typ, points, .. }| {
+                                (database.as_str(), *typ, points.as_slice())
+                            })
+                            .collect::<Vec<_>>();
+                        let mark_written = |results: &[Result<(u64, usize)>]| {
+                            for (task, ret) in tasks.iter().zip(results) {
+                                if let (WalTask::Write { pending, .. }, Ok((seq, _))) = (task, ret) {
+                                    pending_writes.written(*pending, *seq);
+                                }
+                            }
+                        };
+                        match &syncer {
+                            Some(syncer) => {
+                                let (results, files) = wal_manager
+                                    .write_batch_unsynced(&entries)
+                                    .await;
+                                mark_written(&results);
+                                let writes = tasks
+                                    .into_iter()
+                                    .map(|WalTask::Write { cb, .. }| cb)
+                                    .zip(results)
+                                    .collect();
+                                if syncer.sync(SyncTask { files, writes }).is_err() {
+                                    error!("Failed to send the WAL writes to sync");
+                                }
+                            }
+                            None => {
+                                let results =
+                                    wal_manager.write_batch(&entries).await;
+                                mark_written(&results);
+                                let cbs = tasks.into_iter().map(|WalTask::Write { cb, .. }| cb);
+                                for (cb, ret) in cbs.zip(results) {
+                                    if cb.send(ret).is_err() {
                                         warn!("send WAL write result failed.")
                                     }
                                 }
                             }
-                            _ => {
-                                break;
-                            }
                         }
                     }
                     close_task = close_receiver.recv() => {
@@ -245,47 +572,95 @@ impl TsKv {
         version_set: Arc<RwLock<VersionSet>>,
         summary_task_sender: UnboundedSender<SummaryTask>,
     ) {
+        let throttle = self.compaction_throttle.clone();
+        let compact_task_sender = self.compact_task_sender.clone();
         self.runtime.spawn(async move {
-            while let Some(ts_family_id) = receiver.recv().await {
-                let ts_family = version_set.read().get_tsfamily_by_tf_id(ts_family_id);
-                if let Some(tsf) = ts_family {
-                    info!("Starting compaction on ts_family {}", ts_family_id);
-                    let start = Instant::now();
-                    let compact_req = tsf.read().pick_compaction();
-                    if let Some(req) = compact_req {
-                        let database = req.database.clone();
-                        let compact_ts_family = req.ts_family_id;
-                        let out_level = req.out_level;
-                        match compaction::run_compaction_job(req, ctx.clone()) {
-                            Ok(Some(version_edit)) => {
-                                incr_compaction_success();
-                                let (summary_tx, summary_rx) = oneshot::channel();
-                                let ret = summary_task_sender.send(SummaryTask {
-                                    edits: vec![version_edit],
-                                    cb: summary_tx,
-                                });
-                                sample_tskv_compaction_duration(
-                                    database.as_str(),
-                                    compact_ts_family.to_string().as_str(),
-                                    out_level.to_string().as_str(),
-                                    start.elapsed().as_secs_f64(),
-                                )
-                                // TODO Handle summary result using summary_rx.
-                            }
-                            Ok(None) => {
-                                info!("There is nothing to compact.");
-                            }
-                            Err(e) => {
-                                incr_compaction_failed();
-                                error!("Compaction job failed: {}", e);
-                            }
+            // The ts families compacting, compacted again after if requested meanwhile
+            let compacting: Arc<Mutex<HashMap<TseriesFamilyId, bool>>> = Arc::default();
+            // The ts families to compact, the hottest is compacted first
+            let mut queue: HashSet<TseriesFamilyId> = HashSet::new();
+            loop {
+                tokio::select! {
+                    ts_family_id = receiver.recv() => {
+                        let ts_family_id = match ts_family_id {
+                            Some(id) => id,
+                            None => break,
+                        };
+                        if let Some(pending) = compacting.lock().get_mut(&ts_family_id) {
+                            *pending = true;
+                            decr_compaction_backlog();
+                        } else if !queue.insert(ts_family_id) {
+                            decr_compaction_backlog();
                         }
                     }
+                    permit = throttle.start_compaction(), if !queue.is_empty() => {
+                        let tsf = match pick_hottest_ts_family(&version_set, &mut queue) {
+                            Some(tsf) => tsf,
+                            None => continue,
+                        };
+                        let ts_family_id = tsf.read().tf_id();
+                        compacting.lock().insert(ts_family_id, false);
+
+                        let ctx = ctx.clone();
+                        let throttle = throttle.clone();
+                        let summary_task_sender = summary_task_sender.clone();
+                        let compact_task_sender = compact_task_sender.clone();
+                        let compacting = compacting.clone();
+                        tokio::task::spawn_blocking(move || {
+                            Self::compact_ts_family(tsf, ctx, &throttle, &summary_task_sender);
+                            if compacting.lock().remove(&ts_family_id) == Some(true) {
+                                incr_compaction_backlog();
+                                let _ = compact_task_sender.send(ts_family_id);
+                            }
+                            drop(permit);
+                        });
+                    }
                 }
             }
         });
     }
 
+    fn compact_ts_family(
+        tsf: Arc<RwLock<TseriesFamily>>,
+        ctx: Arc<GlobalContext>,
+        throttle: &CompactionThrottle,
+        summary_task_sender: &UnboundedSender<SummaryTask>,
+    ) {
+        info!("Starting compaction on ts_family {}", tsf.read().tf_id());
+        tsf.read().reset_reads();
+        let start = Instant::now();
+        let compact_req = tsf.read().pick_compaction();
+        if let Some(req) = compact_req {
+            let database = req.database.clone();
+            let compact_ts_family = req.ts_family_id;
+            let out_level = req.out_level;
+            match compaction::run_compaction_job(req, ctx, throttle) {
+                Ok(Some(version_edit)) => {
+                    incr_compaction_success();
+                    let (summary_tx, summary_rx) = oneshot::channel();
+                    let ret = summary_task_sender.send(SummaryTask {
+                        edits: vec![version_edit],
+                        cb: summary_tx,
+                    });
+                    sample_tskv_compaction_duration(
+                        database.as_str(),
+                        compact_ts_family.to_string().as_str(),
+                        out_level.to_string().as_str(),
+                        start.elapsed().as_secs_f64(),
+                    )
+                    // TODO Handle summary result using summary_rx.
+                }
+                Ok(None) => {
+                    info!("There is nothing to compact.");
+                }
+                Err(e) => {
+                    incr_compaction_failed();
+                    error!("Compaction job failed: {}", e);
+                }
+            }
+        }
+    }
+
     fn run_summary_job(
         &self,
         summary: Summary,
@@ -303,6 +678,161 @@ impl TsKv {
         info!("Summary task handler started");
     }
 
+    fn run_metrics_job(&self) {
+        let version_set = self.version_set.clone();
+        let global_ctx = self.global_ctx.clone();
+        let wal_dirs = self
+            .options
+            .wal
+            .dirs()
+            .into_iter()
+            .map(|e| e.to_path_buf())
+            .collect::<Vec<_>>();
+        self.runtime.spawn(async move {
+            let mut ticker = tokio::time::interval(METRICS_INTERVAL);
+            loop {
+                ticker.tick().await;
+                for (name, db) in version_set.read().get_all_db() {
+                    let mut size = 0;
+                    db.read()
+                        .for_each_ts_family(|(_, tsf)| size += tsf.read().cache_size());
+                    set_memcache_size(name, size);
+                }
+                set_flush_pending_size(global_ctx.flush_pending_size());
+                // The directories are walked on the blocking threads
+                let dirs = wal_dirs.clone();
+                match tokio::task::spawn_blocking(move || {
+                    dirs.iter().map(file_utils::dir_size).sum::<u64>()
+                })
+                .await
+                {
+                    Ok(size) => set_wal_size(size),
+                    Err(e) => warn!("Failed to measure the size of the wal: {}", e),
+                }
+                let file_manager = file_manager::get_file_manager();
+                set_page_cache_counts(
+                    file_manager.cache_hit_count(),
+                    file_manager.cache_miss_count(),
+                );
+            }
+        });
+        info!("Metrics sampling job started");
+    }
+
+    fn run_series_gc_job(&self) {
+        let interval = self.options.storage.series_gc_interval;
+        if interval.is_zero() {
+            return;
+        }
+        let version_set = self.version_set.clone();
+        let series_gc = self.series_gc.clone();
+        self.runtime.spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // The first tick completes immediately
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let dbs = version_set.read().get_all_db().clone();
+                let series_gc = series_gc.clone();
+                let collected = tokio::task::spawn_blocking(move || {
+                    for (name, db) in dbs {
+                        match series_gc.collect(&name, &db) {
+                            Ok(0) => {}
+                            Ok(n) => {
+                                incr_series_gc_removed(n as u64);
+                                info!("Removed {} series deleted of {} from the index", n, name)
+                            }
+                            Err(e) => warn!("Failed to collect the series of {}: {}", name, e),
+                        }
+                    }
+                })
+                .await;
+                if let Err(e) = collected {
+                    error!("Series gc panicked: {}", e);
+                }
+            }
+        });
+        info!("Series gc job started");
+    }
+
+    fn run_retention_job(&self) {
+        let interval = self.options.storage.retention_interval;
+        if interval.is_zero() {
+            return;
+        }
+        let version_set = self.version_set.clone();
+        let summary_task_sender = self.summary_task_sender.clone();
+        self.runtime.spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let dbs = version_set.read().get_all_db().clone();
+                for (name, db) in dbs {
+                    remove_expired_files(&name, &db, &summary_task_sender).await;
+                }
+            }
+        });
+        info!("Retention job started");
+    }
+
+    /// Accounts the memcaches and the caches against the memory budget, the largest
+    /// memcaches are flushed early and the series cached by the indexes are evicted
+    /// once the budget is exceeded. Only the memory the handlers can free is
+    /// accounted: the memcaches already flushing are not, nor is the page cache,
+    /// which is bounded by `dio_max_resident`.
+    pub fn register_memory_budget(&self, budget: Arc<MemoryBudget>) {
+        if budget.limit() == 0 {
+            return;
+        }
+        {
+            let version_set = self.version_set.clone();
+            let global_ctx = self.global_ctx.clone();
+            let flush_task_sender = self.flush_task_sender.clone();
+            budget
+                .pool(MemoryPoolKind::Memcache)
+                .on_pressure(move |bytes| {
+                    flush_largest_caches(&version_set, &global_ctx, &flush_task_sender, bytes)
+                });
+        }
+        {
+            let version_set = self.version_set.clone();
+            budget.pool(MemoryPoolKind::Cache).on_pressure(move |_| {
+                for db in version_set.read().get_all_db().values() {
+                    db.read().get_index().clear_series_cache();
+                }
+            });
+        }
+
+        let version_set = self.version_set.clone();
+        self.runtime.spawn(async move {
+            let mut ticker = tokio::time::interval(MEMORY_BUDGET_INTERVAL);
+            loop {
+                ticker.tick().await;
+                let (mut memcache_size, mut cache_size) = (0, 0);
+                for db in version_set.read().get_all_db().values() {
+                    let db = db.read();
+                    db.for_each_ts_family(|(_, tsf)| {
+                        memcache_size += tsf.read().unflushed_cache_size()
+                    });
+                    cache_size += db.get_index().series_cache_size();
+                }
+                budget
+                    .pool(MemoryPoolKind::Memcache)
+                    .set_used(memcache_size);
+                budget.pool(MemoryPoolKind::Cache).set_used(cache_size);
+                for (kind, bytes) in budget.relieve_pressure() {
+                    info!(
+                        "Memory budget of {} bytes exceeded, {} asked to free {} bytes",
+                        budget.limit(),
+                        kind.as_str(),
+                        bytes
+                    );
+                }
+            }
+        });
+        info!("Memory budget job started: {:?}", budget);
+    }
+
     // fn run_timer_job(&self, pub_sender: Sender<()>) {
     //     let f = async move {
     //         let interval = Duration::from_secs(1);
@@ -327,7 +857,11 @@ impl TsKv {
             for (ts_family_id, ts_family) in db.read().ts_families() {
                 let compact_req = ts_family.read().pick_compaction();
                 if let Some(req) = compact_req {
-                    match compaction::run_compaction_job(req, self.global_ctx.clone()) {
+                    match compaction::run_compaction_job(
+                        req,
+                        self.global_ctx.clone(),
+                        &self.compaction_throttle,
+                    ) {
                         Ok(Some(version_edit)) => {
                             let (summary_tx, summary_rx) = oneshot::channel();
                             let ret = self.summary_task_sender.send(SummaryTask {
@@ -349,28 +883,31 @@ impl TsKv {
         }
     }
 
-    pub fn get_db(&self, database: &str) -> Result<Arc<RwLock<Database>>> {
-        let db = self
-            .version_set
-            .read()
-            .get_db(database)
-            .ok_or(DatabaseNotFound {
-                database: database.to_string(),
-            })?;
-        Ok(db)
-    }
-}
-
-#[async_trait::async_trait]
-impl Engine for TsKv {
-    async fn write(&self, write_batch: WritePointsRpcRequest) -> Result<WritePointsRpcResponse> {
+    /// Writes the points into the wal as an entry of the type, then into the memcache
+    async fn write_as(
+        &self,
+        write_batch: WritePointsRpcRequest,
+        typ: WalEntryType,
+    ) -> Result<WritePointsRpcResponse> {
+        self.disk_watchdog.check_writable()?;
+        let mut timings = WriteTimings::default();
+        let mut start = Instant::now();
+        self.wait_for_flushes()
+            .instrument(info_span!("wait_for_flushes"))
+            .await?;
+        timings.stall = start.elapsed();
+
+        start = Instant::now();
         let points = Arc::new(write_batch.points);
         let fb_points = flatbuffers::root::<fb_models::Points>(&points)
             .context(error::InvalidFlatbufferSnafu)?;
 
         let db_name = String::from_utf8(fb_points.db().unwrap().to_vec())
             .map_err(|err| Error::ErrCharacterSet)?;
+        timings.decode = start.elapsed();
 
+        // Waits for the locks of the version set and the index if contended
+        start = Instant::now();
         let db_warp = self.version_set.read().get_db(&db_name);
         let db = match db_warp {
             Some(database) => database,
@@ -379,9 +916,18 @@ impl Engine for TsKv {
                 .write()
                 .create_db(DatabaseSchema::new(&db_name))?,
         };
-        let write_group = db.read().build_write_group(fb_points.points().unwrap())?;
-
+        // The series resolved are not removed by the series gc until in the memcache
+        let barrier = db.read().write_barrier();
+        let barrier = barrier.read_owned().await;
+        let fb_points_list = fb_points.points().unwrap();
+        let points_num = fb_points_list.len() as u64;
+        let write_group = db.read().build_write_group(fb_points_list)?;
+        timings.schema_check = start.elapsed();
+
+        start = Instant::now();
         let mut seq = 0;
+        // The wal is not truncated from the write on until it's in the memcache
+        let pending = self.pending_writes.begin();
         if self.options.wal.enabled {
             let (cb, rx) = oneshot::channel();
             let mut enc_points = Vec::new();
@@ -391,32 +937,79 @@ impl Engine for TsKv {
                 .map_err(|_| Error::Send)?;
             self.wal_sender
                 .send(WalTask::Write {
+                    database: db_name.clone(),
+                    typ,
+                    pending: pending.id(),
                     cb,
                     points: Arc::new(enc_points),
                 })
                 .map_err(|err| Error::Send)?;
-            seq = rx.await.context(error::ReceiveSnafu)??.0;
+            seq = rx
+                .instrument(info_span!("wal"))
+                .await
+                .context(error::ReceiveSnafu)??
+                .0;
         }
+        timings.wal = start.elapsed();
 
+        start = Instant::now();
         let opt_tsf = db.read().get_tsfamily_random();
         let tsf = match opt_tsf {
             Some(v) => v,
+            // None of the writes of the new ts family is persisted yet
             None => db.write().add_tsfamily(
                 self.global_ctx.tsfamily_id_next(),
-                seq,
+                seq.saturating_sub(1),
                 self.summary_task_sender.clone(),
                 self.flush_task_sender.clone(),
             ),
         };
 
-        tsf.read().put_points(seq, write_group);
-        tsf.write().check_to_flush();
+        info_span!("memcache").in_scope(|| {
+            tsf.read().put_points(seq, write_group);
+            tsf.write().check_to_flush(&self.global_ctx);
+        });
+        drop(barrier);
+        drop(pending);
+        timings.memcache = start.elapsed();
+        incr_write_points(&db_name, points_num, points.len() as u64);
+        self.log_slow_write(&db_name, points_num, points.len(), &timings);
+        // No receivers is not an error
+        let _ = self.write_notifier.send(WriteEvent {
+            database: db_name,
+            seq,
+        });
         Ok(WritePointsRpcResponse {
             version: 1,
             points: vec![],
         })
     }
 
+    pub fn get_db(&self, database: &str) -> Result<Arc<RwLock<Database>>> {
+        let db = self
+            .version_set
+            .read()
+            .get_db(database)
+            .ok_or(DatabaseNotFound {
+                database: database.to_string(),
+            })?;
+        Ok(db)
+    }
+}
+
+#[async_trait::async_trait]
+impl Engine for TsKv {
+    async fn write(&self, write_batch: WritePointsRpcRequest) -> Result<WritePointsRpcResponse> {
+        self.write_as(write_batch, WalEntryType::Write).await
+    }
+
+    async fn write_replayed(
+        &self,
+        write_batch: WritePointsRpcRequest,
+    ) -> Result<WritePointsRpcResponse> {
+        self.write_as(write_batch, WalEntryType::Replay).await
+    }
+
     async fn write_from_wal(
         &self,
         write_batch: WritePointsRpcRequest,
@@ -434,14 +1027,24 @@ impl Engine for TsKv {
             .write()
             .create_db(DatabaseSchema::new(&db_name))?;
 
+        let opt_tsf = db.read().get_tsfamily_random();
+        if let Some(tsf) = &opt_tsf {
+            if seq <= tsf.read().version().last_seq {
+                // Persisted before the restart
+                return Ok(WritePointsRpcResponse {
+                    version: 1,
+                    points: vec![],
+                });
+            }
+        }
         let write_group = db.read().build_write_group(fb_points.points().unwrap())?;
 
-        let opt_tsf = db.read().get_tsfamily_random();
         let tsf = match opt_tsf {
             Some(v) => v,
+            // None of the writes of the new ts family is persisted yet
             None => db.write().add_tsfamily(
                 self.global_ctx.tsfamily_id_next(),
-                seq,
+                seq.saturating_sub(1),
                 self.summary_task_sender.clone(),
                 self.flush_task_sender.clone(),
             ),
@@ -474,6 +1077,15 @@ impl Engine for TsKv {
                 database: schema.name.clone(),
             })?;
         db.write().alter_db_schema(schema.clone())?;
+
+        // The ttl altered takes effect at once, not on the next pass
+        if !self.options.storage.retention_interval.is_zero() {
+            let database = schema.name.clone();
+            let summary_task_sender = self.summary_task_sender.clone();
+            self.runtime.spawn(async move {
+                remove_expired_files(&database, &db, &summary_task_sender).await;
+            });
+        }
         Ok(())
     }
 
@@ -521,6 +1133,7 @@ impl Engine for TsKv {
                 .write()
                 .remove_db_index(&database);
         }
+        self.series_gc.remove_database(&database);
 
         let idx_dir = self.options.storage.index_dir(&database);
         if let Err(e) = std::fs::remove_dir_all(&idx_dir) {
@@ -627,6 +1240,17 @@ impl Engine for TsKv {
         Ok(())
     }
 
+    fn delete_series_index(&self, database: &str, series_ids: &[SeriesId]) -> Result<()> {
+        if let Some(db) = self.version_set.read().get_db(database) {
+            let index = db.read().get_index();
+            for sid in series_ids {
+                index.del_series_info(*sid).context(error::IndexErrSnafu)?;
+            }
+            index.flush().context(error::IndexErrSnafu)?;
+        }
+        Ok(())
+    }
+
     fn get_table_schema(&self, name: &str, tab: &str) -> Result<Option<TableSchema>> {
         if let Some(db) = self.version_set.read().get_db(name) {
             let val = db
@@ -700,6 +1324,199 @@ impl Engine for TsKv {
         }
     }
 
+    fn database_usage(&self, db: &str) -> Result<DatabaseUsage> {
+        let mut usage = DatabaseUsage::default();
+        self.get_db(db)?.read().for_each_ts_family(|(_, tsf)| {
+            let tsf = tsf.read();
+            usage.tsm_bytes += tsf
+                .version()
+                .levels_info()
+                .iter()
+                .map(|level| level.cur_size)
+                .sum::<u64>();
+            usage.cache_bytes += tsf.cache_size();
+        });
+        if self.options.wal.database_paths.contains_key(db) {
+            usage.wal_bytes = file_utils::dir_size(self.options.wal.database_dir(db));
+        }
+        Ok(usage)
+    }
+
+    fn subscribe_writes(&self) -> BroadcastReceiver<WriteEvent> {
+        self.write_notifier.subscribe()
+    }
+
+    fn read_changes(&self, db: &str, from: ChangeOffset, limit: usize) -> Result<ChangeBatch> {
+        if !self.options.wal.enabled {
+            return Err(Error::WalDisabled);
+        }
+        cdc::read_changes(self.options.wal.database_dir(db), db, from, limit)
+    }
+
+    fn retain_changes(&self, consumer: &str, db: &str, offset: ChangeOffset) -> Result<()> {
+        self.change_consumers
+            .commit(consumer, db, self.options.wal.database_dir(db), offset)
+    }
+
+    fn verify(&self, db: &str) -> Result<VerifyReport> {
+        let database = self.get_db(db)?;
+        let (versions, index) = {
+            let database = database.read();
+            let versions = database
+                .ts_families()
+                .values()
+                .map(|tsf| tsf.read().version())
+                .collect::<Vec<_>>();
+            (versions, database.get_index())
+        };
+        Ok(verify::verify_database(db, &versions, &index))
+    }
+
+    fn export_partition(
+        &self,
+        db: &str,
+        time_range: &TimeRange,
+        dir: &Path,
+    ) -> Result<PartitionManifest> {
+        let database = self.get_db(db)?;
+        let (versions, index) = {
+            let database = database.read();
+            let versions = database
+                .ts_families()
+                .values()
+                .map(|tsf| tsf.read().version())
+                .collect::<Vec<_>>();
+            (versions, database.get_index())
+        };
+        partition::export_partition(db, &versions, &index, time_range, dir)
+    }
+
+    async fn import_partition(&self, db: &str, dir: &Path) -> Result<PartitionManifest> {
+        let manifest = partition::read_manifest(dir)?;
+        let database = self.get_db(db)?;
+        let opt_tsf = database.read().get_tsfamily_random();
+        let tsf = match opt_tsf {
+            Some(v) => v,
+            None => database.write().add_tsfamily(
+                self.global_ctx.tsfamily_id_next(),
+                self.global_ctx.last_seq(),
+                self.summary_task_sender.clone(),
+                self.flush_task_sender.clone(),
+            ),
+        };
+        let index = database.read().get_index();
+        let version = tsf.read().version();
+        // Rewriting the files is blocking and may take long
+        let (metas, manifest, version) = {
+            let (dir, global_ctx, options) = (
+                dir.to_path_buf(),
+                self.global_ctx.clone(),
+                self.options.clone(),
+            );
+            tokio::task::spawn_blocking(move || {
+                partition::import_partition(
+                    &manifest,
+                    &dir,
+                    &index,
+                    &options.storage,
+                    &version,
+                    || global_ctx.file_id_next(),
+                )
+                .map(|metas| (metas, manifest, version))
+            })
+            .await
+            .map_err(|e| Error::Partition {
+                reason: e.to_string(),
+            })??
+        };
+        if metas.is_empty() {
+            return Ok(manifest);
+        }
+
+        let mut edit = VersionEdit::new();
+        edit.tsf_id = version.tf_id();
+        for meta in metas {
+            edit.add_file(meta, version.max_level_ts);
+        }
+        let (cb, rx) = oneshot::channel();
+        self.summary_task_sender
+            .send(SummaryTask {
+                edits: vec![edit],
+                cb,
+            })
+            .map_err(|_| Error::Send)?;
+        rx.await.context(error::ReceiveSnafu)??;
+        info!(
+            "Imported {} files of the partition [{}, {}] into {}",
+            manifest.files.len(),
+            manifest.min_ts,
+            manifest.max_ts,
+            db
+        );
+        Ok(manifest)
+    }
+
+    async fn flush_database(&self, db: &str) -> Result<()> {
+        let database = self.get_db(db)?;
+        let (mut mems, mut flushing) = (vec![], vec![]);
+        let index = {
+            let database = database.read();
+            database.for_each_ts_family(|(_, tsf)| {
+                flushing.extend(flushing_caches(tsf));
+                mems.append(&mut tsf.write().take_unflushed_caches(&self.global_ctx));
+            });
+            database.get_index()
+        };
+        // The series of the data flushed are in the index
+        index.flush().context(error::IndexErrSnafu)?;
+        self.flush_caches(mems).await?;
+        // The caches taken by the flushes in the background before are persisted too
+        self.wait_for_flushing(flushing).await
+    }
+
+    async fn delete_before(&self, db: &str, before: Timestamp) -> Result<DeletedData> {
+        let ts_families = self
+            .get_db(db)?
+            .read()
+            .ts_families()
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+        let edits = ts_families
+            .iter()
+            .filter_map(|tsf| retention::expired_files_edit(&tsf.read().version(), before))
+            .collect::<Vec<_>>();
+        let files = remove_files(edits, &self.summary_task_sender).await?;
+
+        // The points before the timestamp in the caches and in the files kept are
+        // deleted by the tombstones
+        let time_range = TimeRange::new(Timestamp::MIN, before.saturating_sub(1));
+        let mut series = 0;
+        for table in self.list_tables(db)? {
+            let schema = match self.get_table_schema(db, &table)? {
+                Some(TableSchema::TsKvTableSchema(schema)) => schema,
+                _ => continue,
+            };
+            let sids = self
+                .get_series_id_list(db, &table, &[])
+                .context(IndexErrSnafu)?;
+            let storage_fids: Vec<u64> = sids
+                .iter()
+                .flat_map(|sid| schema.columns().iter().map(|c| unite_id(c.id as u64, *sid)))
+                .collect();
+            for tsf in ts_families.iter() {
+                tsf.write().delete_series(&sids, &time_range);
+                let version = tsf.read().super_version();
+                for column_file in version.version.column_files(&storage_fids, &time_range) {
+                    column_file.add_tombstone(&storage_fids, &time_range)?;
+                }
+            }
+            series += sids.len();
+        }
+
+        Ok(DeletedData { files, series })
+    }
+
     fn add_table_column(&self, database: &str, table: &str, column: TableColumn) -> Result<()> {
         let db = self.get_db(database)?;
         db.read()
@@ -745,6 +1562,159 @@ impl Engine for TsKv {
             .context(IndexErrSnafu)?;
         Ok(())
     }
+
+    fn set_table_statistics(
+        &self,
+        database: &str,
+        table: &str,
+        statistics: TableStatistics,
+    ) -> Result<()> {
+        let db = self.get_db(database)?;
+        db.read()
+            .set_table_statistics(table, statistics)
+            .context(IndexErrSnafu)?;
+        Ok(())
+    }
+}
+
+/// Removes the files deleted by the edits from the versions, they are deleted from
+/// disk once they are not read. Returns the number of files removed.
+async fn remove_files(
+    edits: Vec<VersionEdit>,
+    summary_task_sender: &UnboundedSender<SummaryTask>,
+) -> Result<usize> {
+    let files = edits.iter().map(|e| e.del_files.len()).sum();
+    if files == 0 {
+        return Ok(0);
+    }
+    let (cb, rx) = oneshot::channel();
+    summary_task_sender
+        .send(SummaryTask { edits, cb })
+        .map_err(|_| Error::Send)?;
+    rx.await.context(error::ReceiveSnafu)??;
+    Ok(files)
+}
+
+/// Removes the files of the database expired by its ttl from the versions of the
+/// ts families
+async fn remove_expired_files(
+    database: &str,
+    db: &RwLock<Database>,
+    summary_task_sender: &UnboundedSender<SummaryTask>,
+) {
+    let now = chrono::Utc::now().timestamp_nanos();
+    let cutoff = match retention::retention_cutoff(&db.read().get_schema(), now) {
+        Some(cutoff) => cutoff,
+        None => return,
+    };
+    let edits = db
+        .read()
+        .ts_families()
+        .values()
+        .filter_map(|tsf| retention::expired_files_edit(&tsf.read().version(), cutoff))
+        .collect::<Vec<_>>();
+    match remove_files(edits, summary_task_sender).await {
+        Ok(0) => {}
+        Ok(files) => info!("Removed {} files expired by the ttl of {}", files, database),
+        Err(e) => error!("Failed to remove the files expired of {}: {}", database, e),
+    }
+}
+
+/// The caches of the ts family taken by the flushes in the background
+fn flushing_caches(
+    tsf: &Arc<RwLock<TseriesFamily>>,
+) -> Vec<(Arc<RwLock<TseriesFamily>>, Arc<RwLock<MemCache>>)> {
+    tsf.read()
+        .flushing_caches()
+        .into_iter()
+        .map(|cache| (tsf.clone(), cache))
+        .collect()
+}
+
+/// Flushes the memcaches of the ts families of the most cached first, until the
+/// bytes are flushed
+fn flush_largest_caches(
+    version_set: &RwLock<VersionSet>,
+    global_ctx: &Arc<GlobalContext>,
+    flush_task_sender: &UnboundedSender<FlushReq>,
+    bytes: u64,
+) {
+    let mut ts_families = vec![];
+    for db in version_set.read().get_all_db().values() {
+        db.read().for_each_ts_family(|(_, tsf)| {
+            let size = tsf.read().unflushed_cache_size();
+            ts_families.push((size, tsf.clone()));
+        });
+    }
+    ts_families.sort_by_key(|(size, _)| std::cmp::Reverse(*size));
+
+    let mut flushed = 0;
+    for (size, tsf) in ts_families {
+        if flushed >= bytes || size == 0 {
+            break;
+        }
+        let mut tsf = tsf.write();
+        let mems = tsf.take_unflushed_caches(global_ctx);
+        if mems.is_empty() {
+            continue;
+        }
+        info!(
+            "Flushing {} caches of ts_family {} early for the memory budget",
+            mems.len(),
+            tsf.tf_id()
+        );
+        if flush_task_sender.send(FlushReq::new(mems)).is_err() {
+            error!("Failed to send the flush request, the flush job is stopped");
+            return;
+        }
+        flushed += size;
+    }
+}
+
+/// Takes the ts family with the highest compaction score out of the queue, the
+/// ones dropped are removed
+fn pick_hottest_ts_family(
+    version_set: &RwLock<VersionSet>,
+    queue: &mut HashSet<TseriesFamilyId>,
+) -> Option<Arc<RwLock<TseriesFamily>>> {
+    let version_set = version_set.read();
+    let mut hottest: Option<(f64, Arc<RwLock<TseriesFamily>>)> = None;
+    queue.retain(|id| match version_set.get_tsfamily_by_tf_id(*id) {
+        Some(tsf) => {
+            let score = tsf.read().compaction_score();
+            if hottest.as_ref().map_or(true, |(max, _)| score > *max) {
+                hottest = Some((score, tsf));
+            }
+            true
+        }
+        None => {
+            decr_compaction_backlog();
+            false
+        }
+    });
+    let (score, tsf) = hottest?;
+    let ts_family_id = tsf.read().tf_id();
+    debug!(
+        "Picked ts_family {} to compact, score {}",
+        ts_family_id, score
+    );
+    queue.remove(&ts_family_id);
+    decr_compaction_backlog();
+    Some(tsf)
+}
+
+/// The writes in the wal at or before the seq are persisted by all the ts families,
+/// None if none of the ts families has writes in the caches
+fn wal_checkpoint(version_set: &RwLock<VersionSet>) -> Option<u64> {
+    let mut checkpoint: Option<u64> = None;
+    for db in version_set.read().get_all_db().values() {
+        db.read().for_each_ts_family(|(_, tsf)| {
+            if let Some(seq) = tsf.read().wal_checkpoint() {
+                checkpoint = Some(checkpoint.map_or(seq, |e| e.min(seq)));
+            }
+        });
+    }
+    checkpoint
 }
 
 #[cfg(test)]
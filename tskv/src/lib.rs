@@ -4,9 +4,11 @@
 #![allow(unused_imports, unused_variables)]
 
 mod byte_utils;
+pub mod cdc;
 mod compaction;
 mod context;
 pub mod database;
+pub mod disk_watchdog;
 pub mod engine;
 pub mod error;
 pub mod file_system;
@@ -15,11 +17,17 @@ pub mod index;
 pub mod kv_option;
 mod kvcore;
 pub mod memcache;
+pub mod partition;
 mod reader;
 mod record_file;
+mod repair;
+mod retention;
+mod series_gc;
 mod summary;
 pub mod tseries_family;
 pub mod tsm;
+mod upgrade;
+mod verify;
 mod version_set;
 mod wal;
 
@@ -27,12 +35,15 @@ pub use error::{Error, Result};
 pub use kv_option::Options;
 pub use kvcore::TsKv;
 use protos::kv_service::WritePointsRpcResponse;
+pub use repair::{repair_tsm_file, LostRange, RepairReport};
 pub use summary::print_summary_statistics;
 pub use summary::Summary;
 use tokio::sync::oneshot;
 pub use tseries_family::TimeRange;
 pub use tsm::print_tsm_statistics;
+pub use upgrade::{upgrade_files, UpgradeReport};
 use utils::BloomFilter;
+pub use verify::{VerifyProblem, VerifyReport};
 
 pub type ColumnFileId = u64;
 type TseriesFamilyId = u32;
@@ -20,6 +20,7 @@ use std::{borrow::BorrowMut, collections::HashMap, mem::size_of_val, rc::Rc};
 use minivec::{mini_vec, MiniVec};
 use trace::{error, info, warn};
 
+use crate::context::{FlushPending, GlobalContext};
 use crate::tsm::DataBlock;
 use crate::{byte_utils, error::Result, tseries_family::TimeRange, TseriesFamilyId};
 use models::schema::{TableColumn, TskvTableSchema};
@@ -106,63 +107,52 @@ impl Display for FieldVal {
     }
 }
 
+/// Values of the fields written by one point, sorted by column id. Fields the
+/// point did not write are not stored and are read back as NULL.
 #[derive(Debug)]
 pub struct RowData {
     pub ts: i64,
-    pub fields: Vec<Option<FieldVal>>,
+    pub fields: Vec<(ColumnId, FieldVal)>,
 }
 
 impl RowData {
     pub fn point_to_row_data(p: fb_models::Point, schema: &TskvTableSchema) -> RowData {
-        let fields = match p.fields() {
-            None => {
-                let mut fields = Vec::with_capacity(schema.field_num());
-                for i in 0..fields.capacity() {
-                    fields.push(None);
+        let mut fields: Vec<(ColumnId, FieldVal)> = Vec::new();
+        if let Some(fields_inner) = p.fields() {
+            fields.reserve(fields_inner.len());
+            for f in fields_inner.into_iter() {
+                let column = match schema.column(
+                    String::from_utf8(f.name().unwrap().to_vec())
+                        .unwrap()
+                        .as_str(),
+                ) {
+                    Some(column) if column.column_type.is_field() => column,
+                    _ => continue,
+                };
+                let vtype = f.type_().into();
+                let val = FieldVal::new(MiniVec::from(f.value().unwrap()), vtype);
+                // The last value wins if a point repeats a field.
+                match fields.binary_search_by_key(&column.id, |(id, _)| *id) {
+                    Ok(i) => fields[i].1 = val,
+                    Err(i) => fields.insert(i, (column.id, val)),
                 }
-                fields
             }
-            Some(fields_inner) => {
-                let fields_id = schema.fields_id();
-                let mut fields: Vec<Option<FieldVal>> = Vec::with_capacity(fields_id.len());
-                for i in 0..fields.capacity() {
-                    fields.push(None);
-                }
-                for (i, f) in fields_inner.into_iter().enumerate() {
-                    let vtype = f.type_().into();
-                    let val = MiniVec::from(f.value().unwrap());
-                    match schema.column(
-                        String::from_utf8(f.name().unwrap().to_vec())
-                            .unwrap()
-                            .as_str(),
-                    ) {
-                        None => {}
-                        Some(field) => match fields_id.get(&field.id) {
-                            None => {}
-                            Some(index) => {
-                                fields[*index] = Some(FieldVal::new(val, vtype));
-                            }
-                        },
-                    }
-                }
-                fields
-            }
-        };
+        }
         let ts = p.timestamp();
         RowData { ts, fields }
     }
 
+    pub fn field(&self, column_id: ColumnId) -> Option<&FieldVal> {
+        self.fields
+            .binary_search_by_key(&column_id, |(id, _)| *id)
+            .ok()
+            .map(|i| &self.fields[i].1)
+    }
+
     pub fn size(&self) -> usize {
         let mut size = 0;
-        for i in self.fields.iter() {
-            match i {
-                None => {
-                    size += size_of_val(i);
-                }
-                Some(v) => {
-                    size += size_of_val(i) + v.heap_size();
-                }
-            }
+        for (id, val) in self.fields.iter() {
+            size += size_of_val(id) + size_of_val(val) + val.heap_size();
         }
         size += size_of_val(&self.ts);
         size += size_of_val(&self.fields);
@@ -170,26 +160,6 @@ impl RowData {
     }
 }
 
-impl From<fb_models::Point<'_>> for RowData {
-    fn from(p: fb_models::Point<'_>) -> Self {
-        let fields = match p.fields() {
-            Some(fields_inner) => {
-                let mut fields = Vec::with_capacity(fields_inner.len());
-                for f in fields_inner.into_iter() {
-                    let vtype = f.type_().into();
-                    let val = MiniVec::from(f.value().unwrap());
-                    fields.push(Some(FieldVal::new(val, vtype)));
-                }
-                fields
-            }
-            None => vec![],
-        };
-
-        let ts = p.timestamp();
-        Self { ts, fields }
-    }
-}
-
 #[derive(Debug)]
 pub struct RowGroup {
     pub schema: TskvTableSchema,
@@ -227,12 +197,8 @@ impl SeriesData {
                 None => continue,
                 Some(name) => name.to_string(),
             };
-            let index = match item.schema.fields_id().get(&column_id) {
-                None => continue,
-                Some(index) => *index,
-            };
             for row in item.rows.iter_mut() {
-                row.fields.remove(index);
+                row.fields.retain(|(id, _)| *id != column_id);
             }
             item.schema.drop_column(&name);
             item.schema.schema_id += 1;
@@ -258,17 +224,12 @@ impl SeriesData {
     ) -> Vec<DataType> {
         let mut res = Vec::new();
         for group in self.groups.iter() {
-            let field_index = group.schema.fields_id();
-            let index = match field_index.get(&column_id) {
-                None => continue,
-                Some(v) => v,
-            };
             group
                 .rows
                 .iter()
                 .filter(|row| time_predicate(row.ts))
                 .for_each(|row| {
-                    if let Some(Some(field)) = row.fields.get(*index) {
+                    if let Some(field) = row.field(column_id) {
                         if value_predicate(field) {
                             res.push(field.data_value(row.ts));
                         }
@@ -304,6 +265,8 @@ pub struct MemCache {
 
     pub flushed: bool,
     pub flushing: bool,
+    /// Counted as pending flush once it is immutable, until it is flushed
+    flush_pending: Option<FlushPending>,
 
     max_size: u64,
     min_seq_no: u64,
@@ -333,6 +296,7 @@ impl MemCache {
 
             flushed: false,
             flushing: false,
+            flush_pending: None,
 
             part_count: parts,
 
@@ -341,6 +305,19 @@ impl MemCache {
         }
     }
 
+    /// Counts the cache as pending flush, if not yet
+    pub fn mark_flush_pending(&mut self, ctx: &Arc<GlobalContext>) {
+        if self.flush_pending.is_none() && !self.flushed {
+            self.flush_pending = Some(ctx.flush_pending(self.cache_size()));
+        }
+    }
+
+    /// The cache is flushed, it is no longer counted as pending flush
+    pub fn mark_flushed(&mut self) {
+        self.flushed = true;
+        self.flush_pending = None;
+    }
+
     pub fn write_group(&self, sid: SeriesId, seq: u64, group: RowGroup) {
         let (_, sid) = split_id(sid);
         self.seq_no.store(seq, Ordering::Relaxed);
@@ -385,6 +362,24 @@ impl MemCache {
         true
     }
 
+    /// The time range of the rows of the series in the cache, None if empty
+    pub fn time_range(&self) -> Option<TimeRange> {
+        let mut time_range: Option<TimeRange> = None;
+        for part in self.partions.iter() {
+            for series in part.read().values() {
+                let range = series.read().range;
+                if range.min_ts > range.max_ts {
+                    continue;
+                }
+                match time_range.as_mut() {
+                    Some(time_range) => time_range.merge(&range),
+                    None => time_range = Some(range),
+                }
+            }
+        }
+        time_range
+    }
+
     pub fn delete_columns(&self, field_ids: &[FieldId]) {
         for fid in field_ids {
             let (column_id, sid) = utils::split_id(*fid);
@@ -472,6 +467,16 @@ impl DataType {
         }
     }
 
+    /// Bytes of the timestamp and the value
+    pub fn size(&self) -> usize {
+        let val_size = match self {
+            DataType::Str(_, val) => val.len(),
+            DataType::Bool(..) => 1,
+            _ => 8,
+        };
+        8 + val_size
+    }
+
     pub fn with_field_val(ts: Timestamp, field_val: FieldVal) -> Self {
         match field_val {
             FieldVal::Float(val) => Self::F64(ts, val),
@@ -499,12 +504,15 @@ impl Display for DataType {
 pub(crate) mod test {
     use bytes::buf;
     use models::schema::TskvTableSchema;
-    use models::{SchemaId, SeriesId, Timestamp};
+    use models::{ColumnId, SchemaId, SeriesId, Timestamp};
     use std::mem::{size_of, size_of_val};
+    use std::sync::Arc;
 
+    use crate::compaction::flush_tests::default_with_field_id;
+    use crate::context::GlobalContext;
     use crate::{tsm::DataBlock, TimeRange};
 
-    use super::{DataType, FieldVal, MemCache, RowData, RowGroup};
+    use super::{DataType, FieldVal, MemCache, RowData, RowGroup, SeriesData};
 
     pub(crate) fn put_rows_to_cache(
         cache: &mut MemCache,
@@ -518,13 +526,10 @@ pub(crate) mod test {
         let mut size: usize = schema.size();
         for ts in time_range.0..time_range.1 + 1 {
             let mut fields = Vec::new();
-            for _ in 0..schema.columns().len() {
-                size += size_of::<Option<FieldVal>>();
-                if put_none {
-                    fields.push(None);
-                } else {
-                    fields.push(Some(FieldVal::Float(ts as f64)));
-                    size += 8;
+            if !put_none {
+                for column in schema.columns() {
+                    fields.push((column.id, FieldVal::Float(ts as f64)));
+                    size += size_of::<(ColumnId, FieldVal)>();
                 }
             }
             size += 8;
@@ -540,4 +545,111 @@ pub(crate) mod test {
         };
         cache.write_group(series_id, 1, row_group);
     }
+
+    #[test]
+    fn test_sparse_rows() {
+        let mut series = SeriesData::default();
+        series.write(RowGroup {
+            schema: default_with_field_id(vec![0, 1, 2]),
+            range: TimeRange::new(1, 3),
+            rows: vec![
+                RowData {
+                    ts: 1,
+                    fields: vec![(0, FieldVal::Integer(1)), (2, FieldVal::Integer(3))],
+                },
+                RowData {
+                    ts: 2,
+                    fields: vec![(1, FieldVal::Integer(2))],
+                },
+                RowData {
+                    ts: 3,
+                    fields: vec![],
+                },
+            ],
+            size: 0,
+        });
+
+        assert_eq!(series.groups[0].rows[0].field(1), None);
+        assert_eq!(
+            series.groups[0].rows[0].field(2),
+            Some(&FieldVal::Integer(3))
+        );
+        assert_eq!(
+            series.read_data(1, |_| true, |_| true),
+            vec![DataType::I64(2, 2)]
+        );
+        assert_eq!(
+            series.read_data(2, |_| true, |_| true),
+            vec![DataType::I64(1, 3)]
+        );
+
+        series.delete_column(0);
+        assert!(series.read_data(0, |_| true, |_| true).is_empty());
+        assert_eq!(
+            series.read_data(2, |_| true, |_| true),
+            vec![DataType::I64(1, 3)]
+        );
+    }
+
+    #[test]
+    fn test_time_range() {
+        let mut cache = MemCache::new(1, 1024 * 1024, 0);
+        assert_eq!(cache.time_range(), None);
+
+        put_rows_to_cache(
+            &mut cache,
+            1,
+            1,
+            default_with_field_id(vec![0]),
+            (3, 5),
+            false,
+        );
+        put_rows_to_cache(
+            &mut cache,
+            2,
+            1,
+            default_with_field_id(vec![0]),
+            (1, 2),
+            false,
+        );
+        assert_eq!(cache.time_range(), Some(TimeRange::new(1, 5)));
+    }
+
+    #[test]
+    fn test_flush_pending() {
+        let ctx = Arc::new(GlobalContext::new());
+        let mut cache = MemCache::new(1, 1024 * 1024, 0);
+        put_rows_to_cache(
+            &mut cache,
+            1,
+            1,
+            default_with_field_id(vec![0]),
+            (1, 5),
+            false,
+        );
+        let size = cache.cache_size();
+        assert!(size > 0);
+
+        // Counted once however many times it is marked
+        cache.mark_flush_pending(&ctx);
+        cache.mark_flush_pending(&ctx);
+        assert_eq!(ctx.flush_pending_size(), size);
+        cache.mark_flushed();
+        assert_eq!(ctx.flush_pending_size(), 0);
+
+        // Released once dropped without being flushed
+        let mut cache = MemCache::new(1, 1024 * 1024, 0);
+        put_rows_to_cache(
+            &mut cache,
+            1,
+            1,
+            default_with_field_id(vec![0]),
+            (1, 5),
+            false,
+        );
+        cache.mark_flush_pending(&ctx);
+        assert_eq!(ctx.flush_pending_size(), size);
+        drop(cache);
+        assert_eq!(ctx.flush_pending_size(), 0);
+    }
 }
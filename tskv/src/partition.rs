@@ -0,0 +1,459 @@
+//! Exports the data of a time partition of a database as files with a manifest,
+//! and imports the files into a database of another instance, e.g. to move the
+//! data of a partition or to archive it.
+//!
+//! The data of the files overlapping the partition is rewritten to the directory
+//! exported to, one file for each file of the database at the same level, without
+//! the rows deleted or out of the partition. The manifest records the series and
+//! the tables of the fields in the files, the ids of the series and the columns
+//! are mapped to the ones of the database imported into by the keys of the series
+//! and the names of the columns.
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+    sync::Arc,
+};
+
+use models::{
+    schema::{TableSchema, TskvTableSchema},
+    utils::{split_id, unite_id},
+    ColumnId, FieldId, SeriesKey, Timestamp,
+};
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+use trace::info;
+
+use crate::{
+    error::{self, Error, Result},
+    file_utils,
+    index::db_index::DBIndex,
+    kv_option::StorageOptions,
+    summary::CompactMeta,
+    tseries_family::{ColumnFile, TimeRange, Version},
+    tsm::{IndexMeta, TsmReader, TsmWriter},
+    LevelId,
+};
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PartitionManifest {
+    pub database: String,
+    pub min_ts: Timestamp,
+    pub max_ts: Timestamp,
+    pub files: Vec<PartitionFile>,
+    /// Series of the fields in the files
+    pub series: Vec<SeriesKey>,
+    /// Tables of the series
+    pub tables: Vec<TskvTableSchema>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PartitionFile {
+    /// Name of the file in the directory exported to
+    pub file_name: String,
+    pub level: LevelId,
+    pub is_delta: bool,
+    pub min_ts: Timestamp,
+    pub max_ts: Timestamp,
+    pub size: u64,
+}
+
+pub fn export_partition(
+    database: &str,
+    versions: &[Arc<Version>],
+    index: &DBIndex,
+    time_range: &TimeRange,
+    dir: &Path,
+) -> Result<PartitionManifest> {
+    if dir.join(MANIFEST_FILE).exists() {
+        return Err(Error::Partition {
+            reason: format!("a partition is exported to {} already", dir.display()),
+        });
+    }
+    fs::create_dir_all(dir).context(error::IOSnafu)?;
+
+    let mut manifest = PartitionManifest {
+        database: database.to_string(),
+        min_ts: time_range.min_ts,
+        max_ts: time_range.max_ts,
+        ..Default::default()
+    };
+    // The fields are of the low 40 bits of the series ids
+    let mut series_ids = HashSet::new();
+    let mut file_id = 0;
+    for version in versions {
+        for level in version.levels_info() {
+            for file in level.files.iter().filter(|e| e.overlap(time_range)) {
+                file_id += 1;
+                if let Some(exported) =
+                    export_file(file, time_range, dir, file_id, &mut series_ids)?
+                {
+                    manifest.files.push(exported);
+                }
+            }
+        }
+    }
+
+    let mut tables = HashSet::new();
+    for key in index.series_keys().context(error::IndexErrSnafu)? {
+        if series_ids.contains(&split_id(key.id()).1) {
+            tables.insert(key.table().clone());
+            manifest.series.push(key);
+        }
+    }
+    for table in tables {
+        match index
+            .get_table_schema(&table)
+            .context(error::IndexErrSnafu)?
+        {
+            Some(TableSchema::TsKvTableSchema(schema)) => manifest.tables.push(schema),
+            _ => return Err(Error::NotFoundTable { table_name: table }),
+        }
+    }
+
+    let data = serde_json::to_vec_pretty(&manifest).map_err(|e| Error::Partition {
+        reason: e.to_string(),
+    })?;
+    fs::write(dir.join(MANIFEST_FILE), data).context(error::IOSnafu)?;
+    info!(
+        "Exported {} files of the partition [{}, {}] of {} to {}",
+        manifest.files.len(),
+        time_range.min_ts,
+        time_range.max_ts,
+        database,
+        dir.display()
+    );
+    Ok(manifest)
+}
+
+/// Rewrites the rows of the file in the time range, None if there is none
+fn export_file(
+    file: &ColumnFile,
+    time_range: &TimeRange,
+    dir: &Path,
+    file_id: u64,
+    series_ids: &mut HashSet<u64>,
+) -> Result<Option<PartitionFile>> {
+    let path = if file.is_delta() {
+        file_utils::make_delta_file_name(dir, file_id)
+    } else {
+        file_utils::make_tsm_file_name(dir, file_id)
+    };
+    let reader = TsmReader::open(file.file_path())?;
+    let mut writer: Option<TsmWriter> = None;
+    for idx in reader.index_iterator() {
+        for blk_meta in idx.block_iterator_opt(time_range) {
+            // The rows deleted are excluded
+            let mut block = reader.get_data_block(&blk_meta)?;
+            block.retain(time_range);
+            if block.is_empty() {
+                continue;
+            }
+            let writer = match writer.as_mut() {
+                Some(writer) => writer,
+                None => writer.insert(TsmWriter::open(&path, file_id, file.is_delta(), 0)?),
+            };
+            writer
+                .write_block(idx.field_id(), &block)
+                .context(error::WriteTsmSnafu)?;
+            series_ids.insert(split_id(idx.field_id()).1);
+        }
+    }
+
+    let mut writer = match writer {
+        Some(writer) => writer,
+        None => return Ok(None),
+    };
+    writer.write_index().context(error::WriteTsmSnafu)?;
+    writer.finish().context(error::WriteTsmSnafu)?;
+    Ok(Some(PartitionFile {
+        file_name: file_name(&path),
+        level: file.level(),
+        is_delta: file.is_delta(),
+        min_ts: writer.min_ts(),
+        max_ts: writer.max_ts(),
+        size: writer.size(),
+    }))
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|e| e.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+pub fn read_manifest(dir: &Path) -> Result<PartitionManifest> {
+    let data = fs::read(dir.join(MANIFEST_FILE)).context(error::IOSnafu)?;
+    serde_json::from_slice(&data).map_err(|e| Error::Partition {
+        reason: format!("invalid manifest in {}: {}", dir.display(), e),
+    })
+}
+
+/// Rewrites the files exported to the directory into the ts family with the ids of
+/// the series and the columns of the index, returns the metas of the files to be
+/// added to the version.
+///
+/// The files are imported at level 0 as delta files if the version has any data of
+/// the partition, to be merged with by the compactions.
+pub fn import_partition(
+    manifest: &PartitionManifest,
+    dir: &Path,
+    index: &DBIndex,
+    storage: &StorageOptions,
+    version: &Version,
+    file_id_next: impl Fn() -> u64,
+) -> Result<Vec<CompactMeta>> {
+    let columns = import_tables(&manifest.tables, index)?;
+    let mut series = HashMap::new();
+    for key in manifest.series.iter() {
+        let (_, old_id) = split_id(key.id());
+        let mut key = key.clone();
+        key.set_db(version.database().to_string());
+        let table = key.table().clone();
+        let new_id = index
+            .add_series_key_if_not_exists(key)
+            .context(error::IndexErrSnafu)?;
+        series.insert(old_id, (table, new_id));
+    }
+    let field_id_of = |field_id: FieldId| -> Result<FieldId> {
+        let (column_id, series_id) = split_id(field_id);
+        let (table, new_series_id) = series.get(&series_id).ok_or_else(|| Error::Partition {
+            reason: format!("series {} is not in the manifest", series_id),
+        })?;
+        let new_column_id =
+            columns
+                .get(&(table.clone(), column_id))
+                .ok_or_else(|| Error::Partition {
+                    reason: format!("column {} of {} is not in the manifest", column_id, table),
+                })?;
+        Ok(unite_id(*new_column_id as u64, *new_series_id))
+    };
+
+    let time_range = TimeRange::new(manifest.min_ts, manifest.max_ts);
+    let overlapped = version
+        .levels_info()
+        .iter()
+        .any(|e| e.files.iter().any(|f| f.overlap(&time_range)));
+    let tsf_id = version.tf_id();
+    let database = version.database();
+    let mut metas = vec![];
+    for file in manifest.files.iter() {
+        let (level, is_delta) = if overlapped {
+            (0, true)
+        } else {
+            (file.level, file.is_delta)
+        };
+        let file_id = file_id_next();
+        let path = if is_delta {
+            file_utils::make_delta_file_name(storage.delta_dir(database, tsf_id), file_id)
+        } else {
+            file_utils::make_tsm_file_name(storage.tsm_dir(database, tsf_id), file_id)
+        };
+
+        let reader = TsmReader::open(dir.join(&file.file_name))?;
+        // The blocks are written in the order of the new field ids
+        let mut fields = reader
+            .index_iterator()
+            .map(|idx| field_id_of(idx.field_id()).map(|e| (e, idx)))
+            .collect::<Result<Vec<(FieldId, IndexMeta)>>>()?;
+        fields.sort_by_key(|(field_id, _)| *field_id);
+        let mut writer = TsmWriter::open(&path, file_id, is_delta, 0)?;
+        for (field_id, idx) in fields {
+            for blk_meta in idx.block_iterator() {
+                let block = reader.get_data_block(&blk_meta)?;
+                writer
+                    .write_block(field_id, &block)
+                    .context(error::WriteTsmSnafu)?;
+            }
+        }
+        writer.write_index().context(error::WriteTsmSnafu)?;
+        writer.finish().context(error::WriteTsmSnafu)?;
+        metas.push(CompactMeta::new(
+            file_id,
+            writer.size(),
+            tsf_id,
+            level,
+            writer.min_ts(),
+            writer.max_ts(),
+            is_delta,
+        ));
+    }
+    Ok(metas)
+}
+
+/// Creates the tables or adds the columns not in the index, returns the ids of the
+/// columns in the index of the tables and the ids of the columns in the manifest
+fn import_tables(
+    tables: &[TskvTableSchema],
+    index: &DBIndex,
+) -> Result<HashMap<(String, ColumnId), ColumnId>> {
+    let mut columns = HashMap::new();
+    for table in tables {
+        let schema = match index
+            .get_table_schema(&table.name)
+            .context(error::IndexErrSnafu)?
+        {
+            None => {
+                let mut schema = table.clone();
+                schema.db = index.db_schema().name;
+                index
+                    .create_table(&TableSchema::TsKvTableSchema(schema.clone()))
+                    .context(error::IndexErrSnafu)?;
+                schema
+            }
+            Some(TableSchema::TsKvTableSchema(schema)) => {
+                for column in table.columns() {
+                    if schema.column(&column.name).is_none() {
+                        index
+                            .add_table_column(&table.name, column.clone())
+                            .context(error::IndexErrSnafu)?;
+                    }
+                }
+                index
+                    .get_tskv_table_schema(&table.name)
+                    .context(error::IndexErrSnafu)?
+            }
+            Some(_) => {
+                return Err(Error::Partition {
+                    reason: format!("{} is not a table of tskv", table.name),
+                })
+            }
+        };
+
+        for column in table.columns() {
+            match schema.column(&column.name) {
+                Some(e) if e.column_type == column.column_type => {
+                    columns.insert((table.name.clone(), column.id), e.id);
+                }
+                _ => {
+                    return Err(Error::Partition {
+                        reason: format!(
+                            "column {} of {} is of another type",
+                            column.name, table.name
+                        ),
+                    })
+                }
+            }
+        }
+    }
+    Ok(columns)
+}
+
+#[cfg(test)]
+mod test {
+    use models::schema::{ColumnType, DatabaseSchema, TableColumn};
+    use models::{codec::Encoding, ValueType};
+
+    use super::*;
+    use crate::tsm::{codec::DataBlockEncoding, DataBlock};
+
+    #[test]
+    fn test_import_tables() {
+        let dir = "/tmp/test/partition/1";
+        let _ = fs::remove_dir_all(dir);
+        let index = DBIndex::new(dir, DatabaseSchema::new("db1")).unwrap();
+
+        let source = TskvTableSchema::new(
+            "db0".to_string(),
+            "cpu".to_string(),
+            vec![
+                TableColumn::new_time_column(0),
+                TableColumn::new_tag_column(1, "host".to_string()),
+                TableColumn::new(
+                    2,
+                    "usage".to_string(),
+                    ColumnType::Field(ValueType::Float),
+                    Encoding::Default,
+                ),
+            ],
+        );
+        // The columns are of other ids in the index imported into
+        let target = TskvTableSchema::new(
+            "db1".to_string(),
+            "cpu".to_string(),
+            vec![
+                TableColumn::new_time_column(0),
+                TableColumn::new(
+                    1,
+                    "idle".to_string(),
+                    ColumnType::Field(ValueType::Float),
+                    Encoding::Default,
+                ),
+                TableColumn::new_tag_column(2, "host".to_string()),
+            ],
+        );
+        index
+            .create_table(&TableSchema::TsKvTableSchema(target))
+            .unwrap();
+
+        let columns = import_tables(&[source.clone()], &index).unwrap();
+        let schema = index.get_tskv_table_schema("cpu").unwrap();
+        assert_eq!(columns[&("cpu".to_string(), 1)], 2);
+        assert_eq!(
+            columns[&("cpu".to_string(), 2)],
+            schema.column("usage").unwrap().id
+        );
+        assert_eq!(schema.columns().len(), 4);
+
+        // Imported again with no change
+        assert_eq!(import_tables(&[source], &index).unwrap(), columns);
+    }
+
+    #[test]
+    fn test_export_file() {
+        let dir = "/tmp/test/partition/2";
+        let _ = fs::remove_dir_all(dir);
+        fs::create_dir_all(dir).unwrap();
+
+        let path = file_utils::make_tsm_file_name(dir, 1);
+        let mut writer = TsmWriter::open(&path, 1, false, 0).unwrap();
+        for field_id in [unite_id(1, 1), unite_id(1, 2)] {
+            let block = DataBlock::I64 {
+                ts: vec![1, 2, 3, 4],
+                val: vec![1, 2, 3, 4],
+                enc: DataBlockEncoding::default(),
+            };
+            writer.write_block(field_id, &block).unwrap();
+        }
+        writer.write_index().unwrap();
+        writer.finish().unwrap();
+        let file = ColumnFile::new(1, 1, TimeRange::new(1, 4), writer.size(), false, &path);
+        file.add_tombstone(&[unite_id(1, 2)], &TimeRange::new(1, 4))
+            .unwrap();
+
+        let export_dir = Path::new(dir).join("export");
+        fs::create_dir_all(&export_dir).unwrap();
+        let mut series_ids = HashSet::new();
+        let exported = export_file(
+            &file,
+            &TimeRange::new(2, 3),
+            &export_dir,
+            7,
+            &mut series_ids,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!((exported.min_ts, exported.max_ts), (2, 3));
+        assert_eq!(exported.level, 1);
+        // All the rows of the series 2 are deleted
+        assert_eq!(series_ids, HashSet::from([1]));
+
+        let reader = TsmReader::open(export_dir.join(&exported.file_name)).unwrap();
+        let idx = reader.index_iterator().next().unwrap();
+        assert_eq!(idx.field_id(), unite_id(1, 1));
+        let blk_meta = idx.block_iterator().next().unwrap();
+        assert_eq!(reader.get_data_block(&blk_meta).unwrap().ts(), &[2, 3]);
+
+        assert!(export_file(
+            &file,
+            &TimeRange::new(5, 6),
+            &export_dir,
+            8,
+            &mut series_ids
+        )
+        .unwrap()
+        .is_none());
+    }
+}
@@ -0,0 +1,274 @@
+//! Salvages the readable blocks of a damaged tsm file into a new file, by
+//! `cnosdb repair-file` while the server is stopped.
+//!
+//! The index of the file is parsed as far as it is intact, the blocks out of the
+//! bounds of the file, or whose checksums, rows or time ranges mismatch the index
+//! are dropped and reported with the fields and the time ranges lost. The repaired
+//! file is of the same file id and name, it replaces the damaged file in place so
+//! that the version and the tombstones of the file are kept.
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use models::{FieldId, Timestamp};
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+
+use crate::{
+    byte_utils::{decode_be_u16, decode_be_u64},
+    error::{self, Error, Result},
+    file_system::{file_manager, DmaFile},
+    file_utils,
+    tsm::{
+        BlockMeta, DataBlock, Index, IndexIterator, ReadTsmError, TsmWriter, BLOCK_META_SIZE,
+        FOOTER_SIZE, HEADER_SIZE, INDEX_META_SIZE,
+    },
+    verify,
+};
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepairReport {
+    /// Path of the repaired file
+    pub path: PathBuf,
+    pub blocks: usize,
+    pub blocks_salvaged: usize,
+    pub rows_salvaged: u64,
+    pub lost: Vec<LostRange>,
+    /// Why the index is not parsed to the end, the fields indexed after are lost
+    /// and not known
+    pub index_truncated: Option<String>,
+}
+
+/// Rows of a field in the time range dropped with the block
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LostRange {
+    pub field_id: FieldId,
+    pub min_ts: Timestamp,
+    pub max_ts: Timestamp,
+    pub rows: u32,
+    pub reason: String,
+}
+
+impl RepairReport {
+    pub fn is_lossless(&self) -> bool {
+        self.lost.is_empty() && self.index_truncated.is_none()
+    }
+}
+
+/// Writes the readable blocks of the tsm or delta file at `path` to the file of
+/// the same name in `output_dir`
+pub fn repair_tsm_file(path: &Path, output_dir: &Path) -> Result<RepairReport> {
+    let file_id = file_utils::get_tsm_file_id_by_path(path)?;
+    let is_delta = path.extension().map_or(false, |e| e == "delta");
+    let output = if is_delta {
+        file_utils::make_delta_file_name(output_dir, file_id)
+    } else {
+        file_utils::make_tsm_file_name(output_dir, file_id)
+    };
+    if output == path {
+        return Err(Error::Repair {
+            reason: "the repaired file can not overwrite the damaged file".to_string(),
+        });
+    }
+
+    let file = file_manager::open_file(path)?;
+    let (index, index_offset, index_truncated) = read_index(&file)?;
+    let mut report = RepairReport {
+        path: output.clone(),
+        index_truncated,
+        ..Default::default()
+    };
+
+    fs::create_dir_all(output_dir).context(error::IOSnafu)?;
+    let mut writer = TsmWriter::open(&output, file_id, is_delta, 0)?;
+    let mut buf = vec![];
+    let field_count = index.field_ids().len();
+    for idx in IndexIterator::new(index, field_count, 0) {
+        for blk_meta in idx.block_iterator() {
+            report.blocks += 1;
+            match read_block(&file, &blk_meta, index_offset, &mut buf) {
+                Ok(block) => {
+                    writer
+                        .write_block(blk_meta.field_id(), &block)
+                        .context(error::WriteTsmSnafu)?;
+                    report.blocks_salvaged += 1;
+                    report.rows_salvaged += blk_meta.count() as u64;
+                }
+                Err(reason) => report.lost.push(LostRange {
+                    field_id: blk_meta.field_id(),
+                    min_ts: blk_meta.min_ts(),
+                    max_ts: blk_meta.max_ts(),
+                    rows: blk_meta.count(),
+                    reason,
+                }),
+            }
+        }
+    }
+    writer.write_index().context(error::WriteTsmSnafu)?;
+    writer.finish().context(error::WriteTsmSnafu)?;
+
+    Ok(report)
+}
+
+/// Parses the index of the file until the first entry overrunning the index,
+/// returns the index, its offset in the file and why it is truncated
+fn read_index(file: &DmaFile) -> Result<(Arc<Index>, u64, Option<String>)> {
+    let invalid = |reason: &str| Error::ReadTsm {
+        source: ReadTsmError::Invalid {
+            reason: reason.to_string(),
+        },
+    };
+    let file_len = file.len();
+    if file_len < (HEADER_SIZE + FOOTER_SIZE) as u64 {
+        return Err(invalid("file is shorter than the header and the footer"));
+    }
+    let mut buf = [0_u8; 8];
+    file.read_at(file_len - 8, &mut buf)
+        .context(error::IOSnafu)?;
+    let index_offset = u64::from_be_bytes(buf);
+    let index_end = file_len - FOOTER_SIZE as u64;
+    if index_offset < HEADER_SIZE as u64 || index_offset > index_end {
+        return Err(invalid(
+            "offset of the index is out of the file, the blocks are not known",
+        ));
+    }
+    let mut data = vec![0_u8; (index_end - index_offset) as usize];
+    file.read_at(index_offset, &mut data)
+        .context(error::IOSnafu)?;
+
+    let mut entries = vec![];
+    let mut truncated = None;
+    let mut pos = 0_usize;
+    while pos < data.len() {
+        if pos + INDEX_META_SIZE > data.len() {
+            truncated = Some(format!(
+                "index at {} is shorter than the meta of a field",
+                index_offset + pos as u64
+            ));
+            break;
+        }
+        let field_id = decode_be_u64(&data[pos..pos + 8]);
+        let block_count = decode_be_u16(&data[pos + 9..pos + 11]) as usize;
+        let end = pos + INDEX_META_SIZE + BLOCK_META_SIZE * block_count;
+        if end > data.len() {
+            truncated = Some(format!(
+                "index of field {} at {} overruns the index",
+                field_id,
+                index_offset + pos as u64
+            ));
+            break;
+        }
+        entries.push((field_id, pos as u64));
+        pos = end;
+    }
+    entries.sort_by_key(|(field_id, _)| *field_id);
+    let (field_ids, offsets) = entries.into_iter().unzip();
+
+    Ok((
+        Arc::new(Index::new(data, field_ids, offsets)),
+        index_offset,
+        truncated,
+    ))
+}
+
+fn read_block(
+    file: &DmaFile,
+    blk_meta: &BlockMeta,
+    index_offset: u64,
+    buf: &mut Vec<u8>,
+) -> std::result::Result<DataBlock, String> {
+    verify::check_block_bounds(blk_meta, index_offset)?;
+    let len = blk_meta.size() as usize;
+    buf.resize(len, 0);
+    file.read_at(blk_meta.offset(), &mut buf[..len])
+        .map_err(|e| e.to_string())?;
+    verify::decode_checked_block(blk_meta, &buf[..len])
+}
+
+#[cfg(test)]
+mod test {
+    use std::os::unix::fs::FileExt;
+
+    use super::*;
+    use crate::tsm::TsmReader;
+
+    fn write_file(dir: &str, file_id: u64) -> PathBuf {
+        let path = file_utils::make_tsm_file_name(dir, file_id);
+        let mut writer = TsmWriter::open(&path, file_id, false, 0).unwrap();
+        for field_id in [1, 2] {
+            let block = DataBlock::I64 {
+                ts: vec![1, 2, 3],
+                val: vec![1, 2, 3],
+                enc: Default::default(),
+            };
+            writer.write_block(field_id, &block).unwrap();
+        }
+        writer.write_index().unwrap();
+        writer.finish().unwrap();
+        path
+    }
+
+    fn flip_byte(path: &Path, offset: impl Fn(&fs::File, u64) -> u64) {
+        let f = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .unwrap();
+        let len = f.metadata().unwrap().len();
+        let mut buf = [0_u8; 8];
+        f.read_exact_at(&mut buf, len - 8).unwrap();
+        let pos = offset(&f, u64::from_be_bytes(buf));
+        let mut byte = [0_u8];
+        f.read_exact_at(&mut byte, pos).unwrap();
+        f.write_all_at(&[!byte[0]], pos).unwrap();
+    }
+
+    #[test]
+    fn test_repair_tsm_file() {
+        let dir = "/tmp/test/repair/1";
+        let output_dir = Path::new(dir).join("repaired");
+        let _ = fs::remove_dir_all(dir);
+        fs::create_dir_all(dir).unwrap();
+
+        // The last byte of the values of field 2, ahead of the index, the files
+        // are not opened before
+        let path = write_file(dir, 1);
+        flip_byte(&path, |_, index_offset| index_offset - 1);
+        let report = repair_tsm_file(&path, &output_dir).unwrap();
+        assert_eq!(report.blocks, 2);
+        assert_eq!(report.blocks_salvaged, 1);
+        assert_eq!(report.rows_salvaged, 3);
+        assert_eq!(report.lost.len(), 1);
+        assert_eq!(
+            (report.lost[0].field_id, report.lost[0].min_ts),
+            (2, 1),
+            "{:?}",
+            report.lost
+        );
+        assert!(report.lost[0].reason.contains("checksum"));
+        assert!(report.index_truncated.is_none());
+
+        let reader = TsmReader::open(&report.path).unwrap();
+        let field_ids = reader
+            .index_iterator()
+            .map(|e| e.field_id())
+            .collect::<Vec<_>>();
+        assert_eq!(field_ids, vec![1]);
+
+        // The block count of the index of field 2 overruns the index
+        let path = write_file(dir, 2);
+        flip_byte(&path, |f, _| {
+            let index_end = f.metadata().unwrap().len() - FOOTER_SIZE as u64;
+            index_end - (INDEX_META_SIZE + BLOCK_META_SIZE) as u64 + 9
+        });
+        let report = repair_tsm_file(&path, &output_dir).unwrap();
+        assert_eq!(report.blocks_salvaged, 1);
+        assert!(report.lost.is_empty());
+        assert!(report.index_truncated.unwrap().contains("field 2"));
+
+        let report = repair_tsm_file(&report.path, &output_dir);
+        assert!(report.is_err());
+    }
+}
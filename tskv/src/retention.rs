@@ -0,0 +1,85 @@
+//! Enforces the ttl of the databases, the files all the data of which is older
+//! than the ttl are removed from the versions of the ts families and deleted once
+//! they are not read.
+//!
+//! The ttl is read from the schema of the database on each pass, so a ttl altered
+//! takes effect on the next pass. Only the ttl set explicitly is enforced, the data
+//! of the databases created without one is kept, not expired by the default ttl.
+//! The files holding data both older and newer than the ttl are kept until all of
+//! their data expires.
+use models::{schema::DatabaseSchema, Timestamp};
+
+use crate::{summary::VersionEdit, tseries_family::Version};
+
+/// The data before the cutoff is expired by the ttl of the database, None if the
+/// ttl is not set
+pub fn retention_cutoff(schema: &DatabaseSchema, now: Timestamp) -> Option<Timestamp> {
+    schema
+        .config
+        .ttl()
+        .as_ref()
+        .map(|ttl| now.saturating_sub(ttl.to_nanoseconds()))
+}
+
+/// The edit removing the files of the version expired, None if there is none. The
+/// files being compacted are left to the compaction.
+pub fn expired_files_edit(version: &Version, cutoff: Timestamp) -> Option<VersionEdit> {
+    let mut edit = VersionEdit::new();
+    edit.tsf_id = version.tf_id();
+    for file in version.levels_info().iter().flat_map(|e| e.files.iter()) {
+        if file.time_range().max_ts < cutoff && !file.is_compacting() && !file.is_deleted() {
+            edit.del_file(file.level(), file.file_id(), file.is_delta());
+        }
+    }
+    if edit.del_files.is_empty() {
+        None
+    } else {
+        Some(edit)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use config::get_config;
+    use models::schema::{Duration, DurationUnit};
+
+    use super::*;
+    use crate::{
+        kv_option::Options,
+        tseries_family::{ColumnFile, LevelInfo, TimeRange},
+    };
+
+    #[test]
+    fn test_expired_files_edit() {
+        let opt = Arc::new(Options::from(&get_config("../config/config.toml")));
+        let mut levels = LevelInfo::init_levels("db".to_string(), opt.storage.clone());
+        let files = [(1, TimeRange::new(0, 9)), (2, TimeRange::new(5, 20))];
+        for (file_id, time_range) in files {
+            levels[1].push_column_file(Arc::new(ColumnFile::new(
+                file_id,
+                1,
+                time_range,
+                100,
+                false,
+                format!("/tmp/test/retention/_{:06}.tsm", file_id),
+            )));
+        }
+        let version = Version::new(1, "db".to_string(), opt.storage.clone(), 1, levels, 20);
+
+        let edit = expired_files_edit(&version, 10).unwrap();
+        let deleted = edit.del_files.iter().map(|e| e.file_id).collect::<Vec<_>>();
+        assert_eq!(deleted, vec![1]);
+        assert!(expired_files_edit(&version, 5).is_none());
+
+        let mut schema = DatabaseSchema::new("db");
+        assert_eq!(retention_cutoff(&schema, 0), None);
+        schema.config.with_ttl(Duration {
+            time_num: 1,
+            unit: DurationUnit::Hour,
+        });
+        let hour = 3600 * 1_000_000_000;
+        assert_eq!(retention_cutoff(&schema, 3 * hour), Some(2 * hour));
+    }
+}
@@ -0,0 +1,168 @@
+//! Removes the series all the data of which is deleted from the index in the
+//! background, so that the cardinality and the size of the index do not grow
+//! forever.
+//!
+//! A series is alive if any memcache of the database holds rows of it, or any
+//! field of it is in a file of the current versions and not all deleted by the
+//! tombstones of the file. The series found dead are only removed if they are
+//! still dead on the next pass and on a check under the write barrier of the
+//! database, so that the series written to in the meantime are kept.
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use models::{utils::split_id, SeriesId};
+use parking_lot::{Mutex, RwLock};
+use snafu::ResultExt;
+
+use crate::{
+    database::Database,
+    error::{self, Result},
+    memcache::MemCache,
+    tseries_family::TseriesFamily,
+    tsm::TsmReader,
+    verify, ColumnFileId,
+};
+
+#[derive(Debug, Default)]
+pub struct SeriesGc {
+    /// Series of the databases found dead on the last pass
+    candidates: Mutex<HashMap<String, HashSet<SeriesId>>>,
+}
+
+impl SeriesGc {
+    /// Removes the series of the database dead since the last pass from the index,
+    /// returns the number of the series removed
+    pub fn collect(&self, database: &str, db: &RwLock<Database>) -> Result<usize> {
+        let (index, barrier) = {
+            let db = db.read();
+            (db.get_index(), db.write_barrier())
+        };
+
+        let mut scanned = HashSet::new();
+        let live = live_series(&ts_families(db), &mut scanned)?;
+        let dead = index
+            .series_keys()
+            .context(error::IndexErrSnafu)?
+            .iter()
+            .map(|e| e.id())
+            .filter(|e| !live.contains(&split_id(*e).1))
+            .collect::<HashSet<_>>();
+
+        let mut candidates = self.candidates.lock();
+        let last = candidates.remove(database).unwrap_or_default();
+        let (mut expired, dead): (HashSet<_>, HashSet<_>) =
+            dead.into_iter().partition(|e| last.contains(e));
+        if !expired.is_empty() {
+            // No write is resolving its series into the memcache, the ones written
+            // to since the scan are in the memcaches or the files flushed since
+            let _barrier = barrier.blocking_write();
+            let live = live_series(&ts_families(db), &mut scanned)?;
+            expired.retain(|e| !live.contains(&split_id(*e).1));
+            for sid in expired.iter() {
+                index.del_series_info(*sid).context(error::IndexErrSnafu)?;
+            }
+        }
+        if !expired.is_empty() {
+            index.flush().context(error::IndexErrSnafu)?;
+        }
+        candidates.insert(database.to_string(), dead);
+
+        Ok(expired.len())
+    }
+
+    /// Forgets the series found dead of the database dropped
+    pub fn remove_database(&self, database: &str) {
+        self.candidates.lock().remove(database);
+    }
+}
+
+fn ts_families(db: &RwLock<Database>) -> Vec<Arc<RwLock<TseriesFamily>>> {
+    db.read().ts_families().values().cloned().collect()
+}
+
+/// The low 40 bits of the ids of the series alive, which the field ids are of.
+/// The files in `scanned` are skipped, the ones read are added to it.
+fn live_series(
+    ts_families: &[Arc<RwLock<TseriesFamily>>],
+    scanned: &mut HashSet<ColumnFileId>,
+) -> Result<HashSet<u64>> {
+    let mut live = HashSet::new();
+    for tsf in ts_families {
+        let (super_version, mut caches) = {
+            let tsf = tsf.read();
+            let mut caches = tsf.im_cache().clone();
+            caches.push(tsf.cache().clone());
+            (tsf.super_version(), caches)
+        };
+        caches.push(super_version.caches.mut_cache.clone());
+        caches.extend(super_version.caches.immut_cache.iter().cloned());
+        for cache in caches {
+            add_cached_series(&cache.read(), &mut live);
+        }
+
+        for level in super_version.version.levels_info() {
+            for file in level.files.iter() {
+                if !scanned.insert(file.file_id()) {
+                    continue;
+                }
+                let reader = TsmReader::open(file.file_path())?;
+                for idx in reader.index_iterator() {
+                    if !verify::is_deleted(&reader, &idx) {
+                        live.insert(split_id(idx.field_id()).1);
+                    }
+                }
+            }
+        }
+    }
+    Ok(live)
+}
+
+fn add_cached_series(cache: &MemCache, live: &mut HashSet<u64>) {
+    for (sid, data) in cache.read_series_data() {
+        if data.read().groups.iter().any(|e| !e.rows.is_empty()) {
+            live.insert(split_id(sid).1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use models::{schema::TskvTableSchema, utils::unite_id};
+
+    use super::*;
+    use crate::{
+        memcache::{RowData, RowGroup},
+        tseries_family::TimeRange,
+    };
+
+    fn write_row(cache: &MemCache, sid: SeriesId) {
+        let row = RowData {
+            ts: 1,
+            fields: vec![],
+        };
+        cache.write_group(
+            sid,
+            1,
+            RowGroup {
+                schema: TskvTableSchema::default(),
+                range: TimeRange::new(1, 1),
+                size: row.size(),
+                rows: vec![row],
+            },
+        );
+    }
+
+    #[test]
+    fn test_add_cached_series() {
+        let cache = MemCache::new(0, 1 << 20, 0);
+        write_row(&cache, unite_id(1, 1));
+        write_row(&cache, unite_id(2, 2));
+        cache.delete_series(&[unite_id(2, 2)], &TimeRange::all());
+
+        let mut live = HashSet::new();
+        add_cached_series(&cache, &mut live);
+        assert_eq!(live, HashSet::from([1]));
+    }
+}
@@ -402,7 +402,7 @@ impl Summary {
                 let vs = self.version_set.read();
                 let dbs = vs.get_all_db();
                 for (name, db) in dbs {
-                    let (mut tsf, mut tmp_files) = db.read().version_edit(self.ctx.last_seq());
+                    let (mut tsf, mut tmp_files) = db.read().version_edit();
                     edits.append(&mut tsf);
                     files.append(&mut tmp_files);
                 }
@@ -26,6 +26,7 @@ use utils::BloomFilter;
 use crate::file_system::file_manager;
 use crate::{
     compaction::{CompactReq, FlushReq, LevelCompactionPicker, Picker},
+    context::GlobalContext,
     error::{Error, Result},
     file_system::{DmaFile, FileCursor},
     file_utils::{make_delta_file_name, make_tsm_file_name},
@@ -484,7 +485,8 @@ impl Version {
             ts_family_id: self.ts_family_id,
             database: self.database.clone(),
             storage_opt: self.storage_opt.clone(),
-            last_seq: last_seq.unwrap_or(self.last_seq),
+            // The seq persisted never goes back, e.g. the edits of the compactions
+            last_seq: last_seq.map_or(self.last_seq, |e| e.max(self.last_seq)),
             max_level_ts: self.max_level_ts,
             levels_info: new_levels,
         };
@@ -521,6 +523,19 @@ impl Version {
         &self.levels_info
     }
 
+    /// Files a read of a series may open at most, each file of the level 0 and
+    /// each of the other levels with files
+    pub fn read_amplification(&self) -> usize {
+        let files = |lvl: &LevelInfo| lvl.files.iter().filter(|e| !e.is_deleted()).count();
+        self.levels_info
+            .iter()
+            .map(|lvl| match lvl.level {
+                0 => files(lvl),
+                _ => files(lvl).min(1),
+            })
+            .sum()
+    }
+
     pub fn storage_opt(&self) -> Arc<StorageOptions> {
         self.storage_opt.clone()
     }
@@ -561,6 +576,8 @@ pub struct SuperVersion {
     pub caches: CacheGroup,
     pub version: Arc<Version>,
     pub version_number: u64,
+    /// Reads of the ts family since it is compacted, shared by the super versions
+    reads: Arc<AtomicU64>,
 }
 
 impl SuperVersion {
@@ -570,6 +587,7 @@ impl SuperVersion {
         caches: CacheGroup,
         version: Arc<Version>,
         version_number: u64,
+        reads: Arc<AtomicU64>,
     ) -> Self {
         Self {
             ts_family_id,
@@ -577,8 +595,49 @@ impl SuperVersion {
             caches,
             version,
             version_number,
+            reads,
         }
     }
+
+    /// Counts a query of the version, the ts families queried more are compacted
+    /// first
+    pub fn record_read(&self) {
+        self.reads.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The time ranges of the files and the caches not flushed, which are not
+    /// empty
+    fn data_time_ranges(&self) -> impl Iterator<Item = TimeRange> + '_ {
+        let files = self
+            .version
+            .levels_info
+            .iter()
+            .flat_map(|e| e.files.iter())
+            .filter(|e| !e.is_deleted())
+            .map(|e| *e.time_range());
+        let caches = self
+            .caches
+            .immut_cache
+            .iter()
+            .filter(|e| !e.read().flushed)
+            .chain(std::iter::once(&self.caches.mut_cache))
+            .filter_map(|e| e.read().time_range());
+        files.chain(caches)
+    }
+
+    /// The time range of the data of the version, None if there is no data
+    pub fn time_range(&self) -> Option<TimeRange> {
+        self.data_time_ranges().reduce(|mut a, b| {
+            a.merge(&b);
+            a
+        })
+    }
+
+    /// Whether any file or cache not flushed of the version has data in the time
+    /// range
+    pub fn overlaps(&self, time_range: &TimeRange) -> bool {
+        self.data_time_ranges().any(|e| e.overlaps(time_range))
+    }
 }
 
 #[derive(Debug)]
@@ -597,6 +656,7 @@ pub struct TseriesFamily {
     immut_ts_min: AtomicI64,
     mut_ts_max: AtomicI64,
     flush_task_sender: UnboundedSender<FlushReq>,
+    reads: Arc<AtomicU64>,
 }
 
 impl TseriesFamily {
@@ -613,6 +673,7 @@ impl TseriesFamily {
         let mm = Arc::new(RwLock::new(cache));
         let seq = version.last_seq;
         let max_level_ts = version.max_level_ts;
+        let reads = Arc::new(AtomicU64::new(0));
 
         Self {
             tf_id,
@@ -629,6 +690,7 @@ impl TseriesFamily {
                 },
                 version.clone(),
                 0,
+                reads.clone(),
             )),
             super_version_id: AtomicU64::new(0),
             version,
@@ -638,6 +700,7 @@ impl TseriesFamily {
             immut_ts_min: AtomicI64::new(max_level_ts),
             mut_ts_max: AtomicI64::new(i64::MIN),
             flush_task_sender,
+            reads,
         }
     }
 
@@ -658,6 +721,7 @@ impl TseriesFamily {
             },
             version,
             self.super_version_id.load(Ordering::SeqCst),
+            self.reads.clone(),
         ))
     }
 
@@ -674,7 +738,7 @@ impl TseriesFamily {
         self.immut_cache.push(self.mut_cache.clone());
         self.mut_cache = Arc::from(RwLock::new(MemCache::new(
             self.tf_id,
-            self.cache_opt.max_buffer_size,
+            self.cache_opt.max_buffer_size(),
             self.seq_no,
         )));
         self.new_super_version(self.version.clone());
@@ -705,7 +769,7 @@ impl TseriesFamily {
             req_mem.push((self.tf_id, i.clone()));
         }
 
-        if req_mem.len() < self.cache_opt.max_immutable_number as usize {
+        if req_mem.len() < self.cache_opt.max_immutable_number() as usize {
             return;
         }
 
@@ -719,6 +783,18 @@ impl TseriesFamily {
             .expect("error send flush req to kvcore");
     }
 
+    /// The writes of the ts family in the wal at or before the seq are persisted,
+    /// None if the caches are empty, none of the writes is to be persisted
+    pub fn wal_checkpoint(&self) -> Option<u64> {
+        let is_empty = self.mut_cache.read().is_empty()
+            && self.immut_cache.iter().all(|e| e.read().is_empty());
+        if is_empty {
+            None
+        } else {
+            Some(self.version.last_seq)
+        }
+    }
+
     pub fn put_points(&self, seq: u64, points: HashMap<(SeriesId, SchemaId), RowGroup>) {
         for ((sid, schema_id), group) in points {
             let mem = self.super_version.caches.mut_cache.read();
@@ -735,15 +811,52 @@ impl TseriesFamily {
     //     );
     // }
 
-    pub fn check_to_flush(&mut self) {
-        if self.super_version.caches.mut_cache.read().is_full() {
-            info!("mut_cache full,switch to immutable");
+    /// Switches the mutable cache to immutable if it is full, the immutable caches
+    /// are counted as pending flush
+    pub fn check_to_flush(&mut self, ctx: &Arc<GlobalContext>) {
+        if !self.super_version.caches.mut_cache.read().is_full() {
+            return;
+        }
+        info!("mut_cache full,switch to immutable");
+        self.switch_to_immutable();
+        for cache in self.immut_cache.iter() {
+            cache.write().mark_flush_pending(ctx);
+        }
+        if self.immut_cache.len() >= self.cache_opt.max_immutable_number() as usize {
+            self.wrap_flush_req();
+        }
+    }
+
+    /// Switches the mutable cache to immutable, returns the immutable caches neither
+    /// flushed nor flushing, they are marked flushing and counted as pending flush
+    pub fn take_unflushed_caches(
+        &mut self,
+        ctx: &Arc<GlobalContext>,
+    ) -> Vec<(TseriesFamilyId, Arc<RwLock<MemCache>>)> {
+        if !self.mut_cache.read().is_empty() {
             self.switch_to_immutable();
-            if self.immut_cache.len() >= self.cache_opt.max_immutable_number as usize {
-                self.wrap_flush_req();
+        }
+        self.take_immutable_caches(ctx)
+    }
+
+    /// Same as `take_unflushed_caches`, the mutable cache is kept
+    pub fn take_immutable_caches(
+        &mut self,
+        ctx: &Arc<GlobalContext>,
+    ) -> Vec<(TseriesFamilyId, Arc<RwLock<MemCache>>)> {
+        let mut mems = vec![];
+        for cache in self.immut_cache.iter() {
+            let mut c = cache.write();
+            if c.flushed || c.flushing {
+                continue;
             }
+            c.flushing = true;
+            c.mark_flush_pending(ctx);
+            mems.push((self.tf_id, cache.clone()));
         }
+        mems
     }
+
     pub fn delete_columns(&self, field_ids: &[FieldId]) {
         self.mut_cache.read().delete_columns(field_ids);
         for memcache in self.immut_cache.iter() {
@@ -762,6 +875,18 @@ impl TseriesFamily {
         self.compact_picker.pick_compaction(self.version.clone())
     }
 
+    /// Priority of compacting the ts family, the read amplification weighted by
+    /// the queries since it is compacted
+    pub fn compaction_score(&self) -> f64 {
+        let reads = self.reads.load(Ordering::Relaxed);
+        self.version.read_amplification() as f64 * (1 + reads) as f64
+    }
+
+    /// Resets the queries counted once the ts family starts compacting
+    pub fn reset_reads(&self) {
+        self.reads.store(0, Ordering::Relaxed);
+    }
+
     pub fn tf_id(&self) -> TseriesFamilyId {
         self.tf_id
     }
@@ -778,6 +903,38 @@ impl TseriesFamily {
         &self.immut_cache
     }
 
+    /// Bytes of the mutable and immutable memcaches
+    pub fn cache_size(&self) -> u64 {
+        self.immut_cache
+            .iter()
+            .fold(self.mut_cache.read().cache_size(), |size, c| {
+                size + c.read().cache_size()
+            })
+    }
+
+    /// The immutable caches taken by a flush not done yet
+    pub fn flushing_caches(&self) -> Vec<Arc<RwLock<MemCache>>> {
+        self.immut_cache
+            .iter()
+            .filter(|c| {
+                let c = c.read();
+                c.flushing && !c.flushed
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Same as `cache_size`, the caches flushing or flushed are not counted
+    pub fn unflushed_cache_size(&self) -> u64 {
+        self.immut_cache
+            .iter()
+            .map(|c| c.read())
+            .filter(|c| !c.flushing && !c.flushed)
+            .fold(self.mut_cache.read().cache_size(), |size, c| {
+                size + c.cache_size()
+            })
+    }
+
     pub fn super_version(&self) -> Arc<SuperVersion> {
         self.super_version.clone()
     }
@@ -884,6 +1041,8 @@ mod test {
                 LevelInfo::init(database, 4, opt.storage.clone()),
             ],
         };
+        // A file of the level 1 and one of the files of the level 2
+        assert_eq!(version.read_amplification(), 2);
         let mut version_edits = Vec::new();
         let mut ve = VersionEdit::new();
         #[rustfmt::skip]
@@ -1074,9 +1233,9 @@ mod test {
             rows: vec![RowData {
                 ts: 10,
                 fields: vec![
-                    Some(FieldVal::Integer(11)),
-                    Some(FieldVal::Integer(12)),
-                    Some(FieldVal::Integer(13)),
+                    (0, FieldVal::Integer(11)),
+                    (1, FieldVal::Integer(12)),
+                    (2, FieldVal::Integer(13)),
                 ],
             }],
             size: size_of::<RowGroup>() + 3 * size_of::<u32>() + size_of::<Option<FieldVal>>() + 8,
@@ -1153,9 +1312,9 @@ mod test {
             rows: vec![RowData {
                 ts: 10,
                 fields: vec![
-                    Some(FieldVal::Integer(11)),
-                    Some(FieldVal::Integer(12)),
-                    Some(FieldVal::Integer(13)),
+                    (0, FieldVal::Integer(11)),
+                    (1, FieldVal::Integer(12)),
+                    (2, FieldVal::Integer(13)),
                 ],
             }],
             size: size_of::<RowGroup>() + 3 * size_of::<u32>() + size_of::<Option<FieldVal>>() + 8,
@@ -472,6 +472,43 @@ impl DataBlock {
         self.exclude_by_index(min_idx, max_idx);
     }
 
+    /// Keep (ts, val) in this `DataBlock` where ts is in the time range, remove the others
+    pub fn retain(&mut self, time_range: &TimeRange) {
+        let ts_sli = self.ts();
+        let min_idx = ts_sli.partition_point(|ts| *ts < time_range.min_ts);
+        let max_idx = ts_sli.partition_point(|ts| *ts <= time_range.max_ts);
+        if min_idx == 0 && max_idx == ts_sli.len() {
+            return;
+        }
+
+        fn retain_range<T>(v: &mut Vec<T>, min_idx: usize, max_idx: usize) {
+            v.truncate(max_idx);
+            v.drain(..min_idx.min(max_idx));
+        }
+        match self {
+            DataBlock::U64 { ts, val, .. } => {
+                retain_range(ts, min_idx, max_idx);
+                retain_range(val, min_idx, max_idx);
+            }
+            DataBlock::I64 { ts, val, .. } => {
+                retain_range(ts, min_idx, max_idx);
+                retain_range(val, min_idx, max_idx);
+            }
+            DataBlock::Str { ts, val, .. } => {
+                retain_range(ts, min_idx, max_idx);
+                retain_range(val, min_idx, max_idx);
+            }
+            DataBlock::F64 { ts, val, .. } => {
+                retain_range(ts, min_idx, max_idx);
+                retain_range(val, min_idx, max_idx);
+            }
+            DataBlock::Bool { ts, val, .. } => {
+                retain_range(ts, min_idx, max_idx);
+                retain_range(val, min_idx, max_idx);
+            }
+        }
+    }
+
     /// Extract `DataBlock`s to `DataType`s,
     /// returns the minimum timestamp in a series of `DataBlock`s
     fn next_min(
@@ -686,6 +723,27 @@ pub mod test {
         ]);
     }
 
+    #[test]
+    fn test_data_block_retain() {
+        #[rustfmt::skip]
+        let mut blk = DataBlock::U64 {
+            ts: vec![0, 1, 2, 3, 4, 5],
+            val: vec![10, 11, 12, 13, 14, 15],
+            enc: DataBlockEncoding::default()
+        };
+        blk.retain(&TimeRange::from((2, 3)));
+        assert_eq!(
+            blk,
+            DataBlock::U64 {
+                ts: vec![2, 3],
+                val: vec![12, 13],
+                enc: DataBlockEncoding::default()
+            }
+        );
+        blk.retain(&TimeRange::from((5, 9)));
+        assert!(blk.is_empty());
+    }
+
     #[test]
     fn test_data_block_exclude_1() {
         #[rustfmt::skip]
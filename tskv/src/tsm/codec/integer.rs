@@ -7,7 +7,7 @@ use crate::tsm::codec::timestamp::{
 use crate::tsm::codec::Encoding;
 use integer_encoding::*;
 
-use super::simple8b;
+use super::{simple8b, RleRun};
 
 // note: encode/decode adapted from influxdb_iox
 // https://github.com/influxdata/influxdb_iox/tree/main/influxdb_tsm/src/encoders
@@ -223,6 +223,29 @@ fn decode_rle(src: &[u8], dst: &mut Vec<i64>) -> Result<(), Box<dyn Error + Send
     Ok(())
 }
 
+/// Returns the run of the integers encoded by RLE without decoding them, None if
+/// the integers are not encoded by RLE.
+pub fn i64_rle_run(src: &[u8]) -> Option<RleRun> {
+    if src.len() < 2 || src[0] != Encoding::Delta as u8 || src[1] >> 4 != DeltaEncoding::Rle as u8 {
+        return None;
+    }
+    let src = &src[2..];
+    if src.len() < 8 {
+        return None;
+    }
+
+    let first = zig_zag_decode(u64::from_be_bytes(src[0..8].try_into().ok()?));
+    let (delta, n) = u64::decode_var(&src[8..])?;
+    // The first value is not counted in the repeats of the delta
+    let (count, _n) = usize::decode_var(&src[8 + n..])?;
+
+    Some(RleRun {
+        first,
+        delta: zig_zag_decode(delta),
+        count: count + 1,
+    })
+}
+
 fn decode_simple8b(src: &[u8], dst: &mut Vec<i64>) -> Result<(), Box<dyn Error + Send + Sync>> {
     if src.len() < 8 {
         return Err(From::from("not enough data to decode packed integer."));
@@ -462,4 +485,29 @@ mod tests {
         assert_eq!(dec.len(), values.len());
         assert_eq!(dec, values);
     }
+
+    #[test]
+    fn rle_run() {
+        let src: Vec<i64> = vec![100, 50, 0, -50, -100, -150];
+        let mut dst = vec![];
+        i64_zigzag_simple8b_encode(&src, &mut dst).expect("failed to encode");
+
+        let run = i64_rle_run(&dst).expect("not encoded by RLE");
+        assert_eq!(run.first, 100);
+        assert_eq!(run.delta, -50);
+        assert_eq!(run.count, 6);
+        assert_eq!(run.bounds(0..6), Some((-150, 100)));
+        assert_eq!(run.bounds(1..3), Some((0, 50)));
+        assert_eq!(run.bounds(4..10), Some((-150, -100)));
+        assert_eq!(run.bounds(3..3), None);
+
+        let src: Vec<i64> = vec![123; 8];
+        i64_zigzag_simple8b_encode(&src, &mut dst).expect("failed to encode");
+        let run = i64_rle_run(&dst).expect("not encoded by RLE");
+        assert_eq!(run.bounds(0..8), Some((123, 123)));
+
+        let src: Vec<i64> = vec![1, 3, 2];
+        i64_zigzag_simple8b_encode(&src, &mut dst).expect("failed to encode");
+        assert!(i64_rle_run(&dst).is_none());
+    }
 }
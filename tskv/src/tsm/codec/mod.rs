@@ -8,7 +8,10 @@ mod timestamp;
 mod unsigned;
 
 pub use instance::*;
+pub use integer::i64_rle_run;
 use models::codec::Encoding;
+use std::ops::Range;
+pub use timestamp::ts_rle_run;
 
 /// Max number of bytes needed to store a varint-encoded 32-bit integer.
 const MAX_VAR_INT_32: usize = 5;
@@ -16,6 +19,67 @@ const MAX_VAR_INT_32: usize = 5;
 /// Max number of bytes needed to store a varint-encoded 64-bit integer.
 const MAX_VAR_INT_64: usize = 10;
 
+/// The values `first, first + delta, ..., first + delta * (count - 1)` of a block
+/// encoded by RLE, which are known without decoding the block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RleRun {
+    pub first: i64,
+    pub delta: i64,
+    pub count: usize,
+}
+
+impl RleRun {
+    fn value(&self, index: usize) -> i128 {
+        self.first as i128 + self.delta as i128 * index as i128
+    }
+
+    /// Returns the min and the max of the values of the indices, None if the
+    /// indices are empty or the values overflow.
+    pub fn bounds(&self, indices: Range<usize>) -> Option<(i64, i64)> {
+        if indices.start >= indices.end.min(self.count) {
+            return None;
+        }
+        let low = self.value(indices.start);
+        let high = self.value(indices.end.min(self.count) - 1);
+        let (min, max) = if low <= high {
+            (low, high)
+        } else {
+            (high, low)
+        };
+        Some((i64::try_from(min).ok()?, i64::try_from(max).ok()?))
+    }
+
+    /// Returns the indices of the values in [min, max], the values must be
+    /// ascending, e.g. timestamps.
+    pub fn position_range(&self, min: i64, max: i64) -> Range<usize> {
+        debug_assert!(self.delta >= 0);
+        // The number of the values less than v, or not greater than v if inclusive
+        let position = |v: i64, inclusive: bool| -> usize {
+            let distance = v as i128 - self.first as i128;
+            let n = if distance < 0 {
+                0
+            } else if self.delta == 0 {
+                if distance > 0 || inclusive {
+                    self.count as i128
+                } else {
+                    0
+                }
+            } else {
+                let delta = self.delta as i128;
+                if inclusive {
+                    distance / delta + 1
+                } else {
+                    (distance + delta - 1) / delta
+                }
+            };
+            n.min(self.count as i128) as usize
+        };
+        let start = position(min, false);
+        let end = position(max, true).max(start);
+        start..end
+    }
+}
+
 /// Combined encoding ids with timestamp-block-encoding (high 4 bit)
 /// and values-block-encoding (low 4 bit)
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
@@ -83,7 +83,7 @@ pub fn str_snappy_encode(
     let actual_compressed_size = encoder.compress(data, compressed_data)?;
 
     dst.truncate(HEADER_LEN + actual_compressed_size);
-    dst.insert(0, Encoding::Gorilla as u8);
+    dst.insert(0, Encoding::Snappy as u8);
 
     Ok(())
 }
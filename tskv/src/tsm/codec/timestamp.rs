@@ -6,7 +6,7 @@ use crate::tsm::codec::Encoding;
 use integer_encoding::*;
 use q_compress::{auto_compress, auto_decompress, DEFAULT_COMPRESSION_LEVEL};
 
-use super::simple8b;
+use super::{simple8b, RleRun};
 
 // note: encode/decode adapted from influxdb_iox
 // https://github.com/influxdata/influxdb_iox/tree/main/influxdb_tsm/src/encoders
@@ -265,6 +265,30 @@ fn decode_rle(src: &[u8], dst: &mut Vec<i64>) -> Result<(), Box<dyn Error + Send
     Ok(())
 }
 
+/// Returns the run of the timestamps encoded by RLE without decoding them, None
+/// if the timestamps are not encoded by RLE.
+pub fn ts_rle_run(src: &[u8]) -> Option<RleRun> {
+    if src.len() < 2 || src[0] != Encoding::Delta as u8 || src[1] >> 4 != DeltaEncoding::Rle as u8 {
+        return None;
+    }
+    let src = &src[1..];
+    if src.len() < 9 {
+        return None;
+    }
+
+    let scaler = 10_u64.pow((src[0] & 0b0000_1111) as u32);
+    let first = i64::from_be_bytes(src[1..9].try_into().ok()?);
+    let (delta, n) = u64::decode_var(&src[9..])?;
+    let (count, _n) = usize::decode_var(&src[9 + n..])?;
+    let delta = i64::try_from(delta.checked_mul(scaler)?).ok()?;
+
+    Some(RleRun {
+        first,
+        delta,
+        count,
+    })
+}
+
 fn decode_simple8b(src: &[u8], dst: &mut Vec<i64>) -> Result<(), Box<dyn Error + Send + Sync>> {
     if src.len() < 9 {
         return Err(From::from("not enough data to decode packed timestamp"));
@@ -487,4 +511,26 @@ mod tests {
             assert_eq!(got, exp, "{}", test.name);
         }
     }
+
+    #[test]
+    fn rle_run() {
+        let src: Vec<i64> = (0..100).map(|i| 1_000_000 + i * 10_000).collect();
+        let mut dst = vec![];
+        ts_zigzag_simple8b_encode(&src, &mut dst).expect("failed to encode");
+
+        let run = ts_rle_run(&dst).expect("not encoded by RLE");
+        assert_eq!(run.first, 1_000_000);
+        assert_eq!(run.delta, 10_000);
+        assert_eq!(run.count, 100);
+        assert_eq!(run.position_range(i64::MIN, i64::MAX), 0..100);
+        assert_eq!(run.position_range(1_010_000, 1_030_000), 1..4);
+        assert_eq!(run.position_range(1_005_000, 1_035_000), 1..4);
+        assert_eq!(run.position_range(0, 999_999), 0..0);
+        assert_eq!(run.position_range(3_000_000, 4_000_000), 100..100);
+        assert_eq!(run.position_range(1_001_000, 1_009_000), 1..1);
+
+        let src: Vec<i64> = vec![-1000, 0, 213123421];
+        ts_zigzag_simple8b_encode(&src, &mut dst).expect("failed to encode");
+        assert!(ts_rle_run(&dst).is_none());
+    }
 }
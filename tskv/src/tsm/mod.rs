@@ -14,12 +14,21 @@ pub use writer::*;
 // MAX_BLOCK_VALUES is the maximum number of values a TSM block can store.
 pub(crate) const MAX_BLOCK_VALUES: u32 = 1000;
 
-const HEADER_SIZE: usize = 5;
-const INDEX_META_SIZE: usize = 11;
-const BLOCK_META_SIZE: usize = 44;
+const TSM_MAGIC: u32 = 0x01346613;
+/// Format version of the tsm files written, in the header:
+/// - 1: the strings compressed by snappy are tagged GORILLA
+/// - 2: the strings compressed by snappy are tagged SNAPPY
+pub const TSM_VERSION: u8 = 2;
+/// Oldest format version of the tsm files read, the files of the older versions
+/// are rewritten by `cnosdb upgrade-files`
+pub const TSM_MIN_VERSION: u8 = 1;
+
+pub(crate) const HEADER_SIZE: usize = 5;
+pub(crate) const INDEX_META_SIZE: usize = 11;
+pub(crate) const BLOCK_META_SIZE: usize = 44;
 const BLOOM_FILTER_SIZE: usize = 64;
 const BLOOM_FILTER_BITS: u64 = 512; // 64 * 8
-const FOOTER_SIZE: usize = BLOOM_FILTER_SIZE + 8; // 72
+pub(crate) const FOOTER_SIZE: usize = BLOOM_FILTER_SIZE + 8; // 72
 
 pub trait BlockReader {
     fn decode(&mut self, block: &BlockMeta) -> crate::error::Result<DataBlock>;
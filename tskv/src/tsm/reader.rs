@@ -5,7 +5,7 @@ use std::{
     sync::Arc,
 };
 
-use models::{utils as model_utils, FieldId, Timestamp, ValueType};
+use models::{codec::Encoding, utils as model_utils, FieldId, Timestamp, ValueType};
 use parking_lot::RwLock;
 use snafu::{ResultExt, Snafu};
 
@@ -18,13 +18,13 @@ use crate::{
     tseries_family::TimeRange,
     tsm::{
         codec::{
-            get_bool_codec, get_encoding, get_f64_codec, get_i64_codec, get_str_codec,
+            self, get_bool_codec, get_encoding, get_f64_codec, get_i64_codec, get_str_codec,
             get_ts_codec, get_u64_codec, DataBlockEncoding,
         },
         get_data_block_meta_unchecked, get_index_meta_unchecked,
         tombstone::TsmTombstone,
-        BlockMeta, DataBlock, Index, IndexMeta, BLOCK_META_SIZE, FOOTER_SIZE, INDEX_META_SIZE,
-        MAX_BLOCK_VALUES,
+        BlockMeta, DataBlock, Index, IndexMeta, BLOCK_META_SIZE, FOOTER_SIZE, HEADER_SIZE,
+        INDEX_META_SIZE, MAX_BLOCK_VALUES, TSM_MAGIC, TSM_MIN_VERSION, TSM_VERSION,
     },
 };
 
@@ -43,6 +43,14 @@ pub enum ReadTsmError {
 
     #[snafu(display("TSM file is invalid: {}", reason))]
     Invalid { reason: String },
+
+    #[snafu(display(
+        "TSM file of version {} is not supported, only versions {} to {} are",
+        version,
+        TSM_MIN_VERSION,
+        TSM_VERSION
+    ))]
+    UnsupportedVersion { version: u8 },
 }
 
 impl From<ReadTsmError> for Error {
@@ -424,9 +432,32 @@ impl Iterator for BlockMetaIterator {
     }
 }
 
+/// Reads the format version in the header of the tsm file, the versions not
+/// supported are rejected
+pub fn read_tsm_version(reader: &DmaFile) -> ReadTsmResult<u8> {
+    if reader.len() < HEADER_SIZE as u64 {
+        return Err(ReadTsmError::Invalid {
+            reason: "file is shorter than the header".to_string(),
+        });
+    }
+    let mut buf = [0_u8; HEADER_SIZE];
+    reader.read_at(0, &mut buf).context(IOSnafu)?;
+    if decode_be_u32(&buf[..4]) != TSM_MAGIC {
+        return Err(ReadTsmError::Invalid {
+            reason: "magic number mismatched".to_string(),
+        });
+    }
+    let version = buf[4];
+    if !(TSM_MIN_VERSION..=TSM_VERSION).contains(&version) {
+        return Err(ReadTsmError::UnsupportedVersion { version });
+    }
+    Ok(version)
+}
+
 #[derive(Clone)]
 pub struct TsmReader {
     reader: Arc<DmaFile>,
+    version: u8,
     index_reader: Arc<IndexReader>,
     tombstone: Arc<RwLock<TsmTombstone>>,
 }
@@ -436,16 +467,23 @@ impl TsmReader {
         let path = tsm_path.as_ref().to_path_buf();
         let tsm_id = file_utils::get_tsm_file_id_by_path(&path)?;
         let tsm = Arc::new(file_manager::open_file(tsm_path)?);
+        let version = read_tsm_version(&tsm).context(error::ReadTsmSnafu)?;
         let tsm_idx = IndexReader::open(tsm.clone())?;
         let tombstone_path = path.parent().unwrap_or_else(|| Path::new("/"));
         let tombstone = TsmTombstone::new(tombstone_path, tsm_id)?;
         Ok(Self {
             reader: tsm,
+            version,
             index_reader: Arc::new(tsm_idx),
             tombstone: Arc::new(RwLock::new(tombstone)),
         })
     }
 
+    /// Format version of the file
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
     pub fn index_iterator(&self) -> IndexIterator {
         self.index_reader.iter()
     }
@@ -471,6 +509,38 @@ impl TsmReader {
         Ok(blk)
     }
 
+    /// Returns a DataBlock without tombstone of the rows in the time range, the
+    /// values are only decoded if any timestamp is in the time range and the value
+    /// filter is true for the bounds of the values, otherwise returns None.
+    pub fn get_data_block_in_range(
+        &self,
+        block_meta: &BlockMeta,
+        time_range: &TimeRange,
+        value_filter: impl Fn(&ValueBounds) -> bool,
+    ) -> ReadTsmResult<Option<DataBlock>> {
+        if !time_range.overlaps(&TimeRange::new(block_meta.min_ts(), block_meta.max_ts())) {
+            return Ok(None);
+        }
+        let mut buf = vec![0_u8; block_meta.size() as usize];
+        self.reader
+            .read_at(block_meta.offset(), &mut buf)
+            .context(IOSnafu)?;
+        let mut blk = match decode_data_block_in_range(
+            &buf,
+            block_meta.field_type(),
+            block_meta.val_off() - block_meta.offset(),
+            time_range,
+            value_filter,
+        )? {
+            Some(blk) => blk,
+            None => return Ok(None),
+        };
+        self.tombstone
+            .read()
+            .data_block_exclude_tombstones(block_meta.field_id(), &mut blk);
+        Ok(Some(blk))
+    }
+
     // Reads raw data from file and returns the read data size.
     pub fn get_raw_data(&self, block_meta: &BlockMeta, dst: &mut Vec<u8>) -> ReadTsmResult<usize> {
         let data_len = block_meta.size() as usize;
@@ -487,6 +557,11 @@ impl TsmReader {
         !self.tombstone.read().is_empty()
     }
 
+    /// The fields the tombstones of the file are of
+    pub fn tombstone_field_ids(&self) -> Vec<FieldId> {
+        self.tombstone.read().field_ids()
+    }
+
     /// Returns all tombstone `TimeRange`s for a `BlockMeta`.
     /// Returns None if there is nothing to return, or `TimeRange`s is empty.
     pub fn get_block_tombstone_time_ranges(
@@ -564,6 +639,82 @@ pub fn decode_data_block(
     field_type: ValueType,
     val_off: u64,
 ) -> ReadTsmResult<DataBlock> {
+    let (ts, ts_encoding) = decode_timestamps(buf, val_off)?;
+    decode_values(buf, field_type, val_off, ts, ts_encoding)
+}
+
+/// The min and the max of the values of the rows of a block, known without
+/// decoding the values if the values are encoded by RLE.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueBounds {
+    Integer(i64, i64),
+    Unsigned(u64, u64),
+}
+
+/// Decodes the rows of the block in the time range, returns None without decoding
+/// the values if none of the timestamps is in the time range, or the value filter
+/// is false for the bounds of the values of the rows in the time range.
+///
+/// The timestamps and the integer values encoded by RLE are not decoded to be
+/// checked.
+pub fn decode_data_block_in_range(
+    buf: &[u8],
+    field_type: ValueType,
+    val_off: u64,
+    time_range: &TimeRange,
+    value_filter: impl Fn(&ValueBounds) -> bool,
+) -> ReadTsmResult<Option<DataBlock>> {
+    let ts_run = buf
+        .get(4..val_off as usize)
+        .and_then(codec::ts_rle_run)
+        .filter(|e| e.delta >= 0);
+    let (ts, indices) = match ts_run {
+        Some(run) => {
+            let indices = run.position_range(time_range.min_ts, time_range.max_ts);
+            if indices.is_empty() {
+                return Ok(None);
+            }
+            (None, indices)
+        }
+        None => {
+            let (ts, ts_encoding) = decode_timestamps(buf, val_off)?;
+            let start = ts.partition_point(|e| *e < time_range.min_ts);
+            let end = ts.partition_point(|e| *e <= time_range.max_ts);
+            if start >= end {
+                return Ok(None);
+            }
+            (Some((ts, ts_encoding)), start..end)
+        }
+    };
+
+    if let Some(data) = buf.get((val_off + 4) as usize..) {
+        let bounds = codec::i64_rle_run(data)
+            .and_then(|run| run.bounds(indices))
+            .and_then(|(min, max)| match field_type {
+                ValueType::Integer => Some(ValueBounds::Integer(min, max)),
+                // The unsigned integers are encoded as the signed
+                ValueType::Unsigned if min >= 0 => {
+                    Some(ValueBounds::Unsigned(min as u64, max as u64))
+                }
+                _ => None,
+            });
+        if let Some(bounds) = bounds {
+            if !value_filter(&bounds) {
+                return Ok(None);
+            }
+        }
+    }
+
+    let (ts, ts_encoding) = match ts {
+        Some(ts) => ts,
+        None => decode_timestamps(buf, val_off)?,
+    };
+    let mut blk = decode_values(buf, field_type, val_off, ts, ts_encoding)?;
+    blk.retain(time_range);
+    Ok(Some(blk))
+}
+
+fn decode_timestamps(buf: &[u8], val_off: u64) -> ReadTsmResult<(Vec<i64>, Encoding)> {
     debug_assert!(buf.len() >= 8);
     if buf.len() < 8 {
         return Err(ReadTsmError::Decode {
@@ -578,7 +729,16 @@ pub fn decode_data_block(
     ts_codec
         .decode(&buf[4..val_off as usize], &mut ts)
         .context(DecodeSnafu)?;
+    Ok((ts, ts_encoding))
+}
 
+fn decode_values(
+    buf: &[u8],
+    field_type: ValueType,
+    val_off: u64,
+    ts: Vec<i64>,
+    ts_encoding: Encoding,
+) -> ReadTsmResult<DataBlock> {
     // let crc_data = &self.buf[(val_offset - offset) as usize..4];
     let data = &buf[(val_off + 4) as usize..];
     match field_type {
@@ -841,4 +1001,60 @@ pub mod tsm_reader_tests {
             read_opt_and_check(&reader, 2, (5, 12), expected_data);
         }
     }
+
+    #[test]
+    fn test_tsm_reader_in_range() {
+        let (tsm_file, _) = prepare("/tmp/test/tsm_reader/3");
+        let reader = TsmReader::open(&tsm_file).unwrap();
+
+        let mut read_data = vec![];
+        for idx in reader.index_iterator_opt(2) {
+            for blk in idx.block_iterator() {
+                read_data.push(
+                    reader
+                        .get_data_block_in_range(&blk, &TimeRange::from((3, 6)), |_| true)
+                        .unwrap(),
+                );
+            }
+        }
+        #[rustfmt::skip]
+        assert_eq!(read_data, vec![
+            Some(DataBlock::U64 { ts: vec![3, 4], val: vec![103, 104], enc: DataBlockEncoding::default() }),
+            Some(DataBlock::U64 { ts: vec![5, 6], val: vec![105, 106], enc: DataBlockEncoding::default() }),
+            None,
+        ]);
+    }
+
+    #[test]
+    fn test_tsm_reader_value_filter() {
+        let (tsm_file, _) = prepare("/tmp/test/tsm_reader/4");
+        let reader = TsmReader::open(&tsm_file).unwrap();
+
+        let read = |time_range: TimeRange, min_value: u64| {
+            let mut read_data = vec![];
+            for idx in reader.index_iterator_opt(2) {
+                for blk in idx.block_iterator() {
+                    let data_block = reader
+                        .get_data_block_in_range(&blk, &time_range, |bounds| match bounds {
+                            ValueBounds::Unsigned(_, max) => *max >= min_value,
+                            _ => true,
+                        })
+                        .unwrap();
+                    read_data.push(data_block.map(|e| e.len()));
+                }
+            }
+            read_data
+        };
+
+        assert_eq!(read(TimeRange::all(), 108), vec![None, Some(4), Some(4)]);
+        // Only the values of the rows in the time range are bounded
+        assert_eq!(
+            read(TimeRange::from((3, 6)), 105),
+            vec![None, Some(2), None]
+        );
+        assert_eq!(
+            read(TimeRange::from((3, 6)), 104),
+            vec![Some(2), Some(2), None]
+        );
+    }
 }
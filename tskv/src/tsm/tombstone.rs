@@ -252,6 +252,11 @@ impl TsmTombstone {
     }
 
     /// Returns all TimeRanges for a FieldId cloned from TsmTombstone.
+    /// The fields the tombstones are of
+    pub fn field_ids(&self) -> Vec<FieldId> {
+        self.tombstones.keys().copied().collect()
+    }
+
     pub(crate) fn get_cloned_time_ranges(&self, field_id: FieldId) -> Option<Vec<TimeRange>> {
         self.tombstones.get(&field_id).cloned()
     }
@@ -15,7 +15,8 @@ use crate::{
     file_utils,
     tsm::{
         BlockEntry, BlockMeta, BlockMetaIterator, DataBlock, Index, IndexEntry, IndexMeta,
-        BLOCK_META_SIZE, BLOOM_FILTER_BITS, INDEX_META_SIZE, MAX_BLOCK_VALUES,
+        BLOCK_META_SIZE, BLOOM_FILTER_BITS, INDEX_META_SIZE, MAX_BLOCK_VALUES, TSM_MAGIC,
+        TSM_VERSION,
     },
 };
 
@@ -57,8 +58,6 @@ use crate::{
 // └───────────────┴─────────┘
 
 const HEADER_LEN: u64 = 5;
-const TSM_MAGIC: u32 = 0x01346613;
-const VERSION: u8 = 1;
 
 pub type WriteTsmResult<T, E = WriteTsmError> = std::result::Result<T, E>;
 
@@ -346,7 +345,7 @@ pub fn write_header_to(writer: &mut FileCursor) -> WriteTsmResult<usize> {
     let start = writer.pos();
     writer
         .write(TSM_MAGIC.to_be_bytes().as_ref())
-        .and_then(|_| writer.write(&VERSION.to_be_bytes()[..]))
+        .and_then(|_| writer.write(&TSM_VERSION.to_be_bytes()[..]))
         .context(IOSnafu)?;
 
     Ok((writer.pos() - start) as usize)
@@ -0,0 +1,164 @@
+//! Upgrades the files written by the older versions to the current format, by
+//! `cnosdb upgrade-files` while the server is stopped.
+//!
+//! The tsm and delta files of the older versions are rewritten with the same
+//! file ids, the tombstones are kept as they are. The indexes are recorded in
+//! the current format version.
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use snafu::ResultExt;
+use trace::info;
+
+use crate::{
+    error::{self, Error, Result},
+    file_utils,
+    index::IndexEngine,
+    kv_option::Options,
+    tsm::{self, TsmReader, TsmWriter, TSM_VERSION},
+};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct UpgradeReport {
+    pub tsm_files: usize,
+    pub tsm_files_upgraded: usize,
+    pub indexes: usize,
+    pub indexes_upgraded: usize,
+}
+
+pub fn upgrade_files(options: &Options) -> Result<UpgradeReport> {
+    let mut report = UpgradeReport::default();
+
+    let mut tsm_files = vec![];
+    find_tsm_files(&options.storage.data_dir(), &mut tsm_files).context(error::IOSnafu)?;
+    for path in tsm_files {
+        report.tsm_files += 1;
+        if upgrade_tsm_file(&path)? {
+            info!("Upgraded {} to version {}", path.display(), TSM_VERSION);
+            report.tsm_files_upgraded += 1;
+        }
+    }
+
+    let index_dir = options.storage.index_base_dir();
+    if index_dir.exists() {
+        for entry in fs::read_dir(&index_dir).context(error::IOSnafu)? {
+            let path = entry.context(error::IOSnafu)?.path();
+            if !path.is_dir() {
+                continue;
+            }
+            report.indexes += 1;
+            let upgraded = IndexEngine::new(&path)
+                .upgrade_format()
+                .map_err(|e| Error::IndexErr { source: e.into() })?;
+            if upgraded {
+                info!("Upgraded the index {}", path.display());
+                report.indexes_upgraded += 1;
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+fn find_tsm_files(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    if !dir.exists() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_tsm_files(&path, files)?;
+        } else if matches!(
+            path.extension().and_then(|e| e.to_str()),
+            Some("tsm") | Some("delta")
+        ) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Rewrites the tsm file of an older version in the current version, returns
+/// false if it is of the current version already
+fn upgrade_tsm_file(path: &Path) -> Result<bool> {
+    let reader = TsmReader::open(path)?;
+    if reader.version() == TSM_VERSION {
+        return Ok(false);
+    }
+
+    let file_id = file_utils::get_tsm_file_id_by_path(path)?;
+    let is_delta = path.extension().map_or(false, |e| e == "delta");
+    let mut writer = TsmWriter::open(path, file_id, is_delta, 0)?;
+    let mut buf = vec![];
+    for idx in reader.index_iterator() {
+        for blk_meta in idx.block_iterator() {
+            // The blocks are decoded without the tombstones, which are kept
+            let len = reader
+                .get_raw_data(&blk_meta, &mut buf)
+                .context(error::ReadTsmSnafu)?;
+            let block = tsm::decode_data_block(
+                &buf[..len],
+                blk_meta.field_type(),
+                blk_meta.val_off() - blk_meta.offset(),
+            )
+            .context(error::ReadTsmSnafu)?;
+            writer
+                .write_block(blk_meta.field_id(), &block)
+                .context(error::WriteTsmSnafu)?;
+        }
+    }
+    writer.write_index().context(error::WriteTsmSnafu)?;
+    writer.finish().context(error::WriteTsmSnafu)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod test {
+    use std::os::unix::fs::FileExt;
+
+    use minivec::MiniVec;
+    use models::codec::Encoding;
+
+    use super::*;
+    use crate::tsm::{codec::DataBlockEncoding, DataBlock, TsmWriter, TSM_MIN_VERSION};
+
+    #[test]
+    fn test_upgrade_tsm_file() {
+        let dir = "/tmp/test/upgrade/1";
+        let _ = fs::remove_dir_all(dir);
+        fs::create_dir_all(dir).unwrap();
+        let path = file_utils::make_tsm_file_name(dir, 1);
+
+        let block = DataBlock::Str {
+            ts: vec![1, 2],
+            val: vec![MiniVec::from(&b"a"[..]), MiniVec::from(&b"b"[..])],
+            enc: DataBlockEncoding::new(Encoding::Default, Encoding::Snappy),
+        };
+        let mut writer = TsmWriter::open(&path, 1, false, 0).unwrap();
+        writer.write_block(1, &block).unwrap();
+        writer.write_index().unwrap();
+        writer.finish().unwrap();
+
+        // The version in the header
+        let file = fs::OpenOptions::new().write(true).open(&path).unwrap();
+        file.write_all_at(&[TSM_MIN_VERSION], 4).unwrap();
+        drop(file);
+        assert!(upgrade_tsm_file(&path).unwrap());
+
+        let reader = TsmReader::open(&path).unwrap();
+        assert_eq!(reader.version(), TSM_VERSION);
+        let blk_meta = reader
+            .index_iterator()
+            .next()
+            .unwrap()
+            .block_iterator()
+            .next()
+            .unwrap();
+        let upgraded = reader.get_data_block(&blk_meta).unwrap();
+        assert_eq!(upgraded.ts(), block.ts());
+        assert_eq!(upgraded.encodings().split().1, Encoding::Snappy);
+        assert!(!upgrade_tsm_file(&path).unwrap());
+    }
+}
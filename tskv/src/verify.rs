@@ -0,0 +1,297 @@
+//! Verifies the integrity of the files of a database while the node keeps serving,
+//! by `GET /api/v1/verify?db=x` or `cnosdb-cli verify --db x`.
+//!
+//! The files of the current versions of the ts families are checked, the versions
+//! are held until the verification is done so that the files compacted meanwhile
+//! are not removed. The problems found are reported instead of failing, they are:
+//! - the files of the versions missing or not readable,
+//! - the checksums of the blocks mismatched, or the blocks not decoded as indexed,
+//! - the series of the fields in the files not in the index,
+//! - the tombstones of the fields not in the files.
+use std::{collections::HashSet, fs, path::Path, sync::Arc};
+
+use models::utils::split_id;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    byte_utils::decode_be_u32,
+    file_utils,
+    index::db_index::DBIndex,
+    tseries_family::{ColumnFile, TimeRange, Version},
+    tsm::{self, BlockMeta, DataBlock, IndexMeta, TsmReader},
+};
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerifyReport {
+    pub database: String,
+    pub files: usize,
+    pub blocks: usize,
+    pub series: usize,
+    pub problems: Vec<VerifyProblem>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerifyProblem {
+    /// Path of the file or the index the problem is found in
+    pub location: String,
+    pub reason: String,
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    fn problem(&mut self, location: impl AsRef<Path>, reason: impl Into<String>) {
+        self.problems.push(VerifyProblem {
+            location: location.as_ref().display().to_string(),
+            reason: reason.into(),
+        });
+    }
+}
+
+pub fn verify_database(database: &str, versions: &[Arc<Version>], index: &DBIndex) -> VerifyReport {
+    let mut report = VerifyReport {
+        database: database.to_string(),
+        ..Default::default()
+    };
+
+    // The fields are of the low 40 bits of the series ids
+    let series = match index.series_keys() {
+        Ok(keys) => {
+            report.series = keys.len();
+            Some(
+                keys.iter()
+                    .map(|e| split_id(e.id()).1)
+                    .collect::<HashSet<_>>(),
+            )
+        }
+        Err(e) => {
+            report.problem(index.path(), e.to_string());
+            None
+        }
+    };
+
+    for version in versions {
+        for level in version.levels_info() {
+            for file in level.files.iter() {
+                report.files += 1;
+                verify_file(file, series.as_ref(), &mut report);
+            }
+        }
+    }
+    report
+}
+
+fn verify_file(file: &ColumnFile, series: Option<&HashSet<u64>>, report: &mut VerifyReport) {
+    let path = file.file_path();
+    let file_len = match fs::metadata(&path) {
+        Ok(meta) => meta.len(),
+        Err(e) => {
+            report.problem(&path, format!("file of the version is not found: {}", e));
+            return;
+        }
+    };
+    let reader = match TsmReader::open(&path) {
+        Ok(reader) => reader,
+        Err(e) => {
+            report.problem(&path, format!("failed to open: {}", e));
+            return;
+        }
+    };
+
+    let mut field_ids = HashSet::new();
+    let mut last_field_id = None;
+    let mut buf = vec![];
+    for idx in reader.index_iterator() {
+        let field_id = idx.field_id();
+        if last_field_id.map_or(false, |e| e >= field_id) {
+            report.problem(
+                &path,
+                format!("index of field {} is out of order", field_id),
+            );
+        }
+        last_field_id = Some(field_id);
+        field_ids.insert(field_id);
+
+        for blk_meta in idx.block_iterator() {
+            report.blocks += 1;
+            if let Err(reason) = verify_block(&reader, &blk_meta, file_len, &mut buf) {
+                report.problem(
+                    &path,
+                    format!(
+                        "block of field {} at {}: {}",
+                        field_id,
+                        blk_meta.offset(),
+                        reason
+                    ),
+                );
+            }
+        }
+
+        let (_, sid) = split_id(field_id);
+        if series.map_or(false, |e| !e.contains(&sid)) && !is_deleted(&reader, &idx) {
+            report.problem(
+                &path,
+                format!("series {} of field {} is not in the index", sid, field_id),
+            );
+        }
+    }
+
+    if reader.has_tombstone() {
+        let dir = path.parent().unwrap_or_else(|| Path::new("/"));
+        let tombstone_path = file_utils::make_tsm_tombstone_file_name(dir, file.file_id());
+        for field_id in reader.tombstone_field_ids() {
+            if !field_ids.contains(&field_id) {
+                report.problem(
+                    &tombstone_path,
+                    format!("tombstone of field {} is not in the file", field_id),
+                );
+            }
+        }
+    }
+}
+
+/// Whether all the data of the field in the file is deleted by the tombstones
+pub(crate) fn is_deleted(reader: &TsmReader, idx: &IndexMeta) -> bool {
+    let time_range = TimeRange::from(idx.time_range());
+    reader
+        .get_cloned_tombstone_time_ranges(idx.field_id())
+        .map_or(false, |e| e.iter().any(|t| t.includes(&time_range)))
+}
+
+fn verify_block(
+    reader: &TsmReader,
+    blk_meta: &BlockMeta,
+    file_len: u64,
+    buf: &mut Vec<u8>,
+) -> Result<(), String> {
+    check_block_bounds(blk_meta, file_len)?;
+    let len = reader
+        .get_raw_data(blk_meta, buf)
+        .map_err(|e| e.to_string())?;
+    decode_checked_block(blk_meta, &buf[..len])?;
+    Ok(())
+}
+
+/// Whether the block is in the file before the offset `end`
+pub(crate) fn check_block_bounds(blk_meta: &BlockMeta, end: u64) -> Result<(), String> {
+    let (offset, size, val_off) = (blk_meta.offset(), blk_meta.size(), blk_meta.val_off());
+    let in_bounds = offset.checked_add(size).map_or(false, |e| {
+        e <= end && val_off >= offset + 4 && val_off.saturating_add(4) <= e
+    });
+    if !in_bounds {
+        return Err("out of the bounds of the file".to_string());
+    }
+    Ok(())
+}
+
+/// Decodes the raw data of the block, the checksums, the rows and the time range
+/// are checked against the index
+pub(crate) fn decode_checked_block(blk_meta: &BlockMeta, raw: &[u8]) -> Result<DataBlock, String> {
+    let val_off = blk_meta.val_off() - blk_meta.offset();
+
+    // The checksums are ahead of the timestamps and the values
+    let (ts, val) = raw.split_at(val_off as usize);
+    if crc32fast::hash(&ts[4..]) != decode_be_u32(&ts[..4]) {
+        return Err("checksum of the timestamps mismatched".to_string());
+    }
+    if crc32fast::hash(&val[4..]) != decode_be_u32(&val[..4]) {
+        return Err("checksum of the values mismatched".to_string());
+    }
+
+    let block =
+        tsm::decode_data_block(raw, blk_meta.field_type(), val_off).map_err(|e| e.to_string())?;
+    if block.len() != blk_meta.count() as usize {
+        return Err(format!(
+            "{} rows decoded, {} rows indexed",
+            block.len(),
+            blk_meta.count()
+        ));
+    }
+    if block.time_range() != Some((blk_meta.min_ts(), blk_meta.max_ts())) {
+        return Err("time range mismatched the index".to_string());
+    }
+    Ok(block)
+}
+
+#[cfg(test)]
+mod test {
+    use std::os::unix::fs::FileExt;
+
+    use models::utils::unite_id;
+
+    use super::*;
+    use crate::{tseries_family::ColumnFile, tsm::TsmWriter};
+
+    fn write_file(dir: &str, file_id: u64, field_ids: &[u64]) -> ColumnFile {
+        let path = file_utils::make_tsm_file_name(dir, file_id);
+        let mut writer = TsmWriter::open(&path, file_id, false, 0).unwrap();
+        for field_id in field_ids {
+            let block = DataBlock::I64 {
+                ts: vec![1, 2, 3],
+                val: vec![1, 2, 3],
+                enc: Default::default(),
+            };
+            writer.write_block(*field_id, &block).unwrap();
+        }
+        writer.write_index().unwrap();
+        writer.finish().unwrap();
+        ColumnFile::new(file_id, 1, TimeRange::new(1, 3), writer.size(), false, path)
+    }
+
+    #[test]
+    fn test_verify_file() {
+        let dir = "/tmp/test/verify/1";
+        let _ = fs::remove_dir_all(dir);
+        fs::create_dir_all(dir).unwrap();
+        let series = HashSet::from([1, 2]);
+
+        let file = write_file(dir, 1, &[unite_id(0, 1), unite_id(1, 1)]);
+        let mut report = VerifyReport::default();
+        verify_file(&file, Some(&series), &mut report);
+        assert_eq!(report.blocks, 2);
+        assert!(report.is_ok(), "{:?}", report.problems);
+
+        // Series 3 is not in the index unless deleted
+        let file = write_file(dir, 2, &[unite_id(0, 3)]);
+        let mut report = VerifyReport::default();
+        verify_file(&file, Some(&series), &mut report);
+        assert_eq!(report.problems.len(), 1);
+        file.add_tombstone(&[unite_id(0, 3)], &TimeRange::new(0, 10))
+            .unwrap();
+        let mut report = VerifyReport::default();
+        verify_file(&file, Some(&series), &mut report);
+        assert!(report.is_ok(), "{:?}", report.problems);
+
+        // Tombstone of a field not in the file
+        file.add_tombstone(&[unite_id(0, 2)], &TimeRange::new(0, 10))
+            .unwrap();
+        let mut report = VerifyReport::default();
+        verify_file(&file, Some(&series), &mut report);
+        assert_eq!(report.problems.len(), 1);
+        assert!(report.problems[0].location.ends_with(".tombstone"));
+
+        // The checksum of the timestamps of the first block, after the header of
+        // 5 bytes, the file is not opened before
+        let file = write_file(dir, 3, &[unite_id(0, 1)]);
+        let f = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(file.file_path())
+            .unwrap();
+        let mut byte = [0_u8];
+        f.read_exact_at(&mut byte, 5).unwrap();
+        f.write_all_at(&[!byte[0]], 5).unwrap();
+        drop(f);
+        let mut report = VerifyReport::default();
+        verify_file(&file, Some(&series), &mut report);
+        assert_eq!(report.problems.len(), 1);
+        assert!(report.problems[0].reason.contains("checksum"));
+
+        fs::remove_file(file.file_path()).unwrap();
+        let mut report = VerifyReport::default();
+        verify_file(&file, Some(&series), &mut report);
+        assert!(report.problems[0].reason.contains("not found"));
+    }
+}
@@ -1,8 +1,12 @@
 use std::{
+    collections::HashMap,
     io::SeekFrom,
     marker::PhantomData,
     path::{Path, PathBuf},
-    sync::Arc,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
 };
 
 use lazy_static::lazy_static;
@@ -25,7 +29,6 @@ use crate::tsm::codec::get_str_codec;
 use crate::{
     byte_utils,
     compaction::FlushReq,
-    context::GlobalContext,
     error::{self, Error, Result},
     file_system::{DmaFile, FileCursor, FileSync},
     file_utils,
@@ -34,7 +37,7 @@ use crate::{
     version_set::VersionSet,
 };
 
-const SEGMENT_HEADER_SIZE: usize = 32;
+pub const SEGMENT_HEADER_SIZE: usize = 32;
 const SEGMENT_MAGIC: [u8; 4] = [0x57, 0x47, 0x4c, 0x00];
 const SEGMENT_SIZE: u64 = 1073741824; // 1 GiB
 
@@ -42,18 +45,90 @@ const BLOCK_HEADER_SIZE: usize = 17;
 
 pub enum WalTask {
     Write {
+        database: String,
+        /// `Write`, or `Replay` for a write replayed from another replica
+        typ: WalEntryType,
         points: Arc<Vec<u8>>,
+        /// Id of the write pending until it's in the memcache, see [`PendingWrites`]
+        pending: u64,
         // (seq_no, written_size)
         cb: oneshot::Sender<Result<(u64, usize)>>,
     },
 }
 
+impl WalTask {
+    /// Bytes of the entry written
+    pub fn size(&self) -> usize {
+        match self {
+            WalTask::Write { points, .. } => points.len(),
+        }
+    }
+}
+
+/// The writes being written into the wal and then into the memcache, the wal is
+/// only truncated before the first of them written into the wal
+#[derive(Debug, Default)]
+pub struct PendingWrites {
+    next_id: AtomicU64,
+    /// The seqs of the writes, 0 until written into the wal
+    seqs: Mutex<HashMap<u64, u64>>,
+}
+
+impl PendingWrites {
+    /// Begins a write, which is pending until the guard returned is dropped
+    pub fn begin(self: &Arc<Self>) -> PendingWrite {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.seqs.lock().insert(id, 0);
+        PendingWrite {
+            id,
+            writes: self.clone(),
+        }
+    }
+
+    /// The write is in the wal at the seq
+    pub fn written(&self, id: u64, seq: u64) {
+        if let Some(e) = self.seqs.lock().get_mut(&id) {
+            *e = seq;
+        }
+    }
+
+    /// The writes in the wal at or before the seq are all in the memcache, None if
+    /// none of the writes in the wal is pending
+    pub fn applied_seq(&self) -> Option<u64> {
+        self.seqs
+            .lock()
+            .values()
+            .filter(|e| **e != 0)
+            .min()
+            .map(|e| e - 1)
+    }
+}
+
+pub struct PendingWrite {
+    id: u64,
+    writes: Arc<PendingWrites>,
+}
+
+impl PendingWrite {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+}
+
+impl Drop for PendingWrite {
+    fn drop(&mut self) {
+        self.writes.seqs.lock().remove(&self.id);
+    }
+}
+
 #[repr(u8)]
 #[derive(Eq, PartialEq, Clone, Copy, Debug)]
 pub enum WalEntryType {
     Write = 1,
     Delete = 2,
     DeleteRange = 3,
+    /// A write replayed from another replica, not served to the replicas again
+    Replay = 4,
     Unknown = 127,
 }
 
@@ -63,6 +138,7 @@ impl From<u8> for WalEntryType {
             1 => WalEntryType::Write,
             2 => WalEntryType::Delete,
             3 => WalEntryType::DeleteRange,
+            4 => WalEntryType::Replay,
             _ => WalEntryType::Unknown,
         }
     }
@@ -179,10 +255,16 @@ impl WalWriter {
         })
     }
 
-    pub async fn write(&mut self, typ: WalEntryType, data: &[u8]) -> Result<(u64, usize)> {
+    /// Writes the entry of the sequence next to `seq`, the file is not synced
+    fn write_entry(
+        &mut self,
+        mut seq: u64,
+        typ: WalEntryType,
+        data: &[u8],
+    ) -> Result<(u64, usize)> {
         let typ = typ as u8;
         let mut pos = self.size;
-        let mut seq = self.max_sequence;
+        seq += 1;
 
         self.file
             // write type
@@ -209,20 +291,10 @@ impl WalWriter {
                 pos += size as u64;
                 self.file.write_at(pos, data)
             })
-            .and_then(|size| {
-                // sync
-                pos += size as u64;
-                if self.config.sync {
-                    self.file.sync_all(FileSync::Soft)
-                } else {
-                    Ok(())
-                }
-            })
+            .map(|size| pos += size as u64)
             .context(error::IOSnafu)?;
 
-        seq += 1;
-
-        // write & fsync succeed
+        // write succeed
         let written_size = (pos - self.size) as usize;
         self.size = pos;
         self.max_sequence = seq;
@@ -230,6 +302,10 @@ impl WalWriter {
         Ok((seq, written_size))
     }
 
+    fn sync(&self) -> Result<()> {
+        self.file.sync_all(FileSync::Soft).context(error::IOSnafu)
+    }
+
     pub async fn flush(&mut self) -> Result<()> {
         // Write header
         self.header_buf[4..12].copy_from_slice(&self.min_sequence.to_be_bytes());
@@ -248,9 +324,15 @@ impl WalWriter {
 
 pub struct WalManager {
     config: Arc<WalOptions>,
+    seq: u64,
 
     current_dir: PathBuf,
     current_file: WalWriter,
+    /// Writers of the directories of the wal of the databases not in `current_dir`
+    database_files: HashMap<PathBuf, WalWriter>,
+    /// Files not written any more and the max seq of their entries, removed once
+    /// all the writes are persisted
+    segments: Vec<(PathBuf, u64)>,
 }
 
 unsafe impl Send for WalManager {}
@@ -259,90 +341,223 @@ unsafe impl Sync for WalManager {}
 
 impl WalManager {
     pub fn new(config: Arc<WalOptions>) -> Self {
-        if !file_manager::try_exists(&config.path) {
-            std::fs::create_dir_all(&config.path).unwrap();
+        for dir in config.dirs() {
+            if !file_manager::try_exists(dir) {
+                std::fs::create_dir_all(dir).unwrap();
+            }
         }
-
-        // Create a new wal file every time it starts.
-        let new_seq = match file_utils::get_max_sequence_file_name(
-            config.path.clone(),
-            file_utils::get_wal_file_id,
-        ) {
-            Some((_, seq)) => seq + 1,
-            None => 1,
-        };
-
-        let new_wal = file_utils::make_wal_file(config.path.clone(), new_seq);
-        let current_file = WalWriter::open(new_seq, new_wal, config.clone()).unwrap();
         let current_dir = config.path.clone();
+        let current_file = Self::create_writer(&current_dir, config.clone()).unwrap();
         WalManager {
             config,
+            seq: current_file.max_sequence,
             current_dir,
             current_file,
+            database_files: HashMap::new(),
+            segments: Vec::new(),
         }
     }
 
+    /// Creates a new wal file in the directory every time it starts.
+    fn create_writer(dir: &Path, config: Arc<WalOptions>) -> Result<WalWriter> {
+        let new_seq = match file_utils::get_max_sequence_file_name(dir, file_utils::get_wal_file_id)
+        {
+            Some((_, seq)) => seq + 1,
+            None => 1,
+        };
+        let new_wal = file_utils::make_wal_file(dir, new_seq);
+        WalWriter::open(new_seq, new_wal, config)
+    }
+
     pub fn current_seq_no(&self) -> u64 {
-        self.current_file.max_sequence
+        self.seq
+    }
+
+    /// The writer of the directory of the wal of the database, the directories of
+    /// the databases are opened on the first write
+    fn writer(&mut self, database: &str) -> Result<&mut WalWriter> {
+        let dir = self.config.database_dir(database);
+        if dir == self.current_dir {
+            return Ok(&mut self.current_file);
+        }
+        if !self.database_files.contains_key(dir) {
+            let writer = Self::create_writer(dir, self.config.clone())?;
+            info!(
+                "WAL of database '{}' starts write in '{}'",
+                database,
+                dir.display()
+            );
+            self.database_files.insert(dir.to_path_buf(), writer);
+        }
+        Ok(self.database_files.get_mut(dir).unwrap())
     }
 
-    async fn roll_wal_file(&mut self) -> Result<()> {
-        if self.current_file.size > SEGMENT_SIZE {
+    /// Returns the file rolled over and the max seq of its entries
+    async fn roll_wal_file(
+        writer: &mut WalWriter,
+        config: Arc<WalOptions>,
+    ) -> Result<Option<(PathBuf, u64)>> {
+        if writer.size > SEGMENT_SIZE {
             info!(
                 "WAL '{}' is full at seq '{}', begin rolling.",
-                self.current_file.id, self.current_file.max_sequence
+                writer.id, writer.max_sequence
             );
 
-            let new_file_id = self.current_file.id + 1;
-            let new_file_name = file_utils::make_wal_file(&self.config.path, new_file_id);
+            let new_file_id = writer.id + 1;
+            let dir = writer.path.parent().unwrap_or_else(|| Path::new(""));
+            let new_file_name = file_utils::make_wal_file(dir, new_file_id);
 
-            let new_file = WalWriter::open(new_file_id, new_file_name, self.config.clone())?;
-            let mut old_file = std::mem::replace(&mut self.current_file, new_file);
+            let new_file = WalWriter::open(new_file_id, new_file_name, config)?;
+            let mut old_file = std::mem::replace(writer, new_file);
             old_file.flush().await?;
 
-            info!("WAL '{}' starts write", self.current_file.id);
+            info!("WAL '{}' starts write", writer.id);
+            return Ok(Some((old_file.path, old_file.max_sequence)));
         }
-        Ok(())
+        Ok(None)
+    }
+
+    /// Writes the entry into the wal of the database, the entries of all the
+    /// databases share the sequence
+    async fn write_entry(
+        &mut self,
+        database: &str,
+        typ: WalEntryType,
+        data: &[u8],
+    ) -> Result<(u64, usize)> {
+        let config = self.config.clone();
+        let seq = self.seq;
+        let writer = self.writer(database)?;
+        let rolled = Self::roll_wal_file(writer, config).await?;
+        let ret = writer.write_entry(seq, typ, data)?;
+        self.seq = ret.0;
+        self.segments.extend(rolled);
+        Ok(ret)
     }
 
     pub async fn write(&mut self, typ: WalEntryType, data: &[u8]) -> Result<(u64, usize)> {
-        self.roll_wal_file().await?;
-        self.current_file.write(typ, data).await
+        let rolled = Self::roll_wal_file(&mut self.current_file, self.config.clone()).await?;
+        self.segments.extend(rolled);
+        let ret = self.current_file.write_entry(self.seq, typ, data)?;
+        self.seq = ret.0;
+        if self.config.sync {
+            self.current_file.sync()?;
+        }
+        Ok(ret)
+    }
+
+    /// Writes the entries of the databases in order and syncs each file once, so the
+    /// writes coalesced share a single fsync, returns the result of each entry
+    pub async fn write_batch(
+        &mut self,
+        entries: &[(&str, WalEntryType, &[u8])],
+    ) -> Vec<Result<(u64, usize)>> {
+        let (mut results, files) = self.write_batch_unsynced(entries).await;
+        if self.config.sync {
+            sync_files(&files, &mut results);
+        }
+        results
+    }
+
+    /// Same as `write_batch`, the files written are returned to be synced by the
+    /// caller, e.g. the fsync thread
+    pub async fn write_batch_unsynced(
+        &mut self,
+        entries: &[(&str, WalEntryType, &[u8])],
+    ) -> (Vec<Result<(u64, usize)>>, Vec<(PathBuf, DmaFile)>) {
+        let mut results = Vec::with_capacity(entries.len());
+        let mut dirs: Vec<PathBuf> = Vec::new();
+        for (database, typ, data) in entries {
+            let ret = self.write_entry(database, *typ, data).await;
+            let dir = self.config.database_dir(database);
+            if ret.is_ok() && !dirs.iter().any(|e| e == dir) {
+                dirs.push(dir.to_path_buf());
+            }
+            results.push(ret);
+        }
+        // The files rolled over are synced already
+        let files = dirs
+            .iter()
+            .filter_map(|dir| {
+                let writer = if *dir == self.current_dir {
+                    Some(&self.current_file)
+                } else {
+                    self.database_files.get(dir)
+                };
+                writer.map(|e| (e.path.clone(), e.file.clone()))
+            })
+            .collect();
+        (results, files)
     }
 
+    /// Replays the writes after the checkpoint, the writes at or before it are all
+    /// persisted, and removes the files all the writes of which are persisted and
+    /// which are not retained, see [`WalManager::truncate`].
+    ///
+    /// The seq goes on from the max of the writes replayed and `last_seq`, the max
+    /// seq persisted, so the seq never goes back even if the files are removed.
     pub async fn recover(
-        &self,
+        &mut self,
         engine: &impl engine::Engine,
-        global_context: Arc<GlobalContext>,
+        checkpoint: u64,
+        last_seq: u64,
+        retained: &HashMap<PathBuf, u64>,
     ) -> Result<()> {
-        let min_log_seq = global_context.last_seq();
-        warn!("recovering version set from seq '{}'", &min_log_seq);
+        warn!("recovering version set from seq '{}'", checkpoint);
+
+        let mut max_seq = last_seq;
+        let dirs = self
+            .config
+            .dirs()
+            .into_iter()
+            .map(|e| e.to_path_buf())
+            .collect::<Vec<_>>();
+        for dir in dirs {
+            max_seq = max_seq.max(self.recover_dir(&dir, checkpoint, engine).await?);
+        }
+        self.seq = self.seq.max(max_seq);
+        self.truncate(checkpoint, retained);
+        Ok(())
+    }
 
-        let wal_files = file_manager::list_file_names(&self.current_dir);
+    /// Returns the max seq of the entries in the directory
+    async fn recover_dir(
+        &mut self,
+        dir: &Path,
+        checkpoint: u64,
+        engine: &impl engine::Engine,
+    ) -> Result<u64> {
+        let mut dir_max_seq = 0;
+        let wal_files = file_manager::list_file_names(dir);
         for file_name in wal_files {
             let id = file_utils::get_wal_file_id(&file_name)?;
-            let path = self.current_dir.join(file_name);
-            if !file_manager::try_exists(&path) {
+            let path = dir.join(file_name);
+            if !file_manager::try_exists(&path) || path == self.current_file.path {
                 continue;
             }
             let file = file_manager::get_file_manager().open_file(&path)?;
             if file.is_empty() {
+                self.segments.push((path, 0));
                 continue;
             }
             let mut reader = WalReader::new(file.into())?;
-            if reader.max_sequence < min_log_seq {
-                // If this file is a new file, or empty file, continue.
+            // The max seq in the header is only written once the file is closed
+            let mut max_seq = reader.max_sequence;
+            if max_seq != 0 && max_seq <= checkpoint {
+                self.segments.push((path, max_seq));
+                dir_max_seq = dir_max_seq.max(max_seq);
                 continue;
             }
 
             loop {
                 match reader.next_wal_entry() {
                     Ok(Some(e)) => {
-                        if e.seq < min_log_seq {
+                        max_seq = max_seq.max(e.seq);
+                        if e.seq <= checkpoint {
                             continue;
                         }
                         match e.typ {
-                            WalEntryType::Write => {
+                            WalEntryType::Write | WalEntryType::Replay => {
                                 let decoder = get_str_codec(Encoding::Zstd);
                                 let mut dst = Vec::new();
                                 decoder.decode(&e.buf, &mut dst).context(DecodeSnafu)?;
@@ -370,15 +585,128 @@ impl WalManager {
                     }
                 }
             }
+            self.segments.push((path, max_seq));
+            dir_max_seq = dir_max_seq.max(max_seq);
         }
-        Ok(())
+        Ok(dir_max_seq)
+    }
+
+    /// Removes the files all the writes of which are at or before the checkpoint,
+    /// the writes of all the ts families are persisted up to it, except those kept
+    /// for the consumers of the changes, `retained` is the min id of the files kept
+    /// of each directory
+    pub fn truncate(&mut self, checkpoint: u64, retained: &HashMap<PathBuf, u64>) {
+        self.segments.retain(|(path, max_seq)| {
+            if *max_seq > checkpoint {
+                return true;
+            }
+            let is_retained = match (path.parent(), path.file_name()) {
+                (Some(dir), Some(file_name)) => retained.get(dir).map_or(false, |min_id| {
+                    file_utils::get_wal_file_id(&file_name.to_string_lossy())
+                        .map_or(true, |id| id >= *min_id)
+                }),
+                _ => false,
+            };
+            if is_retained {
+                return true;
+            }
+            match std::fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    error!("Failed to remove WAL '{}': {}", path.display(), e);
+                    true
+                }
+                _ => {
+                    info!(
+                        "WAL '{}' is removed, the writes are persisted at seq '{}'",
+                        path.display(),
+                        checkpoint
+                    );
+                    false
+                }
+            }
+        });
     }
 
     pub async fn close(&mut self) -> Result<()> {
+        for writer in self.database_files.values_mut() {
+            writer.flush().await?;
+        }
         self.current_file.flush().await
     }
 }
 
+/// Syncs the files the entries are written into, none of the entries is durable if
+/// any file is failed to sync
+pub fn sync_files(files: &[(PathBuf, DmaFile)], results: &mut [Result<(u64, usize)>]) {
+    if !results.iter().any(|e| e.is_ok()) {
+        return;
+    }
+    for (path, file) in files {
+        if let Err(e) = file.sync_all(FileSync::Soft) {
+            let reason = format!("Failed to sync '{}': {}", path.display(), e);
+            for ret in results.iter_mut().filter(|e| e.is_ok()) {
+                *ret = Err(Error::IO {
+                    source: std::io::Error::new(std::io::ErrorKind::Other, reason.clone()),
+                });
+            }
+            return;
+        }
+    }
+}
+
+/// Writes waiting for the files written into synced
+pub struct SyncTask {
+    pub files: Vec<(PathBuf, DmaFile)>,
+    pub writes: Vec<(oneshot::Sender<Result<(u64, usize)>>, Result<(u64, usize)>)>,
+}
+
+/// Syncs the wal in a dedicated thread, so the wal job goes on writing the next
+/// batch meanwhile, the results of the writes are sent once the files are synced.
+///
+/// The tasks queued meanwhile are coalesced, each file is synced once for them.
+pub struct WalSyncer {
+    sender: std::sync::mpsc::Sender<SyncTask>,
+}
+
+impl WalSyncer {
+    pub fn start() -> Result<Self> {
+        let (sender, receiver) = std::sync::mpsc::channel::<SyncTask>();
+        std::thread::Builder::new()
+            .name("wal-sync".to_string())
+            .spawn(move || {
+                while let Ok(task) = receiver.recv() {
+                    let mut tasks = vec![task];
+                    tasks.extend(receiver.try_iter());
+
+                    let mut files: Vec<(PathBuf, DmaFile)> = Vec::new();
+                    let mut writes = Vec::new();
+                    for task in tasks {
+                        for (path, file) in task.files {
+                            if !files.iter().any(|e| e.0 == path) {
+                                files.push((path, file));
+                            }
+                        }
+                        writes.extend(task.writes);
+                    }
+                    let (cbs, mut results): (Vec<_>, Vec<_>) = writes.into_iter().unzip();
+                    sync_files(&files, &mut results);
+                    for (cb, ret) in cbs.into_iter().zip(results) {
+                        if cb.send(ret).is_err() {
+                            warn!("send WAL write result failed.")
+                        }
+                    }
+                }
+                info!("WAL sync thread stopped.");
+            })
+            .context(error::IOSnafu)?;
+        Ok(Self { sender })
+    }
+
+    pub fn sync(&self, task: SyncTask) -> Result<()> {
+        self.sender.send(task).map_err(|_| Error::Send)
+    }
+}
+
 pub fn reader(f: DmaFile) -> Result<WalReader> {
     WalReader::new(f.into_cursor())
 }
@@ -405,6 +733,16 @@ impl WalReader {
         })
     }
 
+    /// Position of the next entry in the file
+    pub fn pos(&self) -> u64 {
+        self.cursor.pos()
+    }
+
+    /// Continue reading from `pos`, which must be the start of an entry
+    pub fn set_pos(&mut self, pos: u64) {
+        self.cursor.set_pos(pos.max(SEGMENT_HEADER_SIZE as u64));
+    }
+
     pub fn next_wal_entry(&mut self) -> Result<Option<WalEntryBlock>> {
         if self.cursor.len() - self.cursor.pos() < BLOCK_HEADER_SIZE as u64 {
             return Err(Error::WalTruncated);
@@ -624,6 +962,159 @@ mod test {
         check_wal_files(mgr.current_dir);
     }
 
+    #[tokio::test]
+    async fn test_write_batch() {
+        let dir = "/tmp/test/wal/batch".to_string();
+        let _ = std::fs::remove_dir_all(dir.clone()); // Ignore errors
+        let mut global_config = get_config("../config/config.toml");
+        global_config.wal.path = dir.clone();
+        let wal_config = WalOptions::from(&global_config);
+
+        let mut mgr = WalManager::new(Arc::new(wal_config));
+
+        let coder = get_str_codec(Encoding::Zstd);
+        let mut entries = vec![];
+        for _i in 0..5 {
+            let mut fbb = flatbuffers::FlatBufferBuilder::new();
+            let entry = random_wal_entry_block(&mut fbb);
+            let mut enc_points = Vec::new();
+            coder.encode(&[&entry.buf], &mut enc_points).unwrap();
+            entries.push(enc_points);
+        }
+        let entries = entries
+            .iter()
+            .map(|e| ("db", WalEntryType::Write, e.as_slice()))
+            .collect::<Vec<_>>();
+        let seqs = mgr
+            .write_batch(&entries)
+            .await
+            .into_iter()
+            .map(|e| e.unwrap().0)
+            .collect::<Vec<_>>();
+        assert_eq!(seqs, vec![1, 2, 3, 4, 5]);
+        assert_eq!(mgr.current_seq_no(), 5);
+
+        check_wal_files(mgr.current_dir);
+    }
+
+    #[tokio::test]
+    async fn test_write_database_dirs() {
+        let dir = "/tmp/test/wal/databases".to_string();
+        let _ = std::fs::remove_dir_all(dir.clone()); // Ignore errors
+        let mut global_config = get_config("../config/config.toml");
+        global_config.wal.path = format!("{}/default", dir);
+        global_config.wal.databases.insert(
+            "db0".to_string(),
+            config::DatabaseWalConfig {
+                path: format!("{}/db0", dir),
+            },
+        );
+        let wal_config = Arc::new(WalOptions::from(&global_config));
+        assert_eq!(wal_config.dirs().len(), 2);
+
+        let mut mgr = WalManager::new(wal_config);
+        let coder = get_str_codec(Encoding::Zstd);
+        let mut entries = vec![];
+        for db in ["db0", "db1", "db0"] {
+            let mut fbb = flatbuffers::FlatBufferBuilder::new();
+            let entry = random_wal_entry_block(&mut fbb);
+            let mut enc_points = Vec::new();
+            coder.encode(&[&entry.buf], &mut enc_points).unwrap();
+            entries.push((db, enc_points));
+        }
+        let entries = entries
+            .iter()
+            .map(|(db, e)| (*db, WalEntryType::Write, e.as_slice()))
+            .collect::<Vec<_>>();
+        let (results, files) = mgr.write_batch_unsynced(&entries).await;
+        // The databases share the sequence
+        let seqs = results
+            .into_iter()
+            .map(|e| e.unwrap().0)
+            .collect::<Vec<_>>();
+        assert_eq!(seqs, vec![1, 2, 3]);
+        assert_eq!(files.len(), 2);
+        assert!(files[0].0.starts_with(format!("{}/db0", dir)));
+        assert!(files[1].0.starts_with(format!("{}/default", dir)));
+        mgr.close().await.unwrap();
+
+        check_wal_files(PathBuf::from(format!("{}/db0", dir)));
+        check_wal_files(mgr.current_dir);
+    }
+
+    #[tokio::test]
+    async fn test_wal_syncer() {
+        let dir = "/tmp/test/wal/syncer".to_string();
+        let _ = std::fs::remove_dir_all(dir.clone()); // Ignore errors
+        let mut global_config = get_config("../config/config.toml");
+        global_config.wal.path = dir.clone();
+        let mut mgr = WalManager::new(Arc::new(WalOptions::from(&global_config)));
+        let syncer = wal::WalSyncer::start().unwrap();
+
+        let mut receivers = vec![];
+        for _ in 0..3 {
+            let (results, files) = mgr
+                .write_batch_unsynced(&[("db", WalEntryType::Write, b"data".as_slice())])
+                .await;
+            let (cb, rx) = tokio::sync::oneshot::channel();
+            let writes = vec![cb].into_iter().zip(results).collect();
+            syncer.sync(wal::SyncTask { files, writes }).unwrap();
+            receivers.push(rx);
+        }
+        for (i, rx) in receivers.into_iter().enumerate() {
+            assert_eq!(rx.await.unwrap().unwrap().0, i as u64 + 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_truncate() {
+        let dir = "/tmp/test/wal/truncate".to_string();
+        let _ = std::fs::remove_dir_all(dir.clone()); // Ignore errors
+        let mut global_config = get_config("../config/config.toml");
+        global_config.wal.path = dir.clone();
+        let wal_config = Arc::new(WalOptions::from(&global_config));
+
+        let mut mgr = WalManager::new(wal_config.clone());
+        for _ in 0..3 {
+            mgr.write(WalEntryType::Write, b"data").await.unwrap();
+        }
+        mgr.close().await.unwrap();
+        let path = mgr.current_file.path.clone();
+
+        let mut mgr = WalManager::new(wal_config);
+        mgr.segments.push((path.clone(), 3));
+        // Some of the writes are not persisted
+        mgr.truncate(2, &HashMap::new());
+        assert!(file_manager::try_exists(&path));
+        // Kept for a consumer reading from the file
+        let file_id =
+            file_utils::get_wal_file_id(&path.file_name().unwrap().to_string_lossy()).unwrap();
+        let retained = HashMap::from([(path.parent().unwrap().to_path_buf(), file_id)]);
+        mgr.truncate(3, &retained);
+        assert!(file_manager::try_exists(&path));
+        mgr.truncate(3, &HashMap::new());
+        assert!(!file_manager::try_exists(&path));
+        assert!(mgr.segments.is_empty());
+    }
+
+    #[test]
+    fn test_pending_writes() {
+        let writes = Arc::new(PendingWrites::default());
+        assert_eq!(writes.applied_seq(), None);
+
+        let a = writes.begin();
+        let b = writes.begin();
+        // Not written into the wal yet
+        assert_eq!(writes.applied_seq(), None);
+        writes.written(b.id(), 5);
+        writes.written(a.id(), 4);
+        assert_eq!(writes.applied_seq(), Some(3));
+        drop(a);
+        assert_eq!(writes.applied_seq(), Some(4));
+        drop(b);
+        assert_eq!(writes.applied_seq(), None);
+    }
+
     #[tokio::test]
     async fn test_roll_wal_file() {
         init_default_global_tracing("tskv_log", "tskv.log", "debug");
@@ -723,15 +1214,17 @@ mod test {
         let opt = kv_option::Options::from(&global_config);
         let tskv = rt.block_on(TsKv::open(opt, rt.clone())).unwrap();
         let ver = tskv.get_db_version("db0").unwrap().unwrap();
-        let expected = r#"[RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }]"#;
-        let ans = format!(
-            "{:?}",
-            ver.caches.mut_cache.read().read_series_data()[0]
-                .1
-                .read()
-                .groups[0]
-                .rows
+        let series_data = ver.caches.mut_cache.read().read_series_data()[0].1.clone();
+        let series_data = series_data.read();
+        let group = &series_data.groups[0];
+        let fa = group.schema.column("fa").unwrap().id;
+        let fb = group.schema.column("fb").unwrap().id;
+        let row = format!(
+            "RowData {{ ts: 1, fields: [({}, Integer(100)), ({}, Float(4.94e-321))] }}",
+            fa, fb
         );
-        assert_eq!(&ans, expected);
+        let expected = format!("[{}]", vec![row; 50].join(", "));
+        let ans = format!("{:?}", group.rows);
+        assert_eq!(ans, expected);
     }
 }
@@ -138,6 +138,36 @@ mod tests {
         assert!(file_manager::try_exists("data/db/data/db/delta/0"));
     }
 
+    #[test]
+    #[serial]
+    fn test_kvcore_close_flush() {
+        init_default_global_tracing("tskv_log", "tskv.log", "debug");
+        let dir = "/tmp/test/kvcore_close_flush";
+        let _ = std::fs::remove_dir_all(dir);
+        let mut global_config = get_config("../config/config.toml");
+        global_config.storage.path = format!("{}/data", dir);
+        global_config.wal.path = format!("{}/wal", dir);
+        let opt = kv_option::Options::from(&global_config);
+        let rt = Arc::new(runtime::Runtime::new().unwrap());
+
+        let mut fbb = flatbuffers::FlatBufferBuilder::new();
+        let points = models_helper::create_random_points_with_delta(&mut fbb, 10);
+        fbb.finish(points, None);
+        let points = fbb.finished_data().to_vec();
+        let request = kv_service::WritePointsRpcRequest { version: 1, points };
+        rt.clone().block_on(async move {
+            let tskv = TsKv::open(opt, rt).await.unwrap();
+            tskv.write(request).await.unwrap();
+            // The cache is far from full, it is flushed by closing
+            tskv.close().await;
+        });
+
+        assert!(file_manager::try_exists(&format!(
+            "{}/data/data/db/tsm/0",
+            dir
+        )));
+    }
+
     #[tokio::test]
     #[serial]
     async fn test_kvcore_log() {